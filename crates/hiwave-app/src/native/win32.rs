@@ -382,7 +382,7 @@ impl NativeBrowser {
 
     /// Process IPC messages from all views.
     fn process_ipc_messages(&mut self) {
-        let messages = self.engine.borrow().drain_ipc_messages();
+        let messages = self.engine.borrow_mut().drain_ipc_messages();
 
         for (view_id, ipc_msg) in messages {
             let view_type = self.engine_view_types.get(&view_id).copied();
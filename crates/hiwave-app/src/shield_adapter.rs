@@ -237,7 +237,7 @@ impl Default for ShieldInterceptHandler {
 /// Create a request interceptor with the default shield handler.
 pub fn create_shield_interceptor() -> rustkit_net::RequestInterceptor {
     let handler = ShieldInterceptHandler::new();
-    let mut interceptor = rustkit_net::RequestInterceptor::new();
+    let interceptor = rustkit_net::RequestInterceptor::new();
     interceptor.add_handler(Arc::new(handler));
     interceptor
 }
@@ -247,7 +247,7 @@ pub fn create_shield_interceptor_with_counter(
     counter: Arc<AtomicU64>,
 ) -> rustkit_net::RequestInterceptor {
     let handler = ShieldInterceptHandler::with_counter(counter);
-    let mut interceptor = rustkit_net::RequestInterceptor::new();
+    let interceptor = rustkit_net::RequestInterceptor::new();
     interceptor.add_handler(Arc::new(handler));
     interceptor
 }
@@ -260,7 +260,7 @@ where
     F: Fn(&str) + Send + Sync + 'static,
 {
     let handler = ShieldInterceptHandler::new().with_on_blocked(on_blocked);
-    let mut interceptor = rustkit_net::RequestInterceptor::new();
+    let interceptor = rustkit_net::RequestInterceptor::new();
     interceptor.add_handler(Arc::new(handler));
     interceptor
 }
@@ -282,6 +282,8 @@ mod tests {
             timeout: None,
             credentials: Default::default(),
             referrer: None,
+            is_navigation: false,
+            view_id: None,
         }
     }
 
@@ -242,7 +242,11 @@ impl IWebView for RustKitView {
 
     fn set_visible(&self, visible: bool) {
         *self.visible.borrow_mut() = visible;
-        if let Err(e) = self.engine.borrow().set_view_visible(self.view_id, visible) {
+        if let Err(e) = self
+            .engine
+            .borrow_mut()
+            .set_view_visible(self.view_id, visible)
+        {
             warn!(error = %e, "Failed to set view visibility");
         }
     }
@@ -156,6 +156,7 @@ pub enum Role {
 
     // Generic
     Generic,
+    StaticText,
     None,
 }
 
@@ -269,6 +270,31 @@ impl Role {
                 | Role::TreeItem
         )
     }
+
+    /// Check if the accessible name is computed from descendant content.
+    pub fn name_from_content(&self) -> bool {
+        matches!(
+            self,
+            Role::Button
+                | Role::Cell
+                | Role::Checkbox
+                | Role::ColumnHeader
+                | Role::GridCell
+                | Role::Heading
+                | Role::Link
+                | Role::MenuItem
+                | Role::MenuItemCheckbox
+                | Role::MenuItemRadio
+                | Role::Option
+                | Role::Radio
+                | Role::RowHeader
+                | Role::StaticText
+                | Role::Switch
+                | Role::Tab
+                | Role::Tooltip
+                | Role::TreeItem
+        )
+    }
 }
 
 impl Default for Role {
@@ -662,6 +688,53 @@ impl AccessibilityTree {
         }
     }
 
+    /// Get root node.
+    pub fn root(&self) -> Option<&AccessibleNode> {
+        self.root.and_then(|id| self.nodes.get(&id))
+    }
+
+    /// Get next sibling.
+    pub fn next_sibling(&self, id: AccessibleId) -> Option<AccessibleId> {
+        let siblings = &self.nodes.get(&self.nodes.get(&id)?.parent?)?.children;
+        let pos = siblings.iter().position(|&c| c == id)?;
+        siblings.get(pos + 1).copied()
+    }
+
+    /// Get previous sibling.
+    pub fn prev_sibling(&self, id: AccessibleId) -> Option<AccessibleId> {
+        let siblings = &self.nodes.get(&self.nodes.get(&id)?.parent?)?.children;
+        let pos = siblings.iter().position(|&c| c == id)?;
+        pos.checked_sub(1).map(|i| siblings[i])
+    }
+
+    /// Find the deepest node whose bounds contain the point.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<AccessibleId> {
+        let contains = |id: &AccessibleId| {
+            self.nodes
+                .get(id)
+                .and_then(|n| n.bounds)
+                .is_some_and(|(bx, by, bw, bh)| x >= bx && x < bx + bw && y >= by && y < by + bh)
+        };
+
+        let mut current = self.root?;
+        if !contains(&current) {
+            return None;
+        }
+
+        // Later children paint on top, so check them first
+        while let Some(&child) = self
+            .nodes
+            .get(&current)?
+            .children
+            .iter()
+            .rev()
+            .find(|c| contains(c))
+        {
+            current = child;
+        }
+        Some(current)
+    }
+
     /// Count nodes.
     pub fn len(&self) -> usize {
         self.nodes.len()
@@ -673,6 +746,360 @@ impl AccessibilityTree {
     }
 }
 
+// ==================== DOM Mapping ====================
+
+/// Bounds lookup for DOM nodes (x, y, width, height).
+pub type BoundsLookup<'a> = &'a dyn Fn(rustkit_dom::NodeId) -> Option<(f32, f32, f32, f32)>;
+
+/// Elements that never produce accessible nodes.
+const IGNORED_TAGS: &[&str] = &[
+    "head", "title", "meta", "link", "script", "style", "noscript", "template",
+];
+
+/// Infer the role of an element from its `role` attribute or tag name.
+pub fn role_for_element(node: &rustkit_dom::Node) -> Role {
+    if let Some(role) = node
+        .get_attribute("role")
+        .and_then(|r| r.split_whitespace().find_map(Role::from_str))
+    {
+        return role;
+    }
+
+    let Some(tag) = node.tag_name() else {
+        return if node.is_text() {
+            Role::StaticText
+        } else {
+            Role::Generic
+        };
+    };
+
+    match tag.to_ascii_lowercase().as_str() {
+        "a" | "area" if node.get_attribute("href").is_some() => Role::Link,
+        "article" => Role::Article,
+        "aside" => Role::Complementary,
+        "button" => Role::Button,
+        "dialog" => Role::Dialog,
+        "fieldset" | "details" | "optgroup" => Role::Group,
+        "footer" => Role::ContentInfo,
+        "form" => Role::Form,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Role::Heading,
+        "header" => Role::Banner,
+        "hr" => Role::Separator,
+        "img" if node.get_attribute("alt") == Some("") => Role::Presentation,
+        "img" => Role::Img,
+        "input" => match node
+            .get_attribute("type")
+            .unwrap_or("text")
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "button" | "submit" | "reset" | "image" => Role::Button,
+            "checkbox" => Role::Checkbox,
+            "radio" => Role::Radio,
+            "range" => Role::Slider,
+            "number" => Role::SpinButton,
+            "search" => Role::SearchBox,
+            "hidden" => Role::None,
+            _ => Role::TextBox,
+        },
+        "li" => Role::ListItem,
+        "main" => Role::Main,
+        "nav" => Role::Navigation,
+        "ol" | "ul" | "menu" => Role::List,
+        "option" => Role::Option,
+        "progress" => Role::ProgressBar,
+        "section" => Role::Region,
+        "table" => Role::Table,
+        "tbody" | "thead" | "tfoot" => Role::RowGroup,
+        "td" => Role::Cell,
+        "th" => Role::ColumnHeader,
+        "textarea" => Role::TextBox,
+        "tr" => Role::Row,
+        _ => Role::Generic,
+    }
+}
+
+/// Compute the accessible name of a node.
+///
+/// Precedence: `aria-label`, then `alt`, then descendant content (for roles
+/// that take their name from content), then `value`/`placeholder`/`title`.
+pub fn compute_name(node: &rustkit_dom::Node) -> Option<String> {
+    fn non_empty(value: Option<&str>) -> Option<String> {
+        value
+            .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|v| !v.is_empty())
+    }
+
+    if let rustkit_dom::NodeType::Text(text) = &node.node_type {
+        return non_empty(Some(text));
+    }
+
+    if let Some(label) = non_empty(node.get_attribute("aria-label")) {
+        return Some(label);
+    }
+    if let Some(alt) = non_empty(node.get_attribute("alt")) {
+        return Some(alt);
+    }
+
+    let role = role_for_element(node);
+    if role.name_from_content() {
+        let mut content = String::new();
+        collect_content_name(node, &mut content);
+        if let Some(name) = non_empty(Some(&content)) {
+            return Some(name);
+        }
+    }
+
+    if role == Role::Button
+        && node
+            .tag_name()
+            .is_some_and(|t| t.eq_ignore_ascii_case("input"))
+    {
+        if let Some(value) = non_empty(node.get_attribute("value")) {
+            return Some(value);
+        }
+    }
+
+    non_empty(node.get_attribute("placeholder")).or_else(|| non_empty(node.get_attribute("title")))
+}
+
+/// Concatenate the text alternatives of a node's descendants.
+fn collect_content_name(node: &rustkit_dom::Node, out: &mut String) {
+    for child in node.children() {
+        match &child.node_type {
+            rustkit_dom::NodeType::Text(text) => {
+                out.push(' ');
+                out.push_str(text);
+            }
+            rustkit_dom::NodeType::Element { .. } => {
+                if is_excluded(&child) {
+                    continue;
+                }
+                if let Some(label) = child
+                    .get_attribute("aria-label")
+                    .or(child.get_attribute("alt"))
+                {
+                    out.push(' ');
+                    out.push_str(label);
+                } else {
+                    collect_content_name(&child, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check whether an element and its subtree are excluded from the tree.
+fn is_excluded(node: &rustkit_dom::Node) -> bool {
+    node.tag_name()
+        .is_some_and(|t| IGNORED_TAGS.contains(&t.to_ascii_lowercase().as_str()))
+        || node.get_attribute("hidden").is_some()
+        || node.get_attribute("aria-hidden") == Some("true")
+}
+
+impl AccessibleNode {
+    /// Create an accessible node for a DOM node.
+    pub fn from_dom(node: &rustkit_dom::Node) -> Self {
+        let role = role_for_element(node);
+        let mut acc = Self::new(role);
+        acc.dom_node_id = Some(node.id);
+        acc.name = compute_name(node);
+
+        let rustkit_dom::NodeType::Element {
+            tag_name,
+            attributes,
+            ..
+        } = &node.node_type
+        else {
+            return acc;
+        };
+        let tag = tag_name.to_ascii_lowercase();
+
+        // ARIA properties and states
+        for (attr, value) in attributes.iter() {
+            if let Some(aria) = attr.strip_prefix("aria-") {
+                acc.set_property(attr, value);
+                if value == "true" {
+                    if let Some(state) = State::from_str(aria) {
+                        acc.add_state(state);
+                    }
+                }
+            }
+        }
+        acc.description = attributes.get("aria-description").cloned();
+
+        // Native states
+        if attributes.contains_key("disabled") {
+            acc.add_state(State::Disabled);
+        }
+        if attributes.contains_key("checked") {
+            acc.add_state(State::Checked);
+        }
+        if attributes.contains_key("required") {
+            acc.add_state(State::Required);
+        }
+        if attributes.contains_key("readonly") {
+            acc.add_state(State::ReadOnly);
+        }
+        if attributes.contains_key("selected") {
+            acc.add_state(State::Selected);
+        }
+        if tag == "select" && attributes.contains_key("multiple") {
+            acc.add_state(State::Multiselectable);
+        }
+        if tag == "details" && attributes.contains_key("open") {
+            acc.add_state(State::Expanded);
+        }
+
+        // Values for form controls
+        acc.value = match role {
+            Role::TextBox | Role::SearchBox | Role::Slider | Role::SpinButton => {
+                if tag == "textarea" {
+                    Some(node.text_content())
+                } else if attributes
+                    .get("type")
+                    .is_some_and(|t| t.eq_ignore_ascii_case("password"))
+                {
+                    None
+                } else {
+                    attributes.get("value").cloned()
+                }
+            }
+            Role::ProgressBar => attributes.get("value").cloned(),
+            _ => None,
+        }
+        .or_else(|| attributes.get("aria-valuenow").cloned());
+
+        acc.tab_index = attributes
+            .get("tabindex")
+            .and_then(|t| t.trim().parse().ok());
+        acc.level = attributes
+            .get("aria-level")
+            .and_then(|l| l.parse().ok())
+            .or_else(|| match tag.as_str() {
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => tag[1..].parse().ok(),
+                _ => None,
+            });
+
+        if let Some(live) = attributes.get("aria-live") {
+            acc.live_region = Some(LiveRegion {
+                politeness: LiveRegionPoliteness::from_str(live),
+                atomic: attributes.get("aria-atomic").is_some_and(|a| a == "true"),
+                relevant: attributes
+                    .get("aria-relevant")
+                    .map(|r| r.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default(),
+            });
+        }
+
+        acc
+    }
+
+    /// Check if two nodes expose the same information to assistive technology.
+    fn same_content(&self, other: &AccessibleNode) -> bool {
+        self.role == other.role
+            && self.name == other.name
+            && self.description == other.description
+            && self.value == other.value
+            && self.states == other.states
+            && self.properties == other.properties
+            && self.children == other.children
+            && self.tab_index == other.tab_index
+            && self.level == other.level
+            && self.bounds == other.bounds
+    }
+}
+
+impl AccessibilityTree {
+    /// Build a tree from a DOM document.
+    pub fn from_document(document: &rustkit_dom::Document, bounds: BoundsLookup<'_>) -> Self {
+        let mut tree = Self::new();
+        tree.update_from_document(document, bounds);
+        tree
+    }
+
+    /// Sync the tree with a DOM document.
+    ///
+    /// Nodes keep their IDs across updates as long as their DOM node survives.
+    /// Returns the IDs of nodes that were added, changed, or removed.
+    pub fn update_from_document(
+        &mut self,
+        document: &rustkit_dom::Document,
+        bounds: BoundsLookup<'_>,
+    ) -> Vec<AccessibleId> {
+        let old_nodes = std::mem::take(&mut self.nodes);
+        let old_dom_map = std::mem::take(&mut self.dom_map);
+        self.tab_order_dirty = true;
+
+        let dom_root = document.root();
+        let content_root = document.body().or_else(|| document.document_element());
+
+        let mut root = AccessibleNode::new(Role::Document);
+        root.dom_node_id = Some(dom_root.id);
+        root.name = document.title();
+        root.bounds =
+            bounds(dom_root.id).or_else(|| content_root.as_ref().and_then(|n| bounds(n.id)));
+        if let Some(&id) = old_dom_map.get(&dom_root.id) {
+            root.id = id;
+        }
+        let root_id = self.add_node(root);
+        self.root = Some(root_id);
+
+        if let Some(content_root) = content_root {
+            for child in content_root.children() {
+                self.build_dom_node(&child, root_id, &old_dom_map, bounds);
+            }
+        }
+
+        let mut changed: Vec<AccessibleId> = self
+            .nodes
+            .values()
+            .filter(|n| old_nodes.get(&n.id).is_none_or(|old| !old.same_content(n)))
+            .map(|n| n.id)
+            .chain(
+                old_nodes
+                    .keys()
+                    .filter(|id| !self.nodes.contains_key(*id))
+                    .copied(),
+            )
+            .collect();
+        changed.sort_by_key(|id| id.raw());
+
+        if self.focus.is_some_and(|id| !self.nodes.contains_key(&id)) {
+            self.focus = None;
+        }
+
+        changed
+    }
+
+    fn build_dom_node(
+        &mut self,
+        node: &std::rc::Rc<rustkit_dom::Node>,
+        parent: AccessibleId,
+        old_dom_map: &HashMap<rustkit_dom::NodeId, AccessibleId>,
+        bounds: BoundsLookup<'_>,
+    ) {
+        match &node.node_type {
+            rustkit_dom::NodeType::Element { .. } if !is_excluded(node) => {}
+            rustkit_dom::NodeType::Text(text) if !text.trim().is_empty() => {}
+            _ => return,
+        }
+
+        let mut acc = AccessibleNode::from_dom(node);
+        acc.bounds = bounds(node.id);
+        if let Some(&id) = old_dom_map.get(&node.id) {
+            acc.id = id;
+        }
+        let id = self.add_node(acc);
+        self.add_child(parent, id);
+
+        for child in node.children() {
+            self.build_dom_node(&child, id, old_dom_map, bounds);
+        }
+    }
+}
+
 // ==================== Announcements ====================
 
 /// An announcement for screen readers.
@@ -1115,5 +1542,151 @@ mod tests {
         
         assert_eq!(node.get_property("aria-valuenow"), Some("50"));
     }
-}
 
+    #[test]
+    fn test_tree_from_document() {
+        let html = r#"<html><head><title>Fixture</title></head><body>
+            <h1>Welcome</h1>
+            <nav><a href="/home">Home</a></nav>
+            <img src="a.png" alt="Logo">
+            <button disabled>Send</button>
+            <input type="checkbox" checked aria-label="Agree">
+            <input type="text" value="hello">
+            <div hidden><button>Invisible</button></div>
+            <script>var x = 1;</script>
+        </body></html>"#;
+        let document = rustkit_dom::Document::parse_html(html).unwrap();
+        let tree = AccessibilityTree::from_document(&document, &|_| None);
+
+        let root = tree.root().unwrap();
+        assert_eq!(root.role, Role::Document);
+        assert_eq!(root.name.as_deref(), Some("Fixture"));
+
+        let mut found = Vec::new();
+        tree.walk(|node, depth| found.push((node.role, node.name.clone(), depth)));
+
+        let heading = tree
+            .nodes
+            .values()
+            .find(|n| n.role == Role::Heading)
+            .unwrap();
+        assert_eq!(heading.name.as_deref(), Some("Welcome"));
+        assert_eq!(heading.level, Some(1));
+
+        assert!(found.contains(&(Role::Navigation, None, 1)));
+        assert!(found.contains(&(Role::Link, Some("Home".to_string()), 2)));
+        assert!(found.contains(&(Role::Img, Some("Logo".to_string()), 1)));
+
+        let button = tree
+            .nodes
+            .values()
+            .find(|n| n.role == Role::Button)
+            .unwrap();
+        assert_eq!(button.name.as_deref(), Some("Send"));
+        assert!(button.has_state(State::Disabled));
+
+        let checkbox = tree
+            .nodes
+            .values()
+            .find(|n| n.role == Role::Checkbox)
+            .unwrap();
+        assert!(checkbox.has_state(State::Checked));
+
+        let textbox = tree
+            .nodes
+            .values()
+            .find(|n| n.role == Role::TextBox)
+            .unwrap();
+        assert_eq!(textbox.value.as_deref(), Some("hello"));
+
+        // Hidden subtrees and scripts are excluded
+        assert!(!found
+            .iter()
+            .any(|(_, name, _)| name.as_deref() == Some("Invisible")));
+        assert!(!found
+            .iter()
+            .any(|(_, name, _)| name.as_deref() == Some("var x = 1;")));
+    }
+
+    #[test]
+    fn test_name_precedence() {
+        let html = r#"<html><body>
+            <a id="label" href="/" aria-label="From label"><img alt="From alt">Text</a>
+            <img id="alt" alt="From alt" title="From title">
+            <button id="content"> From   content </button>
+            <a id="nested" href="/"><img alt="Nested alt"></a>
+        </body></html>"#;
+        let document = rustkit_dom::Document::parse_html(html).unwrap();
+        let name = |id: &str| compute_name(&document.get_element_by_id(id).unwrap());
+
+        assert_eq!(name("label").as_deref(), Some("From label"));
+        assert_eq!(name("alt").as_deref(), Some("From alt"));
+        assert_eq!(name("content").as_deref(), Some("From content"));
+        assert_eq!(name("nested").as_deref(), Some("Nested alt"));
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let document = rustkit_dom::Document::parse_html(
+            "<html><body><button>One</button><p>Two</p></body></html>",
+        )
+        .unwrap();
+        let mut tree = AccessibilityTree::from_document(&document, &|_| None);
+        let button_id = tree
+            .nodes
+            .values()
+            .find(|n| n.role == Role::Button)
+            .unwrap()
+            .id;
+
+        // Unchanged document reports no changes and keeps IDs
+        assert!(tree.update_from_document(&document, &|_| None).is_empty());
+        assert!(tree.get(button_id).is_some());
+
+        // Bounds changes are reported
+        let button_dom = tree.get(button_id).unwrap().dom_node_id.unwrap();
+        let changed = tree.update_from_document(&document, &|id| {
+            (id == button_dom).then_some((10.0, 10.0, 50.0, 20.0))
+        });
+        assert_eq!(changed, vec![button_id]);
+    }
+
+    #[test]
+    fn test_tree_hit_test() {
+        let document = rustkit_dom::Document::parse_html(
+            "<html><body><div><button>Go</button></div></body></html>",
+        )
+        .unwrap();
+        let body = document.body().unwrap();
+        let div = body
+            .children()
+            .into_iter()
+            .find(|n| n.is_element())
+            .unwrap();
+        let button = div.children().into_iter().find(|n| n.is_element()).unwrap();
+        let (body_id, div_id, button_id) = (body.id, div.id, button.id);
+
+        let tree = AccessibilityTree::from_document(&document, &|id| {
+            if id == body_id {
+                Some((0.0, 0.0, 800.0, 600.0))
+            } else if id == div_id {
+                Some((0.0, 0.0, 800.0, 100.0))
+            } else if id == button_id {
+                Some((10.0, 10.0, 60.0, 20.0))
+            } else {
+                None
+            }
+        });
+
+        let hit = tree.hit_test(20.0, 15.0).unwrap();
+        assert_eq!(tree.get(hit).unwrap().role, Role::Button);
+
+        let hit = tree.hit_test(400.0, 50.0).unwrap();
+        assert_eq!(tree.get(hit).unwrap().dom_node_id, Some(div_id));
+
+        let hit = tree.hit_test(400.0, 300.0).unwrap();
+        assert_eq!(tree.get(hit).unwrap().role, Role::Document);
+
+        assert!(tree.hit_test(900.0, 700.0).is_none());
+    }
+}
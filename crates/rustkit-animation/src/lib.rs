@@ -26,7 +26,7 @@
 //!           └── Timing
 //! ```
 
-use rustkit_css::Color;
+use rustkit_css::{Color, TranslateScale};
 use rustkit_dom::NodeId;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -143,6 +143,8 @@ impl TimingFunction {
             "ease-in" => Ok(TimingFunction::EaseIn),
             "ease-out" => Ok(TimingFunction::EaseOut),
             "ease-in-out" => Ok(TimingFunction::EaseInOut),
+            "step-start" => Ok(TimingFunction::Steps(1, StepPosition::Start)),
+            "step-end" => Ok(TimingFunction::Steps(1, StepPosition::End)),
             _ if s.starts_with("cubic-bezier(") => {
                 let inner = s.trim_start_matches("cubic-bezier(").trim_end_matches(')');
                 let parts: Vec<f64> = inner
@@ -307,6 +309,16 @@ impl AnimatableValue {
             (AnimatableValue::Color(a), AnimatableValue::Color(b)) => {
                 AnimatableValue::Color(interpolate_color(a, b, progress as f32))
             }
+            (AnimatableValue::Transform(a), AnimatableValue::Transform(b)) => {
+                // Translations and scales interpolate; anything else is discrete
+                match (TranslateScale::parse(a), TranslateScale::parse(b)) {
+                    (Some(a), Some(b)) => {
+                        AnimatableValue::Transform(a.lerp(&b, progress as f32).to_css())
+                    }
+                    _ if progress < 0.5 => self.clone(),
+                    _ => other.clone(),
+                }
+            }
             (AnimatableValue::Visibility(a), AnimatableValue::Visibility(b)) => {
                 // Discrete: switch at 50%
                 if progress < 0.5 {
@@ -446,6 +458,52 @@ impl AnimatableProperty {
         )
     }
 
+    /// CSS property name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimatableProperty::Width => "width",
+            AnimatableProperty::Height => "height",
+            AnimatableProperty::MinWidth => "min-width",
+            AnimatableProperty::MinHeight => "min-height",
+            AnimatableProperty::MaxWidth => "max-width",
+            AnimatableProperty::MaxHeight => "max-height",
+            AnimatableProperty::MarginTop => "margin-top",
+            AnimatableProperty::MarginRight => "margin-right",
+            AnimatableProperty::MarginBottom => "margin-bottom",
+            AnimatableProperty::MarginLeft => "margin-left",
+            AnimatableProperty::PaddingTop => "padding-top",
+            AnimatableProperty::PaddingRight => "padding-right",
+            AnimatableProperty::PaddingBottom => "padding-bottom",
+            AnimatableProperty::PaddingLeft => "padding-left",
+            AnimatableProperty::BorderTopWidth => "border-top-width",
+            AnimatableProperty::BorderRightWidth => "border-right-width",
+            AnimatableProperty::BorderBottomWidth => "border-bottom-width",
+            AnimatableProperty::BorderLeftWidth => "border-left-width",
+            AnimatableProperty::Top => "top",
+            AnimatableProperty::Right => "right",
+            AnimatableProperty::Bottom => "bottom",
+            AnimatableProperty::Left => "left",
+            AnimatableProperty::Color => "color",
+            AnimatableProperty::BackgroundColor => "background-color",
+            AnimatableProperty::BorderTopColor => "border-top-color",
+            AnimatableProperty::BorderRightColor => "border-right-color",
+            AnimatableProperty::BorderBottomColor => "border-bottom-color",
+            AnimatableProperty::BorderLeftColor => "border-left-color",
+            AnimatableProperty::FontSize => "font-size",
+            AnimatableProperty::LineHeight => "line-height",
+            AnimatableProperty::LetterSpacing => "letter-spacing",
+            AnimatableProperty::WordSpacing => "word-spacing",
+            AnimatableProperty::Opacity => "opacity",
+            AnimatableProperty::Visibility => "visibility",
+            AnimatableProperty::Transform => "transform",
+            AnimatableProperty::FlexGrow => "flex-grow",
+            AnimatableProperty::FlexShrink => "flex-shrink",
+            AnimatableProperty::Gap => "gap",
+            AnimatableProperty::RowGap => "row-gap",
+            AnimatableProperty::ColumnGap => "column-gap",
+        }
+    }
+
     /// Parse from CSS property name.
     pub fn parse(name: &str) -> Option<Self> {
         match name.to_lowercase().as_str() {
@@ -888,6 +946,8 @@ pub struct Transition {
     pub state: TransitionState,
     /// Current computed value.
     pub current_value: AnimatableValue,
+    /// Active time reached at the last tick, excluding the delay.
+    pub elapsed: Duration,
 }
 
 /// Transition state.
@@ -926,6 +986,19 @@ impl Transition {
             start_time: Some(Instant::now()),
             state: TransitionState::Pending,
             current_value: from,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// An event about this transition.
+    fn event(&self, event_type: AnimationEventType, elapsed_time: f64) -> AnimationEvent {
+        AnimationEvent {
+            event_type,
+            target: self.target,
+            animation_name: None,
+            property_name: Some(self.property.name().to_string()),
+            elapsed_time,
+            pseudo_element: String::new(),
         }
     }
 
@@ -934,6 +1007,12 @@ impl Transition {
         let Some(start) = self.start_time else {
             return false;
         };
+        if matches!(
+            self.state,
+            TransitionState::Completed | TransitionState::Cancelled
+        ) {
+            return false;
+        }
 
         let elapsed = now.duration_since(start);
 
@@ -948,6 +1027,7 @@ impl Transition {
         }
 
         let active_time = elapsed - self.delay;
+        self.elapsed = active_time.min(self.duration);
 
         // Check if complete
         if active_time >= self.duration {
@@ -1075,17 +1155,30 @@ impl AnimationTimeline {
         easing: TimingFunction,
     ) -> TransitionId {
         let transition = Transition::new(target, property, from, to, duration, delay, easing);
-        let id = transition.id;
+        self.add_transition(transition)
+    }
 
+    /// Start a transition built by the caller, e.g. with an explicit start time.
+    pub fn add_transition(&mut self, transition: Transition) -> TransitionId {
+        let id = transition.id;
+        self.pending_events
+            .push(transition.event(AnimationEventType::TransitionRun, 0.0));
+        trace!(
+            "Started transition {:?} for {:?}",
+            transition.property,
+            transition.target
+        );
         self.transitions.insert(id, transition);
-
-        trace!("Started transition {:?} for {:?}", property, target);
         id
     }
 
     /// Update all animations and transitions.
     pub fn tick(&mut self) -> bool {
-        let now = Instant::now();
+        self.tick_at(Instant::now())
+    }
+
+    /// Update all animations and transitions to time `now`.
+    pub fn tick_at(&mut self, now: Instant) -> bool {
         let mut any_running = false;
 
         // Update animations
@@ -1114,30 +1207,23 @@ impl AnimationTimeline {
         }
 
         // Update transitions
-        let mut finished_transitions = Vec::new();
-        for (id, transition) in &mut self.transitions {
-            let was_running = transition.state == TransitionState::Running;
+        for transition in self.transitions.values_mut() {
+            let was_pending = transition.state == TransitionState::Pending;
             let is_running = transition.tick(now);
+            let duration = transition.duration.as_secs_f64();
 
-            if was_running && transition.state == TransitionState::Completed {
-                finished_transitions.push((*id, transition.property, transition.target));
+            if was_pending && transition.state != TransitionState::Pending {
+                self.pending_events
+                    .push(transition.event(AnimationEventType::TransitionStart, 0.0));
+            }
+            if transition.state == TransitionState::Completed {
+                self.pending_events
+                    .push(transition.event(AnimationEventType::TransitionEnd, duration));
             }
 
             any_running |= is_running;
         }
 
-        // Emit transition end events
-        for (_id, property, target) in finished_transitions {
-            self.pending_events.push(AnimationEvent {
-                event_type: AnimationEventType::TransitionEnd,
-                target,
-                animation_name: None,
-                property_name: Some(format!("{:?}", property).to_lowercase()),
-                elapsed_time: 0.0,
-                pseudo_element: String::new(),
-            });
-        }
-
         // Clean up finished animations and transitions
         self.animations.retain(|_, a| a.play_state != AnimationPlayState::Finished);
         self.transitions.retain(|_, t| t.state != TransitionState::Completed && t.state != TransitionState::Cancelled);
@@ -1220,14 +1306,9 @@ impl AnimationTimeline {
     pub fn cancel_transition(&mut self, id: TransitionId) {
         if let Some(transition) = self.transitions.get_mut(&id) {
             transition.cancel();
-            self.pending_events.push(AnimationEvent {
-                event_type: AnimationEventType::TransitionCancel,
-                target: transition.target,
-                animation_name: None,
-                property_name: Some(format!("{:?}", transition.property).to_lowercase()),
-                elapsed_time: 0.0,
-                pseudo_element: String::new(),
-            });
+            let elapsed = transition.elapsed.as_secs_f64();
+            self.pending_events
+                .push(transition.event(AnimationEventType::TransitionCancel, elapsed));
         }
     }
 
@@ -1413,6 +1494,90 @@ mod tests {
         assert_eq!(transition.state, TransitionState::Running);
     }
 
+    #[test]
+    fn test_timeline_transition_events() {
+        let mut timeline = AnimationTimeline::new();
+        let start = Instant::now();
+        let mut transition = Transition::new(
+            NodeId::new(1),
+            AnimatableProperty::BackgroundColor,
+            AnimatableValue::Color(Color::BLACK),
+            AnimatableValue::Color(Color::WHITE),
+            Duration::from_millis(100),
+            Duration::ZERO,
+            TimingFunction::Linear,
+        );
+        transition.start_time = Some(start);
+        let id = timeline.add_transition(transition);
+
+        // Starting and finishing within one tick still reports both
+        assert!(!timeline.tick_at(start + Duration::from_millis(150)));
+        let events: Vec<_> = timeline
+            .take_events()
+            .into_iter()
+            .map(|e| (e.event_type, e.property_name.unwrap(), e.elapsed_time))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (
+                    AnimationEventType::TransitionRun,
+                    "background-color".to_string(),
+                    0.0
+                ),
+                (
+                    AnimationEventType::TransitionStart,
+                    "background-color".to_string(),
+                    0.0
+                ),
+                (
+                    AnimationEventType::TransitionEnd,
+                    "background-color".to_string(),
+                    0.1
+                ),
+            ]
+        );
+        assert!(timeline.get_transition(id).is_none());
+
+        let id = timeline.transition(
+            NodeId::new(1),
+            AnimatableProperty::Transform,
+            AnimatableValue::Transform("none".into()),
+            AnimatableValue::Transform("translateX(100px)".into()),
+            Duration::from_secs(10),
+            Duration::ZERO,
+            TimingFunction::Linear,
+        );
+        timeline.cancel_transition(id);
+        timeline.tick();
+        let types: Vec<_> = timeline
+            .take_events()
+            .into_iter()
+            .map(|e| e.event_type)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                AnimationEventType::TransitionRun,
+                AnimationEventType::TransitionCancel
+            ]
+        );
+        assert_eq!(timeline.transition_count(), 0);
+    }
+
+    #[test]
+    fn test_transform_interpolation() {
+        let from = AnimatableValue::Transform("none".into());
+        let to = AnimatableValue::Transform("translateX(100px) scale(3)".into());
+        assert_eq!(
+            from.interpolate(&to, 0.5),
+            AnimatableValue::Transform("translate(50px, 0px) scale(2, 2)".into())
+        );
+        // Unsupported functions switch halfway
+        let rotate = AnimatableValue::Transform("rotate(90deg)".into());
+        assert_eq!(from.interpolate(&rotate, 0.4), from);
+    }
+
     #[test]
     fn test_animatable_property_parse() {
         assert!(matches!(AnimatableProperty::parse("opacity"), Some(AnimatableProperty::Opacity)));
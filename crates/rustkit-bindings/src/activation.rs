@@ -0,0 +1,160 @@
+//! User activation and `navigator.userActivation`.
+//!
+//! Genuine input gives a window sticky activation, which it keeps, and
+//! transient activation, which lapses after [`TRANSIENT_ACTIVATION`] or
+//! when an API such as `window.open` or a clipboard write consumes it.
+//! Following the HTML consumption model, consuming clears transient
+//! activation across the whole frame tree, so one click is good for one
+//! gated call. Only the engine grants activation; events script
+//! dispatches itself never do.
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+/// How long transient activation lasts after the input that granted it.
+pub const TRANSIENT_ACTIVATION: Duration = Duration::from_secs(5);
+
+/// The activation state of one window.
+#[derive(Debug, Default)]
+pub struct UserActivation {
+    /// When genuine input last activated the window; cleared on consumption.
+    last_activation: Cell<Option<Instant>>,
+    /// Whether the window was ever activated.
+    has_been_active: Cell<bool>,
+    /// The other windows of the frame tree, consumed along with this one.
+    tree: RefCell<Vec<Weak<UserActivation>>>,
+}
+
+impl UserActivation {
+    /// Grant sticky and transient activation at `now`.
+    pub fn activate(&self, now: Instant) {
+        self.last_activation.set(Some(now));
+        self.has_been_active.set(true);
+    }
+
+    /// Whether the window was ever activated (`hasBeenActive`).
+    pub fn has_been_active(&self) -> bool {
+        self.has_been_active.get()
+    }
+
+    /// Whether the window has transient activation at `now` (`isActive`).
+    pub fn is_active(&self, now: Instant) -> bool {
+        self.last_activation
+            .get()
+            .is_some_and(|at| now.saturating_duration_since(at) < TRANSIENT_ACTIVATION)
+    }
+
+    /// Use up transient activation, returning whether there was any. Clears
+    /// it for every window in the frame tree.
+    pub fn consume(&self, now: Instant) -> bool {
+        if !self.is_active(now) {
+            return false;
+        }
+        self.last_activation.set(None);
+        for window in self.tree.borrow().iter().filter_map(Weak::upgrade) {
+            window.last_activation.set(None);
+        }
+        true
+    }
+
+    /// Put `self` and `child` in the same frame tree for consumption.
+    pub(crate) fn link(self: &Rc<Self>, child: &Rc<Self>) {
+        let mut tree = self.tree.borrow().clone();
+        tree.push(Rc::downgrade(self));
+        tree.retain(|window| window.strong_count() > 0);
+        for window in tree.iter().filter_map(Weak::upgrade) {
+            window.tree.borrow_mut().push(Rc::downgrade(child));
+        }
+        *child.tree.borrow_mut() = tree;
+    }
+}
+
+const USER_ACTIVATION_JS: &str = r#"
+    window.navigator.userActivation = {
+        get hasBeenActive() { return __rustkit_activation_state('sticky'); },
+        get isActive() { return __rustkit_activation_state('transient'); }
+    };
+"#;
+
+/// Install `navigator.userActivation`, reading from `activation`.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    activation: Rc<UserActivation>,
+) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_activation_state", 1, move |args| {
+        let active = match args.first().map(String::as_str) {
+            Some("transient") => activation.is_active(Instant::now()),
+            _ => activation.has_been_active(),
+        };
+        Ok(JsValue::Boolean(active))
+    })?;
+    runtime.evaluate_script(USER_ACTIVATION_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    #[test]
+    fn test_transient_activation_expires_and_is_consumed_once() {
+        let start = Instant::now();
+        let activation = UserActivation::default();
+        assert!(!activation.has_been_active());
+        activation.activate(start);
+        assert!(activation.is_active(start + Duration::from_secs(4)));
+        assert!(!activation.is_active(start + TRANSIENT_ACTIVATION));
+        assert!(!activation.consume(start + TRANSIENT_ACTIVATION));
+        assert!(activation.has_been_active());
+
+        activation.activate(start);
+        assert!(activation.consume(start));
+        assert!(!activation.consume(start));
+        assert!(activation.has_been_active());
+    }
+
+    #[test]
+    fn test_consumption_spans_the_frame_tree() {
+        let now = Instant::now();
+        let top = Rc::new(UserActivation::default());
+        let child = Rc::new(UserActivation::default());
+        let grandchild = Rc::new(UserActivation::default());
+        top.link(&child);
+        child.link(&grandchild);
+        for window in [&top, &child, &grandchild] {
+            window.activate(now);
+        }
+        assert!(grandchild.consume(now));
+        assert!([&top, &child, &grandchild]
+            .iter()
+            .all(|window| !window.is_active(now)));
+    }
+
+    #[test]
+    fn test_navigator_user_activation() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let state = || {
+            let JsValue::String(state) = bindings
+                .evaluate(
+                    "navigator.userActivation.hasBeenActive + ',' + \
+                     navigator.userActivation.isActive",
+                )
+                .unwrap()
+            else {
+                panic!("expected a string");
+            };
+            state
+        };
+        assert_eq!(state(), "false,false");
+        bindings.notify_user_activation();
+        assert_eq!(state(), "true,true");
+        // window.open uses it up; sticky activation stays
+        bindings
+            .evaluate("window.open('https://example.com/')")
+            .unwrap();
+        assert_eq!(state(), "true,false");
+    }
+}
@@ -0,0 +1,478 @@
+//! `<audio>` elements and the `Audio` constructor.
+//!
+//! Playback is done by the host: script queues [`AudioCommand`]s for each
+//! media element and the engine reports back with
+//! [`crate::DomBindings::update_audio`], which updates the element's state
+//! and fires its `play`, `playing`, `pause`, `timeupdate`, `ended` and
+//! `error` events. Autoplay policy is enforced here: `play()` on an unmuted
+//! element rejects with `NotAllowedError` unless the window has user
+//! activation.
+
+use crate::activation::UserActivation;
+use rustkit_dom::NodeId;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// What script asked of a media element.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum AudioOp {
+    /// Fetch and decode `url`, dropping the current clip.
+    Load {
+        url: String,
+    },
+    Play,
+    Pause,
+    /// Set `currentTime`, in seconds.
+    Seek {
+        time: f64,
+    },
+    Volume {
+        volume: f64,
+        muted: bool,
+    },
+}
+
+/// An operation on one media element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioCommand {
+    /// Identifies the element in [`crate::DomBindings::update_audio`].
+    pub media_id: u64,
+    /// The element's DOM node; `None` for `new Audio()` elements that are
+    /// not in the document.
+    pub node_id: Option<NodeId>,
+    pub op: AudioOp,
+}
+
+/// A change in a media element's playback, reported by the host.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AudioUpdate {
+    /// The clip loaded; `duration` is in seconds.
+    Metadata { duration: f64 },
+    /// Playback started; pending `play()` promises resolve.
+    Playing,
+    /// Playback stopped at `time`.
+    Paused { time: f64 },
+    /// Playback position moved.
+    Time { time: f64 },
+    /// Playback reached the end of the clip.
+    Ended,
+    /// The clip could not be fetched or decoded: `MediaError` `code`.
+    Error { code: u16, message: String },
+}
+
+impl AudioUpdate {
+    /// `MEDIA_ERR_NETWORK`
+    pub const ERR_NETWORK: u16 = 2;
+    /// `MEDIA_ERR_SRC_NOT_SUPPORTED`
+    pub const ERR_SRC_NOT_SUPPORTED: u16 = 4;
+}
+
+#[derive(Deserialize)]
+struct ScriptCommand {
+    #[serde(rename = "mediaId")]
+    media_id: u64,
+    #[serde(rename = "nodeId")]
+    node_id: Option<usize>,
+    #[serde(flatten)]
+    op: AudioOp,
+}
+
+/// Commands queued by script since the host last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct AudioState {
+    next_id: Cell<u64>,
+    commands: RefCell<Vec<AudioCommand>>,
+}
+
+impl AudioState {
+    pub(crate) fn take_commands(&self) -> Vec<AudioCommand> {
+        self.commands.take()
+    }
+}
+
+const AUDIO_JS: &str = r#"
+    var __rustkit_media = {};
+
+    function __rustkit_mediaEvent(elem, type) {
+        elem.dispatchEvent({
+            type: type, bubbles: false, cancelable: false, defaultPrevented: false,
+            timeStamp: Date.now(), isTrusted: true,
+            preventDefault: function() {},
+            stopPropagation: function() {}, stopImmediatePropagation: function() {}
+        });
+    }
+
+    function __rustkit_defineMedia(elem) {
+        var id = __rustkit_audio_create();
+        var state = {
+            src: null, loaded: null, currentTime: 0, duration: NaN,
+            paused: true, ended: false, volume: 1, muted: false, error: null, plays: []
+        };
+        __rustkit_media[id] = elem;
+        elem._mediaId = id;
+        elem._media = state;
+        elem._listeners = {};
+        elem.addEventListener = window.addEventListener;
+        elem.removeEventListener = window.removeEventListener;
+        elem.dispatchEvent = window.dispatchEvent;
+
+        function send(command) {
+            command.mediaId = id;
+            command.nodeId = elem._nodeId === undefined ? null : elem._nodeId;
+            __rustkit_audio_command(JSON.stringify(command));
+        }
+        function source() {
+            var src = state.src !== null ? state.src : elem.getAttribute('src');
+            if (!src) return '';
+            try { return new URL(src, document.URL).href; } catch (e) { return src; }
+        }
+        function settlePlays(error) {
+            var plays = state.plays;
+            state.plays = [];
+            plays.forEach(function(p) { error ? p.reject(error) : p.resolve(); });
+        }
+        elem._mediaUpdate = function(update) {
+            switch (update.kind) {
+                case 'metadata':
+                    state.duration = update.duration;
+                    __rustkit_mediaEvent(elem, 'durationchange');
+                    __rustkit_mediaEvent(elem, 'loadedmetadata');
+                    __rustkit_mediaEvent(elem, 'canplay');
+                    break;
+                case 'playing':
+                    __rustkit_mediaEvent(elem, 'playing');
+                    settlePlays(null);
+                    break;
+                case 'paused':
+                    state.currentTime = update.time;
+                    break;
+                case 'time':
+                    state.currentTime = update.time;
+                    __rustkit_mediaEvent(elem, 'timeupdate');
+                    break;
+                case 'ended':
+                    state.currentTime = state.duration;
+                    state.paused = true;
+                    state.ended = true;
+                    __rustkit_mediaEvent(elem, 'timeupdate');
+                    __rustkit_mediaEvent(elem, 'pause');
+                    __rustkit_mediaEvent(elem, 'ended');
+                    break;
+                case 'error':
+                    state.error = { code: update.code, message: update.message };
+                    state.paused = true;
+                    settlePlays(new DOMException(update.message, 'NotSupportedError'));
+                    __rustkit_mediaEvent(elem, 'error');
+                    break;
+            }
+        };
+
+        elem.load = function() {
+            state.loaded = source();
+            state.currentTime = 0;
+            state.duration = NaN;
+            state.ended = false;
+            state.error = null;
+            if (!state.paused) {
+                state.paused = true;
+                settlePlays(new DOMException('The play() request was interrupted by a new load request.', 'AbortError'));
+                __rustkit_mediaEvent(elem, 'pause');
+            }
+            if (state.loaded) send({ op: 'load', url: state.loaded });
+        };
+        elem.play = function() {
+            return new Promise(function(resolve, reject) {
+                if (!state.muted && !__rustkit_audio_activated()) {
+                    reject(new DOMException('play() failed because the user didn\'t interact with the document first.',
+                        'NotAllowedError'));
+                    return;
+                }
+                if (state.error) {
+                    reject(new DOMException(state.error.message, 'NotSupportedError'));
+                    return;
+                }
+                if (state.loaded === null) elem.load();
+                state.plays.push({ resolve: resolve, reject: reject });
+                if (state.ended) {
+                    state.ended = false;
+                    state.currentTime = 0;
+                    send({ op: 'seek', time: 0 });
+                }
+                if (state.paused) {
+                    state.paused = false;
+                    __rustkit_mediaEvent(elem, 'play');
+                    send({ op: 'play' });
+                }
+            });
+        };
+        elem.pause = function() {
+            if (state.paused) return;
+            state.paused = true;
+            send({ op: 'pause' });
+            __rustkit_mediaEvent(elem, 'pause');
+            settlePlays(new DOMException('The play() request was interrupted by a call to pause().', 'AbortError'));
+        };
+        elem.canPlayType = function(type) {
+            return /^audio\/(wav|wave|x-wav|mpeg|mp3|ogg)\b/i.test(String(type)) ? 'maybe' : '';
+        };
+
+        Object.defineProperties(elem, {
+            src: {
+                get: function() { return source(); },
+                set: function(value) { state.src = String(value); elem.load(); }
+            },
+            currentSrc: { get: function() { return state.loaded || ''; } },
+            currentTime: {
+                get: function() { return state.currentTime; },
+                set: function(value) {
+                    var time = Math.max(0, Number(value) || 0);
+                    if (!isNaN(state.duration)) time = Math.min(time, state.duration);
+                    state.currentTime = time;
+                    state.ended = false;
+                    send({ op: 'seek', time: time });
+                    __rustkit_mediaEvent(elem, 'timeupdate');
+                }
+            },
+            duration: { get: function() { return state.duration; } },
+            paused: { get: function() { return state.paused; } },
+            ended: { get: function() { return state.ended; } },
+            error: { get: function() { return state.error; } },
+            volume: {
+                get: function() { return state.volume; },
+                set: function(value) {
+                    value = Number(value);
+                    if (!(value >= 0 && value <= 1)) {
+                        throw new DOMException('The volume provided (' + value +
+                            ') is outside the range [0, 1].', 'IndexSizeError');
+                    }
+                    state.volume = value;
+                    send({ op: 'volume', volume: state.volume, muted: state.muted });
+                    __rustkit_mediaEvent(elem, 'volumechange');
+                }
+            },
+            muted: {
+                get: function() { return state.muted; },
+                set: function(value) {
+                    state.muted = !!value;
+                    send({ op: 'volume', volume: state.volume, muted: state.muted });
+                    __rustkit_mediaEvent(elem, 'volumechange');
+                }
+            },
+            controls: {
+                get: function() { return elem.getAttribute('controls') !== null; },
+                set: function(value) {
+                    value ? elem.setAttribute('controls', '') : elem.removeAttribute('controls');
+                }
+            }
+        });
+        return elem;
+    }
+
+    function __rustkit_mediaSettle(id, update) {
+        var elem = __rustkit_media[id];
+        if (elem) elem._mediaUpdate(update);
+    }
+
+    function __rustkit_mediaControl(nodeId, action, fraction) {
+        var elem = document._nodes[nodeId];
+        if (!elem || elem._mediaId === undefined) return;
+        if (action === 'seek') {
+            if (!isNaN(elem.duration)) elem.currentTime = elem.duration * fraction;
+        } else if (elem.paused) {
+            elem.play().catch(function() {});
+        } else {
+            elem.pause();
+        }
+    }
+
+    var __rustkit_mediaCreateElement = document.createElement;
+    document.createElement = function(tagName) {
+        var elem = __rustkit_mediaCreateElement.call(document, tagName);
+        return String(tagName).toLowerCase() === 'audio' ? __rustkit_defineMedia(elem) : elem;
+    };
+
+    // Parsed elements with a source start loading once adopted
+    var __rustkit_mediaAdopt = document._adopt;
+    document._adopt = function(tree) {
+        __rustkit_mediaAdopt.call(document, tree);
+        Object.keys(__rustkit_media).forEach(function(id) {
+            var elem = __rustkit_media[id];
+            if (elem._nodeId !== undefined && elem._media.loaded === null && elem.getAttribute('src')) {
+                elem.load();
+            }
+        });
+    };
+
+    function Audio(src) {
+        var elem = document.createElement('audio');
+        if (src !== undefined) elem.src = src;
+        return elem;
+    }
+    window.Audio = Audio;
+"#;
+
+/// Register the audio natives and the `<audio>` element API.
+///
+/// Needs `DOMException`, `URL` and the element geometry of
+/// [`crate::geometry`] installed first.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<AudioState>,
+    activation: Rc<UserActivation>,
+) -> Result<(), JsError> {
+    let create_state = state.clone();
+    runtime.register_function("__rustkit_audio_create", 0, move |_| {
+        let id = create_state.next_id.get() + 1;
+        create_state.next_id.set(id);
+        Ok(JsValue::Number(id as f64))
+    })?;
+
+    runtime.register_function("__rustkit_audio_command", 1, move |args| {
+        let command: ScriptCommand = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .ok_or_else(|| JsError::TypeError("Invalid media command".into()))?;
+        state.commands.borrow_mut().push(AudioCommand {
+            media_id: command.media_id,
+            node_id: command.node_id.map(NodeId::new),
+            op: command.op,
+        });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.register_function("__rustkit_audio_activated", 0, move |_| {
+        Ok(JsValue::Boolean(activation.is_active(Instant::now())))
+    })?;
+
+    runtime.evaluate_script(AUDIO_JS)?;
+    Ok(())
+}
+
+/// Script reporting `update` to media element `media_id`.
+pub(crate) fn update_script(media_id: u64, update: &AudioUpdate) -> String {
+    format!(
+        "__rustkit_mediaSettle({}, {});",
+        media_id,
+        serde_json::to_string(update).unwrap_or_else(|_| "{}".into())
+    )
+}
+
+/// Script applying a click on the media controls of element `node_id`:
+/// toggle playback, or seek to `fraction` of the duration.
+pub(crate) fn control_script(node_id: NodeId, seek: Option<f64>) -> String {
+    match seek {
+        Some(fraction) => format!(
+            "__rustkit_mediaControl({}, 'seek', {});",
+            node_id.raw(),
+            fraction
+        ),
+        None => format!("__rustkit_mediaControl({}, 'toggle', 0);", node_id.raw()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_play_needs_activation_unless_muted() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 var a = new Audio('https://example.com/ding.wav'); \
+                 a.play().catch(function(e) { out.push(e.name, a.paused); }); \
+                 var quiet = new Audio('https://example.com/quiet.wav'); \
+                 quiet.muted = true; \
+                 quiet.play().then(function() { out.push('muted'); });",
+            )
+            .unwrap();
+
+        let commands = bindings.take_audio_commands();
+        let ops: Vec<_> = commands
+            .iter()
+            .map(|c| (c.media_id, c.op.clone()))
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                (
+                    1,
+                    AudioOp::Load {
+                        url: "https://example.com/ding.wav".into()
+                    }
+                ),
+                (
+                    2,
+                    AudioOp::Load {
+                        url: "https://example.com/quiet.wav".into()
+                    }
+                ),
+                (
+                    2,
+                    AudioOp::Volume {
+                        volume: 1.0,
+                        muted: true
+                    }
+                ),
+                (2, AudioOp::Play),
+            ]
+        );
+        bindings.update_audio(2, &AudioUpdate::Playing).unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "NotAllowedError|true|muted"
+        );
+    }
+
+    #[test]
+    fn test_updates_fire_media_events() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.notify_user_activation();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 var a = document.createElement('audio'); \
+                 ['play', 'playing', 'timeupdate', 'pause', 'ended'].forEach(function(type) { \
+                     a.addEventListener(type, function() { out.push(type); }); \
+                 }); \
+                 a.onloadedmetadata = function() { out.push('duration=' + a.duration); }; \
+                 a.src = 'clip.wav'; \
+                 a.play().then(function() { out.push('resolved'); });",
+            )
+            .unwrap();
+        let commands = bindings.take_audio_commands();
+        assert_eq!(commands.last().unwrap().op, AudioOp::Play);
+        assert!(matches!(&commands[0].op, AudioOp::Load { url } if url.ends_with("clip.wav")));
+
+        for update in [
+            AudioUpdate::Metadata { duration: 0.25 },
+            AudioUpdate::Playing,
+            AudioUpdate::Time { time: 0.1 },
+            AudioUpdate::Ended,
+        ] {
+            bindings.update_audio(1, &update).unwrap();
+        }
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "play|duration=0.25|playing|resolved|timeupdate|timeupdate|pause|ended"
+        );
+        assert!(matches!(
+            bindings.evaluate("a.ended && a.paused && a.currentTime === 0.25"),
+            Ok(JsValue::Boolean(true))
+        ));
+    }
+}
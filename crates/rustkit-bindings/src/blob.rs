@@ -0,0 +1,296 @@
+//! `Blob`, `File`, `URL.createObjectURL` and `<input type="file">` files.
+//!
+//! Blob bytes live on the JS side in a `Uint8Array`. Object URLs are minted
+//! by the host's [`ObjectUrlRegistry`], which serves the bytes to fetches,
+//! image loads and downloads; files picked for a file input arrive through
+//! [`crate::DomBindings::set_input_files`].
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Host side of `URL.createObjectURL` and `URL.revokeObjectURL`.
+pub trait ObjectUrlRegistry {
+    /// Register a blob's bytes and MIME type, returning its `blob:` URL.
+    fn create(&self, bytes: Vec<u8>, content_type: &str) -> Option<String>;
+    /// Forget a URL returned by [`Self::create`].
+    fn revoke(&self, url: &str);
+}
+
+/// A file picked for an `<input type="file">`.
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    pub name: String,
+    /// MIME type; empty if unknown.
+    pub content_type: String,
+    /// Milliseconds since the Unix epoch.
+    pub last_modified: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Object URL state of one document.
+#[derive(Default)]
+pub(crate) struct BlobState {
+    registry: RefCell<Option<Box<dyn ObjectUrlRegistry>>>,
+    /// Numbers URLs minted without a registry, which resolve to nothing.
+    unregistered: Cell<u64>,
+}
+
+impl BlobState {
+    pub(crate) fn set_registry(&self, registry: Box<dyn ObjectUrlRegistry>) {
+        *self.registry.borrow_mut() = Some(registry);
+    }
+}
+
+const BLOB_JS: &str = r#"
+    function __rustkit_blobPartBytes(part) {
+        if (part instanceof Blob) return part._bytes;
+        if (part instanceof ArrayBuffer) return new Uint8Array(part);
+        if (ArrayBuffer.isView(part)) {
+            return new Uint8Array(part.buffer, part.byteOffset, part.byteLength);
+        }
+        return new TextEncoder().encode(String(part));
+    }
+
+    // Types with characters outside U+0020-U+007E are dropped
+    function __rustkit_blobType(type) {
+        type = type === undefined ? '' : String(type);
+        return /^[\x20-\x7E]*$/.test(type) ? type.toLowerCase() : '';
+    }
+
+    function Blob(parts, options) {
+        if (parts !== undefined && (parts === null || typeof parts !== 'object')) {
+            throw new TypeError("Failed to construct 'Blob': The provided value cannot be converted to a sequence.");
+        }
+        var chunks = Array.prototype.map.call(parts || [], __rustkit_blobPartBytes);
+        var size = chunks.reduce(function(total, chunk) { return total + chunk.length; }, 0);
+        var bytes = new Uint8Array(size);
+        var offset = 0;
+        chunks.forEach(function(chunk) {
+            bytes.set(chunk, offset);
+            offset += chunk.length;
+        });
+        this._bytes = bytes;
+        this._type = __rustkit_blobType(options && options.type);
+    }
+    Object.defineProperty(Blob.prototype, 'size', { get: function() { return this._bytes.length; } });
+    Object.defineProperty(Blob.prototype, 'type', { get: function() { return this._type; } });
+    Blob.prototype.slice = function(start, end, contentType) {
+        var size = this._bytes.length;
+        var clamp = function(index, fallback) {
+            if (index === undefined) return fallback;
+            index = Math.trunc(Number(index)) || 0;
+            return index < 0 ? Math.max(size + index, 0) : Math.min(index, size);
+        };
+        var from = clamp(start, 0), to = clamp(end, size);
+        var blob = new Blob([], { type: contentType });
+        blob._bytes = this._bytes.slice(from, Math.max(from, to));
+        return blob;
+    };
+    Blob.prototype.arrayBuffer = function() {
+        return Promise.resolve(this._bytes.slice().buffer);
+    };
+    Blob.prototype.text = function() {
+        return Promise.resolve(new TextDecoder().decode(this._bytes));
+    };
+
+    function File(bits, name, options) {
+        if (arguments.length < 2) {
+            throw new TypeError("Failed to construct 'File': 2 arguments required.");
+        }
+        Blob.call(this, bits, options);
+        this._name = String(name);
+        var modified = options && options.lastModified;
+        this._lastModified = modified === undefined ? Date.now() : Math.trunc(Number(modified)) || 0;
+    }
+    File.prototype = Object.create(Blob.prototype);
+    File.prototype.constructor = File;
+    Object.defineProperty(File.prototype, 'name', { get: function() { return this._name; } });
+    Object.defineProperty(File.prototype, 'lastModified', { get: function() { return this._lastModified; } });
+
+    URL.createObjectURL = function(blob) {
+        if (!(blob instanceof Blob)) {
+            throw new TypeError("Failed to execute 'createObjectURL' on 'URL': parameter 1 is not of type 'Blob'.");
+        }
+        return __rustkit_blob_create_url(JSON.stringify(Array.from(blob._bytes)), blob.type);
+    };
+    URL.revokeObjectURL = function(url) {
+        __rustkit_blob_revoke_url(String(url));
+    };
+
+    // A FileList of files sent by the host
+    function __rustkit_fileList(files) {
+        var list = files.map(function(f) {
+            return new File([new Uint8Array(f.bytes)], f.name,
+                { type: f.type, lastModified: f.lastModified });
+        });
+        list.item = function(index) { return this[index] || null; };
+        return list;
+    }
+
+    function __rustkit_setInputFiles(nodeId, files) {
+        var elem = document._nodes && document._nodes[nodeId];
+        if (!elem) return;
+        var list = __rustkit_fileList(files);
+        elem.files = list;
+        elem.value = list.length ? 'C:\\fakepath\\' + list[0].name : '';
+    }
+
+    window.Blob = Blob;
+    window.File = File;
+"#;
+
+/// Register the object URL natives and install `Blob` and `File`.
+pub(crate) fn install(runtime: &mut JsRuntime, blobs: Rc<BlobState>) -> Result<(), JsError> {
+    let state = blobs.clone();
+    runtime.register_function("__rustkit_blob_create_url", 2, move |args| {
+        let bytes: Vec<u8> = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_default();
+        let content_type = args.get(1).map(String::as_str).unwrap_or("");
+        let url = match state.registry.borrow().as_ref() {
+            Some(registry) => registry.create(bytes, content_type),
+            None => {
+                let n = state.unregistered.get() + 1;
+                state.unregistered.set(n);
+                Some(format!("blob:null/{}", n))
+            }
+        };
+        url.map(JsValue::String)
+            .ok_or_else(|| JsError::TypeError("Could not create an object URL".into()))
+    })?;
+
+    runtime.register_function("__rustkit_blob_revoke_url", 1, move |args| {
+        if let (Some(registry), Some(url)) = (blobs.registry.borrow().as_ref(), args.first()) {
+            registry.revoke(url);
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(BLOB_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+    use rustkit_dom::Document;
+    use std::collections::HashMap;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    /// Registered bytes and type by URL.
+    type Entries = Rc<RefCell<HashMap<String, (Vec<u8>, String)>>>;
+
+    #[derive(Default)]
+    struct Registry(Entries);
+
+    impl ObjectUrlRegistry for Registry {
+        fn create(&self, bytes: Vec<u8>, content_type: &str) -> Option<String> {
+            let url = format!("blob:https://example.com/{}", self.0.borrow().len());
+            self.0
+                .borrow_mut()
+                .insert(url.clone(), (bytes, content_type.to_string()));
+            Some(url)
+        }
+
+        fn revoke(&self, url: &str) {
+            self.0.borrow_mut().remove(url);
+        }
+    }
+
+    #[test]
+    fn test_blob_parts_slice_and_text() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let result = eval_string(
+            &bindings,
+            "var blob = new Blob(['héllo', new Uint8Array([32]), new Blob(['world'])],
+                                 { type: 'Text/Plain' });
+             var slice = blob.slice(-5, undefined, 'text/x-slice');
+             var out = [blob.size, blob.type, slice.size, slice.type];
+             slice.text().then(function(text) { out.push(text); });
+             blob.arrayBuffer().then(function(buffer) { out.push(buffer.byteLength); });
+             var file = new File(['abc'], 'a.txt', { lastModified: 42 });
+             out.push(file instanceof Blob, file.name, file.lastModified, file.size);
+             new Blob([], { type: 'bad\\u00ff' }).type === '' && out.push('dropped');
+             out.join('|')",
+        );
+        assert_eq!(
+            result,
+            "12|text/plain|5|text/x-slice|true|a.txt|42|3|dropped"
+        );
+        // The promise reactions ran once the script finished
+        assert_eq!(eval_string(&bindings, "out.slice(9).join('|')"), "world|12");
+    }
+
+    #[test]
+    fn test_input_files_fire_change() {
+        let document = Rc::new(
+            Document::parse_html(r#"<html><body><input type="file" id="f"></body></html>"#)
+                .unwrap(),
+        );
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_document(document.clone()).unwrap();
+        let input = document.get_element_by_id("f").unwrap().id;
+        bindings.evaluate("var seen = [];").unwrap();
+        for event in ["input", "change"] {
+            bindings.add_event_listener(input, event, "seen.push(e.type);", false);
+        }
+
+        bindings
+            .set_input_files(
+                input,
+                &[InputFile {
+                    name: "notes.txt".into(),
+                    content_type: "text/plain".into(),
+                    last_modified: 1_700_000_000_000,
+                    bytes: b"notes".to_vec(),
+                }],
+            )
+            .unwrap();
+        let result = eval_string(
+            &bindings,
+            "var f = document.getElementById('f');
+             var file = f.files.item(0);
+             [seen.join(','), f.files.length, file.name, file.type, file.size,
+              file.lastModified, file instanceof File, f.value].join('|')",
+        );
+        assert_eq!(
+            result,
+            "input,change|1|notes.txt|text/plain|5|1700000000000|true|C:\\fakepath\\notes.txt"
+        );
+    }
+
+    #[test]
+    fn test_object_urls_use_registry() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let registry = Registry::default();
+        let entries = registry.0.clone();
+        bindings.set_object_url_registry(registry);
+
+        let url = eval_string(
+            &bindings,
+            "var url = URL.createObjectURL(new Blob(['hi'], { type: 'text/plain' })); url",
+        );
+        assert_eq!(
+            entries.borrow().get(&url),
+            Some(&(b"hi".to_vec(), "text/plain".to_string()))
+        );
+        eval_string(&bindings, "URL.revokeObjectURL(url); ''");
+        assert!(entries.borrow().is_empty());
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "var error; try { URL.createObjectURL('x'); } catch (e) { error = e.name; } error"
+            ),
+            "TypeError"
+        );
+    }
+}
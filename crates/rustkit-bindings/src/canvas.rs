@@ -0,0 +1,671 @@
+//! `<canvas>` element bindings.
+//!
+//! `document.createElement('canvas')` (and parsed canvases) get `width`,
+//! `height`, `getContext('2d')`, `toDataURL()` and `toBlob()`. Drawing is
+//! forwarded to a [`CanvasRenderingContext2D`] owned on the Rust side, which
+//! also rasterizes and encodes the bitmap for export.
+//!
+//! `OffscreenCanvas` shares the same contexts without a DOM node. Its frames
+//! move to on-screen canvases as [`ImageBitmap`] handles, which script can
+//! also create from images, canvases and `ImageData` with
+//! `createImageBitmap()`. Decoded `<img>` pixels are supplied by the embedder
+//! through [`crate::DomBindings::register_image_source`].
+
+use rustkit_canvas::{CanvasRenderingContext2D, ExportFormat, ImageBitmap, ImageData};
+use rustkit_css::FillRule;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Largest backing store (in pixels) a canvas may allocate.
+const MAX_CANVAS_AREA: u64 = 4096 * 4096;
+
+/// Rust-side canvas contexts and bitmaps, keyed by the id handed to script.
+#[derive(Default)]
+pub(crate) struct Canvases {
+    contexts: RefCell<HashMap<u32, CanvasRenderingContext2D>>,
+    bitmaps: RefCell<HashMap<u32, ImageBitmap>>,
+    /// Decoded images by absolute URL, for `createImageBitmap(img)`.
+    image_sources: RefCell<HashMap<String, ImageBitmap>>,
+    next_id: Cell<u32>,
+}
+
+impl Canvases {
+    fn next_id(&self) -> u32 {
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        id
+    }
+
+    fn create(&self, width: u32, height: u32) -> u32 {
+        let id = self.next_id();
+        self.contexts
+            .borrow_mut()
+            .insert(id, new_context(width, height));
+        id
+    }
+
+    /// Hand `bitmap` to script; returns its id and size as JSON.
+    fn add_bitmap(&self, bitmap: ImageBitmap) -> JsValue {
+        let id = self.next_id();
+        let result = json!({ "id": id, "width": bitmap.width(), "height": bitmap.height() });
+        self.bitmaps.borrow_mut().insert(id, bitmap);
+        JsValue::String(result.to_string())
+    }
+
+    fn bitmap(&self, args: &[String], index: usize) -> Result<ImageBitmap, JsError> {
+        args.get(index)
+            .and_then(|id| id.parse().ok())
+            .and_then(|id| self.bitmaps.borrow().get(&id).cloned())
+            .ok_or_else(|| JsError::TypeError("The ImageBitmap is detached".into()))
+    }
+
+    /// Make a decoded image available to `createImageBitmap`.
+    pub(crate) fn register_image_source(&self, url: &str, bitmap: ImageBitmap) {
+        self.image_sources
+            .borrow_mut()
+            .insert(url.to_string(), bitmap);
+    }
+
+    fn with<R>(
+        &self,
+        args: &[String],
+        f: impl FnOnce(&mut CanvasRenderingContext2D) -> R,
+    ) -> Result<R, JsError> {
+        let id = args
+            .first()
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| JsError::TypeError("Expected a canvas id".into()))?;
+        let mut contexts = self.contexts.borrow_mut();
+        let context = contexts
+            .get_mut(&id)
+            .ok_or_else(|| JsError::TypeError("Unknown canvas".into()))?;
+        Ok(f(context))
+    }
+}
+
+/// A context whose backing store is dropped when it would be too large.
+fn new_context(width: u32, height: u32) -> CanvasRenderingContext2D {
+    if width as u64 * height as u64 > MAX_CANVAS_AREA {
+        return CanvasRenderingContext2D::new(0, 0);
+    }
+    CanvasRenderingContext2D::new(width, height)
+}
+
+fn dimension_arg(args: &[String], index: usize) -> u32 {
+    args.get(index)
+        .and_then(|n| n.parse::<f64>().ok())
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .map_or(0, |n| n as u32)
+}
+
+/// `toDataURL`/`toBlob` arguments: the MIME type and optional JPEG quality.
+fn export_args(args: &[String]) -> (Option<&str>, Option<f32>) {
+    let mime = args.get(1).map(String::as_str);
+    let quality = args.get(2).and_then(|q| q.parse::<f32>().ok());
+    (mime, quality)
+}
+
+/// Apply one forwarded context call; returns JSON for methods with results.
+fn dispatch(
+    ctx: &mut CanvasRenderingContext2D,
+    method: &str,
+    args: &[Value],
+) -> Result<Option<String>, JsError> {
+    let num = |i: usize| args.get(i).and_then(Value::as_f64).unwrap_or(0.0) as f32;
+    let int = |i: usize| args.get(i).and_then(Value::as_f64).unwrap_or(0.0) as i32;
+    let text = |i: usize| args.get(i).and_then(Value::as_str).unwrap_or("");
+
+    match method {
+        "fillStyle" => ctx.set_fill_style_color(text(0)),
+        "strokeStyle" => ctx.set_stroke_style_color(text(0)),
+        "lineWidth" => ctx.set_line_width(num(0)),
+        "globalAlpha" => ctx.set_global_alpha(num(0)),
+        "font" => ctx.set_font(text(0)),
+        "save" => ctx.save(),
+        "restore" => ctx.restore(),
+        "reset" => ctx.reset(),
+        "translate" => ctx.translate(num(0), num(1)),
+        "scale" => ctx.scale(num(0), num(1)),
+        "rotate" => ctx.rotate(num(0)),
+        "transform" => ctx.transform(num(0), num(1), num(2), num(3), num(4), num(5)),
+        "setTransform" => ctx.set_transform(num(0), num(1), num(2), num(3), num(4), num(5)),
+        "resetTransform" => ctx.reset_transform(),
+        "beginPath" => ctx.begin_path(),
+        "closePath" => ctx.close_path(),
+        "moveTo" => ctx.move_to(num(0), num(1)),
+        "lineTo" => ctx.line_to(num(0), num(1)),
+        "quadraticCurveTo" => ctx.quadratic_curve_to(num(0), num(1), num(2), num(3)),
+        "bezierCurveTo" => ctx.bezier_curve_to(num(0), num(1), num(2), num(3), num(4), num(5)),
+        "arc" => ctx.arc(
+            num(0),
+            num(1),
+            num(2),
+            num(3),
+            num(4),
+            args.get(5).and_then(Value::as_bool).unwrap_or(false),
+        ),
+        "rect" => ctx.rect(num(0), num(1), num(2), num(3)),
+        "fill" => ctx.fill_with_rule(FillRule::parse(text(0)).unwrap_or_default()),
+        "stroke" => ctx.stroke(),
+        "fillRect" => ctx.fill_rect(num(0), num(1), num(2), num(3)),
+        "strokeRect" => ctx.stroke_rect(num(0), num(1), num(2), num(3)),
+        "clearRect" => ctx.clear_rect(num(0), num(1), num(2), num(3)),
+        "fillText" => ctx.fill_text(text(0), num(1), num(2)),
+        "strokeText" => ctx.stroke_text(text(0), num(1), num(2)),
+        "measureText" => {
+            return Ok(Some(
+                json!({ "width": ctx.measure_text(text(0)).width }).to_string(),
+            ));
+        }
+        "getImageData" => {
+            let data = ctx.get_image_data(int(0), int(1), num(2) as u32, num(3) as u32);
+            return Ok(Some(
+                json!({ "width": data.width, "height": data.height, "data": data.data })
+                    .to_string(),
+            ));
+        }
+        "putImageData" => {
+            let (width, height) = (num(1) as u32, num(2) as u32);
+            let bytes = args
+                .first()
+                .and_then(Value::as_array)
+                .map(|a| a.iter().map(|v| v.as_u64().unwrap_or(0) as u8).collect())
+                .unwrap_or_default();
+            let data = ImageData::from_data(width, height, bytes)
+                .map_err(|e| JsError::TypeError(e.to_string()))?;
+            ctx.put_image_data(data, int(3), int(4));
+        }
+        _ => {
+            return Err(JsError::TypeError(format!(
+                "Unsupported canvas method: {}",
+                method
+            )))
+        }
+    }
+    Ok(None)
+}
+
+/// JS side: the canvas element API and a 2D context that forwards to Rust.
+const CANVAS_JS: &str = r#"
+    function CanvasRenderingContext2D(canvas, handle) {
+        this.canvas = canvas;
+        this._handle = handle;
+        this._props = {
+            fillStyle: '#000000',
+            strokeStyle: '#000000',
+            lineWidth: 1,
+            globalAlpha: 1,
+            font: '10px sans-serif'
+        };
+    }
+    CanvasRenderingContext2D.prototype._call = function(method, args) {
+        var result = __rustkit_canvas_call(this._handle(), method,
+            JSON.stringify(Array.prototype.slice.call(args || [])));
+        return result === undefined ? undefined : JSON.parse(result);
+    };
+    ['fillStyle', 'strokeStyle', 'lineWidth', 'globalAlpha', 'font'].forEach(function(name) {
+        Object.defineProperty(CanvasRenderingContext2D.prototype, name, {
+            get: function() { return this._props[name]; },
+            set: function(value) {
+                // Gradients and patterns are not forwarded yet
+                if (typeof value === 'object') return;
+                this._props[name] = value;
+                this._call(name, [value]);
+            }
+        });
+    });
+    ['save', 'restore', 'reset', 'translate', 'scale', 'rotate', 'transform',
+     'setTransform', 'resetTransform', 'beginPath', 'closePath', 'moveTo', 'lineTo',
+     'quadraticCurveTo', 'bezierCurveTo', 'arc', 'rect', 'fill', 'stroke',
+     'fillRect', 'strokeRect', 'clearRect', 'fillText', 'strokeText'
+    ].forEach(function(name) {
+        CanvasRenderingContext2D.prototype[name] = function() {
+            this._call(name, arguments);
+        };
+    });
+    CanvasRenderingContext2D.prototype.measureText = function(text) {
+        return this._call('measureText', [String(text)]);
+    };
+    CanvasRenderingContext2D.prototype.createImageData = function(width, height) {
+        return {
+            width: width,
+            height: height,
+            data: new Uint8ClampedArray(width * height * 4)
+        };
+    };
+    CanvasRenderingContext2D.prototype.getImageData = function(x, y, width, height) {
+        var image = this._call('getImageData', [x, y, width, height]);
+        image.data = new Uint8ClampedArray(image.data);
+        return image;
+    };
+    CanvasRenderingContext2D.prototype.putImageData = function(image, x, y) {
+        this._call('putImageData',
+            [Array.from(image.data), image.width, image.height, x, y]);
+    };
+    CanvasRenderingContext2D.prototype.drawImage = function(image) {
+        if (!(image instanceof ImageBitmap)) {
+            throw new TypeError('drawImage only accepts ImageBitmap sources');
+        }
+        __rustkit_canvas_draw_image(this._handle(), image._id(),
+            JSON.stringify(Array.prototype.slice.call(arguments, 1)));
+    };
+
+    function ImageBitmap(info) {
+        var id = info.id;
+        var width = info.width;
+        var height = info.height;
+        this._id = function() {
+            if (id === null) throw new TypeError('The ImageBitmap is detached');
+            return id;
+        };
+        this._detach = function() {
+            id = null;
+            width = height = 0;
+        };
+        Object.defineProperty(this, 'width', { get: function() { return width; } });
+        Object.defineProperty(this, 'height', { get: function() { return height; } });
+    }
+    ImageBitmap.prototype.close = function() {
+        try { __rustkit_bitmap_close(this._id()); } catch (e) {}
+        this._detach();
+    };
+    window.ImageBitmap = ImageBitmap;
+
+    function __rustkit_transferFromImageBitmap(handle, bitmap) {
+        if (!(bitmap instanceof ImageBitmap)) {
+            throw new TypeError('Expected an ImageBitmap');
+        }
+        __rustkit_canvas_transfer_from_bitmap(handle(), bitmap._id());
+        bitmap._detach();
+    }
+
+    function OffscreenCanvas(width, height) {
+        var self = this;
+        var size = {
+            width: Math.max(0, Math.floor(Number(width)) || 0),
+            height: Math.max(0, Math.floor(Number(height)) || 0)
+        };
+        var id = __rustkit_canvas_create(size.width, size.height);
+        var context = null;
+        function handle() { return id; }
+        ['width', 'height'].forEach(function(name) {
+            Object.defineProperty(self, name, {
+                get: function() { return size[name]; },
+                set: function(value) {
+                    size[name] = Math.max(0, Math.floor(Number(value)) || 0);
+                    __rustkit_canvas_resize(id, size.width, size.height);
+                    if (context) context._props = new CanvasRenderingContext2D()._props;
+                }
+            });
+        });
+        this._handle = handle;
+        this.getContext = function(kind) {
+            if (kind !== '2d') return null;
+            if (!context) context = new CanvasRenderingContext2D(self, handle);
+            return context;
+        };
+        this.transferToImageBitmap = function() {
+            return new ImageBitmap(JSON.parse(__rustkit_canvas_transfer_to_bitmap(id)));
+        };
+    }
+    window.OffscreenCanvas = OffscreenCanvas;
+
+    function createImageBitmap(source) {
+        return new Promise(function(resolve, reject) {
+            // The source is captured now; the bitmap resolves as a microtask
+            var info;
+            if (source instanceof ImageBitmap) {
+                info = __rustkit_bitmap_clone(source._id());
+            } else if (source && source._handle) {
+                info = __rustkit_bitmap_from_canvas(source._handle());
+            } else if (source && source.data && source.width !== undefined) {
+                info = __rustkit_bitmap_from_image_data(
+                    JSON.stringify(Array.from(source.data)), source.width, source.height);
+            } else if (source && String(source.tagName).toLowerCase() === 'img') {
+                var src = source.src || (source.attributes && source.attributes.src) || '';
+                try { src = new URL(src, document.URL).href; } catch (e) {}
+                info = __rustkit_bitmap_from_image(src);
+            } else {
+                throw new TypeError('Unsupported createImageBitmap source');
+            }
+            if (info === undefined) {
+                var error = new Error('The source image could not be decoded');
+                error.name = 'InvalidStateError';
+                throw error;
+            }
+            resolve(new ImageBitmap(JSON.parse(info)));
+        });
+    }
+    window.createImageBitmap = createImageBitmap;
+
+    function __rustkit_defineCanvas(elem) {
+        var size = {};
+        var id = null;
+        var context = null;
+        function dimension(name, fallback) {
+            if (size[name] !== undefined) return size[name];
+            var attr = elem.attributes ? parseInt(elem.attributes[name], 10) : NaN;
+            return attr >= 0 ? attr : fallback;
+        }
+        function handle() {
+            if (id === null) id = __rustkit_canvas_create(elem.width, elem.height);
+            return id;
+        }
+        ['width', 'height'].forEach(function(name) {
+            Object.defineProperty(elem, name, {
+                get: function() { return dimension(name, name === 'width' ? 300 : 150); },
+                set: function(value) {
+                    // Resizing clears the bitmap and context state
+                    size[name] = Math.max(0, Math.floor(Number(value)) || 0);
+                    if (id !== null) __rustkit_canvas_resize(id, elem.width, elem.height);
+                    if (context) context._props = new CanvasRenderingContext2D()._props;
+                }
+            });
+        });
+        elem._handle = handle;
+        elem.getContext = function(kind) {
+            if (kind === 'bitmaprenderer') {
+                return {
+                    canvas: elem,
+                    transferFromImageBitmap: function(bitmap) {
+                        __rustkit_transferFromImageBitmap(handle, bitmap);
+                    }
+                };
+            }
+            if (kind !== '2d') return null;
+            if (!context) context = new CanvasRenderingContext2D(elem, handle);
+            return context;
+        };
+        elem.transferFromImageBitmap = function(bitmap) {
+            __rustkit_transferFromImageBitmap(handle, bitmap);
+        };
+        elem.toDataURL = function(type, quality) {
+            return __rustkit_canvas_to_data_url(handle(), String(type), quality);
+        };
+        elem.toBlob = function(callback, type, quality) {
+            if (typeof callback !== 'function') {
+                throw new TypeError('toBlob requires a callback');
+            }
+            // The bitmap is captured now; the callback runs as a microtask
+            var result = JSON.parse(__rustkit_canvas_to_blob(handle(), String(type), quality));
+            var blob = result && new Blob([new Uint8Array(result.bytes)], { type: result.type });
+            Promise.resolve().then(function() { callback(blob); });
+        };
+        return elem;
+    }
+
+    var __rustkit_canvasCreateElement = document.createElement;
+    document.createElement = function(tagName) {
+        var elem = __rustkit_canvasCreateElement.call(document, tagName);
+        return String(tagName).toLowerCase() === 'canvas' ? __rustkit_defineCanvas(elem) : elem;
+    };
+"#;
+
+/// Register the canvas natives and the `<canvas>` element API.
+pub(crate) fn install(runtime: &mut JsRuntime, canvases: Rc<Canvases>) -> Result<(), JsError> {
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_create", 2, move |args| {
+        let id = state.create(dimension_arg(args, 0), dimension_arg(args, 1));
+        Ok(JsValue::Number(id as f64))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_resize", 3, move |args| {
+        let (width, height) = (dimension_arg(args, 1), dimension_arg(args, 2));
+        state.with(args, |ctx| *ctx = new_context(width, height))?;
+        Ok(JsValue::Undefined)
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_call", 3, move |args| {
+        let method = args.get(1).map(String::as_str).unwrap_or("");
+        let call_args: Vec<Value> = args
+            .get(2)
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_default();
+        let result = state.with(args, |ctx| dispatch(ctx, method, &call_args))??;
+        Ok(result.map_or(JsValue::Undefined, JsValue::String))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_draw_image", 3, move |args| {
+        let bitmap = state.bitmap(args, 1)?;
+        let num: Vec<f32> = args
+            .get(2)
+            .and_then(|a| serde_json::from_str::<Vec<Value>>(a).ok())
+            .unwrap_or_default()
+            .iter()
+            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+            .collect();
+        let source = format!("bitmap:{}", args[1]);
+        state.with(args, |ctx| {
+            ctx.register_image_source(&source, bitmap);
+            match num[..] {
+                [dx, dy] => ctx.draw_image(&source, dx, dy),
+                [dx, dy, dw, dh] => ctx.draw_image_sized(&source, dx, dy, dw, dh),
+                [sx, sy, sw, sh, dx, dy, dw, dh] => {
+                    ctx.draw_image_full(&source, sx, sy, sw, sh, dx, dy, dw, dh)
+                }
+                _ => {
+                    return Err(JsError::TypeError(
+                        "drawImage expects 2, 4 or 8 coordinates".into(),
+                    ))
+                }
+            }
+            Ok(())
+        })??;
+        Ok(JsValue::Undefined)
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_transfer_to_bitmap", 1, move |args| {
+        let bitmap = state.with(args, |ctx| ctx.transfer_to_image_bitmap())?;
+        Ok(state.add_bitmap(bitmap))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_transfer_from_bitmap", 2, move |args| {
+        let bitmap = state.bitmap(args, 1)?;
+        state.with(args, |ctx| ctx.transfer_from_image_bitmap(bitmap))?;
+        if let Some(id) = args.get(1).and_then(|id| id.parse().ok()) {
+            state.bitmaps.borrow_mut().remove(&id);
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_bitmap_close", 1, move |args| {
+        if let Some(id) = args.first().and_then(|id| id.parse().ok()) {
+            state.bitmaps.borrow_mut().remove(&id);
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_bitmap_clone", 1, move |args| {
+        let bitmap = state.bitmap(args, 0)?;
+        Ok(state.add_bitmap(bitmap))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_bitmap_from_canvas", 1, move |args| {
+        let pixels = state.with(args, |ctx| ctx.snapshot())?;
+        Ok(state.add_bitmap(ImageBitmap::from_image_data(pixels)))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_bitmap_from_image_data", 3, move |args| {
+        let bytes: Vec<u8> = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_default();
+        let data = ImageData::from_data(dimension_arg(args, 1), dimension_arg(args, 2), bytes)
+            .map_err(|e| JsError::TypeError(e.to_string()))?;
+        Ok(state.add_bitmap(ImageBitmap::from_image_data(data)))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_bitmap_from_image", 1, move |args| {
+        let url = args.first().map(String::as_str).unwrap_or("");
+        let bitmap = state.image_sources.borrow().get(url).cloned();
+        Ok(bitmap.map_or(JsValue::Undefined, |bitmap| state.add_bitmap(bitmap)))
+    })?;
+
+    let state = canvases.clone();
+    runtime.register_function("__rustkit_canvas_to_data_url", 3, move |args| {
+        let (mime, quality) = export_args(args);
+        state
+            .with(args, |ctx| ctx.to_data_url(mime, quality))?
+            .map(JsValue::String)
+            .map_err(|e| JsError::ExecutionError(e.to_string()))
+    })?;
+
+    runtime.register_function("__rustkit_canvas_to_blob", 3, move |args| {
+        let (mime, quality) = export_args(args);
+        let blob = canvases.with(args, |ctx| {
+            if ctx.width == 0 || ctx.height == 0 {
+                return Ok(Value::Null);
+            }
+            let format = mime
+                .and_then(ExportFormat::from_mime)
+                .unwrap_or(ExportFormat::Png);
+            ctx.encode(format, quality)
+                .map(|bytes| json!({ "type": format.mime(), "bytes": bytes }))
+        })?;
+        blob.map(|b| JsValue::String(b.to_string()))
+            .map_err(|e| JsError::ExecutionError(e.to_string()))
+    })?;
+
+    runtime.evaluate_script(CANVAS_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+
+    fn bindings() -> DomBindings {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var canvas = document.createElement('canvas');
+                 canvas.width = 4; canvas.height = 2;
+                 var ctx = canvas.getContext('2d');",
+            )
+            .unwrap();
+        bindings
+    }
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_to_data_url() {
+        let bindings = bindings();
+        bindings
+            .evaluate("ctx.fillStyle = 'red'; ctx.fillRect(0, 0, 2, 2);")
+            .unwrap();
+
+        let png = eval_string(&bindings, "canvas.toDataURL()");
+        assert!(png.starts_with("data:image/png;base64,"));
+        let fallback = eval_string(&bindings, "canvas.toDataURL('image/bmp')");
+        assert_eq!(fallback, png);
+        let jpeg = eval_string(&bindings, "canvas.toDataURL('image/jpeg', 0.8)");
+        assert!(jpeg.starts_with("data:image/jpeg;base64,"));
+
+        bindings.evaluate("canvas.width = 0;").unwrap();
+        assert_eq!(eval_string(&bindings, "canvas.toDataURL()"), "data:,");
+    }
+
+    #[test]
+    fn test_image_data_round_trip() {
+        let bindings = bindings();
+        let pixel = eval_string(
+            &bindings,
+            "var image = ctx.createImageData(1, 1);
+             image.data[1] = 200; image.data[3] = 255;
+             ctx.putImageData(image, 3, 1);
+             Array.from(ctx.getImageData(3, 1, 1, 1).data).join(',')",
+        );
+        assert_eq!(pixel, "0,200,0,255");
+    }
+
+    #[test]
+    fn test_to_blob_is_async() {
+        let bindings = bindings();
+        bindings
+            .evaluate(
+                "var seen = [];
+                 canvas.toBlob(function(blob) { seen.push(blob.type + ':' + (blob.size > 0)); });
+                 seen.push('sync');",
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "seen.join(',')"),
+            "sync,image/png:true"
+        );
+    }
+
+    #[test]
+    fn test_offscreen_transfer_matches_direct_drawing() {
+        let bindings = bindings();
+        let same = eval_string(
+            &bindings,
+            "function scene(c) {
+                 c.fillStyle = '#3366cc'; c.fillRect(0, 0, 3, 2);
+                 c.fillStyle = 'rgba(255, 0, 0, 0.5)'; c.fillRect(1, 0, 2, 1);
+             }
+             scene(ctx);
+             var direct = Array.from(ctx.getImageData(0, 0, 4, 2).data).join(',');
+
+             var offscreen = new OffscreenCanvas(4, 2);
+             scene(offscreen.getContext('2d'));
+             var bitmap = offscreen.transferToImageBitmap();
+             var target = document.createElement('canvas');
+             target.width = 4; target.height = 2;
+             target.transferFromImageBitmap(bitmap);
+             var transferred = target.getContext('2d').getImageData(0, 0, 4, 2).data;
+             var cleared = offscreen.getContext('2d').getImageData(0, 0, 4, 2).data;
+             [Array.from(transferred).join(',') === direct,
+              bitmap.width,
+              Array.from(cleared).every(function(c) { return c === 0; })].join(',')",
+        );
+        assert_eq!(same, "true,0,true");
+    }
+
+    #[test]
+    fn test_create_image_bitmap_resolves_async() {
+        let bindings = bindings();
+        let url = url::Url::parse("https://example.com/sprite.png").unwrap();
+        let pixels = rustkit_canvas::ImageData::from_data(1, 1, vec![0, 0, 255, 255]).unwrap();
+        bindings.register_image_source(&url, crate::ImageBitmap::from_image_data(pixels));
+        bindings
+            .evaluate(
+                "var seen = [];
+                 var img = document.createElement('img');
+                 img.src = 'https://example.com/sprite.png';
+                 createImageBitmap(img).then(function(bitmap) {
+                     ctx.drawImage(bitmap, 0, 0, 4, 2);
+                     var px = ctx.getImageData(3, 1, 1, 1).data;
+                     seen.push(bitmap.width + 'x' + bitmap.height + ':' + Array.from(px).join(','));
+                 });
+                 createImageBitmap(document.createElement('img')).catch(function(e) {
+                     seen.push(e.name);
+                 });
+                 seen.push('sync');",
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "seen.join(' ')"),
+            "sync 1x1:0,0,255,255 InvalidStateError"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! `navigator.clipboard`, `ClipboardItem` and `document.execCommand('copy')`.
+//!
+//! Clipboard access is mediated by the host: every call queues a
+//! [`ClipboardRequest`] and returns a pending promise that settles when the
+//! engine calls [`crate::DomBindings::complete_clipboard`]. Writes record
+//! whether they consumed the window's transient user activation, which
+//! genuine input grants through [`crate::DomBindings::notify_user_activation`],
+//! so the host can let them through without asking.
+
+use crate::activation::UserActivation;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Data read from or written to the clipboard.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipboardData {
+    /// `text/plain`
+    pub text: Option<String>,
+    /// `text/html`
+    pub html: Option<String>,
+}
+
+/// What script asked to do with the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardOp {
+    /// `read()` or `readText()`.
+    Read,
+    /// `write()`, `writeText()` or `execCommand('copy')`.
+    Write(ClipboardData),
+}
+
+/// A clipboard call waiting for the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardRequest {
+    /// Identifies the call in [`crate::DomBindings::complete_clipboard`].
+    pub id: u64,
+    pub op: ClipboardOp,
+    /// The call consumed transient user activation.
+    pub user_activated: bool,
+}
+
+/// Why a clipboard call failed; script sees it as a `DOMException`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardError {
+    /// The user or host denied access: `NotAllowedError`.
+    NotAllowed,
+    /// The system clipboard could not be used: `DataError`.
+    Unavailable,
+}
+
+impl ClipboardError {
+    fn dom_exception(self) -> (&'static str, &'static str) {
+        match self {
+            ClipboardError::NotAllowed => ("NotAllowedError", "Clipboard access is not allowed"),
+            ClipboardError::Unavailable => ("DataError", "The clipboard is unavailable"),
+        }
+    }
+}
+
+/// Requests queued by script since the host last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct ClipboardState {
+    next_id: Cell<u64>,
+    requests: RefCell<Vec<ClipboardRequest>>,
+}
+
+impl ClipboardState {
+    pub(crate) fn take_requests(&self) -> Vec<ClipboardRequest> {
+        self.requests.take()
+    }
+
+    fn push(&self, op: ClipboardOp, user_activated: bool) -> u64 {
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        self.requests.borrow_mut().push(ClipboardRequest {
+            id,
+            op,
+            user_activated,
+        });
+        id
+    }
+}
+
+const CLIPBOARD_JS: &str = r#"
+    var __rustkit_clipboard = {};
+
+    function __rustkit_clipboardCall(start) {
+        return new Promise(function(resolve, reject) {
+            __rustkit_clipboard[start()] = { resolve: resolve, reject: reject };
+        });
+    }
+
+    function __rustkit_clipboardSettle(id, result) {
+        var pending = __rustkit_clipboard[id];
+        if (!pending) return;
+        delete __rustkit_clipboard[id];
+        if (result.error !== undefined) {
+            pending.reject(new DOMException(result.message, result.error));
+        } else {
+            pending.resolve(result);
+        }
+    }
+
+    function __rustkit_clipboardWrite(text, html) {
+        return __rustkit_clipboardCall(function() {
+            return __rustkit_clipboard_write(JSON.stringify({ text: text, html: html }));
+        }).then(function() {});
+    }
+
+    function ClipboardItem(items) {
+        this._items = items || {};
+        this.types = Object.keys(this._items);
+    }
+    ClipboardItem.prototype.getType = function(type) {
+        if (!(type in this._items)) {
+            return Promise.reject(new DOMException('No ' + type + ' data', 'NotFoundError'));
+        }
+        return Promise.resolve(this._items[type]).then(function(value) {
+            return typeof value === 'string' ? new Blob([value], { type: type }) : value;
+        });
+    };
+    ClipboardItem.supports = function(type) {
+        return type === 'text/plain' || type === 'text/html';
+    };
+
+    var navigator = window.navigator;
+    navigator.clipboard = {
+        writeText: function(text) {
+            return __rustkit_clipboardWrite(String(text), null);
+        },
+        readText: function() {
+            return __rustkit_clipboardCall(__rustkit_clipboard_read).then(function(data) {
+                return data.text || '';
+            });
+        },
+        write: function(items) {
+            var item = items && items[0];
+            if (!item) return Promise.reject(new TypeError('No ClipboardItem to write'));
+            var flavor = function(type) {
+                if (!(type in item._items)) return Promise.resolve(null);
+                return item.getType(type).then(function(blob) { return blob.text(); });
+            };
+            return Promise.all([flavor('text/plain'), flavor('text/html')]).then(function(data) {
+                return __rustkit_clipboardWrite(data[0], data[1]);
+            });
+        },
+        read: function() {
+            return __rustkit_clipboardCall(__rustkit_clipboard_read).then(function(data) {
+                var items = {};
+                if (data.text !== null) items['text/plain'] = data.text;
+                if (data.html !== null) items['text/html'] = data.html;
+                return [new ClipboardItem(items)];
+            });
+        }
+    };
+    window.ClipboardItem = ClipboardItem;
+
+    // Legacy copy: the focused text control's selection
+    document.execCommand = function(command) {
+        if (String(command).toLowerCase() !== 'copy') return false;
+        var el = document.activeElement;
+        if (!el || typeof el.value !== 'string') return false;
+        var text = el.value.substring(el.selectionStart, el.selectionEnd);
+        if (!text) return false;
+        __rustkit_clipboard_write(JSON.stringify({ text: text, html: null }));
+        return true;
+    };
+    document.queryCommandSupported = function(command) {
+        return String(command).toLowerCase() === 'copy';
+    };
+"#;
+
+/// Register the clipboard natives and install `navigator.clipboard`.
+///
+/// Needs `DOMException` and `Blob` installed first.
+///
+/// Writes consume `activation`.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<ClipboardState>,
+    activation: Rc<UserActivation>,
+) -> Result<(), JsError> {
+    let read_state = state.clone();
+    runtime.register_function("__rustkit_clipboard_read", 0, move |_| {
+        Ok(JsValue::Number(
+            read_state.push(ClipboardOp::Read, false) as f64
+        ))
+    })?;
+
+    runtime.register_function("__rustkit_clipboard_write", 1, move |args| {
+        let data: ClipboardData = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .ok_or_else(|| JsError::TypeError("Invalid clipboard data".into()))?;
+        let user_activated = activation.consume(Instant::now());
+        Ok(JsValue::Number(
+            state.push(ClipboardOp::Write(data), user_activated) as f64,
+        ))
+    })?;
+
+    runtime.evaluate_script(CLIPBOARD_JS)?;
+    Ok(())
+}
+
+/// Script settling clipboard call `id` with `result`.
+pub(crate) fn settle_script(id: u64, result: Result<&ClipboardData, ClipboardError>) -> String {
+    let result = match result {
+        Ok(data) => serde_json::to_value(data).unwrap_or_default(),
+        Err(error) => {
+            let (name, message) = error.dom_exception();
+            serde_json::json!({ "error": name, "message": message })
+        }
+    };
+    format!("__rustkit_clipboardSettle({}, {});", id, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_writes_consume_user_activation() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.notify_user_activation();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 navigator.clipboard.writeText('one').then(function() { out.push('one'); }); \
+                 navigator.clipboard.writeText('two');",
+            )
+            .unwrap();
+
+        let requests = bindings.take_clipboard_requests();
+        let written: Vec<_> = requests
+            .iter()
+            .map(|r| match &r.op {
+                ClipboardOp::Write(data) => (data.text.as_deref(), r.user_activated),
+                ClipboardOp::Read => panic!("unexpected read"),
+            })
+            .collect();
+        assert_eq!(written, [(Some("one"), true), (Some("two"), false)]);
+
+        bindings
+            .complete_clipboard(requests[0].id, Ok(ClipboardData::default()))
+            .unwrap();
+        assert_eq!(eval_string(&bindings, "out.join('|')"), "one");
+    }
+
+    #[test]
+    fn test_denied_read_rejects_with_not_allowed() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 navigator.clipboard.readText().catch(function(e) { \
+                     out.push(e.name, e instanceof DOMException); \
+                 }); \
+                 navigator.clipboard.read().then(function(items) { \
+                     out.push(items[0].types.join(',')); \
+                     return items[0].getType('text/html'); \
+                 }).then(function(blob) { return blob.text(); }) \
+                   .then(function(html) { out.push(html); });",
+            )
+            .unwrap();
+        let requests = bindings.take_clipboard_requests();
+        assert!(requests
+            .iter()
+            .all(|r| r.op == ClipboardOp::Read && !r.user_activated));
+
+        bindings
+            .complete_clipboard(requests[0].id, Err(ClipboardError::NotAllowed))
+            .unwrap();
+        bindings
+            .complete_clipboard(
+                requests[1].id,
+                Ok(ClipboardData {
+                    text: Some("Hi".into()),
+                    html: Some("<b>Hi</b>".into()),
+                }),
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "NotAllowedError|true|text/plain,text/html|<b>Hi</b>"
+        );
+    }
+
+    #[test]
+    fn test_exec_command_copies_the_selection() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.notify_user_activation();
+        let copied = bindings
+            .evaluate(
+                "var area = document.createElement('textarea'); \
+                 area.value = 'copy me'; \
+                 area.select(); \
+                 document.execCommand('copy');",
+            )
+            .unwrap();
+        assert!(matches!(copied, JsValue::Boolean(true)));
+        assert_eq!(
+            bindings.take_clipboard_requests(),
+            [ClipboardRequest {
+                id: 1,
+                op: ClipboardOp::Write(ClipboardData {
+                    text: Some("copy me".into()),
+                    html: None,
+                }),
+                user_activated: true,
+            }]
+        );
+    }
+}
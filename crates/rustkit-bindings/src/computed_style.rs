@@ -0,0 +1,592 @@
+//! `window.getComputedStyle`.
+//!
+//! Each call asks the host for the element's computed style through the
+//! provider set with [`crate::DomBindings::set_computed_style_provider`], so
+//! it reflects inline style changes made since the last layout. Where the
+//! resolved value of a property is its used value, as for the box's size,
+//! margins and padding, it comes from the element's first box, laying out
+//! first like the geometry accessors when styles changed.
+//!
+//! [`PROPERTIES`] is the one table of property names: `getComputedStyle`
+//! reports the longhands in it and composes its shorthands, and script
+//! accessors such as `backgroundColor` or `cssFloat` map to its names for
+//! both computed styles and `element.style`.
+
+use crate::geometry::GeometryState;
+use crate::media::MediaState;
+use rustkit_css::{
+    Color, ComputedStyle, Display, FontStyle, Length, MediaEnvironment, Overflow, Position,
+    PseudoElement, TextAlign,
+};
+use rustkit_dom::{Document, NodeId};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use rustkit_layout::{Dimensions, Float, PositionOffsets};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// An element's computed style, with the placement layout keeps on its
+/// box rather than its style.
+#[derive(Debug, Clone, Default)]
+pub struct ElementStyle {
+    pub style: ComputedStyle,
+    pub float: Float,
+    /// Offsets specified in pixels; the others are `auto`.
+    pub offsets: PositionOffsets,
+}
+
+impl From<ComputedStyle> for ElementStyle {
+    fn from(style: ComputedStyle) -> Self {
+        Self {
+            style,
+            ..Self::default()
+        }
+    }
+}
+
+/// Host function computing the style of an element, or of its `::before`
+/// or `::after` box, in a media environment. `None` for nodes that aren't
+/// elements and pseudo-elements that generate no box.
+pub type ComputedStyleProvider = Box<
+    dyn Fn(&Document, NodeId, Option<PseudoElement>, &MediaEnvironment) -> Option<ElementStyle>,
+>;
+
+/// Provider behind `getComputedStyle`.
+#[derive(Default)]
+pub(crate) struct ComputedStyleState {
+    provider: RefCell<Option<ComputedStyleProvider>>,
+}
+
+impl ComputedStyleState {
+    pub(crate) fn set_provider(&self, provider: ComputedStyleProvider) {
+        *self.provider.borrow_mut() = Some(provider);
+    }
+}
+
+/// What a property's resolved value is read from.
+struct Resolved<'a> {
+    element: &'a ElementStyle,
+    /// The first box of the element; `None` for pseudo-elements and
+    /// elements without one, which report computed values.
+    used: Option<Dimensions>,
+}
+
+impl Resolved<'_> {
+    fn style(&self) -> &ComputedStyle {
+        &self.element.style
+    }
+
+    fn font_size(&self) -> f32 {
+        self.style().font_size.to_px(16.0, 16.0, 16.0)
+    }
+
+    /// A computed length in pixels, or as a percentage or `auto`.
+    fn length(&self, length: Length) -> String {
+        match length {
+            Length::Percent(percent) => format!("{}%", number(percent)),
+            Length::Auto => "auto".to_string(),
+            Length::Calc(calc) if calc.has_percent() => calc.to_string(),
+            length => px(length.to_px(self.font_size(), 16.0, 0.0)),
+        }
+    }
+
+    /// The used value of a box edge, or its computed value.
+    fn edge(&self, used: impl Fn(&Dimensions) -> f32, computed: Length) -> String {
+        match self.used {
+            Some(ref dimensions) => px(used(dimensions)),
+            None => self.length(computed),
+        }
+    }
+
+    /// The used content size, for boxes `width` and `height` apply to.
+    fn size(&self, used: impl Fn(&Dimensions) -> f32, computed: Length) -> String {
+        match self.used {
+            Some(ref dimensions) if self.style().display != Display::Inline => px(used(dimensions)),
+            _ => self.length(computed),
+        }
+    }
+
+    fn offset(&self, offset: Option<f32>) -> String {
+        offset.map_or_else(|| "auto".to_string(), px)
+    }
+
+    /// The value of another property in the table.
+    fn get(&self, name: &str) -> String {
+        PROPERTIES
+            .iter()
+            .find(|(property, ..)| *property == name)
+            .map(|(_, _, value)| value(self))
+            .unwrap_or_default()
+    }
+
+    /// Four sides in the shortest form a shorthand takes.
+    fn sides(&self, names: [&str; 4]) -> String {
+        let [top, right, bottom, left] = names.map(|name| self.get(name));
+        if left != right {
+            format!("{top} {right} {bottom} {left}")
+        } else if top != bottom {
+            format!("{top} {right} {bottom}")
+        } else if top != right {
+            format!("{top} {right}")
+        } else {
+            top
+        }
+    }
+}
+
+/// How a property's value is read.
+type Accessor = fn(&Resolved) -> String;
+
+/// Whether a property is a longhand, listed by `item()`, or a shorthand
+/// composed from longhands.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Longhand,
+    Shorthand,
+}
+
+/// Properties `getComputedStyle` resolves, by CSS name.
+const PROPERTIES: &[(&str, Kind, Accessor)] = &[
+    ("background-color", Kind::Longhand, |r| {
+        color(r.style().background_color)
+    }),
+    ("border-bottom-color", Kind::Longhand, |r| {
+        color(r.style().border_bottom_color)
+    }),
+    ("border-bottom-width", Kind::Longhand, |r| {
+        r.edge(|d| d.border.bottom, r.style().border_bottom_width)
+    }),
+    ("border-left-color", Kind::Longhand, |r| {
+        color(r.style().border_left_color)
+    }),
+    ("border-left-width", Kind::Longhand, |r| {
+        r.edge(|d| d.border.left, r.style().border_left_width)
+    }),
+    ("border-right-color", Kind::Longhand, |r| {
+        color(r.style().border_right_color)
+    }),
+    ("border-right-width", Kind::Longhand, |r| {
+        r.edge(|d| d.border.right, r.style().border_right_width)
+    }),
+    ("border-top-color", Kind::Longhand, |r| {
+        color(r.style().border_top_color)
+    }),
+    ("border-top-width", Kind::Longhand, |r| {
+        r.edge(|d| d.border.top, r.style().border_top_width)
+    }),
+    ("bottom", Kind::Longhand, |r| {
+        r.offset(r.element.offsets.bottom)
+    }),
+    ("color", Kind::Longhand, |r| color(r.style().color)),
+    ("display", Kind::Longhand, |r| {
+        match r.style().display {
+            Display::Block => "block",
+            Display::Inline => "inline",
+            Display::InlineBlock => "inline-block",
+            Display::FlowRoot => "flow-root",
+            Display::Flex => "flex",
+            Display::InlineFlex => "inline-flex",
+            Display::Grid => "grid",
+            Display::InlineGrid => "inline-grid",
+            Display::TableCell => "table-cell",
+            Display::None => "none",
+        }
+        .to_string()
+    }),
+    ("float", Kind::Longhand, |r| {
+        match r.element.float {
+            Float::None => "none",
+            Float::Left => "left",
+            Float::Right => "right",
+        }
+        .to_string()
+    }),
+    ("font-family", Kind::Longhand, |r| {
+        r.style().font_family.clone()
+    }),
+    ("font-size", Kind::Longhand, |r| px(r.font_size())),
+    ("font-style", Kind::Longhand, |r| {
+        match r.style().font_style {
+            FontStyle::Normal => "normal",
+            FontStyle::Italic => "italic",
+            FontStyle::Oblique => "oblique",
+        }
+        .to_string()
+    }),
+    ("font-weight", Kind::Longhand, |r| {
+        r.style().font_weight.0.to_string()
+    }),
+    ("height", Kind::Longhand, |r| {
+        r.size(|d| d.content.height, r.style().height)
+    }),
+    ("left", Kind::Longhand, |r| r.offset(r.element.offsets.left)),
+    ("line-height", Kind::Longhand, |r| {
+        px(r.style().line_height * r.font_size())
+    }),
+    ("margin-bottom", Kind::Longhand, |r| {
+        r.edge(|d| d.margin.bottom, r.style().margin_bottom)
+    }),
+    ("margin-left", Kind::Longhand, |r| {
+        r.edge(|d| d.margin.left, r.style().margin_left)
+    }),
+    ("margin-right", Kind::Longhand, |r| {
+        r.edge(|d| d.margin.right, r.style().margin_right)
+    }),
+    ("margin-top", Kind::Longhand, |r| {
+        r.edge(|d| d.margin.top, r.style().margin_top)
+    }),
+    ("opacity", Kind::Longhand, |r| number(r.style().opacity)),
+    ("overflow-x", Kind::Longhand, |r| {
+        overflow(r.style().overflow_x)
+    }),
+    ("overflow-y", Kind::Longhand, |r| {
+        overflow(r.style().overflow_y)
+    }),
+    ("padding-bottom", Kind::Longhand, |r| {
+        r.edge(|d| d.padding.bottom, r.style().padding_bottom)
+    }),
+    ("padding-left", Kind::Longhand, |r| {
+        r.edge(|d| d.padding.left, r.style().padding_left)
+    }),
+    ("padding-right", Kind::Longhand, |r| {
+        r.edge(|d| d.padding.right, r.style().padding_right)
+    }),
+    ("padding-top", Kind::Longhand, |r| {
+        r.edge(|d| d.padding.top, r.style().padding_top)
+    }),
+    ("position", Kind::Longhand, |r| {
+        match r.style().position {
+            Position::Static => "static",
+            Position::Relative => "relative",
+            Position::Absolute => "absolute",
+            Position::Fixed => "fixed",
+            Position::Sticky => "sticky",
+        }
+        .to_string()
+    }),
+    ("right", Kind::Longhand, |r| {
+        r.offset(r.element.offsets.right)
+    }),
+    ("text-align", Kind::Longhand, |r| {
+        match r.style().text_align {
+            TextAlign::Left => "left",
+            TextAlign::Right => "right",
+            TextAlign::Center => "center",
+            TextAlign::Justify => "justify",
+        }
+        .to_string()
+    }),
+    ("top", Kind::Longhand, |r| r.offset(r.element.offsets.top)),
+    ("width", Kind::Longhand, |r| {
+        r.size(|d| d.content.width, r.style().width)
+    }),
+    ("z-index", Kind::Longhand, |r| {
+        r.style()
+            .z_index
+            .map_or_else(|| "auto".to_string(), |z| z.to_string())
+    }),
+    ("border-color", Kind::Shorthand, |r| {
+        r.sides([
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ])
+    }),
+    ("border-width", Kind::Shorthand, |r| {
+        r.sides([
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+        ])
+    }),
+    ("font", Kind::Shorthand, |r| {
+        let mut parts = Vec::new();
+        for (name, initial) in [("font-style", "normal"), ("font-weight", "400")] {
+            let value = r.get(name);
+            if value != initial {
+                parts.push(value);
+            }
+        }
+        parts.push(format!("{} / {}", r.get("font-size"), r.get("line-height")));
+        parts.push(r.get("font-family"));
+        parts.join(" ")
+    }),
+    ("inset", Kind::Shorthand, |r| {
+        r.sides(["top", "right", "bottom", "left"])
+    }),
+    ("margin", Kind::Shorthand, |r| {
+        r.sides(["margin-top", "margin-right", "margin-bottom", "margin-left"])
+    }),
+    ("overflow", Kind::Shorthand, |r| {
+        let (x, y) = (r.get("overflow-x"), r.get("overflow-y"));
+        if x == y {
+            x
+        } else {
+            format!("{x} {y}")
+        }
+    }),
+    ("padding", Kind::Shorthand, |r| {
+        r.sides([
+            "padding-top",
+            "padding-right",
+            "padding-bottom",
+            "padding-left",
+        ])
+    }),
+];
+
+/// `value` without a trailing `.0`, to three decimal places.
+fn number(value: f32) -> String {
+    let value = (value * 1000.0).round() / 1000.0;
+    // Avoid "-0"
+    format!("{}", value + 0.0)
+}
+
+fn px(value: f32) -> String {
+    format!("{}px", number(value))
+}
+
+fn color(color: Color) -> String {
+    if color.a >= 1.0 {
+        format!("rgb({}, {}, {})", color.r, color.g, color.b)
+    } else {
+        let alpha = number(color.a.max(0.0));
+        format!("rgba({}, {}, {}, {alpha})", color.r, color.g, color.b)
+    }
+}
+
+fn overflow(overflow: Overflow) -> String {
+    match overflow {
+        Overflow::Visible => "visible",
+        Overflow::Hidden => "hidden",
+        Overflow::Scroll => "scroll",
+        Overflow::Auto => "auto",
+        Overflow::Clip => "clip",
+    }
+    .to_string()
+}
+
+/// The pseudo-element a `getComputedStyle` argument names: `Ok(None)` for
+/// the element itself, `Err` for ones that generate no box here.
+fn parse_pseudo(pseudo: &str) -> Result<Option<PseudoElement>, ()> {
+    let pseudo = pseudo.trim();
+    if pseudo.is_empty() || pseudo == "null" || pseudo == "undefined" {
+        return Ok(None);
+    }
+    match pseudo.to_ascii_lowercase().as_str() {
+        "::before" | ":before" => Ok(Some(PseudoElement::Before)),
+        "::after" | ":after" => Ok(Some(PseudoElement::After)),
+        _ => Err(()),
+    }
+}
+
+/// Longhand values, listed by `item()`, and shorthand values of `element`;
+/// custom properties are longhands too.
+fn property_values(element: &ElementStyle, used: Option<Dimensions>) -> Value {
+    let resolved = Resolved { element, used };
+    let mut longhands = Map::new();
+    let mut shorthands = Map::new();
+    for (name, kind, value) in PROPERTIES {
+        let values = match kind {
+            Kind::Longhand => &mut longhands,
+            Kind::Shorthand => &mut shorthands,
+        };
+        values.insert(name.to_string(), Value::String(value(&resolved)));
+    }
+    for (name, value) in element.style.custom_properties.iter() {
+        longhands.insert(name.to_string(), Value::String(value.to_string()));
+    }
+    json!({ "longhands": longhands, "shorthands": shorthands })
+}
+
+const COMPUTED_STYLE_JS: &str = r#"
+    (function() {
+        var names = JSON.parse(__rustkit_css_properties());
+        function camel(name) {
+            return name.replace(/-([a-z])/g, function(_, c) { return c.toUpperCase(); });
+        }
+        var byAccessor = { cssFloat: 'float' };
+        names.forEach(function(name) { byAccessor[camel(name)] = name; });
+
+        // The CSS name behind a script property of a style declaration
+        window.__rustkit_cssPropertyName = function(prop) {
+            prop = String(prop);
+            if (prop.indexOf('--') === 0) return prop;
+            if (Object.prototype.hasOwnProperty.call(byAccessor, prop)) return byAccessor[prop];
+            var name = prop.replace(/[A-Z]/g, function(c) { return '-' + c.toLowerCase(); });
+            return name.indexOf('webkit-') === 0 ? '-' + name : name;
+        };
+    })();
+
+    window.getComputedStyle = function(element, pseudo) {
+        var longhands = {}, shorthands = {};
+        if (element && element._nodeId !== undefined) {
+            var values = JSON.parse(__rustkit_computed_style(element._nodeId,
+                pseudo === undefined || pseudo === null ? '' : String(pseudo)));
+            longhands = values.longhands;
+            shorthands = values.shorthands;
+        }
+        var names = Object.keys(longhands);
+        function propertyName(name) {
+            name = String(name).trim();
+            return name.indexOf('--') === 0 ? name : name.toLowerCase();
+        }
+        function value(name) {
+            if (Object.prototype.hasOwnProperty.call(longhands, name)) return longhands[name];
+            if (Object.prototype.hasOwnProperty.call(shorthands, name)) return shorthands[name];
+            return '';
+        }
+        function readOnly() {
+            throw new DOMException('The computed style is read-only.',
+                'NoModificationAllowedError');
+        }
+        var target = {
+            length: names.length,
+            cssText: '',
+            item: function(index) { return names[index] || ''; },
+            getPropertyValue: function(name) { return value(propertyName(name)); },
+            getPropertyPriority: function() { return ''; },
+            setProperty: readOnly,
+            removeProperty: readOnly
+        };
+        names.forEach(function(name, index) { target[index] = name; });
+        return new Proxy(target, {
+            get: function(t, prop) {
+                if (typeof prop !== 'string' || prop in t) return t[prop];
+                return value(window.__rustkit_cssPropertyName(prop));
+            },
+            set: function(t, prop) {
+                if (typeof prop === 'string') readOnly();
+                return false;
+            }
+        });
+    };
+    var getComputedStyle = window.getComputedStyle;
+"#;
+
+/// Register the computed style natives and install `getComputedStyle`.
+///
+/// Needs `DOMException`, from the encoding bindings.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<ComputedStyleState>,
+    geometry: Rc<GeometryState>,
+    media: Rc<MediaState>,
+) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_css_properties", 0, |_| {
+        let names: Vec<&str> = PROPERTIES.iter().map(|(name, ..)| *name).collect();
+        Ok(JsValue::String(json!(names).to_string()))
+    })?;
+
+    runtime.register_function("__rustkit_computed_style", 2, move |args| {
+        let node = args
+            .first()
+            .and_then(|a| a.parse::<f64>().ok())
+            .map(|id| NodeId::new(id as usize));
+        let pseudo = parse_pseudo(args.get(1).map(String::as_str).unwrap_or(""));
+        let element = match (node, pseudo, geometry.document()) {
+            (Some(node), Ok(pseudo), Some(document)) => state
+                .provider
+                .borrow()
+                .as_ref()
+                .and_then(|provider| provider(&document, node, pseudo, &media.environment()))
+                .map(|element| (node, pseudo, element)),
+            _ => None,
+        };
+        let values = match element {
+            Some((node, pseudo, element)) => {
+                // Used values need the element's box as laid out now
+                let used = pseudo.is_none().then(|| geometry.first_box(node)).flatten();
+                property_values(&element, used)
+            }
+            None => json!({ "longhands": {}, "shorthands": {} }),
+        };
+        Ok(JsValue::String(values.to_string()))
+    })?;
+
+    runtime.evaluate_script(COMPUTED_STYLE_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElementStyle;
+    use crate::DomBindings;
+    use rustkit_css::{Color, ComputedStyle, CustomProperties, PseudoElement};
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::rc::Rc;
+
+    fn bindings() -> DomBindings {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document =
+            Rc::new(Document::parse_html(r#"<html><body><p id="p">x</p></body></html>"#).unwrap());
+        bindings.set_document(document).unwrap();
+        bindings
+    }
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_get_computed_style_reads_custom_properties() {
+        let bindings = bindings();
+        bindings.set_computed_style_provider(|_, _, _, _| {
+            Some(ElementStyle::from(ComputedStyle {
+                custom_properties: CustomProperties::cascade(
+                    &CustomProperties::default(),
+                    [("--Accent", " #f00 ")],
+                ),
+                ..ComputedStyle::new()
+            }))
+        });
+
+        let value = eval_string(
+            &bindings,
+            "var s = getComputedStyle(document.getElementById('p')); \
+             [s.getPropertyValue('--Accent'), s.getPropertyValue('--accent'), \
+              s.getPropertyValue('--Accent') === s['--Accent'], \
+              s.getPropertyValue('unknown')].join('|')",
+        );
+        assert_eq!(value, "#f00||true|");
+    }
+
+    #[test]
+    fn test_serializes_keywords_and_shorthands() {
+        let bindings = bindings();
+        bindings.set_computed_style_provider(|_, _, pseudo, _| {
+            let mut style = ComputedStyle::new();
+            style.color = Color::new(0, 0, 255, 0.5);
+            style.display = rustkit_css::Display::InlineBlock;
+            style.margin_top = rustkit_css::Length::Px(4.0);
+            style.margin_bottom = rustkit_css::Length::Px(4.0);
+            match pseudo {
+                Some(PseudoElement::After) => None,
+                _ => Some(style.into()),
+            }
+        });
+
+        let value = eval_string(
+            &bindings,
+            "var s = getComputedStyle(document.getElementById('p')); \
+             var names = []; for (var i = 0; i < s.length; i++) names.push(s.item(i)); \
+             [s.color, s.display, s.fontSize, s.margin, s.cssFloat, \
+              names.indexOf('margin') < 0 && names.indexOf('margin-top') >= 0, \
+              getComputedStyle(document.getElementById('p'), '::after').length, \
+              getComputedStyle(document.getElementById('p'), '::marker').display].join('|')",
+        );
+        assert_eq!(
+            value,
+            "rgba(0, 0, 255, 0.5)|inline-block|16px|4px 0px|none|true|0|"
+        );
+        assert!(bindings
+            .evaluate("getComputedStyle(document.body).color = 'red'")
+            .is_err());
+    }
+}
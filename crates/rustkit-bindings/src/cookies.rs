@@ -0,0 +1,132 @@
+//! `document.cookie`.
+//!
+//! Cookies live in the host's [`CookieStore`], set with
+//! [`crate::DomBindings::set_cookie_store`], which also decides what
+//! script may see and set. Without one the document has no cookies and
+//! assignments are dropped.
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+use url::Url;
+
+/// Host side of `document.cookie`.
+pub trait CookieStore {
+    /// The cookies script at `url` sees, as `name=value` pairs joined by
+    /// `"; "`.
+    fn cookies(&self, url: &Url) -> String;
+    /// Store a cookie script at `url` assigned, in `Set-Cookie` form.
+    /// Cookies the host refuses are ignored.
+    fn set_cookie(&self, url: &Url, cookie: &str);
+}
+
+/// The host store and the document URL cookies are scoped to.
+#[derive(Default)]
+pub(crate) struct CookieState {
+    store: RefCell<Option<Box<dyn CookieStore>>>,
+    url: RefCell<Option<Url>>,
+}
+
+impl CookieState {
+    pub(crate) fn set_store(&self, store: Box<dyn CookieStore>) {
+        *self.store.borrow_mut() = Some(store);
+    }
+
+    pub(crate) fn set_url(&self, url: &Url) {
+        *self.url.borrow_mut() = Some(url.clone());
+    }
+}
+
+const COOKIES_JS: &str = r#"
+    Object.defineProperty(document, 'cookie', {
+        get: function() { return __rustkit_cookie_get(); },
+        set: function(value) { __rustkit_cookie_set(String(value)); },
+        configurable: true,
+        enumerable: true
+    });
+"#;
+
+/// Register the cookie natives and make `document.cookie` an accessor.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<CookieState>) -> Result<(), JsError> {
+    let get = state.clone();
+    runtime.register_function("__rustkit_cookie_get", 0, move |_| {
+        let cookies = match (get.store.borrow().as_ref(), get.url.borrow().as_ref()) {
+            (Some(store), Some(url)) => store.cookies(url),
+            _ => String::new(),
+        };
+        Ok(JsValue::String(cookies))
+    })?;
+    runtime.register_function("__rustkit_cookie_set", 1, move |args| {
+        if let (Some(store), Some(url), Some(cookie)) = (
+            state.store.borrow().as_ref(),
+            state.url.borrow().as_ref(),
+            args.first(),
+        ) {
+            store.set_cookie(url, cookie);
+        }
+        Ok(JsValue::Null)
+    })?;
+    runtime.evaluate_script(COOKIES_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieStore;
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use url::Url;
+
+    struct Store(Rc<RefCell<Vec<(String, String)>>>);
+
+    impl CookieStore for Store {
+        fn cookies(&self, url: &Url) -> String {
+            self.0
+                .borrow()
+                .iter()
+                .filter(|(at, _)| at == url.as_str())
+                .map(|(_, cookie)| cookie.split(';').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        }
+
+        fn set_cookie(&self, url: &Url, cookie: &str) {
+            self.0
+                .borrow_mut()
+                .push((url.to_string(), cookie.to_string()));
+        }
+    }
+
+    fn document_cookie(bindings: &DomBindings) -> String {
+        let JsValue::String(cookies) = bindings.evaluate("document.cookie").unwrap() else {
+            panic!("expected a string");
+        };
+        cookies
+    }
+
+    #[test]
+    fn test_document_cookie_goes_through_the_store() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let url = Url::parse("https://example.com/").unwrap();
+        bindings.set_location(&url).unwrap();
+        // No store, no cookies
+        bindings.evaluate("document.cookie = 'lost=1';").unwrap();
+        assert_eq!(document_cookie(&bindings), "");
+
+        let cookies = Rc::new(RefCell::new(Vec::new()));
+        bindings.set_cookie_store(Store(cookies.clone()));
+        bindings
+            .evaluate("document.cookie = 'a=1; path=/'; document.cookie = 'b=2';")
+            .unwrap();
+        assert_eq!(
+            cookies.borrow()[0],
+            (
+                "https://example.com/".to_string(),
+                "a=1; path=/".to_string()
+            )
+        );
+        assert_eq!(document_cookie(&bindings), "a=1; b=2");
+    }
+}
@@ -0,0 +1,268 @@
+//! `crypto.getRandomValues`, `crypto.randomUUID` and `crypto.subtle.digest`.
+//!
+//! Random bytes come from the OS RNG. `subtle` implements only `digest`;
+//! its other methods reject with `NotSupportedError` so feature detection
+//! still finds them. As in browsers, `subtle` and `randomUUID` are only
+//! exposed in secure contexts, which the host reports with
+//! [`crate::DomBindings::set_secure_context`].
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::json;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// Most bytes one `getRandomValues` call may fill.
+const MAX_RANDOM_BYTES: usize = 65536;
+
+/// A hash `crypto.subtle.digest` supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA-1" => Some(Self::Sha1),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-384" => Some(Self::Sha384),
+            "SHA-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            // Kept for sites that still ask for it
+            Self::Sha1 => Sha1::digest(data).to_vec(),
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// A version 4 UUID from the OS RNG.
+fn random_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+const CRYPTO_JS: &str = r#"
+    var __rustkit_integerArrays = [Int8Array, Uint8Array, Uint8ClampedArray, Int16Array,
+        Uint16Array, Int32Array, Uint32Array];
+    if (typeof BigInt64Array !== 'undefined') {
+        __rustkit_integerArrays.push(BigInt64Array, BigUint64Array);
+    }
+
+    function __rustkit_notSupported() {
+        return Promise.reject(new DOMException('The operation is not supported.', 'NotSupportedError'));
+    }
+
+    var __rustkit_subtle = {
+        digest: function(algorithm, data) {
+            return new Promise(function(resolve, reject) {
+                var name = typeof algorithm === 'object' && algorithm !== null
+                    ? algorithm.name : algorithm;
+                var bytes = JSON.stringify(__rustkit_bufferBytes(data));
+                var digest = __rustkit_crypto_digest(String(name), bytes);
+                if (digest === undefined) {
+                    reject(new DOMException('Unrecognized algorithm name', 'NotSupportedError'));
+                    return;
+                }
+                resolve(new Uint8Array(JSON.parse(digest)).buffer);
+            });
+        }
+    };
+    ['encrypt', 'decrypt', 'sign', 'verify', 'generateKey', 'deriveKey', 'deriveBits',
+        'importKey', 'exportKey', 'wrapKey', 'unwrapKey'].forEach(function(method) {
+        __rustkit_subtle[method] = __rustkit_notSupported;
+    });
+
+    var crypto = {
+        getRandomValues: function(array) {
+            var integer = __rustkit_integerArrays.some(function(type) {
+                return array instanceof type;
+            });
+            if (!integer) {
+                throw new DOMException('The provided ArrayBufferView is not an integer array type',
+                    'TypeMismatchError');
+            }
+            if (array.byteLength > 65536) {
+                throw new DOMException('The ArrayBufferView\'s byte length (' + array.byteLength +
+                    ') exceeds the number of bytes of entropy available via this API (65536).',
+                    'QuotaExceededError');
+            }
+            var bytes = JSON.parse(__rustkit_crypto_random(array.byteLength));
+            new Uint8Array(array.buffer, array.byteOffset, array.byteLength).set(bytes);
+            return array;
+        }
+    };
+    window.crypto = crypto;
+
+    function __rustkit_setSecureContext(secure) {
+        window.isSecureContext = secure;
+        if (secure) {
+            crypto.subtle = __rustkit_subtle;
+            crypto.randomUUID = function() { return __rustkit_crypto_uuid(); };
+        } else {
+            delete crypto.subtle;
+            delete crypto.randomUUID;
+        }
+    }
+    __rustkit_setSecureContext(false);
+"#;
+
+/// Register the crypto natives and install `window.crypto`.
+///
+/// Needs `DOMException` and the byte helpers of [`crate::encoding`]
+/// installed first.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_crypto_random", 1, |args| {
+        let len: usize = args
+            .first()
+            .and_then(|a| a.parse().ok())
+            .filter(|&len| len <= MAX_RANDOM_BYTES)
+            .ok_or_else(|| JsError::TypeError("Invalid random byte count".into()))?;
+        let mut bytes = vec![0u8; len];
+        OsRng.fill_bytes(&mut bytes);
+        Ok(JsValue::String(json!(bytes).to_string()))
+    })?;
+
+    runtime.register_function("__rustkit_crypto_uuid", 0, |_| {
+        Ok(JsValue::String(random_uuid()))
+    })?;
+
+    runtime.register_function("__rustkit_crypto_digest", 2, |args| {
+        let Some(algorithm) = args
+            .first()
+            .and_then(|name| DigestAlgorithm::from_name(name))
+        else {
+            return Ok(JsValue::Undefined);
+        };
+        let data: Vec<u8> = args
+            .get(1)
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_default();
+        Ok(JsValue::String(json!(algorithm.digest(&data)).to_string()))
+    })?;
+
+    runtime.evaluate_script(CRYPTO_JS)?;
+    Ok(())
+}
+
+/// Script exposing or withdrawing the secure-context-only members.
+pub(crate) fn secure_context_script(secure: bool) -> String {
+    format!("__rustkit_setSecureContext({});", secure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sha1_known_vectors() {
+        let hex = |data: &[u8]| -> String {
+            DigestAlgorithm::Sha1
+                .digest(data)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect()
+        };
+        assert_eq!(hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn test_digest_resolves_the_known_vector() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_secure_context(true).unwrap();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 crypto.subtle.digest('SHA-256', new TextEncoder().encode('abc')) \
+                     .then(function(buffer) { \
+                         out.push(Array.from(new Uint8Array(buffer)).map(function(b) { \
+                             return (b < 16 ? '0' : '') + b.toString(16); \
+                         }).join('')); \
+                     }); \
+                 crypto.subtle.digest({ name: 'MD5' }, new Uint8Array(1)) \
+                     .catch(function(e) { out.push(e.name); }); \
+                 crypto.subtle.sign('HMAC', null, null).catch(function(e) { out.push(e.name); });",
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\
+             |NotSupportedError|NotSupportedError"
+        );
+        assert_eq!(
+            eval_string(&bindings, "crypto.randomUUID()").as_bytes()[14],
+            b'4'
+        );
+    }
+
+    #[test]
+    fn test_get_random_values() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let draw = || {
+            eval_string(
+                &bindings,
+                "crypto.getRandomValues(new Uint8Array(16)).join(',')",
+            )
+        };
+        let (first, second) = (draw(), draw());
+        assert_eq!(first.split(',').count(), 16);
+        assert_ne!(first, second);
+
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "var errors = []; \
+                 [new Uint8Array(65537), new Float32Array(4)].forEach(function(array) { \
+                     try { crypto.getRandomValues(array); } catch (e) { errors.push(e.name); } \
+                 }); \
+                 errors.join('|')"
+            ),
+            "QuotaExceededError|TypeMismatchError"
+        );
+    }
+
+    #[test]
+    fn test_subtle_is_absent_outside_secure_contexts() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_secure_context(true).unwrap();
+        bindings.set_secure_context(false).unwrap();
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "[typeof crypto.subtle, typeof crypto.randomUUID, window.isSecureContext].join('|')"
+            ),
+            "undefined|undefined|false"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! `DataTransfer` on drag events.
+//!
+//! The host describes what is dragged over the page with a
+//! [`DataTransfer`] in [`crate::DragEventData`]. Until the drop the data is
+//! protected: `types` says what is there, but `files` is empty and
+//! `getData` returns `''`. Pages only read it; the data and
+//! `effectAllowed` can't be changed, while `dropEffect` is how a
+//! `dragover` listener picks the effect of a drop it accepts.
+
+use crate::DataTransfer;
+use rustkit_js::{JsError, JsRuntime};
+use serde_json::json;
+
+const DRAG_JS: &str = r#"
+    // The dataTransfer of the drag event last dispatched
+    var __rustkit_dragData = null;
+
+    var __rustkit_dropEffects = ['none', 'copy', 'move', 'link'];
+
+    function DataTransfer() {
+        this._dropEffect = 'none';
+        this._effectAllowed = 'uninitialized';
+        this._types = [];
+        this._data = {};
+        this._files = __rustkit_fileList([]);
+        this._protected = false;
+        this._readOnly = false;
+    }
+    Object.defineProperty(DataTransfer.prototype, 'dropEffect', {
+        get: function() { return this._dropEffect; },
+        set: function(value) {
+            value = String(value);
+            if (__rustkit_dropEffects.indexOf(value) >= 0) this._dropEffect = value;
+        }
+    });
+    Object.defineProperty(DataTransfer.prototype, 'effectAllowed', {
+        get: function() { return this._effectAllowed; },
+        set: function(value) {
+            if (!this._readOnly) this._effectAllowed = String(value);
+        }
+    });
+    Object.defineProperty(DataTransfer.prototype, 'types', {
+        get: function() { return this._types.slice(); }
+    });
+    Object.defineProperty(DataTransfer.prototype, 'files', {
+        get: function() { return this._files; }
+    });
+    Object.defineProperty(DataTransfer.prototype, 'items', {
+        get: function() {
+            var data = this;
+            var items = this._types.filter(function(type) {
+                return type !== 'Files';
+            }).map(function(type) {
+                return {
+                    kind: 'string',
+                    type: type,
+                    getAsFile: function() { return null; },
+                    getAsString: function(callback) {
+                        var value = data.getData(type);
+                        if (typeof callback === 'function') {
+                            Promise.resolve().then(function() { callback(value); });
+                        }
+                    }
+                };
+            });
+            Array.prototype.forEach.call(this._files, function(file) {
+                items.push({
+                    kind: 'file',
+                    type: file.type,
+                    getAsFile: function() { return file; },
+                    getAsString: function() {}
+                });
+            });
+            return items;
+        }
+    });
+    DataTransfer.prototype._format = function(format) {
+        format = String(format).toLowerCase();
+        if (format === 'text') return 'text/plain';
+        if (format === 'url') return 'text/uri-list';
+        return format;
+    };
+    DataTransfer.prototype.getData = function(format) {
+        if (this._protected) return '';
+        var wanted = String(format).toLowerCase();
+        var value = this._data[this._format(format)];
+        if (value === undefined) return '';
+        // URL asks for the first URL of the list
+        if (wanted === 'url') {
+            return value.split(/\r?\n/).filter(function(line) {
+                return line && line.charAt(0) !== '#';
+            })[0] || '';
+        }
+        return value;
+    };
+    DataTransfer.prototype.setData = function(format, value) {
+        if (this._readOnly) return;
+        format = this._format(format);
+        if (this._types.indexOf(format) < 0) this._types.push(format);
+        this._data[format] = String(value);
+    };
+    DataTransfer.prototype.clearData = function(format) {
+        if (this._readOnly) return;
+        var formats = format === undefined ? Object.keys(this._data) : [this._format(format)];
+        formats.forEach(function(format) {
+            delete this._data[format];
+            this._types = this._types.filter(function(type) { return type !== format; });
+        }, this);
+    };
+    DataTransfer.prototype.setDragImage = function() {};
+
+    // The read-only dataTransfer of a drag event from the host
+    function __rustkit_dataTransfer(init) {
+        var data = new DataTransfer();
+        data._dropEffect = init.dropEffect;
+        data._effectAllowed = init.effectAllowed;
+        data._types = init.types;
+        data._data = init.data;
+        data._files = __rustkit_fileList(init.files);
+        data._protected = init.protected;
+        data._readOnly = true;
+        return data;
+    }
+
+    window.DataTransfer = DataTransfer;
+"#;
+
+/// Install `DataTransfer`. Needs `File` installed first.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.evaluate_script(DRAG_JS)?;
+    Ok(())
+}
+
+/// Script creating the `dataTransfer` of a drag event, which `protected`
+/// keeps from reading the data.
+pub(crate) fn data_transfer_script(data: &DataTransfer, protected: bool) -> String {
+    let mut types = data.types.clone();
+    if !data.files.is_empty() {
+        types.push("Files".to_string());
+    }
+    let files: Vec<_> = match protected {
+        true => Vec::new(),
+        false => data
+            .files
+            .iter()
+            .map(|file| {
+                json!({
+                    "name": file.name,
+                    "type": file.mime_type,
+                    "lastModified": file.last_modified,
+                    "bytes": file.bytes,
+                })
+            })
+            .collect(),
+    };
+    let init = json!({
+        "dropEffect": data.drop_effect,
+        "effectAllowed": data.effect_allowed,
+        "types": types,
+        "data": data.items,
+        "files": files,
+        "protected": protected,
+    });
+    format!("(__rustkit_dragData = __rustkit_dataTransfer({}))", init)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DomBindings, DragEventData, DroppedFile};
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_data_is_protected_until_the_drop() {
+        let document = Rc::new(
+            Document::parse_html(r#"<html><body><div id="zone"></div></body></html>"#).unwrap(),
+        );
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_document(document.clone()).unwrap();
+        let zone = document.get_element_by_id("zone").unwrap().id;
+        bindings.evaluate("var seen = [];").unwrap();
+        bindings.add_event_listener(
+            zone,
+            "dragover",
+            "seen.push(e.dataTransfer.types.join(','), e.dataTransfer.files.length,
+                       e.dataTransfer.getData('text')); e.dataTransfer.dropEffect = 'link';
+             e.preventDefault();",
+            false,
+        );
+        bindings.add_event_listener(
+            zone,
+            "drop",
+            "var file = e.dataTransfer.files[0];
+             seen.push(file.name, file.size, file instanceof File, e.dataTransfer.getData('text'),
+                       e.dataTransfer.getData('URL'), e.dataTransfer.dropEffect);",
+            false,
+        );
+
+        let mut data = DragEventData::default();
+        data.data_transfer.drop_effect = "copy".into();
+        data.data_transfer.set_data("text/plain", "notes");
+        data.data_transfer.set_data(
+            "text/uri-list",
+            "# dragged\r\nhttps://example.com/a\r\nhttps://example.com/b",
+        );
+        data.data_transfer.files.push(DroppedFile {
+            name: "notes.txt".into(),
+            size: 5,
+            mime_type: "text/plain".into(),
+            path: None,
+            last_modified: 0,
+            bytes: b"notes".to_vec(),
+        });
+        assert_eq!(
+            bindings
+                .dispatch_drag_event(zone, "dragover", &data)
+                .unwrap(),
+            (false, "link".to_string())
+        );
+        // Nothing listens for dragenter, so the effect is as given
+        assert_eq!(
+            bindings
+                .dispatch_drag_event(zone, "dragenter", &data)
+                .unwrap(),
+            (true, "copy".to_string())
+        );
+        data.data_transfer.drop_effect = "link".into();
+        assert!(bindings.dispatch_drag_event(zone, "drop", &data).unwrap().0);
+        let seen = bindings.evaluate("seen.join('|')").unwrap();
+        assert!(
+            matches!(&seen, JsValue::String(s)
+                if s == "text/plain,text/uri-list,Files|0||notes.txt|5|true|notes|https://example.com/a|link"),
+            "{:?}",
+            seen
+        );
+    }
+}
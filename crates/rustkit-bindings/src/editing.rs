@@ -0,0 +1,116 @@
+//! `document.execCommand` for `contenteditable` hosts.
+//!
+//! The caret and selection belong to the host, so editing commands go to
+//! the handler set with [`crate::DomBindings::set_edit_command_handler`].
+//! `insertText`, `delete`, `bold` and `italic` are passed on; `copy` keeps
+//! its clipboard behaviour, and every other command returns false.
+
+use crate::geometry::GeometryState;
+use rustkit_dom::Document;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Host function running an editing command with its value. Returns
+/// whether the command ran.
+pub type EditCommandHandler = Box<dyn Fn(&Document, &str, &str) -> bool>;
+
+/// Handler behind `document.execCommand`.
+#[derive(Default)]
+pub(crate) struct EditingState {
+    handler: RefCell<Option<EditCommandHandler>>,
+}
+
+impl EditingState {
+    pub(crate) fn set_handler(&self, handler: EditCommandHandler) {
+        *self.handler.borrow_mut() = Some(handler);
+    }
+}
+
+const EDITING_JS: &str = r#"
+(function() {
+    var editingCommands = ['inserttext', 'delete', 'bold', 'italic'];
+    var copyCommand = document.execCommand;
+    var copySupported = document.queryCommandSupported;
+    document.execCommand = function(command, showUI, value) {
+        var name = String(command).toLowerCase();
+        if (editingCommands.indexOf(name) < 0) return copyCommand.call(document, command);
+        return __rustkit_exec_command(name, value === undefined ? '' : String(value));
+    };
+    document.queryCommandSupported = function(command) {
+        return editingCommands.indexOf(String(command).toLowerCase()) >= 0
+            || copySupported.call(document, command);
+    };
+})();
+"#;
+
+/// Register the `execCommand` native and wrap `document.execCommand`.
+///
+/// Needs the clipboard bindings installed first.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<EditingState>,
+    geometry: Rc<GeometryState>,
+) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_exec_command", 2, move |args| {
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_default();
+        let ran = match (geometry.document(), state.handler.borrow().as_ref()) {
+            (Some(document), Some(handler)) => handler(&document, arg(0), arg(1)),
+            _ => false,
+        };
+        if ran {
+            geometry.mark_dirty();
+        }
+        Ok(JsValue::Boolean(ran))
+    })?;
+    runtime.evaluate_script(EDITING_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_exec_command_runs_only_editing_commands() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Document::parse_html("<html><body></body></html>").unwrap();
+        bindings.set_document(Rc::new(document)).unwrap();
+        let eval = |script: &str| match bindings.evaluate(&format!("String({})", script)) {
+            Ok(JsValue::String(s)) => s,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        // Nothing runs commands until the host sets a handler
+        assert_eq!(eval("document.execCommand('bold')"), "false");
+
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let seen = commands.clone();
+        bindings.set_edit_command_handler(move |_, command, value| {
+            seen.borrow_mut().push(format!("{}:{}", command, value));
+            command != "delete"
+        });
+        assert_eq!(eval("document.execCommand('Bold', false)"), "true");
+        assert_eq!(
+            eval("document.execCommand('insertText', false, 'hi')"),
+            "true"
+        );
+        assert_eq!(eval("document.execCommand('delete')"), "false");
+        assert_eq!(eval("document.execCommand('underline')"), "false");
+        assert_eq!(*commands.borrow(), ["bold:", "inserttext:hi", "delete:"]);
+        assert!(bindings.needs_relayout());
+
+        assert_eq!(
+            eval(
+                "[document.queryCommandSupported('italic'), \
+                 document.queryCommandSupported('copy'), \
+                 document.queryCommandSupported('indent')].join()"
+            ),
+            "true,true,false"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! `TextEncoder`, `TextDecoder`, `atob` and `btoa`.
+//!
+//! Decoding shares [`rustkit_core::charset`] with response bodies. Bytes
+//! cross the native boundary as JSON arrays and are wrapped in `Uint8Array`
+//! on the JS side; streaming decoders keep their partial-sequence state in
+//! Rust, keyed by an id held by the `TextDecoder` object.
+
+use base64::alphabet;
+use base64::engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use rustkit_core::charset::{Charset, Decoder};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Decodes unpadded input and ignores leftover bits, as forgiving-base64
+/// does once padding has been stripped.
+const FORGIVING: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_allow_trailing_bits(true)
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+);
+
+/// Live `TextDecoder` streams, keyed by the id handed to script.
+#[derive(Default)]
+struct Decoders {
+    decoders: RefCell<HashMap<u32, Decoder>>,
+    next_id: Cell<u32>,
+}
+
+/// The forgiving-base64 decode used by `atob`, or `None` for input that
+/// must throw `InvalidCharacterError`.
+fn forgiving_base64_decode(input: &str) -> Option<Vec<u8>> {
+    let mut data: String = input
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' '))
+        .collect();
+    if data.len().is_multiple_of(4) {
+        for _ in 0..2 {
+            if data.ends_with('=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1
+        || !data
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+    {
+        return None;
+    }
+    FORGIVING.decode(data).ok()
+}
+
+/// `btoa`: base64 of a string whose characters are all below U+0100.
+fn latin1_base64_encode(input: &str) -> Option<String> {
+    let bytes = input
+        .chars()
+        .map(|c| u8::try_from(c).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(general_purpose::STANDARD.encode(bytes))
+}
+
+fn bytes_arg(args: &[String], index: usize) -> Vec<u8> {
+    args.get(index)
+        .and_then(|a| serde_json::from_str(a).ok())
+        .unwrap_or_default()
+}
+
+const ENCODING_JS: &str = r#"
+    if (typeof DOMException === 'undefined') {
+        var DOMException = function(message, name) {
+            this.message = message === undefined ? '' : String(message);
+            this.name = name === undefined ? 'Error' : String(name);
+        };
+        DOMException.prototype = Object.create(Error.prototype);
+        DOMException.prototype.constructor = DOMException;
+        window.DOMException = DOMException;
+    }
+
+    // Replace lone surrogates with U+FFFD so the string is valid Unicode
+    function __rustkit_wellFormed(s) {
+        s = String(s);
+        if (!/[\uD800-\uDFFF]/.test(s)) return s;
+        var out = '';
+        for (var i = 0; i < s.length; i++) {
+            var c = s.charCodeAt(i);
+            if (c >= 0xD800 && c <= 0xDBFF && i + 1 < s.length) {
+                var next = s.charCodeAt(i + 1);
+                if (next >= 0xDC00 && next <= 0xDFFF) {
+                    out += s[i] + s[i + 1];
+                    i++;
+                    continue;
+                }
+            }
+            out += c >= 0xD800 && c <= 0xDFFF ? '\uFFFD' : s[i];
+        }
+        return out;
+    }
+
+    function __rustkit_bufferBytes(input) {
+        if (input === undefined || input === null) return [];
+        if (input instanceof ArrayBuffer) return Array.from(new Uint8Array(input));
+        if (ArrayBuffer.isView(input)) {
+            return Array.from(new Uint8Array(input.buffer, input.byteOffset, input.byteLength));
+        }
+        throw new TypeError('The provided value is not an ArrayBuffer or ArrayBufferView');
+    }
+
+    function TextEncoder() {}
+    Object.defineProperty(TextEncoder.prototype, 'encoding', { get: function() { return 'utf-8'; } });
+    TextEncoder.prototype.encode = function(input) {
+        var text = input === undefined ? '' : __rustkit_wellFormed(input);
+        return new Uint8Array(JSON.parse(__rustkit_text_encode(text)));
+    };
+    TextEncoder.prototype.encodeInto = function(source, destination) {
+        var text = __rustkit_wellFormed(source);
+        var read = 0, written = 0;
+        // Take whole code points while their UTF-8 form still fits
+        while (read < text.length) {
+            var c = text.codePointAt(read);
+            var size = c < 0x80 ? 1 : c < 0x800 ? 2 : c < 0x10000 ? 3 : 4;
+            if (written + size > destination.length) break;
+            written += size;
+            read += c > 0xFFFF ? 2 : 1;
+        }
+        destination.set(this.encode(text.slice(0, read)));
+        return { read: read, written: written };
+    };
+
+    function TextDecoder(label, options) {
+        options = options || {};
+        var info = __rustkit_decoder_create(
+            label === undefined ? 'utf-8' : String(label), !!options.fatal, !!options.ignoreBOM);
+        if (info === undefined) {
+            throw new RangeError('The encoding label provided (\'' + label + '\') is invalid.');
+        }
+        info = JSON.parse(info);
+        this._id = info.id;
+        Object.defineProperty(this, 'encoding', { value: info.encoding });
+        Object.defineProperty(this, 'fatal', { value: !!options.fatal });
+        Object.defineProperty(this, 'ignoreBOM', { value: !!options.ignoreBOM });
+    }
+    TextDecoder.prototype.decode = function(input, options) {
+        var stream = !!(options && options.stream);
+        return __rustkit_decoder_decode(this._id,
+            JSON.stringify(__rustkit_bufferBytes(input)), stream);
+    };
+
+    function btoa(data) {
+        var result = __rustkit_btoa(String(data));
+        if (result === undefined) {
+            throw new DOMException('The string to be encoded contains characters outside of the Latin1 range.',
+                'InvalidCharacterError');
+        }
+        return result;
+    }
+
+    function atob(data) {
+        var result = __rustkit_atob(String(data));
+        if (result === undefined) {
+            throw new DOMException('The string to be decoded is not correctly encoded.',
+                'InvalidCharacterError');
+        }
+        return result;
+    }
+
+    window.TextEncoder = TextEncoder;
+    window.TextDecoder = TextDecoder;
+    window.btoa = btoa;
+    window.atob = atob;
+"#;
+
+/// Register the encoding natives and install the JS API.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    let decoders = Rc::new(Decoders::default());
+
+    runtime.register_function("__rustkit_text_encode", 1, |args| {
+        let text = args.first().map(String::as_str).unwrap_or("");
+        Ok(JsValue::String(json!(text.as_bytes()).to_string()))
+    })?;
+
+    let state = decoders.clone();
+    runtime.register_function("__rustkit_decoder_create", 3, move |args| {
+        let Some(charset) = args.first().and_then(|label| Charset::from_label(label)) else {
+            return Ok(JsValue::Undefined);
+        };
+        let flag = |i: usize| args.get(i).is_some_and(|a| a == "true");
+        let id = state.next_id.get() + 1;
+        state.next_id.set(id);
+        state
+            .decoders
+            .borrow_mut()
+            .insert(id, Decoder::new(charset, flag(1), flag(2)));
+        Ok(JsValue::String(
+            json!({ "id": id, "encoding": charset.name() }).to_string(),
+        ))
+    })?;
+
+    runtime.register_function("__rustkit_decoder_decode", 3, move |args| {
+        let id: u32 = args.first().and_then(|id| id.parse().ok()).unwrap_or(0);
+        let stream = args.get(2).is_some_and(|a| a == "true");
+        let mut decoders = decoders.decoders.borrow_mut();
+        let decoder = decoders
+            .get_mut(&id)
+            .ok_or_else(|| JsError::TypeError("Unknown TextDecoder".into()))?;
+        decoder
+            .decode(&bytes_arg(args, 1), stream)
+            .map(JsValue::String)
+            .map_err(|e| JsError::TypeError(e.to_string()))
+    })?;
+
+    runtime.register_function("__rustkit_btoa", 1, |args| {
+        let data = args.first().map(String::as_str).unwrap_or("");
+        Ok(latin1_base64_encode(data).map_or(JsValue::Undefined, JsValue::String))
+    })?;
+
+    runtime.register_function("__rustkit_atob", 1, |args| {
+        let data = args.first().map(String::as_str).unwrap_or("");
+        Ok(
+            forgiving_base64_decode(data).map_or(JsValue::Undefined, |bytes| {
+                JsValue::String(bytes.into_iter().map(char::from).collect())
+            }),
+        )
+    })?;
+
+    runtime.evaluate_script(ENCODING_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    fn bindings() -> DomBindings {
+        DomBindings::new(JsRuntime::new().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_multibyte_round_trip() {
+        let bindings = bindings();
+        let result = eval_string(
+            &bindings,
+            "var text = 'héllo wörld 日本 😀';
+             var bytes = new TextEncoder().encode(text);
+             var decoded = new TextDecoder().decode(bytes);
+             var latin1 = new TextDecoder('latin1').decode(new Uint8Array([0x80, 0xe9]));
+             var utf16 = new TextDecoder('utf-16le').decode(new Uint8Array([0xff, 0xfe, 0x3d, 0xd8, 0x00, 0xde]));
+             [bytes.length, decoded === text, latin1, utf16].join(',')",
+        );
+        assert_eq!(result, "25,true,€é,😀");
+    }
+
+    #[test]
+    fn test_streaming_decode_split_mid_codepoint() {
+        let bindings = bindings();
+        let result = eval_string(
+            &bindings,
+            "var bytes = new TextEncoder().encode('a€b');
+             var decoder = new TextDecoder('utf-8', { fatal: true });
+             var first = decoder.decode(bytes.subarray(0, 2), { stream: true });
+             var second = decoder.decode(bytes.subarray(2));
+             var error;
+             try { decoder.decode(bytes.subarray(0, 2)); } catch (e) { error = e.name; }
+             [first, second, error].join('|')",
+        );
+        assert_eq!(result, "a|€b|TypeError");
+    }
+
+    #[test]
+    fn test_atob_btoa() {
+        let bindings = bindings();
+        let result = eval_string(
+            &bindings,
+            "var errors = [];
+             try { btoa('\\u0100'); } catch (e) { errors.push(e.name); }
+             try { atob('abc*'); } catch (e) { errors.push(e.name); }
+             try { atob('a'); } catch (e) { errors.push(e.name); }
+             [btoa('\\u00ff\\u0000A'), atob(' /wBB '), atob('YQ'), errors.join(' ')].join('|')",
+        );
+        assert_eq!(
+            result,
+            "/wBB|\u{ff}\u{0}A|a|InvalidCharacterError InvalidCharacterError InvalidCharacterError"
+        );
+    }
+}
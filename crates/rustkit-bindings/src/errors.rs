@@ -0,0 +1,331 @@
+//! Error reporting: `window.onerror`, `unhandledrejection` and
+//! `rejectionhandled`.
+//!
+//! Exceptions that escape a callback the bindings run (event listeners,
+//! timers, animation frames, observers) are reported once: an `error`
+//! event at the window, then a [`PageError`] for the host unless a handler
+//! cancelled the event. Promises rejected with no handler by the end of a
+//! microtask checkpoint fire `unhandledrejection` the same way, and one
+//! that gets a handler later fires `rejectionhandled`.
+//!
+//! Errors from cross-origin scripts are muted to `Script error.` with no
+//! source, position or error object. Every script that runs today is the
+//! document's own; a loader running a classic script fetched from another
+//! origin without CORS approval asks for muting through
+//! [`crate::DomBindings::run_script`].
+//!
+//! Boa does not record where an error was thrown, so the line and column
+//! are those of a syntax error's message, or of a `lineNumber` and
+//! `columnNumber` the error carries, and 0 otherwise.
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// What went uncaught.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageErrorKind {
+    /// An exception thrown out of a script or callback.
+    Exception,
+    /// A promise rejected with no handler.
+    UnhandledRejection,
+    /// A script ran past its execution budget and was stopped.
+    BudgetExceeded,
+}
+
+/// An error in page script that the page did not handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageError {
+    pub kind: PageErrorKind,
+    pub message: String,
+    /// URL of the script, empty when muted.
+    pub source_url: String,
+    /// 1-based, or 0 when unknown.
+    pub line: u32,
+    /// 1-based, or 0 when unknown.
+    pub column: u32,
+    pub stack: Option<String>,
+}
+
+/// Errors reported since the host last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorState {
+    errors: RefCell<Vec<PageError>>,
+}
+
+impl ErrorState {
+    pub(crate) fn take_errors(&self) -> Vec<PageError> {
+        self.errors.take()
+    }
+
+    pub(crate) fn report(&self, error: PageError) {
+        self.errors.borrow_mut().push(error);
+    }
+}
+
+const ERRORS_JS: &str = r#"
+    var __rustkit_reportingError = false;
+    var __rustkit_notifiedRejections = new WeakMap();
+
+    function __rustkit_describeError(error) {
+        var info = { message: '', line: 0, column: 0, stack: '' };
+        try {
+            info.message = String(error);
+        } catch (e) {
+            info.message = 'Uncaught exception';
+        }
+        if (error === null || typeof error !== 'object') return info;
+        if (typeof error.lineNumber === 'number') {
+            info.line = error.lineNumber;
+            info.column = Number(error.columnNumber) || 0;
+        } else {
+            var at = /at line (\d+), col(?:umn)? (\d+)/.exec(info.message);
+            if (at) {
+                info.line = Number(at[1]);
+                info.column = Number(at[2]);
+            }
+        }
+        if (typeof error.stack === 'string') info.stack = error.stack;
+        return info;
+    }
+
+    function __rustkit_windowEvent(type, cancelable, fields) {
+        var event = {
+            type: type, bubbles: false, cancelable: cancelable, defaultPrevented: false,
+            timeStamp: Date.now(), isTrusted: true,
+            preventDefault: function() { if (this.cancelable) this.defaultPrevented = true; },
+            stopPropagation: function() {}, stopImmediatePropagation: function() {}
+        };
+        Object.keys(fields).forEach(function(key) { event[key] = fields[key]; });
+        return event;
+    }
+
+    // Report an exception that nothing caught; `muted` hides the details
+    // of a cross-origin script
+    function __rustkit_reportException(error, muted) {
+        var info = muted
+            ? { message: 'Script error.', line: 0, column: 0, stack: '' }
+            : __rustkit_describeError(error);
+        var source = muted ? '' : String(document.URL || '');
+        var event = __rustkit_windowEvent('error', true, {
+            message: info.message, filename: source, lineno: info.line, colno: info.column,
+            error: muted ? null : error
+        });
+        // An error in an error handler is not dispatched again
+        if (!__rustkit_reportingError) {
+            __rustkit_reportingError = true;
+            try {
+                window.dispatchEvent(event);
+            } finally {
+                __rustkit_reportingError = false;
+            }
+        }
+        if (!event.defaultPrevented) {
+            __rustkit_page_error('exception', info.message, source, info.line, info.column,
+                info.stack);
+        }
+    }
+
+    function __rustkit_promiseRejections(rejected, reasons, handled) {
+        rejected.forEach(function(promise, i) {
+            var reason = reasons[i];
+            __rustkit_notifiedRejections.set(promise, reason);
+            var event = __rustkit_windowEvent('unhandledrejection', true, {
+                promise: promise, reason: reason
+            });
+            window.dispatchEvent(event);
+            if (event.defaultPrevented) return;
+            var info = __rustkit_describeError(reason);
+            __rustkit_page_error('unhandledrejection', 'Uncaught (in promise) ' + info.message,
+                String(document.URL || ''), info.line, info.column, info.stack);
+        });
+        handled.forEach(function(promise) {
+            if (!__rustkit_notifiedRejections.has(promise)) return;
+            var reason = __rustkit_notifiedRejections.get(promise);
+            __rustkit_notifiedRejections.delete(promise);
+            window.dispatchEvent(__rustkit_windowEvent('rejectionhandled', false, {
+                promise: promise, reason: reason
+            }));
+        });
+    }
+
+    window.reportError = function(error) {
+        __rustkit_reportException(error, false);
+    };
+    var reportError = window.reportError;
+"#;
+
+/// Register the error natives, install `reportError` and start tracking
+/// promise rejections.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<ErrorState>) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_page_error", 6, move |args| {
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_default();
+        let kind = match arg(0) {
+            "unhandledrejection" => PageErrorKind::UnhandledRejection,
+            _ => PageErrorKind::Exception,
+        };
+        let position = |i: usize| arg(i).parse::<f64>().map_or(0, |n| n.max(0.0) as u32);
+        state.errors.borrow_mut().push(PageError {
+            kind,
+            message: arg(1).to_string(),
+            source_url: arg(2).to_string(),
+            line: position(3),
+            column: position(4),
+            stack: Some(arg(5)).filter(|s| !s.is_empty()).map(str::to_string),
+        });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(ERRORS_JS)?;
+    runtime.set_rejection_callback("__rustkit_promiseRejections");
+    Ok(())
+}
+
+/// Script running `source` as a classic script and reporting what it
+/// throws.
+pub(crate) fn run_script(source: &str, muted: bool) -> String {
+    format!(
+        "try {{ (0, eval)({:?}); }} catch (e) {{ __rustkit_reportException(e, {}); }}",
+        source, muted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    #[test]
+    fn test_onerror_gets_details_and_can_cancel() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var seen = []; \
+                 window.onerror = function(message, source, line, column, error) { \
+                     seen.push(message, error instanceof TypeError); \
+                     return seen.length > 2; \
+                 }; \
+                 setTimeout(function() { null.x; }, 0); \
+                 setTimeout(function() { throw new TypeError('quiet'); }, 0);",
+            )
+            .unwrap();
+        bindings.run_timers(0.0, 0.0).unwrap();
+
+        let seen = bindings.evaluate("seen.join('|')").unwrap();
+        assert!(
+            matches!(&seen, JsValue::String(s) if s.ends_with("|true|TypeError: quiet|true")),
+            "{:?}",
+            seen
+        );
+        // The second was cancelled by returning true
+        let errors = bindings.take_page_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, PageErrorKind::Exception);
+        assert!(errors[0].message.starts_with("TypeError"));
+    }
+
+    #[test]
+    fn test_errors_in_error_handlers_are_not_redispatched() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var calls = 0; \
+                 window.addEventListener('error', function() { calls++; throw new Error('again'); }); \
+                 reportError(new Error('first'));",
+            )
+            .unwrap();
+        assert!(matches!(bindings.evaluate("calls").unwrap(), JsValue::Number(n) if n == 1.0));
+        let messages: Vec<_> = bindings
+            .take_page_errors()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, ["Error: again", "Error: first"]);
+    }
+
+    #[test]
+    fn test_rejections_fire_unhandled_then_handled() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var fired = []; \
+                 window.addEventListener('unhandledrejection', function(e) { \
+                     fired.push(e.type + ':' + e.reason); \
+                 }); \
+                 window.onrejectionhandled = function(e) { fired.push(e.type + ':' + e.reason); }; \
+                 var late = Promise.reject('late'); \
+                 Promise.reject('caught').catch(function() {}); \
+                 (async function() { throw new RangeError('async'); })();",
+            )
+            .unwrap();
+        bindings.evaluate("late.catch(function() {});").unwrap();
+
+        let fired = bindings.evaluate("fired.join('|')").unwrap();
+        assert!(
+            matches!(&fired, JsValue::String(s)
+                if s == "unhandledrejection:late|unhandledrejection:RangeError: async|rejectionhandled:late"),
+            "{:?}",
+            fired
+        );
+        let errors = bindings.take_page_errors();
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|e| e.kind == PageErrorKind::UnhandledRejection));
+        assert_eq!(errors[1].message, "Uncaught (in promise) RangeError: async");
+    }
+
+    #[test]
+    fn test_script_syntax_error_has_position() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.run_script("var ok = 1;\nvar = 2;", false).unwrap();
+
+        // Nothing of a script that fails to parse runs
+        let ran = bindings.evaluate("typeof ok").unwrap();
+        assert!(matches!(ran, JsValue::String(s) if s == "undefined"));
+        let errors = bindings.take_page_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].message.starts_with("SyntaxError"),
+            "{}",
+            errors[0].message
+        );
+        assert_eq!(errors[0].line, 2);
+    }
+
+    #[test]
+    fn test_runaway_script_is_stopped_and_reported() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .run_script_bounded("var spins = 0; while (true) { spins++; }", false, 1000)
+            .unwrap();
+
+        let errors = bindings.take_page_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, PageErrorKind::BudgetExceeded);
+        // The context stays usable
+        let spins = bindings.evaluate("spins").unwrap();
+        assert!(matches!(spins, JsValue::Number(n) if n > 0.0));
+    }
+
+    #[test]
+    fn test_muted_errors_hide_details() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var details; window.onerror = function(message, source, line, column, error) { \
+                     details = [message, source, line, column, error].join(','); \
+                 };",
+            )
+            .unwrap();
+        bindings
+            .run_script("throw new Error('secret');", true)
+            .unwrap();
+
+        let details = bindings.evaluate("details").unwrap();
+        assert!(matches!(&details, JsValue::String(s) if s == "Script error.,,0,0,"));
+        let errors = bindings.take_page_errors();
+        assert_eq!(errors[0].message, "Script error.");
+        assert_eq!(errors[0].source_url, "");
+    }
+}
@@ -339,6 +339,10 @@ pub struct DroppedFile {
     pub size: u64,
     pub mime_type: String,
     pub path: Option<std::path::PathBuf>,
+    /// Milliseconds since the Unix epoch.
+    pub last_modified: u64,
+    /// Contents, which script sees once the file is dropped.
+    pub bytes: Vec<u8>,
 }
 
 /// Drag event data.
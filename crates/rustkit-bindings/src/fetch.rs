@@ -0,0 +1,801 @@
+//! `fetch`, `Headers`, `Request`, `Response`, `AbortController` and
+//! `AbortSignal`.
+//!
+//! Like `window.open`, fetching is mediated by the host: `fetch()` queues a
+//! [`FetchCommand::Start`] and returns a pending promise that settles when
+//! the engine calls [`crate::DomBindings::complete_fetch`]. Aborting the
+//! request's signal rejects the promise with an `AbortError` straight away
+//! and queues a [`FetchCommand::Abort`] so the host can cancel the network
+//! request.
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use url::Url;
+
+/// A request made with `fetch()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchRequest {
+    /// Identifies the request in [`crate::DomBindings::complete_fetch`].
+    pub id: u64,
+    pub url: Url,
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// A request as serialized by script.
+#[derive(Deserialize)]
+struct ScriptRequest {
+    url: String,
+    method: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+/// Work for the host queued by `fetch()` and aborted signals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchCommand {
+    /// Start a request.
+    Start(FetchRequest),
+    /// Cancel a request; its promise has already been rejected.
+    Abort { id: u64 },
+}
+
+/// A response to hand back to script.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FetchResponse {
+    pub url: String,
+    pub status: u16,
+    #[serde(rename = "statusText")]
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Commands queued by script since the host last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct FetchState {
+    next_id: Cell<u64>,
+    commands: RefCell<Vec<FetchCommand>>,
+}
+
+impl FetchState {
+    pub(crate) fn take_commands(&self) -> Vec<FetchCommand> {
+        self.commands.take()
+    }
+}
+
+const FETCH_JS: &str = r#"
+    function AbortSignal() {
+        this.aborted = false;
+        this.reason = undefined;
+        this.onabort = null;
+        this._listeners = [];
+    }
+    AbortSignal.prototype.addEventListener = function(type, callback) {
+        if (type === 'abort' && callback && this._listeners.indexOf(callback) < 0) {
+            this._listeners.push(callback);
+        }
+    };
+    AbortSignal.prototype.removeEventListener = function(type, callback) {
+        var index = this._listeners.indexOf(callback);
+        if (type === 'abort' && index >= 0) this._listeners.splice(index, 1);
+    };
+    AbortSignal.prototype.throwIfAborted = function() {
+        if (this.aborted) throw this.reason;
+    };
+    AbortSignal.prototype._abort = function(reason) {
+        if (this.aborted) return;
+        this.aborted = true;
+        this.reason = reason === undefined
+            ? new DOMException('signal is aborted without reason', 'AbortError')
+            : reason;
+        var event = { type: 'abort', target: this, currentTarget: this };
+        if (typeof this.onabort === 'function') this.onabort.call(this, event);
+        var signal = this;
+        this._listeners.slice().forEach(function(listener) {
+            if (typeof listener === 'function') listener.call(signal, event);
+            else listener.handleEvent(event);
+        });
+    };
+    AbortSignal.abort = function(reason) {
+        var signal = new AbortSignal();
+        signal._abort(reason);
+        return signal;
+    };
+
+    function AbortController() {
+        this.signal = new AbortSignal();
+    }
+    AbortController.prototype.abort = function(reason) {
+        this.signal._abort(reason);
+    };
+
+    var __rustkit_headerName = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+
+    // Header values lose leading and trailing HTTP whitespace
+    function __rustkit_headerValue(value) {
+        value = String(value).replace(/^[\t\n\r ]+|[\t\n\r ]+$/g, '');
+        if (/[\0\r\n]/.test(value)) throw new TypeError('Invalid header value: ' + JSON.stringify(value));
+        return value;
+    }
+
+    // Header names compare case-insensitively; the list keeps each pair as
+    // it was added, which is what goes on the wire.
+    function Headers(init) {
+        this._list = [];
+        this._guard = 'none';
+        if (init === undefined || init === null) return;
+        var headers = this;
+        if (init instanceof Headers) {
+            init._list.forEach(function(p) { headers.append(p[0], p[1]); });
+        } else if (typeof init === 'object' && typeof init[Symbol.iterator] === 'function') {
+            Array.from(init).forEach(function(pair) {
+                pair = Array.from(pair);
+                if (pair.length !== 2) {
+                    throw new TypeError("Failed to construct 'Headers': Invalid value");
+                }
+                headers.append(pair[0], pair[1]);
+            });
+        } else if (typeof init === 'object') {
+            Object.keys(init).forEach(function(name) { headers.append(name, init[name]); });
+        } else {
+            throw new TypeError("Failed to construct 'Headers': The provided value is not of type 'HeadersInit'.");
+        }
+    }
+    Headers.prototype._check = function(name) {
+        if (this._guard === 'immutable') throw new TypeError('Headers are immutable');
+        name = String(name);
+        if (!__rustkit_headerName.test(name)) throw new TypeError('Invalid header name: ' + JSON.stringify(name));
+        return name;
+    };
+    Headers.prototype.append = function(name, value) {
+        name = this._check(name);
+        this._list.push([name, __rustkit_headerValue(value)]);
+    };
+    Headers.prototype.set = function(name, value) {
+        name = this._check(name);
+        value = __rustkit_headerValue(value);
+        var lower = name.toLowerCase(), replaced = false;
+        this._list = this._list.filter(function(p) {
+            if (p[0].toLowerCase() !== lower) return true;
+            if (replaced) return false;
+            p[1] = value;
+            return replaced = true;
+        });
+        if (!replaced) this._list.push([name, value]);
+    };
+    Headers.prototype['delete'] = function(name) {
+        var lower = this._check(name).toLowerCase();
+        this._list = this._list.filter(function(p) { return p[0].toLowerCase() !== lower; });
+    };
+    Headers.prototype.get = function(name) {
+        var lower = String(name).toLowerCase();
+        var values = this._list.filter(function(p) { return p[0].toLowerCase() === lower; })
+            .map(function(p) { return p[1]; });
+        return values.length ? values.join(', ') : null;
+    };
+    Headers.prototype.getSetCookie = function() {
+        return this._list.filter(function(p) { return p[0].toLowerCase() === 'set-cookie'; })
+            .map(function(p) { return p[1]; });
+    };
+    Headers.prototype.has = function(name) {
+        return this.get(name) !== null;
+    };
+    // Sorted by name with values combined, except that each Set-Cookie
+    // stays a pair of its own
+    Headers.prototype._sorted = function() {
+        var headers = this, names = [];
+        this._list.forEach(function(p) {
+            var lower = p[0].toLowerCase();
+            if (names.indexOf(lower) < 0) names.push(lower);
+        });
+        names.sort();
+        var pairs = [];
+        names.forEach(function(name) {
+            if (name === 'set-cookie') {
+                headers.getSetCookie().forEach(function(value) { pairs.push([name, value]); });
+            } else {
+                pairs.push([name, headers.get(name)]);
+            }
+        });
+        return pairs;
+    };
+    Headers.prototype.forEach = function(callback, thisArg) {
+        var headers = this;
+        this._sorted().forEach(function(p) { callback.call(thisArg, p[1], p[0], headers); });
+    };
+    Headers.prototype.entries = function() {
+        return this._sorted()[Symbol.iterator]();
+    };
+    Headers.prototype.keys = function() {
+        return this._sorted().map(function(p) { return p[0]; })[Symbol.iterator]();
+    };
+    Headers.prototype.values = function() {
+        return this._sorted().map(function(p) { return p[1]; })[Symbol.iterator]();
+    };
+    Headers.prototype[Symbol.iterator] = Headers.prototype.entries;
+
+    // The bytes and default content type of a body init
+    function __rustkit_extractBody(body) {
+        if (body instanceof FormData) return __rustkit_formDataBody(body);
+        if (typeof URLSearchParams !== 'undefined' && body instanceof URLSearchParams) {
+            return {
+                bytes: new TextEncoder().encode(body.toString()),
+                type: 'application/x-www-form-urlencoded;charset=UTF-8'
+            };
+        }
+        if (body instanceof Blob) return { bytes: body._bytes.slice(), type: body.type || null };
+        if (body instanceof ArrayBuffer || ArrayBuffer.isView(body)) {
+            return { bytes: __rustkit_blobPartBytes(body).slice(), type: null };
+        }
+        return { bytes: new TextEncoder().encode(String(body)), type: 'text/plain;charset=UTF-8' };
+    }
+
+    // Body reading shared by Request and Response; a body can be read once
+    function __rustkit_body(proto) {
+        Object.defineProperty(proto, 'bodyUsed', { get: function() { return this._bodyUsed; } });
+        proto._take = function() {
+            if (this._bodyUsed) {
+                return Promise.reject(new TypeError('Body has already been consumed.'));
+            }
+            if (this._bytes !== null) this._bodyUsed = true;
+            return Promise.resolve(this._bytes || new Uint8Array(0));
+        };
+        proto.arrayBuffer = function() {
+            return this._take().then(function(b) { return b.slice().buffer; });
+        };
+        proto.text = function() {
+            return this._take().then(function(b) { return new TextDecoder().decode(b); });
+        };
+        proto.json = function() {
+            return this.text().then(JSON.parse);
+        };
+        proto.blob = function() {
+            var type = this.headers.get('content-type') || '';
+            return this._take().then(function(b) { return new Blob([b], { type: type }); });
+        };
+    }
+
+    var __rustkit_methods = ['DELETE', 'GET', 'HEAD', 'OPTIONS', 'POST', 'PUT'];
+
+    function Request(input, init) {
+        init = init || {};
+        var source = input instanceof Request ? input : null;
+        if (source && source._bodyUsed) {
+            throw new TypeError("Failed to construct 'Request': Cannot construct a Request with a Request object that has already been used.");
+        }
+        var url = source ? source.url
+            : new URL(input && input.href !== undefined ? input.href : String(input),
+                      location.href).href;
+        var method = source ? source.method : 'GET';
+        if (init.method !== undefined) {
+            method = String(init.method);
+            if (!__rustkit_headerName.test(method)) throw new TypeError("'" + method + "' is not a valid HTTP method.");
+            if (__rustkit_methods.indexOf(method.toUpperCase()) >= 0) method = method.toUpperCase();
+            if (/^(CONNECT|TRACE|TRACK)$/i.test(method)) {
+                throw new TypeError("'" + method + "' HTTP method is unsupported.");
+            }
+        }
+        this._url = url;
+        this._method = method;
+        this._headers = new Headers(init.headers !== undefined ? init.headers
+                                    : source ? source.headers : undefined);
+        this._signal = init.signal !== undefined ? init.signal : source ? source.signal : null;
+        this._credentials = init.credentials || (source ? source.credentials : 'same-origin');
+        this._mode = init.mode || (source ? source.mode : 'cors');
+        this._cache = init.cache || (source ? source.cache : 'default');
+        this._redirect = init.redirect || (source ? source.redirect : 'follow');
+        this._bodyUsed = false;
+        this._bytes = null;
+        var body = init.body !== undefined ? init.body : null;
+        if (body !== null) {
+            if (method === 'GET' || method === 'HEAD') {
+                throw new TypeError("Failed to construct 'Request': Request with GET/HEAD method cannot have body.");
+            }
+            var extracted = __rustkit_extractBody(body);
+            this._bytes = extracted.bytes;
+            if (extracted.type && !this._headers.has('content-type')) {
+                this._headers.append('Content-Type', extracted.type);
+            }
+        } else if (source && source._bytes !== null) {
+            // The new request takes over the body of the one it copies
+            this._bytes = source._bytes;
+            source._bodyUsed = true;
+        }
+    }
+    ['url', 'method', 'headers', 'signal', 'credentials', 'mode', 'cache', 'redirect'].forEach(function(name) {
+        Object.defineProperty(Request.prototype, name, { get: function() { return this['_' + name]; } });
+    });
+    __rustkit_body(Request.prototype);
+    Request.prototype.clone = function() {
+        if (this._bodyUsed) throw new TypeError("Failed to execute 'clone' on 'Request': Request body is already used");
+        var copy = new Request(this.url, { method: this.method, headers: this.headers, signal: this.signal,
+                                           credentials: this.credentials, mode: this.mode,
+                                           cache: this.cache, redirect: this.redirect });
+        copy._bytes = this._bytes === null ? null : this._bytes.slice();
+        return copy;
+    };
+
+    function Response(body, init) {
+        init = init || {};
+        var status = init.status === undefined ? 200 : Number(init.status);
+        if (!(status >= 200 && status <= 599) || Math.floor(status) !== status) {
+            throw new RangeError("Failed to construct 'Response': The status provided (" + init.status + ") is outside the range [200, 599].");
+        }
+        this._status = status;
+        this._statusText = init.statusText === undefined ? '' : String(init.statusText);
+        this._headers = new Headers(init.headers);
+        this._url = '';
+        this._type = 'default';
+        this._redirected = false;
+        this._bodyUsed = false;
+        this._bytes = null;
+        if (body !== undefined && body !== null) {
+            if (status === 204 || status === 205 || status === 304) {
+                throw new TypeError("Failed to construct 'Response': Response with null body status cannot have body");
+            }
+            var extracted = __rustkit_extractBody(body);
+            this._bytes = extracted.bytes;
+            if (extracted.type && !this._headers.has('content-type')) {
+                this._headers.append('Content-Type', extracted.type);
+            }
+        }
+        this._headers._guard = 'response';
+    }
+    ['url', 'status', 'statusText', 'headers', 'type', 'redirected'].forEach(function(name) {
+        Object.defineProperty(Response.prototype, name, { get: function() { return this['_' + name]; } });
+    });
+    Object.defineProperty(Response.prototype, 'ok', {
+        get: function() { return this._status >= 200 && this._status < 300; }
+    });
+    __rustkit_body(Response.prototype);
+    Response.prototype.clone = function() {
+        if (this._bodyUsed) throw new TypeError("Failed to execute 'clone' on 'Response': Response body is already used");
+        var copy = Object.create(Response.prototype);
+        Object.keys(this).forEach(function(key) { copy[key] = this[key]; }, this);
+        copy._headers = new Headers(this._headers);
+        copy._headers._guard = this._headers._guard;
+        copy._bytes = this._bytes === null ? null : this._bytes.slice();
+        return copy;
+    };
+    Response.json = function(data, init) {
+        var text = JSON.stringify(data);
+        if (text === undefined) throw new TypeError("Failed to execute 'json' on 'Response': The data is not JSON serializable");
+        var response = new Response(null, init);
+        if (response._status === 204 || response._status === 205 || response._status === 304) {
+            throw new TypeError("Failed to execute 'json' on 'Response': Response with null body status cannot have body");
+        }
+        response._bytes = new TextEncoder().encode(text);
+        if (!response._headers.has('content-type')) {
+            response._headers._list.push(['Content-Type', 'application/json']);
+        }
+        return response;
+    };
+    Response.error = function() {
+        var response = Object.create(Response.prototype);
+        Response.call(response, null, { status: 200 });
+        response._status = 0;
+        response._type = 'error';
+        response._headers._guard = 'immutable';
+        return response;
+    };
+    Response.redirect = function(url, status) {
+        status = status === undefined ? 302 : Number(status);
+        if ([301, 302, 303, 307, 308].indexOf(status) < 0) {
+            throw new RangeError("Failed to execute 'redirect' on 'Response': Invalid status code");
+        }
+        var response = new Response(null, { status: status });
+        response._headers._list.push(['Location', new URL(String(url), location.href).href]);
+        response._headers._guard = 'immutable';
+        return response;
+    };
+
+    // A response from the network; its headers cannot be changed
+    function __rustkit_fetchResponse(result) {
+        var response = Object.create(Response.prototype);
+        Response.call(response, null, {});
+        response._status = result.status;
+        response._statusText = result.statusText;
+        response._url = result.url;
+        response._type = 'basic';
+        response._headers._list = result.headers.map(function(p) { return [p[0], p[1]]; });
+        response._headers._guard = 'immutable';
+        response._bytes = new Uint8Array(result.body);
+        return response;
+    }
+
+    var __rustkit_fetches = {};
+
+    function fetch(input, init) {
+        var request;
+        try {
+            request = new Request(input, init);
+        } catch (e) {
+            return Promise.reject(new TypeError("Failed to execute 'fetch': " + e.message));
+        }
+        var signal = request.signal;
+        if (signal && signal.aborted) return Promise.reject(signal.reason);
+        var body = request._bytes === null ? null : Array.from(request._bytes);
+        if (body !== null) request._bodyUsed = true;
+
+        return new Promise(function(resolve, reject) {
+            var id = __rustkit_fetch_start(JSON.stringify({
+                url: request.url,
+                method: request.method,
+                headers: request.headers._list,
+                body: body
+            }));
+            __rustkit_fetches[id] = { resolve: resolve, reject: reject };
+            if (signal) {
+                signal.addEventListener('abort', function() {
+                    if (!__rustkit_fetches[id]) return;
+                    delete __rustkit_fetches[id];
+                    __rustkit_fetch_abort(id);
+                    reject(signal.reason);
+                });
+            }
+        });
+    }
+
+    function __rustkit_fetchSettle(id, result) {
+        var pending = __rustkit_fetches[id];
+        if (!pending) return;
+        delete __rustkit_fetches[id];
+        if (result.error !== undefined) {
+            pending.reject(new TypeError('Failed to fetch'));
+        } else {
+            pending.resolve(__rustkit_fetchResponse(result));
+        }
+    }
+
+    window.AbortSignal = AbortSignal;
+    window.AbortController = AbortController;
+    window.Headers = Headers;
+    window.Request = Request;
+    window.Response = Response;
+    window.fetch = fetch;
+"#;
+
+/// Register the fetch natives and install `fetch`, `Headers`, `Request`,
+/// `Response` and `AbortController`.
+///
+/// Needs `DOMException`, the `Blob` helpers and `FormData` installed first.
+pub(crate) fn install(runtime: &mut JsRuntime, fetches: Rc<FetchState>) -> Result<(), JsError> {
+    let state = fetches.clone();
+    runtime.register_function("__rustkit_fetch_start", 1, move |args| {
+        let request: ScriptRequest = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .ok_or_else(|| JsError::TypeError("Invalid fetch request".into()))?;
+        let url = Url::parse(&request.url)
+            .map_err(|_| JsError::TypeError(format!("Invalid URL: {}", request.url)))?;
+        let id = state.next_id.get() + 1;
+        state.next_id.set(id);
+        state
+            .commands
+            .borrow_mut()
+            .push(FetchCommand::Start(FetchRequest {
+                id,
+                url,
+                method: request.method,
+                headers: request.headers,
+                body: request.body,
+            }));
+        Ok(JsValue::Number(id as f64))
+    })?;
+
+    runtime.register_function("__rustkit_fetch_abort", 1, move |args| {
+        let id = args
+            .first()
+            .and_then(|a| a.parse::<f64>().ok())
+            .unwrap_or(0.0) as u64;
+        let mut commands = fetches.commands.borrow_mut();
+        // Requests the host has not picked up yet are simply dropped
+        let queued = commands
+            .iter()
+            .position(|c| matches!(c, FetchCommand::Start(r) if r.id == id));
+        match queued {
+            Some(index) => {
+                commands.remove(index);
+            }
+            None => commands.push(FetchCommand::Abort { id }),
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(FETCH_JS)?;
+    Ok(())
+}
+
+/// Script settling fetch `id` with `result`, or with a network error.
+pub(crate) fn settle_script(id: u64, result: Result<&FetchResponse, &str>) -> String {
+    let result = match result {
+        Ok(response) => serde_json::to_value(response).unwrap_or_default(),
+        Err(error) => serde_json::json!({ "error": error }),
+    };
+    format!("__rustkit_fetchSettle({}, {});", id, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    fn started(bindings: &DomBindings) -> Vec<FetchRequest> {
+        bindings
+            .take_fetch_commands()
+            .into_iter()
+            .map(|command| match command {
+                FetchCommand::Start(request) => request,
+                other => panic!("expected a start, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fetch_resolves_with_host_response() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://example.com/app/").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var out = [];
+                 fetch('data.json', { method: 'post', headers: { 'X-Test': '1' }, body: 'hi' })
+                     .then(function(r) { out.push(r.status, r.ok, r.headers.get('content-type')); return r.json(); })
+                     .then(function(data) { out.push(data.answer); });",
+            )
+            .unwrap();
+
+        let requests = started(&bindings);
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.url.as_str(), "https://example.com/app/data.json");
+        assert_eq!(request.method, "POST");
+        assert_eq!(
+            request.headers,
+            vec![
+                ("X-Test".into(), "1".into()),
+                ("Content-Type".into(), "text/plain;charset=UTF-8".into())
+            ]
+        );
+        assert_eq!(request.body.as_deref(), Some(&b"hi"[..]));
+
+        bindings
+            .complete_fetch(
+                request.id,
+                Ok(FetchResponse {
+                    url: request.url.to_string(),
+                    status: 200,
+                    status_text: "OK".into(),
+                    headers: vec![("Content-Type".into(), "application/json".into())],
+                    body: br#"{"answer":42}"#.to_vec(),
+                }),
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "200|true|application/json|42"
+        );
+    }
+
+    #[test]
+    fn test_abort_rejects_and_cancels() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://example.com/").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var out = [];
+                 var controller = new AbortController();
+                 controller.signal.onabort = function(e) { out.push(e.type); };
+                 fetch('/slow', { signal: controller.signal })
+                     .catch(function(e) { out.push(e.name, e instanceof DOMException); });",
+            )
+            .unwrap();
+        let id = started(&bindings)[0].id;
+
+        bindings
+            .evaluate("controller.abort(); controller.abort();")
+            .unwrap();
+        assert_eq!(
+            bindings.take_fetch_commands(),
+            vec![FetchCommand::Abort { id }]
+        );
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "abort|AbortError|true"
+        );
+
+        // The host finishing afterwards changes nothing
+        bindings.complete_fetch(id, Err("cancelled")).unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "abort|AbortError|true"
+        );
+
+        // Aborting before the host picked the request up just drops it
+        bindings
+            .evaluate(
+                "var later = new AbortController();
+                 fetch('/a', { signal: later.signal }).catch(function(e) { out.push(e.message); });
+                 later.abort(new DOMException('gone', 'AbortError'));
+                 fetch('/b', { signal: AbortSignal.abort() }).catch(function(e) { out.push(e.name); });",
+            )
+            .unwrap();
+        assert!(bindings.take_fetch_commands().is_empty());
+        assert_eq!(
+            eval_string(&bindings, "out.slice(3).join('|')"),
+            "gone|AbortError"
+        );
+    }
+
+    #[test]
+    fn test_headers_combine_case_insensitively() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let result = eval_string(
+            &bindings,
+            "var headers = new Headers([['Accept', 'text/html']]);
+             headers.append('accept', ' application/json ');
+             headers.append('Set-Cookie', 'a=1');
+             headers.append('set-cookie', 'b=2');
+             headers.set('X-One', '1');
+             headers.set('x-one', '2');
+             var out = [headers.get('ACCEPT'), headers.get('x-one'), headers.has('x-two'),
+                        headers.getSetCookie().join(';')];
+             headers.forEach(function(value, name) { out.push(name + ':' + value); });
+             headers['delete']('Accept');
+             out.push(headers.has('accept'));
+             var error;
+             try { headers.append('bad name', 'x'); } catch (e) { error = e.name; }
+             out.push(error);
+             out.join('|')",
+        );
+        assert_eq!(
+            result,
+            "text/html, application/json|2|false|a=1;b=2|accept:text/html, application/json|\
+             set-cookie:a=1|set-cookie:b=2|x-one:2|false|TypeError"
+        );
+    }
+
+    #[test]
+    fn test_response_body_read_once_and_clone() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var out = [];
+                 var response = new Response('hello', { status: 201, headers: { 'X-A': '1' } });
+                 var copy = response.clone();
+                 out.push(response.status, response.ok, response.headers.get('content-type'));
+                 response.text()
+                     .then(function(text) { out.push(text, response.bodyUsed); return response.text(); })
+                     .catch(function(e) { out.push(e.name); })
+                     .then(function() { return copy.text(); })
+                     .then(function(text) { out.push('copy:' + text); });
+                 Response.json({ a: 1 }, { status: 202 }).json().then(function(data) { out.push(data.a); });
+                 try { new Response(null, { status: 99 }); } catch (e) { out.push(e.name); }",
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "201|true|text/plain;charset=UTF-8|RangeError|hello|true|1|TypeError|copy:hello"
+        );
+    }
+
+    #[test]
+    fn test_request_as_fetch_input() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://example.com/app/").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var out = [];
+                 var request = new Request('items', {
+                     method: 'patch', body: new URLSearchParams('a=1&b=2'), headers: new Headers({ 'X-B': '2' })
+                 });
+                 out.push(request.url, request.method, request.bodyUsed);
+                 fetch(request).then(function(r) {
+                     out.push(r.headers.get('set-cookie'));
+                     try { r.headers.set('X', '1'); } catch (e) { out.push(e.name); }
+                 });
+                 out.push(request.bodyUsed);
+                 try { new Request('/x', { body: 'no' }); } catch (e) { out.push(e.name); }",
+            )
+            .unwrap();
+        let requests = started(&bindings);
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.url.as_str(), "https://example.com/app/items");
+        assert_eq!(request.method, "patch");
+        assert_eq!(
+            request.headers,
+            vec![
+                ("X-B".into(), "2".into()),
+                (
+                    "Content-Type".into(),
+                    "application/x-www-form-urlencoded;charset=UTF-8".into()
+                )
+            ]
+        );
+        assert_eq!(request.body.as_deref(), Some(&b"a=1&b=2"[..]));
+
+        bindings
+            .complete_fetch(
+                request.id,
+                Ok(FetchResponse {
+                    url: request.url.to_string(),
+                    status: 200,
+                    status_text: "OK".into(),
+                    headers: vec![
+                        ("Set-Cookie".into(), "a=1".into()),
+                        ("Set-Cookie".into(), "b=2".into()),
+                    ],
+                    body: Vec::new(),
+                }),
+            )
+            .unwrap();
+        assert_eq!(
+            eval_string(&bindings, "out.join('|')"),
+            "https://example.com/app/items|patch|false|true|TypeError|a=1, b=2|TypeError"
+        );
+    }
+
+    #[test]
+    fn test_form_data_body_matches_form_encoding() {
+        use rustkit_dom::{FormDataEntry, FormDataValue, FormEnctype, FormState};
+
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://example.com/").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var data = new FormData();
+                 data.append('name', 'Ada Lovelace');
+                 data.append('file', new Blob([new Uint8Array([0, 255])], { type: 'image/png' }), 'a.png');
+                 fetch('/upload', { method: 'POST', body: data });",
+            )
+            .unwrap();
+        let request = started(&bindings).remove(0);
+        let expected = FormState::encode_form_data(
+            &[
+                FormDataEntry {
+                    name: "name".into(),
+                    value: FormDataValue::String("Ada Lovelace".into()),
+                },
+                FormDataEntry {
+                    name: "file".into(),
+                    value: FormDataValue::File {
+                        name: "a.png".into(),
+                        content_type: "image/png".into(),
+                        content: vec![0, 255],
+                    },
+                },
+            ],
+            FormEnctype::MultipartFormData,
+        );
+        assert_eq!(request.body, Some(expected));
+        assert_eq!(
+            request.headers,
+            vec![(
+                "Content-Type".into(),
+                format!(
+                    "multipart/form-data; boundary={}",
+                    rustkit_dom::MULTIPART_BOUNDARY
+                )
+            )]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! `FormData`.
+//!
+//! Entries live on the JS side. `new FormData(form)` asks the host for the
+//! entries the form would submit, through the provider set with
+//! [`crate::DomBindings::set_form_entries_provider`], so script sees exactly
+//! what a submission would send; used as a request body, the entries are
+//! encoded by the same [`FormState::encode_form_data`] form submission uses.
+
+use rustkit_dom::{
+    Document, FormDataEntry, FormDataValue, FormEnctype, FormState, NodeId, MULTIPART_BOUNDARY,
+};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Host function returning the entries a form would submit.
+pub type FormEntriesProvider = Box<dyn Fn(&Document, NodeId) -> Vec<FormDataEntry>>;
+
+/// Document and provider behind `new FormData(form)`.
+#[derive(Default)]
+pub(crate) struct FormDataState {
+    document: RefCell<Option<Rc<Document>>>,
+    provider: RefCell<Option<FormEntriesProvider>>,
+}
+
+impl FormDataState {
+    pub(crate) fn set_document(&self, document: Rc<Document>) {
+        *self.document.borrow_mut() = Some(document);
+    }
+
+    pub(crate) fn set_provider(&self, provider: FormEntriesProvider) {
+        *self.provider.borrow_mut() = Some(provider);
+    }
+}
+
+/// An entry as script holds it.
+#[derive(Serialize, Deserialize)]
+struct ScriptEntry {
+    name: String,
+    #[serde(default)]
+    value: Option<String>,
+    #[serde(default)]
+    file: Option<ScriptFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScriptFile {
+    name: String,
+    #[serde(rename = "type")]
+    content_type: String,
+    bytes: Vec<u8>,
+}
+
+impl From<FormDataEntry> for ScriptEntry {
+    fn from(entry: FormDataEntry) -> Self {
+        match entry.value {
+            FormDataValue::String(value) => Self {
+                name: entry.name,
+                value: Some(value),
+                file: None,
+            },
+            FormDataValue::File {
+                name,
+                content_type,
+                content,
+            } => Self {
+                name: entry.name,
+                value: None,
+                file: Some(ScriptFile {
+                    name,
+                    content_type,
+                    bytes: content,
+                }),
+            },
+        }
+    }
+}
+
+impl From<ScriptEntry> for FormDataEntry {
+    fn from(entry: ScriptEntry) -> Self {
+        let value = match entry.file {
+            Some(file) => FormDataValue::File {
+                name: file.name,
+                content_type: file.content_type,
+                content: file.bytes,
+            },
+            None => FormDataValue::String(entry.value.unwrap_or_default()),
+        };
+        Self {
+            name: entry.name,
+            value,
+        }
+    }
+}
+
+const FORM_DATA_JS: &str = r#"
+    function __rustkit_formDataValue(value, filename) {
+        if (!(value instanceof Blob)) return String(value);
+        if (value instanceof File && filename === undefined) return value;
+        var name = filename !== undefined ? String(filename)
+            : value instanceof File ? value.name : 'blob';
+        return new File([value], name, {
+            type: value.type,
+            lastModified: value instanceof File ? value.lastModified : undefined
+        });
+    }
+
+    function FormData(form, submitter) {
+        this._entries = [];
+        if (form === undefined) return;
+        if (!form || form.tagName === undefined || String(form.tagName).toUpperCase() !== 'FORM') {
+            throw new TypeError("Failed to construct 'FormData': parameter 1 is not of type 'HTMLFormElement'.");
+        }
+        if (form._nodeId === undefined) return;
+        var entries = this._entries;
+        JSON.parse(__rustkit_form_entries(form._nodeId)).forEach(function(entry) {
+            var value = entry.file
+                ? new File([new Uint8Array(entry.file.bytes)], entry.file.name, { type: entry.file.type })
+                : entry.value;
+            entries.push([entry.name, value]);
+        });
+    }
+    FormData.prototype.append = function(name, value, filename) {
+        if (arguments.length < 2) {
+            throw new TypeError("Failed to execute 'append' on 'FormData': 2 arguments required.");
+        }
+        this._entries.push([String(name), __rustkit_formDataValue(value, filename)]);
+    };
+    FormData.prototype.set = function(name, value, filename) {
+        if (arguments.length < 2) {
+            throw new TypeError("Failed to execute 'set' on 'FormData': 2 arguments required.");
+        }
+        name = String(name);
+        var entry = [name, __rustkit_formDataValue(value, filename)];
+        var index = this._entries.findIndex(function(e) { return e[0] === name; });
+        if (index < 0) {
+            this._entries.push(entry);
+            return;
+        }
+        this._entries[index] = entry;
+        this._entries = this._entries.filter(function(e, i) { return i <= index || e[0] !== name; });
+    };
+    FormData.prototype.get = function(name) {
+        name = String(name);
+        var entry = this._entries.find(function(e) { return e[0] === name; });
+        return entry ? entry[1] : null;
+    };
+    FormData.prototype.getAll = function(name) {
+        name = String(name);
+        return this._entries.filter(function(e) { return e[0] === name; })
+            .map(function(e) { return e[1]; });
+    };
+    FormData.prototype.has = function(name) {
+        return this.get(name) !== null;
+    };
+    FormData.prototype['delete'] = function(name) {
+        name = String(name);
+        this._entries = this._entries.filter(function(e) { return e[0] !== name; });
+    };
+    FormData.prototype.forEach = function(callback, thisArg) {
+        var data = this;
+        this._entries.slice().forEach(function(e) { callback.call(thisArg, e[1], e[0], data); });
+    };
+    FormData.prototype.entries = function() {
+        return this._entries.map(function(e) { return [e[0], e[1]]; })[Symbol.iterator]();
+    };
+    FormData.prototype.keys = function() {
+        return this._entries.map(function(e) { return e[0]; })[Symbol.iterator]();
+    };
+    FormData.prototype.values = function() {
+        return this._entries.map(function(e) { return e[1]; })[Symbol.iterator]();
+    };
+    FormData.prototype[Symbol.iterator] = FormData.prototype.entries;
+
+    // The multipart body and content type of `data`
+    function __rustkit_formDataBody(data) {
+        var entries = data._entries.map(function(e) {
+            if (e[1] instanceof File) {
+                return {
+                    name: e[0],
+                    file: { name: e[1].name, type: e[1].type, bytes: Array.from(e[1]._bytes) }
+                };
+            }
+            return { name: e[0], value: e[1] };
+        });
+        var encoded = JSON.parse(__rustkit_form_data_encode(JSON.stringify(entries)));
+        return { bytes: new Uint8Array(encoded.body), type: encoded.type };
+    }
+
+    window.FormData = FormData;
+"#;
+
+/// Register the form natives and install `FormData`.
+///
+/// Needs the `Blob` helpers installed first.
+pub(crate) fn install(runtime: &mut JsRuntime, forms: Rc<FormDataState>) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_form_entries", 1, move |args| {
+        let form = args
+            .first()
+            .and_then(|a| a.parse::<f64>().ok())
+            .map(|id| NodeId::new(id as usize));
+        let entries: Vec<ScriptEntry> = match (
+            form,
+            forms.document.borrow().as_ref(),
+            forms.provider.borrow().as_ref(),
+        ) {
+            (Some(form), Some(document), Some(provider)) => provider(document, form)
+                .into_iter()
+                .map(ScriptEntry::from)
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(JsValue::String(json!(entries).to_string()))
+    })?;
+
+    runtime.register_function("__rustkit_form_data_encode", 1, |args| {
+        let entries: Vec<ScriptEntry> = args
+            .first()
+            .and_then(|a| serde_json::from_str(a).ok())
+            .ok_or_else(|| JsError::TypeError("Invalid form data".into()))?;
+        let entries: Vec<FormDataEntry> = entries.into_iter().map(Into::into).collect();
+        let body = FormState::encode_form_data(&entries, FormEnctype::MultipartFormData);
+        Ok(JsValue::String(
+            json!({
+                "type": format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+                "body": body,
+            })
+            .to_string(),
+        ))
+    })?;
+
+    runtime.evaluate_script(FORM_DATA_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_form_data_entries() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let result = eval_string(
+            &bindings,
+            "var data = new FormData();
+             data.append('a', 1);
+             data.append('b', new Blob(['xy'], { type: 'text/plain' }));
+             data.append('a', 'two');
+             data.append('c', new File(['z'], 'z.txt'), 'renamed.txt');
+             var out = [data.get('a'), data.getAll('a').join(','), data.has('b'),
+                        data.get('b') instanceof File, data.get('b').name, data.get('b').type,
+                        data.get('c').name, data.get('missing')];
+             data.set('a', 'only');
+             data['delete']('b');
+             var keys = [];
+             for (var pair of data) keys.push(pair[0] + '=' + (typeof pair[1] === 'string' ? pair[1] : pair[1].name));
+             out.concat(keys).join('|')",
+        );
+        assert_eq!(
+            result,
+            "1|1,two|true|true|blob|text/plain|renamed.txt||a=only|c=renamed.txt"
+        );
+    }
+
+    #[test]
+    fn test_form_data_from_form_uses_provider() {
+        let document = Rc::new(
+            Document::parse_html(r#"<html><body><form id="f"></form></body></html>"#).unwrap(),
+        );
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_document(document.clone()).unwrap();
+        let form = document.get_element_by_id("f").unwrap().id;
+        bindings.set_form_entries_provider(move |_, node| {
+            assert_eq!(node, form);
+            vec![
+                FormDataEntry {
+                    name: "q".into(),
+                    value: FormDataValue::String("rust".into()),
+                },
+                FormDataEntry {
+                    name: "upload".into(),
+                    value: FormDataValue::File {
+                        name: "a.bin".into(),
+                        content_type: String::new(),
+                        content: vec![1, 2],
+                    },
+                },
+            ]
+        });
+        let result = eval_string(
+            &bindings,
+            "var data = new FormData(document.getElementById('f'));
+             [data.get('q'), data.get('upload').name, data.get('upload').size].join('|')",
+        );
+        assert_eq!(result, "rust|a.bin|2");
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "var error; try { new FormData({}); } catch (e) { error = e.name; } error"
+            ),
+            "TypeError"
+        );
+    }
+}
@@ -0,0 +1,504 @@
+//! `window.parent`, `window.top`, `window.frames` and `frameElement`.
+//!
+//! Every frame runs in its own [`JsRuntime`], so other windows are reached
+//! through proxies. For a same-origin window the proxy forwards property
+//! reads, writes and calls to the other runtime by path: primitives are
+//! copied back as JSON and objects come back as further proxies. A
+//! cross-origin window only exposes `postMessage`, `closed` and a
+//! write-only `location`; anything else throws a `SecurityError`.
+
+use crate::popup::{PopupState, WindowRequest, WindowTarget};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use url::Url;
+
+/// A window in another runtime of the same frame tree.
+#[derive(Clone)]
+pub(crate) struct FrameLink {
+    runtime: Weak<RefCell<JsRuntime>>,
+    state: Weak<FrameState>,
+}
+
+impl FrameLink {
+    pub(crate) fn new(runtime: &Rc<RefCell<JsRuntime>>, state: &Rc<FrameState>) -> Self {
+        Self {
+            runtime: Rc::downgrade(runtime),
+            state: Rc::downgrade(state),
+        }
+    }
+}
+
+/// This window's origin and its links to neighbouring frames.
+#[derive(Default)]
+pub(crate) struct FrameState {
+    /// Serialized origin of the document, `None` while it is opaque.
+    origin: RefCell<Option<String>>,
+    parent: RefCell<Option<FrameLink>>,
+    /// Child frames by index in `window.frames`.
+    children: RefCell<Vec<Option<FrameLink>>>,
+}
+
+/// How script in this window may reach another one.
+enum Access {
+    Same(FrameLink),
+    Cross,
+    Gone,
+}
+
+impl FrameState {
+    pub(crate) fn set_origin(&self, origin: Option<String>) {
+        *self.origin.borrow_mut() = origin;
+    }
+
+    pub(crate) fn set_parent(&self, parent: FrameLink) {
+        *self.parent.borrow_mut() = Some(parent);
+    }
+
+    pub(crate) fn is_top(&self) -> bool {
+        self.parent.borrow().is_none()
+    }
+
+    pub(crate) fn set_child(&self, index: usize, child: FrameLink) {
+        let mut children = self.children.borrow_mut();
+        if children.len() <= index {
+            children.resize(index + 1, None);
+        }
+        children[index] = Some(child);
+    }
+
+    pub(crate) fn clear_children(&self) {
+        self.children.borrow_mut().clear();
+    }
+
+    fn resolve(&self, target: WindowTarget) -> Option<FrameLink> {
+        match target {
+            WindowTarget::Parent => self.parent.borrow().clone(),
+            WindowTarget::Top => {
+                let mut link = self.parent.borrow().clone()?;
+                loop {
+                    let next = link.state.upgrade()?.parent.borrow().clone();
+                    match next {
+                        Some(next) => link = next,
+                        None => return Some(link),
+                    }
+                }
+            }
+            WindowTarget::Frame(index) => self.children.borrow().get(index).cloned().flatten(),
+            _ => None,
+        }
+    }
+
+    fn access(&self, target: WindowTarget) -> Access {
+        let Some(link) = self.resolve(target) else {
+            return Access::Gone;
+        };
+        let Some(state) = link.state.upgrade() else {
+            return Access::Gone;
+        };
+        // Opaque origins are never same-origin, not even with each other
+        let same = match (&*self.origin.borrow(), &*state.origin.borrow()) {
+            (Some(ours), Some(theirs)) => ours == theirs,
+            _ => false,
+        };
+        if same {
+            Access::Same(link)
+        } else {
+            Access::Cross
+        }
+    }
+}
+
+const FRAMES_JS: &str = r#"
+    window.parent = window;
+    window.top = window;
+    window.frames = window;
+    window.length = 0;
+    window.frameElement = null;
+    var parent = window, top = window, frames = window, frameElement = null;
+
+    function __rustkit_securityError(prop) {
+        return new DOMException("Blocked a frame from accessing property '" + String(prop) +
+            "' of a cross-origin frame.", 'SecurityError');
+    }
+
+    function __rustkit_frameResult(result, prop) {
+        result = JSON.parse(result);
+        if (result.error === 'SecurityError') throw __rustkit_securityError(prop);
+        if (result.error) throw new Error(result.error);
+        return result;
+    }
+
+    function __rustkit_frameNavigate(kind, index, url) {
+        __rustkit_frame_navigate(kind, index, new URL(String(url), window.location.href).href);
+    }
+
+    // Proxy for the value at `path` under another same-origin window
+    function __rustkit_remote(kind, index, path) {
+        return new Proxy(function() {}, {
+            get: function(target, prop) {
+                if (typeof prop !== 'string') return undefined;
+                var full = path.concat([prop]);
+                var result = __rustkit_frameResult(
+                    __rustkit_frame_get(kind, index, JSON.stringify(full)), prop);
+                return result.remote ? __rustkit_remote(kind, index, full) : result.value;
+            },
+            set: function(target, prop, value) {
+                var data = JSON.stringify(value);
+                __rustkit_frameResult(__rustkit_frame_set(kind, index,
+                    JSON.stringify(path.concat([String(prop)])),
+                    data === undefined ? 'null' : data), prop);
+                return true;
+            },
+            apply: function(target, thisArg, args) {
+                var data = JSON.stringify(Array.prototype.slice.call(args));
+                return __rustkit_frameResult(__rustkit_frame_call(kind, index,
+                    JSON.stringify(path), data), path[path.length - 1]).value;
+            }
+        });
+    }
+
+    function __rustkit_frameWindow(kind, index) {
+        var remote = __rustkit_remote(kind, index, []);
+        var messenger = __rustkit_windowProxy(kind, index);
+        var navigate = function(url) { __rustkit_frameNavigate(kind, index, url); };
+        var location = new Proxy({}, {
+            get: function(target, prop) {
+                if (prop === 'replace' || prop === 'assign') return navigate;
+                if (__rustkit_frame_access(kind, index) === 'same') return remote.location[prop];
+                throw __rustkit_securityError('location.' + String(prop));
+            },
+            set: function(target, prop, value) {
+                if (prop !== 'href') throw __rustkit_securityError('location.' + String(prop));
+                navigate(value);
+                return true;
+            }
+        });
+        return new Proxy({}, {
+            get: function(target, prop) {
+                if (prop === 'postMessage') {
+                    return function(message, targetOrigin) {
+                        messenger.postMessage(message, targetOrigin);
+                    };
+                }
+                if (prop === 'closed') return __rustkit_frame_access(kind, index) === 'gone';
+                if (prop === 'location') return location;
+                if (typeof prop !== 'string') return undefined;
+                var access = __rustkit_frame_access(kind, index);
+                if (access === 'gone') return undefined;
+                if (access !== 'same') throw __rustkit_securityError(prop);
+                return remote[prop];
+            },
+            set: function(target, prop, value) {
+                if (prop === 'location') {
+                    navigate(value);
+                    return true;
+                }
+                if (__rustkit_frame_access(kind, index) !== 'same') throw __rustkit_securityError(prop);
+                remote[prop] = value;
+                return true;
+            }
+        });
+    }
+
+    function __rustkit_setParent(nodeId, parentIsTop, sameOrigin) {
+        parent = window.parent = __rustkit_frameWindow('parent', 0);
+        top = window.top = parentIsTop ? window.parent : __rustkit_frameWindow('top', 0);
+        frameElement = window.frameElement = sameOrigin
+            ? __rustkit_remote('parent', 0, ['document', '_nodes', String(nodeId)])
+            : null;
+    }
+
+    function __rustkit_frameAttached(index, nodeId) {
+        var proxy = __rustkit_frameWindow('frame', index);
+        window[index] = proxy;
+        window.length = Math.max(window.length, index + 1);
+
+        var elem = document._nodes[nodeId];
+        if (!elem) return;
+        elem.contentWindow = proxy;
+        Object.defineProperty(elem, 'contentDocument', {
+            get: function() {
+                return __rustkit_frame_access('frame', index) === 'same' ? proxy.document : null;
+            },
+            configurable: true
+        });
+        var setAttribute = elem.setAttribute;
+        elem.setAttribute = function(name, value) {
+            setAttribute.call(this, name, value);
+            if (String(name).toLowerCase() === 'src') {
+                __rustkit_frameNavigate('frame', index, value === '' ? 'about:blank' : value);
+            }
+        };
+        Object.defineProperty(elem, 'src', {
+            get: function() {
+                var src = this.attributes.src;
+                return src ? new URL(src, window.location.href).href : '';
+            },
+            set: function(value) { this.setAttribute('src', String(value)); },
+            configurable: true
+        });
+    }
+
+    function __rustkit_framesDetached() {
+        for (var i = 0; i < window.length; i++) delete window[i];
+        window.length = 0;
+    }
+
+    // Entry points other windows evaluate in this runtime
+    function __rustkit_frameResolve(path) {
+        var value = window;
+        for (var i = 0; i < path.length; i++) {
+            if (value === null || value === undefined) return undefined;
+            value = value[path[i]];
+        }
+        return value;
+    }
+
+    function __rustkit_frameExport(value) {
+        if (value !== null && (typeof value === 'object' || typeof value === 'function')) {
+            return '{"remote":true}';
+        }
+        return value === undefined ? '{}' : JSON.stringify({ value: value });
+    }
+
+    function __rustkit_frameGet(path) {
+        return __rustkit_frameExport(__rustkit_frameResolve(JSON.parse(path)));
+    }
+
+    function __rustkit_frameSet(path, value) {
+        path = JSON.parse(path);
+        __rustkit_frameResolve(path.slice(0, -1))[path[path.length - 1]] = JSON.parse(value);
+        return '{}';
+    }
+
+    function __rustkit_frameCall(path, args) {
+        path = JSON.parse(path);
+        var owner = __rustkit_frameResolve(path.slice(0, -1));
+        var result = owner[path[path.length - 1]].apply(owner, JSON.parse(args));
+        if (typeof result === 'function') return '{}';
+        // Returned objects are copied rather than proxied
+        return result !== null && typeof result === 'object'
+            ? JSON.stringify({ value: JSON.parse(JSON.stringify(result)) })
+            : __rustkit_frameExport(result);
+    }
+"#;
+
+/// Evaluate `function(args...)` in the window `target` names, if script here
+/// may reach it. The result is the JSON envelope `__rustkit_frameResult`
+/// expects.
+fn call_in(
+    state: &FrameState,
+    args: &[String],
+    function: &str,
+    extra: usize,
+) -> Result<JsValue, JsError> {
+    let kind = args.first().map(String::as_str).unwrap_or("");
+    let index = args.get(1).map(String::as_str).unwrap_or("0");
+    let link = match state.access(WindowTarget::from_args(kind, index)?) {
+        Access::Same(link) => link,
+        Access::Cross => {
+            return Ok(JsValue::String(
+                json!({ "error": "SecurityError" }).to_string(),
+            ))
+        }
+        Access::Gone => return Ok(JsValue::String("{}".into())),
+    };
+    let Some(runtime) = link.runtime.upgrade() else {
+        return Ok(JsValue::String("{}".into()));
+    };
+    // The other window is still running the script that called into us
+    let Ok(mut runtime) = runtime.try_borrow_mut() else {
+        return Ok(JsValue::String(
+            json!({ "error": "The window is busy running script" }).to_string(),
+        ));
+    };
+
+    let literals: Vec<String> = (2..2 + extra)
+        .map(|i| Value::from(args.get(i).map(String::as_str).unwrap_or("null")).to_string())
+        .collect();
+    let script = format!("{}({});", function, literals.join(", "));
+    Ok(JsValue::String(match runtime.evaluate_script(&script) {
+        Ok(JsValue::String(result)) => result,
+        Ok(_) => "{}".into(),
+        Err(e) => json!({ "error": e.to_string() }).to_string(),
+    }))
+}
+
+/// Register the frame natives and the top-level `parent`/`top`/`frames`.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<FrameState>,
+    popups: Rc<PopupState>,
+) -> Result<(), JsError> {
+    let access_state = state.clone();
+    runtime.register_function("__rustkit_frame_access", 2, move |args| {
+        let kind = args.first().map(String::as_str).unwrap_or("");
+        let index = args.get(1).map(String::as_str).unwrap_or("0");
+        let access = match access_state.access(WindowTarget::from_args(kind, index)?) {
+            Access::Same(_) => "same",
+            Access::Cross => "cross",
+            Access::Gone => "gone",
+        };
+        Ok(JsValue::String(access.into()))
+    })?;
+
+    let get_state = state.clone();
+    runtime.register_function("__rustkit_frame_get", 3, move |args| {
+        call_in(&get_state, args, "__rustkit_frameGet", 1)
+    })?;
+
+    let set_state = state.clone();
+    runtime.register_function("__rustkit_frame_set", 4, move |args| {
+        call_in(&set_state, args, "__rustkit_frameSet", 2)
+    })?;
+
+    runtime.register_function("__rustkit_frame_call", 4, move |args| {
+        call_in(&state, args, "__rustkit_frameCall", 2)
+    })?;
+
+    runtime.register_function("__rustkit_frame_navigate", 3, move |args| {
+        let [kind, index, url] = args else {
+            return Err(JsError::TypeError("navigate expects 3 arguments".into()));
+        };
+        let url = Url::parse(url).map_err(|e| JsError::TypeError(format!("Invalid URL: {}", e)))?;
+        popups.push_request(WindowRequest::Navigate {
+            target: WindowTarget::from_args(kind, index)?,
+            url,
+        });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(FRAMES_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DomBindings, WindowRequest, WindowTarget};
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::rc::Rc;
+    use url::Url;
+
+    const PARENT_HTML: &str = r#"<html><body>
+        <iframe id="child" srcdoc="&lt;p&gt;inner&lt;/p&gt;"></iframe>
+    </body></html>"#;
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    /// A parent at `https://app.example/` with one frame whose document has
+    /// the given origin.
+    fn frame_tree(child_origin: Option<&str>) -> (DomBindings, DomBindings) {
+        let parent = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(Document::parse_html(PARENT_HTML).unwrap());
+        let element = document.get_element_by_id("child").unwrap().id;
+        parent.set_document(document).unwrap();
+        parent
+            .set_location(&Url::parse("https://app.example/").unwrap())
+            .unwrap();
+
+        let child = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        child
+            .set_document(Rc::new(Document::parse_html("<p>inner</p>").unwrap()))
+            .unwrap();
+        child
+            .set_location(&Url::parse("about:srcdoc").unwrap())
+            .unwrap();
+        child.set_origin(child_origin.map(String::from));
+        parent.attach_frame(0, element, &child).unwrap();
+        (parent, child)
+    }
+
+    #[test]
+    fn test_srcdoc_frame_reads_parent_storage() {
+        let (parent, child) = frame_tree(Some("https://app.example"));
+        parent
+            .evaluate("window.localStorage.setItem('k', 'from-parent');")
+            .unwrap();
+
+        let result = eval_string(
+            &child,
+            "parent.document.title = 'set-by-frame';
+             [parent.localStorage.getItem('k'), top === parent, frameElement.id,
+              parent.frames.length].join('|')",
+        );
+        assert_eq!(result, "from-parent|true|child|1");
+        assert_eq!(eval_string(&parent, "document.title"), "set-by-frame");
+        assert!(matches!(
+            parent
+                .evaluate("document.getElementById('child').contentWindow.location.href")
+                .unwrap(),
+            JsValue::String(href) if href == "about:srcdoc"
+        ));
+    }
+
+    #[test]
+    fn test_cross_origin_frame_proxy_is_restricted() {
+        let (parent, child) = frame_tree(None);
+        let result = eval_string(
+            &child,
+            "var errors = [];
+             try { parent.localStorage; } catch (e) { errors.push(e.name); }
+             try { parent.document.title = 'x'; } catch (e) { errors.push(e.name); }
+             try { parent.location.href; } catch (e) { errors.push(e.name); }
+             parent.postMessage({ ready: true }, '*');
+             parent.location.href = 'https://other.example/';
+             [errors.join(' '), frameElement === null, parent.closed].join('|')",
+        );
+        assert_eq!(
+            result,
+            "SecurityError SecurityError SecurityError|true|false"
+        );
+        assert!(matches!(
+            parent
+                .evaluate("document.getElementById('child').contentDocument === null")
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+
+        let requests = child.take_window_requests();
+        assert_eq!(
+            requests[0],
+            WindowRequest::PostMessage {
+                target: WindowTarget::Parent,
+                data: r#"{"ready":true}"#.to_string(),
+                target_origin: "*".to_string(),
+            }
+        );
+        assert!(matches!(
+            &requests[1],
+            WindowRequest::Navigate {
+                target: WindowTarget::Parent,
+                ..
+            }
+        ));
+
+        parent
+            .evaluate("document.getElementById('child').src = 'https://app.example/next';")
+            .unwrap();
+        assert_eq!(
+            parent.take_window_requests(),
+            vec![WindowRequest::Navigate {
+                target: WindowTarget::Frame(0),
+                url: Url::parse("https://app.example/next").unwrap(),
+            }]
+        );
+
+        parent.detach_frames().unwrap();
+        assert!(matches!(
+            parent
+                .evaluate(
+                    "frames.length === 0 && document.getElementById('child').contentWindow.closed"
+                )
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+    }
+}
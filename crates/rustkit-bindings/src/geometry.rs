@@ -0,0 +1,1041 @@
+//! Element geometry bindings.
+//!
+//! `getBoundingClientRect()`, `getClientRects()`, and the `offset*`,
+//! `client*` and `scroll*` properties read the per-node geometry the engine
+//! publishes after each layout. When script has changed inline styles since
+//! then, the first read runs a synchronous layout through the engine's
+//! [`LayoutProvider`].
+//!
+//! Scrolled containers keep their content from jumping across layouts: the
+//! first fully visible box is picked as an anchor before new geometry is
+//! installed, and the scroll offset is moved by however far the anchor moved.
+
+use crate::DomMutation;
+use rustkit_dom::{Document, Node, NodeId, NodeType};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use rustkit_layout::{Dimensions, NodeGeometry, Rect, ScrollState};
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::{debug, trace};
+
+/// Per-node layout geometry, keyed by DOM node.
+pub type GeometryMap = HashMap<NodeId, NodeGeometry>;
+
+/// Lays out a document in a viewport and returns its node geometry.
+pub type LayoutProvider = Box<dyn Fn(&Document, (f32, f32)) -> GeometryMap>;
+
+/// A box whose position a scroll container keeps across a layout.
+struct ScrollAnchor {
+    container: NodeId,
+    node: NodeId,
+    /// Top of the anchor's border box before the layout.
+    top: f32,
+    /// The container's vertical offset and content height before the layout.
+    scroll_y: f32,
+    content_height: f32,
+}
+
+/// First border box of a node.
+fn border_box(geometry: &GeometryMap, node_id: NodeId) -> Option<Rect> {
+    geometry
+        .get(&node_id)?
+        .fragments
+        .first()
+        .map(|d| d.border_box())
+}
+
+/// Client and scroll sizes of a node.
+struct ScrollMetrics {
+    client: (f32, f32),
+    content: (f32, f32),
+}
+
+/// Layout state shared between [`crate::DomBindings`] and the native functions.
+#[derive(Default)]
+pub(crate) struct GeometryState {
+    document: RefCell<Option<Rc<Document>>>,
+    geometry: RefCell<Rc<GeometryMap>>,
+    viewport: Cell<(f32, f32)>,
+    provider: RefCell<Option<LayoutProvider>>,
+    /// Geometry is stale and must be recomputed before the next read.
+    dirty: Cell<bool>,
+    /// Styles changed since the engine last laid out the view.
+    needs_relayout: Cell<bool>,
+    /// Scroll positions; the viewport scroller is keyed by the document element.
+    scroll: RefCell<HashMap<NodeId, ScrollState>>,
+    /// The user scrolled since the last layout, so anchoring is suppressed.
+    user_scrolled: Cell<bool>,
+    /// Scroll offsets adjusted to keep an anchor in place.
+    anchor_adjustments: Cell<u64>,
+    /// Whether script changes to the document are queued in `mutations`.
+    record_mutations: Cell<bool>,
+    mutations: RefCell<Vec<DomMutation>>,
+}
+
+impl GeometryState {
+    pub(crate) fn set_document(&self, document: Rc<Document>) {
+        *self.document.borrow_mut() = Some(document);
+        *self.geometry.borrow_mut() = Rc::default();
+        self.scroll.borrow_mut().clear();
+        self.dirty.set(true);
+    }
+
+    pub(crate) fn set_provider(&self, provider: LayoutProvider) {
+        *self.provider.borrow_mut() = Some(provider);
+    }
+
+    /// Install geometry from a layout performed by the engine.
+    pub(crate) fn set_layout(&self, geometry: Rc<GeometryMap>, viewport: (f32, f32)) {
+        let anchors = self.select_anchors();
+        *self.geometry.borrow_mut() = geometry;
+        self.viewport.set(viewport);
+        self.dirty.set(false);
+        self.needs_relayout.set(false);
+        self.clamp_scroll();
+        self.restore_anchors(&anchors);
+    }
+
+    pub(crate) fn note_user_scroll(&self) {
+        self.user_scrolled.set(true);
+    }
+
+    pub(crate) fn anchor_adjustments(&self) -> u64 {
+        self.anchor_adjustments.get()
+    }
+
+    pub(crate) fn document(&self) -> Option<Rc<Document>> {
+        self.document.borrow().clone()
+    }
+
+    pub(crate) fn needs_relayout(&self) -> bool {
+        self.needs_relayout.get()
+    }
+
+    pub(crate) fn scroll_position(&self, node_id: NodeId) -> (f32, f32) {
+        self.scroll
+            .borrow()
+            .get(&node_id)
+            .map(|s| (s.scroll_x, s.scroll_y))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_mutation_recording(&self, enabled: bool) {
+        self.record_mutations.set(enabled);
+        if !enabled {
+            self.mutations.borrow_mut().clear();
+        }
+    }
+
+    /// Queue a change script made to the document, if anyone is listening.
+    pub(crate) fn record_mutation(&self, mutation: DomMutation) {
+        if self.record_mutations.get() {
+            self.mutations.borrow_mut().push(mutation);
+        }
+    }
+
+    pub(crate) fn take_mutations(&self) -> Vec<DomMutation> {
+        std::mem::take(&mut *self.mutations.borrow_mut())
+    }
+
+    /// The document changed; geometry must be recomputed.
+    pub(crate) fn mark_dirty(&self) {
+        self.dirty.set(true);
+        self.needs_relayout.set(true);
+    }
+
+    /// Recompute geometry synchronously if styles changed since the last layout.
+    fn flush(&self) {
+        if !self.dirty.get() {
+            return;
+        }
+        let Some(document) = self.document.borrow().clone() else {
+            return;
+        };
+        let provider = self.provider.borrow();
+        let Some(provider) = provider.as_ref() else {
+            return;
+        };
+
+        trace!("Flushing layout for geometry read");
+        let anchors = self.select_anchors();
+        *self.geometry.borrow_mut() = Rc::new(provider(&document, self.viewport.get()));
+        self.dirty.set(false);
+        self.clamp_scroll();
+        self.restore_anchors(&anchors);
+    }
+
+    /// Pick an anchor in every vertically scrolled container.
+    fn select_anchors(&self) -> Vec<ScrollAnchor> {
+        let Some(document) = self.document() else {
+            return Vec::new();
+        };
+        let geometry = self.geometry.borrow();
+        let scroll = self.scroll.borrow();
+
+        let mut anchors = Vec::new();
+        for (&container, state) in scroll.iter().filter(|(_, s)| s.scroll_y > 0.0) {
+            let (Some(node), Some(metrics)) = (
+                document.get_node(container),
+                self.metrics(&document, container),
+            ) else {
+                continue;
+            };
+            if geometry.get(&container).is_some_and(|g| g.anchor_excluded) {
+                continue;
+            }
+
+            // The part of the container's content currently scrolled into view
+            let origin = match Self::is_root(&document, container) {
+                true => (0.0, 0.0),
+                false => geometry
+                    .get(&container)
+                    .and_then(|g| g.fragments.first())
+                    .map(|d| d.padding_box())
+                    .map_or((0.0, 0.0), |r| (r.x, r.y)),
+            };
+            let visible = Rect::new(
+                origin.0 + state.scroll_x,
+                origin.1 + state.scroll_y,
+                metrics.client.0,
+                metrics.client.1,
+            );
+
+            let Some(anchor) = Self::select_anchor(&geometry, &scroll, &node, &visible) else {
+                continue;
+            };
+            let Some(rect) = border_box(&geometry, anchor) else {
+                continue;
+            };
+            trace!(?container, ?anchor, "Selected scroll anchor");
+            anchors.push(ScrollAnchor {
+                container,
+                node: anchor,
+                top: rect.y,
+                scroll_y: state.scroll_y,
+                content_height: metrics.content.1,
+            });
+        }
+        anchors
+    }
+
+    /// The first fully visible descendant of `node` in tree order, or if
+    /// there is none the deepest partially visible one.
+    fn select_anchor(
+        geometry: &GeometryMap,
+        scroll: &HashMap<NodeId, ScrollState>,
+        node: &Rc<Node>,
+        visible: &Rect,
+    ) -> Option<NodeId> {
+        Self::first_visible(geometry, scroll, node, visible, true)
+            .or_else(|| Self::first_visible(geometry, scroll, node, visible, false))
+    }
+
+    /// Search `node`'s descendants for a fully (or partially) visible
+    /// candidate. Excluded subtrees are skipped; nested scroll containers are
+    /// candidates but are not searched.
+    fn first_visible(
+        geometry: &GeometryMap,
+        scroll: &HashMap<NodeId, ScrollState>,
+        node: &Rc<Node>,
+        visible: &Rect,
+        fully: bool,
+    ) -> Option<NodeId> {
+        for child in node.children() {
+            let Some(entry) = geometry.get(&child.id) else {
+                continue;
+            };
+            let Some(rect) = border_box(geometry, child.id) else {
+                continue;
+            };
+            let intersects = rect.y < visible.bottom()
+                && rect.bottom() > visible.y
+                && rect.x < visible.right()
+                && rect.right() > visible.x;
+            if entry.anchor_excluded || rect.height <= 0.0 || !intersects {
+                continue;
+            }
+
+            let contained = rect.y >= visible.y
+                && rect.bottom() <= visible.bottom()
+                && rect.x >= visible.x
+                && rect.right() <= visible.right();
+            if fully && contained {
+                return Some(child.id);
+            }
+            if !scroll.contains_key(&child.id) {
+                if let Some(found) = Self::first_visible(geometry, scroll, &child, visible, fully) {
+                    return Some(found);
+                }
+            }
+            if !fully {
+                return Some(child.id);
+            }
+        }
+        None
+    }
+
+    /// Move each container's scroll offset by how far its anchor moved.
+    ///
+    /// Skipped after user scrolling, and when the anchor moved further than
+    /// the content grew or shrank, which means it moved for another reason.
+    fn restore_anchors(&self, anchors: &[ScrollAnchor]) {
+        if self.user_scrolled.replace(false) {
+            return;
+        }
+        let Some(document) = self.document() else {
+            return;
+        };
+        let geometry = self.geometry.borrow().clone();
+
+        for anchor in anchors {
+            let (Some(rect), Some(metrics)) = (
+                border_box(&geometry, anchor.node),
+                self.metrics(&document, anchor.container),
+            ) else {
+                continue;
+            };
+            let shift = rect.y - anchor.top;
+            let growth = metrics.content.1 - anchor.content_height;
+            if shift.abs() < 0.5 || shift.abs() > growth.abs() + 0.5 {
+                continue;
+            }
+
+            let mut scroll = self.scroll.borrow_mut();
+            let Some(state) = scroll.get_mut(&anchor.container) else {
+                continue;
+            };
+            debug!(container = ?anchor.container, shift, "Adjusting scroll for anchor");
+            state.scroll_to(state.scroll_x, anchor.scroll_y + shift);
+            self.anchor_adjustments
+                .set(self.anchor_adjustments.get() + 1);
+        }
+    }
+
+    fn is_root(document: &Document, node_id: NodeId) -> bool {
+        document
+            .document_element()
+            .is_some_and(|html| html.id == node_id)
+    }
+
+    fn metrics(&self, document: &Document, node_id: NodeId) -> Option<ScrollMetrics> {
+        let geometry = self.geometry.borrow();
+        let entry = geometry.get(&node_id)?;
+        let first = entry.fragments.first()?;
+
+        // The root's client area is the viewport
+        let client = if Self::is_root(document, node_id) {
+            self.viewport.get()
+        } else {
+            let padding_box = first.padding_box();
+            (
+                (padding_box.width - entry.scrollbars.0).max(0.0),
+                (padding_box.height - entry.scrollbars.1).max(0.0),
+            )
+        };
+        let content = (
+            client.0.max(entry.overflow.width),
+            client.1.max(entry.overflow.height),
+        );
+
+        Some(ScrollMetrics { client, content })
+    }
+
+    /// Re-clamp scroll positions after the content or viewport changed.
+    fn clamp_scroll(&self) {
+        let Some(document) = self.document.borrow().clone() else {
+            return;
+        };
+        self.scroll.borrow_mut().retain(|&node_id, state| {
+            let Some(metrics) = self.metrics(&document, node_id) else {
+                return false;
+            };
+            state.set_viewport_size(metrics.client.0, metrics.client.1);
+            state.set_content_size(metrics.content.0, metrics.content.1);
+            true
+        });
+    }
+
+    /// Total scroll offset applied to a node's boxes by its scroll containers.
+    fn scroll_offset(&self, document: &Document, node: &Rc<Node>) -> (f32, f32) {
+        // The root's own boxes move with the viewport; other nodes only move
+        // with their ancestors
+        let mut current = match Self::is_root(document, node.id) {
+            true => Some(node.clone()),
+            false => node.parent(),
+        };
+
+        let scroll = self.scroll.borrow();
+        let (mut x, mut y) = (0.0, 0.0);
+        while let Some(ancestor) = current {
+            if let Some(state) = scroll.get(&ancestor.id) {
+                x += state.scroll_x;
+                y += state.scroll_y;
+            }
+            current = ancestor.parent();
+        }
+        (x, y)
+    }
+
+    /// Describe a node's geometry as JSON for the JS layer.
+    fn describe(&self, node_id: NodeId) -> Option<serde_json::Value> {
+        let document = self.document.borrow().clone()?;
+        let node = document.get_node(node_id)?;
+        let metrics = self.metrics(&document, node_id)?;
+        let geometry = self.geometry.borrow().clone();
+        let entry = geometry.get(&node_id)?;
+        let first = entry.fragments.first()?;
+
+        let (scroll_x, scroll_y) = self.scroll_offset(&document, &node);
+        let rects: Vec<_> = entry
+            .fragments
+            .iter()
+            .map(|d| {
+                let r = d.border_box();
+                [r.x - scroll_x, r.y - scroll_y, r.width, r.height]
+            })
+            .collect();
+
+        // Without positioned ancestors the offset parent is the body, whose
+        // offsets are in document coordinates
+        let body = document.body();
+        let is_body = body.as_ref().is_some_and(|b| b.id == node_id);
+        let offset_parent = match is_body || Self::is_root(&document, node_id) {
+            true => None,
+            false => body.map(|b| b.id.raw()),
+        };
+
+        let border_box = first.border_box();
+        let position = self.scroll_position(node_id);
+        Some(json!({
+            "rects": rects,
+            "offset": [border_box.x, border_box.y, border_box.width, border_box.height],
+            "offsetParent": offset_parent,
+            "client": [first.border.left, first.border.top, metrics.client.0, metrics.client.1],
+            "scroll": [position.0, position.1, metrics.content.0, metrics.content.1],
+        }))
+    }
+
+    /// The first box of a node, laying out first if styles changed.
+    pub(crate) fn first_box(&self, node_id: NodeId) -> Option<Dimensions> {
+        self.flush();
+        self.geometry
+            .borrow()
+            .get(&node_id)?
+            .fragments
+            .first()
+            .cloned()
+    }
+
+    /// Bounding border box of a node's boxes in viewport coordinates, as
+    /// `getBoundingClientRect()` reports it.
+    pub(crate) fn client_bounds(&self, node_id: NodeId) -> Option<Rect> {
+        self.flush();
+        let document = self.document()?;
+        let node = document.get_node(node_id)?;
+        let geometry = self.geometry.borrow().clone();
+        let bounds = geometry
+            .get(&node_id)?
+            .fragments
+            .iter()
+            .map(|d| d.border_box())
+            .reduce(|a, b| {
+                let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+                Rect::new(
+                    x,
+                    y,
+                    a.right().max(b.right()) - x,
+                    a.bottom().max(b.bottom()) - y,
+                )
+            })?;
+        let (scroll_x, scroll_y) = self.scroll_offset(&document, &node);
+        Some(Rect::new(
+            bounds.x - scroll_x,
+            bounds.y - scroll_y,
+            bounds.width,
+            bounds.height,
+        ))
+    }
+
+    /// Whether a node clips its content: it has been scrolled or has
+    /// scrollbars.
+    pub(crate) fn clips_content(&self, node_id: NodeId) -> bool {
+        self.scroll.borrow().contains_key(&node_id)
+            || self
+                .geometry
+                .borrow()
+                .get(&node_id)
+                .is_some_and(|g| g.scrollbars != (0.0, 0.0))
+    }
+
+    /// The client area of a scroll container in viewport coordinates; the
+    /// root's is the viewport.
+    pub(crate) fn client_area(&self, node_id: NodeId) -> Option<Rect> {
+        self.flush();
+        let document = self.document()?;
+        if Self::is_root(&document, node_id) {
+            let (width, height) = self.viewport.get();
+            return Some(Rect::new(0.0, 0.0, width, height));
+        }
+        let node = document.get_node(node_id)?;
+        let metrics = self.metrics(&document, node_id)?;
+        let padding_box = self
+            .geometry
+            .borrow()
+            .get(&node_id)?
+            .fragments
+            .first()?
+            .padding_box();
+        let (scroll_x, scroll_y) = self.scroll_offset(&document, &node);
+        Some(Rect::new(
+            padding_box.x - scroll_x,
+            padding_box.y - scroll_y,
+            metrics.client.0,
+            metrics.client.1,
+        ))
+    }
+
+    /// Every scrolled node and its offset.
+    pub(crate) fn scroll_positions(&self) -> Vec<(NodeId, (f32, f32))> {
+        self.scroll
+            .borrow()
+            .iter()
+            .filter(|(_, s)| s.scroll_x != 0.0 || s.scroll_y != 0.0)
+            .map(|(&node_id, s)| (node_id, (s.scroll_x, s.scroll_y)))
+            .collect()
+    }
+
+    /// Scroll a node, clamping to its scrollable range.
+    pub(crate) fn scroll_to(&self, node_id: NodeId, x: f32, y: f32) {
+        self.flush();
+        let Some(document) = self.document.borrow().clone() else {
+            return;
+        };
+        let Some(metrics) = self.metrics(&document, node_id) else {
+            return;
+        };
+
+        let mut scroll = self.scroll.borrow_mut();
+        let state = scroll
+            .entry(node_id)
+            .or_insert_with(|| ScrollState::new(metrics.client.0, metrics.client.1));
+        state.set_viewport_size(metrics.client.0, metrics.client.1);
+        state.set_content_size(metrics.content.0, metrics.content.1);
+        state.scroll_to(x, y);
+    }
+}
+
+pub(crate) fn node_id_arg(args: &[String]) -> Result<NodeId, JsError> {
+    args.first()
+        .and_then(|id| id.parse().ok())
+        .map(NodeId::new)
+        .ok_or_else(|| JsError::TypeError("Expected a node id".into()))
+}
+
+fn number_arg(args: &[String], index: usize) -> f32 {
+    args.get(index)
+        .and_then(|n| n.parse::<f32>().ok())
+        .filter(|n| n.is_finite())
+        .unwrap_or(0.0)
+}
+
+/// JS side: DOMRect, inline style objects, and element geometry accessors.
+const GEOMETRY_JS: &str = r#"
+    function DOMRect(x, y, width, height) {
+        this.x = x || 0;
+        this.y = y || 0;
+        this.width = width || 0;
+        this.height = height || 0;
+    }
+    Object.defineProperties(DOMRect.prototype, {
+        top: { get: function() { return Math.min(this.y, this.y + this.height); } },
+        right: { get: function() { return Math.max(this.x, this.x + this.width); } },
+        bottom: { get: function() { return Math.max(this.y, this.y + this.height); } },
+        left: { get: function() { return Math.min(this.x, this.x + this.width); } }
+    });
+    DOMRect.prototype.toJSON = function() {
+        return {
+            x: this.x, y: this.y, width: this.width, height: this.height,
+            top: this.top, right: this.right, bottom: this.bottom, left: this.left
+        };
+    };
+    window.DOMRect = DOMRect;
+
+    // CSSStyleDeclaration for an element; writes go to the engine as cssText.
+    // Script properties map to CSS names like those of computed styles.
+    function __rustkit_createStyle(elem, cssText) {
+        var decls = {};
+        // Custom property names are case-sensitive
+        function propertyName(name) {
+            name = String(name).trim();
+            return name.indexOf('--') === 0 ? name : name.toLowerCase();
+        }
+        function parse(text) {
+            decls = {};
+            String(text).split(';').forEach(function(decl) {
+                var colon = decl.indexOf(':');
+                if (colon <= 0) return;
+                var name = propertyName(decl.slice(0, colon));
+                var value = decl.slice(colon + 1).trim();
+                if (name && value) decls[name] = value;
+            });
+        }
+        function serialize() {
+            return Object.keys(decls).map(function(name) {
+                return name + ': ' + decls[name];
+            }).join('; ');
+        }
+        function commit() {
+            if (elem._nodeId !== undefined) __rustkit_set_style(elem._nodeId, serialize());
+        }
+        parse(cssText);
+
+        var target = {
+            getPropertyValue: function(name) { return decls[propertyName(name)] || ''; },
+            setProperty: function(name, value) {
+                name = propertyName(name);
+                if (value === '' || value === null || value === undefined) {
+                    delete decls[name];
+                } else {
+                    decls[name] = String(value);
+                }
+                commit();
+            },
+            removeProperty: function(name) {
+                name = propertyName(name);
+                var old = decls[name] || '';
+                delete decls[name];
+                commit();
+                return old;
+            }
+        };
+        Object.defineProperty(target, 'cssText', {
+            get: serialize,
+            set: function(text) { parse(text); commit(); }
+        });
+        Object.defineProperty(target, 'length', {
+            get: function() { return Object.keys(decls).length; }
+        });
+
+        return new Proxy(target, {
+            get: function(t, prop) {
+                if (typeof prop !== 'string' || prop in t) return t[prop];
+                return decls[window.__rustkit_cssPropertyName(prop)] || '';
+            },
+            set: function(t, prop, value) {
+                if (prop === 'cssText') {
+                    t.cssText = value;
+                } else {
+                    t.setProperty(window.__rustkit_cssPropertyName(prop), value);
+                }
+                return true;
+            }
+        });
+    }
+
+    // Geometry accessors; detached elements report empty geometry
+    function __rustkit_defineGeometry(elem) {
+        function geometry() {
+            if (elem._nodeId === undefined) return null;
+            return JSON.parse(__rustkit_geometry(elem._nodeId));
+        }
+        function metric(group, index) {
+            return {
+                get: function() {
+                    var g = geometry();
+                    return g ? Math.round(g[group][index]) : 0;
+                },
+                configurable: true
+            };
+        }
+        function scrollOffset(index) {
+            return {
+                get: function() {
+                    var g = geometry();
+                    return g ? g.scroll[index] : 0;
+                },
+                set: function(value) {
+                    if (elem._nodeId === undefined) return;
+                    var x = index === 0 ? Number(value) : elem.scrollLeft;
+                    var y = index === 1 ? Number(value) : elem.scrollTop;
+                    __rustkit_set_scroll(elem._nodeId, x, y);
+                },
+                configurable: true
+            };
+        }
+
+        elem.getBoundingClientRect = function() {
+            var g = geometry();
+            if (!g || g.rects.length === 0) return new DOMRect(0, 0, 0, 0);
+            var left = Infinity, top = Infinity, right = -Infinity, bottom = -Infinity;
+            g.rects.forEach(function(r) {
+                left = Math.min(left, r[0]);
+                top = Math.min(top, r[1]);
+                right = Math.max(right, r[0] + r[2]);
+                bottom = Math.max(bottom, r[1] + r[3]);
+            });
+            return new DOMRect(left, top, right - left, bottom - top);
+        };
+        elem.getClientRects = function() {
+            var g = geometry();
+            var rects = (g ? g.rects : []).map(function(r) {
+                return new DOMRect(r[0], r[1], r[2], r[3]);
+            });
+            rects.item = function(index) { return this[index] || null; };
+            return rects;
+        };
+
+        Object.defineProperties(elem, {
+            offsetLeft: metric('offset', 0),
+            offsetTop: metric('offset', 1),
+            offsetWidth: metric('offset', 2),
+            offsetHeight: metric('offset', 3),
+            offsetParent: {
+                get: function() {
+                    var g = geometry();
+                    if (!g || g.offsetParent === null) return null;
+                    return document._nodes[g.offsetParent] || null;
+                },
+                configurable: true
+            },
+            clientLeft: metric('client', 0),
+            clientTop: metric('client', 1),
+            clientWidth: metric('client', 2),
+            clientHeight: metric('client', 3),
+            scrollLeft: scrollOffset(0),
+            scrollTop: scrollOffset(1),
+            scrollWidth: metric('scroll', 2),
+            scrollHeight: metric('scroll', 3)
+        });
+        return elem;
+    }
+
+    var __rustkit_createElement = document.createElement;
+    document.createElement = function(tagName) {
+        return __rustkit_defineGeometry(__rustkit_createElement.call(document, tagName));
+    };
+
+    // Wrap parsed elements so script can reach them by id
+    document._nodes = {};
+    document._adopt = function(tree) {
+        tree.elements.forEach(function(node) {
+            var elem = document.createElement(node.tagName);
+            elem._nodeId = node.nodeId;
+            elem.id = node.attributes.id || '';
+            elem.className = node.attributes['class'] || '';
+            elem.attributes = node.attributes;
+            elem.style = __rustkit_createStyle(elem, node.attributes.style || '');
+            document._nodes[node.nodeId] = elem;
+            if (elem.id) document._elements[elem.id] = elem;
+        });
+        document.documentElement = document._nodes[tree.documentElement] || null;
+        document.head = document._nodes[tree.head] || null;
+        document.body = document._nodes[tree.body] || null;
+        document.scrollingElement = document.documentElement;
+    };
+"#;
+
+/// Register the geometry natives and element geometry accessors.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<GeometryState>) -> Result<(), JsError> {
+    let geometry_state = state.clone();
+    runtime.register_function("__rustkit_geometry", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        geometry_state.flush();
+        let geometry = geometry_state.describe(node_id);
+        Ok(JsValue::String(
+            geometry.map_or_else(|| "null".to_string(), |g| g.to_string()),
+        ))
+    })?;
+
+    let style_state = state.clone();
+    runtime.register_function("__rustkit_set_style", 2, move |args| {
+        let node_id = node_id_arg(args)?;
+        let document = style_state.document.borrow().clone();
+        if let Some(node) = document.and_then(|d| d.get_node(node_id)) {
+            node.set_inline_style(args.get(1).map(String::as_str).unwrap_or(""));
+            style_state.mark_dirty();
+            style_state.record_mutation(DomMutation::Attributes {
+                target: node_id,
+                name: "style".to_string(),
+            });
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.register_function("__rustkit_set_scroll", 3, move |args| {
+        let node_id = node_id_arg(args)?;
+        state.scroll_to(node_id, number_arg(args, 1), number_arg(args, 2));
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(GEOMETRY_JS)?;
+    Ok(())
+}
+
+/// Serialize the elements script can reach (those with ids, iframes, the
+/// document element, head, and body) for `document._adopt`.
+pub(crate) fn adopt_script(document: &Document) -> String {
+    let mut elements = Vec::new();
+    let document_element = document.document_element().map(|n| n.id);
+    let head = document.head().map(|n| n.id);
+    let body = document.body().map(|n| n.id);
+
+    document.traverse(|node| {
+        let NodeType::Element {
+            tag_name,
+            attributes,
+            ..
+        } = &node.node_type
+        else {
+            return;
+        };
+        let well_known = [document_element, head, body].contains(&Some(node.id));
+        if well_known || attributes.contains_key("id") || tag_name.eq_ignore_ascii_case("iframe") {
+            elements.push(json!({
+                "nodeId": node.id.raw(),
+                "tagName": tag_name,
+                "attributes": attributes,
+            }));
+        }
+    });
+
+    let tree = json!({
+        "elements": elements,
+        "documentElement": document_element.map(|id| id.raw()),
+        "head": head.map(|id| id.raw()),
+        "body": body.map(|id| id.raw()),
+    });
+    format!("document._adopt({});", tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+    use rustkit_layout::{Dimensions, Rect};
+    use std::cell::Cell;
+
+    const HTML: &str = r#"<html><body>
+        <div id="scroller"><div id="item"></div></div>
+    </body></html>"#;
+
+    fn node(x: f32, y: f32, width: f32, height: f32, overflow_height: f32) -> NodeGeometry {
+        NodeGeometry {
+            fragments: vec![Dimensions {
+                content: Rect::new(x, y, width, height),
+                ..Default::default()
+            }],
+            overflow: Rect::new(x, y, width, overflow_height),
+            scrollbars: (0.0, 0.0),
+            anchor_excluded: false,
+        }
+    }
+
+    /// Fixed layout: a 100x100 scroller with 300px of content at (8, 8).
+    fn layout(document: &Document) -> GeometryMap {
+        let id = |name: &str| document.get_element_by_id(name).unwrap().id;
+        let mut geometry = HashMap::new();
+        geometry.insert(
+            document.document_element().unwrap().id,
+            node(0.0, 0.0, 800.0, 316.0, 316.0),
+        );
+        geometry.insert(
+            document.body().unwrap().id,
+            node(8.0, 8.0, 784.0, 300.0, 300.0),
+        );
+        let mut scroller = node(8.0, 8.0, 100.0, 100.0, 300.0);
+        scroller.scrollbars = (12.0, 0.0);
+        geometry.insert(id("scroller"), scroller);
+        geometry.insert(id("item"), node(8.0, 58.0, 80.0, 20.0, 20.0));
+        geometry
+    }
+
+    fn bindings() -> (DomBindings, Rc<Cell<usize>>) {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(Document::parse_html(HTML).unwrap());
+        bindings.set_document(document.clone()).unwrap();
+
+        let layouts = Rc::new(Cell::new(0));
+        let counter = layouts.clone();
+        bindings.set_layout_provider(move |document, _| {
+            counter.set(counter.get() + 1);
+            layout(document)
+        });
+        bindings.set_layout(Rc::new(layout(&document)), (800.0, 600.0));
+        (bindings, layouts)
+    }
+
+    fn eval_number(bindings: &DomBindings, script: &str) -> f64 {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::Number(n) => n,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_scroll_geometry() {
+        let (bindings, _) = bindings();
+        bindings
+            .evaluate("var scroller = document.getElementById('scroller');")
+            .unwrap();
+
+        assert_eq!(eval_number(&bindings, "scroller.clientWidth"), 88.0);
+        assert_eq!(eval_number(&bindings, "scroller.scrollHeight"), 300.0);
+
+        // Writes clamp to the scrollable range and move descendants
+        bindings.evaluate("scroller.scrollTop = 500;").unwrap();
+        assert_eq!(eval_number(&bindings, "scroller.scrollTop"), 200.0);
+        assert_eq!(
+            eval_number(
+                &bindings,
+                "document.getElementById('item').getBoundingClientRect().top"
+            ),
+            -142.0
+        );
+        assert_eq!(
+            eval_number(&bindings, "document.getElementById('item').offsetTop"),
+            58.0
+        );
+        assert_eq!(
+            eval_number(&bindings, "scroller.getBoundingClientRect().top"),
+            8.0
+        );
+
+        let scroller = bindings.evaluate("scroller._nodeId").unwrap();
+        let JsValue::Number(scroller) = scroller else {
+            panic!("expected node id");
+        };
+        assert_eq!(
+            bindings.scroll_position(NodeId::new(scroller as usize)),
+            (0.0, 200.0)
+        );
+    }
+
+    #[test]
+    fn test_offset_parent() {
+        let (bindings, _) = bindings();
+
+        let result = bindings
+            .evaluate(
+                "document.getElementById('item').offsetParent === document.body \
+                 && document.body.offsetParent === null \
+                 && document.createElement('div').offsetParent === null",
+            )
+            .unwrap();
+        assert!(matches!(result, JsValue::Boolean(true)));
+    }
+
+    #[test]
+    fn test_style_write_flushes_layout() {
+        let (bindings, layouts) = bindings();
+
+        // Reads of clean geometry don't lay out
+        eval_number(&bindings, "document.getElementById('item').offsetWidth");
+        assert_eq!(layouts.get(), 0);
+        assert!(!bindings.needs_relayout());
+
+        bindings
+            .evaluate("document.getElementById('item').style.height = '40px';")
+            .unwrap();
+        assert!(bindings.needs_relayout());
+
+        eval_number(&bindings, "document.getElementById('item').offsetWidth");
+        eval_number(&bindings, "document.getElementById('item').offsetHeight");
+        assert_eq!(layouts.get(), 1);
+
+        let result = bindings
+            .evaluate("document.getElementById('item').style.cssText")
+            .unwrap();
+        assert!(matches!(result, JsValue::String(css) if css == "height: 40px"));
+    }
+
+    const FEED: &str = r#"<html><body>
+        <div id="feed"><div id="ad"></div><div id="a"></div><div id="b"></div><div id="c"></div><div id="d"></div></div>
+    </body></html>"#;
+
+    /// A 100x200 scroller of 100px items below an ad slot `ad_height` tall.
+    fn feed_layout(document: &Document, ad_height: f32, anchoring: bool) -> GeometryMap {
+        let id = |name: &str| document.get_element_by_id(name).unwrap().id;
+        let content = ad_height + 400.0;
+        let mut geometry = HashMap::new();
+        geometry.insert(
+            document.document_element().unwrap().id,
+            node(0.0, 0.0, 800.0, 200.0, 200.0),
+        );
+        geometry.insert(
+            document.body().unwrap().id,
+            node(0.0, 0.0, 800.0, 200.0, 200.0),
+        );
+        let mut feed = node(0.0, 0.0, 100.0, 200.0, content);
+        feed.anchor_excluded = !anchoring;
+        geometry.insert(id("feed"), feed);
+        geometry.insert(id("ad"), node(0.0, 0.0, 100.0, ad_height, ad_height));
+        for (i, item) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            let y = ad_height + i as f32 * 100.0;
+            geometry.insert(id(item), node(0.0, y, 100.0, 100.0, 100.0));
+        }
+        geometry
+    }
+
+    /// Scroll the feed to 150px, then load a 300px ad above the viewport.
+    /// Returns where `c` is on screen before and after.
+    fn load_ad(anchoring: bool) -> (DomBindings, f64, f64) {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(Document::parse_html(FEED).unwrap());
+        bindings.set_document(document.clone()).unwrap();
+        bindings.set_layout(
+            Rc::new(feed_layout(&document, 0.0, anchoring)),
+            (800.0, 200.0),
+        );
+        bindings
+            .evaluate(
+                "document.getElementById('feed').scrollTop = 150; \
+                 var c = document.getElementById('c');",
+            )
+            .unwrap();
+        let before = eval_number(&bindings, "c.getBoundingClientRect().top");
+
+        bindings.set_layout(
+            Rc::new(feed_layout(&document, 300.0, anchoring)),
+            (800.0, 200.0),
+        );
+        let after = eval_number(&bindings, "c.getBoundingClientRect().top");
+        (bindings, before, after)
+    }
+
+    #[test]
+    fn test_scroll_anchoring_keeps_visible_content_in_place() {
+        let (bindings, before, after) = load_ad(true);
+        assert_eq!(before, 50.0);
+        assert_eq!(after, 50.0);
+        assert_eq!(
+            eval_number(&bindings, "document.getElementById('feed').scrollTop"),
+            450.0
+        );
+        assert_eq!(bindings.scroll_anchor_adjustments(), 1);
+
+        let (bindings, before, after) = load_ad(false);
+        assert_eq!(after - before, 300.0);
+        assert_eq!(bindings.scroll_anchor_adjustments(), 0);
+    }
+
+    #[test]
+    fn test_user_scroll_suppresses_anchoring() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(Document::parse_html(FEED).unwrap());
+        bindings.set_document(document.clone()).unwrap();
+        bindings.set_layout(Rc::new(feed_layout(&document, 0.0, true)), (800.0, 200.0));
+        bindings
+            .evaluate("document.getElementById('feed').scrollTop = 150;")
+            .unwrap();
+
+        bindings.note_user_scroll();
+        bindings.set_layout(Rc::new(feed_layout(&document, 300.0, true)), (800.0, 200.0));
+        assert_eq!(
+            eval_number(&bindings, "document.getElementById('feed').scrollTop"),
+            150.0
+        );
+        assert_eq!(bindings.scroll_anchor_adjustments(), 0);
+    }
+}
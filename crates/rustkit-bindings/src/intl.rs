@@ -0,0 +1,997 @@
+//! `Intl.DateTimeFormat`, `Intl.NumberFormat` and the `toLocaleString`
+//! family, formatted from a [`LocaleProvider`]'s data instead of CLDR.
+//!
+//! The provider is the system's on Windows (set by the engine with
+//! [`crate::DomBindings::set_locale_provider`]) and [`BuiltinLocales`]
+//! otherwise. Where this differs from a full ICU implementation:
+//!
+//! - Locales are matched by lookup: `de-CH` uses `de` data when the host
+//!   has no `de-CH`; unmatched requests fall back to the user's languages,
+//!   then `en-US`. Unicode extension keywords are ignored.
+//! - Only the Gregorian calendar and Latin digits.
+//! - `timeZone` is limited to `UTC` and the host's own zone, reported under
+//!   the host's name for it.
+//! - Dates follow the locale's short date picture, or its long one when the
+//!   month is spelled out, so numeric fields are padded as the system pads
+//!   them; `weekday`, `era` and `timeZoneName` are ignored. Date and time
+//!   are joined with `, `.
+//! - Digits are grouped in threes. Currencies other than the locale's own
+//!   show a built-in symbol or their code. `currencyDisplay`, `notation`,
+//!   `signDisplay`, significant digits and `formatToParts` are unsupported.
+
+use rustkit_core::locale::{
+    canonicalize_tag, lookup, negotiate, picture_tokens, BuiltinLocales, CurrencyPlacement,
+    LocaleData, LocaleProvider, PictureToken,
+};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Symbols for common currencies that are not the locale's own.
+const CURRENCY_SYMBOLS: [(&str, &str); 8] = [
+    ("USD", "$"),
+    ("EUR", "€"),
+    ("GBP", "£"),
+    ("JPY", "¥"),
+    ("CNY", "CN¥"),
+    ("INR", "₹"),
+    ("KRW", "₩"),
+    ("CAD", "CA$"),
+];
+
+/// ISO 4217 minor units, where not 2.
+fn currency_digits(code: &str) -> usize {
+    match code {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// The locale provider and the data negotiated from it so far.
+pub(crate) struct IntlState {
+    provider: RefCell<Arc<dyn LocaleProvider>>,
+    locales: RefCell<HashMap<String, LocaleData>>,
+}
+
+impl Default for IntlState {
+    fn default() -> Self {
+        Self {
+            provider: RefCell::new(Arc::new(BuiltinLocales)),
+            locales: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl IntlState {
+    pub(crate) fn set_provider(&self, provider: Arc<dyn LocaleProvider>) {
+        *self.provider.borrow_mut() = provider;
+        self.locales.borrow_mut().clear();
+    }
+
+    pub(crate) fn preferred_languages(&self) -> Vec<String> {
+        self.provider.borrow().preferred_languages()
+    }
+
+    fn negotiate(&self, requested: &[String]) -> LocaleData {
+        let data = negotiate(self.provider.borrow().as_ref(), requested);
+        self.locales
+            .borrow_mut()
+            .insert(data.tag.clone(), data.clone());
+        data
+    }
+
+    /// Data for a tag a format resolved to.
+    fn data(&self, tag: &str) -> LocaleData {
+        let cached = self.locales.borrow().get(tag).cloned();
+        cached.unwrap_or_else(|| self.negotiate(&[tag.to_string()]))
+    }
+}
+
+/// A `TypeError` or `RangeError` for script.
+struct IntlError(&'static str, String);
+
+impl IntlError {
+    fn range(message: impl Into<String>) -> Self {
+        Self("RangeError", message.into())
+    }
+
+    fn json(&self) -> String {
+        json!({ "error": self.0, "message": self.1 }).to_string()
+    }
+}
+
+/// Canonical forms of the requested tags, deduplicated.
+fn canonical_locales(requested: &[String]) -> Result<Vec<String>, IntlError> {
+    let mut canonical = Vec::new();
+    for tag in requested {
+        let tag = canonicalize_tag(tag)
+            .ok_or_else(|| IntlError::range("Incorrect locale information provided"))?;
+        if !canonical.contains(&tag) {
+            canonical.push(tag);
+        }
+    }
+    Ok(canonical)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NumberFormatInput {
+    style: Option<String>,
+    currency: Option<String>,
+    minimum_fraction_digits: Option<f64>,
+    maximum_fraction_digits: Option<f64>,
+    use_grouping: Option<bool>,
+}
+
+/// What `Intl.NumberFormat.prototype.resolvedOptions` reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NumberFormatOptions {
+    locale: String,
+    numbering_system: String,
+    style: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    minimum_integer_digits: u32,
+    minimum_fraction_digits: usize,
+    maximum_fraction_digits: usize,
+    use_grouping: bool,
+}
+
+fn fraction_digits(value: Option<f64>, name: &str) -> Result<Option<usize>, IntlError> {
+    match value {
+        None => Ok(None),
+        Some(value) if value.is_finite() && (0.0..=20.0).contains(&value.floor()) => {
+            Ok(Some(value.floor() as usize))
+        }
+        Some(_) => Err(IntlError::range(format!("{} value is out of range.", name))),
+    }
+}
+
+fn resolve_number_format(
+    state: &IntlState,
+    requested: &[String],
+    input: NumberFormatInput,
+) -> Result<NumberFormatOptions, IntlError> {
+    let requested = canonical_locales(requested)?;
+    let style = input.style.unwrap_or_else(|| "decimal".to_string());
+    let currency = match (style.as_str(), input.currency) {
+        (_, Some(code)) if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_alphabetic()) => {
+            return Err(IntlError::range(format!(
+                "Invalid currency code : {}",
+                code
+            )));
+        }
+        ("currency", None) => {
+            return Err(IntlError(
+                "TypeError",
+                "Currency code is required with currency style.".to_string(),
+            ));
+        }
+        ("currency", Some(code)) => Some(code.to_ascii_uppercase()),
+        ("decimal" | "percent", _) => None,
+        (style, _) => {
+            return Err(IntlError::range(format!(
+                "Value {} out of range for Intl.NumberFormat options property style",
+                style
+            )))
+        }
+    };
+
+    let (default_min, default_max) = match (style.as_str(), &currency) {
+        ("currency", Some(code)) => (currency_digits(code), currency_digits(code)),
+        ("percent", _) => (0, 0),
+        _ => (0, 3),
+    };
+    let min = fraction_digits(input.minimum_fraction_digits, "minimumFractionDigits")?;
+    let max = fraction_digits(input.maximum_fraction_digits, "maximumFractionDigits")?;
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) if min > max => {
+            return Err(IntlError::range(
+                "maximumFractionDigits value is out of range.",
+            ));
+        }
+        (Some(min), Some(max)) => (min, max),
+        (Some(min), None) => (min, min.max(default_max)),
+        (None, Some(max)) => (default_min.min(max), max),
+        (None, None) => (default_min, default_max),
+    };
+
+    Ok(NumberFormatOptions {
+        locale: state.negotiate(&requested).tag,
+        numbering_system: "latn".to_string(),
+        style,
+        currency,
+        minimum_integer_digits: 1,
+        minimum_fraction_digits: min,
+        maximum_fraction_digits: max,
+        use_grouping: input.use_grouping.unwrap_or(true),
+    })
+}
+
+/// Digits of `value` rounded half away from zero to at most `max` and at
+/// least `min` fraction digits, as integer and fraction parts.
+fn round_digits(value: f64, min: usize, max: usize) -> (String, String) {
+    // The shortest representation that round-trips, as ICU rounds
+    let shortest = format!("{}", value.abs());
+    let (integer, fraction) = shortest.split_once('.').unwrap_or((&shortest, ""));
+    let mut digits: Vec<u8> = integer.bytes().chain(fraction.bytes()).collect();
+    let integer_len = integer.len();
+    let keep = integer_len + max.min(fraction.len());
+    let round_up = fraction.len() > max && digits[keep] >= b'5';
+    digits.truncate(keep);
+    let mut integer_len = integer_len;
+    if round_up {
+        let mut index = digits.len();
+        loop {
+            if index == 0 {
+                digits.insert(0, b'1');
+                integer_len += 1;
+                break;
+            }
+            index -= 1;
+            if digits[index] == b'9' {
+                digits[index] = b'0';
+            } else {
+                digits[index] += 1;
+                break;
+            }
+        }
+    }
+    let mut fraction = String::from_utf8(digits.split_off(integer_len)).unwrap();
+    while fraction.len() > min && fraction.ends_with('0') {
+        fraction.pop();
+    }
+    while fraction.len() < min {
+        fraction.push('0');
+    }
+    (String::from_utf8(digits).unwrap(), fraction)
+}
+
+fn format_number(data: &LocaleData, options: &NumberFormatOptions, value: f64) -> String {
+    let value = if options.style == "percent" {
+        value * 100.0
+    } else {
+        value
+    };
+    let number = if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        "∞".to_string()
+    } else {
+        let (integer, fraction) = round_digits(
+            value,
+            options.minimum_fraction_digits,
+            options.maximum_fraction_digits,
+        );
+        let mut grouped = String::new();
+        for (index, digit) in integer.chars().enumerate() {
+            if options.use_grouping && index > 0 && (integer.len() - index) % 3 == 0 {
+                grouped.push_str(&data.group_separator);
+            }
+            grouped.push(digit);
+        }
+        if !fraction.is_empty() {
+            grouped.push_str(&data.decimal_separator);
+            grouped.push_str(&fraction);
+        }
+        grouped
+    };
+    let sign = if value.is_sign_negative() && !value.is_nan() && value != 0.0 {
+        "-"
+    } else {
+        ""
+    };
+
+    match (options.style.as_str(), &options.currency) {
+        ("percent", _) => {
+            let space = if data.percent_space { "\u{A0}" } else { "" };
+            format!("{}{}{}%", sign, number, space)
+        }
+        ("currency", Some(code)) => {
+            let symbol = if *code == data.currency_code {
+                data.currency_symbol.as_str()
+            } else {
+                CURRENCY_SYMBOLS
+                    .iter()
+                    .find(|(symbol_code, _)| symbol_code == code)
+                    .map_or(code.as_str(), |(_, symbol)| symbol)
+            };
+            match data.currency_placement {
+                CurrencyPlacement::Before => format!("{}{}{}", sign, symbol, number),
+                CurrencyPlacement::BeforeSpace => format!("{}{}\u{A0}{}", sign, symbol, number),
+                CurrencyPlacement::After => format!("{}{}{}", sign, number, symbol),
+                CurrencyPlacement::AfterSpace => format!("{}{}\u{A0}{}", sign, number, symbol),
+            }
+        }
+        _ => format!("{}{}", sign, number),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DateTimeFormatInput {
+    year: Option<String>,
+    month: Option<String>,
+    day: Option<String>,
+    hour: Option<String>,
+    minute: Option<String>,
+    second: Option<String>,
+    hour12: Option<bool>,
+    time_zone: Option<String>,
+}
+
+/// What `Intl.DateTimeFormat.prototype.resolvedOptions` reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DateTimeFormatOptions {
+    locale: String,
+    calendar: String,
+    numbering_system: String,
+    time_zone: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hour12: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    year: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    month: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    day: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hour: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minute: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    second: Option<String>,
+}
+
+/// A date's fields in the format's time zone; `month` is 1-based.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct DateFields {
+    year: i64,
+    month: usize,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+/// Resolve options the way `ToDateTimeOptions` does: with none of the
+/// `required` fields ("date", "time" or "any") given, fill in the
+/// `defaults` ("date", "time" or "all") as numeric.
+fn resolve_date_time_format(
+    state: &IntlState,
+    requested: &[String],
+    input: DateTimeFormatInput,
+    required: &str,
+    defaults: &str,
+) -> Result<DateTimeFormatOptions, IntlError> {
+    let requested = canonical_locales(requested)?;
+    let check = |name: &str, value: Option<String>, allowed: &[&str]| match value {
+        Some(value) if !allowed.contains(&value.as_str()) => Err(IntlError::range(format!(
+            "Value {} out of range for Intl.DateTimeFormat options property {}",
+            value, name
+        ))),
+        value => Ok(value),
+    };
+    let numeric = ["numeric", "2-digit"];
+    let mut year = check("year", input.year, &numeric)?;
+    let mut month = check(
+        "month",
+        input.month,
+        &["numeric", "2-digit", "long", "short", "narrow"],
+    )?;
+    let mut day = check("day", input.day, &numeric)?;
+    let mut hour = check("hour", input.hour, &numeric)?;
+    let mut minute = check("minute", input.minute, &numeric)?;
+    let mut second = check("second", input.second, &numeric)?;
+
+    let has_date = year.is_some() || month.is_some() || day.is_some();
+    let has_time = hour.is_some() || minute.is_some() || second.is_some();
+    let need_defaults = !(matches!(required, "date" | "any") && has_date
+        || matches!(required, "time" | "any") && has_time);
+    if need_defaults && matches!(defaults, "date" | "all") {
+        for field in [&mut year, &mut month, &mut day] {
+            *field = Some("numeric".to_string());
+        }
+    }
+    if need_defaults && matches!(defaults, "time" | "all") {
+        for field in [&mut hour, &mut minute, &mut second] {
+            *field = Some("numeric".to_string());
+        }
+    }
+
+    let system_zone = state.provider.borrow().time_zone();
+    let time_zone = match input.time_zone {
+        None => system_zone,
+        Some(zone)
+            if ["UTC", "Etc/UTC", "GMT", "Etc/GMT"]
+                .iter()
+                .any(|utc| utc.eq_ignore_ascii_case(&zone)) =>
+        {
+            "UTC".to_string()
+        }
+        Some(zone) if zone.eq_ignore_ascii_case(&system_zone) => system_zone,
+        Some(zone) => {
+            return Err(IntlError::range(format!(
+                "Unsupported time zone specified {}",
+                zone
+            )));
+        }
+    };
+
+    let data = state.negotiate(&requested);
+    let hour12 = hour
+        .is_some()
+        .then(|| input.hour12.unwrap_or_else(|| data.hour12()));
+    Ok(DateTimeFormatOptions {
+        locale: data.tag,
+        calendar: "gregory".to_string(),
+        numbering_system: "latn".to_string(),
+        time_zone,
+        hour12,
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    })
+}
+
+/// Remove the fields of a picture for which `keep` is false. A field goes
+/// with the literal after it, or the one before it when it is last, so
+/// `MMMM d, yyyy` without the day is `MMMM yyyy` and without the year
+/// `MMMM d`. `prefer_before` takes the literal before first instead, as
+/// `h:mm:ss tt` without seconds must lose the `:`.
+fn drop_fields(
+    tokens: Vec<PictureToken>,
+    keep: impl Fn(char, usize) -> bool,
+    prefer_before: bool,
+) -> Vec<PictureToken> {
+    let mut tokens = tokens;
+    while let Some(index) = tokens
+        .iter()
+        .position(|token| matches!(token, PictureToken::Field(c, n) if !keep(*c, *n)))
+    {
+        tokens.remove(index);
+        let before = index > 0 && matches!(tokens[index - 1], PictureToken::Literal(_));
+        let after = matches!(tokens.get(index), Some(PictureToken::Literal(_)));
+        if before && (prefer_before || !after) {
+            tokens.remove(index - 1);
+        } else if after {
+            tokens.remove(index);
+        }
+    }
+    tokens
+}
+
+fn pad(value: impl std::fmt::Display, two_digit: bool) -> String {
+    if two_digit {
+        format!("{:02}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_date(data: &LocaleData, options: &DateTimeFormatOptions, fields: DateFields) -> String {
+    let mut parts = Vec::new();
+    let month_index = fields.month.clamp(1, 12) - 1;
+
+    if options.year.is_some() || options.month.is_some() || options.day.is_some() {
+        let text_month = matches!(options.month.as_deref(), Some("long" | "short" | "narrow"));
+        let picture = if text_month {
+            &data.long_date
+        } else {
+            &data.short_date
+        };
+        let tokens = drop_fields(
+            picture_tokens(picture),
+            |c, n| match c {
+                'y' => options.year.is_some(),
+                'M' => options.month.is_some(),
+                'd' => n <= 2 && options.day.is_some(),
+                _ => false,
+            },
+            false,
+        );
+        let mut date = String::new();
+        for token in tokens {
+            match token {
+                PictureToken::Literal(text) => date.push_str(&text),
+                PictureToken::Field('y', _) => match options.year.as_deref() {
+                    Some("2-digit") => date.push_str(&pad(fields.year.rem_euclid(100), true)),
+                    _ => date.push_str(&fields.year.to_string()),
+                },
+                PictureToken::Field('M', n) => match options.month.as_deref() {
+                    Some("long") => date.push_str(&data.month_names[month_index]),
+                    Some("short") => date.push_str(&data.abbreviated_month_names[month_index]),
+                    Some("narrow") => date.extend(data.month_names[month_index].chars().next()),
+                    Some("2-digit") => date.push_str(&pad(fields.month, true)),
+                    _ => date.push_str(&pad(fields.month, n == 2)),
+                },
+                PictureToken::Field('d', n) => {
+                    let two_digit = n == 2 || options.day.as_deref() == Some("2-digit");
+                    date.push_str(&pad(fields.day, two_digit));
+                }
+                PictureToken::Field(..) => {}
+            }
+        }
+        parts.push(date);
+    }
+
+    if let Some(hour12) = options.hour12 {
+        let mut tokens = picture_tokens(&data.time_format);
+        if hour12 && !tokens.contains(&PictureToken::Field('t', 2)) {
+            tokens.push(PictureToken::Literal(" ".to_string()));
+            tokens.push(PictureToken::Field('t', 2));
+        }
+        let tokens = drop_fields(
+            tokens,
+            |c, _| match c {
+                'h' | 'H' => true,
+                'm' => options.minute.is_some(),
+                's' => options.second.is_some(),
+                't' => hour12,
+                _ => false,
+            },
+            true,
+        );
+        let mut time = String::new();
+        for token in tokens {
+            match token {
+                PictureToken::Literal(text) => time.push_str(&text),
+                PictureToken::Field(c, n) if c == 'h' || c == 'H' => {
+                    let hour = if hour12 {
+                        (fields.hour + 11) % 12 + 1
+                    } else {
+                        fields.hour
+                    };
+                    // The picture's padding only holds for its own clock
+                    let two_digit = (n == 2 && hour12 == (c == 'h'))
+                        || options.hour.as_deref() == Some("2-digit");
+                    time.push_str(&pad(hour, two_digit));
+                }
+                PictureToken::Field('m', _) => time.push_str(&pad(fields.minute, true)),
+                PictureToken::Field('s', _) => time.push_str(&pad(fields.second, true)),
+                PictureToken::Field('t', _) => {
+                    time.push_str(if fields.hour < 12 { &data.am } else { &data.pm })
+                }
+                PictureToken::Field(..) => {}
+            }
+        }
+        parts.push(time.trim().to_string());
+    }
+
+    parts.join(", ")
+}
+
+fn requested_arg(args: &[String]) -> Vec<String> {
+    args.first()
+        .and_then(|arg| serde_json::from_str(arg).ok())
+        .unwrap_or_default()
+}
+
+fn options_arg<T: for<'de> Deserialize<'de> + Default>(args: &[String], index: usize) -> T {
+    args.get(index)
+        .and_then(|arg| serde_json::from_str(arg).ok())
+        .unwrap_or_default()
+}
+
+fn to_json<T: Serialize>(result: Result<T, IntlError>) -> JsValue {
+    JsValue::String(match result {
+        Ok(value) => serde_json::to_string(&value).unwrap_or_default(),
+        Err(e) => e.json(),
+    })
+}
+
+const INTL_JS: &str = r#"
+    (function() {
+        function call(result) {
+            var parsed = JSON.parse(result);
+            if (parsed.error) {
+                throw new (parsed.error === 'TypeError' ? TypeError : RangeError)(parsed.message);
+            }
+            return parsed;
+        }
+
+        function requested(locales) {
+            if (locales === undefined) return '[]';
+            var list = typeof locales === 'string' ? [locales] : Array.prototype.slice.call(Object(locales));
+            return JSON.stringify(list.map(function(locale) {
+                if (typeof locale !== 'string' && (typeof locale !== 'object' || locale === null)) {
+                    throw new TypeError('Language ID should be string or object.');
+                }
+                return String(locale);
+            }));
+        }
+
+        function pick(options, strings, numbers, booleans) {
+            options = options === undefined ? {} : Object(options);
+            var input = {};
+            strings.forEach(function(name) {
+                if (options[name] !== undefined) input[name] = String(options[name]);
+            });
+            numbers.forEach(function(name) {
+                if (options[name] !== undefined) input[name] = Number(options[name]);
+            });
+            booleans.forEach(function(name) {
+                if (options[name] !== undefined) input[name] = !!options[name];
+            });
+            return JSON.stringify(input);
+        }
+
+        function supportedLocalesOf(locales) {
+            return call(__rustkit_intl_supported(requested(locales))).locales;
+        }
+
+        function NumberFormat(locales, options) {
+            if (!(this instanceof NumberFormat)) return new NumberFormat(locales, options);
+            this._options = call(__rustkit_intl_number_format(
+                requested(locales),
+                pick(options, ['style', 'currency'],
+                    ['minimumFractionDigits', 'maximumFractionDigits'], ['useGrouping'])));
+            this._json = JSON.stringify(this._options);
+        }
+        Object.defineProperty(NumberFormat.prototype, 'format', {
+            get: function() {
+                var format = this;
+                return function(value) {
+                    return __rustkit_intl_format_number(format._json, String(Number(value)));
+                };
+            }
+        });
+        NumberFormat.prototype.resolvedOptions = function() {
+            return Object.assign({}, this._options);
+        };
+        NumberFormat.supportedLocalesOf = supportedLocalesOf;
+
+        function makeDateTimeFormat(format, locales, options, required, defaults) {
+            format._options = call(__rustkit_intl_date_time_format(
+                requested(locales),
+                pick(options, ['year', 'month', 'day', 'hour', 'minute', 'second', 'timeZone'],
+                    [], ['hour12']),
+                required, defaults));
+            format._json = JSON.stringify(format._options);
+            return format;
+        }
+
+        function DateTimeFormat(locales, options) {
+            if (!(this instanceof DateTimeFormat)) return new DateTimeFormat(locales, options);
+            makeDateTimeFormat(this, locales, options, 'any', 'date');
+        }
+        function formatDate(format, date) {
+            var time = date === undefined ? Date.now() : Number(date);
+            if (!isFinite(time)) throw new RangeError('Invalid time value');
+            var d = new Date(time);
+            var utc = format._options.timeZone === 'UTC';
+            var fields = utc ? {
+                year: d.getUTCFullYear(), month: d.getUTCMonth() + 1, day: d.getUTCDate(),
+                hour: d.getUTCHours(), minute: d.getUTCMinutes(), second: d.getUTCSeconds()
+            } : {
+                year: d.getFullYear(), month: d.getMonth() + 1, day: d.getDate(),
+                hour: d.getHours(), minute: d.getMinutes(), second: d.getSeconds()
+            };
+            return __rustkit_intl_format_date(format._json, JSON.stringify(fields));
+        }
+        Object.defineProperty(DateTimeFormat.prototype, 'format', {
+            get: function() {
+                var format = this;
+                return function(date) { return formatDate(format, date); };
+            }
+        });
+        DateTimeFormat.prototype.resolvedOptions = function() {
+            return Object.assign({}, this._options);
+        };
+        DateTimeFormat.supportedLocalesOf = supportedLocalesOf;
+
+        var Intl = {
+            NumberFormat: NumberFormat,
+            DateTimeFormat: DateTimeFormat,
+            getCanonicalLocales: function(locales) {
+                return call(__rustkit_intl_canonical(requested(locales))).locales;
+            }
+        };
+        globalThis.Intl = Intl;
+        window.Intl = Intl;
+
+        Number.prototype.toLocaleString = function(locales, options) {
+            return new NumberFormat(locales, options).format(Number.prototype.valueOf.call(this));
+        };
+        function dateMethod(required, defaults) {
+            return function(locales, options) {
+                var time = Date.prototype.getTime.call(this);
+                if (isNaN(time)) return 'Invalid Date';
+                var format = makeDateTimeFormat(
+                    Object.create(DateTimeFormat.prototype), locales, options, required, defaults);
+                return formatDate(format, time);
+            };
+        }
+        Date.prototype.toLocaleString = dateMethod('any', 'all');
+        Date.prototype.toLocaleDateString = dateMethod('date', 'date');
+        Date.prototype.toLocaleTimeString = dateMethod('time', 'time');
+    })();
+"#;
+
+/// Register `Intl` and the `toLocaleString` methods.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<IntlState>) -> Result<(), JsError> {
+    let intl = state.clone();
+    runtime.register_function("__rustkit_intl_number_format", 2, move |args| {
+        Ok(to_json(resolve_number_format(
+            &intl,
+            &requested_arg(args),
+            options_arg(args, 1),
+        )))
+    })?;
+
+    let intl = state.clone();
+    runtime.register_function("__rustkit_intl_format_number", 2, move |args| {
+        let options: Option<NumberFormatOptions> =
+            args.first().and_then(|arg| serde_json::from_str(arg).ok());
+        let value = args
+            .get(1)
+            .and_then(|arg| arg.parse::<f64>().ok())
+            .unwrap_or(f64::NAN);
+        Ok(JsValue::String(
+            options.map_or_else(String::new, |options| {
+                format_number(&intl.data(&options.locale), &options, value)
+            }),
+        ))
+    })?;
+
+    let intl = state.clone();
+    runtime.register_function("__rustkit_intl_date_time_format", 4, move |args| {
+        let arg = |index: usize| args.get(index).map(String::as_str).unwrap_or("");
+        Ok(to_json(resolve_date_time_format(
+            &intl,
+            &requested_arg(args),
+            options_arg(args, 1),
+            arg(2),
+            arg(3),
+        )))
+    })?;
+
+    let intl = state.clone();
+    runtime.register_function("__rustkit_intl_format_date", 2, move |args| {
+        let options: Option<DateTimeFormatOptions> =
+            args.first().and_then(|arg| serde_json::from_str(arg).ok());
+        let fields: Option<DateFields> = args.get(1).and_then(|arg| serde_json::from_str(arg).ok());
+        Ok(JsValue::String(match (options, fields) {
+            (Some(options), Some(fields)) => {
+                format_date(&intl.data(&options.locale), &options, fields)
+            }
+            _ => String::new(),
+        }))
+    })?;
+
+    let intl = state.clone();
+    runtime.register_function("__rustkit_intl_supported", 1, move |args| {
+        let provider = intl.provider.borrow().clone();
+        Ok(to_json(canonical_locales(&requested_arg(args)).map(
+            |locales| {
+                let supported: Vec<String> = locales
+                    .into_iter()
+                    .filter(|tag| lookup(provider.as_ref(), tag).is_some())
+                    .collect();
+                json!({ "locales": supported })
+            },
+        )))
+    })?;
+
+    runtime.register_function("__rustkit_intl_canonical", 1, move |args| {
+        Ok(to_json(
+            canonical_locales(&requested_arg(args)).map(|locales| json!({ "locales": locales })),
+        ))
+    })?;
+
+    runtime.evaluate_script(INTL_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+
+    fn eval_string(bindings: &DomBindings, script: &str) -> String {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_date_time_format_orders_fields_by_locale() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate("var date = new Date(Date.UTC(2024, 2, 5, 15, 4, 9));")
+            .unwrap();
+        let format = |locale: &str, options: &str| {
+            eval_string(
+                &bindings,
+                &format!(
+                    "new Intl.DateTimeFormat('{}', Object.assign({{ timeZone: 'UTC' }}, {})).format(date)",
+                    locale, options
+                ),
+            )
+        };
+
+        assert_eq!(format("de-DE", "{}"), "05.03.2024");
+        assert_eq!(format("en-US", "{}"), "3/5/2024");
+        assert_eq!(format("ja-JP", "{}"), "2024/03/05");
+        assert_eq!(
+            format(
+                "de-DE",
+                "{ year: 'numeric', month: 'long', day: 'numeric' }"
+            ),
+            "5. März 2024"
+        );
+        assert_eq!(
+            format(
+                "en-US",
+                "{ year: 'numeric', month: 'long', day: 'numeric' }"
+            ),
+            "March 5, 2024"
+        );
+        assert_eq!(
+            format("en-US", "{ month: 'short', year: 'numeric' }"),
+            "Mar 2024"
+        );
+        assert_eq!(
+            format("es", "{ month: 'long', day: 'numeric' }"),
+            "5 de marzo"
+        );
+        assert_eq!(
+            format("en-US", "{ hour: 'numeric', minute: '2-digit' }"),
+            "3:04 PM"
+        );
+        assert_eq!(
+            format(
+                "de-DE",
+                "{ hour: 'numeric', minute: '2-digit', second: '2-digit' }"
+            ),
+            "15:04:09"
+        );
+        assert_eq!(
+            format(
+                "de-DE",
+                "{ hour: 'numeric', minute: '2-digit', hour12: true }"
+            ),
+            "3:04 PM"
+        );
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "date.toLocaleString('en-US', { timeZone: 'UTC' })"
+            ),
+            "3/5/2024, 3:04:09 PM"
+        );
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "date.toLocaleTimeString('ja-JP', { timeZone: 'UTC' })"
+            ),
+            "15:04:09"
+        );
+        assert_eq!(
+            eval_string(&bindings, "new Date(NaN).toLocaleDateString()"),
+            "Invalid Date"
+        );
+
+        let resolved = eval_string(
+            &bindings,
+            "JSON.stringify(new Intl.DateTimeFormat('de-DE', { timeZone: 'UTC', hour: 'numeric' }).resolvedOptions())",
+        );
+        let resolved: serde_json::Value = serde_json::from_str(&resolved).unwrap();
+        assert_eq!(resolved["locale"], "de-DE");
+        assert_eq!(resolved["timeZone"], "UTC");
+        assert_eq!(resolved["hour12"], false);
+        assert_eq!(resolved["hour"], "numeric");
+        assert!(resolved.get("year").is_none());
+    }
+
+    #[test]
+    fn test_number_format_styles() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        for (script, expected) in [
+            ("(1234567.891).toLocaleString('en-US')", "1,234,567.891"),
+            ("(1234567.891).toLocaleString('de-DE')", "1.234.567,891"),
+            ("(0.125).toLocaleString('en-US', { maximumFractionDigits: 2 })", "0.13"),
+            ("(-1.5).toLocaleString('en-US', { minimumFractionDigits: 2 })", "-1.50"),
+            ("(999.9999).toLocaleString('en-US')", "1,000"),
+            ("(1234.5).toLocaleString('en-US', { useGrouping: false })", "1234.5"),
+            ("new Intl.NumberFormat('de-DE', { style: 'percent' }).format(0.256)", "26\u{A0}%"),
+            ("new Intl.NumberFormat('en-US', { style: 'percent' }).format(0.256)", "26%"),
+            (
+                "new Intl.NumberFormat('en-US', { style: 'currency', currency: 'USD' }).format(1234.5)",
+                "$1,234.50",
+            ),
+            (
+                "new Intl.NumberFormat('de-DE', { style: 'currency', currency: 'EUR' }).format(1234.5)",
+                "1.234,50\u{A0}€",
+            ),
+            (
+                "new Intl.NumberFormat('ja-JP', { style: 'currency', currency: 'JPY' }).format(1234.5)",
+                "￥1,235",
+            ),
+            ("[1, 2].map(new Intl.NumberFormat('en-US').format).join(' ')", "1 2"),
+        ] {
+            assert_eq!(eval_string(&bindings, script), expected, "{}", script);
+        }
+
+        let resolved = eval_string(
+            &bindings,
+            "JSON.stringify(new Intl.NumberFormat('ja-JP', { style: 'currency', currency: 'jpy' }).resolvedOptions())",
+        );
+        let resolved: serde_json::Value = serde_json::from_str(&resolved).unwrap();
+        assert_eq!(resolved["locale"], "ja-JP");
+        assert_eq!(resolved["currency"], "JPY");
+        assert_eq!(resolved["minimumFractionDigits"], 0);
+        assert_eq!(resolved["maximumFractionDigits"], 0);
+
+        for (script, error) in [
+            (
+                "new Intl.NumberFormat('en', { style: 'currency' })",
+                "TypeError",
+            ),
+            (
+                "new Intl.NumberFormat('en', { maximumFractionDigits: 30 })",
+                "RangeError",
+            ),
+            ("new Intl.NumberFormat('en_US')", "RangeError"),
+            (
+                "new Intl.DateTimeFormat('en', { timeZone: 'Mars/Olympus' })",
+                "RangeError",
+            ),
+        ] {
+            let caught = eval_string(
+                &bindings,
+                &format!("(function() {{ try {{ {}; return 'none'; }} catch (e) {{ return e.name; }} }})()", script),
+            );
+            assert_eq!(caught, error, "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_locale_falls_back() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "new Intl.NumberFormat('tlh-KX').resolvedOptions().locale"
+            ),
+            "en-US"
+        );
+        assert_eq!(
+            eval_string(&bindings, "(1234.5).toLocaleString(['tlh', 'de-AT'])"),
+            "1.234,5"
+        );
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "new Intl.DateTimeFormat(['tlh', 'de-AT']).resolvedOptions().locale"
+            ),
+            "de"
+        );
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "JSON.stringify(Intl.DateTimeFormat.supportedLocalesOf(['tlh', 'de-AT', 'JA-jp']))"
+            ),
+            r#"["de-AT","ja-JP"]"#
+        );
+        assert_eq!(
+            eval_string(
+                &bindings,
+                "JSON.stringify(Intl.getCanonicalLocales('EN-gb'))"
+            ),
+            r#"["en-GB"]"#
+        );
+    }
+}
@@ -10,7 +10,42 @@
 //! 4. **Extensibility**: Easy to add new APIs
 
 pub mod events;
-
+mod activation;
+mod audio;
+mod blob;
+mod canvas;
+mod clipboard;
+mod computed_style;
+mod cookies;
+mod crypto;
+mod drag;
+mod editing;
+mod encoding;
+mod errors;
+mod fetch;
+mod form_data;
+mod frames;
+mod geometry;
+mod intl;
+mod media;
+mod observers;
+mod permissions;
+mod popup;
+mod scheduler;
+mod tree;
+mod url_api;
+mod workers;
+
+pub use activation::{UserActivation, TRANSIENT_ACTIVATION};
+pub use audio::{AudioCommand, AudioOp, AudioUpdate};
+pub use blob::{InputFile, ObjectUrlRegistry};
+pub use clipboard::{ClipboardData, ClipboardError, ClipboardOp, ClipboardRequest};
+pub use computed_style::{ComputedStyleProvider, ElementStyle};
+pub use cookies::CookieStore;
+pub use editing::EditCommandHandler;
+pub use errors::{PageError, PageErrorKind};
+pub use fetch::{FetchCommand, FetchRequest, FetchResponse};
+pub use form_data::FormEntriesProvider;
 pub use events::{
     AnimationEventData, DataTransfer, DragEventData, DroppedFile, Event, EventDispatcher,
     EventListenerEntry, EventListenerOptions, EventPhase, ExtendedEventData, FocusManager,
@@ -18,13 +53,37 @@ pub use events::{
     PointerLockState, PointerType, RafCallbackId, RafScheduler, Touch, TouchEventData,
     TransitionEventData, WheelDeltaMode, WheelEventData,
 };
-
+pub use geometry::{GeometryMap, LayoutProvider};
+pub use popup::{WindowFeatures, WindowRequest, WindowTarget};
+pub use rustkit_canvas::ImageBitmap;
+pub use workers::{WorkerCommand, WorkerScope};
+
+use blob::BlobState;
+use errors::ErrorState;
+use fetch::FetchState;
+use editing::EditingState;
+use form_data::FormDataState;
+use geometry::GeometryState;
+use intl::IntlState;
+use canvas::Canvases;
+use audio::AudioState;
+use clipboard::ClipboardState;
+use computed_style::ComputedStyleState;
+use cookies::CookieState;
+use frames::{FrameLink, FrameState};
+use media::MediaState;
+use popup::PopupState;
+use workers::WorkerState;
+use rustkit_core::locale::LocaleProvider;
+use rustkit_css::MediaEnvironment;
 use rustkit_dom::{Document, Node, NodeId};
 use rustkit_js::{JsError, JsRuntime, JsValue};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 use tracing::{debug, trace};
 use url::Url;
@@ -117,6 +176,8 @@ pub enum EventData {
     Keyboard(KeyboardEventBindingData),
     Focus(FocusEventBindingData),
     Input(InputEventBindingData),
+    Transition(TransitionEventData),
+    Drag(DragEventData),
 }
 
 /// Location object (window.location).
@@ -289,17 +350,65 @@ pub struct IpcMessage {
     pub payload: String,
 }
 
+/// A change script made to the document, queued while recording is on
+/// (see [`DomBindings::set_mutation_recording`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomMutation {
+    /// Children were added to or removed from `target`.
+    ChildList { target: NodeId },
+    /// An attribute of `target` changed.
+    Attributes { target: NodeId, name: String },
+}
+
 /// IPC callback type for handling messages from JavaScript.
 pub type IpcCallback = Box<dyn Fn(IpcMessage) + Send + Sync>;
 
+/// Loop iterations an unload-time handler may run before it is aborted.
+const UNLOAD_LOOP_LIMIT: u64 = 100_000;
+
 /// DOM bindings context.
 pub struct DomBindings {
-    runtime: RefCell<JsRuntime>,
+    /// Shared so frames in the same tree can reach this window's script
+    runtime: Rc<RefCell<JsRuntime>>,
     window: RefCell<WindowState>,
     event_listeners: RefCell<Vec<EventListener>>,
     node_map: RefCell<HashMap<u64, Rc<Node>>>,
     /// Queue of IPC messages from JavaScript
     ipc_queue: RefCell<Vec<IpcMessage>>,
+    /// Layout geometry backing the element geometry APIs
+    geometry: Rc<GeometryState>,
+    /// Environment `matchMedia` queries are evaluated against
+    media: Rc<MediaState>,
+    /// Popup, postMessage and close requests waiting for the host
+    popups: Rc<PopupState>,
+    /// Canvas contexts, bitmaps and decoded image sources
+    canvases: Rc<Canvases>,
+    /// Origin and parent/child frame links
+    frames: Rc<FrameState>,
+    /// Host registry behind `URL.createObjectURL`
+    blobs: Rc<BlobState>,
+    /// Fetches waiting for the host to start, cancel or settle them
+    fetches: Rc<FetchState>,
+    /// Host function behind `new FormData(form)`
+    forms: Rc<FormDataState>,
+    /// Clipboard calls waiting for the host
+    clipboard: Rc<ClipboardState>,
+    /// Host function behind `document.execCommand`
+    editing: Rc<EditingState>,
+    /// Sticky and transient user activation
+    activation: Rc<UserActivation>,
+    /// Media element commands waiting for the host
+    audio: Rc<AudioState>,
+    /// Uncaught errors waiting for the host
+    errors: Rc<ErrorState>,
+    /// Host function behind `getComputedStyle`
+    computed_styles: Rc<ComputedStyleState>,
+    /// Host store behind `document.cookie`
+    cookies: Rc<CookieState>,
+    /// Locale data behind `Intl` and `toLocaleString`
+    intl: Rc<IntlState>,
+    /// Worker starts, messages and terminations waiting for the host
+    workers: Rc<WorkerState>,
 }
 
 impl DomBindings {
@@ -310,12 +419,123 @@ impl DomBindings {
         // Inject global objects
         Self::inject_globals(&mut runtime)?;
 
+        // window.onerror, reportError and promise rejection events
+        let errors = Rc::new(ErrorState::default());
+        errors::install(&mut runtime, errors.clone())?;
+
+        // Element geometry (getBoundingClientRect, offsetWidth, ...)
+        let geometry = Rc::new(GeometryState::default());
+        geometry::install(&mut runtime, geometry.clone())?;
+
+        // <canvas> elements, OffscreenCanvas and createImageBitmap
+        let canvases = Rc::new(Canvases::default());
+        canvas::install(&mut runtime, canvases.clone())?;
+
+        // TextEncoder, TextDecoder, atob and btoa
+        encoding::install(&mut runtime)?;
+
+        // Intl.DateTimeFormat, Intl.NumberFormat and toLocaleString
+        let intl = Rc::new(IntlState::default());
+        intl::install(&mut runtime, intl.clone())?;
+
+        // crypto.getRandomValues, randomUUID and subtle.digest
+        crypto::install(&mut runtime)?;
+
+        // Blob, File and URL.createObjectURL
+        let blobs = Rc::new(BlobState::default());
+        blob::install(&mut runtime, blobs.clone())?;
+
+        // DataTransfer for drag events
+        drag::install(&mut runtime)?;
+
+        // FormData
+        let forms = Rc::new(FormDataState::default());
+        form_data::install(&mut runtime, forms.clone())?;
+
+        // fetch, Headers, Request, Response and AbortController
+        let fetches = Rc::new(FetchState::default());
+        fetch::install(&mut runtime, fetches.clone())?;
+
+        // navigator.userActivation
+        let activation = Rc::new(UserActivation::default());
+        activation::install(&mut runtime, activation.clone())?;
+
+        // navigator.clipboard and document.execCommand('copy')
+        let clipboard = Rc::new(ClipboardState::default());
+        clipboard::install(&mut runtime, clipboard.clone(), activation.clone())?;
+
+        // document.execCommand editing commands
+        let editing = Rc::new(EditingState::default());
+        editing::install(&mut runtime, editing.clone(), geometry.clone())?;
+
+        // ResizeObserver and IntersectionObserver
+        observers::install(&mut runtime, geometry.clone())?;
+
+        // Fragments, template contents, cloneNode and appendChild
+        tree::install(&mut runtime, geometry.clone())?;
+
+        // <audio> elements and the Audio constructor
+        let audio = Rc::new(AudioState::default());
+        audio::install(&mut runtime, audio.clone(), activation.clone())?;
+
+        // window.matchMedia
+        let media = Rc::new(MediaState::default());
+        media::install(&mut runtime, media.clone())?;
+
+        // window.getComputedStyle
+        let computed_styles = Rc::new(ComputedStyleState::default());
+        computed_style::install(
+            &mut runtime,
+            computed_styles.clone(),
+            geometry.clone(),
+            media.clone(),
+        )?;
+
+        // document.cookie
+        let cookies = Rc::new(CookieState::default());
+        cookies::install(&mut runtime, cookies.clone())?;
+
+        // window.open, window.opener and cross-window postMessage
+        let popups = Rc::new(PopupState::default());
+        popup::install(&mut runtime, popups.clone(), activation.clone())?;
+
+        // navigator.permissions
+        permissions::install(&mut runtime)?;
+
+        // window.parent, window.top, window.frames and frameElement
+        let frames = Rc::new(FrameState::default());
+        frames::install(&mut runtime, frames.clone(), popups.clone())?;
+
+        // setTimeout, setInterval and requestAnimationFrame
+        scheduler::install(&mut runtime)?;
+
+        // Dedicated workers
+        let workers = Rc::new(WorkerState::default());
+        workers::install(&mut runtime, workers.clone())?;
+
         Ok(Self {
-            runtime: RefCell::new(runtime),
+            runtime: Rc::new(RefCell::new(runtime)),
             window: RefCell::new(WindowState::default()),
             event_listeners: RefCell::new(Vec::new()),
             node_map: RefCell::new(HashMap::new()),
             ipc_queue: RefCell::new(Vec::new()),
+            geometry,
+            media,
+            popups,
+            canvases,
+            frames,
+            blobs,
+            fetches,
+            forms,
+            clipboard,
+            editing,
+            activation,
+            audio,
+            errors,
+            computed_styles,
+            cookies,
+            intl,
+            workers,
         })
     }
 
@@ -329,20 +549,6 @@ impl DomBindings {
                 outerWidth: 800,
                 outerHeight: 600,
                 devicePixelRatio: 1,
-                location: {
-                    href: 'about:blank',
-                    protocol: 'about:',
-                    host: '',
-                    hostname: '',
-                    port: '',
-                    pathname: 'blank',
-                    search: '',
-                    hash: '',
-                    origin: 'null',
-                    reload: function() {},
-                    replace: function(url) { this.href = url; },
-                    assign: function(url) { this.href = url; }
-                },
                 navigator: {
                     userAgent: 'RustKit/1.0',
                     language: 'en-US',
@@ -376,14 +582,46 @@ impl DomBindings {
                     get length() { return Object.keys(this._data).length; },
                     key: function(n) { return Object.keys(this._data)[n] || null; }
                 },
-                addEventListener: function(type, callback, options) {},
-                removeEventListener: function(type, callback, options) {},
-                dispatchEvent: function(event) { return true; },
-                requestAnimationFrame: function(callback) { return 0; },
-                cancelAnimationFrame: function(id) {},
-                getComputedStyle: function(element) { return {}; },
-                matchMedia: function(query) {
-                    return { matches: false, media: query, addEventListener: function() {} };
+                _listeners: {},
+                addEventListener: function(type, callback, options) {
+                    if (typeof callback !== 'function') return;
+                    var list = this._listeners[type] || (this._listeners[type] = []);
+                    if (list.indexOf(callback) < 0) list.push(callback);
+                },
+                removeEventListener: function(type, callback, options) {
+                    var list = this._listeners[type];
+                    var index = list ? list.indexOf(callback) : -1;
+                    if (index >= 0) list.splice(index, 1);
+                },
+                dispatchEvent: function(event) {
+                    event.target = event.currentTarget = this;
+                    var list = (this._listeners[event.type] || []).slice();
+                    var handler = this['on' + event.type];
+                    if (typeof handler === 'function') list.push(handler);
+                    // The window's onerror takes an ErrorEvent's fields as arguments
+                    var errorHandler = this === window && event.type === 'error' &&
+                        'message' in event;
+                    for (var i = 0; i < list.length; i++) {
+                        var result;
+                        try {
+                            if (list[i] === handler && errorHandler) {
+                                result = handler.call(this, event.message, event.filename,
+                                    event.lineno, event.colno, event.error);
+                                if (result === true) event.preventDefault();
+                                continue;
+                            }
+                            result = list[i].call(this, event);
+                        } catch (e) {
+                            __rustkit_reportException(e, false);
+                        }
+                        // A string returned from onbeforeunload is the prompt
+                        if (list[i] === handler && event.type === 'beforeunload' &&
+                                result !== undefined && result !== null) {
+                            event.returnValue = String(result);
+                        }
+                        if (list[i] === handler && result === false) event.preventDefault();
+                    }
+                    return !event.defaultPrevented;
                 },
                 alert: function(msg) { console.log('[alert]', msg); },
                 confirm: function(msg) { console.log('[confirm]', msg); return false; },
@@ -396,6 +634,9 @@ impl DomBindings {
 
         runtime.evaluate_script(window_js)?;
 
+        // URL, URLSearchParams, and window.location
+        url_api::install(runtime)?;
+
         // IPC bridge for communication with Rust
         let ipc_js = r#"
             // IPC queue for postMessage calls
@@ -439,6 +680,9 @@ impl DomBindings {
                 domain: '',
                 referrer: '',
                 URL: 'about:blank',
+                visibilityState: 'visible',
+                hidden: false,
+                activeElement: null,
                 
                 getElementById: function(id) {
                     return this._elements[id] || null;
@@ -507,9 +751,10 @@ impl DomBindings {
                     return { children: [], appendChild: function(c) { this.children.push(c); return c; } };
                 },
                 
-                addEventListener: function(type, callback, options) {},
-                removeEventListener: function(type, callback, options) {},
-                dispatchEvent: function(event) { return true; },
+                _listeners: {},
+                addEventListener: window.addEventListener,
+                removeEventListener: window.removeEventListener,
+                dispatchEvent: window.dispatchEvent,
                 
                 write: function(html) {},
                 writeln: function(html) {}
@@ -562,6 +807,7 @@ impl DomBindings {
                 
                 // Selection methods
                 elem.select = function() {
+                    document.activeElement = this;
                     this.selectionStart = 0;
                     this.selectionEnd = this.value.length;
                 };
@@ -646,10 +892,12 @@ impl DomBindings {
                 
                 // Focus/blur methods
                 elem.focus = function() {
+                    document.activeElement = this;
                     this.dispatchEvent(new Event('focus', { bubbles: false }));
                 };
                 
                 elem.blur = function() {
+                    if (document.activeElement === this) document.activeElement = null;
                     this.dispatchEvent(new Event('blur', { bubbles: false }));
                 };
                 
@@ -784,6 +1032,9 @@ impl DomBindings {
         let mut runtime = self.runtime.borrow_mut();
         runtime.evaluate_script(&format!("document.title = {:?};", title))?;
         runtime.evaluate_script("document.readyState = 'complete';")?;
+        runtime.evaluate_script(&geometry::adopt_script(&document))?;
+        self.geometry.set_document(document.clone());
+        self.forms.set_document(document.clone());
 
         // Index elements by ID
         document.traverse(|node| {
@@ -805,34 +1056,84 @@ impl DomBindings {
 
         // Update state
         self.window.borrow_mut().location = location.clone();
+        self.cookies.set_url(url);
+        let origin = url.origin();
+        self.frames
+            .set_origin(origin.is_tuple().then(|| origin.ascii_serialization()));
 
         // Sync to JS
         let mut runtime = self.runtime.borrow_mut();
         runtime.evaluate_script(&format!(
-            r#"
-            window.location.href = {:?};
-            window.location.protocol = {:?};
-            window.location.host = {:?};
-            window.location.hostname = {:?};
-            window.location.port = {:?};
-            window.location.pathname = {:?};
-            window.location.search = {:?};
-            window.location.hash = {:?};
-            window.location.origin = {:?};
-            document.URL = {:?};
-            "#,
-            location.href,
-            location.protocol,
-            location.host,
-            location.hostname,
-            location.port,
-            location.pathname,
-            location.search,
-            location.hash,
-            location.origin,
-            location.href
+            "window.location._setHref({:?}); document.URL = {:?};",
+            location.href, location.href
+        ))?;
+
+        Ok(())
+    }
+
+    /// Expose or withdraw the APIs only secure contexts get, such as
+    /// `crypto.subtle`. Documents start out insecure.
+    pub fn set_secure_context(&self, secure: bool) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&crypto::secure_context_script(secure))?;
+        Ok(())
+    }
+
+    /// Override the document's origin, such as the parent origin an
+    /// `about:srcdoc` frame inherits. `None` marks it opaque.
+    ///
+    /// Call after [`DomBindings::set_location`], which resets the origin to
+    /// that of the URL.
+    pub fn set_origin(&self, origin: Option<String>) {
+        self.frames.set_origin(origin);
+    }
+
+    /// Make `child` the frame at `index` of this window, embedded by the
+    /// iframe `element`.
+    ///
+    /// Wires up `window.frames[index]`, the element's `contentWindow` and
+    /// `contentDocument`, and the child's `parent`, `top` and `frameElement`,
+    /// and puts the child in this window's tree for activation consumption.
+    /// Set both origins first: `frameElement` is only exposed to a
+    /// same-origin child.
+    pub fn attach_frame(
+        &self,
+        index: usize,
+        element: NodeId,
+        child: &DomBindings,
+    ) -> Result<(), BindingError> {
+        self.frames
+            .set_child(index, FrameLink::new(&child.runtime, &child.frames));
+        self.activation.link(&child.activation);
+        child
+            .frames
+            .set_parent(FrameLink::new(&self.runtime, &self.frames));
+
+        let same_origin = matches!(
+            child.evaluate("__rustkit_frame_access('parent', 0) === 'same'")?,
+            JsValue::Boolean(true)
+        );
+        child.runtime.borrow_mut().evaluate_script(&format!(
+            "__rustkit_setParent({}, {}, {});",
+            element.raw(),
+            self.frames.is_top(),
+            same_origin
+        ))?;
+        self.runtime.borrow_mut().evaluate_script(&format!(
+            "__rustkit_frameAttached({}, {});",
+            index,
+            element.raw()
         ))?;
+        Ok(())
+    }
 
+    /// Drop all child frames, as when the document embedding them goes away.
+    pub fn detach_frames(&self) -> Result<(), BindingError> {
+        self.frames.clear_children();
+        self.runtime
+            .borrow_mut()
+            .evaluate_script("__rustkit_framesDetached();")?;
         Ok(())
     }
 
@@ -855,6 +1156,333 @@ impl DomBindings {
         Ok(())
     }
 
+    /// Set `window.devicePixelRatio`. Script hears of a change through
+    /// `matchMedia` resolution queries, once the host updates the media
+    /// environment.
+    pub fn set_device_pixel_ratio(&self, ratio: f64) -> Result<(), BindingError> {
+        self.window.borrow_mut().device_pixel_ratio = ratio;
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&format!("window.devicePixelRatio = {};", ratio))?;
+        Ok(())
+    }
+
+    /// Set the function used to lay out the document when script reads
+    /// geometry after changing styles.
+    pub fn set_layout_provider<F>(&self, provider: F)
+    where
+        F: Fn(&Document, (f32, f32)) -> GeometryMap + 'static,
+    {
+        self.geometry.set_provider(Box::new(provider));
+    }
+
+    /// Set the function `new FormData(form)` takes the entries `form` would
+    /// submit from. Without one, such a `FormData` starts out empty.
+    pub fn set_form_entries_provider<F>(&self, provider: F)
+    where
+        F: Fn(&Document, NodeId) -> Vec<rustkit_dom::FormDataEntry> + 'static,
+    {
+        self.forms.set_provider(Box::new(provider));
+    }
+
+    /// Set the function `getComputedStyle` computes the style of an
+    /// element or its pseudo-element with. Without one, computed styles
+    /// are empty.
+    pub fn set_computed_style_provider<F>(&self, provider: F)
+    where
+        F: Fn(
+                &Document,
+                NodeId,
+                Option<rustkit_css::PseudoElement>,
+                &MediaEnvironment,
+            ) -> Option<ElementStyle>
+            + 'static,
+    {
+        self.computed_styles.set_provider(Box::new(provider));
+    }
+
+    /// Set the function `document.execCommand` runs `insertText`,
+    /// `delete`, `bold` and `italic` with. Without one they return false.
+    pub fn set_edit_command_handler<F>(&self, handler: F)
+    where
+        F: Fn(&Document, &str, &str) -> bool + 'static,
+    {
+        self.editing.set_handler(Box::new(handler));
+    }
+
+    /// Set the store `document.cookie` reads and writes. Without one the
+    /// document has no cookies.
+    pub fn set_cookie_store<S>(&self, store: S)
+    where
+        S: CookieStore + 'static,
+    {
+        self.cookies.set_store(Box::new(store));
+    }
+
+    /// Set the registry `URL.createObjectURL` mints `blob:` URLs from.
+    pub fn set_object_url_registry<R>(&self, registry: R)
+    where
+        R: ObjectUrlRegistry + 'static,
+    {
+        self.blobs.set_registry(Box::new(registry));
+    }
+
+    /// Give a file input the files the user picked, firing `input` and
+    /// `change` at it.
+    pub fn set_input_files(
+        &self,
+        element: NodeId,
+        files: &[InputFile],
+    ) -> Result<(), BindingError> {
+        let files: Vec<serde_json::Value> = files
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "name": f.name,
+                    "type": f.content_type,
+                    "lastModified": f.last_modified,
+                    "bytes": f.bytes,
+                })
+            })
+            .collect();
+        self.runtime.borrow_mut().evaluate_script(&format!(
+            "__rustkit_setInputFiles({}, {});",
+            element.raw(),
+            serde_json::Value::from(files)
+        ))?;
+        self.dispatch_event(element, "input")?;
+        self.dispatch_event(element, "change")?;
+        Ok(())
+    }
+
+    /// Take the fetches script started or aborted since the last call.
+    pub fn take_fetch_commands(&self) -> Vec<FetchCommand> {
+        self.fetches.take_commands()
+    }
+
+    /// Settle fetch `id` with the host's response or network error. Does
+    /// nothing if script already aborted it.
+    pub fn complete_fetch(
+        &self,
+        id: u64,
+        result: Result<FetchResponse, &str>,
+    ) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&fetch::settle_script(id, result.as_ref().map_err(|e| *e)))?;
+        Ok(())
+    }
+
+    /// Take the worker starts, messages and terminations script queued
+    /// since the last call.
+    pub fn take_worker_commands(&self) -> Vec<WorkerCommand> {
+        self.workers.take_commands()
+    }
+
+    /// Fire a `message` event at worker `id`. `data` is JSON from
+    /// [`WorkerScope::take_messages`]; one that doesn't deserialize fires
+    /// `messageerror` instead.
+    pub fn deliver_worker_message(&self, id: u64, data: &str) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&workers::message_script(id, data))?;
+        Ok(())
+    }
+
+    /// Fire an `error` event at worker `id` for what its script left
+    /// unhandled, or for a script that failed to load. Unless a handler
+    /// cancels it, the error is also reported through
+    /// [`Self::take_page_errors`].
+    pub fn deliver_worker_error(&self, id: u64, error: &PageError) -> Result<(), BindingError> {
+        let unhandled = self
+            .runtime
+            .borrow_mut()
+            .evaluate_script(&workers::error_script(id, error))?
+            .is_truthy();
+        if unhandled {
+            self.errors.report(error.clone());
+        }
+        Ok(())
+    }
+
+    /// Take the clipboard calls script made since the last call.
+    pub fn take_clipboard_requests(&self) -> Vec<ClipboardRequest> {
+        self.clipboard.take_requests()
+    }
+
+    /// Settle clipboard call `id`; reads resolve with `data`.
+    pub fn complete_clipboard(
+        &self,
+        id: u64,
+        result: Result<ClipboardData, ClipboardError>,
+    ) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&clipboard::settle_script(
+                id,
+                result.as_ref().map_err(|e| *e),
+            ))?;
+        Ok(())
+    }
+
+    /// Take the media element commands script issued since the last call.
+    pub fn take_audio_commands(&self) -> Vec<AudioCommand> {
+        self.audio.take_commands()
+    }
+
+    /// Report a playback change of media element `media_id`.
+    pub fn update_audio(&self, media_id: u64, update: &AudioUpdate) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&audio::update_script(media_id, update))?;
+        Ok(())
+    }
+
+    /// Apply a click on the controls of media element `node_id`: toggle
+    /// playback, or seek to `seek` as a fraction of the duration. Runs as
+    /// script so the autoplay policy applies as usual.
+    pub fn media_control(&self, node_id: NodeId, seek: Option<f64>) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&audio::control_script(node_id, seek))?;
+        Ok(())
+    }
+
+    /// Grant sticky and transient user activation after genuine input,
+    /// letting the next popup or clipboard write through without a
+    /// permission prompt.
+    pub fn notify_user_activation(&self) {
+        self.activation.activate(Instant::now());
+    }
+
+    /// This window's user activation.
+    pub fn user_activation(&self) -> &Rc<UserActivation> {
+        &self.activation
+    }
+
+    /// Publish the geometry of the engine's latest layout.
+    pub fn set_layout(&self, geometry: Rc<GeometryMap>, viewport: (f32, f32)) {
+        self.geometry.set_layout(geometry, viewport);
+    }
+
+    /// Update the environment `matchMedia` queries are evaluated against,
+    /// firing `change` at every `MediaQueryList` whose result flipped.
+    pub fn set_media_environment(&self, environment: MediaEnvironment) -> Result<(), BindingError> {
+        if self.media.set_environment(environment) {
+            self.runtime
+                .borrow_mut()
+                .evaluate_script("__rustkit_mediaChanged();")?;
+        }
+        Ok(())
+    }
+
+    /// Make the decoded pixels of the image at `url` available to
+    /// `createImageBitmap(img)`.
+    pub fn register_image_source(&self, url: &Url, bitmap: ImageBitmap) {
+        self.canvases.register_image_source(url.as_str(), bitmap);
+    }
+
+    /// Whether script changed styles since the last [`Self::set_layout`].
+    pub fn needs_relayout(&self) -> bool {
+        self.geometry.needs_relayout()
+    }
+
+    /// Start or stop queueing [`DomMutation`]s. Stopping drops the queue.
+    pub fn set_mutation_recording(&self, enabled: bool) {
+        self.geometry.set_mutation_recording(enabled);
+    }
+
+    /// Changes queued since the last call, in the order script made them.
+    pub fn take_mutations(&self) -> Vec<DomMutation> {
+        self.geometry.take_mutations()
+    }
+
+    /// Record a wheel or keyboard scroll by the user. The next layout keeps
+    /// scroll offsets as they are instead of following scroll anchors.
+    pub fn note_user_scroll(&self) {
+        self.geometry.note_user_scroll();
+    }
+
+    /// How many times a layout moved a scroll offset to keep the content in
+    /// view from jumping.
+    pub fn scroll_anchor_adjustments(&self) -> u64 {
+        self.geometry.anchor_adjustments()
+    }
+
+    /// Get the scroll position script set on a node.
+    ///
+    /// The viewport's scroll position is keyed by the document element.
+    pub fn scroll_position(&self, node_id: NodeId) -> (f32, f32) {
+        self.geometry.scroll_position(node_id)
+    }
+
+    /// Every scrolled node with its offset. The viewport is keyed by the
+    /// document element.
+    pub fn scroll_positions(&self) -> Vec<(NodeId, (f32, f32))> {
+        self.geometry.scroll_positions()
+    }
+
+    /// Scroll a node as `scrollTo` would, clamped to its scrollable range.
+    pub fn set_scroll_position(&self, node_id: NodeId, x: f32, y: f32) {
+        self.geometry.scroll_to(node_id, x, y);
+    }
+
+    /// Run page script as a classic script. What it throws is reported to
+    /// the page's error handlers and [`Self::take_page_errors`] rather than
+    /// returned; `muted` hides the details of a cross-origin script fetched
+    /// without CORS approval.
+    pub fn run_script(&self, source: &str, muted: bool) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&errors::run_script(source, muted))?;
+        Ok(())
+    }
+
+    /// Run page script like [`Self::run_script`], stopping it once any of
+    /// its loops has run `max_loop_iterations` times. Page script can't
+    /// catch that; it is reported as a [`PageErrorKind::BudgetExceeded`]
+    /// error instead.
+    pub fn run_script_bounded(
+        &self,
+        source: &str,
+        muted: bool,
+        max_loop_iterations: u64,
+    ) -> Result<(), BindingError> {
+        let result = self
+            .runtime
+            .borrow_mut()
+            .evaluate_script_bounded(&errors::run_script(source, muted), max_loop_iterations);
+        match result {
+            Ok(_) => Ok(()),
+            Err(JsError::ExecutionError(message)) => {
+                let source_url = if muted {
+                    String::new()
+                } else {
+                    self.window.borrow().location.href.clone()
+                };
+                self.errors.report(PageError {
+                    kind: PageErrorKind::BudgetExceeded,
+                    message: if muted {
+                        "Script error.".into()
+                    } else {
+                        message
+                    },
+                    source_url,
+                    line: 0,
+                    column: 0,
+                    stack: None,
+                });
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Take the errors page script left unhandled since the last call.
+    pub fn take_page_errors(&self) -> Vec<PageError> {
+        self.errors.take_errors()
+    }
+
     /// Evaluate a script in the bound context.
     pub fn evaluate(&self, script: &str) -> Result<JsValue, BindingError> {
         self.runtime
@@ -863,6 +1491,294 @@ impl DomBindings {
             .map_err(Into::into)
     }
 
+    /// Fire `beforeunload` at the window.
+    ///
+    /// Returns the message to confirm leaving the page with, if a listener
+    /// cancelled the event or set `returnValue`.
+    pub fn dispatch_before_unload(&self) -> Result<Option<String>, BindingError> {
+        let script = format!(
+            "{} window.dispatchEvent(__rustkit_event); \
+             (function(e) {{ delete window.__rustkit_event; \
+                 return e.defaultPrevented || e.returnValue ? String(e.returnValue || '') : null; \
+             }})(__rustkit_event)",
+            Self::create_window_event("beforeunload", true)
+        );
+        let result = self
+            .runtime
+            .borrow_mut()
+            .evaluate_script_bounded(&script, UNLOAD_LOOP_LIMIT)?;
+        Ok(match result {
+            JsValue::String(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    /// Fire `pagehide` then `unload` at the window before the document is
+    /// replaced.
+    pub fn dispatch_unload(&self) -> Result<(), BindingError> {
+        for event_type in ["pagehide", "unload"] {
+            let script = format!(
+                "{} window.dispatchEvent(__rustkit_event); delete window.__rustkit_event;",
+                Self::create_window_event(event_type, false)
+            );
+            self.runtime
+                .borrow_mut()
+                .evaluate_script_bounded(&script, UNLOAD_LOOP_LIMIT)?;
+        }
+        Ok(())
+    }
+
+    /// Update `navigator.onLine`, firing `online` or `offline` at the
+    /// window if it changed.
+    pub fn set_online(&self, online: bool) -> Result<(), BindingError> {
+        let event_type = if online { "online" } else { "offline" };
+        let script = format!(
+            "if (window.navigator.onLine !== {online}) {{ \
+                 window.navigator.onLine = {online}; \
+                 {event} window.dispatchEvent(__rustkit_event); delete window.__rustkit_event; \
+             }}",
+            online = online,
+            event = Self::create_window_event(event_type, false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Format `Intl` and `toLocaleString` output with `provider`'s data and
+    /// report its preferred languages as `navigator.languages`.
+    pub fn set_locale_provider(
+        &self,
+        provider: Arc<dyn LocaleProvider>,
+    ) -> Result<(), BindingError> {
+        self.intl.set_provider(provider);
+        let languages = self.intl.preferred_languages();
+        let Some(language) = languages.first().cloned() else {
+            return Ok(());
+        };
+        let script = format!(
+            "window.navigator.language = {}; window.navigator.languages = {};",
+            serde_json::to_string(&language).unwrap_or_default(),
+            serde_json::to_string(&languages).unwrap_or_default()
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        let mut window = self.window.borrow_mut();
+        window.navigator.language = language;
+        window.navigator.languages = languages;
+        Ok(())
+    }
+
+    /// Update `document.visibilityState` and `document.hidden`, firing
+    /// `visibilitychange` at the document if they changed.
+    pub fn set_visibility(&self, hidden: bool) -> Result<(), BindingError> {
+        let script = format!(
+            "if (document.hidden !== {hidden}) {{ \
+                 document.hidden = {hidden}; \
+                 document.visibilityState = {state:?}; \
+                 {event} document.dispatchEvent(__rustkit_event); delete window.__rustkit_event; \
+             }}",
+            hidden = hidden,
+            state = if hidden { "hidden" } else { "visible" },
+            event = Self::create_window_event("visibilitychange", false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Set `document.readyState` to `loading` while the document's scripts
+    /// run.
+    pub fn begin_loading(&self) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script("document.readyState = 'loading';")?;
+        Ok(())
+    }
+
+    /// Move `document.readyState` to `interactive` and fire
+    /// `DOMContentLoaded` at the document, once its parser-inserted and
+    /// deferred scripts have run.
+    pub fn dispatch_dom_content_loaded(&self) -> Result<(), BindingError> {
+        self.set_ready_state("interactive")?;
+        let script = format!(
+            "{} document.dispatchEvent(__rustkit_event); delete window.__rustkit_event;",
+            Self::create_window_event("DOMContentLoaded", false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Move `document.readyState` to `complete` and fire `load` at the
+    /// window.
+    pub fn dispatch_load(&self) -> Result<(), BindingError> {
+        self.set_ready_state("complete")?;
+        let script = format!(
+            "{} window.dispatchEvent(__rustkit_event); delete window.__rustkit_event;",
+            Self::create_window_event("load", false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Set `document.readyState`, firing `readystatechange` at the document
+    /// if it changed.
+    fn set_ready_state(&self, state: &str) -> Result<(), BindingError> {
+        let script = format!(
+            "if (document.readyState !== {state:?}) {{ \
+                 document.readyState = {state:?}; \
+                 {event} document.dispatchEvent(__rustkit_event); delete window.__rustkit_event; \
+             }}",
+            state = state,
+            event = Self::create_window_event("readystatechange", false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Fire `focus` or `blur` at the window.
+    pub fn dispatch_window_focus(&self, focused: bool) -> Result<(), BindingError> {
+        let script = format!(
+            "{} window.dispatchEvent(__rustkit_event); delete window.__rustkit_event;",
+            Self::create_window_event(if focused { "focus" } else { "blur" }, false)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Run the `setTimeout` and `setInterval` callbacks due at `now`, in
+    /// milliseconds on the document's clock. Intervals shorter than
+    /// `min_interval` are stretched to it.
+    ///
+    /// Returns when the next timer is due, if any is set.
+    pub fn run_timers(&self, now: f64, min_interval: f64) -> Result<Option<f64>, BindingError> {
+        let script = format!("__rustkit_runTimers({}, {})", now, min_interval);
+        match self.runtime.borrow_mut().evaluate_script(&script)? {
+            JsValue::Number(next) if next >= 0.0 => Ok(Some(next)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Run the `requestAnimationFrame` callbacks queued before this frame
+    /// with timestamp `now`. Returns whether callbacks were queued for the
+    /// next frame.
+    pub fn run_animation_frames(&self, now: f64) -> Result<bool, BindingError> {
+        let script = format!("__rustkit_runAnimationFrames({})", now);
+        Ok(self
+            .runtime
+            .borrow_mut()
+            .evaluate_script(&script)?
+            .is_truthy())
+    }
+
+    /// Deliver `ResizeObserver` and `IntersectionObserver` entries for the
+    /// latest layout and scroll offsets, with `now` as the entries' time.
+    ///
+    /// Resize callbacks may change styles; check [`Self::needs_relayout`]
+    /// afterwards.
+    pub fn run_layout_observers(&self, now: f64) -> Result<(), BindingError> {
+        let script = format!("__rustkit_runLayoutObservers({});", now);
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Whether script has requested an animation frame.
+    pub fn has_animation_frames(&self) -> bool {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script("__rustkit_frames.some(function(f) { return !f.cancelled; })")
+            .is_ok_and(|v| v.is_truthy())
+    }
+
+    /// Create a JavaScript Event object for a window lifecycle event.
+    fn create_window_event(event_type: &str, cancelable: bool) -> String {
+        format!(
+            "var __rustkit_event = {{ type: {:?}, bubbles: false, cancelable: {}, \
+             defaultPrevented: false, returnValue: '', timeStamp: Date.now(), isTrusted: true, \
+             preventDefault: function() {{ if (this.cancelable) this.defaultPrevented = true; }}, \
+             stopPropagation: function() {{}}, stopImmediatePropagation: function() {{}} }};",
+            event_type, cancelable
+        )
+    }
+
+    /// Publish the permission states `navigator.permissions.query` reports,
+    /// as `(name, state)` pairs such as `("clipboard-read", "granted")`.
+    /// Names not listed are rejected as unknown.
+    pub fn set_permission_states(&self, states: &[(&str, &str)]) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&permissions::states_script(states))?;
+        Ok(())
+    }
+
+    /// Take the window requests script queued since the last call.
+    pub fn take_window_requests(&self) -> Vec<WindowRequest> {
+        self.popups.take_requests()
+    }
+
+    /// Open a window as if script called `window.open(url, target, features)`.
+    pub fn open_window(&self, url: &str, target: &str, features: &str) -> Result<(), BindingError> {
+        let script = format!(
+            "window.open({}, {}, {});",
+            serde_json::Value::from(url),
+            serde_json::Value::from(target),
+            serde_json::Value::from(features)
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Mark the proxy of a popup as closed, because the host blocked it or
+    /// its view went away.
+    pub fn popup_closed(&self, popup_id: u64) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&format!("__rustkit_popupClosed({});", popup_id))?;
+        Ok(())
+    }
+
+    /// Give this window an `opener` and the name it was opened with.
+    pub fn set_opener(&self, name: &str) -> Result<(), BindingError> {
+        self.window.borrow_mut().name = name.to_string();
+        self.runtime.borrow_mut().evaluate_script(&format!(
+            "__rustkit_setOpener({});",
+            serde_json::Value::from(name)
+        ))?;
+        Ok(())
+    }
+
+    /// Mark `window.opener` as closed.
+    pub fn opener_closed(&self) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script("__rustkit_openerClosed();")?;
+        Ok(())
+    }
+
+    /// Fire a `message` event at the window. `data` is JSON and `source`
+    /// names the sending window relative to this one.
+    pub fn deliver_message(
+        &self,
+        data: &str,
+        origin: &str,
+        source: WindowTarget,
+    ) -> Result<(), BindingError> {
+        let (kind, id) = match source {
+            WindowTarget::Popup(id) => ("popup", id),
+            WindowTarget::Opener => ("opener", 0),
+            WindowTarget::Current => ("self", 0),
+            WindowTarget::Parent => ("parent", 0),
+            WindowTarget::Top => ("top", 0),
+            WindowTarget::Frame(index) => ("frame", index as u64),
+        };
+        let script = format!(
+            "__rustkit_deliverMessage({}, {}, {:?}, {});",
+            serde_json::Value::from(data),
+            serde_json::Value::from(origin),
+            kind,
+            id
+        );
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
     /// Drain the IPC message queue.
     ///
     /// This method collects all IPC messages that were queued via
@@ -945,21 +1861,58 @@ impl DomBindings {
     }
 
     /// Dispatch an event with additional data.
+    ///
+    /// The event runs the capture listeners from the root down to the
+    /// target, then the others back up, past the target only if the event
+    /// bubbles. Composed events leave shadow trees through their hosts, and
+    /// listeners outside a shadow tree see its host as the target.
     pub fn dispatch_event_with_data(
         &self,
         node_id: NodeId,
         event_type: &str,
         event_data: Option<&EventData>,
     ) -> Result<bool, BindingError> {
-        let listeners: Vec<_> = self
-            .event_listeners
-            .borrow()
+        let (bubbles, composed) = Self::event_flags(event_type);
+
+        // Each node on the path with the target it sees
+        let target = self.geometry.document().and_then(|d| d.get_node(node_id));
+        let path: Vec<(NodeId, NodeId)> = match &target {
+            Some(target) => rustkit_dom::event_path(target, composed)
+                .iter()
+                .map(|node| (node.id, rustkit_dom::retarget(target, node).id))
+                .collect(),
+            None => vec![(node_id, node_id)],
+        };
+
+        // Listener batches in the order they run
+        let listeners = self.event_listeners.borrow();
+        let batch = |node: NodeId, shown: NodeId, capture: bool| {
+            let callbacks: Vec<_> = listeners
+                .iter()
+                .filter(|l| l.node_id == node && l.event_type == event_type && l.capture == capture)
+                .map(|l| l.callback.clone())
+                .collect();
+            let phase = match (node == shown, capture) {
+                (true, _) => 2,
+                (false, true) => 1,
+                (false, false) => 3,
+            };
+            (node, shown, phase, callbacks)
+        };
+        let batches: Vec<_> = path
             .iter()
-            .filter(|l| l.node_id == node_id && l.event_type == event_type)
-            .map(|l| l.callback.clone())
+            .rev()
+            .map(|&(node, shown)| batch(node, shown, true))
+            .chain(
+                path.iter()
+                    .filter(|&&(node, shown)| bubbles || node == shown)
+                    .map(|&(node, shown)| batch(node, shown, false)),
+            )
+            .filter(|(_, _, _, callbacks)| !callbacks.is_empty())
             .collect();
+        drop(listeners);
 
-        if listeners.is_empty() {
+        if batches.is_empty() {
             return Ok(true);
         }
 
@@ -970,11 +1923,29 @@ impl DomBindings {
         runtime.evaluate_script(&event_js)?;
 
         // Execute each listener
-        for callback in listeners {
+        for (node, shown, phase, callbacks) in batches {
+            let stopped = runtime.evaluate_script(
+                "!!(__rustkit_event._stopped || __rustkit_event._stoppedImmediate)",
+            )?;
+            if matches!(stopped, JsValue::Boolean(true)) {
+                break;
+            }
             runtime.evaluate_script(&format!(
-                "(function(e) {{ {} }})(__rustkit_event)",
-                callback
+                "__rustkit_event.target = __rustkit_eventNode({}); \
+                 __rustkit_event.currentTarget = __rustkit_eventNode({}); \
+                 __rustkit_event.eventPhase = {};",
+                shown.raw(),
+                node.raw(),
+                phase
             ))?;
+            for callback in callbacks {
+                runtime.evaluate_script(&format!(
+                    "if (!__rustkit_event._stoppedImmediate) \
+                     try {{ (function(e) {{ {} }})(__rustkit_event); }} \
+                     catch (e) {{ __rustkit_reportException(e, false); }}",
+                    callback
+                ))?;
+            }
         }
 
         // Check if default was prevented
@@ -987,11 +1958,84 @@ impl DomBindings {
         Ok(!was_prevented)
     }
 
+    /// Dispatch drag event `event_type` at `node_id`. Returns whether the
+    /// event was not cancelled and the `dropEffect` its listeners left.
+    pub fn dispatch_drag_event(
+        &self,
+        node_id: NodeId,
+        event_type: &str,
+        data: &DragEventData,
+    ) -> Result<(bool, String), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script("__rustkit_dragData = null;")?;
+        let not_cancelled = self.dispatch_event_with_data(
+            node_id,
+            event_type,
+            Some(&EventData::Drag(data.clone())),
+        )?;
+        // Without listeners no dataTransfer was made
+        let effect = match self
+            .runtime
+            .borrow_mut()
+            .evaluate_script("__rustkit_dragData && __rustkit_dragData.dropEffect")?
+        {
+            JsValue::String(effect) => effect,
+            _ => data.data_transfer.drop_effect.clone(),
+        };
+        Ok((not_cancelled, effect))
+    }
+
+    /// Whether events of `event_type` bubble and whether they are
+    /// composed, leaving shadow trees.
+    fn event_flags(event_type: &str) -> (bool, bool) {
+        let bubbles = !matches!(
+            event_type,
+            "focus"
+                | "blur"
+                | "load"
+                | "unload"
+                | "error"
+                | "abort"
+                | "scroll"
+                | "invalid"
+                | "toggle"
+                | "mouseenter"
+                | "mouseleave"
+                | "pointerenter"
+                | "pointerleave"
+        );
+        let composed = event_type.starts_with("mouse")
+            || event_type.starts_with("pointer")
+            || event_type.starts_with("key")
+            || event_type.starts_with("touch")
+            || event_type.starts_with("drag")
+            || event_type.starts_with("composition")
+            || matches!(
+                event_type,
+                "click"
+                    | "dblclick"
+                    | "auxclick"
+                    | "contextmenu"
+                    | "wheel"
+                    | "drop"
+                    | "focus"
+                    | "blur"
+                    | "focusin"
+                    | "focusout"
+                    | "beforeinput"
+                    | "input"
+            );
+        (bubbles, composed)
+    }
+
     /// Create a JavaScript Event object.
     fn create_event_object(event_type: &str, data: Option<&EventData>) -> String {
+        let (bubbles, composed) = Self::event_flags(event_type);
         let mut props = vec![
             format!("type: {:?}", event_type),
-            "bubbles: true".to_string(),
+            format!("bubbles: {}", bubbles),
+            format!("composed: {}", composed),
             "cancelable: true".to_string(),
             "defaultPrevented: false".to_string(),
             "target: null".to_string(),
@@ -1047,6 +2091,26 @@ impl DomBindings {
                     props.push(format!("inputType: {:?}", input.input_type));
                     props.push(format!("isComposing: {}", input.is_composing));
                 }
+                EventData::Transition(transition) => {
+                    props.push(format!("propertyName: {:?}", transition.property_name));
+                    props.push(format!("elapsedTime: {}", transition.elapsed_time));
+                    props.push(format!("pseudoElement: {:?}", transition.pseudo_element));
+                }
+                EventData::Drag(drag) => {
+                    props.push(format!("clientX: {}", drag.client_x));
+                    props.push(format!("clientY: {}", drag.client_y));
+                    props.push(format!("screenX: {}", drag.screen_x));
+                    props.push(format!("screenY: {}", drag.screen_y));
+                    props.push(format!("ctrlKey: {}", drag.ctrl_key));
+                    props.push(format!("altKey: {}", drag.alt_key));
+                    props.push(format!("shiftKey: {}", drag.shift_key));
+                    props.push(format!("metaKey: {}", drag.meta_key));
+                    // The data stays protected until the drop
+                    props.push(format!(
+                        "dataTransfer: {}",
+                        drag::data_transfer_script(&drag.data_transfer, event_type != "drop")
+                    ));
+                }
             }
         }
 
@@ -1130,6 +2194,28 @@ mod tests {
         assert!(matches!(result, JsValue::String(s) if s == "value"));
     }
 
+    #[test]
+    fn test_set_location() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+
+        let url = Url::parse("https://example.com:8080/app?tab=2#top").unwrap();
+        bindings.set_location(&url).unwrap();
+
+        let origin = bindings.evaluate("location.origin").unwrap();
+        assert!(matches!(origin, JsValue::String(s) if s == "https://example.com:8080"));
+
+        let tab = bindings
+            .evaluate("new URL(window.location.href).searchParams.get('tab')")
+            .unwrap();
+        assert!(matches!(tab, JsValue::String(s) if s == "2"));
+
+        // Relative navigation resolves against the current location
+        bindings.evaluate("location.href = '../other'").unwrap();
+        let href = bindings.evaluate("location.href").unwrap();
+        assert!(matches!(href, JsValue::String(s) if s == "https://example.com:8080/other"));
+    }
+
     #[test]
     fn test_set_dimensions() {
         let runtime = JsRuntime::new().unwrap();
@@ -1288,4 +2374,151 @@ mod tests {
         let method = bindings.evaluate("form.method").unwrap();
         assert!(matches!(method, JsValue::String(s) if s == "post"));
     }
+
+    #[test]
+    fn test_before_unload_prompt() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        assert_eq!(bindings.dispatch_before_unload().unwrap(), None);
+
+        bindings
+            .evaluate(
+                r#"
+            window.addEventListener('beforeunload', function(e) {
+                e.preventDefault();
+                e.returnValue = 'Unsaved changes';
+            });
+        "#,
+            )
+            .unwrap();
+        assert_eq!(
+            bindings.dispatch_before_unload().unwrap().as_deref(),
+            Some("Unsaved changes")
+        );
+
+        // A string returned from the on-handler also asks for confirmation
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings
+            .evaluate("window.onbeforeunload = function() { return 'Leave?'; };")
+            .unwrap();
+        assert_eq!(
+            bindings.dispatch_before_unload().unwrap().as_deref(),
+            Some("Leave?")
+        );
+    }
+
+    #[test]
+    fn test_unload_events() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings
+            .evaluate(
+                r#"
+            var fired = [];
+            window.addEventListener('pagehide', function(e) { fired.push(e.type); });
+            window.onunload = function(e) { fired.push(e.type); };
+        "#,
+            )
+            .unwrap();
+        bindings.dispatch_unload().unwrap();
+
+        let fired = bindings.evaluate("fired.join(',')").unwrap();
+        assert!(matches!(fired, JsValue::String(s) if s == "pagehide,unload"));
+    }
+
+    #[test]
+    fn test_runaway_unload_handler_is_bounded() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings
+            .evaluate("window.onunload = function() { while (true) {} };")
+            .unwrap();
+        // The loop limit aborts the handler instead of hanging the navigation
+        assert!(bindings.dispatch_unload().is_err());
+        let after = bindings.evaluate("1 + 1").unwrap();
+        assert!(matches!(after, JsValue::Number(n) if n == 2.0));
+    }
+
+    #[test]
+    fn test_load_events_follow_ready_state() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings.begin_loading().unwrap();
+        bindings
+            .evaluate(
+                r#"
+            var fired = [document.readyState];
+            document.addEventListener('readystatechange', function() {
+                fired.push(document.readyState);
+            });
+            document.addEventListener('DOMContentLoaded', function(e) { fired.push(e.type); });
+            window.onload = function(e) { fired.push(e.type); };
+        "#,
+            )
+            .unwrap();
+        bindings.dispatch_dom_content_loaded().unwrap();
+        bindings.dispatch_load().unwrap();
+
+        let fired = bindings.evaluate("fired.join(',')").unwrap();
+        assert!(
+            matches!(&fired, JsValue::String(s) if s == "loading,interactive,DOMContentLoaded,complete,load"),
+            "{:?}",
+            fired
+        );
+    }
+
+    #[test]
+    fn test_online_offline_events() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings
+            .evaluate(
+                r#"
+            var fired = [];
+            window.addEventListener('offline', function(e) { fired.push(e.type); });
+            window.ononline = function(e) { fired.push(e.type); };
+        "#,
+            )
+            .unwrap();
+
+        bindings.set_online(false).unwrap();
+        let online = bindings.evaluate("window.navigator.onLine").unwrap();
+        assert!(matches!(online, JsValue::Boolean(false)));
+        // Unchanged state fires nothing
+        bindings.set_online(false).unwrap();
+        bindings.set_online(true).unwrap();
+
+        let fired = bindings.evaluate("fired.join(',')").unwrap();
+        assert!(matches!(fired, JsValue::String(s) if s == "offline,online"));
+    }
+
+    #[test]
+    fn test_visibility_and_focus_events() {
+        let runtime = JsRuntime::new().unwrap();
+        let bindings = DomBindings::new(runtime).unwrap();
+        bindings
+            .evaluate(
+                r#"
+            var fired = [];
+            document.addEventListener('visibilitychange', function(e) {
+                fired.push(e.type + ':' + document.visibilityState + ':' + document.hidden);
+            });
+            window.addEventListener('blur', function(e) { fired.push(e.type); });
+            window.onfocus = function(e) { fired.push(e.type); };
+        "#,
+            )
+            .unwrap();
+
+        bindings.set_visibility(false).unwrap();
+        bindings.set_visibility(true).unwrap();
+        bindings.set_visibility(true).unwrap();
+        bindings.dispatch_window_focus(false).unwrap();
+        bindings.dispatch_window_focus(true).unwrap();
+        bindings.set_visibility(false).unwrap();
+
+        let fired = bindings.evaluate("fired.join(',')").unwrap();
+        assert!(matches!(fired, JsValue::String(s)
+            if s == "visibilitychange:hidden:true,blur,focus,visibilitychange:visible:false"));
+    }
 }
@@ -0,0 +1,163 @@
+//! `window.matchMedia` and `MediaQueryList` change events.
+//!
+//! Queries are evaluated against the environment the engine last published
+//! with [`crate::DomBindings::set_media_environment`]. Every list returned by
+//! `matchMedia` is re-evaluated when it changes, and fires `change` only when
+//! its `matches` flips.
+
+use rustkit_css::{MediaEnvironment, MediaQueryList};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The environment media queries are evaluated against.
+#[derive(Debug, Default)]
+pub(crate) struct MediaState {
+    environment: RefCell<MediaEnvironment>,
+}
+
+impl MediaState {
+    /// Replace the environment. Returns whether it changed.
+    pub(crate) fn set_environment(&self, environment: MediaEnvironment) -> bool {
+        let mut current = self.environment.borrow_mut();
+        if *current == environment {
+            return false;
+        }
+        *current = environment;
+        true
+    }
+
+    pub(crate) fn environment(&self) -> MediaEnvironment {
+        self.environment.borrow().clone()
+    }
+}
+
+const MEDIA_JS: &str = r#"
+    var __rustkit_mediaLists = [];
+
+    window.matchMedia = function(query) {
+        var list = {
+            media: String(query),
+            matches: __rustkit_match_media(String(query)),
+            onchange: null,
+            _listeners: [],
+            addEventListener: function(type, listener) {
+                if (type === 'change' && listener && this._listeners.indexOf(listener) < 0) {
+                    this._listeners.push(listener);
+                }
+            },
+            removeEventListener: function(type, listener) {
+                var index = this._listeners.indexOf(listener);
+                if (type === 'change' && index >= 0) this._listeners.splice(index, 1);
+            },
+            // Deprecated aliases still used by many sites
+            addListener: function(listener) { this.addEventListener('change', listener); },
+            removeListener: function(listener) { this.removeEventListener('change', listener); },
+            dispatchEvent: function(event) {
+                if (typeof this.onchange === 'function') this.onchange.call(this, event);
+                this._listeners.slice().forEach(function(listener) {
+                    listener.call(this, event);
+                }, this);
+                return true;
+            }
+        };
+        __rustkit_mediaLists.push(list);
+        return list;
+    };
+
+    // Re-evaluate every list and notify those whose result flipped
+    function __rustkit_mediaChanged() {
+        __rustkit_mediaLists.forEach(function(list) {
+            var matches = __rustkit_match_media(list.media);
+            if (matches === list.matches) return;
+            list.matches = matches;
+            list.dispatchEvent({
+                type: 'change', media: list.media, matches: matches,
+                target: list, currentTarget: list, timeStamp: Date.now(), isTrusted: true
+            });
+        });
+    }
+"#;
+
+/// Register `matchMedia` on the window.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<MediaState>) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_match_media", 1, move |args| {
+        let query = args.first().map(String::as_str).unwrap_or("");
+        let environment = state.environment.borrow();
+        Ok(JsValue::Boolean(
+            MediaQueryList::parse(query).matches(&environment),
+        ))
+    })?;
+    runtime.evaluate_script(MEDIA_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_css::{ColorScheme, MediaEnvironment};
+    use rustkit_js::{JsRuntime, JsValue};
+
+    fn eval_number(bindings: &DomBindings, script: &str) -> f64 {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::Number(n) => n,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_match_media_listener_fires_once_per_crossing() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_media_environment(MediaEnvironment::screen(800.0, 600.0))
+            .unwrap();
+        bindings
+            .evaluate(
+                "var mql = window.matchMedia('(max-width: 500px)'); \
+                 var changes = []; \
+                 mql.addEventListener('change', function(e) { changes.push(e.matches); }); \
+                 var legacy = 0; mql.addListener(function() { legacy++; });",
+            )
+            .unwrap();
+        assert!(matches!(
+            bindings.evaluate("mql.matches").unwrap(),
+            JsValue::Boolean(false)
+        ));
+
+        // Resizes that stay on one side of the breakpoint do not notify
+        for width in [700.0, 450.0, 400.0, 300.0, 900.0] {
+            bindings
+                .set_media_environment(MediaEnvironment::screen(width, 600.0))
+                .unwrap();
+        }
+        assert_eq!(eval_number(&bindings, "changes.length"), 2.0);
+        assert!(matches!(
+            bindings
+                .evaluate("changes[0] === true && changes[1] === false")
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+        assert_eq!(eval_number(&bindings, "legacy"), 2.0);
+    }
+
+    #[test]
+    fn test_match_media_color_scheme() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var dark = window.matchMedia('(prefers-color-scheme: dark)'); \
+                 var fired = 0; dark.onchange = function() { fired++; };",
+            )
+            .unwrap();
+        bindings
+            .set_media_environment(
+                MediaEnvironment::screen(800.0, 600.0).with_color_scheme(ColorScheme::Dark),
+            )
+            .unwrap();
+        assert!(matches!(
+            bindings.evaluate("dark.matches").unwrap(),
+            JsValue::Boolean(true)
+        ));
+        assert_eq!(eval_number(&bindings, "fired"), 1.0);
+    }
+}
@@ -0,0 +1,534 @@
+//! `ResizeObserver` and `IntersectionObserver`.
+//!
+//! Observers only record their targets here. The engine calls
+//! [`DomBindings::run_layout_observers`](crate::DomBindings::run_layout_observers)
+//! once per frame after layout and scrolling, which delivers resize entries
+//! with the depth-ordered loop of the spec and then intersection entries for
+//! every target that crossed a threshold.
+//!
+//! Resize callbacks that change styles are laid out synchronously through
+//! the [`LayoutProvider`](crate::LayoutProvider) before the next round of
+//! observations is gathered.
+
+use crate::geometry::{node_id_arg, GeometryState};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use rustkit_layout::Rect;
+use serde_json::json;
+use std::rc::Rc;
+
+/// Sizes a `ResizeObserver` reports for a node.
+fn resize_boxes(state: &GeometryState, node_id: rustkit_dom::NodeId) -> Option<serde_json::Value> {
+    let dimensions = state.first_box(node_id)?;
+    let border_box = dimensions.border_box();
+    let content = dimensions.content;
+    let node = state.document()?.get_node(node_id)?;
+    let depth = std::iter::successors(node.parent(), |n| n.parent()).count();
+    Some(json!({
+        "content": [dimensions.padding.left, dimensions.padding.top, content.width, content.height],
+        "border": [border_box.width, border_box.height],
+        "depth": depth,
+    }))
+}
+
+/// Edge-inclusive intersection, so boxes that only touch still intersect.
+fn intersect(a: &Rect, b: &Rect) -> Option<Rect> {
+    let (x, y) = (a.x.max(b.x), a.y.max(b.y));
+    let (right, bottom) = (a.right().min(b.right()), a.bottom().min(b.bottom()));
+    (right >= x && bottom >= y).then(|| Rect::new(x, y, right - x, bottom - y))
+}
+
+fn rect_json(rect: &Rect) -> serde_json::Value {
+    json!([rect.x, rect.y, rect.width, rect.height])
+}
+
+/// Intersection of `target` with the root's client area grown by `margins`
+/// (top, right, bottom, left; each a length or a percentage of the root).
+/// The root defaults to the viewport.
+fn intersection(
+    state: &GeometryState,
+    target: rustkit_dom::NodeId,
+    root: Option<rustkit_dom::NodeId>,
+    margins: &[(f32, bool); 4],
+) -> Option<serde_json::Value> {
+    let document = state.document()?;
+    let root = root.or_else(|| document.document_element().map(|n| n.id))?;
+    let target_rect = state.client_bounds(target)?;
+    let area = state.client_area(root)?;
+    let resolve = |(value, percent): (f32, bool), length: f32| match percent {
+        true => value * length / 100.0,
+        false => value,
+    };
+    let (top, right, bottom, left) = (
+        resolve(margins[0], area.height),
+        resolve(margins[1], area.width),
+        resolve(margins[2], area.height),
+        resolve(margins[3], area.width),
+    );
+    let root_rect = Rect::new(
+        area.x - left,
+        area.y - top,
+        area.width + left + right,
+        area.height + top + bottom,
+    );
+
+    // Clip by every scroll container between the target and the root; a
+    // target outside the root never intersects
+    let mut clipped = Some(target_rect);
+    let mut ancestor = document.get_node(target)?.parent();
+    let mut inside_root = false;
+    while let Some(node) = ancestor {
+        if node.id == root {
+            inside_root = true;
+            break;
+        }
+        if state.clips_content(node.id) {
+            clipped = clipped
+                .zip(state.client_area(node.id))
+                .and_then(|(a, b)| intersect(&a, &b));
+        }
+        ancestor = node.parent();
+    }
+    let hit = clipped
+        .filter(|_| inside_root)
+        .and_then(|rect| intersect(&rect, &root_rect));
+
+    let target_area = target_rect.width * target_rect.height;
+    let ratio = match &hit {
+        Some(rect) if target_area > 0.0 => (rect.width * rect.height / target_area).min(1.0),
+        Some(_) => 1.0,
+        None => 0.0,
+    };
+    Some(json!({
+        "target": rect_json(&target_rect),
+        "root": rect_json(&root_rect),
+        "intersection": rect_json(&hit.unwrap_or_else(Rect::zero)),
+        "intersecting": hit.is_some(),
+        "ratio": ratio,
+    }))
+}
+
+const OBSERVERS_JS: &str = r#"
+    var __rustkit_resizeObservers = [];
+    var __rustkit_intersectionObservers = [];
+
+    function ResizeObserver(callback) {
+        if (typeof callback !== 'function') {
+            throw new TypeError("ResizeObserver requires a callback");
+        }
+        this._callback = callback;
+        this._observations = [];
+        __rustkit_resizeObservers.push(this);
+    }
+    ResizeObserver.prototype.observe = function(target, options) {
+        var box = (options && options.box) || 'content-box';
+        if (['content-box', 'border-box', 'device-pixel-content-box'].indexOf(box) < 0) {
+            throw new TypeError("Invalid box option: " + box);
+        }
+        this.unobserve(target);
+        this._observations.push({ target: target, box: box, size: [0, 0], boxes: null });
+    };
+    ResizeObserver.prototype.unobserve = function(target) {
+        this._observations = this._observations.filter(function(o) {
+            return o.target !== target;
+        });
+    };
+    ResizeObserver.prototype.disconnect = function() {
+        this._observations = [];
+    };
+    // Entries for the observations whose size changed, which are then
+    // considered delivered
+    ResizeObserver.prototype.takeRecords = function() {
+        return this._observations.filter(__rustkit_resizeActive).map(__rustkit_resizeEntry);
+    };
+
+    function __rustkit_observedSize(observation) {
+        var boxes = observation.boxes;
+        if (!boxes) return [0, 0];
+        if (observation.box === 'border-box') return boxes.border;
+        var scale = observation.box === 'device-pixel-content-box' ? window.devicePixelRatio : 1;
+        return [boxes.content[2] * scale, boxes.content[3] * scale];
+    }
+
+    // Read the target's current boxes; true if its observed size changed
+    function __rustkit_resizeActive(observation) {
+        var id = observation.target._nodeId;
+        observation.boxes = id === undefined ? null : JSON.parse(__rustkit_resize_boxes(id));
+        var size = __rustkit_observedSize(observation);
+        return size[0] !== observation.size[0] || size[1] !== observation.size[1];
+    }
+
+    function __rustkit_resizeEntry(observation) {
+        var boxes = observation.boxes || { content: [0, 0, 0, 0], border: [0, 0] };
+        var dpr = window.devicePixelRatio;
+        var content = boxes.content;
+        observation.size = __rustkit_observedSize(observation);
+        return {
+            target: observation.target,
+            contentRect: new DOMRect(content[0], content[1], content[2], content[3]),
+            borderBoxSize: [{ inlineSize: boxes.border[0], blockSize: boxes.border[1] }],
+            contentBoxSize: [{ inlineSize: content[2], blockSize: content[3] }],
+            devicePixelContentBoxSize: [{ inlineSize: content[2] * dpr, blockSize: content[3] * dpr }]
+        };
+    }
+
+    // Mark observations deeper than `depth` active and shallower changed
+    // ones skipped; returns whether any are active
+    function __rustkit_gatherResizes(depth) {
+        var any = false;
+        __rustkit_resizeObservers.forEach(function(observer) {
+            observer._active = [];
+            observer._observations.forEach(function(observation) {
+                observation.skipped = false;
+                if (!__rustkit_resizeActive(observation)) return;
+                var targetDepth = observation.boxes ? observation.boxes.depth : 0;
+                if (targetDepth > depth) {
+                    observer._active.push(observation);
+                    any = true;
+                } else {
+                    observation.skipped = true;
+                }
+            });
+        });
+        return any;
+    }
+
+    // Deliver the active observations; returns the shallowest depth delivered
+    function __rustkit_broadcastResizes() {
+        var shallowest = Infinity;
+        __rustkit_resizeObservers.forEach(function(observer) {
+            if (!observer._active.length) return;
+            var entries = observer._active.map(function(observation) {
+                if (observation.boxes) shallowest = Math.min(shallowest, observation.boxes.depth);
+                return __rustkit_resizeEntry(observation);
+            });
+            observer._active = [];
+            try {
+                observer._callback.call(observer, entries, observer);
+            } catch (e) {
+                __rustkit_reportException(e, false);
+            }
+        });
+        return shallowest;
+    }
+
+    function __rustkit_runResizeObservers() {
+        if (!__rustkit_resizeObservers.length) return;
+        var depth = 0;
+        while (__rustkit_gatherResizes(depth)) {
+            depth = __rustkit_broadcastResizes();
+        }
+        var skipped = __rustkit_resizeObservers.some(function(observer) {
+            return observer._observations.some(function(o) { return o.skipped; });
+        });
+        if (skipped) {
+            var event = {
+                type: 'error', bubbles: false, cancelable: true, defaultPrevented: false,
+                message: 'ResizeObserver loop completed with undelivered notifications.',
+                error: null, timeStamp: Date.now(), isTrusted: true,
+                preventDefault: function() { this.defaultPrevented = true; },
+                stopPropagation: function() {}, stopImmediatePropagation: function() {}
+            };
+            window.dispatchEvent(event);
+        }
+    }
+
+    function __rustkit_parseRootMargin(text) {
+        var parts = String(text).trim().split(/\s+/);
+        if (parts.length > 4) throw new SyntaxError("Invalid rootMargin: " + text);
+        var values = parts.map(function(part) {
+            if (part === '0') return [0, false];
+            var match = /^(-?(?:\d+\.?\d*|\.\d+))(px|%)$/.exec(part);
+            if (!match) throw new SyntaxError("Invalid rootMargin: " + text);
+            return [Number(match[1]), match[2] === '%'];
+        });
+        // Expanded like the margin shorthand to top, right, bottom, left
+        var top = values[0], right = values[1] || top, bottom = values[2] || top;
+        return [top, right, bottom, values[3] || right];
+    }
+
+    function IntersectionObserver(callback, options) {
+        if (typeof callback !== 'function') {
+            throw new TypeError("IntersectionObserver requires a callback");
+        }
+        options = options || {};
+        var thresholds = options.threshold === undefined ? [0] : [].concat(options.threshold);
+        thresholds = thresholds.map(Number).sort(function(a, b) { return a - b; });
+        thresholds.forEach(function(t) {
+            if (!(t >= 0 && t <= 1)) throw new RangeError("Threshold values must be between 0 and 1");
+        });
+        this._callback = callback;
+        this._margins = __rustkit_parseRootMargin(options.rootMargin || '0px');
+        this._observations = [];
+        this._records = [];
+        this.root = options.root || null;
+        this.rootMargin = this._margins.map(function(m) {
+            return m[0] + (m[1] ? '%' : 'px');
+        }).join(' ');
+        this.thresholds = thresholds.length ? thresholds : [0];
+        __rustkit_intersectionObservers.push(this);
+    }
+    IntersectionObserver.prototype.observe = function(target) {
+        if (this._observations.some(function(o) { return o.target === target; })) return;
+        this._observations.push({ target: target, thresholdIndex: -1, intersecting: false });
+    };
+    IntersectionObserver.prototype.unobserve = function(target) {
+        this._observations = this._observations.filter(function(o) {
+            return o.target !== target;
+        });
+    };
+    IntersectionObserver.prototype.disconnect = function() {
+        this._observations = [];
+    };
+    IntersectionObserver.prototype.takeRecords = function() {
+        var records = this._records;
+        this._records = [];
+        return records;
+    };
+
+    // Queue an entry for every target that crossed a threshold
+    IntersectionObserver.prototype._update = function(time) {
+        var root = this.root;
+        var rootId = root ? root._nodeId : -1;
+        if (rootId === undefined) return;
+        var margins = JSON.stringify(this._margins);
+        this._observations.forEach(function(observation) {
+            var id = observation.target._nodeId;
+            var result = id === undefined ? null : JSON.parse(__rustkit_intersection(id, rootId, margins));
+            var intersecting = !!(result && result.intersecting);
+            var ratio = result ? result.ratio : 0;
+            var index = 0;
+            if (intersecting) {
+                while (index < this.thresholds.length && this.thresholds[index] <= ratio) index++;
+            }
+            if (index === observation.thresholdIndex && intersecting === observation.intersecting) return;
+            observation.thresholdIndex = index;
+            observation.intersecting = intersecting;
+            function rect(r) { return r ? new DOMRect(r[0], r[1], r[2], r[3]) : new DOMRect(0, 0, 0, 0); }
+            this._records.push({
+                time: time,
+                target: observation.target,
+                rootBounds: result ? rect(result.root) : null,
+                boundingClientRect: rect(result && result.target),
+                intersectionRect: rect(intersecting ? result.intersection : null),
+                isIntersecting: intersecting,
+                intersectionRatio: ratio
+            });
+        }, this);
+    };
+
+    function __rustkit_runIntersectionObservers(time) {
+        __rustkit_intersectionObservers.forEach(function(observer) {
+            observer._update(time);
+        });
+        __rustkit_intersectionObservers.forEach(function(observer) {
+            var records = observer.takeRecords();
+            if (!records.length) return;
+            try {
+                observer._callback.call(observer, records, observer);
+            } catch (e) {
+                __rustkit_reportException(e, false);
+            }
+        });
+    }
+
+    function __rustkit_runLayoutObservers(time) {
+        __rustkit_runResizeObservers();
+        __rustkit_runIntersectionObservers(time);
+    }
+
+    window.ResizeObserver = ResizeObserver;
+    window.IntersectionObserver = IntersectionObserver;
+"#;
+
+/// Register `ResizeObserver` and `IntersectionObserver`.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<GeometryState>) -> Result<(), JsError> {
+    let resize_state = state.clone();
+    runtime.register_function("__rustkit_resize_boxes", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        let boxes = resize_boxes(&resize_state, node_id);
+        Ok(JsValue::String(
+            boxes.map_or_else(|| "null".to_string(), |b| b.to_string()),
+        ))
+    })?;
+
+    runtime.register_function("__rustkit_intersection", 3, move |args| {
+        let target = node_id_arg(args)?;
+        let root = args
+            .get(1)
+            .and_then(|id| id.parse().ok())
+            .map(rustkit_dom::NodeId::new);
+        let margins: [(f32, bool); 4] = args
+            .get(2)
+            .and_then(|m| serde_json::from_str(m).ok())
+            .unwrap_or_default();
+        let result = intersection(&state, target, root, &margins);
+        Ok(JsValue::String(
+            result.map_or_else(|| "null".to_string(), |r| r.to_string()),
+        ))
+    })?;
+
+    runtime.evaluate_script(OBSERVERS_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DomBindings, GeometryMap};
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use rustkit_layout::{Dimensions, EdgeSizes, NodeGeometry, Rect};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    fn node(x: f32, y: f32, width: f32, height: f32) -> NodeGeometry {
+        NodeGeometry {
+            fragments: vec![Dimensions {
+                content: Rect::new(x, y, width, height),
+                ..Default::default()
+            }],
+            overflow: Rect::new(x, y, width, height),
+            scrollbars: (0.0, 0.0),
+            anchor_excluded: false,
+        }
+    }
+
+    fn eval_number(bindings: &DomBindings, script: &str) -> f64 {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::Number(n) => n,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_intersection_threshold_crossed_once_on_resize() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(
+            Document::parse_html("<html><body><div id='card'></div></body></html>").unwrap(),
+        );
+        bindings.set_document(document.clone()).unwrap();
+        let mut geometry = HashMap::new();
+        geometry.insert(
+            document.document_element().unwrap().id,
+            node(0.0, 0.0, 800.0, 1000.0),
+        );
+        geometry.insert(document.body().unwrap().id, node(0.0, 0.0, 800.0, 1000.0));
+        // 400px tall, starting 500px down the page
+        let card = document.get_element_by_id("card").unwrap().id;
+        geometry.insert(card, node(0.0, 500.0, 800.0, 400.0));
+        let geometry = Rc::new(geometry);
+
+        bindings.set_layout(geometry.clone(), (800.0, 550.0));
+        bindings
+            .evaluate(
+                "var entries = []; \
+                 new IntersectionObserver(function(list) { entries = entries.concat(list); }, \
+                     { threshold: 0.5 }).observe(document.getElementById('card'));",
+            )
+            .unwrap();
+        bindings.run_layout_observers(0.0).unwrap();
+        assert_eq!(eval_number(&bindings, "entries.length"), 1.0);
+        assert_eq!(
+            eval_number(&bindings, "entries[0].intersectionRatio"),
+            0.125
+        );
+
+        // Growing the viewport past the midpoint crosses the threshold once
+        for height in [800.0, 850.0, 900.0] {
+            bindings.set_layout(geometry.clone(), (800.0, height));
+            bindings.run_layout_observers(16.0).unwrap();
+        }
+        assert_eq!(eval_number(&bindings, "entries.length"), 2.0);
+        assert_eq!(eval_number(&bindings, "entries[1].intersectionRatio"), 0.75);
+        assert_eq!(
+            eval_number(&bindings, "entries[1].intersectionRect.height"),
+            300.0
+        );
+    }
+
+    /// Lays out `#box` as wide as its inline `width`, with 10px padding.
+    fn box_layout(document: &Document) -> GeometryMap {
+        let target = document.get_element_by_id("box").unwrap();
+        let width = target
+            .inline_style()
+            .and_then(|css| {
+                css.split(';')
+                    .filter_map(|decl| decl.split_once(':'))
+                    .find(|(name, _)| name.trim() == "width")
+                    .and_then(|(_, value)| value.trim().trim_end_matches("px").parse().ok())
+            })
+            .unwrap_or(100.0);
+        let mut geometry = HashMap::new();
+        geometry.insert(
+            document.document_element().unwrap().id,
+            node(0.0, 0.0, 800.0, 600.0),
+        );
+        geometry.insert(document.body().unwrap().id, node(0.0, 0.0, 800.0, 600.0));
+        let mut dimensions = Dimensions {
+            content: Rect::new(10.0, 10.0, width, 50.0),
+            ..Default::default()
+        };
+        dimensions.padding = EdgeSizes {
+            top: 10.0,
+            right: 10.0,
+            bottom: 10.0,
+            left: 10.0,
+        };
+        geometry.insert(
+            target.id,
+            NodeGeometry {
+                fragments: vec![dimensions],
+                ..node(0.0, 0.0, 0.0, 0.0)
+            },
+        );
+        geometry
+    }
+
+    #[test]
+    fn test_resize_observer_sees_style_change() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let document = Rc::new(
+            Document::parse_html("<html><body><div id='box'></div></body></html>").unwrap(),
+        );
+        bindings.set_document(document.clone()).unwrap();
+        bindings.set_layout_provider(|document, _| box_layout(document));
+        bindings.set_layout(Rc::new(box_layout(&document)), (800.0, 600.0));
+        bindings
+            .evaluate(
+                "var box = document.getElementById('box'); var sizes = []; \
+                 var observer = new ResizeObserver(function(entries) { \
+                     entries.forEach(function(e) { \
+                         sizes.push([e.contentRect.x, e.contentRect.width, e.borderBoxSize[0].inlineSize]); \
+                     }); \
+                 }); \
+                 observer.observe(box);",
+            )
+            .unwrap();
+        bindings.run_layout_observers(0.0).unwrap();
+        bindings.evaluate("box.style.width = '250px';").unwrap();
+        bindings.run_layout_observers(16.0).unwrap();
+        // Nothing changed since
+        bindings.run_layout_observers(32.0).unwrap();
+
+        let result = bindings.evaluate("JSON.stringify(sizes)").unwrap();
+        assert!(
+            matches!(&result, JsValue::String(s) if s == "[[10,100,120],[10,250,270]]"),
+            "{:?}",
+            result
+        );
+
+        // A callback that resizes its own target leaves that change for
+        // the next frame and reports the loop limit
+        bindings
+            .evaluate(
+                "var errors = 0; window.addEventListener('error', function() { errors++; }); \
+                 observer.disconnect(); \
+                 new ResizeObserver(function() { \
+                     box.style.width = (parseInt(box.style.width) + 1) + 'px'; \
+                 }).observe(box);",
+            )
+            .unwrap();
+        bindings.run_layout_observers(48.0).unwrap();
+        assert_eq!(eval_number(&bindings, "errors"), 1.0);
+        assert_eq!(eval_number(&bindings, "box.offsetWidth"), 271.0);
+    }
+}
@@ -0,0 +1,84 @@
+//! `navigator.permissions.query`.
+//!
+//! Decisions are made by the engine; script only sees the states it last
+//! published through [`crate::DomBindings::set_permission_states`]. Queries
+//! settle straight away and their status objects do not fire `change`.
+
+use rustkit_js::{JsError, JsRuntime};
+
+const PERMISSIONS_JS: &str = r#"
+    var __rustkit_permissionStates = {};
+
+    function __rustkit_setPermissionStates(states) {
+        __rustkit_permissionStates = states;
+    }
+
+    window.navigator.permissions = {
+        query: function(descriptor) {
+            var name = descriptor && descriptor.name;
+            if (typeof name !== 'string') {
+                return Promise.reject(new TypeError('Permission descriptor needs a name'));
+            }
+            if (!Object.prototype.hasOwnProperty.call(__rustkit_permissionStates, name)) {
+                return Promise.reject(
+                    new TypeError("'" + name + "' is not a valid permission name")
+                );
+            }
+            return Promise.resolve({
+                name: name,
+                state: __rustkit_permissionStates[name],
+                onchange: null,
+                addEventListener: function() {},
+                removeEventListener: function() {}
+            });
+        }
+    };
+"#;
+
+/// Install `navigator.permissions`, knowing no permissions until the engine
+/// publishes some.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.evaluate_script(PERMISSIONS_JS)?;
+    Ok(())
+}
+
+/// Script replacing the published states.
+pub(crate) fn states_script(states: &[(&str, &str)]) -> String {
+    let states: serde_json::Map<String, serde_json::Value> = states
+        .iter()
+        .map(|(name, state)| (name.to_string(), serde_json::Value::from(*state)))
+        .collect();
+    format!(
+        "__rustkit_setPermissionStates({});",
+        serde_json::Value::Object(states)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+
+    #[test]
+    fn test_query_reports_published_states() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_permission_states(&[("clipboard-read", "granted"), ("clipboard-write", "prompt")])
+            .unwrap();
+        bindings
+            .evaluate(
+                "var out = []; \
+                 navigator.permissions.query({ name: 'clipboard-read' }) \
+                     .then(function(s) { out.push(s.name + '=' + s.state); }); \
+                 navigator.permissions.query({ name: 'clipboard-write' }) \
+                     .then(function(s) { out.push(s.state); }); \
+                 navigator.permissions.query({ name: 'geolocation' }) \
+                     .catch(function(e) { out.push(e.name); });",
+            )
+            .unwrap();
+        let JsValue::String(out) = bindings.evaluate("out.join('|')").unwrap() else {
+            panic!("expected a string");
+        };
+        assert_eq!(out, "clipboard-read=granted|prompt|TypeError");
+    }
+}
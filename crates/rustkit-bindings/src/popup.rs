@@ -0,0 +1,423 @@
+//! `window.open`, `window.opener` and cross-window `postMessage`.
+//!
+//! Opening a window is mediated by the host: `window.open` queues a
+//! [`WindowRequest::Open`] and returns a proxy straight away. The proxy stays
+//! usable until the engine reports the popup blocked or closed. Messages are
+//! serialized to JSON and queued for the engine to deliver to the other view.
+
+use crate::activation::UserActivation;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+use url::Url;
+
+/// Features parsed from the third argument of `window.open`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowFeatures {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub left: Option<i32>,
+    pub top: Option<i32>,
+    /// A popup window rather than a new tab was requested.
+    pub popup: bool,
+    /// The new window must not get an `opener`.
+    pub noopener: bool,
+    /// No referrer is sent; implies `noopener`.
+    pub noreferrer: bool,
+}
+
+impl WindowFeatures {
+    /// Parse a features string such as `"popup,width=500,height=600"`.
+    ///
+    /// Unknown and malformed entries are ignored.
+    pub fn parse(features: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut popup = None;
+        for token in features.split([',', ' ']).filter(|t| !t.trim().is_empty()) {
+            let (name, value) = match token.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (token.trim(), None),
+            };
+            // Booleans are true when valueless, "yes", "true" or non-zero
+            let enabled = value.is_none_or(|v| {
+                v.eq_ignore_ascii_case("yes")
+                    || v.eq_ignore_ascii_case("true")
+                    || v.parse::<i64>().is_ok_and(|n| n != 0)
+            });
+            let number = value.and_then(|v| v.parse::<i32>().ok());
+            match name.to_ascii_lowercase().as_str() {
+                "width" | "innerwidth" => parsed.width = number.map(|n| n.max(100) as u32),
+                "height" | "innerheight" => parsed.height = number.map(|n| n.max(100) as u32),
+                "left" | "screenx" => parsed.left = number,
+                "top" | "screeny" => parsed.top = number,
+                "popup" => popup = Some(enabled),
+                "noopener" => parsed.noopener = enabled,
+                "noreferrer" => parsed.noreferrer = enabled,
+                _ => {}
+            }
+        }
+        if parsed.noreferrer {
+            parsed.noopener = true;
+        }
+        // Asking for a size or position implies a popup
+        parsed.popup = popup.unwrap_or(
+            parsed.width.is_some()
+                || parsed.height.is_some()
+                || parsed.left.is_some()
+                || parsed.top.is_some(),
+        );
+        parsed
+    }
+}
+
+/// The window a message or close request is addressed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowTarget {
+    /// A popup this window opened, by popup id.
+    Popup(u64),
+    /// The window that opened this one.
+    Opener,
+    /// This window itself.
+    Current,
+    /// The window embedding this frame.
+    Parent,
+    /// The top-level window of this frame tree.
+    Top,
+    /// A child frame of this window, by index in `window.frames`.
+    Frame(usize),
+}
+
+impl WindowTarget {
+    pub(crate) fn from_args(kind: &str, id: &str) -> Result<Self, JsError> {
+        match kind {
+            "popup" => id
+                .parse()
+                .map(WindowTarget::Popup)
+                .map_err(|_| JsError::TypeError("Expected a popup id".into())),
+            "opener" => Ok(WindowTarget::Opener),
+            "self" => Ok(WindowTarget::Current),
+            "parent" => Ok(WindowTarget::Parent),
+            "top" => Ok(WindowTarget::Top),
+            "frame" => id
+                .parse()
+                .map(WindowTarget::Frame)
+                .map_err(|_| JsError::TypeError("Expected a frame index".into())),
+            _ => Err(JsError::TypeError(format!("Unknown window {}", kind))),
+        }
+    }
+}
+
+/// A request from script that only the host can carry out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowRequest {
+    /// `window.open` or a `target="_blank"` link. `popup_id` is 0 when the
+    /// new window gets no opener.
+    Open {
+        popup_id: u64,
+        url: Url,
+        name: String,
+        features: WindowFeatures,
+        /// The window had transient user activation when it asked.
+        user_activated: bool,
+    },
+    /// `postMessage` to another window. `data` is JSON.
+    PostMessage {
+        target: WindowTarget,
+        data: String,
+        target_origin: String,
+    },
+    /// `close()` on a window.
+    Close { target: WindowTarget },
+    /// Script set another window's `location` or an iframe's `src`. The
+    /// host loads `url` into that frame through the normal pipeline.
+    Navigate { target: WindowTarget, url: Url },
+}
+
+/// Requests queued by script since the engine last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct PopupState {
+    next_id: Cell<u64>,
+    requests: RefCell<Vec<WindowRequest>>,
+}
+
+impl PopupState {
+    pub(crate) fn take_requests(&self) -> Vec<WindowRequest> {
+        self.requests.take()
+    }
+
+    pub(crate) fn push_request(&self, request: WindowRequest) {
+        self.requests.borrow_mut().push(request);
+    }
+}
+
+const POPUP_JS: &str = r#"
+    var __rustkit_popups = {};
+
+    function __rustkit_windowProxy(kind, id) {
+        return {
+            closed: false,
+            postMessage: function(message, targetOrigin) {
+                if (this.closed) return;
+                var data = JSON.stringify(message === undefined ? null : message);
+                var origin = targetOrigin === undefined ? '/' : String(targetOrigin);
+                __rustkit_post_window_message(kind, id, data === undefined ? 'null' : data, origin);
+            },
+            close: function() {
+                if (!this.closed) __rustkit_close_window(kind, id);
+            },
+            focus: function() {},
+            blur: function() {}
+        };
+    }
+
+    window.opener = null;
+    window.open = function(url, target, features) {
+        url = url === undefined || url === null ? '' : String(url);
+        var href = url === '' ? 'about:blank' : new URL(url, window.location.href).href;
+        var id = __rustkit_window_open(
+            href,
+            target === undefined || target === null || target === '' ? '_blank' : String(target),
+            features === undefined || features === null ? '' : String(features)
+        );
+        // noopener windows are not reachable from script
+        if (!id) return null;
+        var proxy = __rustkit_windowProxy('popup', id);
+        __rustkit_popups[id] = proxy;
+        return proxy;
+    };
+    window.close = function() {
+        __rustkit_close_window('self', 0);
+    };
+
+    function __rustkit_popupClosed(id) {
+        var proxy = __rustkit_popups[id];
+        if (proxy) proxy.closed = true;
+        delete __rustkit_popups[id];
+    }
+
+    function __rustkit_setOpener(name) {
+        window.opener = __rustkit_windowProxy('opener', 0);
+        window.name = name;
+    }
+
+    function __rustkit_openerClosed() {
+        if (window.opener) window.opener.closed = true;
+    }
+
+    function __rustkit_deliverMessage(data, origin, kind, id) {
+        var source = kind === 'opener' ? window.opener
+            : kind === 'parent' ? window.parent
+            : kind === 'top' ? window.top
+            : kind === 'frame' ? (window.frames[id] || null)
+            : (__rustkit_popups[id] || null);
+        window.dispatchEvent({
+            type: 'message', data: JSON.parse(data), origin: origin, source: source,
+            lastEventId: '', ports: [], bubbles: false, cancelable: false,
+            defaultPrevented: false, timeStamp: Date.now(), isTrusted: true,
+            preventDefault: function() {}, stopPropagation: function() {},
+            stopImmediatePropagation: function() {}
+        });
+    }
+"#;
+
+/// Register `window.open`, `window.close` and the proxy natives.
+///
+/// Opening a window consumes `activation`.
+pub(crate) fn install(
+    runtime: &mut JsRuntime,
+    state: Rc<PopupState>,
+    activation: Rc<UserActivation>,
+) -> Result<(), JsError> {
+    let open_state = state.clone();
+    runtime.register_function("__rustkit_window_open", 3, move |args| {
+        let url = args.first().map(String::as_str).unwrap_or("about:blank");
+        let url = Url::parse(url).map_err(|e| JsError::TypeError(format!("Invalid URL: {}", e)))?;
+        let name = args.get(1).cloned().unwrap_or_default();
+        let features = WindowFeatures::parse(args.get(2).map(String::as_str).unwrap_or(""));
+
+        let popup_id = if features.noopener {
+            0
+        } else {
+            open_state.next_id.set(open_state.next_id.get() + 1);
+            open_state.next_id.get()
+        };
+        open_state.requests.borrow_mut().push(WindowRequest::Open {
+            popup_id,
+            url,
+            name,
+            features,
+            user_activated: activation.consume(Instant::now()),
+        });
+        Ok(JsValue::Number(popup_id as f64))
+    })?;
+
+    let message_state = state.clone();
+    runtime.register_function("__rustkit_post_window_message", 4, move |args| {
+        let [kind, id, data, target_origin] = args else {
+            return Err(JsError::TypeError("postMessage expects 4 arguments".into()));
+        };
+        message_state
+            .requests
+            .borrow_mut()
+            .push(WindowRequest::PostMessage {
+                target: WindowTarget::from_args(kind, id)?,
+                data: data.clone(),
+                target_origin: target_origin.clone(),
+            });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.register_function("__rustkit_close_window", 2, move |args| {
+        let kind = args.first().map(String::as_str).unwrap_or("");
+        let id = args.get(1).map(String::as_str).unwrap_or("0");
+        state.requests.borrow_mut().push(WindowRequest::Close {
+            target: WindowTarget::from_args(kind, id)?,
+        });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(POPUP_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    #[test]
+    fn test_parse_features() {
+        let features = WindowFeatures::parse("popup,width=500, height=600,left=10,top=20");
+        assert_eq!(
+            features,
+            WindowFeatures {
+                width: Some(500),
+                height: Some(600),
+                left: Some(10),
+                top: Some(20),
+                popup: true,
+                noopener: false,
+                noreferrer: false,
+            }
+        );
+        assert!(WindowFeatures::parse("width=400").popup);
+        assert!(!WindowFeatures::parse("").popup);
+        assert!(!WindowFeatures::parse("width=400,popup=0").popup);
+
+        let features = WindowFeatures::parse("noreferrer");
+        assert!(features.noopener && features.noreferrer);
+    }
+
+    #[test]
+    fn test_window_open_queues_request() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://app.example/login").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var popup = window.open('/oauth?x=1', 'auth', 'popup,width=480'); \
+                 popup.postMessage({ step: 1 }, 'https://id.example'); \
+                 var detached = window.open('https://other.example/', '_blank', 'noopener');",
+            )
+            .unwrap();
+
+        let requests = bindings.take_window_requests();
+        assert_eq!(requests.len(), 3);
+        let WindowRequest::Open {
+            popup_id,
+            url,
+            name,
+            features,
+            user_activated,
+        } = &requests[0]
+        else {
+            panic!("expected an open request, got {:?}", requests[0]);
+        };
+        assert_eq!(url.as_str(), "https://app.example/oauth?x=1");
+        assert_eq!(name, "auth");
+        assert_eq!(features.width, Some(480));
+        assert!(!user_activated);
+        assert_eq!(
+            requests[1],
+            WindowRequest::PostMessage {
+                target: WindowTarget::Popup(*popup_id),
+                data: r#"{"step":1}"#.to_string(),
+                target_origin: "https://id.example".to_string(),
+            }
+        );
+        assert!(matches!(
+            requests[2],
+            WindowRequest::Open { popup_id: 0, .. }
+        ));
+        assert!(matches!(
+            bindings
+                .evaluate("detached === null && popup.closed === false")
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+        assert!(bindings.take_window_requests().is_empty());
+    }
+
+    #[test]
+    fn test_messages_between_opener_and_popup() {
+        let opener = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        let popup = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        opener
+            .evaluate(
+                "var replies = []; \
+                 window.addEventListener('message', function(e) { \
+                     replies.push(e.data.n); replies.push(e.source === win); \
+                 }); \
+                 var win = window.open('https://id.example/', 'auth'); \
+                 win.postMessage({ n: 1 }, '*');",
+            )
+            .unwrap();
+        popup.set_opener("auth").unwrap();
+        popup
+            .evaluate(
+                "window.onmessage = function(e) { \
+                     e.source.postMessage({ n: e.data.n + 1 }, '*'); \
+                 };",
+            )
+            .unwrap();
+
+        // Route the way the engine does, naming the sender from the receiver
+        let requests = opener.take_window_requests();
+        let (WindowRequest::Open { popup_id, .. }, WindowRequest::PostMessage { data, .. }) =
+            (&requests[0], &requests[1])
+        else {
+            panic!("unexpected requests {:?}", requests);
+        };
+        popup
+            .deliver_message(data, "https://app.example", WindowTarget::Opener)
+            .unwrap();
+        let replies = popup.take_window_requests();
+        let [WindowRequest::PostMessage { target, data, .. }] = replies.as_slice() else {
+            panic!("unexpected replies {:?}", replies);
+        };
+        assert_eq!(*target, WindowTarget::Opener);
+        opener
+            .deliver_message(data, "https://id.example", WindowTarget::Popup(*popup_id))
+            .unwrap();
+
+        assert!(matches!(
+            opener
+                .evaluate("replies[0] === 2 && replies[1] === true")
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+        assert!(matches!(
+            popup
+                .evaluate("window.name === 'auth' && !window.opener.closed")
+                .unwrap(),
+            JsValue::Boolean(true)
+        ));
+
+        // A closed popup swallows further messages
+        opener.popup_closed(*popup_id).unwrap();
+        opener.evaluate("win.postMessage('late', '*');").unwrap();
+        assert!(opener.take_window_requests().is_empty());
+    }
+}
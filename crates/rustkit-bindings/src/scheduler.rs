@@ -0,0 +1,199 @@
+//! `setTimeout`, `setInterval` and `requestAnimationFrame`.
+//!
+//! Script only queues callbacks here; the engine runs them by calling
+//! [`DomBindings::run_timers`](crate::DomBindings::run_timers) and
+//! [`DomBindings::run_animation_frames`](crate::DomBindings::run_animation_frames)
+//! with the document's clock, in milliseconds. A timer's delay counts from
+//! the clock at the last run, which is when the task that set it started.
+
+use rustkit_js::{JsError, JsRuntime};
+
+const SCHEDULER_JS: &str = r#"
+    var __rustkit_clock = 0;
+    var __rustkit_timers = {};
+    var __rustkit_frames = [];
+    var __rustkit_nextTimerId = 1;
+    // `this` of callbacks: the window, or a worker's global scope
+    var __rustkit_callbackThis = window;
+
+    function __rustkit_schedule(callback, delay, args, repeat) {
+        var id = __rustkit_nextTimerId++;
+        __rustkit_timers[id] = {
+            callback: callback,
+            delay: Math.max(0, Number(delay) || 0),
+            args: args,
+            repeat: repeat,
+            base: __rustkit_clock
+        };
+        return id;
+    }
+
+    function setTimeout(callback, delay) {
+        return __rustkit_schedule(callback, delay, Array.prototype.slice.call(arguments, 2), false);
+    }
+
+    function setInterval(callback, delay) {
+        return __rustkit_schedule(callback, delay, Array.prototype.slice.call(arguments, 2), true);
+    }
+
+    function clearTimeout(id) {
+        delete __rustkit_timers[id];
+    }
+
+    function clearInterval(id) {
+        clearTimeout(id);
+    }
+
+    function requestAnimationFrame(callback) {
+        var id = __rustkit_nextTimerId++;
+        __rustkit_frames.push({ id: id, callback: callback, cancelled: false });
+        return id;
+    }
+
+    function cancelAnimationFrame(id) {
+        __rustkit_frames.forEach(function(frame) {
+            if (frame.id === id) frame.cancelled = true;
+        });
+    }
+
+    function __rustkit_timerDue(timer, minInterval) {
+        return timer.base + Math.max(timer.delay, minInterval);
+    }
+
+    // Run the timers due at `now` in due order; returns when the next one
+    // is due, or -1 if none is set.
+    function __rustkit_runTimers(now, minInterval) {
+        __rustkit_clock = now;
+        var due = Object.keys(__rustkit_timers).filter(function(id) {
+            return __rustkit_timerDue(__rustkit_timers[id], minInterval) <= now;
+        }).sort(function(a, b) {
+            return __rustkit_timerDue(__rustkit_timers[a], minInterval) -
+                __rustkit_timerDue(__rustkit_timers[b], minInterval) || a - b;
+        });
+        due.forEach(function(id) {
+            var timer = __rustkit_timers[id];
+            // Cleared by a callback that ran before it
+            if (!timer) return;
+            if (timer.repeat) {
+                timer.base = now;
+            } else {
+                delete __rustkit_timers[id];
+            }
+            try {
+                if (typeof timer.callback === 'function') {
+                    timer.callback.apply(__rustkit_callbackThis, timer.args);
+                } else {
+                    (0, eval)(String(timer.callback));
+                }
+            } catch (e) {
+                __rustkit_reportException(e, false);
+            }
+        });
+        var next = -1;
+        Object.keys(__rustkit_timers).forEach(function(id) {
+            var at = __rustkit_timerDue(__rustkit_timers[id], minInterval);
+            if (next < 0 || at < next) next = at;
+        });
+        return next;
+    }
+
+    // Run the callbacks requested before this frame; returns whether any
+    // were requested for the next one.
+    function __rustkit_runAnimationFrames(now) {
+        __rustkit_clock = now;
+        var frames = __rustkit_frames;
+        __rustkit_frames = [];
+        frames.forEach(function(frame) {
+            if (frame.cancelled) return;
+            try {
+                frame.callback.call(__rustkit_callbackThis, now);
+            } catch (e) {
+                __rustkit_reportException(e, false);
+            }
+        });
+        return __rustkit_frames.length > 0;
+    }
+
+    window.setTimeout = setTimeout;
+    window.setInterval = setInterval;
+    window.clearTimeout = clearTimeout;
+    window.clearInterval = clearInterval;
+    window.requestAnimationFrame = requestAnimationFrame;
+    window.cancelAnimationFrame = cancelAnimationFrame;
+"#;
+
+/// Install the timer and animation frame functions.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.evaluate_script(SCHEDULER_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_js::{JsRuntime, JsValue};
+
+    fn eval_number(bindings: &DomBindings, script: &str) -> f64 {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::Number(n) => n,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_timers_run_in_due_order() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var log = []; \
+                 setTimeout(function(tag) { log.push(tag); }, 50, 'late'); \
+                 setTimeout(function() { log.push('early'); }, 10); \
+                 var cleared = setTimeout(function() { log.push('cleared'); }, 10); \
+                 clearTimeout(cleared);",
+            )
+            .unwrap();
+        assert_eq!(bindings.run_timers(5.0, 0.0).unwrap(), Some(10.0));
+        assert_eq!(bindings.run_timers(60.0, 0.0).unwrap(), None);
+        match bindings.evaluate("log.join()").unwrap() {
+            JsValue::String(log) => assert_eq!(log, "early,late"),
+            other => panic!("log was {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_interval_clamped_to_min_interval() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate("var count = 0; setInterval(function() { count++; }, 100);")
+            .unwrap();
+        let mut now = 0.0;
+        while now < 1000.0 {
+            now += 100.0;
+            bindings.run_timers(now, 0.0).unwrap();
+        }
+        assert_eq!(eval_number(&bindings, "count"), 10.0);
+
+        while now < 3000.0 {
+            now += 100.0;
+            bindings.run_timers(now, 1000.0).unwrap();
+        }
+        assert_eq!(eval_number(&bindings, "count"), 12.0);
+    }
+
+    #[test]
+    fn test_animation_frames_run_once_per_request() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var count = 0; \
+                 function step(time) { count++; last = time; requestAnimationFrame(step); } \
+                 var last = 0; requestAnimationFrame(step); \
+                 cancelAnimationFrame(requestAnimationFrame(function() { count += 100; }));",
+            )
+            .unwrap();
+        assert!(bindings.run_animation_frames(16.0).unwrap());
+        assert!(bindings.run_animation_frames(32.0).unwrap());
+        assert_eq!(eval_number(&bindings, "count"), 2.0);
+        assert_eq!(eval_number(&bindings, "last"), 32.0);
+    }
+}
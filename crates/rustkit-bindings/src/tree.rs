@@ -0,0 +1,784 @@
+//! DOM tree bindings: document fragments, `<template>` contents, shadow
+//! roots, cloning, and inserting, removing and replacing nodes.
+//!
+//! Nodes reached through these APIs are backed by the engine's document, so
+//! appending them to a connected element changes what is laid out. Template
+//! contents live in their own fragment and are only reachable through
+//! `template.content`; shadow trees likewise only through their root.
+
+use crate::geometry::{node_id_arg, GeometryState};
+use crate::DomMutation;
+use rustkit_dom::{
+    AdjacentPosition, Document, DomError, Node, NodeId, NodeType, QuerySelector, ShadowRootMode,
+};
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::json;
+use std::rc::Rc;
+
+/// Describe a node for `document._wrap`.
+fn describe(node: &Node) -> serde_json::Value {
+    let id = node.id.raw();
+    match &node.node_type {
+        NodeType::Element {
+            tag_name,
+            attributes,
+            ..
+        } => json!({ "nodeId": id, "nodeType": 1, "tagName": tag_name, "attributes": attributes }),
+        NodeType::Text(data) => json!({ "nodeId": id, "nodeType": 3, "data": data }),
+        NodeType::Comment(data) => json!({ "nodeId": id, "nodeType": 8, "data": data }),
+        NodeType::DocumentFragment => match node.shadow_root_mode() {
+            Some(mode) => json!({ "nodeId": id, "nodeType": 11, "shadowMode": mode.as_str() }),
+            None => json!({ "nodeId": id, "nodeType": 11 }),
+        },
+        NodeType::Document => json!({ "nodeId": id, "nodeType": 9 }),
+        NodeType::DocumentType { .. } => json!({ "nodeId": id, "nodeType": 10 }),
+        NodeType::ProcessingInstruction { .. } => json!({ "nodeId": id, "nodeType": 7 }),
+    }
+}
+
+fn describe_all(nodes: &[Rc<Node>]) -> JsValue {
+    let nodes: Vec<_> = nodes.iter().map(|n| describe(n)).collect();
+    JsValue::String(serde_json::Value::from(nodes).to_string())
+}
+
+fn describe_one(node: Option<Rc<Node>>) -> JsValue {
+    JsValue::String(node.map_or_else(|| "null".to_string(), |n| describe(&n).to_string()))
+}
+
+/// Outcome of a tree mutation for `__rustkit_treeResult`: the node it
+/// returns, or the `DOMException` to throw.
+fn mutation_result(result: Result<Option<Rc<Node>>, DomError>) -> JsValue {
+    let value = match result {
+        Ok(node) => json!({ "node": node.map(|n| describe(&n)) }),
+        Err(e) => json!({ "error": e.name(), "message": e.to_string() }),
+    };
+    JsValue::String(value.to_string())
+}
+
+/// Where inserting `node` takes children from: its parent, or a fragment
+/// itself.
+fn source_parent(node: &Rc<Node>) -> Option<Rc<Node>> {
+    match node.is_document_fragment() {
+        true => Some(node.clone()),
+        false => node.parent(),
+    }
+}
+
+/// Record child list changes to `parents`, and relayout if any of them is
+/// in the document or one of its shadow trees.
+fn children_changed(state: &GeometryState, document: &Document, parents: &[Rc<Node>]) {
+    let mut recorded: Vec<NodeId> = Vec::new();
+    for parent in parents {
+        if !recorded.contains(&parent.id) {
+            recorded.push(parent.id);
+            state.record_mutation(DomMutation::ChildList { target: parent.id });
+        }
+    }
+    if parents.iter().any(|parent| document.is_connected(parent)) {
+        state.mark_dirty();
+    }
+}
+
+fn node_arg(document: &Document, args: &[String], index: usize) -> Result<Rc<Node>, JsError> {
+    node_id_arg(args.get(index..).unwrap_or_default())
+        .ok()
+        .and_then(|id| document.get_node(id))
+        .ok_or_else(|| JsError::TypeError("Unknown node".into()))
+}
+
+/// JS side: node wrappers and the tree methods on them.
+const TREE_JS: &str = r#"
+    function __rustkit_treeResult(json) {
+        var result = JSON.parse(json);
+        if (result.error) throw new DOMException(result.message, result.error);
+        return result.node ? document._wrap(result.node) : null;
+    }
+    function __rustkit_isTreeNode(node) {
+        return !!node && node._nodeId !== undefined;
+    }
+    // The object script sees as an event's target for a node id
+    function __rustkit_eventNode(id) {
+        var desc = JSON.parse(__rustkit_node(id));
+        return desc && desc.nodeType === 9 ? document : document._wrap(desc);
+    }
+
+    // Tree methods for nodes backed by the engine document
+    function __rustkit_bindTreeNode(node) {
+        if (!node || node._treeBound) return node;
+        node._treeBound = true;
+
+        var jsAppendChild = node.appendChild;
+        node.appendChild = function(child) {
+            if (__rustkit_isTreeNode(this) && __rustkit_isTreeNode(child)) {
+                __rustkit_treeResult(__rustkit_insert_before(this._nodeId, child._nodeId, null));
+                return child;
+            }
+            return jsAppendChild ? jsAppendChild.call(this, child) : child;
+        };
+        node.insertBefore = function(child, reference) {
+            if (!__rustkit_isTreeNode(child)) throw new TypeError('insertBefore needs a node');
+            __rustkit_treeResult(__rustkit_insert_before(this._nodeId, child._nodeId,
+                reference ? reference._nodeId : null));
+            return child;
+        };
+        var jsRemoveChild = node.removeChild;
+        node.removeChild = function(child) {
+            if (!__rustkit_isTreeNode(child)) {
+                return jsRemoveChild ? jsRemoveChild.call(this, child) : child;
+            }
+            __rustkit_treeResult(__rustkit_remove_child(this._nodeId, child._nodeId));
+            return child;
+        };
+        node.replaceChild = function(child, old) {
+            if (!__rustkit_isTreeNode(child) || !__rustkit_isTreeNode(old)) {
+                throw new TypeError('replaceChild needs nodes');
+            }
+            __rustkit_treeResult(__rustkit_replace_child(this._nodeId, child._nodeId, old._nodeId));
+            return old;
+        };
+        node.remove = function() {
+            var parent = this.parentNode;
+            if (parent) parent.removeChild(this);
+        };
+        node.insertAdjacentHTML = function(position, html) {
+            __rustkit_treeResult(__rustkit_insert_adjacent(this._nodeId, String(position),
+                'html', String(html)));
+        };
+        node.insertAdjacentText = function(position, text) {
+            __rustkit_treeResult(__rustkit_insert_adjacent(this._nodeId, String(position),
+                'text', String(text)));
+        };
+        node.insertAdjacentElement = function(position, element) {
+            if (!__rustkit_isTreeNode(element)) throw new TypeError('insertAdjacentElement needs an element');
+            return __rustkit_treeResult(__rustkit_insert_adjacent(this._nodeId, String(position),
+                'node', String(element._nodeId)));
+        };
+        node.cloneNode = function(deep) {
+            return document._wrap(JSON.parse(__rustkit_clone_node(this._nodeId, !!deep)));
+        };
+        node.querySelectorAll = function(selector) {
+            return JSON.parse(__rustkit_query(this._nodeId, String(selector))).map(document._wrap);
+        };
+        node.querySelector = function(selector) {
+            return this.querySelectorAll(selector)[0] || null;
+        };
+
+        function childNodes() {
+            return JSON.parse(__rustkit_node_children(node._nodeId)).map(document._wrap);
+        }
+        function related(which) {
+            return function() {
+                return document._wrap(JSON.parse(__rustkit_node_related(node._nodeId, which)));
+            };
+        }
+        Object.defineProperties(node, {
+            parentNode: { get: related('parent'), configurable: true },
+            nextSibling: { get: related('next'), configurable: true },
+            previousSibling: { get: related('previous'), configurable: true },
+            childNodes: { get: childNodes, configurable: true },
+            children: {
+                get: function() {
+                    return childNodes().filter(function(c) { return c.nodeType === 1; });
+                },
+                configurable: true
+            },
+            firstChild: { get: function() { return childNodes()[0] || null; }, configurable: true },
+            lastChild: {
+                get: function() { var c = childNodes(); return c[c.length - 1] || null; },
+                configurable: true
+            }
+        });
+
+        if (node.tagName) {
+            node.attachShadow = function(init) {
+                var mode = init && init.mode;
+                if (mode !== 'open' && mode !== 'closed') {
+                    throw new TypeError("attachShadow needs a mode of 'open' or 'closed'");
+                }
+                return __rustkit_treeResult(__rustkit_attach_shadow(this._nodeId, mode));
+            };
+            Object.defineProperty(node, 'shadowRoot', {
+                get: related('shadowRoot'),
+                configurable: true
+            });
+        }
+        if (node.tagName === 'TEMPLATE') {
+            Object.defineProperty(node, 'content', {
+                get: function() {
+                    return document._wrap(JSON.parse(__rustkit_template_content(node._nodeId)));
+                },
+                configurable: true
+            });
+        }
+        return node;
+    }
+
+    // Wrap a node description, reusing the object for nodes already wrapped
+    document._wrap = function(desc) {
+        if (!desc) return null;
+        var existing = document._nodes[desc.nodeId];
+        if (existing) return __rustkit_bindTreeNode(existing);
+
+        var node;
+        if (desc.nodeType === 1) {
+            node = document.createElement(desc.tagName);
+            node.id = desc.attributes.id || '';
+            node.className = desc.attributes['class'] || '';
+            node.attributes = desc.attributes;
+            node._nodeId = desc.nodeId;
+            node.style = __rustkit_createStyle(node, desc.attributes.style || '');
+            node.nodeType = 1;
+        } else if (desc.nodeType === 11) {
+            node = { nodeType: 11, nodeName: '#document-fragment', _nodeId: desc.nodeId };
+            if (desc.shadowMode) {
+                node.mode = desc.shadowMode;
+                Object.defineProperties(node, {
+                    host: {
+                        get: function() {
+                            return document._wrap(JSON.parse(__rustkit_node_related(node._nodeId, 'host')));
+                        },
+                        configurable: true
+                    },
+                    innerHTML: {
+                        get: function() { return __rustkit_inner_html(node._nodeId); },
+                        set: function(html) {
+                            __rustkit_treeResult(__rustkit_set_inner_html(node._nodeId, String(html)));
+                        },
+                        configurable: true
+                    }
+                });
+            }
+        } else {
+            node = { nodeType: desc.nodeType, _nodeId: desc.nodeId, data: desc.data || '' };
+            node.textContent = node.data;
+            return node;
+        }
+        document._nodes[desc.nodeId] = node;
+        return __rustkit_bindTreeNode(node);
+    };
+
+    var __rustkit_treeAdopt = document._adopt;
+    document._adopt = function(tree) {
+        // Node ids restart with each document
+        document._nodes = {};
+        __rustkit_treeAdopt(tree);
+        Object.keys(document._nodes).forEach(function(id) {
+            __rustkit_bindTreeNode(document._nodes[id]);
+        });
+    };
+
+    var __rustkit_jsCreateFragment = document.createDocumentFragment;
+    document.createDocumentFragment = function() {
+        var desc = JSON.parse(__rustkit_create_fragment());
+        return desc ? document._wrap(desc) : __rustkit_jsCreateFragment.call(document);
+    };
+    document.importNode = function(node, deep) {
+        return node.cloneNode(!!deep);
+    };
+    document.adoptNode = function(node) {
+        if (!__rustkit_isTreeNode(node)) return node;
+        __rustkit_treeResult(__rustkit_adopt_node(node._nodeId));
+        return node;
+    };
+
+    // Document queries never look inside template contents or shadow trees
+    document.querySelectorAll = function(selector) {
+        return JSON.parse(__rustkit_query(0, String(selector))).map(document._wrap);
+    };
+    document.querySelector = function(selector) {
+        return this.querySelectorAll(selector)[0] || null;
+    };
+    var __rustkit_getElementById = document.getElementById;
+    document.getElementById = function(id) {
+        return __rustkit_getElementById.call(document, id) || document.querySelector('#' + id);
+    };
+"#;
+
+/// Register the tree natives and node wrappers.
+pub(crate) fn install(runtime: &mut JsRuntime, state: Rc<GeometryState>) -> Result<(), JsError> {
+    let children_state = state.clone();
+    runtime.register_function("__rustkit_node_children", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        let node = children_state.document().and_then(|d| d.get_node(node_id));
+        Ok(describe_all(
+            &node.map(|n| n.children()).unwrap_or_default(),
+        ))
+    })?;
+
+    let content_state = state.clone();
+    runtime.register_function("__rustkit_template_content", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        let node = content_state.document().and_then(|d| d.get_node(node_id));
+        Ok(describe_one(node.and_then(|n| n.template_content())))
+    })?;
+
+    let fragment_state = state.clone();
+    runtime.register_function("__rustkit_create_fragment", 0, move |_args| {
+        let fragment = fragment_state
+            .document()
+            .map(|d| d.create_document_fragment());
+        Ok(describe_one(fragment))
+    })?;
+
+    let clone_state = state.clone();
+    runtime.register_function("__rustkit_clone_node", 2, move |args| {
+        let node_id = node_id_arg(args)?;
+        let deep = args.get(1).is_some_and(|d| d == "true");
+        let document = clone_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let node = document
+            .get_node(node_id)
+            .ok_or_else(|| JsError::TypeError("Unknown node".into()))?;
+        Ok(describe_one(Some(document.clone_node(&node, deep))))
+    })?;
+
+    let related_state = state.clone();
+    runtime.register_function("__rustkit_node_related", 2, move |args| {
+        let node_id = node_id_arg(args)?;
+        let node = related_state.document().and_then(|d| d.get_node(node_id));
+        Ok(describe_one(node.and_then(
+            |node| match args.get(1).map(String::as_str) {
+                Some("next") => node.next_sibling(),
+                Some("previous") => node.previous_sibling(),
+                Some("host") => node.host(),
+                Some("shadowRoot") => node.open_shadow_root(),
+                _ => node.parent(),
+            },
+        )))
+    })?;
+
+    let insert_state = state.clone();
+    runtime.register_function("__rustkit_insert_before", 3, move |args| {
+        let document = insert_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let parent = node_arg(&document, args, 0)?;
+        let node = node_arg(&document, args, 1)?;
+        // A missing reference appends
+        let reference = match args.get(2).map(String::as_str) {
+            None | Some("null") | Some("undefined") => None,
+            Some(_) => Some(node_arg(&document, args, 2)?),
+        };
+        let from = source_parent(&node);
+        let result = document.insert_before(&parent, node, reference.as_ref());
+        if result.is_ok() {
+            let parents: Vec<_> = from.into_iter().chain([parent]).collect();
+            children_changed(&insert_state, &document, &parents);
+        }
+        Ok(mutation_result(result.map(|_| None)))
+    })?;
+
+    let remove_state = state.clone();
+    runtime.register_function("__rustkit_remove_child", 2, move |args| {
+        let document = remove_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let parent = node_arg(&document, args, 0)?;
+        let child = node_arg(&document, args, 1)?;
+        let result = document.remove_child(&parent, &child);
+        if result.is_ok() {
+            children_changed(&remove_state, &document, &[parent]);
+        }
+        Ok(mutation_result(result.map(Some)))
+    })?;
+
+    let replace_state = state.clone();
+    runtime.register_function("__rustkit_replace_child", 3, move |args| {
+        let document = replace_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let parent = node_arg(&document, args, 0)?;
+        let node = node_arg(&document, args, 1)?;
+        let old = node_arg(&document, args, 2)?;
+        let from = source_parent(&node);
+        let result = document.replace_child(&parent, node, &old);
+        if result.is_ok() {
+            let parents: Vec<_> = from.into_iter().chain([parent]).collect();
+            children_changed(&replace_state, &document, &parents);
+        }
+        Ok(mutation_result(result.map(Some)))
+    })?;
+
+    let adjacent_state = state.clone();
+    runtime.register_function("__rustkit_insert_adjacent", 4, move |args| {
+        let document = adjacent_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let element = node_arg(&document, args, 0)?;
+        let position = match AdjacentPosition::parse(args.get(1).map_or("", String::as_str)) {
+            Ok(position) => position,
+            Err(e) => return Ok(mutation_result(Err(e))),
+        };
+        let value = args.get(3).map_or("", String::as_str);
+        let target = match position {
+            AdjacentPosition::AfterBegin | AdjacentPosition::BeforeEnd => Some(element.clone()),
+            _ => element.parent(),
+        };
+        let (from, result) = match args.get(2).map(String::as_str) {
+            Some("html") => (
+                None,
+                document
+                    .insert_adjacent_html(&element, position, value)
+                    .map(|_| None),
+            ),
+            Some("text") => (
+                None,
+                document
+                    .insert_adjacent(&element, position, document.create_text_node(value))
+                    .map(|_| None),
+            ),
+            _ => {
+                let node = node_arg(&document, args, 3)?;
+                let from = source_parent(&node);
+                let inserted = document.insert_adjacent(&element, position, node.clone());
+                (from, inserted.map(|inserted| inserted.then_some(node)))
+            }
+        };
+        if result.is_ok() {
+            let parents: Vec<_> = from.into_iter().chain(target).collect();
+            children_changed(&adjacent_state, &document, &parents);
+        }
+        Ok(mutation_result(result))
+    })?;
+
+    let shadow_state = state.clone();
+    runtime.register_function("__rustkit_attach_shadow", 2, move |args| {
+        let document = shadow_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let host = node_arg(&document, args, 0)?;
+        let mode = args
+            .get(1)
+            .and_then(|mode| ShadowRootMode::parse(mode))
+            .ok_or_else(|| JsError::TypeError("Unknown shadow root mode".into()))?;
+        let result = document.attach_shadow(&host, mode);
+        if result.is_ok() && document.is_connected(&host) {
+            // The empty shadow tree now renders in place of the children
+            shadow_state.mark_dirty();
+        }
+        Ok(mutation_result(result.map(Some)))
+    })?;
+
+    let html_state = state.clone();
+    runtime.register_function("__rustkit_inner_html", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        let node = html_state.document().and_then(|d| d.get_node(node_id));
+        Ok(JsValue::String(
+            node.map(|n| n.inner_html()).unwrap_or_default(),
+        ))
+    })?;
+
+    let set_html_state = state.clone();
+    runtime.register_function("__rustkit_set_inner_html", 2, move |args| {
+        let document = set_html_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let node = node_arg(&document, args, 0)?;
+        let result = document.set_inner_html(&node, args.get(1).map_or("", String::as_str));
+        if result.is_ok() {
+            children_changed(&set_html_state, &document, &[node]);
+        }
+        Ok(mutation_result(result.map(|_| None)))
+    })?;
+
+    let node_state = state.clone();
+    runtime.register_function("__rustkit_node", 1, move |args| {
+        let node_id = node_id_arg(args)?;
+        Ok(describe_one(
+            node_state.document().and_then(|d| d.get_node(node_id)),
+        ))
+    })?;
+
+    let adopt_state = state.clone();
+    runtime.register_function("__rustkit_adopt_node", 1, move |args| {
+        let document = adopt_state
+            .document()
+            .ok_or_else(|| JsError::ExecutionError("No document".into()))?;
+        let node = node_arg(&document, args, 0)?;
+        let from = node.parent();
+        let result = document.adopt_node(&node);
+        if result.is_ok() {
+            children_changed(&adopt_state, &document, &Vec::from_iter(from));
+        }
+        Ok(mutation_result(result.map(Some)))
+    })?;
+
+    runtime.register_function("__rustkit_query", 2, move |args| {
+        let node_id = node_id_arg(args)?;
+        let selector = args.get(1).map(String::as_str).unwrap_or("");
+        let scope = state.document().and_then(|d| d.get_node(node_id));
+        let matches = scope
+            .map(|scope| QuerySelector::select_within(&scope, selector))
+            .unwrap_or_default();
+        Ok(describe_all(&matches))
+    })?;
+
+    runtime.evaluate_script(TREE_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DomBindings;
+    use rustkit_dom::Document;
+    use rustkit_js::{JsRuntime, JsValue};
+    use std::rc::Rc;
+
+    const HTML: &str = r#"<html><body>
+        <template id="tpl"><div class="card">Hi</div></template>
+    </body></html>"#;
+
+    fn bind(html: &str) -> (Rc<Document>, DomBindings) {
+        let document = Rc::new(Document::parse_html(html).unwrap());
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_document(document.clone()).unwrap();
+        (document, bindings)
+    }
+
+    fn eval_bool(bindings: &DomBindings, script: &str) -> bool {
+        match bindings.evaluate(script).unwrap() {
+            JsValue::Boolean(b) => b,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_template_content_is_separate_fragment() {
+        let (_, bindings) = bind(HTML);
+        bindings
+            .evaluate("var tpl = document.getElementById('tpl');")
+            .unwrap();
+        assert!(eval_bool(&bindings, "tpl.content.nodeType === 11"));
+        assert!(eval_bool(&bindings, "tpl.content === tpl.content"));
+        assert!(eval_bool(&bindings, "tpl.childNodes.length === 0"));
+        assert!(eval_bool(
+            &bindings,
+            "document.querySelector('.card') === null"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "tpl.content.querySelector('.card').tagName === 'DIV'"
+        ));
+    }
+
+    #[test]
+    fn test_append_clone_moves_fragment_children() {
+        let (document, bindings) = bind(HTML);
+        bindings
+            .evaluate(
+                "var tpl = document.getElementById('tpl'); \
+                 var clone = document.importNode(tpl.content, true); \
+                 document.body.appendChild(clone);",
+            )
+            .unwrap();
+
+        assert!(eval_bool(&bindings, "clone.childNodes.length === 0"));
+        assert!(eval_bool(&bindings, "tpl.content.childNodes.length === 1"));
+        assert!(eval_bool(
+            &bindings,
+            "document.querySelectorAll('.card').length === 1"
+        ));
+        assert!(bindings.needs_relayout());
+        assert!(document.body().unwrap().text_content().contains("Hi"));
+    }
+
+    #[test]
+    fn test_create_document_fragment() {
+        let (document, bindings) = bind(HTML);
+        bindings
+            .evaluate(
+                "var fragment = document.createDocumentFragment(); \
+                 var tpl = document.getElementById('tpl'); \
+                 fragment.appendChild(tpl.content.firstChild.cloneNode(true)); \
+                 fragment.appendChild(tpl.content.firstChild.cloneNode(false));",
+            )
+            .unwrap();
+        assert!(eval_bool(&bindings, "fragment.children.length === 2"));
+        // Filling a detached fragment does not touch the page
+        assert!(!bindings.needs_relayout());
+
+        bindings
+            .evaluate("document.body.appendChild(fragment);")
+            .unwrap();
+        assert!(eval_bool(&bindings, "fragment.childNodes.length === 0"));
+        assert_eq!(document.get_elements_by_class_name("card").len(), 2);
+    }
+
+    #[test]
+    fn test_mutations_recorded_while_enabled() {
+        let (document, bindings) = bind(HTML);
+        let body = document.body().unwrap().id;
+        bindings
+            .evaluate("document.body.appendChild(document.createDocumentFragment());")
+            .unwrap();
+        assert!(bindings.take_mutations().is_empty());
+
+        bindings.set_mutation_recording(true);
+        bindings
+            .evaluate(
+                "var tpl = document.getElementById('tpl'); \
+                 document.body.appendChild(tpl.content.firstChild.cloneNode(true)); \
+                 document.body.style.color = 'red';",
+            )
+            .unwrap();
+        assert_eq!(
+            bindings.take_mutations(),
+            [
+                crate::DomMutation::ChildList { target: body },
+                crate::DomMutation::Attributes {
+                    target: body,
+                    name: "style".into()
+                },
+            ]
+        );
+        assert!(bindings.take_mutations().is_empty());
+    }
+
+    #[test]
+    fn test_insert_remove_and_replace() {
+        let (document, bindings) = bind(
+            r#"<html><body><div id="box"><p id="first">1</p><p id="second">2</p></div></body></html>"#,
+        );
+        bindings
+            .evaluate(
+                "var box = document.getElementById('box'); \
+                 var first = document.getElementById('first'); \
+                 var second = document.getElementById('second'); \
+                 box.insertBefore(second, first); \
+                 first.insertAdjacentText('afterbegin', '0'); \
+                 var span = box.insertAdjacentElement('afterend', first.cloneNode(false));",
+            )
+            .unwrap();
+        assert!(eval_bool(&bindings, "box.firstChild === second"));
+        assert!(eval_bool(&bindings, "second.nextSibling === first"));
+        assert!(eval_bool(&bindings, "first.previousSibling === second"));
+        assert!(eval_bool(&bindings, "span.parentNode === document.body"));
+        assert_eq!(document.body().unwrap().text_content(), "201");
+
+        assert!(eval_bool(
+            &bindings,
+            "box.removeChild(second) === second && second.parentNode === null"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "try { box.removeChild(second); false } catch (e) { e.name === 'NotFoundError' }"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "try { first.appendChild(box); false } \
+             catch (e) { e.name === 'HierarchyRequestError' }"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "try { box.insertAdjacentHTML('inside', '<b></b>'); false } \
+             catch (e) { e.name === 'SyntaxError' }"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "box.replaceChild(second, first) === first && box.firstChild === second"
+        ));
+        document.root().check_tree().unwrap();
+    }
+
+    #[test]
+    fn test_shadow_root_modes_and_scoped_queries() {
+        let (document, bindings) = bind(
+            r#"<html><body><div id="host"><span slot="label">Light</span></div><p id="plain"></p></body></html>"#,
+        );
+        bindings
+            .evaluate(
+                "var host = document.getElementById('host'); \
+                 var root = host.attachShadow({ mode: 'open' }); \
+                 root.innerHTML = '<b class=\"inner\"><slot name=\"label\"></slot></b>'; \
+                 var closed = document.getElementById('plain').attachShadow({ mode: 'closed' });",
+            )
+            .unwrap();
+        assert!(eval_bool(
+            &bindings,
+            "host.shadowRoot === root && root.mode === 'open'"
+        ));
+        assert!(eval_bool(&bindings, "root.host === host"));
+        assert!(eval_bool(
+            &bindings,
+            "document.getElementById('plain').shadowRoot === null && closed.mode === 'closed'"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "try { host.attachShadow({ mode: 'open' }); false } \
+             catch (e) { e.name === 'NotSupportedError' }"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "root.innerHTML === '<b class=\"inner\"><slot name=\"label\"></slot></b>'"
+        ));
+
+        assert!(eval_bool(
+            &bindings,
+            "document.querySelector('.inner') === null"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "root.querySelector('.inner').tagName === 'B'"
+        ));
+        assert!(eval_bool(&bindings, "host.querySelector('slot') === null"));
+        assert!(bindings.needs_relayout());
+        document.root().check_tree().unwrap();
+    }
+
+    #[test]
+    fn test_click_in_shadow_tree_retargets_to_host() {
+        let (document, bindings) = bind(r#"<html><body><div id="host"></div></body></html>"#);
+        bindings
+            .evaluate(
+                "var host = document.getElementById('host'); \
+                 var root = host.attachShadow({ mode: 'closed' }); \
+                 root.innerHTML = '<button>Go</button>'; \
+                 var seen = [];",
+            )
+            .unwrap();
+        let host = document.get_element_by_id("host").unwrap();
+        let button = host.shadow_root().unwrap().children()[0].clone();
+        bindings.add_event_listener(
+            document.root().id,
+            "click",
+            "seen.push(['document', e.target, e.eventPhase]);",
+            false,
+        );
+        bindings.add_event_listener(
+            button.id,
+            "click",
+            "seen.push(['button', e.target, e.eventPhase]);",
+            false,
+        );
+        bindings.add_event_listener(host.id, "focus", "seen.push(['host', e.target]);", false);
+
+        assert!(bindings.dispatch_event(button.id, "click").unwrap());
+        assert!(eval_bool(&bindings, "seen.length === 2"));
+        assert!(eval_bool(
+            &bindings,
+            "seen[0][0] === 'button' && seen[0][1].tagName === 'BUTTON' && seen[0][2] === 2"
+        ));
+        assert!(eval_bool(
+            &bindings,
+            "seen[1][0] === 'document' && seen[1][1] === host && seen[1][2] === 3"
+        ));
+
+        // Events that don't bubble still reach the host they retarget to
+        bindings.dispatch_event(button.id, "focus").unwrap();
+        assert!(eval_bool(
+            &bindings,
+            "seen[2][0] === 'host' && seen[2][1] === host"
+        ));
+    }
+
+    #[test]
+    fn test_append_ancestor_throws() {
+        let (_, bindings) = bind(HTML);
+        assert!(eval_bool(
+            &bindings,
+            "try { document.body.appendChild(document.documentElement); false } \
+             catch (e) { true }"
+        ));
+    }
+}
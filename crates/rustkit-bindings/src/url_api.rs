@@ -0,0 +1,521 @@
+//! URL and URLSearchParams bindings.
+//!
+//! Parsing, resolution, and setter re-serialization are done in Rust by the
+//! `url` crate (which implements the WHATWG URL standard); the JS side is a
+//! thin class layer over a handful of native functions.
+
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use serde_json::json;
+use url::{form_urlencoded, quirks, Url};
+
+/// Serialize the components of a URL as a JSON object.
+fn components(url: &Url) -> JsValue {
+    JsValue::String(
+        json!({
+            "href": quirks::href(url),
+            "origin": quirks::origin(url),
+            "protocol": quirks::protocol(url),
+            "username": quirks::username(url),
+            "password": quirks::password(url),
+            "host": quirks::host(url),
+            "hostname": quirks::hostname(url),
+            "port": quirks::port(url),
+            "pathname": quirks::pathname(url),
+            "search": quirks::search(url),
+            "hash": quirks::hash(url),
+        })
+        .to_string(),
+    )
+}
+
+/// Parse `href`, optionally against `base`.
+fn parse(href: &str, base: Option<&str>) -> Result<Url, JsError> {
+    let invalid = |input: &str| JsError::TypeError(format!("Invalid URL: {}", input));
+
+    match base {
+        Some(base) => Url::parse(base)
+            .map_err(|_| invalid(base))?
+            .join(href)
+            .map_err(|_| invalid(href)),
+        None => Url::parse(href).map_err(|_| invalid(href)),
+    }
+}
+
+/// `__rustkit_url_parse(href[, base])` -> components JSON.
+fn native_parse(args: &[String]) -> Result<JsValue, JsError> {
+    let href = args.first().map(String::as_str).unwrap_or("undefined");
+    parse(href, args.get(1).map(String::as_str)).map(|url| components(&url))
+}
+
+/// `__rustkit_url_set(href, component, value)` -> components JSON.
+///
+/// Invalid values are ignored, except for `href` which throws.
+fn native_set(args: &[String]) -> Result<JsValue, JsError> {
+    let [href, component, value] = args else {
+        return Err(JsError::TypeError("Expected 3 arguments".into()));
+    };
+    let mut url = parse(href, None)?;
+
+    match component.as_str() {
+        "href" => {
+            quirks::set_href(&mut url, value)
+                .map_err(|_| JsError::TypeError(format!("Invalid URL: {}", value)))?;
+        }
+        "protocol" => {
+            let _ = quirks::set_protocol(&mut url, value);
+        }
+        "username" => {
+            let _ = quirks::set_username(&mut url, value);
+        }
+        "password" => {
+            let _ = quirks::set_password(&mut url, value);
+        }
+        "host" => {
+            let _ = quirks::set_host(&mut url, value);
+        }
+        "hostname" => {
+            let _ = quirks::set_hostname(&mut url, value);
+        }
+        "port" => {
+            let _ = quirks::set_port(&mut url, value);
+        }
+        "pathname" => quirks::set_pathname(&mut url, value),
+        "search" => quirks::set_search(&mut url, value),
+        "hash" => quirks::set_hash(&mut url, value),
+        other => {
+            return Err(JsError::TypeError(format!(
+                "Unknown URL component: {}",
+                other
+            )))
+        }
+    }
+
+    Ok(components(&url))
+}
+
+/// `__rustkit_urlencoded_parse(query)` -> JSON array of `[name, value]` pairs.
+fn native_urlencoded_parse(args: &[String]) -> Result<JsValue, JsError> {
+    let query = args.first().map(String::as_str).unwrap_or("");
+    let pairs: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    Ok(JsValue::String(json!(pairs).to_string()))
+}
+
+/// `__rustkit_urlencoded_serialize(pairsJson)` -> query string.
+fn native_urlencoded_serialize(args: &[String]) -> Result<JsValue, JsError> {
+    let pairs: Vec<(String, String)> = args
+        .first()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .ok_or_else(|| JsError::TypeError("Expected a JSON array of pairs".into()))?;
+
+    Ok(JsValue::String(
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish(),
+    ))
+}
+
+/// JS classes built on the native functions.
+const URL_JS: &str = r#"
+    var URLSearchParams = function(init) {
+        this._list = [];
+        this._url = null;
+
+        if (init === undefined || init === null) {
+            return;
+        }
+        if (init instanceof URLSearchParams) {
+            this._list = init._list.map(function(p) { return [p[0], p[1]]; });
+        } else if (typeof init === 'object' && typeof init[Symbol.iterator] === 'function') {
+            var self = this;
+            Array.from(init).forEach(function(pair) {
+                pair = Array.from(pair);
+                if (pair.length !== 2) {
+                    throw new TypeError('Each query pair must be an iterable [name, value] tuple');
+                }
+                self._list.push([String(pair[0]), String(pair[1])]);
+            });
+        } else if (typeof init === 'object') {
+            var list = this._list;
+            Object.keys(init).forEach(function(key) { list.push([key, String(init[key])]); });
+        } else {
+            this._parse(String(init));
+        }
+    };
+
+    URLSearchParams.prototype._parse = function(query) {
+        if (query.charAt(0) === '?') query = query.slice(1);
+        this._list = JSON.parse(__rustkit_urlencoded_parse(query));
+    };
+
+    // Reflect mutations into the owning URL's query
+    URLSearchParams.prototype._update = function() {
+        if (this._url) {
+            var query = this.toString();
+            this._url._c = JSON.parse(__rustkit_url_set(this._url._c.href, 'search', query));
+        }
+    };
+
+    URLSearchParams.prototype.append = function(name, value) {
+        this._list.push([String(name), String(value)]);
+        this._update();
+    };
+
+    URLSearchParams.prototype.delete = function(name, value) {
+        name = String(name);
+        this._list = this._list.filter(function(p) {
+            return p[0] !== name || (value !== undefined && p[1] !== String(value));
+        });
+        this._update();
+    };
+
+    URLSearchParams.prototype.get = function(name) {
+        name = String(name);
+        for (var i = 0; i < this._list.length; i++) {
+            if (this._list[i][0] === name) return this._list[i][1];
+        }
+        return null;
+    };
+
+    URLSearchParams.prototype.getAll = function(name) {
+        name = String(name);
+        return this._list.filter(function(p) { return p[0] === name; })
+            .map(function(p) { return p[1]; });
+    };
+
+    URLSearchParams.prototype.has = function(name, value) {
+        name = String(name);
+        return this._list.some(function(p) {
+            return p[0] === name && (value === undefined || p[1] === String(value));
+        });
+    };
+
+    URLSearchParams.prototype.set = function(name, value) {
+        name = String(name);
+        value = String(value);
+        var found = false;
+        this._list = this._list.filter(function(p) {
+            if (p[0] !== name) return true;
+            if (found) return false;
+            found = true;
+            p[1] = value;
+            return true;
+        });
+        if (!found) this._list.push([name, value]);
+        this._update();
+    };
+
+    URLSearchParams.prototype.sort = function() {
+        // Array.prototype.sort is stable, so equal names keep their order
+        this._list.sort(function(a, b) { return a[0] < b[0] ? -1 : (a[0] > b[0] ? 1 : 0); });
+        this._update();
+    };
+
+    URLSearchParams.prototype.forEach = function(callback, thisArg) {
+        var list = this._list.slice();
+        for (var i = 0; i < list.length; i++) {
+            callback.call(thisArg, list[i][1], list[i][0], this);
+        }
+    };
+
+    URLSearchParams.prototype.entries = function() {
+        return this._list.map(function(p) { return [p[0], p[1]]; })[Symbol.iterator]();
+    };
+
+    URLSearchParams.prototype.keys = function() {
+        return this._list.map(function(p) { return p[0]; })[Symbol.iterator]();
+    };
+
+    URLSearchParams.prototype.values = function() {
+        return this._list.map(function(p) { return p[1]; })[Symbol.iterator]();
+    };
+
+    URLSearchParams.prototype[Symbol.iterator] = URLSearchParams.prototype.entries;
+
+    Object.defineProperty(URLSearchParams.prototype, 'size', {
+        get: function() { return this._list.length; }
+    });
+
+    URLSearchParams.prototype.toString = function() {
+        return __rustkit_urlencoded_serialize(JSON.stringify(this._list));
+    };
+
+    // Component accessors shared by URL and Location
+    var __rustkit_defineUrlComponents = function(proto, resolveHref) {
+        ['href', 'origin', 'protocol', 'username', 'password', 'host', 'hostname',
+         'port', 'pathname', 'search', 'hash'].forEach(function(name) {
+            var desc = { get: function() { return this._c[name]; }, configurable: true };
+            if (name !== 'origin') {
+                desc.set = function(value) {
+                    value = String(value);
+                    if (name === 'href' && resolveHref) {
+                        this._c = JSON.parse(__rustkit_url_parse(value, this._c.href));
+                    } else {
+                        this._c = JSON.parse(__rustkit_url_set(this._c.href, name, value));
+                    }
+                    if (this._params && (name === 'href' || name === 'search')) {
+                        this._params._parse(this._c.search);
+                    }
+                };
+            }
+            Object.defineProperty(proto, name, desc);
+        });
+        proto.toString = function() { return this._c.href; };
+    };
+
+    var URL = function(url, base) {
+        this._c = JSON.parse(base === undefined
+            ? __rustkit_url_parse(String(url))
+            : __rustkit_url_parse(String(url), String(base)));
+        this._params = new URLSearchParams(this._c.search);
+        this._params._url = this;
+    };
+
+    __rustkit_defineUrlComponents(URL.prototype, false);
+
+    Object.defineProperty(URL.prototype, 'searchParams', {
+        get: function() { return this._params; }
+    });
+
+    URL.prototype.toJSON = function() { return this._c.href; };
+
+    URL.canParse = function(url, base) {
+        try {
+            new URL(url, base);
+            return true;
+        } catch (e) {
+            return false;
+        }
+    };
+
+    URL.parse = function(url, base) {
+        try {
+            return new URL(url, base);
+        } catch (e) {
+            return null;
+        }
+    };
+
+    // window.location shares the URL component accessors
+    var __rustkit_Location = function() {
+        this._c = {
+            href: 'about:blank', origin: 'null', protocol: 'about:', username: '',
+            password: '', host: '', hostname: '', port: '', pathname: 'blank',
+            search: '', hash: ''
+        };
+    };
+
+    __rustkit_defineUrlComponents(__rustkit_Location.prototype, true);
+    __rustkit_Location.prototype.reload = function() {};
+    __rustkit_Location.prototype.replace = function(url) { this.href = url; };
+    __rustkit_Location.prototype.assign = function(url) { this.href = url; };
+    // Called from Rust when the view navigates
+    __rustkit_Location.prototype._setHref = function(href) {
+        this._c = JSON.parse(__rustkit_url_parse(href));
+    };
+
+    window.location = new __rustkit_Location();
+    window.URL = URL;
+    window.URLSearchParams = URLSearchParams;
+    var location = window.location;
+"#;
+
+/// Register the native functions and install `URL` and `URLSearchParams`.
+pub(crate) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.register_function("__rustkit_url_parse", 2, native_parse)?;
+    runtime.register_function("__rustkit_url_set", 3, native_set)?;
+    runtime.register_function("__rustkit_urlencoded_parse", 1, native_urlencoded_parse)?;
+    runtime.register_function(
+        "__rustkit_urlencoded_serialize",
+        1,
+        native_urlencoded_serialize,
+    )?;
+    runtime.evaluate_script(URL_JS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> JsRuntime {
+        let mut runtime = JsRuntime::new().unwrap();
+        runtime.evaluate_script("var window = {};").unwrap();
+        install(&mut runtime).unwrap();
+        runtime
+    }
+
+    fn eval_string(runtime: &mut JsRuntime, script: &str) -> String {
+        match runtime.evaluate_script(script).unwrap() {
+            JsValue::String(s) => s,
+            other => panic!("{} returned {:?}", script, other),
+        }
+    }
+
+    #[test]
+    fn test_url_parse_serialize() {
+        let mut runtime = runtime();
+
+        let cases = [
+            (
+                "new URL('https://example.com').href",
+                "https://example.com/",
+            ),
+            (
+                "new URL('HTTPS://Example.COM:443/a/../b').href",
+                "https://example.com/b",
+            ),
+            (
+                "new URL('/path?q=1', 'https://example.com/dir/').href",
+                "https://example.com/path?q=1",
+            ),
+            (
+                "new URL('sub/page', 'https://example.com/dir/index.html').href",
+                "https://example.com/dir/sub/page",
+            ),
+            (
+                "new URL('../up', 'https://example.com/a/b/c').href",
+                "https://example.com/a/up",
+            ),
+            (
+                "new URL('//cdn.example.org/x', 'https://example.com').href",
+                "https://cdn.example.org/x",
+            ),
+            (
+                "new URL('#frag', 'https://example.com/p?q').href",
+                "https://example.com/p?q#frag",
+            ),
+            (
+                "new URL('http://user:pw@host:8080/p?x=1#h').origin",
+                "http://host:8080",
+            ),
+            (
+                "new URL('http://user:pw@host:8080/p?x=1#h').host",
+                "host:8080",
+            ),
+            ("new URL('http://user:pw@host:8080/p?x=1#h').port", "8080"),
+            (
+                "new URL('http://user:pw@host:8080/p?x=1#h').username",
+                "user",
+            ),
+            ("new URL('http://user:pw@host:8080/p?x=1#h').search", "?x=1"),
+            ("new URL('http://user:pw@host:8080/p?x=1#h').hash", "#h"),
+            ("new URL('http://host:80/').port", ""),
+            (
+                "new URL('file:///C:/dir/file.txt').pathname",
+                "/C:/dir/file.txt",
+            ),
+            (
+                "String(new URL('https://example.com/a'))",
+                "https://example.com/a",
+            ),
+        ];
+
+        for (script, expected) in cases {
+            assert_eq!(eval_string(&mut runtime, script), expected, "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_url_invalid_throws_type_error() {
+        let mut runtime = runtime();
+
+        let result = runtime
+            .evaluate_script(
+                "try { new URL('not a url'); 'ok' } catch (e) { e instanceof TypeError }",
+            )
+            .unwrap();
+        assert!(matches!(result, JsValue::Boolean(true)));
+
+        let result = runtime
+            .evaluate_script(
+                "URL.canParse('/relative') || !URL.canParse('/relative', 'https://a.com')",
+            )
+            .unwrap();
+        assert!(matches!(result, JsValue::Boolean(false)));
+    }
+
+    #[test]
+    fn test_url_setters() {
+        let mut runtime = runtime();
+
+        runtime
+            .evaluate_script(
+                "var u = new URL('https://example.com/a?x=1#top');
+                 u.pathname = '/b c';
+                 u.hash = 'end';
+                 u.port = '8443';
+                 u.protocol = 'http';
+                 u.port = 'bogus';",
+            )
+            .unwrap();
+
+        assert_eq!(
+            eval_string(&mut runtime, "u.href"),
+            "http://example.com:8443/b%20c?x=1#end"
+        );
+    }
+
+    #[test]
+    fn test_search_params_live() {
+        let mut runtime = runtime();
+
+        runtime
+            .evaluate_script(
+                "var u = new URL('https://example.com/s?q=rust&page=2&q=boa');
+                 var p = u.searchParams;",
+            )
+            .unwrap();
+
+        assert_eq!(eval_string(&mut runtime, "p.get('q')"), "rust");
+        assert_eq!(
+            eval_string(&mut runtime, "p.getAll('q').join(',')"),
+            "rust,boa"
+        );
+
+        runtime
+            .evaluate_script("p.set('q', 'a b'); p.append('lang', 'en'); p.delete('page');")
+            .unwrap();
+        assert_eq!(
+            eval_string(&mut runtime, "u.href"),
+            "https://example.com/s?q=a+b&lang=en"
+        );
+
+        runtime.evaluate_script("p.sort();").unwrap();
+        assert_eq!(eval_string(&mut runtime, "u.search"), "?lang=en&q=a+b");
+
+        // Setting search updates the params
+        runtime.evaluate_script("u.search = '?z=26';").unwrap();
+        assert_eq!(eval_string(&mut runtime, "p.get('z')"), "26");
+        assert!(matches!(
+            runtime.evaluate_script("p.has('q')").unwrap(),
+            JsValue::Boolean(false)
+        ));
+
+        // Deleting every param drops the '?'
+        runtime.evaluate_script("p.delete('z');").unwrap();
+        assert_eq!(eval_string(&mut runtime, "u.href"), "https://example.com/s");
+    }
+
+    #[test]
+    fn test_search_params_construction() {
+        let mut runtime = runtime();
+
+        let cases = [
+            ("new URLSearchParams('?a=1&b=%20x').toString()", "a=1&b=+x"),
+            ("new URLSearchParams([['a', '1'], ['a', '2']]).getAll('a').join()", "1,2"),
+            ("new URLSearchParams({ x: 1, y: 'two' }).toString()", "x=1&y=two"),
+            ("String(new URLSearchParams('a=1').has('a'))", "true"),
+            (
+                "var out = []; new URLSearchParams('k=v&k2=v2').forEach(function(v, k) { out.push(k + ':' + v); }); out.join()",
+                "k:v,k2:v2",
+            ),
+            ("Array.from(new URLSearchParams('a=1&b=2').keys()).join()", "a,b"),
+        ];
+
+        for (script, expected) in cases {
+            assert_eq!(eval_string(&mut runtime, script), expected, "{}", script);
+        }
+    }
+}
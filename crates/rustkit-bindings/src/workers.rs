@@ -0,0 +1,719 @@
+//! Dedicated workers: `new Worker(url)` in a document and the global scope
+//! script runs in inside one.
+//!
+//! Like `fetch`, starting a worker is mediated by the host: the constructor
+//! queues a [`WorkerCommand::Start`] and returns straight away, and
+//! `postMessage` and `terminate` queue commands for the host to carry to
+//! the worker's thread. Messages coming back are fired at the `Worker`
+//! object by [`crate::DomBindings::deliver_worker_message`].
+//!
+//! Inside the worker, a [`WorkerScope`] owns the worker's own runtime: it
+//! has `self`, `postMessage`, `onmessage`, `close`, `importScripts`, timers
+//! and `fetch`, and no DOM. The host runs it on its own thread and drives
+//! its event loop.
+//!
+//! Messages cross as JSON produced by a structured clone: functions and
+//! symbols fail with a `DataCloneError`, `ArrayBuffer`s and typed arrays
+//! are copied, and an `ArrayBuffer` in the transfer list is detached from
+//! the sender.
+
+use crate::blob::{self, BlobState};
+use crate::encoding;
+use crate::errors::{PageError, PageErrorKind};
+use crate::fetch::{self, FetchCommand, FetchResponse, FetchState};
+use crate::form_data::{self, FormDataState};
+use crate::scheduler;
+use crate::url_api;
+use crate::BindingError;
+use rustkit_js::{JsError, JsRuntime, JsValue};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use url::Url;
+
+/// Work for the host queued by `Worker` objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkerCommand {
+    /// Fetch `url` and run it as a classic worker script.
+    Start { id: u64, url: Url, name: String },
+    /// Deliver a structured-clone message to the worker. `data` is JSON.
+    PostMessage { id: u64, data: String },
+    /// Stop the worker; nothing it sends is delivered any more.
+    Terminate { id: u64 },
+}
+
+/// Commands queued by script since the host last drained them.
+#[derive(Debug, Default)]
+pub(crate) struct WorkerState {
+    next_id: Cell<u64>,
+    commands: RefCell<Vec<WorkerCommand>>,
+}
+
+impl WorkerState {
+    pub(crate) fn take_commands(&self) -> Vec<WorkerCommand> {
+        self.commands.take()
+    }
+}
+
+/// Structured clone over JSON, shared by documents and workers.
+const CLONE_JS: &str = r#"
+    function __rustkit_cloneOut(message, transfer) {
+        var buffers = [];
+        if (transfer !== undefined && transfer !== null) {
+            Array.prototype.forEach.call(transfer, function(item) {
+                if (!(item instanceof ArrayBuffer)) {
+                    throw new DOMException('Value at index ' + buffers.length +
+                        ' does not have a transferable type.', 'DataCloneError');
+                }
+                if (buffers.indexOf(item) >= 0) {
+                    throw new DOMException('ArrayBuffer at index ' + buffers.length +
+                        ' is a duplicate of an earlier ArrayBuffer.', 'DataCloneError');
+                }
+                buffers.push(item);
+            });
+        }
+        var data = JSON.stringify(message === undefined ? null : message, function(key, value) {
+            if (typeof value === 'function' || typeof value === 'symbol') {
+                throw new DOMException(String(value) + ' could not be cloned.', 'DataCloneError');
+            }
+            if (value instanceof ArrayBuffer) {
+                return { __rustkit_clone: 'ArrayBuffer', bytes: Array.from(new Uint8Array(value)) };
+            }
+            if (ArrayBuffer.isView(value)) {
+                var bytes = new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+                return { __rustkit_clone: value.constructor.name, bytes: Array.from(bytes) };
+            }
+            return value;
+        });
+        // Transferred buffers are detached once the message is cloned
+        buffers.forEach(function(buffer) {
+            if (typeof buffer.transfer === 'function') buffer.transfer();
+        });
+        return data === undefined ? 'null' : data;
+    }
+
+    function __rustkit_cloneIn(data) {
+        return JSON.parse(data, function(key, value) {
+            if (value === null || typeof value !== 'object' || !value.__rustkit_clone) return value;
+            var buffer = new Uint8Array(value.bytes).buffer;
+            if (value.__rustkit_clone === 'ArrayBuffer') return buffer;
+            var View = globalThis[value.__rustkit_clone];
+            return typeof View === 'function' ? new View(buffer) : buffer;
+        });
+    }
+
+    function __rustkit_messageEvent(type, target, fields) {
+        var event = {
+            type: type, target: target, currentTarget: target, bubbles: false,
+            cancelable: type === 'error', defaultPrevented: false, timeStamp: Date.now(),
+            isTrusted: true, lastEventId: '', ports: [], source: null, origin: '',
+            preventDefault: function() { if (this.cancelable) this.defaultPrevented = true; },
+            stopPropagation: function() {}, stopImmediatePropagation: function() {}
+        };
+        Object.keys(fields).forEach(function(key) { event[key] = fields[key]; });
+        return event;
+    }
+
+    // Run the `on<type>` handler of `target`, then its listeners
+    function __rustkit_fireAt(target, listeners, event) {
+        var handler = target['on' + event.type];
+        if (typeof handler === 'function') {
+            var result = handler.call(target, event);
+            if (event.type === 'error' && result === true) event.preventDefault();
+        }
+        (listeners[event.type] || []).slice().forEach(function(listener) {
+            if (typeof listener === 'function') listener.call(target, event);
+            else if (listener && typeof listener.handleEvent === 'function') listener.handleEvent(event);
+        });
+        return !event.defaultPrevented;
+    }
+
+    function __rustkit_addListener(listeners, type, callback) {
+        if (!callback) return;
+        var list = listeners[type] || (listeners[type] = []);
+        if (list.indexOf(callback) < 0) list.push(callback);
+    }
+
+    function __rustkit_removeListener(listeners, type, callback) {
+        var list = listeners[type] || [];
+        var index = list.indexOf(callback);
+        if (index >= 0) list.splice(index, 1);
+    }
+"#;
+
+const WORKER_JS: &str = r#"
+    var __rustkit_workers = {};
+
+    function Worker(url, options) {
+        if (!(this instanceof Worker)) {
+            throw new TypeError("Failed to construct 'Worker': Please use the 'new' operator.");
+        }
+        options = options || {};
+        if (options.type === 'module') {
+            throw new TypeError("Failed to construct 'Worker': Module workers are not supported.");
+        }
+        var href;
+        try {
+            href = new URL(String(url), location.href).href;
+        } catch (e) {
+            throw new DOMException("Failed to construct 'Worker': The URL '" + url +
+                "' is invalid.", 'SyntaxError');
+        }
+        this.onmessage = null;
+        this.onmessageerror = null;
+        this.onerror = null;
+        this._listeners = {};
+        this._terminated = false;
+        this._id = __rustkit_worker_start(href, options.name === undefined ? '' : String(options.name));
+        __rustkit_workers[this._id] = this;
+    }
+    Worker.prototype.postMessage = function(message, transfer) {
+        if (transfer !== undefined && transfer !== null && !Array.isArray(transfer)) {
+            transfer = transfer.transfer;
+        }
+        var data = __rustkit_cloneOut(message, transfer);
+        if (!this._terminated) __rustkit_worker_post(this._id, data);
+    };
+    Worker.prototype.terminate = function() {
+        if (this._terminated) return;
+        this._terminated = true;
+        delete __rustkit_workers[this._id];
+        __rustkit_worker_terminate(this._id);
+    };
+    Worker.prototype.addEventListener = function(type, callback) {
+        __rustkit_addListener(this._listeners, type, callback);
+    };
+    Worker.prototype.removeEventListener = function(type, callback) {
+        __rustkit_removeListener(this._listeners, type, callback);
+    };
+    Worker.prototype.dispatchEvent = function(event) {
+        return __rustkit_fireAt(this, this._listeners, event);
+    };
+
+    function __rustkit_workerMessage(id, data) {
+        var worker = __rustkit_workers[id];
+        if (!worker) return;
+        var event;
+        try {
+            event = __rustkit_messageEvent('message', worker, { data: __rustkit_cloneIn(data) });
+        } catch (e) {
+            event = __rustkit_messageEvent('messageerror', worker, { data: null });
+        }
+        __rustkit_fireAt(worker, worker._listeners, event);
+    }
+
+    // Returns whether the error went unhandled
+    function __rustkit_workerError(id, message, filename, lineno, colno) {
+        var worker = __rustkit_workers[id];
+        if (!worker) return false;
+        return __rustkit_fireAt(worker, worker._listeners, __rustkit_messageEvent('error', worker, {
+            message: message, filename: filename, lineno: lineno, colno: colno, error: null
+        }));
+    }
+
+    window.Worker = Worker;
+"#;
+
+/// Register the worker natives and install `Worker`.
+///
+/// Needs `DOMException` and `URL` installed first.
+pub(crate) fn install(runtime: &mut JsRuntime, workers: Rc<WorkerState>) -> Result<(), JsError> {
+    let state = workers.clone();
+    runtime.register_function("__rustkit_worker_start", 2, move |args| {
+        let url = args.first().map(String::as_str).unwrap_or_default();
+        let url = Url::parse(url).map_err(|e| JsError::TypeError(format!("Invalid URL: {}", e)))?;
+        let id = state.next_id.get() + 1;
+        state.next_id.set(id);
+        state.commands.borrow_mut().push(WorkerCommand::Start {
+            id,
+            url,
+            name: args.get(1).cloned().unwrap_or_default(),
+        });
+        Ok(JsValue::Number(id as f64))
+    })?;
+
+    let state = workers.clone();
+    runtime.register_function("__rustkit_worker_post", 2, move |args| {
+        let [id, data] = args else {
+            return Err(JsError::TypeError("postMessage expects 2 arguments".into()));
+        };
+        state.commands.borrow_mut().push(WorkerCommand::PostMessage {
+            id: id_arg(id),
+            data: data.clone(),
+        });
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.register_function("__rustkit_worker_terminate", 1, move |args| {
+        let id = args.first().map_or(0, |id| id_arg(id));
+        let mut commands = workers.commands.borrow_mut();
+        // A worker the host has not started yet never starts
+        let queued = commands
+            .iter()
+            .position(|c| matches!(c, WorkerCommand::Start { id: start, .. } if *start == id));
+        match queued {
+            Some(index) => {
+                commands.retain(|c| match c {
+                    WorkerCommand::PostMessage { id: target, .. } => *target != id,
+                    _ => true,
+                });
+                commands.remove(index);
+            }
+            None => commands.push(WorkerCommand::Terminate { id }),
+        }
+        Ok(JsValue::Undefined)
+    })?;
+
+    runtime.evaluate_script(CLONE_JS)?;
+    runtime.evaluate_script(WORKER_JS)?;
+    Ok(())
+}
+
+fn id_arg(arg: &str) -> u64 {
+    arg.parse::<f64>().unwrap_or(0.0) as u64
+}
+
+/// Script firing `message` at worker `id` with JSON `data`.
+pub(crate) fn message_script(id: u64, data: &str) -> String {
+    format!(
+        "__rustkit_workerMessage({}, {});",
+        id,
+        serde_json::Value::from(data)
+    )
+}
+
+/// Script firing `error` at worker `id` for `error`; evaluates to whether
+/// no handler cancelled it.
+pub(crate) fn error_script(id: u64, error: &PageError) -> String {
+    format!(
+        "__rustkit_workerError({}, {}, {}, {}, {});",
+        id,
+        serde_json::Value::from(error.message.as_str()),
+        serde_json::Value::from(error.source_url.as_str()),
+        error.line,
+        error.column
+    )
+}
+
+/// Loads the source of a script named in `importScripts`.
+type ScriptLoader = Box<dyn Fn(&Url) -> Result<String, String>>;
+
+/// What a worker's script asked of its host since the last drain.
+#[derive(Default)]
+struct ScopeState {
+    /// JSON messages for the owning document.
+    messages: RefCell<Vec<String>>,
+    errors: RefCell<Vec<PageError>>,
+    closing: Cell<bool>,
+    loader: RefCell<Option<ScriptLoader>>,
+}
+
+const WORKER_SCOPE_JS: &str = r#"
+    var self = globalThis;
+    self.name = __rustkit_workerName;
+    self.onmessage = null;
+    self.onmessageerror = null;
+    self.onerror = null;
+    var __rustkit_scopeListeners = {};
+
+    self.postMessage = function(message, transfer) {
+        if (transfer !== undefined && transfer !== null && !Array.isArray(transfer)) {
+            transfer = transfer.transfer;
+        }
+        __rustkit_scope_post(__rustkit_cloneOut(message, transfer));
+    };
+    self.close = function() {
+        __rustkit_scope_close();
+    };
+    self.importScripts = function() {
+        var urls = Array.prototype.map.call(arguments, function(url) {
+            try {
+                return new URL(String(url), location.href).href;
+            } catch (e) {
+                throw new DOMException("Failed to execute 'importScripts': The URL '" + url +
+                    "' is invalid.", 'SyntaxError');
+            }
+        });
+        // Every script is fetched before any of them runs
+        var sources = urls.map(function(url) {
+            var source = __rustkit_scope_import(url);
+            if (source === null) {
+                throw new DOMException("Failed to execute 'importScripts': The script at '" +
+                    url + "' failed to load.", 'NetworkError');
+            }
+            return source;
+        });
+        sources.forEach(function(source) { (0, eval)(source); });
+    };
+    self.addEventListener = function(type, callback) {
+        __rustkit_addListener(__rustkit_scopeListeners, type, callback);
+    };
+    self.removeEventListener = function(type, callback) {
+        __rustkit_removeListener(__rustkit_scopeListeners, type, callback);
+    };
+    self.dispatchEvent = function(event) {
+        return __rustkit_fireAt(self, __rustkit_scopeListeners, event);
+    };
+
+    function __rustkit_reportException(error, muted) {
+        var message = 'Uncaught exception', line = 0, column = 0, stack = '';
+        try {
+            message = String(error);
+        } catch (e) {}
+        if (error !== null && typeof error === 'object') {
+            if (typeof error.lineNumber === 'number') {
+                line = error.lineNumber;
+                column = Number(error.columnNumber) || 0;
+            }
+            if (typeof error.stack === 'string') stack = error.stack;
+        }
+        var at = /at line (\d+), col(?:umn)? (\d+)/.exec(message);
+        if (!line && at) {
+            line = Number(at[1]);
+            column = Number(at[2]);
+        }
+        // A handler in the worker returning true keeps it from the document
+        var event = __rustkit_messageEvent('error', self, {
+            message: message, filename: location.href, lineno: line, colno: column, error: error
+        });
+        if (__rustkit_fireAt(self, __rustkit_scopeListeners, event)) {
+            __rustkit_scope_error(message, location.href, line, column, stack);
+        }
+    }
+
+    function __rustkit_scopeMessage(data) {
+        var event;
+        try {
+            event = __rustkit_messageEvent('message', self, { data: __rustkit_cloneIn(data) });
+        } catch (e) {
+            event = __rustkit_messageEvent('messageerror', self, { data: null });
+        }
+        try {
+            __rustkit_fireAt(self, __rustkit_scopeListeners, event);
+        } catch (e) {
+            __rustkit_reportException(e, false);
+        }
+    }
+"#;
+
+/// The global scope of a dedicated worker, with its own runtime.
+///
+/// Not `Send`: create it on the thread that runs the worker.
+pub struct WorkerScope {
+    runtime: RefCell<JsRuntime>,
+    state: Rc<ScopeState>,
+    fetches: Rc<FetchState>,
+}
+
+impl WorkerScope {
+    /// Set up the global scope of a worker running the script at `url`.
+    pub fn new(mut runtime: JsRuntime, url: &Url, name: &str) -> Result<Self, BindingError> {
+        // The shared installers publish on `window`; a worker has none, so
+        // it points at the global object until they are done
+        runtime.evaluate_script(&format!(
+            "globalThis.window = globalThis; var __rustkit_workerName = {};",
+            serde_json::Value::from(name)
+        ))?;
+        encoding::install(&mut runtime)?;
+        url_api::install(&mut runtime)?;
+        blob::install(&mut runtime, Rc::new(BlobState::default()))?;
+        form_data::install(&mut runtime, Rc::new(FormDataState::default()))?;
+        let fetches = Rc::new(FetchState::default());
+        fetch::install(&mut runtime, fetches.clone())?;
+        scheduler::install(&mut runtime)?;
+        runtime.evaluate_script(CLONE_JS)?;
+
+        let state = Rc::new(ScopeState::default());
+        let post = state.clone();
+        runtime.register_function("__rustkit_scope_post", 1, move |args| {
+            let data = args.first().cloned().unwrap_or_else(|| "null".into());
+            post.messages.borrow_mut().push(data);
+            Ok(JsValue::Undefined)
+        })?;
+        let close = state.clone();
+        runtime.register_function("__rustkit_scope_close", 0, move |_| {
+            close.closing.set(true);
+            Ok(JsValue::Undefined)
+        })?;
+        let errors = state.clone();
+        runtime.register_function("__rustkit_scope_error", 5, move |args| {
+            let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_default();
+            let position = |i: usize| arg(i).parse::<f64>().map_or(0, |n| n.max(0.0) as u32);
+            errors.errors.borrow_mut().push(PageError {
+                kind: PageErrorKind::Exception,
+                message: arg(0).to_string(),
+                source_url: arg(1).to_string(),
+                line: position(2),
+                column: position(3),
+                stack: Some(arg(4)).filter(|s| !s.is_empty()).map(str::to_string),
+            });
+            Ok(JsValue::Undefined)
+        })?;
+        let imports = state.clone();
+        runtime.register_function("__rustkit_scope_import", 1, move |args| {
+            let url = args.first().and_then(|url| Url::parse(url).ok());
+            let loader = imports.loader.borrow();
+            let source = match (url, loader.as_ref()) {
+                (Some(url), Some(load)) => load(&url).ok(),
+                _ => None,
+            };
+            Ok(source.map_or(JsValue::Null, JsValue::String))
+        })?;
+
+        runtime.evaluate_script(WORKER_SCOPE_JS)?;
+        runtime.evaluate_script(&format!(
+            "location._setHref({}); delete globalThis.window;",
+            serde_json::Value::from(url.as_str())
+        ))?;
+
+        Ok(Self {
+            runtime: RefCell::new(runtime),
+            state,
+            fetches,
+        })
+    }
+
+    /// Load the scripts `importScripts` names with `loader`. Without one,
+    /// every import fails with a `NetworkError`.
+    pub fn set_script_loader<F>(&self, loader: F)
+    where
+        F: Fn(&Url) -> Result<String, String> + 'static,
+    {
+        *self.state.loader.borrow_mut() = Some(Box::new(loader));
+    }
+
+    /// Run the worker's script. What it throws, including a syntax error,
+    /// is reported to the worker's error handlers and
+    /// [`Self::take_errors`] rather than returned.
+    pub fn run_script(&self, source: &str) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&crate::errors::run_script(source, false))?;
+        Ok(())
+    }
+
+    /// Fire a `message` event at the global scope. `data` is JSON.
+    pub fn deliver_message(&self, data: &str) -> Result<(), BindingError> {
+        let script = format!("__rustkit_scopeMessage({});", serde_json::Value::from(data));
+        self.runtime.borrow_mut().evaluate_script(&script)?;
+        Ok(())
+    }
+
+    /// Run the timers due at `now`, in milliseconds since the worker
+    /// started. Returns when the next timer is due, if any is set.
+    pub fn run_timers(&self, now: f64) -> Result<Option<f64>, BindingError> {
+        let script = format!("__rustkit_runTimers({}, 0)", now);
+        match self.runtime.borrow_mut().evaluate_script(&script)? {
+            JsValue::Number(next) if next >= 0.0 => Ok(Some(next)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Take the messages posted to the owning document since the last
+    /// call, as JSON.
+    pub fn take_messages(&self) -> Vec<String> {
+        self.state.messages.take()
+    }
+
+    /// Take the errors the worker's script left unhandled since the last
+    /// call.
+    pub fn take_errors(&self) -> Vec<PageError> {
+        self.state.errors.take()
+    }
+
+    /// Whether the script called `close()`.
+    pub fn is_closing(&self) -> bool {
+        self.state.closing.get()
+    }
+
+    /// Take the fetches the worker started or aborted since the last call.
+    pub fn take_fetch_commands(&self) -> Vec<FetchCommand> {
+        self.fetches.take_commands()
+    }
+
+    /// Settle fetch `id` with the host's response or network error.
+    pub fn complete_fetch(
+        &self,
+        id: u64,
+        result: Result<FetchResponse, &str>,
+    ) -> Result<(), BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(&fetch::settle_script(id, result.as_ref().map_err(|e| *e)))?;
+        Ok(())
+    }
+
+    /// Evaluate a script in the worker's global scope.
+    pub fn evaluate(&self, script: &str) -> Result<JsValue, BindingError> {
+        self.runtime
+            .borrow_mut()
+            .evaluate_script(script)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomBindings;
+
+    fn scope() -> WorkerScope {
+        let url = Url::parse("https://app.example/js/sum.js").unwrap();
+        WorkerScope::new(JsRuntime::new().unwrap(), &url, "sum").unwrap()
+    }
+
+    #[test]
+    fn test_worker_queues_commands() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://app.example/index.html").unwrap())
+            .unwrap();
+        bindings
+            .evaluate(
+                "var worker = new Worker('js/sum.js', { name: 'sum' }); \
+                 worker.postMessage([1, 2, 3]); \
+                 var idle = new Worker('idle.js'); idle.postMessage('dropped'); idle.terminate();",
+            )
+            .unwrap();
+
+        let commands = bindings.take_worker_commands();
+        assert_eq!(
+            commands,
+            [
+                WorkerCommand::Start {
+                    id: 1,
+                    url: Url::parse("https://app.example/js/sum.js").unwrap(),
+                    name: "sum".to_string(),
+                },
+                WorkerCommand::PostMessage {
+                    id: 1,
+                    data: "[1,2,3]".to_string(),
+                },
+            ]
+        );
+
+        bindings.evaluate("worker.terminate(); worker.postMessage(4);").unwrap();
+        assert_eq!(
+            bindings.take_worker_commands(),
+            [WorkerCommand::Terminate { id: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_clone_rejects_functions_and_transfers_buffers() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var worker = new Worker('https://app.example/w.js'); var cloneError = ''; \
+                 try { worker.postMessage({ f: function() {} }); } catch (e) { cloneError = e.name; } \
+                 var buffer = new Uint8Array([7, 8]).buffer; \
+                 worker.postMessage({ buffer: buffer }, [buffer]);",
+            )
+            .unwrap();
+        let commands = bindings.take_worker_commands();
+        let [_, WorkerCommand::PostMessage { data, .. }] = commands.as_slice() else {
+            panic!("unexpected commands {:?}", commands);
+        };
+
+        // The receiving side gets an equal buffer back
+        let scope = scope();
+        scope
+            .evaluate("var got; onmessage = function(e) { got = new Uint8Array(e.data.buffer); };")
+            .unwrap();
+        scope.deliver_message(data).unwrap();
+        assert!(matches!(
+            scope.evaluate("got.length === 2 && got[0] === 7 && got[1] === 8").unwrap(),
+            JsValue::Boolean(true)
+        ));
+        assert!(matches!(
+            bindings.evaluate("cloneError").unwrap(),
+            JsValue::String(name) if name == "DataCloneError"
+        ));
+    }
+
+    #[test]
+    fn test_scope_answers_messages() {
+        let scope = scope();
+        scope
+            .run_script(
+                "self.onmessage = function(e) { \
+                     postMessage(e.data.reduce(function(a, b) { return a + b; }, 0)); \
+                     if (e.data.length > 3) close(); \
+                 }; \
+                 setTimeout(function() { postMessage(self.name + ' ' + location.pathname); }, 10);",
+            )
+            .unwrap();
+        assert!(matches!(
+            scope.evaluate("typeof window === 'undefined' && typeof document === 'undefined'").unwrap(),
+            JsValue::Boolean(true)
+        ));
+
+        scope.deliver_message("[1,2,3]").unwrap();
+        assert_eq!(scope.take_messages(), ["6"]);
+        assert!(!scope.is_closing());
+        assert_eq!(scope.run_timers(10.0).unwrap(), None);
+        assert_eq!(scope.take_messages(), [r#""sum /js/sum.js""#]);
+
+        scope.deliver_message("[1,2,3,4]").unwrap();
+        assert_eq!(scope.take_messages(), ["10"]);
+        assert!(scope.is_closing());
+    }
+
+    #[test]
+    fn test_scope_errors_and_imports() {
+        let scope = scope();
+        scope.run_script("var broken = ;").unwrap();
+        let errors = scope.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].source_url, "https://app.example/js/sum.js");
+
+        scope.set_script_loader(|url| match url.path() {
+            "/js/lib.js" => Ok("var imported = 42;".to_string()),
+            _ => Err("not found".to_string()),
+        });
+        scope
+            .run_script(
+                "importScripts('lib.js'); var failed = ''; \
+                 try { importScripts('missing.js'); } catch (e) { failed = e.name; }",
+            )
+            .unwrap();
+        assert!(scope.take_errors().is_empty());
+        assert!(matches!(
+            scope.evaluate("imported === 42 && failed === 'NetworkError'").unwrap(),
+            JsValue::Boolean(true)
+        ));
+    }
+
+    #[test]
+    fn test_worker_error_event() {
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings
+            .evaluate(
+                "var worker = new Worker('https://app.example/w.js'); var seen = []; \
+                 worker.onmessage = function(e) { seen.push(e.data.n); }; \
+                 worker.addEventListener('error', function(e) { seen.push(e.message); });",
+            )
+            .unwrap();
+        bindings.deliver_worker_message(1, r#"{"n":3}"#).unwrap();
+        let error = PageError {
+            kind: PageErrorKind::Exception,
+            message: "SyntaxError: unexpected token".to_string(),
+            source_url: "https://app.example/w.js".to_string(),
+            line: 1,
+            column: 14,
+            stack: None,
+        };
+        bindings.deliver_worker_error(1, &error).unwrap();
+        assert!(matches!(
+            bindings.evaluate("seen.join('|')").unwrap(),
+            JsValue::String(seen) if seen == "3|SyntaxError: unexpected token"
+        ));
+        // Unhandled, so the host hears of it too
+        assert_eq!(bindings.take_page_errors(), std::slice::from_ref(&error));
+
+        bindings
+            .evaluate("worker.onerror = function(e) { e.preventDefault(); };")
+            .unwrap();
+        bindings.deliver_worker_error(1, &error).unwrap();
+        assert!(bindings.take_page_errors().is_empty());
+    }
+}
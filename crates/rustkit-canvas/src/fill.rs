@@ -0,0 +1,216 @@
+//! Scanline filling of shapes made of several subpaths.
+//!
+//! All subpaths of a shape fill one region: a point is inside when the
+//! shape winds around it a number of times its [`FillRule`] accepts, so an
+//! inner subpath can cut a hole into an outer one. The canvas rasterizer
+//! samples the region's spans row by row; GPU renderers take it as
+//! trapezoids from [`fill_trapezoids`].
+
+use crate::Polygon;
+use rustkit_css::FillRule;
+
+/// Shortest band [`fill_trapezoids`] splits to separate crossing edges.
+const MIN_BAND: f32 = 0.25;
+
+/// A non-horizontal edge, oriented top to bottom.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    top: (f32, f32),
+    bottom: (f32, f32),
+    /// +1 if the subpath runs downwards along the edge, -1 if upwards.
+    winding: i32,
+}
+
+impl Edge {
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top.1) / (self.bottom.1 - self.top.1);
+        self.top.0 + t * (self.bottom.0 - self.top.0)
+    }
+}
+
+/// The region a set of closed subpaths fills under a fill rule.
+pub(crate) struct FillRegion {
+    edges: Vec<Edge>,
+    rule: FillRule,
+    /// Bounding box: min x, min y, max x, max y.
+    bounds: Option<[f32; 4]>,
+    /// Edges crossing the last scanline, left to right.
+    crossings: Vec<(f32, usize)>,
+}
+
+impl FillRegion {
+    /// Subpaths with fewer than three points enclose nothing and are
+    /// skipped; the others are closed implicitly.
+    pub(crate) fn new(subpaths: &[Polygon], rule: FillRule) -> Self {
+        let mut edges = Vec::new();
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for polygon in subpaths.iter().filter(|p| p.len() >= 3) {
+            for i in 0..polygon.len() {
+                let a = polygon[i];
+                let b = polygon[(i + 1) % polygon.len()];
+                min_x = min_x.min(a.0);
+                max_x = max_x.max(a.0);
+                min_y = min_y.min(a.1);
+                max_y = max_y.max(a.1);
+                if a.1 < b.1 {
+                    edges.push(Edge {
+                        top: a,
+                        bottom: b,
+                        winding: 1,
+                    });
+                } else if a.1 > b.1 {
+                    edges.push(Edge {
+                        top: b,
+                        bottom: a,
+                        winding: -1,
+                    });
+                }
+            }
+        }
+        let finite = [min_x, min_y, max_x, max_y].iter().all(|v| v.is_finite());
+        Self {
+            bounds: (!edges.is_empty() && finite).then_some([min_x, min_y, max_x, max_y]),
+            edges,
+            rule,
+            crossings: Vec::new(),
+        }
+    }
+
+    /// Bounding box as min x, min y, max x, max y; `None` if the region
+    /// is empty.
+    pub(crate) fn bounds(&self) -> Option<[f32; 4]> {
+        self.bounds
+    }
+
+    /// The inside spans of the scanline at `y`, left to right, as the
+    /// pairs of edges bounding them.
+    pub(crate) fn spans_at(&mut self, y: f32, spans: &mut Vec<(usize, usize)>) {
+        self.crossings.clear();
+        self.crossings.extend(
+            self.edges
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| y >= e.top.1 && y < e.bottom.1)
+                .map(|(i, e)| (e.x_at(y), i)),
+        );
+        self.crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        spans.clear();
+        let mut winding = 0;
+        let mut start = None;
+        for &(_, edge) in &self.crossings {
+            winding += self.edges[edge].winding;
+            match (self.rule.contains(winding), start) {
+                (true, None) => start = Some(edge),
+                (false, Some(left)) => {
+                    spans.push((left, edge));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Where `edge` crosses the scanline at `y`.
+    pub(crate) fn x_at(&self, edge: usize, y: f32) -> f32 {
+        self.edges[edge].x_at(y)
+    }
+
+    /// Push the trapezoids filling the band from `y0` to `y1`, splitting
+    /// it where edges cross inside it.
+    fn band(
+        &mut self,
+        y0: f32,
+        y1: f32,
+        spans: &mut Vec<(usize, usize)>,
+        out: &mut Vec<[(f32, f32); 4]>,
+    ) {
+        let mid = (y0 + y1) / 2.0;
+        self.spans_at(mid, spans);
+        let crossed = self.crossings.windows(2).any(|pair| {
+            let (a, b) = (pair[0].1, pair[1].1);
+            [y0, y1]
+                .iter()
+                .any(|&y| self.x_at(a, y) > self.x_at(b, y) + 1e-3)
+        });
+        if crossed && y1 - y0 > MIN_BAND {
+            self.band(y0, mid, spans, out);
+            self.band(mid, y1, spans, out);
+            return;
+        }
+        for &(left, right) in spans.iter() {
+            out.push([
+                (self.x_at(left, y0), y0),
+                (self.x_at(right, y0), y0),
+                (self.x_at(right, y1), y1),
+                (self.x_at(left, y1), y1),
+            ]);
+        }
+    }
+}
+
+/// Trapezoids covering the region `subpaths` fill under `rule`, each as
+/// top-left, top-right, bottom-right and bottom-left corners with
+/// horizontal top and bottom sides.
+pub fn fill_trapezoids(subpaths: &[Polygon], rule: FillRule) -> Vec<[(f32, f32); 4]> {
+    let mut region = FillRegion::new(subpaths, rule);
+    let mut ys: Vec<f32> = region
+        .edges
+        .iter()
+        .flat_map(|e| [e.top.1, e.bottom.1])
+        .collect();
+    ys.sort_by(f32::total_cmp);
+    ys.dedup();
+
+    let mut spans = Vec::new();
+    let mut trapezoids = Vec::new();
+    for band in ys.windows(2) {
+        region.band(band[0], band[1], &mut spans, &mut trapezoids);
+    }
+    trapezoids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32, clockwise: bool) -> Polygon {
+        let mut points = vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)];
+        if !clockwise {
+            points.reverse();
+        }
+        points
+    }
+
+    fn area(trapezoids: &[[(f32, f32); 4]]) -> f32 {
+        trapezoids
+            .iter()
+            .map(|[tl, tr, br, bl]| ((tr.0 - tl.0) + (br.0 - bl.0)) / 2.0 * (bl.1 - tl.1))
+            .sum()
+    }
+
+    #[test]
+    fn test_trapezoids_cut_holes_by_rule() {
+        let same = [square(0.0, 0.0, 10.0, true), square(3.0, 3.0, 4.0, true)];
+        let opposite = [square(0.0, 0.0, 10.0, true), square(3.0, 3.0, 4.0, false)];
+        assert_eq!(area(&fill_trapezoids(&same, FillRule::NonZero)), 100.0);
+        assert_eq!(area(&fill_trapezoids(&same, FillRule::EvenOdd)), 84.0);
+        assert_eq!(area(&fill_trapezoids(&opposite, FillRule::NonZero)), 84.0);
+    }
+
+    #[test]
+    fn test_self_intersecting_bands_are_split() {
+        // A bow tie: the two triangles meet at (5, 5)
+        let bow_tie = [vec![(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)]];
+        let trapezoids = fill_trapezoids(&bow_tie, FillRule::NonZero);
+        assert!(
+            (area(&trapezoids) - 50.0).abs() < 1.0,
+            "{}",
+            area(&trapezoids)
+        );
+        assert!(trapezoids
+            .iter()
+            .all(|[tl, tr, br, bl]| tl.0 <= tr.0 + 1e-3 && bl.0 <= br.0 + 1e-3));
+    }
+}
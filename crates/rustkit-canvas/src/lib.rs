@@ -10,6 +10,7 @@
 //! - **Transforms**: Translate, rotate, scale, matrix
 //! - **State**: Save/restore state stack
 //! - **Pixel Manipulation**: ImageData get/put
+//! - **Export**: PNG/JPEG encoding for `toDataURL` and `toBlob`
 //!
 //! ## Architecture
 //!
@@ -22,9 +23,19 @@
 //!           └── Transform Matrix
 //! ```
 
-use rustkit_css::Color;
-use std::collections::VecDeque;
+mod fill;
+mod raster;
+mod stroke;
+
+use base64::Engine as _;
+use raster::Surface;
+pub use fill::fill_trapezoids;
+use rustkit_css::{Color, FillRule};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::f32::consts::PI;
+use std::sync::Arc;
+pub use stroke::{dash_polyline, stroke_outline, LineStyle, Polygon};
 use thiserror::Error;
 
 // ==================== Errors ====================
@@ -40,6 +51,9 @@ pub enum CanvasError {
 
     #[error("Out of bounds: {0}")]
     OutOfBounds(String),
+
+    #[error("Encode error: {0}")]
+    Encode(String),
 }
 
 // ==================== Color & Style ====================
@@ -111,29 +125,7 @@ impl LinearGradient {
 
     /// Sample the gradient at a given position.
     pub fn sample(&self, t: f32) -> Color {
-        if self.stops.is_empty() {
-            return Color::TRANSPARENT;
-        }
-        if self.stops.len() == 1 {
-            return self.stops[0].color;
-        }
-
-        let t = t.clamp(0.0, 1.0);
-
-        // Find surrounding stops
-        for i in 0..self.stops.len() - 1 {
-            if t >= self.stops[i].offset && t <= self.stops[i + 1].offset {
-                let range = self.stops[i + 1].offset - self.stops[i].offset;
-                let local_t = if range > 0.0 {
-                    (t - self.stops[i].offset) / range
-                } else {
-                    0.0
-                };
-                return interpolate_color(&self.stops[i].color, &self.stops[i + 1].color, local_t);
-            }
-        }
-
-        self.stops.last().map(|s| s.color).unwrap_or(Color::TRANSPARENT)
+        sample_stops(&self.stops, t)
     }
 }
 
@@ -166,6 +158,11 @@ impl RadialGradient {
         });
         self.stops.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap());
     }
+
+    /// Sample the gradient at a given offset between the two circles.
+    pub fn sample(&self, t: f32) -> Color {
+        sample_stops(&self.stops, t)
+    }
 }
 
 /// Canvas pattern.
@@ -636,6 +633,73 @@ impl ImageData {
     }
 }
 
+// ==================== ImageBitmap ====================
+
+/// Immutable decoded pixels that can be drawn or transferred cheaply.
+///
+/// Clones share the same straight-alpha RGBA buffer, so a bitmap stays valid
+/// after the canvas or image it was created from is gone.
+#[derive(Debug, Clone)]
+pub struct ImageBitmap {
+    width: u32,
+    height: u32,
+    pixels: Arc<Vec<u8>>,
+}
+
+impl ImageBitmap {
+    /// Wrap the pixels of `data`.
+    pub fn from_image_data(data: ImageData) -> Self {
+        Self {
+            width: data.width,
+            height: data.height,
+            pixels: Arc::new(data.data),
+        }
+    }
+
+    /// Copy the pixels of a decoded image, e.g. one held by the image manager.
+    pub fn from_rgba_image(image: &rustkit_codecs::RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: Arc::new(image.data().to_vec()),
+        }
+    }
+
+    /// Bitmap width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Bitmap height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Straight-alpha RGBA pixels.
+    pub fn data(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Copy the pixels into a new [`ImageData`].
+    pub fn to_image_data(&self) -> ImageData {
+        ImageData {
+            width: self.width,
+            height: self.height,
+            data: self.pixels.to_vec(),
+        }
+    }
+
+    /// Pixel at (x, y).
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8, u8)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = ((y * self.width + x) * 4) as usize;
+        let px = &self.pixels[idx..idx + 4];
+        Some((px[0], px[1], px[2], px[3]))
+    }
+}
+
 // ==================== Text Metrics ====================
 
 /// Text measurement result.
@@ -661,14 +725,72 @@ pub struct TextMetrics {
 #[derive(Debug, Clone)]
 pub enum DrawCommand {
     FillRect { x: f32, y: f32, w: f32, h: f32, style: CanvasStyle, transform: Transform2D },
-    StrokeRect { x: f32, y: f32, w: f32, h: f32, style: CanvasStyle, line_width: f32, transform: Transform2D },
-    ClearRect { x: f32, y: f32, w: f32, h: f32, transform: Transform2D },
-    FillPath { segments: Vec<Vec<(f32, f32)>>, style: CanvasStyle, transform: Transform2D },
-    StrokePath { segments: Vec<Vec<(f32, f32)>>, style: CanvasStyle, line_width: f32, transform: Transform2D },
-    FillText { text: String, x: f32, y: f32, style: CanvasStyle, font: String, transform: Transform2D },
-    StrokeText { text: String, x: f32, y: f32, style: CanvasStyle, font: String, line_width: f32, transform: Transform2D },
-    DrawImage { image_id: String, sx: f32, sy: f32, sw: f32, sh: f32, dx: f32, dy: f32, dw: f32, dh: f32, transform: Transform2D },
-    PutImageData { data: ImageData, x: i32, y: i32 },
+    /// `outline` is the stroke's fill area in user space, with joins,
+    /// caps and dashes applied.
+    StrokeRect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        style: CanvasStyle,
+        line_width: f32,
+        outline: Vec<Polygon>,
+        transform: Transform2D,
+    },
+    ClearRect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        transform: Transform2D,
+    },
+    FillPath {
+        segments: Vec<Vec<(f32, f32)>>,
+        fill_rule: FillRule,
+        style: CanvasStyle,
+        transform: Transform2D,
+    },
+    StrokePath {
+        segments: Vec<Vec<(f32, f32)>>,
+        style: CanvasStyle,
+        line_width: f32,
+        outline: Vec<Polygon>,
+        transform: Transform2D,
+    },
+    FillText {
+        text: String,
+        x: f32,
+        y: f32,
+        style: CanvasStyle,
+        font: String,
+        transform: Transform2D,
+    },
+    StrokeText {
+        text: String,
+        x: f32,
+        y: f32,
+        style: CanvasStyle,
+        font: String,
+        line_width: f32,
+        transform: Transform2D,
+    },
+    DrawImage {
+        image_id: String,
+        sx: f32,
+        sy: f32,
+        sw: f32,
+        sh: f32,
+        dx: f32,
+        dy: f32,
+        dw: f32,
+        dh: f32,
+        transform: Transform2D,
+    },
+    PutImageData {
+        data: ImageData,
+        x: i32,
+        y: i32,
+    },
 }
 
 // ==================== Canvas Rendering Context ====================
@@ -688,8 +810,12 @@ pub struct CanvasRenderingContext2D {
     path: Path2D,
     /// Recorded draw commands.
     commands: Vec<DrawCommand>,
-    /// Pixel buffer (optional, for getImageData).
-    pixel_buffer: Option<ImageData>,
+    /// Rasterized pixels for readback and export.
+    surface: RefCell<Surface>,
+    /// Number of commands already baked into `surface`.
+    rasterized: Cell<usize>,
+    /// Bitmaps that `DrawImage` commands refer to by id.
+    image_sources: HashMap<String, ImageBitmap>,
 }
 
 impl CanvasRenderingContext2D {
@@ -702,7 +828,9 @@ impl CanvasRenderingContext2D {
             state_stack: VecDeque::new(),
             path: Path2D::new(),
             commands: Vec::new(),
-            pixel_buffer: None,
+            surface: RefCell::new(Surface::new(width, height)),
+            rasterized: Cell::new(0),
+            image_sources: HashMap::new(),
         }
     }
 
@@ -726,6 +854,8 @@ impl CanvasRenderingContext2D {
         self.state_stack.clear();
         self.path = Path2D::new();
         self.commands.clear();
+        self.rasterized.set(0);
+        self.surface.borrow_mut().clear();
     }
 
     // ==================== Transform ====================
@@ -808,8 +938,15 @@ impl CanvasRenderingContext2D {
         self.state.miter_limit = limit.max(0.0);
     }
 
-    /// Set line dash.
-    pub fn set_line_dash(&mut self, segments: Vec<f32>) {
+    /// Set line dash. Lists with negative or non-finite lengths are
+    /// ignored, and odd-length lists are repeated to make them even.
+    pub fn set_line_dash(&mut self, mut segments: Vec<f32>) {
+        if segments.iter().any(|s| !s.is_finite() || *s < 0.0) {
+            return;
+        }
+        if segments.len() % 2 == 1 {
+            segments.extend_from_within(..);
+        }
         self.state.line_dash = segments;
     }
 
@@ -947,10 +1084,12 @@ impl CanvasRenderingContext2D {
 
     /// Stroke a rectangle.
     pub fn stroke_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        let corners = vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h), (x, y)];
         self.commands.push(DrawCommand::StrokeRect {
             x, y, w, h,
             style: self.state.stroke_style.clone(),
             line_width: self.state.line_width,
+            outline: stroke_outline(&[corners], &self.state.line_style()),
             transform: self.state.transform,
         });
     }
@@ -965,11 +1104,17 @@ impl CanvasRenderingContext2D {
 
     // ==================== Drawing Paths ====================
 
-    /// Fill the current path.
+    /// Fill the current path with the nonzero rule.
     pub fn fill(&mut self) {
+        self.fill_with_rule(FillRule::NonZero);
+    }
+
+    /// Fill the current path, its subpaths together, with `rule`.
+    pub fn fill_with_rule(&mut self, rule: FillRule) {
         let segments = self.path.to_line_segments();
         self.commands.push(DrawCommand::FillPath {
             segments,
+            fill_rule: rule,
             style: self.state.fill_style.clone(),
             transform: self.state.transform,
         });
@@ -978,10 +1123,12 @@ impl CanvasRenderingContext2D {
     /// Stroke the current path.
     pub fn stroke(&mut self) {
         let segments = self.path.to_line_segments();
+        let outline = stroke_outline(&segments, &self.state.line_style());
         self.commands.push(DrawCommand::StrokePath {
             segments,
             style: self.state.stroke_style.clone(),
             line_width: self.state.line_width,
+            outline,
             transform: self.state.transform,
         });
     }
@@ -1066,6 +1213,38 @@ impl CanvasRenderingContext2D {
 
     // ==================== Images ====================
 
+    /// Register `bitmap` as the source drawn for `image_id`.
+    ///
+    /// Draw commands resolve their source when rasterized, so the bitmap must
+    /// be registered before the canvas is read back or exported.
+    pub fn register_image_source(&mut self, image_id: &str, bitmap: ImageBitmap) {
+        self.image_sources.insert(image_id.to_string(), bitmap);
+    }
+
+    /// Forget the source registered for `image_id`.
+    pub fn unregister_image_source(&mut self, image_id: &str) {
+        self.image_sources.remove(image_id);
+    }
+
+    /// Move the current pixels into a bitmap and start a new, transparent
+    /// one, as `transferToImageBitmap` does. Drawing state is kept.
+    pub fn transfer_to_image_bitmap(&mut self) -> ImageBitmap {
+        let bitmap = ImageBitmap::from_image_data(self.snapshot());
+        self.clear_bitmap();
+        bitmap
+    }
+
+    /// Replace the canvas contents with `bitmap`, as
+    /// `transferFromImageBitmap` does. Drawing state is kept.
+    pub fn transfer_from_image_bitmap(&mut self, bitmap: ImageBitmap) {
+        self.clear_bitmap();
+        self.commands.push(DrawCommand::PutImageData {
+            data: bitmap.to_image_data(),
+            x: 0,
+            y: 0,
+        });
+    }
+
     /// Draw image.
     pub fn draw_image(&mut self, image_id: &str, dx: f32, dy: f32) {
         self.commands.push(DrawCommand::DrawImage {
@@ -1105,21 +1284,21 @@ impl CanvasRenderingContext2D {
 
     /// Get image data from canvas.
     pub fn get_image_data(&self, x: i32, y: i32, width: u32, height: u32) -> ImageData {
-        // Return from pixel buffer if available
-        if let Some(ref buffer) = self.pixel_buffer {
-            let mut data = ImageData::new(width, height);
-            for dy in 0..height {
-                for dx in 0..width {
-                    let sx = (x + dx as i32) as u32;
-                    let sy = (y + dy as i32) as u32;
-                    if let Some((r, g, b, a)) = buffer.get_pixel(sx, sy) {
-                        data.set_pixel(dx, dy, r, g, b, a);
-                    }
+        let buffer = self.snapshot();
+        let mut data = ImageData::new(width, height);
+        for dy in 0..height {
+            for dx in 0..width {
+                let sx = x + dx as i32;
+                let sy = y + dy as i32;
+                if sx < 0 || sy < 0 {
+                    continue;
+                }
+                if let Some((r, g, b, a)) = buffer.get_pixel(sx as u32, sy as u32) {
+                    data.set_pixel(dx, dy, r, g, b, a);
                 }
             }
-            return data;
         }
-        ImageData::new(width, height)
+        data
     }
 
     /// Put image data to canvas.
@@ -1136,13 +1315,167 @@ impl CanvasRenderingContext2D {
 
     /// Take recorded draw commands.
     pub fn take_commands(&mut self) -> Vec<DrawCommand> {
+        self.rasterize();
+        self.rasterized.set(0);
         std::mem::take(&mut self.commands)
     }
 
     /// Clear commands.
     pub fn clear_commands(&mut self) {
+        self.rasterize();
+        self.rasterized.set(0);
         self.commands.clear();
     }
+
+    // ==================== Export ====================
+
+    /// Current canvas pixels as straight-alpha RGBA.
+    pub fn snapshot(&self) -> ImageData {
+        self.rasterize();
+        self.surface.borrow().to_image_data()
+    }
+
+    /// Encode the canvas contents.
+    ///
+    /// `quality` only applies to JPEG and is clamped to 0..=1 (default 0.92).
+    /// JPEG has no alpha channel, so the canvas is flattened onto white.
+    pub fn encode(
+        &self,
+        format: ExportFormat,
+        quality: Option<f32>,
+    ) -> Result<Vec<u8>, CanvasError> {
+        self.rasterize();
+        let surface = self.surface.borrow();
+        let result = match format {
+            ExportFormat::Png => {
+                let pixels = surface.to_image_data();
+                rustkit_codecs::RgbaImage::from_rgba8(self.width, self.height, pixels.data)
+                    .and_then(|image| rustkit_codecs::encode_png(&image))
+            }
+            ExportFormat::Jpeg => {
+                let quality = quality.filter(|q| (0.0..=1.0).contains(q)).unwrap_or(0.92);
+                rustkit_codecs::RgbaImage::from_rgba8(
+                    self.width,
+                    self.height,
+                    surface.to_opaque_rgba(),
+                )
+                .and_then(|image| {
+                    rustkit_codecs::encode_jpeg(&image, (quality * 100.0).round().max(1.0) as u8)
+                })
+            }
+        };
+        result.map_err(|e| CanvasError::Encode(e.to_string()))
+    }
+
+    /// Serialize the canvas as a `data:` URL, as `toDataURL` does.
+    ///
+    /// Unsupported or missing MIME types fall back to PNG. An empty canvas
+    /// yields `"data:,"`.
+    pub fn to_data_url(
+        &self,
+        mime: Option<&str>,
+        quality: Option<f32>,
+    ) -> Result<String, CanvasError> {
+        if self.width == 0 || self.height == 0 {
+            return Ok("data:,".to_string());
+        }
+        let format = mime
+            .and_then(ExportFormat::from_mime)
+            .unwrap_or(ExportFormat::Png);
+        let bytes = self.encode(format, quality)?;
+        Ok(format!(
+            "data:{};base64,{}",
+            format.mime(),
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        ))
+    }
+
+    /// Drop every recorded command and clear the pixels to transparent black.
+    fn clear_bitmap(&mut self) {
+        self.commands.clear();
+        self.rasterized.set(0);
+        self.surface.borrow_mut().clear();
+    }
+
+    /// Bake any commands recorded since the last readback into the surface.
+    fn rasterize(&self) {
+        let start = self.rasterized.get();
+        if start >= self.commands.len() {
+            return;
+        }
+        let mut surface = self.surface.borrow_mut();
+        for command in &self.commands[start..] {
+            surface.apply(command, &self.image_sources);
+        }
+        self.rasterized.set(self.commands.len());
+    }
+}
+
+// ==================== OffscreenCanvas ====================
+
+/// A canvas that is not attached to a DOM node.
+///
+/// Frames are drawn through [`OffscreenCanvas::context`] and handed to an
+/// on-screen canvas with [`OffscreenCanvas::transfer_to_image_bitmap`].
+#[derive(Debug)]
+pub struct OffscreenCanvas {
+    context: CanvasRenderingContext2D,
+}
+
+impl OffscreenCanvas {
+    /// Create an offscreen canvas with a transparent bitmap.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            context: CanvasRenderingContext2D::new(width, height),
+        }
+    }
+
+    /// Canvas width.
+    pub fn width(&self) -> u32 {
+        self.context.width
+    }
+
+    /// Canvas height.
+    pub fn height(&self) -> u32 {
+        self.context.height
+    }
+
+    /// The 2D context drawing into this canvas.
+    pub fn context(&mut self) -> &mut CanvasRenderingContext2D {
+        &mut self.context
+    }
+
+    /// Move the current pixels into a bitmap and start a new, transparent
+    /// one. Drawing state such as the transform and styles is kept.
+    pub fn transfer_to_image_bitmap(&mut self) -> ImageBitmap {
+        self.context.transfer_to_image_bitmap()
+    }
+}
+
+/// Image formats supported by canvas export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Png,
+    Jpeg,
+}
+
+impl ExportFormat {
+    /// Resolve an export MIME type, ignoring case.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime.trim().to_ascii_lowercase().as_str() {
+            "image/png" => Some(ExportFormat::Png),
+            "image/jpeg" | "image/jpg" => Some(ExportFormat::Jpeg),
+            _ => None,
+        }
+    }
+
+    /// The MIME type written into data URLs and blobs.
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "image/png",
+            ExportFormat::Jpeg => "image/jpeg",
+        }
+    }
 }
 
 // ==================== Helper Functions ====================
@@ -1230,6 +1563,33 @@ fn parse_font_size(font: &str) -> Option<f32> {
     None
 }
 
+/// Sample a sorted list of gradient stops.
+fn sample_stops(stops: &[ColorStop], t: f32) -> Color {
+    if stops.is_empty() {
+        return Color::TRANSPARENT;
+    }
+    if stops.len() == 1 {
+        return stops[0].color;
+    }
+
+    let t = t.clamp(0.0, 1.0);
+
+    // Find surrounding stops
+    for i in 0..stops.len() - 1 {
+        if t >= stops[i].offset && t <= stops[i + 1].offset {
+            let range = stops[i + 1].offset - stops[i].offset;
+            let local_t = if range > 0.0 {
+                (t - stops[i].offset) / range
+            } else {
+                0.0
+            };
+            return interpolate_color(&stops[i].color, &stops[i + 1].color, local_t);
+        }
+    }
+
+    stops.last().map(|s| s.color).unwrap_or(Color::TRANSPARENT)
+}
+
 /// Interpolate between two colors.
 fn interpolate_color(a: &Color, b: &Color, t: f32) -> Color {
     let lerp = |a: u8, b: u8, t: f32| -> u8 {
@@ -1414,5 +1774,159 @@ mod tests {
         // Point outside
         assert!(!ctx.is_point_in_path(5.0, 5.0));
     }
-}
+    #[test]
+    fn test_png_export_preserves_straight_alpha() {
+        let mut ctx = CanvasRenderingContext2D::new(4, 4);
+        ctx.set_fill_style_color("rgba(255, 0, 0, 0.5)");
+        ctx.fill_rect(0.0, 0.0, 4.0, 4.0);
+
+        let png = ctx.encode(ExportFormat::Png, None).unwrap();
+        let decoded = rustkit_codecs::decode_png(&png).unwrap();
+        let px = &decoded.data()[..4];
+        assert_eq!((px[0], px[1], px[2]), (255, 0, 0));
+        assert!((px[3] as i32 - 128).abs() <= 1);
+    }
+
+    #[test]
+    fn test_fill_rule_cuts_holes_across_subpaths() {
+        let mut ctx = CanvasRenderingContext2D::new(12, 12);
+        ctx.set_fill_style_color("#ff0000");
+        ctx.rect(0.0, 0.0, 12.0, 12.0);
+        ctx.rect(4.0, 4.0, 4.0, 4.0);
+        ctx.fill();
+        assert_eq!(
+            ctx.get_image_data(5, 5, 1, 1).get_pixel(0, 0),
+            Some((255, 0, 0, 255))
+        );
+
+        ctx.clear_rect(0.0, 0.0, 12.0, 12.0);
+        ctx.fill_with_rule(FillRule::EvenOdd);
+        assert_eq!(
+            ctx.get_image_data(5, 5, 1, 1).get_pixel(0, 0),
+            Some((0, 0, 0, 0))
+        );
+        assert_eq!(
+            ctx.get_image_data(1, 1, 1, 1).get_pixel(0, 0),
+            Some((255, 0, 0, 255))
+        );
+    }
+
+    #[test]
+    fn test_export_reflects_put_image_data() {
+        let mut ctx = CanvasRenderingContext2D::new(8, 8);
+        ctx.set_fill_style_color("#0000ff");
+        ctx.fill_rect(0.0, 0.0, 8.0, 8.0);
+        let mut patch = ImageData::new(2, 2);
+        for y in 0..2 {
+            for x in 0..2 {
+                patch.set_pixel(x, y, 0, 255, 0, 255);
+            }
+        }
+        ctx.put_image_data(patch, 3, 3);
+
+        let snapshot = ctx.snapshot();
+        assert_eq!(snapshot.get_pixel(3, 3), Some((0, 255, 0, 255)));
+        assert_eq!(snapshot.get_pixel(0, 0), Some((0, 0, 255, 255)));
+        assert_eq!(
+            ctx.get_image_data(3, 3, 1, 1).get_pixel(0, 0),
+            Some((0, 255, 0, 255))
+        );
+
+        // JPEG has no alpha, so cleared pixels flatten onto white.
+        ctx.clear_rect(0.0, 0.0, 2.0, 2.0);
+        let jpeg = ctx.encode(ExportFormat::Jpeg, Some(1.0)).unwrap();
+        let decoded = rustkit_codecs::decode_jpeg(&jpeg).unwrap();
+        assert!(decoded.data()[..3].iter().all(|c| *c > 240));
+    }
+
+    #[test]
+    fn test_data_url_fallback() {
+        let mut ctx = CanvasRenderingContext2D::new(2, 2);
+        ctx.fill_rect(0.0, 0.0, 2.0, 2.0);
+        assert!(ctx
+            .to_data_url(None, None)
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+        assert!(ctx
+            .to_data_url(Some("image/webp"), None)
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+        assert!(ctx
+            .to_data_url(Some("image/jpeg"), Some(0.5))
+            .unwrap()
+            .starts_with("data:image/jpeg;base64,"));
+        assert_eq!(
+            CanvasRenderingContext2D::new(0, 5)
+                .to_data_url(None, None)
+                .unwrap(),
+            "data:,"
+        );
+    }
+
+    fn draw_scene(ctx: &mut CanvasRenderingContext2D) {
+        ctx.set_fill_style_color("#3366cc");
+        ctx.fill_rect(1.0, 1.0, 6.0, 4.0);
+        ctx.set_fill_style_color("rgba(255, 0, 0, 0.5)");
+        ctx.begin_path();
+        ctx.arc(5.0, 5.0, 3.0, 0.0, 2.0 * PI, false);
+        ctx.fill();
+    }
+
+    #[test]
+    fn test_offscreen_transfer_matches_direct_drawing() {
+        let mut direct = CanvasRenderingContext2D::new(10, 8);
+        draw_scene(&mut direct);
+
+        let mut offscreen = OffscreenCanvas::new(10, 8);
+        draw_scene(offscreen.context());
+        let bitmap = offscreen.transfer_to_image_bitmap();
+        // The offscreen bitmap starts over after a transfer
+        assert!(offscreen.context().snapshot().data.iter().all(|c| *c == 0));
 
+        let mut onscreen = CanvasRenderingContext2D::new(10, 8);
+        onscreen.fill_rect(0.0, 0.0, 10.0, 8.0);
+        onscreen.transfer_from_image_bitmap(bitmap.clone());
+        assert_eq!(onscreen.snapshot().data, direct.snapshot().data);
+
+        // drawImage resolves the bitmap through the image-source registry
+        let mut drawn = CanvasRenderingContext2D::new(10, 8);
+        drawn.register_image_source("frame", bitmap);
+        drawn.draw_image("frame", 0.0, 0.0);
+        assert_eq!(drawn.snapshot().data, direct.snapshot().data);
+    }
+
+    #[test]
+    fn test_image_bitmap_outlives_source() {
+        let bitmap = {
+            let mut offscreen = OffscreenCanvas::new(4, 4);
+            offscreen.context().set_fill_style_color("#00ff00");
+            offscreen.context().fill_rect(0.0, 0.0, 2.0, 2.0);
+            offscreen.transfer_to_image_bitmap()
+        };
+        assert_eq!((bitmap.width(), bitmap.height()), (4, 4));
+        assert_eq!(bitmap.get_pixel(1, 1), Some((0, 255, 0, 255)));
+        assert_eq!(bitmap.get_pixel(3, 3), Some((0, 0, 0, 0)));
+
+        // Scaled draw of the top-left quadrant fills the whole canvas
+        let mut ctx = CanvasRenderingContext2D::new(4, 4);
+        ctx.register_image_source("sprite", bitmap);
+        ctx.draw_image_full("sprite", 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 4.0, 4.0);
+        assert_eq!(ctx.snapshot().get_pixel(3, 3), Some((0, 255, 0, 255)));
+    }
+
+    #[tokio::test]
+    async fn test_data_url_round_trip() {
+        let mut ctx = CanvasRenderingContext2D::new(6, 3);
+        ctx.set_fill_style_color("#ff8000");
+        ctx.fill_rect(0.0, 0.0, 3.0, 3.0);
+
+        let url = url::Url::parse(&ctx.to_data_url(None, None).unwrap()).unwrap();
+        let loaded = rustkit_image::ImageManager::new().load(url).await.unwrap();
+        assert_eq!((loaded.natural_width, loaded.natural_height), (6, 3));
+        let rustkit_image::ImageData::Static(image) = &loaded.data else {
+            panic!("expected a static image");
+        };
+        assert_eq!(&image.data()[..4], &[255, 128, 0, 255]);
+        assert_eq!(&image.data()[20..24], &[0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,436 @@
+//! Software rasterizer backing canvas pixel readback and export.
+//!
+//! Recorded draw commands are baked into a premultiplied RGBA surface so that
+//! `getImageData`, `toDataURL` and `toBlob` observe everything drawn so far.
+//! Geometry is covered with 4x4 supersampling; paths fill by their fill
+//! rule and everything else by the nonzero winding rule.
+
+use crate::fill::FillRegion;
+use crate::{CanvasStyle, DrawCommand, ImageBitmap, ImageData, Transform2D};
+use rustkit_css::{Color, FillRule};
+use std::collections::HashMap;
+
+/// Samples per pixel along each axis.
+const SUBSAMPLES: usize = 4;
+
+type Polygon = Vec<(f32, f32)>;
+
+/// Premultiplied RGBA surface, one `[r, g, b, a]` in 0..=1 per pixel.
+#[derive(Debug, Clone)]
+pub(crate) struct Surface {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Surface {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width as usize * height as usize],
+        }
+    }
+
+    /// Reset every pixel to transparent black.
+    pub(crate) fn clear(&mut self) {
+        self.pixels.fill([0.0; 4]);
+    }
+
+    /// Rasterize a single draw command, resolving images from `sources`.
+    pub(crate) fn apply(&mut self, command: &DrawCommand, sources: &HashMap<String, ImageBitmap>) {
+        match command {
+            DrawCommand::FillRect {
+                x,
+                y,
+                w,
+                h,
+                style,
+                transform,
+            } => {
+                let rect = transform_polygon(&rect_polygon(*x, *y, *w, *h), transform);
+                self.fill(&[rect], FillRule::NonZero, style, transform);
+            }
+            DrawCommand::StrokeRect {
+                style,
+                outline,
+                transform,
+                ..
+            } => {
+                let outline: Vec<Polygon> = outline
+                    .iter()
+                    .map(|p| transform_polygon(p, transform))
+                    .collect();
+                self.fill(&outline, FillRule::NonZero, style, transform);
+            }
+            DrawCommand::ClearRect {
+                x,
+                y,
+                w,
+                h,
+                transform,
+            } => {
+                let rect = transform_polygon(&rect_polygon(*x, *y, *w, *h), transform);
+                self.clear_coverage(&[rect]);
+            }
+            DrawCommand::FillPath {
+                segments,
+                fill_rule,
+                style,
+                transform,
+            } => {
+                let polygons: Vec<Polygon> = segments
+                    .iter()
+                    .map(|p| transform_polygon(p, transform))
+                    .collect();
+                self.fill(&polygons, *fill_rule, style, transform);
+            }
+            DrawCommand::StrokePath {
+                style,
+                outline,
+                transform,
+                ..
+            } => {
+                let outline: Vec<Polygon> = outline
+                    .iter()
+                    .map(|p| transform_polygon(p, transform))
+                    .collect();
+                self.fill(&outline, FillRule::NonZero, style, transform);
+            }
+            DrawCommand::PutImageData { data, x, y } => self.put(data, *x, *y),
+            DrawCommand::DrawImage {
+                image_id,
+                sx,
+                sy,
+                sw,
+                sh,
+                dx,
+                dy,
+                dw,
+                dh,
+                transform,
+            } => {
+                // Unregistered images are left to the compositor
+                if let Some(bitmap) = sources.get(image_id) {
+                    let (sw, sh) = if *sw == 0.0 && *sh == 0.0 {
+                        (bitmap.width() as f32, bitmap.height() as f32)
+                    } else {
+                        (*sw, *sh)
+                    };
+                    let (dw, dh) = if *dw == 0.0 && *dh == 0.0 {
+                        (sw, sh)
+                    } else {
+                        (*dw, *dh)
+                    };
+                    self.draw_bitmap(bitmap, [*sx, *sy, sw, sh], [*dx, *dy, dw, dh], transform);
+                }
+            }
+            // Glyphs are not available to the rasterizer; text is still
+            // recorded for the compositor but does not reach the surface.
+            DrawCommand::FillText { .. } | DrawCommand::StrokeText { .. } => {}
+        }
+    }
+
+    /// Read back the surface as straight (non-premultiplied) RGBA8.
+    pub(crate) fn to_image_data(&self) -> ImageData {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for px in &self.pixels {
+            let a = px[3];
+            if a <= 0.0 {
+                data.extend_from_slice(&[0, 0, 0, 0]);
+                continue;
+            }
+            for c in &px[..3] {
+                data.push(to_u8(c / a));
+            }
+            data.push(to_u8(a));
+        }
+        ImageData {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /// Read back the surface composited over an opaque white background.
+    pub(crate) fn to_opaque_rgba(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for px in &self.pixels {
+            let backdrop = 1.0 - px[3];
+            for c in &px[..3] {
+                data.push(to_u8(c + backdrop));
+            }
+            data.push(255);
+        }
+        data
+    }
+
+    fn fill(
+        &mut self,
+        polygons: &[Polygon],
+        rule: FillRule,
+        style: &CanvasStyle,
+        transform: &Transform2D,
+    ) {
+        let inverse = transform.inverse();
+        let Some(coverage) = Coverage::compute(polygons, rule, self.width, self.height) else {
+            return;
+        };
+        let width = self.width as usize;
+        for (row, y) in coverage.rows() {
+            for (x, &cov) in row.iter().enumerate() {
+                if cov <= 0.0 {
+                    continue;
+                }
+                let x = coverage.x0 + x;
+                let Some(src) = paint(style, inverse.as_ref(), x as f32 + 0.5, y as f32 + 0.5)
+                else {
+                    continue;
+                };
+                let dst = &mut self.pixels[y * width + x];
+                let keep = 1.0 - src[3] * cov;
+                for i in 0..4 {
+                    dst[i] = src[i] * cov + dst[i] * keep;
+                }
+            }
+        }
+    }
+
+    /// Composite the `src` rectangle of `bitmap` into the user-space `dst`
+    /// rectangle, sampling the nearest source pixel.
+    fn draw_bitmap(
+        &mut self,
+        bitmap: &ImageBitmap,
+        src: [f32; 4],
+        dst: [f32; 4],
+        transform: &Transform2D,
+    ) {
+        let [sx, sy, sw, sh] = src;
+        let [dx, dy, dw, dh] = dst;
+        if sw <= 0.0 || sh <= 0.0 || dw <= 0.0 || dh <= 0.0 {
+            return;
+        }
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        let rect = transform_polygon(&rect_polygon(dx, dy, dw, dh), transform);
+        let Some(coverage) = Coverage::compute(&[rect], FillRule::NonZero, self.width, self.height)
+        else {
+            return;
+        };
+        let width = self.width as usize;
+        for (row, y) in coverage.rows() {
+            for (x, &cov) in row.iter().enumerate() {
+                if cov <= 0.0 {
+                    continue;
+                }
+                let x = coverage.x0 + x;
+                let (ux, uy) = inverse.apply(x as f32 + 0.5, y as f32 + 0.5);
+                let bx = sx + (ux - dx) / dw * sw;
+                let by = sy + (uy - dy) / dh * sh;
+                if bx < 0.0 || by < 0.0 {
+                    continue;
+                }
+                let Some((r, g, b, a)) = bitmap.get_pixel(bx as u32, by as u32) else {
+                    continue;
+                };
+                let a = a as f32 / 255.0 * cov;
+                let dst = &mut self.pixels[y * width + x];
+                let keep = 1.0 - a;
+                for (i, c) in [r, g, b].into_iter().enumerate() {
+                    dst[i] = c as f32 / 255.0 * a + dst[i] * keep;
+                }
+                dst[3] = a + dst[3] * keep;
+            }
+        }
+    }
+
+    fn clear_coverage(&mut self, polygons: &[Polygon]) {
+        let Some(coverage) =
+            Coverage::compute(polygons, FillRule::NonZero, self.width, self.height)
+        else {
+            return;
+        };
+        let width = self.width as usize;
+        for (row, y) in coverage.rows() {
+            for (x, &cov) in row.iter().enumerate() {
+                let dst = &mut self.pixels[y * width + coverage.x0 + x];
+                for c in dst.iter_mut() {
+                    *c *= 1.0 - cov;
+                }
+            }
+        }
+    }
+
+    fn put(&mut self, data: &ImageData, x: i32, y: i32) {
+        for sy in 0..data.height {
+            let dy = y + sy as i32;
+            if dy < 0 || dy >= self.height as i32 {
+                continue;
+            }
+            for sx in 0..data.width {
+                let dx = x + sx as i32;
+                if dx < 0 || dx >= self.width as i32 {
+                    continue;
+                }
+                let Some((r, g, b, a)) = data.get_pixel(sx, sy) else {
+                    continue;
+                };
+                let a = a as f32 / 255.0;
+                self.pixels[dy as usize * self.width as usize + dx as usize] = [
+                    r as f32 / 255.0 * a,
+                    g as f32 / 255.0 * a,
+                    b as f32 / 255.0 * a,
+                    a,
+                ];
+            }
+        }
+    }
+}
+
+/// Per-pixel coverage for the bounding rows of a polygon set.
+struct Coverage {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    fn compute(polygons: &[Polygon], rule: FillRule, width: u32, height: u32) -> Option<Self> {
+        let mut region = FillRegion::new(polygons, rule);
+        let [min_x, min_y, max_x, max_y] = region.bounds()?;
+
+        let x0 = min_x.floor().max(0.0) as usize;
+        let x1 = (max_x.ceil().max(0.0) as usize).min(width as usize);
+        let y0 = min_y.floor().max(0.0) as usize;
+        let y1 = (max_y.ceil().max(0.0) as usize).min(height as usize);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let span = x1 - x0;
+        let mut values = vec![0.0; span * (y1 - y0)];
+        let weight = 1.0 / (SUBSAMPLES * SUBSAMPLES) as f32;
+        let mut spans = Vec::new();
+
+        for y in y0..y1 {
+            let row = &mut values[(y - y0) * span..(y - y0 + 1) * span];
+            for sub in 0..SUBSAMPLES {
+                let sy = y as f32 + (sub as f32 + 0.5) / SUBSAMPLES as f32;
+                region.spans_at(sy, &mut spans);
+                for &(left, right) in &spans {
+                    let (start, end) = (region.x_at(left, sy), region.x_at(right, sy));
+                    accumulate_span(row, x0, start, end, weight);
+                }
+            }
+        }
+
+        Some(Self {
+            x0,
+            y0,
+            width: span,
+            values,
+        })
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (&[f32], usize)> {
+        self.values
+            .chunks(self.width)
+            .enumerate()
+            .map(move |(i, row)| (row, self.y0 + i))
+    }
+}
+
+/// Add coverage for the subsamples of `row` that fall inside `[start, end)`.
+fn accumulate_span(row: &mut [f32], x0: usize, start: f32, end: f32, weight: f32) {
+    let first = (start.floor().max(x0 as f32) as usize).saturating_sub(x0);
+    let last = ((end.ceil().max(0.0) as usize).saturating_sub(x0)).min(row.len());
+    for (i, cov) in row.iter_mut().enumerate().take(last).skip(first) {
+        let px = (x0 + i) as f32;
+        let inside = (0..SUBSAMPLES)
+            .filter(|s| {
+                let sx = px + (*s as f32 + 0.5) / SUBSAMPLES as f32;
+                sx >= start && sx < end
+            })
+            .count();
+        *cov = (*cov + inside as f32 * weight).min(1.0);
+    }
+}
+
+/// Resolve the premultiplied source color of `style` at a device-space point.
+fn paint(style: &CanvasStyle, inverse: Option<&Transform2D>, x: f32, y: f32) -> Option<[f32; 4]> {
+    let color = match style {
+        CanvasStyle::Color(color) => *color,
+        CanvasStyle::LinearGradient(gradient) => {
+            let (ux, uy) = inverse?.apply(x, y);
+            let (dx, dy) = (gradient.x1 - gradient.x0, gradient.y1 - gradient.y0);
+            let len = dx * dx + dy * dy;
+            if len == 0.0 {
+                return None;
+            }
+            gradient.sample(((ux - gradient.x0) * dx + (uy - gradient.y0) * dy) / len)
+        }
+        CanvasStyle::RadialGradient(gradient) => {
+            let (ux, uy) = inverse?.apply(x, y);
+            gradient.sample(radial_offset(gradient, ux, uy)?)
+        }
+        // Pattern images are not resolved by the rasterizer.
+        CanvasStyle::Pattern(_) => return None,
+    };
+    Some(premultiply(color))
+}
+
+/// Solve the two-circle gradient equation for the largest valid offset.
+fn radial_offset(g: &crate::RadialGradient, x: f32, y: f32) -> Option<f32> {
+    let (cdx, cdy, dr) = (g.x1 - g.x0, g.y1 - g.y0, g.r1 - g.r0);
+    let (pdx, pdy) = (x - g.x0, y - g.y0);
+    let a = cdx * cdx + cdy * cdy - dr * dr;
+    let b = pdx * cdx + pdy * cdy + g.r0 * dr;
+    let c = pdx * pdx + pdy * pdy - g.r0 * g.r0;
+    let valid = |t: f32| g.r0 + t * dr >= 0.0;
+
+    if a.abs() < f32::EPSILON {
+        if b == 0.0 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return valid(t).then_some(t);
+    }
+    let disc = b * b - a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let root = disc.sqrt();
+    let (t1, t2) = ((b + root) / a, (b - root) / a);
+    let (hi, lo) = if t1 > t2 { (t1, t2) } else { (t2, t1) };
+    if valid(hi) {
+        Some(hi)
+    } else if valid(lo) {
+        Some(lo)
+    } else {
+        None
+    }
+}
+
+fn premultiply(color: Color) -> [f32; 4] {
+    let a = color.a.clamp(0.0, 1.0);
+    [
+        color.r as f32 / 255.0 * a,
+        color.g as f32 / 255.0 * a,
+        color.b as f32 / 255.0 * a,
+        a,
+    ]
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn rect_polygon(x: f32, y: f32, w: f32, h: f32) -> Polygon {
+    vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h), (x, y)]
+}
+
+fn transform_polygon(points: &[(f32, f32)], transform: &Transform2D) -> Polygon {
+    points.iter().map(|&(x, y)| transform.apply(x, y)).collect()
+}
@@ -0,0 +1,425 @@
+//! Stroke geometry: dashing, line joins and line caps.
+//!
+//! A stroke is turned into polygons filled with the nonzero rule: one quad
+//! per segment plus a polygon per join and cap. Every polygon has the same
+//! orientation, so overlapping pieces add up instead of cancelling out.
+//! Dashing splits a polyline first, so each dash gets its own caps.
+
+use crate::{CanvasState, LineCap, LineJoin};
+use std::f32::consts::PI;
+
+type Point = (f32, f32);
+
+/// A closed polygon outline.
+pub type Polygon = Vec<Point>;
+
+/// Largest distance allowed between an arc and its approximating chords.
+const ARC_TOLERANCE: f32 = 0.1;
+
+/// How lines are stroked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineStyle {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths; empty for a solid line.
+    pub dash: Vec<f32>,
+    pub dash_offset: f32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        CanvasState::default().line_style()
+    }
+}
+
+impl CanvasState {
+    /// The line settings strokes use.
+    pub fn line_style(&self) -> LineStyle {
+        LineStyle {
+            width: self.line_width,
+            cap: self.line_cap,
+            join: self.line_join,
+            miter_limit: self.miter_limit,
+            dash: self.line_dash.clone(),
+            dash_offset: self.line_dash_offset,
+        }
+    }
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    (a.0 * s, a.1 * s)
+}
+
+fn cross(a: Point, b: Point) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn length(a: Point) -> f32 {
+    a.0.hypot(a.1)
+}
+
+fn unit(a: Point) -> Point {
+    scale(a, 1.0 / length(a))
+}
+
+/// Left-hand normal of the unit direction `d`, `half` long.
+fn normal(d: Point, half: f32) -> Point {
+    (-d.1 * half, d.0 * half)
+}
+
+/// Split a polyline into its dashes. `pattern` alternates dash and gap
+/// lengths and `offset` is how far into the pattern the line starts. A
+/// pattern without positive length leaves the line whole.
+pub fn dash_polyline(line: &[Point], pattern: &[f32], offset: f32) -> Vec<Vec<Point>> {
+    let total: f32 = pattern.iter().sum();
+    if line.is_empty()
+        || pattern
+            .iter()
+            .any(|length| !length.is_finite() || *length < 0.0)
+        || total <= 0.0
+        || !total.is_finite()
+    {
+        return vec![line.to_vec()];
+    }
+
+    // Find where in the pattern the line starts
+    let mut index = 0;
+    let mut phase = offset.rem_euclid(total);
+    for _ in 0..pattern.len() {
+        if phase < pattern[index] {
+            break;
+        }
+        phase -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut remaining = (pattern[index] - phase).max(0.0);
+    let mut on = index % 2 == 0;
+
+    let mut dashes = Vec::new();
+    let mut current = if on { vec![line[0]] } else { Vec::new() };
+    for pair in line.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let segment = length(sub(b, a));
+        let mut travelled = 0.0;
+        while segment - travelled > remaining {
+            travelled += remaining;
+            let point = add(a, scale(sub(b, a), travelled / segment));
+            current.push(point);
+            if on {
+                dashes.push(std::mem::take(&mut current));
+            }
+            on = !on;
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= segment - travelled;
+        if on {
+            current.push(b);
+        }
+    }
+    if on && current.len() >= 2 {
+        dashes.push(current);
+    }
+    dashes
+}
+
+/// Outline of the strokes along `polylines` as polygons to fill. A
+/// polyline whose last point repeats its first is closed and joined all
+/// the way round; open ones get caps.
+pub fn stroke_outline(polylines: &[Vec<Point>], style: &LineStyle) -> Vec<Polygon> {
+    let half = style.width / 2.0;
+    let mut out = Vec::new();
+    if half <= 0.0 || !half.is_finite() {
+        return out;
+    }
+    let dashed = style.dash.iter().any(|length| *length > 0.0);
+
+    for line in polylines {
+        if dashed {
+            for dash in dash_polyline(line, &style.dash, style.dash_offset) {
+                stroke_piece(&dash, false, style, half, &mut out);
+            }
+        } else {
+            let closed = line.len() > 2 && line.first() == line.last();
+            stroke_piece(line, closed, style, half, &mut out);
+        }
+    }
+    out
+}
+
+fn stroke_piece(
+    line: &[Point],
+    closed: bool,
+    style: &LineStyle,
+    half: f32,
+    out: &mut Vec<Polygon>,
+) {
+    let mut points: Vec<Point> = Vec::with_capacity(line.len());
+    for &point in line {
+        if points.last() != Some(&point) {
+            points.push(point);
+        }
+    }
+    let Some(&first) = points.first() else {
+        return;
+    };
+
+    // A zero-length subpath only shows as its caps
+    if points.len() == 1 {
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Round => out.push(oriented(arc(first, half, 0.0, 2.0 * PI))),
+            LineCap::Square => out.push(oriented(vec![
+                (first.0 - half, first.1 - half),
+                (first.0 + half, first.1 - half),
+                (first.0 + half, first.1 + half),
+                (first.0 - half, first.1 + half),
+            ])),
+        }
+        return;
+    }
+
+    let directions: Vec<Point> = points
+        .windows(2)
+        .map(|pair| unit(sub(pair[1], pair[0])))
+        .collect();
+    for (pair, &d) in points.windows(2).zip(&directions) {
+        let n = normal(d, half);
+        out.push(oriented(vec![
+            add(pair[0], n),
+            add(pair[1], n),
+            sub(pair[1], n),
+            sub(pair[0], n),
+        ]));
+    }
+
+    if closed && points.len() > 2 {
+        // The closing vertex joins the last segment to the first
+        let count = directions.len();
+        for (i, &vertex) in points[..count].iter().enumerate() {
+            let incoming = directions[(i + count - 1) % count];
+            out.extend(join(vertex, incoming, directions[i], half, style));
+        }
+        return;
+    }
+    for (i, &vertex) in points[1..points.len() - 1].iter().enumerate() {
+        out.extend(join(vertex, directions[i], directions[i + 1], half, style));
+    }
+    let last = *points.last().unwrap();
+    out.extend(cap(first, scale(directions[0], -1.0), half, style.cap));
+    out.extend(cap(last, directions[directions.len() - 1], half, style.cap));
+}
+
+/// The polygon filling the outside of the turn at `vertex` from direction
+/// `d0` to `d1`, if the turn leaves a gap.
+fn join(vertex: Point, d0: Point, d1: Point, half: f32, style: &LineStyle) -> Option<Polygon> {
+    let turn = cross(d0, d1);
+    let cos_turn = dot(d0, d1);
+    if turn.abs() < 1e-6 && cos_turn > 0.0 {
+        return None;
+    }
+    // The gap opens on the side the path turns away from
+    let side = if turn > 0.0 { -half } else { half };
+    let (n0, n1) = (normal(d0, side), normal(d1, side));
+    let (a, b) = (add(vertex, n0), add(vertex, n1));
+    let bevel = vec![vertex, a, b];
+
+    let polygon = match style.join {
+        LineJoin::Bevel => bevel,
+        LineJoin::Round => {
+            let start = n0.1.atan2(n0.0);
+            let sweep = cross(n0, n1).atan2(dot(n0, n1));
+            let mut fan = vec![vertex];
+            fan.extend(arc(vertex, half, start, sweep));
+            fan
+        }
+        LineJoin::Miter => {
+            // Miter length over line width is 1 / sin(θ/2) for the angle θ
+            // between the segments
+            let sin_half_angle = ((1.0 + cos_turn) / 2.0).max(0.0).sqrt();
+            let ratio = 1.0 / sin_half_angle;
+            if sin_half_angle <= f32::EPSILON || ratio > style.miter_limit {
+                bevel
+            } else {
+                let tip = add(vertex, scale(unit(add(n0, n1)), half * ratio));
+                vec![vertex, a, tip, b]
+            }
+        }
+    };
+    Some(oriented(polygon))
+}
+
+/// The cap at the `end` of a line leaving in the unit direction `d`.
+fn cap(end: Point, d: Point, half: f32, style: LineCap) -> Option<Polygon> {
+    let n = normal(d, half);
+    let polygon = match style {
+        LineCap::Butt => return None,
+        LineCap::Square => {
+            let out = scale(d, half);
+            vec![
+                add(end, n),
+                add(add(end, n), out),
+                add(sub(end, n), out),
+                sub(end, n),
+            ]
+        }
+        LineCap::Round => {
+            let start = n.1.atan2(n.0);
+            let sweep = if cross(n, d) < 0.0 { -PI } else { PI };
+            arc(end, half, start, sweep)
+        }
+    };
+    Some(oriented(polygon))
+}
+
+/// Points along an arc of `radius` around `center`, from angle `start`
+/// through `sweep` radians, ends included.
+fn arc(center: Point, radius: f32, start: f32, sweep: f32) -> Vec<Point> {
+    let step = if radius > ARC_TOLERANCE {
+        2.0 * (1.0 - ARC_TOLERANCE / radius).acos()
+    } else {
+        PI / 4.0
+    };
+    let steps = ((sweep.abs() / step).ceil() as usize).clamp(2, 256);
+    (0..=steps)
+        .map(|i| {
+            let angle = start + sweep * i as f32 / steps as f32;
+            (
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+/// Normalize winding so overlapping stroke pieces never cancel out.
+pub(crate) fn oriented(mut polygon: Polygon) -> Polygon {
+    let area: f32 = (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if area > 0.0 {
+        polygon.reverse();
+    }
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(join: LineJoin) -> LineStyle {
+        LineStyle {
+            width: 10.0,
+            join,
+            ..Default::default()
+        }
+    }
+
+    /// Going right then down, so the outer corner is at the top right.
+    fn right_angle() -> Vec<Vec<Point>> {
+        vec![vec![(10.0, 50.0), (50.0, 50.0), (50.0, 90.0)]]
+    }
+
+    fn vertices(outline: &[Polygon]) -> impl Iterator<Item = &Point> {
+        outline.iter().flatten()
+    }
+
+    #[test]
+    fn test_round_join_follows_outer_arc() {
+        let outline = stroke_outline(&right_angle(), &style(LineJoin::Round));
+        // Two segments and the join
+        assert_eq!(outline.len(), 3);
+        let on_arc: Vec<_> = vertices(&outline)
+            .filter(|p| p.0 > 50.5 && p.1 < 49.5)
+            .collect();
+        assert!(!on_arc.is_empty());
+        for p in on_arc {
+            assert!(
+                (length(sub(*p, (50.0, 50.0))) - 5.0).abs() < 1e-3,
+                "{:?}",
+                p
+            );
+        }
+        // Nothing reaches the miter corner
+        assert!(vertices(&outline).all(|p| length(sub(*p, (55.0, 45.0))) > 1.0));
+    }
+
+    #[test]
+    fn test_miter_limit_falls_back_to_bevel() {
+        let corner =
+            |outline: &[Polygon]| vertices(outline).any(|p| length(sub(*p, (55.0, 45.0))) < 1e-3);
+        assert!(corner(&stroke_outline(
+            &right_angle(),
+            &style(LineJoin::Miter)
+        )));
+
+        // A right angle's miter is √2 times the line width
+        let limited = LineStyle {
+            miter_limit: 1.0,
+            ..style(LineJoin::Miter)
+        };
+        let outline = stroke_outline(&right_angle(), &limited);
+        assert!(!corner(&outline));
+        let join = &outline[2];
+        assert_eq!(join.len(), 3);
+        assert!(join.contains(&(50.0, 45.0)) && join.contains(&(55.0, 50.0)));
+
+        // Doubling back would need an unbounded miter
+        let back = vec![vec![(0.0, 0.0), (20.0, 0.0), (0.0, 0.1)]];
+        for polygon in stroke_outline(&back, &style(LineJoin::Miter)) {
+            assert!(polygon.iter().all(|p| p.0 < 30.0), "{:?}", polygon);
+        }
+    }
+
+    #[test]
+    fn test_dashes_get_their_own_caps() {
+        let line = vec![vec![(0.0, 0.0), (100.0, 0.0)]];
+        let dashed = LineStyle {
+            dash: vec![10.0, 10.0],
+            ..style(LineJoin::Miter)
+        };
+        assert_eq!(stroke_outline(&line, &dashed).len(), 5);
+
+        // Square caps stick out half the width past each dash
+        let capped = LineStyle {
+            cap: LineCap::Square,
+            ..dashed.clone()
+        };
+        let outline = stroke_outline(&line, &capped);
+        assert_eq!(outline.len(), 15);
+        let xs: Vec<f32> = vertices(&outline).map(|p| p.0).collect();
+        assert_eq!(xs.iter().cloned().fold(f32::MAX, f32::min), -5.0);
+
+        // The offset shifts the pattern along the line
+        let shifted = LineStyle {
+            dash_offset: 5.0,
+            ..dashed
+        };
+        let dashes = dash_polyline(&line[0], &shifted.dash, shifted.dash_offset);
+        assert_eq!(dashes.len(), 6);
+        assert_eq!(dashes[0], [(0.0, 0.0), (5.0, 0.0)]);
+        assert_eq!(dashes[5], [(95.0, 0.0), (100.0, 0.0)]);
+
+        // Dashes run across vertices
+        let bent = vec![(0.0, 0.0), (6.0, 0.0), (6.0, 6.0)];
+        let dashes = dash_polyline(&bent, &[10.0, 2.0], 0.0);
+        assert_eq!(dashes[0], [(0.0, 0.0), (6.0, 0.0), (6.0, 4.0)]);
+    }
+}
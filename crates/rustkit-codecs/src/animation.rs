@@ -0,0 +1,452 @@
+//! Animated GIF and APNG decoding.
+//!
+//! Each frame is composited onto a canvas the size of the image as its
+//! blend and disposal say, so every [`Frame`] is a complete picture that
+//! can be shown on its own. Only the frames that fit a byte budget are
+//! kept, from a given frame on; the delays of all of them always are, so
+//! callers can time the whole animation and decode the rest again when
+//! they reach it.
+
+use crate::{detect_format, png_output_to_rgba, CodecError, Frame, ImageFormat, RgbaImage};
+
+/// Shortest delay a frame is shown for; shorter delays, including none,
+/// are raised to it.
+pub const MIN_FRAME_DELAY_MS: u32 = 10;
+
+/// A decoded animation.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    /// How long each frame shows, kept or not.
+    pub delays: Vec<u32>,
+    /// Index of the first frame in `frames`.
+    pub first: usize,
+    /// Composited frames from `first` on, as many as fit the budget.
+    pub frames: Vec<Frame>,
+    /// Times the frames play through; 0 loops forever.
+    pub plays: u32,
+}
+
+/// How a frame is drawn onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Blend {
+    /// Replace the pixels under the frame.
+    Source,
+    /// Alpha-composite over them.
+    Over,
+}
+
+/// What happens to a frame's area once it has been shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dispose {
+    Keep,
+    /// Clear to transparent.
+    Background,
+    /// Restore what was there before the frame.
+    Previous,
+}
+
+/// A frame's placement on the canvas.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    left: u32,
+    top: u32,
+    blend: Blend,
+    dispose: Dispose,
+}
+
+impl Placement {
+    /// A still image covering the canvas.
+    const STILL: Self = Self {
+        left: 0,
+        top: 0,
+        blend: Blend::Source,
+        dispose: Dispose::Keep,
+    };
+}
+
+/// Decode an animated GIF or PNG; a PNG without animation is one frame
+/// played once. Frames are kept until their pixels would take them past
+/// `max_bytes`, keeping at least the first.
+pub fn decode_animation(bytes: &[u8], max_bytes: usize) -> Result<Animation, CodecError> {
+    decode_animation_from(bytes, 0, max_bytes)
+}
+
+/// Decode an animation as [`decode_animation`] does, keeping frames from
+/// index `first` on.
+pub fn decode_animation_from(
+    bytes: &[u8],
+    first: usize,
+    max_bytes: usize,
+) -> Result<Animation, CodecError> {
+    match detect_format(bytes) {
+        Some(ImageFormat::Gif) => decode_gif(bytes, first, max_bytes),
+        Some(ImageFormat::Png) => decode_apng(bytes, first, max_bytes),
+        format => Err(CodecError::Unsupported(
+            format.unwrap_or(ImageFormat::Unknown),
+        )),
+    }
+}
+
+/// Whether a PNG has an animation control chunk before its image data.
+pub(crate) fn is_apng(bytes: &[u8]) -> bool {
+    let mut chunks = bytes.get(8..).unwrap_or_default();
+    while chunks.len() >= 8 {
+        let length = u32::from_be_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+        match &chunks[4..8] {
+            b"acTL" => return true,
+            b"IDAT" => return false,
+            _ => {}
+        }
+        // Length, type, data and CRC
+        chunks = chunks.get(length.saturating_add(12)..).unwrap_or_default();
+    }
+    false
+}
+
+/// The canvas frames are composited onto.
+struct Canvas {
+    image: RgbaImage,
+    delays: Vec<u32>,
+    first: usize,
+    frames: Vec<Frame>,
+    max_bytes: usize,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, first: usize, max_bytes: usize) -> Self {
+        Self {
+            image: RgbaImage::new(width, height),
+            delays: Vec::new(),
+            first,
+            frames: Vec::new(),
+            max_bytes,
+        }
+    }
+
+    /// Whether another frame fits in the budget.
+    fn has_room(&self) -> bool {
+        self.frames.is_empty()
+            || (self.frames.len() + 1).saturating_mul(self.image.data().len()) <= self.max_bytes
+    }
+
+    /// Composite `frame` at `placement`, keep the result as a frame shown
+    /// for `delay_ms` if it is in the kept range, then dispose of the
+    /// frame's area. Frames past the budget are only timed.
+    fn add(&mut self, frame: &RgbaImage, placement: Placement, delay_ms: u32) {
+        let delay_ms = delay_ms.max(MIN_FRAME_DELAY_MS);
+        let index = self.delays.len();
+        self.delays.push(delay_ms);
+        let keep = index >= self.first;
+        if keep && !self.has_room() {
+            return;
+        }
+        let previous = (placement.dispose == Dispose::Previous).then(|| self.image.clone());
+        self.draw(frame, placement);
+        if keep {
+            self.frames.push(Frame {
+                image: self.image.clone(),
+                delay_ms,
+            });
+        }
+        match placement.dispose {
+            Dispose::Keep => {}
+            Dispose::Background => self.clear(frame, placement),
+            Dispose::Previous => self.image = previous.unwrap(),
+        }
+    }
+
+    /// The area `frame` covers at `placement` as left, top, width and
+    /// height, clipped to the canvas.
+    fn span(&self, frame: &RgbaImage, placement: Placement) -> (u32, u32, u32, u32) {
+        let width = frame
+            .width()
+            .min(self.image.width().saturating_sub(placement.left));
+        let height = frame
+            .height()
+            .min(self.image.height().saturating_sub(placement.top));
+        (placement.left, placement.top, width, height)
+    }
+
+    fn draw(&mut self, frame: &RgbaImage, placement: Placement) {
+        let (left, top, width, height) = self.span(frame, placement);
+        let stride = self.image.width() as usize * 4;
+        let frame_stride = frame.width() as usize * 4;
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let from = y * frame_stride + x * 4;
+                let to = (top as usize + y) * stride + (left as usize + x) * 4;
+                let source = &frame.data()[from..from + 4];
+                let target = &mut self.image.data_mut()[to..to + 4];
+                match placement.blend {
+                    Blend::Source => target.copy_from_slice(source),
+                    Blend::Over => over(source, target),
+                }
+            }
+        }
+    }
+
+    fn into_animation(self, width: u32, height: u32, plays: u32) -> Animation {
+        Animation {
+            width,
+            height,
+            delays: self.delays,
+            first: self.first,
+            frames: self.frames,
+            plays,
+        }
+    }
+
+    fn clear(&mut self, frame: &RgbaImage, placement: Placement) {
+        let (left, top, width, height) = self.span(frame, placement);
+        let stride = self.image.width() as usize * 4;
+        for y in top as usize..(top + height) as usize {
+            let row = y * stride + left as usize * 4;
+            self.image.data_mut()[row..row + width as usize * 4].fill(0);
+        }
+    }
+}
+
+/// `source` composited over `target`, both with straight alpha.
+fn over(source: &[u8], target: &mut [u8]) {
+    match source[3] {
+        0 => {}
+        255 => target.copy_from_slice(source),
+        alpha => {
+            let sa = alpha as f32 / 255.0;
+            let da = target[3] as f32 / 255.0 * (1.0 - sa);
+            let out = sa + da;
+            for channel in 0..3 {
+                let color = (source[channel] as f32 * sa + target[channel] as f32 * da) / out;
+                target[channel] = color.round() as u8;
+            }
+            target[3] = (out * 255.0).round() as u8;
+        }
+    }
+}
+
+fn decode_gif(bytes: &[u8], first: usize, max_bytes: usize) -> Result<Animation, CodecError> {
+    let mut opts = gif::DecodeOptions::new();
+    opts.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = opts
+        .read_info(std::io::Cursor::new(bytes))
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+    let (width, height) = (decoder.width() as u32, decoder.height() as u32);
+    let mut canvas = Canvas::new(width, height, first, max_bytes);
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|e| CodecError::Decode(e.to_string()))?
+    {
+        // Already RGBA, with the transparent index at alpha 0
+        let image = RgbaImage::from_rgba8(
+            frame.width as u32,
+            frame.height as u32,
+            frame.buffer.to_vec(),
+        )?;
+        let placement = Placement {
+            left: frame.left as u32,
+            top: frame.top as u32,
+            blend: Blend::Over,
+            dispose: match frame.dispose {
+                gif::DisposalMethod::Background => Dispose::Background,
+                gif::DisposalMethod::Previous => Dispose::Previous,
+                gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Dispose::Keep,
+            },
+        };
+        // Delays are in hundredths of a second
+        canvas.add(&image, placement, frame.delay as u32 * 10);
+    }
+
+    if canvas.delays.is_empty() {
+        return Err(CodecError::Decode("GIF has no frames".into()));
+    }
+    // The loop count says how often to repeat after the first play
+    let plays = match decoder.repeat() {
+        gif::Repeat::Infinite => 0,
+        gif::Repeat::Finite(repeats) => repeats as u32 + 1,
+    };
+    Ok(canvas.into_animation(width, height, plays))
+}
+
+fn decode_apng(bytes: &[u8], first: usize, max_bytes: usize) -> Result<Animation, CodecError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+    let info = reader.info();
+    let (width, height) = (info.width, info.height);
+    let mut canvas = Canvas::new(width, height, first, max_bytes);
+    let mut buf = vec![0; reader.output_buffer_size()];
+
+    let Some(animation) = info.animation_control else {
+        let output = reader
+            .next_frame(&mut buf)
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        canvas.add(&png_output_to_rgba(&buf, &output)?, Placement::STILL, 0);
+        return Ok(canvas.into_animation(width, height, 1));
+    };
+    // The default image is only the first frame when a frame control
+    // comes before it; otherwise it is there for viewers without APNG
+    // support and is skipped.
+    let first_control = info.frame_control;
+    let images = animation.num_frames as usize + usize::from(first_control.is_none());
+
+    for index in 0..images {
+        let output = reader
+            .next_frame(&mut buf)
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let control = match index {
+            0 => first_control,
+            _ => reader.info().frame_control,
+        };
+        let Some(control) = control else {
+            continue;
+        };
+        let placement = Placement {
+            left: control.x_offset,
+            top: control.y_offset,
+            blend: match control.blend_op {
+                png::BlendOp::Source => Blend::Source,
+                png::BlendOp::Over => Blend::Over,
+            },
+            dispose: match control.dispose_op {
+                png::DisposeOp::None => Dispose::Keep,
+                png::DisposeOp::Background => Dispose::Background,
+                // The first frame has nothing before it to restore
+                png::DisposeOp::Previous if canvas.delays.is_empty() => Dispose::Background,
+                png::DisposeOp::Previous => Dispose::Previous,
+            },
+        };
+        // Delays are fractions of a second; a zero denominator means 100
+        let denominator = match control.delay_den {
+            0 => 100,
+            den => den as u32,
+        };
+        let delay_ms = control.delay_num as u32 * 1000 / denominator;
+        canvas.add(&png_output_to_rgba(&buf, &output)?, placement, delay_ms);
+    }
+
+    if canvas.delays.is_empty() {
+        return Err(CodecError::Decode("APNG has no frames".into()));
+    }
+    Ok(canvas.into_animation(width, height, animation.num_plays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn pixel(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        let at = ((y * image.width() + x) * 4) as usize;
+        image.data()[at..at + 4].try_into().unwrap()
+    }
+
+    /// A 4x4 GIF: a red background, a green square at (1, 1) cleared to
+    /// the background once shown, then a green dot at (3, 3).
+    fn three_frame_gif() -> Vec<u8> {
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 0];
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut bytes, 4, 4, &palette).unwrap();
+            encoder.set_repeat(gif::Repeat::Finite(2)).unwrap();
+            let frames = [
+                (0, 0, 4, [0u8; 16].to_vec(), 10, gif::DisposalMethod::Keep),
+                (1, 1, 2, vec![1; 4], 20, gif::DisposalMethod::Background),
+                (3, 3, 1, vec![1], 0, gif::DisposalMethod::Keep),
+            ];
+            for (left, top, size, indices, delay, dispose) in frames {
+                let frame = gif::Frame {
+                    left,
+                    top,
+                    width: size,
+                    height: size,
+                    delay,
+                    dispose,
+                    buffer: Cow::Owned(indices),
+                    ..Default::default()
+                };
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_gif_frames_composite_with_disposal() {
+        let animation = decode_animation(&three_frame_gif(), usize::MAX).unwrap();
+        assert_eq!((animation.width, animation.height), (4, 4));
+        assert_eq!(animation.plays, 3);
+        let delays: Vec<_> = animation.frames.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, [100, 200, MIN_FRAME_DELAY_MS]);
+
+        let [first, second, third] = &animation.frames[..] else {
+            panic!("expected 3 frames");
+        };
+        assert_eq!(pixel(&first.image, 1, 1), RED);
+        // The second frame draws over the first
+        assert_eq!(pixel(&second.image, 0, 0), RED);
+        assert_eq!(pixel(&second.image, 2, 2), GREEN);
+        // and its square is cleared before the third
+        assert_eq!(pixel(&third.image, 2, 2), CLEAR);
+        assert_eq!(pixel(&third.image, 0, 0), RED);
+        assert_eq!(pixel(&third.image, 3, 3), GREEN);
+    }
+
+    #[test]
+    fn test_frames_past_the_budget_decode_from_later_frames() {
+        // Room for one 4x4 frame: every frame is timed, only one is kept
+        let budget = 4 * 4 * 4;
+        let animation = decode_animation(&three_frame_gif(), budget).unwrap();
+        assert_eq!(animation.delays, [100, 200, MIN_FRAME_DELAY_MS]);
+        assert_eq!((animation.first, animation.frames.len()), (0, 1));
+
+        // Later frames come out composited as in a full decode
+        let full = decode_animation(&three_frame_gif(), usize::MAX).unwrap();
+        for index in 1..3 {
+            let later = decode_animation_from(&three_frame_gif(), index, budget).unwrap();
+            assert_eq!(later.delays, animation.delays);
+            assert_eq!(later.first, index);
+            assert_eq!(later.frames.len(), 1);
+            assert_eq!(
+                later.frames[0].image.data(),
+                full.frames[index].image.data()
+            );
+        }
+    }
+
+    #[test]
+    fn test_apng_frames() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(2, 0).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.set_frame_delay(1, 20).unwrap();
+            writer.write_image_data(&[RED, RED].concat()).unwrap();
+            writer.set_frame_delay(3, 0).unwrap();
+            writer.set_blend_op(png::BlendOp::Over).unwrap();
+            writer
+                .write_image_data(&[[0, 255, 0, 0], GREEN].concat())
+                .unwrap();
+        }
+        assert!(is_apng(&bytes));
+
+        let animation = decode_animation(&bytes, usize::MAX).unwrap();
+        assert_eq!(animation.plays, 0);
+        let delays: Vec<_> = animation.frames.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, [50, 30]);
+        assert_eq!(pixel(&animation.frames[1].image, 0, 0), RED);
+        assert_eq!(pixel(&animation.frames[1].image, 1, 0), GREEN);
+    }
+}
@@ -0,0 +1,416 @@
+//! Image encoders.
+//!
+//! PNG goes through the `png` crate. JPEG is a small baseline encoder
+//! (4:4:4, standard Annex K tables) since no pure-Rust encoder is vendored.
+
+use crate::{CodecError, RgbaImage};
+
+/// Encode an image as an RGBA8 PNG.
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, CodecError> {
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| CodecError::Encode(e.to_string()))?;
+        writer
+            .write_image_data(image.data())
+            .map_err(|e| CodecError::Encode(e.to_string()))?;
+    }
+    Ok(out)
+}
+
+/// Encode an image as a baseline JPEG.
+///
+/// `quality` ranges from 1 to 100. The alpha channel is ignored; callers
+/// that need a particular backdrop should flatten the image first.
+pub fn encode_jpeg(image: &RgbaImage, quality: u8) -> Result<Vec<u8>, CodecError> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(CodecError::Encode(format!(
+            "Unsupported JPEG dimensions {}x{}",
+            width, height
+        )));
+    }
+
+    let luma_quant = scale_quant_table(&LUMA_QUANT, quality);
+    let chroma_quant = scale_quant_table(&CHROMA_QUANT, quality);
+    let tables = [
+        HuffmanTable::new(&DC_LUMA_BITS, &DC_VALUES),
+        HuffmanTable::new(&AC_LUMA_BITS, &AC_LUMA_VALUES),
+        HuffmanTable::new(&DC_CHROMA_BITS, &DC_VALUES),
+        HuffmanTable::new(&AC_CHROMA_BITS, &AC_CHROMA_VALUES),
+    ];
+
+    let mut out = Vec::new();
+    write_headers(
+        &mut out,
+        width as u16,
+        height as u16,
+        &luma_quant,
+        &chroma_quant,
+    );
+
+    let mut writer = BitWriter::new(&mut out);
+    let mut prev_dc = [0i32; 3];
+    let cos = cosine_table();
+
+    for block_y in (0..height).step_by(8) {
+        for block_x in (0..width).step_by(8) {
+            let planes = block_planes(image, block_x, block_y);
+            for (component, plane) in planes.iter().enumerate() {
+                let (quant, dc, ac) = match component {
+                    0 => (&luma_quant, &tables[0], &tables[1]),
+                    _ => (&chroma_quant, &tables[2], &tables[3]),
+                };
+                let coefficients = forward_dct(plane, &cos, quant);
+                encode_block(&mut writer, &coefficients, &mut prev_dc[component], dc, ac);
+            }
+        }
+    }
+
+    writer.flush();
+    out.extend_from_slice(&[0xFF, 0xD9]);
+    Ok(out)
+}
+
+/// Natural-order index of each zigzag position.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const LUMA_QUANT: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMA_QUANT: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+const DC_LUMA_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMA_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const AC_LUMA_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const AC_LUMA_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+const AC_CHROMA_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMA_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+/// Scale a base quantization table using the IJG quality formula.
+fn scale_quant_table(base: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+
+    let mut table = [0u8; 64];
+    for (out, &q) in table.iter_mut().zip(base) {
+        *out = ((q as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    table
+}
+
+/// Canonical Huffman codes for each symbol: (code, length).
+struct HuffmanTable {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanTable {
+    fn new(bits: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0u16, 0u8); 256];
+        let mut code = 0u16;
+        let mut symbols = values.iter();
+        for (index, &count) in bits.iter().enumerate() {
+            for _ in 0..count {
+                if let Some(&symbol) = symbols.next() {
+                    codes[symbol as usize] = (code, index as u8 + 1);
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Self { codes }
+    }
+}
+
+fn write_segment(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+fn write_headers(out: &mut Vec<u8>, width: u16, height: u16, luma: &[u8; 64], chroma: &[u8; 64]) {
+    // SOI + JFIF APP0
+    out.extend_from_slice(&[0xFF, 0xD8]);
+    write_segment(out, 0xE0, b"JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00");
+
+    // Quantization tables, in zigzag order
+    let mut dqt = Vec::with_capacity(130);
+    for (id, table) in [luma, chroma].iter().enumerate() {
+        dqt.push(id as u8);
+        dqt.extend(ZIGZAG.iter().map(|&i| table[i]));
+    }
+    write_segment(out, 0xDB, &dqt);
+
+    // Baseline frame: three components, no subsampling
+    let mut sof = vec![8];
+    sof.extend_from_slice(&height.to_be_bytes());
+    sof.extend_from_slice(&width.to_be_bytes());
+    sof.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    write_segment(out, 0xC0, &sof);
+
+    let mut dht = Vec::new();
+    for (class_id, bits, values) in [
+        (0x00, &DC_LUMA_BITS, &DC_VALUES[..]),
+        (0x10, &AC_LUMA_BITS, &AC_LUMA_VALUES[..]),
+        (0x01, &DC_CHROMA_BITS, &DC_VALUES[..]),
+        (0x11, &AC_CHROMA_BITS, &AC_CHROMA_VALUES[..]),
+    ] {
+        dht.push(class_id);
+        dht.extend_from_slice(bits);
+        dht.extend_from_slice(values);
+    }
+    write_segment(out, 0xC4, &dht);
+
+    write_segment(out, 0xDA, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+}
+
+/// Level-shifted Y, Cb, Cr samples of the 8x8 block at (x, y).
+///
+/// Pixels past the right and bottom edges repeat the last column and row.
+fn block_planes(image: &RgbaImage, x: u32, y: u32) -> [[f32; 64]; 3] {
+    let mut planes = [[0f32; 64]; 3];
+    let data = image.data();
+    for row in 0..8 {
+        let sy = (y + row).min(image.height() - 1);
+        for col in 0..8 {
+            let sx = (x + col).min(image.width() - 1);
+            let i = ((sy * image.width() + sx) * 4) as usize;
+            let (r, g, b) = (data[i] as f32, data[i + 1] as f32, data[i + 2] as f32);
+
+            let index = (row * 8 + col) as usize;
+            planes[0][index] = 0.299 * r + 0.587 * g + 0.114 * b - 128.0;
+            planes[1][index] = -0.168_736 * r - 0.331_264 * g + 0.5 * b;
+            planes[2][index] = 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+        }
+    }
+    planes
+}
+
+/// cos((2x + 1) * u * pi / 16), indexed by [u][x].
+fn cosine_table() -> [[f32; 8]; 8] {
+    let mut table = [[0f32; 8]; 8];
+    for (u, row) in table.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    table
+}
+
+/// Forward DCT and quantization, returning coefficients in natural order.
+fn forward_dct(block: &[f32; 64], cos: &[[f32; 8]; 8], quant: &[u8; 64]) -> [i32; 64] {
+    let alpha = |u: usize| {
+        if u == 0 {
+            std::f32::consts::FRAC_1_SQRT_2
+        } else {
+            1.0
+        }
+    };
+
+    // Separable: rows first, then columns
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| block[y * 8 + x] * cos[u][x]).sum();
+        }
+    }
+
+    let mut out = [0i32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let sum: f32 = (0..8).map(|y| rows[y * 8 + u] * cos[v][y]).sum();
+            let coefficient = 0.25 * alpha(u) * alpha(v) * sum;
+            // Baseline AC coefficients are limited to magnitude category 10
+            out[v * 8 + u] =
+                ((coefficient / quant[v * 8 + u] as f32).round() as i32).clamp(-1023, 1023);
+        }
+    }
+    out
+}
+
+/// Magnitude category and the low bits that encode `value`.
+fn magnitude(value: i32) -> (u8, u16) {
+    let category = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 {
+        (value - 1) as u16 & ((1u16 << category) - 1)
+    } else {
+        value as u16
+    };
+    (category, bits)
+}
+
+fn encode_block(
+    writer: &mut BitWriter,
+    coefficients: &[i32; 64],
+    prev_dc: &mut i32,
+    dc: &HuffmanTable,
+    ac: &HuffmanTable,
+) {
+    let diff = coefficients[0] - *prev_dc;
+    *prev_dc = coefficients[0];
+    let (category, bits) = magnitude(diff);
+    writer.write_code(dc.codes[category as usize]);
+    writer.write_bits(bits, category);
+
+    let mut run = 0;
+    for &index in &ZIGZAG[1..] {
+        let value = coefficients[index];
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            writer.write_code(ac.codes[0xF0]);
+            run -= 16;
+        }
+        let (category, bits) = magnitude(value);
+        writer.write_code(ac.codes[(run << 4) as usize | category as usize]);
+        writer.write_bits(bits, category);
+        run = 0;
+    }
+    if run > 0 {
+        writer.write_code(ac.codes[0x00]);
+    }
+}
+
+/// Entropy-coded segment writer with 0xFF byte stuffing.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self {
+            out,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn write_code(&mut self, (code, length): (u16, u8)) {
+        self.write_bits(code, length);
+    }
+
+    fn write_bits(&mut self, bits: u16, length: u8) {
+        for shift in (0..length).rev() {
+            self.buffer = (self.buffer << 1) | ((bits >> shift) & 1) as u32;
+            self.count += 1;
+            if self.count == 8 {
+                self.emit(self.buffer as u8);
+            }
+        }
+    }
+
+    fn emit(&mut self, byte: u8) {
+        self.out.push(byte);
+        if byte == 0xFF {
+            self.out.push(0x00);
+        }
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    /// Pad the final byte with 1 bits.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            let pad = 8 - self.count;
+            let byte = ((self.buffer << pad) | ((1 << pad) - 1)) as u8;
+            self.emit(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_jpeg, decode_png};
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        for (i, pixel) in image.pixels_mut().enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            pixel.copy_from_slice(&[(x * 10) as u8, (y * 10) as u8, 128, (x + y) as u8]);
+        }
+        image
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let image = gradient(13, 7);
+        let decoded = decode_png(&encode_png(&image).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (13, 7));
+        assert_eq!(decoded.data(), image.data());
+    }
+
+    #[test]
+    fn test_jpeg_round_trip() {
+        // Odd dimensions exercise partial edge blocks
+        let image = gradient(21, 11);
+        let decoded = decode_jpeg(&encode_jpeg(&image, 95).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (21, 11));
+
+        for (original, decoded) in image.data().chunks(4).zip(decoded.data().chunks(4)) {
+            for channel in 0..3 {
+                let delta = (original[channel] as i32 - decoded[channel] as i32).abs();
+                assert!(delta <= 8, "{:?} vs {:?}", original, decoded);
+            }
+            assert_eq!(decoded[3], 255);
+        }
+    }
+
+    #[test]
+    fn test_jpeg_quality_affects_size() {
+        let image = gradient(64, 64);
+        let low = encode_jpeg(&image, 10).unwrap();
+        let high = encode_jpeg(&image, 100).unwrap();
+        assert!(low.len() < high.len());
+    }
+}
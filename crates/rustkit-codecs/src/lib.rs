@@ -6,6 +6,8 @@
 //! - PNG (via `png` crate)
 //! - JPEG (via `jpeg-decoder` crate)
 //! - GIF (static + animated via `gif` crate)
+//! - APNG (via `png` crate)
+//! - PNG and baseline JPEG encoding
 //!
 //! Planned:
 //! - WebP
@@ -13,6 +15,12 @@
 
 use thiserror::Error;
 
+mod animation;
+mod encode;
+
+pub use animation::{decode_animation, decode_animation_from, Animation, MIN_FRAME_DELAY_MS};
+pub use encode::{encode_jpeg, encode_png};
+
 /// Supported image formats (detected by magic bytes).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
@@ -36,6 +44,9 @@ pub enum CodecError {
 
     #[error("Decode error: {0}")]
     Decode(String),
+
+    #[error("Encode error: {0}")]
+    Encode(String),
 }
 
 /// A simple RGBA8 image buffer.
@@ -93,7 +104,7 @@ impl RgbaImage {
     }
 }
 
-/// One decoded animation frame.
+/// One decoded animation frame, composited onto the full canvas.
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub image: RgbaImage,
@@ -134,6 +145,9 @@ pub fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
 pub fn decode_any(bytes: &[u8]) -> Result<Decoded, CodecError> {
     let fmt = detect_format(bytes).unwrap_or(ImageFormat::Unknown);
     match fmt {
+        ImageFormat::Png if animation::is_apng(bytes) => Ok(Decoded::Animated(
+            decode_animation(bytes, usize::MAX)?.frames,
+        )),
         ImageFormat::Png => Ok(Decoded::Static(decode_png(bytes)?)),
         ImageFormat::Jpeg => Ok(Decoded::Static(decode_jpeg(bytes)?)),
         ImageFormat::Gif => Ok(Decoded::Animated(decode_gif(bytes)?)),
@@ -157,6 +171,15 @@ pub fn decode_png(bytes: &[u8]) -> Result<RgbaImage, CodecError> {
         .next_frame(&mut buf)
         .map_err(|e| CodecError::Decode(e.to_string()))?;
 
+    png_output_to_rgba(&buf, &output)
+}
+
+/// The RGBA image of a frame `next_frame` wrote into `buf`, decoded with
+/// `EXPAND | STRIP_16`.
+pub(crate) fn png_output_to_rgba(
+    buf: &[u8],
+    output: &png::OutputInfo,
+) -> Result<RgbaImage, CodecError> {
     let buf = buf[..output.buffer_size()].to_vec();
 
     let width = output.width;
@@ -205,32 +228,9 @@ pub fn decode_jpeg(bytes: &[u8]) -> Result<RgbaImage, CodecError> {
     RgbaImage::from_rgba8(width, height, rgba)
 }
 
+/// Decode a GIF into its frames, each composited onto the full canvas.
 pub fn decode_gif(bytes: &[u8]) -> Result<Vec<Frame>, CodecError> {
-    let mut opts = gif::DecodeOptions::new();
-    opts.set_color_output(gif::ColorOutput::RGBA);
-    let mut decoder = opts
-        .read_info(std::io::Cursor::new(bytes))
-        .map_err(|e| CodecError::Decode(e.to_string()))?;
-
-    let mut frames = Vec::new();
-    while let Some(frame) = decoder
-        .read_next_frame()
-        .map_err(|e| CodecError::Decode(e.to_string()))?
-    {
-        let width = frame.width as u32;
-        let height = frame.height as u32;
-        let rgba = frame.buffer.to_vec(); // already RGBA
-        let image = RgbaImage::from_rgba8(width, height, rgba)?;
-        // Delay is in 1/100s units. Convert to ms, minimum 10ms.
-        let delay_ms = (frame.delay as u32).saturating_mul(10).max(10);
-        frames.push(Frame { image, delay_ms });
-    }
-
-    if frames.is_empty() {
-        return Err(CodecError::Decode("GIF has no frames".into()));
-    }
-
-    Ok(frames)
+    Ok(decode_animation(bytes, usize::MAX)?.frames)
 }
 
 fn rgb_to_rgba(rgb: Vec<u8>, alpha: u8) -> Vec<u8> {
@@ -148,7 +148,8 @@ impl Compositor {
                 .request_device(
                     &wgpu::DeviceDescriptor {
                         label: Some("RustKit Compositor Device"),
-                        required_features: wgpu::Features::empty(),
+                        // Lets the renderer reuse compiled pipelines across runs
+                        required_features: adapter.features() & wgpu::Features::PIPELINE_CACHE,
                         required_limits: wgpu::Limits::default(),
                         memory_hints: wgpu::MemoryHints::Performance,
                     },
@@ -244,7 +245,10 @@ impl Compositor {
             height,
         };
 
-        self.surfaces.write().unwrap().insert(view_id, state);
+        self.surfaces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(view_id, state);
 
         info!(?view_id, "Surface created");
         Ok(())
@@ -257,7 +261,7 @@ impl Compositor {
         width: u32,
         height: u32,
     ) -> Result<(), CompositorError> {
-        let mut surfaces = self.surfaces.write().unwrap();
+        let mut surfaces = self.surfaces.write().unwrap_or_else(|e| e.into_inner());
         let state = surfaces
             .get_mut(&view_id)
             .ok_or(CompositorError::SurfaceNotFound(view_id))?;
@@ -305,7 +309,10 @@ impl Compositor {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: self.config.format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            // Hosts may sample the texture in their own scene
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
 
@@ -316,7 +323,10 @@ impl Compositor {
             height,
         };
 
-        self.headless_textures.write().unwrap().insert(view_id, state);
+        self.headless_textures
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(view_id, state);
 
         info!(?view_id, width, height, "Headless texture created");
         Ok(())
@@ -325,14 +335,17 @@ impl Compositor {
     /// Get the surface dimensions for a view (supports both surfaces and headless textures).
     pub fn get_surface_size(&self, view_id: ViewId) -> Result<(u32, u32), CompositorError> {
         // Check headless textures first
-        let headless = self.headless_textures.read().unwrap();
+        let headless = self
+            .headless_textures
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
         if let Some(state) = headless.get(&view_id) {
             return Ok((state.width, state.height));
         }
         drop(headless);
 
         // Fall back to surfaces
-        let surfaces = self.surfaces.read().unwrap();
+        let surfaces = self.surfaces.read().unwrap_or_else(|e| e.into_inner());
         let state = surfaces
             .get(&view_id)
             .ok_or(CompositorError::SurfaceNotFound(view_id))?;
@@ -346,7 +359,10 @@ impl Compositor {
         color: [f64; 4],
     ) -> Result<(), CompositorError> {
         // Check if this is a headless texture first
-        let headless = self.headless_textures.read().unwrap();
+        let headless = self
+            .headless_textures
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
         if let Some(state) = headless.get(&view_id) {
             let texture_view = state.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -385,7 +401,7 @@ impl Compositor {
         drop(headless);
 
         // Fall back to surfaces
-        let surfaces = self.surfaces.read().unwrap();
+        let surfaces = self.surfaces.read().unwrap_or_else(|e| e.into_inner());
         let state = surfaces
             .get(&view_id)
             .ok_or(CompositorError::SurfaceNotFound(view_id))?;
@@ -433,13 +449,23 @@ impl Compositor {
     /// Destroy a surface or headless texture.
     pub fn destroy_surface(&self, view_id: ViewId) -> Result<(), CompositorError> {
         // Try removing from headless textures first
-        if self.headless_textures.write().unwrap().remove(&view_id).is_some() {
+        if self
+            .headless_textures
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&view_id)
+            .is_some()
+        {
             info!(?view_id, "Headless texture destroyed");
             return Ok(());
         }
 
         // Try removing from surfaces
-        let removed = self.surfaces.write().unwrap().remove(&view_id);
+        let removed = self
+            .surfaces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&view_id);
         if removed.is_some() {
             info!(?view_id, "Surface destroyed");
             Ok(())
@@ -450,7 +476,10 @@ impl Compositor {
 
     /// Get the number of active surfaces.
     pub fn surface_count(&self) -> usize {
-        self.surfaces.read().unwrap().len()
+        self.surfaces
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
     }
 
     /// Get the device.
@@ -478,6 +507,29 @@ impl Compositor {
         wgpu::TextureFormat::Bgra8UnormSrgb
     }
 
+    /// MSAA sample counts render targets in the surface format may use.
+    ///
+    /// The device is created without adapter-specific format features, so
+    /// only the counts WebGPU guarantees (1 and 4) are offered even when
+    /// the adapter reports more.
+    pub fn supported_sample_counts(&self) -> Vec<u32> {
+        let flags = self
+            .adapter
+            .get_texture_format_features(self.surface_format())
+            .flags;
+        let adapter_specific = self
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        [1, 2, 4, 8, 16]
+            .into_iter()
+            .filter(|&count| {
+                count == 1
+                    || ((adapter_specific || count == 4) && flags.sample_count_supported(count))
+            })
+            .collect()
+    }
+
     /// Get GPU adapter info.
     pub fn adapter_info(&self) -> wgpu::AdapterInfo {
         self.adapter.get_info()
@@ -489,7 +541,7 @@ impl Compositor {
         &self,
         view_id: ViewId,
     ) -> Result<(wgpu::SurfaceTexture, wgpu::TextureView), CompositorError> {
-        let surfaces = self.surfaces.read().unwrap();
+        let surfaces = self.surfaces.read().unwrap_or_else(|e| e.into_inner());
         let state = surfaces
             .get(&view_id)
             .ok_or(CompositorError::SurfaceNotFound(view_id))?;
@@ -515,7 +567,10 @@ impl Compositor {
         &self,
         view_id: ViewId,
     ) -> Result<wgpu::TextureView, CompositorError> {
-        let headless = self.headless_textures.read().unwrap();
+        let headless = self
+            .headless_textures
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
         let state = headless
             .get(&view_id)
             .ok_or(CompositorError::SurfaceNotFound(view_id))?;
@@ -527,9 +582,87 @@ impl Compositor {
         Ok(view)
     }
 
+    /// Get the headless texture itself, for hosts compositing it.
+    pub fn get_headless_texture(&self, view_id: ViewId) -> Result<wgpu::Texture, CompositorError> {
+        self.headless_textures
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&view_id)
+            .map(|state| state.texture.clone())
+            .ok_or(CompositorError::SurfaceNotFound(view_id))
+    }
+
+    /// Read back a headless texture as tightly packed RGBA rows.
+    pub fn read_headless_texture(
+        &self,
+        view_id: ViewId,
+    ) -> Result<(u32, u32, Vec<u8>), CompositorError> {
+        let texture = self.get_headless_texture(view_id)?;
+        let (width, height) = (texture.width(), texture.height());
+
+        let bytes_per_pixel = 4u32;
+        let padded_bytes_per_row = (width * bytes_per_pixel + 255) & !255;
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|e| CompositorError::Render(format!("Failed to receive map result: {}", e)))?
+            .map_err(|e| CompositorError::Render(format!("Failed to map buffer: {:?}", e)))?;
+
+        // BGRA -> RGBA without the row padding
+        let data = buffer_slice.get_mapped_range();
+        let mut rgba = Vec::with_capacity((width * height * bytes_per_pixel) as usize);
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            for pixel in row[..(width * bytes_per_pixel) as usize].chunks_exact(4) {
+                rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        }
+        drop(data);
+        staging_buffer.unmap();
+
+        Ok((width, height, rgba))
+    }
+
     /// Check if a view is headless.
     pub fn is_headless(&self, view_id: ViewId) -> bool {
-        self.headless_textures.read().unwrap().contains_key(&view_id)
+        self.headless_textures
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&view_id)
     }
 
     /// Capture a frame to a PPM file (fallback for when no display list is available).
@@ -721,10 +854,9 @@ impl Compositor {
             view_formats: &[],
         });
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
         // Render the display list to the offscreen texture
-        renderer.execute(commands, &texture_view)
+        renderer
+            .execute(commands, &texture)
             .map_err(|e| CompositorError::Render(format!("Renderer error: {}", e)))?;
 
         // Create staging buffer for readback
@@ -824,8 +956,14 @@ impl Compositor {
 impl Drop for Compositor {
     fn drop(&mut self) {
         // Clear all surfaces and headless textures
-        self.surfaces.write().unwrap().clear();
-        self.headless_textures.write().unwrap().clear();
+        self.surfaces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.headless_textures
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
         info!("Compositor dropped");
     }
 }
@@ -0,0 +1,276 @@
+//! Character encodings for decoding response bodies and script data.
+//!
+//! Covers the encodings the engine needs without a full WHATWG encoding
+//! table: UTF-8, UTF-16LE and windows-1252 (which the Encoding Standard
+//! uses for every `latin1`/`iso-8859-1`/`ascii` label). [`Decoder`] keeps
+//! partial sequences between chunks so bodies and `TextDecoder` streams can
+//! be decoded incrementally.
+
+use thiserror::Error;
+
+/// Code points for windows-1252 bytes 0x80..=0x9F. Bytes the code page
+/// leaves undefined map to the matching C1 control, as the standard does.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Error returned by a fatal [`Decoder`] on malformed input.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("The encoded data was not valid {0}")]
+pub struct DecodeError(pub &'static str);
+
+/// A supported character encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Windows1252,
+}
+
+impl Charset {
+    /// Resolve an encoding label, ignoring case and surrounding whitespace.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8"
+            | "x-unicode20utf8" => Some(Charset::Utf8),
+            "utf-16le" | "utf-16" | "ucs-2" | "unicode" | "unicodefeff" | "csunicode"
+            | "iso-10646-ucs-2" => Some(Charset::Utf16Le),
+            "windows-1252" | "latin1" | "l1" | "iso-8859-1" | "iso8859-1" | "iso_8859-1"
+            | "iso88591" | "cp1252" | "cp819" | "x-cp1252" | "ascii" | "us-ascii"
+            | "ansi_x3.4-1968" | "ibm819" | "csisolatin1" | "iso-ir-100" => {
+                Some(Charset::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// The `charset` parameter of a `Content-Type` value, if supported.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        content_type.split(';').skip(1).find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            if !name.trim().eq_ignore_ascii_case("charset") {
+                return None;
+            }
+            Self::from_label(value.trim().trim_matches('"'))
+        })
+    }
+
+    /// Canonical name, as reported by `TextDecoder.encoding`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Windows1252 => "windows-1252",
+        }
+    }
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Charset::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Charset::Utf16Le => &[0xFF, 0xFE],
+            Charset::Windows1252 => &[],
+        }
+    }
+
+    /// Decode a complete buffer, replacing malformed sequences and
+    /// stripping a leading byte order mark.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        Decoder::new(*self, false, false)
+            .decode(bytes, false)
+            .unwrap_or_default()
+    }
+}
+
+/// Incremental decoder for one stream of bytes.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    charset: Charset,
+    fatal: bool,
+    ignore_bom: bool,
+    /// Whether the start of the stream, and any BOM, has been consumed.
+    started: bool,
+    /// Trailing bytes of an incomplete sequence from the previous chunk.
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    /// Create a decoder. `fatal` turns malformed input into an error instead
+    /// of U+FFFD; `ignore_bom` keeps a leading byte order mark in the output.
+    pub fn new(charset: Charset, fatal: bool, ignore_bom: bool) -> Self {
+        Self {
+            charset,
+            fatal,
+            ignore_bom,
+            started: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The encoding being decoded.
+    pub fn charset(&self) -> Charset {
+        self.charset
+    }
+
+    /// Decode the next chunk. With `stream`, an incomplete sequence at the
+    /// end is held for the next call; otherwise the stream ends here and the
+    /// decoder is reset.
+    ///
+    /// After an error the decoder is reset as well.
+    pub fn decode(&mut self, bytes: &[u8], stream: bool) -> Result<String, DecodeError> {
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(bytes);
+
+        let mut start = 0;
+        if !self.started {
+            let bom = self.charset.bom();
+            if stream && input.len() < bom.len() && bom.starts_with(&input) {
+                self.pending = input;
+                return Ok(String::new());
+            }
+            self.started = true;
+            if !self.ignore_bom && input.starts_with(bom) {
+                start = bom.len();
+            }
+        }
+
+        let result = match self.charset {
+            Charset::Utf8 => self.decode_utf8(&input[start..], stream),
+            Charset::Utf16Le => self.decode_utf16le(&input[start..], stream),
+            Charset::Windows1252 => Ok(input[start..].iter().map(|&b| windows_1252(b)).collect()),
+        };
+        if result.is_err() || !stream {
+            self.started = false;
+            self.pending.clear();
+        }
+        result
+    }
+
+    fn malformed(&self, out: &mut String) -> Result<(), DecodeError> {
+        if self.fatal {
+            return Err(DecodeError(self.charset.name()));
+        }
+        out.push(char::REPLACEMENT_CHARACTER);
+        Ok(())
+    }
+
+    fn decode_utf8(&mut self, mut input: &[u8], stream: bool) -> Result<String, DecodeError> {
+        let mut out = String::with_capacity(input.len());
+        loop {
+            match std::str::from_utf8(input) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    return Ok(out);
+                }
+                Err(e) => {
+                    let (valid, rest) = input.split_at(e.valid_up_to());
+                    // Already validated up to here
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            self.malformed(&mut out)?;
+                            input = &rest[len..];
+                        }
+                        // Truncated sequence at the end of the input
+                        None if stream => {
+                            self.pending = rest.to_vec();
+                            return Ok(out);
+                        }
+                        None => {
+                            self.malformed(&mut out)?;
+                            return Ok(out);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode_utf16le(&mut self, input: &[u8], stream: bool) -> Result<String, DecodeError> {
+        let mut units: Vec<u16> = input
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let mut held = input.len() % 2;
+        // Keep a trailing lead surrogate until its pair arrives
+        if stream && matches!(units.last(), Some(0xD800..=0xDBFF)) {
+            units.pop();
+            held += 2;
+        }
+
+        let mut out = String::with_capacity(units.len());
+        for unit in char::decode_utf16(units) {
+            match unit {
+                Ok(c) => out.push(c),
+                Err(_) => self.malformed(&mut out)?,
+            }
+        }
+        if held > 0 {
+            if stream {
+                self.pending = input[input.len() - held..].to_vec();
+            } else {
+                self.malformed(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_and_content_type() {
+        assert_eq!(Charset::from_label(" Latin1 "), Some(Charset::Windows1252));
+        assert_eq!(Charset::from_label("UTF8"), Some(Charset::Utf8));
+        assert_eq!(Charset::from_label("koi8-r"), None);
+        assert_eq!(
+            Charset::from_content_type("text/html; charset=\"ISO-8859-1\""),
+            Some(Charset::Windows1252)
+        );
+        assert_eq!(Charset::from_content_type("text/plain"), None);
+    }
+
+    #[test]
+    fn test_streaming_utf8_split_mid_codepoint() {
+        let bytes = "a€b".as_bytes();
+        let mut decoder = Decoder::new(Charset::Utf8, true, false);
+        assert_eq!(decoder.decode(&bytes[..2], true).unwrap(), "a");
+        assert_eq!(decoder.decode(&bytes[2..], true).unwrap(), "€b");
+        assert_eq!(
+            decoder.decode(&bytes[..3], false),
+            Err(DecodeError("utf-8"))
+        );
+
+        let mut lossy = Decoder::new(Charset::Utf8, false, false);
+        assert_eq!(
+            lossy.decode(&[0x61, 0xFF, 0xE2, 0x82], false).unwrap(),
+            "a\u{FFFD}\u{FFFD}"
+        );
+    }
+
+    #[test]
+    fn test_utf16le_and_windows_1252() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("h😀".encode_utf16().flat_map(u16::to_le_bytes));
+        let mut decoder = Decoder::new(Charset::Utf16Le, false, false);
+        assert_eq!(decoder.decode(&bytes[..5], true).unwrap(), "h");
+        assert_eq!(decoder.decode(&bytes[5..], false).unwrap(), "😀");
+
+        let mut keep_bom = Decoder::new(Charset::Utf16Le, false, true);
+        assert_eq!(keep_bom.decode(&bytes[..4], false).unwrap(), "\u{FEFF}h");
+
+        assert_eq!(Charset::Windows1252.decode(&[0x80, 0xE9, 0x41]), "€éA");
+    }
+}
@@ -5,6 +5,7 @@
 //! into a platform-agnostic representation.
 
 use std::collections::HashSet;
+use std::path::PathBuf;
 
 /// Mouse button identifiers.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -963,12 +964,128 @@ impl FocusEvent {
     }
 }
 
+/// Drag event types, as something dragged from outside moves over a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragEventType {
+    /// The drag entered the view.
+    Enter,
+    /// The drag moved, or stayed, over the view.
+    Over,
+    /// The drag left the view or was cancelled.
+    Leave,
+    /// The data was dropped on the view.
+    Drop,
+}
+
+/// What a drop does with the data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DropEffect {
+    /// The drop is refused.
+    #[default]
+    None,
+    Copy,
+    Move,
+    Link,
+}
+
+impl DropEffect {
+    /// The effect as `dataTransfer.dropEffect` names it.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DropEffect::None => "none",
+            DropEffect::Copy => "copy",
+            DropEffect::Move => "move",
+            DropEffect::Link => "link",
+        }
+    }
+
+    /// Parse a `dataTransfer.dropEffect` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(DropEffect::None),
+            "copy" => Some(DropEffect::Copy),
+            "move" => Some(DropEffect::Move),
+            "link" => Some(DropEffect::Link),
+            _ => None,
+        }
+    }
+}
+
+/// The data a drag carries, in the formats pages can read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DragData {
+    /// Dragged files.
+    pub files: Vec<PathBuf>,
+    /// `text/plain`
+    pub text: Option<String>,
+    /// `text/uri-list`
+    pub urls: Vec<String>,
+}
+
+/// Drag-and-drop event data.
+#[derive(Debug, Clone)]
+pub struct DragEvent {
+    /// Event type.
+    pub event_type: DragEventType,
+    /// Position relative to the view.
+    pub position: Point,
+    /// Position relative to the screen.
+    pub screen_position: Point,
+    /// Modifier keys held during the event.
+    pub modifiers: Modifiers,
+    /// What the drag carries; empty for [`DragEventType::Leave`].
+    pub data: DragData,
+    /// The effects the drag's source allows.
+    pub allowed_effects: Vec<DropEffect>,
+}
+
+impl DragEvent {
+    /// Create a new drag event allowing a copy.
+    pub fn new(event_type: DragEventType, position: Point, data: DragData) -> Self {
+        Self {
+            event_type,
+            position,
+            screen_position: position,
+            modifiers: Modifiers::default(),
+            data,
+            allowed_effects: vec![DropEffect::Copy],
+        }
+    }
+
+    /// Set the effects the source allows.
+    pub fn with_allowed_effects(mut self, effects: &[DropEffect]) -> Self {
+        self.allowed_effects = effects.to_vec();
+        self
+    }
+
+    /// Set modifiers.
+    pub fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+}
+
 /// Unified input event type.
 #[derive(Debug, Clone)]
 pub enum InputEvent {
     Mouse(MouseEvent),
     Key(KeyEvent),
     Focus(FocusEvent),
+    /// A translated Unicode character (after dead-key composition).
+    CharInput {
+        ch: char,
+    },
+    /// IME composition update.
+    ///
+    /// `cursor` is the caret offset in chars within `text`. A commit inserts
+    /// `text`; a non-commit with empty `text` cancels the composition.
+    ImeComposition {
+        text: String,
+        cursor: usize,
+        is_commit: bool,
+    },
+    /// Something dragged from outside the view, such as files.
+    Drag(DragEvent),
 }
 
 /// Track currently pressed keys for repeat detection.
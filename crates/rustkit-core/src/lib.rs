@@ -11,9 +11,11 @@
 //! 4. **Structured logging**: Full tracing support
 //! 5. **Platform-agnostic input**: Unified input event types
 
+pub mod charset;
 pub mod history;
 pub mod input;
 pub mod lifecycle;
+pub mod locale;
 
 pub use history::*;
 pub use input::*;
@@ -170,11 +172,32 @@ impl NavigationRequest {
     }
 }
 
+/// An entry in a frame's back/forward list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavigationEntry {
+    pub url: Url,
+    /// Document title once the entry finished loading.
+    pub title: Option<String>,
+    /// Serialized `history.state` of the entry.
+    pub state: Option<String>,
+}
+
+impl NavigationEntry {
+    /// Create an entry with no title or state.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            title: None,
+            state: None,
+        }
+    }
+}
+
 /// Navigation state machine for a single frame.
 pub struct NavigationStateMachine {
     state: NavigationState,
     current_navigation: Option<NavigationRequest>,
-    history: Vec<Url>,
+    history: Vec<NavigationEntry>,
     history_index: usize,
     event_sender: mpsc::UnboundedSender<LoadEvent>,
 }
@@ -273,10 +296,10 @@ impl NavigationStateMachine {
         if !nav.replace_history {
             // Truncate forward history if navigating from middle
             self.history.truncate(self.history_index + 1);
-            self.history.push(nav.url.clone());
+            self.history.push(NavigationEntry::new(nav.url.clone()));
             self.history_index = self.history.len() - 1;
         } else if let Some(entry) = self.history.get_mut(self.history_index) {
-            *entry = nav.url.clone();
+            *entry = NavigationEntry::new(nav.url.clone());
         }
 
         self.state = NavigationState::Finished;
@@ -330,9 +353,38 @@ impl NavigationStateMachine {
 
     /// Get current URL.
     pub fn current_url(&self) -> Option<&Url> {
+        self.current_entry().map(|entry| &entry.url)
+    }
+
+    /// Get the current history entry.
+    pub fn current_entry(&self) -> Option<&NavigationEntry> {
         self.history.get(self.history_index)
     }
 
+    /// Set the title of the current entry.
+    pub fn set_current_title(&mut self, title: Option<String>) {
+        if let Some(entry) = self.history.get_mut(self.history_index) {
+            entry.title = title;
+        }
+    }
+
+    /// The back/forward list, oldest first.
+    pub fn entries(&self) -> &[NavigationEntry] {
+        &self.history
+    }
+
+    /// Index of the current entry in [`Self::entries`].
+    pub fn current_index(&self) -> usize {
+        self.history_index
+    }
+
+    /// Replace the back/forward list, e.g. from a saved session, without
+    /// loading anything. `index` is clamped to the list.
+    pub fn restore_history(&mut self, entries: Vec<NavigationEntry>, index: usize) {
+        self.history_index = index.min(entries.len().saturating_sub(1));
+        self.history = entries;
+    }
+
     /// Check if can go back.
     pub fn can_go_back(&self) -> bool {
         self.history_index > 0
@@ -347,7 +399,7 @@ impl NavigationStateMachine {
     pub fn go_back(&mut self) -> Option<&Url> {
         if self.can_go_back() {
             self.history_index -= 1;
-            self.history.get(self.history_index)
+            self.current_url()
         } else {
             None
         }
@@ -357,7 +409,7 @@ impl NavigationStateMachine {
     pub fn go_forward(&mut self) -> Option<&Url> {
         if self.can_go_forward() {
             self.history_index += 1;
-            self.history.get(self.history_index)
+            self.current_url()
         } else {
             None
         }
@@ -539,6 +591,31 @@ mod tests {
         assert!(events.len() >= 3);
     }
 
+    #[test]
+    fn test_restore_history() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut nav = NavigationStateMachine::new(tx);
+        let entries: Vec<_> = ["https://example.com/1", "https://example.com/2"]
+            .iter()
+            .map(|url| NavigationEntry::new(Url::parse(url).unwrap()))
+            .collect();
+        nav.restore_history(entries.clone(), 5);
+        assert_eq!(nav.current_index(), 1);
+        assert!(nav.can_go_back());
+        assert!(!nav.can_go_forward());
+
+        // Loading the current entry again keeps the list
+        let url = entries[1].url.clone();
+        nav.start_navigation(NavigationRequest::new(url).with_replace())
+            .unwrap();
+        nav.commit_navigation().unwrap();
+        nav.finish_navigation().unwrap();
+        nav.set_current_title(Some("Two".into()));
+        assert_eq!(nav.entries().len(), 2);
+        assert_eq!(nav.current_entry().unwrap().title.as_deref(), Some("Two"));
+        assert_eq!(nav.go_back(), Some(&entries[0].url));
+    }
+
     #[test]
     fn test_history_navigation() {
         let (tx, _rx) = mpsc::unbounded_channel();
@@ -0,0 +1,451 @@
+//! Locale data behind `Intl` and the `toLocaleString` methods.
+//!
+//! The engine does not bundle CLDR. A [`LocaleProvider`] supplies what the
+//! system knows about a locale — separators, date and time pictures, month
+//! names and the local currency — and [`BuiltinLocales`] covers a handful of
+//! common locales for hosts without such data. Pictures use the Windows
+//! format syntax (`d`, `dd`, `M`…`MMMM`, `yy`, `yyyy`, `h`, `H`, `mm`, `ss`,
+//! `tt`, and `'quoted'` literals), since that is where most data comes from.
+
+/// Where the symbol goes in a positive currency amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurrencyPlacement {
+    /// `$1.00`
+    Before,
+    /// `1.00$`
+    After,
+    /// `$ 1.00`
+    BeforeSpace,
+    /// `1.00 $`
+    AfterSpace,
+}
+
+/// What a locale formats numbers and dates with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleData {
+    /// The BCP 47 tag the data was found under.
+    pub tag: String,
+    pub decimal_separator: String,
+    pub group_separator: String,
+    /// Numeric date picture, e.g. `M/d/yyyy`.
+    pub short_date: String,
+    /// Date picture with the month spelled out, e.g. `MMMM d, yyyy`.
+    pub long_date: String,
+    /// Time picture, e.g. `h:mm:ss tt`.
+    pub time_format: String,
+    /// January to December.
+    pub month_names: Vec<String>,
+    pub abbreviated_month_names: Vec<String>,
+    pub am: String,
+    pub pm: String,
+    /// ISO 4217 code of the locale's own currency.
+    pub currency_code: String,
+    pub currency_symbol: String,
+    pub currency_placement: CurrencyPlacement,
+    /// Whether a space separates a number from its percent sign.
+    pub percent_space: bool,
+}
+
+impl LocaleData {
+    /// Whether times default to a 12-hour clock.
+    pub fn hour12(&self) -> bool {
+        picture_tokens(&self.time_format)
+            .iter()
+            .any(|token| matches!(token, PictureToken::Field('h', _)))
+    }
+}
+
+/// Where locale data comes from.
+pub trait LocaleProvider: Send + Sync {
+    /// Data for a canonical BCP 47 tag, if the host has that locale.
+    fn locale(&self, tag: &str) -> Option<LocaleData>;
+
+    /// The user's languages, most preferred first.
+    fn preferred_languages(&self) -> Vec<String>;
+
+    /// The host's time zone.
+    fn time_zone(&self) -> String;
+}
+
+/// A part of a date or time picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PictureToken {
+    /// A field letter and how many times it is repeated.
+    Field(char, usize),
+    Literal(String),
+}
+
+/// Split a picture into fields and literals.
+pub fn picture_tokens(picture: &str) -> Vec<PictureToken> {
+    let mut tokens = Vec::new();
+    let mut chars = picture.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // '' is a quote; anything else runs to the closing quote
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                literal.push('\'');
+                continue;
+            }
+            for c in chars.by_ref() {
+                if c == '\'' {
+                    break;
+                }
+                literal.push(c);
+            }
+        } else if matches!(c, 'd' | 'M' | 'y' | 'g' | 'h' | 'H' | 'm' | 's' | 't') {
+            let mut count = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                count += 1;
+            }
+            if !literal.is_empty() {
+                tokens.push(PictureToken::Literal(std::mem::take(&mut literal)));
+            }
+            tokens.push(PictureToken::Field(c, count));
+        } else {
+            literal.push(c);
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(PictureToken::Literal(literal));
+    }
+    tokens
+}
+
+/// Check a BCP 47 tag's structure and normalize its case: `EN-us` becomes
+/// `en-US`, `zh-hant-tw` becomes `zh-Hant-TW`. Extensions are kept
+/// lowercase and not validated further.
+pub fn canonicalize_tag(tag: &str) -> Option<String> {
+    let mut subtags = tag.split('-');
+    let language = subtags.next()?;
+    if !matches!(language.len(), 2 | 3 | 5..=8)
+        || !language.bytes().all(|b| b.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let mut canonical = language.to_ascii_lowercase();
+    let mut in_extension = false;
+    for (index, subtag) in subtags.enumerate() {
+        if subtag.is_empty()
+            || subtag.len() > 8
+            || !subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            return None;
+        }
+        canonical.push('-');
+        in_extension |= subtag.len() == 1;
+        if !in_extension
+            && index == 0
+            && subtag.len() == 4
+            && subtag.bytes().all(|b| b.is_ascii_alphabetic())
+        {
+            canonical.push_str(&subtag[..1].to_ascii_uppercase());
+            canonical.push_str(&subtag[1..].to_ascii_lowercase());
+        } else if !in_extension
+            && index <= 1
+            && (subtag.len() == 2 && subtag.bytes().all(|b| b.is_ascii_alphabetic())
+                || subtag.len() == 3 && subtag.bytes().all(|b| b.is_ascii_digit()))
+        {
+            canonical.push_str(&subtag.to_ascii_uppercase());
+        } else {
+            canonical.push_str(&subtag.to_ascii_lowercase());
+        }
+    }
+    Some(canonical)
+}
+
+/// Data for `tag` or, failing that, for the tag with its last subtags
+/// removed in turn (`de-CH` falls back to `de`). `None` if the tag is not
+/// structurally valid or nothing matches.
+pub fn lookup(provider: &dyn LocaleProvider, tag: &str) -> Option<LocaleData> {
+    let mut tag = canonicalize_tag(tag)?;
+    loop {
+        if let Some(data) = provider.locale(&tag) {
+            return Some(data);
+        }
+        tag.truncate(tag.rfind('-')?);
+        // A lone extension singleton is not a fallback
+        if tag.rsplit('-').next().is_some_and(|last| last.len() == 1) {
+            tag.truncate(tag.rfind('-')?);
+        }
+    }
+}
+
+/// [`lookup`] the first of `requested`, then of the user's languages, that
+/// the provider has data for. Ends at the built-in `en-US`.
+pub fn negotiate(provider: &dyn LocaleProvider, requested: &[String]) -> LocaleData {
+    let preferred = provider.preferred_languages();
+    requested
+        .iter()
+        .chain(&preferred)
+        .find_map(|tag| lookup(provider, tag))
+        .unwrap_or_else(|| BuiltinLocales.locale("en-US").expect("en-US is built in"))
+}
+
+/// Data for a few common locales, for hosts without locale data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinLocales;
+
+struct Builtin {
+    tag: &'static str,
+    decimal: &'static str,
+    group: &'static str,
+    short_date: &'static str,
+    long_date: &'static str,
+    time_format: &'static str,
+    months: [&'static str; 12],
+    abbreviated_months: [&'static str; 12],
+    am_pm: (&'static str, &'static str),
+    currency: (&'static str, &'static str, CurrencyPlacement),
+    percent_space: bool,
+}
+
+const ENGLISH_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const ENGLISH_ABBREVIATED_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const BUILTIN: [Builtin; 6] = [
+    Builtin {
+        tag: "en-US",
+        decimal: ".",
+        group: ",",
+        short_date: "M/d/yyyy",
+        long_date: "MMMM d, yyyy",
+        time_format: "h:mm:ss tt",
+        months: ENGLISH_MONTHS,
+        abbreviated_months: ENGLISH_ABBREVIATED_MONTHS,
+        am_pm: ("AM", "PM"),
+        currency: ("USD", "$", CurrencyPlacement::Before),
+        percent_space: false,
+    },
+    Builtin {
+        tag: "en-GB",
+        decimal: ".",
+        group: ",",
+        short_date: "dd/MM/yyyy",
+        long_date: "d MMMM yyyy",
+        time_format: "HH:mm:ss",
+        months: ENGLISH_MONTHS,
+        abbreviated_months: ENGLISH_ABBREVIATED_MONTHS,
+        am_pm: ("am", "pm"),
+        currency: ("GBP", "£", CurrencyPlacement::Before),
+        percent_space: false,
+    },
+    Builtin {
+        tag: "de-DE",
+        decimal: ",",
+        group: ".",
+        short_date: "dd.MM.yyyy",
+        long_date: "d. MMMM yyyy",
+        time_format: "HH:mm:ss",
+        months: [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        abbreviated_months: [
+            "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+            "Dez.",
+        ],
+        am_pm: ("AM", "PM"),
+        currency: ("EUR", "€", CurrencyPlacement::AfterSpace),
+        percent_space: true,
+    },
+    Builtin {
+        tag: "fr-FR",
+        decimal: ",",
+        group: "\u{202F}",
+        short_date: "dd/MM/yyyy",
+        long_date: "d MMMM yyyy",
+        time_format: "HH:mm:ss",
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        abbreviated_months: [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+            "nov.", "déc.",
+        ],
+        am_pm: ("AM", "PM"),
+        currency: ("EUR", "€", CurrencyPlacement::AfterSpace),
+        percent_space: true,
+    },
+    Builtin {
+        tag: "es-ES",
+        decimal: ",",
+        group: ".",
+        short_date: "dd/MM/yyyy",
+        long_date: "d' de 'MMMM' de 'yyyy",
+        time_format: "H:mm:ss",
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        abbreviated_months: [
+            "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+        ],
+        am_pm: ("a. m.", "p. m."),
+        currency: ("EUR", "€", CurrencyPlacement::AfterSpace),
+        percent_space: true,
+    },
+    Builtin {
+        tag: "ja-JP",
+        decimal: ".",
+        group: ",",
+        short_date: "yyyy/MM/dd",
+        long_date: "yyyy'年'M'月'd'日'",
+        time_format: "H:mm:ss",
+        months: [
+            "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+        ],
+        abbreviated_months: [
+            "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+        ],
+        am_pm: ("午前", "午後"),
+        currency: ("JPY", "￥", CurrencyPlacement::Before),
+        percent_space: false,
+    },
+];
+
+impl LocaleProvider for BuiltinLocales {
+    /// An exact match, or for a bare language the first locale of it.
+    fn locale(&self, tag: &str) -> Option<LocaleData> {
+        let builtin = BUILTIN
+            .iter()
+            .find(|b| b.tag.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                (!tag.contains('-'))
+                    .then(|| {
+                        BUILTIN
+                            .iter()
+                            .find(|b| b.tag.split('-').next() == Some(tag))
+                    })
+                    .flatten()
+            })?;
+        let (currency_code, currency_symbol, currency_placement) = builtin.currency;
+        Some(LocaleData {
+            tag: if tag.contains('-') { builtin.tag } else { tag }.to_string(),
+            decimal_separator: builtin.decimal.to_string(),
+            group_separator: builtin.group.to_string(),
+            short_date: builtin.short_date.to_string(),
+            long_date: builtin.long_date.to_string(),
+            time_format: builtin.time_format.to_string(),
+            month_names: builtin.months.iter().map(|m| m.to_string()).collect(),
+            abbreviated_month_names: builtin
+                .abbreviated_months
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            am: builtin.am_pm.0.to_string(),
+            pm: builtin.am_pm.1.to_string(),
+            currency_code: currency_code.to_string(),
+            currency_symbol: currency_symbol.to_string(),
+            currency_placement,
+            percent_space: builtin.percent_space,
+        })
+    }
+
+    fn preferred_languages(&self) -> Vec<String> {
+        vec!["en-US".to_string()]
+    }
+
+    fn time_zone(&self) -> String {
+        "UTC".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_tag() {
+        assert_eq!(canonicalize_tag("EN-us").as_deref(), Some("en-US"));
+        assert_eq!(
+            canonicalize_tag("zh-hant-tw").as_deref(),
+            Some("zh-Hant-TW")
+        );
+        assert_eq!(canonicalize_tag("es-419").as_deref(), Some("es-419"));
+        assert_eq!(
+            canonicalize_tag("de-DE-u-CO-phonebk").as_deref(),
+            Some("de-DE-u-co-phonebk")
+        );
+        for invalid in ["", "e", "en_US", "en--US", "123", "en-toolongsubtag"] {
+            assert_eq!(canonicalize_tag(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_negotiate_falls_back() {
+        let provider = BuiltinLocales;
+        let tags = |tags: &[&str]| {
+            let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+            negotiate(&provider, &tags).tag
+        };
+        assert_eq!(tags(&["de-DE"]), "de-DE");
+        assert_eq!(tags(&["de-CH"]), "de");
+        assert_eq!(tags(&["xx-XX", "ja-jp"]), "ja-JP");
+        assert_eq!(tags(&["xx-XX"]), "en-US");
+        assert_eq!(tags(&["not a tag"]), "en-US");
+        assert_eq!(tags(&[]), "en-US");
+    }
+
+    #[test]
+    fn test_picture_tokens() {
+        assert_eq!(
+            picture_tokens("d' de 'MMMM"),
+            [
+                PictureToken::Field('d', 1),
+                PictureToken::Literal(" de ".to_string()),
+                PictureToken::Field('M', 4),
+            ]
+        );
+        assert!(BuiltinLocales.locale("en-US").unwrap().hour12());
+        assert!(!BuiltinLocales.locale("de-DE").unwrap().hour12());
+    }
+}
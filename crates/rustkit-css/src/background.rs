@@ -0,0 +1,489 @@
+//! `background-*` properties.
+//!
+//! Like the transition longhands, background longhands are kept as lists.
+//! `background-image` sets the number of layers and the other longhands
+//! repeat to match; the first layer paints on top.
+
+use crate::transition::tokens;
+use crate::{parse_color, parse_length, split_top_level_commas, Color, ComputedStyle, Length};
+
+/// A box of the element that a background layer is positioned in or
+/// clipped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundBox {
+    #[default]
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+    /// Only under the element's text; `background-clip` only.
+    Text,
+}
+
+impl BackgroundBox {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "border-box" => Some(Self::BorderBox),
+            "padding-box" => Some(Self::PaddingBox),
+            "content-box" => Some(Self::ContentBox),
+            "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// `background-attachment` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundAttachment {
+    #[default]
+    Scroll,
+    /// Positioned against the viewport rather than the element.
+    Fixed,
+    Local,
+}
+
+impl BackgroundAttachment {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "scroll" => Some(Self::Scroll),
+            "fixed" => Some(Self::Fixed),
+            "local" => Some(Self::Local),
+            _ => None,
+        }
+    }
+}
+
+/// One axis of `background-position`: an offset from the left or top
+/// edge, or from the right or bottom edge when `from_end` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PositionOffset {
+    pub from_end: bool,
+    pub offset: Length,
+}
+
+impl PositionOffset {
+    const CENTER: Self = Self {
+        from_end: false,
+        offset: Length::Percent(50.0),
+    };
+
+    fn edge(from_end: bool) -> Self {
+        Self {
+            from_end,
+            offset: Length::Zero,
+        }
+    }
+
+    fn with(mut self, offset: Length) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Where the image starts along this axis of a positioning area of
+    /// `area` pixels, for an image `image` pixels long. Percentages are of
+    /// the space left over by the image.
+    pub fn resolve(&self, area: f32, image: f32, font_size: f32) -> f32 {
+        let free = area - image;
+        let offset = self.offset.to_px(font_size, 16.0, free);
+        if self.from_end {
+            free - offset
+        } else {
+            offset
+        }
+    }
+}
+
+/// A `<bg-position>`, horizontal then vertical.
+pub type BackgroundPosition = (PositionOffset, PositionOffset);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+    Either,
+}
+
+/// The axis and edge a position keyword names.
+fn position_keyword(token: &str) -> Option<(Axis, PositionOffset)> {
+    match token {
+        "left" => Some((Axis::X, PositionOffset::edge(false))),
+        "right" => Some((Axis::X, PositionOffset::edge(true))),
+        "top" => Some((Axis::Y, PositionOffset::edge(false))),
+        "bottom" => Some((Axis::Y, PositionOffset::edge(true))),
+        "center" => Some((Axis::Either, PositionOffset::CENTER)),
+        _ => None,
+    }
+}
+
+fn position_length(token: &str) -> Option<Length> {
+    match parse_length(token)? {
+        Length::Auto => None,
+        length => Some(length),
+    }
+}
+
+/// Parse a `<bg-position>` of one to four values, such as `right 10px
+/// bottom 20px` or `center bottom 10px`.
+pub fn parse_background_position(value: &str) -> Option<BackgroundPosition> {
+    let value = value.to_ascii_lowercase();
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let x_or_y = |token: &str| {
+        position_keyword(token).or_else(|| {
+            Some((
+                Axis::Either,
+                PositionOffset::edge(false).with(position_length(token)?),
+            ))
+        })
+    };
+    let (first, second) = match tokens.as_slice() {
+        [one] => {
+            let (axis, offset) = x_or_y(one)?;
+            match axis {
+                Axis::Y => (PositionOffset::CENTER, offset),
+                _ => (offset, PositionOffset::CENTER),
+            }
+        }
+        [a, b] => {
+            let (a_axis, a) = x_or_y(a)?;
+            let (b_axis, b) = x_or_y(b)?;
+            match (a_axis, b_axis) {
+                (Axis::X, Axis::X) | (Axis::Y, Axis::Y) => return None,
+                (Axis::Y, _) | (_, Axis::X) => (b, a),
+                _ => (a, b),
+            }
+        }
+        [_, _, _] | [_, _, _, _] => {
+            // Keywords, each optionally followed by an offset from its edge
+            let (mut x, mut y, mut either) = (None, None, Vec::new());
+            let mut rest = tokens.iter().peekable();
+            while let Some(token) = rest.next() {
+                let (axis, mut offset) = position_keyword(token)?;
+                if let Some(length) = rest.peek().and_then(|t| position_length(t)) {
+                    if axis == Axis::Either {
+                        return None;
+                    }
+                    offset.offset = length;
+                    rest.next();
+                }
+                let slot = match axis {
+                    Axis::X => &mut x,
+                    Axis::Y => &mut y,
+                    Axis::Either => {
+                        either.push(offset);
+                        continue;
+                    }
+                };
+                if slot.replace(offset).is_some() {
+                    return None;
+                }
+            }
+            let mut either = either.into_iter();
+            let x = x.or_else(|| either.next())?;
+            let y = y.or_else(|| either.next())?;
+            if either.next().is_some() {
+                return None;
+            }
+            (x, y)
+        }
+        _ => return None,
+    };
+    Some((first, second))
+}
+
+/// The image of a `background-image` entry: a URL, or `None` for `none`
+/// and for images such as gradients that are not painted.
+pub fn parse_background_image(value: &str) -> Option<Option<String>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    if let Some(url) = parse_url(value) {
+        return Some(Some(url));
+    }
+    let (name, _) = value.split_once('(')?;
+    name.to_ascii_lowercase()
+        .ends_with("gradient")
+        .then_some(None)
+}
+
+/// The address inside `url(...)`, without quotes.
+fn parse_url(value: &str) -> Option<String> {
+    let inner = value
+        .get(..4)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("url("))
+        .and_then(|_| value[4..].strip_suffix(')'))?
+        .trim();
+    let unquoted = inner
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+        .unwrap_or(inner);
+    Some(unquoted.to_string())
+}
+
+fn is_repeat(token: &str) -> bool {
+    matches!(
+        token,
+        "repeat" | "repeat-x" | "repeat-y" | "no-repeat" | "space" | "round"
+    )
+}
+
+/// Longhands of a `background` shorthand, one entry per layer, and the
+/// color given in its final layer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackgroundLists {
+    pub images: Vec<Option<String>>,
+    pub positions: Vec<BackgroundPosition>,
+    pub sizes: Vec<String>,
+    pub repeats: Vec<String>,
+    pub origins: Vec<BackgroundBox>,
+    pub clips: Vec<BackgroundBox>,
+    pub attachments: Vec<BackgroundAttachment>,
+    pub color: Option<Color>,
+}
+
+/// Parse the `background` shorthand. Omitted parts take their initial
+/// values; only the final layer may set a color.
+pub fn parse_background(value: &str) -> Option<BackgroundLists> {
+    let mut lists = BackgroundLists::default();
+    let layers = split_top_level_commas(value);
+    for (index, layer) in layers.iter().enumerate() {
+        let mut image = None;
+        let (mut position, mut size) = (Vec::new(), Vec::new());
+        let (mut repeat, mut boxes) = (Vec::new(), Vec::new());
+        let mut attachment = None;
+        let mut after_slash = false;
+        // A `/` separates the position from the size, spaced or not
+        let spaced = space_slashes(layer);
+        for token in tokens(&spaced) {
+            let lower = token.to_ascii_lowercase();
+            if token == "/" {
+                if position.is_empty() || after_slash {
+                    return None;
+                }
+                after_slash = true;
+            } else if after_slash && size.len() < 2 && is_size(&lower) {
+                size.push(lower);
+            } else if position_keyword(&lower).is_some() || position_length(&lower).is_some() {
+                if after_slash {
+                    return None;
+                }
+                position.push(lower);
+            } else if is_repeat(&lower) && repeat.len() < 2 {
+                repeat.push(lower);
+            } else if let Some(value) = BackgroundBox::parse(&lower).filter(|_| boxes.len() < 2) {
+                boxes.push(value);
+            } else if let Some(value) = BackgroundAttachment::parse(&lower) {
+                if attachment.replace(value).is_some() {
+                    return None;
+                }
+            } else if let Some(value) = parse_background_image(token).filter(|_| image.is_none()) {
+                image = Some(value);
+            } else if let Some(color) =
+                parse_color(token).filter(|_| index == layers.len() - 1 && lists.color.is_none())
+            {
+                lists.color = Some(color);
+            } else {
+                return None;
+            }
+        }
+        if after_slash && size.is_empty() {
+            return None;
+        }
+        lists.images.push(image.flatten());
+        lists.positions.push(if position.is_empty() {
+            BackgroundPosition::default()
+        } else {
+            parse_background_position(&position.join(" "))?
+        });
+        lists.sizes.push(if size.is_empty() {
+            "auto".to_string()
+        } else {
+            size.join(" ")
+        });
+        lists.repeats.push(if repeat.is_empty() {
+            "repeat".to_string()
+        } else {
+            repeat.join(" ")
+        });
+        // A single box sets both the origin and the clip
+        lists
+            .origins
+            .push(boxes.first().copied().unwrap_or(BackgroundBox::PaddingBox));
+        lists
+            .clips
+            .push(boxes.last().copied().unwrap_or(BackgroundBox::BorderBox));
+        lists.attachments.push(attachment.unwrap_or_default());
+    }
+    Some(lists)
+}
+
+/// `layer` with spaces around each `/` outside parentheses, leaving the
+/// ones in `url(...)` alone.
+fn space_slashes(layer: &str) -> String {
+    let mut spaced = String::with_capacity(layer.len());
+    let mut depth = 0usize;
+    for c in layer.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '/' if depth == 0 => {
+                spaced.push_str(" / ");
+                continue;
+            }
+            _ => {}
+        }
+        spaced.push(c);
+    }
+    spaced
+}
+
+fn is_size(token: &str) -> bool {
+    matches!(token, "cover" | "contain" | "auto") || position_length(token).is_some()
+}
+
+/// One background layer, with the longhands that apply to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundLayer {
+    pub image: Option<String>,
+    pub position: BackgroundPosition,
+    /// Unparsed `<bg-size>`.
+    pub size: String,
+    /// Unparsed `<repeat-style>`.
+    pub repeat: String,
+    pub origin: BackgroundBox,
+    pub clip: BackgroundBox,
+    pub attachment: BackgroundAttachment,
+}
+
+impl ComputedStyle {
+    /// The background layers, first (topmost) first. There is one per
+    /// `background-image` entry, even for `none`.
+    pub fn background_layers(&self) -> Vec<BackgroundLayer> {
+        fn pick<T: Clone>(list: &[T], index: usize, initial: T) -> T {
+            match list.len() {
+                0 => initial,
+                len => list[index % len].clone(),
+            }
+        }
+        let count = self.background_image.len().max(1);
+        (0..count)
+            .map(|i| BackgroundLayer {
+                image: pick(&self.background_image, i, None),
+                position: pick(&self.background_position, i, BackgroundPosition::default()),
+                size: pick(&self.background_size, i, "auto".to_string()),
+                repeat: pick(&self.background_repeat, i, "repeat".to_string()),
+                origin: pick(&self.background_origin, i, BackgroundBox::PaddingBox),
+                clip: pick(&self.background_clip, i, BackgroundBox::BorderBox),
+                attachment: pick(&self.background_attachment, i, BackgroundAttachment::Scroll),
+            })
+            .collect()
+    }
+
+    /// Whether any background layer has an image to paint.
+    pub fn has_background_image(&self) -> bool {
+        self.background_image.iter().any(Option::is_some)
+    }
+
+    /// Set the longhands from a parsed `background` shorthand, resetting
+    /// those it omits.
+    pub fn set_background(&mut self, lists: BackgroundLists) {
+        self.background_color = lists.color.unwrap_or(Color::TRANSPARENT);
+        self.background_image = lists.images;
+        self.background_position = lists.positions;
+        self.background_size = lists.sizes;
+        self.background_repeat = lists.repeats;
+        self.background_origin = lists.origins;
+        self.background_clip = lists.clips;
+        self.background_attachment = lists.attachments;
+    }
+
+    /// Apply a background longhand other than `background-color`. Returns
+    /// false for other properties and for invalid values.
+    pub fn set_background_longhand(&mut self, property: &str, value: &str) -> bool {
+        fn list<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+            split_top_level_commas(value)
+                .into_iter()
+                .map(parse)
+                .collect()
+        }
+        let keyword = |value: &str| Some(value.trim().to_ascii_lowercase());
+        match property {
+            "background-image" => list(value, parse_background_image)
+                .map(|images| self.background_image = images)
+                .is_some(),
+            "background-position" => list(value, parse_background_position)
+                .map(|positions| self.background_position = positions)
+                .is_some(),
+            "background-size" => list(value, keyword)
+                .map(|sizes| self.background_size = sizes)
+                .is_some(),
+            "background-repeat" => list(value, keyword)
+                .map(|repeats| self.background_repeat = repeats)
+                .is_some(),
+            "background-origin" => list(value, BackgroundBox::parse)
+                .map(|origins| self.background_origin = origins)
+                .is_some(),
+            "background-clip" => list(value, BackgroundBox::parse)
+                .map(|clips| self.background_clip = clips)
+                .is_some(),
+            "background-attachment" => list(value, BackgroundAttachment::parse)
+                .map(|attachments| self.background_attachment = attachments)
+                .is_some(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_position_keywords_and_offsets() {
+        // A 20x20 image in a 200x100 area
+        let resolve = |value: &str| {
+            let (x, y) = parse_background_position(value).unwrap();
+            (x.resolve(200.0, 20.0, 16.0), y.resolve(100.0, 20.0, 16.0))
+        };
+        assert_eq!(resolve("center bottom 10px"), (90.0, 70.0));
+        assert_eq!(resolve("right 10px bottom 20px"), (170.0, 60.0));
+        assert_eq!(resolve("top"), (90.0, 0.0));
+        assert_eq!(resolve("bottom left"), (0.0, 80.0));
+        assert_eq!(resolve("25% 5px"), (45.0, 5.0));
+        assert_eq!(parse_background_position("left right"), None);
+        assert_eq!(parse_background_position("center 10px top"), None);
+    }
+
+    #[test]
+    fn test_background_shorthand_layers() {
+        let lists = parse_background(
+            "url(a.png) no-repeat right 5px top / 10px 20px fixed, \
+             url('b.png') content-box rgb(1, 2, 3)",
+        )
+        .unwrap();
+        assert_eq!(lists.images, [Some("a.png".into()), Some("b.png".into())]);
+        assert_eq!(lists.repeats, ["no-repeat", "repeat"]);
+        assert_eq!(lists.sizes, ["10px 20px", "auto"]);
+        assert_eq!(
+            lists.attachments,
+            [BackgroundAttachment::Fixed, BackgroundAttachment::Scroll]
+        );
+        assert_eq!(lists.origins[1], BackgroundBox::ContentBox);
+        assert_eq!(lists.clips[1], BackgroundBox::ContentBox);
+        assert_eq!(lists.color, Some(Color::from_rgb(1, 2, 3)));
+        // Only the final layer may have a color
+        assert_eq!(parse_background("red, url(b.png)"), None);
+        // Slashes in a URL don't split the layer
+        let remote = parse_background("url(https://cdn.example/a.png) center/cover").unwrap();
+        assert_eq!(remote.images, [Some("https://cdn.example/a.png".into())]);
+        assert_eq!(remote.sizes, ["cover"]);
+
+        let mut style = ComputedStyle::new();
+        style.set_background(lists);
+        assert!(style.set_background_longhand("background-clip", "padding-box"));
+        let layers = style.background_layers();
+        assert_eq!(layers.len(), 2);
+        assert!(layers.iter().all(|l| l.clip == BackgroundBox::PaddingBox));
+        assert_eq!(layers[0].position.0.offset, Length::Px(5.0));
+    }
+}
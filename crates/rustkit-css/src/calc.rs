@@ -0,0 +1,650 @@
+//! Math functions in lengths: `calc()`, `min()`, `max()` and `clamp()`.
+//!
+//! An expression is parsed into a tree, type-checked as CSS requires (one
+//! side of a product must be a number, and only a number can divide) and
+//! folded into a sum with one coefficient per unit, so `calc(100% - 32px)`
+//! keeps just a percentage and a pixel term. `min()`, `max()` and `clamp()`
+//! become bounds on that sum, and fold away entirely when their arguments
+//! are all pixels. An expression that doesn't type-check, divides by zero
+//! or can't be held this way is invalid, which drops its declaration.
+
+use crate::Length;
+use std::fmt;
+
+/// A sum of lengths with one coefficient per unit.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CalcSum {
+    pub px: f32,
+    pub em: f32,
+    pub rem: f32,
+    pub percent: f32,
+}
+
+impl CalcSum {
+    /// The sum in pixels, with percentages of `basis`.
+    pub fn to_px(&self, font_size: f32, root_font_size: f32, basis: f32) -> f32 {
+        self.px + self.em * font_size + self.rem * root_font_size + self.percent / 100.0 * basis
+    }
+
+    /// Whether the sum is in pixels alone.
+    fn is_constant(&self) -> bool {
+        self.em == 0.0 && self.rem == 0.0 && self.percent == 0.0
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            px: self.px + other.px,
+            em: self.em + other.em,
+            rem: self.rem + other.rem,
+            percent: self.percent + other.percent,
+        }
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Self {
+            px: self.px * factor,
+            em: self.em * factor,
+            rem: self.rem * factor,
+            percent: self.percent * factor,
+        }
+    }
+
+    /// The sum as a plain length, if it has at most one unit.
+    fn to_length(self) -> Option<Length> {
+        let units = [
+            (self.px, Length::Px(self.px)),
+            (self.em, Length::Em(self.em)),
+            (self.rem, Length::Rem(self.rem)),
+            (self.percent, Length::Percent(self.percent)),
+        ];
+        let mut used = units.iter().filter(|(value, _)| *value != 0.0);
+        match (used.next(), used.next()) {
+            (None, _) => Some(Length::Px(0.0)),
+            (Some((_, length)), None) => Some(*length),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CalcSum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms = [
+            (self.percent, "%"),
+            (self.em, "em"),
+            (self.rem, "rem"),
+            (self.px, "px"),
+        ];
+        let mut first = true;
+        for (value, unit) in terms.into_iter().filter(|(value, _)| *value != 0.0) {
+            match (first, value < 0.0) {
+                (true, _) => write!(f, "{}{}", value, unit)?,
+                (false, true) => write!(f, " - {}{}", -value, unit)?,
+                (false, false) => write!(f, " + {}{}", value, unit)?,
+            }
+            first = false;
+        }
+        if first {
+            write!(f, "0px")?;
+        }
+        Ok(())
+    }
+}
+
+/// A length given by a math function: a sum, kept at or above `min` and at
+/// or below `max`, `min` winning when they cross.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalcLength {
+    pub sum: CalcSum,
+    pub min: Option<CalcSum>,
+    pub max: Option<CalcSum>,
+}
+
+impl CalcLength {
+    fn new(sum: CalcSum) -> Self {
+        Self {
+            sum,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// The length in pixels, with percentages of `basis`.
+    pub fn to_px(&self, font_size: f32, root_font_size: f32, basis: f32) -> f32 {
+        let resolve = |sum: &CalcSum| sum.to_px(font_size, root_font_size, basis);
+        let mut px = resolve(&self.sum);
+        if let Some(max) = &self.max {
+            px = px.min(resolve(max));
+        }
+        if let Some(min) = &self.min {
+            px = px.max(resolve(min));
+        }
+        px
+    }
+
+    /// Whether any part of the length is a percentage, which can't be
+    /// resolved without a basis.
+    pub fn has_percent(&self) -> bool {
+        [Some(self.sum), self.min, self.max]
+            .iter()
+            .flatten()
+            .any(|sum| sum.percent != 0.0)
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// The pixels of an unbounded constant.
+    fn constant(&self) -> Option<f32> {
+        (!self.is_bounded() && self.sum.is_constant()).then_some(self.sum.px)
+    }
+
+    /// `self + other`. Adding to a bounded length moves its bounds along;
+    /// two bounded lengths can't be added.
+    fn add(self, other: Self) -> Option<Self> {
+        let (bounded, plain) = match (self.is_bounded(), other.is_bounded()) {
+            (true, true) => return None,
+            (true, false) => (self, other),
+            _ => (other, self),
+        };
+        Some(Self {
+            sum: bounded.sum.add(plain.sum),
+            min: bounded.min.map(|min| min.add(plain.sum)),
+            max: bounded.max.map(|max| max.add(plain.sum)),
+        })
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        let (min, max) = match factor < 0.0 {
+            true => (self.max, self.min),
+            false => (self.min, self.max),
+        };
+        Self {
+            sum: self.sum.scale(factor),
+            min: min.map(|min| min.scale(factor)),
+            max: max.map(|max| max.scale(factor)),
+        }
+    }
+
+    /// The smaller of two lengths, as the first bounded above by the
+    /// second.
+    fn min(self, other: Self) -> Option<Self> {
+        if let (Some(a), Some(b)) = (self.constant(), other.constant()) {
+            return Some(Self::new(CalcSum {
+                px: a.min(b),
+                ..CalcSum::default()
+            }));
+        }
+        match (self.is_bounded(), other.is_bounded()) {
+            (false, false) => Some(Self {
+                max: Some(other.sum),
+                ..self
+            }),
+            // A lower bound applies after the upper one, so only another
+            // upper bound can join
+            (true, false) | (false, true) => {
+                let (bounded, plain) = match self.is_bounded() {
+                    true => (self, other),
+                    false => (other, self),
+                };
+                let max = bounded.max?;
+                let limit = plain.constant()?;
+                (bounded.min.is_none() && max.is_constant()).then_some(Self {
+                    max: Some(CalcSum {
+                        px: max.px.min(limit),
+                        ..CalcSum::default()
+                    }),
+                    ..bounded
+                })
+            }
+            (true, true) => None,
+        }
+    }
+
+    /// The larger of two lengths, as the first bounded below by the second.
+    fn max(self, other: Self) -> Option<Self> {
+        if let (Some(a), Some(b)) = (self.constant(), other.constant()) {
+            return Some(Self::new(CalcSum {
+                px: a.max(b),
+                ..CalcSum::default()
+            }));
+        }
+        let (bounded, plain) = match (self.is_bounded(), other.is_bounded()) {
+            (true, true) => return None,
+            (true, false) => (self, other),
+            _ => (other, self),
+        };
+        match bounded.min {
+            None => Some(Self {
+                min: Some(plain.sum),
+                ..bounded
+            }),
+            Some(min) => {
+                let (min, limit) = (min.is_constant().then_some(min.px)?, plain.constant()?);
+                Some(Self {
+                    min: Some(CalcSum {
+                        px: min.max(limit),
+                        ..CalcSum::default()
+                    }),
+                    ..bounded
+                })
+            }
+        }
+    }
+
+    /// The length as a plain one where nothing is lost.
+    fn simplify(self) -> Length {
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if self.sum.is_constant() && min.is_constant() && max.is_constant() {
+                return Length::Px(self.sum.px.min(max.px).max(min.px));
+            }
+        }
+        match self.is_bounded() {
+            false => self.sum.to_length().unwrap_or(Length::Calc(self)),
+            true => Length::Calc(self),
+        }
+    }
+}
+
+impl fmt::Display for CalcLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.min, &self.max) {
+            (None, None) => write!(f, "calc({})", self.sum),
+            (Some(min), Some(max)) => write!(f, "clamp({}, {}, {})", min, self.sum, max),
+            (None, Some(max)) => write!(f, "min({}, {})", self.sum, max),
+            (Some(min), None) => write!(f, "max({}, {})", self.sum, min),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    /// A number with its unit, `%` or none.
+    Number(f32, &'a str),
+    /// A function name and its opening parenthesis.
+    Function(&'a str),
+    Open,
+    Close,
+    Comma,
+    Operator(char),
+}
+
+/// Split `input` into tokens, each with whether whitespace came before it.
+fn tokenize(input: &str) -> Option<Vec<(Token<'_>, bool)>> {
+    let bytes = input.as_bytes();
+    let mut tokens: Vec<(Token, bool)> = Vec::new();
+    let mut space = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let starts_number = |at: usize| {
+            bytes
+                .get(at)
+                .is_some_and(|b| b.is_ascii_digit() || *b == b'.')
+        };
+        // A sign is part of a number only where no operand came before
+        let operand_before = matches!(tokens.last(), Some((Token::Number(..) | Token::Close, _)));
+        let token = match c {
+            c if c.is_ascii_whitespace() => {
+                space = true;
+                i += 1;
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '*' | '/' => Token::Operator(c),
+            '+' | '-' if operand_before || !starts_number(i + 1) => Token::Operator(c),
+            c if c.is_ascii_digit() || c == '.' || c == '+' || c == '-' => {
+                let start = i;
+                i += 1;
+                while starts_number(i) {
+                    i += 1;
+                }
+                let number = input[start..i].parse::<f32>().ok()?;
+                let unit_start = i;
+                if bytes.get(i) == Some(&b'%') {
+                    i += 1;
+                } else {
+                    while bytes.get(i).is_some_and(u8::is_ascii_alphabetic) {
+                        i += 1;
+                    }
+                }
+                tokens.push((Token::Number(number, &input[unit_start..i]), space));
+                space = false;
+                continue;
+            }
+            c if c.is_ascii_alphabetic() => {
+                let start = i;
+                while bytes
+                    .get(i)
+                    .is_some_and(|b| b.is_ascii_alphabetic() || *b == b'-')
+                {
+                    i += 1;
+                }
+                if bytes.get(i) != Some(&b'(') {
+                    return None;
+                }
+                tokens.push((Token::Function(&input[start..i]), space));
+                space = false;
+                i += 1;
+                continue;
+            }
+            _ => return None,
+        };
+        tokens.push((token, space));
+        space = false;
+        i += 1;
+    }
+    Some(tokens)
+}
+
+/// A parsed math expression.
+#[derive(Debug)]
+enum Expr {
+    Number(f32),
+    Length(CalcSum),
+    Add(Box<Expr>, Box<Expr>),
+    Subtract(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    Divide(Box<Expr>, Box<Expr>),
+    Min(Vec<Expr>),
+    Max(Vec<Expr>),
+    Clamp(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// The type an expression has once checked.
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Number(f32),
+    Length(CalcLength),
+}
+
+impl Expr {
+    fn evaluate(&self) -> Option<Value> {
+        Some(match self {
+            Self::Number(number) => Value::Number(*number),
+            Self::Length(sum) => Value::Length(CalcLength::new(*sum)),
+            Self::Add(a, b) => Self::sum(a, b, 1.0)?,
+            Self::Subtract(a, b) => Self::sum(a, b, -1.0)?,
+            Self::Multiply(a, b) => match (a.evaluate()?, b.evaluate()?) {
+                (Value::Number(a), Value::Number(b)) => Value::Number(a * b),
+                (Value::Number(factor), Value::Length(length))
+                | (Value::Length(length), Value::Number(factor)) => {
+                    Value::Length(length.scale(factor))
+                }
+                (Value::Length(_), Value::Length(_)) => return None,
+            },
+            Self::Divide(a, b) => {
+                let Value::Number(divisor) = b.evaluate()? else {
+                    return None;
+                };
+                if divisor == 0.0 {
+                    return None;
+                }
+                match a.evaluate()? {
+                    Value::Number(a) => Value::Number(a / divisor),
+                    Value::Length(length) => Value::Length(length.scale(1.0 / divisor)),
+                }
+            }
+            Self::Min(args) | Self::Max(args) => {
+                let is_min = matches!(self, Self::Min(_));
+                let values = args
+                    .iter()
+                    .map(Self::evaluate)
+                    .collect::<Option<Vec<_>>>()?;
+                // All numbers or all lengths
+                if let Some(numbers) = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Number(number) => Some(*number),
+                        Value::Length(_) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                {
+                    let fold = |a: f32, b: f32| if is_min { a.min(b) } else { a.max(b) };
+                    return numbers.into_iter().reduce(fold).map(Value::Number);
+                }
+                let mut lengths = values.into_iter().map(|value| match value {
+                    Value::Length(length) => Some(length),
+                    Value::Number(_) => None,
+                });
+                let first = lengths.next()??;
+                let length = lengths.try_fold(first, |a, b| match is_min {
+                    true => a.min(b?),
+                    false => a.max(b?),
+                })?;
+                Value::Length(length)
+            }
+            Self::Clamp(min, value, max) => {
+                let (min, value, max) = (min.length()?, value.length()?, max.length()?);
+                Value::Length(value.min(max)?.max(min)?)
+            }
+        })
+    }
+
+    /// `a + sign * b`, both numbers or both lengths.
+    fn sum(a: &Expr, b: &Expr, sign: f32) -> Option<Value> {
+        match (a.evaluate()?, b.evaluate()?) {
+            (Value::Number(a), Value::Number(b)) => Some(Value::Number(a + sign * b)),
+            (Value::Length(a), Value::Length(b)) => a.add(b.scale(sign)).map(Value::Length),
+            _ => None,
+        }
+    }
+
+    /// The expression's value if it is a length.
+    fn length(&self) -> Option<CalcLength> {
+        match self.evaluate()? {
+            Value::Length(length) => Some(length),
+            Value::Number(_) => None,
+        }
+    }
+}
+
+/// Recursive descent over the tokens of a math function.
+struct Parser<'a> {
+    tokens: Vec<(Token<'a>, bool)>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).map(|(token, _)| *token)
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token<'a>) -> Option<()> {
+        (self.next()? == token).then_some(())
+    }
+
+    /// Whether whitespace surrounds the token at `position`, as `+` and `-`
+    /// need.
+    fn spaced(&self, position: usize) -> bool {
+        self.tokens.get(position).is_some_and(|(_, space)| *space)
+            && self
+                .tokens
+                .get(position + 1)
+                .is_some_and(|(_, space)| *space)
+    }
+
+    fn sum(&mut self) -> Option<Expr> {
+        let mut expr = self.product()?;
+        while let Some(Token::Operator(op @ ('+' | '-'))) = self.peek() {
+            if !self.spaced(self.position) {
+                return None;
+            }
+            self.position += 1;
+            let right = Box::new(self.product()?);
+            expr = match op {
+                '+' => Expr::Add(Box::new(expr), right),
+                _ => Expr::Subtract(Box::new(expr), right),
+            };
+        }
+        Some(expr)
+    }
+
+    fn product(&mut self) -> Option<Expr> {
+        let mut expr = self.value()?;
+        while let Some(Token::Operator(op @ ('*' | '/'))) = self.peek() {
+            self.position += 1;
+            let right = Box::new(self.value()?);
+            expr = match op {
+                '*' => Expr::Multiply(Box::new(expr), right),
+                _ => Expr::Divide(Box::new(expr), right),
+            };
+        }
+        Some(expr)
+    }
+
+    fn value(&mut self) -> Option<Expr> {
+        match self.next()? {
+            Token::Number(number, "") => Some(Expr::Number(number)),
+            Token::Number(number, unit) => {
+                let unit = unit.to_ascii_lowercase();
+                let sum = match unit.as_str() {
+                    "px" => CalcSum {
+                        px: number,
+                        ..CalcSum::default()
+                    },
+                    "em" => CalcSum {
+                        em: number,
+                        ..CalcSum::default()
+                    },
+                    "rem" => CalcSum {
+                        rem: number,
+                        ..CalcSum::default()
+                    },
+                    "%" => CalcSum {
+                        percent: number,
+                        ..CalcSum::default()
+                    },
+                    _ => return None,
+                };
+                Some(Expr::Length(sum))
+            }
+            Token::Open => {
+                let expr = self.sum()?;
+                self.expect(Token::Close)?;
+                Some(expr)
+            }
+            Token::Function(name) => self.function(name),
+            _ => None,
+        }
+    }
+
+    /// The arguments and closing parenthesis of function `name`.
+    fn function(&mut self, name: &str) -> Option<Expr> {
+        let mut args = vec![self.sum()?];
+        while self.peek() == Some(Token::Comma) {
+            self.position += 1;
+            args.push(self.sum()?);
+        }
+        self.expect(Token::Close)?;
+        match (name.to_ascii_lowercase().as_str(), args.len()) {
+            ("calc", 1) => args.pop(),
+            ("min", _) => Some(Expr::Min(args)),
+            ("max", _) => Some(Expr::Max(args)),
+            ("clamp", 3) => {
+                let max = Box::new(args.pop()?);
+                let value = Box::new(args.pop()?);
+                let min = Box::new(args.pop()?);
+                Some(Expr::Clamp(min, value, max))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse a `calc()`, `min()`, `max()` or `clamp()` length. One that comes
+/// down to a single unit is returned as a plain length, and anything
+/// invalid as `None`.
+pub fn parse_calc(value: &str) -> Option<Length> {
+    let mut parser = Parser {
+        tokens: tokenize(value.trim())?,
+        position: 0,
+    };
+    let Some(Token::Function(_)) = parser.peek() else {
+        return None;
+    };
+    let expr = parser.value()?;
+    if parser.position != parser.tokens.len() {
+        return None;
+    }
+    match expr.evaluate()? {
+        Value::Length(length) if length.to_px(0.0, 0.0, 0.0).is_finite() => Some(length.simplify()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn px(value: &str, basis: f32) -> f32 {
+        parse_calc(value)
+            .unwrap_or_else(|| panic!("{} is invalid", value))
+            .to_px(16.0, 16.0, basis)
+    }
+
+    #[test]
+    fn test_terms_combine() {
+        let Some(Length::Calc(calc)) = parse_calc("calc(100% - 32px)") else {
+            panic!("not a calc length");
+        };
+        assert_eq!(
+            calc.sum,
+            CalcSum {
+                px: -32.0,
+                percent: 100.0,
+                ..CalcSum::default()
+            }
+        );
+        assert_eq!(calc.to_px(16.0, 16.0, 500.0), 468.0);
+        assert_eq!(calc.to_string(), "calc(100% - 32px)");
+
+        assert_eq!(parse_calc("calc(2 * 8px + 4px)"), Some(Length::Px(20.0)));
+        assert_eq!(parse_calc("calc(50% / 2)"), Some(Length::Percent(25.0)));
+        assert_eq!(px("calc((calc(50% + 10px) - 1em) * 2)", 200.0), 188.0);
+        assert_eq!(px("calc(-1 * (10px - 2rem))", 0.0), 22.0);
+    }
+
+    #[test]
+    fn test_comparison_functions() {
+        assert_eq!(px("clamp(10px, 5%, 20px)", 100.0), 10.0);
+        assert_eq!(px("clamp(10px, 5%, 20px)", 300.0), 15.0);
+        assert_eq!(px("clamp(10px, 5%, 20px)", 1000.0), 20.0);
+        assert_eq!(px("min(50%, 300px)", 800.0), 300.0);
+        assert_eq!(px("max(50%, 300px)", 800.0), 400.0);
+        assert_eq!(px("calc(min(50%, 300px) + 10px)", 400.0), 210.0);
+        assert_eq!(parse_calc("max(4px, 10px, 2px)"), Some(Length::Px(10.0)));
+        let Some(Length::Calc(calc)) = parse_calc("clamp(1em, 50%, 2em)") else {
+            panic!("not a calc length");
+        };
+        assert_eq!(calc.to_string(), "clamp(1em, 50%, 2em)");
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for value in [
+            "calc(10px + 5)",
+            "calc(10px * 2px)",
+            "calc(10px / 2px)",
+            "calc(10px / 0)",
+            "calc(10px -5px)",
+            "calc(10px+5px)",
+            "calc(5)",
+            "calc(10px",
+            "calc(10deg)",
+            "clamp(1px, 2px)",
+            "calc(1px) 2px",
+            "var(--x)",
+        ] {
+            assert_eq!(parse_calc(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Custom properties (`--*`) and `var()` substitution.
+//!
+//! Custom properties keep their specified text and inherit. References
+//! between custom properties on the same element are resolved when they
+//! cascade; a property in a reference cycle becomes invalid, as does one
+//! whose substitution grows past [`MAX_SUBSTITUTED_LEN`]. Other
+//! declarations substitute `var()` against the element's resolved custom
+//! properties before their value is parsed, so shorthands work too.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Longest value a substitution may produce, so a few properties that each
+/// reference another several times can't expand exponentially.
+pub const MAX_SUBSTITUTED_LEN: usize = 64 * 1024;
+
+/// How deeply `var()` fallbacks may nest.
+const MAX_FALLBACK_DEPTH: usize = 32;
+
+/// An element's custom properties, shared with its descendants until one
+/// of them declares its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustomProperties(Arc<BTreeMap<String, String>>);
+
+impl CustomProperties {
+    /// Whether `name` names a custom property.
+    pub fn is_custom(name: &str) -> bool {
+        name.starts_with("--") && name.len() > 2
+    }
+
+    /// The value of `name`, e.g. `--accent`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The custom properties of an element that inherits `parent` and
+    /// declares `declarations` (custom ones only, in cascade order).
+    pub fn cascade<'a>(
+        parent: &CustomProperties,
+        declarations: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> CustomProperties {
+        let mut own: HashMap<&str, &str> = HashMap::new();
+        for (name, value) in declarations {
+            own.insert(name, value.trim());
+        }
+        if own.is_empty() {
+            return parent.clone();
+        }
+
+        let mut resolver = Resolver {
+            own: &own,
+            parent,
+            resolved: HashMap::new(),
+            stack: Vec::new(),
+            cyclic: HashSet::new(),
+        };
+        let mut properties = (*parent.0).clone();
+        let mut names: Vec<&str> = own.keys().copied().collect();
+        names.sort_unstable();
+        for name in names {
+            match resolver.resolve(name) {
+                Some(value) => properties.insert(name.to_string(), value),
+                None => properties.remove(name),
+            };
+        }
+        CustomProperties(Arc::new(properties))
+    }
+
+    /// `value` with its `var()` references replaced. `None` when a
+    /// reference has no value and no fallback, which makes the declaration
+    /// invalid.
+    pub fn substitute(&self, value: &str) -> Option<String> {
+        substitute(value, &mut |name| self.get(name).map(str::to_string), 0)
+    }
+}
+
+/// Whether `value` references a custom property.
+pub fn contains_var(value: &str) -> bool {
+    value.to_ascii_lowercase().contains("var(")
+}
+
+enum Resolved {
+    InProgress,
+    Done(Option<String>),
+}
+
+/// Resolves one element's custom properties against each other, depth
+/// first, marking every property on a cycle invalid.
+struct Resolver<'a> {
+    own: &'a HashMap<&'a str, &'a str>,
+    parent: &'a CustomProperties,
+    resolved: HashMap<&'a str, Resolved>,
+    stack: Vec<&'a str>,
+    cyclic: HashSet<&'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    fn resolve(&mut self, name: &str) -> Option<String> {
+        let Some((&name, &raw)) = self.own.get_key_value(name) else {
+            return self.parent.get(name).map(str::to_string);
+        };
+        match self.resolved.get(name) {
+            Some(Resolved::Done(value)) => return value.clone(),
+            Some(Resolved::InProgress) => {
+                let start = self.stack.iter().rposition(|n| *n == name).unwrap_or(0);
+                self.cyclic.extend(self.stack[start..].iter().copied());
+                return None;
+            }
+            None => {}
+        }
+
+        let value = match raw.to_ascii_lowercase().as_str() {
+            "inherit" | "unset" => self.parent.get(name).map(str::to_string),
+            "initial" => None,
+            _ if contains_var(raw) => {
+                self.resolved.insert(name, Resolved::InProgress);
+                self.stack.push(name);
+                let value = substitute(raw, &mut |n| self.resolve(n), 0);
+                self.stack.pop();
+                value
+            }
+            _ => Some(raw.to_string()),
+        };
+        let value = value.filter(|_| !self.cyclic.contains(name));
+        self.resolved.insert(name, Resolved::Done(value.clone()));
+        value
+    }
+}
+
+fn substitute(
+    value: &str,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+    depth: usize,
+) -> Option<String> {
+    if depth > MAX_FALLBACK_DEPTH {
+        return None;
+    }
+    let bytes = value.as_bytes();
+    let mut out = String::with_capacity(value.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            b'v' | b'V'
+                if bytes.len() - i >= 4
+                    && bytes[i..i + 4].eq_ignore_ascii_case(b"var(")
+                    && (i == 0 || !is_name_byte(bytes[i - 1])) =>
+            {
+                let args_start = i + 4;
+                let end = matching_paren(bytes, args_start)?;
+                let args = &value[args_start..end];
+                let (name, fallback) = match top_level_comma(args) {
+                    Some(comma) => (args[..comma].trim(), Some(&args[comma + 1..])),
+                    None => (args.trim(), None),
+                };
+                if !CustomProperties::is_custom(name) {
+                    return None;
+                }
+                let replacement = match lookup(name) {
+                    Some(replacement) => replacement,
+                    None => substitute(fallback?.trim(), lookup, depth + 1)?,
+                };
+                out.push_str(&value[copied..i]);
+                out.push_str(&replacement);
+                if out.len() > MAX_SUBSTITUTED_LEN {
+                    return None;
+                }
+                i = end + 1;
+                copied = i;
+            }
+            _ => i += 1,
+        }
+    }
+    out.push_str(&value[copied.min(value.len())..]);
+    (out.len() <= MAX_SUBSTITUTED_LEN).then_some(out)
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'
+}
+
+/// Index of the `)` closing the block that starts at `start`, skipping
+/// nested blocks and strings.
+fn matching_paren(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Some(i),
+            b')' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index of the first comma in `args` outside nested blocks and strings.
+fn top_level_comma(args: &str) -> Option<usize> {
+    let bytes = args.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            b',' if depth == 0 => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cascade(parent: &CustomProperties, declarations: &[(&str, &str)]) -> CustomProperties {
+        CustomProperties::cascade(parent, declarations.iter().copied())
+    }
+
+    #[test]
+    fn test_substitute_with_fallbacks() {
+        let props = cascade(
+            &CustomProperties::default(),
+            &[("--gap", "4px"), ("--Color", "red")],
+        );
+        assert_eq!(
+            props.substitute("var(--gap) var(--gap)").as_deref(),
+            Some("4px 4px")
+        );
+        assert_eq!(
+            props.substitute("1px solid VAR(--Color)").as_deref(),
+            Some("1px solid red")
+        );
+        // Names are case-sensitive
+        assert_eq!(
+            props.substitute("var(--color, blue)").as_deref(),
+            Some("blue")
+        );
+        assert_eq!(
+            props
+                .substitute("var(--missing, var(--also-missing, rgb(1, 2, 3)))")
+                .as_deref(),
+            Some("rgb(1, 2, 3)")
+        );
+        assert_eq!(props.substitute("var(--missing)"), None);
+        assert_eq!(
+            props.substitute("'var(--gap)'").as_deref(),
+            Some("'var(--gap)'")
+        );
+        assert_eq!(props.substitute("var(gap)"), None);
+    }
+
+    #[test]
+    fn test_cascade_inherits_and_resolves() {
+        let root = cascade(
+            &CustomProperties::default(),
+            &[("--a", "1px"), ("--b", "2px")],
+        );
+        let child = cascade(&root, &[("--b", "var(--a) 3px"), ("--c", "var(--b)")]);
+        assert_eq!(child.get("--a"), Some("1px"));
+        assert_eq!(child.get("--b"), Some("1px 3px"));
+        assert_eq!(child.get("--c"), Some("1px 3px"));
+
+        let reset = cascade(&child, &[("--a", "initial"), ("--b", "inherit")]);
+        assert_eq!(reset.get("--a"), None);
+        assert_eq!(reset.get("--b"), Some("1px 3px"));
+    }
+
+    #[test]
+    fn test_cycles_are_invalid() {
+        let props = cascade(
+            &CustomProperties::default(),
+            &[
+                ("--a", "var(--b, 1px)"),
+                ("--b", "var(--a, 2px)"),
+                ("--c", "var(--a, 3px)"),
+                ("--self", "var(--self)"),
+            ],
+        );
+        assert_eq!(props.get("--a"), None);
+        assert_eq!(props.get("--b"), None);
+        assert_eq!(props.get("--self"), None);
+        assert_eq!(props.get("--c"), Some("3px"));
+    }
+
+    #[test]
+    fn test_expansion_is_bounded() {
+        let mut declarations = vec![("--v0".to_string(), "x".repeat(64))];
+        for n in 1..20 {
+            let reference = format!("var(--v{})", n - 1);
+            declarations.push((format!("--v{n}"), format!("{reference} {reference}")));
+        }
+        let props = CustomProperties::cascade(
+            &CustomProperties::default(),
+            declarations
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        assert!(props.get("--v5").is_some());
+        assert_eq!(props.get("--v19"), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! `filter` and `backdrop-filter`.
+//!
+//! Both take a list of filter functions applied in order. `blur()` and the
+//! color functions are supported; a list naming any other function is
+//! invalid as a whole, so the declaration is dropped.
+
+use crate::transition::tokens;
+
+/// One function of a filter list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterFunction {
+    /// Gaussian blur; the standard deviation in px.
+    Blur(f32),
+    /// Amounts are fractions, 1 leaving the input unchanged.
+    Brightness(f32),
+    Contrast(f32),
+    /// At most 1.
+    Grayscale(f32),
+    /// At most 1.
+    Opacity(f32),
+    Saturate(f32),
+}
+
+/// A filter list; empty for `none`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterChain {
+    pub functions: Vec<FilterFunction>,
+}
+
+impl FilterChain {
+    /// Parse a `filter` or `backdrop-filter` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        if value == "none" {
+            return Some(Self::default());
+        }
+        let functions = tokens(&value)
+            .into_iter()
+            .map(FilterFunction::parse)
+            .collect::<Option<Vec<_>>>()?;
+        (!functions.is_empty()).then_some(Self { functions })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// How far the chain's blurs spread content past its edges, in px.
+    pub fn outset(&self) -> f32 {
+        self.functions
+            .iter()
+            .map(|function| match function {
+                FilterFunction::Blur(sigma) => 3.0 * sigma,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// The chain with blurs scaled by `factor`, as content drawn at
+    /// `factor` times its size is.
+    pub fn scaled(&self, factor: f32) -> Self {
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| match function {
+                FilterFunction::Blur(sigma) => FilterFunction::Blur(sigma * factor),
+                other => *other,
+            })
+            .collect();
+        Self { functions }
+    }
+}
+
+impl FilterFunction {
+    fn parse(function: &str) -> Option<Self> {
+        let (name, argument) = function.strip_suffix(')')?.split_once('(')?;
+        let argument = argument.trim();
+        let argument = (!argument.is_empty()).then_some(argument);
+        match name {
+            "blur" => argument.map_or(Some(0.0), blur_radius).map(Self::Blur),
+            "brightness" => amount(argument).map(Self::Brightness),
+            "contrast" => amount(argument).map(Self::Contrast),
+            "grayscale" => amount(argument).map(|a| Self::Grayscale(a.min(1.0))),
+            "opacity" => amount(argument).map(|a| Self::Opacity(a.min(1.0))),
+            "saturate" => amount(argument).map(Self::Saturate),
+            _ => None,
+        }
+    }
+}
+
+fn blur_radius(value: &str) -> Option<f32> {
+    let radius = match value {
+        "0" => 0.0,
+        value => value.strip_suffix("px")?.parse::<f32>().ok()?,
+    };
+    (radius >= 0.0).then_some(radius)
+}
+
+/// A non-negative number or percentage; 1 when omitted.
+fn amount(value: Option<&str>) -> Option<f32> {
+    let amount = match value {
+        None => 1.0,
+        Some(value) => match value.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => value.parse::<f32>().ok()?,
+        },
+    };
+    (amount >= 0.0).then_some(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter_lists() {
+        let chain =
+            FilterChain::parse("blur(4px) Grayscale(100%) brightness(1.5) contrast()").unwrap();
+        assert_eq!(
+            chain.functions,
+            [
+                FilterFunction::Blur(4.0),
+                FilterFunction::Grayscale(1.0),
+                FilterFunction::Brightness(1.5),
+                FilterFunction::Contrast(1.0),
+            ]
+        );
+        assert_eq!(chain.outset(), 12.0);
+        assert_eq!(
+            FilterChain::parse("opacity(250%) saturate(0)")
+                .unwrap()
+                .functions,
+            [FilterFunction::Opacity(1.0), FilterFunction::Saturate(0.0)]
+        );
+        assert!(FilterChain::parse("none").unwrap().is_empty());
+
+        assert_eq!(FilterChain::parse(""), None);
+        assert_eq!(FilterChain::parse("blur(2em)"), None);
+        assert_eq!(FilterChain::parse("brightness(-1)"), None);
+        // One unsupported function invalidates the list
+        assert_eq!(FilterChain::parse("grayscale(1) hue-rotate(90deg)"), None);
+    }
+}
@@ -13,6 +13,31 @@ use thiserror::Error;
 use tracing::debug;
 use rustkit_cssparser::parse_stylesheet;
 
+mod background;
+mod calc;
+mod custom_properties;
+mod filter;
+mod logical;
+mod media;
+mod transition;
+
+pub use background::{
+    parse_background, parse_background_image, parse_background_position, BackgroundAttachment,
+    BackgroundBox, BackgroundLayer, BackgroundLists, BackgroundPosition, PositionOffset,
+};
+pub use calc::{parse_calc, CalcLength, CalcSum};
+pub use custom_properties::{contains_var, CustomProperties, MAX_SUBSTITUTED_LEN};
+pub use filter::{FilterChain, FilterFunction};
+pub use logical::{
+    expand_logical_shorthand, parse_direction, parse_writing_mode, physical_property, LogicalSide,
+    PhysicalSide,
+};
+pub use media::{ColorScheme, MediaEnvironment, MediaQueryList};
+pub use transition::{
+    parse_time, parse_time_list, parse_transition, split_top_level_commas, TransitionLists,
+    TransitionSpec, TranslateScale,
+};
+
 /// Errors that can occur in CSS operations.
 #[derive(Error, Debug)]
 pub enum CssError {
@@ -93,6 +118,8 @@ pub enum Length {
     /// Zero.
     #[default]
     Zero,
+    /// A `calc()`, `min()`, `max()` or `clamp()` expression.
+    Calc(CalcLength),
 }
 
 impl Length {
@@ -105,6 +132,7 @@ impl Length {
             Length::Percent(pct) => pct / 100.0 * container_size,
             Length::Auto => 0.0, // Context-dependent
             Length::Zero => 0.0,
+            Length::Calc(calc) => calc.to_px(font_size, root_font_size, container_size),
         }
     }
 }
@@ -116,10 +144,13 @@ pub enum Display {
     Block,
     Inline,
     InlineBlock,
+    /// A block that establishes a new block formatting context.
+    FlowRoot,
     Flex,
     InlineFlex,
     Grid,
     InlineGrid,
+    TableCell,
     None,
 }
 
@@ -670,6 +701,14 @@ pub enum ScrollbarWidth {
     None,
 }
 
+/// Whether a box may be chosen as a scroll anchor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowAnchor {
+    #[default]
+    Auto,
+    None,
+}
+
 /// Scrollbar gutter.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ScrollbarGutter {
@@ -724,6 +763,161 @@ pub enum TextDecorationStyle {
     Wavy,
 }
 
+/// Outline style (`outline-style`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutlineStyle {
+    #[default]
+    None,
+    /// UA-chosen focus ring style; drawn solid.
+    Auto,
+    Solid,
+    Dotted,
+    Dashed,
+    Double,
+}
+
+impl OutlineStyle {
+    /// Parse an `outline-style` keyword; the 3D border styles draw solid.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" | "hidden" => Some(OutlineStyle::None),
+            "auto" => Some(OutlineStyle::Auto),
+            "solid" | "groove" | "ridge" | "inset" | "outset" => Some(OutlineStyle::Solid),
+            "dotted" => Some(OutlineStyle::Dotted),
+            "dashed" => Some(OutlineStyle::Dashed),
+            "double" => Some(OutlineStyle::Double),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a box may be split between columns or pages (`break-inside`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BreakInside {
+    #[default]
+    Auto,
+    Avoid,
+}
+
+impl BreakInside {
+    /// Parse a `break-inside` or legacy `page-break-inside` keyword; every
+    /// `avoid-*` value avoids both column and page breaks.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(BreakInside::Auto),
+            "avoid" | "avoid-column" | "avoid-page" | "avoid-region" => Some(BreakInside::Avoid),
+            _ => None,
+        }
+    }
+}
+
+/// Break before or after a box (`break-before`, `break-after`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BreakBetween {
+    #[default]
+    Auto,
+    Avoid,
+    /// Force a page break.
+    Page,
+}
+
+impl BreakBetween {
+    /// Parse a `break-before`/`break-after` keyword. Column and region
+    /// breaks are not forced; `left`, `right`, `recto` and `verso` force a
+    /// plain page break.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" | "column" | "region" | "avoid-column" | "avoid-region" => {
+                Some(BreakBetween::Auto)
+            }
+            "avoid" | "avoid-page" => Some(BreakBetween::Avoid),
+            "page" | "left" | "right" | "recto" | "verso" => Some(BreakBetween::Page),
+            _ => None,
+        }
+    }
+
+    /// Parse a legacy `page-break-before`/`page-break-after` keyword.
+    pub fn parse_legacy(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "always" => Some(BreakBetween::Page),
+            "column" | "recto" | "verso" => None,
+            other => Self::parse(other),
+        }
+    }
+}
+
+/// Preferred ratio of a box's width to its height (`aspect-ratio`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AspectRatio {
+    /// Replaced elements keep their natural ratio; other boxes have none.
+    #[default]
+    Auto,
+    /// `auto && <ratio>`: the natural ratio of a replaced element that has
+    /// one, else this.
+    AutoOr(f32),
+    /// `<ratio>`, even for replaced elements.
+    Ratio(f32),
+}
+
+impl AspectRatio {
+    /// Parse `auto`, a ratio such as `16 / 9` or `1.5`, or both in either
+    /// order. A ratio with a zero term is degenerate and acts as `auto`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let (auto, ratio) = match (value.strip_prefix("auto"), value.strip_suffix("auto")) {
+            (Some(rest), _) | (None, Some(rest)) => (true, rest.trim()),
+            (None, None) => (false, value.as_str()),
+        };
+        if ratio.is_empty() {
+            return auto.then_some(AspectRatio::Auto);
+        }
+        let (width, height) = match ratio.split_once('/') {
+            Some((width, height)) => (width.trim(), height.trim()),
+            None => (ratio, "1"),
+        };
+        let width: f32 = width.parse().ok()?;
+        let height: f32 = height.parse().ok()?;
+        if !(width.is_finite() && height.is_finite()) || width < 0.0 || height < 0.0 {
+            return None;
+        }
+        if width == 0.0 || height == 0.0 {
+            return Some(AspectRatio::Auto);
+        }
+        Some(match auto {
+            true => AspectRatio::AutoOr(width / height),
+            false => AspectRatio::Ratio(width / height),
+        })
+    }
+}
+
+/// Which points a shape of several subpaths fills (`fill-rule`,
+/// `clip-rule`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    /// Parse a `fill-rule` keyword.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "nonzero" => Some(FillRule::NonZero),
+            "evenodd" => Some(FillRule::EvenOdd),
+            _ => None,
+        }
+    }
+
+    /// Whether a point the shape winds around `winding` times is inside.
+    pub fn contains(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
 /// Font stretch values.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum FontStretch {
@@ -778,6 +972,121 @@ pub enum WordBreak {
     BreakWord,
 }
 
+/// Whether words too long for their line may be broken (`overflow-wrap`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowWrap {
+    #[default]
+    Normal,
+    /// Break only when nothing else fits; min-content width is unaffected.
+    BreakWord,
+    /// Like `BreakWord`, but the breaks also count toward min-content width.
+    Anywhere,
+}
+
+/// Where words may be hyphenated at a line break (`hyphens`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hyphens {
+    /// Never, not even at soft hyphens.
+    None,
+    /// Only at soft hyphens (U+00AD).
+    #[default]
+    Manual,
+    /// At soft hyphens and wherever the content language's hyphenation
+    /// patterns allow.
+    Auto,
+}
+
+/// Whether forced colors apply to an element (`forced-color-adjust`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForcedColorAdjust {
+    #[default]
+    Auto,
+    /// The element and its descendants keep their own colors.
+    None,
+}
+
+/// The mouse cursor over an element (`cursor`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cursor {
+    /// Chosen by what is under the mouse: text, a link or neither.
+    #[default]
+    Auto,
+    Default,
+    None,
+    Pointer,
+    Text,
+    Wait,
+    Progress,
+    Help,
+    Crosshair,
+    Move,
+    NotAllowed,
+    /// `ew-resize`, `col-resize` and the `e`/`w` resizes.
+    EwResize,
+    /// `ns-resize`, `row-resize` and the `n`/`s` resizes.
+    NsResize,
+    /// `nesw-resize` and the `ne`/`sw` resizes.
+    NeswResize,
+    /// `nwse-resize` and the `nw`/`se` resizes.
+    NwseResize,
+}
+
+impl Cursor {
+    /// Parse a `cursor` value. `url()` images are skipped in favor of the
+    /// keyword that must follow them.
+    pub fn parse(value: &str) -> Option<Self> {
+        let keyword = value.rsplit(',').next()?.trim().to_ascii_lowercase();
+        Some(match keyword.as_str() {
+            "auto" => Cursor::Auto,
+            "default" | "context-menu" | "alias" | "copy" | "cell" | "vertical-text" => {
+                Cursor::Default
+            }
+            "none" => Cursor::None,
+            "pointer" => Cursor::Pointer,
+            "text" => Cursor::Text,
+            "wait" => Cursor::Wait,
+            "progress" => Cursor::Progress,
+            "help" => Cursor::Help,
+            "crosshair" => Cursor::Crosshair,
+            "move" | "all-scroll" | "grab" | "grabbing" => Cursor::Move,
+            "not-allowed" | "no-drop" => Cursor::NotAllowed,
+            "ew-resize" | "col-resize" | "e-resize" | "w-resize" => Cursor::EwResize,
+            "ns-resize" | "row-resize" | "n-resize" | "s-resize" => Cursor::NsResize,
+            "nesw-resize" | "ne-resize" | "sw-resize" => Cursor::NeswResize,
+            "nwse-resize" | "nw-resize" | "se-resize" => Cursor::NwseResize,
+            _ => return None,
+        })
+    }
+}
+
+/// How text cut off by `overflow` is marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextOverflow {
+    #[default]
+    Clip,
+    Ellipsis,
+}
+
+impl WhiteSpace {
+    /// Whether lines may wrap at spaces.
+    pub fn wraps(self) -> bool {
+        !matches!(self, WhiteSpace::Nowrap | WhiteSpace::Pre)
+    }
+
+    /// Parse a `white-space` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(WhiteSpace::Normal),
+            "nowrap" => Some(WhiteSpace::Nowrap),
+            "pre" => Some(WhiteSpace::Pre),
+            "pre-wrap" => Some(WhiteSpace::PreWrap),
+            "pre-line" => Some(WhiteSpace::PreLine),
+            "break-spaces" => Some(WhiteSpace::BreakSpaces),
+            _ => None,
+        }
+    }
+}
+
 /// Vertical alignment.
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum VerticalAlign {
@@ -790,7 +1099,10 @@ pub enum VerticalAlign {
     Middle,
     Bottom,
     TextBottom,
+    /// Raise (positive) or lower the baseline by a length in px.
     Length(f32),
+    /// Raise or lower the baseline by a percentage of the line height.
+    Percent(f32),
 }
 
 /// Writing mode.
@@ -820,6 +1132,136 @@ pub enum Direction {
     Rtl,
 }
 
+/// A pseudo-element that generates a box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    Before,
+    After,
+}
+
+impl PseudoElement {
+    /// Split a selector such as `h2::before` into the element selector and
+    /// its pseudo-element, accepting the legacy single-colon form.
+    pub fn split_selector(selector: &str) -> (&str, Option<PseudoElement>) {
+        let selector = selector.trim();
+        for (suffix, pseudo) in [
+            ("::before", PseudoElement::Before),
+            ("::after", PseudoElement::After),
+            (":before", PseudoElement::Before),
+            (":after", PseudoElement::After),
+        ] {
+            if selector.len() >= suffix.len()
+                && selector[selector.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+            {
+                return (&selector[..selector.len() - suffix.len()], Some(pseudo));
+            }
+        }
+        (selector, None)
+    }
+}
+
+/// Numbering style of a `counter()` or `counters()` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CounterStyle {
+    #[default]
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    None,
+}
+
+impl CounterStyle {
+    /// Parse a counter style name.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "decimal" => Some(CounterStyle::Decimal),
+            "lower-alpha" | "lower-latin" => Some(CounterStyle::LowerAlpha),
+            "upper-alpha" | "upper-latin" => Some(CounterStyle::UpperAlpha),
+            "lower-roman" => Some(CounterStyle::LowerRoman),
+            "upper-roman" => Some(CounterStyle::UpperRoman),
+            "none" => Some(CounterStyle::None),
+            _ => None,
+        }
+    }
+
+    /// Format a counter value, falling back to decimal outside the
+    /// style's range.
+    pub fn format(self, value: i32) -> String {
+        match self {
+            CounterStyle::None => String::new(),
+            CounterStyle::LowerAlpha | CounterStyle::UpperAlpha if value > 0 => {
+                let mut n = value as u32;
+                let mut letters = Vec::new();
+                while n > 0 {
+                    n -= 1;
+                    letters.push((b'a' + (n % 26) as u8) as char);
+                    n /= 26;
+                }
+                let text: String = letters.into_iter().rev().collect();
+                if self == CounterStyle::UpperAlpha {
+                    text.to_uppercase()
+                } else {
+                    text
+                }
+            }
+            CounterStyle::LowerRoman | CounterStyle::UpperRoman if (1..4000).contains(&value) => {
+                const NUMERALS: [(i32, &str); 13] = [
+                    (1000, "m"),
+                    (900, "cm"),
+                    (500, "d"),
+                    (400, "cd"),
+                    (100, "c"),
+                    (90, "xc"),
+                    (50, "l"),
+                    (40, "xl"),
+                    (10, "x"),
+                    (9, "ix"),
+                    (5, "v"),
+                    (4, "iv"),
+                    (1, "i"),
+                ];
+                let mut n = value;
+                let mut text = String::new();
+                for (weight, numeral) in NUMERALS {
+                    while n >= weight {
+                        text.push_str(numeral);
+                        n -= weight;
+                    }
+                }
+                if self == CounterStyle::UpperRoman {
+                    text.to_uppercase()
+                } else {
+                    text
+                }
+            }
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// One component of the `content` property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentItem {
+    /// A string literal.
+    String(String),
+    /// `attr(name)`: the originating element's attribute value.
+    Attr(String),
+    /// `counter(name, style)`: the innermost counter value.
+    Counter { name: String, style: CounterStyle },
+    /// `counters(name, separator, style)`: all nested counter values.
+    Counters {
+        name: String,
+        separator: String,
+        style: CounterStyle,
+    },
+    /// `open-quote`.
+    OpenQuote,
+    /// `close-quote`.
+    CloseQuote,
+}
+
 /// Computed style for an element.
 #[derive(Debug, Clone, Default)]
 pub struct ComputedStyle {
@@ -832,6 +1274,7 @@ pub struct ComputedStyle {
     pub min_height: Length,
     pub max_width: Length,
     pub max_height: Length,
+    pub aspect_ratio: AspectRatio,
 
     // Margin
     pub margin_top: Length,
@@ -859,6 +1302,15 @@ pub struct ComputedStyle {
     pub color: Color,
     pub background_color: Color,
 
+    // Backgrounds; empty lists take the initial value
+    pub background_image: Vec<Option<String>>, // None = none
+    pub background_position: Vec<BackgroundPosition>,
+    pub background_size: Vec<String>,   // Unparsed
+    pub background_repeat: Vec<String>, // Unparsed
+    pub background_origin: Vec<BackgroundBox>,
+    pub background_clip: Vec<BackgroundBox>,
+    pub background_attachment: Vec<BackgroundAttachment>,
+
     // Typography - Basic
     pub font_size: Length,
     pub font_weight: FontWeight,
@@ -879,15 +1331,43 @@ pub struct ComputedStyle {
     pub text_transform: TextTransform,
     pub white_space: WhiteSpace,
     pub word_break: WordBreak,
+    pub overflow_wrap: OverflowWrap,
+    pub text_overflow: TextOverflow,
+    pub hyphens: Hyphens,
+    /// Text shown at a hyphenated line break; `None` for `auto`.
+    pub hyphenate_character: Option<String>,
+    /// Content language from the nearest `lang` attribute.
+    pub lang: Option<String>,
     pub vertical_align: VerticalAlign,
     pub writing_mode: WritingMode,
     pub direction: Direction,
 
+    // Custom properties (`--*`), inherited
+    pub custom_properties: CustomProperties,
+
     // Visual
+    pub forced_color_adjust: ForcedColorAdjust,
+    pub cursor: Cursor,
     pub opacity: f32,
+    pub z_index: Option<i32>,      // None = auto
+    pub transform: Option<String>, // Unparsed; None = none
+    pub filter: FilterChain,
+    pub backdrop_filter: FilterChain,
     pub overflow_x: Overflow,
     pub overflow_y: Overflow,
 
+    // Outline; drawn outside the border box without affecting layout
+    pub outline_width: Length,
+    pub outline_style: OutlineStyle,
+    pub outline_color: Option<Color>, // None = currentColor
+    pub outline_offset: Length,
+
+    // Transitions; empty lists take the initial value
+    pub transition_property: Vec<String>,
+    pub transition_duration: Vec<f32>, // Seconds
+    pub transition_timing_function: Vec<String>,
+    pub transition_delay: Vec<f32>, // Seconds
+
     // Flexbox Container
     pub flex_direction: FlexDirection,
     pub flex_wrap: FlexWrap,
@@ -895,7 +1375,23 @@ pub struct ComputedStyle {
     pub align_items: AlignItems,
     pub align_content: AlignContent,
     pub row_gap: Length,
-    pub column_gap: Length,
+    pub column_gap: Length, // Auto = normal
+
+    // Multi-column
+    pub column_count: Option<u32>,    // None = auto
+    pub column_width: Option<Length>, // None = auto
+    pub column_rule_width: Length,
+    pub column_rule_style: OutlineStyle,
+    pub column_rule_color: Option<Color>, // None = currentColor
+    pub break_inside: BreakInside,
+
+    // Fragmentation
+    pub break_before: BreakBetween,
+    pub break_after: BreakBetween,
+    /// Fewest lines of a block left at the bottom of a page.
+    pub orphans: u32,
+    /// Fewest lines of a block carried to the top of a page.
+    pub widows: u32,
 
     // Flexbox Item
     pub order: i32,
@@ -911,6 +1407,7 @@ pub struct ComputedStyle {
     pub scrollbar_width: ScrollbarWidth,
     pub scrollbar_gutter: ScrollbarGutter,
     pub scrollbar_color: Option<(Color, Color)>, // (thumb, track)
+    pub overflow_anchor: OverflowAnchor,
 
     // Grid Container
     pub grid_template_columns: GridTemplate,
@@ -929,6 +1426,11 @@ pub struct ComputedStyle {
     // Grid Alignment (also used by Flexbox)
     pub justify_items: JustifyItems,
     pub justify_self: JustifySelf,
+
+    // Generated content
+    pub content: Option<Vec<ContentItem>>, // None = normal/none
+    pub counter_reset: Vec<(String, i32)>,
+    pub counter_increment: Vec<(String, i32)>,
 }
 
 impl ComputedStyle {
@@ -937,6 +1439,7 @@ impl ComputedStyle {
         Self {
             font_size: Length::Px(16.0),
             line_height: 1.2,
+            width: Length::Auto,
             opacity: 1.0,
             color: Color::BLACK,
             background_color: Color::TRANSPARENT,
@@ -944,6 +1447,11 @@ impl ComputedStyle {
             text_decoration_line: TextDecorationLine::NONE,
             text_decoration_color: None,
             text_decoration_thickness: Length::Auto,
+            outline_width: Length::Px(3.0), // medium
+            column_gap: Length::Auto,
+            column_rule_width: Length::Px(3.0),
+            orphans: 2,
+            widows: 2,
             // Flexbox item defaults
             flex_shrink: 1.0, // Default is 1, not 0
             ..Default::default()
@@ -968,8 +1476,17 @@ impl ComputedStyle {
             text_transform: parent.text_transform,
             white_space: parent.white_space,
             word_break: parent.word_break,
+            overflow_wrap: parent.overflow_wrap,
+            hyphens: parent.hyphens,
+            hyphenate_character: parent.hyphenate_character.clone(),
+            lang: parent.lang.clone(),
             direction: parent.direction,
             writing_mode: parent.writing_mode,
+            forced_color_adjust: parent.forced_color_adjust,
+            cursor: parent.cursor,
+            custom_properties: parent.custom_properties.clone(),
+            orphans: parent.orphans,
+            widows: parent.widows,
 
             // Text decoration is NOT inherited (each element sets its own)
             text_decoration_line: TextDecorationLine::NONE,
@@ -978,9 +1495,18 @@ impl ComputedStyle {
             text_decoration_thickness: Length::Auto,
 
             // Non-inherited get defaults
+            opacity: 1.0,
+            outline_width: Length::Px(3.0),
+            column_gap: Length::Auto,
+            column_rule_width: Length::Px(3.0),
             ..Default::default()
         }
     }
+
+    /// Whether the box lays its content out in columns.
+    pub fn is_multicol(&self) -> bool {
+        self.column_count.is_some() || self.column_width.is_some()
+    }
 }
 
 /// CSS property value (unparsed or parsed).
@@ -1007,6 +1533,15 @@ pub struct Declaration {
 pub struct Rule {
     pub selector: String,
     pub declarations: Vec<Declaration>,
+    /// Conditions of the enclosing `@media` blocks; all must match.
+    pub media: Vec<MediaQueryList>,
+}
+
+impl Rule {
+    /// Whether the rule's `@media` conditions hold in `env`.
+    pub fn applies_in(&self, env: &MediaEnvironment) -> bool {
+        self.media.iter().all(|m| m.matches(env))
+    }
 }
 
 /// A complete stylesheet.
@@ -1040,6 +1575,7 @@ impl Stylesheet {
                         important: d.important,
                     })
                     .collect(),
+                media: r.media.iter().map(|m| MediaQueryList::parse(m)).collect(),
             })
             .collect::<Vec<_>>();
 
@@ -1131,6 +1667,9 @@ pub fn parse_length(value: &str) -> Option<Length> {
     if value == "0" {
         return Some(Length::Zero);
     }
+    if value.ends_with(')') {
+        return parse_calc(value);
+    }
 
     if value.ends_with("px") {
         let num = value.trim_end_matches("px").parse::<f32>().ok()?;
@@ -1163,12 +1702,183 @@ pub fn parse_display(value: &str) -> Option<Display> {
         "block" => Some(Display::Block),
         "inline" => Some(Display::Inline),
         "inline-block" => Some(Display::InlineBlock),
+        "flow-root" => Some(Display::FlowRoot),
         "flex" => Some(Display::Flex),
+        "table-cell" => Some(Display::TableCell),
         "none" => Some(Display::None),
         _ => None,
     }
 }
 
+/// Parse a `vertical-align` value.
+pub fn parse_vertical_align(value: &str) -> Option<VerticalAlign> {
+    let value = value.trim().to_lowercase();
+    let align = match value.as_str() {
+        "baseline" => VerticalAlign::Baseline,
+        "sub" => VerticalAlign::Sub,
+        "super" => VerticalAlign::Super,
+        "top" => VerticalAlign::Top,
+        "text-top" => VerticalAlign::TextTop,
+        "middle" => VerticalAlign::Middle,
+        "bottom" => VerticalAlign::Bottom,
+        "text-bottom" => VerticalAlign::TextBottom,
+        _ => match parse_length(&value)? {
+            Length::Percent(p) => VerticalAlign::Percent(p),
+            Length::Px(px) => VerticalAlign::Length(px),
+            Length::Zero => VerticalAlign::Length(0.0),
+            // Font-relative offsets resolve against the default font size
+            Length::Em(em) | Length::Rem(em) => VerticalAlign::Length(em * 16.0),
+            Length::Calc(calc) if !calc.has_percent() => {
+                VerticalAlign::Length(calc.to_px(16.0, 16.0, 0.0))
+            }
+            Length::Auto | Length::Calc(_) => return None,
+        },
+    };
+    Some(align)
+}
+
+/// Parse a `content` value. Returns `None` for `normal`, `none` or an
+/// invalid value.
+pub fn parse_content(value: &str) -> Option<Vec<ContentItem>> {
+    let mut items = Vec::new();
+    let mut rest = value.trim();
+
+    while !rest.is_empty() {
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            let (text, remaining) = parse_css_string(rest)?;
+            items.push(ContentItem::String(text));
+            rest = remaining;
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '(' || c == '"' || c == '\'')
+                .unwrap_or(rest.len());
+            let ident = rest[..end].to_lowercase();
+            rest = &rest[end..];
+
+            if rest.starts_with('(') {
+                let close = rest.find(')')?;
+                let args = split_function_args(&rest[1..close])?;
+                rest = &rest[close + 1..];
+                items.push(match (ident.as_str(), args.as_slice()) {
+                    ("attr", [name]) => ContentItem::Attr(name.clone()),
+                    ("counter", [name]) => ContentItem::Counter {
+                        name: name.clone(),
+                        style: CounterStyle::Decimal,
+                    },
+                    ("counter", [name, style]) => ContentItem::Counter {
+                        name: name.clone(),
+                        style: CounterStyle::parse(style)?,
+                    },
+                    ("counters", [name, separator]) => ContentItem::Counters {
+                        name: name.clone(),
+                        separator: separator.clone(),
+                        style: CounterStyle::Decimal,
+                    },
+                    ("counters", [name, separator, style]) => ContentItem::Counters {
+                        name: name.clone(),
+                        separator: separator.clone(),
+                        style: CounterStyle::parse(style)?,
+                    },
+                    _ => return None,
+                });
+            } else {
+                match ident.as_str() {
+                    "normal" | "none" if items.is_empty() && rest.trim().is_empty() => return None,
+                    "open-quote" => items.push(ContentItem::OpenQuote),
+                    "close-quote" => items.push(ContentItem::CloseQuote),
+                    "no-open-quote" | "no-close-quote" => {}
+                    _ => return None,
+                }
+            }
+        }
+        rest = rest.trim_start();
+    }
+
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+/// Parse a `counter-reset` or `counter-increment` value into
+/// `(name, value)` pairs, using `default` where a name has no integer.
+pub fn parse_counter_list(value: &str, default: i32) -> Vec<(String, i32)> {
+    let mut counters: Vec<(String, i32)> = Vec::new();
+    for token in value.split_whitespace() {
+        if let Ok(n) = token.parse::<i32>() {
+            if let Some(last) = counters.last_mut() {
+                last.1 = n;
+            }
+        } else if token.eq_ignore_ascii_case("none") {
+            return Vec::new();
+        } else {
+            counters.push((token.to_string(), default));
+        }
+    }
+    counters
+}
+
+/// Parse a quoted CSS string, returning its value and the remaining input.
+fn parse_css_string(input: &str) -> Option<(String, &str)> {
+    let quote = input.chars().next()?;
+    let mut text = String::new();
+    let mut chars = input.char_indices().skip(1).peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((text, &input[i + 1..])),
+            '\\' => {
+                let mut hex = String::new();
+                while hex.len() < 6 {
+                    match chars.peek() {
+                        Some(&(_, h)) if h.is_ascii_hexdigit() => {
+                            hex.push(h);
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                if hex.is_empty() {
+                    if let Some((_, escaped)) = chars.next() {
+                        text.push(escaped);
+                    }
+                } else {
+                    // A single whitespace terminates a hex escape
+                    if chars.peek().is_some_and(|&(_, c)| c == ' ') {
+                        chars.next();
+                    }
+                    text.push(
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or('\u{FFFD}'),
+                    );
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+/// Split comma-separated function arguments, unquoting string arguments.
+fn split_function_args(args: &str) -> Option<Vec<String>> {
+    args.split(',')
+        .map(|arg| {
+            let arg = arg.trim();
+            if arg.starts_with('"') || arg.starts_with('\'') {
+                let (text, rest) = parse_css_string(arg)?;
+                rest.trim().is_empty().then_some(text)
+            } else if arg.is_empty() {
+                None
+            } else {
+                Some(arg.to_string())
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1195,6 +1905,37 @@ mod tests {
         assert_eq!(parse_length("auto"), Some(Length::Auto));
     }
 
+    #[test]
+    fn test_parse_vertical_align() {
+        assert_eq!(parse_vertical_align("middle"), Some(VerticalAlign::Middle));
+        assert_eq!(
+            parse_vertical_align("Text-Top"),
+            Some(VerticalAlign::TextTop)
+        );
+        assert_eq!(
+            parse_vertical_align("-4px"),
+            Some(VerticalAlign::Length(-4.0))
+        );
+        assert_eq!(
+            parse_vertical_align("50%"),
+            Some(VerticalAlign::Percent(50.0))
+        );
+        assert_eq!(parse_vertical_align("auto"), None);
+        assert_eq!(parse_vertical_align("center"), None);
+    }
+
+    #[test]
+    fn test_parse_cursor() {
+        assert_eq!(Cursor::parse("pointer"), Some(Cursor::Pointer));
+        assert_eq!(Cursor::parse("Col-Resize"), Some(Cursor::EwResize));
+        assert_eq!(
+            Cursor::parse("url(hand.cur) 4 4, not-allowed"),
+            Some(Cursor::NotAllowed)
+        );
+        assert_eq!(Cursor::parse("url(hand.cur)"), None);
+        assert_eq!(Cursor::parse("zoom-sideways"), None);
+    }
+
     #[test]
     fn test_parse_stylesheet() {
         let css = r#"
@@ -1224,4 +1965,93 @@ mod tests {
         // Non-inherited properties should be default
         assert_eq!(child.display, Display::Block);
     }
+
+    #[test]
+    fn test_parse_content() {
+        assert_eq!(parse_content("none"), None);
+        assert_eq!(parse_content("normal"), None);
+        assert_eq!(
+            parse_content(r#"counter(sec) ". ""#),
+            Some(vec![
+                ContentItem::Counter {
+                    name: "sec".into(),
+                    style: CounterStyle::Decimal,
+                },
+                ContentItem::String(". ".into()),
+            ])
+        );
+        assert_eq!(
+            parse_content(r#"counters(item, ".", upper-roman) attr(data-label)"#),
+            Some(vec![
+                ContentItem::Counters {
+                    name: "item".into(),
+                    separator: ".".into(),
+                    style: CounterStyle::UpperRoman,
+                },
+                ContentItem::Attr("data-label".into()),
+            ])
+        );
+        assert_eq!(
+            parse_content(r#"'\201C' open-quote"#),
+            Some(vec![
+                ContentItem::String("\u{201C}".into()),
+                ContentItem::OpenQuote,
+            ])
+        );
+        assert_eq!(parse_content("counter()"), None);
+        assert_eq!(parse_content(r#""unterminated"#), None);
+    }
+
+    #[test]
+    fn test_parse_counter_list() {
+        assert_eq!(parse_counter_list("sec", 1), vec![("sec".into(), 1)]);
+        assert_eq!(
+            parse_counter_list("sec 2 sub", 0),
+            vec![("sec".into(), 2), ("sub".into(), 0)]
+        );
+        assert!(parse_counter_list("none", 0).is_empty());
+    }
+
+    #[test]
+    fn test_counter_style_format() {
+        assert_eq!(CounterStyle::Decimal.format(-3), "-3");
+        assert_eq!(CounterStyle::LowerAlpha.format(28), "ab");
+        assert_eq!(CounterStyle::UpperRoman.format(1994), "MCMXCIV");
+        assert_eq!(CounterStyle::LowerRoman.format(0), "0");
+    }
+
+    #[test]
+    fn test_parse_aspect_ratio() {
+        assert_eq!(AspectRatio::parse("auto"), Some(AspectRatio::Auto));
+        assert_eq!(
+            AspectRatio::parse("16 / 9"),
+            Some(AspectRatio::Ratio(16.0 / 9.0))
+        );
+        assert_eq!(AspectRatio::parse("2"), Some(AspectRatio::Ratio(2.0)));
+        assert_eq!(
+            AspectRatio::parse("auto 4/3"),
+            Some(AspectRatio::AutoOr(4.0 / 3.0))
+        );
+        assert_eq!(
+            AspectRatio::parse("4/3 AUTO"),
+            Some(AspectRatio::AutoOr(4.0 / 3.0))
+        );
+        assert_eq!(AspectRatio::parse("0 / 1"), Some(AspectRatio::Auto));
+        assert_eq!(AspectRatio::parse("-1"), None);
+        assert_eq!(AspectRatio::parse("auto auto"), None);
+        assert_eq!(AspectRatio::parse("wide"), None);
+    }
+
+    #[test]
+    fn test_split_pseudo_element() {
+        assert_eq!(
+            PseudoElement::split_selector("h2::before"),
+            ("h2", Some(PseudoElement::Before))
+        );
+        assert_eq!(
+            PseudoElement::split_selector(".note:after"),
+            (".note", Some(PseudoElement::After))
+        );
+        assert_eq!(PseudoElement::split_selector("p"), ("p", None));
+    }
 }
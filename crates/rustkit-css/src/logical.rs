@@ -0,0 +1,245 @@
+//! Flow-relative (logical) properties.
+//!
+//! Maps `margin-inline-start`, `padding-block`, `inset-inline-end`,
+//! `inline-size` and friends onto physical properties for an element's
+//! writing mode and direction. Logical declarations are meant to be
+//! rewritten where they appear in a declaration block, so a logical and a
+//! physical declaration for the same side still cascade by order.
+
+use crate::{Direction, WritingMode};
+
+/// A physical box side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl PhysicalSide {
+    /// Name used in property names, e.g. `left` in `margin-left`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PhysicalSide::Top => "top",
+            PhysicalSide::Right => "right",
+            PhysicalSide::Bottom => "bottom",
+            PhysicalSide::Left => "left",
+        }
+    }
+}
+
+/// A flow-relative box side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalSide {
+    BlockStart,
+    BlockEnd,
+    InlineStart,
+    InlineEnd,
+}
+
+impl LogicalSide {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "block-start" => Some(LogicalSide::BlockStart),
+            "block-end" => Some(LogicalSide::BlockEnd),
+            "inline-start" => Some(LogicalSide::InlineStart),
+            "inline-end" => Some(LogicalSide::InlineEnd),
+            _ => None,
+        }
+    }
+
+    /// The physical side this side falls on.
+    pub fn to_physical(self, writing_mode: WritingMode, direction: Direction) -> PhysicalSide {
+        use PhysicalSide::*;
+        // Block start and end, then inline start and end for ltr
+        let (block_start, block_end, line_start, line_end) = match writing_mode {
+            WritingMode::HorizontalTb => (Top, Bottom, Left, Right),
+            WritingMode::VerticalRl => (Right, Left, Top, Bottom),
+            WritingMode::VerticalLr => (Left, Right, Top, Bottom),
+        };
+        let rtl = direction == Direction::Rtl;
+        match self {
+            LogicalSide::BlockStart => block_start,
+            LogicalSide::BlockEnd => block_end,
+            LogicalSide::InlineStart if rtl => line_end,
+            LogicalSide::InlineStart => line_start,
+            LogicalSide::InlineEnd if rtl => line_start,
+            LogicalSide::InlineEnd => line_end,
+        }
+    }
+}
+
+/// The physical property a logical longhand sets, such as `margin-right`
+/// for `margin-inline-start` in a right-to-left element, or `None` if the
+/// property is not flow-relative.
+pub fn physical_property(
+    property: &str,
+    writing_mode: WritingMode,
+    direction: Direction,
+) -> Option<String> {
+    let horizontal = writing_mode == WritingMode::HorizontalTb;
+    for prefix in ["", "min-", "max-"] {
+        let size = match property.strip_prefix(prefix) {
+            Some("inline-size") => horizontal,
+            Some("block-size") => !horizontal,
+            _ => continue,
+        };
+        let physical = if size { "width" } else { "height" };
+        return Some(format!("{}{}", prefix, physical));
+    }
+
+    let (base, rest) = property.split_once('-')?;
+    if !matches!(base, "margin" | "padding" | "border" | "inset") {
+        return None;
+    }
+    // Border longhands carry a width, color or style suffix
+    let (side, suffix) = match base {
+        "border" => {
+            let (side, suffix) = rest.rsplit_once('-')?;
+            if !matches!(suffix, "width" | "color" | "style") {
+                return None;
+            }
+            (side, Some(suffix))
+        }
+        _ => (rest, None),
+    };
+    let side = LogicalSide::parse(side)?
+        .to_physical(writing_mode, direction)
+        .name();
+    Some(match (base, suffix) {
+        ("inset", _) => side.to_string(),
+        (base, Some(suffix)) => format!("{}-{}-{}", base, side, suffix),
+        (base, None) => format!("{}-{}", base, side),
+    })
+}
+
+/// Split a logical shorthand such as `margin-inline: 4px 8px` into its start
+/// and end longhands; one value applies to both. `None` if `property` is not
+/// such a shorthand or the value does not have one or two parts.
+pub fn expand_logical_shorthand(property: &str, value: &str) -> Option<[(String, String); 2]> {
+    let (base, suffix) = property
+        .split_once("-inline")
+        .or_else(|| property.split_once("-block"))?;
+    let axis = if property.contains("-inline") {
+        "inline"
+    } else {
+        "block"
+    };
+    let valid = match base {
+        "margin" | "padding" | "inset" => suffix.is_empty(),
+        "border" => matches!(suffix, "-width" | "-color" | "-style"),
+        _ => false,
+    };
+    if !valid {
+        return None;
+    }
+
+    let values: Vec<&str> = value.split_whitespace().collect();
+    let (start, end) = match values.as_slice() {
+        [both] => (*both, *both),
+        [start, end] => (*start, *end),
+        _ => return None,
+    };
+    Some([
+        (
+            format!("{}-{}-start{}", base, axis, suffix),
+            start.to_string(),
+        ),
+        (format!("{}-{}-end{}", base, axis, suffix), end.to_string()),
+    ])
+}
+
+/// Parse a `direction` value.
+pub fn parse_direction(value: &str) -> Option<Direction> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "ltr" => Some(Direction::Ltr),
+        "rtl" => Some(Direction::Rtl),
+        _ => None,
+    }
+}
+
+/// Parse a `writing-mode` value, including the legacy SVG keywords.
+pub fn parse_writing_mode(value: &str) -> Option<WritingMode> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "horizontal-tb" | "lr" | "lr-tb" | "rl" | "rl-tb" => Some(WritingMode::HorizontalTb),
+        "vertical-rl" | "tb" | "tb-rl" => Some(WritingMode::VerticalRl),
+        "vertical-lr" => Some(WritingMode::VerticalLr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn physical(property: &str, writing_mode: WritingMode, direction: Direction) -> String {
+        physical_property(property, writing_mode, direction).unwrap()
+    }
+
+    #[test]
+    fn test_horizontal_mapping() {
+        let (h, ltr, rtl) = (WritingMode::HorizontalTb, Direction::Ltr, Direction::Rtl);
+        assert_eq!(physical("margin-inline-start", h, ltr), "margin-left");
+        assert_eq!(physical("margin-inline-start", h, rtl), "margin-right");
+        assert_eq!(physical("padding-block-end", h, rtl), "padding-bottom");
+        assert_eq!(
+            physical("border-inline-end-width", h, ltr),
+            "border-right-width"
+        );
+        assert_eq!(physical("inset-inline-end", h, rtl), "left");
+        assert_eq!(physical("inline-size", h, rtl), "width");
+        assert_eq!(physical("max-block-size", h, ltr), "max-height");
+        assert_eq!(physical_property("margin-left", h, ltr), None);
+        assert_eq!(physical_property("border-inline-start", h, ltr), None);
+    }
+
+    #[test]
+    fn test_vertical_mapping() {
+        let ltr = Direction::Ltr;
+        assert_eq!(
+            physical("margin-block-start", WritingMode::VerticalRl, ltr),
+            "margin-right"
+        );
+        assert_eq!(
+            physical("margin-block-start", WritingMode::VerticalLr, ltr),
+            "margin-left"
+        );
+        assert_eq!(
+            physical("padding-inline-start", WritingMode::VerticalRl, ltr),
+            "padding-top"
+        );
+        assert_eq!(
+            physical(
+                "padding-inline-start",
+                WritingMode::VerticalLr,
+                Direction::Rtl
+            ),
+            "padding-bottom"
+        );
+        assert_eq!(
+            physical("inline-size", WritingMode::VerticalRl, ltr),
+            "height"
+        );
+    }
+
+    #[test]
+    fn test_expand_shorthand() {
+        assert_eq!(
+            expand_logical_shorthand("margin-inline", "4px 8px"),
+            Some([
+                ("margin-inline-start".to_string(), "4px".to_string()),
+                ("margin-inline-end".to_string(), "8px".to_string()),
+            ])
+        );
+        assert_eq!(
+            expand_logical_shorthand("border-block-width", "2px").map(|[start, _]| start.0),
+            Some("border-block-start-width".to_string())
+        );
+        assert_eq!(expand_logical_shorthand("margin-inline-start", "4px"), None);
+        assert_eq!(
+            expand_logical_shorthand("padding-block", "1px 2px 3px"),
+            None
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Media queries.
+//!
+//! Supports media types (`all`, `screen`, `print`), `not`/`only`, and the
+//! `width`, `height`, `aspect-ratio` (each with `min-`/`max-` forms),
+//! `orientation`, `prefers-color-scheme`, `prefers-reduced-motion` and
+//! `forced-colors` features. Lengths may be given in `px` or `em`. Unknown
+//! features never match.
+
+/// Preferred color scheme of the user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// The environment media queries are evaluated against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaEnvironment {
+    /// Viewport width in CSS pixels.
+    pub width: f32,
+    /// Viewport height in CSS pixels.
+    pub height: f32,
+    /// Initial font size, used to resolve `em` lengths.
+    pub font_size: f32,
+    pub color_scheme: ColorScheme,
+    /// The user asked for less motion.
+    pub reduced_motion: bool,
+    /// A forced color palette, such as a high contrast theme, is in use.
+    pub forced_colors: bool,
+    /// Media type, e.g. `screen` or `print`.
+    pub media_type: String,
+    /// Device pixels per CSS pixel.
+    pub resolution: f32,
+}
+
+impl MediaEnvironment {
+    /// A screen of the given viewport size.
+    pub fn screen(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            font_size: 16.0,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            forced_colors: false,
+            media_type: "screen".to_string(),
+            resolution: 1.0,
+        }
+    }
+
+    /// A printed page with the given content area.
+    pub fn print(width: f32, height: f32) -> Self {
+        Self {
+            media_type: "print".to_string(),
+            ..Self::screen(width, height)
+        }
+    }
+
+    pub fn with_color_scheme(mut self, color_scheme: ColorScheme) -> Self {
+        self.color_scheme = color_scheme;
+        self
+    }
+
+    pub fn with_reduced_motion(mut self, reduced_motion: bool) -> Self {
+        self.reduced_motion = reduced_motion;
+        self
+    }
+
+    pub fn with_forced_colors(mut self, forced_colors: bool) -> Self {
+        self.forced_colors = forced_colors;
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: f32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl Default for MediaEnvironment {
+    fn default() -> Self {
+        Self::screen(800.0, 600.0)
+    }
+}
+
+/// How a range feature compares against its value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Range {
+    Min,
+    Max,
+    Exact,
+}
+
+impl Range {
+    fn test(self, actual: f32, value: f32) -> bool {
+        match self {
+            Range::Min => actual >= value,
+            Range::Max => actual <= value,
+            Range::Exact => (actual - value).abs() < 0.01,
+        }
+    }
+}
+
+/// A length given in a media feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MediaLength {
+    Px(f32),
+    Em(f32),
+}
+
+impl MediaLength {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        if let Some(n) = value.strip_suffix("px") {
+            n.trim().parse().ok().map(MediaLength::Px)
+        } else if let Some(n) = value.strip_suffix("em") {
+            n.trim().parse().ok().map(MediaLength::Em)
+        } else if value == "0" {
+            Some(MediaLength::Px(0.0))
+        } else {
+            None
+        }
+    }
+
+    fn to_px(self, env: &MediaEnvironment) -> f32 {
+        match self {
+            MediaLength::Px(px) => px,
+            MediaLength::Em(em) => em * env.font_size,
+        }
+    }
+}
+
+/// A single media feature test, e.g. `(max-width: 500px)`.
+#[derive(Debug, Clone, PartialEq)]
+enum MediaFeature {
+    Width(Range, MediaLength),
+    Height(Range, MediaLength),
+    AspectRatio(Range, f32),
+    /// In dots per CSS pixel.
+    Resolution(Range, f32),
+    Portrait,
+    Landscape,
+    PrefersColorScheme(ColorScheme),
+    PrefersReducedMotion(bool),
+    ForcedColors(bool),
+    Unknown,
+}
+
+impl MediaFeature {
+    fn parse(feature: &str) -> Self {
+        // Boolean-context features such as `(color)` are not supported
+        let Some((name, value)) = feature.split_once(':') else {
+            return MediaFeature::Unknown;
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        // `-webkit-min-device-pixel-ratio: 2` is `min-resolution: 2dppx`
+        if let Some(rest) = name.strip_prefix("-webkit-") {
+            let (range, base) = if let Some(base) = rest.strip_prefix("min-") {
+                (Range::Min, base)
+            } else if let Some(base) = rest.strip_prefix("max-") {
+                (Range::Max, base)
+            } else {
+                (Range::Exact, rest)
+            };
+            return match (base, value.parse::<f32>()) {
+                ("device-pixel-ratio", Ok(ratio)) => MediaFeature::Resolution(range, ratio),
+                _ => MediaFeature::Unknown,
+            };
+        }
+        let (range, base) = if let Some(base) = name.strip_prefix("min-") {
+            (Range::Min, base)
+        } else if let Some(base) = name.strip_prefix("max-") {
+            (Range::Max, base)
+        } else {
+            (Range::Exact, name.as_str())
+        };
+
+        let parsed = match (range, base) {
+            (_, "width") => MediaLength::parse(value).map(|l| MediaFeature::Width(range, l)),
+            (_, "height") => MediaLength::parse(value).map(|l| MediaFeature::Height(range, l)),
+            (_, "aspect-ratio") => parse_ratio(value).map(|r| MediaFeature::AspectRatio(range, r)),
+            (_, "resolution") => {
+                parse_resolution(value).map(|r| MediaFeature::Resolution(range, r))
+            }
+            (Range::Exact, "orientation") => match value.to_ascii_lowercase().as_str() {
+                "portrait" => Some(MediaFeature::Portrait),
+                "landscape" => Some(MediaFeature::Landscape),
+                _ => None,
+            },
+            (Range::Exact, "prefers-color-scheme") => match value.to_ascii_lowercase().as_str() {
+                "light" => Some(MediaFeature::PrefersColorScheme(ColorScheme::Light)),
+                "dark" => Some(MediaFeature::PrefersColorScheme(ColorScheme::Dark)),
+                _ => None,
+            },
+            (Range::Exact, "prefers-reduced-motion") => match value.to_ascii_lowercase().as_str() {
+                "reduce" => Some(MediaFeature::PrefersReducedMotion(true)),
+                "no-preference" => Some(MediaFeature::PrefersReducedMotion(false)),
+                _ => None,
+            },
+            (Range::Exact, "forced-colors") => match value.to_ascii_lowercase().as_str() {
+                "active" => Some(MediaFeature::ForcedColors(true)),
+                "none" => Some(MediaFeature::ForcedColors(false)),
+                _ => None,
+            },
+            _ => None,
+        };
+        parsed.unwrap_or(MediaFeature::Unknown)
+    }
+
+    fn matches(&self, env: &MediaEnvironment) -> bool {
+        match self {
+            MediaFeature::Width(range, length) => range.test(env.width, length.to_px(env)),
+            MediaFeature::Height(range, length) => range.test(env.height, length.to_px(env)),
+            MediaFeature::AspectRatio(range, ratio) => {
+                env.height > 0.0 && range.test(env.width / env.height, *ratio)
+            }
+            MediaFeature::Resolution(range, dppx) => range.test(env.resolution, *dppx),
+            // Square viewports are portrait
+            MediaFeature::Portrait => env.height >= env.width,
+            MediaFeature::Landscape => env.width > env.height,
+            MediaFeature::PrefersColorScheme(scheme) => env.color_scheme == *scheme,
+            MediaFeature::PrefersReducedMotion(reduce) => env.reduced_motion == *reduce,
+            MediaFeature::ForcedColors(active) => env.forced_colors == *active,
+            MediaFeature::Unknown => false,
+        }
+    }
+}
+
+/// Parse a resolution such as `2dppx`, `2x` or `192dpi` into dots per CSS
+/// pixel.
+fn parse_resolution(value: &str) -> Option<f32> {
+    let value = value.trim().to_ascii_lowercase();
+    let (number, per_dppx) = if let Some(n) = value.strip_suffix("dppx") {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix("dpcm") {
+        (n, 96.0 / 2.54)
+    } else if let Some(n) = value.strip_suffix("dpi") {
+        (n, 96.0)
+    } else if let Some(n) = value.strip_suffix('x') {
+        (n, 1.0)
+    } else {
+        return None;
+    };
+    number.trim().parse::<f32>().ok().map(|n| n / per_dppx)
+}
+
+/// Parse `16/9` or a plain number.
+fn parse_ratio(value: &str) -> Option<f32> {
+    match value.split_once('/') {
+        Some((w, h)) => {
+            let w: f32 = w.trim().parse().ok()?;
+            let h: f32 = h.trim().parse().ok()?;
+            (h > 0.0).then(|| w / h)
+        }
+        None => value.trim().parse().ok(),
+    }
+}
+
+/// One query of a comma-separated list, e.g. `screen and (min-width: 40em)`.
+#[derive(Debug, Clone, PartialEq)]
+struct MediaQuery {
+    negated: bool,
+    /// `None` for `all` or an omitted type.
+    media_type: Option<String>,
+    features: Vec<MediaFeature>,
+}
+
+impl MediaQuery {
+    fn parse(query: &str) -> Self {
+        let mut negated = false;
+        let mut media_type = None;
+        let mut features = Vec::new();
+        let mut invalid = false;
+
+        let mut rest = query.trim();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('(') {
+                let Some(end) = after.find(')') else {
+                    invalid = true;
+                    break;
+                };
+                features.push(MediaFeature::parse(&after[..end]));
+                rest = after[end + 1..].trim_start();
+                continue;
+            }
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '(')
+                .unwrap_or(rest.len());
+            let word = rest[..end].to_ascii_lowercase();
+            rest = rest[end..].trim_start();
+            match word.as_str() {
+                "and" | "only" => {}
+                "not" if media_type.is_none() && features.is_empty() => negated = true,
+                "all" if media_type.is_none() => media_type = Some(None),
+                _ if media_type.is_none() && features.is_empty() => media_type = Some(Some(word)),
+                _ => {
+                    invalid = true;
+                    break;
+                }
+            }
+        }
+
+        if invalid {
+            // A malformed query is treated as `not all`
+            return Self {
+                negated: false,
+                media_type: None,
+                features: vec![MediaFeature::Unknown],
+            };
+        }
+        Self {
+            negated,
+            media_type: media_type.flatten(),
+            features,
+        }
+    }
+
+    fn matches(&self, env: &MediaEnvironment) -> bool {
+        let type_matches = self
+            .media_type
+            .as_ref()
+            .is_none_or(|t| t.eq_ignore_ascii_case(&env.media_type));
+        let matches = type_matches && self.features.iter().all(|f| f.matches(env));
+        matches != self.negated
+    }
+}
+
+/// A comma-separated list of media queries. Matches when any query does;
+/// an empty list always matches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaQueryList {
+    queries: Vec<MediaQuery>,
+}
+
+impl MediaQueryList {
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        if text.is_empty() {
+            return Self::default();
+        }
+        Self {
+            queries: text.split(',').map(MediaQuery::parse).collect(),
+        }
+    }
+
+    pub fn matches(&self, env: &MediaEnvironment) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|q| q.matches(env))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, width: f32, height: f32) -> bool {
+        MediaQueryList::parse(query).matches(&MediaEnvironment::screen(width, height))
+    }
+
+    #[test]
+    fn test_width_ranges() {
+        assert!(matches("(max-width: 500px)", 500.0, 600.0));
+        assert!(!matches("(max-width: 500px)", 501.0, 600.0));
+        assert!(matches("(min-width: 40em)", 640.0, 600.0));
+        assert!(!matches("(min-width: 40em)", 639.0, 600.0));
+        assert!(matches(
+            "(min-height: 300px) and (max-height: 700px)",
+            10.0,
+            600.0
+        ));
+        assert!(matches("(width: 800px)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_media_types_and_negation() {
+        assert!(matches("screen", 800.0, 600.0));
+        assert!(matches("all and (min-width: 0)", 800.0, 600.0));
+        assert!(!matches("print", 800.0, 600.0));
+        assert!(matches("not print", 800.0, 600.0));
+        assert!(matches("only screen and (max-width: 900px)", 800.0, 600.0));
+        assert!(!matches("not screen and (max-width: 900px)", 800.0, 600.0));
+        assert!(matches("print, (max-width: 900px)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_orientation_and_aspect_ratio() {
+        assert!(matches("(orientation: landscape)", 800.0, 600.0));
+        assert!(matches("(orientation: portrait)", 400.0, 800.0));
+        assert!(matches("(min-aspect-ratio: 16/9)", 1920.0, 1080.0));
+        assert!(!matches("(min-aspect-ratio: 16/9)", 800.0, 600.0));
+        assert!(matches("(max-aspect-ratio: 1)", 500.0, 500.0));
+    }
+
+    #[test]
+    fn test_color_scheme() {
+        let dark = MediaEnvironment::screen(800.0, 600.0).with_color_scheme(ColorScheme::Dark);
+        assert!(MediaQueryList::parse("(prefers-color-scheme: dark)").matches(&dark));
+        assert!(!matches("(prefers-color-scheme: dark)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_resolution() {
+        let hidpi = MediaEnvironment::screen(800.0, 600.0).with_resolution(2.0);
+        for query in [
+            "(resolution: 2dppx)",
+            "(min-resolution: 192dpi)",
+            "(min-resolution: 1.5x)",
+            "(-webkit-min-device-pixel-ratio: 2)",
+        ] {
+            assert!(MediaQueryList::parse(query).matches(&hidpi), "{query}");
+            assert!(!matches(query, 800.0, 600.0), "{query}");
+        }
+        assert!(matches("(max-resolution: 1dppx)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_accessibility_preferences() {
+        let env = MediaEnvironment::screen(800.0, 600.0)
+            .with_reduced_motion(true)
+            .with_forced_colors(true);
+        assert!(MediaQueryList::parse("(prefers-reduced-motion: reduce)").matches(&env));
+        assert!(MediaQueryList::parse("(forced-colors: active)").matches(&env));
+        assert!(matches(
+            "(prefers-reduced-motion: no-preference)",
+            800.0,
+            600.0
+        ));
+        assert!(matches("(forced-colors: none)", 800.0, 600.0));
+        assert!(!matches("(forced-colors: on)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_never_match() {
+        assert!(!matches("(hover: hover)", 800.0, 600.0));
+        assert!(!matches("(max-width: wide)", 800.0, 600.0));
+        assert!(!matches("screen and (max-width: 500px", 400.0, 600.0));
+        assert!(matches("", 800.0, 600.0));
+    }
+}
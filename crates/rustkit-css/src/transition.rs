@@ -0,0 +1,320 @@
+//! `transition-*` properties and the translate/scale subset of `transform`.
+//!
+//! Transition longhands are kept as lists; a property at index `i` of
+//! `transition-property` takes the other longhands at `i`, repeating shorter
+//! lists as needed.
+
+use crate::ComputedStyle;
+
+/// The transition that applies to one property of an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionSpec {
+    /// Duration in seconds.
+    pub duration: f32,
+    /// Delay in seconds; may be negative.
+    pub delay: f32,
+    /// Unparsed `<easing-function>`.
+    pub timing_function: String,
+}
+
+/// Longhands of a `transition` shorthand, one entry per comma-separated item.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransitionLists {
+    pub properties: Vec<String>,
+    pub durations: Vec<f32>,
+    pub timing_functions: Vec<String>,
+    pub delays: Vec<f32>,
+}
+
+/// Split `value` at commas outside parentheses.
+pub fn split_top_level_commas(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(value[start..].trim());
+    items
+}
+
+/// Parse a `<time>` in `s` or `ms` into seconds.
+pub fn parse_time(value: &str) -> Option<f32> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(ms) = value.strip_suffix("ms") {
+        ms.parse::<f32>().ok().map(|ms| ms / 1000.0)
+    } else {
+        value.strip_suffix('s')?.parse().ok()
+    }
+}
+
+/// Parse a comma-separated list of times, as in `transition-duration`.
+pub fn parse_time_list(value: &str) -> Option<Vec<f32>> {
+    split_top_level_commas(value)
+        .into_iter()
+        .map(parse_time)
+        .collect()
+}
+
+fn is_easing_function(token: &str) -> bool {
+    matches!(
+        token,
+        "linear" | "ease" | "ease-in" | "ease-out" | "ease-in-out" | "step-start" | "step-end"
+    ) || token.starts_with("cubic-bezier(")
+        || token.starts_with("steps(")
+}
+
+/// Split a transition item at whitespace outside parentheses.
+pub(crate) fn tokens(item: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, c) in item.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    tokens.push(&item[s..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    tokens.extend(start.map(|s| &item[s..]));
+    tokens
+}
+
+/// Parse the `transition` shorthand. Omitted parts take their initial
+/// values: `all`, `0s`, `ease` and `0s`.
+pub fn parse_transition(value: &str) -> Option<TransitionLists> {
+    let mut lists = TransitionLists::default();
+    for item in split_top_level_commas(&value.to_ascii_lowercase()) {
+        let (mut property, mut duration, mut delay, mut timing) = (None, None, None, None);
+        for token in tokens(item) {
+            if let Some(time) = parse_time(token) {
+                match duration {
+                    None => duration = Some(time),
+                    Some(_) if delay.is_none() => delay = Some(time),
+                    Some(_) => return None,
+                }
+            } else if is_easing_function(token) && timing.is_none() {
+                timing = Some(token.to_string());
+            } else if property.is_none() {
+                property = Some(token.to_string());
+            } else {
+                return None;
+            }
+        }
+        lists
+            .properties
+            .push(property.unwrap_or_else(|| "all".into()));
+        lists.durations.push(duration.unwrap_or(0.0));
+        lists
+            .timing_functions
+            .push(timing.unwrap_or_else(|| "ease".into()));
+        lists.delays.push(delay.unwrap_or(0.0));
+    }
+    Some(lists)
+}
+
+impl ComputedStyle {
+    /// The transition for `property`, if one with a positive combined
+    /// duration applies. Later entries of `transition-property` win.
+    pub fn transition_for(&self, property: &str) -> Option<TransitionSpec> {
+        let index = if self.transition_property.is_empty() {
+            // The initial value is `all`
+            0
+        } else {
+            self.transition_property
+                .iter()
+                .rposition(|p| p == property || p == "all")?
+        };
+        let pick = |list: &[f32]| match list.len() {
+            0 => 0.0,
+            len => list[index % len],
+        };
+        let spec = TransitionSpec {
+            duration: pick(&self.transition_duration).max(0.0),
+            delay: pick(&self.transition_delay),
+            timing_function: match self.transition_timing_function.len() {
+                0 => "ease".to_string(),
+                len => self.transition_timing_function[index % len].clone(),
+            },
+        };
+        (spec.duration + spec.delay > 0.0).then_some(spec)
+    }
+}
+
+/// A `transform` made of translations and scales, applied about the
+/// transform origin as `p' = scale * p + translate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TranslateScale {
+    pub translate_x: f32,
+    pub translate_y: f32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl Default for TranslateScale {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl TranslateScale {
+    pub const IDENTITY: Self = Self {
+        translate_x: 0.0,
+        translate_y: 0.0,
+        scale_x: 1.0,
+        scale_y: 1.0,
+    };
+
+    /// Parse a `transform` value. Returns `None` for functions other than
+    /// `translate*` and `scale*`, and for non-pixel translations.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        if value == "none" {
+            return Some(Self::IDENTITY);
+        }
+        let mut result = Self::IDENTITY;
+        for function in tokens(&value) {
+            let (name, args) = function.strip_suffix(')')?.split_once('(')?;
+            let args: Vec<&str> = args.split(',').map(str::trim).collect();
+            let step = match (name, args.as_slice()) {
+                ("translate", [x]) => Self::translation(px(x)?, 0.0),
+                ("translate", [x, y]) => Self::translation(px(x)?, px(y)?),
+                ("translatex", [x]) => Self::translation(px(x)?, 0.0),
+                ("translatey", [y]) => Self::translation(0.0, px(y)?),
+                ("scale", [s]) => Self::scaling(s.parse().ok()?, s.parse().ok()?),
+                ("scale", [x, y]) => Self::scaling(x.parse().ok()?, y.parse().ok()?),
+                ("scalex", [x]) => Self::scaling(x.parse().ok()?, 1.0),
+                ("scaley", [y]) => Self::scaling(1.0, y.parse().ok()?),
+                _ => return None,
+            };
+            result = result.then(&step);
+        }
+        Some(result)
+    }
+
+    fn translation(x: f32, y: f32) -> Self {
+        Self {
+            translate_x: x,
+            translate_y: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    fn scaling(x: f32, y: f32) -> Self {
+        Self {
+            scale_x: x,
+            scale_y: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// `self` applied after `inner`, as functions compose in a transform list.
+    fn then(&self, inner: &Self) -> Self {
+        Self {
+            translate_x: self.scale_x * inner.translate_x + self.translate_x,
+            translate_y: self.scale_y * inner.translate_y + self.translate_y,
+            scale_x: self.scale_x * inner.scale_x,
+            scale_y: self.scale_y * inner.scale_y,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// Map a point given relative to the transform origin.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale_x + self.translate_x,
+            y * self.scale_y + self.translate_y,
+        )
+    }
+
+    /// Interpolate component-wise.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            translate_x: lerp(self.translate_x, other.translate_x),
+            translate_y: lerp(self.translate_y, other.translate_y),
+            scale_x: lerp(self.scale_x, other.scale_x),
+            scale_y: lerp(self.scale_y, other.scale_y),
+        }
+    }
+
+    /// Serialize as a `transform` value.
+    pub fn to_css(&self) -> String {
+        format!(
+            "translate({}px, {}px) scale({}, {})",
+            self.translate_x, self.translate_y, self.scale_x, self.scale_y
+        )
+    }
+}
+
+fn px(value: &str) -> Option<f32> {
+    match value {
+        "0" => Some(0.0),
+        value => value.strip_suffix("px")?.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_shorthand_and_lookup() {
+        let lists = parse_transition(
+            "opacity .2s, transform 100ms cubic-bezier(0.1, 0.7, 1.0, 0.1) 50ms, color 1s",
+        )
+        .unwrap();
+        assert_eq!(lists.properties, ["opacity", "transform", "color"]);
+        assert_eq!(lists.durations, [0.2, 0.1, 1.0]);
+        assert_eq!(
+            lists.timing_functions[1],
+            "cubic-bezier(0.1, 0.7, 1.0, 0.1)"
+        );
+        assert_eq!(lists.delays, [0.0, 0.05, 0.0]);
+        assert!(parse_transition("opacity 1s 2s 3s").is_none());
+
+        let mut style = ComputedStyle::new();
+        assert_eq!(style.transition_for("opacity"), None);
+        style.transition_property = vec!["all".into(), "color".into()];
+        style.transition_duration = parse_time_list("1s, 250ms").unwrap();
+        assert_eq!(style.transition_for("color").unwrap().duration, 0.25);
+        assert_eq!(style.transition_for("width").unwrap().duration, 1.0);
+        assert_eq!(
+            style.transition_for("width").unwrap().timing_function,
+            "ease"
+        );
+    }
+
+    #[test]
+    fn test_translate_scale_parse_and_lerp() {
+        let t = TranslateScale::parse("translateX(10px) scale(2)").unwrap();
+        // The scale applies first, then the translation
+        assert_eq!(t.apply(5.0, 5.0), (20.0, 10.0));
+        let t = TranslateScale::parse("scale(2) translate(10px, 0)").unwrap();
+        assert_eq!(t.apply(0.0, 0.0), (20.0, 0.0));
+        assert!(TranslateScale::parse("none").unwrap().is_identity());
+        assert!(TranslateScale::parse("rotate(45deg)").is_none());
+
+        let half = TranslateScale::IDENTITY.lerp(&t, 0.5);
+        assert_eq!((half.translate_x, half.scale_x), (10.0, 1.5));
+        assert_eq!(TranslateScale::parse(&half.to_css()), Some(half));
+    }
+}
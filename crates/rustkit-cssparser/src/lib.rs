@@ -4,7 +4,8 @@
 //! `cssparser` dependency over time.
 //!
 //! Current implementation is a **minimal** stylesheet parser suitable for RustKit's current
-//! needs: parse basic rules `selector { prop: value; }` into an AST.
+//! needs: parse basic rules `selector { prop: value; }` into an AST. Rules inside `@media`
+//! blocks are flattened, each keeping the conditions of its enclosing blocks.
 
 use thiserror::Error;
 
@@ -29,6 +30,9 @@ pub struct StylesheetAst {
 pub struct RuleAst {
     pub selector: String,
     pub declarations: Vec<DeclarationAst>,
+    /// Preludes of the enclosing `@media` blocks, outermost first. The rule
+    /// applies only when all of them match.
+    pub media: Vec<String>,
 }
 
 /// A parsed declaration AST.
@@ -43,7 +47,8 @@ pub struct DeclarationAst {
 ///
 /// Notes:
 /// - This is not a full CSS parser.
-/// - It does not currently support nested rules (`@media`, `@supports`) or complex tokenization.
+/// - `@media` blocks are supported; other nested rules (`@supports`) and complex
+///   tokenization are not.
 /// - It attempts to be robust for common author CSS and RustKit test inputs.
 pub fn parse_stylesheet(css: &str) -> Result<StylesheetAst, ParseError> {
     let mut out = StylesheetAst::default();
@@ -74,6 +79,15 @@ pub fn parse_stylesheet(css: &str) -> Result<StylesheetAst, ParseError> {
 
         if !in_block {
             if c == '{' {
+                if let Some(prelude) = media_prelude(&current_selector) {
+                    let body = take_block(&mut chars)?;
+                    for mut rule in parse_stylesheet(&body)?.rules {
+                        rule.media.insert(0, prelude.clone());
+                        out.rules.push(rule);
+                    }
+                    current_selector.clear();
+                    continue;
+                }
                 in_block = true;
                 current_selector = current_selector.trim().to_string();
                 current_property.clear();
@@ -94,6 +108,7 @@ pub fn parse_stylesheet(css: &str) -> Result<StylesheetAst, ParseError> {
                 out.rules.push(RuleAst {
                     selector,
                     declarations: current_decls.clone(),
+                    media: Vec::new(),
                 });
             }
 
@@ -134,6 +149,32 @@ pub fn parse_stylesheet(css: &str) -> Result<StylesheetAst, ParseError> {
     Ok(out)
 }
 
+/// The condition of an `@media` rule prelude, if `selector` is one.
+fn media_prelude(selector: &str) -> Option<String> {
+    let selector = selector.trim();
+    let keyword = selector.get(..6)?;
+    if !keyword.eq_ignore_ascii_case("@media") {
+        return None;
+    }
+    Some(selector[6..].trim().to_string())
+}
+
+/// Consume the contents of a block up to its matching `}`.
+fn take_block(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<String, ParseError> {
+    let mut body = String::new();
+    let mut depth = 0usize;
+    for c in chars.by_ref() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok(body),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        body.push(c);
+    }
+    Err(ParseError::UnexpectedEof)
+}
+
 fn flush_decl(
     current_property: &mut String,
     current_value: &mut String,
@@ -198,6 +239,28 @@ mod tests {
         assert_eq!(ast.rules[0].declarations.len(), 2);
     }
 
+    #[test]
+    fn parse_media_blocks() {
+        let css = r#"
+            p { color: black; }
+            @media screen and (max-width: 600px) {
+                p { color: red; }
+                @media (orientation: portrait) { .nav { display: none; } }
+            }
+            div { width: 10px; }
+        "#;
+        let ast = parse_stylesheet(css).unwrap();
+        let selectors: Vec<_> = ast.rules.iter().map(|r| r.selector.as_str()).collect();
+        assert_eq!(selectors, ["p", "p", ".nav", "div"]);
+        assert!(ast.rules[0].media.is_empty());
+        assert_eq!(ast.rules[1].media, ["screen and (max-width: 600px)"]);
+        assert_eq!(
+            ast.rules[2].media,
+            ["screen and (max-width: 600px)", "(orientation: portrait)"]
+        );
+        assert!(ast.rules[3].media.is_empty());
+    }
+
     #[test]
     fn unclosed_block_is_error() {
         let css = "body { color: black;";
@@ -0,0 +1,1184 @@
+//! Editing inside `contenteditable` hosts.
+//!
+//! Carets and selections are DOM positions: a text node and a char offset
+//! into its data, or an element and a child index. Text nodes can't change
+//! in place, so an edit to one replaces it with a new node and the
+//! selection moves to the replacement.
+//!
+//! Edits are [`EditAction`]s applied with [`Document::apply_edit`]. Deleting
+//! across a block boundary merges the blocks; Enter either inserts a `<br>`
+//! or splits the block around the caret (see [`EnterBehavior`]).
+
+use crate::{Document, Node, NodeId, NodeType};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+/// A point in the tree: a char offset into a text node, or a child index
+/// of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DomPosition {
+    pub node: NodeId,
+    pub offset: usize,
+}
+
+impl DomPosition {
+    pub fn new(node: NodeId, offset: usize) -> Self {
+        Self { node, offset }
+    }
+}
+
+/// A selection in an editing host. Extending it moves the focus and
+/// leaves the anchor where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditSelection {
+    pub anchor: DomPosition,
+    pub focus: DomPosition,
+}
+
+impl EditSelection {
+    pub fn new(anchor: DomPosition, focus: DomPosition) -> Self {
+        Self { anchor, focus }
+    }
+
+    /// A caret at `position`.
+    pub fn collapsed(position: DomPosition) -> Self {
+        Self::new(position, position)
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+}
+
+/// What Enter does in an editing host.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnterBehavior {
+    /// Insert a `<br>`.
+    #[default]
+    LineBreak,
+    /// Split the block around the caret in two.
+    SplitBlock,
+}
+
+/// An edit, named after the `inputType` of the input events it fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditAction {
+    InsertText(String),
+    /// Plain text from the clipboard; its newlines become line breaks.
+    InsertFromPaste(String),
+    InsertLineBreak,
+    InsertParagraph,
+    DeleteContentBackward,
+    DeleteContentForward,
+    FormatBold,
+    FormatItalic,
+}
+
+impl EditAction {
+    /// The `inputType` of the action's `beforeinput` and `input` events.
+    pub fn input_type(&self) -> &'static str {
+        match self {
+            Self::InsertText(_) => "insertText",
+            Self::InsertFromPaste(_) => "insertFromPaste",
+            Self::InsertLineBreak => "insertLineBreak",
+            Self::InsertParagraph => "insertParagraph",
+            Self::DeleteContentBackward => "deleteContentBackward",
+            Self::DeleteContentForward => "deleteContentForward",
+            Self::FormatBold => "formatBold",
+            Self::FormatItalic => "formatItalic",
+        }
+    }
+
+    /// The `data` of the action's input events.
+    pub fn data(&self) -> Option<&str> {
+        match self {
+            Self::InsertText(text) | Self::InsertFromPaste(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// A caret movement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaretMove {
+    Backward,
+    Forward,
+    HostStart,
+    HostEnd,
+}
+
+/// Elements that start a new line rather than flowing with text.
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements the caret steps over as a whole.
+const LEAF_TAGS: &[&str] = &["br", "img", "input"];
+
+fn has_tag(node: &Node, tags: &[&str]) -> bool {
+    node.tag_name()
+        .is_some_and(|t| tags.iter().any(|tag| t.eq_ignore_ascii_case(tag)))
+}
+
+fn is_block(node: &Node) -> bool {
+    has_tag(node, BLOCK_TAGS)
+}
+
+fn is_br(node: &Node) -> bool {
+    has_tag(node, &["br"])
+}
+
+fn text_of(node: &Node) -> Option<&str> {
+    match &node.node_type {
+        NodeType::Text(data) => Some(data),
+        _ => None,
+    }
+}
+
+fn char_len(node: &Node) -> usize {
+    text_of(node).map_or(0, |data| data.chars().count())
+}
+
+fn index_in_parent(node: &Rc<Node>) -> usize {
+    node.parent()
+        .and_then(|p| p.children().iter().position(|c| Rc::ptr_eq(c, node)))
+        .unwrap_or(0)
+}
+
+/// `data` with chars `start..end` replaced by `insert`.
+fn splice(data: &str, start: usize, end: usize, insert: &str) -> String {
+    let byte = |i: usize| data.char_indices().nth(i).map_or(data.len(), |(b, _)| b);
+    let (start, end) = (byte(start), byte(end));
+    format!("{}{}{}", &data[..start], insert, &data[end..])
+}
+
+/// Whether `node` has `contenteditable` on (`Some(true)`), off, or unset.
+fn editable_attribute(node: &Node) -> Option<bool> {
+    node.get_attribute("contenteditable").map(|value| {
+        value.is_empty()
+            || value.eq_ignore_ascii_case("true")
+            || value.eq_ignore_ascii_case("plaintext-only")
+    })
+}
+
+/// The editing host `node` is in: the outermost of the `contenteditable`
+/// elements around it, up to one that turns editing off.
+pub fn editing_host(node: &Rc<Node>) -> Option<Rc<Node>> {
+    let mut host = None;
+    let mut current = Some(node.clone());
+    while let Some(node) = current {
+        match editable_attribute(&node) {
+            Some(true) => host = Some(node.clone()),
+            Some(false) => break,
+            None => {}
+        }
+        current = node.parent();
+    }
+    host
+}
+
+/// Whether `node` is an editing host itself.
+pub fn is_editing_host(node: &Rc<Node>) -> bool {
+    editing_host(node).is_some_and(|host| Rc::ptr_eq(&host, node))
+}
+
+/// Whitespace between blocks, which the caret never stops in.
+fn is_interblock_space(node: &Rc<Node>) -> bool {
+    text_of(node).is_some_and(|data| data.trim().is_empty())
+        && (node.previous_sibling().is_none_or(|n| is_block(&n))
+            || node.next_sibling().is_none_or(|n| is_block(&n)))
+}
+
+/// Text nodes and atomic elements under `root`, in tree order.
+fn leaves(root: &Rc<Node>) -> Vec<Rc<Node>> {
+    fn walk(node: &Rc<Node>, leaves: &mut Vec<Rc<Node>>) {
+        for child in node.children() {
+            if child.is_text() {
+                if char_len(&child) > 0 && !is_interblock_space(&child) {
+                    leaves.push(child);
+                }
+            } else if has_tag(&child, LEAF_TAGS) {
+                leaves.push(child);
+            } else if child.is_element() {
+                walk(&child, leaves);
+            }
+        }
+    }
+    let mut found = Vec::new();
+    walk(root, &mut found);
+    found
+}
+
+/// The block `node` lays out in: its nearest block ancestor, or the host.
+fn block_of(host: &Rc<Node>, node: &Rc<Node>) -> Rc<Node> {
+    let mut current = match node.is_text() || has_tag(node, LEAF_TAGS) {
+        true => node.parent(),
+        false => Some(node.clone()),
+    };
+    while let Some(node) = current {
+        if Rc::ptr_eq(&node, host) || is_block(&node) {
+            return node;
+        }
+        current = node.parent();
+    }
+    host.clone()
+}
+
+/// Child indices from `host` down to `node`, then `offset`. Positions in
+/// tree order compare in the same order as their paths.
+fn path(host: &Rc<Node>, node: &Rc<Node>, offset: usize) -> Vec<usize> {
+    let mut path = vec![offset];
+    let mut current = node.clone();
+    while !Rc::ptr_eq(&current, host) {
+        let Some(parent) = current.parent() else {
+            break;
+        };
+        path.push(index_in_parent(&current));
+        current = parent;
+    }
+    path.reverse();
+    path
+}
+
+/// The position just before `leaf`.
+fn before_leaf(leaf: &Rc<Node>) -> DomPosition {
+    match leaf.is_text() {
+        true => DomPosition::new(leaf.id, 0),
+        false => DomPosition::new(
+            leaf.parent().map_or(leaf.id, |p| p.id),
+            index_in_parent(leaf),
+        ),
+    }
+}
+
+/// The position just after `leaf`.
+fn after_leaf(leaf: &Rc<Node>) -> DomPosition {
+    match leaf.is_text() {
+        true => DomPosition::new(leaf.id, char_len(leaf)),
+        false => DomPosition::new(
+            leaf.parent().map_or(leaf.id, |p| p.id),
+            index_in_parent(leaf) + 1,
+        ),
+    }
+}
+
+/// Where the caret goes to be at the start of `node`'s content.
+pub fn start_of(node: &Rc<Node>) -> DomPosition {
+    leaves(node)
+        .first()
+        .map_or(DomPosition::new(node.id, 0), before_leaf)
+}
+
+/// Where the caret goes to be at the end of `node`'s content. A `<br>`
+/// ending it only holds the line open, so the caret goes before it.
+pub fn end_of(node: &Rc<Node>) -> DomPosition {
+    match leaves(node).last() {
+        Some(leaf) if is_br(leaf) => before_leaf(leaf),
+        Some(leaf) => after_leaf(leaf),
+        None => DomPosition::new(node.id, node.children().len()),
+    }
+}
+
+/// A position between children: a parent and a child index.
+type Boundary = (Rc<Node>, usize);
+
+impl Document {
+    /// Apply `action` in `host` at `selection`, and move the selection to
+    /// where the edit leaves the caret. Returns false if nothing changed.
+    pub fn apply_edit(
+        &self,
+        host: &Rc<Node>,
+        selection: &mut EditSelection,
+        action: &EditAction,
+    ) -> bool {
+        if !self.selection_in(host, selection) {
+            return false;
+        }
+        match action {
+            EditAction::InsertText(text) => self.insert_text(host, selection, text),
+            EditAction::InsertFromPaste(text) => {
+                let mut changed = false;
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        changed |= self.insert_line_break(host, selection);
+                    }
+                    changed |= self.insert_text(host, selection, line.trim_end_matches('\r'));
+                }
+                changed
+            }
+            EditAction::InsertLineBreak => self.insert_line_break(host, selection),
+            EditAction::InsertParagraph => self.insert_paragraph(host, selection),
+            EditAction::DeleteContentBackward => self.delete_backward(host, selection),
+            EditAction::DeleteContentForward => self.delete_forward(host, selection),
+            EditAction::FormatBold => self.toggle_format(host, selection, "b"),
+            EditAction::FormatItalic => self.toggle_format(host, selection, "i"),
+        }
+    }
+
+    /// Move the focus of `selection` in `host`. With `extend` the anchor
+    /// stays; without it the selection collapses, and a non-collapsed one
+    /// collapses to the end in the direction of travel.
+    pub fn move_caret(
+        &self,
+        host: &Rc<Node>,
+        selection: &mut EditSelection,
+        movement: CaretMove,
+        extend: bool,
+    ) -> bool {
+        if !self.selection_in(host, selection) {
+            return false;
+        }
+        let before = *selection;
+        let target = match movement {
+            CaretMove::Backward | CaretMove::Forward if !extend && !selection.is_collapsed() => {
+                let (start, end) = self.ordered(host, selection);
+                Some(match movement {
+                    CaretMove::Backward => start,
+                    _ => end,
+                })
+            }
+            CaretMove::Backward => self.step_backward(host, self.normalize(selection.focus)),
+            CaretMove::Forward => self.step_forward(host, self.normalize(selection.focus)),
+            CaretMove::HostStart => Some(start_of(host)),
+            CaretMove::HostEnd => Some(end_of(host)),
+        };
+        let Some(target) = target else {
+            return false;
+        };
+        selection.focus = target;
+        if !extend {
+            selection.anchor = target;
+        }
+        *selection != before
+    }
+
+    /// The selected part of each text node `selection` covers, as char
+    /// ranges, in tree order.
+    pub fn selected_text(
+        &self,
+        host: &Rc<Node>,
+        selection: &EditSelection,
+    ) -> Vec<(NodeId, usize, usize)> {
+        if selection.is_collapsed() || !self.selection_in(host, selection) {
+            return Vec::new();
+        }
+        let (start, end) = self.ordered(host, selection);
+        let position_path = |p: DomPosition| {
+            self.get_node(p.node)
+                .map(|node| path(host, &node, p.offset))
+                .unwrap_or_default()
+        };
+        let (start_path, end_path) = (position_path(start), position_path(end));
+        leaves(host)
+            .iter()
+            .filter(|leaf| leaf.is_text())
+            .filter_map(|text| {
+                let len = char_len(text);
+                let from = match position_path(before_leaf(text)) < start_path {
+                    true if start.node == text.id => start.offset,
+                    true => return None,
+                    false => 0,
+                };
+                let to = match position_path(after_leaf(text)) > end_path {
+                    true if end.node == text.id => end.offset,
+                    true => return None,
+                    false => len,
+                };
+                (from < to).then_some((text.id, from, to))
+            })
+            .collect()
+    }
+
+    fn selection_in(&self, host: &Rc<Node>, selection: &EditSelection) -> bool {
+        [selection.anchor, selection.focus].iter().all(|p| {
+            self.get_node(p.node)
+                .is_some_and(|node| host.contains(&node))
+        })
+    }
+
+    /// The ends of `selection` in tree order.
+    fn ordered(&self, host: &Rc<Node>, selection: &EditSelection) -> (DomPosition, DomPosition) {
+        let (a, b) = (selection.anchor, selection.focus);
+        match self.compare(host, a, b) {
+            Ordering::Greater => (b, a),
+            _ => (a, b),
+        }
+    }
+
+    fn compare(&self, host: &Rc<Node>, a: DomPosition, b: DomPosition) -> Ordering {
+        match (self.get_node(a.node), self.get_node(b.node)) {
+            (Some(x), Some(y)) => path(host, &x, a.offset).cmp(&path(host, &y, b.offset)),
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// Move a position between children into an adjacent text node.
+    fn normalize(&self, position: DomPosition) -> DomPosition {
+        let Some(node) = self.get_node(position.node) else {
+            return position;
+        };
+        if node.is_text() {
+            return DomPosition::new(node.id, position.offset.min(char_len(&node)));
+        }
+        let children = node.children();
+        let before = position.offset.checked_sub(1).and_then(|i| children.get(i));
+        if let Some(text) = before.filter(|c| c.is_text()) {
+            return DomPosition::new(text.id, char_len(text));
+        }
+        if let Some(text) = children.get(position.offset).filter(|c| c.is_text()) {
+            return DomPosition::new(text.id, 0);
+        }
+        position
+    }
+
+    fn position_path(&self, host: &Rc<Node>, position: DomPosition) -> Option<Vec<usize>> {
+        self.get_node(position.node)
+            .map(|node| path(host, &node, position.offset))
+    }
+
+    /// The last leaf ending at or before `position`.
+    fn leaf_before(&self, host: &Rc<Node>, position: DomPosition) -> Option<Rc<Node>> {
+        let at = self.position_path(host, position)?;
+        leaves(host).into_iter().rev().find(|leaf| {
+            self.position_path(host, after_leaf(leaf))
+                .is_some_and(|p| p <= at)
+        })
+    }
+
+    /// The first leaf starting at or after `position`.
+    fn leaf_after(&self, host: &Rc<Node>, position: DomPosition) -> Option<Rc<Node>> {
+        let at = self.position_path(host, position)?;
+        leaves(host).into_iter().find(|leaf| {
+            self.position_path(host, before_leaf(leaf))
+                .is_some_and(|p| p >= at)
+        })
+    }
+
+    fn step_backward(&self, host: &Rc<Node>, position: DomPosition) -> Option<DomPosition> {
+        let node = self.get_node(position.node)?;
+        if node.is_text() && position.offset > 0 {
+            return Some(DomPosition::new(node.id, position.offset - 1));
+        }
+        let previous = self.leaf_before(host, position)?;
+        let same_block = Rc::ptr_eq(&block_of(host, &previous), &block_of(host, &node));
+        Some(match previous.is_text() {
+            true if same_block => DomPosition::new(previous.id, char_len(&previous) - 1),
+            true => after_leaf(&previous),
+            false => self.normalize(before_leaf(&previous)),
+        })
+    }
+
+    fn step_forward(&self, host: &Rc<Node>, position: DomPosition) -> Option<DomPosition> {
+        let node = self.get_node(position.node)?;
+        if node.is_text() && position.offset < char_len(&node) {
+            return Some(DomPosition::new(node.id, position.offset + 1));
+        }
+        let next = self.leaf_after(host, position)?;
+        let same_block = Rc::ptr_eq(&block_of(host, &next), &block_of(host, &node));
+        Some(match next.is_text() {
+            true if same_block => DomPosition::new(next.id, 1),
+            true => before_leaf(&next),
+            false => self.normalize(after_leaf(&next)),
+        })
+    }
+
+    /// Put a text node holding `data` in place of `text`, or remove `text`
+    /// when `data` is empty. Returns the replacement.
+    fn replace_text(&self, text: &Rc<Node>, data: &str) -> Option<Rc<Node>> {
+        let parent = text.parent()?;
+        if data.is_empty() {
+            let _ = self.remove_child(&parent, text);
+            return None;
+        }
+        let replacement = self.create_text_node(data);
+        self.replace_child(&parent, replacement.clone(), text)
+            .ok()?;
+        Some(replacement)
+    }
+
+    /// Turn `position` into a boundary between children, splitting its text
+    /// node in two when it falls inside one. Also returns the node now
+    /// holding the text before a split.
+    fn split_text(&self, position: DomPosition) -> Option<(Boundary, Option<Rc<Node>>)> {
+        let node = self.get_node(position.node)?;
+        let Some(data) = text_of(&node) else {
+            return Some(((node.clone(), position.offset), None));
+        };
+        let parent = node.parent()?;
+        let index = index_in_parent(&node);
+        let len = data.chars().count();
+        match position.offset {
+            0 => Some(((parent, index), None)),
+            offset if offset >= len => Some(((parent, index + 1), None)),
+            offset => {
+                let before = self.create_text_node(&splice(data, offset, len, ""));
+                let after = self.create_text_node(&splice(data, 0, offset, ""));
+                self.replace_child(&parent, before.clone(), &node).ok()?;
+                self.insert_before(&parent, after, before.next_sibling().as_ref())
+                    .ok()?;
+                Some(((parent, index + 1), Some(before)))
+            }
+        }
+    }
+
+    /// Split the text nodes at both ends of `selection`, returning the
+    /// boundaries the ends become, in tree order.
+    fn split_range(
+        &self,
+        host: &Rc<Node>,
+        selection: &EditSelection,
+    ) -> Option<(Boundary, Boundary)> {
+        let (mut start, end) = self.ordered(host, selection);
+        let ((end_parent, mut end_index), before) = self.split_text(end)?;
+        if let Some(before) = before {
+            if start.node == end.node {
+                start.node = before.id;
+            }
+        }
+        let ((start_parent, start_index), split) = self.split_text(start)?;
+        if split.is_some() && Rc::ptr_eq(&start_parent, &end_parent) {
+            end_index += 1;
+        }
+        Some(((start_parent, start_index), (end_parent, end_index)))
+    }
+
+    /// Leaves wholly between two boundaries.
+    fn leaves_between(&self, host: &Rc<Node>, start: &Boundary, end: &Boundary) -> Vec<Rc<Node>> {
+        let (start, end) = (path(host, &start.0, start.1), path(host, &end.0, end.1));
+        leaves(host)
+            .into_iter()
+            .filter(|leaf| {
+                let Some(parent) = leaf.parent() else {
+                    return false;
+                };
+                let index = index_in_parent(leaf);
+                path(host, &parent, index) >= start && path(host, &parent, index + 1) <= end
+            })
+            .collect()
+    }
+
+    /// Split the elements from `container` up to, not including, `top` at
+    /// child `index` of `container`, so the split point becomes a boundary
+    /// among `top`'s children. What follows it moves into shallow copies of
+    /// the elements split.
+    fn split_to(&self, container: &Rc<Node>, index: usize, top: &Rc<Node>) -> Option<usize> {
+        let (mut container, mut index) = (container.clone(), index);
+        while !Rc::ptr_eq(&container, top) {
+            let parent = container.parent()?;
+            // An element split at its edge stays whole rather than leave an
+            // empty copy behind
+            if index == 0 {
+                index = index_in_parent(&container);
+            } else if index >= container.children().len() {
+                index = index_in_parent(&container) + 1;
+            } else {
+                let copy = self.clone_node(&container, false);
+                for child in container.children().into_iter().skip(index) {
+                    self.append_child(&copy, child).ok()?;
+                }
+                self.insert_before(&parent, copy, container.next_sibling().as_ref())
+                    .ok()?;
+                index = index_in_parent(&container) + 1;
+            }
+            container = parent;
+        }
+        Some(index)
+    }
+
+    /// Split `top` in two at child `index` of `container`, which is `top`
+    /// or inside it, and return the copy of `top` holding what follows.
+    fn split_tree(&self, container: &Rc<Node>, index: usize, top: &Rc<Node>) -> Option<Rc<Node>> {
+        let index = self.split_to(container, index, top)?;
+        let parent = top.parent()?;
+        let copy = self.clone_node(top, false);
+        for child in top.children().into_iter().skip(index) {
+            self.append_child(&copy, child).ok()?;
+        }
+        self.insert_before(&parent, copy.clone(), top.next_sibling().as_ref())
+            .ok()?;
+        Some(copy)
+    }
+
+    /// Join `second` onto the end of `first`. Returns where they meet.
+    fn merge_blocks(&self, first: &Rc<Node>, second: &Rc<Node>) -> Option<DomPosition> {
+        if Rc::ptr_eq(first, second) || second.contains(first) {
+            return None;
+        }
+        let parent = second.parent()?;
+        let join = if first.contains(second) {
+            // A block inside the first one's content is unwrapped in place
+            let join = DomPosition::new(parent.id, index_in_parent(second));
+            for child in second.children() {
+                self.insert_before(&parent, child, Some(second)).ok()?;
+            }
+            join
+        } else {
+            // A <br> ending the first block only held its line open
+            if let Some(br) = first.last_child().filter(|c| is_br(c)) {
+                if second.first_child().is_some() {
+                    self.remove_child(first, &br).ok()?;
+                }
+            }
+            let join = DomPosition::new(first.id, first.children().len());
+            for child in second.children() {
+                self.append_child(first, child).ok()?;
+            }
+            join
+        };
+        self.remove_child(&parent, second).ok()?;
+        Some(self.normalize(join))
+    }
+
+    /// Remove the selected content and collapse the selection to where it
+    /// started, merging the block it ended in into the one it started in.
+    fn delete_range(&self, host: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        let Some((start, end)) = self.split_range(host, selection) else {
+            return false;
+        };
+        let (start_block, end_block) = (block_of(host, &start.0), block_of(host, &end.0));
+        let selected = self.leaves_between(host, &start, &end);
+        for leaf in &selected {
+            let mut parent = leaf.parent();
+            leaf.remove_from_parent();
+            // Drop elements the removal emptied
+            while let Some(element) = parent {
+                if Rc::ptr_eq(&element, host)
+                    || element.first_child().is_some()
+                    || element.contains(&start.0)
+                    || element.contains(&end.0)
+                {
+                    break;
+                }
+                parent = element.parent();
+                element.remove_from_parent();
+            }
+        }
+        let mut caret = self.normalize(DomPosition::new(start.0.id, start.1));
+        if !Rc::ptr_eq(&start_block, &end_block) {
+            if let Some(join) = self.merge_blocks(&start_block, &end_block) {
+                caret = join;
+            }
+        }
+        *selection = EditSelection::collapsed(caret);
+        !selected.is_empty()
+    }
+
+    /// Remove the char at `index` of `text` and put the caret there.
+    fn delete_char(&self, text: &Rc<Node>, index: usize, selection: &mut EditSelection) -> bool {
+        let (Some(data), Some(parent)) = (text_of(text), text.parent()) else {
+            return false;
+        };
+        let at = index_in_parent(text);
+        let caret = match self.replace_text(text, &splice(data, index, index + 1, "")) {
+            Some(replacement) => DomPosition::new(replacement.id, index),
+            None => self.normalize(DomPosition::new(parent.id, at)),
+        };
+        *selection = EditSelection::collapsed(caret);
+        true
+    }
+
+    /// Remove an atomic leaf and put the caret where it was.
+    fn remove_leaf(&self, leaf: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        let Some(parent) = leaf.parent() else {
+            return false;
+        };
+        let at = index_in_parent(leaf);
+        leaf.remove_from_parent();
+        *selection = EditSelection::collapsed(self.normalize(DomPosition::new(parent.id, at)));
+        true
+    }
+
+    fn insert_text(&self, host: &Rc<Node>, selection: &mut EditSelection, text: &str) -> bool {
+        let deleted = !selection.is_collapsed() && self.delete_range(host, selection);
+        if text.is_empty() {
+            return deleted;
+        }
+        let position = self.normalize(selection.focus);
+        let Some(node) = self.get_node(position.node) else {
+            return deleted;
+        };
+        let inserted = text.chars().count();
+        if let Some(data) = text_of(&node) {
+            let offset = position.offset.min(char_len(&node));
+            let Some(replacement) = self.replace_text(&node, &splice(data, offset, offset, text))
+            else {
+                return deleted;
+            };
+            *selection =
+                EditSelection::collapsed(DomPosition::new(replacement.id, offset + inserted));
+            return true;
+        }
+        let reference = node.children().get(position.offset).cloned();
+        let new_text = self.create_text_node(text);
+        if self
+            .insert_before(&node, new_text.clone(), reference.as_ref())
+            .is_err()
+        {
+            return deleted;
+        }
+        *selection = EditSelection::collapsed(DomPosition::new(new_text.id, inserted));
+        true
+    }
+
+    fn delete_backward(&self, host: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        if !selection.is_collapsed() {
+            return self.delete_range(host, selection);
+        }
+        let position = self.normalize(selection.focus);
+        let Some(node) = self.get_node(position.node) else {
+            return false;
+        };
+        if node.is_text() && position.offset > 0 {
+            return self.delete_char(&node, position.offset - 1, selection);
+        }
+        let Some(previous) = self.leaf_before(host, position) else {
+            return false;
+        };
+        let (here, there) = (block_of(host, &node), block_of(host, &previous));
+        if !Rc::ptr_eq(&here, &there) {
+            let Some(join) = self.merge_blocks(&there, &here) else {
+                return false;
+            };
+            *selection = EditSelection::collapsed(join);
+            return true;
+        }
+        match previous.is_text() {
+            true => self.delete_char(&previous, char_len(&previous) - 1, selection),
+            false => self.remove_leaf(&previous, selection),
+        }
+    }
+
+    fn delete_forward(&self, host: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        if !selection.is_collapsed() {
+            return self.delete_range(host, selection);
+        }
+        let position = self.normalize(selection.focus);
+        let Some(node) = self.get_node(position.node) else {
+            return false;
+        };
+        if node.is_text() && position.offset < char_len(&node) {
+            return self.delete_char(&node, position.offset, selection);
+        }
+        let Some(next) = self.leaf_after(host, position) else {
+            return false;
+        };
+        let (here, there) = (block_of(host, &node), block_of(host, &next));
+        if !Rc::ptr_eq(&here, &there) {
+            let Some(join) = self.merge_blocks(&here, &there) else {
+                return false;
+            };
+            *selection = EditSelection::collapsed(join);
+            return true;
+        }
+        match next.is_text() {
+            true => self.delete_char(&next, 0, selection),
+            false => self.remove_leaf(&next, selection),
+        }
+    }
+
+    fn insert_line_break(&self, host: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        if !selection.is_collapsed() {
+            self.delete_range(host, selection);
+        }
+        let Some(((parent, index), _)) = self.split_text(self.normalize(selection.focus)) else {
+            return false;
+        };
+        let reference = parent.children().get(index).cloned();
+        let br = self.create_element("br");
+        if self
+            .insert_before(&parent, br.clone(), reference.as_ref())
+            .is_err()
+        {
+            return false;
+        }
+        // A <br> ending a block doesn't start a line of its own, so a
+        // second one holds the new line open
+        let after = DomPosition::new(parent.id, index + 1);
+        let block = block_of(host, &br);
+        let line_follows = self
+            .leaf_after(host, after)
+            .is_some_and(|next| Rc::ptr_eq(&block_of(host, &next), &block));
+        if !line_follows {
+            let placeholder = self.create_element("br");
+            let _ = self.insert_before(&parent, placeholder, br.next_sibling().as_ref());
+        }
+        *selection = EditSelection::collapsed(self.normalize(after));
+        true
+    }
+
+    fn insert_paragraph(&self, host: &Rc<Node>, selection: &mut EditSelection) -> bool {
+        if !selection.is_collapsed() {
+            self.delete_range(host, selection);
+        }
+        let Some(((parent, index), _)) = self.split_text(self.normalize(selection.focus)) else {
+            return false;
+        };
+        let block = block_of(host, &parent);
+        let new_block = if Rc::ptr_eq(&block, host) {
+            // Text straight in the host: the rest of its line moves into a
+            // new <div>
+            let Some(index) = self.split_to(&parent, index, host) else {
+                return false;
+            };
+            let parent = host.clone();
+            let rest: Vec<_> = parent
+                .children()
+                .into_iter()
+                .skip(index)
+                .take_while(|c| !is_block(c))
+                .collect();
+            let div = self.create_element("div");
+            let reference = parent.children().get(index).cloned();
+            if self
+                .insert_before(&parent, div.clone(), reference.as_ref())
+                .is_err()
+            {
+                return false;
+            }
+            for child in rest {
+                let _ = self.append_child(&div, child);
+            }
+            div
+        } else {
+            match self.split_tree(&parent, index, &block) {
+                Some(copy) => copy,
+                None => return false,
+            }
+        };
+        // Empty blocks get a <br> so they keep a line's height
+        for half in [&block, &new_block] {
+            if !Rc::ptr_eq(half, host) && leaves(half).is_empty() {
+                let _ = self.append_child(half, self.create_element("br"));
+            }
+        }
+        *selection = EditSelection::collapsed(start_of(&new_block));
+        true
+    }
+
+    /// Wrap the selection in `tag`, or unwrap it if it is all inside one.
+    fn toggle_format(&self, host: &Rc<Node>, selection: &mut EditSelection, tag: &str) -> bool {
+        if selection.is_collapsed() {
+            return false;
+        }
+        let Some((start, end)) = self.split_range(host, selection) else {
+            return false;
+        };
+        let selected = self.leaves_between(host, &start, &end);
+        let texts: Vec<_> = selected.iter().filter(|l| l.is_text()).cloned().collect();
+        let (Some(first), Some(last)) = (texts.first(), texts.last()) else {
+            return false;
+        };
+        if texts
+            .iter()
+            .all(|t| format_ancestor(host, t, tag).is_some())
+        {
+            self.unformat(host, &texts, tag);
+        } else {
+            self.format(host, &selected, tag);
+        }
+        *selection = EditSelection::new(
+            DomPosition::new(first.id, 0),
+            DomPosition::new(last.id, char_len(last)),
+        );
+        true
+    }
+
+    fn format(&self, host: &Rc<Node>, selected: &[Rc<Node>], tag: &str) {
+        // Wrap the highest wholly selected ancestors, so `a<i>b</i>c`
+        // becomes `<b>a<i>b</i>c</b>` rather than three wrappers
+        let mut tops: Vec<Rc<Node>> = Vec::new();
+        for leaf in selected {
+            let mut node = leaf.clone();
+            while let Some(parent) = node.parent() {
+                let whole = leaves(&parent)
+                    .iter()
+                    .all(|l| selected.iter().any(|s| Rc::ptr_eq(s, l)));
+                if Rc::ptr_eq(&parent, host) || is_block(&parent) || !whole {
+                    break;
+                }
+                node = parent;
+            }
+            if !tops.iter().any(|top| top.contains(&node)) {
+                tops.push(node);
+            }
+        }
+
+        let mut runs: Vec<Vec<Rc<Node>>> = Vec::new();
+        for node in tops {
+            match runs.last_mut() {
+                Some(run)
+                    if run
+                        .last()
+                        .and_then(|n| n.next_sibling())
+                        .is_some_and(|next| Rc::ptr_eq(&next, &node)) =>
+                {
+                    run.push(node)
+                }
+                _ => runs.push(vec![node]),
+            }
+        }
+        for run in runs {
+            let Some(parent) = run[0].parent() else {
+                continue;
+            };
+            let wrapper = self.create_element(tag);
+            if self
+                .insert_before(&parent, wrapper.clone(), Some(&run[0]))
+                .is_err()
+            {
+                continue;
+            }
+            for node in run {
+                let _ = self.append_child(&wrapper, node);
+            }
+        }
+    }
+
+    fn unformat(&self, host: &Rc<Node>, texts: &[Rc<Node>], tag: &str) {
+        let mut formats: Vec<Rc<Node>> = Vec::new();
+        for text in texts {
+            if let Some(element) = format_ancestor(host, text, tag) {
+                if !formats.iter().any(|f| Rc::ptr_eq(f, &element)) {
+                    formats.push(element);
+                }
+            }
+        }
+        for element in formats {
+            let inside: Vec<_> = texts.iter().filter(|t| element.contains(t)).collect();
+            let (Some(first), Some(last)) = (inside.first(), inside.last()) else {
+                continue;
+            };
+            // Split off what follows the selection, then what precedes it
+            let (Some(last_parent), Some(first_parent)) = (last.parent(), first.parent()) else {
+                continue;
+            };
+            let rest = self.split_tree(&last_parent, index_in_parent(last) + 1, &element);
+            let Some(middle) = self.split_tree(&first_parent, index_in_parent(first), &element)
+            else {
+                continue;
+            };
+            for part in [Some(element.clone()), rest].into_iter().flatten() {
+                if part.first_child().is_none() {
+                    part.remove_from_parent();
+                }
+            }
+            let Some(parent) = middle.parent() else {
+                continue;
+            };
+            for child in middle.children() {
+                let _ = self.insert_before(&parent, child, Some(&middle));
+            }
+            middle.remove_from_parent();
+        }
+    }
+}
+
+/// The nearest `tag` element around `node` inside `host`, counting
+/// `<strong>` as `<b>` and `<em>` as `<i>`.
+fn format_ancestor(host: &Rc<Node>, node: &Rc<Node>, tag: &str) -> Option<Rc<Node>> {
+    let tags: &[&str] = match tag {
+        "b" => &["b", "strong"],
+        "i" => &["i", "em"],
+        _ => &[],
+    };
+    let mut current = node.parent();
+    while let Some(element) = current {
+        if Rc::ptr_eq(&element, host) {
+            return None;
+        }
+        if has_tag(&element, tags) {
+            return Some(element);
+        }
+        current = element.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The markup of `node`'s children, with text nodes in brackets.
+    fn markup(node: &Rc<Node>) -> String {
+        node.children()
+            .iter()
+            .map(|child| match &child.node_type {
+                NodeType::Text(data) => format!("[{}]", data),
+                NodeType::Element { tag_name, .. } if tag_name == "br" => "<br>".to_string(),
+                NodeType::Element { tag_name, .. } => {
+                    format!("<{}>{}</{}>", tag_name, markup(child), tag_name)
+                }
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    fn editable(html: &str) -> (Document, Rc<Node>) {
+        let document = Document::parse_html(&format!(
+            "<html><body><div id=\"host\" contenteditable=\"true\">{}</div></body></html>",
+            html
+        ))
+        .unwrap();
+        let host = document.get_element_by_id("host").unwrap();
+        (document, host)
+    }
+
+    fn type_text(document: &Document, host: &Rc<Node>, selection: &mut EditSelection, s: &str) {
+        for ch in s.chars() {
+            assert!(document.apply_edit(host, selection, &EditAction::InsertText(ch.into())));
+        }
+    }
+
+    #[test]
+    fn test_editing_host() {
+        let document = Document::parse_html(
+            r#"<html><body><div id="a" contenteditable><p id="b">x</p>
+            <span id="c" contenteditable="false"><i id="d">y</i></span></div>
+            <div id="e">z</div></body></html>"#,
+        )
+        .unwrap();
+        let element = |id| document.get_element_by_id(id).unwrap();
+        assert!(is_editing_host(&element("a")));
+        assert_eq!(editing_host(&element("b")).unwrap().id, element("a").id);
+        assert!(editing_host(&element("d")).is_none());
+        assert!(editing_host(&element("e")).is_none());
+    }
+
+    #[test]
+    fn test_typing_inserts_text_nodes_at_the_caret() {
+        let (document, host) = editable("");
+        let mut selection = EditSelection::collapsed(end_of(&host));
+        type_text(&document, &host, &mut selection, "Helo");
+        assert_eq!(markup(&host), "[Helo]");
+
+        document.move_caret(&host, &mut selection, CaretMove::Backward, false);
+        type_text(&document, &host, &mut selection, "l");
+        assert_eq!(markup(&host), "[Hello]");
+        document.move_caret(&host, &mut selection, CaretMove::HostEnd, false);
+        type_text(&document, &host, &mut selection, "!");
+        assert_eq!(markup(&host), "[Hello!]");
+
+        let backspace = EditAction::DeleteContentBackward;
+        assert!(document.apply_edit(&host, &mut selection, &backspace));
+        assert_eq!(markup(&host), "[Hello]");
+
+        // Typing over a selection replaces it
+        let (document, host) = host_with_bold();
+        let mut selection = EditSelection::collapsed(start_of(&host));
+        for _ in 0..3 {
+            document.move_caret(&host, &mut selection, CaretMove::Forward, true);
+        }
+        type_text(&document, &host, &mut selection, "A");
+        assert_eq!(markup(&host), "[A]<b>[c]</b>[d]");
+    }
+
+    fn host_with_bold() -> (Document, Rc<Node>) {
+        editable("ab<b>bc</b>d")
+    }
+
+    #[test]
+    fn test_enter_breaks_the_line_or_splits_the_block() {
+        let (document, host) = editable("Hello world");
+        let text = host.first_child().unwrap();
+        let mut selection = EditSelection::collapsed(DomPosition::new(text.id, 5));
+        assert!(document.apply_edit(&host, &mut selection, &EditAction::InsertLineBreak));
+        assert_eq!(markup(&host), "[Hello]<br>[ world]");
+        // At the end of the block a second <br> holds the new line open
+        selection = EditSelection::collapsed(end_of(&host));
+        document.apply_edit(&host, &mut selection, &EditAction::InsertLineBreak);
+        type_text(&document, &host, &mut selection, "x");
+        assert_eq!(markup(&host), "[Hello]<br>[ world]<br>[x]<br>");
+
+        let (document, host) = editable("<p>Hello world</p>");
+        let text = host.first_child().unwrap().first_child().unwrap();
+        let mut selection = EditSelection::collapsed(DomPosition::new(text.id, 5));
+        assert!(document.apply_edit(&host, &mut selection, &EditAction::InsertParagraph));
+        assert_eq!(markup(&host), "<p>[Hello]</p><p>[ world]</p>");
+        type_text(&document, &host, &mut selection, ",");
+        assert_eq!(markup(&host), "<p>[Hello]</p><p>[, world]</p>");
+        // Backspace at the start of a block merges it into the one before
+        document.move_caret(&host, &mut selection, CaretMove::Backward, false);
+        let backspace = EditAction::DeleteContentBackward;
+        assert!(document.apply_edit(&host, &mut selection, &backspace));
+        assert_eq!(markup(&host), "<p>[Hello][, world]</p>");
+
+        // Without a block, the rest of the line moves into a new <div>
+        let (document, host) = editable("one <i>two</i>");
+        let mut selection = EditSelection::collapsed(end_of(&host));
+        document.apply_edit(&host, &mut selection, &EditAction::InsertParagraph);
+        assert_eq!(markup(&host), "[one ]<i>[two]</i><div><br></div>");
+        let text = host.first_child().unwrap();
+        let mut selection = EditSelection::collapsed(DomPosition::new(text.id, 2));
+        document.apply_edit(&host, &mut selection, &EditAction::InsertParagraph);
+        assert_eq!(
+            markup(&host),
+            "[on]<div>[e ]<i>[two]</i></div><div><br></div>"
+        );
+    }
+
+    #[test]
+    fn test_bold_wraps_exactly_the_selection() {
+        let (document, host) = editable("Hello brave world");
+        let text = host.first_child().unwrap();
+        let mut selection =
+            EditSelection::new(DomPosition::new(text.id, 11), DomPosition::new(text.id, 6));
+        assert!(document.apply_edit(&host, &mut selection, &EditAction::FormatBold));
+        assert_eq!(markup(&host), "[Hello ]<b>[brave]</b>[ world]");
+        assert_eq!(
+            document.selected_text(&host, &selection),
+            vec![(host.children()[1].first_child().unwrap().id, 0, 5)]
+        );
+
+        // Again unwraps it
+        assert!(document.apply_edit(&host, &mut selection, &EditAction::FormatBold));
+        assert_eq!(markup(&host), "[Hello ][brave][ world]");
+
+        // Across elements, the highest wholly selected nodes are wrapped
+        let (document, host) = editable("ab<i>cd</i>ef");
+        let children = host.children();
+        let mut selection = EditSelection::new(
+            DomPosition::new(children[0].id, 1),
+            DomPosition::new(children[2].id, 1),
+        );
+        document.apply_edit(&host, &mut selection, &EditAction::FormatBold);
+        assert_eq!(markup(&host), "[a]<b>[b]<i>[cd]</i>[e]</b>[f]");
+
+        // Unbolding part of a bold run keeps the rest bold
+        let (document, host) = editable("<b>abc</b>");
+        let text = host.first_child().unwrap().first_child().unwrap();
+        let mut selection =
+            EditSelection::new(DomPosition::new(text.id, 1), DomPosition::new(text.id, 2));
+        document.apply_edit(&host, &mut selection, &EditAction::FormatBold);
+        assert_eq!(markup(&host), "<b>[a]</b>[b]<b>[c]</b>");
+        let collapsed = &mut EditSelection::collapsed(start_of(&host));
+        assert!(!document.apply_edit(&host, collapsed, &EditAction::FormatItalic));
+    }
+}
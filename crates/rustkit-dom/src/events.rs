@@ -524,6 +524,61 @@ impl EventDispatcher {
 
         !event.event().default_prevented()
     }
+
+    /// Dispatch an event along the path from `target` up through its
+    /// ancestors, crossing into shadow hosts if the event is composed.
+    ///
+    /// Each node sees the target retargeted against itself, so listeners
+    /// outside a shadow tree see its host; a host seeing itself as the
+    /// target is at target too. Returns true if the event was not prevented.
+    pub fn dispatch_composed(event: &mut DomEvent, target: &Rc<Node>) -> bool {
+        let event_type = event.event().event_type.clone();
+        let bubbles = event.event().bubbles;
+        let path: Vec<(Rc<Node>, Rc<Node>)> = crate::event_path(target, event.event().composed)
+            .into_iter()
+            .map(|node| (crate::retarget(target, &node), node))
+            .collect();
+
+        let invoke = |event: &mut DomEvent, node: &Rc<Node>, shown: &Rc<Node>, filter| {
+            let phase = if Rc::ptr_eq(node, shown) {
+                EventPhase::AtTarget
+            } else {
+                filter
+            };
+            event.event().set_target(shown.id);
+            event.event().set_phase(phase);
+            event.event().set_current_target(Some(node.id));
+            let to_remove = node.event_target.invoke_listeners(event, filter);
+            node.event_target.remove_listeners(&event_type, to_remove);
+        };
+
+        // Capture listeners from the root down, those at target included
+        for (shown, node) in path.iter().rev() {
+            if event.event().propagation_stopped() {
+                break;
+            }
+            invoke(event, node, shown, EventPhase::Capturing);
+        }
+
+        // Then the others back up, past the target only if the event bubbles
+        for (shown, node) in &path {
+            if event.event().propagation_stopped() {
+                break;
+            }
+            if bubbles || Rc::ptr_eq(node, shown) {
+                invoke(event, node, shown, EventPhase::Bubbling);
+            }
+        }
+
+        // The target outside every shadow tree stays visible afterwards
+        if let Some((shown, _)) = path.last() {
+            event.event().set_target(shown.id);
+        }
+        event.event().set_phase(EventPhase::None);
+        event.event().set_current_target(None);
+
+        !event.event().default_prevented()
+    }
 }
 
 #[cfg(test)]
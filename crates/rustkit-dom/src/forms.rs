@@ -345,7 +345,7 @@ impl TextEditState {
         } else if sel.start > 0 {
             // Delete character before caret
             let pos = sel.start;
-            let start = pos.saturating_sub(1);
+            let start = prev_char_boundary(&value, pos);
             value.replace_range(start..pos, "");
             drop(value);
             self.selection.set(SelectionRange::caret(start));
@@ -376,7 +376,7 @@ impl TextEditState {
         } else if sel.start < len {
             // Delete character after caret
             let pos = sel.start;
-            let end = (pos + 1).min(len);
+            let end = next_char_boundary(&value, pos);
             value.replace_range(pos..end, "");
             drop(value);
             // Caret stays in place
@@ -390,7 +390,7 @@ impl TextEditState {
     /// Move caret left.
     pub fn move_left(&self, extend_selection: bool) {
         let sel = self.selection.get();
-        let new_pos = sel.start.saturating_sub(1);
+        let new_pos = prev_char_boundary(&self.value.borrow(), sel.start);
 
         if extend_selection {
             self.selection.set(SelectionRange::new(new_pos, sel.end));
@@ -406,8 +406,7 @@ impl TextEditState {
     /// Move caret right.
     pub fn move_right(&self, extend_selection: bool) {
         let sel = self.selection.get();
-        let len = self.value.borrow().len();
-        let new_pos = (sel.end + 1).min(len);
+        let new_pos = next_char_boundary(&self.value.borrow(), sel.end);
 
         if extend_selection {
             self.selection.set(SelectionRange::new(sel.start, new_pos));
@@ -516,6 +515,24 @@ impl TextEditState {
     }
 }
 
+/// Byte offset of the char boundary before `pos`.
+fn prev_char_boundary(text: &str, pos: usize) -> usize {
+    let pos = pos.min(text.len());
+    text[..pos]
+        .chars()
+        .next_back()
+        .map_or(0, |c| pos - c.len_utf8())
+}
+
+/// Byte offset of the char boundary after `pos`.
+fn next_char_boundary(text: &str, pos: usize) -> usize {
+    let pos = pos.min(text.len());
+    text[pos..]
+        .chars()
+        .next()
+        .map_or(pos, |c| pos + c.len_utf8())
+}
+
 /// State for checkbox/radio inputs.
 #[derive(Debug, Default)]
 pub struct CheckableState {
@@ -572,9 +589,17 @@ pub struct FormDataEntry {
 #[derive(Debug, Clone)]
 pub enum FormDataValue {
     String(String),
-    File { name: String, content: Vec<u8> },
+    File {
+        name: String,
+        /// MIME type; empty if unknown.
+        content_type: String,
+        content: Vec<u8>,
+    },
 }
 
+/// Boundary between the parts of `multipart/form-data` bodies.
+pub const MULTIPART_BOUNDARY: &str = "----RustKitFormBoundary";
+
 /// Encoding types for form submission.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum FormEnctype {
@@ -1020,7 +1045,7 @@ impl FormState {
 
     fn encode_multipart(entries: &[FormDataEntry]) -> Vec<u8> {
         // Simplified multipart - real implementation would use proper boundary
-        let boundary = "----RustKitFormBoundary";
+        let boundary = MULTIPART_BOUNDARY;
         let mut result = Vec::new();
 
         for entry in entries {
@@ -1036,12 +1061,21 @@ impl FormState {
                         .as_bytes(),
                     );
                 }
-                FormDataValue::File { name, content } => {
+                FormDataValue::File {
+                    name,
+                    content_type,
+                    content,
+                } => {
+                    let content_type = if content_type.is_empty() {
+                        "application/octet-stream"
+                    } else {
+                        content_type
+                    };
                     result.extend_from_slice(
                         format!(
                             "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
-                             Content-Type: application/octet-stream\r\n\r\n",
-                            entry.name, name
+                             Content-Type: {}\r\n\r\n",
+                            entry.name, name, content_type
                         )
                         .as_bytes(),
                     );
@@ -1106,7 +1140,7 @@ impl FormState {
                 let content_type = match enctype {
                     FormEnctype::UrlEncoded => "application/x-www-form-urlencoded".to_string(),
                     FormEnctype::MultipartFormData => {
-                        "multipart/form-data; boundary=----RustKitFormBoundary".to_string()
+                        format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY)
                     }
                     FormEnctype::TextPlain => "text/plain".to_string(),
                 };
@@ -1652,6 +1686,18 @@ mod tests {
         assert_eq!(state.caret_position(), 0);
     }
 
+    #[test]
+    fn test_text_edit_multibyte() {
+        let state = TextEditState::with_value("naïve");
+        state.set_caret(4);
+
+        state.delete_backward();
+        assert_eq!(state.value(), "nave");
+        state.move_left(false);
+        state.delete_forward();
+        assert_eq!(state.value(), "nve");
+    }
+
     #[test]
     fn test_text_edit_selection_replace() {
         let state = TextEditState::with_value("Hello World");
@@ -11,18 +11,27 @@
 //! 4. **Mutation support**: Node insertion, removal, attribute modification
 //! 5. **Event dispatch**: DOM Events with capture/bubble phases
 
+pub mod editing;
 pub mod events;
 pub mod forms;
 pub mod images;
+mod mutation;
+mod shadow;
 
+pub use editing::{
+    editing_host, end_of, is_editing_host, start_of, CaretMove, DomPosition, EditAction,
+    EditSelection, EnterBehavior,
+};
 pub use events::{
     AddEventListenerOptions, DomEvent, Event, EventDispatcher, EventId, EventListenerCallback,
     EventPhase, EventTarget, FocusEventData, InputEventData, KeyboardEventData, MouseEventData,
 };
 pub use forms::{
     CheckableState, FormDataEntry, FormDataValue, FormEnctype, FormMethod, FormState, InputType,
-    SelectionDirection, SelectionRange, TextEditState,
+    SelectionDirection, SelectionRange, TextEditState, MULTIPART_BOUNDARY,
 };
+pub use mutation::AdjacentPosition;
+pub use shadow::{event_path, retarget, ShadowRootMode};
 pub use images::{
     CrossOrigin, FaviconLink, ImageDecoding, ImageElement, ImageElementManager, ImageLoading,
     ImageLoadingState, PictureElement, PictureSource,
@@ -32,7 +41,7 @@ use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, trace};
 
 /// Errors that can occur in DOM operations.
 #[derive(Error, Debug)]
@@ -45,6 +54,32 @@ pub enum DomError {
 
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    #[error("Hierarchy request error: {0}")]
+    HierarchyRequest(String),
+
+    #[error("Syntax error: {0}")]
+    Syntax(String),
+
+    #[error("Not supported: {0}")]
+    NotSupported(String),
+
+    #[error("No modification allowed: {0}")]
+    NoModificationAllowed(String),
+}
+
+impl DomError {
+    /// The `DOMException` name script sees for this error.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ParseError(_) | Self::Syntax(_) => "SyntaxError",
+            Self::NodeNotFound => "NotFoundError",
+            Self::InvalidOperation(_) => "InvalidStateError",
+            Self::HierarchyRequest(_) => "HierarchyRequestError",
+            Self::NotSupported(_) => "NotSupportedError",
+            Self::NoModificationAllowed(_) => "NoModificationAllowedError",
+        }
+    }
 }
 
 /// Unique identifier for a DOM node.
@@ -83,8 +118,19 @@ pub enum NodeType {
         target: String,
         data: String,
     },
+    /// A parentless container, such as a template's contents.
+    DocumentFragment,
 }
 
+/// Elements serialized without children or an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param",
+    "source", "track", "wbr",
+];
+
+/// Elements whose text is serialized as it is.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "xmp", "iframe", "noembed", "noframes"];
+
 /// A DOM node.
 #[derive(Debug)]
 pub struct Node {
@@ -102,8 +148,25 @@ pub struct Node {
     next_sibling: RefCell<Option<Weak<Node>>>,
     /// Event target mixin for event handling.
     pub event_target: EventTarget,
+    /// Inline style set from script, replacing the `style` attribute.
+    inline_style: RefCell<Option<String>>,
+    /// Contents of a `<template>` element, kept outside the tree.
+    template_content: RefCell<Option<Rc<Node>>>,
+    /// Shadow root attached to this element, kept outside the tree.
+    shadow_root: RefCell<Option<Rc<Node>>>,
+    /// For a shadow root, its host and mode.
+    shadow_host: RefCell<Option<(Weak<Node>, ShadowRootMode)>>,
 }
 
+/// Nodes are equal only to themselves, like `isSameNode`.
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Node {}
+
 impl Node {
     /// Create a new node.
     pub fn new(id: NodeId, node_type: NodeType) -> Rc<Self> {
@@ -115,6 +178,10 @@ impl Node {
             prev_sibling: RefCell::new(None),
             next_sibling: RefCell::new(None),
             event_target: EventTarget::new(),
+            inline_style: RefCell::new(None),
+            template_content: RefCell::new(None),
+            shadow_root: RefCell::new(None),
+            shadow_host: RefCell::new(None),
         })
     }
 
@@ -134,6 +201,62 @@ impl Node {
         }
     }
 
+    /// Get the effective inline style declarations.
+    ///
+    /// Returns the script-set style if any, otherwise the `style` attribute.
+    pub fn inline_style(&self) -> Option<String> {
+        self.inline_style
+            .borrow()
+            .clone()
+            .or_else(|| self.get_attribute("style").map(str::to_string))
+    }
+
+    /// Replace the inline style declarations (e.g. from `element.style`).
+    pub fn set_inline_style(&self, css: &str) {
+        *self.inline_style.borrow_mut() = Some(css.to_string());
+    }
+
+    /// The inert contents of a `<template>` element.
+    ///
+    /// Template children are parsed into this fragment rather than the
+    /// element itself, so they are not rendered or matched by document
+    /// queries.
+    pub fn template_content(&self) -> Option<Rc<Node>> {
+        self.template_content.borrow().clone()
+    }
+
+    /// Check if this is a `<template>` element.
+    pub fn is_template(&self) -> bool {
+        self.tag_name()
+            .is_some_and(|t| t.eq_ignore_ascii_case("template"))
+    }
+
+    /// Check if this is a document fragment.
+    pub fn is_document_fragment(&self) -> bool {
+        matches!(self.node_type, NodeType::DocumentFragment)
+    }
+
+    /// Whether `other` is this node or one of its descendants.
+    pub fn contains(&self, other: &Rc<Node>) -> bool {
+        let mut current = Some(other.clone());
+        while let Some(node) = current {
+            if std::ptr::eq(Rc::as_ptr(&node), self) {
+                return true;
+            }
+            current = node.parent();
+        }
+        false
+    }
+
+    /// The topmost ancestor of this node, or the node itself.
+    pub fn root_node(self: &Rc<Self>) -> Rc<Node> {
+        let mut root = self.clone();
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        root
+    }
+
     /// Get the text content.
     pub fn text_content(&self) -> String {
         let mut result = String::new();
@@ -152,6 +275,77 @@ impl Node {
         }
     }
 
+    /// Serialize the children as HTML (`innerHTML`). Attributes come out
+    /// sorted by name.
+    pub fn inner_html(&self) -> String {
+        let mut result = String::new();
+        for child in self.children.borrow().iter() {
+            child.serialize(&mut result, self.tag_name());
+        }
+        result
+    }
+
+    fn serialize(&self, result: &mut String, parent_tag: Option<&str>) {
+        fn escape(text: &str, attribute: bool) -> String {
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                match c {
+                    '&' => escaped.push_str("&amp;"),
+                    '\u{a0}' => escaped.push_str("&nbsp;"),
+                    '"' if attribute => escaped.push_str("&quot;"),
+                    '<' if !attribute => escaped.push_str("&lt;"),
+                    '>' if !attribute => escaped.push_str("&gt;"),
+                    c => escaped.push(c),
+                }
+            }
+            escaped
+        }
+
+        match &self.node_type {
+            NodeType::Element {
+                tag_name,
+                attributes,
+                ..
+            } => {
+                let tag = tag_name.to_ascii_lowercase();
+                result.push('<');
+                result.push_str(&tag);
+                let mut names: Vec<_> = attributes.keys().collect();
+                names.sort();
+                for name in names {
+                    result.push_str(&format!(" {}=\"{}\"", name, escape(&attributes[name], true)));
+                }
+                result.push('>');
+                if VOID_ELEMENTS.contains(&tag.as_str()) {
+                    return;
+                }
+                for child in self.children.borrow().iter() {
+                    child.serialize(result, Some(&tag));
+                }
+                result.push_str(&format!("</{}>", tag));
+            }
+            NodeType::Text(text) => {
+                let raw = parent_tag.is_some_and(|tag| {
+                    RAW_TEXT_ELEMENTS.iter().any(|raw| tag.eq_ignore_ascii_case(raw))
+                });
+                match raw {
+                    true => result.push_str(text),
+                    false => result.push_str(&escape(text, false)),
+                }
+            }
+            NodeType::Comment(data) => result.push_str(&format!("<!--{}-->", data)),
+            NodeType::ProcessingInstruction { target, data } => {
+                result.push_str(&format!("<?{} {}>", target, data))
+            }
+            NodeType::DocumentType { name, .. } => {
+                result.push_str(&format!("<!DOCTYPE {}>", name))
+            }
+            NodeType::Document | NodeType::DocumentFragment => {
+                result.push_str(&self.inner_html())
+            }
+        }
+    }
+
     /// Get parent node.
     pub fn parent(&self) -> Option<Rc<Node>> {
         self.parent.borrow().as_ref().and_then(|w| w.upgrade())
@@ -268,46 +462,62 @@ pub struct Document {
     /// Root node of the document.
     root: Rc<Node>,
     /// All nodes indexed by ID.
-    nodes: HashMap<NodeId, Rc<Node>>,
+    nodes: RefCell<HashMap<NodeId, Rc<Node>>>,
     /// Elements indexed by ID attribute.
-    elements_by_id: HashMap<String, Rc<Node>>,
+    elements_by_id: RefCell<HashMap<String, Rc<Node>>>,
     /// Next node ID.
     next_id: Cell<usize>,
 }
 
 /// Sink for building a Document from HTML parsing.
-struct DocumentSink {
+struct DocumentSink<'a> {
     doc: Document,
     /// Stack of open elements during parsing.
     open_elements: Vec<Rc<Node>>,
+    /// Receives recoverable parse errors.
+    on_error: Option<&'a mut dyn FnMut(&str)>,
 }
 
-impl DocumentSink {
+impl<'a> DocumentSink<'a> {
     fn new() -> Self {
         Self {
             doc: Document::new(),
             open_elements: vec![],
+            on_error: None,
         }
     }
 
     fn current_parent(&self) -> Rc<Node> {
         self.open_elements
             .last()
-            .cloned()
+            .map(insertion_parent)
             .unwrap_or_else(|| self.doc.root.clone())
     }
 
     fn create_node(&mut self, node_type: NodeType) -> Rc<Node> {
-        let id = NodeId::new(self.doc.next_id.get());
-        self.doc.next_id.set(self.doc.next_id.get() + 1);
+        self.doc.create_node(node_type)
+    }
 
-        let node = Node::new(id, node_type);
-        self.doc.nodes.insert(id, node.clone());
-        node
+    /// Index an element the parser just put in place by its ID attribute;
+    /// template contents are not searchable.
+    fn index_id(&self, node: &Rc<Node>) {
+        if let Some(id) = node.get_attribute("id") {
+            if self.doc.is_in_document(node) {
+                self.doc
+                    .elements_by_id
+                    .borrow_mut()
+                    .insert(id.to_string(), node.clone());
+            }
+        }
     }
 }
 
-impl rustkit_html::TreeSink for DocumentSink {
+/// Children inserted into a template go into its contents instead.
+fn insertion_parent(node: &Rc<Node>) -> Rc<Node> {
+    node.template_content().unwrap_or_else(|| node.clone())
+}
+
+impl rustkit_html::TreeSink for DocumentSink<'_> {
     type NodeId = Rc<Node>;
 
     fn doctype(&mut self, name: String, public_id: String, system_id: String) {
@@ -336,13 +546,9 @@ impl rustkit_html::TreeSink for DocumentSink {
             attributes,
         });
 
-        // Index by ID attribute
-        if let Some(id) = node.get_attribute("id") {
-            self.doc.elements_by_id.insert(id.to_string(), node.clone());
-        }
-
         let parent = self.current_parent();
         parent.append_child(node.clone());
+        self.index_id(&node);
 
         // Push onto stack for nested elements (but not void/self-closing elements)
         if !self_closing {
@@ -424,7 +630,8 @@ impl rustkit_html::TreeSink for DocumentSink {
     }
 
     fn append_child(&mut self, parent: Self::NodeId, child: Self::NodeId) {
-        parent.append_child(child);
+        insertion_parent(&parent).append_child(child.clone());
+        self.index_id(&child);
     }
 
     fn remove_from_parent(&mut self, node: Self::NodeId) {
@@ -433,7 +640,8 @@ impl rustkit_html::TreeSink for DocumentSink {
 
     fn reparent_children(&mut self, from: Self::NodeId, to: Self::NodeId) {
         // Move all children from 'from' to 'to'
-        let children = from.children();
+        let children = insertion_parent(&from).children();
+        let to = insertion_parent(&to);
         for child in children {
             child.remove_from_parent();
             to.append_child(child);
@@ -441,11 +649,13 @@ impl rustkit_html::TreeSink for DocumentSink {
     }
 
     fn insert_before(&mut self, parent: Self::NodeId, node: Self::NodeId, reference: Option<Self::NodeId>) {
+        let parent = insertion_parent(&parent);
         if let Some(ref_node) = reference {
-            parent.insert_before(node, ref_node);
+            parent.insert_before(node.clone(), ref_node);
         } else {
-            parent.append_child(node);
+            parent.append_child(node.clone());
         }
+        self.index_id(&node);
     }
 
     fn get_parent(&self, node: Self::NodeId) -> Option<Self::NodeId> {
@@ -455,6 +665,25 @@ impl rustkit_html::TreeSink for DocumentSink {
     fn get_tag_name(&self, node: Self::NodeId) -> Option<String> {
         node.tag_name().map(|s| s.to_string())
     }
+
+    fn foster_parent_text(&mut self, table: Self::NodeId, data: String) {
+        let node = self.create_node(NodeType::Text(data));
+        match table.parent() {
+            Some(parent) => parent.insert_before(node, table),
+            None => self.current_parent().append_child(node),
+        }
+    }
+
+    fn reset_open_elements(&mut self, stack: &[Self::NodeId]) {
+        self.open_elements = stack.to_vec();
+    }
+
+    fn parse_error(&mut self, error: &str) {
+        trace!(error, "HTML parse error");
+        if let Some(on_error) = self.on_error.as_mut() {
+            on_error(error);
+        }
+    }
 }
 
 impl Document {
@@ -466,20 +695,100 @@ impl Document {
 
         Self {
             root,
-            nodes,
-            elements_by_id: HashMap::new(),
+            nodes: RefCell::new(nodes),
+            elements_by_id: RefCell::new(HashMap::new()),
             next_id: Cell::new(1),
         }
     }
 
+    /// Allocate a node owned by this document. `<template>` elements get
+    /// an empty content fragment.
+    fn create_node(&self, node_type: NodeType) -> Rc<Node> {
+        let id = NodeId::new(self.next_id.get());
+        self.next_id.set(self.next_id.get() + 1);
+
+        let node = Node::new(id, node_type);
+        self.nodes.borrow_mut().insert(id, node.clone());
+        if node.is_template() {
+            *node.template_content.borrow_mut() =
+                Some(self.create_node(NodeType::DocumentFragment));
+        }
+        node
+    }
+
+    /// Create an empty document fragment.
+    pub fn create_document_fragment(&self) -> Rc<Node> {
+        self.create_node(NodeType::DocumentFragment)
+    }
+
+    /// Copy a node, and with `deep` its descendants and template contents.
+    /// The copy has no parent.
+    pub fn clone_node(&self, node: &Rc<Node>, deep: bool) -> Rc<Node> {
+        let copy = self.create_node(node.node_type.clone());
+        *copy.inline_style.borrow_mut() = node.inline_style.borrow().clone();
+        if !deep {
+            return copy;
+        }
+        for child in node.children() {
+            copy.append_child(self.clone_node(&child, true));
+        }
+        if let (Some(from), Some(to)) = (node.template_content(), copy.template_content()) {
+            for child in from.children() {
+                to.append_child(self.clone_node(&child, true));
+            }
+        }
+        copy
+    }
+
+    /// Append `child` to `parent`, moving it from its current position.
+    ///
+    /// Appending a fragment moves its children instead and leaves it empty.
+    pub fn append_child(&self, parent: &Rc<Node>, child: Rc<Node>) -> Result<(), DomError> {
+        self.insert_before(parent, child, None)
+    }
+
+    /// Whether `node` is connected to this document's tree. Template
+    /// contents, fragments and detached nodes are not.
+    pub fn is_in_document(&self, node: &Rc<Node>) -> bool {
+        Rc::ptr_eq(&node.root_node(), &self.root)
+    }
+
+    fn index_ids(&self, node: &Rc<Node>) {
+        if let Some(id) = node.get_attribute("id") {
+            let mut ids = self.elements_by_id.borrow_mut();
+            // The first connected element with an id wins
+            if ids
+                .get(id)
+                .is_none_or(|existing| !self.is_in_document(existing))
+            {
+                ids.insert(id.to_string(), node.clone());
+            }
+        }
+        for child in node.children() {
+            self.index_ids(&child);
+        }
+    }
+
     /// Parse HTML and create a document (new rustkit-html parser).
     pub fn parse_html(html: &str) -> Result<Self, DomError> {
+        Self::parse_html_reporting(html, &mut |_| {})
+    }
+
+    /// Parse HTML like [`Document::parse_html`], passing each recoverable
+    /// parse error to `on_error` as it is found, e.g. for a devtools console.
+    pub fn parse_html_reporting(
+        html: &str,
+        on_error: &mut dyn FnMut(&str),
+    ) -> Result<Self, DomError> {
         debug!(len = html.len(), "Parsing HTML (rustkit-html)");
 
-        let sink = DocumentSink::new();
+        let sink = DocumentSink {
+            on_error: Some(on_error),
+            ..DocumentSink::new()
+        };
         let sink = rustkit_html::parse(html, sink).map_err(|e| DomError::ParseError(e.to_string()))?;
 
-        debug!(node_count = sink.doc.nodes.len(), "HTML parsed");
+        debug!(node_count = sink.doc.nodes.borrow().len(), "HTML parsed");
         Ok(sink.doc)
     }
 
@@ -515,18 +824,24 @@ impl Document {
 
     /// Get element by ID.
     pub fn get_element_by_id(&self, id: &str) -> Option<Rc<Node>> {
-        self.elements_by_id.get(id).cloned()
+        self.elements_by_id
+            .borrow()
+            .get(id)
+            .filter(|node| self.is_in_document(node))
+            .cloned()
     }
 
     /// Get elements by tag name.
     pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<Rc<Node>> {
         let tag_name_lower = tag_name.to_lowercase();
         self.nodes
+            .borrow()
             .values()
             .filter(|n| {
                 n.tag_name()
                     .map(|t| t.to_lowercase() == tag_name_lower)
                     .unwrap_or(false)
+                    && self.is_in_document(n)
             })
             .cloned()
             .collect()
@@ -535,11 +850,13 @@ impl Document {
     /// Get elements by class name.
     pub fn get_elements_by_class_name(&self, class_name: &str) -> Vec<Rc<Node>> {
         self.nodes
+            .borrow()
             .values()
             .filter(|n| {
                 n.get_attribute("class")
                     .map(|c| c.split_whitespace().any(|cls| cls == class_name))
                     .unwrap_or(false)
+                    && self.is_in_document(n)
             })
             .cloned()
             .collect()
@@ -547,7 +864,7 @@ impl Document {
 
     /// Get node by ID.
     pub fn get_node(&self, id: NodeId) -> Option<Rc<Node>> {
-        self.nodes.get(&id).cloned()
+        self.nodes.borrow().get(&id).cloned()
     }
 
     /// Get the title of the document.
@@ -606,6 +923,38 @@ impl QuerySelector {
             doc.get_elements_by_tag_name(selector)
         }
     }
+
+    /// Select descendants of `scope` matching a simple selector, in tree
+    /// order. Template contents are only searched when `scope` is (or is
+    /// inside) the contents fragment itself.
+    pub fn select_within(scope: &Rc<Node>, selector: &str) -> Vec<Rc<Node>> {
+        let selector = selector.trim();
+        let mut matches = Vec::new();
+        Self::collect_matches(scope, selector, &mut matches);
+        matches
+    }
+
+    fn collect_matches(node: &Rc<Node>, selector: &str, matches: &mut Vec<Rc<Node>>) {
+        for child in node.children() {
+            if Self::matches(&child, selector) {
+                matches.push(child.clone());
+            }
+            Self::collect_matches(&child, selector, matches);
+        }
+    }
+
+    /// Whether an element matches a tag, `#id` or `.class` selector.
+    pub fn matches(node: &Node, selector: &str) -> bool {
+        if let Some(id) = selector.strip_prefix('#') {
+            node.get_attribute("id") == Some(id)
+        } else if let Some(class) = selector.strip_prefix('.') {
+            node.get_attribute("class")
+                .is_some_and(|c| c.split_whitespace().any(|cls| cls == class))
+        } else {
+            node.tag_name()
+                .is_some_and(|t| selector == "*" || t.eq_ignore_ascii_case(selector))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -664,6 +1013,22 @@ mod tests {
         assert_eq!(by_tag.len(), 2);
     }
 
+    #[test]
+    fn test_inline_style_override() {
+        let html = r#"<html><body><div id="box" style="width: 10px"></div></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+
+        let div = doc.get_element_by_id("box").unwrap();
+        assert_eq!(div.inline_style().as_deref(), Some("width: 10px"));
+
+        div.set_inline_style("width: 20px; height: 5px");
+        assert_eq!(
+            div.inline_style().as_deref(),
+            Some("width: 20px; height: 5px")
+        );
+        assert_eq!(div.get_attribute("style"), Some("width: 10px"));
+    }
+
     #[test]
     fn test_traversal() {
         let html = "<html><head></head><body><div><p>Text</p></div></body></html>";
@@ -703,6 +1068,125 @@ mod tests {
             Some("A".to_string())
         );
     }
+
+    #[test]
+    fn test_template_content_is_inert() {
+        let html = r#"<html><body><template id="tpl"><div id="inner" class="card">Hi</div></template><p>After</p></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+
+        let template = doc.get_element_by_id("tpl").unwrap();
+        assert!(template.children().is_empty());
+        let content = template.template_content().unwrap();
+        assert!(content.is_document_fragment());
+        assert_eq!(content.children().len(), 1);
+
+        // Nothing inside the template is reachable from the document
+        assert!(doc.get_element_by_id("inner").is_none());
+        assert!(doc.get_elements_by_tag_name("div").is_empty());
+        assert!(QuerySelector::select(&doc, ".card").is_empty());
+        assert_eq!(doc.body().unwrap().text_content(), "After");
+        let mut divs = 0;
+        doc.traverse(|n| {
+            if n.tag_name() == Some("div") {
+                divs += 1;
+            }
+        });
+        assert_eq!(divs, 0);
+
+        // ...but the fragment itself can be queried
+        assert_eq!(QuerySelector::select_within(&content, ".card").len(), 1);
+    }
+
+    #[test]
+    fn test_append_fragment_moves_children() {
+        let html =
+            r#"<html><body><template id="tpl"><div id="inner">Hi</div></template></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+        let content = doc
+            .get_element_by_id("tpl")
+            .unwrap()
+            .template_content()
+            .unwrap();
+
+        let clone = doc.clone_node(&content, true);
+        assert!(clone.is_document_fragment());
+        assert_eq!(clone.children().len(), 1);
+
+        let body = doc.body().unwrap();
+        doc.append_child(&body, clone.clone()).unwrap();
+        assert!(clone.children().is_empty());
+        assert_eq!(content.children().len(), 1);
+        assert_eq!(body.text_content(), "Hi");
+        let inner = doc.get_element_by_id("inner").unwrap();
+        assert!(Rc::ptr_eq(&inner.parent().unwrap(), &body));
+        assert_eq!(doc.get_elements_by_tag_name("div").len(), 1);
+    }
+
+    #[test]
+    fn test_misnested_markup_is_repaired_and_reported() {
+        let html = r#"<html><body><b>1<p id="p">2</b>3</p><table id="t"><tr><td>x</td></tr>stray</table></body></html>"#;
+        let mut errors = Vec::new();
+        let doc =
+            Document::parse_html_reporting(html, &mut |e| errors.push(e.to_string())).unwrap();
+
+        // The paragraph moves out of the bold, which is copied into it
+        let p = doc.get_element_by_id("p").unwrap();
+        assert_eq!(p.parent().unwrap().tag_name(), Some("body"));
+        assert_eq!(p.children()[0].tag_name(), Some("b"));
+        assert_eq!(p.text_content(), "23");
+
+        // Text inside the table but outside a cell goes before the table
+        let table = doc.get_element_by_id("t").unwrap();
+        let body = doc.body().unwrap();
+        let index = body
+            .children()
+            .iter()
+            .position(|c| Rc::ptr_eq(c, &table))
+            .unwrap();
+        assert_eq!(body.children()[index - 1].text_content(), "stray");
+
+        assert_eq!(
+            errors,
+            [
+                "misnested-formatting-element </b>",
+                "foster-parented-content #text"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_append_child_rejects_ancestor() {
+        let html = r#"<html><body><div id="outer"><p id="inner"></p></div></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+        let outer = doc.get_element_by_id("outer").unwrap();
+        let inner = doc.get_element_by_id("inner").unwrap();
+        assert!(doc.append_child(&inner, outer.clone()).is_err());
+        assert!(doc.append_child(&outer, outer.clone()).is_err());
+    }
+
+    #[test]
+    fn test_clone_template_copies_content() {
+        let html = r#"<html><body><template id="tpl"><b>x</b></template></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+        let template = doc.get_element_by_id("tpl").unwrap();
+
+        let shallow = doc.clone_node(&template, false);
+        assert!(shallow.template_content().unwrap().children().is_empty());
+        let deep = doc.clone_node(&template, true);
+        assert_eq!(deep.template_content().unwrap().text_content(), "x");
+        assert!(deep.parent().is_none());
+    }
+
+    #[test]
+    fn test_inner_html_serializes_children() {
+        let html = r#"<html><body><div id="box"><p title='a "b"' class=x>1 &lt; 2<br></p><style>a > b {}</style></div></body></html>"#;
+        let doc = Document::parse_html(html).unwrap();
+        let div = doc.get_element_by_id("box").unwrap();
+        assert_eq!(
+            div.inner_html(),
+            r#"<p class="x" title="a &quot;b&quot;">1 &lt; 2<br></p><style>a > b {}</style>"#
+        );
+    }
 }
 
     #[test]
@@ -0,0 +1,443 @@
+//! Tree mutation: inserting, removing, replacing and importing nodes, the
+//! `insertAdjacent*` family and setting `innerHTML`.
+//!
+//! Every operation validates the change before touching the tree, so a
+//! rejected insertion leaves it as it was. Debug builds check the parent
+//! and sibling links around each change.
+
+use crate::{Document, DomError, Node, NodeType};
+use std::rc::Rc;
+
+/// Where `insertAdjacent*` puts content relative to an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjacentPosition {
+    /// Before the element, as its previous sibling.
+    BeforeBegin,
+    /// Inside the element, before its first child.
+    AfterBegin,
+    /// Inside the element, after its last child.
+    BeforeEnd,
+    /// After the element, as its next sibling.
+    AfterEnd,
+}
+
+impl AdjacentPosition {
+    /// Parse a position keyword, ignoring ASCII case.
+    pub fn parse(keyword: &str) -> Result<Self, DomError> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "beforebegin" => Ok(Self::BeforeBegin),
+            "afterbegin" => Ok(Self::AfterBegin),
+            "beforeend" => Ok(Self::BeforeEnd),
+            "afterend" => Ok(Self::AfterEnd),
+            _ => Err(DomError::Syntax(format!(
+                "'{}' is not a valid insertion position",
+                keyword
+            ))),
+        }
+    }
+
+    /// Whether content goes inside the element rather than beside it.
+    fn is_inside(self) -> bool {
+        matches!(self, Self::AfterBegin | Self::BeforeEnd)
+    }
+}
+
+impl Node {
+    /// Check that the parent and sibling links below this node agree with
+    /// the child lists.
+    pub fn check_tree(&self) -> Result<(), String> {
+        let children = self.children.borrow();
+        for (i, child) in children.iter().enumerate() {
+            let parent_ok = child
+                .parent()
+                .is_some_and(|p| std::ptr::eq(Rc::as_ptr(&p), self));
+            if !parent_ok {
+                return Err(format!("node {} has the wrong parent", child.id.raw()));
+            }
+            let prev = child.previous_sibling();
+            let expected_prev = i.checked_sub(1).map(|i| &children[i]);
+            if prev.as_ref().map(Rc::as_ptr) != expected_prev.map(Rc::as_ptr) {
+                return Err(format!(
+                    "node {} has the wrong previous sibling",
+                    child.id.raw()
+                ));
+            }
+            let next = child.next_sibling();
+            if next.as_ref().map(Rc::as_ptr) != children.get(i + 1).map(Rc::as_ptr) {
+                return Err(format!(
+                    "node {} has the wrong next sibling",
+                    child.id.raw()
+                ));
+            }
+            child.check_tree()?;
+        }
+        Ok(())
+    }
+}
+
+fn debug_check(node: &Node) {
+    if cfg!(debug_assertions) {
+        if let Err(e) = node.check_tree() {
+            panic!("DOM tree invariant broken: {}", e);
+        }
+    }
+}
+
+fn can_have_children(node: &Node) -> bool {
+    matches!(
+        node.node_type,
+        NodeType::Element { .. } | NodeType::Document | NodeType::DocumentFragment
+    )
+}
+
+impl Document {
+    /// Check that `node` may be inserted into `parent` before `reference`.
+    fn validate_insertion(
+        parent: &Rc<Node>,
+        node: &Rc<Node>,
+        reference: Option<&Rc<Node>>,
+    ) -> Result<(), DomError> {
+        if !can_have_children(parent) {
+            return Err(DomError::HierarchyRequest(
+                "the parent cannot have children".into(),
+            ));
+        }
+        if node.shadow_including_contains(parent) {
+            return Err(DomError::HierarchyRequest(
+                "cannot insert a node into itself or its descendant".into(),
+            ));
+        }
+        if matches!(node.node_type, NodeType::Document) {
+            return Err(DomError::HierarchyRequest(
+                "cannot insert a document".into(),
+            ));
+        }
+        if let Some(reference) = reference {
+            if !reference.parent().is_some_and(|p| Rc::ptr_eq(&p, parent)) {
+                return Err(DomError::NodeNotFound);
+            }
+        }
+        Ok(())
+    }
+
+    /// Insert `node` into `parent` before `reference`, or at the end when
+    /// `reference` is `None`, moving it from where it was.
+    ///
+    /// Inserting a fragment moves its children instead and leaves it empty.
+    pub fn insert_before(
+        &self,
+        parent: &Rc<Node>,
+        node: Rc<Node>,
+        reference: Option<&Rc<Node>>,
+    ) -> Result<(), DomError> {
+        Self::validate_insertion(parent, &node, reference)?;
+
+        // Inserting a node before itself puts it where it already is
+        let mut reference = reference.cloned();
+        if reference.as_ref().is_some_and(|r| Rc::ptr_eq(r, &node)) {
+            reference = node.next_sibling();
+        }
+
+        let moved = match node.is_document_fragment() {
+            true => node.children(),
+            false => vec![node],
+        };
+        for node in &moved {
+            if let Some(old_parent) = node.parent() {
+                node.remove_from_parent();
+                debug_check(&old_parent);
+            }
+            match reference {
+                Some(ref reference) => parent.insert_before(node.clone(), reference.clone()),
+                None => parent.append_child(node.clone()),
+            }
+        }
+        debug_check(parent);
+
+        if self.is_in_document(parent) {
+            for node in &moved {
+                self.index_ids(node);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove `child` from `parent` and return it.
+    pub fn remove_child(&self, parent: &Rc<Node>, child: &Rc<Node>) -> Result<Rc<Node>, DomError> {
+        if !child.parent().is_some_and(|p| Rc::ptr_eq(&p, parent)) {
+            return Err(DomError::NodeNotFound);
+        }
+        child.remove_from_parent();
+        debug_check(parent);
+        Ok(child.clone())
+    }
+
+    /// Put `node` in place of `old` among `parent`'s children and return
+    /// `old`.
+    pub fn replace_child(
+        &self,
+        parent: &Rc<Node>,
+        node: Rc<Node>,
+        old: &Rc<Node>,
+    ) -> Result<Rc<Node>, DomError> {
+        Self::validate_insertion(parent, &node, Some(old))?;
+        if Rc::ptr_eq(&node, old) {
+            return Ok(node);
+        }
+        let mut reference = old.next_sibling();
+        if reference.as_ref().is_some_and(|r| Rc::ptr_eq(r, &node)) {
+            reference = node.next_sibling();
+        }
+        self.remove_child(parent, old)?;
+        self.insert_before(parent, node, reference.as_ref())?;
+        Ok(old.clone())
+    }
+
+    /// Whether `node` was created by this document.
+    pub fn owns(&self, node: &Rc<Node>) -> bool {
+        self.nodes
+            .borrow()
+            .get(&node.id)
+            .is_some_and(|own| Rc::ptr_eq(own, node))
+    }
+
+    /// Copy a node of any document into this one (`document.importNode`).
+    pub fn import_node(&self, node: &Rc<Node>, deep: bool) -> Rc<Node> {
+        self.clone_node(node, deep)
+    }
+
+    /// Take a node into this document, removing it from its parent
+    /// (`document.adoptNode`). Node ids belong to the document that
+    /// created them, so a node of another document is replaced by a copy.
+    pub fn adopt_node(&self, node: &Rc<Node>) -> Result<Rc<Node>, DomError> {
+        if matches!(node.node_type, NodeType::Document) {
+            return Err(DomError::NotSupported("cannot adopt a document".into()));
+        }
+        node.remove_from_parent();
+        if self.owns(node) {
+            return Ok(node.clone());
+        }
+        Ok(self.clone_node(node, true))
+    }
+
+    /// Create a text node.
+    pub fn create_text_node(&self, data: &str) -> Rc<Node> {
+        self.create_node(NodeType::Text(data.to_string()))
+    }
+
+    /// Create an HTML element without attributes.
+    pub fn create_element(&self, tag_name: &str) -> Rc<Node> {
+        self.create_node(NodeType::Element {
+            tag_name: tag_name.to_ascii_lowercase(),
+            namespace: String::from("http://www.w3.org/1999/xhtml"),
+            attributes: Default::default(),
+        })
+    }
+
+    /// Parse `html` as the contents of a `context` element into a new
+    /// fragment of this document.
+    pub fn parse_fragment(&self, html: &str, context: &str) -> Result<Rc<Node>, DomError> {
+        let sink = rustkit_html::parse_fragment(html, crate::DocumentSink::new(), context)
+            .map_err(|e| DomError::ParseError(e.to_string()))?;
+        let fragment = self.create_document_fragment();
+        for child in sink.doc.root.children() {
+            fragment.append_child(self.clone_node(&child, true));
+        }
+        Ok(fragment)
+    }
+
+    /// Insert `node` at `position` relative to `element`. Returns whether it
+    /// was inserted; nothing can go beside an element without a parent.
+    pub fn insert_adjacent(
+        &self,
+        element: &Rc<Node>,
+        position: AdjacentPosition,
+        node: Rc<Node>,
+    ) -> Result<bool, DomError> {
+        match position {
+            AdjacentPosition::BeforeBegin | AdjacentPosition::AfterEnd => {
+                let Some(parent) = element.parent() else {
+                    return Ok(false);
+                };
+                if matches!(parent.node_type, NodeType::Document) {
+                    return Ok(false);
+                }
+                let reference = match position {
+                    AdjacentPosition::BeforeBegin => Some(element.clone()),
+                    _ => element.next_sibling(),
+                };
+                self.insert_before(&parent, node, reference.as_ref())?;
+            }
+            AdjacentPosition::AfterBegin => {
+                self.insert_before(element, node, element.first_child().as_ref())?;
+            }
+            AdjacentPosition::BeforeEnd => self.insert_before(element, node, None)?,
+        }
+        Ok(true)
+    }
+
+    /// Parse `html` and insert it at `position` relative to `element`
+    /// (`element.insertAdjacentHTML`).
+    pub fn insert_adjacent_html(
+        &self,
+        element: &Rc<Node>,
+        position: AdjacentPosition,
+        html: &str,
+    ) -> Result<(), DomError> {
+        let context = match position.is_inside() {
+            true => Some(element.clone()),
+            false => element.parent(),
+        };
+        let context = context
+            .as_ref()
+            .and_then(|c| c.tag_name())
+            .unwrap_or("body")
+            .to_ascii_lowercase();
+        if !position.is_inside() && element.parent().is_none_or(|p| !p.is_element()) {
+            return Err(DomError::NoModificationAllowed(
+                "the element has no parent element".into(),
+            ));
+        }
+        let fragment = self.parse_fragment(html, &context)?;
+        self.insert_adjacent(element, position, fragment)?;
+        Ok(())
+    }
+
+    /// Replace the children of `node` with `html` parsed in its context. A
+    /// shadow root parses in the context of its host.
+    pub fn set_inner_html(&self, node: &Rc<Node>, html: &str) -> Result<(), DomError> {
+        if !can_have_children(node) {
+            return Err(DomError::NoModificationAllowed(
+                "the node cannot have children".into(),
+            ));
+        }
+        let context = node
+            .host()
+            .as_ref()
+            .unwrap_or(node)
+            .tag_name()
+            .unwrap_or("body")
+            .to_ascii_lowercase();
+        let fragment = self.parse_fragment(html, &context)?;
+        for child in node.children() {
+            self.remove_child(node, &child)?;
+        }
+        self.insert_before(node, fragment, None)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(node: &Rc<Node>) -> Vec<String> {
+        node.children()
+            .iter()
+            .map(|c| match &c.node_type {
+                NodeType::Text(text) => format!("#{}", text),
+                _ => c.get_attribute("id").unwrap_or("?").to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tree_operations_keep_links_consistent() {
+        let doc = Document::parse_html(
+            r#"<html><body><ul id="list"><li id="a"></li><li id="b"></li><li id="c"></li></ul></body></html>"#,
+        )
+        .unwrap();
+        let list = doc.get_element_by_id("list").unwrap();
+        let node = |id: &str| doc.get_element_by_id(id).unwrap();
+
+        // A null reference appends
+        doc.insert_before(&list, node("a"), None).unwrap();
+        assert_eq!(ids(&list), ["b", "c", "a"]);
+        doc.insert_before(&list, node("a"), Some(&node("b")))
+            .unwrap();
+        assert_eq!(ids(&list), ["a", "b", "c"]);
+        // Before itself is a no-op
+        doc.insert_before(&list, node("b"), Some(&node("b")))
+            .unwrap();
+        assert_eq!(ids(&list), ["a", "b", "c"]);
+
+        let removed = doc.remove_child(&list, &node("b")).unwrap();
+        assert!(removed.parent().is_none() && removed.next_sibling().is_none());
+        assert!(doc.remove_child(&list, &removed).is_err());
+
+        let old = doc
+            .replace_child(&list, removed.clone(), &node("c"))
+            .unwrap();
+        assert_eq!(ids(&list), ["a", "b"]);
+        assert!(old.parent().is_none());
+
+        let copy = doc.clone_node(&list, true);
+        assert!(copy.parent().is_none());
+        assert_eq!(ids(&copy), ["a", "b"]);
+        assert!(!Rc::ptr_eq(&copy.children()[0], &node("a")));
+
+        doc.insert_adjacent(
+            &list,
+            AdjacentPosition::AfterBegin,
+            doc.create_text_node("x"),
+        )
+        .unwrap();
+        doc.insert_adjacent_html(&node("a"), AdjacentPosition::AfterEnd, "<li id=\"d\"></li>")
+            .unwrap();
+        doc.insert_adjacent_html(
+            &list,
+            AdjacentPosition::BeforeBegin,
+            "<p id=\"title\">T</p>",
+        )
+        .unwrap();
+        assert_eq!(ids(&list), ["#x", "a", "d", "b"]);
+        assert_eq!(ids(&doc.body().unwrap()), ["title", "list"]);
+        // Nodes from insertAdjacentHTML are findable by id
+        assert!(doc.get_element_by_id("d").is_some());
+
+        doc.root().check_tree().unwrap();
+    }
+
+    #[test]
+    fn test_rejected_insertions_leave_the_tree_alone() {
+        let doc = Document::parse_html(
+            r#"<html><body><div id="outer"><p id="inner">t</p></div></body></html>"#,
+        )
+        .unwrap();
+        let outer = doc.get_element_by_id("outer").unwrap();
+        let inner = doc.get_element_by_id("inner").unwrap();
+
+        let err = doc.insert_before(&inner, outer.clone(), None).unwrap_err();
+        assert_eq!(err.name(), "HierarchyRequestError");
+        let text = inner.first_child().unwrap();
+        let err = doc
+            .insert_before(&text, doc.create_text_node("x"), None)
+            .unwrap_err();
+        assert_eq!(err.name(), "HierarchyRequestError");
+        let err = doc.insert_before(&outer, doc.create_text_node("x"), Some(&text));
+        assert_eq!(err.unwrap_err().name(), "NotFoundError");
+        let err = AdjacentPosition::parse("inside").unwrap_err();
+        assert_eq!(err.name(), "SyntaxError");
+
+        assert!(Rc::ptr_eq(&inner.parent().unwrap(), &outer));
+        doc.root().check_tree().unwrap();
+    }
+
+    #[test]
+    fn test_nodes_move_between_documents() {
+        let doc = Document::parse_html("<html><body></body></html>").unwrap();
+        let other =
+            Document::parse_html(r#"<html><body><b id="x">bold</b></body></html>"#).unwrap();
+        let foreign = other.get_element_by_id("x").unwrap();
+
+        let imported = doc.import_node(&foreign, true);
+        assert!(doc.owns(&imported) && !doc.owns(&foreign));
+        assert!(foreign.parent().is_some());
+
+        let adopted = doc.adopt_node(&foreign).unwrap();
+        assert!(foreign.parent().is_none());
+        doc.insert_before(&doc.body().unwrap(), adopted, None)
+            .unwrap();
+        assert_eq!(doc.get_element_by_id("x").unwrap().text_content(), "bold");
+    }
+}
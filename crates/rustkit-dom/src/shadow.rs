@@ -0,0 +1,396 @@
+//! Shadow trees: `attachShadow`, slot assignment and the flattened tree.
+//!
+//! A shadow root is a document fragment kept on its host outside the host's
+//! children, like a template's contents, so document queries and traversals
+//! never reach into it. Rendering follows the flattened tree instead: a
+//! host's shadow root's children stand in for its own, and each `<slot>` in
+//! a shadow tree for the host children assigned to it, or for its own
+//! children when none are. Events leaving a shadow tree are retargeted so
+//! listeners outside see the host rather than the node inside.
+
+use crate::{Document, DomError, Node, NodeType};
+use std::rc::Rc;
+
+/// Whether script outside a shadow tree can reach it through
+/// `element.shadowRoot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
+}
+
+impl ShadowRootMode {
+    /// Parse the `mode` of `attachShadow`'s options.
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "open" => Some(Self::Open),
+            "closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+
+    /// The mode as script sees it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+/// Built-in elements that can host a shadow root.
+const SHADOW_HOSTS: [&str; 18] = [
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "div",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "main",
+    "nav",
+    "p",
+    "section",
+    "span",
+];
+
+/// Whether `tag` is a valid custom element name, which may host a shadow
+/// root too.
+fn is_custom_element_name(tag: &str) -> bool {
+    tag.starts_with(|c: char| c.is_ascii_lowercase()) && tag.contains('-')
+}
+
+impl Node {
+    /// The shadow root attached to this element, whatever its mode.
+    pub fn shadow_root(&self) -> Option<Rc<Node>> {
+        self.shadow_root.borrow().clone()
+    }
+
+    /// The shadow root script sees as `element.shadowRoot`: only an open
+    /// one.
+    pub fn open_shadow_root(&self) -> Option<Rc<Node>> {
+        self.shadow_root()
+            .filter(|root| root.shadow_root_mode() == Some(ShadowRootMode::Open))
+    }
+
+    /// The element this shadow root is attached to.
+    pub fn host(&self) -> Option<Rc<Node>> {
+        self.shadow_host
+            .borrow()
+            .as_ref()
+            .and_then(|(host, _)| host.upgrade())
+    }
+
+    /// The mode of a shadow root.
+    pub fn shadow_root_mode(&self) -> Option<ShadowRootMode> {
+        self.shadow_host.borrow().as_ref().map(|(_, mode)| *mode)
+    }
+
+    /// Check if this is a shadow root.
+    pub fn is_shadow_root(&self) -> bool {
+        self.shadow_host.borrow().is_some()
+    }
+
+    /// The shadow root of the tree this node is in, if it is in one.
+    pub fn containing_shadow_root(self: &Rc<Self>) -> Option<Rc<Node>> {
+        Some(self.root_node()).filter(|root| root.is_shadow_root())
+    }
+
+    /// The parent of this node, or the host of a shadow root.
+    pub fn parent_or_host(&self) -> Option<Rc<Node>> {
+        self.parent().or_else(|| self.host())
+    }
+
+    /// Whether `other` is this node or below it, counting the contents of
+    /// shadow trees of hosts below it.
+    pub fn shadow_including_contains(&self, other: &Rc<Node>) -> bool {
+        std::iter::successors(Some(other.clone()), |node| node.parent_or_host())
+            .any(|node| std::ptr::eq(Rc::as_ptr(&node), self))
+    }
+
+    /// Check if this is a `<slot>` element in a shadow tree, which lays out
+    /// the host children assigned to it.
+    pub fn is_slot(self: &Rc<Self>) -> bool {
+        self.tag_name()
+            .is_some_and(|t| t.eq_ignore_ascii_case("slot"))
+            && self.containing_shadow_root().is_some()
+    }
+
+    /// The slot name of a host child: an element's `slot` attribute, or the
+    /// default slot's empty name.
+    fn slot_name(&self) -> &str {
+        self.get_attribute("slot").unwrap_or("")
+    }
+
+    /// The first slot in tree order named `name` under `root`.
+    fn find_slot(root: &Rc<Node>, name: &str) -> Option<Rc<Node>> {
+        for child in root.children() {
+            if child
+                .tag_name()
+                .is_some_and(|t| t.eq_ignore_ascii_case("slot"))
+                && child.get_attribute("name").unwrap_or("") == name
+            {
+                return Some(child);
+            }
+            if let Some(slot) = Self::find_slot(&child, name) {
+                return Some(slot);
+            }
+        }
+        None
+    }
+
+    /// The host children assigned to this slot: those naming it in their
+    /// `slot` attribute, or for a slot without a name the text and the
+    /// elements naming none. Only the first slot of a name gets them.
+    pub fn assigned_nodes(self: &Rc<Self>) -> Vec<Rc<Node>> {
+        let Some(root) = self.containing_shadow_root() else {
+            return Vec::new();
+        };
+        let Some(host) = root.host() else {
+            return Vec::new();
+        };
+        let name = self.get_attribute("name").unwrap_or("");
+        if !Self::find_slot(&root, name).is_some_and(|slot| Rc::ptr_eq(&slot, self)) {
+            return Vec::new();
+        }
+        host.children()
+            .into_iter()
+            .filter(|child| match child.node_type {
+                NodeType::Element { .. } => child.slot_name() == name,
+                NodeType::Text(_) => name.is_empty(),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// The slot a child of a shadow host is assigned to.
+    pub fn assigned_slot(&self) -> Option<Rc<Node>> {
+        let name = match self.node_type {
+            NodeType::Element { .. } => self.slot_name(),
+            NodeType::Text(_) => "",
+            _ => return None,
+        };
+        let root = self.parent()?.shadow_root()?;
+        Self::find_slot(&root, name)
+    }
+
+    /// Children in the flattened tree: a shadow root's children for its
+    /// host, and a slot's assigned nodes, or its own children when nothing
+    /// is assigned.
+    pub fn flat_children(self: &Rc<Self>) -> Vec<Rc<Node>> {
+        if let Some(root) = self.shadow_root() {
+            return root.children();
+        }
+        if self.is_slot() {
+            let assigned = self.assigned_nodes();
+            if !assigned.is_empty() {
+                return assigned;
+            }
+        }
+        self.children()
+    }
+
+    /// Parent in the flattened tree: the slot a host child is assigned to,
+    /// or the host for the top of a shadow tree. Host children assigned to
+    /// no slot have none.
+    pub fn flat_parent(&self) -> Option<Rc<Node>> {
+        let parent = self.parent()?;
+        if parent.shadow_root().is_some() {
+            return self.assigned_slot();
+        }
+        Some(parent.host().unwrap_or(parent))
+    }
+}
+
+impl Document {
+    /// Attach a shadow root to `host` (`element.attachShadow`).
+    ///
+    /// Only custom elements and the built-in elements that take one may
+    /// host a shadow root, and only one.
+    pub fn attach_shadow(
+        &self,
+        host: &Rc<Node>,
+        mode: ShadowRootMode,
+    ) -> Result<Rc<Node>, DomError> {
+        let tag = host
+            .tag_name()
+            .ok_or_else(|| DomError::NotSupported("only elements can host a shadow root".into()))?
+            .to_ascii_lowercase();
+        if !SHADOW_HOSTS.contains(&tag.as_str()) && !is_custom_element_name(&tag) {
+            return Err(DomError::NotSupported(format!(
+                "<{}> cannot host a shadow root",
+                tag
+            )));
+        }
+        if host.shadow_root().is_some() {
+            return Err(DomError::NotSupported(
+                "the element already hosts a shadow root".into(),
+            ));
+        }
+        let root = self.create_document_fragment();
+        *root.shadow_host.borrow_mut() = Some((Rc::downgrade(host), mode));
+        *host.shadow_root.borrow_mut() = Some(root.clone());
+        Ok(root)
+    }
+
+    /// Whether `node` is in this document's tree or in a shadow tree of a
+    /// host that is.
+    pub fn is_connected(&self, node: &Rc<Node>) -> bool {
+        let mut root = node.root_node();
+        while let Some(host) = root.host() {
+            root = host.root_node();
+        }
+        Rc::ptr_eq(&root, self.root())
+    }
+}
+
+/// `target` as seen from `observer`: the host of the outermost shadow tree
+/// containing `target` that `observer` is outside of, or `target` itself.
+pub fn retarget(target: &Rc<Node>, observer: &Rc<Node>) -> Rc<Node> {
+    let mut target = target.clone();
+    loop {
+        let root = target.root_node();
+        match root.host() {
+            Some(host) if !root.shadow_including_contains(observer) => target = host,
+            _ => return target,
+        }
+    }
+}
+
+/// The nodes an event dispatched at `target` passes through, `target`
+/// first and the root last. A `composed` event leaves shadow trees through
+/// their hosts; any other stops at the shadow root of `target`'s tree.
+pub fn event_path(target: &Rc<Node>, composed: bool) -> Vec<Rc<Node>> {
+    let mut path = Vec::new();
+    let mut node = Some(target.clone());
+    while let Some(current) = node {
+        node = match current.host() {
+            Some(host) if composed => Some(host),
+            Some(_) => None,
+            None => current.parent(),
+        };
+        path.push(current);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AddEventListenerOptions, DomEvent, Event, EventDispatcher};
+    use crate::QuerySelector;
+
+    fn tags(nodes: &[Rc<Node>]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| match &n.node_type {
+                NodeType::Text(text) => text.clone(),
+                _ => n.tag_name().unwrap_or("#").to_string(),
+            })
+            .collect()
+    }
+
+    fn host_with_shadow(shadow_html: &str) -> (Document, Rc<Node>, Rc<Node>) {
+        let document = Document::parse_html(
+            r#"<html><body><div id="host"><b slot="title">T</b>light<i>I</i></div></body></html>"#,
+        )
+        .unwrap();
+        let host = document.get_element_by_id("host").unwrap();
+        let root = document.attach_shadow(&host, ShadowRootMode::Open).unwrap();
+        document.set_inner_html(&root, shadow_html).unwrap();
+        (document, host, root)
+    }
+
+    #[test]
+    fn test_slots_distribute_host_children() {
+        let (document, host, root) = host_with_shadow(
+            r#"<h1><slot name="title"></slot></h1><slot></slot><slot name="none">fallback</slot>"#,
+        );
+        assert_eq!(tags(&host.flat_children()), ["h1", "slot", "slot"]);
+
+        let title = QuerySelector::select_within(&root, "slot")[0].clone();
+        assert_eq!(tags(&title.flat_children()), ["b"]);
+        let default = &root.children()[1];
+        assert_eq!(tags(&default.flat_children()), ["light", "i"]);
+        assert_eq!(tags(&root.children()[2].flat_children()), ["fallback"]);
+
+        let b = &host.children()[0];
+        assert!(Rc::ptr_eq(&b.assigned_slot().unwrap(), &title));
+        assert!(Rc::ptr_eq(&b.flat_parent().unwrap(), &title));
+        assert!(Rc::ptr_eq(
+            &root.children()[0].flat_parent().unwrap(),
+            &host
+        ));
+
+        // The shadow tree is out of reach of the document
+        assert!(document.is_connected(&title));
+        assert!(!document.is_in_document(&title));
+        assert!(document.get_elements_by_tag_name("slot").is_empty());
+        assert!(QuerySelector::select(&document, "h1").is_empty());
+    }
+
+    #[test]
+    fn test_attach_shadow_checks_host() {
+        let document = Document::parse_html(
+            r#"<html><body><div id="a"></div><img id="b"><x-card id="c"></x-card></body></html>"#,
+        )
+        .unwrap();
+        let div = document.get_element_by_id("a").unwrap();
+        let root = document
+            .attach_shadow(&div, ShadowRootMode::Closed)
+            .unwrap();
+        assert!(root.is_shadow_root());
+        assert!(Rc::ptr_eq(&div.shadow_root().unwrap(), &root));
+        assert!(div.open_shadow_root().is_none());
+        assert!(document.attach_shadow(&div, ShadowRootMode::Open).is_err());
+
+        let img = document.get_element_by_id("b").unwrap();
+        assert!(document.attach_shadow(&img, ShadowRootMode::Open).is_err());
+        let card = document.get_element_by_id("c").unwrap();
+        assert!(document.attach_shadow(&card, ShadowRootMode::Open).is_ok());
+    }
+
+    #[test]
+    fn test_events_retarget_at_the_host() {
+        let (document, host, root) = host_with_shadow("<p><span>x</span></p>");
+        assert_eq!(root.inner_html(), "<p><span>x</span></p>");
+        let span = QuerySelector::select_within(&root, "span")[0].clone();
+        let body = document.body().unwrap();
+
+        let composed = event_path(&span, true);
+        assert_eq!(tags(&composed[..4]), ["span", "p", "#", "div"]);
+        assert!(Rc::ptr_eq(composed.last().unwrap(), document.root()));
+        assert_eq!(event_path(&span, false).len(), 3);
+
+        assert!(Rc::ptr_eq(&retarget(&span, &body), &host));
+        assert!(Rc::ptr_eq(&retarget(&span, &host), &host));
+        assert!(Rc::ptr_eq(&retarget(&span, &root), &span));
+
+        let seen = Rc::new(std::cell::RefCell::new(Vec::new()));
+        for node in [&body, &span] {
+            let seen = seen.clone();
+            node.event_target.add_event_listener(
+                "click",
+                Box::new(move |event| seen.borrow_mut().push(event.event().target())),
+                AddEventListenerOptions::default(),
+            );
+        }
+        let mut click = Event::new("click", true, true);
+        click.composed = true;
+        let mut click = DomEvent::Generic(click);
+        assert!(EventDispatcher::dispatch_composed(&mut click, &span));
+        assert_eq!(*seen.borrow(), [Some(span.id), Some(host.id)]);
+
+        // An event that isn't composed stays in the shadow tree
+        seen.borrow_mut().clear();
+        let mut click = DomEvent::Generic(Event::new("click", true, true));
+        EventDispatcher::dispatch_composed(&mut click, &span);
+        assert_eq!(*seen.borrow(), [Some(span.id)]);
+    }
+}
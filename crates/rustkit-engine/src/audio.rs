@@ -0,0 +1,979 @@
+//! Playback of `<audio>` elements.
+//!
+//! Script drives each element through [`rustkit_bindings::AudioCommand`]s;
+//! the engine keeps a [`MediaPlayback`] per element that owns its clip and
+//! its timeline, and reports progress back as
+//! [`rustkit_bindings::AudioUpdate`]s. Clips are fetched through the
+//! resource loader. A clip whose server sends `Accept-Ranges: bytes` is
+//! read only up to [`HEAD_BYTES`] at first; WAV and constant-bitrate MP3
+//! clips then fetch the rest with `Range` requests from the byte a play or
+//! seek starts at, other formats the rest of the clip in one go. Clips from
+//! other servers arrive whole and seek in memory. Sound goes out through an
+//! [`AudioPlayer`]: the system's default device on Windows, nothing
+//! elsewhere. Durations are read from the container headers, so timelines
+//! behave the same with or without a device.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustkit_bindings::AudioUpdate;
+use rustkit_dom::NodeId;
+use thiserror::Error;
+use tracing::warn;
+
+/// How often a playing element fires `timeupdate`.
+const TIMEUPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Errors decoding or playing a clip.
+#[derive(Error, Debug)]
+pub enum AudioError {
+    #[error("Unsupported audio format")]
+    Unsupported,
+
+    #[error("Malformed audio: {0}")]
+    Malformed(&'static str),
+
+    #[error("Audio output error: {0}")]
+    Output(String),
+}
+
+/// Bytes read of a clip from a server that accepts ranges before it is
+/// fetched in ranges; enough for the headers of any format that seeks by
+/// byte offset.
+pub(crate) const HEAD_BYTES: u64 = 64 * 1024;
+
+/// An encoded clip and how long it plays.
+#[derive(Debug, Clone)]
+pub struct AudioClip {
+    bytes: Arc<[u8]>,
+    duration: Duration,
+    /// Where in the clip `bytes` begin, for a clip fetched from a seek.
+    start: Duration,
+}
+
+impl AudioClip {
+    /// Probe WAV, Ogg Vorbis or MP3 data for its duration.
+    pub fn decode(bytes: Vec<u8>) -> Result<Self, AudioError> {
+        let duration = if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+            wav_duration(&bytes)?
+        } else if bytes.starts_with(b"OggS") {
+            ogg_duration(&bytes)?
+        } else {
+            mp3_duration(&bytes)?
+        };
+        Ok(Self {
+            bytes: bytes.into(),
+            duration,
+            start: Duration::ZERO,
+        })
+    }
+
+    /// The encoded data.
+    pub fn bytes(&self) -> &Arc<[u8]> {
+        &self.bytes
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The time of the first sound in [`Self::bytes`]; players start a
+    /// clip at an offset from it.
+    pub fn start(&self) -> Duration {
+        self.start
+    }
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// The `data` chunk of a WAV file.
+struct WavData {
+    /// Offset of the chunk body.
+    start: usize,
+    /// Length in the chunk header; streamed files may leave it unset.
+    declared: usize,
+    /// Length of the body that arrived.
+    arrived: usize,
+}
+
+/// The `fmt ` chunk body and the `data` chunk of a WAV file.
+fn wav_chunks(bytes: &[u8]) -> (Option<&[u8]>, Option<WavData>) {
+    let (mut fmt, mut data) = (None, None);
+    let mut pos = 12;
+    while let Some(len) = u32_le(bytes, pos + 4) {
+        let len = len as usize;
+        let body = &bytes[pos + 8..(pos + 8).saturating_add(len).min(bytes.len())];
+        match &bytes[pos..pos + 4] {
+            b"fmt " => fmt = Some(body),
+            b"data" => {
+                data = Some(WavData {
+                    start: pos + 8,
+                    declared: len,
+                    arrived: body.len(),
+                })
+            }
+            _ => {}
+        }
+        pos = pos.saturating_add(8 + len + (len & 1));
+    }
+    (fmt, data)
+}
+
+/// Length of the `data` chunk over the byte rate of the `fmt ` chunk.
+fn wav_duration(bytes: &[u8]) -> Result<Duration, AudioError> {
+    let (fmt, data) = wav_chunks(bytes);
+    let byte_rate = fmt.and_then(|fmt| u32_le(fmt, 8));
+    // Streamed files may leave the length unset; what arrived plays
+    match (byte_rate, data) {
+        (Some(rate), Some(data)) if rate > 0 => {
+            Ok(Duration::from_secs_f64(data.arrived as f64 / rate as f64))
+        }
+        _ => Err(AudioError::Malformed("WAV without fmt and data chunks")),
+    }
+}
+
+/// Granule position of the last page over the Vorbis sample rate.
+fn ogg_duration(bytes: &[u8]) -> Result<Duration, AudioError> {
+    let header = bytes
+        .windows(7)
+        .position(|w| w == b"\x01vorbis")
+        .ok_or(AudioError::Unsupported)?;
+    let rate = u32_le(bytes, header + 12).filter(|&rate| rate > 0);
+    let last_page = bytes.windows(4).rposition(|w| w == b"OggS");
+    let granule = last_page
+        .and_then(|at| bytes.get(at + 6..at + 14))
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+    match (rate, granule) {
+        (Some(rate), Some(granule)) => Ok(Duration::from_secs_f64(granule as f64 / rate as f64)),
+        _ => Err(AudioError::Malformed("Ogg without Vorbis header or pages")),
+    }
+}
+
+/// The first frame of an MPEG audio layer III stream.
+struct Mp3Stream {
+    /// Offset of the first frame.
+    frame: usize,
+    /// Frame count of a Xing/Info header, which variable bitrates need.
+    frames: Option<u32>,
+    samples_per_frame: u32,
+    sample_rate: u32,
+    kbps: u32,
+}
+
+/// Find the first frame of an MP3 stream and read its header.
+fn mp3_stream(bytes: &[u8]) -> Result<Mp3Stream, AudioError> {
+    const MPEG1_KBPS: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_KBPS: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    // Skip an ID3v2 tag, whose size is stored in 7-bit bytes
+    let mut start = 0;
+    if bytes.starts_with(b"ID3") && bytes.len() >= 10 {
+        let size = bytes[6..10]
+            .iter()
+            .fold(0usize, |size, &b| (size << 7) | (b & 0x7f) as usize);
+        let footer = if bytes[5] & 0x10 != 0 { 10 } else { 0 };
+        start = 10 + size + footer;
+    }
+    let frame = bytes
+        .get(start..)
+        .and_then(|rest| {
+            rest.windows(2)
+                .position(|w| w[0] == 0xff && w[1] & 0xe0 == 0xe0)
+        })
+        .map(|at| start + at)
+        .ok_or(AudioError::Unsupported)?;
+    let header = u32::from_be_bytes(
+        bytes
+            .get(frame..frame + 4)
+            .ok_or(AudioError::Unsupported)?
+            .try_into()
+            .unwrap(),
+    );
+
+    let version = (header >> 19) & 0b11;
+    let layer = (header >> 17) & 0b11;
+    let bitrate_index = ((header >> 12) & 0xf) as usize;
+    let rate_index = ((header >> 10) & 0b11) as usize;
+    if version == 0b01 || layer != 0b01 || bitrate_index == 0xf || rate_index == 3 {
+        return Err(AudioError::Unsupported);
+    }
+    let mpeg1 = version == 0b11;
+    // MPEG-2 and 2.5 halve and quarter the MPEG-1 rates
+    let divisor = match version {
+        0b11 => 1,
+        0b10 => 2,
+        _ => 4,
+    };
+    let sample_rate = [44100, 48000, 32000][rate_index] / divisor;
+    let samples_per_frame = if mpeg1 { 1152 } else { 576 };
+    let mono = (header >> 6) & 0b11 == 0b11;
+
+    let side_info = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    let xing = frame + 4 + side_info;
+    let frames = bytes
+        .get(xing..xing + 12)
+        .filter(|tag| (&tag[..4] == b"Xing" || &tag[..4] == b"Info") && tag[7] & 1 != 0)
+        .map(|tag| u32::from_be_bytes(tag[8..12].try_into().unwrap()));
+
+    let kbps = if mpeg1 { MPEG1_KBPS } else { MPEG2_KBPS }[bitrate_index];
+    if kbps == 0 {
+        return Err(AudioError::Malformed("free-format MP3"));
+    }
+    Ok(Mp3Stream {
+        frame,
+        frames,
+        samples_per_frame,
+        sample_rate,
+        kbps,
+    })
+}
+
+/// MPEG audio layer III: the frame count of a Xing/Info header, or the
+/// stream length at the first frame's bitrate.
+fn mp3_duration(bytes: &[u8]) -> Result<Duration, AudioError> {
+    let stream = mp3_stream(bytes)?;
+    if let Some(frames) = stream.frames {
+        return Ok(Duration::from_secs_f64(
+            frames as f64 * stream.samples_per_frame as f64 / stream.sample_rate as f64,
+        ));
+    }
+    let audio_len = bytes.len() - stream.frame;
+    Ok(Duration::from_secs_f64(
+        audio_len as f64 * 8.0 / (stream.kbps as f64 * 1000.0),
+    ))
+}
+
+/// Where the sound of a clip sits in its resource, for formats whose byte
+/// offsets follow from the time: PCM WAV and constant-bitrate MP3.
+#[derive(Debug, Clone)]
+pub(crate) struct ByteMap {
+    /// Bytes ahead of the sound, put back in front of later ranges.
+    header: Vec<u8>,
+    data_start: u64,
+    data_end: u64,
+    byte_rate: u64,
+    /// Bytes in one sample frame; ranges start on one.
+    block: u64,
+}
+
+impl ByteMap {
+    /// Map a resource of `total` bytes from its opening `head`.
+    pub(crate) fn probe(head: &[u8], total: u64) -> Option<Self> {
+        if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE") {
+            let (fmt, data) = wav_chunks(head);
+            let (fmt, data) = (fmt?, data?);
+            // Only PCM seeks by offset
+            if u16_le(fmt, 0)? != 1 {
+                return None;
+            }
+            let byte_rate = u64::from(u32_le(fmt, 8)?);
+            let block = u64::from(u16_le(fmt, 12)?).max(1);
+            let data_start = data.start as u64;
+            let data_end = match data.declared as u64 {
+                0 => total,
+                declared => (data_start + declared).min(total),
+            };
+            (byte_rate > 0).then(|| Self {
+                header: head[..data_start as usize].to_vec(),
+                data_start,
+                data_end,
+                byte_rate,
+                block,
+            })
+        } else if head.starts_with(b"OggS") {
+            None
+        } else {
+            let stream = mp3_stream(head).ok()?;
+            // Variable bitrates need a seek table
+            if stream.frames.is_some() {
+                return None;
+            }
+            Some(Self {
+                header: Vec::new(),
+                data_start: stream.frame as u64,
+                data_end: total,
+                byte_rate: u64::from(stream.kbps) * 1000 / 8,
+                block: 1,
+            })
+        }
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        self.time(self.data_end)
+    }
+
+    /// The byte playback at `time` starts from.
+    pub(crate) fn offset(&self, time: Duration) -> u64 {
+        let bytes = (time.as_secs_f64() * self.byte_rate as f64) as u64;
+        (self.data_start + bytes / self.block * self.block).min(self.data_end)
+    }
+
+    fn time(&self, offset: u64) -> Duration {
+        let bytes = offset.saturating_sub(self.data_start);
+        Duration::from_secs_f64(bytes as f64 / self.byte_rate as f64)
+    }
+
+    /// A playable clip of the resource's bytes from `offset` on.
+    pub(crate) fn clip(&self, offset: u64, bytes: &[u8]) -> AudioClip {
+        let skip = self.data_start.saturating_sub(offset) as usize;
+        let data = bytes.get(skip..).unwrap_or_default();
+        let len = self.data_end.saturating_sub(offset.max(self.data_start)) as usize;
+        let data = &data[..data.len().min(len)];
+        let mut clip = self.header.clone();
+        if clip.len() >= 8 {
+            // A WAV header: size the RIFF and data chunks to what follows
+            let riff = (clip.len() - 8 + data.len()) as u32;
+            clip[4..8].copy_from_slice(&riff.to_le_bytes());
+            let at = clip.len() - 4;
+            clip[at..].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        }
+        clip.extend_from_slice(data);
+        AudioClip {
+            bytes: clip.into(),
+            duration: self.duration(),
+            start: self.time(offset.max(self.data_start)),
+        }
+    }
+}
+
+/// The first byte and length of a `Content-Range: bytes` value.
+pub(crate) fn content_range(value: &str) -> Option<(u64, u64)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (first, _) = range.split_once('-')?;
+    Some((first.trim().parse().ok()?, total.trim().parse().ok()?))
+}
+
+/// Bytes of a clip as the server sent them.
+pub(crate) struct AudioBody {
+    pub(crate) bytes: Vec<u8>,
+    /// Where `bytes` begin in the resource and its length, when the server
+    /// sent only part of it.
+    pub(crate) range: Option<(u64, u64)>,
+}
+
+/// The fetched part of a clip from a server that accepts `Range` requests.
+struct Ranged {
+    /// Where the sound sits, unless the format gives no byte offsets and
+    /// the rest of the clip is fetched to decode whole.
+    map: Option<ByteMap>,
+    /// Length of the resource.
+    total: u64,
+    /// Where `bytes` begin in the resource.
+    offset: u64,
+    bytes: Vec<u8>,
+    /// A range request is in flight.
+    fetching: bool,
+}
+
+impl Ranged {
+    fn end(&self) -> u64 {
+        self.offset + self.bytes.len() as u64
+    }
+
+    /// Take in the bytes from `offset` on, next to the fetched ones or
+    /// instead of them.
+    fn merge(&mut self, offset: u64, mut bytes: Vec<u8>) {
+        if offset == self.end() {
+            self.bytes.extend_from_slice(&bytes);
+        } else if offset + bytes.len() as u64 == self.offset {
+            bytes.extend_from_slice(&self.bytes);
+            self.bytes = bytes;
+            self.offset = offset;
+        } else {
+            self.bytes = bytes;
+            self.offset = offset;
+        }
+    }
+
+    /// The range playing from `position` still needs, as its first byte
+    /// and its last unless that is the end of the resource.
+    fn missing_from(&self, position: Duration) -> Option<(u64, Option<u64>)> {
+        match &self.map {
+            Some(map) => self.missing(map.offset(position), map.data_end),
+            None => self.missing(0, self.total),
+        }
+    }
+
+    /// The range playing from byte `from` to `until` still needs.
+    fn missing(&self, from: u64, until: u64) -> Option<(u64, Option<u64>)> {
+        let complete = self.end() >= until;
+        if from >= self.offset && complete {
+            None
+        } else if from >= self.offset && from <= self.end() {
+            Some((self.end(), None))
+        } else if from < self.offset && complete {
+            Some((from, Some(self.offset - 1)))
+        } else {
+            Some((from, None))
+        }
+    }
+}
+
+/// A clip sounding on an [`AudioPlayer`]; dropping it stops the sound.
+pub trait AudioVoice {
+    /// Set the gain, from 0 (silent) to 1.
+    fn set_volume(&self, volume: f32);
+}
+
+/// Where `<audio>` elements send their sound.
+pub trait AudioPlayer {
+    /// Start playing `clip` from `offset` at `volume`.
+    fn play(
+        &self,
+        clip: &AudioClip,
+        offset: Duration,
+        volume: f32,
+    ) -> Result<Box<dyn AudioVoice>, AudioError>;
+}
+
+/// Plays nothing; elements still advance through their clips.
+#[derive(Debug, Default)]
+pub struct SilentAudioPlayer;
+
+struct SilentVoice;
+
+impl AudioVoice for SilentVoice {
+    fn set_volume(&self, _volume: f32) {}
+}
+
+impl AudioPlayer for SilentAudioPlayer {
+    fn play(
+        &self,
+        _clip: &AudioClip,
+        _offset: Duration,
+        _volume: f32,
+    ) -> Result<Box<dyn AudioVoice>, AudioError> {
+        Ok(Box::new(SilentVoice))
+    }
+}
+
+#[cfg(windows)]
+mod system {
+    use super::*;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+    use std::io::Cursor;
+
+    /// The default output device, through WASAPI.
+    pub struct SystemAudioPlayer {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    impl SystemAudioPlayer {
+        pub fn new() -> Result<Self, AudioError> {
+            let (stream, handle) =
+                OutputStream::try_default().map_err(|e| AudioError::Output(e.to_string()))?;
+            Ok(Self {
+                _stream: stream,
+                handle,
+            })
+        }
+    }
+
+    impl AudioVoice for Sink {
+        fn set_volume(&self, volume: f32) {
+            Sink::set_volume(self, volume);
+        }
+    }
+
+    impl AudioPlayer for SystemAudioPlayer {
+        fn play(
+            &self,
+            clip: &AudioClip,
+            offset: Duration,
+            volume: f32,
+        ) -> Result<Box<dyn AudioVoice>, AudioError> {
+            let source = Decoder::new(Cursor::new(clip.bytes.clone()))
+                .map_err(|_| AudioError::Unsupported)?;
+            let sink =
+                Sink::try_new(&self.handle).map_err(|e| AudioError::Output(e.to_string()))?;
+            sink.set_volume(volume);
+            sink.append(source.skip_duration(offset));
+            Ok(Box::new(sink))
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use system::SystemAudioPlayer;
+
+/// The system's output device, or silence if there is none.
+pub(crate) fn default_player() -> Box<dyn AudioPlayer> {
+    #[cfg(windows)]
+    match SystemAudioPlayer::new() {
+        Ok(player) => return Box::new(player),
+        Err(e) => warn!(error = %e, "No audio output device"),
+    }
+    Box::new(SilentAudioPlayer)
+}
+
+/// The clip and timeline of one media element.
+pub(crate) struct MediaPlayback {
+    /// The element's node, if it is in the document.
+    pub(crate) node_id: Option<NodeId>,
+    /// Bumped by every load, so a superseded load is ignored.
+    pub(crate) generation: u64,
+    /// URL of the current load.
+    src: String,
+    clip: Option<AudioClip>,
+    ranged: Option<Ranged>,
+    /// A load is in flight.
+    loading: bool,
+    /// Position when playback last started, stopped or seeked.
+    position: Duration,
+    /// When the sound started playing from `position`.
+    started: Option<Instant>,
+    /// Script asked to play; starts once the clip loads.
+    wants_play: bool,
+    volume: f32,
+    muted: bool,
+    voice: Option<Box<dyn AudioVoice>>,
+    last_timeupdate: Option<Instant>,
+}
+
+impl MediaPlayback {
+    pub(crate) fn new(node_id: Option<NodeId>) -> Self {
+        Self {
+            node_id,
+            generation: 0,
+            src: String::new(),
+            clip: None,
+            ranged: None,
+            loading: false,
+            position: Duration::ZERO,
+            started: None,
+            wants_play: false,
+            volume: 1.0,
+            muted: false,
+            voice: None,
+            last_timeupdate: None,
+        }
+    }
+
+    /// Drop the current clip for one about to load from `src`; returns the
+    /// load's generation.
+    pub(crate) fn load(&mut self, src: &str) -> u64 {
+        self.generation += 1;
+        self.src = src.to_string();
+        self.clip = None;
+        self.ranged = None;
+        self.loading = true;
+        self.position = Duration::ZERO;
+        self.started = None;
+        self.wants_play = false;
+        self.voice = None;
+        self.generation
+    }
+
+    pub(crate) fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// Take in fetched bytes of the clip, all of it or a range, starting it
+    /// if script already asked to play and the bytes that needs are here.
+    pub(crate) fn received(
+        &mut self,
+        body: AudioBody,
+        player: &dyn AudioPlayer,
+        view_muted: bool,
+        now: Instant,
+    ) -> Result<Vec<AudioUpdate>, AudioError> {
+        let Some((offset, total)) = body.range else {
+            self.ranged = None;
+            let clip = AudioClip::decode(body.bytes)?;
+            return Ok(self.loaded(clip, player, view_muted, now));
+        };
+        let ranged = match &mut self.ranged {
+            Some(ranged) => {
+                ranged.fetching = false;
+                ranged.merge(offset, body.bytes);
+                ranged
+            }
+            None => self.ranged.insert(Ranged {
+                map: ByteMap::probe(&body.bytes, total),
+                total,
+                offset,
+                bytes: body.bytes,
+                fetching: false,
+            }),
+        };
+        let Some(map) = &ranged.map else {
+            if ranged.missing_from(Duration::ZERO).is_some() {
+                return Ok(Vec::new());
+            }
+            let bytes = self.ranged.take().map(|ranged| ranged.bytes);
+            let clip = AudioClip::decode(bytes.unwrap_or_default())?;
+            return Ok(self.loaded(clip, player, view_muted, now));
+        };
+        let clip = map.clip(ranged.offset, &ranged.bytes);
+        if self.clip.is_none() {
+            return Ok(self.loaded(clip, player, view_muted, now));
+        }
+        self.clip = Some(clip);
+        Ok(if self.wants_play {
+            self.start(player, view_muted, now).into_iter().collect()
+        } else {
+            Vec::new()
+        })
+    }
+
+    /// Claim the byte range of the clip to fetch next, as its first byte
+    /// and its last unless that is the end; `None` while a range is in
+    /// flight or none is needed.
+    pub(crate) fn next_range(&mut self) -> Option<(u64, Option<u64>)> {
+        let (position, wants_play) = (self.position, self.wants_play);
+        let ranged = self.ranged.as_mut().filter(|ranged| !ranged.fetching)?;
+        // Ranges for a seek are fetched once script plays
+        if ranged.map.is_some() && !wants_play {
+            return None;
+        }
+        let missing = ranged.missing_from(position)?;
+        ranged.fetching = true;
+        Some(missing)
+    }
+
+    /// Take the loaded clip, starting it if script already asked to play.
+    fn loaded(
+        &mut self,
+        clip: AudioClip,
+        player: &dyn AudioPlayer,
+        view_muted: bool,
+        now: Instant,
+    ) -> Vec<AudioUpdate> {
+        let mut updates = vec![AudioUpdate::Metadata {
+            duration: clip.duration.as_secs_f64(),
+        }];
+        self.position = self.position.min(clip.duration);
+        self.clip = Some(clip);
+        self.loading = false;
+        if self.wants_play {
+            updates.extend(self.start(player, view_muted, now));
+        }
+        updates
+    }
+
+    /// The clip could not be loaded; nothing plays until the next load or,
+    /// for a failed range, the next play.
+    pub(crate) fn failed(&mut self) {
+        self.loading = false;
+        self.wants_play = false;
+        if let Some(ranged) = &mut self.ranged {
+            ranged.fetching = false;
+        }
+    }
+
+    pub(crate) fn is_loading(&self) -> bool {
+        self.loading || self.ranged.as_ref().is_some_and(|ranged| ranged.fetching)
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub(crate) fn duration(&self) -> Option<Duration> {
+        self.clip.as_ref().map(|clip| clip.duration)
+    }
+
+    pub(crate) fn current_time(&self, now: Instant) -> Duration {
+        let elapsed = self
+            .started
+            .map_or(Duration::ZERO, |at| now.saturating_duration_since(at));
+        let time = self.position + elapsed;
+        self.duration().map_or(time, |duration| time.min(duration))
+    }
+
+    fn gain(&self, view_muted: bool) -> f32 {
+        if self.muted || view_muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+
+    fn start(
+        &mut self,
+        player: &dyn AudioPlayer,
+        view_muted: bool,
+        now: Instant,
+    ) -> Option<AudioUpdate> {
+        let clip = self.clip.as_ref()?;
+        if self.started.is_some() {
+            return None;
+        }
+        // Wait for the range playing from here needs
+        if self
+            .ranged
+            .as_ref()
+            .is_some_and(|ranged| ranged.missing_from(self.position).is_some())
+        {
+            return None;
+        }
+        let offset = self.position.saturating_sub(clip.start);
+        self.voice = player
+            .play(clip, offset, self.gain(view_muted))
+            .map_err(|e| warn!(error = %e, "Audio playback failed; continuing silently"))
+            .ok();
+        self.started = Some(now);
+        self.last_timeupdate = Some(now);
+        Some(AudioUpdate::Playing)
+    }
+
+    fn stop(&mut self, now: Instant) {
+        self.position = self.current_time(now);
+        self.started = None;
+        self.voice = None;
+    }
+
+    pub(crate) fn play(
+        &mut self,
+        player: &dyn AudioPlayer,
+        view_muted: bool,
+        now: Instant,
+    ) -> Option<AudioUpdate> {
+        self.wants_play = true;
+        self.start(player, view_muted, now)
+    }
+
+    pub(crate) fn pause(&mut self, now: Instant) -> AudioUpdate {
+        self.wants_play = false;
+        self.stop(now);
+        AudioUpdate::Paused {
+            time: self.position.as_secs_f64(),
+        }
+    }
+
+    pub(crate) fn seek(
+        &mut self,
+        time: f64,
+        player: &dyn AudioPlayer,
+        view_muted: bool,
+        now: Instant,
+    ) {
+        let playing = self.is_playing();
+        self.stop(now);
+        let time = Duration::from_secs_f64(time.max(0.0));
+        self.position = self.duration().map_or(time, |duration| time.min(duration));
+        if playing {
+            self.start(player, view_muted, now);
+        }
+    }
+
+    pub(crate) fn set_volume(&mut self, volume: f64, muted: bool, view_muted: bool) {
+        self.volume = volume.clamp(0.0, 1.0) as f32;
+        self.muted = muted;
+        self.apply_gain(view_muted);
+    }
+
+    pub(crate) fn apply_gain(&self, view_muted: bool) {
+        if let Some(voice) = &self.voice {
+            voice.set_volume(self.gain(view_muted));
+        }
+    }
+
+    /// Advance to `now`: `timeupdate` while playing, `ended` at the end.
+    pub(crate) fn tick(&mut self, now: Instant) -> Option<AudioUpdate> {
+        let (Some(duration), true) = (self.duration(), self.is_playing()) else {
+            return None;
+        };
+        if self.current_time(now) >= duration {
+            self.wants_play = false;
+            self.stop(now);
+            return Some(AudioUpdate::Ended);
+        }
+        if self
+            .last_timeupdate
+            .is_some_and(|at| now.saturating_duration_since(at) < TIMEUPDATE_INTERVAL)
+        {
+            return None;
+        }
+        self.last_timeupdate = Some(now);
+        Some(AudioUpdate::Time {
+            time: self.current_time(now).as_secs_f64(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn wav_fixture(sample_rate: u32, samples: usize) -> Vec<u8> {
+    let data_len = samples * 2;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data_len as u32).to_le_bytes());
+    wav.resize(wav.len() + data_len, 0);
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations_from_headers() {
+        let wav = AudioClip::decode(wav_fixture(8000, 4000)).unwrap();
+        assert_eq!(wav.duration(), Duration::from_millis(500));
+
+        // MPEG-1 layer III at 128 kbps, 44.1 kHz: 16000 bytes is one second
+        let mut mp3 = b"ID3\x03\x00\x00\x00\x00\x00\x0a".to_vec();
+        mp3.extend_from_slice(&[0; 10]);
+        mp3.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        mp3.resize(20 + 16000, 0);
+        let mp3 = AudioClip::decode(mp3).unwrap();
+        assert_eq!(mp3.duration(), Duration::from_secs(1));
+
+        let mut ogg = b"OggS\x00\x02".to_vec();
+        ogg.extend_from_slice(&[0; 22]);
+        ogg.extend_from_slice(b"\x01vorbis\x00\x00\x00\x00\x02");
+        ogg.extend_from_slice(&48000u32.to_le_bytes());
+        ogg.extend_from_slice(b"OggS\x00\x04");
+        ogg.extend_from_slice(&96000u64.to_le_bytes());
+        assert_eq!(
+            AudioClip::decode(ogg).unwrap().duration(),
+            Duration::from_secs(2)
+        );
+
+        assert!(matches!(
+            AudioClip::decode(b"<html>".to_vec()),
+            Err(AudioError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn test_byte_offsets_for_ranges() {
+        // Ten seconds of 8 kHz 16-bit mono: 16000 bytes a second
+        let wav = wav_fixture(8000, 80000);
+        let map = ByteMap::probe(&wav[..HEAD_BYTES as usize], wav.len() as u64).unwrap();
+        assert_eq!(map.duration(), Duration::from_secs(10));
+        assert_eq!(map.offset(Duration::from_secs(8)), 44 + 128000);
+        assert_eq!(map.offset(Duration::from_secs(60)), wav.len() as u64);
+
+        // A range from a seek plays as a WAV file of its own
+        let clip = map.clip(44 + 128000, &wav[44 + 128000..]);
+        assert_eq!(clip.start(), Duration::from_secs(8));
+        assert_eq!(clip.duration(), Duration::from_secs(10));
+        let alone = AudioClip::decode(clip.bytes().to_vec()).unwrap();
+        assert_eq!(alone.duration(), Duration::from_secs(2));
+
+        let mut mp3 = vec![0xff, 0xfb, 0x90, 0x00];
+        mp3.resize(HEAD_BYTES as usize, 0);
+        let map = ByteMap::probe(&mp3, 160000).unwrap();
+        assert_eq!(map.duration(), Duration::from_secs(10));
+        assert_eq!(map.offset(Duration::from_secs(1)), 16000);
+
+        // Ogg pages carry no offsets to seek by
+        assert!(ByteMap::probe(b"OggS\x00\x02", 160000).is_none());
+
+        assert_eq!(content_range("bytes 100-199/1000"), Some((100, 1000)));
+        assert_eq!(content_range("bytes */1000"), None);
+    }
+
+    #[test]
+    fn test_ranged_playback_fetches_from_the_seek() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let player = SilentAudioPlayer;
+        let wav = wav_fixture(8000, 80000);
+        let total = wav.len() as u64;
+        let body = |from: u64, to: u64| AudioBody {
+            bytes: wav[from as usize..to as usize].to_vec(),
+            range: Some((from, total)),
+        };
+        let mut playback = MediaPlayback::new(None);
+        playback.load("clip.wav");
+
+        let updates = playback
+            .received(body(0, HEAD_BYTES), &player, false, start)
+            .unwrap();
+        assert_eq!(updates, [AudioUpdate::Metadata { duration: 10.0 }]);
+        assert_eq!(playback.next_range(), None);
+
+        // Playing from the seek waits for the bytes from there on
+        playback.seek(8.0, &player, false, start);
+        assert_eq!(playback.play(&player, false, start), None);
+        assert_eq!(playback.next_range(), Some((44 + 128000, None)));
+        assert_eq!(playback.next_range(), None);
+        assert!(playback.is_loading());
+        let updates = playback
+            .received(body(44 + 128000, total), &player, false, start)
+            .unwrap();
+        assert_eq!(updates, [AudioUpdate::Playing]);
+        assert_eq!(playback.current_time(at(500)), Duration::from_millis(8500));
+        assert_eq!(playback.tick(at(2000)), Some(AudioUpdate::Ended));
+
+        // Seeking back fetches only up to the bytes already here
+        playback.seek(1.0, &player, false, at(2000));
+        assert_eq!(playback.play(&player, false, at(2000)), None);
+        assert_eq!(
+            playback.next_range(),
+            Some((44 + 16000, Some(44 + 128000 - 1)))
+        );
+        let updates = playback
+            .received(body(44 + 16000, 44 + 128000), &player, false, at(2000))
+            .unwrap();
+        assert_eq!(updates, [AudioUpdate::Playing]);
+    }
+
+    #[test]
+    fn test_playback_timeline() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let player = SilentAudioPlayer;
+        let mut playback = MediaPlayback::new(None);
+        playback.load("clip.wav");
+
+        // Play before the clip arrives starts it on load
+        assert_eq!(playback.play(&player, false, start), None);
+        let updates = playback.loaded(
+            AudioClip::decode(wav_fixture(8000, 8000)).unwrap(),
+            &player,
+            false,
+            start,
+        );
+        assert_eq!(
+            updates,
+            [
+                AudioUpdate::Metadata { duration: 1.0 },
+                AudioUpdate::Playing
+            ]
+        );
+
+        assert_eq!(playback.tick(at(100)), None);
+        assert_eq!(
+            playback.tick(at(300)),
+            Some(AudioUpdate::Time { time: 0.3 })
+        );
+        assert_eq!(playback.pause(at(400)), AudioUpdate::Paused { time: 0.4 });
+        assert_eq!(playback.tick(at(900)), None);
+
+        playback.seek(0.75, &player, false, at(900));
+        assert_eq!(playback.current_time(at(950)), Duration::from_millis(750));
+        assert_eq!(
+            playback.play(&player, false, at(1000)),
+            Some(AudioUpdate::Playing)
+        );
+        assert_eq!(playback.tick(at(1300)), Some(AudioUpdate::Ended));
+        assert!(!playback.is_playing());
+        assert_eq!(playback.current_time(at(2000)), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,364 @@
+//! Form introspection for password managers and autofill.
+//!
+//! [`crate::Engine::get_forms`] describes each `<form>` of a document and
+//! the text controls it owns, read fresh from the DOM on every call. The
+//! values of password fields are never described; hosts fill them with
+//! [`crate::Engine::fill_form_fields`] like any other field.
+
+use std::rc::Rc;
+
+use rustkit_bindings::GeometryMap;
+use rustkit_dom::{Document, FormDataEntry, FormDataValue, FormMethod, InputType, Node, NodeId};
+use rustkit_layout::Rect;
+use url::Url;
+
+use crate::text_input::TextInput;
+
+/// A `<form>` and the fields it owns.
+#[derive(Debug, Clone)]
+pub struct FormDescriptor {
+    pub node_id: NodeId,
+    /// The `action` resolved against the document URL.
+    pub action: Option<Url>,
+    pub method: FormMethod,
+    pub fields: Vec<FieldDescriptor>,
+}
+
+/// A text control that autofill can fill.
+#[derive(Debug, Clone)]
+pub struct FieldDescriptor {
+    pub node_id: NodeId,
+    /// The `name` attribute, or the `id` if it has none.
+    pub name: String,
+    /// Lowercase control type: an `<input>` type or `"textarea"`.
+    pub field_type: String,
+    /// Field name token of the `autocomplete` attribute, e.g. `"username"`.
+    pub autocomplete: Option<String>,
+    /// Text of the `<label for>` or the wrapping `<label>`.
+    pub label: Option<String>,
+    /// Current value; always `None` for password fields.
+    pub value: Option<String>,
+    pub is_password: bool,
+    /// Border box from the last layout, for anchoring an autofill popup.
+    pub rect: Option<Rect>,
+}
+
+fn is_tag(node: &Node, tag: &str) -> bool {
+    node.tag_name().is_some_and(|t| t.eq_ignore_ascii_case(tag))
+}
+
+fn input_type(node: &Node) -> InputType {
+    InputType::from_str(node.get_attribute("type").unwrap_or("text"))
+}
+
+/// Whether `node` is a text control autofill may describe and fill.
+pub(crate) fn is_fillable(node: &Node) -> bool {
+    TextInput::is_text_control(node) && node.get_attribute("disabled").is_none()
+}
+
+/// The form `control` belongs to: the one its `form` attribute names, or
+/// its nearest `<form>` ancestor.
+fn form_owner(document: &Document, control: &Rc<Node>) -> Option<Rc<Node>> {
+    if let Some(id) = control.get_attribute("form") {
+        return document
+            .get_element_by_id(id)
+            .filter(|form| is_tag(form, "form"));
+    }
+    std::iter::successors(control.parent(), |n| n.parent()).find(|n| is_tag(n, "form"))
+}
+
+/// Text of a label, with whitespace collapsed.
+fn label_text(label: &Node) -> Option<String> {
+    let text = label
+        .text_content()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn field_label(document: &Document, control: &Rc<Node>) -> Option<String> {
+    if let Some(id) = control.get_attribute("id").filter(|id| !id.is_empty()) {
+        let labelled = document
+            .get_elements_by_tag_name("label")
+            .into_iter()
+            .find(|label| label.get_attribute("for") == Some(id));
+        if let Some(text) = labelled.and_then(|label| label_text(&label)) {
+            return Some(text);
+        }
+    }
+    std::iter::successors(control.parent(), |n| n.parent())
+        .find(|n| is_tag(n, "label"))
+        .and_then(|label| label_text(&label))
+}
+
+/// Describe one fillable control.
+pub(crate) fn describe_field(
+    document: &Document,
+    control: &Rc<Node>,
+    text_input: &TextInput,
+    geometry: &GeometryMap,
+) -> FieldDescriptor {
+    let field_type = if is_tag(control, "textarea") {
+        "textarea".to_string()
+    } else {
+        control
+            .get_attribute("type")
+            .unwrap_or("text")
+            .to_ascii_lowercase()
+    };
+    let is_password = !is_tag(control, "textarea") && input_type(control) == InputType::Password;
+    FieldDescriptor {
+        node_id: control.id,
+        name: control
+            .get_attribute("name")
+            .or_else(|| control.get_attribute("id"))
+            .unwrap_or_default()
+            .to_string(),
+        field_type,
+        // Section and hint tokens come first; the field name is last
+        autocomplete: control
+            .get_attribute("autocomplete")
+            .and_then(|a| a.split_whitespace().last())
+            .map(str::to_ascii_lowercase),
+        label: field_label(document, control),
+        value: (!is_password).then(|| text_input.value_of(control)),
+        is_password,
+        rect: geometry
+            .get(&control.id)
+            .and_then(|g| g.fragments.first())
+            .map(|d| d.border_box()),
+    }
+}
+
+/// Describe the forms of `document` and their fillable fields.
+pub(crate) fn describe_forms(
+    document: &Document,
+    base: Option<&Url>,
+    text_input: &TextInput,
+    geometry: &GeometryMap,
+) -> Vec<FormDescriptor> {
+    let mut forms: Vec<FormDescriptor> = document
+        .get_elements_by_tag_name("form")
+        .into_iter()
+        .map(|form| FormDescriptor {
+            node_id: form.id,
+            action: Url::options()
+                .base_url(base)
+                .parse(form.get_attribute("action").unwrap_or(""))
+                .ok(),
+            method: FormMethod::from_str(form.get_attribute("method").unwrap_or("get")),
+            fields: Vec::new(),
+        })
+        .collect();
+
+    document.traverse(|node| {
+        if !is_fillable(node) {
+            return;
+        }
+        let Some(owner) = form_owner(document, node) else {
+            return;
+        };
+        if let Some(form) = forms.iter_mut().find(|f| f.node_id == owner.id) {
+            form.fields
+                .push(describe_field(document, node, text_input, geometry));
+        }
+    });
+    forms
+}
+
+/// The entries `form` would submit, in tree order.
+pub(crate) fn form_entries(
+    document: &Document,
+    form: NodeId,
+    text_input: &TextInput,
+) -> Vec<FormDataEntry> {
+    let mut entries = Vec::new();
+    document.traverse(|node| {
+        let Some(name) = node.get_attribute("name").filter(|n| !n.is_empty()) else {
+            return;
+        };
+        if node.get_attribute("disabled").is_some()
+            || form_owner(document, node).is_none_or(|owner| owner.id != form)
+        {
+            return;
+        }
+        let value = if TextInput::is_text_control(node) {
+            text_input.value_of(node)
+        } else if is_tag(node, "input") {
+            let kind = input_type(node);
+            if kind.is_button() || kind == InputType::File {
+                return;
+            }
+            if kind.is_checkable() && node.get_attribute("checked").is_none() {
+                return;
+            }
+            node.get_attribute("value")
+                .unwrap_or(if kind.is_checkable() { "on" } else { "" })
+                .to_string()
+        } else if is_tag(node, "select") {
+            let options = rustkit_dom::QuerySelector::select_within(node, "option");
+            let Some(option) = options
+                .iter()
+                .find(|o| o.get_attribute("selected").is_some())
+                .or(options.first())
+            else {
+                return;
+            };
+            option
+                .get_attribute("value")
+                .map(str::to_string)
+                .unwrap_or_else(|| option.text_content().trim().to_string())
+        } else {
+            return;
+        };
+        entries.push(FormDataEntry {
+            name: name.to_string(),
+            value: FormDataValue::String(value),
+        });
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_dom::{FormEnctype, FormState};
+
+    const LOGIN: &str = r#"<html><body>
+        <form action="/session" method="post">
+            <label for="user">User name</label>
+            <input id="user" name="username" autocomplete="section-login username" value="bob">
+            <label>Password <input type="password" name="password"
+                autocomplete="current-password" value="secret"></label>
+            <input type="hidden" name="csrf" value="t0k">
+            <button type="submit">Sign in</button>
+        </form>
+    </body></html>"#;
+
+    #[test]
+    fn test_login_form_fields() {
+        let document = Document::parse_html(LOGIN).unwrap();
+        let base = Url::parse("https://example.com/login").unwrap();
+        let forms = describe_forms(
+            &document,
+            Some(&base),
+            &TextInput::new(),
+            &GeometryMap::new(),
+        );
+
+        assert_eq!(forms.len(), 1);
+        let form = &forms[0];
+        assert_eq!(
+            form.action.as_ref().unwrap().as_str(),
+            "https://example.com/session"
+        );
+        assert_eq!(form.method, FormMethod::Post);
+        let fields: Vec<_> = form
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.autocomplete.as_deref(),
+                    f.label.as_deref(),
+                    f.value.as_deref(),
+                    f.is_password,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (
+                    "username",
+                    Some("username"),
+                    Some("User name"),
+                    Some("bob"),
+                    false
+                ),
+                (
+                    "password",
+                    Some("current-password"),
+                    Some("Password"),
+                    None,
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_form_entries_use_edited_values() {
+        let document = Document::parse_html(LOGIN).unwrap();
+        let form = document.get_elements_by_tag_name("form")[0].id;
+        let mut text_input = TextInput::new();
+        let user = document.get_element_by_id("user").unwrap();
+        assert!(text_input.fill(&user, "alice"));
+
+        let body = FormState::encode_form_data(
+            &form_entries(&document, form, &text_input),
+            FormEnctype::UrlEncoded,
+        );
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "username=alice&password=secret&csrf=t0k"
+        );
+    }
+
+    #[test]
+    fn test_script_form_data_matches_submission() {
+        use rustkit_bindings::{DomBindings, FetchCommand};
+        use rustkit_js::JsRuntime;
+        use std::cell::RefCell;
+
+        let document = Rc::new(
+            Document::parse_html(
+                r#"<html><body><form id="f" method="post" enctype="multipart/form-data">
+                    <input name="user" value="bob">
+                    <input type="checkbox" name="remember" checked>
+                    <input type="checkbox" name="skipped">
+                    <select name="plan"><option>free</option><option value="pro" selected>Pro</option></select>
+                    <textarea name="bio">Hi there</textarea>
+                    <input name="off" value="x" disabled>
+                </form></body></html>"#,
+            )
+            .unwrap(),
+        );
+        let form = document.get_element_by_id("f").unwrap().id;
+        let text_input = Rc::new(RefCell::new(TextInput::new()));
+        let user = document
+            .get_elements_by_tag_name("input")
+            .into_iter()
+            .find(|input| input.get_attribute("name") == Some("user"))
+            .unwrap();
+        assert!(text_input.borrow_mut().fill(&user, "alice & bob"));
+
+        let bindings = DomBindings::new(JsRuntime::new().unwrap()).unwrap();
+        bindings.set_document(document.clone()).unwrap();
+        bindings
+            .set_location(&Url::parse("https://example.com/").unwrap())
+            .unwrap();
+        let shared = text_input.clone();
+        bindings.set_form_entries_provider(move |document, form| {
+            form_entries(document, form, &shared.borrow())
+        });
+        bindings
+            .evaluate(
+                "fetch('/profile', { method: 'POST', body: new FormData(document.getElementById('f')) });",
+            )
+            .unwrap();
+        let Some(FetchCommand::Start(request)) = bindings.take_fetch_commands().pop() else {
+            panic!("no fetch started");
+        };
+
+        let submitted = FormState::encode_form_data(
+            &form_entries(&document, form, &text_input.borrow()),
+            FormEnctype::MultipartFormData,
+        );
+        assert_eq!(request.body, Some(submitted));
+        assert!(String::from_utf8(request.body.unwrap())
+            .unwrap()
+            .contains("alice & bob"));
+    }
+}
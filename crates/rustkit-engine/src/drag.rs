@@ -0,0 +1,144 @@
+//! Drops from outside a view, such as files onto an upload area.
+//!
+//! The view host reports a drag over a view as [`rustkit_core::DragEvent`]s.
+//! As the element under the pointer changes it gets `dragenter` and the one
+//! before `dragleave`, and each move fires `dragover` at it. Only a
+//! `dragover` a listener cancelled accepts a drop, with the `dropEffect` the
+//! listener left if the source allows it; the host is told the effect so
+//! the cursor shows it. An accepted drop fires `drop`. A drop the page
+//! doesn't take does nothing, rather than navigating to a dropped file, and
+//! its files are offered to the host with [`crate::EngineEvent::FileDropped`].
+
+use std::path::Path;
+
+use rustkit_bindings::{DataTransfer, DroppedFile};
+use rustkit_core::{DragData, DropEffect};
+use rustkit_dom::NodeId;
+
+/// A drag over a view.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DragSession {
+    /// Element under the pointer, which got `dragenter`.
+    pub(crate) target: Option<NodeId>,
+    /// What a drop would do, as the last `dragover` decided.
+    pub(crate) effect: DropEffect,
+}
+
+/// `effectAllowed` for the effects a source allows.
+fn effect_allowed(allowed: &[DropEffect]) -> &'static str {
+    let allows = |effect| allowed.contains(&effect);
+    match (
+        allows(DropEffect::Copy),
+        allows(DropEffect::Move),
+        allows(DropEffect::Link),
+    ) {
+        (true, true, true) => "all",
+        (true, true, false) => "copyMove",
+        (true, false, true) => "copyLink",
+        (false, true, true) => "linkMove",
+        (true, false, false) => "copy",
+        (false, true, false) => "move",
+        (false, false, true) => "link",
+        (false, false, false) => "none",
+    }
+}
+
+/// The `dropEffect` a `dragenter` or `dragover` starts with: copy, or
+/// failing that link, then move.
+pub(crate) fn default_effect(allowed: &[DropEffect]) -> DropEffect {
+    [DropEffect::Copy, DropEffect::Link, DropEffect::Move]
+        .into_iter()
+        .find(|effect| allowed.contains(effect))
+        .unwrap_or_default()
+}
+
+/// What a drop does once a `dragover` listener accepted it leaving
+/// `dropEffect` as `chosen`: that effect if the source allows it.
+pub(crate) fn accepted_effect(chosen: &str, allowed: &[DropEffect]) -> DropEffect {
+    DropEffect::parse(chosen)
+        .filter(|effect| allowed.contains(effect))
+        .unwrap_or_default()
+}
+
+/// What the page sees of `data`. File contents are only read for the drop.
+pub(crate) fn data_transfer(
+    data: &DragData,
+    allowed: &[DropEffect],
+    drop_effect: DropEffect,
+    read_files: bool,
+) -> DataTransfer {
+    let mut transfer = DataTransfer::new();
+    transfer.drop_effect = drop_effect.as_str().to_string();
+    transfer.effect_allowed = effect_allowed(allowed).to_string();
+    if let Some(text) = &data.text {
+        transfer.set_data("text/plain", text);
+    }
+    if !data.urls.is_empty() {
+        transfer.set_data("text/uri-list", &data.urls.join("\r\n"));
+    }
+    transfer.files = data
+        .files
+        .iter()
+        .filter_map(|path| dropped_file(path, read_files))
+        .collect();
+    transfer
+}
+
+/// The file at `path`, or `None` for a directory or a file that can't be
+/// read.
+fn dropped_file(path: &Path, read: bool) -> Option<DroppedFile> {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let bytes = match read {
+        true => std::fs::read(path).ok()?,
+        false => Vec::new(),
+    };
+    let last_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as u64);
+    Some(DroppedFile {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: metadata.len(),
+        mime_type: mime_guess::from_path(path)
+            .first_raw()
+            .unwrap_or("")
+            .to_string(),
+        path: Some(path.to_path_buf()),
+        last_modified,
+        bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effects_follow_what_the_source_allows() {
+        let all = [DropEffect::Copy, DropEffect::Move, DropEffect::Link];
+        assert_eq!(effect_allowed(&all), "all");
+        assert_eq!(
+            effect_allowed(&[DropEffect::Move, DropEffect::Link]),
+            "linkMove"
+        );
+        assert_eq!(effect_allowed(&[]), "none");
+
+        assert_eq!(default_effect(&all), DropEffect::Copy);
+        assert_eq!(
+            default_effect(&[DropEffect::Move, DropEffect::Link]),
+            DropEffect::Link
+        );
+        assert_eq!(default_effect(&[]), DropEffect::None);
+
+        assert_eq!(accepted_effect("move", &all), DropEffect::Move);
+        assert_eq!(
+            accepted_effect("move", &[DropEffect::Copy]),
+            DropEffect::None
+        );
+        assert_eq!(accepted_effect("bogus", &all), DropEffect::None);
+    }
+}
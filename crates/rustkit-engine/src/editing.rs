@@ -0,0 +1,394 @@
+//! Editing in `contenteditable` hosts.
+//!
+//! The focused editing host keeps a caret and selection as DOM positions.
+//! Typed characters, Backspace, Delete, Enter and paste become
+//! [`EditAction`]s: each fires a cancelable `beforeinput` at the host,
+//! changes the document with [`Document::apply_edit`] unless it was
+//! cancelled, then fires `input`. The arrow, Home and End keys move the
+//! caret, and with Shift extend the selection, which is painted over the
+//! selected text.
+
+use std::rc::Rc;
+
+use rustkit_core::{KeyCode, KeyEvent, KeyEventType};
+use rustkit_css::{Color, Length};
+use rustkit_dom::{
+    editing_host, end_of, CaretMove, Document, DomPosition, EditAction, EditSelection,
+    EnterBehavior, Node, NodeId, NodeType,
+};
+use rustkit_layout::{
+    calculate_caret_position, calculate_selection_rects, BoxType, DisplayCommand, LayoutBox, Rect,
+};
+
+/// Background of selected text.
+const SELECTION_COLOR: Color = Color {
+    r: 51,
+    g: 144,
+    b: 255,
+    a: 0.5,
+};
+
+/// What a key press does in an editing host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EditKey {
+    Edit(EditAction),
+    /// Move the caret; `true` extends the selection.
+    Move(CaretMove, bool),
+    Paste,
+}
+
+impl EditKey {
+    /// The editing command of a key press, if it has one. Backspace and
+    /// Enter arrive as characters instead.
+    pub(crate) fn of(event: &KeyEvent) -> Option<Self> {
+        if event.event_type != KeyEventType::KeyDown {
+            return None;
+        }
+        let modifiers = event.modifiers;
+        let shortcut = (modifiers.ctrl || modifiers.meta) && !modifiers.alt;
+        Some(match event.key_code {
+            KeyCode::Delete => Self::Edit(EditAction::DeleteContentForward),
+            KeyCode::ArrowLeft => Self::Move(CaretMove::Backward, modifiers.shift),
+            KeyCode::ArrowRight => Self::Move(CaretMove::Forward, modifiers.shift),
+            KeyCode::Home => Self::Move(CaretMove::HostStart, modifiers.shift),
+            KeyCode::End => Self::Move(CaretMove::HostEnd, modifiers.shift),
+            KeyCode::KeyB if shortcut => Self::Edit(EditAction::FormatBold),
+            KeyCode::KeyI if shortcut => Self::Edit(EditAction::FormatItalic),
+            KeyCode::KeyV if shortcut => Self::Paste,
+            _ => return None,
+        })
+    }
+}
+
+/// The caret and selection of one view's editing host.
+#[derive(Debug, Default)]
+pub struct Editing {
+    host: Option<NodeId>,
+    selection: Option<EditSelection>,
+    enter: EnterBehavior,
+}
+
+impl Editing {
+    pub fn new(enter: EnterBehavior) -> Self {
+        Self {
+            enter,
+            ..Self::default()
+        }
+    }
+
+    /// The focused editing host and its selection.
+    pub fn selection(&self) -> Option<(NodeId, EditSelection)> {
+        self.host.zip(self.selection)
+    }
+
+    /// Select within the editing host `selection` is in. Returns false if
+    /// its ends aren't both in the same host.
+    pub fn set_selection(&mut self, document: &Document, selection: EditSelection) -> bool {
+        let host_of = |position: DomPosition| {
+            document
+                .get_node(position.node)
+                .and_then(|node| editing_host(&node))
+        };
+        match (host_of(selection.anchor), host_of(selection.focus)) {
+            (Some(a), Some(b)) if Rc::ptr_eq(&a, &b) => {
+                self.host = Some(a.id);
+                self.selection = Some(selection);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Start editing `host`, with the caret at its end unless the selection
+    /// is already in it.
+    pub(crate) fn focus(&mut self, host: &Rc<Node>) {
+        if self.host != Some(host.id) || self.selection.is_none() {
+            self.host = Some(host.id);
+            self.selection = Some(EditSelection::collapsed(end_of(host)));
+        }
+    }
+
+    pub(crate) fn blur(&mut self) {
+        self.host = None;
+        self.selection = None;
+    }
+
+    /// Forget the selection of a previous document.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.enter);
+    }
+
+    /// The edit a typed character asks for.
+    pub(crate) fn action_for_char(&self, ch: char) -> Option<EditAction> {
+        match ch {
+            '\u{8}' => Some(EditAction::DeleteContentBackward),
+            '\r' | '\n' => Some(match self.enter {
+                EnterBehavior::LineBreak => EditAction::InsertLineBreak,
+                EnterBehavior::SplitBlock => EditAction::InsertParagraph,
+            }),
+            ch if ch.is_control() => None,
+            ch => Some(EditAction::InsertText(ch.to_string())),
+        }
+    }
+
+    fn host(&self, document: &Document) -> Option<Rc<Node>> {
+        self.host
+            .and_then(|id| document.get_node(id))
+            .filter(|host| document.is_in_document(host))
+    }
+
+    /// Apply `action` at the selection. Returns whether the document
+    /// changed.
+    pub(crate) fn apply(&mut self, document: &Document, action: &EditAction) -> bool {
+        let (Some(host), Some(selection)) = (self.host(document), self.selection.as_mut()) else {
+            return false;
+        };
+        document.apply_edit(&host, selection, action)
+    }
+
+    /// Move the caret. Returns whether the selection changed.
+    pub(crate) fn move_caret(
+        &mut self,
+        document: &Document,
+        movement: CaretMove,
+        extend: bool,
+    ) -> bool {
+        let (Some(host), Some(selection)) = (self.host(document), self.selection.as_mut()) else {
+            return false;
+        };
+        document.move_caret(&host, selection, movement, extend)
+    }
+
+    /// Run a `document.execCommand` command. Only `insertText`, `delete`,
+    /// `bold` and `italic` are supported; the others return false.
+    pub(crate) fn exec_command(&mut self, document: &Document, command: &str, value: &str) -> bool {
+        let action = match command.to_ascii_lowercase().as_str() {
+            "inserttext" => EditAction::InsertText(value.to_string()),
+            "delete" => EditAction::DeleteContentBackward,
+            "bold" => EditAction::FormatBold,
+            "italic" => EditAction::FormatItalic,
+            _ => return false,
+        };
+        self.apply(document, &action)
+    }
+
+    /// Paint the selection, or the caret when it is collapsed and the host
+    /// has focus.
+    pub(crate) fn paint(
+        &self,
+        document: &Document,
+        layout: &LayoutBox,
+        focused: bool,
+    ) -> Vec<DisplayCommand> {
+        let (Some(host), Some(selection)) = (self.host(document), self.selection) else {
+            return Vec::new();
+        };
+        if !selection.is_collapsed() {
+            return document
+                .selected_text(&host, &selection)
+                .into_iter()
+                .flat_map(|(node, from, to)| selection_rects(layout, node, from, to))
+                .map(|rect| DisplayCommand::SolidColor(SELECTION_COLOR, rect))
+                .collect();
+        }
+        if !focused {
+            return Vec::new();
+        }
+        caret_rect(document, layout, selection.focus)
+            .map(|rect| DisplayCommand::SolidColor(Color::from_rgb(0, 0, 0), rect))
+            .into_iter()
+            .collect()
+    }
+}
+
+fn boxes_of<'a>(layout: &'a LayoutBox, node: NodeId, found: &mut Vec<&'a LayoutBox>) {
+    if layout.node_id == Some(node) {
+        found.push(layout);
+    }
+    for child in &layout.children {
+        boxes_of(child, node, found);
+    }
+}
+
+fn font_size(layout_box: &LayoutBox) -> f32 {
+    match layout_box.style.font_size {
+        Length::Px(px) => px,
+        _ => 16.0,
+    }
+}
+
+/// The lines of a text node's boxes: their text and where it is drawn.
+fn text_lines(layout: &LayoutBox, node: NodeId) -> Vec<(String, Rect, f32)> {
+    let mut boxes = Vec::new();
+    boxes_of(layout, node, &mut boxes);
+    boxes
+        .into_iter()
+        .flat_map(|b| {
+            let size = font_size(b);
+            let lines: Vec<_> = match &b.box_type {
+                BoxType::Text(_) if !b.text_lines.is_empty() => b
+                    .text_lines
+                    .iter()
+                    .map(|line| (line.text.clone(), line.rect, size))
+                    .collect(),
+                BoxType::Text(text) => vec![(text.clone(), b.dimensions.content, size)],
+                _ => Vec::new(),
+            };
+            lines
+        })
+        .collect()
+}
+
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+/// Where the caret at `position` is drawn. Offsets count chars as laid
+/// out, after whitespace collapsing, so they are approximate.
+fn caret_rect(document: &Document, layout: &LayoutBox, position: DomPosition) -> Option<Rect> {
+    let node = document.get_node(position.node)?;
+    if let NodeType::Text(_) = node.node_type {
+        let lines = text_lines(layout, node.id);
+        let mut remaining = position.offset;
+        for (i, (text, rect, size)) in lines.iter().enumerate() {
+            let len = text.chars().count();
+            if remaining <= len || i + 1 == lines.len() {
+                let caret = calculate_caret_position(
+                    text,
+                    byte_offset(text, remaining.min(len)),
+                    rect,
+                    *size,
+                );
+                return Some(Rect::new(caret.x, caret.y, caret.width, caret.height));
+            }
+            remaining -= len;
+        }
+        return None;
+    }
+    // Between children: at the start of the element's content
+    let mut boxes = Vec::new();
+    boxes_of(layout, node.id, &mut boxes);
+    let element = boxes.first()?;
+    let content = element.dimensions.content;
+    Some(Rect::new(
+        content.x,
+        content.y,
+        1.0,
+        font_size(element) * 1.2,
+    ))
+}
+
+/// The rects covering chars `from..to` of a text node.
+fn selection_rects(layout: &LayoutBox, node: NodeId, from: usize, to: usize) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    let mut start = 0;
+    for (text, rect, size) in text_lines(layout, node) {
+        let len = text.chars().count();
+        let (a, b) = (
+            from.max(start) - start,
+            to.min(start + len).saturating_sub(start),
+        );
+        if a < b {
+            rects.extend(calculate_selection_rects(
+                &text,
+                byte_offset(&text, a),
+                byte_offset(&text, b),
+                &rect,
+                size,
+            ));
+        }
+        start += len;
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_core::Modifiers;
+    use rustkit_css::MediaEnvironment;
+
+    fn editable(html: &str, enter: EnterBehavior) -> (Document, Editing) {
+        let document = Document::parse_html(html).unwrap();
+        let mut editing = Editing::new(enter);
+        editing.focus(&document.get_element_by_id("host").unwrap());
+        (document, editing)
+    }
+
+    #[test]
+    fn test_typed_keys_edit_the_host() {
+        let html = r#"<html><body><div id="host" contenteditable>ab</div></body></html>"#;
+        let (document, mut editing) = editable(html, EnterBehavior::LineBreak);
+        let host = document.get_element_by_id("host").unwrap();
+        for ch in "c\rd\u{8}e".chars() {
+            let action = editing.action_for_char(ch).unwrap();
+            assert!(editing.apply(&document, &action));
+        }
+        assert_eq!(editing.action_for_char('\t'), None);
+        let children = host.children();
+        // The line break keeps its placeholder `<br>` at the end of the host
+        assert_eq!(children.len(), 4);
+        assert_eq!(children[0].text_content(), "abc");
+        assert_eq!(children[1].tag_name(), Some("br"));
+        assert_eq!(children[2].text_content(), "e");
+        assert_eq!(children[3].tag_name(), Some("br"));
+
+        let html = r#"<html><body><div id="host" contenteditable><p>ab</p></div></body></html>"#;
+        let (document, mut editing) = editable(html, EnterBehavior::SplitBlock);
+        let enter = editing.action_for_char('\r').unwrap();
+        assert_eq!(enter, EditAction::InsertParagraph);
+        let left = KeyEvent::new(KeyEventType::KeyDown, KeyCode::ArrowLeft, Modifiers::new());
+        let Some(EditKey::Move(movement, extend)) = EditKey::of(&left) else {
+            panic!("arrow keys move the caret");
+        };
+        editing.move_caret(&document, movement, extend);
+        editing.apply(&document, &enter);
+        let paragraphs = document.get_elements_by_tag_name("p");
+        let mut texts: Vec<_> = paragraphs.iter().map(|p| p.text_content()).collect();
+        texts.sort();
+        assert_eq!(texts, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_exec_command_supports_only_basic_commands() {
+        let html = r#"<html><body><div id="host" contenteditable>Hello world</div></body></html>"#;
+        let (document, mut editing) = editable(html, EnterBehavior::LineBreak);
+        let text = document
+            .get_element_by_id("host")
+            .unwrap()
+            .first_child()
+            .unwrap();
+        assert!(editing.set_selection(
+            &document,
+            EditSelection::new(DomPosition::new(text.id, 0), DomPosition::new(text.id, 5)),
+        ));
+        assert!(editing.exec_command(&document, "bold", ""));
+        let bold = document.get_elements_by_tag_name("b");
+        assert_eq!(bold.len(), 1);
+        assert_eq!(bold[0].text_content(), "Hello");
+        assert!(!editing.exec_command(&document, "underline", ""));
+        assert!(!editing.exec_command(&document, "createLink", "x"));
+
+        // The selection is painted over the laid-out text
+        let media = MediaEnvironment::default();
+        let mut root = crate::Engine::build_layout_from_document(&document, &media);
+        crate::Engine::lay_out(&mut root, &media);
+        let painted = editing.paint(&document, &root, true);
+        assert!(matches!(
+            painted.as_slice(),
+            [DisplayCommand::SolidColor(color, rect)] if *color == SELECTION_COLOR && rect.width > 0.0
+        ));
+
+        assert!(editing.exec_command(&document, "insertText", "Bye"));
+        let host = document.get_element_by_id("host").unwrap();
+        assert_eq!(host.text_content(), "Bye world");
+        assert!(editing.exec_command(&document, "delete", ""));
+        assert_eq!(host.text_content(), "By world");
+        let mut root = crate::Engine::build_layout_from_document(&document, &media);
+        crate::Engine::lay_out(&mut root, &media);
+        let painted = editing.paint(&document, &root, true);
+        assert_eq!(painted.len(), 1, "a caret");
+    }
+}
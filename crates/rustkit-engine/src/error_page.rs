@@ -0,0 +1,249 @@
+//! Error documents shown in place of a page that failed to load.
+//!
+//! The page is ordinary HTML committed at the URL that failed, so reloading
+//! the view retries the navigation. Its root element carries the error code
+//! in `data-error-code`, and the seconds until the engine retries by itself
+//! in `data-retry-in`, for hosts and user stylesheets.
+
+use rustkit_net::{HttpError, NetError};
+use std::time::Duration;
+use url::Url;
+
+/// Why a navigation failed, as shown on its error page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NavigationErrorKind {
+    NameNotResolved,
+    ConnectionRefused,
+    /// No route to the host or its network.
+    Unreachable,
+    TimedOut,
+    Certificate,
+    Offline,
+    Blocked,
+    /// A 4xx or 5xx response with an empty body.
+    HttpStatus(u16),
+    Other(String),
+}
+
+impl NavigationErrorKind {
+    /// Classify a fetch failure. Returns `None` for cancelled requests,
+    /// which were superseded rather than failed.
+    pub fn from_net_error(error: &NetError) -> Option<Self> {
+        Some(match error {
+            NetError::Cancelled => return None,
+            NetError::Timeout(_) => Self::TimedOut,
+            NetError::Offline => Self::Offline,
+            NetError::Blocked => Self::Blocked,
+            NetError::TlsError { .. } => Self::Certificate,
+            NetError::HttpError(HttpError::Timeout) => Self::TimedOut,
+            NetError::HttpError(HttpError::TlsError(_)) => Self::Certificate,
+            NetError::HttpError(HttpError::ConnectionFailed(message)) => {
+                Self::from_connect_error(message)
+            }
+            other => Self::Other(other.to_string()),
+        })
+    }
+
+    /// Tell resolver failures from refused connections by the OS message.
+    fn from_connect_error(message: &str) -> Self {
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("refused") {
+            Self::ConnectionRefused
+        } else if lower.contains("unreachable") || lower.contains("no route to host") {
+            Self::Unreachable
+        } else if lower.contains("lookup")
+            || lower.contains("resolve")
+            || lower.contains("no such host")
+            || lower.contains("name or service not known")
+        {
+            Self::NameNotResolved
+        } else if lower.contains("timed out") {
+            Self::TimedOut
+        } else {
+            Self::Other(message.to_string())
+        }
+    }
+
+    /// Whether the failure is one a working network connection would
+    /// likely fix, so the navigation is worth retrying.
+    pub fn is_connectivity_error(&self) -> bool {
+        matches!(
+            self,
+            Self::NameNotResolved | Self::ConnectionRefused | Self::Unreachable | Self::Offline
+        )
+    }
+
+    /// Machine-readable code, e.g. `ERR_CONNECTION_REFUSED` or `HTTP_404`.
+    pub fn code(&self) -> String {
+        match self {
+            Self::NameNotResolved => "ERR_NAME_NOT_RESOLVED".into(),
+            Self::ConnectionRefused => "ERR_CONNECTION_REFUSED".into(),
+            Self::Unreachable => "ERR_ADDRESS_UNREACHABLE".into(),
+            Self::TimedOut => "ERR_TIMED_OUT".into(),
+            Self::Certificate => "ERR_CERT_INVALID".into(),
+            Self::Offline => "ERR_INTERNET_DISCONNECTED".into(),
+            Self::Blocked => "ERR_BLOCKED_BY_CLIENT".into(),
+            Self::HttpStatus(status) => format!("HTTP_{}", status),
+            Self::Other(_) => "ERR_FAILED".into(),
+        }
+    }
+
+    /// Page title.
+    pub fn title(&self) -> String {
+        match self {
+            Self::NameNotResolved => "Server not found".into(),
+            Self::ConnectionRefused | Self::Unreachable => "Unable to connect".into(),
+            Self::TimedOut => "The connection timed out".into(),
+            Self::Certificate => "Your connection is not private".into(),
+            Self::Offline => "You are offline".into(),
+            Self::Blocked => "This page was blocked".into(),
+            Self::HttpStatus(status) if *status >= 500 => format!("Server error ({})", status),
+            Self::HttpStatus(status) => format!("Page unavailable ({})", status),
+            Self::Other(_) => "This page could not be loaded".into(),
+        }
+    }
+
+    /// Explanation for the user.
+    pub fn description(&self) -> String {
+        match self {
+            Self::NameNotResolved => {
+                "The server's address could not be found. Check the address for typos.".into()
+            }
+            Self::ConnectionRefused => {
+                "The server refused the connection. It may be down or not accepting connections."
+                    .into()
+            }
+            Self::Unreachable => "The server could not be reached. The network may be down.".into(),
+            Self::TimedOut => "The server took too long to respond.".into(),
+            Self::Certificate => {
+                "The server's certificate could not be verified, so the connection was stopped."
+                    .into()
+            }
+            Self::Offline => "The network is offline. Check your connection.".into(),
+            Self::Blocked => "The request was blocked before it was sent.".into(),
+            Self::HttpStatus(status) => {
+                format!("The server answered with status {} and no content.", status)
+            }
+            Self::Other(message) => format!("The request failed: {}", message),
+        }
+    }
+}
+
+const DEFAULT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html data-error-code="{{error_code}}" data-retry-in="{{retry_in}}">
+<head><title>{{title}}</title>
+<style>
+body { font-family: sans-serif; margin: 64px; color: #333; background: #fafafa }
+h1 { font-size: 24px; color: #222 }
+.url { color: #666; word-break: break-all }
+.code { color: #888; font-size: 12px }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<p>{{description}}</p>
+<p class="url">{{url}}</p>
+<p class="code">{{error_code}}</p>
+<p>Reload the page to try again.</p>
+</body>
+</html>
+"#;
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The error document for `kind` at `url`, retried after `retry_in` if
+/// that is set. `template` replaces the built-in page; its `{{url}}`,
+/// `{{error_code}}`, `{{description}}` and `{{title}}` placeholders are
+/// filled in HTML-escaped, and `{{retry_in}}` with whole seconds, or
+/// nothing when no retry is coming.
+pub fn render(
+    template: Option<&str>,
+    url: &Url,
+    kind: &NavigationErrorKind,
+    retry_in: Option<Duration>,
+) -> String {
+    let retry_in = retry_in.map_or(String::new(), |delay| {
+        delay.as_secs_f64().ceil().to_string()
+    });
+    template
+        .unwrap_or(DEFAULT_TEMPLATE)
+        .replace("{{retry_in}}", &retry_in)
+        .replace("{{url}}", &escape_html(url.as_str()))
+        .replace("{{error_code}}", &escape_html(&kind.code()))
+        .replace("{{description}}", &escape_html(&kind.description()))
+        .replace("{{title}}", &escape_html(&kind.title()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_connect_errors() {
+        let connect = |message: &str| {
+            NavigationErrorKind::from_net_error(&NetError::HttpError(HttpError::ConnectionFailed(
+                message.into(),
+            )))
+        };
+        assert_eq!(
+            connect("Connection refused (os error 111)"),
+            Some(NavigationErrorKind::ConnectionRefused)
+        );
+        assert_eq!(
+            connect("failed to lookup address information: Name or service not known"),
+            Some(NavigationErrorKind::NameNotResolved)
+        );
+        assert_eq!(
+            connect("Network is unreachable (os error 101)"),
+            Some(NavigationErrorKind::Unreachable)
+        );
+        assert!(NavigationErrorKind::Unreachable.is_connectivity_error());
+        assert!(!NavigationErrorKind::TimedOut.is_connectivity_error());
+        assert_eq!(
+            NavigationErrorKind::from_net_error(&NetError::Timeout(
+                std::time::Duration::from_secs(30)
+            )),
+            Some(NavigationErrorKind::TimedOut)
+        );
+        assert_eq!(
+            NavigationErrorKind::from_net_error(&NetError::Cancelled),
+            None
+        );
+        assert_eq!(NavigationErrorKind::HttpStatus(503).code(), "HTTP_503");
+    }
+
+    #[test]
+    fn test_render_escapes_placeholders() {
+        let url = Url::parse("http://example.test/?q=<b>").unwrap();
+        let kind = NavigationErrorKind::ConnectionRefused;
+        let html = render(None, &url, &kind, None);
+        assert!(html.contains(r#"data-error-code="ERR_CONNECTION_REFUSED""#));
+        assert!(html.contains(r#"data-retry-in="""#));
+        assert!(html.contains("Unable to connect"));
+        assert!(html.contains("?q=%3Cb%3E"));
+
+        let html = render(
+            Some("<p data-code='{{error_code}}' data-retry='{{retry_in}}'>{{description}} {{url}}</p>"),
+            &Url::parse("http://a.test/x'y").unwrap(),
+            &kind,
+            Some(Duration::from_millis(1500)),
+        );
+        assert!(html.starts_with(
+            "<p data-code='ERR_CONNECTION_REFUSED' data-retry='2'>The server refused"
+        ));
+        assert!(html.ends_with("http://a.test/x&#39;y</p>"));
+    }
+}
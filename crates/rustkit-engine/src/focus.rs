@@ -0,0 +1,139 @@
+//! Sequential focus navigation and the keyboard focus ring.
+//!
+//! Tab and Shift+Tab move focus through the document's focusable elements
+//! in tab order. Focus that came from the keyboard shows a UA focus ring
+//! (`:focus-visible`): an outline applied to the focused element's boxes
+//! when the author gave it none.
+
+use std::rc::Rc;
+
+use rustkit_bindings::FocusableElement;
+use rustkit_css::{Color, ColorScheme, Length, OutlineStyle};
+use rustkit_dom::{Document, Node, NodeId};
+use rustkit_layout::LayoutBox;
+
+/// Width of the UA focus ring, in pixels.
+pub(crate) const FOCUS_RING_WIDTH: f32 = 2.0;
+/// Gap between the focused element's border box and its focus ring.
+pub(crate) const FOCUS_RING_OFFSET: f32 = 1.0;
+
+/// Focus ring color for the color scheme.
+fn focus_ring_color(scheme: ColorScheme) -> Color {
+    match scheme {
+        ColorScheme::Light => Color::new(0, 95, 204, 1.0),
+        ColorScheme::Dark => Color::new(153, 200, 255, 1.0),
+    }
+}
+
+/// Whether a click on `node` focuses it.
+pub(crate) fn is_focusable(node: &Rc<Node>) -> bool {
+    let Some(tag) = node.tag_name().map(str::to_ascii_lowercase) else {
+        return false;
+    };
+    if node.get_attribute("tabindex").is_some() || rustkit_dom::is_editing_host(node) {
+        return true;
+    }
+    match tag.as_str() {
+        "a" | "area" => node.get_attribute("href").is_some(),
+        "input" => !node
+            .get_attribute("type")
+            .is_some_and(|t| t.eq_ignore_ascii_case("hidden")),
+        "button" | "select" | "textarea" | "summary" => true,
+        _ => false,
+    }
+}
+
+/// The focusable element `node` is or is inside, if any.
+pub(crate) fn focusable_ancestor(node: Rc<Node>) -> Option<Rc<Node>> {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        if is_focusable(&node) {
+            return Some(node);
+        }
+        current = node.parent();
+    }
+    None
+}
+
+/// Focusable elements of `document` in tree order, for
+/// [`rustkit_bindings::FocusManager::update_tab_order`].
+pub(crate) fn focusable_elements(document: &Document) -> Vec<FocusableElement> {
+    let mut elements = Vec::new();
+    document.traverse(|node| {
+        if !is_focusable(node) {
+            return;
+        }
+        let tab_index = node
+            .get_attribute("tabindex")
+            .and_then(|t| t.trim().parse().ok())
+            .unwrap_or(0);
+        let is_inert = std::iter::successors(Some(node.clone()), |n| n.parent())
+            .any(|n| n.get_attribute("inert").is_some());
+        elements.push(FocusableElement {
+            node_id: node.id,
+            tab_index,
+            is_disabled: node.get_attribute("disabled").is_some(),
+            is_inert,
+        });
+    });
+    elements
+}
+
+/// Give the boxes of `focused` the UA focus ring, unless the author styled
+/// their outline.
+pub(crate) fn apply_focus_ring(layout: &mut LayoutBox, focused: NodeId, scheme: ColorScheme) {
+    if layout.node_id == Some(focused)
+        && layout.pseudo.is_none()
+        && layout.style.outline_style == OutlineStyle::None
+    {
+        let style = &mut layout.style;
+        style.outline_style = OutlineStyle::Auto;
+        style.outline_width = Length::Px(FOCUS_RING_WIDTH);
+        style.outline_color = Some(focus_ring_color(scheme));
+        style.outline_offset = Length::Px(FOCUS_RING_OFFSET);
+    }
+    for child in &mut layout.children {
+        apply_focus_ring(child, focused, scheme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tab_order_skips_disabled_and_inert() {
+        let document = Document::parse_html(
+            r#"<html><body>
+                <a href="/a">a</a><a>no href</a>
+                <button disabled>b</button>
+                <div inert><input></div>
+                <span tabindex="2">s</span><textarea></textarea>
+            </body></html>"#,
+        )
+        .unwrap();
+        let mut focus = rustkit_bindings::FocusManager::new();
+        focus.update_tab_order(focusable_elements(&document));
+
+        let mut order = Vec::new();
+        while let Some(next) = focus.move_next() {
+            if order.contains(&next) {
+                break;
+            }
+            focus.set_focus(Some(next), true);
+            order.push(next);
+        }
+        let tags: Vec<String> = order
+            .iter()
+            .map(|id| {
+                document
+                    .get_node(*id)
+                    .unwrap()
+                    .tag_name()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(tags, ["span", "a", "textarea"]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Forced colors mode.
+//!
+//! While a high contrast theme is on, author colors give way to the
+//! system palette after the cascade: backgrounds take the window color
+//! (keeping their alpha, so transparent boxes stay transparent), text,
+//! borders and outlines the window text color, and links the hyperlink
+//! color. Border colors are made opaque so every edge the author drew
+//! stays visible. `forced-color-adjust: none` opts an element and its
+//! descendants out. Styles here carry no `box-shadow` or
+//! `background-image`, so there is nothing else to drop.
+
+use rustkit_css::{Color, ForcedColorAdjust};
+use rustkit_dom::Document;
+use rustkit_layout::LayoutBox;
+use rustkit_viewhost::{settings::Rgb, SystemColors};
+
+fn color(rgb: Rgb, alpha: f32) -> Color {
+    Color::new(rgb[0], rgb[1], rgb[2], alpha)
+}
+
+/// Replace the colors of `root` and its descendants with `colors`.
+pub(crate) fn apply(root: &mut LayoutBox, document: &Document, colors: &SystemColors) {
+    apply_box(root, document, colors, color(colors.window_text, 1.0));
+}
+
+fn apply_box(layout_box: &mut LayoutBox, document: &Document, colors: &SystemColors, text: Color) {
+    if layout_box.style.forced_color_adjust == ForcedColorAdjust::None {
+        return;
+    }
+    let is_link = layout_box
+        .node_id
+        .and_then(|id| document.get_node(id))
+        .is_some_and(|node| {
+            node.tag_name()
+                .is_some_and(|tag| tag.eq_ignore_ascii_case("a"))
+                && node.get_attribute("href").is_some()
+        });
+    let text = if is_link {
+        color(colors.hot_light, 1.0)
+    } else {
+        text
+    };
+
+    let style = &mut layout_box.style;
+    style.color = text;
+    style.background_color = color(colors.window, style.background_color.a);
+    style.border_top_color = text;
+    style.border_right_color = text;
+    style.border_bottom_color = text;
+    style.border_left_color = text;
+    style.outline_color = None;
+    style.text_decoration_color = None;
+    style.scrollbar_color = None;
+
+    for child in &mut layout_box.children {
+        apply_box(child, document, colors, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use rustkit_css::MediaEnvironment;
+    use rustkit_layout::{DisplayCommand, DisplayList};
+
+    fn painted(html: &str) -> Vec<DisplayCommand> {
+        let document = Document::parse_html(html).unwrap();
+        let mut root =
+            Engine::build_layout_from_document(&document, &MediaEnvironment::screen(800.0, 600.0));
+        let colors = SystemColors {
+            window: [0, 0, 0],
+            window_text: [255, 255, 0],
+            hot_light: [0, 255, 255],
+            ..SystemColors::default()
+        };
+        apply(&mut root, &document, &colors);
+        Engine::lay_out(&mut root, &MediaEnvironment::screen(800.0, 600.0));
+        DisplayList::build(&root).commands
+    }
+
+    fn text_color(commands: &[DisplayCommand], text: &str) -> Color {
+        commands
+            .iter()
+            .find_map(|c| match c {
+                DisplayCommand::Text { text: t, color, .. } if t == text => Some(*color),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_system_palette_replaces_author_colors() {
+        let commands = painted(
+            r#"<html><body><div style="background-color: red; color: green; width: 10px;
+                height: 10px"></div><p>Text <a href="/next">link</a></p></body></html>"#,
+        );
+        assert!(!commands.iter().any(|c| {
+            matches!(c, DisplayCommand::SolidColor(color, _) if color.r == 255 && color.g == 0)
+        }));
+        assert!(commands.iter().any(|c| {
+            matches!(c, DisplayCommand::SolidColor(color, _) if *color == Color::new(0, 0, 0, 1.0))
+        }));
+        assert_eq!(text_color(&commands, "Text"), Color::new(255, 255, 0, 1.0));
+        assert_eq!(text_color(&commands, "link"), Color::new(0, 255, 255, 1.0));
+    }
+
+    #[test]
+    fn test_forced_color_adjust_none_keeps_author_colors() {
+        let commands = painted(
+            r#"<html><body><div style="forced-color-adjust: none; background-color: red;
+                width: 10px; height: 10px"><span>Kept</span></div></body></html>"#,
+        );
+        assert!(commands.iter().any(|c| {
+            matches!(c, DisplayCommand::SolidColor(color, _) if color.r == 255 && color.g == 0)
+        }));
+        assert_eq!(text_color(&commands, "Kept"), Color::BLACK);
+    }
+}
@@ -0,0 +1,96 @@
+//! What the mouse is over: the link a host shows in its status area and
+//! the cursor the view shows.
+
+use std::rc::Rc;
+
+use rustkit_css::Cursor;
+use rustkit_dom::{Node, NodeType};
+use rustkit_layout::{BoxType, HitTestResult};
+use rustkit_viewhost::CursorType;
+
+use crate::text_input::TextInput;
+
+/// The `<a href>` or `<area href>` `node` is or is inside, if any.
+pub(crate) fn link_ancestor(node: &Rc<Node>) -> Option<Rc<Node>> {
+    std::iter::successors(Some(node.clone()), |n| n.parent()).find(|n| {
+        n.tag_name()
+            .is_some_and(|t| t.eq_ignore_ascii_case("a") || t.eq_ignore_ascii_case("area"))
+            && n.get_attribute("href").is_some()
+    })
+}
+
+/// Whether `node` is a disabled form control or inside a disabled
+/// `<fieldset>`.
+fn is_disabled_control(node: &Rc<Node>) -> bool {
+    let control = |n: &Node, tags: &[&str]| {
+        n.tag_name()
+            .is_some_and(|t| tags.iter().any(|tag| t.eq_ignore_ascii_case(tag)))
+            && n.get_attribute("disabled").is_some()
+    };
+    let controls = [
+        "button", "input", "select", "textarea", "option", "optgroup",
+    ];
+    control(node, &controls)
+        || (control(node, &controls[..4])
+            && std::iter::successors(node.parent(), |n| n.parent())
+                .any(|n| control(&n, &["fieldset"])))
+}
+
+/// Whether clicking `node` presses a button.
+fn is_button(node: &Node) -> bool {
+    match node.tag_name().map(str::to_ascii_lowercase).as_deref() {
+        Some("button" | "summary") => true,
+        Some("input") => matches!(
+            node.get_attribute("type")
+                .map(str::to_ascii_lowercase)
+                .as_deref(),
+            Some("button" | "submit" | "reset" | "image" | "checkbox" | "radio" | "file")
+        ),
+        _ => false,
+    }
+}
+
+/// The cursor to show over `hit`, whose DOM node is `node`.
+///
+/// A `cursor` other than `auto` wins. Otherwise disabled controls show
+/// not-allowed, links and buttons a hand, and text, text fields and
+/// editing hosts an I-beam.
+pub(crate) fn cursor_for(hit: &HitTestResult, node: Option<&Rc<Node>>) -> CursorType {
+    match hit.cursor {
+        Cursor::Auto => {}
+        Cursor::Default => return CursorType::Arrow,
+        Cursor::None => return CursorType::Hidden,
+        Cursor::Pointer => return CursorType::Hand,
+        Cursor::Text => return CursorType::IBeam,
+        Cursor::Wait => return CursorType::Wait,
+        Cursor::Progress => return CursorType::Progress,
+        Cursor::Help => return CursorType::Help,
+        Cursor::Crosshair => return CursorType::Crosshair,
+        Cursor::Move => return CursorType::Move,
+        Cursor::NotAllowed => return CursorType::NotAllowed,
+        Cursor::EwResize => return CursorType::SizeWE,
+        Cursor::NsResize => return CursorType::SizeNS,
+        Cursor::NeswResize => return CursorType::SizeNESW,
+        Cursor::NwseResize => return CursorType::SizeNWSE,
+    }
+
+    let Some(node) = node else {
+        return CursorType::Arrow;
+    };
+    let ancestors = || std::iter::successors(Some(node.clone()), |n| n.parent());
+    if ancestors().any(|n| is_disabled_control(&n)) {
+        return CursorType::NotAllowed;
+    }
+    if link_ancestor(node).is_some() || ancestors().any(|n| is_button(&n)) {
+        return CursorType::Hand;
+    }
+    let over_text =
+        matches!(hit.box_type, BoxType::Text(_)) || matches!(node.node_type, NodeType::Text(_));
+    let editable =
+        ancestors().any(|n| TextInput::is_text_control(&n) || rustkit_dom::is_editing_host(&n));
+    if over_text || editable {
+        CursorType::IBeam
+    } else {
+        CursorType::Arrow
+    }
+}
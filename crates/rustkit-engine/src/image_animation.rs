@@ -0,0 +1,250 @@
+//! Animated GIF and APNG images of a view's document.
+//!
+//! Every display list build stamps the frame each animated image is on
+//! onto the commands drawing it, and keeps the images it painted; images
+//! no longer painted are forgotten. Each animation frame advances them
+//! by the time since the last one, and when a frame changes only the
+//! commands drawing that image are updated, without relayout or a
+//! display list rebuild. Images play only while the view is shown and
+//! their boxes are within a margin of the viewport; time spent hidden or
+//! scrolled away doesn't count, so they pick up where they left off.
+//! Frames an image evicted over its memory budget are decoded again as
+//! playback reaches them.
+
+use rustkit_image::{ImageData, LoadedImage};
+use rustkit_layout::{DisplayCommand, Rect};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// An animated image the display list paints.
+struct Playing {
+    image: Arc<LoadedImage>,
+    /// Time the image has played for.
+    elapsed: Duration,
+    /// When it was last advanced; `None` while paused.
+    last_tick: Option<Instant>,
+    frame: usize,
+    /// Union of the rects it is drawn in.
+    area: Rect,
+}
+
+impl Playing {
+    fn frame_at(&self, elapsed: Duration) -> usize {
+        match &self.image.data {
+            ImageData::Animated(animation) => animation.frame_index_at(elapsed),
+            ImageData::Static(_) => 0,
+        }
+    }
+
+    /// Have the pixels of `frame` at hand, decoding them again if the
+    /// image evicted them.
+    fn load_frame(&self, frame: usize) {
+        if let ImageData::Animated(animation) = &self.image.data {
+            animation.frame(frame);
+        }
+    }
+
+    /// Whether a finite animation has played through.
+    fn finished(&self) -> bool {
+        match &self.image.data {
+            ImageData::Animated(animation) => {
+                animation.loop_count > 0
+                    && self.elapsed >= animation.total_duration() * animation.loop_count
+            }
+            ImageData::Static(_) => true,
+        }
+    }
+}
+
+/// The animated images of a view's current document by the URL the
+/// display list refers to them with.
+#[derive(Default)]
+pub(crate) struct ImageAnimations {
+    playing: HashMap<String, Playing>,
+}
+
+/// The image URL and rect of a command drawing an image.
+fn image_of(command: &mut DisplayCommand) -> Option<(&str, Rect, &mut usize)> {
+    match command {
+        DisplayCommand::Image {
+            url,
+            dest_rect,
+            frame,
+            ..
+        } => Some((url.as_str(), *dest_rect, frame)),
+        DisplayCommand::BackgroundImage {
+            url, rect, frame, ..
+        } => Some((url.as_str(), *rect, frame)),
+        _ => None,
+    }
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    Rect::new(
+        x,
+        y,
+        (a.x + a.width).max(b.x + b.width) - x,
+        (a.y + a.height).max(b.y + b.height) - y,
+    )
+}
+
+impl ImageAnimations {
+    /// Stamp current frames onto a newly built display list, starting the
+    /// animated images `resolve` finds for its URLs. Images the list no
+    /// longer paints are dropped.
+    pub(crate) fn paint(
+        &mut self,
+        commands: &mut [DisplayCommand],
+        resolve: impl Fn(&str) -> Option<Arc<LoadedImage>>,
+    ) {
+        let mut painted: HashMap<String, Playing> = HashMap::new();
+        for command in commands.iter_mut() {
+            let Some((url, rect, frame)) = image_of(command) else {
+                continue;
+            };
+            if let Some(playing) = painted.get_mut(url) {
+                playing.area = union(playing.area, rect);
+                *frame = playing.frame;
+                continue;
+            }
+            let playing = match self.playing.remove(url) {
+                Some(playing) => Playing {
+                    area: rect,
+                    ..playing
+                },
+                None => match resolve(url).filter(|image| image.is_animated()) {
+                    Some(image) => Playing {
+                        image,
+                        elapsed: Duration::ZERO,
+                        last_tick: None,
+                        frame: 0,
+                        area: rect,
+                    },
+                    None => continue,
+                },
+            };
+            *frame = playing.frame;
+            painted.insert(url.to_string(), playing);
+        }
+        self.playing = painted;
+    }
+
+    /// Advance the images whose areas `visible` accepts to `now`; the
+    /// others are paused. Returns whether any image changed frames.
+    pub(crate) fn tick(&mut self, now: Instant, visible: impl Fn(&Rect) -> bool) -> bool {
+        let mut changed = false;
+        for playing in self.playing.values_mut() {
+            if !visible(&playing.area) || playing.finished() {
+                playing.last_tick = None;
+                continue;
+            }
+            if let Some(last_tick) = playing.last_tick {
+                playing.elapsed += now.saturating_duration_since(last_tick);
+            }
+            playing.last_tick = Some(now);
+            let frame = playing.frame_at(playing.elapsed);
+            if frame != playing.frame {
+                playing.load_frame(frame);
+                playing.frame = frame;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Pause every image, as when the view is hidden.
+    pub(crate) fn pause(&mut self) {
+        for playing in self.playing.values_mut() {
+            playing.last_tick = None;
+        }
+    }
+
+    /// Update the frames of the commands drawing animated images.
+    pub(crate) fn apply(&self, commands: &mut [DisplayCommand]) {
+        for command in commands {
+            if let Some((url, _, frame)) = image_of(command) {
+                if let Some(playing) = self.playing.get(url) {
+                    *frame = playing.frame;
+                }
+            }
+        }
+    }
+
+    /// Whether any image still has frames to play.
+    pub(crate) fn is_running(&self) -> bool {
+        self.playing.values().any(|playing| !playing.finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_codecs::RgbaImage;
+    use rustkit_image::AnimationFrame;
+    use url::Url;
+
+    fn animation(delays: &[u32]) -> Arc<LoadedImage> {
+        let frames = delays
+            .iter()
+            .map(|&delay_ms| AnimationFrame {
+                image: RgbaImage::new(1, 1),
+                delay_ms,
+            })
+            .collect();
+        Arc::new(LoadedImage::animated(
+            Url::parse("https://example.com/a.gif").unwrap(),
+            frames,
+        ))
+    }
+
+    fn drawn(url: &str, x: f32) -> DisplayCommand {
+        rustkit_layout::render_image(
+            url,
+            Rect::new(x, 0.0, 10.0, 10.0),
+            1.0,
+            1.0,
+            Default::default(),
+            (0.5, 0.5),
+            1.0,
+        )
+    }
+
+    fn frames(commands: &[DisplayCommand]) -> Vec<usize> {
+        commands
+            .iter()
+            .map(|command| match command {
+                DisplayCommand::Image { frame, .. } => *frame,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_frames_advance_only_while_visible() {
+        let image = animation(&[100, 100, 100]);
+        let mut animations = ImageAnimations::default();
+        let mut commands = vec![drawn("a.gif", 0.0), drawn("a.gif", 500.0)];
+        animations.paint(&mut commands, |_| Some(image.clone()));
+        assert!(animations.is_running());
+
+        let start = Instant::now();
+        assert!(!animations.tick(start, |_| true));
+        assert!(animations.tick(start + Duration::from_millis(150), |_| true));
+        animations.apply(&mut commands);
+        assert_eq!(frames(&commands), [1, 1]);
+
+        // Offscreen time doesn't count
+        assert!(!animations.tick(start + Duration::from_secs(5), |area| area.x > 1000.0));
+        assert!(!animations.tick(start + Duration::from_secs(6), |_| true));
+        assert!(animations.tick(start + Duration::from_millis(6100), |_| true));
+
+        // A rebuilt list keeps the frame; images it drops are forgotten
+        let mut rebuilt = vec![drawn("a.gif", 0.0)];
+        animations.paint(&mut rebuilt, |_| None);
+        assert_eq!(frames(&rebuilt), [2]);
+        animations.paint(&mut [], |_| None);
+        assert!(!animations.is_running());
+    }
+}
@@ -0,0 +1,541 @@
+//! DOM inspector: serialized document trees, applied styles, box overlays
+//! and node picking for a devtools panel.
+//!
+//! A panel drives the inspector over IPC with messages like
+//! `{"inspector": {"id": 1, "method": "getDocument", "params": {"viewId": 2}}}`.
+//! Replies (`{"id": 1, "result": ...}` or `{"id": 1, "error": "..."}`) and
+//! change notifications are passed to `window.ipc.oninspectormessage` in the
+//! panel's view.
+
+use std::collections::BTreeMap;
+
+use rustkit_bindings::{DomMutation, GeometryMap};
+use rustkit_css::{Color, ComputedStyle, Length};
+use rustkit_dom::{Document, Node, NodeId, NodeType};
+use rustkit_layout::{Dimensions, DisplayCommand, DisplayList, Rect};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::EngineViewId;
+
+/// Longest text preview, in characters.
+const TEXT_PREVIEW: usize = 100;
+
+/// Overlay colors for the content, padding, border and margin boxes.
+const CONTENT_COLOR: Color = Color {
+    r: 111,
+    g: 168,
+    b: 220,
+    a: 0.66,
+};
+const PADDING_COLOR: Color = Color {
+    r: 147,
+    g: 196,
+    b: 125,
+    a: 0.55,
+};
+const BORDER_COLOR: Color = Color {
+    r: 255,
+    g: 229,
+    b: 153,
+    a: 0.66,
+};
+const MARGIN_COLOR: Color = Color {
+    r: 246,
+    g: 178,
+    b: 107,
+    a: 0.66,
+};
+
+/// A node of a serialized document tree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InspectorNode {
+    pub node_id: usize,
+    /// DOM `nodeType`.
+    pub node_type: u16,
+    /// Lowercase tag name for elements, `#text`, `#comment` and the like
+    /// for other nodes.
+    pub name: String,
+    /// Attributes, sorted by name.
+    pub attributes: Vec<(String, String)>,
+    /// Start of the data of text and comment nodes.
+    pub text: Option<String>,
+    pub child_count: usize,
+    /// Children, or `None` below the requested depth.
+    pub children: Option<Vec<InspectorNode>>,
+}
+
+impl InspectorNode {
+    /// Serialize `node` and its descendants `depth` levels down.
+    pub fn from_node(node: &Node, depth: usize) -> Self {
+        let (node_type, name, text) = match &node.node_type {
+            NodeType::Element { tag_name, .. } => (1, tag_name.to_ascii_lowercase(), None),
+            NodeType::Text(text) => (3, "#text".to_string(), Some(preview(text))),
+            NodeType::ProcessingInstruction { target, data } => {
+                (7, target.clone(), Some(preview(data)))
+            }
+            NodeType::Comment(text) => (8, "#comment".to_string(), Some(preview(text))),
+            NodeType::Document => (9, "#document".to_string(), None),
+            NodeType::DocumentType { name, .. } => (10, name.clone(), None),
+            NodeType::DocumentFragment => (11, "#document-fragment".to_string(), None),
+        };
+        let children = inspectable_children(node);
+        Self {
+            node_id: node.id.raw(),
+            node_type,
+            name,
+            attributes: sorted_attributes(node),
+            text,
+            child_count: children.len(),
+            children: (depth > 0).then(|| {
+                children
+                    .iter()
+                    .map(|child| Self::from_node(child, depth - 1))
+                    .collect()
+            }),
+        }
+    }
+}
+
+fn preview(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(TEXT_PREVIEW) {
+        Some((end, _)) => format!("{}{}", &text[..end], rustkit_layout::ELLIPSIS),
+        None => text.to_string(),
+    }
+}
+
+fn sorted_attributes(node: &Node) -> Vec<(String, String)> {
+    let NodeType::Element { attributes, .. } = &node.node_type else {
+        return Vec::new();
+    };
+    let mut attributes: Vec<_> = attributes
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    attributes.sort();
+    attributes
+}
+
+/// Children shown in the tree; whitespace between tags is left out.
+fn inspectable_children(node: &Node) -> Vec<std::rc::Rc<Node>> {
+    node.children()
+        .into_iter()
+        .filter(|child| !matches!(&child.node_type, NodeType::Text(text) if text.trim().is_empty()))
+        .collect()
+}
+
+/// Where a block of declarations applied to a node came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StyleOrigin {
+    /// Style sheets of the page.
+    Author,
+    /// The `style` attribute or `element.style`.
+    Inline,
+}
+
+/// Declarations from one origin, in cascade order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchedDeclarations {
+    pub origin: StyleOrigin,
+    pub declarations: Vec<(String, String)>,
+}
+
+/// Styles of an element.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InspectorStyles {
+    pub node_id: usize,
+    /// Computed values by property name.
+    pub computed: BTreeMap<String, String>,
+    /// Declarations applied over the element's user-agent defaults, lowest
+    /// priority first.
+    pub matched: Vec<MatchedDeclarations>,
+}
+
+impl InspectorStyles {
+    pub(crate) fn new(
+        node_id: NodeId,
+        style: &ComputedStyle,
+        author: Vec<(String, String)>,
+        inline: Option<&str>,
+    ) -> Self {
+        let mut matched = Vec::new();
+        if !author.is_empty() {
+            matched.push(MatchedDeclarations {
+                origin: StyleOrigin::Author,
+                declarations: author,
+            });
+        }
+        if let Some(inline) = inline {
+            let declarations: Vec<_> = inline
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .map(|(property, value)| (property.trim().to_lowercase(), value.trim().to_string()))
+                .collect();
+            if !declarations.is_empty() {
+                matched.push(MatchedDeclarations {
+                    origin: StyleOrigin::Inline,
+                    declarations,
+                });
+            }
+        }
+        Self {
+            node_id: node_id.raw(),
+            computed: computed_values(style),
+            matched,
+        }
+    }
+}
+
+fn length(value: Length) -> String {
+    match value {
+        Length::Px(px) => format!("{}px", px),
+        Length::Em(em) => format!("{}em", em),
+        Length::Rem(rem) => format!("{}rem", rem),
+        Length::Percent(percent) => format!("{}%", percent),
+        Length::Auto => "auto".to_string(),
+        Length::Zero => "0px".to_string(),
+        Length::Calc(calc) => calc.to_string(),
+    }
+}
+
+fn color(value: Color) -> String {
+    format!("rgba({}, {}, {}, {})", value.r, value.g, value.b, value.a)
+}
+
+/// A keyword enum's CSS spelling, from its variant name.
+fn keyword(value: impl std::fmt::Debug) -> String {
+    let mut keyword = String::new();
+    for (i, c) in format!("{:?}", value).chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                keyword.push('-');
+            }
+            keyword.push(c.to_ascii_lowercase());
+        } else {
+            keyword.push(c);
+        }
+    }
+    keyword
+}
+
+fn computed_values(style: &ComputedStyle) -> BTreeMap<String, String> {
+    let values = [
+        ("display", keyword(style.display)),
+        ("position", keyword(style.position)),
+        ("width", length(style.width)),
+        ("height", length(style.height)),
+        ("margin-top", length(style.margin_top)),
+        ("margin-right", length(style.margin_right)),
+        ("margin-bottom", length(style.margin_bottom)),
+        ("margin-left", length(style.margin_left)),
+        ("padding-top", length(style.padding_top)),
+        ("padding-right", length(style.padding_right)),
+        ("padding-bottom", length(style.padding_bottom)),
+        ("padding-left", length(style.padding_left)),
+        ("border-top-width", length(style.border_top_width)),
+        ("border-right-width", length(style.border_right_width)),
+        ("border-bottom-width", length(style.border_bottom_width)),
+        ("border-left-width", length(style.border_left_width)),
+        ("color", color(style.color)),
+        ("background-color", color(style.background_color)),
+        ("font-size", length(style.font_size)),
+        ("font-weight", style.font_weight.0.to_string()),
+        ("font-style", keyword(style.font_style)),
+        ("font-family", style.font_family.clone()),
+        ("line-height", style.line_height.to_string()),
+        ("text-align", keyword(style.text_align)),
+        ("white-space", keyword(style.white_space)),
+        ("opacity", style.opacity.to_string()),
+        (
+            "z-index",
+            style.z_index.map_or("auto".to_string(), |z| z.to_string()),
+        ),
+        ("overflow-x", keyword(style.overflow_x)),
+        ("overflow-y", keyword(style.overflow_y)),
+    ];
+    values
+        .into_iter()
+        .map(|(property, value)| (property.to_string(), value))
+        .collect()
+}
+
+/// The parts of `outer` outside `inner`, as up to four strips.
+fn ring(outer: Rect, inner: Rect) -> impl Iterator<Item = Rect> {
+    let top = inner.y - outer.y;
+    let bottom = outer.y + outer.height - (inner.y + inner.height);
+    let left = inner.x - outer.x;
+    let right = outer.x + outer.width - (inner.x + inner.width);
+    [
+        Rect::new(outer.x, outer.y, outer.width, top),
+        Rect::new(outer.x, inner.y + inner.height, outer.width, bottom),
+        Rect::new(outer.x, inner.y, left, inner.height),
+        Rect::new(inner.x + inner.width, inner.y, right, inner.height),
+    ]
+    .into_iter()
+    .filter(|strip| strip.width > 0.0 && strip.height > 0.0)
+}
+
+/// Overlay commands showing the boxes of a node's fragments.
+pub(crate) fn highlight_commands(fragments: &[Dimensions]) -> Vec<DisplayCommand> {
+    let mut commands = Vec::new();
+    for fragment in fragments {
+        let (content, padding) = (fragment.content, fragment.padding_box());
+        let (border, margin) = (fragment.border_box(), fragment.margin_box());
+        for (color, outer, inner) in [
+            (MARGIN_COLOR, margin, border),
+            (BORDER_COLOR, border, padding),
+            (PADDING_COLOR, padding, content),
+        ] {
+            commands
+                .extend(ring(outer, inner).map(|strip| DisplayCommand::SolidColor(color, strip)));
+        }
+        if content.width > 0.0 && content.height > 0.0 {
+            commands.push(DisplayCommand::SolidColor(CONTENT_COLOR, content));
+        }
+    }
+    commands
+}
+
+/// Per-view inspector state.
+#[derive(Debug, Default)]
+pub(crate) struct InspectorState {
+    /// View of the panel inspecting this one, which gets change
+    /// notifications.
+    pub client: Option<EngineViewId>,
+    /// Node whose boxes are highlighted.
+    pub highlighted: Option<NodeId>,
+    /// Overlay commands at the end of the display list.
+    overlay_len: usize,
+}
+
+impl InspectorState {
+    /// Append the highlight overlay to a newly built display list.
+    pub fn paint(&mut self, geometry: &GeometryMap, list: &mut DisplayList) {
+        let overlay = self
+            .highlighted
+            .and_then(|node| geometry.get(&node))
+            .map(|geometry| highlight_commands(&geometry.fragments))
+            .unwrap_or_default();
+        self.overlay_len = overlay.len();
+        list.commands.extend(overlay);
+    }
+
+    /// Replace the overlay painted into `list` by [`Self::paint`].
+    pub fn update(&mut self, geometry: &GeometryMap, list: &mut DisplayList) {
+        let painted = list.commands.len().saturating_sub(self.overlay_len);
+        list.commands.truncate(painted);
+        self.paint(geometry, list);
+    }
+}
+
+/// A request read from an IPC payload.
+#[derive(Debug)]
+pub(crate) struct InspectorRequest {
+    pub id: Value,
+    pub method: String,
+    pub params: Value,
+}
+
+impl InspectorRequest {
+    /// The request in `payload`, if it is an inspector message.
+    pub fn parse(payload: &str) -> Option<Self> {
+        let mut message: Value = serde_json::from_str(payload).ok()?;
+        let request = message.get_mut("inspector")?.take();
+        Some(Self {
+            id: request.get("id").cloned().unwrap_or(Value::Null),
+            method: request.get("method")?.as_str()?.to_string(),
+            params: request.get("params").cloned().unwrap_or(Value::Null),
+        })
+    }
+
+    pub fn reply(&self, result: Result<Value, String>) -> Value {
+        match result {
+            Ok(result) => json!({ "id": self.id, "result": result }),
+            Err(error) => json!({ "id": self.id, "error": error }),
+        }
+    }
+}
+
+/// Script delivering `message` to the panel's handler.
+pub(crate) fn delivery_script(message: &Value) -> String {
+    format!(
+        "if (window.ipc && typeof window.ipc.oninspectormessage === 'function') \
+         window.ipc.oninspectormessage({});",
+        message
+    )
+}
+
+/// Notifications for DOM changes in the view `view_id`. Several changes to
+/// one node send one notification.
+pub(crate) fn mutation_notifications(
+    view_id: EngineViewId,
+    document: &Document,
+    mutations: &[DomMutation],
+) -> Vec<Value> {
+    let mut seen = Vec::new();
+    let mut notifications = Vec::new();
+    for mutation in mutations {
+        let key = match mutation {
+            DomMutation::ChildList { target } => (*target, true),
+            DomMutation::Attributes { target, .. } => (*target, false),
+        };
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        let Some(node) = document.get_node(key.0) else {
+            continue;
+        };
+        let notification = if key.1 {
+            json!({
+                "method": "childNodesUpdated",
+                "params": {
+                    "viewId": view_id.raw(),
+                    "nodeId": node.id.raw(),
+                    "children": inspectable_children(&node)
+                        .iter()
+                        .map(|child| InspectorNode::from_node(child, 0))
+                        .collect::<Vec<_>>(),
+                },
+            })
+        } else {
+            json!({
+                "method": "attributesUpdated",
+                "params": {
+                    "viewId": view_id.raw(),
+                    "nodeId": node.id.raw(),
+                    "attributes": sorted_attributes(&node),
+                },
+            })
+        };
+        notifications.push(notification);
+    }
+    notifications
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use rustkit_css::MediaEnvironment;
+
+    const FIXTURE: &str = "<!DOCTYPE html><html><head><title>Fixture</title></head>\n\
+        <body>\n  <div id=\"main\" class=\"card\">Hello <b>world</b></div>\n  \
+        <!-- note -->\n</body></html>";
+
+    fn shape(node: &InspectorNode) -> String {
+        match &node.children {
+            Some(children) if !children.is_empty() => format!(
+                "{}({})",
+                node.name,
+                children.iter().map(shape).collect::<Vec<_>>().join(" ")
+            ),
+            _ => node.name.clone(),
+        }
+    }
+
+    #[test]
+    fn test_document_tree_shape() {
+        let document = Document::parse_html(FIXTURE).unwrap();
+        let tree = InspectorNode::from_node(document.root(), 5);
+        assert_eq!(
+            shape(&tree),
+            "#document(html html(head(title(#text)) body(div(#text b(#text)) #comment)))"
+        );
+
+        let html = &tree.children.as_ref().unwrap()[1];
+        let body = &html.children.as_ref().unwrap()[1];
+        let div = &body.children.as_ref().unwrap()[0];
+        assert_eq!(div.node_type, 1);
+        assert_eq!(
+            div.attributes,
+            [
+                ("class".to_string(), "card".to_string()),
+                ("id".to_string(), "main".to_string())
+            ]
+        );
+        assert_eq!(div.child_count, 2);
+        let text = &div.children.as_ref().unwrap()[0];
+        assert_eq!((text.node_type, text.text.as_deref()), (3, Some("Hello")));
+
+        // Below the requested depth only counts are given
+        let shallow = InspectorNode::from_node(document.root(), 2);
+        let html = &shallow.children.as_ref().unwrap()[1];
+        let body = &html.children.as_ref().unwrap()[1];
+        assert_eq!((body.child_count, body.children.as_ref()), (2, None));
+    }
+
+    #[test]
+    fn test_highlight_overlay_is_added_and_removed() {
+        let document =
+            Document::parse_html("<body><p style=\"margin: 8px; padding: 4px\">Hi</p></body>")
+                .unwrap();
+        let layout = Engine::layout_document(&document, &MediaEnvironment::screen(800.0, 600.0));
+        let geometry = Engine::collect_geometry(&document, &layout);
+        let mut list = DisplayList::build(&layout);
+        let page = list.commands.clone();
+
+        let p = document.get_elements_by_tag_name("p")[0].id;
+        let mut inspector = InspectorState {
+            highlighted: Some(p),
+            ..Default::default()
+        };
+        inspector.update(&geometry, &mut list);
+        let overlay = highlight_commands(&geometry[&p].fragments);
+        // Content plus margin and padding rings; the border is empty
+        assert_eq!(overlay.len(), 9);
+        assert_eq!(list.commands.len(), page.len() + overlay.len());
+        assert_eq!(
+            format!("{:?}", &list.commands[page.len()..]),
+            format!("{:?}", overlay)
+        );
+
+        // Highlighting again replaces the overlay rather than stacking it
+        inspector.update(&geometry, &mut list);
+        assert_eq!(list.commands.len(), page.len() + overlay.len());
+
+        inspector.highlighted = None;
+        inspector.update(&geometry, &mut list);
+        assert_eq!(format!("{:?}", list.commands), format!("{:?}", page));
+    }
+
+    #[test]
+    fn test_requests_and_mutations() {
+        let request = InspectorRequest::parse(
+            r#"{"inspector": {"id": 7, "method": "getStyles", "params": {"nodeId": 3}}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.method, "getStyles");
+        assert_eq!(
+            request.reply(Err("No such node".into())),
+            json!({"id": 7, "error": "No such node"})
+        );
+        assert!(InspectorRequest::parse(r#"{"type": "navigate"}"#).is_none());
+        assert!(InspectorRequest::parse("not json").is_none());
+
+        let document = Document::parse_html("<body><div id=a></div></body>").unwrap();
+        let div = document.get_element_by_id("a").unwrap();
+        let body = document.body().unwrap();
+        let mutations = [
+            DomMutation::ChildList { target: body.id },
+            DomMutation::Attributes {
+                target: div.id,
+                name: "class".into(),
+            },
+            DomMutation::ChildList { target: body.id },
+        ];
+        let view = EngineViewId::new();
+        let notifications = mutation_notifications(view, &document, &mutations);
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0]["method"], "childNodesUpdated");
+        assert_eq!(notifications[0]["params"]["children"][0]["name"], "div");
+        assert_eq!(
+            notifications[1]["params"]["attributes"][0],
+            json!(["id", "a"])
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Lazy loading of `<img loading=lazy>` and `<iframe loading=lazy>`.
+//!
+//! Lazy elements are left out of a document's first fetches. After each
+//! layout and scroll the engine takes the ones whose boxes have come within
+//! a margin of the viewport, nearest first, and starts their loads. An
+//! element that is never rendered never loads. Boxes don't wait on the
+//! content: an image is laid out from its `width` and `height` attributes,
+//! so it doesn't move what follows when it arrives.
+
+use std::rc::Rc;
+
+use rustkit_bindings::GeometryMap;
+use rustkit_dom::{Document, Node, NodeId, NodeType};
+use rustkit_image::loader::{parse_srcset, select_srcset_entry};
+use rustkit_layout::Rect;
+use url::Url;
+
+/// Lazy loading counters for a view's current document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LazyLoadStats {
+    /// Lazy elements that were not near the viewport at the first layout.
+    pub deferred: u64,
+    /// Deferred elements whose loads started once scrolling or a later
+    /// layout brought them near.
+    pub loaded_on_scroll: u64,
+}
+
+/// What a lazy element loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LazyTarget {
+    Image,
+    /// The document of the frame at this index.
+    Frame(usize),
+}
+
+/// A lazy element whose load hasn't started.
+#[derive(Debug, Clone)]
+pub(crate) struct LazyElement {
+    pub(crate) node: NodeId,
+    pub(crate) url: Url,
+    pub(crate) target: LazyTarget,
+}
+
+/// The lazy elements of a document still waiting to load.
+#[derive(Debug, Default)]
+pub(crate) struct LazyLoads {
+    waiting: Vec<LazyElement>,
+    /// Set once the document's first layout has been checked.
+    laid_out: bool,
+    stats: LazyLoadStats,
+}
+
+impl LazyLoads {
+    /// Hold back the load of `url` until `node` comes near the viewport.
+    pub(crate) fn defer(&mut self, node: NodeId, url: Url, target: LazyTarget) {
+        self.waiting.push(LazyElement { node, url, target });
+    }
+
+    pub(crate) fn stats(&self) -> LazyLoadStats {
+        self.stats
+    }
+
+    /// Remove and return the waiting elements that are within `margin` of
+    /// `viewport` (in document coordinates), nearest first.
+    pub(crate) fn take_due(
+        &mut self,
+        geometry: &GeometryMap,
+        viewport: Rect,
+        margin: f32,
+    ) -> Vec<LazyElement> {
+        let mut due = Vec::new();
+        self.waiting.retain(|element| {
+            let distance = geometry
+                .get(&element.node)
+                .and_then(|g| g.fragments.first())
+                .map(|d| distance(&d.border_box(), &viewport));
+            match distance {
+                Some(distance) if distance <= margin => {
+                    due.push((distance, element.clone()));
+                    false
+                }
+                _ => true,
+            }
+        });
+        due.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        if self.laid_out {
+            self.stats.loaded_on_scroll += due.len() as u64;
+        } else {
+            self.laid_out = true;
+            self.stats.deferred = self.waiting.len() as u64;
+        }
+        due.into_iter().map(|(_, element)| element).collect()
+    }
+}
+
+/// How far `rect` is from `viewport`; zero when they overlap.
+fn distance(rect: &Rect, viewport: &Rect) -> f32 {
+    let gap = |start: f32, size: f32, view_start: f32, view_size: f32| {
+        (view_start - (start + size))
+            .max(start - (view_start + view_size))
+            .max(0.0)
+    };
+    let dx = gap(rect.x, rect.width, viewport.x, viewport.width);
+    let dy = gap(rect.y, rect.height, viewport.y, viewport.height);
+    dx.max(dy)
+}
+
+/// Whether `node` asks to load lazily.
+pub(crate) fn is_lazy(node: &Node) -> bool {
+    node.get_attribute("loading")
+        .is_some_and(|loading| loading.trim().eq_ignore_ascii_case("lazy"))
+}
+
+/// The lazy `<img>` elements of `document` and the URLs they would load,
+/// choosing `srcset` candidates for a viewport `viewport_width` CSS pixels
+/// wide.
+pub(crate) fn lazy_images(
+    document: &Document,
+    base: &Url,
+    viewport_width: u32,
+    device_pixel_ratio: f64,
+) -> Vec<(NodeId, Url)> {
+    let mut images: Vec<Rc<Node>> = Vec::new();
+    document.traverse(|node| {
+        if matches!(&node.node_type, NodeType::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("img"))
+            && is_lazy(node)
+        {
+            images.push(node.clone());
+        }
+    });
+    images
+        .iter()
+        .filter_map(|image| {
+            let entries = image
+                .get_attribute("srcset")
+                .map(parse_srcset)
+                .unwrap_or_default();
+            let selected = select_srcset_entry(&entries, viewport_width, device_pixel_ratio)
+                .map(|entry| entry.url.clone());
+            let src = selected.as_deref().or(image.get_attribute("src"))?.trim();
+            let url = base.join(src).ok().filter(|_| !src.is_empty())?;
+            Some((image.id, url))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_layout::{Dimensions, NodeGeometry};
+
+    fn geometry(boxes: &[(usize, f32)]) -> GeometryMap {
+        boxes
+            .iter()
+            .map(|&(id, y)| {
+                let content = Rect::new(0.0, y, 100.0, 100.0);
+                let geometry = NodeGeometry {
+                    fragments: vec![Dimensions {
+                        content,
+                        ..Default::default()
+                    }],
+                    overflow: content,
+                    ..Default::default()
+                };
+                (NodeId::new(id), geometry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_due_elements_are_nearest_first() {
+        let url = Url::parse("http://example.com/a.png").unwrap();
+        let mut loads = LazyLoads::default();
+        for id in [1, 2, 3, 4] {
+            loads.defer(NodeId::new(id), url.clone(), LazyTarget::Image);
+        }
+        // Node 4 has no box and never loads
+        let geometry = geometry(&[(1, 0.0), (2, 650.0), (3, 450.0)]);
+        let viewport = Rect::new(0.0, 0.0, 400.0, 300.0);
+
+        let due = loads.take_due(&geometry, viewport, 100.0);
+        assert_eq!(due.len(), 1);
+        assert_eq!(loads.stats().deferred, 3);
+
+        let scrolled = Rect::new(0.0, 200.0, 400.0, 300.0);
+        let due: Vec<_> = loads
+            .take_due(&geometry, scrolled, 200.0)
+            .iter()
+            .map(|element| element.node)
+            .collect();
+        assert_eq!(due, [NodeId::new(3), NodeId::new(2)]);
+        assert_eq!(
+            loads.stats(),
+            LazyLoadStats {
+                deferred: 3,
+                loaded_on_scroll: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Memory budgets and trimming under memory pressure.
+//!
+//! The engine trims its caches to a [`MemoryBudget`] when the host or the
+//! system reports pressure. Hidden views give up what can be rebuilt: their
+//! decoded images at [`MemoryPressure::Moderate`], and their layout trees
+//! and display lists at [`MemoryPressure::Critical`], laid out again when
+//! they are next shown.
+
+use std::collections::HashSet;
+use std::mem::size_of;
+
+use rustkit_dom::Document;
+use rustkit_layout::{DisplayCommand, DisplayList, LayoutBox};
+use serde::Serialize;
+use url::Url;
+
+use crate::EngineViewId;
+
+/// Bytes each subsystem may keep once memory is trimmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Decoded and encoded images.
+    pub image_cache: usize,
+    /// Responses held by the resource loader.
+    pub http_cache: usize,
+    /// Image textures and the glyph atlas.
+    pub gpu_textures: usize,
+    /// Layout tree and display list of each hidden view.
+    pub per_hidden_view_retained: usize,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self {
+            image_cache: 128 * 1024 * 1024,
+            http_cache: 16 * 1024 * 1024,
+            gpu_textures: 128 * 1024 * 1024,
+            per_hidden_view_retained: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// How hard [`crate::Engine::trim_memory`] trims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    /// Trim caches to budget and release decoded images only hidden views
+    /// use, keeping their encoded bytes.
+    Moderate,
+    /// Also empty the glyph atlas and drop the layout trees and display
+    /// lists of hidden views.
+    Critical,
+}
+
+/// Memory held by a view's retained layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewMemory {
+    pub view_id: u64,
+    pub hidden: bool,
+    /// Estimated bytes of the layout tree and display list.
+    pub retained_bytes: usize,
+}
+
+/// Memory held by the engine, for host diagnostics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryReport {
+    /// Decoded image pixels.
+    pub image_cache_bytes: usize,
+    /// Encoded bytes kept to decode released images again.
+    pub image_encoded_bytes: usize,
+    pub http_cache_bytes: usize,
+    pub gpu_texture_bytes: usize,
+    pub glyph_atlas_bytes: usize,
+    pub views: Vec<ViewMemory>,
+}
+
+impl MemoryReport {
+    /// Total bytes across subsystems and views.
+    pub fn total_bytes(&self) -> usize {
+        self.image_cache_bytes
+            + self.image_encoded_bytes
+            + self.http_cache_bytes
+            + self.gpu_texture_bytes
+            + self.glyph_atlas_bytes
+            + self.views.iter().map(|v| v.retained_bytes).sum::<usize>()
+    }
+}
+
+/// Estimated bytes of a layout tree and display list.
+pub(crate) fn retained_bytes(
+    layout: Option<&LayoutBox>,
+    display_list: Option<&DisplayList>,
+) -> usize {
+    layout.map_or(0, |root| box_count(root) * size_of::<LayoutBox>())
+        + display_list.map_or(0, |list| list.commands.len() * size_of::<DisplayCommand>())
+}
+
+/// Boxes in a layout tree.
+pub(crate) fn box_count(layout: &LayoutBox) -> usize {
+    1 + layout.children.iter().map(box_count).sum::<usize>()
+}
+
+/// Images a document's `<img>` elements show.
+pub(crate) fn image_urls(document: &Document, base: Option<&Url>, urls: &mut HashSet<Url>) {
+    for img in document.get_elements_by_tag_name("img") {
+        let Some(src) = img.get_attribute("src") else {
+            continue;
+        };
+        if let Ok(url) = Url::options().base_url(base).parse(src) {
+            urls.insert(url);
+        }
+    }
+}
+
+impl ViewMemory {
+    pub(crate) fn new(id: EngineViewId, hidden: bool, retained_bytes: usize) -> Self {
+        Self {
+            view_id: id.raw(),
+            hidden,
+            retained_bytes,
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Per-origin permissions for powerful web APIs.
+//!
+//! Features ask the [`PermissionBroker`] before acting for a page. A
+//! decision stored for the page's origin, or the default policy for the
+//! kind, settles the request at once; otherwise the engine emits
+//! [`crate::EngineEvent::PermissionRequested`] and carries the action out,
+//! or drops it, when the host answers with [`crate::Engine::resolve_permission`].
+//! Decisions the user asked to remember are saved as `permissions.json`
+//! in the profile directory.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rustkit_bindings::{ClipboardData, ClipboardError, ClipboardOp, ClipboardRequest, WindowFeatures};
+use rustkit_viewhost::{Clipboard, ClipboardContents};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use url::Url;
+
+use crate::EngineViewId;
+
+/// File in the profile directory decisions are saved to.
+const PERMISSIONS_FILE: &str = "permissions.json";
+
+/// A capability a page asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionKind {
+    /// Reading the system clipboard.
+    ClipboardRead,
+    /// Writing the system clipboard without user activation.
+    ClipboardWrite,
+    /// Opening windows without user activation.
+    Popups,
+    /// Starting more than one download from the same document.
+    AutomaticDownloads,
+}
+
+impl PermissionKind {
+    /// Every kind, in the order `navigator.permissions` lists them.
+    pub const ALL: [PermissionKind; 4] = [
+        PermissionKind::ClipboardRead,
+        PermissionKind::ClipboardWrite,
+        PermissionKind::Popups,
+        PermissionKind::AutomaticDownloads,
+    ];
+
+    /// The name `navigator.permissions.query` knows the kind by.
+    pub fn name(self) -> &'static str {
+        match self {
+            PermissionKind::ClipboardRead => "clipboard-read",
+            PermissionKind::ClipboardWrite => "clipboard-write",
+            PermissionKind::Popups => "popups",
+            PermissionKind::AutomaticDownloads => "automatic-downloads",
+        }
+    }
+
+    /// The policy for origins without a stored decision, unless
+    /// [`crate::EngineConfig::permission_defaults`] overrides it.
+    pub fn default_state(self) -> PermissionState {
+        match self {
+            PermissionKind::ClipboardRead | PermissionKind::ClipboardWrite => {
+                PermissionState::Prompt
+            }
+            PermissionKind::Popups | PermissionKind::AutomaticDownloads => PermissionState::Granted,
+        }
+    }
+}
+
+/// Whether an origin may use a capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    /// The user is asked each time.
+    Prompt,
+}
+
+impl PermissionState {
+    /// The state as `PermissionStatus.state` spells it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+            PermissionState::Prompt => "prompt",
+        }
+    }
+}
+
+/// Identifies a permission prompt in [`crate::Engine::resolve_permission`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PermissionRequestId(u64);
+
+impl PermissionRequestId {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(1);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// What the engine does once a request is granted.
+#[derive(Debug)]
+pub(crate) enum PermittedAction {
+    /// Settle a clipboard call.
+    Clipboard(ClipboardRequest),
+    /// Offer a popup to the host with `PopupRequested`.
+    Popup {
+        popup_id: u64,
+        url: Url,
+        name: String,
+        features: WindowFeatures,
+    },
+    /// Offer a download to the host with `DownloadRequested`.
+    Download {
+        url: Url,
+        suggested_filename: String,
+    },
+}
+
+/// A request waiting for the host's decision.
+#[derive(Debug)]
+pub(crate) struct PendingPermission {
+    pub(crate) view_id: EngineViewId,
+    pub(crate) origin: String,
+    pub(crate) kind: PermissionKind,
+    pub(crate) action: PermittedAction,
+}
+
+/// Decides permission requests and remembers per-origin decisions.
+#[derive(Debug, Default)]
+pub(crate) struct PermissionBroker {
+    defaults: HashMap<PermissionKind, PermissionState>,
+    decisions: BTreeMap<String, BTreeMap<PermissionKind, PermissionState>>,
+    /// Where decisions are saved; `None` keeps them in memory.
+    path: Option<PathBuf>,
+    pending: HashMap<PermissionRequestId, PendingPermission>,
+}
+
+impl PermissionBroker {
+    /// A broker applying `defaults` over the built-in policies, with the
+    /// decisions saved in `profile_directory`.
+    pub(crate) fn new(
+        defaults: HashMap<PermissionKind, PermissionState>,
+        profile_directory: Option<&Path>,
+    ) -> Self {
+        let path = profile_directory.map(|dir| dir.join(PERMISSIONS_FILE));
+        let decisions = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| warn!(error = %e, "Ignoring unreadable permission decisions"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            defaults,
+            decisions,
+            path,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// The state of `kind` for `origin`: its stored decision, else the
+    /// default policy.
+    pub(crate) fn state(&self, origin: &str, kind: PermissionKind) -> PermissionState {
+        self.decisions
+            .get(origin)
+            .and_then(|kinds| kinds.get(&kind))
+            .or_else(|| self.defaults.get(&kind))
+            .copied()
+            .unwrap_or_else(|| kind.default_state())
+    }
+
+    /// Store a decision for `origin`; `Prompt` forgets it. Opaque origins
+    /// ("null") never keep decisions.
+    pub(crate) fn set(&mut self, origin: &str, kind: PermissionKind, state: PermissionState) {
+        if origin == "null" {
+            return;
+        }
+        if state == PermissionState::Prompt {
+            if let Some(kinds) = self.decisions.get_mut(origin) {
+                kinds.remove(&kind);
+                if kinds.is_empty() {
+                    self.decisions.remove(origin);
+                }
+            }
+        } else {
+            self.decisions
+                .entry(origin.to_string())
+                .or_default()
+                .insert(kind, state);
+        }
+        self.save();
+    }
+
+    /// Forget the decisions of `origin`, or of every origin.
+    pub(crate) fn clear(&mut self, origin: Option<&str>) {
+        match origin {
+            Some(origin) => {
+                self.decisions.remove(origin);
+            }
+            None => self.decisions.clear(),
+        }
+        self.save();
+    }
+
+    /// `(name, state)` of every kind for `origin`, as pages see them.
+    pub(crate) fn states_for(&self, origin: &str) -> Vec<(&'static str, &'static str)> {
+        PermissionKind::ALL
+            .iter()
+            .map(|&kind| (kind.name(), self.state(origin, kind).as_str()))
+            .collect()
+    }
+
+    /// Park a request until the host decides.
+    pub(crate) fn ask(&mut self, pending: PendingPermission) -> PermissionRequestId {
+        let request_id = PermissionRequestId::new();
+        self.pending.insert(request_id, pending);
+        request_id
+    }
+
+    /// Take a parked request back.
+    pub(crate) fn take(&mut self, request_id: PermissionRequestId) -> Option<PendingPermission> {
+        self.pending.remove(&request_id)
+    }
+
+    /// Drop the requests of a closed view.
+    pub(crate) fn forget_view(&mut self, view_id: EngineViewId) {
+        self.pending.retain(|_, p| p.view_id != view_id);
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&self.decisions)
+            .map_err(std::io::Error::from)
+            .and_then(|json| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, json)
+            });
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Saving permission decisions failed");
+        }
+    }
+}
+
+/// The permission a clipboard call needs, or `None` if user activation
+/// already allows it.
+pub(crate) fn clipboard_permission(call: &ClipboardRequest) -> Option<PermissionKind> {
+    match call.op {
+        ClipboardOp::Read => Some(PermissionKind::ClipboardRead),
+        ClipboardOp::Write(_) if call.user_activated => None,
+        ClipboardOp::Write(_) => Some(PermissionKind::ClipboardWrite),
+    }
+}
+
+/// Carry out an allowed clipboard call.
+pub(crate) fn run_clipboard_call(
+    clipboard: &dyn Clipboard,
+    op: &ClipboardOp,
+) -> Result<ClipboardData, ClipboardError> {
+    match op {
+        ClipboardOp::Read => clipboard
+            .read()
+            .map(|contents| ClipboardData {
+                text: contents.text,
+                html: contents.html,
+            })
+            .map_err(|e| {
+                warn!(error = %e, "Clipboard read failed");
+                ClipboardError::Unavailable
+            }),
+        ClipboardOp::Write(data) => clipboard
+            .write(&ClipboardContents {
+                text: data.text.clone(),
+                html: data.html.clone(),
+            })
+            .map(|()| ClipboardData::default())
+            .map_err(|e| {
+                warn!(error = %e, "Clipboard write failed");
+                ClipboardError::Unavailable
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_viewhost::MemoryClipboard;
+
+    #[test]
+    fn test_decisions_persist_across_brokers() {
+        let dir = std::env::temp_dir().join(format!("rustkit-permissions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let origin = "https://app.example";
+
+        let mut broker = PermissionBroker::new(HashMap::new(), Some(&dir));
+        assert_eq!(
+            broker.state(origin, PermissionKind::ClipboardRead),
+            PermissionState::Prompt
+        );
+        assert_eq!(
+            broker.state(origin, PermissionKind::Popups),
+            PermissionState::Granted
+        );
+        broker.set(
+            origin,
+            PermissionKind::ClipboardRead,
+            PermissionState::Granted,
+        );
+        broker.set(
+            "null",
+            PermissionKind::ClipboardRead,
+            PermissionState::Granted,
+        );
+
+        let defaults = HashMap::from([(PermissionKind::Popups, PermissionState::Denied)]);
+        let mut restarted = PermissionBroker::new(defaults, Some(&dir));
+        assert_eq!(
+            restarted.state(origin, PermissionKind::ClipboardRead),
+            PermissionState::Granted
+        );
+        assert_eq!(
+            restarted.state("null", PermissionKind::ClipboardRead),
+            PermissionState::Prompt
+        );
+        assert_eq!(
+            restarted.state(origin, PermissionKind::Popups),
+            PermissionState::Denied
+        );
+        assert!(restarted
+            .states_for(origin)
+            .contains(&("clipboard-read", "granted")));
+
+        restarted.clear(Some(origin));
+        assert_eq!(
+            PermissionBroker::new(HashMap::new(), Some(&dir))
+                .state(origin, PermissionKind::ClipboardRead),
+            PermissionState::Prompt
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_only_activated_writes_skip_the_prompt() {
+        let call = |op, user_activated| ClipboardRequest {
+            id: 1,
+            op,
+            user_activated,
+        };
+        let write = ClipboardOp::Write(ClipboardData {
+            text: Some("hi".into()),
+            html: None,
+        });
+        assert_eq!(clipboard_permission(&call(write.clone(), true)), None);
+        assert_eq!(
+            clipboard_permission(&call(write.clone(), false)),
+            Some(PermissionKind::ClipboardWrite)
+        );
+        assert_eq!(
+            clipboard_permission(&call(ClipboardOp::Read, true)),
+            Some(PermissionKind::ClipboardRead)
+        );
+
+        let clipboard = MemoryClipboard::new();
+        run_clipboard_call(&clipboard, &write).unwrap();
+        assert_eq!(
+            run_clipboard_call(&clipboard, &ClipboardOp::Read)
+                .unwrap()
+                .text
+                .as_deref(),
+            Some("hi")
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Preload scanning of documents as they download.
+//!
+//! A tokenizer of its own, separate from the HTML parser, that looks only
+//! for stylesheets, scripts, images and `<link rel=preload>` in the bytes
+//! received so far, so they can be fetched while the rest of the document
+//! is still arriving. Tags split across chunks are carried over to the next
+//! one. URLs found before the base URL is settled — by a `<base href>`, the
+//! end of the head, or the end of the document — are held back until it is.
+//! A `<meta http-equiv=Content-Security-Policy>` stops the scan, since the
+//! policy may forbid what it would fetch.
+
+use rustkit_image::loader::{parse_srcset, select_srcset_entry};
+use rustkit_net::{HintKind, PreloadDestination, ResourceHint};
+use std::collections::HashSet;
+use url::Url;
+
+/// Elements whose contents are text, not markup.
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "textarea", "title"];
+
+/// Elements that may appear in the head; any other start tag ends it.
+const HEAD_ELEMENTS: [&str; 10] = [
+    "html", "head", "base", "link", "meta", "script", "style", "title", "noscript", "template",
+];
+
+/// A subresource found before the base URL was settled.
+struct Candidate {
+    href: String,
+    destination: PreloadDestination,
+    anonymous: bool,
+}
+
+/// Finds subresources in a document's bytes as they arrive.
+pub(crate) struct PreloadScanner {
+    base: Url,
+    base_settled: bool,
+    device_pixel_ratio: f64,
+    viewport_width: u32,
+    /// Unconsumed bytes from the end of the last chunk.
+    pending: Vec<u8>,
+    /// The raw text element whose end tag is awaited.
+    raw_text: Option<&'static str>,
+    deferred: Vec<Candidate>,
+    seen: HashSet<Url>,
+    stopped: bool,
+}
+
+impl PreloadScanner {
+    /// A scanner for the document at `document_url`, choosing `srcset`
+    /// candidates for a viewport `viewport_width` CSS pixels wide.
+    pub(crate) fn new(document_url: Url, device_pixel_ratio: f64, viewport_width: u32) -> Self {
+        Self {
+            base: document_url,
+            base_settled: false,
+            device_pixel_ratio,
+            viewport_width,
+            pending: Vec::new(),
+            raw_text: None,
+            deferred: Vec::new(),
+            seen: HashSet::new(),
+            stopped: false,
+        }
+    }
+
+    /// Scan the next chunk of the document, returning the subresources
+    /// that can now be fetched.
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<ResourceHint> {
+        if self.stopped {
+            return Vec::new();
+        }
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(chunk);
+        let mut found = Vec::new();
+        let consumed = self.scan(&input, &mut found);
+        self.pending = input[consumed..].to_vec();
+        found
+    }
+
+    /// The end of the document: release everything still held back.
+    pub(crate) fn finish(&mut self) -> Vec<ResourceHint> {
+        self.pending.clear();
+        let mut found = Vec::new();
+        if !self.stopped {
+            self.settle_base(&mut found);
+        }
+        found
+    }
+
+    /// Scan `input`, returning how much of it was consumed.
+    fn scan(&mut self, input: &[u8], found: &mut Vec<ResourceHint>) -> usize {
+        let mut position = 0;
+        loop {
+            if self.stopped {
+                return input.len();
+            }
+            if let Some(element) = self.raw_text {
+                let end_tag = format!("</{}", element);
+                match find_ignore_case(&input[position..], end_tag.as_bytes()) {
+                    Some(offset) => {
+                        position += offset + end_tag.len();
+                        self.raw_text = None;
+                    }
+                    // Keep enough to match an end tag split across chunks
+                    None => return input.len().saturating_sub(end_tag.len() - 1).max(position),
+                }
+                continue;
+            }
+
+            let Some(offset) = input[position..].iter().position(|&b| b == b'<') else {
+                return input.len();
+            };
+            position += offset;
+            let rest = &input[position + 1..];
+            match rest.first() {
+                None => return position,
+                Some(b'!') => {
+                    let (terminator, skip): (&[u8], usize) = if rest.starts_with(b"!--") {
+                        (b"-->", 3)
+                    } else if b"!--".starts_with(rest) {
+                        return position;
+                    } else {
+                        (b">", 1)
+                    };
+                    match find(&rest[skip..], terminator) {
+                        Some(end) => position += 1 + skip + end + terminator.len(),
+                        None => return position,
+                    }
+                }
+                Some(b'?') => match rest.iter().position(|&b| b == b'>') {
+                    Some(end) => position += end + 2,
+                    None => return position,
+                },
+                Some(b'/') => {
+                    let Some(end) = rest.iter().position(|&b| b == b'>') else {
+                        return position;
+                    };
+                    let name = tag_name(&rest[1..end]);
+                    if name == "head" {
+                        self.settle_base(found);
+                    }
+                    position += end + 2;
+                }
+                Some(b) if b.is_ascii_alphabetic() => {
+                    let Some((tag, length)) = parse_start_tag(rest) else {
+                        return position;
+                    };
+                    position += 1 + length;
+                    self.start_tag(&tag, found);
+                }
+                Some(_) => position += 1,
+            }
+        }
+    }
+
+    fn start_tag(&mut self, tag: &StartTag, found: &mut Vec<ResourceHint>) {
+        if !self.base_settled && !HEAD_ELEMENTS.contains(&tag.name.as_str()) {
+            self.settle_base(found);
+        }
+        self.raw_text = RAW_TEXT_ELEMENTS
+            .iter()
+            .copied()
+            .find(|element| *element == tag.name);
+
+        let anonymous = tag
+            .attribute("crossorigin")
+            .is_some_and(|value| !value.trim().eq_ignore_ascii_case("use-credentials"));
+        match tag.name.as_str() {
+            "base" if !self.base_settled => {
+                if let Some(base) = tag
+                    .attribute("href")
+                    .and_then(|href| self.base.join(href.trim()).ok())
+                {
+                    self.base = base;
+                    self.settle_base(found);
+                }
+            }
+            "meta" => {
+                let policy = tag.attribute("http-equiv").is_some_and(|name| {
+                    name.trim().eq_ignore_ascii_case("content-security-policy")
+                });
+                if policy {
+                    self.stopped = true;
+                    self.deferred.clear();
+                }
+            }
+            "link" => {
+                let Some(href) = tag.attribute("href") else {
+                    return;
+                };
+                let rel = tag.attribute("rel").unwrap_or("").to_ascii_lowercase();
+                let rel: Vec<&str> = rel.split_whitespace().collect();
+                let destination = if rel.contains(&"stylesheet") && !rel.contains(&"alternate") {
+                    Some(PreloadDestination::Style)
+                } else if rel.contains(&"preload") {
+                    tag.attribute("as").and_then(PreloadDestination::parse)
+                } else {
+                    None
+                };
+                if let Some(destination) = destination {
+                    self.found(href, destination, anonymous, found);
+                }
+            }
+            "script" => {
+                let executable = tag.attribute("type").is_none_or(|kind| {
+                    let kind = kind.trim().to_ascii_lowercase();
+                    kind.is_empty() || kind == "module" || kind.contains("javascript")
+                });
+                if let Some(src) = tag.attribute("src").filter(|_| executable) {
+                    self.found(src, PreloadDestination::Script, anonymous, found);
+                }
+            }
+            // Lazy images wait for layout to find them near the viewport
+            "img"
+                if tag
+                    .attribute("loading")
+                    .is_some_and(|loading| loading.trim().eq_ignore_ascii_case("lazy")) => {}
+            "img" => {
+                let entries = tag
+                    .attribute("srcset")
+                    .map(parse_srcset)
+                    .unwrap_or_default();
+                let selected =
+                    select_srcset_entry(&entries, self.viewport_width, self.device_pixel_ratio)
+                        .map(|entry| entry.url.clone());
+                if let Some(src) = selected.as_deref().or(tag.attribute("src")) {
+                    self.found(src, PreloadDestination::Image, anonymous, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn found(
+        &mut self,
+        href: &str,
+        destination: PreloadDestination,
+        anonymous: bool,
+        found: &mut Vec<ResourceHint>,
+    ) {
+        let candidate = Candidate {
+            href: href.trim().to_string(),
+            destination,
+            anonymous,
+        };
+        if self.base_settled {
+            found.extend(self.resolve(candidate));
+        } else {
+            self.deferred.push(candidate);
+        }
+    }
+
+    fn settle_base(&mut self, found: &mut Vec<ResourceHint>) {
+        self.base_settled = true;
+        for candidate in std::mem::take(&mut self.deferred) {
+            found.extend(self.resolve(candidate));
+        }
+    }
+
+    /// A preload for a candidate not seen before, if it is fetchable.
+    fn resolve(&mut self, candidate: Candidate) -> Option<ResourceHint> {
+        let url = self.base.join(&candidate.href).ok()?;
+        if !matches!(url.scheme(), "http" | "https") || !self.seen.insert(url.clone()) {
+            return None;
+        }
+        Some(ResourceHint {
+            kind: HintKind::Preload,
+            url,
+            anonymous: candidate.anonymous,
+            destination: Some(candidate.destination),
+        })
+    }
+}
+
+/// A start tag's lowercased name and its attributes, first of each name.
+struct StartTag {
+    name: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl StartTag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse a start tag following its `<`, returning it and its length up to
+/// and including the `>`, or `None` if the input ends first.
+fn parse_start_tag(input: &[u8]) -> Option<(StartTag, usize)> {
+    let is_space = |b: u8| b.is_ascii_whitespace();
+    let name_end = input
+        .iter()
+        .position(|&b| is_space(b) || b == b'>' || b == b'/')?;
+    let mut tag = StartTag {
+        name: tag_name(&input[..name_end]),
+        attributes: Vec::new(),
+    };
+    let mut position = name_end;
+    loop {
+        while position < input.len() && (is_space(input[position]) || input[position] == b'/') {
+            position += 1;
+        }
+        if *input.get(position)? == b'>' {
+            return Some((tag, position + 1));
+        }
+
+        let name_start = position;
+        position += 1;
+        while position < input.len()
+            && !is_space(input[position])
+            && !matches!(input[position], b'=' | b'>' | b'/')
+        {
+            position += 1;
+        }
+        let name = String::from_utf8_lossy(&input[name_start..position]).to_ascii_lowercase();
+        while position < input.len() && is_space(input[position]) {
+            position += 1;
+        }
+
+        let mut value = String::new();
+        if *input.get(position)? == b'=' {
+            position += 1;
+            while position < input.len() && is_space(input[position]) {
+                position += 1;
+            }
+            let raw = match *input.get(position)? {
+                quote @ (b'"' | b'\'') => {
+                    let length = input[position + 1..].iter().position(|&b| b == quote)?;
+                    let raw = &input[position + 1..position + 1 + length];
+                    position += length + 2;
+                    raw
+                }
+                _ => {
+                    let start = position;
+                    while position < input.len()
+                        && !is_space(input[position])
+                        && input[position] != b'>'
+                    {
+                        position += 1;
+                    }
+                    if position == input.len() {
+                        return None;
+                    }
+                    &input[start..position]
+                }
+            };
+            value = String::from_utf8_lossy(raw).replace("&amp;", "&");
+        }
+        if tag.attribute(&name).is_none() {
+            tag.attributes.push((name, value));
+        }
+    }
+}
+
+fn tag_name(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_ascii_lowercase()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_in_chunks(html: &str, chunk_size: usize, dpr: f64) -> Vec<String> {
+        let mut scanner = PreloadScanner::new(
+            Url::parse("https://example.com/dir/page.html").unwrap(),
+            dpr,
+            800,
+        );
+        let mut hints = Vec::new();
+        for chunk in html.as_bytes().chunks(chunk_size) {
+            hints.extend(scanner.push(chunk));
+        }
+        hints.extend(scanner.finish());
+        hints.into_iter().map(|hint| hint.url.to_string()).collect()
+    }
+
+    #[test]
+    fn test_finds_subresources_across_chunk_boundaries() {
+        let html = r#"<!DOCTYPE html><html><head>
+            <link rel="stylesheet" href="a.css">
+            <link rel=preload as=font href="/f.woff2" crossorigin>
+            <link rel="alternate stylesheet" href="alt.css">
+            <!-- <script src="commented.js"></script> -->
+            <script src='app.js?x=1&amp;y=2'></script>
+            <script>var s = "<img src=inline.png>";</script>
+            <script type="text/template" src="template.js"></script>
+            </head><body><img src="a.png" alt="a > b">
+            <textarea><img src="text.png"></textarea>
+            <img src="a.png"><img src="data:image/png;base64,AAAA">
+            </body></html>"#;
+        let expected = [
+            "https://example.com/dir/a.css",
+            "https://example.com/f.woff2",
+            "https://example.com/dir/app.js?x=1&y=2",
+            "https://example.com/dir/a.png",
+        ];
+        for chunk_size in [1, 2, 3, 7, 16, html.len()] {
+            assert_eq!(
+                scan_in_chunks(html, chunk_size, 1.0),
+                expected,
+                "{}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_later_base_href_is_honored() {
+        let html = r#"<head><link rel=stylesheet href=a.css><script src=b.js></script>
+            <base href="https://cdn.example.com/assets/"><link rel=stylesheet href=c.css>
+            <base href="https://ignored.example.com/"></head><body><img src=d.png>"#;
+        assert_eq!(
+            scan_in_chunks(html, 5, 1.0),
+            [
+                "https://cdn.example.com/assets/a.css",
+                "https://cdn.example.com/assets/b.js",
+                "https://cdn.example.com/assets/c.css",
+                "https://cdn.example.com/assets/d.png",
+            ]
+        );
+
+        // A policy in the document stops the scan
+        let html = r#"<link rel=stylesheet href=a.css>
+            <meta http-equiv="Content-Security-Policy" content="style-src 'none'">
+            <link rel=stylesheet href=b.css><body><img src=c.png>"#;
+        assert!(scan_in_chunks(html, 6, 1.0).is_empty());
+
+        // Without a base, URLs wait for the head to end
+        let mut scanner =
+            PreloadScanner::new(Url::parse("https://example.com/").unwrap(), 1.0, 800);
+        assert!(scanner.push(b"<link rel=stylesheet href=a.css>").is_empty());
+        assert_eq!(scanner.push(b"<p>").len(), 1);
+        assert_eq!(scanner.push(b"<img src=b.png>").len(), 1);
+        assert!(scanner.push(b"<img src=c.png loading=LAZY>").is_empty());
+    }
+
+    #[test]
+    fn test_srcset_candidate_for_device_pixel_ratio() {
+        let html = r#"<body><img src="small.png" srcset="small.png 1x, large.png 2x">"#;
+        assert_eq!(
+            scan_in_chunks(html, 4, 1.0),
+            ["https://example.com/dir/small.png"]
+        );
+        assert_eq!(
+            scan_in_chunks(html, 4, 2.0),
+            ["https://example.com/dir/large.png"]
+        );
+    }
+}
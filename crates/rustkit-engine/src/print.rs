@@ -0,0 +1,260 @@
+//! Paged capture for printing and PDF export.
+//!
+//! The document is laid out at the width of the page's content area, with
+//! `print` media rules in force when asked for, then cut into pages by
+//! [`rustkit_layout::paginate`]. Backgrounds are left out unless
+//! [`PrintOptions::print_backgrounds`] is set, as browsers do by default.
+
+use rustkit_css::{Color, MediaEnvironment};
+use rustkit_dom::Document;
+use rustkit_layout::{paginate, LayoutBox, PageSlice};
+
+use crate::Engine;
+
+/// CSS pixels per millimetre.
+const PX_PER_MM: f32 = 96.0 / 25.4;
+
+/// Paper size and margins, in millimetres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSetup {
+    pub width_mm: f32,
+    pub height_mm: f32,
+    pub margin_top_mm: f32,
+    pub margin_right_mm: f32,
+    pub margin_bottom_mm: f32,
+    pub margin_left_mm: f32,
+}
+
+impl PageSetup {
+    /// Default margin on every side.
+    const MARGIN_MM: f32 = 10.0;
+
+    /// ISO A4, 210 x 297 mm.
+    pub fn a4() -> Self {
+        Self::custom_mm(210.0, 297.0)
+    }
+
+    /// US Letter, 8.5 x 11 in.
+    pub fn letter() -> Self {
+        Self::custom_mm(215.9, 279.4)
+    }
+
+    /// Paper of any size, with the default margins.
+    pub fn custom_mm(width_mm: f32, height_mm: f32) -> Self {
+        Self {
+            width_mm,
+            height_mm,
+            margin_top_mm: Self::MARGIN_MM,
+            margin_right_mm: Self::MARGIN_MM,
+            margin_bottom_mm: Self::MARGIN_MM,
+            margin_left_mm: Self::MARGIN_MM,
+        }
+    }
+
+    pub fn with_margins_mm(mut self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.margin_top_mm = top;
+        self.margin_right_mm = right;
+        self.margin_bottom_mm = bottom;
+        self.margin_left_mm = left;
+        self
+    }
+
+    /// Page size in device pixels at 96 DPI.
+    pub fn size_px(&self) -> (u32, u32) {
+        let px = |mm: f32| ((mm * PX_PER_MM).round() as u32).max(1);
+        (px(self.width_mm), px(self.height_mm))
+    }
+
+    /// Left and top margins in CSS pixels.
+    pub(crate) fn content_origin(&self) -> (f32, f32) {
+        (
+            self.margin_left_mm * PX_PER_MM,
+            self.margin_top_mm * PX_PER_MM,
+        )
+    }
+
+    /// Width and height of the area inside the margins, in CSS pixels.
+    pub fn content_size(&self) -> (f32, f32) {
+        let width = self.width_mm - self.margin_left_mm - self.margin_right_mm;
+        let height = self.height_mm - self.margin_top_mm - self.margin_bottom_mm;
+        ((width * PX_PER_MM).max(1.0), (height * PX_PER_MM).max(1.0))
+    }
+}
+
+impl Default for PageSetup {
+    fn default() -> Self {
+        Self::a4()
+    }
+}
+
+/// How [`Engine::capture_full_page`] lays the document out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintOptions {
+    pub page: PageSetup,
+    /// Apply `print` media rules instead of `screen` ones.
+    pub use_print_styles: bool,
+    /// Paint background colors.
+    pub print_backgrounds: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            page: PageSetup::default(),
+            use_print_styles: true,
+            print_backgrounds: false,
+        }
+    }
+}
+
+/// One page of a [`FullPageCapture`].
+#[derive(Debug, Clone)]
+pub struct CapturedPage {
+    /// The part of the document on this page, in CSS pixels from the top
+    /// of the document.
+    pub top: f32,
+    pub height: f32,
+    /// RGBA pixels of the whole sheet, margins included.
+    pub rgba: Vec<u8>,
+}
+
+/// A document rendered page by page.
+#[derive(Debug, Clone)]
+pub struct FullPageCapture {
+    pub page_count: usize,
+    /// Sheet size in pixels, the same for every page.
+    pub width: u32,
+    pub height: u32,
+    pub pages: Vec<CapturedPage>,
+}
+
+/// Lay `document` out for `options` and cut it into pages.
+pub(crate) fn paginate_document(
+    document: &Document,
+    options: &PrintOptions,
+    base: &MediaEnvironment,
+) -> (LayoutBox, Vec<PageSlice>) {
+    let (width, height) = options.page.content_size();
+    let media_type = if options.use_print_styles {
+        "print"
+    } else {
+        "screen"
+    };
+    let media = MediaEnvironment {
+        width,
+        height,
+        media_type: media_type.to_string(),
+        ..base.clone()
+    };
+    let mut root = Engine::build_layout_from_document(document, &media);
+    if !options.print_backgrounds {
+        strip_backgrounds(&mut root);
+    }
+    Engine::lay_out(&mut root, &media);
+    let pages = paginate(&root, height);
+    (root, pages)
+}
+
+fn strip_backgrounds(layout_box: &mut LayoutBox) {
+    layout_box.style.background_color = Color::TRANSPARENT;
+    for child in &mut layout_box.children {
+        strip_backgrounds(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_layout::{DisplayCommand, DisplayList, Rect};
+
+    fn paginated(html: &str, options: &PrintOptions) -> (LayoutBox, Vec<PageSlice>) {
+        let document = Document::parse_html(html).unwrap();
+        paginate_document(&document, options, &MediaEnvironment::default())
+    }
+
+    fn border_box(layout_box: &LayoutBox, document: &Document, id: &str) -> Option<Rect> {
+        let node = document.get_element_by_id(id)?.id;
+        fn walk(b: &LayoutBox, node: rustkit_dom::NodeId) -> Option<Rect> {
+            if b.node_id == Some(node) {
+                return Some(b.dimensions.border_box());
+            }
+            b.children.iter().find_map(|c| walk(c, node))
+        }
+        walk(layout_box, node)
+    }
+
+    #[test]
+    fn test_page_setup_presets() {
+        assert_eq!(PageSetup::a4().size_px(), (794, 1123));
+        assert_eq!(PageSetup::letter().size_px(), (816, 1056));
+        let page = PageSetup::custom_mm(100.0, 50.0).with_margins_mm(0.0, 0.0, 0.0, 0.0);
+        let (width, height) = page.content_size();
+        assert!((width - 377.95).abs() < 0.01 && (height - 188.98).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_break_before_page_starts_a_page_per_heading() {
+        let html = r#"<html><head><style>h2 { break-before: page }</style></head><body>
+            <p>Intro</p>
+            <h2>One</h2><p>First</p>
+            <h2>Two</h2><p>Second</p>
+            <h2>Three</h2><p>Third</p>
+            </body></html>"#;
+        let (_, pages) = paginated(html, &PrintOptions::default());
+        assert_eq!(pages.len(), 4);
+
+        // A legacy property and a screen-only rule
+        let html = r#"<html><head><style>
+            @media screen { h2 { page-break-before: always } }
+            </style></head><body><p>Intro</p><h2>One</h2></body></html>"#;
+        let (_, printed) = paginated(html, &PrintOptions::default());
+        assert_eq!(printed.len(), 1);
+        let screen = PrintOptions {
+            use_print_styles: false,
+            ..PrintOptions::default()
+        };
+        assert_eq!(paginated(html, &screen).1.len(), 2);
+    }
+
+    #[test]
+    fn test_break_inside_avoid_box_stays_on_one_page() {
+        let html = r#"<html><body style="margin: 0">
+            <div style="height: 900px"></div>
+            <div id="kept" style="break-inside: avoid">
+                <div style="height: 100px"></div>
+                <div style="height: 100px"></div>
+                <div style="height: 100px"></div>
+            </div>
+            <div style="height: 900px"></div>
+            </body></html>"#;
+        let document = Document::parse_html(html).unwrap();
+        let (root, pages) = paginate_document(
+            &document,
+            &PrintOptions::default(),
+            &MediaEnvironment::default(),
+        );
+        let kept = border_box(&root, &document, "kept").unwrap();
+        assert!(pages.len() >= 2);
+        for page in &pages {
+            let straddles = kept.y < page.bottom - 0.01 && kept.bottom() > page.bottom + 0.01;
+            assert!(!straddles, "{:?} straddles {:?}", kept, page);
+        }
+    }
+
+    #[test]
+    fn test_backgrounds_only_when_asked_for() {
+        let html = r#"<html><body><div style="background-color: red; height: 10px"></div>
+            </body></html>"#;
+        let red = |options: &PrintOptions| {
+            DisplayList::build(&paginated(html, options).0)
+                .commands
+                .iter()
+                .any(|c| matches!(c, DisplayCommand::SolidColor(color, _) if color.r == 255))
+        };
+        assert!(!red(&PrintOptions::default()));
+        assert!(red(&PrintOptions {
+            print_backgrounds: true,
+            ..PrintOptions::default()
+        }));
+    }
+}
@@ -0,0 +1,393 @@
+//! Profiling captures: a timeline of one view's parse, style, layout,
+//! paint and script work.
+//!
+//! Between [`crate::Engine::start_profiling`] and
+//! [`crate::Engine::stop_profiling`] each phase of the view's work records
+//! when it began, how long it took and what it worked on into a bounded
+//! ring buffer. Without a capture running a phase costs one check of an
+//! empty cell. A [`ProfileCapture`] exports as Chrome Trace Event JSON,
+//! which Perfetto and `chrome://tracing` open directly, and sums itself up
+//! per phase for hosts to show.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+/// Events a capture keeps; older events are dropped past this.
+const CAPACITY: usize = 16 * 1024;
+
+/// A phase of a view's work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    /// Parsing a document's HTML.
+    Parse,
+    /// Cascading styles and building the box tree.
+    Style,
+    /// Laying out the box tree.
+    Layout,
+    /// Building the display list from the laid-out boxes.
+    DisplayList,
+    /// Encoding the display list into GPU work.
+    Render,
+    /// Presenting the rendered frame.
+    Present,
+    /// Running a script.
+    Script,
+    /// Decoding an image.
+    ImageDecode,
+    /// A whole relayout: style through render.
+    Frame,
+}
+
+impl Phase {
+    /// The name the phase shows under in a trace.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Parse => "Parse HTML",
+            Phase::Style => "Style",
+            Phase::Layout => "Layout",
+            Phase::DisplayList => "Display list",
+            Phase::Render => "Render",
+            Phase::Present => "Present",
+            Phase::Script => "Script",
+            Phase::ImageDecode => "Image decode",
+            Phase::Frame => "Frame",
+        }
+    }
+}
+
+/// Whether an event spans a phase or marks a phase that ran over budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Complete,
+    /// An instant at the end of a phase that took longer than
+    /// [`crate::EngineConfig::profile_phase_budget`].
+    OverBudget,
+}
+
+/// One recorded event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEvent {
+    pub phase: Phase,
+    pub kind: EventKind,
+    /// Since the capture started.
+    pub start: Duration,
+    /// Zero for [`EventKind::OverBudget`].
+    pub duration: Duration,
+    /// What the phase worked on, such as box or command counts; `null`
+    /// when there is nothing to tell.
+    pub args: Value,
+}
+
+/// What a capture recorded, in start order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileCapture {
+    pub view_id: u64,
+    /// From start to stop.
+    pub duration: Duration,
+    pub budget: Duration,
+    pub events: Vec<ProfileEvent>,
+    /// Events dropped once the capture filled up.
+    pub dropped: usize,
+}
+
+/// Time spent in one phase of a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseSummary {
+    pub phase: Phase,
+    pub count: usize,
+    /// Including phases nested in it.
+    pub total: Duration,
+    pub max: Duration,
+    pub over_budget: usize,
+}
+
+/// A capture summed up per phase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSummary {
+    pub duration: Duration,
+    /// Phases that ran, in [`Phase`] order.
+    pub phases: Vec<PhaseSummary>,
+    pub dropped: usize,
+}
+
+impl ProfileSummary {
+    /// The summary of `phase`, if it ran.
+    pub fn phase(&self, phase: Phase) -> Option<&PhaseSummary> {
+        self.phases.iter().find(|p| p.phase == phase)
+    }
+}
+
+impl ProfileCapture {
+    /// The capture in the Chrome Trace Event format, with the engine's
+    /// process as `pid` and the view as `tid`.
+    pub fn to_chrome_trace(&self) -> Value {
+        let pid = std::process::id();
+        let tid = self.view_id;
+        let micros = |d: Duration| d.as_secs_f64() * 1e6;
+        let metadata = |name: &str, value: String| {
+            json!({
+                "name": name,
+                "ph": "M",
+                "pid": pid,
+                "tid": tid,
+                "args": { "name": value },
+            })
+        };
+        let mut trace = vec![
+            metadata("process_name", "RustKit".into()),
+            metadata("thread_name", format!("View {}", self.view_id)),
+        ];
+        for event in &self.events {
+            let mut entry = match event.kind {
+                EventKind::Complete => json!({
+                    "name": event.phase.name(),
+                    "cat": "rustkit",
+                    "ph": "X",
+                    "ts": micros(event.start),
+                    "dur": micros(event.duration),
+                    "pid": pid,
+                    "tid": tid,
+                }),
+                EventKind::OverBudget => json!({
+                    "name": format!("{} over budget", event.phase.name()),
+                    "cat": "rustkit",
+                    "ph": "i",
+                    "s": "t",
+                    "ts": micros(event.start),
+                    "pid": pid,
+                    "tid": tid,
+                }),
+            };
+            if !event.args.is_null() {
+                entry["args"] = event.args.clone();
+            }
+            trace.push(entry);
+        }
+        json!({
+            "traceEvents": trace,
+            "displayTimeUnit": "ms",
+            "otherData": { "droppedEvents": self.dropped },
+        })
+    }
+
+    /// Count and time per phase.
+    pub fn summary(&self) -> ProfileSummary {
+        let mut phases: Vec<PhaseSummary> = Vec::new();
+        for event in &self.events {
+            let index = match phases.iter().position(|p| p.phase == event.phase) {
+                Some(index) => index,
+                None => {
+                    phases.push(PhaseSummary {
+                        phase: event.phase,
+                        count: 0,
+                        total: Duration::ZERO,
+                        max: Duration::ZERO,
+                        over_budget: 0,
+                    });
+                    phases.len() - 1
+                }
+            };
+            let summary = &mut phases[index];
+            match event.kind {
+                EventKind::Complete => {
+                    summary.count += 1;
+                    summary.total += event.duration;
+                    summary.max = summary.max.max(event.duration);
+                }
+                EventKind::OverBudget => summary.over_budget += 1,
+            }
+        }
+        phases.sort_by_key(|p| p.phase);
+        ProfileSummary {
+            duration: self.duration,
+            phases,
+            dropped: self.dropped,
+        }
+    }
+}
+
+/// A view's capture, while one runs.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    recording: RefCell<Option<Recording>>,
+}
+
+struct Recording {
+    started: Instant,
+    budget: Duration,
+    events: VecDeque<ProfileEvent>,
+    dropped: usize,
+}
+
+impl Recording {
+    fn push(&mut self, event: ProfileEvent) {
+        if self.events.len() == CAPACITY {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+}
+
+impl Profiler {
+    /// Start a capture, discarding any already running.
+    pub(crate) fn start(&self, budget: Duration) {
+        *self.recording.borrow_mut() = Some(Recording {
+            started: Instant::now(),
+            budget,
+            events: VecDeque::new(),
+            dropped: 0,
+        });
+    }
+
+    /// Stop the capture of the view `view_id`; empty if none ran.
+    pub(crate) fn stop(&self, view_id: u64) -> ProfileCapture {
+        let Some(recording) = self.recording.borrow_mut().take() else {
+            return ProfileCapture {
+                view_id,
+                duration: Duration::ZERO,
+                budget: Duration::ZERO,
+                events: Vec::new(),
+                dropped: 0,
+            };
+        };
+        let mut events = Vec::from(recording.events);
+        // Outer phases first where they start together
+        events.sort_by(|a, b| a.start.cmp(&b.start).then(b.duration.cmp(&a.duration)));
+        ProfileCapture {
+            view_id,
+            duration: recording.started.elapsed(),
+            budget: recording.budget,
+            events,
+            dropped: recording.dropped,
+        }
+    }
+
+    /// Now, if a capture is running and a phase beginning now should be
+    /// timed.
+    pub(crate) fn begin(&self) -> Option<Instant> {
+        self.recording.borrow().as_ref().map(|_| Instant::now())
+    }
+
+    /// Record a phase that [`Self::begin`] timed from `began`. `args` is
+    /// only worked out while capturing.
+    pub(crate) fn end(&self, phase: Phase, began: Option<Instant>, args: impl FnOnce() -> Value) {
+        let Some(began) = began else {
+            return;
+        };
+        let ended = Instant::now();
+        let mut recording = self.recording.borrow_mut();
+        // Stopped while the phase ran
+        let Some(recording) = recording.as_mut() else {
+            return;
+        };
+        let start = began.saturating_duration_since(recording.started);
+        let duration = ended.duration_since(began);
+        recording.push(ProfileEvent {
+            phase,
+            kind: EventKind::Complete,
+            start,
+            duration,
+            args: args(),
+        });
+        if duration > recording.budget {
+            let budget = recording.budget;
+            recording.push(ProfileEvent {
+                phase,
+                kind: EventKind::OverBudget,
+                start: start + duration,
+                duration: Duration::ZERO,
+                args: json!({
+                    "durationMs": duration.as_secs_f64() * 1e3,
+                    "budgetMs": budget.as_secs_f64() * 1e3,
+                }),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(phase: Phase, start_ms: u64, duration_ms: u64) -> ProfileEvent {
+        ProfileEvent {
+            phase,
+            kind: EventKind::Complete,
+            start: Duration::from_millis(start_ms),
+            duration: Duration::from_millis(duration_ms),
+            args: Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_disabled_records_nothing() {
+        let profiler = Profiler::default();
+        let began = profiler.begin();
+        assert!(began.is_none());
+        profiler.end(Phase::Layout, began, || {
+            unreachable!("args built while off")
+        });
+        assert!(profiler.stop(1).events.is_empty());
+    }
+
+    #[test]
+    fn test_over_budget_marks_instant() {
+        let profiler = Profiler::default();
+        profiler.start(Duration::ZERO);
+        let began = profiler.begin();
+        std::thread::sleep(Duration::from_millis(1));
+        profiler.end(Phase::Script, began, || json!({ "url": "a.js" }));
+        let capture = profiler.stop(1);
+        let kinds: Vec<_> = capture.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [EventKind::Complete, EventKind::OverBudget]);
+        assert_eq!(
+            capture.summary().phase(Phase::Script).unwrap().over_budget,
+            1
+        );
+    }
+
+    #[test]
+    fn test_chrome_trace_and_summary() {
+        let mut layout = event(Phase::Layout, 2, 3);
+        layout.args = json!({ "boxes": 12 });
+        let capture = ProfileCapture {
+            view_id: 7,
+            duration: Duration::from_millis(20),
+            budget: Duration::from_millis(16),
+            events: vec![
+                event(Phase::Frame, 1, 10),
+                layout,
+                event(Phase::DisplayList, 5, 2),
+                event(Phase::Layout, 12, 5),
+            ],
+            dropped: 0,
+        };
+
+        let trace = capture.to_chrome_trace();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let complete: Vec<_> = events.iter().filter(|e| e["ph"] == "X").collect();
+        assert_eq!(complete.len(), 4);
+        for event in &complete {
+            for field in ["name", "ph", "ts", "dur", "pid", "tid"] {
+                assert!(event.get(field).is_some(), "missing {field}");
+            }
+            assert_eq!(event["tid"], 7);
+        }
+        assert_eq!(complete[1]["name"], "Layout");
+        assert_eq!(complete[1]["ts"], 2000.0);
+        assert_eq!(complete[1]["dur"], 3000.0);
+        assert_eq!(complete[1]["args"]["boxes"], 12);
+
+        let summary = capture.summary();
+        let layout = summary.phase(Phase::Layout).unwrap();
+        assert_eq!(layout.count, 2);
+        assert_eq!(layout.total, Duration::from_millis(8));
+        assert_eq!(layout.max, Duration::from_millis(5));
+        let order: Vec<_> = summary.phases.iter().map(|p| p.phase).collect();
+        assert_eq!(order, [Phase::Layout, Phase::DisplayList, Phase::Frame]);
+    }
+}
@@ -0,0 +1,890 @@
+//! Reader mode: a page's main article in a plain layout.
+//!
+//! Extraction works like Readability. Each paragraph scores the containers
+//! it sits in by its length and commas; containers lose score for the
+//! share of their text that is links, and the best one, with the siblings
+//! that score close to it, holds the article. Navigation, sidebars, footers
+//! and ad-like blocks are dropped, and the rest is rebuilt from headings,
+//! paragraphs, figures, lists, quotes and code, so none of the page's
+//! styles or scripts come along. Images keep their URLs and `loading`, and
+//! load like any page's.
+//!
+//! The page stays loaded underneath: its document, script context and
+//! layout are set aside while the article shows, and put back as they were
+//! on exit.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rustkit_bindings::{DomBindings, FocusManager, GeometryMap};
+use rustkit_dom::{Document, Node, NodeId, NodeType};
+use rustkit_layout::{DisplayList, LayoutBox};
+use rustkit_viewhost::Bounds;
+use url::Url;
+
+use crate::audio::MediaPlayback;
+use crate::error_page::escape_html;
+use crate::lazy_load::LazyLoads;
+use crate::transitions::Transitions;
+use crate::ChildFrame;
+
+/// Words read per minute, for [`ReaderArticle::reading_minutes`].
+const WORDS_PER_MINUTE: usize = 230;
+
+/// Shortest paragraph that scores its containers, in characters.
+const MIN_PARAGRAPH_LEN: usize = 25;
+
+/// Least text an article needs, in characters.
+const MIN_ARTICLE_LEN: usize = 250;
+
+/// Elements dropped with everything in them.
+const DROPPED: &[&str] = &[
+    "aside", "button", "canvas", "embed", "footer", "form", "iframe", "input", "nav", "noscript",
+    "object", "script", "select", "style", "svg", "template", "textarea",
+];
+
+/// ARIA roles of page furniture.
+const DROPPED_ROLES: &[&str] = &[
+    "banner",
+    "complementary",
+    "contentinfo",
+    "dialog",
+    "navigation",
+];
+
+/// Class and id words of page furniture, matched against the start of each
+/// word; `ad` and `ads` must match whole.
+const UNLIKELY: &[&str] = &[
+    "advert",
+    "author",
+    "banner",
+    "breadcrumb",
+    "byline",
+    "comment",
+    "cookie",
+    "footer",
+    "menu",
+    "meta",
+    "nav",
+    "newsletter",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+    "subscribe",
+    "widget",
+];
+
+/// Class and id words of article content.
+const LIKELY: &[&str] = &[
+    "article", "body", "content", "entry", "main", "post", "story", "text",
+];
+
+/// Block-level elements; a `div` without any holds a paragraph.
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements kept as they are, without attributes.
+const KEPT: &[&str] = &[
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "em",
+    "figcaption",
+    "figure",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// An article taken from a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReaderArticle {
+    /// From `og:title`, the page's `<h1>` or its `<title>`.
+    pub title: String,
+    pub byline: Option<String>,
+    /// When the article was published, as the page gives it.
+    pub published: Option<String>,
+    pub word_count: usize,
+    /// Estimated reading time, at least a minute.
+    pub reading_minutes: usize,
+    /// Paragraphs in the article.
+    pub paragraphs: usize,
+    /// Cleaned markup of the article body.
+    pub content: String,
+}
+
+/// Colors of the reader page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReaderTheme {
+    #[default]
+    Light,
+    Sepia,
+    Dark,
+}
+
+impl ReaderTheme {
+    /// Background, text, secondary text and link colors.
+    fn colors(self) -> [&'static str; 4] {
+        match self {
+            Self::Light => ["#ffffff", "#1b1b1b", "#6b6b6b", "#0b57d0"],
+            Self::Sepia => ["#f4ecd8", "#5b4636", "#8a7560", "#8a4b12"],
+            Self::Dark => ["#1c1b22", "#eeeeee", "#a8a8b3", "#8ab4f8"],
+        }
+    }
+}
+
+/// How reader pages look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReaderPreferences {
+    /// Body text size in CSS pixels.
+    pub font_size: f32,
+    pub theme: ReaderTheme,
+    /// Widest the text column gets, in CSS pixels.
+    pub content_width: f32,
+}
+
+impl Default for ReaderPreferences {
+    fn default() -> Self {
+        Self {
+            font_size: 20.0,
+            theme: ReaderTheme::default(),
+            content_width: 680.0,
+        }
+    }
+}
+
+/// A view's page while its article shows.
+pub(crate) struct SavedPage {
+    pub(crate) document: Option<Rc<Document>>,
+    pub(crate) bindings: Option<DomBindings>,
+    pub(crate) layout: Option<LayoutBox>,
+    pub(crate) display_list: Option<DisplayList>,
+    pub(crate) geometry: Rc<GeometryMap>,
+    pub(crate) media_matches: Vec<Vec<bool>>,
+    pub(crate) focused_node: Option<NodeId>,
+    pub(crate) focus: FocusManager,
+    pub(crate) lazy_loads: LazyLoads,
+    pub(crate) transitions: Transitions,
+    pub(crate) frames: Vec<ChildFrame>,
+    pub(crate) media: HashMap<u64, MediaPlayback>,
+    /// Viewport the layout was made for.
+    pub(crate) bounds: Option<Bounds>,
+}
+
+/// A view in reader mode.
+pub(crate) struct ReaderState {
+    pub(crate) article: ReaderArticle,
+    pub(crate) page: SavedPage,
+}
+
+fn element_is(node: &Node, tags: &[&str]) -> bool {
+    node.tag_name()
+        .is_some_and(|tag| tags.iter().any(|t| tag.eq_ignore_ascii_case(t)))
+}
+
+fn elements(node: &Node) -> impl Iterator<Item = Rc<Node>> {
+    node.children()
+        .into_iter()
+        .filter(|child| child.is_element())
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whitespace-collapsed text of `node`.
+fn inner_text(node: &Node) -> String {
+    collapse_whitespace(&node.text_content())
+}
+
+/// The words of an element's `class` and `id`, lowercased.
+fn class_words(node: &Node) -> Vec<String> {
+    let names = [node.get_attribute("class"), node.get_attribute("id")];
+    names
+        .into_iter()
+        .flatten()
+        .flat_map(|names| names.split(|c: char| !c.is_ascii_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+/// +25 for content-like class and id words, -25 for furniture-like ones.
+fn class_weight(node: &Node) -> f32 {
+    let words = class_words(node);
+    let mut weight = 0.0;
+    if words.iter().any(|w| LIKELY.contains(&w.as_str())) {
+        weight += 25.0;
+    }
+    if words
+        .iter()
+        .any(|w| w == "ad" || w == "ads" || UNLIKELY.iter().any(|u| w.starts_with(u)))
+    {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// Whether `node` is page furniture rather than article content.
+fn is_furniture(node: &Node) -> bool {
+    if element_is(node, DROPPED) {
+        return true;
+    }
+    if node
+        .get_attribute("role")
+        .is_some_and(|role| DROPPED_ROLES.contains(&role.trim().to_ascii_lowercase().as_str()))
+    {
+        return true;
+    }
+    if node.get_attribute("hidden").is_some() || node.get_attribute("aria-hidden") == Some("true") {
+        return true;
+    }
+    !element_is(node, &["body", "article", "main"]) && class_weight(node) < 0.0
+}
+
+/// Share of `node`'s text that is inside links.
+fn link_density(node: &Node) -> f32 {
+    let length = inner_text(node).len();
+    if length == 0 {
+        return 0.0;
+    }
+    let mut linked = 0;
+    let mut stack = vec![node.children()];
+    while let Some(children) = stack.pop() {
+        for child in children {
+            if element_is(&child, &["a"]) {
+                linked += inner_text(&child).len();
+            } else {
+                stack.push(child.children());
+            }
+        }
+    }
+    linked as f32 / length as f32
+}
+
+/// Whether `node` holds a paragraph: a `p` or `pre`, or a `div` with no
+/// block-level children.
+fn is_paragraph(node: &Node) -> bool {
+    element_is(node, &["p", "pre"])
+        || (element_is(node, &["div"]) && !elements(node).any(|child| element_is(&child, BLOCKS)))
+}
+
+/// Score a container starts with, by what kind of element it is.
+fn base_score(node: &Node) -> f32 {
+    let tag = node.tag_name().unwrap_or_default().to_ascii_lowercase();
+    let score = match tag.as_str() {
+        "article" => 10.0,
+        "div" | "main" => 5.0,
+        "blockquote" | "pre" | "section" | "td" => 3.0,
+        "address" | "dd" | "dl" | "dt" | "li" | "ol" | "ul" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    score + class_weight(node)
+}
+
+/// Whether the page looks like it has an article, cheaply enough to check
+/// every page: the paragraphs of at least 140 characters outside page
+/// furniture add up past a threshold, longer ones counting more.
+pub(crate) fn is_probably_readable(document: &Document) -> bool {
+    let Some(body) = document.body() else {
+        return false;
+    };
+    let mut score = 0.0;
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        for child in elements(&node) {
+            if is_furniture(&child) {
+                continue;
+            }
+            if element_is(&child, &["p", "pre", "article"]) {
+                let length = inner_text(&child).len();
+                if length >= 140 {
+                    score += ((length - 140) as f32).sqrt();
+                    if score > 20.0 {
+                        return true;
+                    }
+                }
+            }
+            stack.push(child);
+        }
+    }
+    false
+}
+
+/// Paragraphs of `root` outside page furniture, in document order.
+fn paragraphs(root: &Rc<Node>) -> Vec<Rc<Node>> {
+    let mut found = Vec::new();
+    let mut stack = vec![root.clone()];
+    while let Some(node) = stack.pop() {
+        let children: Vec<_> = elements(&node).filter(|c| !is_furniture(c)).collect();
+        for child in children.into_iter().rev() {
+            if is_paragraph(&child) {
+                found.push(child.clone());
+            }
+            if !element_is(&child, &["p", "pre"]) {
+                stack.push(child);
+            }
+        }
+    }
+    found
+}
+
+/// The container holding the article, and the score a sibling needs to
+/// be part of it.
+fn top_candidate(body: &Rc<Node>) -> Option<(Rc<Node>, f32)> {
+    let mut scores: HashMap<NodeId, (Rc<Node>, f32)> = HashMap::new();
+    for paragraph in paragraphs(body) {
+        let text = inner_text(&paragraph);
+        if text.len() < MIN_PARAGRAPH_LEN {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f32 + (text.len() / 100).min(3) as f32;
+        let mut ancestor = paragraph.parent();
+        for level in 0..3 {
+            let Some(node) = ancestor.filter(|n| n.is_element()) else {
+                break;
+            };
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                _ => level as f32 * 3.0,
+            };
+            let entry = scores
+                .entry(node.id)
+                .or_insert_with(|| (node.clone(), base_score(&node)));
+            entry.1 += score / divider;
+            ancestor = node.parent();
+        }
+    }
+    scores
+        .into_values()
+        .map(|(node, score)| {
+            let score = score * (1.0 - link_density(&node));
+            (node, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Rebuilds article markup from the kept elements.
+struct Cleaner<'a> {
+    base: &'a Url,
+    title: &'a str,
+    out: String,
+    paragraphs: usize,
+    words: usize,
+}
+
+impl Cleaner<'_> {
+    fn node(&mut self, node: &Rc<Node>) {
+        match &node.node_type {
+            NodeType::Text(text) => {
+                self.words += text.split_whitespace().count();
+                self.out.push_str(&escape_html(text));
+            }
+            NodeType::Element { .. } => self.element(node),
+            _ => {}
+        }
+    }
+
+    fn children(&mut self, node: &Rc<Node>) {
+        for child in node.children() {
+            self.node(&child);
+        }
+    }
+
+    fn element(&mut self, node: &Rc<Node>) {
+        if is_furniture(node) {
+            return;
+        }
+        let tag = node.tag_name().unwrap_or_default().to_ascii_lowercase();
+        // Blocks that are mostly links, such as lists of related stories
+        if element_is(node, &["div", "section", "ul", "ol", "table"])
+            && !is_paragraph(node)
+            && link_density(node) > 0.5
+        {
+            return;
+        }
+        match tag.as_str() {
+            "h1" => {
+                let text = inner_text(node);
+                if text.is_empty() || text == self.title {
+                    return;
+                }
+                self.wrap("h2", node);
+            }
+            "img" => self.image(node),
+            "a" => {
+                let href = node
+                    .get_attribute("href")
+                    .and_then(|href| self.base.join(href.trim()).ok())
+                    .filter(|url| matches!(url.scheme(), "http" | "https"));
+                match href {
+                    Some(href) => {
+                        self.out
+                            .push_str(&format!("<a href=\"{}\">", escape_html(href.as_str())));
+                        self.children(node);
+                        self.out.push_str("</a>");
+                    }
+                    None => self.children(node),
+                }
+            }
+            "p" if inner_text(node).is_empty() && !has_image(node) => {}
+            "p" => {
+                self.paragraphs += 1;
+                self.wrap("p", node);
+            }
+            "div" if is_paragraph(node) && !inner_text(node).is_empty() => {
+                self.paragraphs += 1;
+                self.wrap("p", node);
+            }
+            tag if KEPT.contains(&tag) => self.wrap(tag, node),
+            _ => self.children(node),
+        }
+    }
+
+    fn wrap(&mut self, tag: &str, node: &Rc<Node>) {
+        self.out.push_str(&format!("<{}>", tag));
+        self.children(node);
+        if tag != "br" && tag != "hr" {
+            self.out.push_str(&format!("</{}>", tag));
+        }
+    }
+
+    fn image(&mut self, node: &Rc<Node>) {
+        let Some(src) = node
+            .get_attribute("src")
+            .and_then(|src| self.base.join(src.trim()).ok())
+        else {
+            return;
+        };
+        self.out
+            .push_str(&format!("<img src=\"{}\"", escape_html(src.as_str())));
+        for name in ["alt", "width", "height", "loading"] {
+            if let Some(value) = node.get_attribute(name) {
+                self.out
+                    .push_str(&format!(" {}=\"{}\"", name, escape_html(value)));
+            }
+        }
+        self.out.push('>');
+    }
+}
+
+fn has_image(node: &Rc<Node>) -> bool {
+    node.children()
+        .iter()
+        .any(|child| element_is(child, &["img"]) || has_image(child))
+}
+
+/// `content` of the first `<meta>` whose `name` or `property` is one of
+/// `names`.
+fn meta_content(document: &Document, names: &[&str]) -> Option<String> {
+    document
+        .get_elements_by_tag_name("meta")
+        .into_iter()
+        .find(|meta| {
+            let key = meta
+                .get_attribute("property")
+                .or_else(|| meta.get_attribute("name"))
+                .unwrap_or_default();
+            names
+                .iter()
+                .any(|name| key.trim().eq_ignore_ascii_case(name))
+        })
+        .and_then(|meta| meta.get_attribute("content").map(|c| c.trim().to_string()))
+        .filter(|content| !content.is_empty())
+}
+
+/// The article title: `og:title`, else the page's `<h1>` when the
+/// `<title>` contains it, else the `<title>` without a trailing site name.
+fn article_title(document: &Document) -> String {
+    if let Some(title) = meta_content(document, &["og:title", "twitter:title"]) {
+        return title;
+    }
+    let title = document
+        .title()
+        .map(|t| collapse_whitespace(&t))
+        .unwrap_or_default();
+    let heading = document
+        .get_elements_by_tag_name("h1")
+        .first()
+        .map(|h1| inner_text(h1))
+        .filter(|h1| !h1.is_empty());
+    if let Some(heading) = heading.as_ref().filter(|h| title.contains(h.as_str())) {
+        return heading.clone();
+    }
+    let trimmed = [" | ", " - ", " — ", " :: "]
+        .iter()
+        .filter_map(|separator| title.rsplit_once(separator).map(|(head, _)| head.trim()))
+        .max_by_key(|head| head.len())
+        .filter(|head| head.split_whitespace().count() >= 3)
+        .unwrap_or(&title);
+    if trimmed.is_empty() {
+        return heading.unwrap_or_default();
+    }
+    trimmed.to_string()
+}
+
+/// The author, from `<meta name=author>` or a short byline element.
+fn byline(document: &Document) -> Option<String> {
+    if let Some(author) = meta_content(document, &["author", "article:author"]) {
+        return Some(author);
+    }
+    let mut found = None;
+    document.traverse(|node| {
+        if found.is_some() || !node.is_element() {
+            return;
+        }
+        let rel_author = node.get_attribute("rel") == Some("author");
+        let itemprop_author = node.get_attribute("itemprop") == Some("author");
+        let classed = class_words(node)
+            .iter()
+            .any(|w| w.starts_with("byline") || w == "author");
+        if rel_author || itemprop_author || classed {
+            let text = inner_text(node);
+            if !text.is_empty() && text.len() < 100 {
+                found = Some(text);
+            }
+        }
+    });
+    found
+}
+
+/// When the article was published, from its metadata or first `<time>`.
+fn published(document: &Document) -> Option<String> {
+    meta_content(
+        document,
+        &["article:published_time", "datePublished", "date"],
+    )
+    .or_else(|| {
+        document
+            .get_elements_by_tag_name("time")
+            .first()
+            .and_then(|time| {
+                time.get_attribute("datetime")
+                    .map(str::to_string)
+                    .or_else(|| Some(inner_text(time)))
+            })
+            .filter(|date| !date.trim().is_empty())
+    })
+}
+
+/// Extract the article of `document`, resolving links and images against
+/// `base`. `None` when nothing on the page looks like one.
+pub(crate) fn extract(document: &Document, base: &Url) -> Option<ReaderArticle> {
+    let body = document.body()?;
+    let (top, top_score) = top_candidate(&body)?;
+    let title = article_title(document);
+
+    // Siblings scoring close to the top candidate, and prose-like
+    // paragraphs next to it, belong to the article too
+    let threshold = (top_score * 0.2).max(10.0);
+    let siblings = match top.parent() {
+        Some(parent) if !Rc::ptr_eq(&top, &body) => elements(&parent).collect(),
+        _ => vec![top.clone()],
+    };
+    let mut cleaner = Cleaner {
+        base,
+        title: &title,
+        out: String::new(),
+        paragraphs: 0,
+        words: 0,
+    };
+    for sibling in siblings {
+        let include = Rc::ptr_eq(&sibling, &top)
+            || (!is_furniture(&sibling)
+                && (top_candidate_score(&sibling) >= threshold
+                    || (element_is(&sibling, &["p"])
+                        && inner_text(&sibling).len() > 80
+                        && link_density(&sibling) < 0.25)));
+        if include {
+            cleaner.node(&sibling);
+        }
+    }
+    let text_len = Document::parse_html(&cleaner.out)
+        .ok()
+        .map_or(0, |parsed| inner_text(parsed.root()).len());
+    if text_len < MIN_ARTICLE_LEN {
+        return None;
+    }
+
+    Some(ReaderArticle {
+        byline: byline(document),
+        published: published(document),
+        word_count: cleaner.words,
+        reading_minutes: cleaner.words.div_ceil(WORDS_PER_MINUTE).max(1),
+        paragraphs: cleaner.paragraphs,
+        content: cleaner.out,
+        title,
+    })
+}
+
+/// The score a sibling of the top candidate has as a container.
+fn top_candidate_score(node: &Rc<Node>) -> f32 {
+    top_candidate(node)
+        .filter(|(candidate, _)| Rc::ptr_eq(candidate, node))
+        .map_or(0.0, |(_, score)| score)
+}
+
+/// A date as shown under the title: the day of an ISO 8601 timestamp, or
+/// the text as given.
+fn display_date(date: &str) -> &str {
+    let date = date.trim();
+    let day = date.get(..10).filter(|day| {
+        day.bytes().enumerate().all(|(i, b)| {
+            if i == 4 || i == 7 {
+                b == b'-'
+            } else {
+                b.is_ascii_digit()
+            }
+        })
+    });
+    day.unwrap_or(date)
+}
+
+/// The reader stylesheet: declarations for the page's wrappers and each
+/// element an article keeps. Page `<style>` sheets only carry a few
+/// properties here and text takes its color from its own element, so
+/// every element gets the text declarations as a `style` attribute.
+fn stylesheet(preferences: &ReaderPreferences) -> Vec<(&'static str, String)> {
+    let [background, text, secondary, link] = preferences.theme.colors();
+    let size = preferences.font_size;
+    let small = size * 0.8;
+    let base = format!(
+        "color: {text}; font-family: Georgia, serif; font-size: {size}px; line-height: 1.6"
+    );
+    let heading = |scale: f32| {
+        format!(
+            "{base}; font-size: {}px; font-weight: bold; line-height: 1.3; margin: {}px 0 {}px 0",
+            size * scale,
+            size * 1.2,
+            size * 0.4
+        )
+    };
+
+    let mut styles = vec![
+        (
+            "body",
+            format!("{base}; margin: 0; padding: 32px 24px; background-color: {background}"),
+        ),
+        (
+            "article",
+            format!(
+                "{base}; max-width: {}px; margin: 0 auto",
+                preferences.content_width
+            ),
+        ),
+        (
+            "h1",
+            format!(
+                "{base}; font-size: {}px; font-weight: bold; line-height: 1.25; margin: 0 0 8px 0",
+                size * 1.6
+            ),
+        ),
+        ("h2", heading(1.35)),
+        ("h3", heading(1.2)),
+        ("h4", heading(1.1)),
+        ("h5", heading(1.0)),
+        ("h6", heading(1.0)),
+        ("p", format!("{base}; margin: 0 0 {size}px 0")),
+        (
+            "meta",
+            format!("{base}; color: {secondary}; font-size: {small}px; margin: 0 0 24px 0"),
+        ),
+        ("a", format!("{base}; color: {link}")),
+        ("img", "max-width: 100%; height: auto".to_string()),
+        ("figure", format!("{base}; margin: {size}px 0")),
+        (
+            "figcaption",
+            format!("{base}; color: {secondary}; font-size: {small}px"),
+        ),
+        (
+            "blockquote",
+            format!(
+                "{base}; margin: {size}px 0; padding-left: 16px; border-left: 3px solid {secondary}"
+            ),
+        ),
+        (
+            "pre",
+            format!(
+                "{base}; padding: 12px; border: 1px solid {secondary}; white-space: pre-wrap; \
+                 font-family: Consolas, monospace; font-size: {}px",
+                size * 0.85
+            ),
+        ),
+    ];
+    for tag in KEPT {
+        if !styles.iter().any(|(name, _)| name == tag) {
+            styles.push((tag, base.clone()));
+        }
+    }
+    styles
+}
+
+/// The reader page for `article`.
+pub(crate) fn render(article: &ReaderArticle, preferences: &ReaderPreferences) -> String {
+    let styles = stylesheet(preferences);
+    let style = |tag: &str| {
+        styles
+            .iter()
+            .find(|(name, _)| *name == tag)
+            .map_or(String::new(), |(_, style)| format!(" style=\"{}\"", style))
+    };
+
+    // The cleaned markup opens kept elements without attributes, and its
+    // text is escaped, so each opening tag can take its style directly
+    let mut content = article.content.clone();
+    for (tag, _) in &styles {
+        let styled = match *tag {
+            "a" => content.replace("<a href=", &format!("<a{} href=", style("a"))),
+            "img" => content.replace("<img src=", &format!("<img{} src=", style("img"))),
+            tag => content.replace(&format!("<{}>", tag), &format!("<{}{}>", tag, style(tag))),
+        };
+        content = styled;
+    }
+
+    let mut meta = Vec::new();
+    if let Some(byline) = &article.byline {
+        meta.push(escape_html(byline));
+    }
+    if let Some(published) = &article.published {
+        meta.push(escape_html(display_date(published)));
+    }
+    meta.push(format!("{} min read", article.reading_minutes));
+
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+         <body{body}><article{wrapper}><h1{h1}>{title}</h1>\
+         <p class=\"reader-meta\"{meta_style}>{meta}</p>{content}</article></body></html>",
+        title = escape_html(&article.title),
+        body = style("body"),
+        wrapper = style("article"),
+        h1 = style("h1"),
+        meta_style = style("meta"),
+        meta = meta.join(" · "),
+    )
+}
+
+#[cfg(test)]
+pub(crate) const ARTICLE_FIXTURE: &str = r#"<!DOCTYPE html>
+<html><head>
+    <title>Rivers Return to the Valley | Daily Ledger</title>
+    <meta property="og:title" content="Rivers Return to the Valley">
+    <meta name="author" content="Ana Ruiz">
+    <meta property="article:published_time" content="2024-04-02T08:30:00Z">
+</head><body>
+    <header class="site-header"><nav><a href="/">Home</a> <a href="/world">World</a></nav></header>
+    <div class="layout">
+        <main>
+            <article class="story">
+                <h1>Rivers Return to the Valley</h1>
+                <p class="byline">By Ana Ruiz</p>
+                <p>After three dry summers, the rivers of the northern valley are running again, and farmers who had sold their herds are counting the weeks until the pastures recover.</p>
+                <p>Hydrologists say the snowpack this winter was the deepest in a decade, which means the meltwater will keep the channels full well into the autumn, long after the usual peak.</p>
+                <figure><img src="/images/river.jpg" alt="The river at dawn" loading="lazy"><figcaption>The river at dawn, near the old mill.</figcaption></figure>
+                <p>Not everyone is celebrating. Engineers warn that levees neglected during the drought may not hold, and the regional council has asked for emergency funds to inspect them.</p>
+                <blockquote>We built for the floods we remembered, not the ones we are going to get.</blockquote>
+                <ul class="related"><li><a href="/a">Drought ends</a></li><li><a href="/b">Levee funding</a></li></ul>
+                <p>For now, the valley is green again, and the markets in town are busy with the first harvest of spring greens, sold from the backs of trucks along the river road.</p>
+            </article>
+        </main>
+        <aside class="sidebar"><h3>Most read</h3><p>Sidebar story about celebrity gardening tips that nobody in the valley asked for.</p></aside>
+    </div>
+    <div class="ad-banner">Buy more things today, special offer</div>
+    <footer>Copyright Daily Ledger</footer>
+</body></html>"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_article_without_furniture() {
+        let document = Document::parse_html(ARTICLE_FIXTURE).unwrap();
+        let base = Url::parse("https://news.example/2024/rivers").unwrap();
+        assert!(is_probably_readable(&document));
+
+        let article = extract(&document, &base).unwrap();
+        assert_eq!(article.title, "Rivers Return to the Valley");
+        assert_eq!(article.byline.as_deref(), Some("Ana Ruiz"));
+        assert_eq!(
+            display_date(article.published.as_deref().unwrap()),
+            "2024-04-02"
+        );
+        assert_eq!(article.paragraphs, 4);
+        assert_eq!(article.reading_minutes, 1);
+        assert!(article.content.contains("<blockquote>"));
+        assert!(article
+            .content
+            .contains(r#"<img src="https://news.example/images/river.jpg" alt="The river at dawn" loading="lazy">"#));
+        assert!(article.content.contains("<figcaption>"));
+        for furniture in [
+            "Sidebar story",
+            "Buy more things",
+            "Copyright",
+            "Home",
+            "By Ana Ruiz",
+            "Drought ends",
+        ] {
+            assert!(!article.content.contains(furniture), "{furniture}");
+        }
+
+        let page = render(&article, &ReaderPreferences::default());
+        assert!(page.contains("Ana Ruiz · 2024-04-02 · 1 min read"));
+
+        let navigation = Document::parse_html(
+            "<html><body><nav><a href=/a>A</a></nav><p>Short.</p></body></html>",
+        )
+        .unwrap();
+        assert!(!is_probably_readable(&navigation));
+        assert_eq!(extract(&navigation, &base), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! Declarative refresh: `<meta http-equiv="refresh">` and the `Refresh`
+//! response header.
+//!
+//! Both take a delay in seconds, optionally followed by the URL to load,
+//! as in `5; url=/next`. Real pages write this loosely, so parsing follows
+//! the HTML standard's lenient steps: a fraction on the delay is ignored,
+//! `url=` may be missing, and the URL may be quoted. A document refreshes
+//! at most once; the header comes before any `<meta>`. The refresh is a
+//! deadline on the view that [`crate::Engine::run_timers`] turns into a
+//! navigation once due.
+
+use std::time::{Duration, Instant};
+
+use rustkit_dom::Document;
+use url::Url;
+
+/// A refresh parsed from a header or `<meta>` element.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Refresh {
+    pub(crate) delay: Duration,
+    pub(crate) url: Url,
+}
+
+/// A refresh waiting for its deadline.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingRefresh {
+    pub(crate) url: Url,
+    pub(crate) due: Instant,
+    /// Whether the navigation replaces the current history entry, as an
+    /// immediate refresh is a redirect.
+    pub(crate) replace: bool,
+}
+
+impl PendingRefresh {
+    pub(crate) fn new(refresh: Refresh, now: Instant) -> Self {
+        Self {
+            replace: refresh.delay.is_zero(),
+            due: now + refresh.delay,
+            url: refresh.url,
+        }
+    }
+}
+
+/// Parse a refresh `content` value of the document at `document_url`.
+/// Without a URL, the document refreshes itself.
+pub(crate) fn parse(content: &str, document_url: &Url) -> Option<Refresh> {
+    let input = content.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let digits = input.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 && !input.starts_with('.') {
+        return None;
+    }
+    let seconds = match digits {
+        0 => 0,
+        _ => input[..digits].parse::<u64>().unwrap_or(u64::MAX),
+    };
+    // Any fraction is ignored
+    let rest = input[digits..].trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    let rest = match rest.chars().next() {
+        None => "",
+        Some(c) if c.is_ascii_whitespace() || c == ';' || c == ',' => {
+            let rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            let rest = rest.strip_prefix(&[';', ','][..]).unwrap_or(rest);
+            rest.trim_start_matches(|c: char| c.is_ascii_whitespace())
+        }
+        Some(_) => return None,
+    };
+    let delay = Duration::from_secs(seconds.min(u32::MAX as u64));
+    if rest.is_empty() {
+        return Some(Refresh {
+            delay,
+            url: document_url.clone(),
+        });
+    }
+
+    let url = strip_quotes(strip_url_prefix(rest));
+    let url = document_url.join(url.trim_end()).ok()?;
+    Some(Refresh { delay, url })
+}
+
+/// `rest` past a leading `url=`, if it has one.
+fn strip_url_prefix(rest: &str) -> &str {
+    let Some(prefix) = rest.get(..3) else {
+        return rest;
+    };
+    if !prefix.eq_ignore_ascii_case("url") {
+        return rest;
+    }
+    let after = rest[3..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+    match after.strip_prefix('=') {
+        Some(url) => url.trim_start_matches(|c: char| c.is_ascii_whitespace()),
+        // A URL that merely starts with "url"
+        None => rest,
+    }
+}
+
+/// A URL in single or double quotes, up to the closing quote.
+fn strip_quotes(url: &str) -> &str {
+    let Some(quote) = url.chars().next().filter(|c| *c == '\'' || *c == '"') else {
+        return url;
+    };
+    let url = &url[1..];
+    url.find(quote).map_or(url, |end| &url[..end])
+}
+
+/// `content` of the first `<meta http-equiv="refresh">` in `document`'s
+/// head.
+pub(crate) fn meta_refresh(document: &Document) -> Option<String> {
+    let head = document.head()?;
+    document
+        .get_elements_by_tag_name("meta")
+        .into_iter()
+        .filter(|meta| head.contains(meta))
+        .find(|meta| {
+            meta.get_attribute("http-equiv")
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case("refresh"))
+        })
+        .and_then(|meta| meta.get_attribute("content").map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> Url {
+        Url::parse("https://example.com/dir/page").unwrap()
+    }
+
+    fn refresh(content: &str) -> Option<(u64, String)> {
+        parse(content, &page()).map(|r| (r.delay.as_secs(), r.url.to_string()))
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        let next = "https://example.com/next".to_string();
+        assert_eq!(refresh("5; url=/next"), Some((5, next.clone())));
+        assert_eq!(refresh("  0;URL = '/next'"), Some((0, next.clone())));
+        assert_eq!(refresh("3, \"/next\" trailing"), Some((3, next.clone())));
+        assert_eq!(refresh("1.5; /next"), Some((1, next.clone())));
+        assert_eq!(refresh(".5;url=/next"), Some((0, next)));
+        assert_eq!(
+            refresh("2 urlpage"),
+            Some((2, "https://example.com/dir/urlpage".into()))
+        );
+        assert_eq!(refresh("10"), Some((10, page().to_string())));
+        assert_eq!(refresh("7;"), Some((7, page().to_string())));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        assert_eq!(refresh(""), None);
+        assert_eq!(refresh("url=/next"), None);
+        assert_eq!(refresh("5x; url=/next"), None);
+        assert_eq!(refresh("-1; url=/next"), None);
+    }
+
+    #[test]
+    fn test_meta_refresh_in_head() {
+        let document = Document::parse_html(
+            r#"<html><head><meta http-equiv="Refresh" content="0; url=/a"></head>
+               <body><meta http-equiv="refresh" content="1; url=/b"></body></html>"#,
+        )
+        .unwrap();
+        assert_eq!(meta_refresh(&document).as_deref(), Some("0; url=/a"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Automatic retries of navigations that failed for want of a network.
+//!
+//! When a top-level navigation fails because the name didn't resolve, the
+//! connection was refused, the host was unreachable or the network is
+//! offline, the view keeps showing the error page at the URL and the
+//! engine tries it again after a delay that doubles with each failed
+//! attempt. Like a refresh, a retry is a deadline on the view that
+//! [`crate::Engine::run_timers`] turns into a load for
+//! [`crate::Engine::load_frames`]. Connectivity coming back makes a waiting
+//! retry due at once, and a navigation the user starts cancels it.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+/// How failed navigations are retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries of one URL before the error page is left as it is; 0
+    /// turns retrying off.
+    pub max_attempts: u32,
+    /// Delay before the first retry; each later one doubles it.
+    pub base_delay: Duration,
+    /// Longest delay between retries.
+    pub max_delay: Duration,
+    /// Fraction of each delay added or taken away at random, so views
+    /// that failed together don't all retry at the same moment.
+    pub jitter: f32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry `attempt`, counting from 1, without jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// [`Self::delay`] moved by up to the jitter fraction either way.
+    fn jittered_delay(&self, attempt: u32) -> Duration {
+        let delay = self.delay(attempt);
+        let jitter = self.jitter.clamp(0.0, 1.0) as f64;
+        if jitter == 0.0 {
+            return delay;
+        }
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        // Uniform in [-1, 1)
+        let unit = (hasher.finish() >> 11) as f64 / (1u64 << 52) as f64 - 1.0;
+        delay.mul_f64(1.0 + jitter * unit)
+    }
+}
+
+/// A failed navigation being retried.
+#[derive(Debug, Clone)]
+pub(crate) struct NavigationRetry {
+    pub(crate) url: Url,
+    /// Retries scheduled so far, this one included.
+    pub(crate) attempt: u32,
+    /// When the retry is due; `None` once its load is queued.
+    pub(crate) due: Option<Instant>,
+}
+
+impl NavigationRetry {
+    /// The retry after a navigation to `url` failed, following `previous`
+    /// if that was for the same URL. Returns it with its delay, or `None`
+    /// once the attempts have run out.
+    pub(crate) fn next(
+        previous: Option<Self>,
+        url: &Url,
+        policy: &RetryPolicy,
+        now: Instant,
+    ) -> Option<(Self, Duration)> {
+        let attempt = previous
+            .filter(|retry| retry.url == *url)
+            .map_or(1, |retry| retry.attempt + 1);
+        if attempt > policy.max_attempts {
+            return None;
+        }
+        let delay = policy.jittered_delay(attempt);
+        let retry = Self {
+            url: url.clone(),
+            attempt,
+            due: Some(now + delay),
+        };
+        Some((retry, delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delays_double_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+            jitter: 0.0,
+        };
+        let delays: Vec<_> = (1..=5).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [500, 1000, 2000, 3000, 3000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.delay(200), Duration::from_secs(3));
+
+        let jittered = RetryPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..20 {
+            let delay = jittered.jittered_delay(2);
+            assert!((Duration::from_millis(500)..=Duration::from_millis(1500)).contains(&delay));
+        }
+    }
+
+    #[test]
+    fn test_attempts_count_per_url() {
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let url = Url::parse("http://example.test/").unwrap();
+        let now = Instant::now();
+        let (first, delay) = NavigationRetry::next(None, &url, &policy, now).unwrap();
+        assert_eq!((first.attempt, delay), (1, Duration::from_secs(1)));
+        let (second, delay) = NavigationRetry::next(Some(first), &url, &policy, now).unwrap();
+        assert_eq!((second.attempt, delay), (2, Duration::from_secs(2)));
+        assert!(NavigationRetry::next(Some(second.clone()), &url, &policy, now).is_none());
+
+        // Another URL starts over
+        let other = Url::parse("http://other.test/").unwrap();
+        let (retry, _) = NavigationRetry::next(Some(second), &other, &policy, now).unwrap();
+        assert_eq!(retry.attempt, 1);
+    }
+}
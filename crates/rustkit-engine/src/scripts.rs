@@ -0,0 +1,395 @@
+//! Running a document's `<script>` elements.
+//!
+//! A document is parsed whole before any of its script runs, so parser
+//! order is document order and a script already sees the elements after
+//! it. Classic scripts without `async` or `defer`, inline or external, run
+//! in document order before the first layout, then `defer` scripts in
+//! order, then `DOMContentLoaded` fires. `async` scripts run whenever their
+//! fetch finishes, before `load` at the latest. Module scripts and data
+//! blocks don't run.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use rustkit_dom::{Document, Node, NodeId, NodeType};
+use rustkit_net::{
+    ContentSecurityPolicy, CorsChecker, CorsResult, CredentialsMode, CspDirective, CspSource,
+    Origin, Request, ResourceLoader, Response, SecurityContext,
+};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// When a script runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScriptTiming {
+    /// In document order, before the first layout.
+    Parser,
+    /// After the parser's scripts, in document order.
+    Defer,
+    /// As soon as it has been fetched.
+    Async,
+}
+
+/// Where a script's source comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ScriptSource {
+    Inline(String),
+    External {
+        url: Url,
+        /// The credentials mode of a `crossorigin` script, fetched with
+        /// CORS; `None` for a script fetched without.
+        cors: Option<CredentialsMode>,
+    },
+}
+
+/// A classic `<script>` element of a document.
+#[derive(Debug, Clone)]
+pub(crate) struct PageScript {
+    pub(crate) node: NodeId,
+    pub(crate) timing: ScriptTiming,
+    pub(crate) source: ScriptSource,
+    pub(crate) nonce: Option<String>,
+}
+
+/// Source of a script ready to run.
+#[derive(Debug, Clone)]
+pub(crate) struct FetchedScript {
+    pub(crate) text: String,
+    /// Errors it throws are reported as `Script error.`: it came from
+    /// another origin without CORS approval.
+    pub(crate) muted: bool,
+}
+
+/// Where a script of the document being loaded stands.
+pub(crate) enum ScriptState {
+    Ready(FetchedScript),
+    Fetching(JoinHandle<Result<FetchedScript, String>>),
+    /// Refused by policy or not fetched; `error` fires at the element.
+    Failed(String),
+}
+
+/// A script waiting for its turn to run.
+pub(crate) struct PendingScript {
+    pub(crate) node: NodeId,
+    pub(crate) timing: ScriptTiming,
+    pub(crate) state: ScriptState,
+}
+
+impl PendingScript {
+    /// Whether the script can run without waiting.
+    pub(crate) fn is_ready(&self) -> bool {
+        match &self.state {
+            ScriptState::Fetching(handle) => handle.is_finished(),
+            _ => true,
+        }
+    }
+
+    /// The script's source, waiting for its fetch to finish.
+    pub(crate) async fn source(self) -> Result<FetchedScript, String> {
+        match self.state {
+            ScriptState::Ready(script) => Ok(script),
+            ScriptState::Fetching(handle) => handle.await.unwrap_or_else(|e| Err(e.to_string())),
+            ScriptState::Failed(reason) => Err(reason),
+        }
+    }
+}
+
+/// Whether a `type` attribute names a classic script.
+fn is_classic(script_type: Option<&str>) -> bool {
+    let Some(script_type) = script_type.map(str::trim) else {
+        return true;
+    };
+    let essence = script_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    matches!(
+        essence.as_str(),
+        "" | "text/javascript"
+            | "application/javascript"
+            | "application/x-javascript"
+            | "text/x-javascript"
+            | "application/ecmascript"
+            | "text/ecmascript"
+            | "text/jscript"
+            | "text/livescript"
+    )
+}
+
+/// The classic scripts of `document` in the order they run: the parser's,
+/// then deferred ones, then `async` ones. `defer` and `async` only apply to
+/// external scripts; a `src` that doesn't resolve against `base` is
+/// skipped.
+pub(crate) fn page_scripts(document: &Document, base: &Url) -> Vec<PageScript> {
+    let mut elements: Vec<Rc<Node>> = Vec::new();
+    document.traverse(|node| {
+        if matches!(&node.node_type, NodeType::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("script"))
+        {
+            elements.push(node.clone());
+        }
+    });
+
+    let mut scripts: Vec<PageScript> = elements
+        .iter()
+        .filter(|element| is_classic(element.get_attribute("type")))
+        .filter_map(|element| {
+            let (source, timing) = match element.get_attribute("src") {
+                Some(src) => {
+                    let url = base
+                        .join(src.trim())
+                        .ok()
+                        .filter(|_| !src.trim().is_empty())?;
+                    let cors = element.get_attribute("crossorigin").map(|mode| {
+                        if mode.trim().eq_ignore_ascii_case("use-credentials") {
+                            CredentialsMode::Include
+                        } else {
+                            CredentialsMode::SameOrigin
+                        }
+                    });
+                    let timing = if element.get_attribute("async").is_some() {
+                        ScriptTiming::Async
+                    } else if element.get_attribute("defer").is_some() {
+                        ScriptTiming::Defer
+                    } else {
+                        ScriptTiming::Parser
+                    };
+                    (ScriptSource::External { url, cors }, timing)
+                }
+                None => (
+                    ScriptSource::Inline(element.text_content()),
+                    ScriptTiming::Parser,
+                ),
+            };
+            Some(PageScript {
+                node: element.id,
+                timing,
+                source,
+                nonce: element.get_attribute("nonce").map(str::to_string),
+            })
+        })
+        .collect();
+    scripts.sort_by_key(|script| match script.timing {
+        ScriptTiming::Parser => 0,
+        ScriptTiming::Defer => 1,
+        ScriptTiming::Async => 2,
+    });
+    scripts
+}
+
+/// A policy's text, keeping the directives this engine knows. Only the
+/// first of a repeated directive counts.
+fn parse_policy(text: &str) -> Option<ContentSecurityPolicy> {
+    let mut policy = ContentSecurityPolicy::new();
+    for directive in text.split(';') {
+        let Ok(parsed) = ContentSecurityPolicy::parse(directive) else {
+            continue;
+        };
+        for (name, sources) in parsed.directives {
+            policy.directives.entry(name).or_insert(sources);
+        }
+    }
+    (!policy.directives.is_empty()).then_some(policy)
+}
+
+/// The policies of a response's `Content-Security-Policy` headers.
+pub(crate) fn header_policies(response: &Response) -> Vec<ContentSecurityPolicy> {
+    response
+        .headers
+        .get_all("content-security-policy")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_policy)
+        .collect()
+}
+
+/// The policies of the `<meta http-equiv="Content-Security-Policy">`
+/// elements in `document`'s head.
+pub(crate) fn meta_policies(document: &Document) -> Vec<ContentSecurityPolicy> {
+    let Some(head) = document.head() else {
+        return Vec::new();
+    };
+    document
+        .get_elements_by_tag_name("meta")
+        .into_iter()
+        .filter(|meta| head.contains(meta))
+        .filter(|meta| {
+            meta.get_attribute("http-equiv")
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case("content-security-policy"))
+        })
+        .filter_map(|meta| meta.get_attribute("content").and_then(parse_policy))
+        .collect()
+}
+
+/// Whether every one of `policies` lets `script` run in a document at
+/// `document_url`. A matching nonce allows any script; `'self'` allows
+/// external scripts of the document's origin.
+pub(crate) fn allowed(
+    policies: &[ContentSecurityPolicy],
+    document_url: &Url,
+    script: &PageScript,
+) -> bool {
+    policies
+        .iter()
+        .filter(|policy| !policy.report_only)
+        .all(|policy| {
+            let Some(sources) = policy.get_sources(CspDirective::ScriptSrc) else {
+                return true;
+            };
+            let nonce = sources.iter().any(|source| {
+            matches!(source, CspSource::Nonce(nonce) if Some(nonce) == script.nonce.as_ref())
+        });
+            nonce
+                || match &script.source {
+                    ScriptSource::Inline(_) => policy.allows_script(None, true, None, None),
+                    ScriptSource::External { url, .. } => {
+                        policy.allows_script(Some(url), false, None, None)
+                            || (sources.contains(&CspSource::Self_)
+                                && Origin::from_url(document_url)
+                                    .same_origin(&Origin::from_url(url)))
+                    }
+                }
+        })
+}
+
+/// Whether every one of `policies` lets a document at `document_url`
+/// start a worker from `url`. `'self'` allows the document's origin.
+pub(crate) fn worker_allowed(
+    policies: &[ContentSecurityPolicy],
+    document_url: &Url,
+    url: &Url,
+) -> bool {
+    policies
+        .iter()
+        .filter(|policy| !policy.report_only)
+        .all(|policy| {
+            let self_allowed = policy
+                .directives
+                .get(&CspDirective::WorkerSrc)
+                .or_else(|| policy.get_sources(CspDirective::ScriptSrc))
+                .is_some_and(|sources| sources.contains(&CspSource::Self_));
+            policy.allows_worker(url)
+                || (self_allowed
+                    && Origin::from_url(document_url).same_origin(&Origin::from_url(url)))
+        })
+}
+
+/// Fetch an external script for a document in `context`.
+///
+/// A `crossorigin` script from another origin must pass a CORS check to
+/// run at all; one fetched without CORS runs with its errors muted.
+pub(crate) async fn fetch(
+    loader: Arc<ResourceLoader>,
+    context: SecurityContext,
+    view_id: u64,
+    url: Url,
+    cors: Option<CredentialsMode>,
+) -> Result<FetchedScript, String> {
+    let mut request = Request::get(url).for_view(view_id);
+    // Scripts fetched without CORS send their cookies like any subresource
+    request.credentials = cors.unwrap_or(CredentialsMode::Include);
+    let request = match cors {
+        Some(_) => request.issued_from(&context),
+        None => {
+            request.top_level_site = context.top_level_site.clone();
+            request
+        }
+    };
+    let response = loader.fetch(request).await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("HTTP {}", response.status.as_u16()));
+    }
+
+    let same_origin = context.is_same_origin(&response.url);
+    if let (Some(credentials), false) = (cors, same_origin) {
+        let header = |name| response.headers.get(name).and_then(|v| v.to_str().ok());
+        let check = CorsChecker::new().check_response(
+            &context.origin.serialize(),
+            header("access-control-allow-origin"),
+            header("access-control-allow-credentials"),
+            credentials == CredentialsMode::Include,
+        );
+        if let CorsResult::Denied(reason) = check {
+            return Err(format!("CORS check failed: {}", reason));
+        }
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    Ok(FetchedScript {
+        text,
+        muted: !same_origin && cors.is_none(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_in_run_order_and_policy() {
+        let base = Url::parse("https://example.com/page").unwrap();
+        let document = Document::parse_html(
+            r#"<html><head>
+                <script src="a.js" async></script>
+                <script src="d.js" defer></script>
+                <script>var inline;</script>
+                <script type="module" src="m.js"></script>
+                <script type="application/json">{}</script>
+                <script src="https://cdn.example.net/lib.js" crossorigin></script>
+                <script nonce="n1" defer>var deferIgnored;</script>
+            </head><body></body></html>"#,
+        )
+        .unwrap();
+        let scripts = page_scripts(&document, &base);
+        let order: Vec<_> = scripts
+            .iter()
+            .map(|script| match &script.source {
+                ScriptSource::Inline(text) => text.trim().to_string(),
+                ScriptSource::External { url, .. } => url.path().to_string(),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "var inline;",
+                "/lib.js",
+                "var deferIgnored;",
+                "/d.js",
+                "/a.js"
+            ]
+        );
+        assert_eq!(
+            scripts[1].source,
+            ScriptSource::External {
+                url: Url::parse("https://cdn.example.net/lib.js").unwrap(),
+                cors: Some(CredentialsMode::SameOrigin),
+            }
+        );
+
+        let policies =
+            [parse_policy("script-src 'self' 'nonce-n1'; upgrade-insecure-requests").unwrap()];
+        let allowed: Vec<_> = scripts
+            .iter()
+            .map(|script| super::allowed(&policies, &base, script))
+            .collect();
+        assert_eq!(allowed, [false, false, true, true, true]);
+        assert!(scripts.iter().all(|s| super::allowed(&[], &base, s)));
+    }
+
+    #[test]
+    fn test_worker_policy_falls_back_to_script_src() {
+        let base = Url::parse("https://example.com/page").unwrap();
+        let local = Url::parse("https://example.com/worker.js").unwrap();
+        let cdn = Url::parse("https://cdn.example.net/worker.js").unwrap();
+
+        let policies = [parse_policy("script-src 'self'").unwrap()];
+        assert!(worker_allowed(&policies, &base, &local));
+        assert!(!worker_allowed(&policies, &base, &cdn));
+
+        let policies = [parse_policy("script-src 'self'; worker-src https://cdn.example.net").unwrap()];
+        assert!(!worker_allowed(&policies, &base, &local));
+        assert!(worker_allowed(&policies, &base, &cdn));
+        assert!(worker_allowed(&[], &base, &cdn));
+    }
+}
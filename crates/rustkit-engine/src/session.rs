@@ -0,0 +1,199 @@
+//! Saved navigation state of views, for restoring a session after a
+//! restart or crash.
+//!
+//! Nodes are identified by their path of element indices from the document
+//! root plus their tag name, so state saved for a page that has since
+//! changed shape is skipped rather than applied to the wrong element.
+
+use rustkit_core::NavigationEntry;
+use rustkit_dom::{Document, Node, NodeId};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use url::Url;
+
+/// Version of [`ViewSessionState`] written by this engine.
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
+/// A view's back/forward list, scroll offsets and text control values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewSessionState {
+    /// Format version; newer versions than [`SESSION_FORMAT_VERSION`] are
+    /// rejected on restore.
+    pub version: u32,
+    pub entries: Vec<SessionHistoryEntry>,
+    /// Index of the current entry in `entries`.
+    pub current_index: usize,
+    /// Offsets of scrolled containers in the current document.
+    #[serde(default)]
+    pub scroll: Vec<ScrollSnapshot>,
+    /// Edited text control values in the current document.
+    #[serde(default)]
+    pub form_values: Vec<FormValueSnapshot>,
+}
+
+impl ViewSessionState {
+    /// Drop the text control values, for hosts that don't persist what
+    /// users typed.
+    pub fn without_form_values(mut self) -> Self {
+        self.form_values.clear();
+        self
+    }
+}
+
+/// One back/forward entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHistoryEntry {
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Serialized `history.state`.
+    #[serde(default)]
+    pub state: Option<String>,
+}
+
+/// Location of a node that survives reloading the same page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodePath {
+    /// Element child indices from the document root.
+    pub indices: Vec<usize>,
+    /// Lowercase tag name of the node.
+    pub tag: String,
+}
+
+/// Scroll offset of one scroll container; the viewport is the root element.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrollSnapshot {
+    pub node: NodePath,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Value of one text control.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormValueSnapshot {
+    pub node: NodePath,
+    pub value: String,
+}
+
+/// State applied once the current entry of a restored view has loaded.
+pub(crate) struct PendingRestore {
+    pub url: Url,
+    pub scroll: Vec<ScrollSnapshot>,
+    pub form_values: Vec<FormValueSnapshot>,
+}
+
+impl SessionHistoryEntry {
+    pub(crate) fn from_entry(entry: &NavigationEntry) -> Self {
+        Self {
+            url: entry.url.to_string(),
+            title: entry.title.clone(),
+            state: entry.state.clone(),
+        }
+    }
+
+    pub(crate) fn to_entry(&self) -> Result<NavigationEntry, url::ParseError> {
+        Ok(NavigationEntry {
+            url: Url::parse(&self.url)?,
+            title: self.title.clone(),
+            state: self.state.clone(),
+        })
+    }
+}
+
+fn element_children(node: &Node) -> impl Iterator<Item = Rc<Node>> {
+    node.children()
+        .into_iter()
+        .filter(|child| child.tag_name().is_some())
+}
+
+impl NodePath {
+    /// Path of the element `node_id` in `document`.
+    pub(crate) fn of(document: &Document, node_id: NodeId) -> Option<Self> {
+        let node = document.get_node(node_id)?;
+        let tag = node.tag_name()?.to_ascii_lowercase();
+        let mut indices = Vec::new();
+        let mut current = node;
+        while let Some(parent) = current.parent() {
+            let index = element_children(&parent).position(|child| child.id == current.id)?;
+            indices.push(index);
+            current = parent;
+        }
+        indices.reverse();
+        Some(Self { indices, tag })
+    }
+
+    /// The element at this path, if it still has the same tag.
+    pub(crate) fn resolve(&self, document: &Document) -> Option<Rc<Node>> {
+        let mut node = document.root().clone();
+        for &index in &self.indices {
+            node = element_children(&node).nth(index)?;
+        }
+        node.tag_name()
+            .is_some_and(|tag| tag.eq_ignore_ascii_case(&self.tag))
+            .then_some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_paths_skip_changed_pages() {
+        let document = Document::parse_html(
+            "<html><body><p>intro</p><div id='feed'><textarea></textarea></div></body></html>",
+        )
+        .unwrap();
+        let feed = document.get_element_by_id("feed").unwrap();
+        let path = NodePath::of(&document, feed.id).unwrap();
+        assert_eq!(path.tag, "div");
+        assert_eq!(path.resolve(&document).unwrap().id, feed.id);
+
+        // Text between elements does not shift the path
+        let spaced = Document::parse_html(
+            "<html><body>\n  <p>intro</p>\n  <div id='feed'></div></body></html>",
+        )
+        .unwrap();
+        assert_eq!(
+            path.resolve(&spaced).unwrap().id,
+            spaced.get_element_by_id("feed").unwrap().id
+        );
+
+        let changed = Document::parse_html("<html><body><p>a</p><p>b</p></body></html>").unwrap();
+        assert!(path.resolve(&changed).is_none());
+    }
+
+    #[test]
+    fn test_serialized_state_round_trips() {
+        let state = ViewSessionState {
+            version: SESSION_FORMAT_VERSION,
+            entries: vec![SessionHistoryEntry {
+                url: "https://example.com/".into(),
+                title: Some("Example".into()),
+                state: Some("{\"page\":2}".into()),
+            }],
+            current_index: 0,
+            scroll: vec![ScrollSnapshot {
+                node: NodePath {
+                    indices: vec![0],
+                    tag: "html".into(),
+                },
+                x: 0.0,
+                y: 120.0,
+            }],
+            form_values: Vec::new(),
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ViewSessionState>(&json).unwrap(),
+            state
+        );
+
+        // Older snapshots without the optional sections still load
+        let minimal: ViewSessionState = serde_json::from_str(
+            r#"{"version":1,"entries":[{"url":"https://example.com/"}],"current_index":0}"#,
+        )
+        .unwrap();
+        assert!(minimal.scroll.is_empty() && minimal.form_values.is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! Engine startup: lazy GPU initialization and its timings.
+//!
+//! [`Engine::new`](crate::Engine::new) creates no GPU objects. The
+//! compositor's device and the renderer's pipelines are built the first time
+//! a view is created or painted, or ahead of that on a background thread by
+//! [`Engine::warm_up`](crate::Engine::warm_up). Views created while the
+//! background work runs get their surfaces, and their first paint, once it
+//! finishes.
+//!
+//! Pipelines are compiled through a pipeline cache kept in the profile
+//! directory, so later runs skip shader compilation on drivers that support
+//! it.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use rustkit_compositor::Compositor;
+use rustkit_renderer::{AntialiasSettings, Renderer, TextRenderingSettings};
+use tracing::{debug, info, warn};
+
+use crate::{EngineConfig, EngineError};
+
+/// Directory under the profile holding pipeline caches, one per adapter
+/// and driver.
+const PIPELINE_CACHE_DIR: &str = "gpu-cache";
+
+/// How long each phase of starting the engine took.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InitStats {
+    /// All of [`Engine::new`](crate::Engine::new).
+    pub engine_new: Duration,
+    /// Building the resource loader, alongside the image manager and the
+    /// stored permissions.
+    pub network: Duration,
+    /// Building the image manager.
+    pub images: Duration,
+    /// Loading stored permission decisions from the profile.
+    pub permissions: Duration,
+    /// Selecting the adapter and creating the device, once done.
+    pub gpu_device: Option<Duration>,
+    /// Creating the renderer and compiling its pipelines, once done.
+    pub renderer: Option<Duration>,
+    /// Whether the pipelines were built from a cache an earlier run saved.
+    pub pipeline_cache_hit: bool,
+    /// Whether the GPU was initialized by [`Engine::warm_up`](crate::Engine::warm_up)
+    /// in the background.
+    pub warmed_in_background: bool,
+}
+
+/// The GPU objects of an engine.
+pub(crate) struct Gpu {
+    pub(crate) compositor: Compositor,
+    pub(crate) renderer: Renderer,
+}
+
+/// What building the GPU objects needs from the engine configuration.
+#[derive(Clone)]
+struct GpuOptions {
+    text_rendering: TextRenderingSettings,
+    antialiasing: AntialiasSettings,
+    profile_directory: Option<PathBuf>,
+}
+
+impl GpuOptions {
+    fn new(config: &EngineConfig) -> Self {
+        Self {
+            text_rendering: config.text_rendering,
+            antialiasing: config.antialiasing,
+            profile_directory: config.profile_directory.clone(),
+        }
+    }
+}
+
+/// Timings of building the GPU objects.
+struct GpuTimings {
+    device: Duration,
+    renderer: Duration,
+    cache_hit: bool,
+}
+
+type Built = Result<(Gpu, GpuTimings), String>;
+
+enum GpuState {
+    Cold,
+    Warming(mpsc::Receiver<Built>),
+    Ready(Box<Gpu>),
+    Failed(String),
+}
+
+/// The engine's GPU objects, built on first use.
+pub(crate) struct GpuSlot {
+    state: GpuState,
+    timings: Option<GpuTimings>,
+    warmed_in_background: bool,
+}
+
+impl GpuSlot {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: GpuState::Cold,
+            timings: None,
+            warmed_in_background: false,
+        }
+    }
+
+    /// Start building the GPU objects on a background thread.
+    pub(crate) fn warm_up(&mut self, config: &EngineConfig) {
+        if !matches!(self.state, GpuState::Cold) {
+            return;
+        }
+        let options = GpuOptions::new(config);
+        let (tx, rx) = mpsc::channel();
+        let spawned = std::thread::Builder::new()
+            .name("gpu-warm-up".into())
+            .spawn(move || {
+                let _ = tx.send(build(&options));
+            });
+        match spawned {
+            Ok(_) => {
+                debug!("Warming up the GPU in the background");
+                self.state = GpuState::Warming(rx);
+                self.warmed_in_background = true;
+            }
+            Err(e) => warn!(error = %e, "Could not start GPU warm-up"),
+        }
+    }
+
+    /// Whether background warm-up is still running.
+    pub(crate) fn is_warming(&mut self, config: &EngineConfig) -> bool {
+        self.poll(config);
+        matches!(self.state, GpuState::Warming(_))
+    }
+
+    /// The GPU objects if they are ready, taking in a finished warm-up.
+    pub(crate) fn poll(&mut self, config: &EngineConfig) -> Option<&mut Gpu> {
+        if let GpuState::Warming(rx) = &self.state {
+            match rx.try_recv() {
+                Ok(built) => self.finish(built, config),
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.state = GpuState::Failed("GPU warm-up thread exited".into());
+                }
+            }
+        }
+        self.get_mut()
+    }
+
+    /// The GPU objects, building them now or waiting for warm-up to finish.
+    pub(crate) fn ensure(&mut self, config: &EngineConfig) -> Result<&mut Gpu, EngineError> {
+        match &self.state {
+            GpuState::Cold => {
+                let built = build(&GpuOptions::new(config));
+                self.finish(built, config);
+            }
+            GpuState::Warming(rx) => {
+                let built = rx
+                    .recv()
+                    .unwrap_or_else(|_| Err("GPU warm-up thread exited".into()));
+                self.finish(built, config);
+            }
+            GpuState::Ready(_) | GpuState::Failed(_) => {}
+        }
+        match &mut self.state {
+            GpuState::Ready(gpu) => Ok(gpu),
+            GpuState::Failed(e) => Err(EngineError::RenderError(e.clone())),
+            GpuState::Cold | GpuState::Warming(_) => unreachable!(),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&Gpu> {
+        match &self.state {
+            GpuState::Ready(gpu) => Some(gpu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> Option<&mut Gpu> {
+        match &mut self.state {
+            GpuState::Ready(gpu) => Some(gpu),
+            _ => None,
+        }
+    }
+
+    /// Add the GPU phases to `stats`.
+    pub(crate) fn report(&self, stats: &mut InitStats) {
+        if let Some(timings) = &self.timings {
+            stats.gpu_device = Some(timings.device);
+            stats.renderer = Some(timings.renderer);
+            stats.pipeline_cache_hit = timings.cache_hit;
+        }
+        stats.warmed_in_background = self.warmed_in_background;
+    }
+
+    fn finish(&mut self, built: Built, config: &EngineConfig) {
+        self.state = match built {
+            Ok((mut gpu, timings)) => {
+                // Settings may have changed while the renderer was built
+                gpu.renderer.set_text_rendering(config.text_rendering);
+                let supported = gpu.compositor.supported_sample_counts();
+                gpu.renderer
+                    .set_antialiasing(config.antialiasing, &supported);
+                info!(
+                    adapter = ?gpu.compositor.adapter_info().name,
+                    device_ms = timings.device.as_millis() as u64,
+                    renderer_ms = timings.renderer.as_millis() as u64,
+                    pipeline_cache_hit = timings.cache_hit,
+                    "GPU renderer initialized"
+                );
+                self.timings = Some(timings);
+                GpuState::Ready(Box::new(gpu))
+            }
+            Err(e) => {
+                warn!(error = %e, "GPU initialization failed");
+                GpuState::Failed(e)
+            }
+        };
+    }
+}
+
+/// Build the compositor and the renderer.
+fn build(options: &GpuOptions) -> Built {
+    let start = Instant::now();
+    let compositor = Compositor::new().map_err(|e| e.to_string())?;
+    let device = start.elapsed();
+
+    let start = Instant::now();
+    let cache_path = options
+        .profile_directory
+        .as_deref()
+        .and_then(|dir| pipeline_cache_path(dir, &compositor.adapter_info()));
+    let device_arc = compositor.device_arc();
+    let (cache, cache_hit) = open_pipeline_cache(&device_arc, cache_path.as_deref());
+    let mut renderer = Renderer::with_pipeline_cache(
+        device_arc,
+        compositor.queue_arc(),
+        compositor.surface_format(),
+        cache.clone(),
+    )
+    .map_err(|e| e.to_string())?;
+    renderer.set_text_rendering(options.text_rendering);
+    renderer.set_antialiasing(options.antialiasing, &compositor.supported_sample_counts());
+    if let (Some(cache), Some(path)) = (&cache, &cache_path) {
+        save_pipeline_cache(cache, path);
+    }
+    let renderer_time = start.elapsed();
+
+    Ok((
+        Gpu {
+            compositor,
+            renderer,
+        },
+        GpuTimings {
+            device,
+            renderer: renderer_time,
+            cache_hit,
+        },
+    ))
+}
+
+/// Where the pipeline cache for `adapter` is kept, or `None` when its
+/// backend has no pipeline caches.
+fn pipeline_cache_path(profile: &Path, adapter: &wgpu::AdapterInfo) -> Option<PathBuf> {
+    let key = wgpu::util::pipeline_cache_key(adapter)?;
+    Some(profile.join(PIPELINE_CACHE_DIR).join(key))
+}
+
+/// A pipeline cache seeded from `path`, and whether there was data there.
+fn open_pipeline_cache(
+    device: &wgpu::Device,
+    path: Option<&Path>,
+) -> (Option<wgpu::PipelineCache>, bool) {
+    let Some(path) = path else {
+        return (None, false);
+    };
+    if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+        return (None, false);
+    }
+    let data = std::fs::read(path).ok().filter(|data| !data.is_empty());
+    // SAFETY: the data was written by `save_pipeline_cache` for the same
+    // adapter and driver, as the file name encodes, and `fallback` makes
+    // the driver ignore data it does not accept.
+    let cache = unsafe {
+        device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+            label: Some("RustKit Pipeline Cache"),
+            data: data.as_deref(),
+            fallback: true,
+        })
+    };
+    (Some(cache), data.is_some())
+}
+
+fn save_pipeline_cache(cache: &wgpu::PipelineCache, path: &Path) {
+    let Some(data) = cache.get_data() else {
+        return;
+    };
+    let temp = path.with_extension("tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&temp, &data))
+        .and_then(|()| std::fs::rename(&temp, path));
+    match result {
+        Ok(()) => debug!(path = %path.display(), bytes = data.len(), "Saved pipeline cache"),
+        Err(e) => warn!(path = %path.display(), error = %e, "Could not save pipeline cache"),
+    }
+}
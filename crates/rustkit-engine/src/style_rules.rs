@@ -0,0 +1,590 @@
+//! Author stylesheet rules for generated content.
+//!
+//! Rules from `<style>` elements are matched for `::before`/`::after`
+//! pseudo-elements, for `counter-reset`/`counter-increment`, for the
+//! fragmentation properties (`break-*`, `page-break-*`, `orphans`,
+//! `widows`) and for custom properties. Other element properties still come from tag defaults and
+//! inline styles.
+//! Rules inside `@media` blocks apply only while their queries match.
+//!
+//! Selectors support type, `*`, `#id`, `.class` and `[attr]`/`[attr=value]`
+//! compounds joined by descendant or child combinators. Rules using anything
+//! else are skipped rather than matched loosely.
+//!
+//! Styles in a shadow tree match only inside it, and document styles don't
+//! reach in. A shadow tree can style its host with `:host` or
+//! `:host(compound)` and the host children its slots take with
+//! `::slotted(compound)`; rules of the host's own tree win over both.
+
+use rustkit_css::{CustomProperties, MediaEnvironment, PseudoElement, Stylesheet};
+use rustkit_dom::{Document, Node, NodeId};
+use std::rc::Rc;
+use tracing::debug;
+
+/// Element properties taken from author rules.
+const ELEMENT_PROPERTIES: [&str; 10] = [
+    "counter-reset",
+    "counter-increment",
+    "break-before",
+    "break-after",
+    "break-inside",
+    "page-break-before",
+    "page-break-after",
+    "page-break-inside",
+    "orphans",
+    "widows",
+];
+
+/// A compound selector such as `h2.title[data-x]`.
+#[derive(Debug, Default)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, Option<String>)>,
+}
+
+impl Compound {
+    fn parse(input: &str) -> Option<Self> {
+        let mut compound = Compound::default();
+        let mut rest = input;
+
+        let ident_end = |s: &str| {
+            s.find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(s.len())
+        };
+
+        if let Some(stripped) = rest.strip_prefix('*') {
+            rest = stripped;
+        } else {
+            let end = ident_end(rest);
+            if end > 0 {
+                compound.tag = Some(rest[..end].to_ascii_lowercase());
+                rest = &rest[end..];
+            }
+        }
+
+        while let Some(c) = rest.chars().next() {
+            match c {
+                '#' | '.' => {
+                    let end = ident_end(&rest[1..]) + 1;
+                    if end == 1 {
+                        return None;
+                    }
+                    let name = rest[1..end].to_string();
+                    if c == '#' {
+                        compound.id = Some(name);
+                    } else {
+                        compound.classes.push(name);
+                    }
+                    rest = &rest[end..];
+                }
+                '[' => {
+                    let close = rest.find(']')?;
+                    let inner = &rest[1..close];
+                    let attribute = match inner.split_once('=') {
+                        Some((name, value)) => (
+                            name.trim().to_ascii_lowercase(),
+                            Some(value.trim().trim_matches(['"', '\'']).to_string()),
+                        ),
+                        None => (inner.trim().to_ascii_lowercase(), None),
+                    };
+                    // Operators such as ~= and ^= are not supported
+                    if attribute.0.is_empty() || attribute.0.ends_with(['~', '|', '^', '$', '*']) {
+                        return None;
+                    }
+                    compound.attributes.push(attribute);
+                    rest = &rest[close + 1..];
+                }
+                _ => return None,
+            }
+        }
+        Some(compound)
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        let Some(tag) = node.tag_name() else {
+            return false;
+        };
+        self.tag
+            .as_ref()
+            .is_none_or(|t| tag.eq_ignore_ascii_case(t))
+            && self
+                .id
+                .as_ref()
+                .is_none_or(|id| node.get_attribute("id") == Some(id.as_str()))
+            && self.classes.iter().all(|class| {
+                node.get_attribute("class")
+                    .is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+            })
+            && self
+                .attributes
+                .iter()
+                .all(|(name, value)| match (node.get_attribute(name), value) {
+                    (Some(actual), Some(expected)) => actual == expected,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                })
+    }
+
+    /// Specificity as (ids, classes and attributes, types).
+    fn specificity(&self) -> (u32, u32, u32) {
+        (
+            self.id.is_some() as u32,
+            (self.classes.len() + self.attributes.len()) as u32,
+            self.tag.is_some() as u32,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// A complex selector, stored rightmost compound first.
+#[derive(Debug)]
+struct Selector {
+    /// Each compound with the combinator joining it to the next one left.
+    parts: Vec<(Compound, Option<Combinator>)>,
+}
+
+impl Selector {
+    fn parse(input: &str) -> Option<Self> {
+        let spaced = input.replace('>', " > ");
+        let mut parts: Vec<(Compound, Option<Combinator>)> = Vec::new();
+        let mut pending = None;
+        for token in spaced.split_whitespace() {
+            if token == ">" {
+                if parts.is_empty() || pending.is_some() {
+                    return None;
+                }
+                pending = Some(Combinator::Child);
+                continue;
+            }
+            if let Some(last) = parts.last_mut() {
+                last.1 = Some(pending.take().unwrap_or(Combinator::Descendant));
+            }
+            parts.push((Compound::parse(token)?, None));
+        }
+        if parts.is_empty() || pending.is_some() {
+            return None;
+        }
+        parts.reverse();
+        // Shift combinators so each compound records how it joins its left
+        // neighbour
+        let combinators: Vec<_> = parts.iter().map(|(_, c)| *c).collect();
+        for (i, part) in parts.iter_mut().enumerate() {
+            part.1 = combinators.get(i + 1).copied().flatten();
+        }
+        Some(Self { parts })
+    }
+
+    fn matches(&self, node: &Rc<Node>) -> bool {
+        self.matches_from(node, 0)
+    }
+
+    fn matches_from(&self, node: &Rc<Node>, index: usize) -> bool {
+        let (compound, combinator) = &self.parts[index];
+        if !compound.matches(node) {
+            return false;
+        }
+        match combinator {
+            None => true,
+            Some(Combinator::Child) => node
+                .parent()
+                .is_some_and(|parent| self.matches_from(&parent, index + 1)),
+            Some(Combinator::Descendant) => {
+                let mut ancestor = node.parent();
+                while let Some(current) = ancestor {
+                    if self.matches_from(&current, index + 1) {
+                        return true;
+                    }
+                    ancestor = current.parent();
+                }
+                false
+            }
+        }
+    }
+
+    fn specificity(&self) -> (u32, u32, u32) {
+        self.parts.iter().fold((0, 0, 0), |acc, (compound, _)| {
+            let s = compound.specificity();
+            (acc.0 + s.0, acc.1 + s.1, acc.2 + s.2)
+        })
+    }
+}
+
+/// What a rule's selector matches.
+#[derive(Debug)]
+enum RuleTarget {
+    /// Elements of the rule's own tree.
+    Tree(Selector),
+    /// `:host` or `:host(compound)`: the host of the rule's shadow tree.
+    Host(Option<Compound>),
+    /// `::slotted(compound)`: host children assigned to the tree's slots.
+    Slotted(Compound),
+}
+
+impl RuleTarget {
+    fn parse(selector: &str) -> Option<Self> {
+        let selector = selector.trim();
+        if let Some(rest) = selector.strip_prefix(":host") {
+            if rest.is_empty() {
+                return Some(Self::Host(None));
+            }
+            let inner = rest.strip_prefix('(')?.strip_suffix(')')?;
+            return Some(Self::Host(Some(Compound::parse(inner.trim())?)));
+        }
+        if let Some(rest) = selector.strip_prefix("::slotted(") {
+            let inner = rest.strip_suffix(')')?;
+            return Some(Self::Slotted(Compound::parse(inner.trim())?));
+        }
+        Selector::parse(selector).map(Self::Tree)
+    }
+
+    fn specificity(&self) -> (u32, u32, u32) {
+        match self {
+            Self::Tree(selector) => selector.specificity(),
+            Self::Host(compound) => {
+                let s = compound.as_ref().map_or((0, 0, 0), Compound::specificity);
+                (s.0, s.1 + 1, s.2)
+            }
+            Self::Slotted(compound) => {
+                let s = compound.specificity();
+                (s.0, s.1, s.2 + 1)
+            }
+        }
+    }
+}
+
+/// One selector of a rule with the declarations it applies.
+#[derive(Debug)]
+struct StyleRule {
+    target: RuleTarget,
+    pseudo: Option<PseudoElement>,
+    specificity: (u32, u32, u32),
+    declarations: Vec<(String, String)>,
+    /// Shadow root of the tree whose styles the rule came from, or `None`
+    /// for the document's.
+    scope: Option<NodeId>,
+}
+
+impl StyleRule {
+    /// Whether the rule applies to `node`, and if so whether from the
+    /// tree `node` is in rather than a shadow tree below it.
+    fn matches(&self, node: &Rc<Node>) -> Option<bool> {
+        let scope_of = |node: &Rc<Node>| node.containing_shadow_root().map(|root| root.id);
+        match &self.target {
+            RuleTarget::Tree(selector) => {
+                (self.scope == scope_of(node) && selector.matches(node)).then_some(true)
+            }
+            RuleTarget::Host(compound) => {
+                let own = node.shadow_root().map(|root| root.id);
+                (own.is_some()
+                    && self.scope == own
+                    && compound.as_ref().is_none_or(|c| c.matches(node)))
+                .then_some(false)
+            }
+            RuleTarget::Slotted(compound) => {
+                let slot = node.assigned_slot()?;
+                (self.scope == scope_of(&slot) && compound.matches(node)).then_some(false)
+            }
+        }
+    }
+}
+
+/// Every `<style>` element under `node` in tree order, with the shadow root
+/// of the tree it is in, including those in shadow trees of hosts under it.
+fn style_elements(
+    node: &Rc<Node>,
+    scope: Option<&Rc<Node>>,
+    styles: &mut Vec<(Option<NodeId>, Rc<Node>)>,
+) {
+    if node
+        .tag_name()
+        .is_some_and(|t| t.eq_ignore_ascii_case("style"))
+    {
+        styles.push((scope.map(|root| root.id), node.clone()));
+    }
+    if let Some(root) = node.shadow_root() {
+        style_elements(&root, Some(&root), styles);
+    }
+    for child in node.children() {
+        style_elements(&child, scope, styles);
+    }
+}
+
+/// Rules relevant to generated content, in source order.
+#[derive(Debug, Default)]
+pub(crate) struct StyleRules {
+    rules: Vec<StyleRule>,
+}
+
+impl StyleRules {
+    /// Collect the rules of every `<style>` element in `document` and its
+    /// shadow trees that apply in `media`.
+    pub(crate) fn from_document(document: &Document, media: &MediaEnvironment) -> Self {
+        let mut styles = Vec::new();
+        style_elements(document.root(), None, &mut styles);
+
+        // One stylesheet per tree
+        let mut sheets: Vec<(Option<NodeId>, String)> = Vec::new();
+        for (scope, style) in styles {
+            let index = match sheets.iter().position(|(s, _)| *s == scope) {
+                Some(index) => index,
+                None => {
+                    sheets.push((scope, String::new()));
+                    sheets.len() - 1
+                }
+            };
+            let css = &mut sheets[index].1;
+            css.push_str(&style.text_content());
+            css.push('\n');
+        }
+
+        let mut rules = Self::default();
+        for (scope, css) in sheets {
+            if css.trim().is_empty() {
+                continue;
+            }
+            match Stylesheet::parse(&css) {
+                Ok(stylesheet) => rules.rules.extend(
+                    Self::from_stylesheet(&stylesheet, media)
+                        .rules
+                        .into_iter()
+                        .map(|rule| StyleRule { scope, ..rule }),
+                ),
+                Err(e) => debug!(error = %e, "Ignoring unparsable stylesheet"),
+            }
+        }
+        rules
+    }
+
+    fn from_stylesheet(stylesheet: &Stylesheet, media: &MediaEnvironment) -> Self {
+        let mut rules = Vec::new();
+        for rule in stylesheet.rules.iter().filter(|r| r.applies_in(media)) {
+            let declarations: Vec<(String, String)> = rule
+                .declarations
+                .iter()
+                .filter_map(|d| match &d.value {
+                    rustkit_css::PropertyValue::Specified(value) => {
+                        // Custom property names are case-sensitive
+                        let property = if CustomProperties::is_custom(&d.property) {
+                            d.property.clone()
+                        } else {
+                            d.property.to_ascii_lowercase()
+                        };
+                        Some((property, value.clone()))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            for selector in rule.selector.split(',') {
+                let (selector, pseudo) = PseudoElement::split_selector(selector);
+                let declarations: Vec<_> = match pseudo {
+                    Some(_) => declarations.clone(),
+                    None => declarations
+                        .iter()
+                        .filter(|(property, _)| {
+                            ELEMENT_PROPERTIES.contains(&property.as_str())
+                                || CustomProperties::is_custom(property)
+                        })
+                        .cloned()
+                        .collect(),
+                };
+                if declarations.is_empty() {
+                    continue;
+                }
+                let Some(target) = RuleTarget::parse(selector) else {
+                    continue;
+                };
+                rules.push(StyleRule {
+                    specificity: target.specificity(),
+                    target,
+                    pseudo,
+                    declarations,
+                    scope: None,
+                });
+            }
+        }
+        Self { rules }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Declarations matching `node` (or its pseudo-element) in cascade
+    /// order, so later entries win. Rules of the tree `node` is in come
+    /// after those a shadow tree gives it through `:host` or `::slotted`.
+    pub(crate) fn declarations(
+        &self,
+        node: &Rc<Node>,
+        pseudo: Option<PseudoElement>,
+    ) -> Vec<&(String, String)> {
+        let mut matched: Vec<_> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.pseudo == pseudo)
+            .filter_map(|(order, rule)| Some((rule.matches(node)?, order, rule)))
+            .collect();
+        matched.sort_by_key(|(own_tree, order, rule)| (*own_tree, rule.specificity, *order));
+        matched
+            .into_iter()
+            .flat_map(|(_, _, rule)| rule.declarations.iter())
+            .collect()
+    }
+}
+
+/// Which `@media` rules of each `<style>` element match `media`, in
+/// document order. Styles only need recomputing when this changes.
+pub(crate) fn media_matches(document: &Document, media: &MediaEnvironment) -> Vec<Vec<bool>> {
+    let mut styles = Vec::new();
+    style_elements(document.root(), None, &mut styles);
+    styles
+        .iter()
+        .map(|(_, node)| {
+            Stylesheet::parse(&node.text_content())
+                .map(|sheet| {
+                    sheet
+                        .rules
+                        .iter()
+                        .filter(|r| !r.media.is_empty())
+                        .map(|r| r.applies_in(media))
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_matching() {
+        let document = Document::parse_html(
+            r#"<html><body><div class="doc"><h2 id="a" data-x="1">A</h2></div><h2 id="b">B</h2></body></html>"#,
+        )
+        .unwrap();
+        let a = document.get_element_by_id("a").unwrap();
+        let b = document.get_element_by_id("b").unwrap();
+
+        let matches = |selector: &str, node: &Rc<Node>| {
+            Selector::parse(selector).is_some_and(|s| s.matches(node))
+        };
+        assert!(matches("h2", &a));
+        assert!(matches(".doc h2", &a));
+        assert!(!matches(".doc h2", &b));
+        assert!(matches("div > h2#a", &a));
+        assert!(!matches("body > h2#a", &a));
+        assert!(matches("body > h2", &b));
+        assert!(matches("[data-x=\"1\"]", &a));
+        assert!(!matches("h2:hover", &a));
+    }
+
+    #[test]
+    fn test_rules_keep_generated_content_only() {
+        let stylesheet = Stylesheet::parse(
+            "h2 { color: red; counter-increment: sec } \
+             h2::before, .note:after { content: counter(sec) } \
+             p { color: blue }",
+        )
+        .unwrap();
+        let rules = StyleRules::from_stylesheet(&stylesheet, &MediaEnvironment::default());
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(
+            rules.rules[0].declarations,
+            [("counter-increment".to_string(), "sec".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_specificity_orders_declarations() {
+        let document =
+            Document::parse_html(r#"<html><body><h2 id="t" class="x">T</h2></body></html>"#)
+                .unwrap();
+        let stylesheet = Stylesheet::parse(
+            "#t::before { content: 'id' } h2.x::before { content: 'class' } h2::before { content: 'tag' }",
+        )
+        .unwrap();
+        let rules = StyleRules::from_stylesheet(&stylesheet, &MediaEnvironment::default());
+        let node = document.get_element_by_id("t").unwrap();
+        let values: Vec<_> = rules
+            .declarations(&node, Some(PseudoElement::Before))
+            .into_iter()
+            .map(|(_, value)| value.as_str())
+            .collect();
+        assert_eq!(values, ["'tag'", "'class'", "'id'"]);
+    }
+
+    #[test]
+    fn test_shadow_styles_are_scoped() {
+        let document = Document::parse_html(
+            r#"<html><head><style>
+                p { counter-reset: outer } b { counter-increment: light }
+            </style></head><body><div id="host"><b>slotted</b></div><p id="light"></p></body></html>"#,
+        )
+        .unwrap();
+        let host = document.get_element_by_id("host").unwrap();
+        let root = document
+            .attach_shadow(&host, rustkit_dom::ShadowRootMode::Open)
+            .unwrap();
+        document
+            .set_inner_html(
+                &root,
+                "<style>p { counter-reset: inner } :host(div) { counter-reset: host } \
+                 ::slotted(b) { counter-increment: slotted }</style><p></p><slot></slot>",
+            )
+            .unwrap();
+        let rules = StyleRules::from_document(&document, &MediaEnvironment::default());
+        let values = |node: &Rc<Node>| -> Vec<String> {
+            rules
+                .declarations(node, None)
+                .into_iter()
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            values(&document.get_element_by_id("light").unwrap()),
+            ["outer"]
+        );
+        assert_eq!(values(&root.children()[1]), ["inner"]);
+        assert_eq!(values(&host), ["host"]);
+        // The document's own rule wins over the shadow tree's
+        assert_eq!(values(&host.children()[0]), ["slotted", "light"]);
+        assert_eq!(
+            media_matches(&document, &MediaEnvironment::default()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_media_rules_follow_viewport() {
+        let document = Document::parse_html(
+            r#"<html><head><style>
+                @media (max-width: 500px) { .nav::before { content: "menu" } }
+                @media (orientation: portrait) { .nav::after { content: "!" } }
+            </style></head><body><div class="nav"></div></body></html>"#,
+        )
+        .unwrap();
+        let wide = MediaEnvironment::screen(800.0, 600.0);
+        let narrow = MediaEnvironment::screen(400.0, 600.0);
+        assert!(StyleRules::from_document(&document, &wide).is_empty());
+        assert_eq!(StyleRules::from_document(&document, &narrow).rules.len(), 2);
+
+        assert_eq!(media_matches(&document, &wide), [vec![false, false]]);
+        assert_eq!(media_matches(&document, &narrow), [vec![true, true]]);
+        // Height changes that cross no breakpoint keep the same matches
+        assert_eq!(
+            media_matches(&document, &MediaEnvironment::screen(800.0, 700.0)),
+            media_matches(&document, &wide)
+        );
+    }
+}
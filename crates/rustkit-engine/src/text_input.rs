@@ -0,0 +1,410 @@
+//! Text entry for `<input>` and `<textarea>` controls.
+//!
+//! Translated characters (`InputEvent::CharInput`) are inserted at the caret
+//! of the focused control. IME compositions (`InputEvent::ImeComposition`) are
+//! kept beside the value and only painted, underlined, at the caret until
+//! they are committed or cancelled, so the value changes exactly once.
+
+use rustkit_core::InputEvent;
+use rustkit_css::{ComputedStyle, Length};
+use rustkit_dom::{Document, InputType, Node, NodeId, NodeType, TextEditState};
+use rustkit_layout::{
+    calculate_caret_position, calculate_selection_rects, DisplayCommand, Rect,
+    TextDecorationStyleValue,
+};
+use std::collections::HashMap;
+
+/// An in-progress IME composition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Composition {
+    /// The control being composed into.
+    pub node_id: NodeId,
+    /// The uncommitted text.
+    pub text: String,
+    /// Caret offset within `text`, in chars.
+    pub cursor: usize,
+}
+
+/// Editing state for the text controls of one view.
+#[derive(Debug, Default)]
+pub struct TextInput {
+    fields: HashMap<NodeId, TextEditState>,
+    composition: Option<Composition>,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `node` accepts typed text.
+    pub fn is_text_control(node: &Node) -> bool {
+        match &node.node_type {
+            NodeType::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("textarea") => true,
+            NodeType::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("input") => {
+                InputType::from_str(node.get_attribute("type").unwrap_or("text")).is_text_input()
+            }
+            _ => false,
+        }
+    }
+
+    /// Apply a text event to the focused control.
+    ///
+    /// Returns true if the value or the composition changed.
+    pub fn handle_event(&mut self, node: &Node, event: &InputEvent) -> bool {
+        if !Self::is_text_control(node) {
+            return false;
+        }
+
+        match event {
+            InputEvent::CharInput { ch } => {
+                // Characters typed mid-composition belong to the IME
+                if self.composition.is_some() {
+                    return false;
+                }
+                let field = self.field(node);
+                match ch {
+                    '\u{8}' => field.delete_backward(),
+                    '\r' | '\t' => false,
+                    ch => field.insert_text(ch.encode_utf8(&mut [0; 4])),
+                }
+            }
+            InputEvent::ImeComposition {
+                text,
+                is_commit: true,
+                ..
+            } => {
+                let had_composition = self.composition.take().is_some();
+                self.field(node).insert_text(text) || had_composition
+            }
+            InputEvent::ImeComposition { text, .. } if text.is_empty() => {
+                self.composition.take().is_some()
+            }
+            InputEvent::ImeComposition { text, cursor, .. } => {
+                let composition = Composition {
+                    node_id: node.id,
+                    text: text.clone(),
+                    cursor: (*cursor).min(text.chars().count()),
+                };
+                let changed = self.composition.as_ref() != Some(&composition);
+                self.composition = Some(composition);
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    /// The current value of a control, if it has been edited.
+    pub fn value(&self, node_id: NodeId) -> Option<String> {
+        self.fields.get(&node_id).map(TextEditState::value)
+    }
+
+    /// The value of a control: its edited value or its initial one.
+    pub fn value_of(&self, node: &Node) -> String {
+        self.value(node.id)
+            .unwrap_or_else(|| Self::initial_value(node))
+    }
+
+    /// Replace the value of a control as if `value` were typed over it.
+    /// Returns false if the control can't be edited or `value` is too long.
+    pub fn fill(&mut self, node: &Node, value: &str) -> bool {
+        if !Self::is_text_control(node) {
+            return false;
+        }
+        if self.composition_for(node.id).is_some() {
+            self.composition = None;
+        }
+        let field = self.field(node);
+        if !field.is_editable() {
+            return false;
+        }
+        field.select_all();
+        if value.is_empty() {
+            field.delete_backward();
+            true
+        } else {
+            field.insert_text(value)
+        }
+    }
+
+    /// Edited values of controls other than password fields.
+    pub fn saved_values(&self, document: &Document) -> Vec<(NodeId, String)> {
+        self.fields
+            .iter()
+            .filter(|(&node_id, _)| {
+                document.get_node(node_id).is_some_and(|node| {
+                    !node
+                        .get_attribute("type")
+                        .is_some_and(|t| t.eq_ignore_ascii_case("password"))
+                })
+            })
+            .map(|(&node_id, field)| (node_id, field.value()))
+            .collect()
+    }
+
+    /// Put back a value saved with [`Self::saved_values`]. Returns false
+    /// if `node` is no longer a text control.
+    pub fn restore_value(&mut self, node: &Node, value: &str) -> bool {
+        if !Self::is_text_control(node) {
+            return false;
+        }
+        self.field(node).set_value(value);
+        true
+    }
+
+    /// Controls with an edited value or a composition to paint.
+    pub fn nodes(&self) -> Vec<NodeId> {
+        let mut nodes: Vec<_> = self.fields.keys().copied().collect();
+        if let Some(c) = &self.composition {
+            if !self.fields.contains_key(&c.node_id) {
+                nodes.push(c.node_id);
+            }
+        }
+        nodes
+    }
+
+    /// The in-progress composition, if any.
+    pub fn composition(&self) -> Option<&Composition> {
+        self.composition.as_ref()
+    }
+
+    /// Drop the composition when focus leaves its control.
+    pub fn blur(&mut self, node_id: NodeId) {
+        if self.composition.as_ref().map(|c| c.node_id) == Some(node_id) {
+            self.composition = None;
+        }
+    }
+
+    /// The caret rect for `node_id` inside `content_box`, including any
+    /// composition cursor.
+    pub fn caret_rect(&self, node_id: NodeId, content_box: &Rect, font_size: f32) -> Rect {
+        let (text, caret) = self.display_text(node_id);
+        let caret = calculate_caret_position(&text, caret, content_box, font_size);
+        Rect::new(caret.x, caret.y, caret.width, caret.height)
+    }
+
+    /// Paint the edited value of `node_id`, with the composition spliced in
+    /// at the caret and underlined.
+    pub fn paint(
+        &self,
+        node_id: NodeId,
+        content_box: &Rect,
+        style: &ComputedStyle,
+        focused: bool,
+    ) -> Vec<DisplayCommand> {
+        if !self.fields.contains_key(&node_id) && self.composition_for(node_id).is_none() {
+            return Vec::new();
+        }
+
+        let font_size = match style.font_size {
+            Length::Px(px) => px,
+            _ => 14.0,
+        };
+        let (text, caret) = self.display_text(node_id);
+        let mut commands = vec![DisplayCommand::Text {
+            text: text.clone(),
+            x: content_box.x,
+            y: content_box.y + font_size,
+            color: style.color,
+            font_size,
+            font_family: style.font_family.clone(),
+            font_weight: style.font_weight.0,
+            font_style: match style.font_style {
+                rustkit_css::FontStyle::Normal => 0,
+                rustkit_css::FontStyle::Italic => 1,
+                rustkit_css::FontStyle::Oblique => 2,
+            },
+        }];
+
+        if let Some(composition) = self.composition_for(node_id) {
+            let start = self.caret_byte(node_id);
+            let end = start + composition.text.len();
+            for rect in calculate_selection_rects(&text, start, end, content_box, font_size) {
+                commands.push(DisplayCommand::TextDecoration {
+                    x: rect.x,
+                    y: rect.y + rect.height - 1.0,
+                    width: rect.width,
+                    thickness: 1.0,
+                    color: style.color,
+                    style: TextDecorationStyleValue::Solid,
+                });
+            }
+        }
+
+        if focused {
+            let caret = calculate_caret_position(&text, caret, content_box, font_size);
+            commands.push(DisplayCommand::SolidColor(
+                caret.color,
+                Rect::new(caret.x, caret.y, caret.width, caret.height),
+            ));
+        }
+        commands
+    }
+
+    fn field(&mut self, node: &Node) -> &TextEditState {
+        self.fields.entry(node.id).or_insert_with(|| {
+            let state = TextEditState::with_value(Self::initial_value(node));
+            state.set_max_length(
+                node.get_attribute("maxlength")
+                    .and_then(|m| m.trim().parse().ok()),
+            );
+            state.set_read_only(node.get_attribute("readonly").is_some());
+            state.set_disabled(node.get_attribute("disabled").is_some());
+            state
+        })
+    }
+
+    fn initial_value(node: &Node) -> String {
+        match &node.node_type {
+            NodeType::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("textarea") => {
+                node.text_content()
+            }
+            _ => node.get_attribute("value").unwrap_or("").to_string(),
+        }
+    }
+
+    fn composition_for(&self, node_id: NodeId) -> Option<&Composition> {
+        self.composition.as_ref().filter(|c| c.node_id == node_id)
+    }
+
+    fn caret_byte(&self, node_id: NodeId) -> usize {
+        self.fields
+            .get(&node_id)
+            .map_or(0, |f| f.selection().normalize().start)
+    }
+
+    /// The value with the composition spliced in, and the caret byte offset.
+    fn display_text(&self, node_id: NodeId) -> (String, usize) {
+        let mut text = self.value(node_id).unwrap_or_default();
+        let caret = self.caret_byte(node_id);
+        match self.composition_for(node_id) {
+            Some(composition) => {
+                text.insert_str(caret, &composition.text);
+                let cursor: usize = composition
+                    .text
+                    .chars()
+                    .take(composition.cursor)
+                    .map(char::len_utf8)
+                    .sum();
+                (text, caret + cursor)
+            }
+            None => (text, caret),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_core::{KeyCode, KeyEvent, KeyEventType, Modifiers};
+    use rustkit_dom::Document;
+
+    fn input(html: &str) -> (Document, NodeId) {
+        let document = Document::parse_html(html).unwrap();
+        let id = document.get_element_by_id("field").unwrap().id;
+        (document, id)
+    }
+
+    fn compose(text: &str, cursor: usize, is_commit: bool) -> InputEvent {
+        InputEvent::ImeComposition {
+            text: text.to_string(),
+            cursor,
+            is_commit,
+        }
+    }
+
+    #[test]
+    fn test_char_input_and_dead_keys() {
+        let (document, id) = input(r#"<input id="field" value="caf">"#);
+        let node = document.get_node(id).unwrap();
+        let mut text = TextInput::new();
+
+        // A dead key produces a key-down but no character of its own
+        let dead = KeyEvent::new(
+            KeyEventType::KeyDown,
+            KeyCode::Unknown,
+            Modifiers::default(),
+        );
+        assert!(!text.handle_event(&node, &InputEvent::Key(dead)));
+        assert!(text.handle_event(&node, &InputEvent::CharInput { ch: 'é' }));
+        assert_eq!(text.value(id).as_deref(), Some("café"));
+
+        assert!(text.handle_event(&node, &InputEvent::CharInput { ch: '\u{8}' }));
+        assert!(text.handle_event(&node, &InputEvent::CharInput { ch: '😀' }));
+        assert_eq!(text.value(id).as_deref(), Some("caf😀"));
+    }
+
+    #[test]
+    fn test_ime_composition_commit() {
+        let (document, id) = input(r#"<input id="field" value="a">"#);
+        let node = document.get_node(id).unwrap();
+        let mut text = TextInput::new();
+        text.handle_event(&node, &InputEvent::CharInput { ch: 'b' });
+
+        assert!(text.handle_event(&node, &compose("にほ", 2, false)));
+        assert!(text.handle_event(&node, &compose("日本", 2, false)));
+        // The value is untouched while composing
+        assert_eq!(text.value(id).as_deref(), Some("ab"));
+
+        let style = ComputedStyle::new();
+        let content = Rect::new(0.0, 0.0, 200.0, 20.0);
+        let commands = text.paint(id, &content, &style, true);
+        assert!(matches!(&commands[0], DisplayCommand::Text { text, .. } if text == "ab日本"));
+        let underlines: Vec<_> = commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::TextDecoration { x, width, .. } => Some((*x, *width)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(underlines.len(), 1);
+        let char_width = match style.font_size {
+            Length::Px(px) => px * 0.5,
+            _ => 7.0,
+        };
+        assert_eq!(underlines[0], (2.0 * char_width, 2.0 * char_width));
+
+        assert!(text.handle_event(&node, &compose("日本", 2, true)));
+        assert_eq!(text.value(id).as_deref(), Some("ab日本"));
+        assert!(text.composition().is_none());
+        let commands = text.paint(id, &content, &style, true);
+        assert!(!commands
+            .iter()
+            .any(|c| matches!(c, DisplayCommand::TextDecoration { .. })));
+
+        // The end-of-composition notification after a commit changes nothing
+        assert!(!text.handle_event(&node, &compose("", 0, false)));
+        assert_eq!(text.value(id).as_deref(), Some("ab日本"));
+    }
+
+    #[test]
+    fn test_ime_composition_cancel() {
+        let (document, id) = input(r#"<textarea id="field">x</textarea>"#);
+        let node = document.get_node(id).unwrap();
+        let mut text = TextInput::new();
+
+        text.handle_event(&node, &compose("ㅎ", 1, false));
+        // Characters are swallowed while the IME owns the keyboard
+        assert!(!text.handle_event(&node, &InputEvent::CharInput { ch: 'q' }));
+        assert!(text.handle_event(&node, &compose("", 0, false)));
+        assert!(text.composition().is_none());
+        assert_eq!(text.value(id), None);
+        assert!(text
+            .paint(
+                id,
+                &Rect::new(0.0, 0.0, 100.0, 20.0),
+                &ComputedStyle::new(),
+                true
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_non_text_controls_ignore_input() {
+        let (document, id) = input(r#"<input id="field" type="checkbox">"#);
+        let node = document.get_node(id).unwrap();
+        let mut text = TextInput::new();
+        assert!(!text.handle_event(&node, &InputEvent::CharInput { ch: 'a' }));
+        assert_eq!(text.value(id), None);
+    }
+}
@@ -0,0 +1,441 @@
+//! CSS transitions of a view's document.
+//!
+//! Each restyle compares an element's transitionable values with the ones
+//! from the previous restyle; a change covered by its `transition-*`
+//! properties starts a transition on the view's timeline. Running
+//! transitions override the computed style. Opacity, colors and transform
+//! only need the existing layout tree repainted each frame; sizes and
+//! margins need a relayout. When the user prefers reduced motion, changes
+//! that move content (transform, sizes, margins) take effect at once;
+//! fades of opacity and color still run.
+
+use rustkit_animation::{
+    AnimatableProperty, AnimatableValue, AnimationEvent, AnimationTimeline, TimingFunction,
+    Transition, TransitionId,
+};
+use rustkit_css::{ComputedStyle, Length};
+use rustkit_dom::NodeId;
+use rustkit_layout::{BoxType, LayoutBox};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Properties that transition.
+const PROPERTIES: [AnimatableProperty; 10] = [
+    AnimatableProperty::Opacity,
+    AnimatableProperty::Color,
+    AnimatableProperty::BackgroundColor,
+    AnimatableProperty::Transform,
+    AnimatableProperty::Width,
+    AnimatableProperty::Height,
+    AnimatableProperty::MarginTop,
+    AnimatableProperty::MarginRight,
+    AnimatableProperty::MarginBottom,
+    AnimatableProperty::MarginLeft,
+];
+
+/// What a frame has to redo for the transitions it advanced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameWork {
+    None,
+    Paint,
+    Layout,
+}
+
+/// The value of `property` in `style`; `None` for lengths other than px.
+fn value_of(style: &ComputedStyle, property: AnimatableProperty) -> Option<AnimatableValue> {
+    let px = |length: Length| match length {
+        Length::Px(px) => Some(AnimatableValue::Length(px)),
+        _ => None,
+    };
+    match property {
+        AnimatableProperty::Opacity => Some(AnimatableValue::Opacity(style.opacity)),
+        AnimatableProperty::Color => Some(AnimatableValue::Color(style.color)),
+        AnimatableProperty::BackgroundColor => Some(AnimatableValue::Color(style.background_color)),
+        AnimatableProperty::Transform => Some(AnimatableValue::Transform(
+            style.transform.clone().unwrap_or_else(|| "none".into()),
+        )),
+        AnimatableProperty::Width => px(style.width),
+        AnimatableProperty::Height => px(style.height),
+        AnimatableProperty::MarginTop => px(style.margin_top),
+        AnimatableProperty::MarginRight => px(style.margin_right),
+        AnimatableProperty::MarginBottom => px(style.margin_bottom),
+        AnimatableProperty::MarginLeft => px(style.margin_left),
+        _ => None,
+    }
+}
+
+fn set_value(style: &mut ComputedStyle, property: AnimatableProperty, value: &AnimatableValue) {
+    match (property, value) {
+        (AnimatableProperty::Opacity, AnimatableValue::Opacity(opacity)) => {
+            style.opacity = *opacity
+        }
+        (AnimatableProperty::Color, AnimatableValue::Color(color)) => style.color = *color,
+        (AnimatableProperty::BackgroundColor, AnimatableValue::Color(color)) => {
+            style.background_color = *color
+        }
+        (AnimatableProperty::Transform, AnimatableValue::Transform(transform)) => {
+            style.transform = (transform != "none").then(|| transform.clone())
+        }
+        (AnimatableProperty::Width, AnimatableValue::Length(px)) => style.width = Length::Px(*px),
+        (AnimatableProperty::Height, AnimatableValue::Length(px)) => style.height = Length::Px(*px),
+        (AnimatableProperty::MarginTop, AnimatableValue::Length(px)) => {
+            style.margin_top = Length::Px(*px)
+        }
+        (AnimatableProperty::MarginRight, AnimatableValue::Length(px)) => {
+            style.margin_right = Length::Px(*px)
+        }
+        (AnimatableProperty::MarginBottom, AnimatableValue::Length(px)) => {
+            style.margin_bottom = Length::Px(*px)
+        }
+        (AnimatableProperty::MarginLeft, AnimatableValue::Length(px)) => {
+            style.margin_left = Length::Px(*px)
+        }
+        _ => {}
+    }
+}
+
+/// Call `f` with the style of every element box in the tree.
+fn for_each_element(layout_box: &mut LayoutBox, f: &mut impl FnMut(NodeId, &mut ComputedStyle)) {
+    if let Some(node) = layout_box.node_id {
+        if !matches!(layout_box.box_type, BoxType::Text(_)) {
+            f(node, &mut layout_box.style);
+        }
+    }
+    for child in &mut layout_box.children {
+        for_each_element(child, f);
+    }
+}
+
+/// Transitions of one document.
+#[derive(Debug, Default)]
+pub(crate) struct Transitions {
+    timeline: AnimationTimeline,
+    /// After-change values of each element from the last restyle.
+    styles: HashMap<NodeId, HashMap<AnimatableProperty, AnimatableValue>>,
+    /// The transition running for an element's property.
+    running: HashMap<(NodeId, AnimatableProperty), TransitionId>,
+    reduced_motion: bool,
+}
+
+impl Transitions {
+    /// Follow `prefers-reduced-motion` for transitions started from now on.
+    pub(crate) fn set_reduced_motion(&mut self, reduced_motion: bool) {
+        self.reduced_motion = reduced_motion;
+    }
+
+    /// Start, replace or cancel transitions for the styles just computed
+    /// into `root`, then apply running transitions as of `now`. Call before
+    /// laying out `root`.
+    pub(crate) fn restyle(&mut self, root: &mut LayoutBox, now: Instant) {
+        let mut seen = HashSet::new();
+        for_each_element(root, &mut |node, style| {
+            seen.insert(node);
+            for property in PROPERTIES {
+                self.update(node, property, style, now);
+            }
+        });
+
+        // Elements that left the tree take their transitions with them
+        self.styles.retain(|node, _| seen.contains(node));
+        let orphaned: Vec<_> = self
+            .running
+            .keys()
+            .filter(|(node, _)| !seen.contains(node))
+            .copied()
+            .collect();
+        for key in orphaned {
+            self.cancel(key);
+        }
+
+        self.tick(now);
+        self.apply(root, |_| true);
+    }
+
+    fn update(
+        &mut self,
+        node: NodeId,
+        property: AnimatableProperty,
+        style: &ComputedStyle,
+        now: Instant,
+    ) {
+        let Some(after) = value_of(style, property) else {
+            return;
+        };
+        let spec = style.transition_for(property.name());
+        let before = self
+            .styles
+            .entry(node)
+            .or_default()
+            .insert(property, after.clone());
+        let Some(before) = before.filter(|before| *before != after) else {
+            // Unchanged; a running transition stops if no longer covered
+            if spec.is_none() {
+                self.cancel((node, property));
+            }
+            return;
+        };
+
+        // A change mid-transition starts from the value on screen
+        let from = self
+            .running
+            .get(&(node, property))
+            .and_then(|id| self.timeline.get_transition(*id))
+            .map_or(before, |t| t.current_value.clone());
+        self.cancel((node, property));
+        let Some(spec) = spec else {
+            return;
+        };
+        if self.reduced_motion
+            && (property == AnimatableProperty::Transform || property.triggers_layout())
+        {
+            return;
+        }
+
+        let easing = TimingFunction::parse(&spec.timing_function).unwrap_or_default();
+        let mut transition = Transition::new(
+            node,
+            property,
+            from,
+            after,
+            Duration::from_secs_f32(spec.duration),
+            Duration::from_secs_f32(spec.delay.max(0.0)),
+            easing,
+        );
+        // A negative delay starts the transition part way through
+        let skipped = Duration::from_secs_f32((-spec.delay).max(0.0));
+        transition.start_time = Some(now.checked_sub(skipped).unwrap_or(now));
+        let id = self.timeline.add_transition(transition);
+        self.running.insert((node, property), id);
+    }
+
+    fn cancel(&mut self, key: (NodeId, AnimatableProperty)) {
+        if let Some(id) = self.running.remove(&key) {
+            self.timeline.cancel_transition(id);
+        }
+    }
+
+    /// Advance running transitions to `now`.
+    pub(crate) fn tick(&mut self, now: Instant) -> FrameWork {
+        if self.running.is_empty() {
+            return FrameWork::None;
+        }
+        let layout = self.running.keys().any(|(_, p)| p.triggers_layout());
+        self.timeline.tick_at(now);
+        let timeline = &self.timeline;
+        self.running
+            .retain(|_, id| timeline.get_transition(*id).is_some());
+        if layout {
+            FrameWork::Layout
+        } else {
+            FrameWork::Paint
+        }
+    }
+
+    /// Re-apply paint-only values to a tree that is already laid out.
+    pub(crate) fn apply_paint(&self, root: &mut LayoutBox) {
+        self.apply(root, |property| !property.triggers_layout());
+    }
+
+    /// Write the value of each transitioned property into `root`: the
+    /// running transition's, or the after-change value once it finished.
+    fn apply(&self, root: &mut LayoutBox, filter: impl Fn(AnimatableProperty) -> bool) {
+        for_each_element(root, &mut |node, style| {
+            let Some(values) = self.styles.get(&node) else {
+                return;
+            };
+            for (property, after) in values.iter().filter(|(p, _)| filter(**p)) {
+                let value = self
+                    .running
+                    .get(&(node, *property))
+                    .and_then(|id| self.timeline.get_transition(*id))
+                    .map_or(after, |t| &t.current_value);
+                set_value(style, *property, value);
+            }
+        });
+    }
+
+    /// Whether any transition is running or waiting for its delay.
+    pub(crate) fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Transition events since the last call.
+    pub(crate) fn take_events(&mut self) -> Vec<AnimationEvent> {
+        self.timeline.take_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use rustkit_animation::AnimationEventType;
+    use rustkit_css::MediaEnvironment;
+    use rustkit_dom::Document;
+    use rustkit_layout::{DisplayCommand, DisplayList};
+
+    fn restyle(transitions: &mut Transitions, html: &str, now: Instant) -> LayoutBox {
+        let document = Document::parse_html(html).unwrap();
+        let mut root =
+            Engine::build_layout_from_document(&document, &MediaEnvironment::screen(800.0, 600.0));
+        transitions.restyle(&mut root, now);
+        root
+    }
+
+    /// Alpha of the red layer in the display list.
+    fn layer_alpha(root: &LayoutBox) -> f32 {
+        DisplayList::build(root)
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                DisplayCommand::SolidColor(color, _) if color.r == 255 && color.g == 0 => {
+                    Some(color.a)
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn page(opacity: &str) -> String {
+        format!(
+            r#"<html><body><div style="background-color: red; width: 10px; height: 10px;
+                transition: opacity 100ms linear; opacity: {}"></div></body></html>"#,
+            opacity
+        )
+    }
+
+    #[test]
+    fn test_opacity_transition_interpolates_and_ends_once() {
+        let mut transitions = Transitions::default();
+        let start = Instant::now();
+        let root = restyle(&mut transitions, &page("1"), start);
+        assert_eq!(layer_alpha(&root), 1.0);
+        assert!(!transitions.is_running());
+
+        let mut root = restyle(&mut transitions, &page("0"), start);
+        assert!(transitions.is_running());
+        assert_eq!(layer_alpha(&root), 1.0);
+
+        for (ms, expected) in [(25, 0.75), (50, 0.5), (75, 0.25)] {
+            let work = transitions.tick(start + Duration::from_millis(ms));
+            assert_eq!(work, FrameWork::Paint);
+            transitions.apply_paint(&mut root);
+            assert!((layer_alpha(&root) - expected).abs() < 1e-3, "at {}ms", ms);
+        }
+        transitions.tick(start + Duration::from_millis(120));
+        transitions.apply_paint(&mut root);
+        assert!(!transitions.is_running());
+        assert_eq!(
+            transitions.tick(start + Duration::from_millis(200)),
+            FrameWork::None
+        );
+
+        let events: Vec<_> = transitions
+            .take_events()
+            .into_iter()
+            .map(|e| (e.event_type, e.property_name.unwrap()))
+            .collect();
+        let ends = events
+            .iter()
+            .filter(|(t, _)| *t == AnimationEventType::TransitionEnd)
+            .count();
+        assert_eq!(ends, 1);
+        assert!(events.contains(&(AnimationEventType::TransitionStart, "opacity".into())));
+        // The layer is fully transparent after the transition ends
+        let transparent = DisplayList::build(&root).commands.iter().any(|c| {
+            matches!(c, DisplayCommand::SolidColor(color, _) if color.r == 255 && color.a == 0.0)
+        });
+        assert!(transparent);
+    }
+
+    #[test]
+    fn test_retarget_and_uncovered_changes() {
+        let mut transitions = Transitions::default();
+        let start = Instant::now();
+        restyle(&mut transitions, &page("1"), start);
+        restyle(&mut transitions, &page("0"), start);
+        transitions.tick(start + Duration::from_millis(50));
+
+        // Reversing mid-way starts from the current value and cancels the old one
+        let root = restyle(
+            &mut transitions,
+            &page("1"),
+            start + Duration::from_millis(50),
+        );
+        assert!((layer_alpha(&root) - 0.5).abs() < 1e-3);
+        let types: Vec<_> = transitions
+            .take_events()
+            .into_iter()
+            .map(|e| e.event_type)
+            .collect();
+        assert!(types.contains(&AnimationEventType::TransitionCancel));
+
+        // Without a transition the new value applies at once
+        let html = r#"<html><body><div style="background-color: red; width: 10px;
+            height: 10px; opacity: 0.2"></div></body></html>"#;
+        let root = restyle(&mut transitions, html, start + Duration::from_millis(60));
+        assert!(!transitions.is_running());
+        assert!((layer_alpha(&root) - 0.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_reduced_motion_jumps_in_one_frame() {
+        let mut transitions = Transitions::default();
+        transitions.set_reduced_motion(true);
+        let start = Instant::now();
+        let html = |width: u32| {
+            format!(
+                r#"<html><body><div style="width: {}px; transition: width 1s linear">
+                    </div></body></html>"#,
+                width
+            )
+        };
+        restyle(&mut transitions, &html(100), start);
+        let mut root = restyle(&mut transitions, &html(200), start);
+        assert!(!transitions.is_running());
+        let mut widths = Vec::new();
+        for_each_element(&mut root, &mut |_, style| {
+            if let Length::Px(px) = style.width {
+                widths.push(px);
+            }
+        });
+        assert_eq!(widths, [200.0]);
+
+        // Fades do not move anything and still run
+        restyle(&mut transitions, &page("1"), start);
+        restyle(&mut transitions, &page("0"), start);
+        assert!(transitions.is_running());
+    }
+
+    #[test]
+    fn test_geometry_transitions_need_layout() {
+        let mut transitions = Transitions::default();
+        let start = Instant::now();
+        let html = |width: u32| {
+            format!(
+                r#"<html><body><div style="width: {}px; transition: width 1s linear">
+                    </div></body></html>"#,
+                width
+            )
+        };
+        restyle(&mut transitions, &html(100), start);
+        restyle(&mut transitions, &html(200), start);
+        assert_eq!(
+            transitions.tick(start + Duration::from_millis(500)),
+            FrameWork::Layout
+        );
+        let root = restyle(
+            &mut transitions,
+            &html(200),
+            start + Duration::from_millis(500),
+        );
+        let mut root = root;
+        let mut widths = Vec::new();
+        for_each_element(&mut root, &mut |_, style| {
+            if let Length::Px(px) = style.width {
+                widths.push(px);
+            }
+        });
+        assert_eq!(widths, [150.0]);
+    }
+}
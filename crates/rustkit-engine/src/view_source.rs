@@ -0,0 +1,393 @@
+//! `view-source:` listings.
+//!
+//! The inner URL is fetched like any navigation, but its markup is shown
+//! rather than parsed: a synthetic document with one pre-formatted row per
+//! source line, numbered, with tags, attribute names, attribute values,
+//! comments and doctypes each in their own color. Listings run no script
+//! and have no links or subresources. A long source commits with its first
+//! rows and gets the rest from [`crate::Engine::run_idle_work`].
+
+use rustkit_dom::{Document, DomError};
+use url::Url;
+
+use crate::error_page::escape_html;
+
+const SCHEME: &str = "view-source";
+
+/// Rows in a listing when it is committed.
+const FIRST_ROWS: usize = 500;
+
+/// Rows appended to a listing per idle call.
+pub(crate) const IDLE_ROWS: usize = 500;
+
+/// Elements whose content is text up to their end tag.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Color of the line numbers.
+const LINE_NUMBER_COLOR: &str = "#999";
+
+/// Whether `url` asks for a listing.
+pub(crate) fn is_view_source(url: &Url) -> bool {
+    url.scheme() == SCHEME
+}
+
+/// The URL whose source a `view-source:` URL lists. `None` for other URLs,
+/// and for ones whose inner URL is invalid or itself `view-source:`.
+pub(crate) fn inner_url(url: &Url) -> Option<Url> {
+    if !is_view_source(url) {
+        return None;
+    }
+    Url::parse(&url.as_str()[SCHEME.len() + 1..])
+        .ok()
+        .filter(|inner| !is_view_source(inner))
+}
+
+/// What a span of source is highlighted as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Tag,
+    AttributeName,
+    AttributeValue,
+    Comment,
+    Doctype,
+}
+
+impl Kind {
+    fn color(self) -> Option<&'static str> {
+        match self {
+            Self::Text => None,
+            Self::Tag => Some("#881280"),
+            Self::AttributeName => Some("#994500"),
+            Self::AttributeValue => Some("#1a1aa6"),
+            Self::Comment => Some("#236e25"),
+            Self::Doctype => Some("#808080"),
+        }
+    }
+}
+
+/// Splits markup into highlighted spans. It only has to color the source
+/// sensibly, so unlike the parser it never fails or fixes anything up.
+struct Highlighter<'a> {
+    source: &'a str,
+    /// ASCII-lowercased source, for finding end tags.
+    lower: String,
+    pos: usize,
+    spans: Vec<(Kind, &'a str)>,
+}
+
+impl<'a> Highlighter<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            lower: source.to_ascii_lowercase(),
+            pos: 0,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Emit the source from the current position to `end` as `kind`.
+    fn emit(&mut self, kind: Kind, end: usize) {
+        if end > self.pos {
+            self.spans.push((kind, &self.source[self.pos..end]));
+        }
+        self.pos = end;
+    }
+
+    fn find(&self, from: usize, needle: &str) -> Option<usize> {
+        self.source[from..].find(needle).map(|i| from + i)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let bytes = self.source.as_bytes();
+        let mut end = self.pos;
+        while end < bytes.len() && bytes[end].is_ascii_whitespace() {
+            end += 1;
+        }
+        self.emit(Kind::Text, end);
+    }
+
+    fn run(mut self) -> Vec<(Kind, &'a str)> {
+        let len = self.source.len();
+        let mut scan = 0;
+        while let Some(start) = self.find(scan, "<") {
+            let next = self.source.as_bytes().get(start + 1).copied();
+            scan = start + 1;
+            if self.source[start..].starts_with("<!--") {
+                self.emit(Kind::Text, start);
+                let end = self.find(start + 4, "-->").map_or(len, |i| i + 3);
+                self.emit(Kind::Comment, end);
+            } else if matches!(next, Some(b'!' | b'?')) {
+                self.emit(Kind::Text, start);
+                let end = self.find(start, ">").map_or(len, |i| i + 1);
+                self.emit(Kind::Doctype, end);
+            } else if next.is_some_and(|b| b.is_ascii_alphabetic() || b == b'/') {
+                self.emit(Kind::Text, start);
+                if let Some(name) = self.tag() {
+                    // Everything up to the end tag is text
+                    let end_tag = format!("</{}", name);
+                    scan = self.lower[self.pos..]
+                        .find(&end_tag)
+                        .map_or(len, |i| self.pos + i);
+                    continue;
+                }
+            }
+            scan = scan.max(self.pos);
+        }
+        self.emit(Kind::Text, len);
+        self.spans
+    }
+
+    /// Emit the tag at the current position. Returns the name of a raw
+    /// text element it opens.
+    fn tag(&mut self) -> Option<String> {
+        let bytes = self.source.as_bytes();
+        let len = bytes.len();
+        let closing = bytes.get(self.pos + 1) == Some(&b'/');
+        let name_start = self.pos + 1 + usize::from(closing);
+        let mut end = name_start;
+        while end < len && !bytes[end].is_ascii_whitespace() && !matches!(bytes[end], b'>' | b'/') {
+            end += 1;
+        }
+        let name = self.lower[name_start..end].to_string();
+        self.emit(Kind::Tag, end);
+
+        loop {
+            self.skip_whitespace();
+            match bytes.get(self.pos) {
+                None => break,
+                Some(b'>') => {
+                    self.emit(Kind::Tag, self.pos + 1);
+                    break;
+                }
+                Some(b'/') if bytes[self.pos..].starts_with(b"/>") => {
+                    self.emit(Kind::Tag, self.pos + 2);
+                    break;
+                }
+                _ => {}
+            }
+
+            let mut end = self.pos + 1;
+            while end < len
+                && !bytes[end].is_ascii_whitespace()
+                && !matches!(bytes[end], b'=' | b'>')
+                && !bytes[end..].starts_with(b"/>")
+            {
+                end += 1;
+            }
+            self.emit(Kind::AttributeName, end);
+
+            let mut equals = end;
+            while equals < len && bytes[equals].is_ascii_whitespace() {
+                equals += 1;
+            }
+            if bytes.get(equals) != Some(&b'=') {
+                continue;
+            }
+            self.emit(Kind::Text, equals + 1);
+            self.skip_whitespace();
+            let end = match bytes.get(self.pos) {
+                Some(&quote @ (b'"' | b'\'')) => self.source[self.pos + 1..]
+                    .find(quote as char)
+                    .map_or(len, |i| self.pos + i + 2),
+                _ => {
+                    let mut end = self.pos;
+                    while end < len && !bytes[end].is_ascii_whitespace() && bytes[end] != b'>' {
+                        end += 1;
+                    }
+                    end
+                }
+            };
+            self.emit(Kind::AttributeValue, end);
+        }
+
+        (!closing && RAW_TEXT_ELEMENTS.contains(&name.as_str())).then_some(name)
+    }
+}
+
+/// Styles are inline, as author rules do not reach element colors.
+fn span(color: &str, html: &str) -> String {
+    format!(
+        "<span style=\"color: {}; white-space: pre\">{}</span>",
+        color, html
+    )
+}
+
+/// The listing's rows, each a numbered line of highlighted source.
+fn rows(source: &str) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for (kind, text) in Highlighter::new(source).run() {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            let part = part.strip_suffix('\r').unwrap_or(part);
+            if part.is_empty() {
+                continue;
+            }
+            let line = lines.last_mut().unwrap();
+            match kind.color() {
+                Some(color) => line.push_str(&span(color, &escape_html(part))),
+                None => line.push_str(&escape_html(part)),
+            }
+        }
+    }
+    // A final newline ends the last line rather than starting another
+    if lines.len() > 1 && lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+
+    let width = lines.len().to_string().len();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let number = format!("{:>width$}  ", i + 1, width = width);
+            format!(
+                "<div style=\"white-space: pre\">{}{}</div>",
+                span(LINE_NUMBER_COLOR, &number),
+                line
+            )
+        })
+        .collect()
+}
+
+/// Rows of a listing not yet in its document.
+pub(crate) struct PendingRows {
+    rows: std::vec::IntoIter<String>,
+}
+
+impl PendingRows {
+    /// The markup of up to `count` more rows, if any are left.
+    pub fn take(&mut self, count: usize) -> Option<String> {
+        let html: String = self.rows.by_ref().take(count).collect();
+        (!html.is_empty()).then_some(html)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.len() == 0
+    }
+}
+
+/// The listing document of `source` at `url`, with its first rows, and the
+/// rows left to append.
+pub(crate) fn listing(url: &Url, source: &str) -> (String, PendingRows) {
+    let mut rows = rows(source).into_iter();
+    let first: String = rows.by_ref().take(FIRST_ROWS).collect();
+    let html = format!(
+        "<!DOCTYPE html><html><head><title>{}</title></head>\
+         <body><div id=\"source\">{}</div></body></html>",
+        escape_html(url.as_str()),
+        first
+    );
+    (html, PendingRows { rows })
+}
+
+/// Append rows from [`PendingRows::take`] to a listing document.
+pub(crate) fn append(document: &Document, rows: &str) -> Result<(), DomError> {
+    let container = document
+        .get_element_by_id("source")
+        .ok_or(DomError::NodeNotFound)?;
+    let fragment = document.parse_fragment(rows, "div")?;
+    document.append_child(&container, fragment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use rustkit_css::{Color, MediaEnvironment};
+    use rustkit_layout::{DisplayCommand, DisplayList};
+
+    fn texts(document: &Document) -> Vec<(String, Color)> {
+        let layout = Engine::layout_document(document, &MediaEnvironment::screen(800.0, 600.0));
+        DisplayList::build(&layout)
+            .commands
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, color, .. } => Some((text, color)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inner_url() {
+        let url = Url::parse("view-source:https://example.com/a?b=1#c").unwrap();
+        assert_eq!(
+            inner_url(&url).unwrap().as_str(),
+            "https://example.com/a?b=1#c"
+        );
+        assert!(inner_url(&Url::parse("https://example.com/").unwrap()).is_none());
+        assert!(
+            inner_url(&Url::parse("view-source:view-source:https://a.test/").unwrap()).is_none()
+        );
+        assert!(inner_url(&Url::parse("view-source:nonsense").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_highlight_spans() {
+        let spans = Highlighter::new(
+            "<!DOCTYPE html><!-- note --><a href=\"/x\" hidden>a < b</a><script>if (a<b) {}</script>",
+        )
+        .run();
+        let of = |kind: Kind| -> Vec<&str> {
+            spans
+                .iter()
+                .filter(|(k, _)| *k == kind)
+                .map(|(_, text)| *text)
+                .collect()
+        };
+        assert_eq!(of(Kind::Doctype), ["<!DOCTYPE html>"]);
+        assert_eq!(of(Kind::Comment), ["<!-- note -->"]);
+        assert_eq!(of(Kind::AttributeName), ["href", "hidden"]);
+        assert_eq!(of(Kind::AttributeValue), ["\"/x\""]);
+        assert_eq!(
+            of(Kind::Tag),
+            ["<a", ">", "</a", ">", "<script", ">", "</script", ">"]
+        );
+        assert_eq!(of(Kind::Text), [" ", "=", " ", "a < b", "if (a<b) {}"]);
+    }
+
+    #[test]
+    fn test_listing_shows_numbered_markup() {
+        let url = Url::parse("view-source:http://example.test/").unwrap();
+        let source = "<html>\n<body>\n<h1 class=\"big\">Title</h1>\n<a href=\"/next\">next</a>\n</body>\n</html>\n";
+        let (html, mut rest) = listing(&url, source);
+        assert!(rest.take(IDLE_ROWS).is_none());
+        let document = Document::parse_html(&html).unwrap();
+        assert!(document.get_elements_by_tag_name("h1").is_empty());
+        assert!(document.get_elements_by_tag_name("a").is_empty());
+        assert_eq!(document.title().as_deref(), Some(url.as_str()));
+
+        let texts = texts(&document);
+        let has = |text: &str| texts.iter().any(|(t, _)| t.trim() == text);
+        for line in 1..=6 {
+            assert!(has(&line.to_string()), "line {} in {:?}", line, texts);
+        }
+        assert!(!has("7"));
+        assert!(has("<h1"));
+        assert!(has("</h1"));
+        let value = texts.iter().find(|(t, _)| t == "\"big\"").unwrap();
+        assert_eq!(value.1, Color::new(0x1a, 0x1a, 0xa6, 1.0));
+    }
+
+    #[test]
+    fn test_long_listing_appends_rows() {
+        let url = Url::parse("view-source:http://example.test/").unwrap();
+        let source = "<p>row</p>\n".repeat(FIRST_ROWS + 20);
+        let (html, mut rest) = listing(&url, &source);
+        let document = Document::parse_html(&html).unwrap();
+        let container = document.get_element_by_id("source").unwrap();
+        assert_eq!(container.children().len(), FIRST_ROWS);
+
+        append(&document, &rest.take(IDLE_ROWS).unwrap()).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(container.children().len(), FIRST_ROWS + 20);
+        assert!(container
+            .last_child()
+            .unwrap()
+            .text_content()
+            .starts_with(&(FIRST_ROWS + 20).to_string()));
+    }
+}
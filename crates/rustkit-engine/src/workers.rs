@@ -0,0 +1,476 @@
+//! Dedicated workers of a view's documents.
+//!
+//! Each worker runs a [`WorkerScope`] on a thread of the Tokio blocking
+//! pool, with an event loop of its own: messages from the document,
+//! settled fetches and timers run there, one at a time. The document and
+//! the worker share nothing; messages cross as structured-clone JSON
+//! through channels in both directions and are delivered on each side's
+//! own loop, the document's through [`Engine::deliver_worker_messages`].
+//!
+//! A worker stops when its document terminates it, when it calls
+//! `close()`, when its handle is dropped with the document or view, or
+//! when its script fails to load. Script can't be interrupted, so a
+//! worker busy in a long task stops once the task returns.
+//!
+//! [`Engine::deliver_worker_messages`]: crate::Engine::deliver_worker_messages
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustkit_bindings::{
+    BindingError, FetchCommand, FetchResponse, PageError, PageErrorKind, WorkerScope,
+};
+use rustkit_js::JsRuntime;
+use rustkit_net::{CancelHandle, CredentialsMode, FetchApi, ResourceLoader, SecurityContext};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::scripts::{self, FetchedScript};
+use crate::{panic_message, run_fetch, EngineViewId};
+
+/// How long a worker's loop sleeps when no timer is due.
+const IDLE_WAIT: Duration = Duration::from_millis(250);
+
+/// How long `importScripts` waits for a script.
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Work for a worker's thread.
+pub(crate) enum ToWorker {
+    /// The worker's script, or why it could not be loaded.
+    Script(Result<FetchedScript, String>),
+    /// A message from the document, as JSON.
+    Message(String),
+    /// A fetch the worker started finished.
+    Fetched {
+        fetch_id: u64,
+        result: Result<FetchResponse, String>,
+    },
+    Terminate,
+}
+
+/// What a worker told its document.
+pub(crate) enum WorkerReport {
+    /// A message, as JSON.
+    Message(String),
+    /// An error the worker's script left unhandled, or a failed load.
+    Error(PageError),
+    /// The worker's thread is done.
+    Closed,
+}
+
+/// A report from a worker, waiting for
+/// [`crate::Engine::deliver_worker_messages`].
+pub(crate) struct WorkerEvent {
+    pub(crate) view_id: EngineViewId,
+    /// Id script knows the worker by.
+    pub(crate) worker_id: u64,
+    /// Tells the worker apart from one with the same id in a later document.
+    pub(crate) instance: u64,
+    pub(crate) report: WorkerReport,
+}
+
+/// A worker to start for view `view_id`.
+pub(crate) struct WorkerSpawn {
+    pub(crate) view_id: EngineViewId,
+    pub(crate) worker_id: u64,
+    pub(crate) url: Url,
+    pub(crate) name: String,
+    pub(crate) loader: Arc<ResourceLoader>,
+    /// The creating document's context; the worker's fetches are issued
+    /// from it.
+    pub(crate) context: SecurityContext,
+    pub(crate) events: mpsc::UnboundedSender<WorkerEvent>,
+}
+
+/// A running worker of a view's current document. Dropping it stops the
+/// worker.
+pub(crate) struct WorkerHandle {
+    pub(crate) instance: u64,
+    sender: std_mpsc::Sender<ToWorker>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl WorkerHandle {
+    /// Queue a message for the worker's `onmessage`.
+    pub(crate) fn post(&self, data: String) {
+        let _ = self.sender.send(ToWorker::Message(data));
+    }
+
+    /// Hand the worker its script once `script` has fetched it on
+    /// `runtime`, or the reason it failed to load.
+    pub(crate) fn provide_script(
+        &self,
+        script: impl Future<Output = Result<FetchedScript, String>> + Send + 'static,
+        runtime: &Handle,
+    ) {
+        let sender = self.sender.clone();
+        runtime.spawn(async move {
+            let _ = sender.send(ToWorker::Script(script.await));
+        });
+    }
+
+    /// Stop the worker once its current task returns.
+    pub(crate) fn terminate(&self) {
+        self.terminated.store(true, Ordering::Release);
+        let _ = self.sender.send(ToWorker::Terminate);
+    }
+}
+
+impl Drop for WorkerHandle {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+/// Start the thread of a worker on `runtime`. It waits for its script from
+/// [`WorkerHandle::provide_script`].
+pub(crate) fn start(spawn: WorkerSpawn, runtime: &Handle) -> WorkerHandle {
+    static INSTANCES: AtomicU64 = AtomicU64::new(1);
+    let instance = INSTANCES.fetch_add(1, Ordering::Relaxed);
+    let (sender, inbox) = std_mpsc::channel();
+    let terminated = Arc::new(AtomicBool::new(false));
+
+    let thread = WorkerThread {
+        instance,
+        inbox,
+        outbox: sender.clone(),
+        terminated: terminated.clone(),
+        runtime: runtime.clone(),
+        spawn,
+    };
+    runtime.spawn_blocking(move || {
+        let events = thread.spawn.events.clone();
+        let (view_id, worker_id, url) = (
+            thread.spawn.view_id,
+            thread.spawn.worker_id,
+            thread.spawn.url.to_string(),
+        );
+        // A panic takes down the worker, not the engine
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| thread.run())) {
+            let message = panic_message(payload.as_ref());
+            warn!(?view_id, worker_id, %message, "Worker panicked");
+            for report in [
+                WorkerReport::Error(worker_error(format!("Worker crashed: {}", message), &url)),
+                WorkerReport::Closed,
+            ] {
+                let _ = events.send(WorkerEvent {
+                    view_id,
+                    worker_id,
+                    instance,
+                    report,
+                });
+            }
+        }
+    });
+
+    WorkerHandle {
+        instance,
+        sender,
+        terminated,
+    }
+}
+
+/// A page error reported for a worker's script at `url`.
+pub(crate) fn worker_error(message: String, url: &str) -> PageError {
+    PageError {
+        kind: PageErrorKind::Exception,
+        message,
+        source_url: url.to_string(),
+        line: 0,
+        column: 0,
+        stack: None,
+    }
+}
+
+/// Whether the script of a worker at `url` created by a document in
+/// `context` is fetched with CORS: classic workers from another origin
+/// must be allowed by it.
+pub(crate) fn script_cors(context: &SecurityContext, url: &Url) -> Option<CredentialsMode> {
+    (url.scheme() != "data" && !context.is_same_origin(url)).then_some(CredentialsMode::SameOrigin)
+}
+
+/// State owned by a worker's thread.
+struct WorkerThread {
+    instance: u64,
+    inbox: std_mpsc::Receiver<ToWorker>,
+    /// Where the worker's fetches deliver their results.
+    outbox: std_mpsc::Sender<ToWorker>,
+    terminated: Arc<AtomicBool>,
+    runtime: Handle,
+    spawn: WorkerSpawn,
+}
+
+impl WorkerThread {
+    fn report(&self, report: WorkerReport) {
+        let _ = self.spawn.events.send(WorkerEvent {
+            view_id: self.spawn.view_id,
+            worker_id: self.spawn.worker_id,
+            instance: self.instance,
+            report,
+        });
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::Acquire)
+    }
+
+    fn run(self) {
+        self.run_loop();
+        self.report(WorkerReport::Closed);
+    }
+
+    fn run_loop(&self) {
+        let url = self.spawn.url.as_str();
+
+        // Messages posted before the script loaded wait for it
+        let mut early = Vec::new();
+        let script = loop {
+            match self.inbox.recv() {
+                Ok(ToWorker::Script(script)) => break script,
+                Ok(ToWorker::Message(data)) => early.push(data),
+                Ok(ToWorker::Fetched { .. }) => {}
+                Ok(ToWorker::Terminate) | Err(_) => return,
+            }
+        };
+        let script = match script {
+            Ok(script) => script,
+            Err(reason) => {
+                debug!(%url, %reason, "Worker script failed to load");
+                self.report(WorkerReport::Error(worker_error(
+                    format!("Failed to load worker script {}: {}", url, reason),
+                    url,
+                )));
+                return;
+            }
+        };
+        if self.is_terminated() {
+            return;
+        }
+
+        let scope = match JsRuntime::new()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                WorkerScope::new(runtime, &self.spawn.url, &self.spawn.name)
+                    .map_err(|e| e.to_string())
+            }) {
+            Ok(scope) => scope,
+            Err(e) => {
+                self.report(WorkerReport::Error(worker_error(e, url)));
+                return;
+            }
+        };
+        scope.set_script_loader(self.import_loader());
+
+        let mut fetches = HashMap::new();
+        if let Err(e) = self.drive(&scope, &script, early, &mut fetches) {
+            // The runtime itself failed, not the worker's script
+            self.flush(&scope);
+            self.report(WorkerReport::Error(worker_error(e.to_string(), url)));
+        }
+        for cancel in fetches.values() {
+            cancel.cancel();
+        }
+    }
+
+    /// Run the worker's script, then its event loop until it closes or is
+    /// terminated.
+    fn drive(
+        &self,
+        scope: &WorkerScope,
+        script: &FetchedScript,
+        early: Vec<String>,
+        fetches: &mut HashMap<u64, CancelHandle>,
+    ) -> Result<(), BindingError> {
+        let epoch = Instant::now();
+        let clock = || epoch.elapsed().as_secs_f64() * 1000.0;
+        scope.run_script(&script.text)?;
+        for data in early {
+            scope.deliver_message(&data)?;
+        }
+
+        loop {
+            let next_timer = scope.run_timers(clock())?;
+            self.start_fetches(scope, fetches);
+            self.flush(scope);
+            if scope.is_closing() || self.is_terminated() {
+                return Ok(());
+            }
+
+            let wait = next_timer.map_or(IDLE_WAIT, |due| {
+                Duration::from_secs_f64((due - clock()).max(0.0) / 1000.0).min(IDLE_WAIT)
+            });
+            let work = self.inbox.recv_timeout(wait);
+            // Nothing reaches a terminated worker
+            if self.is_terminated() {
+                return Ok(());
+            }
+            match work {
+                Ok(ToWorker::Message(data)) => scope.deliver_message(&data)?,
+                Ok(ToWorker::Fetched { fetch_id, result }) => {
+                    if fetches.remove(&fetch_id).is_some() {
+                        scope.complete_fetch(
+                            fetch_id,
+                            result.as_ref().map_err(|e| e.as_str()).cloned(),
+                        )?;
+                    }
+                }
+                Ok(ToWorker::Script(_)) | Err(RecvTimeoutError::Timeout) => {}
+                Ok(ToWorker::Terminate) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    /// Pass what the worker's script posted and left unhandled on to the
+    /// document.
+    fn flush(&self, scope: &WorkerScope) {
+        for data in scope.take_messages() {
+            self.report(WorkerReport::Message(data));
+        }
+        for error in scope.take_errors() {
+            self.report(WorkerReport::Error(error));
+        }
+    }
+
+    /// Start and cancel the fetches the worker's script asked for. They run
+    /// on the engine's runtime and settle on the worker's loop.
+    fn start_fetches(&self, scope: &WorkerScope, fetches: &mut HashMap<u64, CancelHandle>) {
+        for command in scope.take_fetch_commands() {
+            match command {
+                FetchCommand::Start(fetch) => {
+                    let cancel = CancelHandle::new();
+                    fetches.insert(fetch.id, cancel.clone());
+                    let api = FetchApi::with_security_context(
+                        self.spawn.loader.clone(),
+                        self.spawn.context.clone(),
+                    );
+                    let outbox = self.outbox.clone();
+                    let view_id = self.spawn.view_id;
+                    self.runtime.spawn(async move {
+                        let fetch_id = fetch.id;
+                        let result = run_fetch(&api, fetch, cancel, view_id).await;
+                        let _ = outbox.send(ToWorker::Fetched {
+                            fetch_id,
+                            result: result.map_err(|e| e.to_string()),
+                        });
+                    });
+                }
+                FetchCommand::Abort { id } => {
+                    if let Some(cancel) = fetches.remove(&id) {
+                        cancel.cancel();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Loads `importScripts` URLs synchronously, as the spec has it, by
+    /// waiting on a fetch run on the engine's runtime.
+    fn import_loader(&self) -> impl Fn(&Url) -> Result<String, String> + 'static {
+        let loader = self.spawn.loader.clone();
+        let context = self.spawn.context.clone();
+        let runtime = self.runtime.clone();
+        let view_id = self.spawn.view_id.raw();
+        move |url: &Url| {
+            let (tx, rx) = std_mpsc::channel();
+            let fetch = scripts::fetch(loader.clone(), context.clone(), view_id, url.clone(), None);
+            runtime.spawn(async move {
+                let _ = tx.send(fetch.await);
+            });
+            rx.recv_timeout(IMPORT_TIMEOUT)
+                .map_err(|_| format!("Timed out loading {}", url))?
+                .map(|script| script.text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_net::LoaderConfig;
+
+    struct TestWorker {
+        handle: WorkerHandle,
+        events: mpsc::UnboundedReceiver<WorkerEvent>,
+    }
+
+    impl TestWorker {
+        fn start(source: &str) -> Self {
+            let url = Url::parse("https://app.example/worker.js").unwrap();
+            let (tx, events) = mpsc::unbounded_channel();
+            let spawn = WorkerSpawn {
+                view_id: EngineViewId::new(),
+                worker_id: 1,
+                context: SecurityContext::from_url(&url),
+                url,
+                name: String::new(),
+                loader: Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap()),
+                events: tx,
+            };
+            let handle = start(spawn, &Handle::current());
+            let script = FetchedScript {
+                text: source.to_string(),
+                muted: false,
+            };
+            handle.provide_script(async { Ok(script) }, &Handle::current());
+            Self { handle, events }
+        }
+
+        async fn next(&mut self) -> WorkerReport {
+            tokio::time::timeout(Duration::from_secs(5), self.events.recv())
+                .await
+                .expect("worker went quiet")
+                .expect("worker dropped its sender")
+                .report
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_sums_posted_array() {
+        let mut worker = TestWorker::start(
+            "onmessage = function(e) { \
+                 postMessage(e.data.reduce(function(a, b) { return a + b; }, 0)); \
+             };",
+        );
+        worker.handle.post("[1,2,3,4.5]".to_string());
+        assert!(matches!(worker.next().await, WorkerReport::Message(data) if data == "10.5"));
+    }
+
+    #[tokio::test]
+    async fn test_terminate_stops_worker() {
+        let mut worker =
+            TestWorker::start("var n = 0; setInterval(function() { postMessage(++n); }, 5);");
+        assert!(matches!(worker.next().await, WorkerReport::Message(_)));
+        worker.handle.terminate();
+        loop {
+            match worker.next().await {
+                WorkerReport::Closed => break,
+                // Posted before the thread saw the termination
+                WorkerReport::Message(_) => {}
+                WorkerReport::Error(error) => panic!("unexpected error {:?}", error),
+            }
+        }
+        assert!(worker.events.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_syntax_error_reports_error() {
+        let mut worker = TestWorker::start("onmessage = function(e) {");
+        let WorkerReport::Error(error) = worker.next().await else {
+            panic!("expected an error");
+        };
+        assert!(error.message.contains("SyntaxError"), "{}", error.message);
+        assert_eq!(error.source_url, "https://app.example/worker.js");
+
+        // The worker stays up, as a browser's would
+        worker.handle.post("1".to_string());
+        worker.handle.terminate();
+        assert!(matches!(worker.next().await, WorkerReport::Closed));
+    }
+}
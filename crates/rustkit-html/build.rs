@@ -0,0 +1,27 @@
+//! Turns the spec's `data/entities.json` into a sorted table of named
+//! character references for `src/entities.rs`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=data/entities.json");
+
+    let json = std::fs::read_to_string("data/entities.json").expect("read data/entities.json");
+    let entities: BTreeMap<String, serde_json::Value> =
+        serde_json::from_str(&json).expect("parse data/entities.json");
+
+    let mut table = String::from("static NAMED_ENTITIES: &[(&str, &str)] = &[\n");
+    for (name, entity) in &entities {
+        let name = name.strip_prefix('&').expect("entity names start with '&'");
+        let characters = entity["characters"]
+            .as_str()
+            .expect("entities have characters");
+        writeln!(table, "    ({:?}, {:?}),", name, characters).unwrap();
+    }
+    table.push_str("];\n");
+
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("named_entities.rs");
+    std::fs::write(out, table).expect("write named_entities.rs");
+}
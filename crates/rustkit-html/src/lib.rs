@@ -10,8 +10,10 @@
 //! - Full document parsing via [`parse`]
 //! - Fragment parsing via [`parse_fragment`] (for innerHTML, insertAdjacentHTML)
 //! - Quirks mode detection based on doctype
-//! - Table parsing with implicit element insertion
+//! - Table parsing with implicit element insertion and foster parenting
 //! - Adoption Agency Algorithm for misnested formatting elements
+//! - The full table of named character references
+//! - Recoverable parse errors reported through [`TreeSink::parse_error`]
 //!
 //! ## Example
 //!
@@ -53,8 +55,9 @@ pub type ParseResult<T> = Result<T, ParseError>;
 /// This trait is implemented by DOM builders that want to construct
 /// a tree from HTML parsing events.
 pub trait TreeSink {
-    /// The type used to identify nodes in the tree.
-    type NodeId: Clone;
+    /// The type used to identify nodes in the tree. Equal ids must name
+    /// the same node.
+    type NodeId: Clone + PartialEq;
 
     /// Called when a doctype declaration is encountered.
     fn doctype(&mut self, name: String, public_id: String, system_id: String);
@@ -134,7 +137,20 @@ pub trait TreeSink {
         }
     }
 
-    /// Called when a parse error is encountered.
+    /// Insert text at the foster parent location, just before `table`.
+    /// Default implementation inserts it at the current node.
+    fn foster_parent_text(&mut self, _table: Self::NodeId, data: String) {
+        self.text(data);
+    }
+
+    /// Called when the stack of open elements changed other than through
+    /// `start_element` and `end_element`, as when the AAA moves nodes or
+    /// content is foster parented. Sinks that track the current node take
+    /// `stack` as their own.
+    fn reset_open_elements(&mut self, _stack: &[Self::NodeId]) {}
+
+    /// Called when a recoverable parse error is encountered, with its
+    /// code, e.g. `missing-semicolon-after-character-reference`.
     /// The default implementation ignores errors.
     fn parse_error(&mut self, _error: &str) {
         // Default: ignore parse errors
@@ -28,6 +28,8 @@ pub enum Token {
     Character(char),
     /// Comment
     Comment(String),
+    /// A recoverable parse error, by its spec code
+    ParseError(String),
     /// End of file
     Eof,
 }
@@ -243,8 +245,8 @@ impl Tokenizer {
             Some('<') => self.state = State::TagOpen,
             Some('&') => {
                 // Decode entity in text content
-                let entity = self.consume_entity();
-                for ch in entity.chars() {
+                let characters = self.consume_character_reference(false);
+                for ch in characters.chars() {
                     self.emit(Token::Character(ch));
                 }
             }
@@ -474,9 +476,8 @@ impl Tokenizer {
                 self.state = State::AfterAttributeName;
             }
             Some('&') => {
-                // Decode entity
-                let entity = self.consume_entity();
-                self.current_attr_value.push_str(&entity);
+                let characters = self.consume_character_reference(true);
+                self.current_attr_value.push_str(&characters);
             }
             Some(ch) => {
                 self.current_attr_value.push(ch);
@@ -496,8 +497,8 @@ impl Tokenizer {
                 self.state = State::AfterAttributeName;
             }
             Some('&') => {
-                let entity = self.consume_entity();
-                self.current_attr_value.push_str(&entity);
+                let characters = self.consume_character_reference(true);
+                self.current_attr_value.push_str(&characters);
             }
             Some(ch) => {
                 self.current_attr_value.push(ch);
@@ -519,8 +520,8 @@ impl Tokenizer {
             }
             Some('&') => {
                 self.consume();
-                let entity = self.consume_entity();
-                self.current_attr_value.push_str(&entity);
+                let characters = self.consume_character_reference(true);
+                self.current_attr_value.push_str(&characters);
             }
             Some('>') => {
                 self.consume();
@@ -540,27 +541,15 @@ impl Tokenizer {
         }
     }
 
-    fn consume_entity(&mut self) -> String {
-        let mut entity_str = String::from("&");
-
-        while let Some(ch) = self.current_char() {
-            if ch == ';' {
-                entity_str.push(ch);
-                self.consume();
-                break;
-            } else if ch.is_alphanumeric() || ch == '#' {
-                entity_str.push(ch);
-                self.consume();
-            } else {
-                break;
-            }
-
-            if entity_str.len() > 32 {
-                break;
-            }
+    /// Consume the character reference after an `&`, returning what it
+    /// stands for.
+    fn consume_character_reference(&mut self, in_attribute: bool) -> String {
+        let reference = entities::consume(&self.input[self.pos..], in_attribute);
+        if let Some(error) = reference.error {
+            self.emit(Token::ParseError(error.to_string()));
         }
-
-        entities::decode(&entity_str)
+        self.pos += reference.len;
+        reference.characters
     }
 
     fn state_markup_declaration_open(&mut self) {
@@ -1040,8 +1029,8 @@ impl Tokenizer {
         // RCDATA mode (for textarea, title)
         match self.consume() {
             Some('&') => {
-                let entity = self.consume_entity();
-                for ch in entity.chars() {
+                let characters = self.consume_character_reference(false);
+                for ch in characters.chars() {
                     self.emit(Token::Character(ch));
                 }
             }
@@ -1,7 +1,9 @@
 //! HTML tree builder.
 //!
-//! Implements a simplified HTML5 tree construction algorithm with
-//! insertion modes and error recovery.
+//! Implements HTML5 tree construction with insertion modes and the spec's
+//! error recovery: implied end tags, the adoption agency algorithm for
+//! misnested formatting, and foster parenting of content misplaced in
+//! tables. Recoverable errors are reported to [`TreeSink::parse_error`].
 
 use crate::tokenizer::Token;
 use crate::{ParseResult, TreeSink};
@@ -94,11 +96,58 @@ const TABLE_CELL_ELEMENTS: &[&str] = &["td", "th"];
 const TABLE_SCOPE_ELEMENTS: &[&str] = &["html", "table", "template"];
 
 /// Elements that can be in table context but trigger foster parenting.
-#[allow(dead_code)]
 const TABLE_CONTEXT_ELEMENTS: &[&str] = &[
     "table", "tbody", "tfoot", "thead", "tr",
 ];
 
+/// Elements that limit the default scope.
+const SCOPE_ELEMENTS: &[&str] = &[
+    "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+];
+
+/// Elements whose end tags are implied when an enclosing element closes.
+const IMPLIED_END_TAG_ELEMENTS: &[&str] = &[
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+];
+
+const HEADING_ELEMENTS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Elements whose end tags close everything open inside them.
+const BLOCK_END_TAG_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "button",
+    "center",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "header",
+    "hgroup",
+    "listing",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "pre",
+    "search",
+    "section",
+    "summary",
+    "ul",
+];
+
+/// Bounds on the adoption agency algorithm's outer and inner loops, which
+/// keep pathological nesting from taking quadratic time.
+const AAA_OUTER_LIMIT: usize = 8;
+const AAA_INNER_LIMIT: usize = 3;
+
 /// Formatting elements that participate in the Adoption Agency Algorithm.
 const FORMATTING_ELEMENTS: &[&str] = &[
     "a", "b", "big", "code", "em", "font", "i", "nobr",
@@ -126,7 +175,6 @@ enum FormattingEntry<NodeId: Clone> {
     Element {
         name: String,
         attrs: Vec<(String, String)>,
-        #[allow(dead_code)]
         node_id: NodeId,
     },
     /// Scope marker (e.g., pushed when entering table, button, etc.)
@@ -244,10 +292,18 @@ impl<S: TreeSink> TreeBuilder<S> {
     fn flush_text(&mut self) {
         if !self.text_buffer.is_empty() {
             let text = std::mem::take(&mut self.text_buffer);
-            self.sink.text(text);
+            match self.foster_parent_table() {
+                Some(table) => self.sink.foster_parent_text(table, text),
+                None => self.sink.text(text),
+            }
         }
     }
 
+    fn error(&mut self, error: &str) {
+        trace!(error, "Parse error");
+        self.sink.parse_error(error);
+    }
+
     fn current_node(&self) -> Option<&(String, S::NodeId)> {
         self.open_elements.last()
     }
@@ -257,36 +313,129 @@ impl<S: TreeSink> TreeBuilder<S> {
     }
 
     fn has_element_in_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(tag_name, &[])
+    }
+
+    fn has_element_in_list_item_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(tag_name, &["ol", "ul"])
+    }
+
+    fn has_element_in_button_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(tag_name, &["button"])
+    }
+
+    /// Check for `tag_name` above the nearest scope limiter, counting
+    /// `extra` as limiters too.
+    fn has_element_in_specific_scope(&self, tag_name: &str, extra: &[&str]) -> bool {
         for (name, _) in self.open_elements.iter().rev() {
             if name == tag_name {
                 return true;
             }
-            // Scope-limiting elements
-            if matches!(
-                name.as_str(),
-                "applet" | "caption" | "html" | "table" | "td" | "th" | "marquee" | "object" | "template"
-            ) {
+            if SCOPE_ELEMENTS.contains(&name.as_str()) || extra.contains(&name.as_str()) {
                 return false;
             }
         }
         false
     }
 
+    /// Whether the open element at `index` is in scope.
+    fn is_in_scope_at(&self, index: usize) -> bool {
+        !self.open_elements[index + 1..]
+            .iter()
+            .any(|(name, _)| SCOPE_ELEMENTS.contains(&name.as_str()))
+    }
+
+    /// Pop the current node.
+    fn pop_element(&mut self) {
+        if let Some((name, _)) = self.open_elements.pop() {
+            self.sink.end_element(name);
+        }
+    }
+
+    /// Pop elements up to and including the last `tag_name`.
     fn pop_until(&mut self, tag_name: &str) {
+        self.pop_until_one_of(&[tag_name]);
+    }
+
+    /// Pop elements up to and including the last one named in `tag_names`.
+    fn pop_until_one_of(&mut self, tag_names: &[&str]) {
         while let Some((name, _)) = self.open_elements.pop() {
-            if name == tag_name {
+            let done = tag_names.contains(&name.as_str());
+            self.sink.end_element(name);
+            if done {
                 break;
             }
         }
     }
 
+    /// Pop elements whose end tags are implied, except `except`.
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        while let Some(name) = self.current_node_name() {
+            if !IMPLIED_END_TAG_ELEMENTS.contains(&name) || Some(name) == except {
+                break;
+            }
+            self.pop_element();
+        }
+    }
+
+    /// Close the `p` element in button scope, if any.
     fn close_p_element(&mut self) {
-        if self.has_element_in_scope("p") {
-            self.sink.close_p_element_in_button_scope();
-            self.pop_until("p");
+        if self.has_element_in_button_scope("p") {
+            self.close_element("p");
         }
     }
 
+    /// Close the last open `tag_name` and anything above it, reporting
+    /// elements other than those with implied end tags that close with it.
+    fn close_element(&mut self, tag_name: &str) {
+        self.generate_implied_end_tags(Some(tag_name));
+        if self.current_node_name() != Some(tag_name) {
+            self.error(&format!("end-tag-too-early </{}>", tag_name));
+        }
+        self.pop_until(tag_name);
+    }
+
+    /// Handle an end tag with no rule of its own: close the matching
+    /// element unless a special element is open above it.
+    fn close_any_other_end_tag(&mut self, tag_name: &str) {
+        for i in (0..self.open_elements.len()).rev() {
+            let name = self.open_elements[i].0.as_str();
+            if name == tag_name {
+                self.generate_implied_end_tags(Some(tag_name));
+                if self.open_elements.len() != i + 1 {
+                    self.error(&format!("end-tag-too-early </{}>", tag_name));
+                }
+                while self.open_elements.len() > i {
+                    self.pop_element();
+                }
+                return;
+            }
+            if SPECIAL_ELEMENTS.contains(&name) {
+                break;
+            }
+        }
+        self.error(&format!("unexpected-end-tag </{}>", tag_name));
+    }
+
+    /// Close everything still open at the end of input.
+    fn stop_parsing(&mut self) {
+        self.flush_text();
+        while !self.open_elements.is_empty() {
+            self.pop_element();
+        }
+    }
+
+    /// Hand the sink the stack of open elements after it changed other
+    /// than by pushing and popping.
+    fn sync_open_elements(&mut self) {
+        let stack: Vec<S::NodeId> = self
+            .open_elements
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect();
+        self.sink.reset_open_elements(&stack);
+    }
+
     /// Check if an element is in table scope.
     fn has_element_in_table_scope(&self, tag_name: &str) -> bool {
         for (name, _) in self.open_elements.iter().rev() {
@@ -306,8 +455,7 @@ impl<S: TreeSink> TreeBuilder<S> {
             if name == "table" || name == "template" || name == "html" {
                 break;
             }
-            let (tag_name, _) = self.open_elements.pop().unwrap();
-            self.sink.end_element(tag_name);
+            self.pop_element();
         }
     }
 
@@ -320,8 +468,7 @@ impl<S: TreeSink> TreeBuilder<S> {
             {
                 break;
             }
-            let (tag_name, _) = self.open_elements.pop().unwrap();
-            self.sink.end_element(tag_name);
+            self.pop_element();
         }
     }
 
@@ -331,86 +478,58 @@ impl<S: TreeSink> TreeBuilder<S> {
             if name == "tr" || name == "template" || name == "html" {
                 break;
             }
-            let (tag_name, _) = self.open_elements.pop().unwrap();
-            self.sink.end_element(tag_name);
+            self.pop_element();
         }
     }
 
     /// Find the index of the last table element in the stack.
-    #[allow(dead_code)]
     fn find_last_table_index(&self) -> Option<usize> {
-        for i in (0..self.open_elements.len()).rev() {
-            if self.open_elements[i].0 == "table" {
-                return Some(i);
-            }
-        }
-        None
+        self.open_elements
+            .iter()
+            .rposition(|(name, _)| name == "table")
     }
 
-    /// Get the foster parent location (element before table, or table's parent).
-    #[allow(dead_code)]
-    fn get_foster_parent_index(&self) -> usize {
-        if let Some(table_idx) = self.find_last_table_index() {
-            if table_idx > 0 {
-                table_idx - 1
-            } else {
-                0
-            }
-        } else {
-            // No table, use current node
-            self.open_elements.len().saturating_sub(1)
+    /// The table that content inserted now goes before: set while foster
+    /// parenting is on and the current node is part of a table's structure.
+    fn foster_parent_table(&self) -> Option<S::NodeId> {
+        if !self.foster_parenting
+            || !self
+                .current_node_name()
+                .is_some_and(|name| TABLE_CONTEXT_ELEMENTS.contains(&name))
+        {
+            return None;
         }
+        self.find_last_table_index()
+            .map(|index| self.open_elements[index].1.clone())
     }
 
-    /// Insert element, respecting foster parenting if active.
-    #[allow(dead_code)]
+    /// Insert an element at the current node, or before the table when
+    /// foster parenting, and push it unless it is void or self-closing.
     fn insert_element(&mut self, name: String, attrs: Vec<(String, String)>, self_closing: bool) -> S::NodeId {
-        if self.foster_parenting {
-            if let Some(table_idx) = self.find_last_table_index() {
-                let (_table_name, table_id) = self.open_elements[table_idx].clone();
-                // Create the element without appending it to current node
-                let node_id = self.sink.create_element(name.clone(), attrs);
-                // Foster parent it to just before the table
-                self.sink.foster_parent(table_id, node_id.clone());
-                // Still add to open elements if not self-closing
-                if !self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
-                    self.open_elements.push((name, node_id.clone()));
-                }
-                return node_id;
+        let closed = self_closing || VOID_ELEMENTS.contains(&name.as_str());
+        if let Some(table) = self.foster_parent_table() {
+            let node_id = self.sink.create_element(name.clone(), attrs);
+            self.sink.foster_parent(table, node_id.clone());
+            if !closed {
+                self.open_elements.push((name, node_id.clone()));
+                self.sync_open_elements();
             }
+            return node_id;
         }
 
-        // Normal insertion
-        let node_id = self.sink.start_element(name.clone(), attrs, self_closing);
-        if !self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+        let node_id = self.sink.start_element(name.clone(), attrs, closed);
+        if !closed {
             self.open_elements.push((name, node_id.clone()));
         }
         node_id
     }
 
-    /// Insert text, respecting foster parenting if active.
-    #[allow(dead_code)]
-    fn insert_text(&mut self, text: String) {
-        if self.foster_parenting {
-            // For foster parented text, we call the sink's text method
-            // which should handle it appropriately
-            // Note: Real foster parenting for text would need more complex handling
-            self.sink.text(text);
-        } else {
-            self.sink.text(text);
-        }
-    }
-
     /// Close cell and switch to InRow mode.
     fn close_cell(&mut self) {
         self.flush_text();
         // Pop until we find td or th
-        while let Some((name, _)) = self.open_elements.pop() {
-            self.sink.end_element(name.clone());
-            if TABLE_CELL_ELEMENTS.contains(&name.as_str()) {
-                break;
-            }
-        }
+        self.pop_until_one_of(TABLE_CELL_ELEMENTS);
+        self.clear_active_formatting_to_marker();
         self.mode = InsertionMode::InRow;
     }
 
@@ -454,7 +573,6 @@ impl<S: TreeSink> TreeBuilder<S> {
     }
 
     /// Clear active formatting elements up to (and including) the last marker.
-    #[allow(dead_code)]
     fn clear_active_formatting_to_marker(&mut self) {
         while let Some(entry) = self.active_formatting_elements.pop() {
             if matches!(entry, FormattingEntry::Marker) {
@@ -463,211 +581,202 @@ impl<S: TreeSink> TreeBuilder<S> {
         }
     }
 
-    /// Find a formatting element in the active formatting list by name.
-    #[allow(dead_code)]
-    fn find_formatting_element(&self, tag_name: &str) -> Option<(usize, S::NodeId)> {
-        for (i, entry) in self.active_formatting_elements.iter().enumerate().rev() {
-            match entry {
-                FormattingEntry::Marker => return None,
-                FormattingEntry::Element { name, node_id, .. } => {
-                    if name == tag_name {
-                        return Some((i, node_id.clone()));
-                    }
-                }
-            }
-        }
-        None
-    }
-
     /// Find the position of a node in the open elements stack.
-    #[allow(dead_code)]
-    fn find_in_open_elements(&self, node_id: &S::NodeId) -> Option<usize>
-    where
-        S::NodeId: PartialEq
-    {
+    fn find_in_open_elements(&self, node_id: &S::NodeId) -> Option<usize> {
         self.open_elements.iter().position(|(_, id)| id == node_id)
     }
 
-    /// Check if an element is in the active formatting elements list.
-    #[allow(dead_code)]
-    fn is_in_active_formatting(&self, node_id: &S::NodeId) -> bool
-    where
-        S::NodeId: PartialEq
-    {
-        self.active_formatting_elements.iter().any(|entry| {
-            matches!(entry, FormattingEntry::Element { node_id: id, .. } if id == node_id)
-        })
+    /// Find the position of an element in the active formatting elements list.
+    fn find_in_active_formatting(&self, node_id: &S::NodeId) -> Option<usize> {
+        self.active_formatting_elements.iter().position(
+            |entry| matches!(entry, FormattingEntry::Element { node_id: id, .. } if id == node_id),
+        )
+    }
+
+    /// Whether a formatting list entry is a marker or an open element,
+    /// where reconstruction stops.
+    fn is_marker_or_open(&self, entry: &FormattingEntry<S::NodeId>) -> bool {
+        match entry {
+            FormattingEntry::Marker => true,
+            FormattingEntry::Element { node_id, .. } => {
+                self.find_in_open_elements(node_id).is_some()
+            }
+        }
     }
 
     /// Reconstruct active formatting elements.
     /// Called before inserting character tokens or certain elements.
     fn reconstruct_active_formatting(&mut self) {
-        // If the list is empty, do nothing
-        if self.active_formatting_elements.is_empty() {
+        let Some(last) = self.active_formatting_elements.last() else {
             return;
-        }
-
-        // Check if there's anything to reconstruct
-        let last_idx = self.active_formatting_elements.len() - 1;
-
-        // If the last entry is a marker, nothing to do
-        if matches!(&self.active_formatting_elements[last_idx], FormattingEntry::Marker) {
-            return;
-        }
-
-        // Check if the last formatting element is already in the open elements
-        // We use name-based tracking: if formatting element name is in open_elements, it's "active"
-        let last_name = match &self.active_formatting_elements[last_idx] {
-            FormattingEntry::Element { name, .. } => name.clone(),
-            FormattingEntry::Marker => return,
         };
-
-        // Check if this element is already on the stack as the current node or near top
-        // Simple heuristic: if the element name is anywhere in open_elements after the body, skip
-        let body_idx = self.open_elements.iter().position(|(n, _)| n == "body").unwrap_or(0);
-        for i in (body_idx + 1)..self.open_elements.len() {
-            if self.open_elements[i].0 == last_name {
-                // Already have this formatting element open, don't reconstruct
-                return;
-            }
+        if self.is_marker_or_open(last) {
+            return;
         }
 
-        // Find the first element that needs to be reconstructed
-        // Walk back from the end to find entries not in the stack
-        let mut reconstruct_start = last_idx;
-
-        while reconstruct_start > 0 {
-            reconstruct_start -= 1;
-            match &self.active_formatting_elements[reconstruct_start] {
-                FormattingEntry::Marker => {
-                    reconstruct_start += 1;
-                    break;
-                }
-                FormattingEntry::Element { name, .. } => {
-                    // Check if in open elements (after body)
-                    let in_stack = self.open_elements[body_idx + 1..].iter()
-                        .any(|(n, _)| n == name);
-                    if in_stack {
-                        reconstruct_start += 1;
-                        break;
-                    }
-                }
-            }
+        // Rewind to the entry after the last marker or open element
+        let mut start = self.active_formatting_elements.len() - 1;
+        while start > 0 && !self.is_marker_or_open(&self.active_formatting_elements[start - 1]) {
+            start -= 1;
         }
 
-        // Now reopen elements from reconstruct_start to the end
-        while reconstruct_start < self.active_formatting_elements.len() {
-            let (name, attrs) = match &self.active_formatting_elements[reconstruct_start] {
-                FormattingEntry::Element { name, attrs, .. } => (name.clone(), attrs.clone()),
-                FormattingEntry::Marker => {
-                    reconstruct_start += 1;
-                    continue;
-                }
+        // Reopen each entry from there, in order
+        for i in start..self.active_formatting_elements.len() {
+            let FormattingEntry::Element { name, attrs, .. } =
+                self.active_formatting_elements[i].clone()
+            else {
+                continue;
             };
-
-            // Create new element
-            let new_node_id = self.sink.start_element(name.clone(), attrs.clone(), false);
-            self.open_elements.push((name.clone(), new_node_id.clone()));
-
-            // Update the entry with new node id
-            self.active_formatting_elements[reconstruct_start] = FormattingEntry::Element {
+            let node_id = self.insert_element(name.clone(), attrs.clone(), false);
+            self.active_formatting_elements[i] = FormattingEntry::Element {
                 name,
                 attrs,
-                node_id: new_node_id,
+                node_id,
             };
-
-            reconstruct_start += 1;
         }
     }
 
-    /// Adoption Agency Algorithm for formatting elements.
-    /// Handles misnested tags like <b><i></b></i>.
-    fn adoption_agency_algorithm(&mut self, tag_name: &str) -> bool {
-        // Step 1: If the current node is the formatting element, just pop it
-        if let Some((name, _)) = self.current_node() {
-            if name == tag_name {
-                // Pop from open elements
-                let (popped_name, _) = self.open_elements.pop().unwrap();
-                self.sink.end_element(popped_name);
-
-                // Remove from active formatting list if present
-                if let Some(idx) = self.active_formatting_elements.iter().rposition(|entry| {
-                    matches!(entry, FormattingEntry::Element { name, .. } if name == tag_name)
-                }) {
-                    self.active_formatting_elements.remove(idx);
-                }
-                return true;
+    /// Adoption Agency Algorithm for the end tag of a formatting element.
+    ///
+    /// Handles misnested tags like `<b>1<p>2</b>3</p>`: the block that
+    /// broke up the formatting element moves out of it, and a copy of the
+    /// formatting element takes over the block's children.
+    fn adoption_agency_algorithm(&mut self, subject: &str) {
+        if let Some((name, node_id)) = self.current_node() {
+            if name == subject && self.find_in_active_formatting(node_id).is_none() {
+                self.pop_element();
+                return;
             }
         }
 
-        // Step 2-4: Find the formatting element in the list
-        let formatting_idx = match self.find_formatting_element_index(tag_name) {
-            Some(idx) => idx,
-            None => {
-                // No matching formatting element - treat as any other end tag
-                return false;
+        for _ in 0..AAA_OUTER_LIMIT {
+            let Some(formatting_index) = self.find_formatting_element_index(subject) else {
+                self.close_any_other_end_tag(subject);
+                return;
+            };
+            let FormattingEntry::Element {
+                name,
+                attrs,
+                node_id: formatting,
+            } = self.active_formatting_elements[formatting_index].clone()
+            else {
+                return;
+            };
+            let Some(formatting_stack) = self.find_in_open_elements(&formatting) else {
+                self.error(&format!("unexpected-end-tag </{}>", subject));
+                self.active_formatting_elements.remove(formatting_index);
+                return;
+            };
+            if !self.is_in_scope_at(formatting_stack) {
+                self.error(&format!("unexpected-end-tag </{}>", subject));
+                return;
             }
-        };
-
-        // Get formatting element info
-        let (fe_name, fe_node_idx) = match &self.active_formatting_elements[formatting_idx] {
-            FormattingEntry::Element { name, .. } => {
-                // Find in open elements
-                let stack_idx = self.open_elements.iter().rposition(|(n, _)| n == name);
-                match stack_idx {
-                    Some(idx) => (name.clone(), idx),
-                    None => {
-                        // Not in open elements - remove from formatting list
-                        self.active_formatting_elements.remove(formatting_idx);
-                        return true;
-                    }
-                }
+            if formatting_stack + 1 != self.open_elements.len() {
+                self.error(&format!("misnested-formatting-element </{}>", subject));
             }
-            FormattingEntry::Marker => return false,
-        };
-
-        // Step 5: Check if formatting element is in scope
-        if !self.has_element_in_scope(&fe_name) {
-            // Parse error - do nothing
-            return false;
-        }
 
-        // Step 6: Check if formatting element is the current node
-        if fe_node_idx == self.open_elements.len() - 1 {
-            // Just pop it
-            let (popped_name, _) = self.open_elements.pop().unwrap();
-            self.sink.end_element(popped_name);
-            self.active_formatting_elements.remove(formatting_idx);
-            return true;
-        }
+            // The furthest block is the first special element above it
+            let furthest_block = (formatting_stack + 1..self.open_elements.len())
+                .find(|&i| SPECIAL_ELEMENTS.contains(&self.open_elements[i].0.as_str()));
+            let (Some(furthest_block_stack), Some(common_ancestor)) =
+                (furthest_block, formatting_stack.checked_sub(1))
+            else {
+                while self.open_elements.len() > formatting_stack {
+                    self.pop_element();
+                }
+                self.active_formatting_elements.remove(formatting_index);
+                return;
+            };
+            let (common_ancestor_name, common_ancestor) =
+                self.open_elements[common_ancestor].clone();
+            let furthest_block = self.open_elements[furthest_block_stack].1.clone();
+
+            // Where the copy of the formatting element goes in the list
+            let mut bookmark = formatting_index;
+            let mut node_stack = furthest_block_stack;
+            let mut last_node = furthest_block.clone();
+            let mut inner = 0;
+            loop {
+                inner += 1;
+                node_stack -= 1;
+                let (node_name, node_id) = self.open_elements[node_stack].clone();
+                if node_id == formatting {
+                    break;
+                }
 
-        // Step 7-8: Find furthest block
-        let furthest_block_idx = self.find_furthest_block(fe_node_idx);
+                let mut node_index = self.find_in_active_formatting(&node_id);
+                if inner > AAA_INNER_LIMIT {
+                    if let Some(index) = node_index.take() {
+                        self.active_formatting_elements.remove(index);
+                        if index < bookmark {
+                            bookmark -= 1;
+                        }
+                    }
+                }
+                let Some(node_index) = node_index else {
+                    self.open_elements.remove(node_stack);
+                    continue;
+                };
 
-        if furthest_block_idx.is_none() {
-            // No furthest block - pop elements up to and including formatting element
-            while self.open_elements.len() > fe_node_idx {
-                let (popped_name, _) = self.open_elements.pop().unwrap();
-                self.sink.end_element(popped_name);
+                // Replace the node with a copy that takes over the last node
+                let FormattingEntry::Element {
+                    attrs: node_attrs, ..
+                } = self.active_formatting_elements[node_index].clone()
+                else {
+                    continue;
+                };
+                let copy = self
+                    .sink
+                    .create_element(node_name.clone(), node_attrs.clone());
+                self.active_formatting_elements[node_index] = FormattingEntry::Element {
+                    name: node_name.clone(),
+                    attrs: node_attrs,
+                    node_id: copy.clone(),
+                };
+                self.open_elements[node_stack] = (node_name, copy.clone());
+                if last_node == furthest_block {
+                    bookmark = node_index + 1;
+                }
+                self.sink.remove_from_parent(last_node.clone());
+                self.sink.append_child(copy.clone(), last_node);
+                last_node = copy;
+            }
+
+            self.sink.remove_from_parent(last_node.clone());
+            match self.find_last_table_index() {
+                Some(table)
+                    if self.foster_parenting
+                        && TABLE_CONTEXT_ELEMENTS.contains(&common_ancestor_name.as_str()) =>
+                {
+                    let table = self.open_elements[table].1.clone();
+                    self.sink.foster_parent(table, last_node);
+                }
+                _ => self.sink.append_child(common_ancestor, last_node),
             }
-            self.active_formatting_elements.remove(formatting_idx);
-            return true;
-        }
 
-        // Simplified AAA: For complex cases, just close the formatting element
-        // and remove it from the active formatting list.
-        // Full AAA requires DOM manipulation not supported by current TreeSink.
+            // A copy of the formatting element adopts the furthest block's children
+            let copy = self.sink.create_element(name.clone(), attrs.clone());
+            self.sink
+                .reparent_children(furthest_block.clone(), copy.clone());
+            self.sink.append_child(furthest_block.clone(), copy.clone());
 
-        // Close all elements up to and including the formatting element
-        while self.open_elements.len() > fe_node_idx {
-            let (popped_name, _) = self.open_elements.pop().unwrap();
-            self.sink.end_element(popped_name);
+            self.active_formatting_elements.insert(
+                bookmark,
+                FormattingEntry::Element {
+                    name: name.clone(),
+                    attrs,
+                    node_id: copy.clone(),
+                },
+            );
+            if let Some(index) = self.find_in_active_formatting(&formatting) {
+                self.active_formatting_elements.remove(index);
+            }
+            self.open_elements.retain(|(_, id)| *id != formatting);
+            if let Some(index) = self.find_in_open_elements(&furthest_block) {
+                self.open_elements.insert(index + 1, (name, copy));
+            }
+            self.sync_open_elements();
         }
-
-        // Remove from active formatting
-        self.active_formatting_elements.remove(formatting_idx);
-
-        true
     }
 
     /// Find formatting element index in active formatting list.
@@ -682,17 +791,6 @@ impl<S: TreeSink> TreeBuilder<S> {
         None
     }
 
-    /// Find the furthest block after the formatting element.
-    fn find_furthest_block(&self, fe_idx: usize) -> Option<usize> {
-        for i in (fe_idx + 1)..self.open_elements.len() {
-            let (name, _) = &self.open_elements[i];
-            if SPECIAL_ELEMENTS.contains(&name.as_str()) {
-                return Some(i);
-            }
-        }
-        None
-    }
-
     /// Check if a tag is a formatting element.
     fn is_formatting_element(tag_name: &str) -> bool {
         FORMATTING_ELEMENTS.contains(&tag_name)
@@ -708,6 +806,17 @@ impl<S: TreeSink> TreeBuilder<S> {
     fn process_token(&mut self, token: Token) -> ParseResult<()> {
         trace!(mode = ?self.mode, token = ?token, "Processing token");
 
+        // Tokenizer errors leave the insertion mode alone
+        if let Token::ParseError(error) = &token {
+            self.error(error);
+            return Ok(());
+        }
+        // Buffered text belongs where it was collected, before anything
+        // the next token inserts or closes
+        if !matches!(token, Token::Character(_)) {
+            self.flush_text();
+        }
+
         match self.mode {
             InsertionMode::Initial => self.handle_initial(token)?,
             InsertionMode::BeforeHtml => self.handle_before_html(token)?,
@@ -981,118 +1090,170 @@ impl<S: TreeSink> TreeBuilder<S> {
             } => {
                 // Flush any pending text before starting a new element
                 self.flush_text();
+                let attrs: Vec<(String, String)> = attrs.into_iter().collect();
 
-                // Handle table specially - switch to InTable mode
-                if name == "table" {
-                    self.close_p_element();
-                    let node_id = self.sink.start_element(
-                        name.clone(),
-                        attrs.into_iter().collect(),
-                        false,
-                    );
-                    self.open_elements.push((name, node_id));
-                    // Push marker when entering table (scope boundary)
-                    self.push_formatting_marker();
-                    self.mode = InsertionMode::InTable;
-                    return Ok(());
-                }
-
-                // Handle button - push marker
-                if name == "button" {
-                    if self.has_element_in_scope("button") {
-                        // Parse error - close the button
-                        self.adoption_agency_algorithm("button");
+                match name.as_str() {
+                    "html" | "body" => {
+                        self.error(&format!("unexpected-start-tag <{}>", name));
                     }
-                    self.reconstruct_active_formatting();
-                    let attrs_vec: Vec<(String, String)> = attrs.into_iter().collect();
-                    let node_id = self.sink.start_element(name.clone(), attrs_vec, false);
-                    self.open_elements.push((name, node_id));
-                    self.push_formatting_marker();
-                    return Ok(());
-                }
-
-                // Handle anchor specially - check for existing anchor in formatting list
-                if name == "a" {
-                    // If there's an 'a' element in the active formatting list, use AAA
-                    if self.find_formatting_element_index("a").is_some() {
-                        self.adoption_agency_algorithm("a");
+                    "table" => {
+                        if self.quirks_mode != QuirksMode::Quirks {
+                            self.close_p_element();
+                        }
+                        self.insert_element(name, attrs, false);
+                        self.mode = InsertionMode::InTable;
                     }
-                    self.reconstruct_active_formatting();
-                    let attrs_vec: Vec<(String, String)> = attrs.into_iter().collect();
-                    let node_id = self.sink.start_element(name.clone(), attrs_vec.clone(), false);
-                    self.open_elements.push((name.clone(), node_id.clone()));
-                    self.push_formatting_element(name, attrs_vec, node_id);
-                    return Ok(());
-                }
-
-                // Close p element if necessary
-                if P_CLOSING_ELEMENTS.contains(&name.as_str()) {
-                    self.close_p_element();
-                }
-
-                // Reconstruct active formatting elements before most elements
-                if !SPECIAL_ELEMENTS.contains(&name.as_str()) {
-                    self.reconstruct_active_formatting();
-                }
+                    "button" => {
+                        if self.has_element_in_scope("button") {
+                            self.error("unexpected-start-tag-implies-end-tag <button>");
+                            self.generate_implied_end_tags(None);
+                            self.pop_until("button");
+                        }
+                        self.reconstruct_active_formatting();
+                        self.insert_element(name, attrs, false);
+                    }
+                    "a" => {
+                        // An open anchor is closed first, as if by </a>
+                        if let Some(index) = self.find_formatting_element_index("a") {
+                            self.error("unexpected-start-tag-implies-end-tag <a>");
+                            let FormattingEntry::Element { node_id, .. } =
+                                self.active_formatting_elements[index].clone()
+                            else {
+                                unreachable!("formatting element lookups skip markers");
+                            };
+                            self.adoption_agency_algorithm("a");
+                            if let Some(index) = self.find_in_active_formatting(&node_id) {
+                                self.active_formatting_elements.remove(index);
+                            }
+                            if let Some(index) = self.find_in_open_elements(&node_id) {
+                                self.open_elements.remove(index);
+                                self.sync_open_elements();
+                            }
+                        }
+                        self.reconstruct_active_formatting();
+                        let node_id = self.insert_element(name.clone(), attrs.clone(), false);
+                        self.push_formatting_element(name, attrs, node_id);
+                    }
+                    "li" | "dd" | "dt" => {
+                        // Close the list item this one follows
+                        let siblings: &[&str] = if name == "li" { &["li"] } else { &["dd", "dt"] };
+                        for i in (0..self.open_elements.len()).rev() {
+                            let open = self.open_elements[i].0.clone();
+                            if siblings.contains(&open.as_str()) {
+                                self.close_element(&open);
+                                break;
+                            }
+                            if SPECIAL_ELEMENTS.contains(&open.as_str())
+                                && !matches!(open.as_str(), "address" | "div" | "p")
+                            {
+                                break;
+                            }
+                        }
+                        self.close_p_element();
+                        self.insert_element(name, attrs, self_closing);
+                    }
+                    name_str if HEADING_ELEMENTS.contains(&name_str) => {
+                        self.close_p_element();
+                        if self
+                            .current_node_name()
+                            .is_some_and(|current| HEADING_ELEMENTS.contains(&current))
+                        {
+                            self.error(&format!("unexpected-start-tag <{}>", name));
+                            self.pop_element();
+                        }
+                        self.insert_element(name, attrs, self_closing);
+                    }
+                    "option" | "optgroup" => {
+                        if self.current_node_name() == Some("option") {
+                            self.pop_element();
+                        }
+                        self.reconstruct_active_formatting();
+                        self.insert_element(name, attrs, self_closing);
+                    }
+                    _ => {
+                        // Close p element if necessary
+                        if P_CLOSING_ELEMENTS.contains(&name.as_str()) {
+                            self.close_p_element();
+                        }
 
-                let is_void = VOID_ELEMENTS.contains(&name.as_str());
-                let attrs_vec: Vec<(String, String)> = attrs.into_iter().collect();
-                let node_id = self.sink.start_element(
-                    name.clone(),
-                    attrs_vec.clone(),
-                    self_closing || is_void,
-                );
+                        // Reconstruct active formatting elements before most elements
+                        if !SPECIAL_ELEMENTS.contains(&name.as_str()) {
+                            self.reconstruct_active_formatting();
+                        }
 
-                if !is_void && !self_closing {
-                    self.open_elements.push((name.clone(), node_id.clone()));
+                        let node_id =
+                            self.insert_element(name.clone(), attrs.clone(), self_closing);
 
-                    // Track formatting elements
-                    if Self::is_formatting_element(&name) {
-                        self.push_formatting_element(name, attrs_vec, node_id);
+                        // Track formatting elements
+                        if !self_closing && Self::is_formatting_element(&name) {
+                            self.push_formatting_element(name, attrs, node_id);
+                        }
                     }
                 }
             }
             Token::EndTag { name } => {
                 self.flush_text();
 
-                // Handle formatting elements with AAA
-                if Self::is_formatting_element(&name) {
-                    self.adoption_agency_algorithm(&name);
-                    return Ok(());
-                }
-
-                // Find matching open element
-                let mut found = false;
-                for i in (0..self.open_elements.len()).rev() {
-                    if self.open_elements[i].0 == name {
-                        // Close all elements up to and including this one
-                        for _ in i..self.open_elements.len() {
-                            let (tag_name, _) = self.open_elements.pop().unwrap();
-                            self.sink.end_element(tag_name);
+                match name.as_str() {
+                    "body" | "html" => {
+                        if !self.has_element_in_scope("body") {
+                            self.error(&format!("unexpected-end-tag </{}>", name));
+                            return Ok(());
+                        }
+                        self.mode = InsertionMode::AfterBody;
+                        if name == "html" {
+                            self.process_token(Token::EndTag { name })?;
                         }
-                        found = true;
-                        break;
                     }
-                }
-
-                if !found {
-                    // Parse error - ignore
-                    trace!("Unmatched end tag: {}", name);
-                }
-
-                // Check if we closed body
-                if name == "body" {
-                    self.mode = InsertionMode::AfterBody;
-                }
-            }
-            Token::Eof => {
-                self.flush_text();
-
-                // Close all open elements
-                while let Some((tag_name, _)) = self.open_elements.pop() {
-                    self.sink.end_element(tag_name);
+                    "p" => {
+                        if !self.has_element_in_button_scope("p") {
+                            // </p> with no open p makes an empty one
+                            self.error("unexpected-end-tag </p>");
+                            self.insert_element(name, vec![], false);
+                        }
+                        self.close_element("p");
+                    }
+                    "li" | "dd" | "dt" => {
+                        let in_scope = if name == "li" {
+                            self.has_element_in_list_item_scope(&name)
+                        } else {
+                            self.has_element_in_scope(&name)
+                        };
+                        if in_scope {
+                            self.close_element(&name);
+                        } else {
+                            self.error(&format!("unexpected-end-tag </{}>", name));
+                        }
+                    }
+                    name_str if HEADING_ELEMENTS.contains(&name_str) => {
+                        if !HEADING_ELEMENTS
+                            .iter()
+                            .any(|h| self.has_element_in_scope(h))
+                        {
+                            self.error(&format!("unexpected-end-tag </{}>", name));
+                            return Ok(());
+                        }
+                        self.generate_implied_end_tags(None);
+                        if self.current_node_name() != Some(name_str) {
+                            self.error(&format!("end-tag-too-early </{}>", name));
+                        }
+                        self.pop_until_one_of(HEADING_ELEMENTS);
+                    }
+                    name_str if BLOCK_END_TAG_ELEMENTS.contains(&name_str) => {
+                        if self.has_element_in_scope(name_str) {
+                            self.close_element(name_str);
+                        } else {
+                            self.error(&format!("unexpected-end-tag </{}>", name));
+                        }
+                    }
+                    // Handle formatting elements with AAA
+                    name_str if Self::is_formatting_element(name_str) => {
+                        self.adoption_agency_algorithm(name_str);
+                    }
+                    _ => self.close_any_other_end_tag(&name),
                 }
             }
+            Token::Eof => self.stop_parsing(),
             _ => {}
         }
         Ok(())
@@ -1102,14 +1263,18 @@ impl<S: TreeSink> TreeBuilder<S> {
 
     fn handle_in_table(&mut self, token: Token) -> ParseResult<()> {
         match &token {
-            Token::Character(ch) if ch.is_whitespace() => {
-                // Switch to InTableText mode to accumulate whitespace
-                self.original_mode = Some(InsertionMode::InTable);
+            Token::Character(_)
+                if self
+                    .current_node_name()
+                    .is_some_and(|name| TABLE_CONTEXT_ELEMENTS.contains(&name)) =>
+            {
+                // Collect the run of text to see if it is all whitespace
+                self.original_mode = Some(self.mode);
                 self.mode = InsertionMode::InTableText;
-                self.pending_table_chars.push(*ch);
+                self.process_token(token)?;
             }
             Token::Character(_) => {
-                // Non-whitespace character - foster parent it
+                // Text inside content that was itself foster parented
                 self.foster_parenting = true;
                 self.handle_in_body(token)?;
                 self.foster_parenting = false;
@@ -1127,6 +1292,7 @@ impl<S: TreeSink> TreeBuilder<S> {
                             false,
                         );
                         self.open_elements.push((name.clone(), node_id));
+                        self.push_formatting_marker();
                         self.mode = InsertionMode::InCaption;
                     }
                     "colgroup" => {
@@ -1183,6 +1349,7 @@ impl<S: TreeSink> TreeBuilder<S> {
                     }
                     _ => {
                         // Anything else - foster parent
+                        self.error(&format!("foster-parented-content <{}>", name));
                         self.foster_parenting = true;
                         self.handle_in_body(token)?;
                         self.foster_parenting = false;
@@ -1194,7 +1361,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                     "table" => {
                         if self.has_element_in_table_scope("table") {
                             self.pop_until("table");
-                            self.sink.end_element("table".to_string());
                             self.reset_insertion_mode();
                         }
                     }
@@ -1234,9 +1400,10 @@ impl<S: TreeSink> TreeBuilder<S> {
 
                 if has_non_whitespace {
                     // Foster parent all characters
+                    self.error("foster-parented-content #text");
                     self.foster_parenting = true;
                     for ch in chars {
-                        self.text_buffer.push(ch);
+                        self.handle_in_body(Token::Character(ch))?;
                     }
                     self.flush_text();
                     self.foster_parenting = false;
@@ -1265,12 +1432,8 @@ impl<S: TreeSink> TreeBuilder<S> {
                     // Flush any pending text before closing caption
                     self.flush_text();
                     // Pop until caption
-                    while let Some((tag, _)) = self.open_elements.pop() {
-                        self.sink.end_element(tag.clone());
-                        if tag == "caption" {
-                            break;
-                        }
-                    }
+                    self.pop_until("caption");
+                    self.clear_active_formatting_to_marker();
                     self.mode = InsertionMode::InTable;
                 }
             }
@@ -1284,12 +1447,8 @@ impl<S: TreeSink> TreeBuilder<S> {
                 if self.has_element_in_table_scope("caption") {
                     // Flush any pending text before closing caption
                     self.flush_text();
-                    while let Some((tag, _)) = self.open_elements.pop() {
-                        self.sink.end_element(tag.clone());
-                        if tag == "caption" {
-                            break;
-                        }
-                    }
+                    self.pop_until("caption");
+                    self.clear_active_formatting_to_marker();
                     self.mode = InsertionMode::InTable;
                     self.process_token(token)?;
                 }
@@ -1299,12 +1458,8 @@ impl<S: TreeSink> TreeBuilder<S> {
                 if self.has_element_in_table_scope("caption") {
                     // Flush any pending text before closing caption
                     self.flush_text();
-                    while let Some((tag, _)) = self.open_elements.pop() {
-                        self.sink.end_element(tag.clone());
-                        if tag == "caption" {
-                            break;
-                        }
-                    }
+                    self.pop_until("caption");
+                    self.clear_active_formatting_to_marker();
                     self.mode = InsertionMode::InTable;
                     self.process_token(token)?;
                 }
@@ -1440,6 +1595,7 @@ impl<S: TreeSink> TreeBuilder<S> {
                     false,
                 );
                 self.open_elements.push((name.clone(), node_id));
+                self.push_formatting_marker();
                 self.mode = InsertionMode::InCell;
             }
             Token::EndTag { name } if name == "tr" => {
@@ -1509,12 +1665,8 @@ impl<S: TreeSink> TreeBuilder<S> {
                 if self.has_element_in_table_scope(name) {
                     self.flush_text();
                     // Pop until the cell
-                    while let Some((tag, _)) = self.open_elements.pop() {
-                        self.sink.end_element(tag.clone());
-                        if TABLE_CELL_ELEMENTS.contains(&tag.as_str()) {
-                            break;
-                        }
-                    }
+                    self.pop_until_one_of(TABLE_CELL_ELEMENTS);
+                    self.clear_active_formatting_to_marker();
                     self.mode = InsertionMode::InRow;
                 }
             }
@@ -1687,7 +1839,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                         // Parse error - close select
                         if self.has_element_in_select_scope("select") {
                             self.pop_until("select");
-                            self.sink.end_element("select".to_string());
                             self.reset_insertion_mode();
                         }
                     }
@@ -1695,7 +1846,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                         // Parse error - close select and reprocess
                         if self.has_element_in_select_scope("select") {
                             self.pop_until("select");
-                            self.sink.end_element("select".to_string());
                             self.reset_insertion_mode();
                             self.process_token(token)?;
                         }
@@ -1734,7 +1884,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                     "select" => {
                         if self.has_element_in_select_scope("select") {
                             self.pop_until("select");
-                            self.sink.end_element("select".to_string());
                             self.reset_insertion_mode();
                         }
                     }
@@ -1765,7 +1914,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                 // Parse error - close select and reprocess
                 self.flush_text();
                 self.pop_until("select");
-                self.sink.end_element("select".to_string());
                 self.reset_insertion_mode();
                 self.process_token(token)?;
             }
@@ -1779,7 +1927,6 @@ impl<S: TreeSink> TreeBuilder<S> {
                 if self.has_element_in_table_scope(name) {
                     self.flush_text();
                     self.pop_until("select");
-                    self.sink.end_element("select".to_string());
                     self.reset_insertion_mode();
                     self.process_token(token)?;
                 }
@@ -2128,9 +2275,7 @@ impl<S: TreeSink> TreeBuilder<S> {
             Token::EndTag { name } if name == "html" => {
                 self.mode = InsertionMode::AfterAfterBody;
             }
-            Token::Eof => {
-                // Done
-            }
+            Token::Eof => self.stop_parsing(),
             _ => {
                 // Parse error - reprocess in "in body" mode
                 self.mode = InsertionMode::InBody;
@@ -2150,9 +2295,7 @@ impl<S: TreeSink> TreeBuilder<S> {
                 self.process_token(Token::Character(ch))?;
                 self.mode = InsertionMode::AfterAfterBody;
             }
-            Token::Eof => {
-                // Done
-            }
+            Token::Eof => self.stop_parsing(),
             _ => {
                 // Parse error - reprocess in "in body" mode
                 self.mode = InsertionMode::InBody;
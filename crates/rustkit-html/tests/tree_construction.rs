@@ -0,0 +1,278 @@
+//! Tree construction tests against fixtures in the html5lib format.
+//!
+//! Each test in `fixtures/tree_construction.dat` gives a document
+//! (`#data`), the parse errors this parser reports for it (`#errors`),
+//! and the tree a conforming parser builds (`#document`), one node per
+//! line, indented two spaces per level, with attributes sorted by name.
+
+use rustkit_html::{parse, TreeSink};
+
+enum Data {
+    Document,
+    Doctype(String),
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+struct Node {
+    data: Data,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// A sink that builds a real tree, merging adjacent text like a DOM does.
+struct ArenaSink {
+    nodes: Vec<Node>,
+    open_elements: Vec<usize>,
+    errors: Vec<String>,
+}
+
+impl ArenaSink {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                data: Data::Document,
+                parent: None,
+                children: vec![],
+            }],
+            open_elements: vec![],
+            errors: vec![],
+        }
+    }
+
+    fn create(&mut self, data: Data) -> usize {
+        self.nodes.push(Node {
+            data,
+            parent: None,
+            children: vec![],
+        });
+        self.nodes.len() - 1
+    }
+
+    fn current(&self) -> usize {
+        self.open_elements.last().copied().unwrap_or(0)
+    }
+
+    fn detach(&mut self, node: usize) {
+        if let Some(parent) = self.nodes[node].parent.take() {
+            self.nodes[parent].children.retain(|&child| child != node);
+        }
+    }
+
+    fn insert_at(&mut self, parent: usize, index: usize, node: usize) {
+        self.detach(node);
+        self.nodes[node].parent = Some(parent);
+        self.nodes[parent].children.insert(index, node);
+    }
+
+    fn insert_text_at(&mut self, parent: usize, index: usize, text: String) {
+        if let Some(&previous) = index
+            .checked_sub(1)
+            .and_then(|i| self.nodes[parent].children.get(i))
+        {
+            if let Data::Text(existing) = &mut self.nodes[previous].data {
+                existing.push_str(&text);
+                return;
+            }
+        }
+        let node = self.create(Data::Text(text));
+        self.insert_at(parent, index, node);
+    }
+
+    fn serialize(&self, node: usize, depth: usize, out: &mut String) {
+        let indent = format!("| {}", "  ".repeat(depth));
+        match &self.nodes[node].data {
+            Data::Document => {}
+            Data::Doctype(name) => out.push_str(&format!("{}<!DOCTYPE {}>\n", indent, name)),
+            Data::Element { name, attrs } => {
+                out.push_str(&format!("{}<{}>\n", indent, name));
+                let mut attrs = attrs.clone();
+                attrs.sort();
+                for (key, value) in attrs {
+                    out.push_str(&format!("{}  {}=\"{}\"\n", indent, key, value));
+                }
+            }
+            Data::Text(text) => out.push_str(&format!("{}\"{}\"\n", indent, text)),
+            Data::Comment(text) => out.push_str(&format!("{}<!-- {} -->\n", indent, text)),
+        }
+        let depth = match self.nodes[node].data {
+            Data::Document => 0,
+            _ => depth + 1,
+        };
+        for &child in &self.nodes[node].children {
+            self.serialize(child, depth, out);
+        }
+    }
+}
+
+impl TreeSink for ArenaSink {
+    type NodeId = usize;
+
+    fn doctype(&mut self, name: String, _public_id: String, _system_id: String) {
+        let node = self.create(Data::Doctype(name));
+        self.insert_at(0, self.nodes[0].children.len(), node);
+    }
+
+    fn start_element(
+        &mut self,
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    ) -> Self::NodeId {
+        let node = self.create(Data::Element { name, attrs });
+        let parent = self.current();
+        self.insert_at(parent, self.nodes[parent].children.len(), node);
+        if !self_closing {
+            self.open_elements.push(node);
+        }
+        node
+    }
+
+    fn end_element(&mut self, _name: String) {
+        self.open_elements.pop();
+    }
+
+    fn text(&mut self, data: String) {
+        let parent = self.current();
+        self.insert_text_at(parent, self.nodes[parent].children.len(), data);
+    }
+
+    fn comment(&mut self, data: String) {
+        let node = self.create(Data::Comment(data));
+        let parent = self.current();
+        self.insert_at(parent, self.nodes[parent].children.len(), node);
+    }
+
+    fn current_node(&self) -> Option<Self::NodeId> {
+        self.open_elements.last().copied()
+    }
+
+    fn in_scope(&self, _tag_name: &str) -> bool {
+        false
+    }
+
+    fn pop_until(&mut self, _tag_name: &str) {}
+
+    fn close_p_element_in_button_scope(&mut self) {}
+
+    fn reconstruct_active_formatting_elements(&mut self) {}
+
+    fn create_element(&mut self, name: String, attrs: Vec<(String, String)>) -> Self::NodeId {
+        self.create(Data::Element { name, attrs })
+    }
+
+    fn append_child(&mut self, parent: Self::NodeId, child: Self::NodeId) {
+        self.detach(child);
+        self.insert_at(parent, self.nodes[parent].children.len(), child);
+    }
+
+    fn remove_from_parent(&mut self, node: Self::NodeId) {
+        self.detach(node);
+    }
+
+    fn reparent_children(&mut self, from: Self::NodeId, to: Self::NodeId) {
+        for child in std::mem::take(&mut self.nodes[from].children) {
+            self.nodes[child].parent = None;
+            self.insert_at(to, self.nodes[to].children.len(), child);
+        }
+    }
+
+    fn insert_before(
+        &mut self,
+        parent: Self::NodeId,
+        node: Self::NodeId,
+        reference: Option<Self::NodeId>,
+    ) {
+        self.detach(node);
+        let index = reference
+            .and_then(|reference| {
+                self.nodes[parent]
+                    .children
+                    .iter()
+                    .position(|&c| c == reference)
+            })
+            .unwrap_or(self.nodes[parent].children.len());
+        self.insert_at(parent, index, node);
+    }
+
+    fn get_parent(&self, node: Self::NodeId) -> Option<Self::NodeId> {
+        self.nodes[node].parent
+    }
+
+    fn get_tag_name(&self, node: Self::NodeId) -> Option<String> {
+        match &self.nodes[node].data {
+            Data::Element { name, .. } => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    fn foster_parent_text(&mut self, table: Self::NodeId, data: String) {
+        let Some(parent) = self.nodes[table].parent else {
+            return self.text(data);
+        };
+        let index = self.nodes[parent]
+            .children
+            .iter()
+            .position(|&c| c == table)
+            .unwrap();
+        self.insert_text_at(parent, index, data);
+    }
+
+    fn reset_open_elements(&mut self, stack: &[Self::NodeId]) {
+        self.open_elements = stack.to_vec();
+    }
+
+    fn parse_error(&mut self, error: &str) {
+        self.errors.push(error.to_string());
+    }
+}
+
+struct Fixture {
+    data: String,
+    errors: Vec<String>,
+    document: String,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    include_str!("fixtures/tree_construction.dat")
+        .split("#data\n")
+        .skip(1)
+        .map(|test| {
+            let (data, rest) = test.split_once("\n#errors\n").expect("#errors section");
+            let (errors, document) = rest.split_once("#document\n").expect("#document section");
+            Fixture {
+                data: data.to_string(),
+                errors: errors.lines().map(str::to_string).collect(),
+                document: document.trim_end().to_string(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_tree_construction_fixtures() {
+    let fixtures = fixtures();
+    assert!(fixtures.len() >= 12);
+
+    let mut failures = Vec::new();
+    for fixture in &fixtures {
+        let sink = parse(&fixture.data, ArenaSink::new()).unwrap();
+        let mut document = String::new();
+        sink.serialize(0, 0, &mut document);
+        let document = document.trim_end();
+        if document != fixture.document || sink.errors != fixture.errors {
+            failures.push(format!(
+                "#data\n{}\n#errors\n{}\n#document (got)\n{}\n#document (expected)\n{}\n",
+                fixture.data,
+                sink.errors.join("\n"),
+                document,
+                fixture.document
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,253 @@
+//! HTTP/2 connections and server push.
+//!
+//! A TLS connection that negotiates `h2` is kept for its `host:port` and
+//! carries every later request there as a new stream. Servers may push
+//! responses alongside one: each promise is offered to the client's
+//! [`PushHandler`], and one it declines, or one beyond the connection's
+//! limit of pushes in flight, is reset with `CANCEL`. Without a handler,
+//! push is disabled in the connection's settings.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::future::{select, Either};
+use futures::FutureExt;
+use h2::client::SendRequest;
+use h2::{Reason, RecvStream};
+use http::header::{ACCEPT, CONNECTION, HOST, TRANSFER_ENCODING, UPGRADE, USER_AGENT};
+use http::{HeaderMap, Method, Version};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+use tracing::{debug, trace};
+use url::Url;
+
+use crate::{HttpError, RawResponse, Response, TlsInfo};
+
+/// A response the server promised to push.
+#[derive(Debug, Clone)]
+pub struct PushPromise {
+    /// URL of the promised request.
+    pub url: Url,
+    /// Method of the promised request.
+    pub method: Method,
+    /// Headers of the promised request.
+    pub headers: HeaderMap,
+    /// URL of the request the promise came with.
+    pub initiator: Url,
+    /// Top-level site partition of that request.
+    pub site: Option<String>,
+}
+
+/// Decides which pushed responses to receive, and takes them.
+pub trait PushHandler: Send + Sync {
+    /// Whether to receive `promise`. Declined promises are reset.
+    fn accept(&self, promise: &PushPromise) -> bool;
+
+    /// `promise` was reset without being offered, because its connection
+    /// already had its limit of pushes in flight.
+    fn refused(&self, _promise: &PushPromise) {}
+
+    /// An accepted push arrived in full, or failed.
+    fn received(&self, promise: PushPromise, response: Result<Response, HttpError>);
+}
+
+/// What receiving the pushes that come with one request takes.
+pub(crate) struct PushContext {
+    pub handler: Option<Arc<dyn PushHandler>>,
+    /// Most accepted pushes in flight on the connection at once.
+    pub limit: usize,
+    pub initiator: Url,
+    pub site: Option<String>,
+}
+
+/// An HTTP/2 connection, shared by the requests to one `host:port`.
+#[derive(Clone)]
+pub(crate) struct H2Connection {
+    send: SendRequest<Bytes>,
+    /// TLS parameters the connection was opened with.
+    pub tls: TlsInfo,
+    /// Accepted pushes still arriving.
+    pushes_in_flight: Arc<AtomicUsize>,
+}
+
+impl H2Connection {
+    /// Perform the HTTP/2 handshake on `stream`, then drive the connection
+    /// in the background.
+    pub async fn handshake(
+        stream: TlsStream<TcpStream>,
+        tls: TlsInfo,
+        push: bool,
+    ) -> Result<Self, HttpError> {
+        let (send, connection) = h2::client::Builder::new()
+            .enable_push(push)
+            .handshake(stream)
+            .await
+            .map_err(h2_error)?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!(error = %e, "HTTP/2 connection failed");
+            }
+        });
+        Ok(Self {
+            send,
+            tls,
+            pushes_in_flight: Arc::default(),
+        })
+    }
+
+    /// Send a request on a new stream. Returns once the response and
+    /// every push promised with it are in; pushed bodies arrive later.
+    ///
+    /// Fails with [`HttpError::ConnectionClosed`] only when the server
+    /// cannot have processed the request, so it may be sent again.
+    pub async fn send(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        user_agent: &str,
+        body: &Option<Bytes>,
+        push: PushContext,
+    ) -> Result<RawResponse, HttpError> {
+        let mut send = self.send.clone().ready().await.map_err(closed)?;
+
+        let mut uri = url.clone();
+        uri.set_fragment(None);
+        let mut request = http::Request::builder()
+            .method(method.clone())
+            .uri(uri.as_str())
+            .version(Version::HTTP_2)
+            .header(USER_AGENT, user_agent)
+            .header(ACCEPT, "*/*");
+        // Connection-specific headers are malformed in HTTP/2
+        for (name, value) in headers.iter().filter(|(name, _)| {
+            ![CONNECTION, HOST, TRANSFER_ENCODING, UPGRADE].contains(name) && *name != "keep-alive"
+        }) {
+            request = request.header(name, value);
+        }
+        let request = request
+            .body(())
+            .map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
+
+        let body = body.as_ref().filter(|body| !body.is_empty());
+        let (mut response, mut stream) =
+            send.send_request(request, body.is_none()).map_err(closed)?;
+        if let Some(body) = body {
+            stream.send_data(body.clone(), true).map_err(h2_error)?;
+        }
+
+        // Promises are offered as they come, beside the response
+        let mut promises = response.push_promises();
+        let mut receive = Box::pin(async {
+            let (parts, body) = response.await.map_err(h2_error)?.into_parts();
+            Ok::<_, HttpError>((parts, read_body(body).await?))
+        });
+        let result = loop {
+            let promise = Box::pin(promises.push_promise());
+            match select(&mut receive, promise).await {
+                Either::Left((result, _)) => break result,
+                Either::Right((Some(Ok(promise)), _)) => {
+                    self.offer(promise, &push);
+                }
+                Either::Right(_) => break receive.await,
+            }
+        };
+        // h2 does not wake the promise stream when its stream ends, so
+        // take what came with the last frames without waiting
+        while let Some(Some(Ok(promise))) = promises.push_promise().now_or_never() {
+            self.offer(promise, &push);
+        }
+        let (parts, body) = result?;
+
+        trace!(status = %parts.status, body_len = body.len(), "HTTP/2 response received");
+        Ok(RawResponse {
+            status: parts.status,
+            version: Version::HTTP_2,
+            headers: parts.headers,
+            body,
+            certificate_pinned: false,
+            tls: None,
+        })
+    }
+
+    /// Offer a promise to the handler and receive it in the background if
+    /// accepted. Promises are reset by dropping their response future.
+    fn offer(&self, promise: h2::client::PushPromise, context: &PushContext) {
+        let (request, response) = promise.into_parts();
+        let Some(handler) = &context.handler else {
+            return;
+        };
+        let Ok(url) = Url::parse(&request.uri().to_string()) else {
+            return;
+        };
+        let promise = PushPromise {
+            url,
+            method: request.method().clone(),
+            headers: request.headers().clone(),
+            initiator: context.initiator.clone(),
+            site: context.site.clone(),
+        };
+
+        if self.pushes_in_flight.load(Ordering::SeqCst) >= context.limit {
+            debug!(url = %promise.url, "Refusing push over the connection's limit");
+            handler.refused(&promise);
+            return;
+        }
+        if !handler.accept(&promise) {
+            debug!(url = %promise.url, "Declined push");
+            return;
+        }
+
+        self.pushes_in_flight.fetch_add(1, Ordering::SeqCst);
+        let handler = Arc::clone(handler);
+        let tls = self.tls.reused();
+        let in_flight = Arc::clone(&self.pushes_in_flight);
+        tokio::spawn(async move {
+            let result = async {
+                let (parts, body) = response.await.map_err(h2_error)?.into_parts();
+                Ok(Response {
+                    status: parts.status,
+                    version: Version::HTTP_2,
+                    headers: parts.headers,
+                    body: read_body(body).await?,
+                    url: promise.url.clone(),
+                    certificate_pinned: false,
+                    tls: Some(tls),
+                })
+            }
+            .await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            handler.received(promise, result);
+        });
+    }
+}
+
+async fn read_body(mut body: RecvStream) -> Result<Bytes, HttpError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(h2_error)?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(data))
+}
+
+/// The connection could not take a new stream, so nothing was sent.
+fn closed(e: h2::Error) -> HttpError {
+    HttpError::ConnectionClosed(format!("HTTP/2: {}", e))
+}
+
+fn h2_error(e: h2::Error) -> HttpError {
+    // A refused stream was not processed (RFC 9113, section 8.7)
+    if e.reason() == Some(Reason::REFUSED_STREAM) {
+        return closed(e);
+    }
+    if e.is_io() {
+        if let Some(e) = e.into_io() {
+            return HttpError::IoError(e);
+        }
+        return HttpError::ConnectionFailed("HTTP/2 connection lost".to_string());
+    }
+    HttpError::InvalidResponse(format!("HTTP/2: {}", e))
+}
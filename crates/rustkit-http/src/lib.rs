@@ -1,24 +1,39 @@
 //! # RustKit HTTP
 //!
-//! Minimal HTTP/1.1 client for the RustKit browser engine.
+//! Minimal HTTP/1.1 and HTTP/2 client for the RustKit browser engine.
 //!
 //! This crate provides a simple async HTTP client using native-tls for TLS,
 //! eliminating the need for reqwest and its transitive dependencies.
 
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version};
-use native_tls::TlsConnector as NativeTlsConnector;
+use native_tls::{Certificate, Protocol, TlsConnector as NativeTlsConnector};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::timeout;
-use tokio_native_tls::TlsConnector;
+use tokio_native_tls::{TlsConnector, TlsStream};
 use tracing::{debug, trace};
 use url::Url;
 
+pub mod http2;
+pub mod multipart;
+pub mod pool;
+pub mod proxy;
+
+pub use http2::{PushHandler, PushPromise};
+pub use pool::{HostPoolStats, PoolConfig, PoolStats, TcpKeepalive};
+pub use proxy::{ProxyResolver, ProxyServer};
+use http2::{H2Connection, PushContext};
+use pool::{Lease, Pool, PooledConnection, PooledStream};
+
 /// HTTP client errors.
 #[derive(Error, Debug)]
 pub enum HttpError {
@@ -31,6 +46,15 @@ pub enum HttpError {
     #[error("TLS error: {0}")]
     TlsError(String),
 
+    #[error("Certificate error for {host} ({kind:?}): {message}")]
+    CertificateError {
+        host: String,
+        kind: CertificateErrorKind,
+        /// DER-encoded certificates presented by the server, leaf first.
+        cert_chain_der: Vec<Vec<u8>>,
+        message: String,
+    },
+
     #[error("Request timeout")]
     Timeout,
 
@@ -45,8 +69,74 @@ pub enum HttpError {
 
     #[error("Unsupported scheme: {0}")]
     UnsupportedScheme(String),
+
+    #[error("Connection closed before the response: {0}")]
+    ConnectionClosed(String),
+
+    #[error("Unsupported TLS setting: {0}")]
+    UnsupportedTlsSetting(String),
+}
+
+/// Why a server certificate failed validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateErrorKind {
+    Expired,
+    HostnameMismatch,
+    UntrustedRoot,
+    Revoked,
+    Other,
+}
+
+impl CertificateErrorKind {
+    /// Classify a handshake error message from the platform TLS backend
+    /// (OpenSSL, Schannel, or Secure Transport).
+    pub fn from_message(message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| message.contains(n));
+
+        if has(&["revoked"]) {
+            Self::Revoked
+        } else if has(&["expired", "not yet valid"]) {
+            Self::Expired
+        } else if has(&[
+            "hostname mismatch",
+            "principal name is incorrect",
+            "name mismatch",
+        ]) {
+            Self::HostnameMismatch
+        } else if has(&[
+            "self-signed",
+            "self signed",
+            "unable to get local issuer",
+            "untrusted",
+            "not trusted",
+            "unknown ca",
+        ]) {
+            Self::UntrustedRoot
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// SHA-256 fingerprint of a DER-encoded certificate.
+pub fn certificate_fingerprint(der: &[u8]) -> [u8; 32] {
+    Sha256::digest(der).into()
+}
+
+/// A certificate accepted for one host even though it fails validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedCertificate {
+    /// Host the certificate is accepted for.
+    pub host: String,
+    /// SHA-256 fingerprint of the leaf certificate.
+    pub sha256: [u8; 32],
 }
 
+/// Receives the interim (1xx) responses, such as `103 Early Hints`, read
+/// before a request's final response.
+pub type InterimHandler<'a> = dyn Fn(StatusCode, &HeaderMap) + Send + Sync + 'a;
+
 /// HTTP response.
 #[derive(Debug)]
 pub struct Response {
@@ -60,6 +150,10 @@ pub struct Response {
     pub body: Bytes,
     /// Final URL (after redirects).
     pub url: Url,
+    /// Whether the connection was only accepted because of a [`PinnedCertificate`].
+    pub certificate_pinned: bool,
+    /// Negotiated TLS parameters, for HTTPS responses.
+    pub tls: Option<TlsInfo>,
 }
 
 impl Response {
@@ -89,6 +183,115 @@ impl Response {
     }
 }
 
+/// Lowest TLS version a connection may negotiate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsVersion {
+    /// TLS 1.2 or later.
+    #[default]
+    Tls12,
+    /// TLS 1.3 only.
+    Tls13,
+}
+
+impl TlsVersion {
+    fn protocol(self) -> Protocol {
+        match self {
+            TlsVersion::Tls12 => Protocol::Tlsv12,
+            TlsVersion::Tls13 => Protocol::Tlsv13,
+        }
+    }
+}
+
+/// How a server certificate's revocation status is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RevocationMode {
+    /// No revocation checking.
+    #[default]
+    Off,
+    /// Reject a certificate a stapled OCSP response reports revoked, and
+    /// accept one that comes without a response.
+    SoftFail,
+    /// Reject a certificate unless its status is confirmed good.
+    HardFail,
+}
+
+/// TLS settings applied to new connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Minimum protocol version.
+    pub min_version: TlsVersion,
+    /// ALPN protocols to offer, most preferred first. Empty disables ALPN.
+    pub alpn_protocols: Vec<String>,
+    /// Keep established TLS connections idle and reuse them for later
+    /// requests to the same host.
+    ///
+    /// native-tls does not expose session tickets or IDs, so a session is
+    /// reused by keeping its connection alive rather than by an abbreviated
+    /// handshake on a new connection.
+    pub session_resumption: bool,
+    /// Most idle TLS connections kept.
+    pub session_cache_size: usize,
+    /// Revocation checking. native-tls exposes neither stapled OCSP
+    /// responses nor the platform's revocation checks, so only
+    /// [`RevocationMode::Off`] is supported; other modes are rejected
+    /// with [`HttpError::UnsupportedTlsSetting`].
+    pub revocation: RevocationMode,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            min_version: TlsVersion::Tls12,
+            alpn_protocols: vec!["http/1.1".to_string()],
+            session_resumption: true,
+            session_cache_size: 32,
+            revocation: RevocationMode::Off,
+        }
+    }
+}
+
+/// TLS parameters of the connection a response arrived on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Protocol selected by ALPN, if the server chose one.
+    pub alpn_protocol: Option<String>,
+    /// Negotiated protocol version, such as `TLSv1.3`. native-tls does not
+    /// report it, so it is known only when the minimum version allows no
+    /// other.
+    pub protocol_version: Option<String>,
+    /// Negotiated cipher suite. native-tls does not report it, so it is
+    /// `None` until the TLS backend does.
+    pub cipher_suite: Option<String>,
+    /// Whether the connection was open before the request, idle in the
+    /// pool or preconnected, so the request made no handshake. Sessions
+    /// are never resumed on new connections.
+    pub connection_reused: bool,
+}
+
+impl TlsInfo {
+    /// Parameters of a new connection made under `config`.
+    fn new(stream: &TlsStream<TcpStream>, config: &TlsConfig) -> Self {
+        let protocol_version = match config.min_version {
+            TlsVersion::Tls13 => Some("TLSv1.3".to_string()),
+            TlsVersion::Tls12 => None,
+        };
+        Self {
+            alpn_protocol: negotiated_alpn(stream),
+            protocol_version,
+            cipher_suite: None,
+            connection_reused: false,
+        }
+    }
+
+    /// The same parameters, for a later request on the connection.
+    pub(crate) fn reused(&self) -> Self {
+        Self {
+            connection_reused: true,
+            ..self.clone()
+        }
+    }
+}
+
 /// HTTP client configuration.
 #[derive(Clone)]
 pub struct ClientConfig2 {
@@ -100,6 +303,17 @@ pub struct ClientConfig2 {
     pub max_redirects: usize,
     /// Whether to follow redirects.
     pub follow_redirects: bool,
+    /// Additional DER-encoded root certificates to trust.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// TLS settings.
+    pub tls: TlsConfig,
+    /// Receives HTTP/2 server pushes. Without one, push is disabled.
+    pub push_handler: Option<Arc<dyn PushHandler>>,
+    /// Most pushes received at once on one HTTP/2 connection; further
+    /// promises are reset.
+    pub max_concurrent_pushes: usize,
+    /// Keep-alive connection settings.
+    pub pool: PoolConfig,
 }
 
 impl Default for ClientConfig2 {
@@ -109,14 +323,124 @@ impl Default for ClientConfig2 {
             timeout: Duration::from_secs(30),
             max_redirects: 10,
             follow_redirects: true,
+            root_certificates: Vec::new(),
+            tls: TlsConfig::default(),
+            push_handler: None,
+            max_concurrent_pushes: 8,
+            pool: PoolConfig::default(),
         }
     }
 }
 
+/// Connectors built from the current TLS settings.
+struct TlsState {
+    config: TlsConfig,
+    roots: Vec<Vec<u8>>,
+    connector: TlsConnector,
+    /// Connector offering only `http/1.1`, for hosts pinned to HTTP/1.1.
+    http1_connector: TlsConnector,
+    /// Connector that skips validation, used to inspect rejected certificates.
+    unverified_connector: TlsConnector,
+}
+
+impl TlsState {
+    fn new(config: TlsConfig, roots: Vec<Vec<u8>>) -> Result<Self, HttpError> {
+        if config.revocation != RevocationMode::Off {
+            return Err(HttpError::UnsupportedTlsSetting(format!(
+                "{:?} revocation checking",
+                config.revocation
+            )));
+        }
+        let connector = build_tls_connector(&config, &roots, false)?;
+        let http1_config = TlsConfig {
+            alpn_protocols: config
+                .alpn_protocols
+                .iter()
+                .filter(|p| *p == "http/1.1")
+                .cloned()
+                .collect(),
+            ..config.clone()
+        };
+        let http1_connector = build_tls_connector(&http1_config, &roots, false)?;
+        let unverified_connector = build_tls_connector(&config, &[], true)?;
+        Ok(Self {
+            config,
+            roots,
+            connector,
+            http1_connector,
+            unverified_connector,
+        })
+    }
+}
+
+/// How long resolved addresses are reused.
+const DNS_TTL: Duration = Duration::from_secs(60);
+
+/// A connection opened by [`Client::preconnect`] that has not carried a
+/// request yet.
+struct WarmConnection {
+    /// Origin and credentials partition, from [`warm_key`].
+    key: String,
+    stream: PooledStream,
+    expires: Instant,
+}
+
+/// What connection state a request may share with others. Anonymous and
+/// credentialed requests never share preconnected connections, and
+/// requests for different top-level sites share neither those nor
+/// resolved addresses, so one site cannot time another's lookups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Partition<'a> {
+    /// Sent without credentials.
+    pub anonymous: bool,
+    /// Serialized top-level site, or `None` to share with every site.
+    pub site: Option<&'a str>,
+}
+
+/// Resolver cache key of `host:port` within a site partition.
+fn dns_key(host: &str, port: u16, site: Option<&str>) -> String {
+    format!(
+        "{} {}:{}",
+        site.unwrap_or(""),
+        host.to_ascii_lowercase(),
+        port
+    )
+}
+
+/// Pool key of preconnected connections, from the origin and partition.
+fn warm_key(scheme: &str, host: &str, port: u16, partition: Partition<'_>) -> String {
+    let credentials = if partition.anonymous {
+        "anonymous"
+    } else {
+        "credentialed"
+    };
+    format!(
+        "{}://{}:{} {} {}",
+        scheme,
+        host.to_ascii_lowercase(),
+        port,
+        credentials,
+        partition.site.unwrap_or("")
+    )
+}
+
 /// HTTP client.
 pub struct Client {
     config: ClientConfig2,
-    tls_connector: TlsConnector,
+    tls: RwLock<TlsState>,
+    /// Idle keep-alive connections.
+    pool: Arc<Mutex<Pool>>,
+    /// `host:port` pairs that only offer `http/1.1` via ALPN.
+    http1_only: RwLock<HashSet<String>>,
+    /// Resolved addresses by top-level site and `host:port`, with when
+    /// they were resolved.
+    dns: Mutex<HashMap<String, (Vec<SocketAddr>, Instant)>>,
+    /// Preconnected connections waiting for their first request.
+    warm: Mutex<Vec<WarmConnection>>,
+    /// HTTP/2 connections by `host:port`.
+    h2: Mutex<HashMap<String, H2Connection>>,
+    /// Chooses proxies; `None` connects directly.
+    proxy: RwLock<Option<Arc<dyn ProxyResolver>>>,
 }
 
 impl Client {
@@ -127,16 +451,290 @@ impl Client {
 
     /// Create a new HTTP client with custom configuration.
     pub fn with_config(config: ClientConfig2) -> Result<Self, HttpError> {
-        // Build native-tls connector
-        let native_connector = NativeTlsConnector::new()
-            .map_err(|e| HttpError::TlsError(e.to_string()))?;
-
-        let tls_connector = TlsConnector::from(native_connector);
+        let tls = TlsState::new(config.tls.clone(), config.root_certificates.clone())?;
 
         Ok(Self {
             config,
-            tls_connector,
+            tls: RwLock::new(tls),
+            pool: Arc::new(Mutex::new(Pool::default())),
+            http1_only: RwLock::new(HashSet::new()),
+            dns: Mutex::new(HashMap::new()),
+            warm: Mutex::new(Vec::new()),
+            h2: Mutex::new(HashMap::new()),
+            proxy: RwLock::new(None),
+        })
+    }
+
+    /// Resolve `host:port`, reusing addresses resolved in the last minute
+    /// for the same top-level `site`.
+    pub async fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        site: Option<&str>,
+    ) -> Result<Vec<SocketAddr>, HttpError> {
+        let key = dns_key(host, port, site);
+        if let Some((addrs, resolved)) = self.dns.lock().unwrap().get(&key) {
+            if resolved.elapsed() < DNS_TTL {
+                return Ok(addrs.clone());
+            }
+        }
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| HttpError::ConnectionFailed(e.to_string()))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(HttpError::ConnectionFailed(format!(
+                "No addresses for {}",
+                host
+            )));
+        }
+        trace!(host, count = addrs.len(), "Resolved host");
+        self.dns
+            .lock()
+            .unwrap()
+            .insert(key, (addrs.clone(), Instant::now()));
+        Ok(addrs)
+    }
+
+    /// Open a TCP connection through the resolver cache. Addresses that
+    /// refuse the connection are resolved again next time.
+    async fn connect_tcp(
+        &self,
+        host: &str,
+        port: u16,
+        site: Option<&str>,
+    ) -> Result<TcpStream, HttpError> {
+        let addrs = self.resolve(host, port, site).await?;
+        let stream = TcpStream::connect(&addrs[..]).await.map_err(|e| {
+            self.dns.lock().unwrap().remove(&dns_key(host, port, site));
+            HttpError::ConnectionFailed(e.to_string())
+        })?;
+        if let Some(keepalive) = self.config.pool.tcp_keepalive {
+            if let Err(e) = keepalive.apply(&stream) {
+                debug!(host, error = %e, "Couldn't set TCP keepalive");
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Send requests through the proxies `resolver` picks, or directly.
+    ///
+    /// Applies from the next request: idle and preconnected connections
+    /// are dropped.
+    pub fn set_proxy_resolver(&self, resolver: Option<Arc<dyn ProxyResolver>>) {
+        *self.proxy.write().unwrap() = resolver;
+        self.pool.lock().unwrap().close(|_| true);
+        self.warm.lock().unwrap().clear();
+        self.h2.lock().unwrap().clear();
+    }
+
+    /// Open a TCP connection for a request to `url` at `host:port`,
+    /// directly or through the first proxy that works. An `https`
+    /// connection through a proxy is tunnelled to `host:port`; for `http`
+    /// the flag says the connection goes to a proxy, so requests must name
+    /// the full URL.
+    async fn connect_route(
+        &self,
+        url: &Url,
+        host: &str,
+        port: u16,
+        site: Option<&str>,
+    ) -> Result<(TcpStream, bool), HttpError> {
+        let resolver = self.proxy.read().unwrap().clone();
+        let Some(resolver) = resolver else {
+            return Ok((self.connect_tcp(host, port, site).await?, false));
+        };
+        let mut proxies = resolver.proxies_for(url).await;
+        if proxies.is_empty() {
+            proxies.push(ProxyServer::Direct);
+        }
+
+        let mut last_error = None;
+        for proxy in proxies {
+            let attempt = match &proxy {
+                ProxyServer::Direct => self
+                    .connect_tcp(host, port, site)
+                    .await
+                    .map(|stream| (stream, false)),
+                ProxyServer::Http {
+                    host: proxy_host,
+                    port: proxy_port,
+                } => timeout(proxy::PROXY_CONNECT_TIMEOUT, async {
+                    let mut stream = self.connect_tcp(proxy_host, *proxy_port, None).await?;
+                    if url.scheme() == "https" {
+                        proxy::tunnel(&mut stream, host, port, &self.config.user_agent).await?;
+                        return Ok((stream, false));
+                    }
+                    Ok((stream, true))
+                })
+                .await
+                .unwrap_or(Err(HttpError::Timeout)),
+            };
+            match attempt {
+                Ok(connection) => {
+                    trace!(host, %proxy, "Connected");
+                    return Ok(connection);
+                }
+                Err(e) => {
+                    debug!(host, %proxy, error = %e, "Route failed");
+                    if proxy != ProxyServer::Direct {
+                        resolver.report_failure(&proxy);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| HttpError::ConnectionFailed(host.to_string())))
+    }
+
+    /// Open a connection to the origin of `url`, including the TLS
+    /// handshake for `https`, and hold it for the next request to the
+    /// origin in the same credentials partition, for at most `ttl`.
+    pub async fn preconnect(
+        &self,
+        url: &Url,
+        partition: Partition<'_>,
+        ttl: Duration,
+    ) -> Result<(), HttpError> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| HttpError::InvalidUrl("Missing host".to_string()))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| HttpError::UnsupportedScheme(url.scheme().to_string()))?;
+        if self.has_preconnected(url, partition) {
+            return Ok(());
+        }
+
+        let stream = timeout(self.config.timeout, async {
+            let (tcp, proxied) = self.connect_route(url, host, port, partition.site).await?;
+            match url.scheme() {
+                "https" => self
+                    .connector_for(host, port)
+                    .connect(host, tcp)
+                    .await
+                    .map(|tls| Some(PooledStream::Tls(tls)))
+                    .map_err(|e| HttpError::TlsError(e.to_string())),
+                // Warm connections carry origin-form requests only
+                "http" => Ok(Some(PooledStream::Plain(tcp)).filter(|_| !proxied)),
+                scheme => Err(HttpError::UnsupportedScheme(scheme.to_string())),
+            }
         })
+        .await
+        .map_err(|_| HttpError::Timeout)??;
+        let Some(stream) = stream else {
+            return Ok(());
+        };
+
+        debug!(host, port, anonymous = partition.anonymous, "Preconnected");
+        let now = Instant::now();
+        let mut warm = self.warm.lock().unwrap();
+        warm.retain(|c| c.expires > now);
+        warm.push(WarmConnection {
+            key: warm_key(url.scheme(), host, port, partition),
+            stream,
+            expires: now + ttl,
+        });
+        Ok(())
+    }
+
+    /// Whether a preconnected connection to the origin of `url` is waiting
+    /// in the given credentials partition.
+    pub fn has_preconnected(&self, url: &Url, partition: Partition<'_>) -> bool {
+        let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+            return false;
+        };
+        let key = warm_key(url.scheme(), host, port, partition);
+        let now = Instant::now();
+        self.warm
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|c| c.key == key && c.expires > now)
+    }
+
+    fn take_preconnected(
+        &self,
+        scheme: &str,
+        host: &str,
+        port: u16,
+        partition: Partition<'_>,
+    ) -> Option<PooledStream> {
+        let key = warm_key(scheme, host, port, partition);
+        let now = Instant::now();
+        let mut warm = self.warm.lock().unwrap();
+        warm.retain(|c| c.expires > now);
+        let index = warm.iter().position(|c| c.key == key)?;
+        Some(warm.swap_remove(index).stream)
+    }
+
+    /// Replace the extra trusted root certificates (DER-encoded).
+    pub fn set_root_certificates(&self, roots: Vec<Vec<u8>>) -> Result<(), HttpError> {
+        let config = self.tls_config();
+        self.replace_tls(TlsState::new(config, roots)?);
+        Ok(())
+    }
+
+    /// Current TLS settings.
+    pub fn tls_config(&self) -> TlsConfig {
+        self.tls.read().unwrap().config.clone()
+    }
+
+    /// Change the TLS settings.
+    ///
+    /// Applies to connections made from now on; idle TLS connections are
+    /// dropped.
+    pub fn set_tls_config(&self, config: TlsConfig) -> Result<(), HttpError> {
+        let roots = self.tls.read().unwrap().roots.clone();
+        self.replace_tls(TlsState::new(config, roots)?);
+        Ok(())
+    }
+
+    /// Offer only `http/1.1` via ALPN to `host:port`, or go back to the
+    /// configured protocols.
+    ///
+    /// Applies from the next request: the host's idle session and HTTP/2
+    /// connection are dropped so they cannot carry a protocol negotiated
+    /// before the change.
+    pub fn set_http1_only(&self, host: &str, port: u16, http1_only: bool) {
+        let addr = format!("{}:{}", host.to_ascii_lowercase(), port);
+        let origin = format!("https://{}", addr);
+        self.pool.lock().unwrap().close(|c| c.origin == origin);
+        self.h2.lock().unwrap().remove(&addr);
+        let mut hosts = self.http1_only.write().unwrap();
+        if http1_only {
+            hosts.insert(addr);
+        } else {
+            hosts.remove(&addr);
+        }
+    }
+
+    /// Whether `host:port` is pinned to HTTP/1.1.
+    pub fn is_http1_only(&self, host: &str, port: u16) -> bool {
+        let addr = format!("{}:{}", host.to_ascii_lowercase(), port);
+        self.http1_only.read().unwrap().contains(&addr)
+    }
+
+    /// The connector for new connections to `host:port`.
+    fn connector_for(&self, host: &str, port: u16) -> TlsConnector {
+        let http1_only = self.is_http1_only(host, port);
+        let tls = self.tls.read().unwrap();
+        if http1_only {
+            tls.http1_connector.clone()
+        } else {
+            tls.connector.clone()
+        }
+    }
+
+    fn replace_tls(&self, state: TlsState) {
+        *self.tls.write().unwrap() = state;
+        self.pool
+            .lock()
+            .unwrap()
+            .close(|c| matches!(c.stream, PooledStream::Tls(_)));
+        self.warm.lock().unwrap().clear();
+        self.h2.lock().unwrap().clear();
     }
 
     /// Create a client builder.
@@ -162,18 +760,82 @@ impl Client {
         url: &str,
         headers: HeaderMap,
         body: Option<Bytes>,
+    ) -> Result<Response, HttpError> {
+        self.request_pinned(method, url, headers, body, &[]).await
+    }
+
+    /// Perform an HTTP request, accepting the given certificates for their
+    /// hosts even if they fail validation.
+    pub async fn request_pinned(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+        pinned: &[PinnedCertificate],
+    ) -> Result<Response, HttpError> {
+        self.request_partitioned(method, url, headers, body, pinned, Partition::default())
+            .await
+    }
+
+    /// Like [`Self::request_pinned`], for a request sent with or without
+    /// credentials. Only connections preconnected for the same partition
+    /// are used.
+    pub async fn request_partitioned(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+        pinned: &[PinnedCertificate],
+        partition: Partition<'_>,
+    ) -> Result<Response, HttpError> {
+        let parsed_url = Url::parse(url).map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
+        self.request_url(
+            method, parsed_url, headers, body, pinned, partition, None, 0,
+        )
+        .await
+    }
+
+    /// Like [`Self::request_partitioned`], passing interim responses to
+    /// `on_interim` as they arrive. Their headers are not part of the
+    /// final response's.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn request_with_interim(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+        pinned: &[PinnedCertificate],
+        partition: Partition<'_>,
+        on_interim: &InterimHandler<'_>,
     ) -> Result<Response, HttpError> {
         let parsed_url = Url::parse(url).map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
-        self.request_url(method, parsed_url, headers, body, 0).await
+        self.request_url(
+            method,
+            parsed_url,
+            headers,
+            body,
+            pinned,
+            partition,
+            Some(on_interim),
+            0,
+        )
+        .await
     }
 
     /// Internal request implementation with redirect counting.
+    #[allow(clippy::too_many_arguments)]
     async fn request_url(
         &self,
         method: Method,
         url: Url,
         headers: HeaderMap,
         body: Option<Bytes>,
+        pinned: &[PinnedCertificate],
+        partition: Partition<'_>,
+        on_interim: Option<&InterimHandler<'_>>,
         redirect_count: usize,
     ) -> Result<Response, HttpError> {
         if redirect_count > self.config.max_redirects {
@@ -195,8 +857,18 @@ impl Client {
         // Connect with timeout
         let response = timeout(self.config.timeout, async {
             match scheme {
-                "https" => self.request_https(host, port, &method, &url, &headers, &body).await,
-                "http" => self.request_http(host, port, &method, &url, &headers, &body).await,
+                "https" => {
+                    self.request_https(
+                        host, port, &method, &url, &headers, &body, pinned, partition, on_interim,
+                    )
+                    .await
+                }
+                "http" => {
+                    self.request_http(
+                        host, port, &method, &url, &headers, &body, partition, on_interim,
+                    )
+                    .await
+                }
                 _ => Err(HttpError::UnsupportedScheme(scheme.to_string())),
             }
         })
@@ -210,8 +882,17 @@ impl Client {
                     .join(location)
                     .map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
                 debug!(from = %url, to = %redirect_url, "Following redirect");
-                return Box::pin(self.request_url(Method::GET, redirect_url, HeaderMap::new(), None, redirect_count + 1))
-                    .await;
+                return Box::pin(self.request_url(
+                    Method::GET,
+                    redirect_url,
+                    HeaderMap::new(),
+                    None,
+                    pinned,
+                    partition,
+                    on_interim,
+                    redirect_count + 1,
+                ))
+                .await;
             }
         }
 
@@ -221,10 +902,13 @@ impl Client {
             headers: response.headers,
             body: response.body,
             url,
+            certificate_pinned: response.certificate_pinned,
+            tls: response.tls,
         })
     }
 
     /// HTTPS request.
+    #[allow(clippy::too_many_arguments)]
     async fn request_https(
         &self,
         host: &str,
@@ -233,23 +917,228 @@ impl Client {
         url: &Url,
         headers: &HeaderMap,
         body: &Option<Bytes>,
+        pinned: &[PinnedCertificate],
+        partition: Partition<'_>,
+        on_interim: Option<&InterimHandler<'_>>,
     ) -> Result<RawResponse, HttpError> {
         let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr)
-            .await
-            .map_err(|e| HttpError::ConnectionFailed(e.to_string()))?;
+        let keep_alive = self.tls.read().unwrap().config.session_resumption;
+
+        // Open a new stream on an HTTP/2 connection to the host
+        let h2 = self.h2.lock().unwrap().get(&addr).cloned();
+        if let Some(connection) = h2 {
+            match connection
+                .send(
+                    method,
+                    url,
+                    headers,
+                    &self.config.user_agent,
+                    body,
+                    self.push_context(url, partition),
+                )
+                .await
+            {
+                Ok(mut response) => {
+                    trace!(host, "Reused HTTP/2 connection");
+                    response.tls = Some(connection.tls.reused());
+                    return Ok(response);
+                }
+                // Only a request the server never saw may be sent again
+                Err(HttpError::ConnectionClosed(e)) => {
+                    debug!(host, error = %e, "HTTP/2 connection closed; retrying on a new one");
+                    self.h2.lock().unwrap().remove(&addr);
+                }
+                Err(e) => {
+                    self.h2.lock().unwrap().remove(&addr);
+                    return Err(e);
+                }
+            }
+        }
 
-        let tls_stream = self
-            .tls_connector
-            .connect(host, stream)
+        let origin = format!("https://{}", addr);
+        let _lease = Lease::new(&self.pool, &origin);
+        if let Some(response) = self
+            .send_pooled(&origin, host, method, url, headers, body, on_interim)
             .await
-            .map_err(|e| HttpError::TlsError(e.to_string()))?;
+        {
+            return response;
+        }
 
-        self.send_request(tls_stream, host, method, url, headers, body)
-            .await
+        if let Some(PooledStream::Tls(mut tls_stream)) =
+            self.take_preconnected("https", host, port, partition)
+        {
+            let tls = TlsInfo::new(&tls_stream, &self.tls.read().unwrap().config);
+            if tls.alpn_protocol.as_deref() == Some("h2") {
+                trace!(host, "Using preconnected HTTP/2 connection");
+                let mut response = self
+                    .request_h2(
+                        &addr,
+                        tls_stream,
+                        tls.clone(),
+                        keep_alive,
+                        method,
+                        url,
+                        headers,
+                        body,
+                        partition,
+                    )
+                    .await?;
+                response.tls = Some(tls.reused());
+                return Ok(response);
+            }
+            match self
+                .send_request(
+                    &mut tls_stream,
+                    host,
+                    method,
+                    url,
+                    headers,
+                    body,
+                    keep_alive,
+                    on_interim,
+                )
+                .await
+            {
+                Ok((mut response, reusable)) => {
+                    trace!(host, "Used preconnected TLS connection");
+                    response.tls = Some(tls.reused());
+                    if reusable {
+                        self.pool_connection(PooledConnection::new(
+                            origin,
+                            PooledStream::Tls(tls_stream),
+                            Some(tls),
+                        ));
+                    }
+                    return Ok(response);
+                }
+                Err(HttpError::ConnectionClosed(e)) => {
+                    debug!(host, error = %e, "Preconnected TLS connection closed; retrying on a new one");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let (stream, _) = self.connect_route(url, host, port, partition.site).await?;
+
+        let connector = self.connector_for(host, port);
+        let error = match connector.connect(host, stream).await {
+            Ok(mut tls_stream) => {
+                let tls = TlsInfo::new(&tls_stream, &self.tls.read().unwrap().config);
+                if tls.alpn_protocol.as_deref() == Some("h2") {
+                    let mut response = self
+                        .request_h2(
+                            &addr,
+                            tls_stream,
+                            tls.clone(),
+                            keep_alive,
+                            method,
+                            url,
+                            headers,
+                            body,
+                            partition,
+                        )
+                        .await?;
+                    response.tls = Some(tls);
+                    return Ok(response);
+                }
+                let (mut response, reusable) = self
+                    .send_request(
+                        &mut tls_stream,
+                        host,
+                        method,
+                        url,
+                        headers,
+                        body,
+                        keep_alive,
+                        on_interim,
+                    )
+                    .await?;
+                response.tls = Some(tls.clone());
+                if reusable {
+                    self.pool_connection(PooledConnection::new(
+                        origin,
+                        PooledStream::Tls(tls_stream),
+                        Some(tls),
+                    ));
+                }
+                return Ok(response);
+            }
+            Err(e) => e.to_string(),
+        };
+
+        // Handshake again without validation to see what the server presented.
+        // If that fails too, the problem wasn't the certificate.
+        let (stream, _) = self.connect_route(url, host, port, partition.site).await?;
+        let connector = self.tls.read().unwrap().unverified_connector.clone();
+        let Ok(mut tls_stream) = connector.connect(host, stream).await else {
+            return Err(HttpError::TlsError(error));
+        };
+
+        // native-tls only exposes the leaf certificate
+        let cert_chain_der: Vec<Vec<u8>> = tls_stream
+            .get_ref()
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .and_then(|cert| cert.to_der().ok())
+            .into_iter()
+            .collect();
+
+        let accepted = cert_chain_der.first().is_some_and(|leaf| {
+            let fingerprint = certificate_fingerprint(leaf);
+            pinned
+                .iter()
+                .any(|p| p.host.eq_ignore_ascii_case(host) && p.sha256 == fingerprint)
+        });
+
+        if accepted {
+            debug!(host, "Accepting pinned certificate");
+            let tls = TlsInfo::new(&tls_stream, &self.tls.read().unwrap().config);
+            if tls.alpn_protocol.as_deref() == Some("h2") {
+                let mut response = self
+                    .request_h2(
+                        &addr,
+                        tls_stream,
+                        tls.clone(),
+                        false,
+                        method,
+                        url,
+                        headers,
+                        body,
+                        partition,
+                    )
+                    .await?;
+                response.certificate_pinned = true;
+                response.tls = Some(tls);
+                return Ok(response);
+            }
+            let (mut response, _) = self
+                .send_request(
+                    &mut tls_stream,
+                    host,
+                    method,
+                    url,
+                    headers,
+                    body,
+                    false,
+                    on_interim,
+                )
+                .await?;
+            response.certificate_pinned = true;
+            response.tls = Some(tls);
+            return Ok(response);
+        }
+
+        Err(HttpError::CertificateError {
+            host: host.to_string(),
+            kind: CertificateErrorKind::from_message(&error),
+            cert_chain_der,
+            message: error,
+        })
     }
 
     /// HTTP request.
+    #[allow(clippy::too_many_arguments)]
     async fn request_http(
         &self,
         host: &str,
@@ -258,34 +1147,330 @@ impl Client {
         url: &Url,
         headers: &HeaderMap,
         body: &Option<Bytes>,
+        partition: Partition<'_>,
+        on_interim: Option<&InterimHandler<'_>>,
     ) -> Result<RawResponse, HttpError> {
-        let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr)
+        let origin = format!("http://{}:{}", host, port);
+        let _lease = Lease::new(&self.pool, &origin);
+        if let Some(response) = self
+            .send_pooled(&origin, host, method, url, headers, body, on_interim)
             .await
-            .map_err(|e| HttpError::ConnectionFailed(e.to_string()))?;
+        {
+            return response;
+        }
 
-        self.send_request(stream, host, method, url, headers, body)
+        if let Some(mut stream) = self.take_preconnected("http", host, port, partition) {
+            match self
+                .send_request(
+                    &mut stream,
+                    host,
+                    method,
+                    url,
+                    headers,
+                    body,
+                    true,
+                    on_interim,
+                )
+                .await
+            {
+                Ok((response, reusable)) => {
+                    trace!(host, "Used preconnected connection");
+                    if reusable {
+                        self.pool_connection(PooledConnection::new(origin, stream, None));
+                    }
+                    return Ok(response);
+                }
+                Err(HttpError::ConnectionClosed(e)) => {
+                    debug!(host, error = %e, "Preconnected connection closed; retrying on a new one");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Connections to a proxy aren't kept, as requests on them name
+        // the full URL
+        let (stream, proxied) = self.connect_route(url, host, port, partition.site).await?;
+        let mut stream = PooledStream::Plain(stream);
+        let (response, reusable) = self
+            .send_request_to(
+                &mut stream,
+                host,
+                method,
+                url,
+                headers,
+                body,
+                !proxied,
+                proxied,
+                on_interim,
+            )
+            .await?;
+        if reusable {
+            self.pool_connection(PooledConnection::new(origin, stream, None));
+        }
+        Ok(response)
+    }
+
+    /// Start HTTP/2 on a connection that negotiated `h2` and send the
+    /// request on it, with `tls` describing the connection. With
+    /// `keep_alive`, later requests to `addr` share the connection.
+    #[allow(clippy::too_many_arguments)]
+    async fn request_h2(
+        &self,
+        addr: &str,
+        stream: TlsStream<TcpStream>,
+        tls: TlsInfo,
+        keep_alive: bool,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &Option<Bytes>,
+        partition: Partition<'_>,
+    ) -> Result<RawResponse, HttpError> {
+        let connection =
+            H2Connection::handshake(stream, tls, self.config.push_handler.is_some()).await?;
+        if keep_alive {
+            self.h2
+                .lock()
+                .unwrap()
+                .insert(addr.to_string(), connection.clone());
+        }
+        connection
+            .send(
+                method,
+                url,
+                headers,
+                &self.config.user_agent,
+                body,
+                self.push_context(url, partition),
+            )
             .await
     }
 
+    /// Where pushes that come with the request for `url` go.
+    fn push_context(&self, url: &Url, partition: Partition<'_>) -> PushContext {
+        PushContext {
+            handler: self.config.push_handler.clone(),
+            limit: self.config.max_concurrent_pushes,
+            initiator: url.clone(),
+            site: partition.site.map(str::to_string),
+        }
+    }
+
+    /// Send a request on an idle connection to `origin`. `None` when
+    /// there is none, or when the server closed it before the response
+    /// started, so the request can go on a new connection whatever its
+    /// method.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_pooled(
+        &self,
+        origin: &str,
+        host: &str,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &Option<Bytes>,
+        on_interim: Option<&InterimHandler<'_>>,
+    ) -> Option<Result<RawResponse, HttpError>> {
+        let mut connection = {
+            let mut pool = self.pool.lock().unwrap();
+            pool.take(origin, &self.config.pool)?
+        };
+        let sent = self
+            .send_request(
+                &mut connection.stream,
+                host,
+                method,
+                url,
+                headers,
+                body,
+                true,
+                on_interim,
+            )
+            .await;
+        match sent {
+            Ok((mut response, reusable)) => {
+                trace!(origin, "Reused idle connection");
+                response.tls = connection.tls.as_ref().map(TlsInfo::reused);
+                if reusable {
+                    self.pool_connection(connection);
+                }
+                Some(Ok(response))
+            }
+            Err(HttpError::ConnectionClosed(e)) => {
+                debug!(origin, error = %e, "Idle connection closed; retrying on a new one");
+                self.pool.lock().unwrap().count_retry();
+                None
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Keep an idle connection for later requests to the same origin.
+    fn pool_connection(&self, connection: PooledConnection) {
+        let capacity = match connection.stream {
+            PooledStream::Tls(_) => {
+                let tls = self.tls.read().unwrap();
+                if !tls.config.session_resumption {
+                    return;
+                }
+                tls.config.session_cache_size
+            }
+            PooledStream::Plain(_) => self.config.pool.max_idle,
+        };
+        self.pool
+            .lock()
+            .unwrap()
+            .put(connection, &self.config.pool, capacity);
+    }
+
+    /// Connections open and idle by origin, with the pool's counters.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.lock().unwrap().stats(&self.config.pool)
+    }
+
+    /// Reset the counters of [`Self::pool_stats`].
+    pub fn reset_pool_stats(&self) {
+        self.pool.lock().unwrap().reset_stats();
+    }
+
+    /// Close every idle connection, e.g. to free memory. Returns how many
+    /// were closed.
+    pub fn close_idle_connections(&self) -> usize {
+        self.pool.lock().unwrap().close(|_| true)
+    }
+
     /// Send HTTP request and read response.
+    ///
+    /// With `keep_alive`, also returns whether the connection can carry
+    /// another request.
+    #[allow(clippy::too_many_arguments)]
     async fn send_request<S>(
         &self,
-        stream: S,
+        stream: &mut S,
+        host: &str,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &Option<Bytes>,
+        keep_alive: bool,
+        on_interim: Option<&InterimHandler<'_>>,
+    ) -> Result<(RawResponse, bool), HttpError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        self.send_request_to(
+            stream, host, method, url, headers, body, keep_alive, false, on_interim,
+        )
+        .await
+    }
+
+    /// Like [`Self::send_request`]; with `absolute_form`, the request
+    /// target is the full URL, as a proxy expects.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_request_to<S>(
+        &self,
+        stream: &mut S,
         host: &str,
         method: &Method,
         url: &Url,
         headers: &HeaderMap,
         body: &Option<Bytes>,
-    ) -> Result<RawResponse, HttpError>
+        keep_alive: bool,
+        absolute_form: bool,
+        on_interim: Option<&InterimHandler<'_>>,
+    ) -> Result<(RawResponse, bool), HttpError>
     where
         S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
     {
-        let (reader, mut writer) = tokio::io::split(stream);
-        let mut reader = BufReader::new(reader);
+        let request = self.request_head(
+            host,
+            method,
+            url,
+            headers,
+            body.as_ref().map(Bytes::len),
+            keep_alive,
+            absolute_form,
+        )?;
+
+        // A connection that fails before the response starts never
+        // processed the request
+        let sent = async {
+            stream.write_all(&request).await?;
+            if let Some(b) = body {
+                stream.write_all(b).await?;
+            }
+            stream.flush().await
+        }
+        .await;
+        if let Err(e) = sent {
+            return Err(HttpError::ConnectionClosed(e.to_string()));
+        }
+
+        let mut reader = BufReader::new(stream);
+        match reader.fill_buf().await {
+            Ok([]) => return Err(HttpError::ConnectionClosed("end of stream".to_string())),
+            Err(e) => return Err(HttpError::ConnectionClosed(e.to_string())),
+            Ok(_) => {}
+        }
+        let (version, status, response_headers) = read_final_head(&mut reader, on_interim).await?;
 
-        // Build request
-        let path = if let Some(query) = url.query() {
+        // Responses to HEAD, and 204 and 304 responses, end with their head
+        let bodiless = *method == Method::HEAD
+            || matches!(status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED);
+        let body = if bodiless {
+            Bytes::new()
+        } else {
+            read_body(&mut reader, &response_headers).await?
+        };
+
+        trace!(status = %status, body_len = body.len(), "Response received");
+
+        // Only a delimited body leaves the connection at a request boundary
+        let header = |name: &str| {
+            response_headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_ascii_lowercase)
+        };
+        let delimited = bodiless
+            || header("content-length").is_some()
+            || header("transfer-encoding").is_some_and(|te| te.contains("chunked"));
+        let reusable = keep_alive
+            && version == Version::HTTP_11
+            && delimited
+            && !header("connection").is_some_and(|c| c.contains("close"))
+            && reader.buffer().is_empty();
+
+        Ok((
+            RawResponse {
+                status,
+                version,
+                headers: response_headers,
+                body,
+                certificate_pinned: false,
+                tls: None,
+            },
+            reusable,
+        ))
+    }
+
+    /// Serialize the request line and headers.
+    #[allow(clippy::too_many_arguments)]
+    fn request_head(
+        &self,
+        host: &str,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        content_length: Option<usize>,
+        keep_alive: bool,
+        absolute_form: bool,
+    ) -> io::Result<Vec<u8>> {
+        let path = if absolute_form {
+            let mut target = url.clone();
+            target.set_fragment(None);
+            target.to_string()
+        } else if let Some(query) = url.query() {
             format!("{}?{}", url.path(), query)
         } else {
             url.path().to_string()
@@ -297,7 +1482,11 @@ impl Client {
         writeln!(request, "Host: {}\r", host)?;
         writeln!(request, "User-Agent: {}\r", self.config.user_agent)?;
         writeln!(request, "Accept: */*\r")?;
-        writeln!(request, "Connection: close\r")?;
+        if keep_alive {
+            writeln!(request, "Connection: keep-alive\r")?;
+        } else {
+            writeln!(request, "Connection: close\r")?;
+        }
 
         // Add custom headers
         for (name, value) in headers.iter() {
@@ -307,59 +1496,12 @@ impl Client {
         }
 
         // Content-Length for body
-        if let Some(b) = body {
-            writeln!(request, "Content-Length: {}\r", b.len())?;
+        if let Some(len) = content_length {
+            writeln!(request, "Content-Length: {}\r", len)?;
         }
 
         writeln!(request, "\r")?;
-
-        // Send headers
-        writer.write_all(&request).await?;
-
-        // Send body
-        if let Some(b) = body {
-            writer.write_all(b).await?;
-        }
-
-        writer.flush().await?;
-
-        // Read response status line
-        let mut status_line = String::new();
-        reader.read_line(&mut status_line).await?;
-
-        let (version, status) = parse_status_line(&status_line)?;
-
-        // Read headers
-        let mut response_headers = HeaderMap::new();
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            let line = line.trim();
-            if line.is_empty() {
-                break;
-            }
-
-            if let Some((name, value)) = line.split_once(':') {
-                if let (Ok(n), Ok(v)) = (
-                    HeaderName::try_from(name.trim()),
-                    HeaderValue::try_from(value.trim()),
-                ) {
-                    response_headers.insert(n, v);
-                }
-            }
-        }
-
-        // Read body
-        let body = read_body(&mut reader, &response_headers).await?;
-
-        trace!(status = %status, body_len = body.len(), "Response received");
-
-        Ok(RawResponse {
-            status,
-            version,
-            headers: response_headers,
-            body,
-        })
+        Ok(request)
     }
 }
 
@@ -375,6 +1517,8 @@ struct RawResponse {
     version: Version,
     headers: HeaderMap,
     body: Bytes,
+    certificate_pinned: bool,
+    tls: Option<TlsInfo>,
 }
 
 impl RawResponse {
@@ -416,6 +1560,32 @@ impl ClientBuilder {
         self
     }
 
+    /// Trust an additional DER-encoded root certificate.
+    pub fn add_root_certificate(mut self, der: Vec<u8>) -> Self {
+        self.config.root_certificates.push(der);
+        self
+    }
+
+    /// Set TLS settings.
+    pub fn tls_config(mut self, config: TlsConfig) -> Self {
+        self.config.tls = config;
+        self
+    }
+
+    /// Receive HTTP/2 server pushes with `handler`, at most `max_concurrent`
+    /// at once per connection.
+    pub fn push_handler(mut self, handler: Arc<dyn PushHandler>, max_concurrent: usize) -> Self {
+        self.config.push_handler = Some(handler);
+        self.config.max_concurrent_pushes = max_concurrent;
+        self
+    }
+
+    /// Set how keep-alive connections are kept.
+    pub fn pool_config(mut self, config: PoolConfig) -> Self {
+        self.config.pool = config;
+        self
+    }
+
     /// Placeholder for cookie_store (not implemented in minimal client).
     pub fn cookie_store(self, _enabled: bool) -> Self {
         // Cookie support would require additional implementation
@@ -434,6 +1604,39 @@ impl Default for ClientBuilder {
     }
 }
 
+/// Build a TLS connector for `config` that also trusts `roots`, or that
+/// skips validation entirely if `unverified`.
+fn build_tls_connector(
+    config: &TlsConfig,
+    roots: &[Vec<u8>],
+    unverified: bool,
+) -> Result<TlsConnector, HttpError> {
+    let mut builder = NativeTlsConnector::builder();
+    builder.min_protocol_version(Some(config.min_version.protocol()));
+    let alpn: Vec<&str> = config.alpn_protocols.iter().map(String::as_str).collect();
+    builder.request_alpns(&alpn);
+    if unverified {
+        builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+    for der in roots {
+        let cert = Certificate::from_der(der).map_err(|e| HttpError::TlsError(e.to_string()))?;
+        builder.add_root_certificate(cert);
+    }
+
+    let connector = builder
+        .build()
+        .map_err(|e| HttpError::TlsError(e.to_string()))?;
+    Ok(TlsConnector::from(connector))
+}
+
+/// The protocol the server selected via ALPN.
+fn negotiated_alpn(stream: &TlsStream<TcpStream>) -> Option<String> {
+    let protocol = stream.get_ref().negotiated_alpn().ok().flatten()?;
+    String::from_utf8(protocol).ok()
+}
+
 /// Parse HTTP status line.
 fn parse_status_line(line: &str) -> Result<(Version, StatusCode), HttpError> {
     let parts: Vec<&str> = line.split_whitespace().collect();
@@ -458,6 +1661,58 @@ fn parse_status_line(line: &str) -> Result<(Version, StatusCode), HttpError> {
     Ok((version, status))
 }
 
+/// Read the status line and headers of a response.
+async fn read_response_head<R: tokio::io::AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<(Version, StatusCode, HeaderMap), HttpError> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).await?;
+    let (version, status) = parse_status_line(&status_line)?;
+
+    let mut headers = HeaderMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(HttpError::InvalidResponse(
+                "Connection closed in response head".to_string(),
+            ));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if let (Ok(n), Ok(v)) = (
+                HeaderName::try_from(name.trim()),
+                HeaderValue::try_from(value.trim()),
+            ) {
+                headers.append(n, v);
+            }
+        }
+    }
+
+    Ok((version, status, headers))
+}
+
+/// Read response heads up to the final one, passing interim (1xx) ones
+/// other than `101 Switching Protocols` to `on_interim`.
+async fn read_final_head<R: tokio::io::AsyncBufRead + Unpin>(
+    reader: &mut R,
+    on_interim: Option<&InterimHandler<'_>>,
+) -> Result<(Version, StatusCode, HeaderMap), HttpError> {
+    loop {
+        let (version, status, headers) = read_response_head(reader).await?;
+        if !status.is_informational() || status == StatusCode::SWITCHING_PROTOCOLS {
+            return Ok((version, status, headers));
+        }
+        trace!(status = %status, "Interim response");
+        if let Some(on_interim) = on_interim {
+            on_interim(status, &headers);
+        }
+    }
+}
+
 /// Read response body based on headers.
 async fn read_body<R: tokio::io::AsyncBufRead + Unpin>(
     reader: &mut R,
@@ -519,7 +1774,43 @@ async fn read_chunked_body<R: tokio::io::AsyncBufRead + Unpin>(
     Ok(Bytes::from(body))
 }
 
-/// Streaming response for downloads.
+/// How the end of a streamed body is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFraming {
+    /// `Content-Length` bytes remain.
+    Length(u64),
+    /// Bytes left in the current chunk; 0 before a chunk-size line.
+    Chunked(usize),
+    /// Runs until the server closes the connection.
+    UntilClose,
+    /// The terminating chunk has been read.
+    Done,
+}
+
+impl BodyFraming {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let chunked = headers
+            .get("transfer-encoding")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+        let length = headers
+            .get("content-length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.trim().parse().ok());
+        match (chunked, length) {
+            (true, _) => Self::Chunked(0),
+            (false, Some(length)) => Self::Length(length),
+            (false, None) => Self::UntilClose,
+        }
+    }
+}
+
+/// A response whose body is read as it arrives, for downloads and for
+/// bodies that never end, like long polls and `multipart/x-mixed-replace`.
+///
+/// Chunked transfer encoding is decoded. The client timeout applies to
+/// each read rather than the whole body. Dropping the response closes the
+/// connection.
 pub struct StreamingResponse {
     /// HTTP status code.
     pub status: StatusCode,
@@ -527,136 +1818,258 @@ pub struct StreamingResponse {
     pub headers: HeaderMap,
     /// Content length if known.
     pub content_length: Option<u64>,
+    /// Final URL after redirects.
+    pub url: Url,
+    framing: BodyFraming,
+    read_timeout: Duration,
     /// The underlying stream reader.
-    reader: Box<dyn tokio::io::AsyncRead + Send + Unpin>,
+    reader: Box<dyn tokio::io::AsyncBufRead + Send + Unpin>,
 }
 
 impl StreamingResponse {
-    /// Read a chunk of data.
+    /// Read a chunk of data, returning 0 at the end of the body.
     pub async fn chunk(&mut self, buf: &mut [u8]) -> Result<usize, HttpError> {
-        use tokio::io::AsyncReadExt;
-        let n = self.reader.read(buf).await?;
-        Ok(n)
+        timeout(self.read_timeout, self.read_decoded(buf))
+            .await
+            .map_err(|_| HttpError::Timeout)?
+    }
+
+    /// Read the next piece of the body as it arrives, or `None` at its end.
+    pub async fn next_chunk(&mut self) -> Result<Option<Bytes>, HttpError> {
+        let mut buf = vec![0u8; 16 * 1024];
+        let n = self.chunk(&mut buf).await?;
+        buf.truncate(n);
+        Ok((n > 0).then(|| Bytes::from(buf)))
+    }
+
+    /// How long a read may wait for data before failing with
+    /// [`HttpError::Timeout`].
+    pub fn set_read_timeout(&mut self, read_timeout: Duration) {
+        self.read_timeout = read_timeout;
+    }
+
+    /// Get the `Content-Type` header.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+    }
+
+    async fn read_decoded(&mut self, buf: &mut [u8]) -> Result<usize, HttpError> {
+        let truncated = || HttpError::InvalidResponse("Connection closed mid-body".to_string());
+        loop {
+            match &mut self.framing {
+                BodyFraming::Done | BodyFraming::Length(0) => return Ok(0),
+                BodyFraming::UntilClose => return Ok(self.reader.read(buf).await?),
+                BodyFraming::Length(remaining) => {
+                    let max = (*remaining).min(buf.len() as u64) as usize;
+                    let n = self.reader.read(&mut buf[..max]).await?;
+                    if n == 0 {
+                        return Err(truncated());
+                    }
+                    *remaining -= n as u64;
+                    return Ok(n);
+                }
+                BodyFraming::Chunked(0) => {
+                    let mut size_line = String::new();
+                    if self.reader.read_line(&mut size_line).await? == 0 {
+                        return Err(truncated());
+                    }
+                    // Ignore chunk extensions
+                    let size = size_line.split(';').next().unwrap_or_default().trim();
+                    let size = usize::from_str_radix(size, 16).map_err(|_| {
+                        HttpError::InvalidResponse("Invalid chunk size".to_string())
+                    })?;
+                    if size > 0 {
+                        self.framing = BodyFraming::Chunked(size);
+                        continue;
+                    }
+                    // Skip trailers up to the final empty line
+                    loop {
+                        let mut line = String::new();
+                        if self.reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    self.framing = BodyFraming::Done;
+                    return Ok(0);
+                }
+                BodyFraming::Chunked(remaining) => {
+                    let max = (*remaining).min(buf.len());
+                    let n = self.reader.read(&mut buf[..max]).await?;
+                    if n == 0 {
+                        return Err(truncated());
+                    }
+                    *remaining -= n;
+                    if *remaining == 0 {
+                        // CRLF after the chunk data
+                        let mut crlf = String::new();
+                        self.reader.read_line(&mut crlf).await?;
+                    }
+                    return Ok(n);
+                }
+            }
+        }
     }
 }
 
-/// Client extension for streaming downloads.
+/// Client extension for streaming responses.
 impl Client {
     /// Start a streaming GET request (for downloads).
     pub async fn get_streaming(&self, url: &str) -> Result<StreamingResponse, HttpError> {
-        let parsed_url = Url::parse(url).map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
-
-        let scheme = parsed_url.scheme();
-        let host = parsed_url
-            .host_str()
-            .ok_or_else(|| HttpError::InvalidUrl("Missing host".to_string()))?;
-        let port = parsed_url.port_or_known_default().unwrap_or(if scheme == "https" {
-            443
-        } else {
-            80
-        });
-
-        match scheme {
-            "https" => self.streaming_https(host, port, &parsed_url).await,
-            "http" => self.streaming_http(host, port, &parsed_url).await,
-            _ => Err(HttpError::UnsupportedScheme(scheme.to_string())),
-        }
+        self.request_streaming(
+            Method::GET,
+            url,
+            HeaderMap::new(),
+            None,
+            Partition::default(),
+        )
+        .await
     }
 
-    async fn streaming_https(
+    /// Start a request whose body is read as it arrives.
+    ///
+    /// The client timeout covers connecting and reading the response
+    /// headers; after that it applies to each read of the body.
+    pub async fn request_streaming(
         &self,
-        host: &str,
-        port: u16,
-        url: &Url,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+        partition: Partition<'_>,
     ) -> Result<StreamingResponse, HttpError> {
-        let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr)
+        self.request_streaming_with_interim(method, url, headers, body, partition, None)
             .await
-            .map_err(|e| HttpError::ConnectionFailed(e.to_string()))?;
+    }
 
-        let tls_stream = self
-            .tls_connector
-            .connect(host, stream)
+    /// Like [`Self::request_streaming`], reporting the informational
+    /// responses that arrive before the final one to `on_interim`.
+    pub async fn request_streaming_with_interim(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+        partition: Partition<'_>,
+        on_interim: Option<&InterimHandler<'_>>,
+    ) -> Result<StreamingResponse, HttpError> {
+        let mut url = Url::parse(url).map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
+        let (mut method, mut headers, mut body) = (method, headers, body);
+        for _ in 0..=self.config.max_redirects {
+            let response = timeout(
+                self.config.timeout,
+                self.open_streaming(&method, &url, &headers, &body, partition, on_interim),
+            )
             .await
-            .map_err(|e| HttpError::TlsError(e.to_string()))?;
-
-        self.send_streaming_request(tls_stream, host, url).await
+            .map_err(|_| HttpError::Timeout)??;
+
+            let location = response
+                .headers
+                .get("location")
+                .and_then(|v| v.to_str().ok())
+                .filter(|_| self.config.follow_redirects && response.status.is_redirection());
+            let Some(location) = location else {
+                return Ok(response);
+            };
+            let redirect_url = url
+                .join(location)
+                .map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
+            debug!(from = %url, to = %redirect_url, "Following redirect");
+            url = redirect_url;
+            (method, headers, body) = (Method::GET, HeaderMap::new(), None);
+        }
+        Err(HttpError::TooManyRedirects)
     }
 
-    async fn streaming_http(
+    async fn open_streaming(
         &self,
-        host: &str,
-        port: u16,
+        method: &Method,
         url: &Url,
+        headers: &HeaderMap,
+        body: &Option<Bytes>,
+        partition: Partition<'_>,
+        on_interim: Option<&InterimHandler<'_>>,
     ) -> Result<StreamingResponse, HttpError> {
-        let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr)
-            .await
-            .map_err(|e| HttpError::ConnectionFailed(e.to_string()))?;
+        let scheme = url.scheme();
+        let host = url
+            .host_str()
+            .ok_or_else(|| HttpError::InvalidUrl("Missing host".to_string()))?;
+        let port = url
+            .port_or_known_default()
+            .unwrap_or(if scheme == "https" { 443 } else { 80 });
 
-        self.send_streaming_request(stream, host, url).await
+        let connect = || self.connect_route(url, host, port, partition.site);
+        match scheme {
+            "https" => {
+                let (stream, _) = connect().await?;
+                let tls_stream = self
+                    .connector_for(host, port)
+                    .connect(host, stream)
+                    .await
+                    .map_err(|e| HttpError::TlsError(e.to_string()))?;
+                self.send_streaming_request(
+                    tls_stream, host, method, url, headers, body, false, on_interim,
+                )
+                .await
+            }
+            "http" => {
+                let (stream, proxied) = connect().await?;
+                self.send_streaming_request(
+                    stream, host, method, url, headers, body, proxied, on_interim,
+                )
+                .await
+            }
+            _ => Err(HttpError::UnsupportedScheme(scheme.to_string())),
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn send_streaming_request<S>(
         &self,
         mut stream: S,
         host: &str,
+        method: &Method,
         url: &Url,
+        headers: &HeaderMap,
+        body: &Option<Bytes>,
+        absolute_form: bool,
+        on_interim: Option<&InterimHandler<'_>>,
     ) -> Result<StreamingResponse, HttpError>
     where
         S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
     {
-        // Build and send request
-        let path = if let Some(query) = url.query() {
-            format!("{}?{}", url.path(), query)
-        } else {
-            url.path().to_string()
-        };
-        let path = if path.is_empty() { "/" } else { &path };
-
-        let request = format!(
-            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
-            path, host, self.config.user_agent
-        );
-
-        stream.write_all(request.as_bytes()).await?;
+        let request = self.request_head(
+            host,
+            method,
+            url,
+            headers,
+            body.as_ref().map(Bytes::len),
+            false,
+            absolute_form,
+        )?;
+        stream.write_all(&request).await?;
+        if let Some(b) = body {
+            stream.write_all(b).await?;
+        }
         stream.flush().await?;
 
-        // Read status and headers
         let mut reader = BufReader::new(stream);
+        let (_, status, headers) = read_final_head(&mut reader, on_interim).await?;
 
-        let mut status_line = String::new();
-        reader.read_line(&mut status_line).await?;
-        let (_, status) = parse_status_line(&status_line)?;
-
-        let mut headers = HeaderMap::new();
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            let line = line.trim();
-            if line.is_empty() {
-                break;
-            }
-
-            if let Some((name, value)) = line.split_once(':') {
-                if let (Ok(n), Ok(v)) = (
-                    HeaderName::try_from(name.trim()),
-                    HeaderValue::try_from(value.trim()),
-                ) {
-                    headers.insert(n, v);
-                }
-            }
-        }
-
-        let content_length = headers
-            .get("content-length")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.parse().ok());
+        let framing = BodyFraming::from_headers(&headers);
+        let content_length = match framing {
+            BodyFraming::Length(length) => Some(length),
+            _ => None,
+        };
 
         Ok(StreamingResponse {
             status,
             headers,
             content_length,
+            url: url.clone(),
+            framing,
+            read_timeout: self.config.timeout,
             reader: Box::new(reader),
         })
     }
@@ -758,6 +2171,8 @@ mod tests {
             headers,
             body: Bytes::from("Hello"),
             url: Url::parse("https://example.com").unwrap(),
+            certificate_pinned: false,
+            tls: None,
         };
 
         assert!(response.is_success());
@@ -766,6 +2181,37 @@ mod tests {
         assert_eq!(response.text().unwrap(), "Hello");
     }
 
+    #[test]
+    fn test_certificate_error_kind() {
+        let cases = [
+            (
+                "certificate verify failed: (self-signed certificate)",
+                CertificateErrorKind::UntrustedRoot,
+            ),
+            (
+                "A certificate chain processed, but terminated in a root certificate which is not trusted by the trust provider.",
+                CertificateErrorKind::UntrustedRoot,
+            ),
+            ("certificate verify failed: (certificate has expired)", CertificateErrorKind::Expired),
+            (
+                "The target principal name is incorrect.",
+                CertificateErrorKind::HostnameMismatch,
+            ),
+            ("certificate verify failed: (Hostname mismatch)", CertificateErrorKind::HostnameMismatch),
+            ("certificate verify failed: (certificate revoked)", CertificateErrorKind::Revoked),
+            ("unexpected eof while reading", CertificateErrorKind::Other),
+        ];
+
+        for (message, kind) in cases {
+            assert_eq!(
+                CertificateErrorKind::from_message(message),
+                kind,
+                "{}",
+                message
+            );
+        }
+    }
+
     #[test]
     fn test_default_config() {
         let config = ClientConfig2::default();
@@ -773,6 +2219,257 @@ mod tests {
         assert_eq!(config.timeout, Duration::from_secs(30));
         assert_eq!(config.max_redirects, 10);
         assert!(config.follow_redirects);
+        assert_eq!(config.tls.min_version, TlsVersion::Tls12);
+        assert_eq!(config.tls.alpn_protocols, vec!["http/1.1".to_string()]);
+        assert!(config.tls.session_resumption);
     }
-}
 
+    #[test]
+    fn test_set_tls_config() {
+        let client = Client::new().unwrap();
+        let config = TlsConfig {
+            min_version: TlsVersion::Tls13,
+            alpn_protocols: Vec::new(),
+            session_resumption: false,
+            session_cache_size: 0,
+            revocation: RevocationMode::Off,
+        };
+        client.set_tls_config(config.clone()).unwrap();
+        assert_eq!(client.tls_config(), config);
+    }
+
+    #[test]
+    fn test_revocation_checking_unsupported() {
+        let client = Client::new().unwrap();
+        for revocation in [RevocationMode::SoftFail, RevocationMode::HardFail] {
+            let result = client.set_tls_config(TlsConfig {
+                revocation,
+                ..Default::default()
+            });
+            assert!(matches!(result, Err(HttpError::UnsupportedTlsSetting(_))));
+        }
+        assert_eq!(client.tls_config(), TlsConfig::default());
+    }
+
+    #[test]
+    fn test_http1_only_hosts() {
+        let client = Client::new().unwrap();
+        client.set_http1_only("Example.com", 443, true);
+        assert!(client.is_http1_only("example.com", 443));
+        assert!(!client.is_http1_only("example.com", 8443));
+        assert!(!client.is_http1_only("example.org", 443));
+
+        client.set_http1_only("example.com", 443, false);
+        assert!(!client.is_http1_only("example.com", 443));
+    }
+
+    #[tokio::test]
+    async fn test_resolver_cache_is_partitioned_by_site() {
+        let client = Client::new().unwrap();
+        for site in [Some("https://a.example"), Some("https://a.example"), None] {
+            client.resolve("127.0.0.1", 80, site).await.unwrap();
+        }
+        assert_eq!(client.dns.lock().unwrap().len(), 2);
+        client
+            .resolve("127.0.0.1", 80, Some("https://b.example"))
+            .await
+            .unwrap();
+        assert_eq!(client.dns.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_timeout_applies_between_chunks() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .await
+                .unwrap();
+            // Longer in total than the timeout, but never idle for that long
+            for _ in 0..4 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                socket.write_all(b"3;ext=1\r\nabc\r\n").await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            let _ = socket.write_all(b"0\r\n\r\n").await;
+        });
+
+        let client = Client::builder()
+            .timeout(Duration::from_millis(300))
+            .build()
+            .unwrap();
+        let mut response = client
+            .get_streaming(&format!("http://127.0.0.1:{}/poll", port))
+            .await
+            .unwrap();
+        assert_eq!(response.content_length, None);
+        let mut body = Vec::new();
+        let error = loop {
+            match response.next_chunk().await {
+                Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                Ok(None) => panic!("stream ended"),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(body, b"abcabcabcabc");
+        assert!(matches!(error, HttpError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_interim_responses_precede_the_final_one() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(
+                    b"HTTP/1.1 103 Early Hints\r\nLink: </a.css>; rel=preload; as=style\r\n\
+                      Link: </b.js>; rel=preload; as=script\r\n\r\n\
+                      HTTP/1.1 103 Early Hints\r\nLink: </c.woff2>; rel=preload; as=font\r\n\r\n\
+                      HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone",
+                )
+                .await
+                .unwrap();
+        });
+
+        let interim = Mutex::new(Vec::new());
+        let on_interim = |status: StatusCode, headers: &HeaderMap| {
+            let links: Vec<String> = headers
+                .get_all("link")
+                .iter()
+                .map(|v| v.to_str().unwrap().to_string())
+                .collect();
+            interim.lock().unwrap().push((status.as_u16(), links.len()));
+        };
+        let response = Client::new()
+            .unwrap()
+            .request_with_interim(
+                Method::GET,
+                &format!("http://127.0.0.1:{}/", port),
+                HeaderMap::new(),
+                None,
+                &[],
+                Partition::default(),
+                &on_interim,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(&response.body[..], b"done");
+        assert!(response.headers.get("link").is_none());
+        assert_eq!(*interim.lock().unwrap(), [(103, 2), (103, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_response_ends_with_its_head() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // Both answers go over one kept-alive connection
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(b"HTTP/1.1 304 Not Modified\r\nETag: \"1\"\r\n\r\n")
+                .await
+                .unwrap();
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .unwrap();
+        let url = format!("http://127.0.0.1:{}/", port);
+        let response = client.get(&url).await.unwrap();
+        assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+        assert!(response.body.is_empty());
+        let response = client.get(&url).await.unwrap();
+        assert_eq!(&response.body[..], b"ok");
+    }
+
+    #[tokio::test]
+    async fn test_idle_connections_dropped_by_the_server() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Answer requests until idle for 50ms, then hang up silently
+                tokio::spawn(async move {
+                    let mut request = [0u8; 1024];
+                    let idle = Duration::from_millis(50);
+                    while let Ok(Ok(n)) = timeout(idle, socket.read(&mut request)).await {
+                        if n == 0 {
+                            return;
+                        }
+                        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        socket.write_all(response).await.unwrap();
+                    }
+                });
+            }
+        });
+        let url = format!("http://127.0.0.1:{}/", port);
+        let origin = format!("http://127.0.0.1:{}", port);
+
+        // Probed before reuse
+        let client = Client::builder()
+            .pool_config(PoolConfig {
+                stale_after: Duration::ZERO,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        client.get(&url).await.unwrap();
+        let stats = client.pool_stats();
+        assert_eq!(stats.hosts[&origin].open, 1);
+        assert_eq!(stats.hosts[&origin].idle, 1);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let response = client.get(&url).await.unwrap();
+        assert_eq!(&response.body[..], b"ok");
+        client.get(&url).await.unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        let stats = client.pool_stats();
+        assert_eq!((stats.stale_discarded, stats.retried), (1, 0));
+
+        // Not probed: the request fails on the closed connection and is
+        // sent again on a new one
+        let client = Client::builder()
+            .pool_config(PoolConfig {
+                stale_after: Duration::from_secs(3600),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        client.post(&url, Bytes::from_static(b"x")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let response = client.post(&url, Bytes::from_static(b"y")).await.unwrap();
+        assert_eq!(&response.body[..], b"ok");
+        let stats = client.pool_stats();
+        assert_eq!((stats.stale_discarded, stats.retried), (0, 1));
+        assert_eq!(client.close_idle_connections(), 1);
+        assert!(client.pool_stats().hosts.is_empty());
+    }
+}
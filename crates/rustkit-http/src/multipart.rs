@@ -0,0 +1,241 @@
+//! `multipart/x-mixed-replace` bodies, as served by MJPEG cameras and
+//! server-push endpoints.
+//!
+//! Each part replaces the previous one, so parts are produced as soon as
+//! they are complete: when their `Content-Length` has arrived, or else
+//! when the next boundary does. The body never has to end.
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::stream::{self, BoxStream};
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::{HttpError, StreamingResponse};
+
+/// One part of a multipart body.
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Part {
+    /// Get the part's `Content-Type`.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+    }
+}
+
+/// The `boundary` parameter of a `multipart/*` content type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let essence = params.next()?.trim();
+    if !essence.to_ascii_lowercase().starts_with("multipart/") {
+        return None;
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+}
+
+#[derive(Debug)]
+enum State {
+    /// Looking for the next delimiter line.
+    Delimiter,
+    Headers,
+    Body,
+    /// Saw the close delimiter.
+    Closed,
+}
+
+/// Incremental parser splitting a multipart body into [`Part`]s.
+#[derive(Debug)]
+pub struct MultipartParser {
+    /// `--` and the boundary.
+    delimiter: Vec<u8>,
+    buffer: BytesMut,
+    state: State,
+    headers: HeaderMap,
+}
+
+impl MultipartParser {
+    /// Create a parser for parts separated by `boundary`.
+    pub fn new(boundary: &str) -> Self {
+        Self {
+            delimiter: format!("--{}", boundary).into_bytes(),
+            buffer: BytesMut::new(),
+            state: State::Delimiter,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Whether the close delimiter has been seen.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.state, State::Closed)
+    }
+
+    /// Feed body bytes, returning the parts they complete.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Part> {
+        self.buffer.extend_from_slice(data);
+        let mut parts = Vec::new();
+        loop {
+            match self.state {
+                State::Closed => {
+                    self.buffer.clear();
+                    return parts;
+                }
+                State::Delimiter => {
+                    let Some(start) = find(&self.buffer, &self.delimiter) else {
+                        // Keep a tail that may hold the start of a delimiter
+                        let keep = self.delimiter.len().min(self.buffer.len());
+                        self.buffer.advance(self.buffer.len() - keep);
+                        return parts;
+                    };
+                    let Some(end) = find(&self.buffer[start..], b"\n") else {
+                        return parts;
+                    };
+                    let line = &self.buffer[start + self.delimiter.len()..start + end];
+                    self.state = if line.starts_with(b"--") {
+                        State::Closed
+                    } else {
+                        State::Headers
+                    };
+                    self.buffer.advance(start + end + 1);
+                }
+                State::Headers => {
+                    let Some(end) = find(&self.buffer, b"\n") else {
+                        return parts;
+                    };
+                    let line = self.buffer.split_to(end + 1);
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim();
+                    if line.is_empty() {
+                        self.state = State::Body;
+                    } else if let Some((name, value)) = line.split_once(':') {
+                        if let (Ok(n), Ok(v)) = (
+                            HeaderName::try_from(name.trim()),
+                            HeaderValue::try_from(value.trim()),
+                        ) {
+                            self.headers.insert(n, v);
+                        }
+                    }
+                }
+                State::Body => {
+                    let length = self
+                        .headers
+                        .get("content-length")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|s| s.trim().parse::<usize>().ok());
+                    let body = match length {
+                        Some(length) if self.buffer.len() >= length => {
+                            self.buffer.split_to(length).freeze()
+                        }
+                        Some(_) => return parts,
+                        None => {
+                            let Some(end) = self.body_end() else {
+                                return parts;
+                            };
+                            self.buffer.split_to(end).freeze()
+                        }
+                    };
+                    parts.push(Part {
+                        headers: std::mem::take(&mut self.headers),
+                        body,
+                    });
+                    self.state = State::Delimiter;
+                }
+            }
+        }
+    }
+
+    /// End of a body without a length: the line break before the next
+    /// delimiter line.
+    fn body_end(&self) -> Option<usize> {
+        if self.buffer.starts_with(&self.delimiter) {
+            return Some(0);
+        }
+        let mut line_break = self.delimiter.clone();
+        line_break.insert(0, b'\n');
+        let at = find(&self.buffer, &line_break)?;
+        Some(if at > 0 && self.buffer[at - 1] == b'\r' {
+            at - 1
+        } else {
+            at
+        })
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+impl StreamingResponse {
+    /// Split a `multipart/*` body into its parts as they arrive.
+    ///
+    /// The stream ends at the close delimiter or when the server closes
+    /// the connection; dropping it closes the connection.
+    pub fn parts(self) -> Result<BoxStream<'static, Result<Part, HttpError>>, HttpError> {
+        let boundary = self
+            .content_type()
+            .and_then(boundary)
+            .ok_or_else(|| HttpError::InvalidResponse("Not a multipart response".to_string()))?;
+        let parser = MultipartParser::new(&boundary);
+        let state = (self, parser, std::collections::VecDeque::new());
+        Ok(Box::pin(stream::try_unfold(
+            state,
+            |(mut response, mut parser, mut ready)| async move {
+                loop {
+                    if let Some(part) = ready.pop_front() {
+                        return Ok(Some((part, (response, parser, ready))));
+                    }
+                    if parser.is_closed() {
+                        return Ok(None);
+                    }
+                    match response.next_chunk().await? {
+                        Some(chunk) => ready.extend(parser.push(&chunk)),
+                        None => return Ok(None),
+                    }
+                }
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boundary_from_content_type() {
+        assert_eq!(
+            boundary("multipart/x-mixed-replace; boundary=frame").as_deref(),
+            Some("frame")
+        );
+        assert_eq!(
+            boundary("Multipart/X-Mixed-Replace;Boundary=\"a b\"").as_deref(),
+            Some("a b")
+        );
+        assert_eq!(boundary("image/jpeg; boundary=frame"), None);
+    }
+
+    #[test]
+    fn test_parts_split_across_pushes() {
+        let body = b"preamble\r\n--frame\r\nContent-Type: text/plain\r\n\r\nfirst\r\n\
+                     --frame\r\nContent-Length: 6\r\n\r\nsecond--frame\r\n\r\nthird\r\n--frame--\r\n";
+        let mut parser = MultipartParser::new("frame");
+        let mut parts = Vec::new();
+        // Byte at a time, so every delimiter and header straddles a push
+        for byte in body.iter() {
+            parts.extend(parser.push(std::slice::from_ref(byte)));
+        }
+        let bodies: Vec<&[u8]> = parts.iter().map(|p| p.body.as_ref()).collect();
+        assert_eq!(bodies, [&b"first"[..], b"second", b"third"]);
+        assert_eq!(parts[0].content_type(), Some("text/plain"));
+        assert!(parser.is_closed());
+    }
+}
@@ -0,0 +1,336 @@
+//! Idle HTTP/1.1 connections kept for reuse.
+//!
+//! A connection that ends a response at a message boundary waits here for
+//! the next request to its origin, for at most [`PoolConfig::idle_timeout`]
+//! and never past [`PoolConfig::max_age`] from when it was opened. Servers
+//! and middleboxes often drop idle connections without telling anyone, so
+//! one that has sat longer than [`PoolConfig::stale_after`] is probed
+//! before reuse and discarded if its peer has gone.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+
+use crate::TlsInfo;
+
+/// TCP keepalive probes on new connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    /// How long a connection is quiet before the first probe.
+    pub idle: Duration,
+    /// Time between unanswered probes.
+    pub interval: Duration,
+}
+
+impl Default for TcpKeepalive {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(45),
+            interval: Duration::from_secs(15),
+        }
+    }
+}
+
+impl TcpKeepalive {
+    pub(crate) fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        let keepalive = socket2::TcpKeepalive::new()
+            .with_time(self.idle)
+            .with_interval(self.interval);
+        socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)
+    }
+}
+
+/// How idle connections are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Keepalive probes on new connections; `None` leaves the system's
+    /// setting, usually off.
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    /// How long a connection may wait idle.
+    pub idle_timeout: Duration,
+    /// How long after it opened a connection stops being reused.
+    pub max_age: Duration,
+    /// Idle time after which a connection is checked before reuse.
+    pub stale_after: Duration,
+    /// Most idle connections kept to one origin.
+    pub max_idle_per_host: usize,
+    /// Most idle plain HTTP connections kept. TLS ones are limited by
+    /// [`crate::TlsConfig::session_cache_size`].
+    pub max_idle: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            tcp_keepalive: Some(TcpKeepalive::default()),
+            idle_timeout: Duration::from_secs(90),
+            max_age: Duration::from_secs(5 * 60),
+            stale_after: Duration::from_secs(1),
+            max_idle_per_host: 6,
+            max_idle: 32,
+        }
+    }
+}
+
+/// Connections to one origin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostPoolStats {
+    /// Connections carrying a request or waiting idle.
+    pub open: usize,
+    pub idle: usize,
+    /// How long ago each idle connection was opened, oldest first.
+    pub idle_ages: Vec<Duration>,
+}
+
+/// What the connection pool holds, and counters since it was created or
+/// last reset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Connections by serialized origin, e.g. `https://example.com:443`.
+    pub hosts: BTreeMap<String, HostPoolStats>,
+    /// Idle connections found closed by their peer when about to be reused.
+    pub stale_discarded: u64,
+    /// Idle connections dropped for their idle time or age.
+    pub expired: u64,
+    /// Requests sent again on a new connection after a reused one closed
+    /// before the response started.
+    pub retried: u64,
+}
+
+/// A plain or TLS connection.
+pub(crate) enum PooledStream {
+    Plain(TcpStream),
+    Tls(TlsStream<TcpStream>),
+}
+
+impl PooledStream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Self::Plain(stream) => stream,
+            Self::Tls(stream) => stream.get_ref().get_ref().get_ref(),
+        }
+    }
+
+    /// Whether the peer has closed the connection. An idle HTTP/1.1
+    /// connection has nothing to read, so end of stream, unsolicited data
+    /// (a `408` or a TLS alert) and errors all mean it can't be reused.
+    fn is_closed(&self) -> bool {
+        let mut byte = [MaybeUninit::uninit()];
+        !matches!(
+            socket2::SockRef::from(self.tcp()).peek(&mut byte),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock
+        )
+    }
+}
+
+impl AsyncRead for PooledStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for PooledStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// An idle keep-alive connection.
+pub(crate) struct PooledConnection {
+    /// Serialized origin, `scheme://host:port`.
+    pub(crate) origin: String,
+    pub(crate) stream: PooledStream,
+    /// TLS parameters the connection was opened with.
+    pub(crate) tls: Option<TlsInfo>,
+    opened: Instant,
+    idle_since: Instant,
+}
+
+impl PooledConnection {
+    pub(crate) fn new(origin: String, stream: PooledStream, tls: Option<TlsInfo>) -> Self {
+        let now = Instant::now();
+        Self {
+            origin,
+            stream,
+            tls,
+            opened: now,
+            idle_since: now,
+        }
+    }
+
+    fn is_tls(&self) -> bool {
+        matches!(self.stream, PooledStream::Tls(_))
+    }
+}
+
+/// Idle connections, least recently used first, and the number of
+/// connections out carrying requests.
+#[derive(Default)]
+pub(crate) struct Pool {
+    idle: VecDeque<PooledConnection>,
+    active: HashMap<String, usize>,
+    stats: PoolStats,
+}
+
+impl Pool {
+    /// Take the most recently used idle connection to `origin` that is
+    /// still usable, dropping expired and stale ones on the way.
+    pub(crate) fn take(&mut self, origin: &str, config: &PoolConfig) -> Option<PooledConnection> {
+        let now = Instant::now();
+        self.expire(config, now);
+        loop {
+            let index = self.idle.iter().rposition(|c| c.origin == origin)?;
+            let connection = self.idle.remove(index)?;
+            if now.duration_since(connection.idle_since) >= config.stale_after
+                && connection.stream.is_closed()
+            {
+                tracing::debug!(origin, "Discarding idle connection closed by the server");
+                self.stats.stale_discarded += 1;
+                continue;
+            }
+            return Some(connection);
+        }
+    }
+
+    /// Keep `connection` idle, within `config`'s per-origin limit and
+    /// `capacity` connections of its kind.
+    pub(crate) fn put(
+        &mut self,
+        mut connection: PooledConnection,
+        config: &PoolConfig,
+        capacity: usize,
+    ) {
+        let now = Instant::now();
+        connection.idle_since = now;
+        if now.duration_since(connection.opened) >= config.max_age {
+            self.stats.expired += 1;
+            return;
+        }
+        let (origin, tls) = (connection.origin.clone(), connection.is_tls());
+        self.idle.push_back(connection);
+        while self.idle.iter().filter(|c| c.origin == origin).count() > config.max_idle_per_host {
+            let index = self.idle.iter().position(|c| c.origin == origin);
+            index.and_then(|index| self.idle.remove(index));
+        }
+        while self.idle.iter().filter(|c| c.is_tls() == tls).count() > capacity {
+            let index = self.idle.iter().position(|c| c.is_tls() == tls);
+            index.and_then(|index| self.idle.remove(index));
+        }
+    }
+
+    /// Drop idle connections past their idle timeout or age.
+    fn expire(&mut self, config: &PoolConfig, now: Instant) {
+        let before = self.idle.len();
+        self.idle.retain(|c| {
+            now.duration_since(c.idle_since) < config.idle_timeout
+                && now.duration_since(c.opened) < config.max_age
+        });
+        self.stats.expired += (before - self.idle.len()) as u64;
+    }
+
+    /// Drop the idle connections `remove` picks, returning how many.
+    pub(crate) fn close(&mut self, remove: impl Fn(&PooledConnection) -> bool) -> usize {
+        let before = self.idle.len();
+        self.idle.retain(|c| !remove(c));
+        before - self.idle.len()
+    }
+
+    pub(crate) fn count_retry(&mut self) {
+        self.stats.retried += 1;
+    }
+
+    pub(crate) fn stats(&mut self, config: &PoolConfig) -> PoolStats {
+        let now = Instant::now();
+        self.expire(config, now);
+        let mut stats = PoolStats {
+            hosts: BTreeMap::new(),
+            ..self.stats.clone()
+        };
+        for (origin, &active) in &self.active {
+            stats.hosts.entry(origin.clone()).or_default().open += active;
+        }
+        for connection in &self.idle {
+            let host = stats.hosts.entry(connection.origin.clone()).or_default();
+            host.open += 1;
+            host.idle += 1;
+            host.idle_ages.push(now.duration_since(connection.opened));
+        }
+        for host in stats.hosts.values_mut() {
+            host.idle_ages.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        stats
+    }
+
+    pub(crate) fn reset_stats(&mut self) {
+        self.stats = PoolStats::default();
+    }
+}
+
+/// Counts a connection to an origin as open while it carries a request.
+pub(crate) struct Lease {
+    pool: Arc<Mutex<Pool>>,
+    origin: String,
+}
+
+impl Lease {
+    pub(crate) fn new(pool: &Arc<Mutex<Pool>>, origin: &str) -> Self {
+        *pool
+            .lock()
+            .unwrap()
+            .active
+            .entry(origin.to_string())
+            .or_default() += 1;
+        Self {
+            pool: Arc::clone(pool),
+            origin: origin.to_string(),
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut pool = self.pool.lock().unwrap();
+        if let Some(active) = pool.active.get_mut(&self.origin) {
+            *active -= 1;
+            if *active == 0 {
+                pool.active.remove(&self.origin);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Sending requests through HTTP proxies.
+//!
+//! A [`ProxyResolver`] set on the client names the proxies to try for each
+//! URL, in order. `http` requests go to the proxy with the full URL as the
+//! request target; `https` ones go through a `CONNECT` tunnel, so TLS stays
+//! end to end. A proxy that cannot be reached or refuses the tunnel is
+//! reported to the resolver and the next entry is tried.
+
+use std::fmt;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::HttpError;
+
+/// How long connecting to a proxy and opening a tunnel may take before the
+/// next proxy is tried.
+pub const PROXY_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest `CONNECT` response head read.
+const MAX_TUNNEL_HEAD: usize = 16 * 1024;
+
+/// One way of reaching a server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProxyServer {
+    /// Connect to the server itself.
+    Direct,
+    /// An HTTP proxy.
+    Http { host: String, port: u16 },
+}
+
+impl fmt::Display for ProxyServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyServer::Direct => write!(f, "DIRECT"),
+            ProxyServer::Http { host, port } => write!(f, "PROXY {}:{}", host, port),
+        }
+    }
+}
+
+/// Chooses the proxies for each request.
+pub trait ProxyResolver: Send + Sync {
+    /// Ways to reach the server of `url`, to be tried in order. Empty
+    /// means direct.
+    fn proxies_for<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Vec<ProxyServer>>;
+
+    /// A proxy from [`Self::proxies_for`] could not be used.
+    fn report_failure(&self, _proxy: &ProxyServer) {}
+}
+
+/// Ask a proxy to open a tunnel to `host:port` over `stream`.
+pub(crate) async fn tunnel(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    user_agent: &str,
+) -> Result<(), HttpError> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let request = format!(
+        "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\nUser-Agent: {1}\r\n\r\n",
+        authority, user_agent
+    );
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+
+    // Byte by byte, so nothing the server sends after the head is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_TUNNEL_HEAD {
+            return Err(HttpError::InvalidResponse(
+                "Proxy response head too long".to_string(),
+            ));
+        }
+        let byte = stream
+            .read_u8()
+            .await
+            .map_err(|_| HttpError::ConnectionFailed("Proxy closed the connection".to_string()))?;
+        head.push(byte);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| HttpError::InvalidResponse("Invalid proxy response".to_string()))?;
+    if !(200..300).contains(&status) {
+        return Err(HttpError::ConnectionFailed(format!(
+            "Proxy refused tunnel to {} with status {}",
+            authority, status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::TcpListener;
+
+    /// A proxy that answers one `CONNECT` with `status` and echoes what
+    /// follows.
+    async fn proxy(status: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut line = String::new();
+            while stream.read_line(&mut line).await.unwrap() > 0 && !line.ends_with("\r\n\r\n") {}
+            assert!(line.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+            let mut stream = stream.into_inner();
+            let response = format!("HTTP/1.1 {}\r\nVia: test\r\n\r\n", status);
+            stream.write_all(response.as_bytes()).await.unwrap();
+            let mut buf = [0u8; 5];
+            if stream.read_exact(&mut buf).await.is_ok() {
+                stream.write_all(&buf).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_established() {
+        let port = proxy("200 Connection established").await;
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        tunnel(&mut stream, "example.com", 443, "test")
+            .await
+            .unwrap();
+
+        stream.write_all(b"hello").await.unwrap();
+        let mut echoed = [0u8; 5];
+        stream.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_refused() {
+        let port = proxy("403 Forbidden").await;
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let error = tunnel(&mut stream, "example.com", 443, "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HttpError::ConnectionFailed(m) if m.contains("403")));
+    }
+}
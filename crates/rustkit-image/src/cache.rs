@@ -2,6 +2,7 @@
 //!
 //! Provides memory and disk caching for decoded images.
 
+use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 
@@ -24,6 +25,9 @@ pub struct CacheStats {
 
     /// Estimated memory usage in bytes
     pub memory_bytes: usize,
+
+    /// Encoded bytes kept so released images can be decoded again
+    pub encoded_bytes: usize,
 }
 
 impl CacheStats {
@@ -43,6 +47,9 @@ pub struct ImageCache {
     /// LRU cache of images
     cache: LruCache<Url, Arc<LoadedImage>>,
 
+    /// Encoded bytes of images decoded from fetched data
+    encoded: HashMap<Url, Arc<[u8]>>,
+
     /// Cache statistics
     stats: CacheStats,
 
@@ -56,6 +63,7 @@ impl ImageCache {
     pub fn new(capacity: usize) -> Self {
         Self {
             cache: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(1).unwrap())),
+            encoded: HashMap::new(),
             stats: CacheStats::default(),
             max_memory: 256 * 1024 * 1024, // 256MB default
         }
@@ -70,19 +78,81 @@ impl ImageCache {
 
     /// Insert an image into the cache
     pub fn insert(&mut self, url: Url, image: Arc<LoadedImage>) {
-        self.cache.put(url, image);
+        self.stats.memory_bytes += Self::estimate_memory(&image);
+        if let Some((_, old)) = self.cache.push(url, image) {
+            self.stats.memory_bytes -= Self::estimate_memory(&old);
+        }
         self.stats.count = self.cache.len();
     }
 
+    /// Keep the encoded bytes an image was decoded from.
+    pub fn insert_encoded(&mut self, url: Url, bytes: Arc<[u8]>) {
+        self.stats.encoded_bytes += bytes.len();
+        if let Some(old) = self.encoded.insert(url, bytes) {
+            self.stats.encoded_bytes -= old.len();
+        }
+    }
+
+    /// The encoded bytes kept for an image
+    pub fn encoded(&self, url: &Url) -> Option<Arc<[u8]>> {
+        self.encoded.get(url).cloned()
+    }
+
     /// Check if an image is in the cache
     pub fn contains(&self, url: &Url) -> bool {
         self.cache.contains(url)
     }
 
+    /// Drop the decoded pixels of an image whose encoded bytes are kept.
+    /// Returns the bytes freed.
+    pub fn release(&mut self, url: &Url) -> usize {
+        if !self.encoded.contains_key(url) {
+            return 0;
+        }
+        let freed = self
+            .cache
+            .pop(url)
+            .map_or(0, |image| Self::estimate_memory(&image));
+        self.stats.memory_bytes -= freed;
+        self.stats.count = self.cache.len();
+        freed
+    }
+
+    /// Evict least recently inserted images until the cache holds at most
+    /// `target_bytes`. Decoded pixels go first, keeping their encoded bytes;
+    /// encoded bytes go once no decoded images are left. Returns the bytes
+    /// freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let before = self.stats.memory_bytes + self.stats.encoded_bytes;
+        while self.stats.memory_bytes + self.stats.encoded_bytes > target_bytes {
+            let Some((_, image)) = self.cache.pop_lru() else {
+                break;
+            };
+            self.stats.memory_bytes -= Self::estimate_memory(&image);
+        }
+        if self.stats.encoded_bytes > target_bytes {
+            let mut urls: Vec<Url> = self.encoded.keys().cloned().collect();
+            urls.sort();
+            for url in urls {
+                if self.stats.encoded_bytes <= target_bytes {
+                    break;
+                }
+                if let Some(bytes) = self.encoded.remove(&url) {
+                    self.stats.encoded_bytes -= bytes.len();
+                }
+            }
+        }
+        self.stats.count = self.cache.len();
+        before - (self.stats.memory_bytes + self.stats.encoded_bytes)
+    }
+
     /// Clear the cache
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.encoded.clear();
         self.stats.count = 0;
+        self.stats.memory_bytes = 0;
+        self.stats.encoded_bytes = 0;
     }
 
     /// Get cache statistics
@@ -105,7 +175,7 @@ impl ImageCache {
         let pixels = (image.natural_width as usize) * (image.natural_height as usize);
         match &image.data {
             crate::ImageData::Static(_) => pixels * 4, // RGBA
-            crate::ImageData::Animated(anim) => pixels * 4 * anim.frames.len(),
+            crate::ImageData::Animated(anim) => pixels * 4 * anim.max_resident_frames(pixels * 4),
         }
     }
 }
@@ -121,10 +191,41 @@ mod tests {
             misses: 25,
             count: 10,
             memory_bytes: 1000,
+            encoded_bytes: 0,
         };
         assert!((stats.hit_rate() - 75.0).abs() < 0.001);
     }
 
+    #[test]
+    fn test_trim_keeps_encoded_bytes() {
+        let mut cache = ImageCache::new(10);
+        let image = |name: &str| {
+            let url = Url::parse(&format!("https://example.com/{name}.png")).unwrap();
+            (
+                url.clone(),
+                Arc::new(LoadedImage::new(
+                    url,
+                    rustkit_codecs::RgbaImage::new(10, 10),
+                )),
+            )
+        };
+        let (a, a_image) = image("a");
+        let (b, b_image) = image("b");
+        cache.insert(a.clone(), a_image);
+        cache.insert_encoded(a.clone(), Arc::from(&[0u8; 50][..]));
+        cache.insert(b.clone(), b_image);
+        assert_eq!(cache.stats().memory_bytes, 800);
+
+        // The oldest image goes first and its encoded bytes stay
+        assert_eq!(cache.trim(500), 400);
+        assert!(!cache.contains(&a) && cache.contains(&b));
+        assert_eq!(cache.encoded(&a).unwrap().len(), 50);
+
+        assert_eq!(cache.release(&b), 0);
+        assert_eq!(cache.trim(0), 450);
+        assert_eq!(cache.stats().memory_bytes + cache.stats().encoded_bytes, 0);
+    }
+
     #[test]
     fn test_cache_stats_empty() {
         let stats = CacheStats::default();
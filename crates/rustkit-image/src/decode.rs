@@ -48,9 +48,12 @@ pub fn mime_to_format(mime: &str) -> Option<ImageFormat> {
     }
 }
 
-/// Check if a format supports animation
+/// Check if a format supports animation (PNG as APNG)
 pub fn supports_animation(format: ImageFormat) -> bool {
-    matches!(format, ImageFormat::Gif | ImageFormat::WebP)
+    matches!(
+        format,
+        ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP
+    )
 }
 
 /// Check if a format supports transparency
@@ -82,7 +85,7 @@ mod tests {
     #[test]
     fn test_supports_animation() {
         assert!(supports_animation(ImageFormat::Gif));
-        assert!(!supports_animation(ImageFormat::Png));
+        assert!(supports_animation(ImageFormat::Png));
         assert!(!supports_animation(ImageFormat::Jpeg));
     }
 
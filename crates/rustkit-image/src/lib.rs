@@ -5,13 +5,15 @@
 //! This crate handles:
 //! - Async image fetching from URLs
 //! - Decoding of PNG, JPEG, GIF, WebP, BMP, and ICO formats
-//! - Animated GIF support
+//! - Animated GIF and APNG support
 //! - Memory and disk caching
 //! - GPU texture management
 //! - Lazy loading support
+//! - MJPEG and other `multipart/x-mixed-replace` streams
 
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use rustkit_codecs::{Decoded, ImageFormat, RgbaImage};
@@ -23,10 +25,12 @@ use url::Url;
 pub mod cache;
 pub mod decode;
 pub mod loader;
+pub mod stream;
 
 pub use cache::*;
 pub use decode::*;
 pub use loader::*;
+pub use stream::*;
 
 /// Errors that can occur during image operations
 #[derive(Error, Debug)]
@@ -59,6 +63,10 @@ pub enum ImageError {
 /// Result type for image operations
 pub type ImageResult<T> = Result<T, ImageError>;
 
+/// Most bytes of decoded frames kept for one animated image; frames past
+/// it are evicted and decoded again when shown.
+const MAX_ANIMATION_BYTES: usize = 64 * 1024 * 1024;
+
 /// Represents a loaded and decoded image
 #[derive(Clone)]
 pub struct LoadedImage {
@@ -112,10 +120,7 @@ impl LoadedImage {
             url,
             natural_width,
             natural_height,
-            data: ImageData::Animated(AnimatedImage {
-                frames,
-                loop_count: 0, // Infinite
-            }),
+            data: ImageData::Animated(AnimatedImage::new(frames, 0)), // Infinite
             decoded_at: Instant::now(),
             content_type: None,
             complete: true,
@@ -123,10 +128,10 @@ impl LoadedImage {
     }
 
     /// Get the current frame to display
-    pub fn current_frame(&self, elapsed: Duration) -> &RgbaImage {
+    pub fn current_frame(&self, elapsed: Duration) -> Cow<'_, RgbaImage> {
         match &self.data {
-            ImageData::Static(img) => img,
-            ImageData::Animated(anim) => anim.frame_at(elapsed),
+            ImageData::Static(img) => Cow::Borrowed(img),
+            ImageData::Animated(anim) => Cow::Owned(RgbaImage::clone(&anim.frame_at(elapsed))),
         }
     }
 
@@ -158,41 +163,156 @@ pub enum ImageData {
 /// Animated image with frames
 #[derive(Clone)]
 pub struct AnimatedImage {
-    /// All frames
-    pub frames: Vec<AnimationFrame>,
+    /// How long each frame shows, in milliseconds
+    pub delays: Vec<u32>,
 
-    /// Number of times to loop (0 = infinite)
+    /// Number of times the frames play (0 = infinite)
     pub loop_count: u32,
+
+    /// Composited frames, shared by clones
+    frames: Arc<FrameStore>,
+}
+
+/// The composited frames of an animation. Frames over its byte budget are
+/// evicted and decoded again from the encoded image when asked for.
+struct FrameStore {
+    /// Encoded image, for animations with frames evicted.
+    source: Option<Arc<[u8]>>,
+    max_bytes: usize,
+    resident: Mutex<Vec<Option<Arc<RgbaImage>>>>,
 }
 
 impl AnimatedImage {
+    /// An animation with all its frames in memory
+    pub fn new(frames: Vec<AnimationFrame>, loop_count: u32) -> Self {
+        let delays = frames.iter().map(|f| f.delay_ms).collect();
+        let resident = frames
+            .into_iter()
+            .map(|f| Some(Arc::new(f.image)))
+            .collect();
+        Self {
+            delays,
+            loop_count,
+            frames: Arc::new(FrameStore {
+                source: None,
+                max_bytes: usize::MAX,
+                resident: Mutex::new(resident),
+            }),
+        }
+    }
+
+    /// An animation decoded from `source` holding the frames that fit
+    /// `max_bytes`; the others are decoded from `source` when shown.
+    fn decoded(animation: rustkit_codecs::Animation, source: &[u8], max_bytes: usize) -> Self {
+        let mut resident = vec![None; animation.delays.len()];
+        let evicted = animation.frames.len() < resident.len();
+        for (index, frame) in animation.frames.into_iter().enumerate() {
+            resident[animation.first + index] = Some(Arc::new(frame.image));
+        }
+        Self {
+            delays: animation.delays,
+            loop_count: animation.plays,
+            frames: Arc::new(FrameStore {
+                source: evicted.then(|| source.into()),
+                max_bytes,
+                resident: Mutex::new(resident),
+            }),
+        }
+    }
+
+    /// Number of frames, decoded or not
+    pub fn frame_count(&self) -> usize {
+        self.delays.len()
+    }
+
+    /// Number of frames decoded and held in memory
+    pub fn resident_frames(&self) -> usize {
+        let resident = self
+            .frames
+            .resident
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        resident.iter().filter(|frame| frame.is_some()).count()
+    }
+
+    /// The most frames held in memory at once
+    pub fn max_resident_frames(&self, frame_bytes: usize) -> usize {
+        let fit = self.frames.max_bytes / frame_bytes.max(1);
+        fit.clamp(1, self.frame_count().max(1))
+    }
+
+    /// The composited frame at `index`. An evicted frame is decoded again,
+    /// along with the frames after it that fit the budget, in place of the
+    /// frames held before.
+    pub fn frame(&self, index: usize) -> Arc<RgbaImage> {
+        let store = &self.frames;
+        let mut resident = store.resident.lock().unwrap_or_else(|e| e.into_inner());
+        let index = index.min(resident.len().saturating_sub(1));
+        if let Some(Some(frame)) = resident.get(index) {
+            return frame.clone();
+        }
+        let source = store.source.as_deref().unwrap_or_default();
+        match rustkit_codecs::decode_animation_from(source, index, store.max_bytes) {
+            Ok(animation) if !animation.frames.is_empty() => {
+                resident.iter_mut().for_each(|frame| *frame = None);
+                for (offset, frame) in animation.frames.into_iter().enumerate() {
+                    if let Some(slot) = resident.get_mut(index + offset) {
+                        *slot = Some(Arc::new(frame.image));
+                    }
+                }
+                resident[index].clone().unwrap()
+            }
+            result => {
+                debug!(error = ?result.err(), index, "Failed to decode animation frame again");
+                // Show whatever frame is still held
+                resident
+                    .iter()
+                    .flatten()
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(RgbaImage::new(0, 0)))
+            }
+        }
+    }
+
     /// Get the frame at a given elapsed time
-    pub fn frame_at(&self, elapsed: Duration) -> &RgbaImage {
-        if self.frames.is_empty() {
+    pub fn frame_at(&self, elapsed: Duration) -> Arc<RgbaImage> {
+        if self.delays.is_empty() {
             panic!("AnimatedImage has no frames");
         }
 
-        let total_duration: u64 = self.frames.iter().map(|f| f.delay_ms as u64).sum();
+        self.frame(self.frame_index_at(elapsed))
+    }
+
+    /// Index of the frame shown at a given elapsed time. Once a finite
+    /// animation has played through, it stays on its last frame.
+    pub fn frame_index_at(&self, elapsed: Duration) -> usize {
+        let total_duration: u64 = self.delays.iter().map(|&d| d as u64).sum();
         if total_duration == 0 {
-            return &self.frames[0].image;
+            return 0;
         }
 
-        let elapsed_ms = elapsed.as_millis() as u64 % total_duration;
+        let elapsed_ms = elapsed.as_millis() as u64;
+        if self.loop_count > 0 && elapsed_ms >= total_duration * self.loop_count as u64 {
+            return self.delays.len() - 1;
+        }
+
+        let elapsed_ms = elapsed_ms % total_duration;
         let mut cumulative = 0u64;
 
-        for frame in &self.frames {
-            cumulative += frame.delay_ms as u64;
+        for (index, &delay_ms) in self.delays.iter().enumerate() {
+            cumulative += delay_ms as u64;
             if elapsed_ms < cumulative {
-                return &frame.image;
+                return index;
             }
         }
 
-        &self.frames.last().unwrap().image
+        self.delays.len() - 1
     }
 
     /// Get the total animation duration
     pub fn total_duration(&self) -> Duration {
-        let total_ms: u64 = self.frames.iter().map(|f| f.delay_ms as u64).sum();
+        let total_ms: u64 = self.delays.iter().map(|&d| d as u64).sum();
         Duration::from_millis(total_ms)
     }
 }
@@ -284,6 +404,10 @@ pub struct ImageManager {
     /// Memory cache for decoded images
     cache: Arc<RwLock<ImageCache>>,
 
+    /// Top-level sites each cached image was loaded for, so a site cannot
+    /// time whether another site has shown an image
+    sites: RwLock<HashMap<Url, HashSet<String>>>,
+
     /// HTTP client for fetching images
     client: rustkit_http::Client,
 
@@ -309,6 +433,7 @@ impl ImageManager {
 
         Self {
             cache: Arc::new(RwLock::new(ImageCache::new(100))),
+            sites: RwLock::new(HashMap::new()),
             client: rustkit_http::Client::builder()
                 .timeout(Duration::from_secs(30))
                 .build()
@@ -323,14 +448,19 @@ impl ImageManager {
     /// Load an image from a URL
     pub async fn load(&self, url: Url) -> ImageResult<Arc<LoadedImage>> {
         // Check cache first
-        if let Some(cached) = self.cache.read().unwrap().get(&url) {
+        if let Some(cached) = self
+            .cache
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&url)
+        {
             debug!("Image cache hit: {}", url);
             return Ok(cached);
         }
 
         // Check if already loading
         let already_loading = {
-            let pending = self.pending.read().unwrap();
+            let pending = self.pending.read().unwrap_or_else(|e| e.into_inner());
             pending.contains_key(&url)
         };
 
@@ -338,22 +468,40 @@ impl ImageManager {
             debug!("Image already loading: {}", url);
             // Add ourselves to the waiting list
             let (tx, rx) = oneshot::channel();
-            self.pending.write().unwrap().entry(url.clone()).or_default().push(tx);
-            return rx.await.map_err(|_| ImageError::FetchError("Load cancelled".into()))?;
+            self.pending
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(url.clone())
+                .or_default()
+                .push(tx);
+            return rx
+                .await
+                .map_err(|_| ImageError::FetchError("Load cancelled".into()))?;
         }
 
         // Start loading
         debug!("Starting image load: {}", url);
-        self.pending.write().unwrap().insert(url.clone(), vec![]);
+        self.pending
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.clone(), vec![]);
 
         let result = self.fetch_and_decode(url.clone()).await;
 
         // Notify waiters and cache result
-        let waiters = self.pending.write().unwrap().remove(&url).unwrap_or_default();
-        
+        let waiters = self
+            .pending
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&url)
+            .unwrap_or_default();
+
         match &result {
             Ok(image) => {
-                self.cache.write().unwrap().insert(url.clone(), image.clone());
+                self.cache
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(url.clone(), image.clone());
                 for waiter in waiters {
                     let _ = waiter.send(Ok(image.clone()));
                 }
@@ -396,6 +544,45 @@ impl ImageManager {
         Ok(Arc::new(loaded))
     }
 
+    /// Decode an image fetched elsewhere, such as from a `blob:` URL,
+    /// without caching it.
+    pub fn decode(&self, url: &Url, bytes: &[u8]) -> ImageResult<Arc<LoadedImage>> {
+        self.decode_bytes(url, bytes).map(Arc::new)
+    }
+
+    /// Decode an image fetched elsewhere and cache it under `url`.
+    ///
+    /// The encoded bytes are kept too, so [`Self::release`] can drop the
+    /// pixels and [`Self::get_cached`] decode them again on demand.
+    pub fn decode_and_cache(&self, url: &Url, bytes: &[u8]) -> ImageResult<Arc<LoadedImage>> {
+        let image = self.decode(url, bytes)?;
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        cache.insert(url.clone(), image.clone());
+        cache.insert_encoded(url.clone(), Arc::from(bytes));
+        Ok(image)
+    }
+
+    /// Like [`Self::decode_and_cache`], for a document under the top-level
+    /// `site`; see [`Self::get_cached_for`].
+    pub fn decode_and_cache_for(
+        &self,
+        site: Option<&str>,
+        url: &Url,
+        bytes: &[u8],
+    ) -> ImageResult<Arc<LoadedImage>> {
+        let image = self.decode_and_cache(url, bytes)?;
+        if let Some(site) = site {
+            let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+            let mut sites = self.sites.write().unwrap_or_else(|e| e.into_inner());
+            sites.retain(|url, _| cache.contains(url) || cache.encoded(url).is_some());
+            sites
+                .entry(url.clone())
+                .or_default()
+                .insert(site.to_string());
+        }
+        Ok(image)
+    }
+
     /// Decode image from bytes
     fn decode_bytes(&self, url: &Url, bytes: &[u8]) -> ImageResult<LoadedImage> {
         // Guess format from bytes
@@ -406,9 +593,9 @@ impl ImageManager {
             return Err(ImageError::DecodeError("Unknown image format".into()));
         }
 
-        // Handle animated GIFs specially
-        if format == ImageFormat::Gif {
-            return self.decode_gif(url, bytes);
+        // GIFs and PNGs may be animated
+        if matches!(format, ImageFormat::Gif | ImageFormat::Png) {
+            return self.decode_animation(url, format, bytes);
         }
 
         // Decode static image
@@ -435,45 +622,43 @@ impl ImageManager {
         Ok(LoadedImage::new(url.clone(), img))
     }
 
-    /// Decode an animated GIF
-    fn decode_gif(&self, url: &Url, bytes: &[u8]) -> ImageResult<LoadedImage> {
-        let decoded_frames = rustkit_codecs::decode_gif(bytes)
+    /// Decode a GIF or PNG, keeping its frames if it is animated
+    fn decode_animation(
+        &self,
+        url: &Url,
+        format: ImageFormat,
+        bytes: &[u8],
+    ) -> ImageResult<LoadedImage> {
+        let animation = rustkit_codecs::decode_animation(bytes, MAX_ANIMATION_BYTES)
             .map_err(|e| ImageError::DecodeError(e.to_string()))?;
 
-        let mut frames = Vec::with_capacity(decoded_frames.len());
-        for f in decoded_frames {
-            // Check dimensions
-            if f.image.width() > self.max_dimensions.0 || f.image.height() > self.max_dimensions.1 {
-                return Err(ImageError::TooLarge {
-                    width: f.image.width(),
-                    height: f.image.height(),
-                });
-            }
-            frames.push(AnimationFrame {
-                image: f.image,
-                delay_ms: f.delay_ms.max(10),
-            });
-        }
-
-        if frames.is_empty() {
-            return Err(ImageError::DecodeError("GIF has no frames".into()));
+        // Check dimensions
+        let (width, height) = (animation.width, animation.height);
+        if width > self.max_dimensions.0 || height > self.max_dimensions.1 {
+            return Err(ImageError::TooLarge { width, height });
         }
 
         // Single frame = static image
-        if frames.len() == 1 {
-            let frame = frames.remove(0);
-            return Ok(LoadedImage {
-                url: url.clone(),
-                natural_width: frame.image.width(),
-                natural_height: frame.image.height(),
-                data: ImageData::Static(frame.image),
-                decoded_at: Instant::now(),
-                content_type: Some("image/gif".into()),
-                complete: true,
-            });
-        }
+        let data = if animation.delays.len() == 1 {
+            let mut frames = animation.frames;
+            ImageData::Static(frames.remove(0).image)
+        } else {
+            ImageData::Animated(AnimatedImage::decoded(
+                animation,
+                bytes,
+                MAX_ANIMATION_BYTES,
+            ))
+        };
 
-        Ok(LoadedImage::animated(url.clone(), frames))
+        Ok(LoadedImage {
+            url: url.clone(),
+            natural_width: width,
+            natural_height: height,
+            data,
+            decoded_at: Instant::now(),
+            content_type: Some(format_to_mime(format).into()),
+            complete: true,
+        })
     }
 
     /// Decode a data URL
@@ -512,22 +697,71 @@ impl ImageManager {
 
     /// Clear the cache
     pub fn clear_cache(&self) {
-        self.cache.write().unwrap().clear();
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.sites
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
     }
 
     /// Get cache statistics
     pub fn cache_stats(&self) -> CacheStats {
-        self.cache.read().unwrap().stats()
+        self.cache.read().unwrap_or_else(|e| e.into_inner()).stats()
+    }
+
+    /// Evict cached images until the cache holds at most `target_bytes`.
+    /// Returns the bytes freed.
+    pub fn trim(&self, target_bytes: usize) -> usize {
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .trim(target_bytes)
+    }
+
+    /// Drop the decoded pixels of images whose encoded bytes are kept.
+    /// Returns the bytes freed.
+    pub fn release<'a>(&self, urls: impl IntoIterator<Item = &'a Url>) -> usize {
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        urls.into_iter().map(|url| cache.release(url)).sum()
     }
 
     /// Check if an image is cached
     pub fn is_cached(&self, url: &Url) -> bool {
-        self.cache.read().unwrap().contains(url)
+        let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+        cache.contains(url) || cache.encoded(url).is_some()
     }
 
-    /// Get a cached image if available
+    /// Get a cached image if available, decoding released images again
     pub fn get_cached(&self, url: &Url) -> Option<Arc<LoadedImage>> {
-        self.cache.read().unwrap().get(url)
+        let encoded = {
+            let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(image) = cache.get(url) {
+                return Some(image);
+            }
+            cache.encoded(url)?
+        };
+        let image = self.decode(url, &encoded).ok()?;
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.clone(), image.clone());
+        Some(image)
+    }
+
+    /// Like [`Self::get_cached`], for a document under the top-level
+    /// `site`: only images cached by [`Self::decode_and_cache_for`] for
+    /// the same site are returned. `None` shares the cache across sites.
+    pub fn get_cached_for(&self, site: Option<&str>, url: &Url) -> Option<Arc<LoadedImage>> {
+        if let Some(site) = site {
+            let sites = self.sites.read().unwrap_or_else(|e| e.into_inner());
+            if !sites.get(url).is_some_and(|sites| sites.contains(site)) {
+                return None;
+            }
+        }
+        self.get_cached(url)
     }
 }
 
@@ -738,22 +972,83 @@ mod tests {
     fn test_animated_image_frame_at() {
         let rgba1 = RgbaImage::new(10, 10);
         let rgba2 = RgbaImage::new(10, 10);
-        let anim = AnimatedImage {
-            frames: vec![
+        let anim = AnimatedImage::new(
+            vec![
                 AnimationFrame { image: rgba1, delay_ms: 100 },
                 AnimationFrame { image: rgba2, delay_ms: 100 },
             ],
-            loop_count: 0,
-        };
+            0,
+        );
 
         // At 0ms, should be frame 0
-        let _ = anim.frame_at(Duration::from_millis(0));
+        assert_eq!(anim.frame_index_at(Duration::from_millis(0)), 0);
         
         // At 150ms, should be frame 1
-        let _ = anim.frame_at(Duration::from_millis(150));
+        assert_eq!(anim.frame_index_at(Duration::from_millis(150)), 1);
 
         // At 250ms (past loop), should be frame 0 again
-        let _ = anim.frame_at(Duration::from_millis(250));
+        assert_eq!(anim.frame_index_at(Duration::from_millis(250)), 0);
+
+        // Played twice, it stays on the last frame
+        let anim = AnimatedImage {
+            loop_count: 2,
+            ..anim
+        };
+        assert_eq!(anim.frame_index_at(Duration::from_millis(250)), 0);
+        assert_eq!(anim.frame_index_at(Duration::from_millis(450)), 1);
+    }
+
+    #[test]
+    fn test_animation_over_budget_plays_every_frame() {
+        // A 1x1 GIF looping forever: red, green and blue, 100ms each
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x81\x00\x00".to_vec();
+        gif.extend([0xFF, 0, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0]);
+        gif.extend(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        for index in 0..3u8 {
+            gif.extend([0x21, 0xF9, 0x04, 0x00, 10, 0, 0, 0]);
+            gif.extend([0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+            gif.extend([0x02, 0x02, 0x44 | (index << 3), 0x01, 0x00]);
+        }
+        gif.push(0x3B);
+
+        // Room for a single frame
+        let budget = 4;
+        let animation = rustkit_codecs::decode_animation(&gif, budget).unwrap();
+        let anim = AnimatedImage::decoded(animation, &gif, budget);
+        assert_eq!(anim.frame_count(), 3);
+        assert_eq!(anim.total_duration(), Duration::from_millis(300));
+        assert_eq!(anim.resident_frames(), 1);
+
+        let shown: Vec<_> = [0, 150, 250, 350]
+            .map(|ms| anim.frame_at(Duration::from_millis(ms)).data().to_vec())
+            .into();
+        assert_eq!(
+            shown,
+            [
+                [0xFF, 0, 0, 0xFF],
+                [0, 0xFF, 0, 0xFF],
+                [0, 0, 0xFF, 0xFF],
+                [0xFF, 0, 0, 0xFF]
+            ]
+        );
+        assert_eq!(anim.resident_frames(), 1);
+    }
+
+    #[test]
+    fn test_cached_images_are_partitioned_by_site() {
+        let manager = ImageManager::new();
+        let url = Url::parse("https://cdn.example/logo.png").unwrap();
+        let png = rustkit_codecs::encode_png(&RgbaImage::new(2, 2)).unwrap();
+        manager
+            .decode_and_cache_for(Some("https://a.example"), &url, &png)
+            .unwrap();
+        assert!(manager
+            .get_cached_for(Some("https://a.example"), &url)
+            .is_some());
+        assert!(manager
+            .get_cached_for(Some("https://b.example"), &url)
+            .is_none());
+        assert!(manager.get_cached_for(None, &url).is_some());
     }
 
     #[test]
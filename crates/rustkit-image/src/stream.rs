@@ -0,0 +1,129 @@
+//! Images that update in place from a `multipart/x-mixed-replace`
+//! response, such as MJPEG camera feeds.
+
+use std::sync::Arc;
+
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use rustkit_http::multipart::Part;
+use rustkit_http::HttpError;
+use tracing::debug;
+use url::Url;
+
+use crate::{ImageError, ImageManager, ImageResult, LoadedImage};
+
+/// A streamed image, open until it is dropped.
+pub struct ImageStream<'a> {
+    manager: &'a ImageManager,
+    url: Url,
+    parts: BoxStream<'static, Result<Part, HttpError>>,
+}
+
+impl ImageManager {
+    /// Open a `multipart/x-mixed-replace` image. Each part replaces the
+    /// cached image for `url`; dropping the stream closes the connection.
+    pub async fn open_stream(&self, url: Url) -> ImageResult<ImageStream<'_>> {
+        let response = self.client.get_streaming(url.as_str()).await?;
+        if !response.status.is_success() {
+            return Err(ImageError::FetchError(format!(
+                "HTTP {} for {}",
+                response.status, url
+            )));
+        }
+        Ok(ImageStream {
+            manager: self,
+            url,
+            parts: response.parts()?,
+        })
+    }
+}
+
+impl ImageStream<'_> {
+    /// URL of the stream.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Wait for the next frame and make it the cached image for the URL.
+    /// Frames that fail to decode are skipped. Returns `None` once the
+    /// server ends the stream.
+    pub async fn next_frame(&mut self) -> ImageResult<Option<Arc<LoadedImage>>> {
+        while let Some(part) = self.parts.next().await.transpose()? {
+            let mut image = match self.manager.decode_bytes(&self.url, &part.body) {
+                Ok(image) => image,
+                Err(e) => {
+                    debug!(url = %self.url, error = %e, "Skipping undecodable frame");
+                    continue;
+                }
+            };
+            image.content_type = part.content_type().map(str::to_string);
+            let image = Arc::new(image);
+            self.manager
+                .cache
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(self.url.clone(), image.clone());
+            return Ok(Some(image));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_codecs::RgbaImage;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_mjpeg_frames_replace_image() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (closed_tx, closed_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: multipart/x-mixed-replace; boundary=frame\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            for width in [4, 8, 12] {
+                let jpeg = rustkit_codecs::encode_jpeg(&RgbaImage::new(width, 6), 80).unwrap();
+                let head = format!(
+                    "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                    jpeg.len()
+                );
+                socket.write_all(head.as_bytes()).await.unwrap();
+                socket.write_all(&jpeg).await.unwrap();
+                socket.write_all(b"\r\n").await.unwrap();
+            }
+            // The feed never ends; the client hanging up is the only way out
+            let closed = tokio::time::timeout(Duration::from_secs(5), async {
+                let mut buf = [0u8; 64];
+                matches!(socket.read(&mut buf).await, Ok(0) | Err(_))
+            })
+            .await;
+            let _ = closed_tx.send(closed == Ok(true));
+        });
+
+        let manager = ImageManager::new();
+        let url = Url::parse(&format!("http://127.0.0.1:{}/camera", port)).unwrap();
+        let mut stream = manager.open_stream(url.clone()).await.unwrap();
+        let mut updates = Vec::new();
+        for _ in 0..3 {
+            let image = stream.next_frame().await.unwrap().unwrap();
+            updates.push((image.natural_width, image.natural_height));
+        }
+        assert_eq!(updates, [(4, 6), (8, 6), (12, 6)]);
+        assert_eq!(manager.get_cached(&url).unwrap().natural_width, 12);
+
+        drop(stream);
+        assert!(closed_rx.await.unwrap());
+    }
+}
@@ -10,6 +10,8 @@
 //! 4. **Async support**: Event loop integration
 
 use std::collections::HashMap;
+#[cfg(feature = "boa")]
+use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -85,6 +87,12 @@ pub enum LogLevel {
 /// Console output handler.
 pub type ConsoleHandler = Box<dyn Fn(LogLevel, &str) + Send + Sync>;
 
+/// Native function callable from scripts. Arguments are converted to strings.
+///
+/// Returning `JsError::TypeError` throws a `TypeError` in the calling script;
+/// other errors throw a plain `Error`.
+pub type NativeFunction = dyn Fn(&[String]) -> Result<JsValue, JsError>;
+
 /// Timer callback.
 pub type TimerCallback = Box<dyn FnOnce() + Send + 'static>;
 
@@ -105,10 +113,86 @@ pub struct JsRuntimeConfig {
     pub timeout: Option<Duration>,
 }
 
+/// Promise rejections seen by the engine's HostPromiseRejectionTracker
+/// hook since the last microtask checkpoint, kept in the context's data.
+#[cfg(feature = "boa")]
+#[derive(Default, boa_engine::JsData)]
+struct RejectionTracker {
+    /// Rejected with no handler attached.
+    rejected: boa_engine::gc::GcRefCell<Vec<boa_engine::JsObject>>,
+    /// Given a first handler after a checkpoint reported them rejected.
+    handled: boa_engine::gc::GcRefCell<Vec<boa_engine::JsObject>>,
+}
+
+#[cfg(feature = "boa")]
+impl RejectionTracker {
+    /// Take the rejected and the newly handled promises.
+    fn take(&self) -> (Vec<boa_engine::JsObject>, Vec<boa_engine::JsObject>) {
+        (
+            std::mem::take(&mut *self.rejected.borrow_mut()),
+            std::mem::take(&mut *self.handled.borrow_mut()),
+        )
+    }
+}
+
+#[cfg(feature = "boa")]
+impl boa_engine::gc::Finalize for RejectionTracker {}
+
+// SAFETY: both fields are traced.
+#[cfg(feature = "boa")]
+unsafe impl boa_engine::gc::Trace for RejectionTracker {
+    boa_engine::gc::custom_trace!(this, mark, {
+        mark(&this.rejected);
+        mark(&this.handled);
+    });
+}
+
+/// Host hooks of every runtime; each keeps its [`RejectionTracker`] in its
+/// context's data.
+#[cfg(feature = "boa")]
+struct RuntimeHooks;
+
+#[cfg(feature = "boa")]
+static RUNTIME_HOOKS: RuntimeHooks = RuntimeHooks;
+
+#[cfg(feature = "boa")]
+impl boa_engine::context::HostHooks for RuntimeHooks {
+    fn promise_rejection_tracker(
+        &self,
+        promise: &boa_engine::JsObject,
+        operation: boa_engine::builtins::promise::OperationType,
+        context: &mut boa_engine::Context,
+    ) {
+        use boa_engine::builtins::promise::OperationType;
+
+        let Some(tracker) = context.get_data::<RejectionTracker>() else {
+            return;
+        };
+        match operation {
+            OperationType::Reject => tracker.rejected.borrow_mut().push(promise.clone()),
+            OperationType::Handle => {
+                // Handled before anyone heard of it: nothing to report
+                let mut rejected = tracker.rejected.borrow_mut();
+                match rejected
+                    .iter()
+                    .position(|p| boa_engine::JsObject::equals(p, promise))
+                {
+                    Some(index) => {
+                        rejected.remove(index);
+                    }
+                    None => tracker.handled.borrow_mut().push(promise.clone()),
+                }
+            }
+        }
+    }
+}
+
 /// JavaScript runtime that wraps the underlying engine.
 pub struct JsRuntime {
     #[cfg(feature = "boa")]
     context: boa_engine::Context,
+    /// Global function told about promise rejections at each checkpoint.
+    rejection_callback: Option<String>,
     console_handler: Option<Arc<ConsoleHandler>>,
     timers: Arc<Mutex<HashMap<TimerId, PendingTimer>>>,
     globals: HashMap<String, JsValue>,
@@ -124,12 +208,21 @@ impl JsRuntime {
     pub fn with_config(_config: JsRuntimeConfig) -> Result<Self, JsError> {
         info!("Initializing JavaScript runtime");
 
+        // Promise reactions go through a real job queue so that `.then`
+        // callbacks run at the microtask checkpoint after each script.
         #[cfg(feature = "boa")]
-        let context = boa_engine::Context::default();
+        let mut context = boa_engine::Context::builder()
+            .job_queue(Rc::new(boa_engine::job::SimpleJobQueue::new()))
+            .host_hooks(&RUNTIME_HOOKS)
+            .build()
+            .map_err(|e| JsError::ExecutionError(e.to_string()))?;
+        #[cfg(feature = "boa")]
+        context.insert_data(RejectionTracker::default());
 
         let mut runtime = Self {
             #[cfg(feature = "boa")]
             context,
+            rejection_callback: None,
             console_handler: None,
             timers: Arc::new(Mutex::new(HashMap::new())),
             globals: HashMap::new(),
@@ -190,6 +283,8 @@ impl JsRuntime {
             use boa_engine::Source;
 
             let result = self.context.eval(Source::from_bytes(source));
+            self.context.run_jobs();
+            self.notify_rejections();
 
             match result {
                 Ok(value) => {
@@ -210,6 +305,96 @@ impl JsRuntime {
         }
     }
 
+    /// Evaluate JavaScript code with every loop capped at
+    /// `max_loop_iterations`, so a runaway script throws instead of hanging.
+    pub fn evaluate_script_bounded(
+        &mut self,
+        source: &str,
+        max_loop_iterations: u64,
+    ) -> Result<JsValue, JsError> {
+        #[cfg(feature = "boa")]
+        {
+            let limits = self.context.runtime_limits();
+            self.context
+                .runtime_limits_mut()
+                .set_loop_iteration_limit(max_loop_iterations);
+            let result = self.evaluate_script(source);
+            self.context.set_runtime_limits(limits);
+            result
+        }
+
+        #[cfg(not(feature = "boa"))]
+        {
+            let _ = max_loop_iterations;
+            self.evaluate_script(source)
+        }
+    }
+
+    /// Have the global function `name` told about promise rejections after
+    /// each microtask checkpoint, as `name(rejected, reasons, handled)`:
+    /// the promises rejected with no handler and their reasons, then the
+    /// previously reported promises that have since been given a handler.
+    pub fn set_rejection_callback(&mut self, name: &str) {
+        self.rejection_callback = Some(name.to_string());
+    }
+
+    /// Pass the rejections tracked since the last checkpoint to the
+    /// rejection callback. Rejections its own jobs cause are passed on in
+    /// the next round.
+    #[cfg(feature = "boa")]
+    fn notify_rejections(&mut self) {
+        use boa_engine::builtins::promise::PromiseState;
+        use boa_engine::object::builtins::{JsArray, JsPromise};
+        use boa_engine::JsString;
+
+        let take = |context: &boa_engine::Context| {
+            context
+                .get_data::<RejectionTracker>()
+                .map(RejectionTracker::take)
+                .unwrap_or_default()
+        };
+        let Some(name) = self.rejection_callback.clone() else {
+            take(&self.context);
+            return;
+        };
+        // Bounded, in case each round rejects another promise
+        for _ in 0..16 {
+            let (rejected, handled) = take(&self.context);
+            if rejected.is_empty() && handled.is_empty() {
+                return;
+            }
+            let context = &mut self.context;
+            let callback = match context
+                .global_object()
+                .get(JsString::from(name.as_str()), context)
+            {
+                Ok(boa_engine::JsValue::Object(callback)) if callback.is_callable() => callback,
+                _ => return,
+            };
+            let reasons: Vec<_> = rejected
+                .iter()
+                .map(
+                    |promise| match JsPromise::from_object(promise.clone()).map(|p| p.state()) {
+                        Ok(PromiseState::Rejected(reason)) => reason,
+                        _ => boa_engine::JsValue::undefined(),
+                    },
+                )
+                .collect();
+            let args = [
+                JsArray::from_iter(rejected.into_iter().map(Into::into), context).into(),
+                JsArray::from_iter(reasons, context).into(),
+                JsArray::from_iter(handled.into_iter().map(Into::into), context).into(),
+            ];
+            if let Err(err) = callback.call(&boa_engine::JsValue::undefined(), &args, context) {
+                debug!(error = %err, "Rejection callback failed");
+            }
+            context.run_jobs();
+        }
+    }
+
+    #[cfg(not(feature = "boa"))]
+    fn notify_rejections(&mut self) {}
+
     /// Flush console logs and call handler.
     fn flush_console_logs(&mut self) {
         if self.console_handler.is_none() {
@@ -264,6 +449,64 @@ impl JsRuntime {
         Ok(())
     }
 
+    /// Register a native Rust function as a global.
+    pub fn register_function<F>(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: F,
+    ) -> Result<(), JsError>
+    where
+        F: Fn(&[String]) -> Result<JsValue, JsError> + 'static,
+    {
+        trace!(name, arity, "Registering native function");
+
+        #[cfg(feature = "boa")]
+        {
+            use boa_engine::{JsNativeError, JsString, NativeFunction as BoaNativeFunction};
+
+            let function: std::rc::Rc<NativeFunction> = std::rc::Rc::new(function);
+            // SAFETY: the closure only captures an `Rc` of a Rust function,
+            // which holds no garbage-collected values.
+            let native = unsafe {
+                BoaNativeFunction::from_closure(move |_this, args, context| {
+                    let args = args
+                        .iter()
+                        .map(|arg| arg.to_string(context).map(|s| s.to_std_string_escaped()))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    match function(&args) {
+                        Ok(value) => Ok(match value {
+                            JsValue::Null => boa_engine::JsValue::null(),
+                            JsValue::Boolean(b) => boa_engine::JsValue::from(b),
+                            JsValue::Number(n) => boa_engine::JsValue::from(n),
+                            JsValue::String(s) => {
+                                boa_engine::JsValue::from(JsString::from(s.as_str()))
+                            }
+                            _ => boa_engine::JsValue::undefined(),
+                        }),
+                        Err(JsError::TypeError(msg)) => {
+                            Err(JsNativeError::typ().with_message(msg).into())
+                        }
+                        Err(err) => {
+                            Err(JsNativeError::error().with_message(err.to_string()).into())
+                        }
+                    }
+                })
+            };
+
+            self.context
+                .register_global_callable(JsString::from(name), arity, native)
+                .map_err(|e| JsError::ExecutionError(e.to_string()))
+        }
+
+        #[cfg(not(feature = "boa"))]
+        {
+            let _ = function;
+            Err(JsError::NotInitialized)
+        }
+    }
+
     /// Get a global variable.
     pub fn get_global(&mut self, name: &str) -> Result<JsValue, JsError> {
         self.evaluate_script(name)
@@ -278,7 +521,10 @@ impl JsRuntime {
             repeat: false,
         };
 
-        self.timers.lock().unwrap().insert(id, timer);
+        self.timers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, timer);
         trace!(?id, delay_ms, "Timeout scheduled");
         id
     }
@@ -292,20 +538,26 @@ impl JsRuntime {
             repeat: true,
         };
 
-        self.timers.lock().unwrap().insert(id, timer);
+        self.timers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, timer);
         trace!(?id, interval_ms, "Interval scheduled");
         id
     }
 
     /// Cancel a timeout or interval.
     pub fn clear_timer(&mut self, id: TimerId) {
-        self.timers.lock().unwrap().remove(&id);
+        self.timers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
         trace!(?id, "Timer cleared");
     }
 
     /// Get pending timers that are due.
     pub fn get_due_timers(&self) -> Vec<(TimerId, String, bool)> {
-        let timers = self.timers.lock().unwrap();
+        let timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
         timers
             .iter()
             .map(|(id, t)| (*id, t.callback.clone(), t.repeat))
@@ -315,7 +567,7 @@ impl JsRuntime {
     /// Execute a timer callback.
     pub fn execute_timer(&mut self, id: TimerId) -> Result<(), JsError> {
         let timer = {
-            let timers = self.timers.lock().unwrap();
+            let timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
             timers.get(&id).map(|t| (t.callback.clone(), t.repeat))
         };
 
@@ -323,7 +575,10 @@ impl JsRuntime {
             self.evaluate_script(&callback)?;
 
             if !repeat {
-                self.timers.lock().unwrap().remove(&id);
+                self.timers
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&id);
             }
         }
 
@@ -447,4 +702,74 @@ mod tests {
         let result = runtime.evaluate_script("nonexistent.property");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_native_function() {
+        let mut runtime = JsRuntime::new().unwrap();
+
+        runtime
+            .register_function("shout", 1, |args| match args.first() {
+                Some(s) if !s.is_empty() => Ok(JsValue::String(s.to_uppercase())),
+                _ => Err(JsError::TypeError("empty".into())),
+            })
+            .unwrap();
+
+        let result = runtime.evaluate_script("shout('hi')").unwrap();
+        assert!(matches!(result, JsValue::String(s) if s == "HI"));
+
+        let result = runtime
+            .evaluate_script("try { shout(''); 'no' } catch (e) { e instanceof TypeError }")
+            .unwrap();
+        assert!(matches!(result, JsValue::Boolean(true)));
+    }
+    #[test]
+    fn test_evaluate_script_bounded() {
+        let mut runtime = JsRuntime::new().unwrap();
+        assert!(runtime
+            .evaluate_script_bounded("while (true) {}", 1000)
+            .is_err());
+
+        // The limit only applies to the bounded call
+        let result = runtime
+            .evaluate_script("var n = 0; for (var i = 0; i < 5000; i++) { n++; } n")
+            .unwrap();
+        assert!(matches!(result, JsValue::Number(n) if n == 5000.0));
+    }
+
+    #[test]
+    fn test_rejection_callback_sees_unhandled_and_late_handled() {
+        let mut runtime = JsRuntime::new().unwrap();
+        runtime
+            .evaluate_script(
+                "var log = []; \
+                 function report(rejected, reasons, handled) { \
+                     log.push(rejected.length + ':' + reasons.join(',') + ':' + handled.length); \
+                 }",
+            )
+            .unwrap();
+        runtime.set_rejection_callback("report");
+
+        runtime
+            .evaluate_script(
+                "var late = Promise.reject('a'); \
+                 Promise.reject('b').catch(function() {});",
+            )
+            .unwrap();
+        runtime
+            .evaluate_script("late.catch(function() {});")
+            .unwrap();
+        let result = runtime.evaluate_script("log.join('|')").unwrap();
+        assert!(matches!(result, JsValue::String(s) if s == "1:a:0|0::1"));
+    }
+
+    #[test]
+    fn test_promise_jobs_run_after_script() {
+        let mut runtime = JsRuntime::new().unwrap();
+
+        runtime
+            .evaluate_script("var order = []; Promise.resolve(1).then(function() { order.push('job'); }); order.push('sync');")
+            .unwrap();
+        let result = runtime.evaluate_script("order.join(',')").unwrap();
+        assert!(matches!(result, JsValue::String(s) if s == "sync,job"));
+    }
 }
@@ -0,0 +1,161 @@
+//! Preferred aspect ratios and the sizes they transfer.
+//!
+//! A box keeps a ratio from `aspect-ratio`, or, for a replaced element, from
+//! its content's natural size. When one of its width and height is definite
+//! and the other `auto`, the auto one follows through the ratio, within its
+//! `min-*` and `max-*` limits. A replaced element with neither set takes its
+//! natural size.
+
+use rustkit_css::AspectRatio;
+
+/// Size of a replaced element when nothing else gives one, as for an
+/// object without natural dimensions.
+pub const DEFAULT_REPLACED_SIZE: (f32, f32) = (300.0, 150.0);
+
+/// Natural dimensions of a replaced element's content, any of which may be
+/// unknown, e.g. an image that hasn't loaded or an SVG with only a
+/// `viewBox`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NaturalSize {
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// Width over height, when known without both dimensions.
+    pub ratio: Option<f32>,
+}
+
+impl NaturalSize {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width: Some(width),
+            height: Some(height),
+            ratio: None,
+        }
+    }
+
+    /// The natural ratio, width over height.
+    pub fn ratio(&self) -> Option<f32> {
+        let ratio = match (self.width, self.height) {
+            (Some(width), Some(height)) if height > 0.0 => Some(width / height),
+            _ => self.ratio,
+        };
+        ratio.filter(|r| r.is_finite() && *r > 0.0)
+    }
+}
+
+/// The ratio a box's auto size follows, width over height. `auto` takes a
+/// replaced element's natural ratio, and `auto && <ratio>` falls back to
+/// the given one when it has none.
+pub fn preferred_ratio(aspect_ratio: AspectRatio, natural: Option<&NaturalSize>) -> Option<f32> {
+    let natural = natural.and_then(NaturalSize::ratio);
+    match aspect_ratio {
+        AspectRatio::Auto => natural,
+        AspectRatio::AutoOr(ratio) => natural.or(Some(ratio)),
+        AspectRatio::Ratio(ratio) => Some(ratio),
+    }
+}
+
+/// The height of a box `width` wide at `ratio`, within `min`..=`max`.
+pub fn height_for_width(width: f32, ratio: f32, min: f32, max: f32) -> f32 {
+    (width / ratio).min(max).max(min)
+}
+
+/// The width of a box `height` tall at `ratio`, within `min`..=`max`.
+pub fn width_for_height(height: f32, ratio: f32, min: f32, max: f32) -> f32 {
+    (height * ratio).min(max).max(min)
+}
+
+/// The content size of a replaced element given its definite `width` and
+/// `height`, `None` where `auto`. A missing one follows `ratio` from the
+/// other, else takes the natural one; with neither set the natural size
+/// applies, and what is still unknown comes from `default`.
+pub fn replaced_size(
+    width: Option<f32>,
+    height: Option<f32>,
+    natural: &NaturalSize,
+    ratio: Option<f32>,
+    default: (f32, f32),
+) -> (f32, f32) {
+    match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => {
+            let height = ratio.map(|r| width / r).or(natural.height);
+            (width, height.unwrap_or(default.1))
+        }
+        (None, Some(height)) => {
+            let width = ratio.map(|r| height * r).or(natural.width);
+            (width.unwrap_or(default.0), height)
+        }
+        (None, None) => match (natural.width, natural.height, ratio) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, Some(r)) => (width, width / r),
+            (None, Some(height), Some(r)) => (height * r, height),
+            (None, None, Some(r)) => (default.0, default.0 / r),
+            (width, height, _) => (width.unwrap_or(default.0), height.unwrap_or(default.1)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferred_ratio_sources() {
+        let photo = NaturalSize::new(400.0, 300.0);
+        assert_eq!(preferred_ratio(AspectRatio::Auto, None), None);
+        assert_eq!(
+            preferred_ratio(AspectRatio::Auto, Some(&photo)),
+            Some(4.0 / 3.0)
+        );
+        assert_eq!(
+            preferred_ratio(AspectRatio::AutoOr(2.0), Some(&photo)),
+            Some(4.0 / 3.0)
+        );
+        assert_eq!(
+            preferred_ratio(AspectRatio::AutoOr(2.0), Some(&NaturalSize::default())),
+            Some(2.0)
+        );
+        assert_eq!(
+            preferred_ratio(AspectRatio::Ratio(2.0), Some(&photo)),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn test_replaced_size_transfers_through_ratio() {
+        let photo = NaturalSize::new(400.0, 300.0);
+        let ratio = photo.ratio();
+        let default = DEFAULT_REPLACED_SIZE;
+        assert_eq!(
+            replaced_size(None, None, &photo, ratio, default),
+            (400.0, 300.0)
+        );
+        assert_eq!(
+            replaced_size(Some(200.0), None, &photo, ratio, default),
+            (200.0, 150.0)
+        );
+        assert_eq!(
+            replaced_size(None, Some(60.0), &photo, ratio, default),
+            (80.0, 60.0)
+        );
+
+        let vector = NaturalSize {
+            ratio: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            replaced_size(None, None, &vector, vector.ratio(), default),
+            (300.0, 150.0)
+        );
+        let unknown = NaturalSize::default();
+        assert_eq!(
+            replaced_size(Some(50.0), None, &unknown, None, default),
+            (50.0, 150.0)
+        );
+        assert_eq!(height_for_width(320.0, 16.0 / 9.0, 0.0, 100.0), 100.0);
+        assert_eq!(
+            width_for_height(90.0, 16.0 / 9.0, 0.0, f32::INFINITY),
+            160.0
+        );
+    }
+}
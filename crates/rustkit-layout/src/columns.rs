@@ -0,0 +1,432 @@
+//! Multi-column layout (`column-count`, `column-width`).
+//!
+//! The content is laid out once at the column width as one tall flow, then
+//! cut into columns and the pieces moved side by side. Cuts fall between
+//! the container's children, or between the children of a block that
+//! allows breaking inside it, so a line box or an unbreakable box is never
+//! split. Columns are balanced: the shortest column height that fits the
+//! flow into the column count wins.
+//!
+//! `column-span` is not supported; a spanning box stays in its column.
+
+use crate::{BoxType, Dimensions, Float, LayoutBox, Position, Rect};
+use rustkit_css::{BreakInside, ComputedStyle, Length};
+use std::ops::Range;
+
+/// Slack for comparing positions.
+const EPSILON: f32 = 0.01;
+
+/// Used column count, column width and gap for `available` pixels of
+/// content width. A `normal` gap is 1em.
+pub fn used_columns(style: &ComputedStyle, available: f32) -> (usize, f32, f32) {
+    let font_size = match style.font_size {
+        Length::Px(px) => px,
+        _ => 16.0,
+    };
+    let gap = match style.column_gap {
+        Length::Auto => font_size,
+        gap => gap.to_px(font_size, 16.0, available),
+    }
+    .max(0.0);
+    let width = style
+        .column_width
+        .map(|width| width.to_px(font_size, 16.0, available).max(1.0));
+
+    let count = match (style.column_count, width) {
+        (count, Some(width)) => {
+            let fit = ((available + gap) / (width + gap)).floor().max(1.0) as usize;
+            count.map_or(fit, |count| fit.min(count as usize))
+        }
+        (Some(count), None) => count as usize,
+        (None, None) => 1,
+    }
+    .max(1);
+    let width = ((available - gap * (count - 1) as f32) / count as f32).max(0.0);
+    (count, width, gap)
+}
+
+/// A stretch of the flow that goes into one column: some of the
+/// container's children, or some children of one child that is broken up.
+struct Piece {
+    children: Range<usize>,
+    inner: Option<Range<usize>>,
+    start: f32,
+    end: f32,
+}
+
+/// Lay out a multi-column block container.
+pub(crate) fn layout_multicol(container: &mut LayoutBox, containing_block: &Dimensions) {
+    container.calculate_block_width(containing_block);
+    container.calculate_block_position(containing_block);
+
+    let available = container.dimensions.content.width;
+    let (count, width, gap) = used_columns(&container.style, available);
+    container.dimensions.content.width = width;
+    container.layout_block_children();
+    container.dimensions.content.width = available;
+
+    let top = container.dimensions.content.y;
+    let pieces = pieces(&container.children);
+    container.column_boxes.clear();
+    if pieces.is_empty() {
+        container.calculate_block_height();
+        return;
+    }
+
+    let columns = balance(&pieces, count);
+    let extents = extents(&pieces, &columns);
+    let offset = |column: usize| (column as f32 * (width + gap), top - extents[column].0);
+
+    let mut old = std::mem::take(&mut container.children).into_iter();
+    let mut children = Vec::with_capacity(old.len());
+    let mut index = 0;
+    while index < pieces.len() {
+        let piece = &pieces[index];
+        if piece.inner.is_none() {
+            let (dx, dy) = offset(columns[index]);
+            for mut child in old.by_ref().take(piece.children.len()) {
+                child.translate(dx, dy);
+                children.push(child);
+            }
+            index += 1;
+            continue;
+        }
+        let end = index
+            + pieces[index..]
+                .iter()
+                .take_while(|p| p.children == piece.children)
+                .count();
+        let Some(parent) = old.next() else { break };
+        children.extend(fragment(
+            parent,
+            &pieces[index..end],
+            &columns[index..end],
+            offset,
+        ));
+        index = end;
+    }
+    container.children = children;
+
+    let height = extents
+        .iter()
+        .map(|(start, end)| end - start)
+        .fold(0.0, f32::max);
+    container.dimensions.content.height = height;
+    container.calculate_block_height();
+
+    let content = container.dimensions.content;
+    container.column_boxes = (0..extents.len())
+        .map(|column| {
+            Rect::new(
+                content.x + column as f32 * (width + gap),
+                content.y,
+                width,
+                content.height,
+            )
+        })
+        .collect();
+}
+
+pub(crate) fn in_flow(child: &LayoutBox) -> bool {
+    child.float == Float::None && !matches!(child.position, Position::Absolute | Position::Fixed)
+}
+
+/// Group children into runs that cannot be separated: boxes sharing a line,
+/// with out-of-flow boxes kept with what precedes them. Each run comes with
+/// its top and bottom.
+pub(crate) fn groups(children: &[LayoutBox]) -> Vec<(Range<usize>, f32, f32)> {
+    let mut groups: Vec<(Range<usize>, f32, f32)> = Vec::new();
+    for (i, child) in children.iter().enumerate() {
+        let margin_box = child.dimensions.margin_box();
+        let end = if in_flow(child) {
+            margin_box.bottom()
+        } else {
+            margin_box.y
+        };
+        match groups.last_mut() {
+            Some(group) if !in_flow(child) || margin_box.y < group.2 - EPSILON => {
+                group.0.end = i + 1;
+                group.2 = group.2.max(end);
+            }
+            _ => groups.push((i..i + 1, margin_box.y, end)),
+        }
+    }
+    groups
+}
+
+/// Whether a column break may fall between the children of `child`.
+fn breakable(child: &LayoutBox) -> bool {
+    child.style.break_inside == BreakInside::Auto
+        && matches!(child.box_type, BoxType::Block)
+        && !child.style.display.is_flex()
+        && !child.style.display.is_grid()
+        && !child.style.is_multicol()
+        && in_flow(child)
+        && groups(&child.children).len() > 1
+}
+
+/// The flow cut at every allowed break, one level deep.
+fn pieces(children: &[LayoutBox]) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    for (range, start, end) in groups(children) {
+        let child = &children[range.start];
+        if range.len() == 1 && breakable(child) {
+            // The first and last pieces carry the box's own top and bottom edges
+            let inner = groups(&child.children);
+            let last = inner.len() - 1;
+            for (i, (inner, inner_start, inner_end)) in inner.into_iter().enumerate() {
+                pieces.push(Piece {
+                    children: range.clone(),
+                    inner: Some(inner),
+                    start: if i == 0 { start } else { inner_start },
+                    end: if i == last { end } else { inner_end },
+                });
+            }
+        } else {
+            pieces.push(Piece {
+                children: range,
+                inner: None,
+                start,
+                end,
+            });
+        }
+    }
+    pieces
+}
+
+/// Column of each piece when columns are cut at `limit` pixels tall. A
+/// piece taller than the limit gets a column to itself, and the last
+/// column takes whatever is left.
+fn assign(pieces: &[Piece], count: usize, limit: f32) -> Vec<usize> {
+    let mut columns: Vec<usize> = Vec::with_capacity(pieces.len());
+    let (mut column, mut start) = (0, pieces[0].start);
+    for piece in pieces {
+        if !columns.is_empty() && column + 1 < count && piece.end - start > limit + EPSILON {
+            column += 1;
+            start = piece.start;
+        }
+        columns.push(column);
+    }
+    columns
+}
+
+/// Top and bottom of each column's content.
+fn extents(pieces: &[Piece], columns: &[usize]) -> Vec<(f32, f32)> {
+    let mut extents: Vec<(f32, f32)> = Vec::new();
+    for (piece, &column) in pieces.iter().zip(columns) {
+        match extents.get_mut(column) {
+            Some(extent) => extent.1 = extent.1.max(piece.end),
+            None => extents.push((piece.start, piece.end)),
+        }
+    }
+    extents
+}
+
+/// The columns of the shortest cut that fits into `count` columns.
+fn balance(pieces: &[Piece], count: usize) -> Vec<usize> {
+    let tallest = |limit: f32| {
+        extents(pieces, &assign(pieces, count, limit))
+            .iter()
+            .map(|(start, end)| end - start)
+            .fold(0.0, f32::max)
+    };
+    let bottom = pieces.iter().map(|p| p.end).fold(f32::MIN, f32::max);
+    let (mut low, mut high) = (0.0, bottom - pieces[0].start);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.0;
+        if tallest(mid) <= mid + EPSILON {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    assign(pieces, count, high)
+}
+
+/// Split a broken-up block wherever its pieces change column, and move
+/// each fragment into its column.
+fn fragment(
+    mut parent: LayoutBox,
+    pieces: &[Piece],
+    columns: &[usize],
+    offset: impl Fn(usize) -> (f32, f32),
+) -> Vec<LayoutBox> {
+    let mut fragments = Vec::new();
+    // From the end, so the indices of earlier children stay put
+    for i in (1..pieces.len()).rev() {
+        if columns[i] == columns[i - 1] {
+            continue;
+        }
+        let Some(inner) = &pieces[i].inner else {
+            continue;
+        };
+        let mut rest = split_off(&mut parent, inner.start);
+        let (dx, dy) = offset(columns[i]);
+        rest.translate(dx, dy);
+        fragments.push(rest);
+    }
+    let (dx, dy) = offset(columns[0]);
+    parent.translate(dx, dy);
+    fragments.push(parent);
+    fragments.reverse();
+    fragments
+}
+
+/// Move the children of `parent` from `at` on into a continuation box. The
+/// break drops the bottom edges of `parent` and the top edges of the
+/// continuation.
+fn split_off(parent: &mut LayoutBox, at: usize) -> LayoutBox {
+    let mut rest = LayoutBox::new(parent.box_type.clone(), parent.style.clone());
+    rest.node_id = parent.node_id;
+    rest.pseudo = parent.pseudo;
+    rest.position = parent.position;
+    rest.offsets = parent.offsets;
+    rest.clear = parent.clear;
+    rest.update_stacking_context();
+    rest.children = parent.children.split_off(at);
+
+    let d = &mut parent.dimensions;
+    let break_y = rest.children[0].dimensions.margin_box().y;
+    let bottom = d.content.y + d.content.height;
+    rest.dimensions = d.clone();
+    rest.dimensions.margin.top = 0.0;
+    rest.dimensions.border.top = 0.0;
+    rest.dimensions.padding.top = 0.0;
+    rest.dimensions.content.y = break_y;
+    rest.dimensions.content.height = (bottom - break_y).max(0.0);
+
+    d.margin.bottom = 0.0;
+    d.border.bottom = 0.0;
+    d.padding.bottom = 0.0;
+    d.content.height = (break_y - d.content.y).max(0.0);
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DisplayList;
+    use crate::DisplayCommand;
+    use rustkit_css::{Color, OutlineStyle};
+
+    fn block(height: f32) -> LayoutBox {
+        let style = ComputedStyle {
+            height: Length::Px(height),
+            ..ComputedStyle::new()
+        };
+        LayoutBox::new(BoxType::Block, style)
+    }
+
+    fn lay_out(style: ComputedStyle, children: Vec<LayoutBox>) -> LayoutBox {
+        let mut container = LayoutBox::new(BoxType::Block, style);
+        container.children = children;
+        let viewport = Dimensions {
+            content: Rect::new(0.0, 0.0, 620.0, 0.0),
+            ..Default::default()
+        };
+        container.layout(&viewport);
+        container
+    }
+
+    fn two_columns() -> ComputedStyle {
+        ComputedStyle {
+            column_count: Some(2),
+            column_gap: Length::Px(20.0),
+            ..ComputedStyle::new()
+        }
+    }
+
+    #[test]
+    fn test_used_columns() {
+        let style = ComputedStyle {
+            column_width: Some(Length::Px(200.0)),
+            ..ComputedStyle::new()
+        };
+        // 1em gap: three 200px columns need 632px
+        let (count, width, gap) = used_columns(&style, 620.0);
+        assert_eq!((count, gap), (2, 16.0));
+        assert!((width - 302.0).abs() < EPSILON);
+
+        let style = ComputedStyle {
+            column_count: Some(2),
+            ..style
+        };
+        assert_eq!(used_columns(&style, 1000.0).0, 2);
+    }
+
+    #[test]
+    fn test_paragraphs_balance_into_two_columns() {
+        let container = lay_out(two_columns(), (0..6).map(|_| block(50.0)).collect());
+
+        assert!((container.dimensions.content.height - 150.0).abs() < EPSILON);
+        for (i, child) in container.children.iter().enumerate() {
+            let rect = child.dimensions.content;
+            let (x, y) = if i < 3 {
+                (0.0, i as f32 * 50.0)
+            } else {
+                (320.0, (i - 3) as f32 * 50.0)
+            };
+            assert!((rect.x - x).abs() < EPSILON, "{} at {:?}", i, rect);
+            assert!((rect.y - y).abs() < EPSILON, "{} at {:?}", i, rect);
+            assert!((rect.width - 300.0).abs() < EPSILON);
+        }
+        assert_eq!(container.column_boxes.len(), 2);
+    }
+
+    #[test]
+    fn test_breaks_inside_blocks_unless_avoided() {
+        let section = |break_inside| {
+            let mut section = LayoutBox::new(
+                BoxType::Block,
+                ComputedStyle {
+                    break_inside,
+                    ..ComputedStyle::new()
+                },
+            );
+            section.children = (0..4).map(|_| block(50.0)).collect();
+            section
+        };
+
+        // One 200px section splits down the middle
+        let container = lay_out(two_columns(), vec![section(BreakInside::Auto)]);
+        assert_eq!(container.children.len(), 2);
+        assert!((container.dimensions.content.height - 100.0).abs() < EPSILON);
+        let second = &container.children[1];
+        assert!((second.dimensions.content.x - 320.0).abs() < EPSILON);
+        assert!(second.dimensions.content.y.abs() < EPSILON);
+        assert_eq!(second.children.len(), 2);
+
+        // Kept whole, it fills the first column alone
+        let container = lay_out(
+            two_columns(),
+            vec![section(BreakInside::Avoid), block(50.0)],
+        );
+        assert!((container.dimensions.content.height - 200.0).abs() < EPSILON);
+        assert!((container.children[1].dimensions.content.x - 320.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_rule_is_centered_in_gap() {
+        let style = ComputedStyle {
+            column_rule_width: Length::Px(2.0),
+            column_rule_style: OutlineStyle::Solid,
+            column_rule_color: Some(Color::new(255, 0, 0, 1.0)),
+            ..two_columns()
+        };
+        let container = lay_out(style, (0..4).map(|_| block(50.0)).collect());
+
+        let rules: Vec<_> = DisplayList::build(&container)
+            .commands
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::SolidColor(color, rect) if color.r == 255 => Some(rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rules.len(), 1);
+        // The gap runs from 300 to 320
+        assert!((rules[0].x - 309.0).abs() < EPSILON, "{:?}", rules[0]);
+        assert!((rules[0].width - 2.0).abs() < EPSILON);
+        assert!((rules[0].height - 100.0).abs() < EPSILON);
+    }
+}
@@ -0,0 +1,435 @@
+//! Viewport culling of display lists.
+//!
+//! A long page builds far more commands than a viewport shows. A
+//! [`Culler`] keeps the bounds of each command, taken once when it is
+//! made, and picks the ones that meet the viewport grown by
+//! [`CULL_MARGIN`]. The scrolled commands are indexed by horizontal bands
+//! of the document, so a scroll only re-tests commands in the bands the
+//! viewport's top and bottom edges swept over; the rest of the previous
+//! result stands. Fixed commands are culled against the viewport itself
+//! and are untouched by scrolling.
+//!
+//! Clips, stacking contexts and layers are kept while anything inside
+//! them is drawn and dropped, push and pop together, once nothing is.
+
+use std::collections::BTreeSet;
+
+use crate::{DisplayCommand, Rect};
+
+/// How far past the viewport commands are still drawn, in CSS pixels, so
+/// antialiased edges and estimated text bounds don't clip at its edges.
+pub const CULL_MARGIN: f32 = 64.0;
+
+/// Height of the bands commands are indexed by.
+const BAND_HEIGHT: f32 = 256.0;
+
+/// What a command does to the culled list.
+#[derive(Debug, Clone, Copy)]
+enum Extent {
+    /// Paints within a rectangle.
+    Bounded(Rect),
+    /// Paints somewhere unknown, so it is always drawn.
+    Unbounded,
+    /// Opens a clip, stacking context or layer.
+    Push,
+    /// Opens a layer that filters its backdrop within a rectangle, so it
+    /// paints there even with nothing drawn inside.
+    Backdrop(Rect),
+    /// Closes one.
+    Pop,
+}
+
+/// Commands that move together: the scrolled ones or the fixed ones.
+#[derive(Debug, Default)]
+struct Layer {
+    /// Top of the first band.
+    origin: f32,
+    /// Commands overlapping each band.
+    bands: Vec<Vec<usize>>,
+    /// Every command in the layer.
+    members: Vec<usize>,
+    /// The window the layer was last culled against.
+    window: Option<Rect>,
+    /// Commands meeting that window.
+    visible: BTreeSet<usize>,
+}
+
+impl Layer {
+    fn new(members: Vec<usize>, extents: &[Extent]) -> Self {
+        let rects = || {
+            members.iter().filter_map(|&index| match extents[index] {
+                Extent::Bounded(rect) => Some((index, rect)),
+                _ => None,
+            })
+        };
+        let origin = rects().map(|(_, r)| r.y).fold(f32::INFINITY, f32::min);
+        let mut layer = Layer {
+            origin: if origin.is_finite() { origin } else { 0.0 },
+            ..Self::default()
+        };
+        for (index, rect) in rects() {
+            let first = layer.band(rect.y);
+            let last = layer.band(rect.bottom());
+            if layer.bands.len() <= last {
+                layer.bands.resize(last + 1, Vec::new());
+            }
+            for band in &mut layer.bands[first..=last] {
+                band.push(index);
+            }
+        }
+        layer.members = members;
+        layer
+    }
+
+    /// The band holding `y`, at or below the origin.
+    fn band(&self, y: f32) -> usize {
+        ((y - self.origin) / BAND_HEIGHT).max(0.0) as usize
+    }
+
+    /// The commands in bands overlapping `top..=bottom`.
+    fn commands_between(&self, top: f32, bottom: f32) -> impl Iterator<Item = usize> + '_ {
+        let bands = if bottom < self.origin || self.bands.is_empty() {
+            0..0
+        } else {
+            self.band(top).min(self.bands.len())..(self.band(bottom) + 1).min(self.bands.len())
+        };
+        self.bands[bands].iter().flatten().copied()
+    }
+
+    /// Cull against `window`, re-testing only the commands whose
+    /// visibility a vertical move from the last window can change.
+    fn cull(&mut self, window: Rect, extents: &[Extent], recomputed: &mut usize) {
+        let mut test = |visible: &mut BTreeSet<usize>, index: usize| {
+            let Extent::Bounded(rect) = extents[index] else {
+                return;
+            };
+            *recomputed += 1;
+            if intersects(&rect, &window) {
+                visible.insert(index);
+            } else {
+                visible.remove(&index);
+            }
+        };
+        let last = match self.window {
+            Some(last)
+                if last.x == window.x
+                    && last.width == window.width
+                    && last.height == window.height =>
+            {
+                last
+            }
+            _ => {
+                self.visible.clear();
+                for &index in &self.members {
+                    test(&mut self.visible, index);
+                }
+                self.window = Some(window);
+                return;
+            }
+        };
+        if last.y == window.y {
+            return;
+        }
+        // Only commands the moving top or bottom edge passed over change
+        let (near, far) = (last.y.min(window.y), last.y.max(window.y));
+        let edges = [(near, far), (near + window.height, far + window.height)];
+        let retest: BTreeSet<usize> = edges
+            .iter()
+            .flat_map(|&(top, bottom)| self.commands_between(top, bottom))
+            .collect();
+        for index in retest {
+            test(&mut self.visible, index);
+        }
+        self.window = Some(window);
+    }
+}
+
+/// Picks the commands of a display list that a viewport shows.
+#[derive(Debug)]
+pub struct Culler {
+    extents: Vec<Extent>,
+    fixed: Vec<bool>,
+    /// Clip and stacking context commands, always considered.
+    brackets: Vec<usize>,
+    /// Commands drawn wherever the viewport is.
+    unbounded: Vec<usize>,
+    scrolled: Layer,
+    pinned: Layer,
+    recomputed: usize,
+}
+
+impl Culler {
+    /// Index `commands`, where `fixed[i]` says command `i` stays put when
+    /// the document scrolls.
+    pub fn new(commands: &[DisplayCommand], fixed: &[bool]) -> Self {
+        let extents: Vec<Extent> = commands
+            .iter()
+            .map(|command| match command {
+                DisplayCommand::PushClip(_)
+                | DisplayCommand::PushClipPath { .. }
+                | DisplayCommand::PushStackingContext { .. } => Extent::Push,
+                DisplayCommand::PushLayer {
+                    rect,
+                    backdrop_filter,
+                    ..
+                } => {
+                    if backdrop_filter.is_empty() {
+                        Extent::Push
+                    } else {
+                        Extent::Backdrop(*rect)
+                    }
+                }
+                DisplayCommand::PopClip
+                | DisplayCommand::PopStackingContext
+                | DisplayCommand::PopLayer => Extent::Pop,
+                _ => match command.bounds() {
+                    Some(rect)
+                        if [rect.x, rect.y, rect.width, rect.height]
+                            .iter()
+                            .all(|v| v.is_finite()) =>
+                    {
+                        Extent::Bounded(rect)
+                    }
+                    _ => Extent::Unbounded,
+                },
+            })
+            .collect();
+        let is_fixed = |index: usize| fixed.get(index).copied().unwrap_or(false);
+        let of_kind = |keep: fn(&Extent) -> bool| -> Vec<usize> {
+            (0..extents.len()).filter(|&i| keep(&extents[i])).collect()
+        };
+        let brackets = of_kind(|e| matches!(e, Extent::Push | Extent::Backdrop(_) | Extent::Pop));
+        let unbounded = of_kind(|e| matches!(e, Extent::Unbounded));
+        let (pinned, scrolled): (Vec<usize>, Vec<usize>) =
+            of_kind(|e| matches!(e, Extent::Bounded(_)))
+                .into_iter()
+                .partition(|&index| is_fixed(index));
+        Self {
+            scrolled: Layer::new(scrolled, &extents),
+            pinned: Layer::new(pinned, &extents),
+            fixed: (0..extents.len()).map(is_fixed).collect(),
+            extents,
+            brackets,
+            unbounded,
+            recomputed: 0,
+        }
+    }
+
+    /// Whether command `index` stays put when the document scrolls.
+    pub fn is_fixed(&self, index: usize) -> bool {
+        self.fixed.get(index).copied().unwrap_or(false)
+    }
+
+    /// The number of bounds tests culling has done, which a scroll keeps
+    /// to the commands near the viewport's edges.
+    pub fn recomputed(&self) -> usize {
+        self.recomputed
+    }
+
+    /// Indices of the commands to draw, in paint order, for a viewport of
+    /// `size` with the document scrolled by `scroll`.
+    pub fn cull(&mut self, scroll: (f32, f32), size: (f32, f32)) -> Vec<usize> {
+        let window = |x: f32, y: f32| {
+            Rect::new(
+                x - CULL_MARGIN,
+                y - CULL_MARGIN,
+                size.0 + 2.0 * CULL_MARGIN,
+                size.1 + 2.0 * CULL_MARGIN,
+            )
+        };
+        self.scrolled.cull(
+            window(scroll.0, scroll.1),
+            &self.extents,
+            &mut self.recomputed,
+        );
+        self.pinned
+            .cull(window(0.0, 0.0), &self.extents, &mut self.recomputed);
+
+        let mut indices: Vec<usize> = self
+            .scrolled
+            .visible
+            .iter()
+            .chain(&self.pinned.visible)
+            .chain(&self.brackets)
+            .chain(&self.unbounded)
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        self.balance(indices)
+    }
+
+    /// Drop the clip and stacking context pairs that no longer hold
+    /// anything drawn.
+    fn balance(&self, indices: Vec<usize>) -> Vec<usize> {
+        let mut drawn = Vec::with_capacity(indices.len());
+        // Where each open push is in `drawn`, and whether it holds anything
+        let mut open: Vec<(usize, bool)> = Vec::new();
+        for index in indices {
+            match self.extents[index] {
+                Extent::Push => {
+                    open.push((drawn.len(), false));
+                    drawn.push(index);
+                }
+                Extent::Backdrop(rect) => {
+                    let layer = if self.is_fixed(index) {
+                        &self.pinned
+                    } else {
+                        &self.scrolled
+                    };
+                    let shown = layer.window.is_some_and(|w| intersects(&rect, &w));
+                    open.push((drawn.len(), shown));
+                    drawn.push(index);
+                }
+                Extent::Pop => match open.pop() {
+                    Some((_, true)) | None => {
+                        drawn.push(index);
+                        if let Some((_, used)) = open.last_mut() {
+                            *used = true;
+                        }
+                    }
+                    // Everything after the push was an empty pair, too
+                    Some((at, false)) => drawn.truncate(at),
+                },
+                Extent::Bounded(_) | Extent::Unbounded => {
+                    drawn.push(index);
+                    if let Some((_, used)) = open.last_mut() {
+                        *used = true;
+                    }
+                }
+            }
+        }
+        drawn
+    }
+}
+
+/// Whether two rectangles meet, counting touching edges.
+fn intersects(a: &Rect, b: &Rect) -> bool {
+    a.x <= b.right() && a.right() >= b.x && a.y <= b.bottom() && a.bottom() >= b.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustkit_css::Color;
+
+    /// A column of `count` 20px rows.
+    fn rows(count: usize) -> Vec<DisplayCommand> {
+        (0..count)
+            .map(|i| {
+                DisplayCommand::SolidColor(
+                    Color::BLACK,
+                    Rect::new(0.0, i as f32 * 20.0, 100.0, 20.0),
+                )
+            })
+            .collect()
+    }
+
+    fn is_bracket(command: &DisplayCommand) -> bool {
+        command.bounds().is_none()
+    }
+
+    #[test]
+    fn test_culls_to_viewport() {
+        let commands = rows(10_000);
+        let mut culler = Culler::new(&commands, &[]);
+        let drawn = culler.cull((0.0, 1000.0), (800.0, 600.0));
+        // Rows meeting 936..=1664, the viewport grown by the margin
+        let expected: Vec<usize> = (46..=83).collect();
+        assert_eq!(drawn, expected);
+        assert_eq!(culler.recomputed(), 10_000);
+    }
+
+    #[test]
+    fn test_empty_pairs_dropped_and_balance_kept() {
+        let mut commands = Vec::new();
+        // A clip around rows far below the viewport
+        commands.push(DisplayCommand::PushClip(Rect::new(
+            0.0, 5000.0, 100.0, 100.0,
+        )));
+        commands.extend(rows(300).into_iter().skip(250));
+        commands.push(DisplayCommand::PopClip);
+        // A stacking context straddling the viewport's bottom edge
+        commands.push(DisplayCommand::PushStackingContext {
+            z_index: 1,
+            rect: Rect::zero(),
+        });
+        commands.push(DisplayCommand::PushClip(Rect::zero()));
+        commands.extend(rows(60).into_iter().skip(30));
+        commands.push(DisplayCommand::PopClip);
+        commands.push(DisplayCommand::PopStackingContext);
+
+        let mut culler = Culler::new(&commands, &[]);
+        let drawn: Vec<&DisplayCommand> = culler
+            .cull((0.0, 0.0), (800.0, 600.0))
+            .into_iter()
+            .map(|i| &commands[i])
+            .collect();
+        let mut depth = 0i32;
+        for command in &drawn {
+            match command {
+                DisplayCommand::PushClip(_) | DisplayCommand::PushStackingContext { .. } => {
+                    depth += 1
+                }
+                DisplayCommand::PopClip | DisplayCommand::PopStackingContext => depth -= 1,
+                _ => {}
+            }
+            assert!(depth >= 0);
+        }
+        assert_eq!(depth, 0);
+        // Only the straddling pairs survive, around the rows they show
+        // down to 664, the viewport's bottom grown by the margin
+        assert_eq!(drawn.iter().filter(|c| is_bracket(c)).count(), 4);
+        assert!(matches!(
+            drawn[0],
+            DisplayCommand::PushStackingContext { .. }
+        ));
+        assert_eq!(drawn.len(), 4 + (30..=33).count());
+    }
+
+    #[test]
+    fn test_backdrop_layers_kept_while_shown() {
+        let backdrop = |y: f32| DisplayCommand::PushLayer {
+            rect: Rect::new(0.0, y, 100.0, 100.0),
+            filter: Default::default(),
+            backdrop_filter: rustkit_css::FilterChain::parse("blur(4px)").unwrap(),
+        };
+        let commands = vec![
+            backdrop(100.0),
+            DisplayCommand::PopLayer,
+            backdrop(5000.0),
+            DisplayCommand::PopLayer,
+        ];
+        let mut culler = Culler::new(&commands, &[]);
+        assert_eq!(culler.cull((0.0, 0.0), (800.0, 600.0)), [0, 1]);
+        assert_eq!(culler.cull((0.0, 4800.0), (800.0, 600.0)), [2, 3]);
+    }
+
+    #[test]
+    fn test_scroll_reuses_previous_result() {
+        let commands = rows(10_000);
+        let mut culler = Culler::new(&commands, &[]);
+        culler.cull((0.0, 1000.0), (800.0, 600.0));
+        let before = culler.recomputed();
+
+        let drawn = culler.cull((0.0, 1010.0), (800.0, 600.0));
+        let retested = culler.recomputed() - before;
+        assert!(retested > 0 && retested < 100, "retested {retested}");
+        let mut fresh = Culler::new(&commands, &[]);
+        assert_eq!(drawn, fresh.cull((0.0, 1010.0), (800.0, 600.0)));
+    }
+
+    #[test]
+    fn test_fixed_commands_ignore_scroll() {
+        let mut commands = rows(100);
+        commands.push(DisplayCommand::SolidColor(
+            Color::BLACK,
+            Rect::new(0.0, 0.0, 800.0, 40.0),
+        ));
+        let mut fixed = vec![false; 100];
+        fixed.push(true);
+        let mut culler = Culler::new(&commands, &fixed);
+        let drawn = culler.cull((0.0, 1500.0), (800.0, 300.0));
+        assert_eq!(drawn.last(), Some(&100));
+        assert!(!drawn.contains(&0));
+    }
+}
@@ -85,6 +85,10 @@ pub struct FlexItem<'a> {
     /// Maximum cross size.
     pub max_cross_size: f32,
 
+    /// Cross size per pixel of main size, for an item without a cross size
+    /// whose aspect ratio sets it.
+    pub cross_per_main: Option<f32>,
+
     /// Align self value.
     pub align_self: AlignSelf,
 
@@ -282,15 +286,36 @@ fn create_flex_item<'a>(
         ),
     };
 
+    let (main_size, cross_size, min_main_size) = match main_axis {
+        Axis::Horizontal => (
+            layout_box.style.width,
+            layout_box.style.height,
+            layout_box.style.min_width,
+        ),
+        Axis::Vertical => (
+            layout_box.style.height,
+            layout_box.style.width,
+            layout_box.style.min_height,
+        ),
+    };
+    let is_auto = |length: Length| matches!(length, Length::Auto | Length::Zero);
+    let cross_per_main = layout_box.preferred_ratio().map(|ratio| match main_axis {
+        Axis::Horizontal => 1.0 / ratio,
+        Axis::Vertical => ratio,
+    });
+    // The main size a definite cross size gives through the ratio
+    let transferred_main = match (cross_per_main, cross_size) {
+        (Some(per_main), Length::Px(cross)) => Some(cross / per_main),
+        _ => None,
+    };
+
     // Calculate flex basis
     let flex_basis = match flex_basis_value {
-        FlexBasis::Auto => {
+        FlexBasis::Auto => match transferred_main {
+            Some(transferred) if is_auto(main_size) => transferred,
             // Use main size property
-            match main_axis {
-                Axis::Horizontal => resolve_length(&layout_box.style.width, container_main),
-                Axis::Vertical => resolve_length(&layout_box.style.height, container_main),
-            }
-        }
+            _ => resolve_length(&main_size, container_main),
+        },
         FlexBasis::Content => {
             // Use content size (simplified - would need actual content measurement)
             0.0
@@ -315,6 +340,24 @@ fn create_flex_item<'a>(
         ),
     };
 
+    // The automatic minimum of an item with a ratio is its transferred
+    // size, but no more than its main size
+    let min_main = match transferred_main {
+        Some(transferred)
+            if is_auto(min_main_size)
+                && !layout_box.style.overflow_x.clips_content()
+                && !layout_box.style.overflow_y.clips_content() =>
+        {
+            let specified =
+                (!is_auto(main_size)).then(|| resolve_length(&main_size, container_main));
+            transferred
+                .min(specified.unwrap_or(f32::INFINITY))
+                .min(max_main)
+                .max(min_main)
+        }
+        _ => min_main,
+    };
+
     // Hypothetical main size (clamped)
     let hypothetical_main_size = flex_basis.max(min_main).min(max_main);
 
@@ -334,6 +377,7 @@ fn create_flex_item<'a>(
         max_main_size: max_main,
         min_cross_size: min_cross,
         max_cross_size: max_cross,
+        cross_per_main: cross_per_main.filter(|_| is_auto(cross_size)),
         align_self,
         main_margin_start,
         main_margin_end,
@@ -497,6 +541,8 @@ fn calculate_cross_sizes(line: &mut FlexLine, container_cross: f32, align_items:
         if align == AlignItems::Stretch {
             // Stretch to fill line (will be adjusted later)
             item.cross_size = container_cross - item.cross_margin_start - item.cross_margin_end;
+        } else if let Some(per_main) = item.cross_per_main {
+            item.cross_size = item.target_main_size * per_main;
         } else {
             // Use hypothetical cross size (content-based)
             // For simplicity, use min_cross_size as a placeholder
@@ -717,13 +763,15 @@ fn resolve_length(length: &Length, container_size: f32) -> f32 {
         Length::Percent(pct) => pct / 100.0 * container_size,
         Length::Auto => 0.0,
         Length::Zero => 0.0,
+        Length::Calc(calc) => calc.to_px(16.0, 16.0, container_size),
     }
 }
 
-/// Resolve a max Length (returns f32::INFINITY for Auto).
+/// Resolve a max Length (returns f32::INFINITY for Auto, and for the zero
+/// an unset maximum starts as).
 fn resolve_max_length(length: &Length, container_size: f32) -> f32 {
     match length {
-        Length::Auto => f32::INFINITY,
+        Length::Auto | Length::Zero => f32::INFINITY,
         _ => resolve_length(length, container_size),
     }
 }
@@ -789,6 +837,42 @@ mod tests {
         assert_eq!(container.children.len(), 2);
     }
 
+    #[test]
+    fn test_aspect_ratio_sets_cross_size_and_basis() {
+        let mut style = ComputedStyle::new();
+        style.display = rustkit_css::Display::Flex;
+        style.align_items = AlignItems::FlexStart;
+        let mut container = LayoutBox::new(BoxType::Block, style);
+
+        let mut wide = ComputedStyle::new();
+        wide.width = Length::Px(160.0);
+        wide.aspect_ratio = rustkit_css::AspectRatio::Ratio(2.0);
+        container
+            .children
+            .push(LayoutBox::new(BoxType::Block, wide));
+
+        let mut tall = ComputedStyle::new();
+        tall.width = Length::Auto;
+        tall.height = Length::Px(60.0);
+        tall.aspect_ratio = rustkit_css::AspectRatio::Ratio(0.5);
+        container
+            .children
+            .push(LayoutBox::new(BoxType::Block, tall));
+
+        let containing = Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 300.0),
+            ..Default::default()
+        };
+        layout_flex_container(&mut container, &containing);
+
+        let size = |i: usize| {
+            let content = container.children[i].dimensions.content;
+            (content.width, content.height)
+        };
+        assert_eq!(size(0), (160.0, 80.0));
+        assert_eq!(size(1).0, 30.0);
+    }
+
     #[test]
     fn test_flex_grow() {
         let mut style = ComputedStyle::new();
@@ -439,6 +439,142 @@ pub fn render_radio(border_box: Rect, checked: bool, state: InputState) -> Vec<D
     commands
 }
 
+/// Part of a media control bar under the pointer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaControlHit {
+    /// The play/pause button.
+    PlayPause,
+    /// The progress track, at this fraction of the duration.
+    Seek(f32),
+}
+
+/// Size of the play/pause button of a media control bar.
+const MEDIA_BUTTON_SIZE: f32 = 32.0;
+/// Width reserved for the time text, enough for "00:00 / 00:00".
+const MEDIA_TIME_WIDTH: f32 = 84.0;
+const MEDIA_PADDING: f32 = 11.0;
+
+/// The button and the progress track of a media control bar.
+fn media_control_parts(border_box: Rect) -> (Rect, Rect) {
+    let button = Rect::new(
+        border_box.x + MEDIA_PADDING,
+        border_box.y + (border_box.height - MEDIA_BUTTON_SIZE) / 2.0,
+        MEDIA_BUTTON_SIZE,
+        MEDIA_BUTTON_SIZE,
+    );
+    let track_x = button.x + button.width + 8.0 + MEDIA_TIME_WIDTH + 8.0;
+    let track = Rect::new(
+        track_x,
+        border_box.y + border_box.height / 2.0 - 2.0,
+        (border_box.x + border_box.width - MEDIA_PADDING - track_x).max(0.0),
+        4.0,
+    );
+    (button, track)
+}
+
+/// Format seconds as `m:ss`, or `h:mm:ss` from an hour on.
+pub fn format_media_time(seconds: f64) -> String {
+    let total = if seconds.is_finite() {
+        seconds.max(0.0) as u64
+    } else {
+        0
+    };
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Generate display commands for the control bar of a media element:
+/// a play/pause button, the time, and a progress track.
+pub fn render_media_controls(
+    border_box: Rect,
+    playing: bool,
+    current_time: f64,
+    duration: Option<f64>,
+) -> Vec<DisplayCommand> {
+    let mut commands = Vec::new();
+    let (button, track) = media_control_parts(border_box);
+    let foreground = Color::from_rgb(32, 33, 36);
+
+    commands.push(DisplayCommand::SolidColor(
+        Color::from_rgb(241, 243, 244),
+        border_box,
+    ));
+
+    // Pause bars while playing, a play triangle otherwise
+    let (cx, cy) = (
+        button.x + button.width / 2.0,
+        button.y + button.height / 2.0,
+    );
+    if playing {
+        for x in [cx - 5.0, cx + 2.0] {
+            commands.push(DisplayCommand::FillRect {
+                rect: Rect::new(x, cy - 7.0, 3.0, 14.0),
+                color: foreground,
+            });
+        }
+    } else {
+        commands.push(DisplayCommand::FillPolygon {
+            points: vec![(cx - 4.0, cy - 7.0), (cx + 7.0, cy), (cx - 4.0, cy + 7.0)],
+            color: foreground,
+        });
+    }
+
+    let font_size = 13.0;
+    commands.push(DisplayCommand::Text {
+        text: format!(
+            "{} / {}",
+            format_media_time(current_time),
+            format_media_time(duration.unwrap_or(0.0))
+        ),
+        x: button.x + button.width + 8.0,
+        y: border_box.y + (border_box.height + font_size) / 2.0 - 2.0,
+        color: foreground,
+        font_size,
+        font_family: "sans-serif".to_string(),
+        font_weight: 400,
+        font_style: 0,
+    });
+
+    commands.push(DisplayCommand::FillRect {
+        rect: track,
+        color: Color::from_rgb(190, 193, 197),
+    });
+    let progress = match duration {
+        Some(duration) if duration > 0.0 => (current_time / duration).clamp(0.0, 1.0) as f32,
+        _ => 0.0,
+    };
+    if progress > 0.0 {
+        commands.push(DisplayCommand::FillRect {
+            rect: Rect::new(track.x, track.y, track.width * progress, track.height),
+            color: foreground,
+        });
+    }
+
+    commands
+}
+
+/// The part of a media control bar at (`x`, `y`), if any.
+pub fn media_control_hit(border_box: Rect, x: f32, y: f32) -> Option<MediaControlHit> {
+    let (button, track) = media_control_parts(border_box);
+    let inside = |rect: Rect, slop: f32| {
+        x >= rect.x
+            && x <= rect.x + rect.width
+            && y >= rect.y - slop
+            && y <= rect.y + rect.height + slop
+    };
+    if inside(button, 0.0) {
+        Some(MediaControlHit::PlayPause)
+    } else if track.width > 0.0 && inside(track, 8.0) {
+        Some(MediaControlHit::Seek((x - track.x) / track.width))
+    } else {
+        None
+    }
+}
+
 /// Lighten a color by a factor (0.0 - 1.0).
 fn lighten_color(color: &Color, factor: f32) -> Color {
     let factor = factor.clamp(0.0, 1.0);
@@ -511,6 +647,31 @@ mod tests {
         assert!(indeterminate.len() > unchecked.len());
     }
 
+    #[test]
+    fn test_media_controls() {
+        let bar = Rect::new(0.0, 0.0, 300.0, 54.0);
+        let paused = render_media_controls(bar, false, 5.0, Some(70.0));
+        assert!(paused.iter().any(|c| matches!(
+            c,
+            DisplayCommand::Text { text, .. } if text == "0:05 / 1:10"
+        )));
+        assert!(paused
+            .iter()
+            .any(|c| matches!(c, DisplayCommand::FillPolygon { .. })));
+        assert_eq!(format_media_time(3725.0), "1:02:05");
+
+        assert_eq!(
+            media_control_hit(bar, 27.0, 27.0),
+            Some(MediaControlHit::PlayPause)
+        );
+        let (_, track) = media_control_parts(bar);
+        assert_eq!(
+            media_control_hit(bar, track.x + track.width / 4.0, 27.0),
+            Some(MediaControlHit::Seek(0.25))
+        );
+        assert_eq!(media_control_hit(bar, 100.0, 27.0), None);
+    }
+
     #[test]
     fn test_lighten_color() {
         let color = Color::from_rgb(100, 100, 100);
@@ -0,0 +1,238 @@
+//! Generated content: CSS counters and `::before`/`::after` boxes.
+//!
+//! Counters are processed in document order. A counter created by
+//! `counter-reset` on an element is in scope for that element, its following
+//! siblings and all their descendants; `counter-increment` without a counter
+//! in scope instantiates one on the element itself.
+
+use crate::{BoxType, LayoutBox};
+use rustkit_css::{ComputedStyle, ContentItem, Display, PseudoElement};
+
+/// A counter instance and the tree depth of the element that created it.
+#[derive(Debug, Clone)]
+struct Counter {
+    name: String,
+    value: i32,
+    depth: usize,
+}
+
+/// The counters in scope at the current point of a document-order walk.
+#[derive(Debug, Default)]
+pub struct CounterScopes {
+    counters: Vec<Counter>,
+}
+
+impl CounterScopes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enter an element at `depth`, applying its `counter-reset` and then
+    /// its `counter-increment`.
+    ///
+    /// Elements must be entered in document order; a `::before` box counts
+    /// as the first child of its element and `::after` as the last.
+    pub fn enter(&mut self, depth: usize, style: &ComputedStyle) {
+        // Counters created deeper than this element went out of scope when
+        // their parent ended
+        self.counters.retain(|c| c.depth <= depth);
+
+        for (name, value) in &style.counter_reset {
+            // A reset on a following sibling replaces the sibling's counter
+            if let Some(index) = self.innermost_index(name) {
+                if self.counters[index].depth == depth {
+                    self.counters.remove(index);
+                }
+            }
+            self.counters.push(Counter {
+                name: name.clone(),
+                value: *value,
+                depth,
+            });
+        }
+
+        for (name, amount) in &style.counter_increment {
+            let index = self.innermost_index(name).unwrap_or_else(|| {
+                self.counters.push(Counter {
+                    name: name.clone(),
+                    value: 0,
+                    depth,
+                });
+                self.counters.len() - 1
+            });
+            let counter = &mut self.counters[index];
+            counter.value = counter.value.wrapping_add(*amount);
+        }
+    }
+
+    /// The value of the innermost counter called `name`, or 0.
+    pub fn value(&self, name: &str) -> i32 {
+        self.innermost_index(name)
+            .map_or(0, |index| self.counters[index].value)
+    }
+
+    /// The values of all nested counters called `name`, outermost first.
+    pub fn values(&self, name: &str) -> Vec<i32> {
+        self.counters
+            .iter()
+            .filter(|c| c.name == name)
+            .map(|c| c.value)
+            .collect()
+    }
+
+    /// Resolve a `content` value to text. `attr` looks up attributes of the
+    /// originating element.
+    pub fn resolve_content(
+        &self,
+        items: &[ContentItem],
+        attr: impl Fn(&str) -> Option<String>,
+    ) -> String {
+        let mut text = String::new();
+        for item in items {
+            match item {
+                ContentItem::String(s) => text.push_str(s),
+                ContentItem::Attr(name) => text.push_str(&attr(name).unwrap_or_default()),
+                ContentItem::Counter { name, style } => {
+                    text.push_str(&style.format(self.value(name)));
+                }
+                ContentItem::Counters {
+                    name,
+                    separator,
+                    style,
+                } => {
+                    let mut values = self.values(name);
+                    if values.is_empty() {
+                        values.push(0);
+                    }
+                    let formatted: Vec<_> = values.into_iter().map(|v| style.format(v)).collect();
+                    text.push_str(&formatted.join(separator));
+                }
+                ContentItem::OpenQuote => text.push('\u{201C}'),
+                ContentItem::CloseQuote => text.push('\u{201D}'),
+            }
+        }
+        text
+    }
+
+    fn innermost_index(&self, name: &str) -> Option<usize> {
+        self.counters.iter().rposition(|c| c.name == name)
+    }
+}
+
+/// Build the box for a `::before` or `::after` pseudo-element.
+///
+/// The box is inline unless `style.display` is block-level or
+/// `inline-block`, and holds the resolved text in a single text run.
+pub fn generated_box(pseudo: PseudoElement, style: ComputedStyle, text: String) -> LayoutBox {
+    let box_type = match style.display {
+        Display::Block | Display::FlowRoot | Display::Flex | Display::Grid => BoxType::Block,
+        Display::InlineBlock => BoxType::InlineBlock,
+        _ => BoxType::Inline,
+    };
+    let text_style = ComputedStyle::inherit_from(&style);
+    let mut generated = LayoutBox::new(box_type, style);
+    generated.pseudo = Some(pseudo);
+    if !text.is_empty() {
+        generated
+            .children
+            .push(LayoutBox::new(BoxType::Text(text), text_style));
+    }
+    generated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rect;
+    use rustkit_css::{parse_content, parse_counter_list};
+
+    fn style(reset: &str, increment: &str) -> ComputedStyle {
+        ComputedStyle {
+            counter_reset: parse_counter_list(reset, 0),
+            counter_increment: parse_counter_list(increment, 1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sibling_counters() {
+        let mut counters = CounterScopes::new();
+        let content = parse_content(r#"counter(sec) ". ""#).unwrap();
+        let mut labels = Vec::new();
+        counters.enter(1, &style("sec", ""));
+        for _ in 0..3 {
+            counters.enter(2, &style("", "sec"));
+            labels.push(counters.resolve_content(&content, |_| None));
+        }
+        assert_eq!(labels, ["1. ", "2. ", "3. "]);
+    }
+
+    #[test]
+    fn test_nested_list_counters() {
+        // <ol><li><ol><li/><li/></ol></li><li/></ol>
+        let mut counters = CounterScopes::new();
+        let content = parse_content(r#"counters(item, ".")"#).unwrap();
+        let label = |counters: &CounterScopes| counters.resolve_content(&content, |_| None);
+
+        counters.enter(0, &style("item", ""));
+        counters.enter(1, &style("", "item"));
+        assert_eq!(label(&counters), "1");
+        counters.enter(2, &style("item", ""));
+        counters.enter(3, &style("", "item"));
+        assert_eq!(label(&counters), "1.1");
+        counters.enter(3, &style("", "item"));
+        assert_eq!(label(&counters), "1.2");
+        // Leaving the inner list drops its counter
+        counters.enter(1, &style("", "item"));
+        assert_eq!(label(&counters), "2");
+    }
+
+    #[test]
+    fn test_sibling_reset_replaces_counter() {
+        let mut counters = CounterScopes::new();
+        counters.enter(1, &style("c 5", ""));
+        counters.enter(1, &style("c", "c"));
+        assert_eq!(counters.values("c"), [1]);
+    }
+
+    #[test]
+    fn test_attr_and_quotes() {
+        let counters = CounterScopes::new();
+        let content = parse_content(r#"open-quote attr(data-label) close-quote"#).unwrap();
+        let text = counters.resolve_content(&content, |name| {
+            (name == "data-label").then(|| "Note".to_string())
+        });
+        assert_eq!(text, "\u{201C}Note\u{201D}");
+    }
+
+    #[test]
+    fn test_generated_box_is_inline_by_default() {
+        let style = ComputedStyle {
+            display: Display::Inline,
+            ..Default::default()
+        };
+        let generated = generated_box(PseudoElement::Before, style, "§".into());
+        assert!(matches!(generated.box_type, BoxType::Inline));
+        assert_eq!(generated.pseudo, Some(PseudoElement::Before));
+        assert!(generated.node_id.is_none());
+        assert!(matches!(&generated.children[0].box_type, BoxType::Text(t) if t == "§"));
+    }
+
+    #[test]
+    fn test_generated_box_hit_tests_to_element() {
+        let element_id = rustkit_dom::NodeId::new(7);
+        let mut element =
+            LayoutBox::new(BoxType::Block, ComputedStyle::new()).with_node_id(element_id);
+        element.dimensions.content = Rect::new(0.0, 0.0, 200.0, 20.0);
+        let mut before = generated_box(PseudoElement::Before, ComputedStyle::new(), "1. ".into());
+        before.dimensions.content = Rect::new(0.0, 0.0, 30.0, 20.0);
+        element.children.push(before);
+
+        let hit = element.hit_test(10.0, 10.0).unwrap();
+        assert_eq!(hit.node_id, Some(element_id));
+
+        let mut rects = std::collections::HashMap::new();
+        element.collect_node_rects(&mut rects);
+        assert_eq!(rects.len(), 1);
+    }
+}
@@ -606,6 +606,26 @@ pub fn layout_grid_container(
                 child,
             );
 
+            // An item with a ratio and an auto height keeps its ratio
+            // rather than stretching, unless told to stretch
+            let (y, height) = match child.preferred_ratio() {
+                Some(ratio)
+                    if matches!(child.style.height, Length::Auto | Length::Zero)
+                        && child.style.align_self == AlignSelf::Auto =>
+                {
+                    let (min, max) = child.size_limits(
+                        child.style.min_height,
+                        child.style.max_height,
+                        Some(rect.height),
+                    );
+                    (
+                        rect.y,
+                        crate::aspect_ratio::height_for_width(width, ratio, min, max),
+                    )
+                }
+                _ => (y, height),
+            };
+
             child.dimensions.content.x = x;
             child.dimensions.content.y = y;
             child.dimensions.content.width = width;
@@ -708,6 +728,7 @@ fn apply_justify_self(
         Length::Auto => cell_width,
         Length::Px(w) => w,
         Length::Percent(p) => cell_width * p / 100.0,
+        Length::Calc(calc) => calc.to_px(16.0, 16.0, cell_width),
         _ => cell_width,
     };
 
@@ -742,6 +763,7 @@ fn apply_align_self(
         Length::Auto => cell_height,
         Length::Px(h) => h,
         Length::Percent(p) => cell_height * p / 100.0,
+        Length::Calc(calc) => calc.to_px(16.0, 16.0, cell_height),
         _ => cell_height,
     };
 
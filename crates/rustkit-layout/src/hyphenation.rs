@@ -0,0 +1,196 @@
+//! Automatic hyphenation with Knuth-Liang patterns.
+//!
+//! Each language is a pattern file in the TeX `\patterns` format, where
+//! digits between letters score the gap they sit in and odd scores allow
+//! a hyphen, plus a list of exception words spelled with their hyphens.
+//! Languages are looked up by the primary subtag of the content language,
+//! so `en`, `en-US` and `en-GB` all share the English patterns. Adding a
+//! language is a matter of adding its files to [`LANGUAGES`].
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A bundled language: primary subtag, patterns, exceptions, and the
+/// fewest letters kept before and after a hyphen.
+struct Language {
+    tag: &'static str,
+    patterns: &'static str,
+    exceptions: &'static str,
+    left_min: usize,
+    right_min: usize,
+}
+
+const LANGUAGES: &[Language] = &[Language {
+    tag: "en",
+    patterns: include_str!("hyphenation/en-us.pat"),
+    exceptions: include_str!("hyphenation/en-us.hyp"),
+    left_min: 2,
+    right_min: 3,
+}];
+
+/// Hyphenation patterns of one language.
+#[derive(Debug, Default)]
+pub struct Hyphenator {
+    /// Letters of each pattern and the score of each of its gaps.
+    patterns: HashMap<String, Vec<u8>>,
+    /// Words and the letter counts they break after.
+    exceptions: HashMap<String, Vec<usize>>,
+    longest_pattern: usize,
+    left_min: usize,
+    right_min: usize,
+}
+
+impl Hyphenator {
+    /// Build a hyphenator from whitespace-separated `patterns` such as
+    /// `.ach4` or `hy3ph`, and `exceptions` such as `ta-ble`. Lines
+    /// starting with `%` are comments.
+    pub fn parse(patterns: &str, exceptions: &str, left_min: usize, right_min: usize) -> Self {
+        let mut hyphenator = Self {
+            left_min,
+            right_min,
+            ..Self::default()
+        };
+        for pattern in words(patterns) {
+            let mut letters = String::new();
+            let mut scores = vec![0];
+            for c in pattern.chars() {
+                match c.to_digit(10) {
+                    Some(score) => *scores.last_mut().unwrap() = score as u8,
+                    None => {
+                        letters.push(c);
+                        scores.push(0);
+                    }
+                }
+            }
+            hyphenator.longest_pattern = hyphenator.longest_pattern.max(letters.chars().count());
+            hyphenator.patterns.insert(letters, scores);
+        }
+        for word in words(exceptions) {
+            let mut breaks = Vec::new();
+            let mut letters = 0;
+            for c in word.chars() {
+                if c == '-' {
+                    breaks.push(letters);
+                } else {
+                    letters += 1;
+                }
+            }
+            hyphenator.exceptions.insert(word.replace('-', ""), breaks);
+        }
+        hyphenator
+    }
+
+    /// The hyphenator for content language `lang`, such as `en-US`, if
+    /// its patterns are bundled.
+    pub fn for_language(lang: &str) -> Option<&'static Hyphenator> {
+        static LOADED: OnceLock<Vec<OnceLock<Hyphenator>>> = OnceLock::new();
+        let primary = lang.split(['-', '_']).next()?.to_ascii_lowercase();
+        let index = LANGUAGES.iter().position(|l| l.tag == primary)?;
+        let loaded = LOADED.get_or_init(|| LANGUAGES.iter().map(|_| OnceLock::new()).collect());
+        Some(loaded[index].get_or_init(|| {
+            let language = &LANGUAGES[index];
+            Self::parse(
+                language.patterns,
+                language.exceptions,
+                language.left_min,
+                language.right_min,
+            )
+        }))
+    }
+
+    /// Byte offsets in `word` where it may be hyphenated. Words with
+    /// anything but letters are left whole.
+    pub fn break_points(&self, word: &str) -> Vec<usize> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < self.left_min + self.right_min || !chars.iter().all(|c| c.is_alphabetic())
+        {
+            return Vec::new();
+        }
+        let lower: String = word.to_lowercase();
+        let letters: Vec<char> = lower.chars().collect();
+        if letters.len() != chars.len() {
+            return Vec::new();
+        }
+
+        // Letter counts a hyphen may follow
+        let allowed: Vec<usize> = match self.exceptions.get(&lower) {
+            Some(breaks) => breaks.clone(),
+            None => {
+                let dotted: Vec<char> = std::iter::once('.')
+                    .chain(letters.iter().copied())
+                    .chain(std::iter::once('.'))
+                    .collect();
+                // scores[i] is the gap before dotted[i]
+                let mut scores = vec![0u8; dotted.len() + 1];
+                for start in 0..dotted.len() {
+                    let end_max = (start + self.longest_pattern).min(dotted.len());
+                    for end in start + 1..=end_max {
+                        let key: String = dotted[start..end].iter().collect();
+                        if let Some(pattern) = self.patterns.get(&key) {
+                            for (offset, &score) in pattern.iter().enumerate() {
+                                let gap = &mut scores[start + offset];
+                                *gap = (*gap).max(score);
+                            }
+                        }
+                    }
+                }
+                // The gap after `n` letters is before dotted[n + 1]
+                (1..letters.len())
+                    .filter(|&n| scores[n + 1] % 2 == 1)
+                    .collect()
+            }
+        };
+
+        let offsets: Vec<usize> = word.char_indices().map(|(i, _)| i).collect();
+        allowed
+            .into_iter()
+            .filter(|&n| n >= self.left_min && n + self.right_min <= chars.len())
+            .map(|n| offsets[n])
+            .collect()
+    }
+}
+
+fn words(list: &str) -> impl Iterator<Item = &str> {
+    list.lines()
+        .filter(|line| !line.trim_start().starts_with('%'))
+        .flat_map(str::split_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hyphenate(word: &str) -> String {
+        let hyphenator = Hyphenator::for_language("en-US").unwrap();
+        let mut out = String::new();
+        let mut last = 0;
+        for at in hyphenator.break_points(word) {
+            out.push_str(&word[last..at]);
+            out.push('-');
+            last = at;
+        }
+        out.push_str(&word[last..]);
+        out
+    }
+
+    #[test]
+    fn test_patterns_score_gaps() {
+        let hyphenator = Hyphenator::parse("a1b\nb1c 2cd\n% comment c1d\n", "", 1, 1);
+        // The even score of `2cd` outweighs `b1c`
+        assert_eq!(hyphenator.break_points("abcd"), [1]);
+        assert_eq!(hyphenator.break_points("abce"), [1, 2]);
+        assert!(hyphenator.break_points("ab3d").is_empty());
+    }
+
+    #[test]
+    fn test_english_hyphenation() {
+        assert_eq!(hyphenate("hyphenation"), "hy-phen-ation");
+        assert_eq!(hyphenate("Hyphenation"), "Hy-phen-ation");
+        // Exceptions win over the patterns
+        assert_eq!(hyphenate("table"), "ta-ble");
+        // Too short to leave two letters before and three after
+        assert_eq!(hyphenate("into"), "into");
+        assert!(Hyphenator::for_language("en_GB").is_some());
+        assert!(Hyphenator::for_language("xx").is_none());
+    }
+}
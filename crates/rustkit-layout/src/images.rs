@@ -5,6 +5,7 @@
 //! - Generating display commands for images
 //! - Background image positioning and tiling
 
+use crate::aspect_ratio::{replaced_size, NaturalSize};
 use crate::{BackgroundRepeat, BackgroundSize, DisplayCommand, ObjectFit, Rect};
 use rustkit_css::Color;
 
@@ -43,6 +44,7 @@ pub fn render_image(
         dest_rect: draw_rect.dest,
         object_fit,
         opacity,
+        frame: 0,
     }
 }
 
@@ -56,43 +58,52 @@ pub fn render_background_image(
     position: (f32, f32),
     repeat: BackgroundRepeat,
 ) -> Vec<DisplayCommand> {
-    let mut commands = Vec::new();
-
     // Calculate the size of the background image
     let (bg_width, bg_height) = size.compute_size(container, image_width, image_height);
 
     if bg_width == 0.0 || bg_height == 0.0 {
-        return commands;
+        return Vec::new();
     }
 
     // Calculate the starting position
-    let start_x = container.x + (container.width - bg_width) * position.0;
-    let start_y = container.y + (container.height - bg_height) * position.1;
+    let tile = Rect {
+        x: container.x + (container.width - bg_width) * position.0,
+        y: container.y + (container.height - bg_height) * position.1,
+        width: bg_width,
+        height: bg_height,
+    };
+    tile_background(url, tile, container, size, position, repeat)
+}
+
+/// Generate display commands tiling a background image placed at `tile`
+/// over `container`, along the axes `repeat` repeats on.
+pub fn tile_background(
+    url: &str,
+    tile: Rect,
+    container: Rect,
+    size: &BackgroundSize,
+    position: (f32, f32),
+    repeat: BackgroundRepeat,
+) -> Vec<DisplayCommand> {
+    let mut commands = Vec::new();
+    let (start_x, start_y, bg_width, bg_height) = (tile.x, tile.y, tile.width, tile.height);
+    if bg_width <= 0.0 || bg_height <= 0.0 {
+        return commands;
+    }
 
     // Determine tiling
-    let (tile_x, tile_y) = match repeat {
-        BackgroundRepeat::Repeat => (true, true),
-        BackgroundRepeat::RepeatX => (true, false),
-        BackgroundRepeat::RepeatY => (false, true),
-        BackgroundRepeat::NoRepeat => (false, false),
-        BackgroundRepeat::Space => (true, true), // TODO: proper spacing
-        BackgroundRepeat::Round => (true, true), // TODO: proper rounding
-    };
+    let (tile_x, tile_y) = (repeat.repeats_x(), repeat.repeats_y());
 
     // Generate tile positions
     if !tile_x && !tile_y {
         // Single image
         commands.push(DisplayCommand::BackgroundImage {
             url: url.to_string(),
-            rect: Rect {
-                x: start_x,
-                y: start_y,
-                width: bg_width,
-                height: bg_height,
-            },
+            rect: tile,
             size: size.clone(),
             position,
             repeat,
+            frame: 0,
         });
     } else {
         // Tiled images
@@ -135,6 +146,7 @@ pub fn render_background_image(
                         size: size.clone(),
                         position,
                         repeat,
+                        frame: 0,
                     });
                 }
 
@@ -241,35 +253,21 @@ pub fn calculate_intrinsic_size(
     explicit_height: Option<f32>,
     container_width: f32,
 ) -> (f32, f32) {
-    match (natural_width, natural_height, explicit_width, explicit_height) {
-        // Both explicit dimensions
-        (_, _, Some(w), Some(h)) => (w, h),
-
-        // Explicit width, calculate height from aspect ratio
-        (Some(nw), Some(nh), Some(w), None) if nw > 0.0 => {
-            (w, w * nh / nw)
-        }
-
-        // Explicit height, calculate width from aspect ratio
-        (Some(nw), Some(nh), None, Some(h)) if nh > 0.0 => {
-            (h * nw / nh, h)
-        }
-
-        // Natural dimensions available
-        (Some(nw), Some(nh), None, None) => (nw, nh),
-
-        // Only natural width
-        (Some(nw), None, None, None) => (nw, nw),
-
-        // Only natural height
-        (None, Some(nh), None, None) => (nh, nh),
-
-        // No dimensions known - use container width and assume square
-        _ => {
-            let size = container_width.min(300.0); // Default max size
-            (size, size)
-        }
-    }
+    let natural = NaturalSize {
+        width: natural_width,
+        height: natural_height,
+        // With one natural dimension, assume square
+        ratio: Some(1.0),
+    };
+    // No dimensions known - use container width and assume square
+    let size = container_width.min(300.0); // Default max size
+    replaced_size(
+        explicit_width,
+        explicit_height,
+        &natural,
+        natural.ratio(),
+        (size, size),
+    )
 }
 
 /// Calculate placeholder dimensions while image is loading
@@ -279,17 +277,20 @@ pub fn calculate_placeholder_size(
     aspect_ratio: Option<f32>,
     container_width: f32,
 ) -> (f32, f32) {
-    match (explicit_width, explicit_height, aspect_ratio) {
-        (Some(w), Some(h), _) => (w, h),
-        (Some(w), None, Some(ar)) if ar > 0.0 => (w, w / ar),
-        (None, Some(h), Some(ar)) if ar > 0.0 => (h * ar, h),
-        (Some(w), None, None) => (w, w), // Assume square
-        (None, Some(h), None) => (h, h), // Assume square
-        _ => {
-            // No hints - use a default placeholder size
-            (container_width.min(150.0), 100.0)
-        }
+    if explicit_width.is_none() && explicit_height.is_none() {
+        // No hints - use a default placeholder size
+        return (container_width.min(150.0), 100.0);
     }
+    let natural = NaturalSize::default();
+    // Assume square without a ratio
+    let ratio = aspect_ratio.filter(|ar| *ar > 0.0).unwrap_or(1.0);
+    replaced_size(
+        explicit_width,
+        explicit_height,
+        &natural,
+        Some(ratio),
+        (0.0, 0.0),
+    )
 }
 
 #[cfg(test)]
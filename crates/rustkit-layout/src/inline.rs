@@ -0,0 +1,404 @@
+//! Inline formatting: placing the items of a line box and `vertical-align`.
+//!
+//! Items are laid out left to right at the top of the line, then moved so
+//! their baseline sits where `vertical-align` puts it relative to the
+//! parent's baseline. The line grows to fit every item and the parent's
+//! strut; `top` and `bottom` items are placed last against the final line.
+//!
+//! Text runs have their baseline at the half-leading plus the font ascent.
+//! Other atomic items (replaced elements, inline-blocks) use the bottom of
+//! their margin box; nested inline boxes use the baseline of their own line.
+
+use crate::{BoxType, Dimensions, Float, FloatContext, LayoutBox, Position, Rect};
+use rustkit_css::{ComputedStyle, Length, VerticalAlign};
+
+/// Ascent of the content area as a fraction of the font size.
+const ASCENT: f32 = 0.8;
+/// Descent of the content area as a fraction of the font size.
+const DESCENT: f32 = 0.2;
+/// x-height as a fraction of the font size; `middle` aligns to half of it.
+const X_HEIGHT: f32 = 0.5;
+/// Baseline shifts of `sub` and `super` as fractions of the parent font size.
+const SUB_SHIFT: f32 = 0.2;
+const SUPER_SHIFT: f32 = 0.33;
+
+fn font_size(style: &ComputedStyle) -> f32 {
+    match style.font_size {
+        Length::Px(px) => px,
+        _ => 16.0,
+    }
+}
+
+fn line_height(style: &ComputedStyle) -> f32 {
+    font_size(style) * 1.2
+}
+
+/// Ascent and descent of a strut of `style`, including half-leading.
+fn strut(style: &ComputedStyle) -> (f32, f32) {
+    let font_size = font_size(style);
+    let half_leading = (line_height(style) - font_size) / 2.0;
+    (
+        font_size * ASCENT + half_leading,
+        font_size * DESCENT + half_leading,
+    )
+}
+
+/// Distance from the top of a laid-out item's margin box to its baseline.
+fn item_baseline(item: &LayoutBox) -> f32 {
+    match item.box_type {
+        BoxType::Text(_) => strut(&item.style).0,
+        _ => item
+            .baseline
+            .unwrap_or_else(|| item.dimensions.margin_box().height),
+    }
+}
+
+/// How far `align` raises the item's baseline above the parent's, or `None`
+/// for `top`/`bottom`, which align to the line box instead.
+fn baseline_shift(
+    align: VerticalAlign,
+    item_ascent: f32,
+    item_height: f32,
+    item_line_height: f32,
+    parent_font_size: f32,
+) -> Option<f32> {
+    let shift = match align {
+        VerticalAlign::Baseline => 0.0,
+        VerticalAlign::Sub => -parent_font_size * SUB_SHIFT,
+        VerticalAlign::Super => parent_font_size * SUPER_SHIFT,
+        // Center the item on the parent's baseline plus half its x-height
+        VerticalAlign::Middle => {
+            parent_font_size * X_HEIGHT / 2.0 + item_height / 2.0 - item_ascent
+        }
+        VerticalAlign::TextTop => parent_font_size * ASCENT - item_ascent,
+        VerticalAlign::TextBottom => item_height - item_ascent - parent_font_size * DESCENT,
+        VerticalAlign::Length(px) => px,
+        VerticalAlign::Percent(percent) => percent / 100.0 * item_line_height,
+        VerticalAlign::Top | VerticalAlign::Bottom => return None,
+    };
+    Some(shift)
+}
+
+/// Lay out the children of `line` as one line box starting at its content
+/// origin, setting its content size and baseline.
+pub(crate) fn layout_line(line: &mut LayoutBox) {
+    let top = line.dimensions.content.y;
+
+    let mut cursor_x = 0.0;
+    for child in &mut line.children {
+        let mut cb = line.dimensions.clone();
+        cb.content.x = line.dimensions.content.x + cursor_x;
+        cb.content.width = 0.0;
+        cb.content.height = 0.0;
+        child.layout(&cb);
+        cursor_x += child.dimensions.margin_box().width;
+    }
+    let (height, above) = align_line(&mut line.children, &line.style, top);
+
+    line.dimensions.content.width = cursor_x;
+    line.dimensions.content.height = height;
+    line.baseline = Some(above);
+}
+
+/// Vertically align `items`, already laid out side by side, in a line box
+/// whose top is at `top`. Returns the line height and its baseline offset.
+fn align_line(items: &mut [LayoutBox], parent: &ComputedStyle, top: f32) -> (f32, f32) {
+    let parent_font_size = font_size(parent);
+    let (mut above, mut below) = strut(parent);
+
+    let mut shifts = Vec::with_capacity(items.len());
+    for child in items.iter() {
+        let height = child.dimensions.margin_box().height;
+        let ascent = item_baseline(child);
+        let shift = baseline_shift(
+            child.style.vertical_align,
+            ascent,
+            height,
+            line_height(&child.style),
+            parent_font_size,
+        );
+        if let Some(shift) = shift {
+            above = above.max(ascent + shift);
+            below = below.max(height - ascent - shift);
+        }
+        shifts.push(shift);
+    }
+
+    // Line-relative items only grow the line when taller than it
+    for (child, shift) in items.iter().zip(&shifts) {
+        if shift.is_none() {
+            let height = child.dimensions.margin_box().height;
+            if child.style.vertical_align == VerticalAlign::Top {
+                below = below.max(height - above);
+            } else {
+                above = above.max(height - below);
+            }
+        }
+    }
+
+    let height = above + below;
+    let baseline = top + above;
+    for (child, shift) in items.iter_mut().zip(shifts) {
+        let margin_box = child.dimensions.margin_box();
+        let target = match shift {
+            Some(shift) => baseline - shift - item_baseline(child),
+            None if child.style.vertical_align == VerticalAlign::Top => top,
+            None => top + height - margin_box.height,
+        };
+        child.translate(0.0, target - margin_box.y);
+    }
+    (height, above)
+}
+
+/// The empty box built for whitespace between elements.
+fn is_placeholder(item: &LayoutBox) -> bool {
+    matches!(item.box_type, BoxType::Block)
+        && item.node_id.is_none()
+        && item.pseudo.is_none()
+        && item.children.is_empty()
+}
+
+fn is_in_flow_inline(item: &LayoutBox) -> bool {
+    matches!(
+        item.box_type,
+        BoxType::Inline | BoxType::InlineBlock | BoxType::Text(_)
+    ) && item.float == Float::None
+        && matches!(item.position, Position::Static | Position::Relative)
+}
+
+/// Length of the run of inline-level siblings at the start of `children`,
+/// or 0 unless it holds an inline-block. Whitespace placeholders between
+/// the items belong to the run.
+pub(crate) fn wrapping_run(children: &[LayoutBox]) -> usize {
+    let run = children
+        .iter()
+        .take_while(|child| is_in_flow_inline(child) || is_placeholder(child))
+        .count();
+    let has_inline_block = children[..run]
+        .iter()
+        .any(|child| matches!(child.box_type, BoxType::InlineBlock));
+    if has_inline_block {
+        run
+    } else {
+        0
+    }
+}
+
+/// Lay out `items` in line boxes that wrap at the width of `container`
+/// left free by `floats`, the first starting `top` below its content top.
+/// Items never split; one wider than the line gets a line to itself.
+/// Returns the lines' height.
+pub(crate) fn layout_wrapped(
+    items: &mut [LayoutBox],
+    container: &Dimensions,
+    parent: &ComputedStyle,
+    top: f32,
+    floats: &FloatContext,
+) -> f32 {
+    let mut y = top;
+    let mut start = 0;
+    while start < items.len() {
+        let (left, right) = floats.edges_in(
+            container.content.y + y,
+            0.0,
+            container.content.x,
+            container.content.right(),
+        );
+        let width = right - left;
+        let mut cursor_x = 0.0;
+        let mut end = start;
+        while end < items.len() {
+            let item = &mut items[end];
+            let x = left + cursor_x;
+            if is_placeholder(item) {
+                item.dimensions = Dimensions::default();
+                item.dimensions.content = Rect::new(x, container.content.y + y, 0.0, 0.0);
+                end += 1;
+                continue;
+            }
+            let mut cb = container.clone();
+            cb.content.x = x;
+            cb.content.height = y;
+            item.layout(&cb);
+            let item_width = item.dimensions.margin_box().width;
+            if end > start && cursor_x + item_width > width {
+                break;
+            }
+            cursor_x += item_width;
+            end += 1;
+        }
+        let (height, _) = align_line(&mut items[start..end], parent, container.content.y + y);
+        y += height;
+        start = end;
+    }
+    y - top
+}
+
+/// Offset of the content of a table cell whose content is `content_height`
+/// tall within a cell of `cell_height`.
+pub(crate) fn cell_content_offset(
+    align: VerticalAlign,
+    content_height: f32,
+    cell_height: f32,
+) -> f32 {
+    let slack = (cell_height - content_height).max(0.0);
+    match align {
+        VerticalAlign::Middle => slack / 2.0,
+        VerticalAlign::Bottom => slack,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BoxType, Dimensions, LayoutBox, Rect};
+    use rustkit_css::{ComputedStyle, Display, Length, VerticalAlign};
+
+    fn text(content: &str) -> LayoutBox {
+        LayoutBox::new(BoxType::Text(content.into()), ComputedStyle::new())
+    }
+
+    fn atomic(height: f32, align: VerticalAlign) -> LayoutBox {
+        let style = ComputedStyle {
+            display: Display::InlineBlock,
+            width: Length::Px(30.0),
+            height: Length::Px(height),
+            vertical_align: align,
+            ..ComputedStyle::new()
+        };
+        LayoutBox::new(BoxType::Block, style)
+    }
+
+    fn lay_out(children: Vec<LayoutBox>) -> LayoutBox {
+        let mut line = LayoutBox::new(BoxType::Inline, ComputedStyle::new());
+        line.children = children;
+        let containing_block = Dimensions {
+            content: Rect::new(0.0, 10.0, 400.0, 0.0),
+            ..Default::default()
+        };
+        line.layout(&containing_block);
+        line
+    }
+
+    #[test]
+    fn test_middle_centers_on_text_midline() {
+        let line = lay_out(vec![text("Label"), atomic(30.0, VerticalAlign::Middle)]);
+        let baseline = 10.0 + line.baseline.unwrap();
+        let icon = line.children[1].dimensions.margin_box();
+
+        // 16px text: x-height midline 4px above the baseline
+        let center = icon.y + icon.height / 2.0;
+        assert!(
+            (center - (baseline - 4.0)).abs() < 0.01,
+            "center {}",
+            center
+        );
+        // The line is exactly as tall as the icon needs
+        assert!((line.dimensions.content.height - 30.0).abs() < 0.01);
+        assert!((icon.y - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_baseline_uses_bottom_margin_edge() {
+        let line = lay_out(vec![text("Label"), atomic(30.0, VerticalAlign::Baseline)]);
+        let baseline = 10.0 + line.baseline.unwrap();
+        let icon = line.children[1].dimensions.margin_box();
+        assert!((icon.bottom() - baseline).abs() < 0.01);
+        // Text descent (0.2em plus half-leading) hangs below the icon
+        assert!((line.dimensions.content.height - (30.0 + 4.8)).abs() < 0.01);
+
+        let label = &line.children[0].dimensions.content;
+        assert!((label.y + 14.4 - baseline).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_top_bottom_and_offsets() {
+        let line = lay_out(vec![
+            text("Label"),
+            atomic(40.0, VerticalAlign::Top),
+            atomic(10.0, VerticalAlign::Bottom),
+            atomic(10.0, VerticalAlign::Length(5.0)),
+        ]);
+        let height = line.dimensions.content.height;
+        let baseline = 10.0 + line.baseline.unwrap();
+        assert!((height - 40.0).abs() < 0.01);
+        assert!((line.children[1].dimensions.margin_box().y - 10.0).abs() < 0.01);
+        assert!((line.children[2].dimensions.margin_box().bottom() - (10.0 + height)).abs() < 0.01);
+        assert!(
+            (line.children[3].dimensions.margin_box().bottom() - (baseline - 5.0)).abs() < 0.01
+        );
+    }
+
+    #[test]
+    fn test_text_only_line_keeps_line_height() {
+        let line = lay_out(vec![text("Just text")]);
+        assert!((line.dimensions.content.height - 19.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_text_in_line_keeps_its_width() {
+        let line = lay_out(vec![text("Just"), text("text")]);
+        let (first, second) = (&line.children[0], &line.children[1]);
+        assert!(first.dimensions.content.width > 0.0);
+        assert_eq!(
+            second.dimensions.content.x,
+            first.dimensions.content.x + first.dimensions.content.width
+        );
+    }
+
+    #[test]
+    fn test_inline_blocks_wrap_into_lines() {
+        let pill = || {
+            let style = ComputedStyle {
+                display: Display::InlineBlock,
+                width: Length::Px(80.0),
+                height: Length::Px(20.0),
+                ..ComputedStyle::new()
+            };
+            LayoutBox::new(BoxType::InlineBlock, style)
+        };
+        let mut container = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        container.children = vec![
+            pill(),
+            LayoutBox::new(BoxType::Block, ComputedStyle::new()),
+            pill(),
+            pill(),
+        ];
+        container.layout(&Dimensions {
+            content: Rect::new(0.0, 0.0, 200.0, 0.0),
+            ..Default::default()
+        });
+
+        let boxes: Vec<Rect> = [0, 2, 3]
+            .iter()
+            .map(|&i| container.children[i].dimensions.margin_box())
+            .collect();
+        assert_eq!((boxes[0].x, boxes[1].x, boxes[2].x), (0.0, 80.0, 0.0));
+        assert_eq!(boxes[0].y, boxes[1].y);
+        assert!(boxes[2].y >= boxes[0].bottom());
+        // Each line is as tall as its inline-blocks above the strut descent
+        assert!((container.dimensions.content.height - 2.0 * (20.0 + 4.8)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_table_cell_vertical_align() {
+        let cell = |align| {
+            let style = ComputedStyle {
+                display: Display::TableCell,
+                height: Length::Px(100.0),
+                vertical_align: align,
+                ..ComputedStyle::new()
+            };
+            let mut cell = LayoutBox::new(BoxType::Block, style);
+            cell.children.push(atomic(20.0, VerticalAlign::Baseline));
+            cell.layout(&Dimensions {
+                content: Rect::new(0.0, 0.0, 200.0, 0.0),
+                ..Default::default()
+            });
+            cell.children[0].dimensions.content.y
+        };
+        assert_eq!(cell(VerticalAlign::Top), 0.0);
+        assert_eq!(cell(VerticalAlign::Middle), 40.0);
+        assert_eq!(cell(VerticalAlign::Bottom), 80.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Intrinsic widths of boxes, for shrink-to-fit sizing.
+//!
+//! The min-content width is the narrowest a box gets without overflowing:
+//! its longest word or widest atomic descendant. The max-content width is
+//! what it takes with no line breaks at all, where consecutive inline-level
+//! children share a line and block-level children each take their own.
+//! Floats add their width to the content beside them.
+
+use crate::{BoxType, Clear, Float, LayoutBox};
+use rustkit_css::Length;
+
+/// Estimated advance of `text` at `font_size`, matching text layout.
+pub(crate) fn estimated_text_width(text: &str, font_size: f32) -> f32 {
+    let visible = text
+        .chars()
+        .filter(|&c| c != crate::line_break::SOFT_HYPHEN)
+        .count();
+    visible as f32 * font_size * 0.5
+}
+
+fn font_size(layout_box: &LayoutBox) -> f32 {
+    match layout_box.style.font_size {
+        Length::Px(px) => px,
+        _ => 16.0,
+    }
+}
+
+/// Horizontal margins, borders and padding, with percentages taken as 0.
+fn horizontal_edges(layout_box: &LayoutBox) -> f32 {
+    let style = &layout_box.style;
+    let font_size = font_size(layout_box);
+    [
+        style.margin_left,
+        style.margin_right,
+        style.border_left_width,
+        style.border_right_width,
+        style.padding_left,
+        style.padding_right,
+    ]
+    .iter()
+    .map(|length| length.to_px(font_size, 16.0, 0.0))
+    .sum()
+}
+
+fn is_inline_level(layout_box: &LayoutBox) -> bool {
+    matches!(
+        layout_box.box_type,
+        BoxType::Inline | BoxType::InlineBlock | BoxType::Text(_)
+    )
+}
+
+/// Width of the margin box of `child` when its content is `content` wide;
+/// a fixed `width` wins over the content.
+fn contribution(child: &LayoutBox, content: fn(&LayoutBox) -> f32) -> f32 {
+    let width = match child.style.width {
+        Length::Px(px) => px,
+        _ => content(child),
+    };
+    width + horizontal_edges(child)
+}
+
+/// Min-content width of the content box of `layout_box`.
+pub fn min_content_width(layout_box: &LayoutBox) -> f32 {
+    if let BoxType::Text(text) = &layout_box.box_type {
+        let font_size = font_size(layout_box);
+        if crate::line_break::breaks_anywhere(&layout_box.style) {
+            return text
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(|c| estimated_text_width(c.encode_utf8(&mut [0; 4]), font_size))
+                .fold(0.0, f32::max);
+        }
+        let style = &layout_box.style;
+        let hyphen = estimated_text_width(crate::line_break::hyphenate_character(style), font_size);
+        return text
+            .split_whitespace()
+            .flat_map(|word| {
+                // Each piece between hyphenation points, all but the last
+                // ending in a hyphen
+                let points = crate::line_break::hyphenation_points(word, style);
+                let ends = points.iter().copied().chain(std::iter::once(word.len()));
+                let starts = std::iter::once(0).chain(points.iter().copied());
+                starts
+                    .zip(ends)
+                    .enumerate()
+                    .map(|(i, (start, end))| {
+                        let hyphen = if i < points.len() { hyphen } else { 0.0 };
+                        estimated_text_width(&word[start..end], font_size) + hyphen
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .fold(0.0, f32::max);
+    }
+    layout_box
+        .children
+        .iter()
+        .map(|child| contribution(child, min_content_width))
+        .fold(0.0, f32::max)
+}
+
+/// Max-content width of the content box of `layout_box`.
+pub fn max_content_width(layout_box: &LayoutBox) -> f32 {
+    if let BoxType::Text(text) = &layout_box.box_type {
+        return estimated_text_width(text, font_size(layout_box));
+    }
+    let mut widest = 0.0f32;
+    let mut line = 0.0;
+    // Floats sit side by side, beside the content that follows them
+    let mut floats = 0.0;
+    for child in &layout_box.children {
+        let width = contribution(child, max_content_width);
+        if child.clear != Clear::None {
+            floats = 0.0;
+        }
+        if child.float != Float::None {
+            floats += width;
+            widest = widest.max(floats + line);
+        } else if is_inline_level(child) {
+            line += width;
+        } else {
+            widest = widest.max(floats + line).max(floats + width);
+            line = 0.0;
+        }
+    }
+    widest.max(floats + line)
+}
+
+/// Shrink-to-fit content width of `layout_box` given `available` width:
+/// `min(max(min-content, available), max-content)`.
+pub fn shrink_to_fit_width(layout_box: &LayoutBox, available: f32) -> f32 {
+    min_content_width(layout_box)
+        .max(available)
+        .min(max_content_width(layout_box))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dimensions, Rect};
+    use rustkit_css::{ComputedStyle, Display};
+
+    fn text(content: &str) -> LayoutBox {
+        LayoutBox::new(BoxType::Text(content.into()), ComputedStyle::new())
+    }
+
+    #[test]
+    fn test_intrinsic_widths() {
+        let mut block = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        block.children = vec![text("two words"), text("x")];
+        // 16px text estimates 8px per character
+        assert_eq!(min_content_width(&block), 40.0);
+        assert_eq!(max_content_width(&block), 80.0);
+        assert_eq!(shrink_to_fit_width(&block, 60.0), 60.0);
+        assert_eq!(shrink_to_fit_width(&block, 10.0), 40.0);
+        assert_eq!(shrink_to_fit_width(&block, 500.0), 80.0);
+    }
+
+    #[test]
+    fn test_auto_inline_block_shrinks_to_text() {
+        let style = ComputedStyle {
+            display: Display::InlineBlock,
+            padding_left: Length::Px(6.0),
+            padding_right: Length::Px(6.0),
+            ..ComputedStyle::new()
+        };
+        let mut pill = LayoutBox::new(BoxType::InlineBlock, style);
+        pill.children.push(text("hi"));
+        pill.layout(&Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 0.0),
+            ..Default::default()
+        });
+        assert_eq!(pill.dimensions.content.width, 16.0);
+        assert_eq!(pill.dimensions.border_box().width, 28.0);
+    }
+}
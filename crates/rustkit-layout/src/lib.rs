@@ -14,19 +14,35 @@
 //! 7. **Stacking contexts**: Z-index based paint ordering
 //! 8. **Text rendering**: Font fallback, decorations, line height
 
+pub mod aspect_ratio;
+mod columns;
+pub mod cull;
 pub mod flex;
 pub mod forms;
+pub mod generated;
 pub mod grid;
+pub mod hyphenation;
 pub mod images;
+mod inline;
+pub mod intrinsic;
+pub mod line_break;
+mod paginate;
 pub mod scroll;
 pub mod text;
 
+pub use aspect_ratio::NaturalSize;
+pub use cull::Culler;
 pub use grid::{layout_grid_container, GridItem, GridLayout, GridTrack};
 pub use forms::{
-    calculate_caret_position, calculate_selection_rects, render_button, render_checkbox,
-    render_input, render_radio, CaretInfo, InputLayout, InputState, SelectionInfo,
+    calculate_caret_position, calculate_selection_rects, format_media_time, media_control_hit,
+    render_button, render_checkbox, render_input, render_media_controls, render_radio, CaretInfo,
+    InputLayout, InputState, MediaControlHit, SelectionInfo,
 };
 pub use flex::{layout_flex_container, Axis, FlexItem, FlexLine};
+pub use generated::{generated_box, CounterScopes};
+pub use intrinsic::{max_content_width, min_content_width, shrink_to_fit_width};
+pub use line_break::{TextLine, ELLIPSIS};
+pub use paginate::{paginate, PageSlice};
 pub use scroll::{
     calculate_scroll_into_view, handle_wheel_event, is_scroll_container, render_scrollbars,
     ScrollAlignment, Scrollbar, ScrollbarOrientation, ScrollMomentum, ScrollState, StickyOffsets,
@@ -34,7 +50,7 @@ pub use scroll::{
 };
 pub use images::{
     calculate_intrinsic_size, calculate_placeholder_size, render_background_image,
-    render_broken_image, render_image, ImageLayoutInfo,
+    render_broken_image, render_image, tile_background, ImageLayoutInfo,
 };
 pub use text::{
     apply_text_transform, collapse_whitespace, FontCache, FontDisplay, FontFaceRule,
@@ -42,8 +58,12 @@ pub use text::{
     TextMetrics, TextShaper,
 };
 
-use rustkit_css::{Color, ComputedStyle, Length};
-use std::cmp::Ordering;
+use rustkit_css::{
+    BackgroundAttachment, BackgroundBox, BackgroundLayer, Color, ComputedStyle, FillRule,
+    FilterChain, Length, OutlineStyle, TranslateScale,
+};
+use std::collections::HashMap;
+use std::ops::Range;
 use thiserror::Error;
 
 /// Errors that can occur in layout.
@@ -67,6 +87,18 @@ pub enum Position {
     Sticky,
 }
 
+impl From<rustkit_css::Position> for Position {
+    fn from(position: rustkit_css::Position) -> Self {
+        match position {
+            rustkit_css::Position::Static => Position::Static,
+            rustkit_css::Position::Relative => Position::Relative,
+            rustkit_css::Position::Absolute => Position::Absolute,
+            rustkit_css::Position::Fixed => Position::Fixed,
+            rustkit_css::Position::Sticky => Position::Sticky,
+        }
+    }
+}
+
 /// CSS float property values.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Float {
@@ -151,8 +183,40 @@ impl FloatContext {
         (left_edge, right_edge)
     }
 
+    /// Left and right edges of the space between `left` and `right` that
+    /// the floats overlapping the band `height` tall at `top` leave free.
+    /// Coordinates are those of the formatting context root, as floats
+    /// are recorded in; a band of no height is the line at `top`.
+    pub fn edges_in(&self, top: f32, height: f32, left: f32, right: f32) -> (f32, f32) {
+        let overlaps =
+            |rect: &Rect| rect.bottom() > top && (rect.y <= top || rect.y < top + height);
+        let left_edge = self
+            .left_floats
+            .iter()
+            .filter(|float| overlaps(&float.rect))
+            .map(|float| float.rect.right())
+            .fold(left, f32::max);
+        let right_edge = self
+            .right_floats
+            .iter()
+            .filter(|float| overlaps(&float.rect))
+            .map(|float| float.rect.x)
+            .fold(right, f32::min);
+        (left_edge, right_edge)
+    }
+
+    /// The first float bottom below `y`, where the free space next changes.
+    pub fn next_bottom_below(&self, y: f32) -> Option<f32> {
+        self.left_floats
+            .iter()
+            .chain(&self.right_floats)
+            .map(|float| float.rect.bottom())
+            .filter(|&bottom| bottom > y)
+            .reduce(f32::min)
+    }
+
     /// Clear floats up to a given y position.
-    pub fn clear(&mut self, clear: Clear) -> f32 {
+    pub fn clear(&self, clear: Clear) -> f32 {
         let mut clear_y: f32 = 0.0;
 
         match clear {
@@ -326,6 +390,9 @@ pub enum BoxType {
     Block,
     /// Inline-level box.
     Inline,
+    /// Atomic inline-level box whose contents form a block formatting
+    /// context (`display: inline-block`).
+    InlineBlock,
     /// Anonymous block (for grouping inline content).
     AnonymousBlock,
     /// Text run.
@@ -362,43 +429,79 @@ pub struct LayoutBox {
     pub float: Float,
     /// Clear property.
     pub clear: Clear,
-    /// Z-index for stacking.
-    pub z_index: i32,
+    /// Z-index for stacking (None for auto).
+    pub z_index: Option<i32>,
     /// Whether this box creates a stacking context.
     pub stacking_context: Option<StackingContext>,
     /// Reference to containing block (for positioned elements).
     #[allow(dead_code)]
     pub containing_block_index: Option<usize>,
+    /// DOM node that generated this box (None for anonymous boxes).
+    pub node_id: Option<rustkit_dom::NodeId>,
+    /// Set for `::before`/`::after` boxes, which hit-test to their parent.
+    pub pseudo: Option<rustkit_css::PseudoElement>,
+    /// Distance from the top of the margin box to the baseline of the box's
+    /// line, set by inline layout.
+    pub baseline: Option<f32>,
+    /// Lines of a text box that wrapped or was cut off with an ellipsis;
+    /// empty when the text is drawn on one line as it is.
+    pub text_lines: Vec<TextLine>,
+    /// Content rects of the columns of a multi-column box that received
+    /// content; empty for other boxes.
+    pub column_boxes: Vec<Rect>,
+    /// Set for replaced elements, whose auto size comes from their content
+    /// rather than their children.
+    pub natural: Option<NaturalSize>,
 }
 
 impl LayoutBox {
     /// Create a new layout box.
     pub fn new(box_type: BoxType, style: ComputedStyle) -> Self {
-        Self {
+        let mut layout_box = Self {
             box_type,
             dimensions: Dimensions::default(),
+            position: style.position.into(),
+            z_index: style.z_index,
             style,
             children: Vec::new(),
-            position: Position::Static,
             offsets: PositionOffsets::default(),
             float: Float::None,
             clear: Clear::None,
-            z_index: 0,
             stacking_context: None,
             containing_block_index: None,
-        }
+            node_id: None,
+            pseudo: None,
+            baseline: None,
+            text_lines: Vec::new(),
+            column_boxes: Vec::new(),
+            natural: None,
+        };
+        layout_box.update_stacking_context();
+        layout_box
+    }
+
+    /// Set the DOM node that generated this box.
+    pub fn with_node_id(mut self, node_id: rustkit_dom::NodeId) -> Self {
+        self.node_id = Some(node_id);
+        self
+    }
+
+    /// Make this the box of a replaced element with `natural` dimensions.
+    pub fn with_natural_size(mut self, natural: NaturalSize) -> Self {
+        self.natural = Some(natural);
+        self
+    }
+
+    /// The ratio, width over height, this box's auto size follows.
+    pub fn preferred_ratio(&self) -> Option<f32> {
+        aspect_ratio::preferred_ratio(self.style.aspect_ratio, self.natural.as_ref())
     }
 
     /// Create a new layout box with positioning.
     pub fn with_position(box_type: BoxType, style: ComputedStyle, position: Position) -> Self {
         let mut layout_box = Self::new(box_type, style);
         layout_box.position = position;
-
-        // Create stacking context if positioned with z-index
-        if position != Position::Static {
-            layout_box.stacking_context = Some(StackingContext::default());
-        }
-
+        layout_box.update_stacking_context();
         layout_box
     }
 
@@ -409,14 +512,39 @@ impl LayoutBox {
         layout_box
     }
 
-    /// Set z-index and create stacking context if needed.
-    pub fn set_z_index(&mut self, z_index: i32) {
-        self.z_index = z_index;
-        if self.position != Position::Static {
-            let mut ctx = self.stacking_context.take().unwrap_or_default();
+    /// Set z-index (`None` for auto) and create stacking context if needed.
+    pub fn set_z_index(&mut self, z_index: impl Into<Option<i32>>) {
+        self.z_index = z_index.into();
+        self.update_stacking_context();
+    }
+
+    /// Whether this box establishes a stacking context: positioned with a
+    /// non-auto z-index, opacity below 1, a transform or a filter.
+    pub fn creates_stacking_context(&self) -> bool {
+        (self.position != Position::Static && self.z_index.is_some())
+            || self.style.opacity < 1.0
+            || self.style.transform.is_some()
+            || self.has_layer()
+    }
+
+    /// Whether this box paints through a layer for `filter` or
+    /// `backdrop-filter`.
+    pub fn has_layer(&self) -> bool {
+        !self.style.filter.is_empty() || !self.style.backdrop_filter.is_empty()
+    }
+
+    /// Refresh `stacking_context` after position, z-index or style changes.
+    pub fn update_stacking_context(&mut self) {
+        let creates_context = self.creates_stacking_context();
+        let z_index = self.stacking_z();
+        if creates_context || self.position != Position::Static {
+            let ctx = self
+                .stacking_context
+                .get_or_insert_with(StackingContext::default);
             ctx.z_index = z_index;
-            ctx.creates_context = true;
-            self.stacking_context = Some(ctx);
+            ctx.creates_context = creates_context;
+        } else {
+            self.stacking_context = None;
         }
     }
 
@@ -456,6 +584,8 @@ impl LayoutBox {
                         self.dimensions.content.width,
                         self.dimensions.content.height,
                     );
+                } else if self.style.is_multicol() {
+                    columns::layout_multicol(self, containing_block);
                 } else {
                     self.layout_block(containing_block);
                 }
@@ -464,9 +594,10 @@ impl LayoutBox {
                 // Inline boxes: position at containing block's current content area
                 self.layout_inline(containing_block);
             }
+            BoxType::InlineBlock => self.layout_inline_block(containing_block),
             BoxType::Text(text) => {
                 // Text boxes: calculate dimensions based on text content
-                self.layout_text(text.clone(), containing_block);
+                self.layout_text(text.clone(), containing_block, &FloatContext::new());
             }
         }
 
@@ -474,33 +605,128 @@ impl LayoutBox {
         self.apply_position_offsets(containing_block);
     }
 
+    /// Whether this box establishes a block formatting context: one that
+    /// contains the floats inside it and keeps clear of those outside.
+    pub fn establishes_bfc(&self) -> bool {
+        use rustkit_css::{Display, Overflow};
+        self.float != Float::None
+            || matches!(self.position, Position::Absolute | Position::Fixed)
+            || !matches!(self.box_type, BoxType::Block | BoxType::AnonymousBlock)
+            || self.style.overflow_x != Overflow::Visible
+            || self.style.overflow_y != Overflow::Visible
+            || self.style.is_multicol()
+            || matches!(
+                self.style.display,
+                Display::FlowRoot
+                    | Display::InlineBlock
+                    | Display::Flex
+                    | Display::InlineFlex
+                    | Display::Grid
+                    | Display::InlineGrid
+                    | Display::TableCell
+            )
+    }
+
+    /// How far to move this box down so its border box, `border_top` with
+    /// its collapsed margin above, clears the floats `clear` names.
+    fn clearance(&self, floats: &FloatContext, border_top: f32) -> f32 {
+        if self.clear == Clear::None {
+            return 0.0;
+        }
+        (floats.clear(self.clear) - border_top).max(0.0)
+    }
+
+    /// Lay out an in-flow child of a block, within the block formatting
+    /// context whose floats are `floats`. Blocks that don't establish a
+    /// context of their own share it with their children; text wraps
+    /// around the floats. Returns the clearance added above the box.
+    fn layout_in_flow(&mut self, containing_block: &Dimensions, floats: &mut FloatContext) -> f32 {
+        match &self.box_type {
+            BoxType::Text(text) => {
+                self.layout_text(text.clone(), containing_block, floats);
+                self.apply_position_offsets(containing_block);
+                0.0
+            }
+            BoxType::Block | BoxType::AnonymousBlock if !self.establishes_bfc() => {
+                self.calculate_block_width(containing_block);
+                self.calculate_block_position(containing_block);
+                let border_top = self.dimensions.border_box().y;
+                let clearance = self.clearance(floats, border_top);
+                self.dimensions.content.y += clearance;
+
+                self.layout_block_children_in(floats);
+                self.calculate_block_height();
+                self.apply_text_overflow();
+                self.apply_position_offsets(containing_block);
+                clearance
+            }
+            BoxType::Block | BoxType::AnonymousBlock => {
+                // The border box of a new context sits beside the floats
+                let cb_width = containing_block.content.width;
+                let top = containing_block.content.y + containing_block.content.height;
+                let margin_top = self.length_to_px(self.style.margin_top, cb_width);
+                let clearance = self.clearance(floats, top + margin_top);
+                let (left, right) = floats.edges_in(
+                    top + clearance + margin_top,
+                    0.0,
+                    containing_block.content.x,
+                    containing_block.content.right(),
+                );
+                let mut beside = containing_block.clone();
+                beside.content.x = left;
+                beside.content.width = (right - left).max(0.0);
+                beside.content.height += clearance;
+                self.layout(&beside);
+                clearance
+            }
+            _ => {
+                self.layout(containing_block);
+                0.0
+            }
+        }
+    }
+
     /// Layout an inline box.
     fn layout_inline(&mut self, containing_block: &Dimensions) {
         // Position at containing block's content area
         self.dimensions.content.x = containing_block.content.x;
         self.dimensions.content.y = containing_block.content.y + containing_block.content.height;
-        
-        // Layout inline children sequentially
-        let mut cursor_x = 0.0;
-        let mut max_height = 0.0f32;
-        
+
+        // Children form one line box, aligned by vertical-align
+        inline::layout_line(self);
+    }
+
+    /// Layout an inline-block at the containing block's current content
+    /// position. An auto width shrinks to fit the contents.
+    fn layout_inline_block(&mut self, containing_block: &Dimensions) {
+        self.calculate_block_width(containing_block);
+        if matches!(self.style.width, Length::Auto)
+            && self.auto_width(containing_block.content.width).is_none()
+        {
+            let edges = self.dimensions.margin_box().width - self.dimensions.content.width;
+            let available = (containing_block.content.width - edges).max(0.0);
+            self.dimensions.content.width = shrink_to_fit_width(self, available);
+        }
+        self.calculate_block_position(containing_block);
+        self.layout_block_children();
+        self.calculate_block_height();
+    }
+
+    /// Move this box and all its descendants.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.dimensions.content.x += dx;
+        self.dimensions.content.y += dy;
+        for rect in &mut self.column_boxes {
+            rect.x += dx;
+            rect.y += dy;
+        }
         for child in &mut self.children {
-            let mut cb = self.dimensions.clone();
-            cb.content.x = self.dimensions.content.x + cursor_x;
-            cb.content.height = 0.0;
-            
-            child.layout(&cb);
-            
-            cursor_x += child.dimensions.margin_box().width;
-            max_height = max_height.max(child.dimensions.margin_box().height);
+            child.translate(dx, dy);
         }
-        
-        self.dimensions.content.width = cursor_x;
-        self.dimensions.content.height = max_height.max(self.get_line_height());
     }
 
     /// Layout a text box.
-    fn layout_text(&mut self, text: String, containing_block: &Dimensions) {
+    fn layout_text(&mut self, text: String, containing_block: &Dimensions, floats: &FloatContext) {
         // Get font size
         let font_size = match self.style.font_size {
             Length::Px(px) => px,
@@ -509,15 +735,19 @@ impl LayoutBox {
         
         // Estimate text width (rough approximation: 0.6 * font_size * char_count)
         // In a real implementation, this would use font metrics
-        let char_count = text.chars().count() as f32;
-        let avg_char_width = font_size * 0.5; // Approximate average character width
-        let text_width = char_count * avg_char_width;
+        let text_width = intrinsic::estimated_text_width(&text, font_size);
         
         // Position at containing block's content area
         self.dimensions.content.x = containing_block.content.x;
         self.dimensions.content.y = containing_block.content.y + containing_block.content.height;
-        self.dimensions.content.width = text_width.min(containing_block.content.width);
+        // In a line box there is no width to fit; the text keeps its own
+        self.dimensions.content.width = if containing_block.content.width > 0.0 {
+            text_width.min(containing_block.content.width)
+        } else {
+            text_width
+        };
         self.dimensions.content.height = self.get_line_height();
+        self.break_text_lines(&text, containing_block, floats);
     }
 
     /// Get line height for text layout.
@@ -537,26 +767,21 @@ impl LayoutBox {
         margin_context: &mut MarginCollapseContext,
         float_context: &mut FloatContext,
     ) {
-        // Handle clear property
-        if self.clear != Clear::None {
-            let clear_y = float_context.clear(self.clear);
-            if clear_y > 0.0 {
-                margin_context.reset();
-            }
-        }
-
-        match self.box_type {
-            BoxType::Block | BoxType::AnonymousBlock => {
-                self.layout_block_with_collapse(containing_block, margin_context, float_context);
-            }
-            BoxType::Inline | BoxType::Text(_) => {
-                // Inline layout handled by parent
-            }
-        }
-
-        // Handle float
         if self.float != Float::None {
             self.layout_float(containing_block, float_context);
+        } else {
+            match self.box_type {
+                BoxType::Block | BoxType::AnonymousBlock => {
+                    self.layout_block_with_collapse(
+                        containing_block,
+                        margin_context,
+                        float_context,
+                    );
+                }
+                BoxType::Inline | BoxType::InlineBlock | BoxType::Text(_) => {
+                    // Inline layout handled by parent
+                }
+            }
         }
 
         // Apply positioning offsets after normal layout
@@ -573,9 +798,23 @@ impl LayoutBox {
 
         // Layout children
         self.layout_block_children();
+        let content_height = self.dimensions.content.height;
 
         // Height depends on children
         self.calculate_block_height();
+        self.apply_text_overflow();
+
+        // Table cells align their content within a taller cell
+        if self.style.display == rustkit_css::Display::TableCell {
+            let offset = inline::cell_content_offset(
+                self.style.vertical_align,
+                content_height,
+                self.dimensions.content.height,
+            );
+            for child in &mut self.children {
+                child.translate(0.0, offset);
+            }
+        }
     }
 
     /// Layout a block-level box with margin collapse.
@@ -607,23 +846,39 @@ impl LayoutBox {
             + self.dimensions.border.top
             + self.dimensions.padding.top;
 
+        // Clearance puts the border box below the floats, using up as much
+        // of the collapsed margin as it needs
+        let border_top = self.dimensions.border_box().y;
+        let clearance = self.clearance(float_context, border_top);
+        self.dimensions.content.y += clearance;
+
         // If this box has border or padding, margins don't collapse through it
-        let blocks_collapse = self.dimensions.border.top > 0.0
+        let blocks_collapse = clearance > 0.0
+            || self.dimensions.border.top > 0.0
             || self.dimensions.padding.top > 0.0
             || self.dimensions.border.bottom > 0.0
             || self.dimensions.padding.bottom > 0.0;
 
+        // A new formatting context keeps its floats to itself
+        let mut own_floats = FloatContext::new();
+        let bfc = self.establishes_bfc();
+        let floats = if bfc { &mut own_floats } else { float_context };
+
         // Layout children with new margin context
-        if blocks_collapse {
+        if blocks_collapse || bfc {
             let mut child_margin_context = MarginCollapseContext::new();
-            self.layout_block_children_with_collapse(&mut child_margin_context, float_context);
+            self.layout_block_children_with_collapse(&mut child_margin_context, floats);
         } else {
             // Margins can collapse through this box
-            self.layout_block_children_with_collapse(margin_context, float_context);
+            self.layout_block_children_with_collapse(margin_context, floats);
+        }
+        if bfc {
+            self.contain_floats(&own_floats);
         }
 
         // Height depends on children
         self.calculate_block_height();
+        self.apply_text_overflow();
 
         // Reset margin context for next sibling, add bottom margin
         margin_context.reset();
@@ -648,43 +903,49 @@ impl LayoutBox {
             self.length_to_px(style.padding_bottom, containing_block.content.width);
     }
 
-    /// Layout a floated box.
+    /// Layout a floated box, as high as the current line allows and as far
+    /// left or right as the floats already in `float_context` leave room
+    /// for, and add it to them. An auto width shrinks to fit.
     fn layout_float(&mut self, containing_block: &Dimensions, float_context: &mut FloatContext) {
         // Calculate dimensions
         self.calculate_block_width(containing_block);
+        if matches!(self.style.width, Length::Auto)
+            && self.auto_width(containing_block.content.width).is_none()
+        {
+            let edges = self.dimensions.margin_box().width - self.dimensions.content.width;
+            let available = (containing_block.content.width - edges).max(0.0);
+            self.dimensions.content.width = shrink_to_fit_width(self, available);
+        }
         self.calculate_block_vertical_box_model(containing_block);
 
-        // Find position based on float type
-        let (left_edge, right_edge) = float_context.available_width(
-            containing_block.content.y + containing_block.content.height,
-            containing_block.content.width,
-        );
-
+        // Move down past floats until the box fits beside them
         let box_width = self.dimensions.margin_box().width;
-
-        match self.float {
-            Float::Left => {
-                self.dimensions.content.x = containing_block.content.x
-                    + left_edge
-                    + self.dimensions.margin.left
-                    + self.dimensions.border.left
-                    + self.dimensions.padding.left;
-
-                float_context.add_left(self.dimensions.margin_box());
-            }
-            Float::Right => {
-                self.dimensions.content.x = containing_block.content.x + right_edge - box_width
-                    + self.dimensions.margin.left
-                    + self.dimensions.border.left
-                    + self.dimensions.padding.left;
-
-                float_context.add_right(self.dimensions.margin_box());
-            }
-            Float::None => {}
+        let mut top = containing_block.content.y + containing_block.content.height;
+        if self.clear != Clear::None {
+            top = top.max(float_context.clear(self.clear));
         }
+        let (left_edge, right_edge) = loop {
+            let (left, right) = float_context.edges_in(
+                top,
+                0.0,
+                containing_block.content.x,
+                containing_block.content.right(),
+            );
+            match float_context.next_bottom_below(top) {
+                Some(next) if right - left < box_width => top = next,
+                _ => break (left, right),
+            }
+        };
 
-        self.dimensions.content.y = containing_block.content.y
-            + containing_block.content.height
+        let margin_left = match self.float {
+            Float::Right => right_edge - box_width,
+            _ => left_edge,
+        };
+        self.dimensions.content.x = margin_left
+            + self.dimensions.margin.left
+            + self.dimensions.border.left
+            + self.dimensions.padding.left;
+        self.dimensions.content.y = top
             + self.dimensions.margin.top
             + self.dimensions.border.top
             + self.dimensions.padding.top;
@@ -692,6 +953,12 @@ impl LayoutBox {
         // Layout children
         self.layout_block_children();
         self.calculate_block_height();
+
+        match self.float {
+            Float::Left => float_context.add_left(self.dimensions.margin_box()),
+            Float::Right => float_context.add_right(self.dimensions.margin_box()),
+            Float::None => {}
+        }
     }
 
     /// Apply position offsets for positioned elements.
@@ -747,8 +1014,8 @@ impl LayoutBox {
                 }
             }
             Position::Fixed => {
-                // Position relative to viewport (root containing block)
-                // In a full implementation, this would use the viewport dimensions
+                // Placed against the viewport by `layout_fixed`; until then,
+                // and inside transformed ancestors, like absolute
                 self.apply_position_offsets_absolute(containing_block);
             }
             Position::Sticky => {
@@ -797,6 +1064,44 @@ impl LayoutBox {
         }
     }
 
+    /// Place the `position: fixed` boxes of a laid-out tree against
+    /// `viewport`, their containing block whatever their depth. Fixed
+    /// boxes inside a transformed ancestor stay positioned like `absolute`.
+    pub fn layout_fixed(&mut self, viewport: Rect) {
+        for child in &mut self.children {
+            if child.position == Position::Fixed {
+                child.place_in_viewport(viewport);
+            }
+            if child.style.transform.is_none() {
+                child.layout_fixed(viewport);
+            }
+        }
+    }
+
+    /// Lay this fixed box out again with the viewport as its containing
+    /// block. Sides without an offset keep the box's static position.
+    fn place_in_viewport(&mut self, viewport: Rect) {
+        let static_box = self.dimensions.margin_box();
+        let offsets = std::mem::take(&mut self.offsets);
+        self.layout(&Dimensions {
+            content: Rect::new(viewport.x, viewport.y, viewport.width, 0.0),
+            ..Default::default()
+        });
+        self.offsets = offsets;
+        let laid_out = self.dimensions.margin_box();
+        self.translate(static_box.x - laid_out.x, static_box.y - laid_out.y);
+
+        // Resolve the offsets, then move the descendants along
+        let before = self.dimensions.content;
+        self.apply_position_offsets_absolute(&Dimensions {
+            content: viewport,
+            ..Default::default()
+        });
+        let after = self.dimensions.content;
+        self.dimensions.content = before;
+        self.translate(after.x - before.x, after.y - before.y);
+    }
+
     /// Calculate block width.
     fn calculate_block_width(&mut self, containing_block: &Dimensions) {
         let style = &self.style;
@@ -816,10 +1121,11 @@ impl LayoutBox {
 
         // Calculate content width
         let content_width = match style.width {
-            Length::Auto => {
+            Length::Auto => match self.auto_width(containing_block.content.width) {
+                Some(width) => width,
                 // Fill available space
-                (containing_block.content.width - total_margin_border_padding).max(0.0)
-            }
+                None => (containing_block.content.width - total_margin_border_padding).max(0.0),
+            },
             _ => self.length_to_px(style.width, containing_block.content.width),
         };
 
@@ -862,24 +1168,58 @@ impl LayoutBox {
             + self.dimensions.padding.top;
     }
 
-    /// Layout block children.
+    /// Layout block children as the root of a block formatting context,
+    /// growing to contain the floats among them.
     fn layout_block_children(&mut self) {
+        let mut floats = FloatContext::new();
+        self.layout_block_children_in(&mut floats);
+        self.contain_floats(&floats);
+    }
+
+    /// Grow the content box to the bottom of the lowest of `floats`.
+    fn contain_floats(&mut self, floats: &FloatContext) {
+        let float_bottom = floats.clear(Clear::Both) - self.dimensions.content.y;
+        self.dimensions.content.height = self.dimensions.content.height.max(float_bottom);
+    }
+
+    /// Layout block children within the block formatting context whose
+    /// floats are `floats`.
+    ///
+    /// A run of inline-level children that includes an inline-block is
+    /// laid out in line boxes that wrap at the content width.
+    fn layout_block_children_in(&mut self, floats: &mut FloatContext) {
         let mut cursor_y = 0.0;
 
-        for child in &mut self.children {
+        let mut index = 0;
+        while index < self.children.len() {
+            let run = inline::wrapping_run(&self.children[index..]);
+            if run > 0 {
+                let items = &mut self.children[index..index + run];
+                cursor_y +=
+                    inline::layout_wrapped(items, &self.dimensions, &self.style, cursor_y, floats);
+                index += run;
+                continue;
+            }
+            let child = &mut self.children[index];
+            index += 1;
+
             // Create a containing block at current cursor position
             let mut cb = self.dimensions.clone();
             cb.content.height = cursor_y;
 
-            child.layout(&cb);
-
-            // Advance cursor by child's margin box height (unless floated or positioned)
-            if child.float == Float::None
-                && child.position != Position::Absolute
-                && child.position != Position::Fixed
-            {
-                cursor_y += child.dimensions.margin_box().height;
+            if matches!(child.position, Position::Absolute | Position::Fixed) {
+                child.layout(&cb);
+                continue;
+            }
+            if child.float != Float::None {
+                child.layout_float(&cb, floats);
+                child.apply_position_offsets(&cb);
+                continue;
             }
+            let clearance = child.layout_in_flow(&cb, floats);
+
+            // Advance cursor by child's margin box height
+            cursor_y += clearance + child.dimensions.margin_box().height;
         }
 
         self.dimensions.content.height = cursor_y;
@@ -916,12 +1256,108 @@ impl LayoutBox {
     /// Calculate block height.
     fn calculate_block_height(&mut self) {
         // If height is explicitly set, use it
-        if let Length::Px(h) = self.style.height {
+        if let Some(h) = self.definite_height() {
             self.dimensions.content.height = h;
+        } else if let Some(height) = self.auto_height() {
+            self.dimensions.content.height = height;
         }
         // Otherwise, content.height was set by layout_block_children
     }
 
+    /// The set `height` in pixels, if it doesn't depend on the containing
+    /// block.
+    fn definite_height(&self) -> Option<f32> {
+        match self.style.height {
+            Length::Px(h) => Some(h),
+            Length::Calc(calc) if !calc.has_percent() => {
+                Some(self.length_to_px(self.style.height, 0.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Width of an auto-width box sized by its height through its ratio, or
+    /// by its content if replaced. `None` for a box that fills the
+    /// containing block.
+    fn auto_width(&self, containing_width: f32) -> Option<f32> {
+        let height = self.definite_height();
+        let ratio = self.preferred_ratio();
+        let (min, max) = self.size_limits(
+            self.style.min_width,
+            self.style.max_width,
+            Some(containing_width),
+        );
+        let width = match (&self.natural, ratio, height) {
+            (_, Some(ratio), Some(height)) => height * ratio,
+            // With only a ratio to go on, a replaced element fills the line
+            (Some(natural), Some(_), None)
+                if natural.width.is_none() && natural.height.is_none() =>
+            {
+                return None
+            }
+            (Some(natural), ratio, height) => {
+                aspect_ratio::replaced_size(
+                    None,
+                    height,
+                    natural,
+                    ratio,
+                    aspect_ratio::DEFAULT_REPLACED_SIZE,
+                )
+                .0
+            }
+            (None, _, _) => return None,
+        };
+        Some(width.min(max).max(min))
+    }
+
+    /// Height of an auto-height box laid out `dimensions.content.width`
+    /// wide, through its ratio or as a replaced element. `None` for a box
+    /// as tall as its content.
+    fn auto_height(&self) -> Option<f32> {
+        let width = self.dimensions.content.width;
+        let (min, max) = self.size_limits(self.style.min_height, self.style.max_height, None);
+        match (&self.natural, self.preferred_ratio()) {
+            (Some(natural), ratio) => {
+                let (_, height) = aspect_ratio::replaced_size(
+                    Some(width),
+                    None,
+                    natural,
+                    ratio,
+                    aspect_ratio::DEFAULT_REPLACED_SIZE,
+                );
+                Some(height.min(max).max(min))
+            }
+            (None, Some(ratio)) => {
+                let height = aspect_ratio::height_for_width(width, ratio, min, max);
+                // Content taller than the ratio allows grows the box, as its
+                // automatic minimum height, unless it may overflow
+                let content = self.dimensions.content.height;
+                Some(match self.style.overflow_y {
+                    rustkit_css::Overflow::Visible => height.max(content),
+                    _ => height,
+                })
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// A `min-*` and `max-*` pair in pixels, with percentages of `basis`;
+    /// without one, percentages don't limit. An unset maximum is unlimited.
+    fn size_limits(&self, min: Length, max: Length, basis: Option<f32>) -> (f32, f32) {
+        let resolve = |length: Length| match (length, basis) {
+            (Length::Percent(_), None) => None,
+            (Length::Calc(calc), None) if calc.has_percent() => None,
+            (length, basis) => Some(self.length_to_px(length, basis.unwrap_or(0.0))),
+        };
+        let min = resolve(min).unwrap_or(0.0);
+        // Styles start with a zero maximum, meaning none
+        let max = match max {
+            Length::Auto | Length::Zero => None,
+            max => resolve(max),
+        };
+        (min, max.unwrap_or(f32::INFINITY).max(min))
+    }
+
     /// Convert a Length to pixels.
     fn length_to_px(&self, length: Length, container_size: f32) -> f32 {
         let font_size = match self.style.font_size {
@@ -931,43 +1367,51 @@ impl LayoutBox {
         length.to_px(font_size, 16.0, container_size)
     }
 
-    /// Get children sorted by z-index for painting.
+    /// Get children in painting order: negative z-index contexts, in-flow
+    /// blocks, floats, z-index auto/0 positioned boxes, then positive z-index.
     pub fn get_paint_order(&self) -> Vec<&LayoutBox> {
+        let mut negative: Vec<(&LayoutBox, i32)> = Vec::new();
         let mut normal_flow: Vec<&LayoutBox> = Vec::new();
-        let mut positioned: Vec<(&LayoutBox, i32)> = Vec::new();
+        let mut floats: Vec<&LayoutBox> = Vec::new();
+        let mut zero: Vec<&LayoutBox> = Vec::new();
+        let mut positive: Vec<(&LayoutBox, i32)> = Vec::new();
 
         for child in &self.children {
-            if child.position == Position::Static {
-                normal_flow.push(child);
+            let z = child.stacking_z();
+            if z < 0 {
+                negative.push((child, z));
+            } else if z > 0 {
+                positive.push((child, z));
+            } else if child.creates_stacking_context() || child.position != Position::Static {
+                zero.push(child);
+            } else if child.float != Float::None {
+                floats.push(child);
             } else {
-                positioned.push((child, child.z_index));
+                normal_flow.push(child);
             }
         }
 
-        // Sort positioned elements by z-index
-        positioned.sort_by(|a, b| a.1.cmp(&b.1));
+        // Stable sorts keep tree order within equal z-index
+        negative.sort_by_key(|(_, z)| *z);
+        positive.sort_by_key(|(_, z)| *z);
 
-        // Combine: negative z-index, normal flow, positive z-index
         let mut result: Vec<&LayoutBox> = Vec::new();
-
-        // Add negative z-index positioned elements first
-        for (child, z) in positioned.iter() {
-            if *z < 0 {
-                result.push(child);
-            }
-        }
-
-        // Add normal flow elements
+        result.extend(negative.into_iter().map(|(child, _)| child));
         result.extend(normal_flow);
+        result.extend(floats);
+        result.extend(zero);
+        result.extend(positive.into_iter().map(|(child, _)| child));
+        result
+    }
 
-        // Add zero and positive z-index positioned elements
-        for (child, z) in positioned.iter() {
-            if *z >= 0 {
-                result.push(child);
-            }
+    /// Z-index this box is ordered by within its parent stacking context
+    /// (0 unless it creates a context with a non-auto z-index).
+    fn stacking_z(&self) -> i32 {
+        if self.position != Position::Static {
+            self.z_index.unwrap_or(0)
+        } else {
+            0
         }
-
-        result
     }
 
     /// Perform hit testing at the given point.
@@ -990,6 +1434,13 @@ impl LayoutBox {
         let paint_order = self.get_paint_order();
         for child in paint_order.iter().rev() {
             if let Some(mut result) = child.hit_test_internal(x, y, depth + 1) {
+                // Generated content targets its originating element
+                if child.pseudo.is_some() {
+                    result.node_id = self.node_id;
+                }
+                if result.cursor == rustkit_css::Cursor::Auto {
+                    result.cursor = self.style.cursor;
+                }
                 // Found a hit in a child - add ourselves to the path
                 result.ancestors.push(HitTestAncestor {
                     box_type: self.box_type.clone(),
@@ -1015,6 +1466,8 @@ impl LayoutBox {
             z_index: self.z_index,
             position: self.position,
             is_scrollable: false, // TODO: detect overflow
+            node_id: self.node_id,
+            cursor: self.style.cursor,
         })
     }
 
@@ -1023,6 +1476,87 @@ impl LayoutBox {
         self.dimensions.border_box().contains(x, y)
     }
 
+    /// Collect the border box of every box that has a DOM node.
+    pub fn collect_node_rects(
+        &self,
+        out: &mut std::collections::HashMap<rustkit_dom::NodeId, Rect>,
+    ) {
+        if let Some(node_id) = self.node_id {
+            out.insert(node_id, self.dimensions.border_box());
+        }
+        for child in &self.children {
+            child.collect_node_rects(out);
+        }
+    }
+
+    /// Collect the layout geometry of every box that has a DOM node.
+    ///
+    /// Returns the scrollable overflow extent of this box's subtree.
+    pub fn collect_node_geometry(
+        &self,
+        out: &mut std::collections::HashMap<rustkit_dom::NodeId, NodeGeometry>,
+    ) -> Rect {
+        let padding_box = self.dimensions.padding_box();
+        let mut right = padding_box.right();
+        let mut bottom = padding_box.bottom();
+        for child in &self.children {
+            let extent = child.collect_node_geometry(out);
+            right = right.max(extent.right());
+            bottom = bottom.max(extent.bottom());
+        }
+        let overflow = Rect::new(
+            padding_box.x,
+            padding_box.y,
+            right - padding_box.x,
+            bottom - padding_box.y,
+        );
+
+        if let Some(node_id) = self.node_id {
+            let scrollbar = match self.style.scrollbar_width {
+                rustkit_css::ScrollbarWidth::Auto => 12.0,
+                rustkit_css::ScrollbarWidth::Thin => 8.0,
+                rustkit_css::ScrollbarWidth::None => 0.0,
+            };
+            let gutter =
+                |overflow_style: rustkit_css::Overflow, overflows: bool| match overflow_style {
+                    rustkit_css::Overflow::Scroll => scrollbar,
+                    rustkit_css::Overflow::Auto if overflows => scrollbar,
+                    _ => 0.0,
+                };
+
+            let geometry = out.entry(node_id).or_default();
+            if self.text_lines.is_empty() {
+                geometry.fragments.push(self.dimensions.clone());
+            }
+            // Each line of wrapped text is a fragment of its own
+            geometry
+                .fragments
+                .extend(self.text_lines.iter().map(|line| Dimensions {
+                    content: line.rect,
+                    ..Default::default()
+                }));
+            geometry.overflow = overflow;
+            geometry.scrollbars = (
+                gutter(self.style.overflow_y, overflow.height > padding_box.height),
+                gutter(self.style.overflow_x, overflow.width > padding_box.width),
+            );
+            geometry.anchor_excluded = self.style.overflow_anchor
+                == rustkit_css::OverflowAnchor::None
+                || matches!(
+                    self.style.position,
+                    rustkit_css::Position::Fixed | rustkit_css::Position::Sticky
+                );
+        }
+
+        let margin_box = self.dimensions.margin_box();
+        Rect::new(
+            margin_box.x,
+            margin_box.y,
+            margin_box.right().max(right) - margin_box.x,
+            margin_box.bottom().max(bottom) - margin_box.y,
+        )
+    }
+
     /// Get all elements at a point (including overlapping elements).
     pub fn hit_test_all(&self, x: f32, y: f32) -> Vec<HitTestResult> {
         let mut results = Vec::new();
@@ -1051,6 +1585,8 @@ impl LayoutBox {
             z_index: self.z_index,
             position: self.position,
             is_scrollable: false,
+            node_id: self.node_id,
+            cursor: self.style.cursor,
         });
 
         // Check all children
@@ -1060,6 +1596,20 @@ impl LayoutBox {
     }
 }
 
+/// Layout geometry of a DOM node, as exposed to script.
+#[derive(Debug, Clone, Default)]
+pub struct NodeGeometry {
+    /// Dimensions of each box the node generated, in tree order.
+    pub fragments: Vec<Dimensions>,
+    /// Padding box extended to cover descendant margin boxes.
+    pub overflow: Rect,
+    /// Width of the vertical and height of the horizontal scrollbar.
+    pub scrollbars: (f32, f32),
+    /// The node and its descendants are never scroll anchors
+    /// (`overflow-anchor: none`, or fixed or sticky positioning).
+    pub anchor_excluded: bool,
+}
+
 /// Result of a hit test operation.
 #[derive(Debug, Clone)]
 pub struct HitTestResult {
@@ -1079,12 +1629,16 @@ pub struct HitTestResult {
     pub depth: u32,
     /// Ancestor chain from parent to root.
     pub ancestors: Vec<HitTestAncestor>,
-    /// Z-index of the hit element.
-    pub z_index: i32,
+    /// Z-index of the hit element (None for auto).
+    pub z_index: Option<i32>,
     /// Position property of the hit element.
     pub position: Position,
     /// Whether the element is scrollable.
     pub is_scrollable: bool,
+    /// DOM node of the hit box, if any.
+    pub node_id: Option<rustkit_dom::NodeId>,
+    /// `cursor` of the hit box, or of its nearest ancestor that sets one.
+    pub cursor: rustkit_css::Cursor,
 }
 
 impl HitTestResult {
@@ -1120,8 +1674,8 @@ pub struct HitTestAncestor {
     pub border_box: Rect,
     /// Content box.
     pub content_box: Rect,
-    /// Z-index.
-    pub z_index: i32,
+    /// Z-index (None for auto).
+    pub z_index: Option<i32>,
     /// Position property.
     pub position: Position,
 }
@@ -1140,6 +1694,14 @@ pub enum DisplayCommand {
         bottom: f32,
         left: f32,
     },
+    /// Draw an outline `offset` outside a border box; it takes no space.
+    Outline {
+        rect: Rect,
+        width: f32,
+        color: Color,
+        style: OutlineStyle,
+        offset: f32,
+    },
     /// Draw text.
     Text {
         text: String,
@@ -1172,6 +1734,8 @@ pub enum DisplayCommand {
         object_fit: ObjectFit,
         /// Opacity (0.0 - 1.0)
         opacity: f32,
+        /// Animation frame to draw (0 for still images)
+        frame: usize,
     },
     /// Draw a background image.
     BackgroundImage {
@@ -1185,15 +1749,34 @@ pub enum DisplayCommand {
         position: (f32, f32),
         /// Background repeat
         repeat: BackgroundRepeat,
+        /// Animation frame to draw (0 for still images)
+        frame: usize,
     },
     /// Push a clip rect (for overflow handling).
     PushClip(Rect),
+    /// Push a clip to closed subpaths under a fill rule, ended by
+    /// `PopClip` like a clip rect.
+    PushClipPath {
+        subpaths: Vec<Vec<(f32, f32)>>,
+        fill_rule: FillRule,
+    },
     /// Pop clip rect.
     PopClip,
     /// Start stacking context.
     PushStackingContext { z_index: i32, rect: Rect },
     /// End stacking context.
     PopStackingContext,
+    /// Start painting into a layer of `rect`, the border box of a box with
+    /// `filter` or `backdrop-filter`. The backdrop under `rect` is run
+    /// through `backdrop_filter` first; at `PopLayer` the layer is run
+    /// through `filter` and composited over it.
+    PushLayer {
+        rect: Rect,
+        filter: FilterChain,
+        backdrop_filter: FilterChain,
+    },
+    /// End the layer.
+    PopLayer,
 
     // SVG-specific commands
     /// Fill a rectangle with solid color.
@@ -1212,11 +1795,260 @@ pub enum DisplayCommand {
     Polyline { points: Vec<(f32, f32)>, color: Color, width: f32 },
     /// Fill a polygon.
     FillPolygon { points: Vec<(f32, f32)>, color: Color },
+    /// Fill the region of several closed subpaths together under a fill
+    /// rule, so inner subpaths can cut holes.
+    FillPath {
+        subpaths: Vec<Vec<(f32, f32)>>,
+        fill_rule: FillRule,
+        color: Color,
+    },
     /// Stroke a polygon.
     StrokePolygon { points: Vec<(f32, f32)>, color: Color, width: f32 },
 }
 
 /// Text decoration style for display commands.
+impl DisplayCommand {
+    /// Multiply the alpha of what the command paints by `opacity`.
+    pub fn fade(&mut self, opacity: f32) {
+        match self {
+            DisplayCommand::SolidColor(color, _)
+            | DisplayCommand::Border { color, .. }
+            | DisplayCommand::Outline { color, .. }
+            | DisplayCommand::Text { color, .. }
+            | DisplayCommand::TextDecoration { color, .. }
+            | DisplayCommand::FillRect { color, .. }
+            | DisplayCommand::StrokeRect { color, .. }
+            | DisplayCommand::FillCircle { color, .. }
+            | DisplayCommand::StrokeCircle { color, .. }
+            | DisplayCommand::FillEllipse { color, .. }
+            | DisplayCommand::Line { color, .. }
+            | DisplayCommand::Polyline { color, .. }
+            | DisplayCommand::FillPolygon { color, .. }
+            | DisplayCommand::FillPath { color, .. }
+            | DisplayCommand::StrokePolygon { color, .. } => color.a *= opacity,
+            DisplayCommand::Image { opacity: alpha, .. } => *alpha *= opacity,
+            DisplayCommand::BackgroundImage { .. }
+            | DisplayCommand::PushClip(_)
+            | DisplayCommand::PushClipPath { .. }
+            | DisplayCommand::PopClip
+            | DisplayCommand::PushStackingContext { .. }
+            | DisplayCommand::PopStackingContext
+            | DisplayCommand::PushLayer { .. }
+            | DisplayCommand::PopLayer => {}
+        }
+    }
+
+    /// Map the command's geometry through `transform` about `origin`.
+    pub fn transform(&mut self, transform: &TranslateScale, origin: (f32, f32)) {
+        let (sx, sy) = (transform.scale_x, transform.scale_y);
+        let point = |x: &mut f32, y: &mut f32| {
+            let (tx, ty) = transform.apply(*x - origin.0, *y - origin.1);
+            (*x, *y) = (tx + origin.0, ty + origin.1);
+        };
+        let rect = |r: &mut Rect| {
+            point(&mut r.x, &mut r.y);
+            r.width *= sx;
+            r.height *= sy;
+        };
+        let mean = (sx.abs() + sy.abs()) / 2.0;
+        match self {
+            DisplayCommand::SolidColor(_, r)
+            | DisplayCommand::PushClip(r)
+            | DisplayCommand::PushStackingContext { rect: r, .. }
+            | DisplayCommand::BackgroundImage { rect: r, .. }
+            | DisplayCommand::FillRect { rect: r, .. }
+            | DisplayCommand::FillEllipse { rect: r, .. } => rect(r),
+            DisplayCommand::Image { dest_rect, .. } => rect(dest_rect),
+            DisplayCommand::StrokeRect { rect: r, width, .. } => {
+                rect(r);
+                *width *= mean;
+            }
+            DisplayCommand::Outline {
+                rect: r,
+                width,
+                offset,
+                ..
+            } => {
+                rect(r);
+                *width *= mean;
+                *offset *= mean;
+            }
+            DisplayCommand::Border {
+                rect: r,
+                top,
+                right,
+                bottom,
+                left,
+                ..
+            } => {
+                rect(r);
+                *top *= sy;
+                *bottom *= sy;
+                *left *= sx;
+                *right *= sx;
+            }
+            DisplayCommand::Text {
+                x, y, font_size, ..
+            } => {
+                point(x, y);
+                *font_size *= sy;
+            }
+            DisplayCommand::TextDecoration {
+                x,
+                y,
+                width,
+                thickness,
+                ..
+            } => {
+                point(x, y);
+                *width *= sx;
+                *thickness *= sy;
+            }
+            DisplayCommand::FillCircle { cx, cy, radius, .. } => {
+                point(cx, cy);
+                *radius *= mean;
+            }
+            DisplayCommand::StrokeCircle {
+                cx,
+                cy,
+                radius,
+                width,
+                ..
+            } => {
+                point(cx, cy);
+                *radius *= mean;
+                *width *= mean;
+            }
+            DisplayCommand::Line {
+                x1,
+                y1,
+                x2,
+                y2,
+                width,
+                ..
+            } => {
+                point(x1, y1);
+                point(x2, y2);
+                *width *= mean;
+            }
+            DisplayCommand::Polyline { points, width, .. }
+            | DisplayCommand::StrokePolygon { points, width, .. } => {
+                points.iter_mut().for_each(|(x, y)| point(x, y));
+                *width *= mean;
+            }
+            DisplayCommand::FillPolygon { points, .. } => {
+                points.iter_mut().for_each(|(x, y)| point(x, y));
+            }
+            DisplayCommand::FillPath { subpaths, .. }
+            | DisplayCommand::PushClipPath { subpaths, .. } => {
+                subpaths.iter_mut().flatten().for_each(|(x, y)| point(x, y));
+            }
+            DisplayCommand::PushLayer {
+                rect: r,
+                filter,
+                backdrop_filter,
+            } => {
+                rect(r);
+                *filter = filter.scaled(mean);
+                *backdrop_filter = backdrop_filter.scaled(mean);
+            }
+            DisplayCommand::PopClip
+            | DisplayCommand::PopStackingContext
+            | DisplayCommand::PopLayer => {}
+        }
+    }
+
+    /// A rectangle containing everything the command paints, or `None`
+    /// for the clip, stacking context and layer commands that only bracket
+    /// others.
+    ///
+    /// Strokes add half their width; text is estimated from its glyph run
+    /// at up to an em per character, since shaping happens in the renderer.
+    pub fn bounds(&self) -> Option<Rect> {
+        let outset = |r: &Rect, by: f32| {
+            Rect::new(r.x - by, r.y - by, r.width + 2.0 * by, r.height + 2.0 * by)
+        };
+        fn extent<'a>(points: impl Iterator<Item = &'a (f32, f32)>) -> Rect {
+            let (mut x0, mut y0) = (f32::INFINITY, f32::INFINITY);
+            let (mut x1, mut y1) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+            for &(x, y) in points {
+                (x0, y0) = (x0.min(x), y0.min(y));
+                (x1, y1) = (x1.max(x), y1.max(y));
+            }
+            if x0 > x1 {
+                return Rect::zero();
+            }
+            Rect::new(x0, y0, x1 - x0, y1 - y0)
+        }
+        let bounds = match self {
+            DisplayCommand::SolidColor(_, rect)
+            | DisplayCommand::Border { rect, .. }
+            | DisplayCommand::BackgroundImage { rect, .. }
+            | DisplayCommand::FillRect { rect, .. }
+            | DisplayCommand::FillEllipse { rect, .. } => *rect,
+            DisplayCommand::Image { dest_rect, .. } => *dest_rect,
+            DisplayCommand::Outline {
+                rect,
+                width,
+                offset,
+                ..
+            } => outset(rect, width + offset.max(0.0)),
+            DisplayCommand::StrokeRect { rect, width, .. } => outset(rect, width / 2.0),
+            DisplayCommand::Text {
+                text,
+                x,
+                y,
+                font_size,
+                ..
+            } => Rect::new(
+                *x,
+                y - font_size / 2.0,
+                text.chars().count() as f32 * font_size,
+                font_size * 2.0,
+            ),
+            DisplayCommand::TextDecoration {
+                x,
+                y,
+                width,
+                thickness,
+                ..
+            } => outset(&Rect::new(*x, *y, *width, *thickness), *thickness),
+            DisplayCommand::FillCircle { cx, cy, radius, .. } => {
+                outset(&Rect::new(*cx, *cy, 0.0, 0.0), *radius)
+            }
+            DisplayCommand::StrokeCircle {
+                cx,
+                cy,
+                radius,
+                width,
+                ..
+            } => outset(&Rect::new(*cx, *cy, 0.0, 0.0), radius + width / 2.0),
+            DisplayCommand::Line {
+                x1,
+                y1,
+                x2,
+                y2,
+                width,
+                ..
+            } => outset(&extent([(*x1, *y1), (*x2, *y2)].iter()), width / 2.0),
+            DisplayCommand::Polyline { points, width, .. }
+            | DisplayCommand::StrokePolygon { points, width, .. } => {
+                outset(&extent(points.iter()), width / 2.0)
+            }
+            DisplayCommand::FillPolygon { points, .. } => extent(points.iter()),
+            DisplayCommand::FillPath { subpaths, .. } => extent(subpaths.iter().flatten()),
+            DisplayCommand::PushClip(_)
+            | DisplayCommand::PushClipPath { .. }
+            | DisplayCommand::PopClip
+            | DisplayCommand::PushStackingContext { .. }
+            | DisplayCommand::PopStackingContext
+            | DisplayCommand::PushLayer { .. }
+            | DisplayCommand::PopLayer => return None,
+        };
+        Some(bounds)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextDecorationStyleValue {
     Solid,
@@ -1433,6 +2265,12 @@ impl BackgroundRepeat {
             "no-repeat" => BackgroundRepeat::NoRepeat,
             "space" => BackgroundRepeat::Space,
             "round" => BackgroundRepeat::Round,
+            // Two values give the horizontal then the vertical repeat
+            "repeat no-repeat" => BackgroundRepeat::RepeatX,
+            "no-repeat repeat" => BackgroundRepeat::RepeatY,
+            "no-repeat no-repeat" => BackgroundRepeat::NoRepeat,
+            "space space" => BackgroundRepeat::Space,
+            "round round" => BackgroundRepeat::Round,
             _ => BackgroundRepeat::default(),
         }
     }
@@ -1448,6 +2286,15 @@ impl BackgroundRepeat {
     }
 }
 
+/// The box of `d` that a background layer is positioned in or clipped to.
+fn background_area(d: &Dimensions, area: BackgroundBox) -> Rect {
+    match area {
+        BackgroundBox::BorderBox | BackgroundBox::Text => d.border_box(),
+        BackgroundBox::PaddingBox => d.padding_box(),
+        BackgroundBox::ContentBox => d.content,
+    }
+}
+
 /// Parse a CSS length value to pixels
 fn parse_length(value: &str) -> Option<f32> {
     let value = value.trim();
@@ -1481,119 +2328,337 @@ impl PaintItem {
     }
 }
 
-/// A display list of paint commands.
-#[derive(Debug, Default)]
-pub struct DisplayList {
-    pub commands: Vec<DisplayCommand>,
+/// A box painted in the positioned layers of a stacking context.
+enum Stacked<'a> {
+    /// A descendant that creates its own stacking context.
+    Context(&'a LayoutBox),
+    /// A positioned box with z-index auto, painted as if it created a
+    /// context but whose positioned descendants belong to the parent one.
+    Positioned(&'a LayoutBox, FlowLayers<'a>),
 }
 
-impl DisplayList {
-    /// Create an empty display list.
-    pub fn new() -> Self {
-        Self {
-            commands: Vec::new(),
-        }
-    }
+/// Non-positioned descendants of a stacking context, by paint layer.
+#[derive(Default)]
+struct FlowLayers<'a> {
+    /// In-flow block-level boxes (backgrounds and borders).
+    blocks: Vec<&'a LayoutBox>,
+    /// Floats, each painted atomically with its own flow content.
+    floats: Vec<(&'a LayoutBox, FlowLayers<'a>)>,
+    /// In-flow boxes whose inline content (text) paints in this layer.
+    /// Inline-blocks carry their own layers and paint atomically.
+    inlines: Vec<(&'a LayoutBox, Option<FlowLayers<'a>>)>,
+}
+
+fn is_block_level(layout_box: &LayoutBox) -> bool {
+    matches!(
+        layout_box.box_type,
+        BoxType::Block | BoxType::AnonymousBlock
+    )
+}
+
+/// Sort the descendants of `parent` into paint layers without crossing
+/// stacking context boundaries.
+///
+/// Positioned boxes and nested contexts go to `stacked` (owned by the
+/// nearest stacking context, even when found inside a float or a z-index
+/// auto positioned box); everything else goes to `flow`. Entries are
+/// flagged when they are or are inside a fixed box, with `fixed` telling
+/// whether `parent` is.
+fn collect_layers<'a>(
+    parent: &'a LayoutBox,
+    stacked: &mut Vec<(i32, Stacked<'a>, bool)>,
+    flow: &mut FlowLayers<'a>,
+    fixed: bool,
+) {
+    for child in &parent.children {
+        let fixed = fixed || child.position == Position::Fixed;
+        if child.creates_stacking_context() {
+            stacked.push((child.stacking_z(), Stacked::Context(child), fixed));
+        } else if child.position != Position::Static {
+            // Reserve the slot first so it paints before its descendants
+            let index = stacked.len();
+            stacked.push((0, Stacked::Positioned(child, FlowLayers::default()), fixed));
+            let mut inner = FlowLayers::default();
+            collect_layers(child, stacked, &mut inner, fixed);
+            stacked[index].1 = Stacked::Positioned(child, inner);
+        } else if child.float != Float::None {
+            let mut inner = FlowLayers::default();
+            collect_layers(child, stacked, &mut inner, fixed);
+            flow.floats.push((child, inner));
+        } else if matches!(child.box_type, BoxType::InlineBlock) {
+            let mut inner = FlowLayers::default();
+            collect_layers(child, stacked, &mut inner, fixed);
+            flow.inlines.push((child, Some(inner)));
+        } else {
+            if is_block_level(child) {
+                flow.blocks.push(child);
+            }
+            flow.inlines.push((child, None));
+            collect_layers(child, stacked, flow, fixed);
+        }
+    }
+}
+
+/// A display list of paint commands.
+///
+/// Commands of `position: fixed` subtrees come last, in paint order, as
+/// the `fixed_groups` ranges; everything before them scrolls with the
+/// document, so a scroll only re-translates it with [`Self::scrolled`].
+/// The images of `background-attachment: fixed` layers outside those
+/// subtrees stay put too, as the `fixed_backgrounds` ranges.
+#[derive(Debug, Default)]
+pub struct DisplayList {
+    pub commands: Vec<DisplayCommand>,
+    /// Ranges of `commands` painted by fixed subtrees, which stay put
+    /// when the document scrolls.
+    pub fixed_groups: Vec<Range<usize>>,
+    /// Ranges of `commands` drawing fixed background images, painted in
+    /// place but not scrolled.
+    pub fixed_backgrounds: Vec<Range<usize>>,
+    /// What fixed background layers are positioned in.
+    viewport: Rect,
+    /// Natural sizes of background images by URL.
+    image_sizes: HashMap<String, (f32, f32)>,
+    /// Set while building inside a fixed subtree.
+    in_fixed: bool,
+    /// Transformed stacking contexts the builder is inside.
+    transform_depth: usize,
+}
+
+impl From<Vec<DisplayCommand>> for DisplayList {
+    fn from(commands: Vec<DisplayCommand>) -> Self {
+        Self {
+            commands,
+            ..Self::default()
+        }
+    }
+}
+
+impl DisplayList {
+    /// Create an empty display list.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
     /// Build display list from a layout box with proper stacking order.
+    ///
+    /// Background images are drawn at the size of their positioning area,
+    /// and fixed ones are positioned in the root's border box.
     pub fn build(root: &LayoutBox) -> Self {
-        let mut list = DisplayList::new();
-        list.render_stacking_context(root, 0, &mut 0);
+        Self::build_with_images(root, root.dimensions.border_box(), HashMap::new())
+    }
+
+    /// Build display list, drawing background images at their natural
+    /// sizes from `image_sizes` and positioning fixed ones in `viewport`.
+    pub fn build_with_images(
+        root: &LayoutBox,
+        viewport: Rect,
+        image_sizes: HashMap<String, (f32, f32)>,
+    ) -> Self {
+        let mut list = DisplayList {
+            viewport,
+            image_sizes,
+            ..Self::default()
+        };
+        list.render_stacking_context(root);
+        list.group_fixed();
         list
     }
 
-    /// Render a stacking context with proper z-ordering.
-    fn render_stacking_context(&mut self, layout_box: &LayoutBox, parent_z: i32, layer: &mut u32) {
-        let z_index = if layout_box.position != Position::Static {
-            layout_box.z_index
-        } else {
-            parent_z
+    /// Move the fixed subtrees' commands, captured where they painted,
+    /// after the scrolled content.
+    fn group_fixed(&mut self) {
+        if self.fixed_groups.is_empty() {
+            return;
+        }
+        // Fixed backgrounds move up past the groups painted before them
+        for range in &mut self.fixed_backgrounds {
+            let shift: usize = self
+                .fixed_groups
+                .iter()
+                .filter(|group| group.end <= range.start)
+                .map(|group| group.len())
+                .sum();
+            *range = range.start - shift..range.end - shift;
+        }
+        let mut fixed = Vec::new();
+        let mut ranges = Vec::new();
+        for range in self.fixed_groups.iter().rev() {
+            fixed.push(self.commands.drain(range.clone()).collect::<Vec<_>>());
+        }
+        for group in fixed.into_iter().rev() {
+            let start = self.commands.len();
+            self.commands.extend(group);
+            ranges.push(start..self.commands.len());
+        }
+        self.fixed_groups = ranges;
+    }
+
+    /// Whether command `index` stays put when the document scrolls: it
+    /// belongs to a fixed subtree or draws a fixed background image.
+    pub fn is_fixed(&self, index: usize) -> bool {
+        self.fixed_groups
+            .iter()
+            .chain(&self.fixed_backgrounds)
+            .any(|range| range.contains(&index))
+    }
+
+    /// The commands as painted with the document scrolled by (`dx`, `dy`):
+    /// everything but the fixed groups moves up and left.
+    pub fn scrolled(&self, dx: f32, dy: f32) -> Vec<DisplayCommand> {
+        let offset = TranslateScale {
+            translate_x: -dx,
+            translate_y: -dy,
+            ..TranslateScale::IDENTITY
         };
+        let mut commands = self.commands.clone();
+        for (index, command) in commands.iter_mut().enumerate() {
+            if !self.is_fixed(index) {
+                command.transform(&offset, (0.0, 0.0));
+            }
+        }
+        commands
+    }
 
-        // Check if this creates a new stacking context
-        let creates_context = layout_box
-            .stacking_context
-            .as_ref()
-            .map(|ctx| ctx.creates_context)
-            .unwrap_or(false);
+    /// A [`Culler`] over the commands, for painting only what a viewport
+    /// shows. The commands must not change while it is in use.
+    pub fn culler(&self) -> Culler {
+        let fixed: Vec<bool> = (0..self.commands.len())
+            .map(|index| self.is_fixed(index))
+            .collect();
+        Culler::new(&self.commands, &fixed)
+    }
 
+    /// Render a stacking context in CSS 2.1 appendix E order.
+    fn render_stacking_context(&mut self, layout_box: &LayoutBox) {
+        let creates_context = layout_box.creates_stacking_context();
+        let start = self.commands.len();
+        let transformed = layout_box.style.transform.is_some();
+        self.transform_depth += usize::from(transformed);
         if creates_context {
             self.commands.push(DisplayCommand::PushStackingContext {
-                z_index,
+                z_index: layout_box.stacking_z(),
                 rect: layout_box.dimensions.border_box(),
             });
         }
+        let layered = layout_box.has_layer();
+        if layered {
+            self.commands.push(DisplayCommand::PushLayer {
+                rect: layout_box.dimensions.border_box(),
+                filter: layout_box.style.filter.clone(),
+                backdrop_filter: layout_box.style.backdrop_filter.clone(),
+            });
+        }
 
-        // Render this box
-        self.render_box_content(layout_box);
+        let mut stacked = Vec::new();
+        let mut flow = FlowLayers::default();
+        collect_layers(layout_box, &mut stacked, &mut flow, false);
 
-        // Collect children grouped by paint order
-        let mut negative_z: Vec<(&LayoutBox, u32)> = Vec::new();
-        let mut normal_flow: Vec<(&LayoutBox, u32)> = Vec::new();
-        let mut positive_z: Vec<(&LayoutBox, u32)> = Vec::new();
+        // Stable sort keeps tree order within equal z-index
+        stacked.sort_by_key(|(z, ..)| *z);
 
-        for child in &layout_box.children {
-            *layer += 1;
-            let child_layer = *layer;
+        // 1. Background and borders of the context root
+        self.render_background(layout_box);
+        self.render_borders(layout_box);
 
-            if child.position != Position::Static {
-                if child.z_index < 0 {
-                    negative_z.push((child, child_layer));
-                } else {
-                    positive_z.push((child, child_layer));
-                }
-            } else if child.float != Float::None {
-                // Floats paint between normal flow and positioned
-                positive_z.push((child, child_layer));
-            } else {
-                normal_flow.push((child, child_layer));
-            }
+        // 2. Negative z-index stacking contexts
+        for item in stacked.iter().filter(|(z, ..)| *z < 0) {
+            self.render_stacked(item);
         }
 
-        // Sort by z-index, then by layer for stability
-        negative_z.sort_by(|a, b| {
-            let z_cmp = a.0.z_index.cmp(&b.0.z_index);
-            if z_cmp == Ordering::Equal {
-                a.1.cmp(&b.1)
-            } else {
-                z_cmp
-            }
-        });
-        positive_z.sort_by(|a, b| {
-            let z_cmp = a.0.z_index.cmp(&b.0.z_index);
-            if z_cmp == Ordering::Equal {
-                a.1.cmp(&b.1)
-            } else {
-                z_cmp
-            }
-        });
+        // 3-5. In-flow blocks, floats, then inline content
+        self.render_text(layout_box);
+        self.render_flow(&flow);
 
-        // Render in correct order:
-        // 1. Negative z-index positioned descendants
-        for (child, _) in negative_z {
-            self.render_stacking_context(child, z_index, layer);
+        // 6-7. Positioned descendants with z-index auto or 0, then positive
+        for item in stacked.iter().filter(|(z, ..)| *z >= 0) {
+            self.render_stacked(item);
         }
 
-        // 2. Normal flow block children
-        for (child, _) in &normal_flow {
-            self.render_stacking_context(child, z_index, layer);
+        // 10. Outlines, above everything else in the context
+        self.render_outlines(layout_box);
+
+        if creates_context {
+            self.apply_effects(layout_box, start);
+            if layered {
+                self.commands.push(DisplayCommand::PopLayer);
+            }
+            self.commands.push(DisplayCommand::PopStackingContext);
         }
+        self.transform_depth -= usize::from(transformed);
+    }
 
-        // 3. Floats and positive/zero z-index positioned descendants
-        for (child, _) in positive_z {
-            self.render_stacking_context(child, z_index, layer);
+    /// Apply the context root's opacity and transform to what the context
+    /// painted from command `start` on. Transforms other than translations
+    /// and scales are not painted.
+    fn apply_effects(&mut self, layout_box: &LayoutBox, start: usize) {
+        let style = &layout_box.style;
+        if style.opacity < 1.0 {
+            for command in &mut self.commands[start..] {
+                command.fade(style.opacity);
+            }
         }
+        let Some(transform) = style.transform.as_deref().and_then(TranslateScale::parse) else {
+            return;
+        };
+        if transform.is_identity() {
+            return;
+        }
+        // The default transform-origin is the border box center
+        let border_box = layout_box.dimensions.border_box();
+        let origin = (
+            border_box.x + border_box.width / 2.0,
+            border_box.y + border_box.height / 2.0,
+        );
+        for command in &mut self.commands[start..] {
+            command.transform(&transform, origin);
+        }
+    }
 
-        if creates_context {
-            self.commands.push(DisplayCommand::PopStackingContext);
+    /// Render an entry from a stacking context's positioned layers,
+    /// capturing it as a fixed group if it is or is inside a fixed box.
+    fn render_stacked(&mut self, (_, item, fixed): &(i32, Stacked<'_>, bool)) {
+        let capture = *fixed && !self.in_fixed && self.transform_depth == 0;
+        let start = self.commands.len();
+        self.in_fixed |= capture;
+        match item {
+            Stacked::Context(layout_box) => self.render_stacking_context(layout_box),
+            Stacked::Positioned(layout_box, flow) => {
+                self.render_background(layout_box);
+                self.render_borders(layout_box);
+                self.render_text(layout_box);
+                self.render_flow(flow);
+            }
+        }
+        if capture {
+            self.in_fixed = false;
+            self.fixed_groups.push(start..self.commands.len());
         }
     }
 
-    /// Render a layout box's own content (background, borders, text).
-    fn render_box_content(&mut self, layout_box: &LayoutBox) {
-        self.render_background(layout_box);
-        self.render_borders(layout_box);
-        self.render_text(layout_box);
+    /// Render the non-positioned layers of a stacking context or of a box
+    /// painted as if it created one.
+    fn render_flow(&mut self, flow: &FlowLayers<'_>) {
+        for block in &flow.blocks {
+            self.render_background(block);
+            self.render_borders(block);
+        }
+        for (float, inner) in &flow.floats {
+            self.render_background(float);
+            self.render_borders(float);
+            self.render_text(float);
+            self.render_flow(inner);
+        }
+        for (inline, atomic) in &flow.inlines {
+            if !is_block_level(inline) {
+                self.render_background(inline);
+                self.render_borders(inline);
+            }
+            self.render_text(inline);
+            if let Some(inner) = atomic {
+                self.render_flow(inner);
+            }
+        }
     }
 
     /// Render a layout box and its children (legacy method).
@@ -1608,15 +2673,101 @@ impl DisplayList {
         }
     }
 
-    /// Render background.
+    /// Render background: the color, clipped like the bottom layer, then
+    /// the image layers from the bottom up.
     fn render_background(&mut self, layout_box: &LayoutBox) {
-        let color = layout_box.style.background_color;
-        if color.a > 0.0 {
+        let style = &layout_box.style;
+        let layers = style.background_layers();
+        let clip = layers
+            .last()
+            .map_or(BackgroundBox::BorderBox, |layer| layer.clip);
+        let color = style.background_color;
+        // `background-clip: text` is not painted
+        if color.a > 0.0 && clip != BackgroundBox::Text {
             self.commands.push(DisplayCommand::SolidColor(
                 color,
-                layout_box.dimensions.border_box(),
+                background_area(&layout_box.dimensions, clip),
             ));
         }
+        if style.has_background_image() {
+            for layer in layers.iter().rev() {
+                self.render_background_layer(layout_box, layer);
+            }
+        }
+    }
+
+    /// Render the image of a background layer, tiled over and clipped to
+    /// its painting area.
+    fn render_background_layer(&mut self, layout_box: &LayoutBox, layer: &BackgroundLayer) {
+        let Some(url) = &layer.image else {
+            return;
+        };
+        if layer.clip == BackgroundBox::Text {
+            return;
+        }
+        let d = &layout_box.dimensions;
+        let painting = background_area(d, layer.clip);
+        // Fixed layers under a transform scroll with the element
+        let fixed = layer.attachment == BackgroundAttachment::Fixed && self.transform_depth == 0;
+        let positioning = if fixed {
+            self.viewport
+        } else {
+            background_area(d, layer.origin)
+        };
+        let (image_width, image_height) = self
+            .image_sizes
+            .get(url)
+            .copied()
+            .unwrap_or((positioning.width, positioning.height));
+        let size = BackgroundSize::from_css(&layer.size);
+        let (width, height) = size.compute_size(positioning, image_width, image_height);
+        if width <= 0.0 || height <= 0.0 || painting.width <= 0.0 || painting.height <= 0.0 {
+            return;
+        }
+
+        let font_size = match layout_box.style.font_size {
+            Length::Px(px) => px,
+            _ => 16.0,
+        };
+        let (x, y) = layer.position;
+        let tile = Rect::new(
+            positioning.x + x.resolve(positioning.width, width, font_size),
+            positioning.y + y.resolve(positioning.height, height, font_size),
+            width,
+            height,
+        );
+        let fraction = |offset: f32, free: f32| if free == 0.0 { 0.0 } else { offset / free };
+        let position = (
+            fraction(tile.x - positioning.x, positioning.width - width),
+            fraction(tile.y - positioning.y, positioning.height - height),
+        );
+        // A fixed image only shows through the viewport, so tiling it is
+        // enough whatever the scroll offset
+        let area = if fixed { positioning } else { painting };
+        let repeat = BackgroundRepeat::from_css(&layer.repeat);
+        let tiles = images::tile_background(url, tile, area, &size, position, repeat);
+        let clipped = fixed
+            || tiles.iter().any(|command| {
+                let DisplayCommand::BackgroundImage { rect, .. } = command else {
+                    return false;
+                };
+                rect.x < painting.x
+                    || rect.y < painting.y
+                    || rect.right() > painting.right()
+                    || rect.bottom() > painting.bottom()
+            });
+
+        if clipped {
+            self.commands.push(DisplayCommand::PushClip(painting));
+        }
+        let start = self.commands.len();
+        self.commands.extend(tiles);
+        if fixed && !self.in_fixed && start < self.commands.len() {
+            self.fixed_backgrounds.push(start..self.commands.len());
+        }
+        if clipped {
+            self.commands.push(DisplayCommand::PopClip);
+        }
     }
 
     /// Render borders.
@@ -1672,95 +2823,202 @@ impl DisplayList {
             self.commands
                 .push(DisplayCommand::SolidColor(s.border_left_color, rect));
         }
+
+        if layout_box.column_boxes.len() > 1 {
+            self.render_column_rules(layout_box);
+        }
     }
 
-    /// Render text with decorations.
-    fn render_text(&mut self, layout_box: &LayoutBox) {
-        if let BoxType::Text(ref text) = layout_box.box_type {
-            let style = &layout_box.style;
-            let font_size = match style.font_size {
+    /// Render the rules centered in the gaps between columns.
+    fn render_column_rules(&mut self, layout_box: &LayoutBox) {
+        let s = &layout_box.style;
+        let font_size = match s.font_size {
+            Length::Px(px) => px,
+            _ => 16.0,
+        };
+        let width = s.column_rule_width.to_px(font_size, 16.0, 0.0);
+        if s.column_rule_style == OutlineStyle::None || width <= 0.0 {
+            return;
+        }
+        let color = s.column_rule_color.unwrap_or(s.color);
+        for pair in layout_box.column_boxes.windows(2) {
+            let x = (pair[0].right() + pair[1].x - width) / 2.0;
+            let (top, bottom) = (pair[0].y, pair[0].bottom().max(pair[1].bottom()));
+            match s.column_rule_style {
+                OutlineStyle::Double if width >= 3.0 => {
+                    let line = width / 3.0;
+                    for x in [x, x + width - line] {
+                        let rect = Rect::new(x, top, line, bottom - top);
+                        self.commands.push(DisplayCommand::SolidColor(color, rect));
+                    }
+                }
+                OutlineStyle::Dotted | OutlineStyle::Dashed => {
+                    let dash = if s.column_rule_style == OutlineStyle::Dotted {
+                        width
+                    } else {
+                        width * 3.0
+                    }
+                    .max(1.0);
+                    let mut y = top;
+                    while y < bottom {
+                        let rect = Rect::new(x, y, width, dash.min(bottom - y));
+                        self.commands.push(DisplayCommand::SolidColor(color, rect));
+                        y += dash * 2.0;
+                    }
+                }
+                _ => {
+                    let rect = Rect::new(x, top, width, bottom - top);
+                    self.commands.push(DisplayCommand::SolidColor(color, rect));
+                }
+            }
+        }
+    }
+
+    /// Render the outlines of a box and of its descendants in the same
+    /// stacking context.
+    fn render_outlines(&mut self, layout_box: &LayoutBox) {
+        let s = &layout_box.style;
+        if s.outline_style != OutlineStyle::None {
+            let font_size = match s.font_size {
                 Length::Px(px) => px,
                 _ => 16.0,
             };
+            let width = s.outline_width.to_px(font_size, 16.0, 0.0);
+            if width > 0.0 {
+                self.commands.push(DisplayCommand::Outline {
+                    rect: layout_box.dimensions.border_box(),
+                    width,
+                    color: s.outline_color.unwrap_or(s.color),
+                    style: s.outline_style,
+                    offset: s.outline_offset.to_px(font_size, 16.0, 0.0),
+                });
+            }
+        }
+        for child in &layout_box.children {
+            if !child.creates_stacking_context() {
+                self.render_outlines(child);
+            }
+        }
+    }
 
-            let x = layout_box.dimensions.content.x;
-            let y = layout_box.dimensions.content.y;
-            let text_width = layout_box.dimensions.content.width;
+    /// Render text with decorations.
+    fn render_text(&mut self, layout_box: &LayoutBox) {
+        let BoxType::Text(ref text) = layout_box.box_type else {
+            return;
+        };
+        if layout_box.text_lines.is_empty() {
+            let content = layout_box.dimensions.content;
+            let text = text.replace(crate::line_break::SOFT_HYPHEN, "");
+            self.render_text_run(
+                &layout_box.style,
+                &text,
+                content.x,
+                content.y,
+                content.width,
+            );
+            return;
+        }
+        for line in &layout_box.text_lines {
+            let rect = line.rect;
+            self.render_text_run(&layout_box.style, &line.text, rect.x, rect.y, rect.width);
+            if let Some(ellipsis) = line.ellipsis {
+                self.render_text_run(
+                    &layout_box.style,
+                    ELLIPSIS,
+                    ellipsis.x,
+                    ellipsis.y,
+                    ellipsis.width,
+                );
+            }
+        }
+    }
 
-            // Draw text
-            self.commands.push(DisplayCommand::Text {
-                text: text.clone(),
-                x,
-                y,
-                color: style.color,
-                font_size,
-                font_family: style.font_family.clone(),
-                font_weight: style.font_weight.0,
-                font_style: match style.font_style {
-                    rustkit_css::FontStyle::Normal => 0,
-                    rustkit_css::FontStyle::Italic => 1,
-                    rustkit_css::FontStyle::Oblique => 2,
-                },
-            });
+    /// Draw one run of text with its decorations.
+    fn render_text_run(
+        &mut self,
+        style: &ComputedStyle,
+        text: &str,
+        x: f32,
+        y: f32,
+        text_width: f32,
+    ) {
+        let font_size = match style.font_size {
+            Length::Px(px) => px,
+            _ => 16.0,
+        };
 
-            // Draw text decorations
-            let decoration_line = style.text_decoration_line;
-            if decoration_line.underline || decoration_line.overline || decoration_line.line_through
-            {
-                let decoration_color = style.text_decoration_color.unwrap_or(style.color);
-                let decoration_style = match style.text_decoration_style {
-                    rustkit_css::TextDecorationStyle::Solid => TextDecorationStyleValue::Solid,
-                    rustkit_css::TextDecorationStyle::Double => TextDecorationStyleValue::Double,
-                    rustkit_css::TextDecorationStyle::Dotted => TextDecorationStyleValue::Dotted,
-                    rustkit_css::TextDecorationStyle::Dashed => TextDecorationStyleValue::Dashed,
-                    rustkit_css::TextDecorationStyle::Wavy => TextDecorationStyleValue::Wavy,
-                };
+        // Draw text
+        self.commands.push(DisplayCommand::Text {
+            text: text.to_string(),
+            x,
+            y,
+            color: style.color,
+            font_size,
+            font_family: style.font_family.clone(),
+            font_weight: style.font_weight.0,
+            font_style: match style.font_style {
+                rustkit_css::FontStyle::Normal => 0,
+                rustkit_css::FontStyle::Italic => 1,
+                rustkit_css::FontStyle::Oblique => 2,
+            },
+        });
 
-                // Calculate thickness
-                let thickness = match style.text_decoration_thickness {
-                    Length::Px(px) => px,
-                    Length::Em(em) => em * font_size,
-                    _ => font_size / 14.0, // Auto thickness
-                };
+        // Draw text decorations
+        let decoration_line = style.text_decoration_line;
+        if decoration_line.underline || decoration_line.overline || decoration_line.line_through {
+            let decoration_color = style.text_decoration_color.unwrap_or(style.color);
+            let decoration_style = match style.text_decoration_style {
+                rustkit_css::TextDecorationStyle::Solid => TextDecorationStyleValue::Solid,
+                rustkit_css::TextDecorationStyle::Double => TextDecorationStyleValue::Double,
+                rustkit_css::TextDecorationStyle::Dotted => TextDecorationStyleValue::Dotted,
+                rustkit_css::TextDecorationStyle::Dashed => TextDecorationStyleValue::Dashed,
+                rustkit_css::TextDecorationStyle::Wavy => TextDecorationStyleValue::Wavy,
+            };
 
-                let ascent = font_size * 0.8;
-                let descent = font_size * 0.2;
-
-                // Underline
-                if decoration_line.underline {
-                    self.commands.push(DisplayCommand::TextDecoration {
-                        x,
-                        y: y + ascent + descent * 0.3,
-                        width: text_width,
-                        thickness,
-                        color: decoration_color,
-                        style: decoration_style,
-                    });
-                }
+            // Calculate thickness
+            let thickness = match style.text_decoration_thickness {
+                Length::Px(px) => px,
+                Length::Em(em) => em * font_size,
+                _ => font_size / 14.0, // Auto thickness
+            };
 
-                // Overline
-                if decoration_line.overline {
-                    self.commands.push(DisplayCommand::TextDecoration {
-                        x,
-                        y: y - thickness,
-                        width: text_width,
-                        thickness,
-                        color: decoration_color,
-                        style: decoration_style,
-                    });
-                }
+            let ascent = font_size * 0.8;
+            let descent = font_size * 0.2;
+
+            // Underline
+            if decoration_line.underline {
+                self.commands.push(DisplayCommand::TextDecoration {
+                    x,
+                    y: y + ascent + descent * 0.3,
+                    width: text_width,
+                    thickness,
+                    color: decoration_color,
+                    style: decoration_style,
+                });
+            }
 
-                // Line-through (strikethrough)
-                if decoration_line.line_through {
-                    self.commands.push(DisplayCommand::TextDecoration {
-                        x,
-                        y: y + ascent * 0.35,
-                        width: text_width,
-                        thickness,
-                        color: decoration_color,
-                        style: decoration_style,
-                    });
-                }
+            // Overline
+            if decoration_line.overline {
+                self.commands.push(DisplayCommand::TextDecoration {
+                    x,
+                    y: y - thickness,
+                    width: text_width,
+                    thickness,
+                    color: decoration_color,
+                    style: decoration_style,
+                });
+            }
+
+            // Line-through (strikethrough)
+            if decoration_line.line_through {
+                self.commands.push(DisplayCommand::TextDecoration {
+                    x,
+                    y: y + ascent * 0.35,
+                    width: text_width,
+                    thickness,
+                    color: decoration_color,
+                    style: decoration_style,
+                });
             }
         }
     }
@@ -1948,6 +3206,95 @@ mod tests {
         assert_eq!(ctx.clear(Clear::None), 0.0);
     }
 
+    fn float_style(width: f32, height: f32) -> ComputedStyle {
+        ComputedStyle {
+            width: Length::Px(width),
+            height: Length::Px(height),
+            ..ComputedStyle::new()
+        }
+    }
+
+    fn paragraph(text: &str) -> LayoutBox {
+        let mut p = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        let text = LayoutBox::new(BoxType::Text(text.into()), ComputedStyle::new());
+        p.children.push(text);
+        p
+    }
+
+    #[test]
+    fn test_text_wraps_around_float_across_paragraphs() {
+        let mut root = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        root.children = vec![
+            LayoutBox::with_float(BoxType::Block, float_style(100.0, 60.0), Float::Left),
+            paragraph(&"word ".repeat(12)),
+            paragraph(&"word ".repeat(30)),
+        ];
+        root.layout(&Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 0.0),
+            ..Default::default()
+        });
+
+        // Lines beside the float start at its right edge and are shortened
+        let first = &root.children[1].children[0].text_lines;
+        assert_eq!(first.len(), 2);
+        assert!(first
+            .iter()
+            .all(|line| line.rect.x == 100.0 && line.rect.right() <= 400.0));
+        let second = &root.children[2].children[0].text_lines;
+        assert_eq!(second[0].rect.y, 38.4);
+        assert_eq!(second[0].rect.x, 100.0);
+        assert!(second[0].rect.width <= 300.0);
+        // Below the float, lines take the full width again
+        let below = second.iter().find(|line| line.rect.y >= 60.0).unwrap();
+        assert_eq!(below.rect.x, 0.0);
+        assert!(below.rect.width > 300.0);
+    }
+
+    #[test]
+    fn test_flow_root_contains_its_float() {
+        let style = ComputedStyle {
+            display: rustkit_css::Display::FlowRoot,
+            ..ComputedStyle::new()
+        };
+        let mut parent = LayoutBox::new(BoxType::Block, style);
+        parent.children = vec![
+            LayoutBox::with_float(BoxType::Block, float_style(50.0, 200.0), Float::Left),
+            paragraph("short"),
+        ];
+        let mut root = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        root.children = vec![parent, paragraph("after")];
+        root.layout(&Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 0.0),
+            ..Default::default()
+        });
+
+        assert_eq!(root.children[0].dimensions.content.height, 200.0);
+        // The float doesn't reach the sibling after the flow root
+        let after = &root.children[1];
+        assert_eq!(after.dimensions.content.y, 200.0);
+        assert_eq!(after.children[0].dimensions.content.x, 0.0);
+    }
+
+    #[test]
+    fn test_clearance_uses_up_margin() {
+        let mut root = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        let mut cleared = paragraph("below");
+        cleared.clear = Clear::Left;
+        cleared.style.margin_top = Length::Px(20.0);
+        root.children = vec![
+            LayoutBox::with_float(BoxType::Block, float_style(100.0, 60.0), Float::Left),
+            cleared,
+        ];
+        root.layout(&Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 0.0),
+            ..Default::default()
+        });
+
+        // The border box sits at the float's bottom, not 20px further down
+        assert_eq!(root.children[1].dimensions.border_box().y, 60.0);
+        assert!((root.dimensions.content.height - 79.2).abs() < 0.01);
+    }
+
     #[test]
     fn test_position_offsets() {
         let style = ComputedStyle::new();
@@ -1966,7 +3313,7 @@ mod tests {
         let mut layout_box = LayoutBox::with_position(BoxType::Block, style, Position::Absolute);
         layout_box.set_z_index(5);
 
-        assert_eq!(layout_box.z_index, 5);
+        assert_eq!(layout_box.z_index, Some(5));
         let ctx = layout_box.stacking_context.as_ref().unwrap();
         assert!(ctx.creates_context);
         assert_eq!(ctx.z_index, 5);
@@ -2022,8 +3369,445 @@ mod tests {
 
         // Order should be: negative z-index, normal flow, positive z-index
         assert_eq!(paint_order.len(), 3);
-        assert_eq!(paint_order[0].z_index, -1);
+        assert_eq!(paint_order[0].z_index, Some(-1));
         assert_eq!(paint_order[1].position, Position::Static);
-        assert_eq!(paint_order[2].z_index, 1);
+        assert_eq!(paint_order[2].z_index, Some(1));
+    }
+
+    /// A box with a background whose red channel identifies it.
+    fn tagged(tag: u8, position: Position, z_index: Option<i32>) -> LayoutBox {
+        let mut style = ComputedStyle::new();
+        style.background_color = Color::from_rgb(tag, 0, 0);
+        let mut layout_box = LayoutBox::with_position(BoxType::Block, style, position);
+        layout_box.set_z_index(z_index);
+        layout_box
+    }
+
+    /// Paint sequence as background tags, with 0 for text and
+    /// `Push`/`Pop` stacking contexts as 254/255.
+    fn paint_sequence(root: &LayoutBox) -> Vec<u8> {
+        DisplayList::build(root)
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::SolidColor(color, _) => Some(color.r),
+                DisplayCommand::Text { .. } => Some(0),
+                DisplayCommand::PushStackingContext { .. } => Some(254),
+                DisplayCommand::PopStackingContext => Some(255),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_z_index_auto_does_not_create_context() {
+        let mut root = tagged(1, Position::Static, None);
+        let mut wrapper = tagged(2, Position::Relative, None);
+        wrapper
+            .children
+            .push(tagged(3, Position::Absolute, Some(-1)));
+        root.children.push(wrapper);
+
+        // The negative child escapes to the root context and paints below
+        // its z-index: auto parent
+        let wrapper = &root.children[0];
+        assert!(!wrapper.creates_stacking_context());
+        assert!(!wrapper.stacking_context.as_ref().unwrap().creates_context);
+        assert_eq!(paint_sequence(&root), vec![1, 254, 3, 255, 2]);
+
+        // With z-index: 0 the parent contains it
+        root.children[0].set_z_index(0);
+        assert!(root.children[0].creates_stacking_context());
+        assert_eq!(paint_sequence(&root), vec![1, 254, 2, 254, 3, 255, 255]);
+    }
+
+    #[test]
+    fn test_opacity_and_transform_create_contexts() {
+        let mut style = ComputedStyle::new();
+        style.opacity = 0.5;
+        let mut faded = LayoutBox::new(BoxType::Block, style);
+        assert!(faded.creates_stacking_context());
+
+        // A negative z-index child stays inside the translucent box
+        faded.children.push(tagged(3, Position::Absolute, Some(-1)));
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(faded);
+        assert_eq!(paint_sequence(&root), vec![1, 254, 254, 3, 255, 255]);
+
+        let mut style = ComputedStyle::new();
+        style.transform = Some("translateX(10px)".to_string());
+        let transformed = LayoutBox::new(BoxType::Block, style);
+        assert!(transformed.creates_stacking_context());
+        assert!(!LayoutBox::new(BoxType::Block, ComputedStyle::new()).creates_stacking_context());
+    }
+
+    #[test]
+    fn test_opacity_and_transform_paint_the_context() {
+        let mut style = ComputedStyle::new();
+        style.opacity = 0.5;
+        style.transform = Some("translateX(10px) scale(2)".to_string());
+        let mut layer = tagged(2, Position::Static, None);
+        layer.style = style;
+        layer.style.background_color = Color::from_rgb(2, 0, 0);
+        layer.dimensions.content = Rect::new(0.0, 0.0, 20.0, 10.0);
+        let mut inner = tagged(3, Position::Static, None);
+        inner.dimensions.content = Rect::new(0.0, 0.0, 10.0, 10.0);
+        layer.children.push(inner);
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(layer);
+
+        let painted: Vec<_> = DisplayList::build(&root)
+            .commands
+            .into_iter()
+            .filter_map(|c| match c {
+                DisplayCommand::SolidColor(color, r) => {
+                    Some((color.r, color.a, (r.x, r.y, r.width, r.height)))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(painted[0], (1, 1.0, (0.0, 0.0, 0.0, 0.0)));
+        // Scaled about the layer's center (10, 5), then moved right
+        assert_eq!(painted[1], (2, 0.5, (0.0, -5.0, 40.0, 20.0)));
+        assert_eq!(painted[2], (3, 0.5, (0.0, -5.0, 20.0, 20.0)));
+    }
+
+    #[test]
+    fn test_filter_paints_through_a_layer() {
+        let mut layer = tagged(2, Position::Static, None);
+        layer.style.filter = FilterChain::parse("blur(2px) grayscale(1)").unwrap();
+        layer.style.transform = Some("scale(2)".to_string());
+        layer.dimensions.content = Rect::new(0.0, 0.0, 20.0, 10.0);
+        assert!(layer.creates_stacking_context());
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(layer);
+
+        let commands = DisplayList::build(&root).commands;
+        let push = commands
+            .iter()
+            .position(|c| matches!(c, DisplayCommand::PushLayer { .. }))
+            .unwrap();
+        assert!(matches!(
+            commands[push - 1],
+            DisplayCommand::PushStackingContext { .. }
+        ));
+        assert!(matches!(
+            commands[commands.len() - 2..],
+            [DisplayCommand::PopLayer, DisplayCommand::PopStackingContext]
+        ));
+        // Blurs scale with the layer's transform
+        let DisplayCommand::PushLayer { rect, filter, .. } = &commands[push] else {
+            unreachable!()
+        };
+        assert_eq!((rect.x, rect.width), (-10.0, 40.0));
+        assert_eq!(filter.outset(), 12.0);
+    }
+
+    #[test]
+    fn test_paint_order_appendix_e() {
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(tagged(10, Position::Absolute, Some(1)));
+
+        let mut block = tagged(20, Position::Static, None);
+        block.children.push(LayoutBox::new(
+            BoxType::Text("hello".to_string()),
+            ComputedStyle::new(),
+        ));
+        root.children.push(block);
+
+        let mut float = tagged(30, Position::Static, None);
+        float.float = Float::Left;
+        // Positioned descendants of a float belong to the parent context
+        float.children.push(tagged(31, Position::Relative, None));
+        root.children.push(float);
+
+        root.children.push(tagged(40, Position::Relative, None));
+        root.children.push(tagged(50, Position::Absolute, Some(-2)));
+
+        // Root, negative z, blocks, floats, inline content, auto in tree
+        // order, positive z
+        assert_eq!(
+            paint_sequence(&root),
+            vec![1, 254, 50, 255, 20, 30, 0, 31, 40, 254, 10, 255]
+        );
+
+        let hit_order: Vec<_> = root
+            .get_paint_order()
+            .iter()
+            .map(|b| b.style.background_color.r)
+            .collect();
+        assert_eq!(hit_order, vec![50, 20, 30, 40, 10]);
+    }
+
+    #[test]
+    fn test_outlines_paint_last_in_their_context() {
+        let mut root = tagged(1, Position::Static, None);
+        let mut outlined = tagged(2, Position::Static, None);
+        outlined.style.outline_style = OutlineStyle::Dashed;
+        outlined.style.outline_width = Length::Px(4.0);
+        outlined.style.outline_offset = Length::Px(2.0);
+        outlined.dimensions.content = Rect::new(10.0, 10.0, 20.0, 10.0);
+        outlined.dimensions.border = EdgeSizes {
+            top: 1.0,
+            right: 1.0,
+            bottom: 1.0,
+            left: 1.0,
+        };
+        root.children.push(outlined);
+        root.children.push(tagged(3, Position::Static, None));
+
+        let commands = DisplayList::build(&root).commands;
+        let outline = commands
+            .iter()
+            .position(|c| matches!(c, DisplayCommand::Outline { .. }))
+            .unwrap();
+        // After the later sibling's background; the border box is unchanged
+        assert_eq!(outline, commands.len() - 1);
+        assert_eq!(
+            format!("{:?}", commands[outline]),
+            format!(
+                "{:?}",
+                DisplayCommand::Outline {
+                    rect: Rect::new(9.0, 9.0, 22.0, 12.0),
+                    width: 4.0,
+                    color: Color::BLACK,
+                    style: OutlineStyle::Dashed,
+                    offset: 2.0,
+                }
+            )
+        );
+
+        // An outline in a nested context paints before the context ends
+        root.children[0].position = Position::Relative;
+        root.children[0].set_z_index(1);
+        let commands = DisplayList::build(&root).commands;
+        assert!(matches!(
+            commands[commands.len() - 2..],
+            [
+                DisplayCommand::Outline { .. },
+                DisplayCommand::PopStackingContext
+            ]
+        ));
+    }
+
+    /// A block of the given size painted with `tag`.
+    fn sized(tag: u8, position: Position, width: f32, height: f32) -> LayoutBox {
+        let mut layout_box = tagged(tag, position, None);
+        layout_box.style.width = Length::Px(width);
+        layout_box.style.height = Length::Px(height);
+        layout_box
+    }
+
+    #[test]
+    fn test_fixed_boxes_are_placed_in_the_viewport() {
+        let laid_out = |transform: Option<&str>| {
+            let mut header = sized(3, Position::Fixed, 100.0, 40.0);
+            header.set_offsets(Some(10.0), None, None, Some(20.0));
+            header.children.push(sized(4, Position::Static, 50.0, 20.0));
+            let mut footer = sized(5, Position::Fixed, 100.0, 40.0);
+            footer.set_offsets(None, None, Some(0.0), None);
+
+            let mut wrapper = tagged(2, Position::Static, None);
+            wrapper.style.transform = transform.map(str::to_string);
+            wrapper.children.push(header);
+            wrapper.children.push(footer);
+            let mut root = tagged(1, Position::Static, None);
+            root.children.push(sized(6, Position::Static, 800.0, 300.0));
+            root.children.push(wrapper);
+            root.layout(&Dimensions {
+                content: Rect::new(0.0, 0.0, 800.0, 0.0),
+                ..Default::default()
+            });
+            root.layout_fixed(Rect::new(0.0, 0.0, 800.0, 600.0));
+            root
+        };
+
+        let root = laid_out(None);
+        let wrapper = &root.children[1];
+        let header = &wrapper.children[0];
+        assert_eq!(
+            (header.dimensions.content.x, header.dimensions.content.y),
+            (20.0, 10.0)
+        );
+        // Descendants move with the fixed box
+        let inner = header.children[0].dimensions.content;
+        assert_eq!((inner.x, inner.y), (20.0, 10.0));
+        // Without a horizontal offset, the static position stays
+        let footer = wrapper.children[1].dimensions.content;
+        assert_eq!((footer.x, footer.y + footer.height), (0.0, 600.0));
+
+        // Inside a transformed ancestor, fixed degrades to absolute
+        let root = laid_out(Some("translateX(10px)"));
+        let header = root.children[1].children[0].dimensions.content;
+        assert!(header.y >= 300.0, "{:?}", header);
+    }
+
+    #[test]
+    fn test_fixed_subtrees_paint_as_unscrolled_groups() {
+        let mut fixed = sized(2, Position::Fixed, 100.0, 40.0);
+        fixed.set_z_index(Some(-1));
+        fixed.children.push(tagged(3, Position::Static, None));
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(fixed);
+        root.children.push(tagged(4, Position::Static, None));
+
+        // The negative z-index layer still moves after the scrolled content
+        assert_eq!(paint_sequence(&root), vec![1, 4, 254, 2, 3, 255]);
+        let list = DisplayList::build(&root);
+        assert_eq!(list.fixed_groups, vec![2..list.commands.len()]);
+
+        let scrolled: Vec<_> = list
+            .scrolled(0.0, 100.0)
+            .into_iter()
+            .filter_map(|c| match c {
+                DisplayCommand::SolidColor(color, r) => Some((color.r, r.y)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(scrolled, [(1, -100.0), (4, -100.0), (2, 0.0), (3, 0.0)]);
+
+        // Under a transform the box scrolls with its ancestor
+        let mut layer = tagged(5, Position::Static, None);
+        layer.style.transform = Some("translateX(10px)".to_string());
+        layer.children.push(root.children.remove(0));
+        root.children.push(layer);
+        assert!(DisplayList::build(&root).fixed_groups.is_empty());
+    }
+
+    /// A 200x100 content box with 10px padding at (10, 10).
+    fn background_box(background: &str) -> LayoutBox {
+        let mut style = ComputedStyle::new();
+        style.set_background(rustkit_css::parse_background(background).unwrap());
+        let mut layout_box = LayoutBox::new(BoxType::Block, style);
+        layout_box.dimensions.content = Rect::new(10.0, 10.0, 200.0, 100.0);
+        layout_box.dimensions.padding = EdgeSizes {
+            top: 10.0,
+            right: 10.0,
+            bottom: 10.0,
+            left: 10.0,
+        };
+        layout_box
+    }
+
+    fn painted_backgrounds(list: &DisplayList) -> Vec<(String, Rect)> {
+        list.commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::BackgroundImage { url, rect, .. } => Some((url.clone(), *rect)),
+                DisplayCommand::SolidColor(_, rect) => Some(("color".to_string(), *rect)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn rect_of(rect: Rect) -> (f32, f32, f32, f32) {
+        (rect.x, rect.y, rect.width, rect.height)
+    }
+
+    #[test]
+    fn test_background_layers_paint_bottom_layer_first() {
+        let layout_box = background_box(
+            "url(top.png) no-repeat center bottom 10px, url(bottom.png) no-repeat right top",
+        );
+        let sizes = HashMap::from([
+            ("top.png".to_string(), (20.0, 20.0)),
+            ("bottom.png".to_string(), (40.0, 10.0)),
+        ]);
+        let list = DisplayList::build_with_images(&layout_box, Rect::zero(), sizes);
+        let painted: Vec<_> = painted_backgrounds(&list)
+            .into_iter()
+            .map(|(url, rect)| (url, rect_of(rect)))
+            .collect();
+        // Positioned in the 220x120 padding box at (0, 0)
+        assert_eq!(
+            painted,
+            [
+                ("bottom.png".to_string(), (180.0, 0.0, 40.0, 10.0)),
+                ("top.png".to_string(), (100.0, 90.0, 20.0, 20.0)),
+            ]
+        );
+        // Both fit their painting area, so nothing is clipped
+        assert!(!list
+            .commands
+            .iter()
+            .any(|c| matches!(c, DisplayCommand::PushClip(_))));
+    }
+
+    #[test]
+    fn test_background_clip_and_origin_boxes() {
+        let layout_box = background_box("red content-box");
+        let painted = painted_backgrounds(&DisplayList::build(&layout_box));
+        assert_eq!(painted.len(), 1);
+        assert_eq!(rect_of(painted[0].1), (10.0, 10.0, 200.0, 100.0));
+
+        // Tiles from the content box origin cover the padding box, clipped
+        let layout_box = background_box("url(a.png) content-box padding-box");
+        let sizes = HashMap::from([("a.png".to_string(), (50.0, 50.0))]);
+        let list = DisplayList::build_with_images(&layout_box, Rect::zero(), sizes);
+        assert!(matches!(
+            list.commands.first(),
+            Some(DisplayCommand::PushClip(clip)) if rect_of(*clip) == (0.0, 0.0, 220.0, 120.0)
+        ));
+        let tiles = painted_backgrounds(&list);
+        assert_eq!(rect_of(tiles[0].1), (-40.0, -40.0, 50.0, 50.0));
+        assert_eq!(tiles.len(), 6 * 4);
+
+        // Text clipping is not painted
+        let layout_box = background_box("url(a.png) red text");
+        assert!(DisplayList::build(&layout_box).commands.is_empty());
+    }
+
+    #[test]
+    fn test_fixed_backgrounds_stay_put_when_scrolled() {
+        // The fixed box paints before the background, then moves last
+        let mut fixed = sized(2, Position::Fixed, 10.0, 10.0);
+        fixed.set_z_index(Some(-1));
+        let mut root = tagged(1, Position::Static, None);
+        root.children.push(fixed);
+        root.children
+            .push(background_box("url(a.png) no-repeat fixed right bottom"));
+        let sizes = HashMap::from([("a.png".to_string(), (20.0, 20.0))]);
+        let list = DisplayList::build_with_images(&root, Rect::new(0.0, 0.0, 800.0, 600.0), sizes);
+        let image = list
+            .commands
+            .iter()
+            .position(|c| matches!(c, DisplayCommand::BackgroundImage { .. }))
+            .unwrap();
+        assert_eq!(list.fixed_backgrounds, vec![image..image + 1]);
+        let scrolled = list.scrolled(0.0, 100.0);
+        // The clip scrolls with the box, the image stays in the viewport
+        assert!(matches!(scrolled[image - 1], DisplayCommand::PushClip(clip) if clip.y == -100.0));
+        assert!(matches!(
+            scrolled[image],
+            DisplayCommand::BackgroundImage { rect, .. } if rect_of(rect) == (780.0, 580.0, 20.0, 20.0)
+        ));
+    }
+
+    #[test]
+    fn test_collect_node_geometry() {
+        let mut style = ComputedStyle::new();
+        style.overflow_y = rustkit_css::Overflow::Auto;
+        let mut parent =
+            LayoutBox::new(BoxType::Block, style).with_node_id(rustkit_dom::NodeId::new(1));
+        parent.dimensions.content = Rect::new(0.0, 0.0, 100.0, 50.0);
+
+        let mut child = LayoutBox::new(BoxType::Block, ComputedStyle::new())
+            .with_node_id(rustkit_dom::NodeId::new(2));
+        child.dimensions.content = Rect::new(0.0, 0.0, 80.0, 120.0);
+        child.dimensions.margin.bottom = 10.0;
+        parent.children.push(child);
+
+        let mut out = std::collections::HashMap::new();
+        parent.collect_node_geometry(&mut out);
+
+        let parent_geometry = &out[&rustkit_dom::NodeId::new(1)];
+        assert_eq!(parent_geometry.fragments.len(), 1);
+        assert_eq!(parent_geometry.overflow.width, 100.0);
+        assert_eq!(parent_geometry.overflow.height, 130.0);
+        assert_eq!(parent_geometry.scrollbars, (12.0, 0.0));
+
+        let child_geometry = &out[&rustkit_dom::NodeId::new(2)];
+        assert_eq!(child_geometry.fragments[0].border_box().height, 120.0);
+        assert_eq!(child_geometry.scrollbars, (0.0, 0.0));
     }
 }
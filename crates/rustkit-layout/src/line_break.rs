@@ -0,0 +1,478 @@
+//! Breaking text boxes into lines, and truncating overflowing lines with
+//! an ellipsis.
+//!
+//! Lines wrap at spaces unless `white-space` forbids it. A word longer than
+//! the line is only split when `overflow-wrap` or `word-break` allows it;
+//! `word-break: break-all` allows a break between any two characters.
+//! Before that, a word is hyphenated at its soft hyphens, or with `hyphens:
+//! auto` where the language's patterns allow; soft hyphens only show when
+//! a line ends on them. Widths use the same per-character estimate as the
+//! rest of text layout.
+
+use crate::hyphenation::Hyphenator;
+use crate::intrinsic::estimated_text_width;
+use crate::{Dimensions, FloatContext, LayoutBox, Rect};
+use rustkit_css::{ComputedStyle, Direction, Hyphens, OverflowWrap, TextOverflow, WordBreak};
+
+/// The glyph appended to text cut off by `text-overflow: ellipsis`.
+pub const ELLIPSIS: &str = "\u{2026}";
+
+/// U+00AD, a break opportunity that is invisible unless taken.
+pub const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Characters of `text` that are drawn.
+fn visible_len(text: &str) -> usize {
+    text.chars().filter(|&c| c != SOFT_HYPHEN).count()
+}
+
+/// The glyph ending a hyphenated line.
+pub(crate) fn hyphenate_character(style: &ComputedStyle) -> &str {
+    style.hyphenate_character.as_deref().unwrap_or("-")
+}
+
+/// Byte offsets in `word` where it may be hyphenated: after its soft
+/// hyphens, or where the patterns for `lang` allow with `hyphens: auto`.
+pub(crate) fn hyphenation_points(word: &str, style: &ComputedStyle) -> Vec<usize> {
+    if style.hyphens == Hyphens::None {
+        return Vec::new();
+    }
+    if word.contains(SOFT_HYPHEN) {
+        return word
+            .char_indices()
+            .filter(|&(_, c)| c == SOFT_HYPHEN)
+            .map(|(i, c)| i + c.len_utf8())
+            .collect();
+    }
+    if style.hyphens != Hyphens::Auto {
+        return Vec::new();
+    }
+    let Some(hyphenator) = style.lang.as_deref().and_then(Hyphenator::for_language) else {
+        return Vec::new();
+    };
+    // Punctuation around the word is not part of it
+    let start = word.find(char::is_alphabetic).unwrap_or(0);
+    let core = word[start..].trim_end_matches(|c: char| !c.is_alphabetic());
+    hyphenator
+        .break_points(core)
+        .into_iter()
+        .map(|at| start + at)
+        .collect()
+}
+
+/// The last of `points` after `start` that leaves a hyphenated piece of
+/// `word` no more than `room` characters long.
+fn hyphenation_break(
+    word: &str,
+    start: usize,
+    points: &[usize],
+    hyphen: &str,
+    room: usize,
+) -> Option<usize> {
+    points.iter().copied().rev().find(|&at| {
+        at > start && {
+            let piece = visible_len(&word[start..at]);
+            piece > 0 && piece + visible_len(hyphen) <= room
+        }
+    })
+}
+
+/// One line of a text box.
+#[derive(Debug, Clone)]
+pub struct TextLine {
+    pub text: String,
+    /// Where the line's text is drawn.
+    pub rect: Rect,
+    /// Position of the ellipsis ending a truncated line.
+    pub ellipsis: Option<Rect>,
+}
+
+/// Whether a word that doesn't fit on a line may be split.
+fn may_split_words(style: &ComputedStyle) -> bool {
+    style.overflow_wrap != OverflowWrap::Normal
+        || matches!(style.word_break, WordBreak::BreakWord | WordBreak::BreakAll)
+}
+
+/// Whether breaks inside words count toward min-content width.
+pub(crate) fn breaks_anywhere(style: &ComputedStyle) -> bool {
+    style.overflow_wrap == OverflowWrap::Anywhere
+        || matches!(style.word_break, WordBreak::BreakWord | WordBreak::BreakAll)
+}
+
+/// Split `text` into lines, each at most `width_of` its index wide.
+pub(crate) fn break_lines_within(
+    text: &str,
+    style: &ComputedStyle,
+    font_size: f32,
+    width_of: impl Fn(usize) -> f32,
+) -> Vec<String> {
+    if !style.white_space.wraps() || estimated_text_width(text, font_size) <= width_of(0) {
+        return vec![text.replace(SOFT_HYPHEN, "")];
+    }
+    // At least one character per line, however narrow the line
+    let char_width = estimated_text_width("x", font_size);
+    let capacity_of = |line: usize| ((width_of(line) / char_width).floor() as usize).max(1);
+
+    let mut lines = Vec::new();
+    if style.word_break == WordBreak::BreakAll {
+        let mut line = String::new();
+        for c in text.chars().filter(|&c| c != SOFT_HYPHEN) {
+            if line.is_empty() && c.is_whitespace() {
+                continue;
+            }
+            if line.chars().count() == capacity_of(lines.len()) {
+                lines.push(std::mem::take(&mut line).trim_end().to_string());
+                if c.is_whitespace() {
+                    continue;
+                }
+            }
+            line.push(c);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        return lines;
+    }
+
+    let split = may_split_words(style);
+    let hyphen = hyphenate_character(style);
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let capacity = capacity_of(lines.len());
+        let length = visible_len(&line);
+        if length > 0 && length + 1 + visible_len(word) <= capacity {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        let points = hyphenation_points(word, style);
+        let mut start = 0;
+        // Hyphenate the start of the word onto the end of the line
+        let room = match length {
+            0 => capacity,
+            _ => capacity.saturating_sub(length + 1),
+        };
+        if let Some(at) = hyphenation_break(word, start, &points, hyphen, room) {
+            if length > 0 {
+                line.push(' ');
+            }
+            line.push_str(&word[..at]);
+            line.push_str(hyphen);
+            start = at;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        while visible_len(&word[start..]) > capacity_of(lines.len()) {
+            let capacity = capacity_of(lines.len());
+            if let Some(at) = hyphenation_break(word, start, &points, hyphen, capacity) {
+                lines.push(format!("{}{}", &word[start..at], hyphen));
+                start = at;
+            } else if split {
+                let (at, _) = word[start..]
+                    .char_indices()
+                    .filter(|&(_, c)| c != SOFT_HYPHEN)
+                    .nth(capacity)
+                    .unwrap();
+                lines.push(word[start..start + at].to_string());
+                start += at;
+            } else {
+                break;
+            }
+        }
+        line.push_str(&word[start..]);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+        .into_iter()
+        .map(|line| line.replace(SOFT_HYPHEN, ""))
+        .collect()
+}
+
+impl LayoutBox {
+    /// Truncate text children that overflow this box's content box, if it
+    /// clips them and asks for an ellipsis with `text-overflow`.
+    pub(crate) fn apply_text_overflow(&mut self) {
+        let style = &self.style;
+        if style.text_overflow != TextOverflow::Ellipsis
+            || style.overflow_x == rustkit_css::Overflow::Visible
+            || style.white_space.wraps()
+        {
+            return;
+        }
+        let content = self.dimensions.content;
+        let direction = style.direction;
+        for child in &mut self.children {
+            child.ellipsize(&content, direction);
+        }
+    }
+
+    /// Cut this text box's single line to fit `content`, ending it with an
+    /// ellipsis at the visual end of the line.
+    fn ellipsize(&mut self, content: &Rect, direction: Direction) {
+        let crate::BoxType::Text(ref text) = self.box_type else {
+            return;
+        };
+        let font_size = self.font_size();
+        let x = self.dimensions.content.x;
+        if x + estimated_text_width(text, font_size) <= content.right() + 0.01 {
+            return;
+        }
+
+        let ellipsis_width = estimated_text_width(ELLIPSIS, font_size);
+        let available = content.right() - x - ellipsis_width;
+        let fitting = text
+            .char_indices()
+            .map(|(i, c)| i + c.len_utf8())
+            .take_while(|&end| estimated_text_width(&text[..end], font_size) <= available)
+            .last()
+            .unwrap_or(0);
+        let kept = text[..fitting].trim_end().replace(SOFT_HYPHEN, "");
+        let kept_width = estimated_text_width(&kept, font_size);
+
+        let y = self.dimensions.content.y;
+        let height = self.dimensions.content.height;
+        let (text_x, ellipsis_x) = match direction {
+            Direction::Ltr => (x, x + kept_width),
+            // The logical end of a right-to-left line is its left edge
+            Direction::Rtl => (x + ellipsis_width, x),
+        };
+        self.dimensions.content.width = (kept_width + ellipsis_width).min(content.right() - x);
+        self.text_lines = vec![TextLine {
+            text: kept,
+            rect: Rect::new(text_x, y, kept_width, height),
+            ellipsis: Some(Rect::new(ellipsis_x, y, ellipsis_width, height)),
+        }];
+    }
+
+    pub(crate) fn font_size(&self) -> f32 {
+        match self.style.font_size {
+            rustkit_css::Length::Px(px) => px,
+            _ => 16.0,
+        }
+    }
+
+    /// Break this text box into lines within `containing_block`, each
+    /// line in the space `floats` leave free at its height.
+    pub(crate) fn break_text_lines(
+        &mut self,
+        text: &str,
+        containing_block: &Dimensions,
+        floats: &FloatContext,
+    ) {
+        let font_size = self.font_size();
+        let line_height = font_size * 1.2;
+        let available = containing_block.content.width;
+        self.text_lines.clear();
+        // Line boxes lay text out against no width at all; it stays on the line
+        if available <= 0.0 {
+            return;
+        }
+        let x = self.dimensions.content.x;
+        let y = self.dimensions.content.y;
+        let line_edges = |i: usize| {
+            floats.edges_in(
+                y + i as f32 * line_height,
+                line_height,
+                containing_block.content.x,
+                containing_block.content.right(),
+            )
+        };
+        let lines = break_lines_within(text, &self.style, font_size, |i| {
+            let (left, right) = line_edges(i);
+            right - left
+        });
+
+        // A single line needs no fragments unless a float moves it
+        let shifted = line_edges(0).0 != x;
+        self.text_lines = match lines.len() {
+            1 if !shifted => Vec::new(),
+            _ => lines
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let width = estimated_text_width(&line, font_size);
+                    let (left, _) = line_edges(i);
+                    TextLine {
+                        rect: Rect::new(left, y + i as f32 * line_height, width, line_height),
+                        text: line,
+                        ellipsis: None,
+                    }
+                })
+                .collect(),
+        };
+        if !self.text_lines.is_empty() {
+            let widest = self
+                .text_lines
+                .iter()
+                .map(|line| line.rect.right() - x)
+                .fold(0.0, f32::max);
+            self.dimensions.content.width = widest.min(available);
+            self.dimensions.content.height = self.text_lines.len() as f32 * line_height;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoxType, DisplayCommand, DisplayList};
+    use rustkit_css::{Length, Overflow, WhiteSpace};
+
+    fn style(f: impl FnOnce(&mut ComputedStyle)) -> ComputedStyle {
+        let mut style = ComputedStyle::new();
+        style.font_size = Length::Px(16.0);
+        f(&mut style);
+        style
+    }
+
+    fn lay_out(container: ComputedStyle, text: &str, text_style: ComputedStyle) -> LayoutBox {
+        let mut root = LayoutBox::new(BoxType::Block, container);
+        root.children
+            .push(LayoutBox::new(BoxType::Text(text.to_string()), text_style));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        root.layout(&viewport);
+        root
+    }
+
+    #[test]
+    fn test_break_word_splits_long_token() {
+        let token = "a".repeat(400);
+        let narrow = style(|s| s.width = Length::Px(100.0));
+
+        // Without overflow-wrap the token overflows on a single line
+        let root = lay_out(narrow.clone(), &token, style(|_| {}));
+        assert!(root.children[0].text_lines.is_empty());
+
+        let wrapping = style(|s| s.overflow_wrap = OverflowWrap::BreakWord);
+        let root = lay_out(narrow, &token, wrapping.clone());
+        let text = &root.children[0];
+        // 8px per character, so 12 characters to a 100px line
+        assert_eq!(text.text_lines.len(), 34);
+        assert!(text.text_lines.iter().all(|l| l.rect.width <= 100.0));
+        assert_eq!(root.dimensions.content.height, 34.0 * 16.0 * 1.2);
+
+        // Only anywhere and break-all let the breaks shrink min-content
+        let mut text_box = LayoutBox::new(BoxType::Text(token.clone()), wrapping);
+        assert_eq!(crate::min_content_width(&text_box), 3200.0);
+        text_box.style.overflow_wrap = OverflowWrap::Anywhere;
+        assert_eq!(crate::min_content_width(&text_box), 8.0);
+    }
+
+    #[test]
+    fn test_words_wrap_before_breaking() {
+        let wrapping = style(|s| s.overflow_wrap = OverflowWrap::BreakWord);
+        assert_eq!(
+            break_lines_within("see https://example.com/path ok", &wrapping, 16.0, |_| {
+                100.0
+            }),
+            ["see", "https://exam", "ple.com/path", "ok"]
+        );
+        let break_all = style(|s| s.word_break = WordBreak::BreakAll);
+        assert_eq!(
+            break_lines_within("see https://example.com", &break_all, 16.0, |_| 100.0),
+            ["see https://", "example.com"]
+        );
+    }
+
+    #[test]
+    fn test_soft_hyphen_breaks_narrow_lines() {
+        let text = "supercali\u{ad}fragilistic";
+        let hyphens = |root: &LayoutBox| {
+            let list = DisplayList::build(root);
+            let texts: Vec<String> = list
+                .commands
+                .iter()
+                .filter_map(|c| match c {
+                    DisplayCommand::Text { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect();
+            assert!(texts.iter().all(|t| !t.contains(SOFT_HYPHEN)));
+            texts.concat().matches('-').count()
+        };
+
+        let narrow = style(|s| s.width = Length::Px(100.0));
+        let root = lay_out(narrow, text, style(|_| {}));
+        let lines: Vec<_> = root.children[0]
+            .text_lines
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(lines, ["supercali-", "fragilistic"]);
+        assert_eq!(hyphens(&root), 1);
+
+        let root = lay_out(style(|_| {}), text, style(|_| {}));
+        assert!(root.children[0].text_lines.is_empty());
+        assert_eq!(root.children[0].dimensions.content.width, 160.0);
+        assert_eq!(hyphens(&root), 0);
+
+        // The widest piece, not the whole word, sets min-content
+        let mut text_box = LayoutBox::new(BoxType::Text(text.into()), style(|_| {}));
+        assert_eq!(crate::min_content_width(&text_box), 88.0);
+        text_box.style.hyphens = Hyphens::None;
+        assert_eq!(crate::min_content_width(&text_box), 160.0);
+    }
+
+    #[test]
+    fn test_auto_hyphenation() {
+        let auto = style(|s| {
+            s.hyphens = Hyphens::Auto;
+            s.lang = Some("en".into());
+            s.hyphenate_character = Some("\u{2010}".into());
+        });
+        assert_eq!(
+            break_lines_within("the hyphenation", &auto, 16.0, |_| 64.0),
+            ["the hy\u{2010}", "phen\u{2010}", "ation"]
+        );
+        // Without a language there are no patterns to go by
+        let unknown = style(|s| s.hyphens = Hyphens::Auto);
+        assert_eq!(
+            break_lines_within("the hyphenation", &unknown, 16.0, |_| 64.0),
+            ["the", "hyphenation"]
+        );
+    }
+
+    #[test]
+    fn test_ellipsis_fits_content_box() {
+        let label = style(|s| {
+            s.width = Length::Px(100.0);
+            s.overflow_x = Overflow::Hidden;
+            s.white_space = WhiteSpace::Nowrap;
+            s.text_overflow = TextOverflow::Ellipsis;
+        });
+        let text = style(|s| s.white_space = WhiteSpace::Nowrap);
+        let root = lay_out(
+            label.clone(),
+            "A rather long label for a button",
+            text.clone(),
+        );
+
+        let list = DisplayList::build(&root);
+        let texts: Vec<_> = list
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::Text { text, x, .. } => Some((text.clone(), *x)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts.len(), 2);
+        let (last, x) = &texts[0];
+        assert_eq!(texts[1].0, ELLIPSIS);
+        let ellipsis_width = estimated_text_width(ELLIPSIS, 16.0);
+        assert!(estimated_text_width(last, 16.0) + ellipsis_width <= 100.0);
+        assert_eq!(texts[1].1, x + estimated_text_width(last, 16.0));
+        // The box keeps its full text
+        assert!(matches!(&root.children[0].box_type, BoxType::Text(t) if t.ends_with("button")));
+
+        // Right-to-left lines end on the left
+        let mut rtl = label;
+        rtl.direction = Direction::Rtl;
+        let root = lay_out(rtl, "A rather long label for a button", text);
+        let line = &root.children[0].text_lines[0];
+        assert_eq!(line.ellipsis.unwrap().x, root.dimensions.content.x);
+    }
+}
@@ -0,0 +1,262 @@
+//! Fragmenting a laid-out document into pages.
+//!
+//! The document is laid out once at the page width as one tall flow, then
+//! cut into page-tall slices. Cuts fall between block boxes, or between the
+//! lines of a block's inline content. `break-before`/`break-after: page`
+//! force a cut, `break-inside: avoid` keeps a box on one page when it fits,
+//! and `orphans`/`widows` keep that many lines of a paragraph together on
+//! either side of a cut. When no allowed cut fits on a page, the best
+//! avoided one is taken, and failing that the content is sliced at the page
+//! bottom.
+
+use crate::columns::{groups, in_flow};
+use crate::{BoxType, LayoutBox};
+use rustkit_css::{BreakBetween, BreakInside};
+
+/// Slack for comparing positions.
+const EPSILON: f32 = 0.01;
+
+/// The part of the document one page shows, in document coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSlice {
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl PageSlice {
+    pub fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+}
+
+/// A place the flow may be cut.
+#[derive(Debug, Clone, Copy)]
+struct BreakPoint {
+    y: f32,
+    forced: bool,
+    avoid: bool,
+}
+
+/// Cut the document laid out in `root` into pages of `page_height` pixels.
+/// There is always at least one page.
+pub fn paginate(root: &LayoutBox, page_height: f32) -> Vec<PageSlice> {
+    let margin_box = root.dimensions.margin_box();
+    let end = margin_box.bottom();
+    let mut points = Vec::new();
+    collect(
+        root,
+        root.style.break_inside == BreakInside::Avoid,
+        &mut points,
+    );
+    points.sort_by(|a, b| a.y.total_cmp(&b.y));
+
+    let page_height = page_height.max(1.0);
+    let mut pages = Vec::new();
+    let mut top = margin_box.y;
+    while top < end - EPSILON {
+        let limit = top + page_height;
+        let candidates: Vec<&BreakPoint> = points
+            .iter()
+            .filter(|p| p.y > top + EPSILON && p.y < end - EPSILON && p.y <= limit + EPSILON)
+            .collect();
+        let last = |avoid: bool| {
+            candidates
+                .iter()
+                .rev()
+                .find(|p| p.avoid == avoid)
+                .map(|p| p.y)
+        };
+        let bottom = match candidates.iter().find(|p| p.forced) {
+            Some(point) => point.y,
+            None if end <= limit + EPSILON => end,
+            None => last(false).or_else(|| last(true)).unwrap_or(limit),
+        };
+        pages.push(PageSlice { top, bottom });
+        top = bottom;
+    }
+    if pages.is_empty() {
+        pages.push(PageSlice {
+            top: margin_box.y,
+            bottom: end.max(margin_box.y),
+        });
+    }
+    pages
+}
+
+/// Whether cuts may fall between the children of `container`.
+fn fragmentable(container: &LayoutBox) -> bool {
+    matches!(container.box_type, BoxType::Block | BoxType::AnonymousBlock)
+        && !container.style.display.is_flex()
+        && !container.style.display.is_grid()
+        && !container.style.is_multicol()
+        && in_flow(container)
+}
+
+fn is_inline_level(child: &LayoutBox) -> bool {
+    matches!(
+        child.box_type,
+        BoxType::Inline | BoxType::InlineBlock | BoxType::Text(_)
+    )
+}
+
+/// Add the break points inside `container`. `avoid` is set inside a box
+/// with `break-inside: avoid`.
+fn collect(container: &LayoutBox, avoid: bool, points: &mut Vec<BreakPoint>) {
+    if !fragmentable(container) {
+        return;
+    }
+    if container.children.iter().all(is_inline_level) {
+        collect_lines(container, avoid, points);
+        return;
+    }
+
+    let rows = groups(&container.children);
+    for (i, (range, top, _)) in rows.iter().enumerate() {
+        if i > 0 {
+            let before = &container.children[rows[i - 1].0.clone()];
+            let after = &container.children[range.clone()];
+            let between = |f: fn(&LayoutBox) -> BreakBetween, boxes: &[LayoutBox], value| {
+                boxes.iter().any(|b| in_flow(b) && f(b) == value)
+            };
+            let break_after = |b: &LayoutBox| b.style.break_after;
+            let break_before = |b: &LayoutBox| b.style.break_before;
+            points.push(BreakPoint {
+                y: *top,
+                forced: between(break_after, before, BreakBetween::Page)
+                    || between(break_before, after, BreakBetween::Page),
+                avoid: avoid
+                    || between(break_after, before, BreakBetween::Avoid)
+                    || between(break_before, after, BreakBetween::Avoid),
+            });
+        }
+        for child in &container.children[range.clone()] {
+            collect(
+                child,
+                avoid || child.style.break_inside == BreakInside::Avoid,
+                points,
+            );
+        }
+    }
+}
+
+/// Add the break points between the lines of an inline formatting context.
+/// A cut leaving fewer than `orphans` lines before it or `widows` lines
+/// after it is avoided.
+fn collect_lines(container: &LayoutBox, avoid: bool, points: &mut Vec<BreakPoint>) {
+    let mut lines = Vec::new();
+    for (range, top, _) in groups(&container.children) {
+        match &container.children[range] {
+            [text] if text.text_lines.len() > 1 => {
+                lines.extend(text.text_lines.iter().map(|line| line.rect.y))
+            }
+            _ => lines.push(top),
+        }
+    }
+    let orphans = container.style.orphans.max(1) as usize;
+    let widows = container.style.widows.max(1) as usize;
+    for (i, &y) in lines.iter().enumerate().skip(1) {
+        points.push(BreakPoint {
+            y,
+            forced: false,
+            avoid: avoid || i < orphans || lines.len() - i < widows,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dimensions, Rect};
+    use rustkit_css::{ComputedStyle, Length};
+
+    fn block(height: f32, f: impl FnOnce(&mut ComputedStyle)) -> LayoutBox {
+        let mut style = ComputedStyle {
+            height: Length::Px(height),
+            ..ComputedStyle::new()
+        };
+        f(&mut style);
+        LayoutBox::new(BoxType::Block, style)
+    }
+
+    fn lay_out(children: Vec<LayoutBox>) -> LayoutBox {
+        let mut root = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        root.children = children;
+        let viewport = Dimensions {
+            content: Rect::new(0.0, 0.0, 400.0, 0.0),
+            ..Default::default()
+        };
+        root.layout(&viewport);
+        root
+    }
+
+    fn bounds(pages: &[PageSlice]) -> Vec<(f32, f32)> {
+        pages.iter().map(|p| (p.top, p.bottom)).collect()
+    }
+
+    #[test]
+    fn test_pages_cut_between_blocks() {
+        let root = lay_out((0..5).map(|_| block(40.0, |_| {})).collect());
+        let pages = paginate(&root, 100.0);
+        assert_eq!(
+            bounds(&pages),
+            vec![(0.0, 80.0), (80.0, 160.0), (160.0, 200.0)]
+        );
+
+        let empty = lay_out(Vec::new());
+        assert_eq!(paginate(&empty, 100.0).len(), 1);
+    }
+
+    #[test]
+    fn test_forced_and_avoided_breaks() {
+        let root = lay_out(vec![
+            block(20.0, |_| {}),
+            block(20.0, |s| s.break_before = BreakBetween::Page),
+            block(20.0, |s| s.break_after = BreakBetween::Page),
+            block(20.0, |_| {}),
+        ]);
+        assert_eq!(
+            bounds(&paginate(&root, 100.0)),
+            vec![(0.0, 20.0), (20.0, 60.0), (60.0, 80.0)]
+        );
+
+        let mut kept = block(60.0, |s| {
+            s.height = Length::Auto;
+            s.break_inside = BreakInside::Avoid;
+        });
+        kept.children = (0..3).map(|_| block(20.0, |_| {})).collect();
+        let root = lay_out(vec![block(60.0, |_| {}), kept]);
+        let pages = paginate(&root, 100.0);
+        assert_eq!(bounds(&pages), vec![(0.0, 60.0), (60.0, 120.0)]);
+
+        // Too tall to keep whole, it is cut anyway
+        let root = lay_out(vec![block(150.0, |_| {})]);
+        assert_eq!(
+            bounds(&paginate(&root, 100.0)),
+            vec![(0.0, 100.0), (100.0, 150.0)]
+        );
+    }
+
+    #[test]
+    fn test_orphans_and_widows() {
+        // Six 19.2px lines; a page takes five of them
+        let mut paragraph = LayoutBox::new(BoxType::Block, ComputedStyle::new());
+        let mut text = LayoutBox::new(BoxType::Text("word ".repeat(60)), ComputedStyle::new());
+        text.style.font_size = Length::Px(16.0);
+        paragraph.children = vec![text];
+        let root = lay_out(vec![paragraph]);
+        let lines = root.children[0].children[0].text_lines.len();
+        assert!(lines > 3, "{} lines", lines);
+        let line_height = 16.0 * 1.2;
+        let page_height = line_height * (lines as f32 - 0.5);
+
+        let pages = paginate(&root, page_height);
+        assert_eq!(pages.len(), 2);
+        // Two widows move to the second page
+        let cut = pages[0].bottom;
+        assert!(
+            (cut - line_height * (lines - 2) as f32).abs() < 0.1,
+            "cut at {}",
+            cut
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! `blob:` URL registry.
+//!
+//! `URL.createObjectURL` registers a blob's bytes here. Fetches, image loads
+//! and downloads of the URL are answered from memory until the URL is
+//! revoked or the document that created it goes away.
+
+use bytes::Bytes;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tracing::debug;
+use url::Url;
+
+/// Bytes and type of a registered blob.
+#[derive(Debug, Clone)]
+pub struct BlobData {
+    pub bytes: Bytes,
+    /// The blob's `type`; empty if none was given.
+    pub content_type: String,
+}
+
+struct BlobEntry {
+    /// Document that created the URL.
+    owner: u64,
+    data: BlobData,
+}
+
+/// In-memory store behind `blob:` URLs.
+pub struct BlobUrlStore {
+    entries: RwLock<HashMap<String, BlobEntry>>,
+    counter: AtomicU64,
+    random: RandomState,
+}
+
+impl Default for BlobUrlStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlobUrlStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            counter: AtomicU64::new(0),
+            random: RandomState::new(),
+        }
+    }
+
+    /// A random UUID for a new URL.
+    fn uuid(&self) -> String {
+        let mut halves = [0u64; 2];
+        for half in &mut halves {
+            let mut hasher = self.random.build_hasher();
+            hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+            *half = hasher.finish();
+        }
+        // Version 4, RFC 4122 variant
+        let [high, low] = halves;
+        let high = (high & !0xf000) | 0x4000;
+        let low = (low & !(0b11 << 62)) | (0b10 << 62);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        )
+    }
+
+    /// Register `data` for a document `owner` with serialized `origin`
+    /// (`"null"` if opaque) and return its new `blob:` URL.
+    pub fn register(&self, owner: u64, origin: &str, data: BlobData) -> Url {
+        let url =
+            Url::parse(&format!("blob:{}/{}", origin, self.uuid())).expect("blob URL is valid");
+        debug!(%url, owner, size = data.bytes.len(), "Registered blob URL");
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.to_string(), BlobEntry { owner, data });
+        url
+    }
+
+    /// The blob `url` refers to. A fragment does not change the blob.
+    pub fn resolve(&self, url: &Url) -> Option<BlobData> {
+        let mut key = url.clone();
+        key.set_fragment(None);
+        self.entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key.as_str())
+            .map(|entry| entry.data.clone())
+    }
+
+    /// Forget `url`; returns whether it was registered.
+    pub fn revoke(&self, url: &Url) -> bool {
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(url.as_str())
+            .is_some()
+    }
+
+    /// Forget every URL `owner` created, as when its document unloads.
+    pub fn revoke_owner(&self, owner: u64) -> usize {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let before = entries.len();
+        entries.retain(|_, entry| entry.owner != owner);
+        before - entries.len()
+    }
+
+    /// Number of live URLs.
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no URLs are live.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &'static str) -> BlobData {
+        BlobData {
+            bytes: Bytes::from_static(text.as_bytes()),
+            content_type: "text/plain".into(),
+        }
+    }
+
+    #[test]
+    fn test_register_resolve_revoke() {
+        let store = BlobUrlStore::new();
+        let a = store.register(1, "https://example.com", text("a"));
+        let b = store.register(2, "null", text("b"));
+        assert_ne!(a, b);
+        assert!(a.as_str().starts_with("blob:https://example.com/"));
+        assert_eq!(a.as_str().len(), "blob:https://example.com/".len() + 36);
+
+        let mut with_fragment = a.clone();
+        with_fragment.set_fragment(Some("x"));
+        assert_eq!(&store.resolve(&with_fragment).unwrap().bytes[..], b"a");
+
+        assert!(store.revoke(&a));
+        assert!(!store.revoke(&a));
+        assert!(store.resolve(&a).is_none());
+
+        assert_eq!(store.revoke_owner(2), 1);
+        assert!(store.is_empty());
+    }
+}
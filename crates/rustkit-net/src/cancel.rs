@@ -0,0 +1,382 @@
+//! Request cancellation and request lifecycle events.
+//!
+//! Every [`crate::Request`] carries a [`CancelHandle`]. Cancelling it aborts
+//! the wait for response headers, the body stream and anything queued
+//! behind it; the fetch or the next body read then fails with
+//! [`NetError::Cancelled`]. The loader keeps a registry of requests still in
+//! flight so a view's requests can be cancelled together when it navigates
+//! away or is destroyed, and so [`Priority::VeryLow`] requests can wait for
+//! critical ones to finish.
+
+use crate::hints::{HintKind, ResourceHint};
+use crate::telemetry::{self, ErrorClass, RequestOutcome, RequestRecord};
+use crate::{NetError, Priority, Request, RequestId, Response};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use tokio::sync::{mpsc, watch, Notify};
+use tracing::debug;
+use url::Url;
+
+/// Aborts a request, and every request sharing the handle, on [`cancel`].
+///
+/// [`cancel`]: CancelHandle::cancel
+#[derive(Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancelHandle {
+    /// A handle that has not been cancelled.
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Cancel. Has no effect on requests that already completed.
+    pub fn cancel(&self) {
+        self.cancelled
+            .send_if_modified(|cancelled| !std::mem::replace(cancelled, true));
+    }
+
+    /// Whether [`Self::cancel`] was called.
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Resolve once cancelled.
+    pub async fn cancelled(&self) {
+        let mut rx = self.cancelled.subscribe();
+        // The sender lives in `self`, so the channel cannot close
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// What caused a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Initiator {
+    /// The page or the user: navigations, subresources and script fetches.
+    #[default]
+    Page,
+    /// A resource hint.
+    Hint,
+    /// An HTTP/2 server push.
+    Push,
+}
+
+/// Lifecycle of a request made through the loader.
+#[derive(Debug, Clone)]
+pub enum NetEvent {
+    /// The request was issued.
+    Started {
+        request_id: RequestId,
+        url: Url,
+        view_id: Option<u64>,
+        initiator: Initiator,
+    },
+    /// A chunk of the response body was read.
+    Progress {
+        request_id: RequestId,
+        received: u64,
+        total: Option<u64>,
+    },
+    /// The response body was read to the end.
+    Finished { request_id: RequestId },
+    /// The request failed.
+    Failed {
+        request_id: RequestId,
+        error: String,
+    },
+    /// The request was cancelled before it completed.
+    Cancelled { request_id: RequestId },
+    /// The request was redirected, by the server with `status` or by an
+    /// interceptor before it left.
+    Redirected {
+        request_id: RequestId,
+        from: Url,
+        to: Url,
+        status: Option<u16>,
+    },
+    /// A `103 Early Hints` response arrived ahead of the response to
+    /// `url`, with the hints in its `Link` headers.
+    EarlyHintsReceived {
+        request_id: RequestId,
+        url: Url,
+        view_id: Option<u64>,
+        hints: Vec<ResourceHint>,
+    },
+    /// A resource hint was taken up, or skipped for `skipped`. Prefetches
+    /// then report their request like any other.
+    Hinted {
+        kind: HintKind,
+        url: Url,
+        view_id: Option<u64>,
+        skipped: Option<String>,
+    },
+    /// The request was answered with a response the server pushed.
+    ServedFromPush {
+        request_id: RequestId,
+        url: Url,
+        view_id: Option<u64>,
+    },
+}
+
+impl NetEvent {
+    /// What caused the event, for events that say.
+    pub fn initiator(&self) -> Option<Initiator> {
+        match self {
+            NetEvent::Started { initiator, .. } => Some(*initiator),
+            NetEvent::Hinted { .. } => Some(Initiator::Hint),
+            NetEvent::ServedFromPush { .. } => Some(Initiator::Push),
+            _ => None,
+        }
+    }
+}
+
+/// Requests still in flight, and the event observer.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    requests: Mutex<HashMap<RequestId, (Option<u64>, CancelHandle)>>,
+    event_tx: RwLock<Option<mpsc::UnboundedSender<NetEvent>>>,
+    /// Requests of [`Priority::High`] in flight.
+    critical: AtomicUsize,
+    critical_done: Notify,
+    pub(crate) telemetry: telemetry::Dispatcher,
+}
+
+impl InFlight {
+    pub(crate) fn set_event_sender(&self, tx: Option<mpsc::UnboundedSender<NetEvent>>) {
+        *self.event_tx.write().unwrap_or_else(|e| e.into_inner()) = tx;
+    }
+
+    pub(crate) fn emit(&self, event: NetEvent) {
+        if let Some(tx) = self
+            .event_tx
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Register `request` until the returned guard is dropped.
+    pub(crate) fn track(self: &Arc<Self>, request: &Request) -> Tracked {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request.id, (request.view_id, request.cancel.clone()));
+        let critical = request.priority >= Priority::High;
+        if critical {
+            self.critical.fetch_add(1, Ordering::SeqCst);
+        }
+        self.emit(NetEvent::Started {
+            request_id: request.id,
+            url: request.url.clone(),
+            view_id: request.view_id,
+            initiator: request.initiator,
+        });
+        let record = self.telemetry.is_enabled().then(|| {
+            let sent = request.body.as_ref().map_or(0, |body| body.len() as u64);
+            RequestRecord::new(
+                request.method.as_str(),
+                &request.url,
+                sent,
+                request.initiator,
+            )
+        });
+        Tracked {
+            id: request.id,
+            cancel: request.cancel.clone(),
+            total: None,
+            received: 0,
+            critical,
+            in_flight: self.clone(),
+            started: Instant::now(),
+            record,
+        }
+    }
+
+    /// Resolve once no critical request is in flight.
+    pub(crate) async fn critical_idle(&self) {
+        loop {
+            let done = self.critical_done.notified();
+            tokio::pin!(done);
+            done.as_mut().enable();
+            if self.critical.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            done.await;
+        }
+    }
+
+    /// Cancel every request in flight for `view_id`.
+    pub(crate) fn cancel_view(&self, view_id: u64) -> usize {
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let mut cancelled = 0;
+        for (_, cancel) in requests.values().filter(|(view, _)| *view == Some(view_id)) {
+            cancel.cancel();
+            cancelled += 1;
+        }
+        debug!(view_id, cancelled, "Cancelled view requests");
+        cancelled
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// A request's registration, carried by its response until the body ends.
+pub(crate) struct Tracked {
+    id: RequestId,
+    pub(crate) cancel: CancelHandle,
+    pub(crate) total: Option<u64>,
+    received: u64,
+    critical: bool,
+    in_flight: Arc<InFlight>,
+    started: Instant,
+    /// Telemetry for the request, when a sink wants it.
+    record: Option<RequestRecord>,
+}
+
+impl std::fmt::Debug for Tracked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracked")
+            .field("id", &self.id)
+            .field("received", &self.received)
+            .finish()
+    }
+}
+
+impl Tracked {
+    /// Stop counting the request among its view's, so cancelling the view's
+    /// requests leaves it running.
+    pub(crate) fn detach_from_view(&self) {
+        if let Some((view, _)) = self
+            .in_flight
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&self.id)
+        {
+            *view = None;
+        }
+    }
+
+    /// Note the response headers arriving.
+    pub(crate) fn responded(&mut self, response: &Response) {
+        let Some(record) = &mut self.record else {
+            return;
+        };
+        record.outcome = RequestOutcome::Status(response.status.as_u16());
+        record.time_to_headers = Some(self.started.elapsed());
+        record.from_cache = response.from_cache;
+        record.protocol = response
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.alpn_protocol.clone());
+        record.redirects = response.redirect_chain.count();
+    }
+
+    /// Report a body read. Returns whether the request is over.
+    pub(crate) fn observe(&mut self, chunk: &Result<Option<Bytes>, NetError>) -> bool {
+        let event = match chunk {
+            Ok(Some(bytes)) => {
+                self.received += bytes.len() as u64;
+                NetEvent::Progress {
+                    request_id: self.id,
+                    received: self.received,
+                    total: self.total,
+                }
+            }
+            Ok(None) => NetEvent::Finished {
+                request_id: self.id,
+            },
+            Err(error) => self.failure(error),
+        };
+        let over = !matches!(event, NetEvent::Progress { .. });
+        self.in_flight.emit(event);
+        over
+    }
+
+    /// Report the request failing before a response arrived.
+    pub(crate) fn fail(mut self, error: &NetError) {
+        let event = self.failure(error);
+        self.in_flight.emit(event);
+    }
+
+    fn failure(&mut self, error: &NetError) -> NetEvent {
+        if let Some(record) = &mut self.record {
+            record.outcome = RequestOutcome::Error(ErrorClass::of(error));
+        }
+        match error {
+            NetError::Cancelled => NetEvent::Cancelled {
+                request_id: self.id,
+            },
+            error => NetEvent::Failed {
+                request_id: self.id,
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.in_flight
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+        if self.critical && self.in_flight.critical.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.in_flight.critical_done.notify_waiters();
+        }
+        if let Some(mut record) = self.record.take() {
+            record.duration = self.started.elapsed();
+            record.bytes_received = self.received;
+            self.in_flight.telemetry.submit(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_wakes_waiters_once() {
+        let handle = CancelHandle::new();
+        let waiter = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.cancelled().await }
+        });
+        assert!(!handle.is_cancelled());
+        handle.cancel();
+        handle.cancel();
+        waiter.await.unwrap();
+        assert!(handle.is_cancelled());
+        // Already cancelled handles resolve immediately
+        handle.cancelled().await;
+    }
+}
@@ -0,0 +1,266 @@
+//! Conditional requests for embedders that poll resources.
+//!
+//! [`crate::ResourceLoader::fetch_if_modified`] sends the validators of an
+//! earlier response as `If-None-Match` and `If-Modified-Since`, so an
+//! unchanged resource costs a `304` without a body. This works without any
+//! HTTP cache: the caller keeps the validators and the last body.
+//! [`crate::ResourceLoader::poll`] runs that in a loop and sends only the
+//! bodies that changed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use url::Url;
+
+use crate::{Request, Response};
+
+/// Longest a poller waits between requests while backing off.
+pub const MAX_POLL_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Validators of an earlier response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    /// The `ETag`, verbatim; weak ones keep their `W/` prefix.
+    pub etag: Option<String>,
+    /// The `Last-Modified` date, verbatim.
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Validators a response carries.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            etag: get(header::ETAG),
+            last_modified: get(header::LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Add `If-None-Match` and `If-Modified-Since` to `request`.
+    pub(crate) fn apply(&self, request: &mut Request) {
+        let set = |request: &mut Request, name, value: &Option<String>| {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                request.headers.insert(name, value);
+            }
+        };
+        set(request, header::IF_NONE_MATCH, &self.etag);
+        set(request, header::IF_MODIFIED_SINCE, &self.last_modified);
+    }
+
+    /// These validators updated by the ones a `304` carries; a `304`
+    /// without them leaves these unchanged.
+    pub(crate) fn refreshed(&self, headers: &HeaderMap) -> Self {
+        let sent = Self::from_headers(headers);
+        Self {
+            etag: sent.etag.or_else(|| self.etag.clone()),
+            last_modified: sent.last_modified.or_else(|| self.last_modified.clone()),
+        }
+    }
+}
+
+impl Response {
+    /// The response's validators, for a later
+    /// [`crate::ResourceLoader::fetch_if_modified`].
+    pub fn validators(&self) -> Validators {
+        Validators::from_headers(&self.headers)
+    }
+}
+
+/// Result of a conditional fetch.
+#[derive(Debug)]
+pub enum ConditionalResponse {
+    /// The server answered `304`; the body the validators came from is
+    /// current.
+    NotModified { validators: Validators },
+    /// The resource changed, or the server ignored the validators. Its new
+    /// validators are [`Response::validators`].
+    Modified(Box<Response>),
+}
+
+/// How [`crate::ResourceLoader::poll_with`] polls.
+#[derive(Debug, Clone)]
+pub struct PollOptions {
+    /// Time between requests.
+    pub interval: Duration,
+    /// Skip a `200` whose body is the same as the last one sent, for
+    /// servers whose validators change when the content doesn't.
+    pub compare_content: bool,
+}
+
+impl PollOptions {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            compare_content: true,
+        }
+    }
+}
+
+/// A changed body found by a poller.
+#[derive(Debug, Clone)]
+pub struct PollUpdate {
+    pub url: Url,
+    pub body: Bytes,
+    pub content_type: Option<String>,
+    pub validators: Validators,
+}
+
+/// Hash of a body, to tell whether a `200` changed anything.
+pub(crate) fn content_hash(body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// How long to wait after a throttling response (`429`, or `503` with a
+/// `Retry-After`), given the previous wait. `None` for other responses.
+pub(crate) fn backoff(
+    status: StatusCode,
+    headers: &HeaderMap,
+    previous: Duration,
+    now: SystemTime,
+) -> Option<Duration> {
+    let retry_after = headers
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, now));
+    let throttled = status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::SERVICE_UNAVAILABLE && retry_after.is_some());
+    if !throttled {
+        return None;
+    }
+    let wait = retry_after.unwrap_or_else(|| previous.saturating_mul(2));
+    Some(wait.min(MAX_POLL_BACKOFF))
+}
+
+/// A `Retry-After` value: delay seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// An IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|n| n.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next() != Some("GMT") || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m as i64 + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = u64::try_from(days).ok()? * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_and_backoff() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:47 GMT", now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        let mut headers = HeaderMap::new();
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            backoff(StatusCode::TOO_MANY_REQUESTS, &headers, second, now),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            backoff(StatusCode::SERVICE_UNAVAILABLE, &headers, second, now),
+            None
+        );
+        headers.insert(header::RETRY_AFTER, HeaderValue::from_static("30"));
+        assert_eq!(
+            backoff(StatusCode::SERVICE_UNAVAILABLE, &headers, second, now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(backoff(StatusCode::OK, &headers, second, now), None);
+    }
+
+    #[test]
+    fn test_validators_refreshed_by_304() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("W/\"v1\""));
+        headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        let validators = Validators::from_headers(&headers);
+        assert_eq!(validators.etag.as_deref(), Some("W/\"v1\""));
+
+        let mut request = Request::get(Url::parse("http://example.com/feed").unwrap());
+        validators.apply(&mut request);
+        assert_eq!(request.headers[header::IF_NONE_MATCH], "W/\"v1\"");
+        assert_eq!(
+            request.headers[header::IF_MODIFIED_SINCE],
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+
+        let mut not_modified = HeaderMap::new();
+        not_modified.insert(header::ETAG, HeaderValue::from_static("\"v2\""));
+        let refreshed = validators.refreshed(&not_modified);
+        assert_eq!(refreshed.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(refreshed.last_modified, validators.last_modified);
+        assert_eq!(validators.refreshed(&HeaderMap::new()), validators);
+    }
+}
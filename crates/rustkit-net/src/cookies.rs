@@ -0,0 +1,509 @@
+//! The cookie jar shared by requests and `document.cookie`.
+//!
+//! A session has one jar: a cookie a response sets is readable by script
+//! straight away, and one script sets goes out on the next request. Script
+//! neither sees `HttpOnly` cookies nor overwrites them.
+//!
+//! Cookies are stored as RFC 6265bis describes, keyed by name, domain and
+//! path. A `Domain` attribute naming a host the URL is not under, or a
+//! public suffix, makes the whole cookie ignored.
+
+use std::cmp::Reverse;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use url::Url;
+
+use crate::conditional::parse_http_date;
+use crate::security::{CookieAttributes, Origin, SameSite};
+use crate::site;
+
+/// Longest name and value together, in bytes.
+const MAX_COOKIE_SIZE: usize = 4096;
+
+/// Longest lifetime a cookie may ask for: 400 days.
+const MAX_LIFETIME: Duration = Duration::from_secs(400 * 24 * 60 * 60);
+
+/// Where a cookie being set came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieSource {
+    /// A `Set-Cookie` response header.
+    Http,
+    /// A `document.cookie` assignment.
+    Script,
+}
+
+/// A cookie as kept in the jar.
+#[derive(Debug, Clone)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    /// Lowercase, without a leading dot.
+    domain: String,
+    /// Set without a `Domain`, so sent to `domain` itself only.
+    host_only: bool,
+    path: String,
+    /// `None` for session cookies.
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
+}
+
+impl StoredCookie {
+    fn matches(&self, url: &Url, now: SystemTime) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_matches
+            && path_match(url.path(), &self.path)
+            && (!self.secure || Origin::from_url(url).is_secure())
+            && self.expires.is_none_or(|expires| expires > now)
+    }
+
+    /// Whether the cookie goes to a request with the given context, as
+    /// `SameSite` allows.
+    fn allowed(&self, is_same_site: bool, is_top_level_navigation: bool) -> bool {
+        match self.same_site {
+            SameSite::Strict => is_same_site,
+            SameSite::Lax => is_same_site || is_top_level_navigation,
+            SameSite::None => true,
+        }
+    }
+
+    fn same_key(&self, other: &StoredCookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn pair(&self) -> String {
+        if self.name.is_empty() {
+            self.value.clone()
+        } else {
+            format!("{}={}", self.name, self.value)
+        }
+    }
+}
+
+/// Cookies for a session.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<StoredCookie>>,
+}
+
+impl CookieJar {
+    /// Create an empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a cookie from a `Set-Cookie` header or `document.cookie`
+    /// assignment made by `url`. A cookie expiring in the past deletes the
+    /// one it would replace.
+    ///
+    /// `is_same_site` is whether `url` is same-site with the top-level
+    /// document; cross-site contexts may only set `SameSite=None` cookies.
+    /// Returns whether the cookie was accepted.
+    pub fn set_cookie(
+        &self,
+        url: &Url,
+        header: &str,
+        source: CookieSource,
+        is_same_site: bool,
+    ) -> bool {
+        let Some(attributes) = parse_set_cookie(header) else {
+            return false;
+        };
+        let now = SystemTime::now();
+        let Some(cookie) = Self::admit(url, attributes, source, is_same_site, now) else {
+            return false;
+        };
+
+        let mut cookies = self.cookies.lock().unwrap();
+        let replaced = cookies.iter().position(|old| old.same_key(&cookie));
+        if source == CookieSource::Script && replaced.is_some_and(|i| cookies[i].http_only) {
+            return false;
+        }
+        if !Origin::from_url(url).is_secure() {
+            // An insecure origin may not shadow a secure cookie
+            let shadows = cookies.iter().any(|old| {
+                old.secure
+                    && old.name == cookie.name
+                    && (domain_match(&old.domain, &cookie.domain)
+                        || domain_match(&cookie.domain, &old.domain))
+                    && path_match(&cookie.path, &old.path)
+            });
+            if shadows {
+                return false;
+            }
+        }
+        if let Some(index) = replaced {
+            cookies.remove(index);
+        }
+        if cookie.expires.is_some_and(|expires| expires <= now) {
+            return true;
+        }
+        cookies.retain(|cookie| cookie.expires.is_none_or(|expires| expires > now));
+        cookies.push(cookie);
+        true
+    }
+
+    /// The `Cookie` header for a request to `url`, if any cookies go with
+    /// it. HttpOnly cookies are included.
+    pub fn cookie_header(
+        &self,
+        url: &Url,
+        is_same_site: bool,
+        is_top_level_navigation: bool,
+    ) -> Option<String> {
+        let header = self.serialize(url, |cookie| {
+            cookie.allowed(is_same_site, is_top_level_navigation)
+        });
+        (!header.is_empty()).then_some(header)
+    }
+
+    /// `document.cookie` for a document at `url`: the cookies a same-site
+    /// request would send, less HttpOnly ones.
+    pub fn document_cookie(&self, url: &Url, is_same_site: bool) -> String {
+        self.serialize(url, |cookie| {
+            !cookie.http_only && cookie.allowed(is_same_site, false)
+        })
+    }
+
+    /// Forget every cookie.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Number of cookies stored.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `name=value` pairs of the cookies matching `url`, longest path
+    /// first and otherwise oldest first.
+    fn serialize(&self, url: &Url, include: impl Fn(&StoredCookie) -> bool) -> String {
+        let now = SystemTime::now();
+        let cookies = self.cookies.lock().unwrap();
+        let mut matching: Vec<_> = cookies
+            .iter()
+            .filter(|cookie| cookie.matches(url, now) && include(cookie))
+            .collect();
+        matching.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        matching
+            .iter()
+            .map(|cookie| cookie.pair())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// The cookie `url` may store from `attributes`, or `None` if it is
+    /// ignored.
+    fn admit(
+        url: &Url,
+        attributes: CookieAttributes,
+        source: CookieSource,
+        is_same_site: bool,
+        now: SystemTime,
+    ) -> Option<StoredCookie> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let secure_origin = Origin::from_url(url).is_secure();
+        if attributes.http_only && source == CookieSource::Script {
+            return None;
+        }
+        if attributes.secure && !secure_origin {
+            return None;
+        }
+        if attributes.same_site == SameSite::None && !attributes.secure {
+            return None;
+        }
+        if !is_same_site && attributes.same_site != SameSite::None {
+            return None;
+        }
+
+        let (domain, host_only) = match attributes.domain.as_deref() {
+            None => (host.clone(), true),
+            Some(domain) => {
+                let domain = domain.to_ascii_lowercase();
+                if site::is_public_suffix(&domain) {
+                    // Only the suffix itself may set a cookie for it
+                    if domain != host {
+                        return None;
+                    }
+                    (domain, true)
+                } else if domain_match(&host, &domain) {
+                    (domain, false)
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        let path = match attributes.path {
+            Some(path) if path.starts_with('/') => path,
+            _ => default_path(url),
+        };
+
+        // Prefixes promise where the cookie came from
+        let name = &attributes.name;
+        let secured = attributes.secure && secure_origin;
+        if name.starts_with("__Secure-") && !secured {
+            return None;
+        }
+        if name.starts_with("__Host-") && (!secured || !host_only || path != "/") {
+            return None;
+        }
+
+        let latest = now + MAX_LIFETIME;
+        let expires = match attributes.max_age {
+            Some(seconds) if seconds <= 0 => Some(SystemTime::UNIX_EPOCH),
+            Some(seconds) => Some(now + Duration::from_secs(seconds as u64).min(MAX_LIFETIME)),
+            None => attributes.expires.map(|expires| expires.min(latest)),
+        };
+
+        Some(StoredCookie {
+            name: attributes.name,
+            value: attributes.value,
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: attributes.secure,
+            http_only: attributes.http_only,
+            same_site: attributes.same_site,
+        })
+    }
+}
+
+/// Parse a `Set-Cookie` value, or a `document.cookie` assignment, which
+/// has the same form. Unknown attributes are skipped; `None` if the
+/// name-value pair is unusable.
+pub fn parse_set_cookie(header: &str) -> Option<CookieAttributes> {
+    let mut parts = header.split(';');
+    let pair = parts.next()?;
+    let (name, value) = match pair.split_once('=') {
+        Some((name, value)) => (name.trim(), value.trim()),
+        // A lone value is a cookie with an empty name
+        None => ("", pair.trim()),
+    };
+    if (name.is_empty() && value.is_empty())
+        || name.len() + value.len() > MAX_COOKIE_SIZE
+        || name.chars().chain(value.chars()).any(char::is_control)
+    {
+        return None;
+    }
+
+    let mut cookie = CookieAttributes {
+        name: name.to_string(),
+        value: value.to_string(),
+        ..Default::default()
+    };
+    for attribute in parts {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "domain" => {
+                let domain = value.strip_prefix('.').unwrap_or(value);
+                cookie.domain = (!domain.is_empty()).then(|| domain.to_string());
+            }
+            "path" => cookie.path = Some(value.to_string()),
+            "expires" => {
+                // Older servers separate the date with dashes
+                if let Some(expires) = parse_http_date(&value.replace('-', " ")) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => {
+                if let Ok(seconds) = value.parse() {
+                    cookie.max_age = Some(seconds);
+                }
+            }
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => cookie.same_site = value.parse().unwrap_or_default(),
+            _ => {}
+        }
+    }
+    Some(cookie)
+}
+
+/// Whether `host` is `domain` or a subdomain of it.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// Whether `request_path` is `cookie_path` or beneath it.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of `url`'s path, for cookies set without a `Path`.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => url.path()[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn set(jar: &CookieJar, at: &str, header: &str) -> bool {
+        jar.set_cookie(&url(at), header, CookieSource::Http, true)
+    }
+
+    #[test]
+    fn test_scope_and_order() {
+        let jar = CookieJar::new();
+        assert!(set(&jar, "https://www.example.com/a/b", "local=1"));
+        assert!(set(
+            &jar,
+            "https://www.example.com/",
+            "wide=2; Domain=.example.com; Path=/"
+        ));
+        assert!(set(&jar, "https://www.example.com/", "deep=3; Path=/a"));
+
+        assert_eq!(
+            jar.cookie_header(&url("https://www.example.com/a/c"), true, false),
+            Some("local=1; deep=3; wide=2".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header(&url("https://api.example.com/"), true, false),
+            Some("wide=2".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header(&url("https://www.example.com/ab"), true, false),
+            Some("wide=2".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header(&url("https://example.org/"), true, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_foreign_and_public_suffix_domains_are_ignored() {
+        let jar = CookieJar::new();
+        assert!(!set(&jar, "https://example.com/", "a=1; domain=other.com"));
+        assert!(!set(&jar, "https://example.co.uk/", "a=1; domain=co.uk"));
+        assert!(!set(
+            &jar,
+            "https://example.com/",
+            "a=1; domain=www.example.com"
+        ));
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn test_secure_and_prefix_rules() {
+        let jar = CookieJar::new();
+        assert!(!set(&jar, "http://example.com/", "a=1; Secure"));
+        assert!(!set(&jar, "https://example.com/", "__Secure-a=1"));
+        assert!(set(&jar, "https://example.com/", "__Secure-a=1; Secure"));
+        assert!(!set(
+            &jar,
+            "https://example.com/",
+            "__Host-b=1; Secure; Path=/; Domain=example.com"
+        ));
+        assert!(!set(&jar, "https://example.com/x/", "__Host-b=1; Secure"));
+        assert!(set(
+            &jar,
+            "https://example.com/",
+            "__Host-b=1; Secure; Path=/"
+        ));
+        assert!(!set(&jar, "https://example.com/", "c=1; SameSite=None"));
+
+        // Secure cookies stay off insecure requests, and cannot be
+        // shadowed from them
+        assert!(set(&jar, "https://example.com/", "d=1; Secure"));
+        assert_eq!(
+            jar.cookie_header(&url("http://example.com/"), true, false),
+            None
+        );
+        assert!(!set(&jar, "http://example.com/", "d=2"));
+        assert!(!set(&jar, "http://example.com/", "d=2; Domain=example.com"));
+        assert_eq!(
+            jar.cookie_header(&url("https://example.com/"), true, false),
+            Some("__Secure-a=1; __Host-b=1; d=1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_same_site_and_expiry() {
+        let jar = CookieJar::new();
+        assert!(set(&jar, "https://example.com/", "lax=1"));
+        assert!(set(
+            &jar,
+            "https://example.com/",
+            "strict=1; SameSite=Strict"
+        ));
+        assert!(set(
+            &jar,
+            "https://example.com/",
+            "none=1; SameSite=None; Secure"
+        ));
+        let at = url("https://example.com/");
+        assert_eq!(
+            jar.cookie_header(&at, false, false),
+            Some("none=1".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header(&at, false, true),
+            Some("lax=1; none=1".to_string())
+        );
+        // Cross-site contexts may not set same-site cookies
+        assert!(!jar.set_cookie(&at, "other=1", CookieSource::Http, false));
+
+        assert!(set(&jar, "https://example.com/", "lax=gone; Max-Age=0"));
+        assert!(set(
+            &jar,
+            "https://example.com/",
+            "strict=gone; Expires=Thu, 01-Jan-1970 00:00:01 GMT"
+        ));
+        assert_eq!(jar.document_cookie(&at, true), "none=1");
+    }
+
+    #[test]
+    fn test_script_cannot_see_or_replace_http_only_cookies() {
+        let jar = CookieJar::new();
+        let at = url("https://example.com/");
+        assert!(set(
+            &jar,
+            "https://example.com/",
+            "session=abc; HttpOnly; Path=/"
+        ));
+        assert!(jar.set_cookie(&at, "theme=dark", CookieSource::Script, true));
+        assert!(!jar.set_cookie(&at, "session=evil; Path=/", CookieSource::Script, true));
+        assert!(!jar.set_cookie(&at, "x=1; HttpOnly", CookieSource::Script, true));
+
+        assert_eq!(jar.document_cookie(&at, true), "theme=dark");
+        assert_eq!(
+            jar.cookie_header(&at, true, false),
+            Some("session=abc; theme=dark".to_string())
+        );
+    }
+}
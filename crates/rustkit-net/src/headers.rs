@@ -0,0 +1,283 @@
+//! Request header validation.
+//!
+//! Header names and values built from embedder or script strings reach the
+//! wire as written, so a value holding `\r\n` could start a header line or
+//! a whole request of its own. Names must be non-empty RFC 9110 tokens and
+//! values visible ASCII with inner spaces and tabs, no whitespace at either
+//! end and at most [`MAX_VALUE_LEN`] bytes. Script may not set the headers
+//! the user agent owns ([`is_forbidden`]); the loader drops them from
+//! [`Provenance::ScriptOriginated`] requests.
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use thiserror::Error;
+
+/// Longest header value accepted, in bytes.
+pub const MAX_VALUE_LEN: usize = 8 * 1024;
+
+/// Headers of the Fetch standard's forbidden list that script may not
+/// set, besides the `sec-` and `proxy-` prefixes.
+const FORBIDDEN: &[&str] = &[
+    "accept-charset",
+    "accept-encoding",
+    "access-control-request-headers",
+    "access-control-request-method",
+    "connection",
+    "content-length",
+    "cookie",
+    "cookie2",
+    "date",
+    "dnt",
+    "expect",
+    "host",
+    "keep-alive",
+    "referer",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "via",
+];
+
+/// Headers a request carries at most once; a later value replaces the
+/// earlier one instead of joining it.
+const SINGLETONS: &[&str] = &[
+    "authorization",
+    "content-length",
+    "content-type",
+    "host",
+    "if-modified-since",
+    "if-unmodified-since",
+    "max-forwards",
+    "origin",
+    "range",
+    "referer",
+    "user-agent",
+];
+
+/// Who built a request, which decides the headers it may carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Provenance {
+    /// The engine or embedder, which may set any header.
+    #[default]
+    UserAgentInternal,
+    /// A page's script, through `fetch()` or `XMLHttpRequest`.
+    ScriptOriginated,
+}
+
+/// Why a header was refused.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderError {
+    #[error("empty name")]
+    EmptyName,
+    #[error("name is not a token")]
+    InvalidName,
+    #[error("control character in value")]
+    ControlCharacter,
+    #[error("non-ASCII value")]
+    NonAscii,
+    #[error("whitespace around value")]
+    SurroundingWhitespace,
+    #[error("value longer than {MAX_VALUE_LEN} bytes")]
+    TooLong,
+}
+
+/// Check a header built from strings, lowercasing its name.
+pub fn validate(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), HeaderError> {
+    if name.is_empty() {
+        return Err(HeaderError::EmptyName);
+    }
+    if !name.bytes().all(is_token) {
+        return Err(HeaderError::InvalidName);
+    }
+    check_value(value.as_bytes())?;
+    let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| HeaderError::InvalidName)?;
+    let value = HeaderValue::from_str(value).map_err(|_| HeaderError::ControlCharacter)?;
+    Ok((name, value))
+}
+
+/// Check a header value's bytes.
+pub fn check_value(value: &[u8]) -> Result<(), HeaderError> {
+    if value.len() > MAX_VALUE_LEN {
+        return Err(HeaderError::TooLong);
+    }
+    for &byte in value {
+        if byte != b'\t' && byte.is_ascii_control() {
+            return Err(HeaderError::ControlCharacter);
+        }
+        if !byte.is_ascii() {
+            return Err(HeaderError::NonAscii);
+        }
+    }
+    let blank = |byte: Option<&u8>| matches!(byte, Some(b' ' | b'\t'));
+    if blank(value.first()) || blank(value.last()) {
+        return Err(HeaderError::SurroundingWhitespace);
+    }
+    Ok(())
+}
+
+/// `tchar` of RFC 9110.
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Whether script is kept from setting `name`.
+pub fn is_forbidden(name: &HeaderName) -> bool {
+    let name = name.as_str();
+    name.starts_with("sec-") || name.starts_with("proxy-") || FORBIDDEN.contains(&name)
+}
+
+/// Add `value` under `name`: a list-valued header joins the values already
+/// there into one field with `, ` (`; ` for `Cookie`), and one sent once
+/// replaces them.
+pub fn combine(
+    headers: &mut HeaderMap,
+    name: HeaderName,
+    value: HeaderValue,
+) -> Result<(), HeaderError> {
+    if SINGLETONS.contains(&name.as_str()) || !headers.contains_key(&name) {
+        headers.insert(name, value);
+        return Ok(());
+    }
+    let separator: &[u8] = if name == http::header::COOKIE {
+        b"; "
+    } else {
+        b", "
+    };
+    let joined = headers
+        .get_all(&name)
+        .iter()
+        .chain(std::iter::once(&value))
+        .map(HeaderValue::as_bytes)
+        .collect::<Vec<_>>()
+        .join(separator);
+    check_value(&joined)?;
+    let joined = HeaderValue::from_bytes(&joined).map_err(|_| HeaderError::ControlCharacter)?;
+    headers.insert(name, joined);
+    Ok(())
+}
+
+/// Drop the headers a request built by `provenance` may not send: invalid
+/// values from anyone, and forbidden names from script. Returns the names
+/// dropped.
+pub fn sanitize(headers: &mut HeaderMap, provenance: Provenance) -> Vec<HeaderName> {
+    let dropped: Vec<HeaderName> = headers
+        .iter()
+        .filter(|(name, value)| {
+            (provenance == Provenance::ScriptOriginated && is_forbidden(name))
+                || check_value(value.as_bytes()).is_err()
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in &dropped {
+        headers.remove(name);
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Strings that must never reach the wire as given.
+    fn adversarial() -> Vec<String> {
+        let mut values: Vec<String> = [
+            "a\r\nHost: evil.example",
+            "a\r\n\r\nGET /admin HTTP/1.1",
+            "a\nb",
+            "a\rb",
+            "nul\0byte",
+            "bell\x07",
+            "del\x7f",
+            " leading",
+            "trailing\t",
+            "caf\u{e9}",
+            "\u{202e}rtl",
+            "\u{2028}",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        values.push("x".repeat(MAX_VALUE_LEN + 1));
+        values.extend(
+            (0u8..0x20)
+                .filter(|b| *b != b'\t')
+                .map(|b| format!("a{}b", b as char)),
+        );
+        values
+    }
+
+    #[test]
+    fn test_validate_rejects_adversarial_values() {
+        for value in adversarial() {
+            assert!(validate("x-test", &value).is_err(), "{value:?} accepted");
+        }
+        assert_eq!(validate("", "a"), Err(HeaderError::EmptyName));
+        for name in ["x test", "x:y", "x\r\ny", "caf\u{e9}", "a\0"] {
+            assert_eq!(
+                validate(name, "a"),
+                Err(HeaderError::InvalidName),
+                "{name:?}"
+            );
+        }
+        let (name, value) = validate("X-Custom", "a b\tc").unwrap();
+        assert_eq!(name.as_str(), "x-custom");
+        assert_eq!(value, "a b\tc");
+        assert!(validate("x-test", "").is_ok());
+        assert!(validate("x-test", &"x".repeat(MAX_VALUE_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_combine_duplicates() {
+        let mut headers = HeaderMap::new();
+        let mut add = |name: &str, value: &str| {
+            let (name, value) = validate(name, value).unwrap();
+            combine(&mut headers, name, value)
+        };
+        add("accept", "text/html").unwrap();
+        add("Accept", "application/json").unwrap();
+        add("cookie", "a=1").unwrap();
+        add("cookie", "b=2").unwrap();
+        add("content-type", "text/plain").unwrap();
+        add("content-type", "application/json").unwrap();
+        let long = "x".repeat(MAX_VALUE_LEN / 2 + 1);
+        add("x-long", &long).unwrap();
+        assert_eq!(add("x-long", &long), Err(HeaderError::TooLong));
+
+        assert_eq!(headers.get_all("accept").iter().count(), 1);
+        assert_eq!(headers["accept"], "text/html, application/json");
+        assert_eq!(headers["cookie"], "a=1; b=2");
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["x-long"], long.as_str());
+    }
+
+    #[test]
+    fn test_sanitize_by_provenance() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("host", "evil.example"),
+            ("cookie", "session=stolen"),
+            ("sec-fetch-site", "same-origin"),
+            ("proxy-authorization", "Basic x"),
+            ("x-custom", "kept"),
+        ] {
+            let (name, value) = validate(name, value).unwrap();
+            headers.insert(name, value);
+        }
+        let mut internal = headers.clone();
+        // Typed values that bypassed validation
+        headers.insert("x-padded", HeaderValue::from_static(" padded"));
+        internal.insert("x-padded", HeaderValue::from_static(" padded"));
+
+        assert_eq!(
+            sanitize(&mut internal, Provenance::UserAgentInternal).len(),
+            1
+        );
+        assert!(internal.contains_key("host") && internal.contains_key("cookie"));
+
+        assert_eq!(
+            sanitize(&mut headers, Provenance::ScriptOriginated).len(),
+            5
+        );
+        assert_eq!(headers.keys().collect::<Vec<_>>(), ["x-custom"]);
+    }
+}
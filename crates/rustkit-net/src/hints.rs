@@ -0,0 +1,535 @@
+//! Resource hints: `dns-prefetch`, `preconnect`, `prefetch` and `preload`
+//! links, from markup, a `Link` response header or `103 Early Hints`.
+//!
+//! Hints are best-effort and never hold up the page. DNS and connection
+//! warm-up run beside its requests, and prefetches are sent at
+//! [`Priority::VeryLow`](crate::Priority::VeryLow), so they wait for the
+//! page's critical requests. Preloads are for the current page and are
+//! sent at the priority of what they load. A prefetched or preloaded
+//! response is kept for the next request to its URL, as are responses
+//! servers push (see [`crate::push`]).
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+
+use crate::{Priority, Site};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How long a preconnected connection is held for its first request.
+pub const PRECONNECT_TTL: Duration = Duration::from_secs(10);
+
+/// How long a prefetched response may be used, whatever its cache headers
+/// say.
+pub const PREFETCH_FRESHNESS: Duration = Duration::from_secs(5 * 60);
+
+/// Kind of resource hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintKind {
+    /// Resolve the host ahead of time.
+    DnsPrefetch,
+    /// Resolve the host and open a connection, including the TLS handshake.
+    Preconnect,
+    /// Fetch the resource for a likely next navigation.
+    Prefetch,
+    /// Fetch a resource the current page is about to use.
+    Preload,
+}
+
+impl HintKind {
+    fn from_rel(keyword: &str) -> Option<Self> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "dns-prefetch" => Some(Self::DnsPrefetch),
+            "preconnect" => Some(Self::Preconnect),
+            "prefetch" => Some(Self::Prefetch),
+            "preload" => Some(Self::Preload),
+            _ => None,
+        }
+    }
+}
+
+/// What a preload fetches, from the link's `as` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreloadDestination {
+    Style,
+    Script,
+    Font,
+    Image,
+    Fetch,
+}
+
+impl PreloadDestination {
+    /// Parse an `as` value. Preloads without a known one are ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "style" => Some(Self::Style),
+            "script" => Some(Self::Script),
+            "font" => Some(Self::Font),
+            "image" => Some(Self::Image),
+            "fetch" => Some(Self::Fetch),
+            _ => None,
+        }
+    }
+
+    /// Priority the resource would be requested at by the page.
+    pub fn priority(self) -> Priority {
+        match self {
+            Self::Style | Self::Font => Priority::High,
+            Self::Script | Self::Fetch => Priority::Medium,
+            Self::Image => Priority::Low,
+        }
+    }
+}
+
+/// A resource hint for one URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceHint {
+    pub kind: HintKind,
+    pub url: Url,
+    /// Connect or fetch without credentials, from a `crossorigin`
+    /// attribute other than `use-credentials`.
+    pub anonymous: bool,
+    /// What a [`HintKind::Preload`] fetches.
+    pub destination: Option<PreloadDestination>,
+}
+
+impl ResourceHint {
+    /// Hints of a link, one per hint keyword in `rel`; `destination` is
+    /// its `as` attribute. Links to anything but `http` and `https` URLs
+    /// give none.
+    pub fn from_link(
+        rel: &str,
+        href: &str,
+        destination: Option<&str>,
+        crossorigin: Option<&str>,
+        base: &Url,
+    ) -> Vec<Self> {
+        let Ok(url) = base.join(href.trim()) else {
+            return Vec::new();
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            return Vec::new();
+        }
+        let anonymous =
+            crossorigin.is_some_and(|value| !value.trim().eq_ignore_ascii_case("use-credentials"));
+        let destination = destination.and_then(PreloadDestination::parse);
+        let mut hints: Vec<Self> = Vec::new();
+        for kind in rel.split_whitespace().filter_map(HintKind::from_rel) {
+            if kind == HintKind::Preload && destination.is_none() {
+                continue;
+            }
+            if !hints.iter().any(|hint| hint.kind == kind) {
+                hints.push(Self {
+                    kind,
+                    url: url.clone(),
+                    anonymous,
+                    destination: destination.filter(|_| kind == HintKind::Preload),
+                });
+            }
+        }
+        hints
+    }
+}
+
+/// Hints in a `Link` header value, e.g.
+/// `<https://cdn.example>; rel=preconnect; crossorigin`.
+pub fn parse_link_header(value: &str, base: &Url) -> Vec<ResourceHint> {
+    let mut hints = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let href = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        // Parameters run to the next comma outside a quoted string
+        let mut quoted = false;
+        let params_end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == ',' && !quoted
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let (mut rel, mut destination, mut crossorigin) = (None, None, None);
+        for param in rest[..params_end].split(';') {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim().trim_matches('"')),
+                None => (param.trim(), ""),
+            };
+            if name.eq_ignore_ascii_case("rel") {
+                rel.get_or_insert(value);
+            } else if name.eq_ignore_ascii_case("as") {
+                destination.get_or_insert(value);
+            } else if name.eq_ignore_ascii_case("crossorigin") {
+                crossorigin = Some(value);
+            }
+        }
+        if let Some(rel) = rel {
+            hints.extend(ResourceHint::from_link(
+                rel,
+                href,
+                destination,
+                crossorigin,
+                base,
+            ));
+        }
+        rest = &rest[params_end..];
+    }
+    hints
+}
+
+/// Hints in all `Link` headers of a response.
+pub fn parse_link_headers(headers: &HeaderMap, base: &Url) -> Vec<ResourceHint> {
+    headers
+        .get_all(http::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| parse_link_header(value, base))
+        .collect()
+}
+
+/// Counters for subresources a document's preload scanner found while
+/// the document was still arriving.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpeculativeStats {
+    /// URLs handed to the loader.
+    pub urls_discovered: u64,
+    /// Fetches started for them; the others were already cached or skipped.
+    pub fetches_started: u64,
+    /// Requests answered by a response fetched for the scanner.
+    pub cache_hits: u64,
+}
+
+/// A prefetched or pushed response.
+pub(crate) struct Prefetched {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// Pushed by the server rather than fetched.
+    pub pushed: bool,
+    /// Fetched for a document's preload scanner.
+    pub speculative: bool,
+    stored: Instant,
+}
+
+/// Where a kept response came from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Hint,
+    Scanner,
+    Push,
+}
+
+impl Prefetched {
+    fn is_fresh(&self) -> bool {
+        self.stored.elapsed() < PREFETCH_FRESHNESS
+    }
+}
+
+/// Prefetched responses by top-level site and URL. Each is used once, by
+/// the next `GET` for its URL from the same site within
+/// [`PREFETCH_FRESHNESS`]. Unpartitioned, every site shares one entry
+/// per URL.
+#[derive(Default)]
+pub(crate) struct PrefetchCache {
+    /// Whether entries are keyed by top-level site.
+    partitioned: bool,
+    entries: Mutex<HashMap<CacheKey, Prefetched>>,
+    /// Pushed responses dropped unused since last taken.
+    wasted_pushes: AtomicU64,
+}
+
+pub(crate) type CacheKey = (Option<Site>, Url);
+
+pub(crate) fn cache_key(site: Option<&Site>, url: &Url) -> CacheKey {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    (site.cloned(), url)
+}
+
+impl PrefetchCache {
+    /// An empty cache, keyed by top-level site if `partitioned`.
+    pub fn new(partitioned: bool) -> Self {
+        Self {
+            partitioned,
+            ..Default::default()
+        }
+    }
+
+    /// The key of `url` for `site`, which an unpartitioned cache ignores.
+    fn key(&self, site: Option<&Site>, url: &Url) -> CacheKey {
+        cache_key(site.filter(|_| self.partitioned), url)
+    }
+
+    /// Keep a response, unless it forbids storing.
+    pub fn store(
+        &self,
+        site: Option<&Site>,
+        url: &Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) {
+        self.insert(site, url, status, headers, body, Source::Hint);
+    }
+
+    /// Keep a response fetched for a preload scanner, unless it forbids
+    /// storing.
+    pub fn store_speculative(
+        &self,
+        site: Option<&Site>,
+        url: &Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) {
+        self.insert(site, url, status, headers, body, Source::Scanner);
+    }
+
+    /// Keep a pushed response, unless it forbids storing or a fresh
+    /// response for `url` is already kept. Returns whether it was kept.
+    pub fn store_pushed(
+        &self,
+        site: Option<&Site>,
+        url: &Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> bool {
+        self.insert(site, url, status, headers, body, Source::Push)
+    }
+
+    fn insert(
+        &self,
+        site: Option<&Site>,
+        url: &Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+        source: Source,
+    ) -> bool {
+        let no_store = headers
+            .get_all(http::header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"));
+        if no_store {
+            return false;
+        }
+        let key = self.key(site, url);
+        let mut entries = self.entries.lock().unwrap();
+        self.drop_stale(&mut entries);
+        if source == Source::Push && entries.contains_key(&key) {
+            return false;
+        }
+        let replaced = entries.insert(
+            key,
+            Prefetched {
+                status,
+                headers,
+                body,
+                pushed: source == Source::Push,
+                speculative: source == Source::Scanner,
+                stored: Instant::now(),
+            },
+        );
+        if let Some(replaced) = replaced {
+            self.dropped(&replaced);
+        }
+        true
+    }
+
+    fn drop_stale(&self, entries: &mut HashMap<CacheKey, Prefetched>) {
+        entries.retain(|_, entry| {
+            let fresh = entry.is_fresh();
+            if !fresh {
+                self.dropped(entry);
+            }
+            fresh
+        });
+    }
+
+    /// Count `entry` as wasted if it was pushed.
+    fn dropped(&self, entry: &Prefetched) {
+        if entry.pushed {
+            self.wasted_pushes.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Pushed responses dropped without being used since the last call.
+    pub fn take_wasted_pushes(&self) -> u64 {
+        self.wasted_pushes.swap(0, Ordering::SeqCst)
+    }
+
+    /// Whether a usable response for `url` is stored for `site`.
+    pub fn is_fresh(&self, site: Option<&Site>, url: &Url) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&self.key(site, url))
+            .is_some_and(Prefetched::is_fresh)
+    }
+
+    /// Remove and return the response for `url` stored for `site`, if
+    /// still usable.
+    pub fn take(&self, site: Option<&Site>, url: &Url) -> Option<Prefetched> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.remove(&self.key(site, url))?;
+        if !entry.is_fresh() {
+            self.dropped(&entry);
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// Body bytes held.
+    pub fn usage(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.body.len())
+            .sum()
+    }
+
+    /// Drop stale responses, then the oldest, until at most `target_bytes`
+    /// are held. Returns the bytes freed.
+    pub fn trim(&self, target_bytes: usize) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before: usize = entries.values().map(|entry| entry.body.len()).sum();
+        self.drop_stale(&mut entries);
+        let mut held: usize = entries.values().map(|entry| entry.body.len()).sum();
+        let mut by_age: Vec<(Instant, CacheKey)> = entries
+            .iter()
+            .map(|(key, entry)| (entry.stored, key.clone()))
+            .collect();
+        by_age.sort_by_key(|(stored, _)| *stored);
+        for (_, key) in by_age {
+            if held <= target_bytes {
+                break;
+            }
+            if let Some(entry) = entries.remove(&key) {
+                held -= entry.body.len();
+                self.dropped(&entry);
+            }
+        }
+        before - held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_header_hints() {
+        let base = Url::parse("https://example.com/articles/1").unwrap();
+        let hints = parse_link_header(
+            "<https://cdn.example.com>; rel=preconnect; crossorigin, \
+             </articles/2?a=1,2>; rel=\"prefetch next\", \
+             <//fonts.example.com>; rel=\"dns-prefetch preconnect\"; crossorigin=use-credentials, \
+             </style.css>; rel=preload, <javascript:alert(1)>; rel=prefetch, \
+             </font.woff2>; rel=preload; as=font; crossorigin",
+            &base,
+        );
+        let summary: Vec<_> = hints
+            .iter()
+            .map(|h| (h.kind, h.url.as_str(), h.anonymous))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (HintKind::Preconnect, "https://cdn.example.com/", true),
+                (
+                    HintKind::Prefetch,
+                    "https://example.com/articles/2?a=1,2",
+                    false
+                ),
+                (HintKind::DnsPrefetch, "https://fonts.example.com/", false),
+                (HintKind::Preconnect, "https://fonts.example.com/", false),
+                (HintKind::Preload, "https://example.com/font.woff2", true),
+            ]
+        );
+        // The preload of /style.css had no `as` and gave no hint
+        assert_eq!(
+            hints.last().unwrap().destination,
+            Some(PreloadDestination::Font)
+        );
+    }
+
+    #[test]
+    fn test_prefetched_responses_are_used_once() {
+        let cache = PrefetchCache::new(true);
+        let url = Url::parse("https://example.com/next").unwrap();
+        cache.store(
+            None,
+            &url,
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from("next"),
+        );
+        assert!(cache.is_fresh(None, &url.join("#top").unwrap()));
+        assert_eq!(cache.take(None, &url).unwrap().body, "next");
+        assert!(cache.take(None, &url).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CACHE_CONTROL,
+            "private, no-store".parse().unwrap(),
+        );
+        cache.store(None, &url, StatusCode::OK, headers, Bytes::from("next"));
+        assert!(!cache.is_fresh(None, &url));
+    }
+
+    #[test]
+    fn test_entries_without_a_site_not_shared_when_partitioned() {
+        let url = Url::parse("https://example.com/next").unwrap();
+        let site_b = Site::from_url(&Url::parse("https://b.example.org/").unwrap()).unwrap();
+        let store = |cache: &PrefetchCache| {
+            cache.store(None, &url, StatusCode::OK, HeaderMap::new(), Bytes::from("next"))
+        };
+
+        let cache = PrefetchCache::new(true);
+        store(&cache);
+        assert!(!cache.is_fresh(Some(&site_b), &url));
+        assert!(cache.take(Some(&site_b), &url).is_none());
+        assert!(cache.is_fresh(None, &url));
+
+        let cache = PrefetchCache::new(false);
+        store(&cache);
+        assert!(cache.is_fresh(Some(&site_b), &url));
+        assert_eq!(cache.take(Some(&site_b), &url).unwrap().body, "next");
+    }
+
+    #[test]
+    fn test_trim_drops_oldest_prefetches() {
+        let cache = PrefetchCache::new(true);
+        let old = Url::parse("https://example.com/old").unwrap();
+        let new = Url::parse("https://example.com/new").unwrap();
+        cache.store(
+            None,
+            &old,
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from("0123456789"),
+        );
+        cache.store(
+            None,
+            &new,
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from("01234"),
+        );
+        assert_eq!(cache.usage(), 15);
+        assert_eq!(cache.trim(8), 10);
+        assert!(!cache.is_fresh(None, &old) && cache.is_fresh(None, &new));
+        assert_eq!(cache.trim(0), 5);
+    }
+}
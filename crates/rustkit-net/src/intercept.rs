@@ -1,8 +1,11 @@
 //! Request interception for URL filtering and modification.
 
 use crate::{Request, Url};
-use std::sync::Arc;
-use tracing::{debug, trace};
+use http::StatusCode;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, trace, warn};
 
 /// Action to take for an intercepted request.
 #[derive(Debug, Clone)]
@@ -11,16 +14,63 @@ pub enum InterceptAction {
     Allow,
     /// Block the request.
     Block,
-    /// Redirect to a different URL.
+    /// Answer without a network request by redirecting to a different URL.
+    /// The loader follows it as an internal redirect: it counts against
+    /// the redirect limit and shows in the response's redirect chain.
     Redirect(Url),
     /// Modify the request.
     Modify(Box<Request>),
 }
 
+/// What to do with a redirect the server sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectAction {
+    /// Follow it to the `Location`.
+    Follow,
+    /// Follow it to a different URL.
+    FollowModified(Url),
+    /// Don't follow it; the redirect response completes the request.
+    Block,
+}
+
 /// Handler for intercepting requests.
 pub trait InterceptHandler: Send + Sync {
     /// Called for each request. Return the action to take.
     fn intercept(&self, request: &Request) -> InterceptAction;
+
+    /// Called for each redirect a server sends in answer to `request`,
+    /// before it is followed.
+    fn on_redirect(
+        &self,
+        _request: &Request,
+        _location: &Url,
+        _status: StatusCode,
+    ) -> RedirectAction {
+        RedirectAction::Follow
+    }
+}
+
+/// Identifier returned by [`RequestInterceptor::add_handler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+impl HandlerId {
+    fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(1);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A registered handler.
+#[derive(Clone)]
+struct HandlerEntry {
+    id: HandlerId,
+    priority: i32,
+    handler: Arc<dyn InterceptHandler>,
 }
 
 /// URL pattern for matching.
@@ -119,10 +169,14 @@ pub enum RuleAction {
 }
 
 /// Request interceptor with configurable rules.
+///
+/// Handlers may be added and removed at any time, including while requests
+/// are in flight; each request runs against the handlers registered when it
+/// was dispatched.
 pub struct RequestInterceptor {
     rules: Vec<InterceptRule>,
     default_action: InterceptAction,
-    handlers: Vec<Arc<dyn InterceptHandler>>,
+    handlers: RwLock<Vec<HandlerEntry>>,
 }
 
 impl RequestInterceptor {
@@ -131,7 +185,7 @@ impl RequestInterceptor {
         Self {
             rules: Vec::new(),
             default_action: InterceptAction::Allow,
-            handlers: Vec::new(),
+            handlers: RwLock::new(Vec::new()),
         }
     }
 
@@ -152,9 +206,54 @@ impl RequestInterceptor {
         self.rules.retain(|r| r.pattern.pattern != pattern);
     }
 
-    /// Add a custom handler.
-    pub fn add_handler(&mut self, handler: Arc<dyn InterceptHandler>) {
-        self.handlers.push(handler);
+    /// Add a custom handler with priority 0.
+    pub fn add_handler(&self, handler: Arc<dyn InterceptHandler>) -> HandlerId {
+        self.add_handler_with_priority(0, handler)
+    }
+
+    /// Add a custom handler. Higher priorities run first; handlers with equal
+    /// priority run in the order they were added.
+    pub fn add_handler_with_priority(
+        &self,
+        priority: i32,
+        handler: Arc<dyn InterceptHandler>,
+    ) -> HandlerId {
+        let id = HandlerId::new();
+        let mut handlers = self.handlers.write().unwrap_or_else(|e| e.into_inner());
+        let index = handlers.partition_point(|h| h.priority >= priority);
+        handlers.insert(
+            index,
+            HandlerEntry {
+                id,
+                priority,
+                handler,
+            },
+        );
+        id
+    }
+
+    /// Remove a handler. Returns false if it was not registered.
+    pub fn remove_handler(&self, id: HandlerId) -> bool {
+        let mut handlers = self.handlers.write().unwrap_or_else(|e| e.into_inner());
+        let before = handlers.len();
+        handlers.retain(|h| h.id != id);
+        handlers.len() != before
+    }
+
+    /// Number of registered handlers.
+    pub fn handler_count(&self) -> usize {
+        self.handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Whether every request is allowed unchanged: no rules, no handlers
+    /// and an allowing default.
+    pub fn is_passthrough(&self) -> bool {
+        self.rules.is_empty()
+            && matches!(self.default_action, InterceptAction::Allow)
+            && self.handler_count() == 0
     }
 
     /// Block URLs matching a pattern.
@@ -186,16 +285,38 @@ impl RequestInterceptor {
 
     /// Intercept a request.
     pub async fn intercept(&self, request: &Request) -> InterceptAction {
+        self.decide(request)
+            .unwrap_or_else(|| self.default_action.clone())
+    }
+
+    /// Run handlers and rules against a request.
+    ///
+    /// Returns `None` when no handler acted and no rule matched, leaving the
+    /// decision to the default action or to a broader interceptor.
+    pub fn decide(&self, request: &Request) -> Option<InterceptAction> {
         trace!(url = %request.url, "Intercepting request");
 
+        // Snapshot so handlers can add or remove handlers without deadlocking
+        let handlers = self
+            .handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
         // Check custom handlers first
-        for handler in &self.handlers {
-            let action = handler.intercept(request);
+        for entry in &handlers {
+            let action = match catch_unwind(AssertUnwindSafe(|| entry.handler.intercept(request))) {
+                Ok(action) => action,
+                Err(_) => {
+                    warn!(url = %request.url, handler = entry.id.raw(), "Intercept handler panicked");
+                    continue;
+                }
+            };
             match action {
                 InterceptAction::Allow => continue,
                 other => {
                     debug!(url = %request.url, action = ?other, "Handler intercepted");
-                    return other;
+                    return Some(other);
                 }
             }
         }
@@ -215,12 +336,43 @@ impl RequestInterceptor {
                     }
                 };
                 debug!(url = %request.url, pattern = %rule.pattern.pattern, action = ?action, "Rule matched");
-                return action;
+                return Some(action);
             }
         }
 
-        // Default action
-        self.default_action.clone()
+        None
+    }
+
+    /// Ask handlers about a server redirect.
+    ///
+    /// Returns `None` when every handler follows it unchanged.
+    pub fn decide_redirect(
+        &self,
+        request: &Request,
+        location: &Url,
+        status: StatusCode,
+    ) -> Option<RedirectAction> {
+        let handlers = self
+            .handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for entry in &handlers {
+            let action = catch_unwind(AssertUnwindSafe(|| {
+                entry.handler.on_redirect(request, location, status)
+            }));
+            match action {
+                Ok(RedirectAction::Follow) => continue,
+                Ok(other) => {
+                    debug!(url = %request.url, %location, action = ?other, "Handler decided redirect");
+                    return Some(other);
+                }
+                Err(_) => {
+                    warn!(url = %request.url, handler = entry.id.raw(), "Redirect handler panicked");
+                }
+            }
+        }
+        None
     }
 }
 
@@ -230,6 +382,126 @@ impl Default for RequestInterceptor {
     }
 }
 
+/// Query parameters [`TrackingParamStripper::new`] removes. A trailing `*`
+/// matches any suffix.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "mc_eid", "igshid",
+    "yclid", "twclid", "_hsenc", "_hsmi",
+];
+
+/// Removes tracking query parameters from every request before it is
+/// sent, by redirecting to the URL without them.
+#[derive(Debug, Clone)]
+pub struct TrackingParamStripper {
+    params: Vec<String>,
+    exceptions: Vec<String>,
+}
+
+impl TrackingParamStripper {
+    /// A stripper for [`DEFAULT_TRACKING_PARAMS`].
+    pub fn new() -> Self {
+        Self::with_params(DEFAULT_TRACKING_PARAMS.iter().copied())
+    }
+
+    /// A stripper for the given parameter names.
+    pub fn with_params<'a>(params: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            params: params.into_iter().map(str::to_ascii_lowercase).collect(),
+            exceptions: Vec::new(),
+        }
+    }
+
+    /// Also strip `param`.
+    pub fn deny(mut self, param: &str) -> Self {
+        self.params.push(param.to_ascii_lowercase());
+        self
+    }
+
+    /// Leave URLs on `domain` and its subdomains alone.
+    pub fn except_domain(mut self, domain: &str) -> Self {
+        self.exceptions
+            .push(domain.trim_start_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    fn is_tracking(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == *param,
+            })
+    }
+
+    fn is_excepted(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        self.exceptions.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+
+    /// `url` without tracking parameters, or `None` if it has none. The
+    /// fragment and the order of the other parameters are kept.
+    pub fn strip(&self, url: &Url) -> Option<Url> {
+        let query = url.query()?;
+        if self.is_excepted(url) {
+            return None;
+        }
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                !url::form_urlencoded::parse(pair.as_bytes())
+                    .next()
+                    .is_some_and(|(name, _)| self.is_tracking(&name))
+            })
+            .collect();
+        if kept.len() == query.split('&').count() {
+            return None;
+        }
+        let mut stripped = url.clone();
+        stripped.set_query(
+            Some(&kept.join("&"))
+                .filter(|q| !q.is_empty())
+                .map(String::as_str),
+        );
+        Some(stripped)
+    }
+}
+
+impl Default for TrackingParamStripper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterceptHandler for TrackingParamStripper {
+    fn intercept(&self, request: &Request) -> InterceptAction {
+        match self.strip(&request.url) {
+            Some(url) => InterceptAction::Redirect(url),
+            None => InterceptAction::Allow,
+        }
+    }
+
+    fn on_redirect(
+        &self,
+        _request: &Request,
+        location: &Url,
+        _status: StatusCode,
+    ) -> RedirectAction {
+        match self.strip(location) {
+            Some(url) => RedirectAction::FollowModified(url),
+            None => RedirectAction::Follow,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +517,44 @@ mod tests {
             timeout: None,
             credentials: Default::default(),
             referrer: None,
+            is_navigation: false,
+            view_id: None,
+            cancel: Default::default(),
+            streaming: false,
+            priority: Default::default(),
+            initiator: Default::default(),
+            top_level_site: None,
+            provenance: Default::default(),
+        }
+    }
+
+    struct BlockAll;
+
+    impl InterceptHandler for BlockAll {
+        fn intercept(&self, _request: &Request) -> InterceptAction {
+            InterceptAction::Block
+        }
+    }
+
+    struct Panics;
+
+    impl InterceptHandler for Panics {
+        fn intercept(&self, _request: &Request) -> InterceptAction {
+            panic!("handler bug");
+        }
+    }
+
+    /// Registers a blocking handler on its interceptor mid-request.
+    struct AddsBlocker(std::sync::Weak<RequestInterceptor>);
+
+    impl InterceptHandler for AddsBlocker {
+        fn intercept(&self, _request: &Request) -> InterceptAction {
+            if let Some(interceptor) = self.0.upgrade() {
+                if interceptor.handler_count() == 1 {
+                    interceptor.add_handler_with_priority(-1, Arc::new(BlockAll));
+                }
+            }
+            InterceptAction::Allow
         }
     }
 
@@ -318,4 +628,101 @@ mod tests {
             _ => panic!("Expected redirect"),
         }
     }
+
+    #[tokio::test]
+    async fn test_handler_added_at_runtime() {
+        let interceptor = Arc::new(RequestInterceptor::new());
+        interceptor.add_handler(Arc::new(AddsBlocker(Arc::downgrade(&interceptor))));
+
+        // The in-flight request keeps the handler list it started with
+        let first = interceptor
+            .intercept(&test_request("https://example.com/a"))
+            .await;
+        assert!(matches!(first, InterceptAction::Allow));
+        assert_eq!(interceptor.handler_count(), 2);
+
+        let second = interceptor
+            .intercept(&test_request("https://example.com/b"))
+            .await;
+        assert!(matches!(second, InterceptAction::Block));
+    }
+
+    #[tokio::test]
+    async fn test_handler_priority_and_removal() {
+        let interceptor = RequestInterceptor::new();
+        let redirect = Url::parse("https://new.com/").unwrap();
+
+        struct RedirectTo(Url);
+        impl InterceptHandler for RedirectTo {
+            fn intercept(&self, _request: &Request) -> InterceptAction {
+                InterceptAction::Redirect(self.0.clone())
+            }
+        }
+
+        let block = interceptor.add_handler(Arc::new(BlockAll));
+        interceptor.add_handler_with_priority(-5, Arc::new(RedirectTo(redirect)));
+        let request = test_request("https://example.com/");
+        assert!(matches!(
+            interceptor.intercept(&request).await,
+            InterceptAction::Block
+        ));
+
+        assert!(interceptor.remove_handler(block));
+        assert!(!interceptor.remove_handler(block));
+        assert!(matches!(
+            interceptor.intercept(&request).await,
+            InterceptAction::Redirect(_)
+        ));
+    }
+
+    #[test]
+    fn test_tracking_param_stripper() {
+        let stripper = TrackingParamStripper::new()
+            .deny("ref_src")
+            .except_domain("partner.example");
+        let strip = |url: &str| stripper.strip(&Url::parse(url).unwrap()).map(String::from);
+
+        assert_eq!(
+            strip("https://example.com/a?id=1&utm_source=x&UTM_Medium=y&fbclid=z&ref_src=t#top")
+                .as_deref(),
+            Some("https://example.com/a?id=1#top")
+        );
+        assert_eq!(
+            strip("https://example.com/a?utm_campaign=x").as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(strip("https://example.com/a?id=1&utmost=2"), None);
+        assert_eq!(strip("https://example.com/a"), None);
+        assert_eq!(strip("https://cdn.partner.example/a?utm_source=x"), None);
+        assert!(strip("https://notpartner.example/a?utm_source=x").is_some());
+
+        let request = test_request("https://example.com/?gclid=1");
+        assert!(matches!(
+            stripper.intercept(&request),
+            InterceptAction::Redirect(url) if url.as_str() == "https://example.com/"
+        ));
+        let location = Url::parse("https://example.com/next?fbclid=1").unwrap();
+        assert_eq!(
+            stripper.on_redirect(&request, &location, StatusCode::FOUND),
+            RedirectAction::FollowModified(Url::parse("https://example.com/next").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panicking_handler_continues() {
+        let interceptor = RequestInterceptor::new();
+        interceptor.add_handler_with_priority(1, Arc::new(Panics));
+        let request = test_request("https://example.com/");
+        assert!(matches!(
+            interceptor.intercept(&request).await,
+            InterceptAction::Allow
+        ));
+
+        // The handler list is still usable afterwards
+        interceptor.add_handler(Arc::new(BlockAll));
+        assert!(matches!(
+            interceptor.intercept(&request).await,
+            InterceptAction::Block
+        ));
+    }
 }
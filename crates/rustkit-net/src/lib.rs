@@ -8,33 +8,92 @@
 //! 2. **Request interception**: Filter/modify/block requests
 //! 3. **Download management**: Progress, pause, resume, cancel
 //! 4. **fetch() API**: JavaScript-compatible fetch interface
-
-use std::collections::HashMap;
+//! 5. **Traffic shaping**: Simulated latency, bandwidth caps and offline mode
+//! 6. **Protocol preferences**: Alt-Svc records and per-origin HTTP/1.1 pinning
+//! 7. **Cancellation**: Per-request abort handles and per-view cancellation
+//! 8. **Resource hints**: DNS prefetch, preconnect and prefetch
+//! 9. **Telemetry**: Per-request records for metrics and tracing sinks
+//! 10. **Conditional requests**: ETag revalidation and resource polling
+//! 11. **Cookies**: One jar for requests and `document.cookie`
+//! 12. **URL safety**: Display-safe hosts, no userinfo on the wire
+
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{Stream, StreamExt};
 use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
 use mime::Mime;
-use rustkit_http::Client as HttpClient;
+use rustkit_core::charset::Charset;
+use rustkit_http::{Client as HttpClient, PinnedCertificate};
 use thiserror::Error;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, trace, warn};
 use url::Url;
 
+pub mod blob;
+pub mod cancel;
+pub mod conditional;
+pub mod cookies;
 pub mod download;
+pub mod headers;
+pub mod hints;
 pub mod intercept;
+pub mod protocol;
+pub mod proxy;
+pub mod push;
 pub mod security;
-
-pub use download::{Download, DownloadEvent, DownloadId, DownloadManager, DownloadState};
-pub use intercept::{InterceptAction, InterceptHandler, RequestInterceptor};
+pub mod site;
+pub mod telemetry;
+pub mod throttle;
+pub mod urls;
+
+pub use blob::{BlobData, BlobUrlStore};
+pub use cancel::{CancelHandle, Initiator, NetEvent};
+pub use conditional::{ConditionalResponse, PollOptions, PollUpdate, Validators};
+pub use cookies::{parse_set_cookie, CookieJar, CookieSource};
+pub use download::{
+    sanitize_filename, unique_destination, Download, DownloadEvent, DownloadHook, DownloadId,
+    DownloadManager, DownloadState, HookVerdict,
+};
+pub use headers::{HeaderError, Provenance};
+pub use hints::{
+    parse_link_header, parse_link_headers, HintKind, PreloadDestination, ResourceHint,
+    SpeculativeStats,
+};
+pub use intercept::{
+    HandlerId, InterceptAction, InterceptHandler, RedirectAction, RequestInterceptor,
+    TrackingParamStripper, DEFAULT_TRACKING_PARAMS,
+};
+pub use protocol::{
+    parse_alt_svc, AltService, AltSvc, NetStats, OriginStats, ProtocolOverride, ProtocolPreferences,
+};
+pub use proxy::{ManualProxy, ProxyMode, SystemProxySettings};
+pub use push::PushStats;
+pub use rustkit_http::multipart::{MultipartParser, Part};
+pub use rustkit_http::{
+    certificate_fingerprint, CertificateErrorKind, HostPoolStats, HttpError, Partition, PoolConfig,
+    PoolStats, ProxyServer, RevocationMode, TcpKeepalive, TlsConfig, TlsInfo, TlsVersion,
+};
 pub use security::{
     check_mixed_content, ContentSecurityPolicy, CookieAttributes, CorsChecker, CorsResult,
     CspDirective, CspSource, HashAlgorithm, MixedContentResult, MixedContentType, Origin,
     ReferrerPolicy, SameSite, SandboxFlags, SecurityContext, SecurityError,
 };
+pub use site::{PublicSuffixList, Site};
+pub use telemetry::{
+    ErrorClass, RequestOutcome, RequestRecord, StatusClass, TelemetryAggregator, TelemetrySink,
+    TelemetrySnapshot, TracingTelemetrySink,
+};
+pub use throttle::NetworkConditions;
+pub use urls::{
+    display_host, display_url, has_userinfo, normalize_path, parse_url, without_userinfo,
+};
 
 /// Errors that can occur in networking.
 #[derive(Error, Debug)]
@@ -45,6 +104,9 @@ pub enum NetError {
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
 
+    #[error("Invalid header {name:?}: {reason}")]
+    InvalidHeader { name: String, reason: HeaderError },
+
     #[error("Timeout after {0:?}")]
     Timeout(Duration),
 
@@ -54,11 +116,40 @@ pub enum NetError {
     #[error("Request blocked")]
     Blocked,
 
+    #[error("Network is offline")]
+    Offline,
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("TLS certificate error for {host}: {kind:?}")]
+    TlsError {
+        host: String,
+        kind: CertificateErrorKind,
+        /// DER-encoded certificates presented by the server, leaf first.
+        cert_chain_der: Vec<Vec<u8>>,
+    },
+
     #[error("HTTP error: {0}")]
-    HttpError(#[from] rustkit_http::HttpError),
+    HttpError(rustkit_http::HttpError),
+}
+
+impl From<rustkit_http::HttpError> for NetError {
+    fn from(error: rustkit_http::HttpError) -> Self {
+        match error {
+            rustkit_http::HttpError::CertificateError {
+                host,
+                kind,
+                cert_chain_der,
+                ..
+            } => NetError::TlsError {
+                host,
+                kind,
+                cert_chain_der,
+            },
+            other => NetError::HttpError(other),
+        }
+    }
 }
 
 /// Unique identifier for a request.
@@ -93,6 +184,22 @@ pub struct Request {
     pub timeout: Option<Duration>,
     pub credentials: CredentialsMode,
     pub referrer: Option<Url>,
+    /// Top-level navigation (certificate exceptions only apply to these).
+    pub is_navigation: bool,
+    /// View that issued the request, for view-scoped interception.
+    pub view_id: Option<u64>,
+    /// Aborts the request; clones of the request share it.
+    pub cancel: CancelHandle,
+    /// Deliver the body as it arrives rather than once it is complete.
+    pub streaming: bool,
+    pub priority: Priority,
+    pub initiator: Initiator,
+    /// Site of the top-level document the request is made for, whose
+    /// partition of the caches it uses. Navigations use their own site.
+    pub top_level_site: Option<Site>,
+    /// Who built the request; the loader drops the headers script may not
+    /// set from script-originated requests.
+    pub provenance: Provenance,
 }
 
 impl Request {
@@ -107,6 +214,14 @@ impl Request {
             timeout: Some(Duration::from_secs(30)),
             credentials: CredentialsMode::SameOrigin,
             referrer: None,
+            is_navigation: false,
+            view_id: None,
+            cancel: CancelHandle::new(),
+            streaming: false,
+            priority: Priority::default(),
+            initiator: Initiator::default(),
+            top_level_site: None,
+            provenance: Provenance::default(),
         }
     }
 
@@ -121,12 +236,60 @@ impl Request {
             timeout: Some(Duration::from_secs(30)),
             credentials: CredentialsMode::SameOrigin,
             referrer: None,
+            is_navigation: false,
+            view_id: None,
+            cancel: CancelHandle::new(),
+            streaming: false,
+            priority: Priority::default(),
+            initiator: Initiator::default(),
+            top_level_site: None,
+            provenance: Provenance::default(),
         }
     }
 
-    /// Add a header.
+    /// Add a header, replacing any of the same name.
+    ///
+    /// Deprecated in favor of [`Self::try_header`]: kept for existing
+    /// callers, it logs and drops a value that fails validation rather than
+    /// reporting it.
+    #[deprecated(note = "use try_header")]
     pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
-        self.headers.insert(name, value);
+        match headers::check_value(value.as_bytes()) {
+            Ok(()) => {
+                self.headers.insert(name, value);
+            }
+            Err(reason) => warn!(%name, %reason, "Dropping invalid request header"),
+        }
+        self
+    }
+
+    /// Add a header built from strings, such as embedder or script input.
+    /// A repeated list-valued header joins the values already set, and one
+    /// sent once replaces them.
+    pub fn try_header(mut self, name: &str, value: &str) -> Result<Self, NetError> {
+        let invalid = |reason| NetError::InvalidHeader {
+            name: name.to_string(),
+            reason,
+        };
+        let (name, value) = headers::validate(name, value).map_err(invalid)?;
+        headers::combine(&mut self.headers, name, value).map_err(invalid)?;
+        Ok(self)
+    }
+
+    /// Send the userinfo of the URL as `Authorization: Basic` credentials.
+    /// Without this the loader drops userinfo unsent.
+    pub fn basic_auth_from_url(mut self) -> Self {
+        if let Some(value) = urls::basic_auth(&self.url) {
+            self.headers.insert(http::header::AUTHORIZATION, value);
+        }
+        self.url = without_userinfo(&self.url);
+        self
+    }
+
+    /// Mark as built by a page's script, which may not set the headers the
+    /// user agent owns.
+    pub fn script_originated(mut self) -> Self {
+        self.provenance = Provenance::ScriptOriginated;
         self
     }
 
@@ -141,6 +304,66 @@ impl Request {
         self.referrer = Some(referrer);
         self
     }
+
+    /// Mark as a top-level navigation, at [`Priority::High`].
+    pub fn navigation(mut self) -> Self {
+        self.is_navigation = true;
+        self.priority = Priority::High;
+        self
+    }
+
+    /// Set the scheduling priority.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Record what caused the request, for the event stream.
+    pub fn initiated_by(mut self, initiator: Initiator) -> Self {
+        self.initiator = initiator;
+        self
+    }
+
+    /// Attribute the request to a view.
+    pub fn for_view(mut self, view_id: u64) -> Self {
+        self.view_id = Some(view_id);
+        self
+    }
+
+    /// Make the request for a document under the top-level site `site`.
+    pub fn top_level_site(mut self, site: Site) -> Self {
+        self.top_level_site = Some(site);
+        self
+    }
+
+    /// Issue the request from a document in `context`: to another origin
+    /// it carries an `Origin` header and, in `same-origin` credentials
+    /// mode, no credentials.
+    pub fn issued_from(mut self, context: &SecurityContext) -> Self {
+        self.top_level_site = context.top_level_site.clone();
+        apply_request_origin(&mut self, context);
+        self
+    }
+
+    /// Abort the request with `cancel`, e.g. one handle shared by every
+    /// request behind a JS `AbortSignal`.
+    pub fn cancel_with(mut self, cancel: CancelHandle) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Read the body as it arrives, for responses that may never finish
+    /// such as long polls and `multipart/x-mixed-replace` streams. The
+    /// timeout then applies between chunks of the body.
+    pub fn streaming(mut self) -> Self {
+        self.streaming = true;
+        self
+    }
+
+    /// Handle that aborts this request.
+    pub fn cancel_token(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
 }
 
 /// Credentials mode for requests.
@@ -155,6 +378,19 @@ pub enum CredentialsMode {
     Include,
 }
 
+/// Scheduling priority of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    /// Speculative work; waits until no [`Priority::High`] request is in
+    /// flight.
+    VeryLow,
+    Low,
+    #[default]
+    Medium,
+    /// Critical to showing the page, such as the document itself.
+    High,
+}
+
 /// Redirect handling mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum RedirectMode {
@@ -180,6 +416,9 @@ pub enum RedirectType {
     TemporaryRedirect,
     /// 308 Permanent Redirect - preserve method.
     PermanentRedirect,
+    /// Redirect made by an interceptor before the request left; reported
+    /// as 307 and preserves the method.
+    Internal,
 }
 
 impl RedirectType {
@@ -199,7 +438,9 @@ impl RedirectType {
     pub fn preserves_method(self) -> bool {
         matches!(
             self,
-            RedirectType::TemporaryRedirect | RedirectType::PermanentRedirect
+            RedirectType::TemporaryRedirect
+                | RedirectType::PermanentRedirect
+                | RedirectType::Internal
         )
     }
 
@@ -217,7 +458,7 @@ impl RedirectType {
             RedirectType::MovedPermanently => 301,
             RedirectType::Found => 302,
             RedirectType::SeeOther => 303,
-            RedirectType::TemporaryRedirect => 307,
+            RedirectType::TemporaryRedirect | RedirectType::Internal => 307,
             RedirectType::PermanentRedirect => 308,
         }
     }
@@ -310,12 +551,23 @@ pub struct Response {
     pub headers: HeaderMap,
     pub content_type: Option<Mime>,
     pub content_length: Option<u64>,
+    /// Loaded over a certificate that failed validation but had an exception.
+    pub certificate_error_overridden: bool,
+    /// Negotiated TLS parameters, for HTTPS responses.
+    pub tls: Option<TlsInfo>,
+    /// Served from a prefetched copy without a network request.
+    pub from_cache: bool,
+    /// Redirects followed to reach `url`, internal ones included.
+    pub redirect_chain: RedirectChain,
+    /// Hints of the `103 Early Hints` responses that preceded this one.
+    pub early_hints: Vec<ResourceHint>,
     body: ResponseBody,
+    /// Registration with the loader while the body is unread.
+    tracked: Option<cancel::Tracked>,
 }
 
 /// Response body variants.
 #[derive(Debug)]
-#[allow(dead_code)]
 enum ResponseBody {
     /// Full body already loaded.
     Full(Bytes),
@@ -331,25 +583,114 @@ impl Response {
         self.status.is_success()
     }
 
+    /// Where a redirect response points, resolved against its URL. A
+    /// target without a fragment keeps the one `request_url` had, and one
+    /// with userinfo loses it.
+    fn redirect_location(&self, request_url: &Url) -> Option<Url> {
+        RedirectType::from_status(self.status)?;
+        let location = self.headers.get(http::header::LOCATION)?.to_str().ok()?;
+        let mut url = without_userinfo(&self.url.join(location).ok()?);
+        if url.fragment().is_none() {
+            url.set_fragment(request_url.fragment());
+        }
+        Some(url)
+    }
+
     /// Get the body as bytes.
-    pub async fn bytes(self) -> Result<Bytes, NetError> {
-        match self.body {
-            ResponseBody::Full(b) => Ok(b),
+    pub async fn bytes(mut self) -> Result<Bytes, NetError> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            chunks.push(chunk);
+        }
+        Ok(match chunks.len() {
+            1 => chunks.swap_remove(0),
+            _ => chunks.concat().into(),
+        })
+    }
+
+    /// Read the next chunk of the body, or `None` once it is exhausted.
+    ///
+    /// Under a download cap chunks arrive paced, so each one can drive a
+    /// progress update. Fails with [`NetError::Cancelled`] once the
+    /// request is cancelled, unless the body was already read to the end.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, NetError> {
+        let Some(tracked) = &self.tracked else {
+            return self.next_chunk().await;
+        };
+        let cancel = tracked.cancel.clone();
+        let chunk = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(NetError::Cancelled),
+            chunk = self.next_chunk() => chunk,
+        };
+        if chunk.is_err() {
+            // Dropping the stream stops whatever feeds it
+            self.body = ResponseBody::Empty;
+        }
+        if self.tracked.as_mut().is_some_and(|t| t.observe(&chunk)) {
+            self.tracked = None;
+        }
+        chunk
+    }
+
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, NetError> {
+        match std::mem::replace(&mut self.body, ResponseBody::Empty) {
+            ResponseBody::Full(bytes) => Ok(Some(bytes).filter(|b| !b.is_empty())),
             ResponseBody::Stream(mut rx) => {
-                let mut chunks = Vec::new();
-                while let Some(chunk) = rx.recv().await {
-                    chunks.push(chunk?);
+                let chunk = rx.recv().await.transpose();
+                if matches!(chunk, Ok(Some(_))) {
+                    self.body = ResponseBody::Stream(rx);
                 }
-                Ok(chunks.into_iter().flatten().collect())
+                chunk
             }
-            ResponseBody::Empty => Ok(Bytes::new()),
+            ResponseBody::Empty => Ok(None),
         }
     }
 
-    /// Get the body as text.
+    /// Split a `multipart/*` body into its parts as they arrive, such as
+    /// the frames of an MJPEG stream. Dropping the stream aborts the body.
+    pub fn parts(self) -> Result<impl Stream<Item = Result<Part, NetError>>, NetError> {
+        let boundary = self
+            .headers
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .and_then(rustkit_http::multipart::boundary)
+            .ok_or_else(|| NetError::RequestFailed("Not a multipart response".into()))?;
+        let state = (self, MultipartParser::new(&boundary), VecDeque::new());
+        Ok(futures::stream::try_unfold(
+            state,
+            |(mut response, mut parser, mut ready)| async move {
+                loop {
+                    if let Some(part) = ready.pop_front() {
+                        return Ok(Some((part, (response, parser, ready))));
+                    }
+                    if parser.is_closed() {
+                        return Ok(None);
+                    }
+                    match response.chunk().await? {
+                        Some(chunk) => ready.extend(parser.push(&chunk)),
+                        None => return Ok(None),
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Get the body as text, decoded with the `Content-Type` charset
+    /// (UTF-8 by default). Malformed sequences become U+FFFD.
     pub async fn text(self) -> Result<String, NetError> {
+        let charset = self.charset();
         let bytes = self.bytes().await?;
-        String::from_utf8(bytes.to_vec()).map_err(|e| NetError::RequestFailed(e.to_string()))
+        Ok(charset.decode(&bytes))
+    }
+
+    /// The charset named by `Content-Type`, or the default.
+    pub fn charset(&self) -> Charset {
+        self.content_type
+            .as_ref()
+            .and_then(|mime| mime.get_param(mime::CHARSET))
+            .and_then(|charset| Charset::from_label(charset.as_str()))
+            .unwrap_or_default()
     }
 
     /// Get the body as JSON.
@@ -358,6 +699,16 @@ impl Response {
         serde_json::from_slice(&bytes).map_err(|e| NetError::RequestFailed(e.to_string()))
     }
 
+    /// Whether `Content-Disposition` asks for the body to be saved rather
+    /// than displayed.
+    pub fn is_attachment(&self) -> bool {
+        self.headers
+            .get("content-disposition")
+            .and_then(|cd| cd.to_str().ok())
+            .and_then(|cd| cd.split(';').next())
+            .is_some_and(|disposition| disposition.trim().eq_ignore_ascii_case("attachment"))
+    }
+
     /// Get a suggested filename from Content-Disposition or URL.
     pub fn suggested_filename(&self) -> Option<String> {
         // Try Content-Disposition header
@@ -365,11 +716,8 @@ impl Response {
             if let Ok(cd_str) = cd.to_str() {
                 if let Some(start) = cd_str.find("filename=") {
                     let start = start + 9;
-                    let filename = &cd_str[start..];
-                    let filename = filename.trim_matches('"').trim_matches('\'');
-                    if let Some(end) = filename.find(';') {
-                        return Some(filename[..end].to_string());
-                    }
+                    let filename = cd_str[start..].split(';').next().unwrap_or_default();
+                    let filename = filename.trim().trim_matches('"').trim_matches('\'');
                     return Some(filename.to_string());
                 }
             }
@@ -397,6 +745,27 @@ pub struct LoaderConfig {
     pub max_redirects: usize,
     /// Enable cookies.
     pub cookies_enabled: bool,
+    /// TLS settings for new connections.
+    pub tls: TlsConfig,
+    /// Key the HTTP and resolver caches by top-level site as well as URL.
+    /// Turning this off shares them across sites, which lets one site
+    /// time whether another has loaded a resource.
+    pub partition_caches: bool,
+    /// Receive HTTP/2 server pushes. When off, promises are reset.
+    pub accept_server_push: bool,
+    /// Most pushes received at once on one connection.
+    pub max_concurrent_pushes: usize,
+    /// Where requests find their proxy.
+    pub proxy: ProxyMode,
+    /// Longest a PAC script may take to pick the proxies for a request
+    /// before it goes direct.
+    pub pac_timeout: Duration,
+    /// Mark completed downloads as coming from the internet, so Windows
+    /// warns before opening them.
+    pub mark_of_the_web: bool,
+    /// How keep-alive connections are kept: TCP keepalive, idle timeout
+    /// and age limits.
+    pub connection_pool: PoolConfig,
 }
 
 impl Default for LoaderConfig {
@@ -407,42 +776,149 @@ impl Default for LoaderConfig {
             default_timeout: Duration::from_secs(30),
             max_redirects: 10,
             cookies_enabled: true,
+            tls: TlsConfig::default(),
+            partition_caches: true,
+            accept_server_push: true,
+            max_concurrent_pushes: 8,
+            proxy: ProxyMode::Off,
+            pac_timeout: Duration::from_secs(3),
+            mark_of_the_web: true,
+            connection_pool: PoolConfig::default(),
         }
     }
 }
 
+/// A user-approved exception for a host presenting an invalid certificate.
+#[derive(Debug, Clone)]
+pub struct CertificateException {
+    /// Host the exception applies to.
+    pub host: String,
+    /// SHA-256 fingerprint of the exact certificate allowed.
+    pub sha256: [u8; 32],
+    /// When the exception stops applying.
+    pub expiry: SystemTime,
+}
+
 /// Resource loader for fetching URLs.
 pub struct ResourceLoader {
     client: HttpClient,
     config: LoaderConfig,
-    interceptor: Option<Arc<RwLock<RequestInterceptor>>>,
+    interceptor: Arc<RequestInterceptor>,
+    view_interceptors: std::sync::RwLock<HashMap<u64, Arc<RequestInterceptor>>>,
     download_manager: Arc<DownloadManager>,
+    certificate_exceptions: std::sync::RwLock<Vec<CertificateException>>,
+    throttle: throttle::Throttle,
+    protocols: std::sync::RwLock<ProtocolPreferences>,
+    stats: Arc<std::sync::Mutex<NetStats>>,
+    blob_urls: BlobUrlStore,
+    in_flight: Arc<cancel::InFlight>,
+    prefetched: Arc<hints::PrefetchCache>,
+    pushes: Arc<push::PushReceiver>,
+    cookies: Arc<CookieJar>,
+    /// Views whose cross-site requests and frames get no cookies.
+    third_party_blocking: std::sync::RwLock<HashSet<u64>>,
 }
 
 impl ResourceLoader {
     /// Create a new resource loader.
     pub fn new(config: LoaderConfig) -> Result<Self, NetError> {
+        let stats = Arc::new(std::sync::Mutex::new(NetStats::default()));
+        let in_flight = Arc::new(cancel::InFlight::default());
+        let prefetched = Arc::new(hints::PrefetchCache::new(config.partition_caches));
+        let pushes = Arc::new(push::PushReceiver::new(
+            config.accept_server_push,
+            Arc::clone(&prefetched),
+            Arc::clone(&stats),
+            Arc::clone(&in_flight),
+        ));
         let client = HttpClient::builder()
             .user_agent(&config.user_agent)
             .timeout(config.default_timeout)
-            .redirect(true, config.max_redirects)
+            // Redirects are followed here, so interceptors see each one
+            .redirect(false, config.max_redirects)
             .cookie_store(config.cookies_enabled)
+            .tls_config(config.tls.clone())
+            .push_handler(pushes.clone(), config.max_concurrent_pushes)
+            .pool_config(config.connection_pool.clone())
             .build()
             .map_err(|e| NetError::RequestFailed(e.to_string()))?;
+        client.set_proxy_resolver(proxy::resolver(&config.proxy, config.pac_timeout));
+
+        let download_manager = DownloadManager::new();
+        download_manager.set_mark_of_the_web(config.mark_of_the_web);
 
         info!("ResourceLoader initialized");
 
         Ok(Self {
             client,
             config,
-            interceptor: None,
-            download_manager: Arc::new(DownloadManager::new()),
+            interceptor: Arc::new(RequestInterceptor::new()),
+            view_interceptors: std::sync::RwLock::new(HashMap::new()),
+            download_manager: Arc::new(download_manager),
+            certificate_exceptions: std::sync::RwLock::new(Vec::new()),
+            throttle: throttle::Throttle::new(),
+            protocols: std::sync::RwLock::new(ProtocolPreferences::default()),
+            stats,
+            blob_urls: BlobUrlStore::new(),
+            in_flight,
+            prefetched,
+            pushes,
+            cookies: Arc::new(CookieJar::new()),
+            third_party_blocking: std::sync::RwLock::new(HashSet::new()),
         })
     }
 
     /// Set the request interceptor.
     pub fn set_interceptor(&mut self, interceptor: RequestInterceptor) {
-        self.interceptor = Some(Arc::new(RwLock::new(interceptor)));
+        self.interceptor = Arc::new(interceptor);
+    }
+
+    /// Get the global request interceptor, e.g. to add handlers at runtime.
+    pub fn interceptor(&self) -> Arc<RequestInterceptor> {
+        Arc::clone(&self.interceptor)
+    }
+
+    /// Set or clear the interceptor for requests attributed to a view.
+    ///
+    /// It is consulted before the global interceptor; requests it does not
+    /// decide fall through to the global chain.
+    pub fn set_view_interceptor(&self, view_id: u64, interceptor: Option<RequestInterceptor>) {
+        let mut views = self.view_interceptors.write().unwrap();
+        match interceptor {
+            Some(interceptor) => {
+                views.insert(view_id, Arc::new(interceptor));
+            }
+            None => {
+                views.remove(&view_id);
+            }
+        }
+    }
+
+    /// Get the interceptor for a view, if one is set.
+    pub fn view_interceptor(&self, view_id: u64) -> Option<Arc<RequestInterceptor>> {
+        self.view_interceptors
+            .read()
+            .unwrap()
+            .get(&view_id)
+            .cloned()
+    }
+
+    /// Whether requests attributed to `view_id` may be blocked, redirected
+    /// or modified by an interceptor.
+    pub fn intercepts(&self, view_id: Option<u64>) -> bool {
+        let view = view_id.and_then(|id| self.view_interceptor(id));
+        !self.interceptor.is_passthrough() || view.is_some_and(|view| !view.is_passthrough())
+    }
+
+    /// Decide what to do with a request: the view's interceptor first, then
+    /// the global one.
+    pub async fn intercept(&self, request: &Request) -> InterceptAction {
+        if let Some(view) = request.view_id.and_then(|id| self.view_interceptor(id)) {
+            if let Some(action) = view.decide(request) {
+                return action;
+            }
+        }
+        self.interceptor.intercept(request).await
     }
 
     /// Create a new resource loader with an interceptor.
@@ -457,205 +933,3101 @@ impl ResourceLoader {
         Arc::clone(&self.download_manager)
     }
 
-    /// Get a reference to the HTTP client.
-    pub fn client(&self) -> &HttpClient {
-        &self.client
+    /// The registry behind `blob:` URLs.
+    pub fn blob_urls(&self) -> &BlobUrlStore {
+        &self.blob_urls
     }
 
-    /// Fetch a URL.
-    pub async fn fetch(&self, request: Request) -> Result<Response, NetError> {
-        debug!(url = %request.url, method = %request.method, "Fetching resource");
+    /// The session's cookie jar, which requests and `document.cookie`
+    /// share.
+    pub fn cookie_jar(&self) -> Arc<CookieJar> {
+        Arc::clone(&self.cookies)
+    }
 
-        // Apply interception
-        if let Some(interceptor) = &self.interceptor {
-            let action = interceptor.read().await.intercept(&request).await;
-            match action {
-                InterceptAction::Allow => {}
-                InterceptAction::Block => {
-                    warn!(url = %request.url, "Request blocked by interceptor");
-                    return Err(NetError::Blocked);
-                }
-                InterceptAction::Redirect(new_url) => {
-                    debug!(url = %request.url, new_url = %new_url, "Request redirected");
-                    let mut new_request = request.clone();
-                    new_request.url = new_url;
-                    return Box::pin(self.fetch(new_request)).await;
-                }
-                InterceptAction::Modify(modified) => {
-                    return Box::pin(self.fetch(*modified)).await;
-                }
-            }
+    /// Block or allow cookies in a view's third-party contexts: requests
+    /// and documents not same-site with its top-level document.
+    pub fn set_third_party_cookies_blocked(&self, view_id: u64, blocked: bool) {
+        let mut views = self.third_party_blocking.write().unwrap();
+        if blocked {
+            views.insert(view_id);
+        } else {
+            views.remove(&view_id);
         }
+    }
 
-        // Build headers for rustkit-http request
-        let mut headers = request.headers.clone();
+    /// Whether a view blocks third-party cookies.
+    pub fn third_party_cookies_blocked(&self, view_id: u64) -> bool {
+        self.third_party_blocking.read().unwrap().contains(&view_id)
+    }
 
-        // Add Accept-Language
-        if let Ok(val) = HeaderValue::try_from(&self.config.accept_language) {
-            headers.insert(HeaderName::from_static("accept-language"), val);
+    /// `document.cookie` for a document at `url` in `view_id`, whose
+    /// top-level document is at `top_level_site`. Empty where cookies are
+    /// disabled or blocked.
+    pub fn document_cookie(
+        &self,
+        view_id: u64,
+        url: &Url,
+        top_level_site: Option<&Site>,
+    ) -> String {
+        match self.cookie_access(Some(view_id), url, top_level_site) {
+            Some(is_same_site) => self.cookies.document_cookie(url, is_same_site),
+            None => String::new(),
         }
+    }
 
-        // Add referrer
-        if let Some(ref referrer) = request.referrer {
-            if let Ok(val) = HeaderValue::try_from(referrer.as_str()) {
-                headers.insert(HeaderName::from_static("referer"), val);
-            }
+    /// Assign `document.cookie` for a document at `url` in `view_id`.
+    /// Returns whether the cookie was stored; invalid and disallowed
+    /// cookies are ignored, as script assigning them cannot tell.
+    pub fn set_document_cookie(
+        &self,
+        view_id: u64,
+        url: &Url,
+        top_level_site: Option<&Site>,
+        cookie: &str,
+    ) -> bool {
+        let Some(is_same_site) = self.cookie_access(Some(view_id), url, top_level_site) else {
+            return false;
+        };
+        let stored = self
+            .cookies
+            .set_cookie(url, cookie, CookieSource::Script, is_same_site);
+        if !stored {
+            debug!(%url, "Ignoring cookie set by script");
         }
+        stored
+    }
 
-        // Execute request using rustkit-http
-        let http_response = self
-            .client
-            .request(
-                request.method.clone(),
-                request.url.as_str(),
-                headers,
-                request.body.clone(),
-            )
-            .await?;
-
-        let url = http_response.url.clone();
-
-        // Parse content type
-        let content_type = http_response
-            .content_type()
-            .and_then(|s| s.parse::<Mime>().ok());
+    /// Whether a context at `url` under `top_level_site` is same-site with
+    /// it, or `None` if it may not use cookies at all.
+    fn cookie_access(
+        &self,
+        view_id: Option<u64>,
+        url: &Url,
+        top_level_site: Option<&Site>,
+    ) -> Option<bool> {
+        if !self.config.cookies_enabled {
+            return None;
+        }
+        let is_same_site =
+            top_level_site.is_none_or(|top| Site::from_url(url).as_ref() == Some(top));
+        let blocked = view_id.is_some_and(|id| self.third_party_cookies_blocked(id));
+        (is_same_site || !blocked).then_some(is_same_site)
+    }
 
-        // Get content length
-        let content_length = http_response.content_length();
+    /// Whether `request` carries cookies, and if so whether it is
+    /// same-site. A navigation is same-site with the page it leaves, and
+    /// never third-party.
+    fn request_cookie_access(&self, request: &Request) -> Option<bool> {
+        if !self.config.cookies_enabled || request.credentials == CredentialsMode::Omit {
+            return None;
+        }
+        if request.is_navigation {
+            let from = request.referrer.as_ref().and_then(Site::from_url);
+            return Some(from.is_none_or(|from| Site::from_url(&request.url) == Some(from)));
+        }
+        self.cookie_access(
+            request.view_id,
+            &request.url,
+            request.top_level_site.as_ref(),
+        )
+    }
 
-        trace!(
-            url = %url,
-            status = %http_response.status,
-            content_type = ?content_type,
-            content_length = ?content_length,
-            body_len = http_response.body.len(),
-            "Response received"
-        );
+    /// Store the cookies a response to `request` sets.
+    fn store_cookies(&self, request: &Request, headers: &HeaderMap) {
+        let Some(is_same_site) = self.request_cookie_access(request) else {
+            return;
+        };
+        // A navigation's response becomes the top-level document
+        let is_same_site = is_same_site || request.is_navigation;
+        for value in headers.get_all(http::header::SET_COOKIE) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            if !self
+                .cookies
+                .set_cookie(&request.url, value, CookieSource::Http, is_same_site)
+            {
+                debug!(url = %request.url, "Ignoring cookie set by response");
+            }
+        }
+    }
 
+    /// Answer a `blob:` request from the registry.
+    fn fetch_blob(&self, request: &Request) -> Result<Response, NetError> {
+        let data = self
+            .blob_urls
+            .resolve(&request.url)
+            .filter(|_| request.method == Method::GET)
+            .ok_or_else(|| NetError::RequestFailed(format!("No blob at {}", request.url)))?;
+
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::try_from(data.content_type.as_str()) {
+            headers.insert(http::header::CONTENT_TYPE, value);
+        }
+        headers.insert(http::header::CONTENT_LENGTH, data.bytes.len().into());
         Ok(Response {
             request_id: request.id,
-            url,
-            status: http_response.status,
-            headers: http_response.headers,
-            content_type,
-            content_length,
-            body: ResponseBody::Full(http_response.body),
+            url: request.url.clone(),
+            status: StatusCode::OK,
+            headers,
+            content_type: data.content_type.parse().ok(),
+            content_length: Some(data.bytes.len() as u64),
+            certificate_error_overridden: false,
+            tls: None,
+            from_cache: false,
+            redirect_chain: RedirectChain::default(),
+            early_hints: Vec::new(),
+            body: ResponseBody::Full(data.bytes),
+            tracked: None,
         })
     }
 
-    /// Start a download.
-    pub async fn start_download(
+    /// Get a reference to the HTTP client.
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Allow top-level navigations to `host` while it presents exactly the
+    /// certificate with this SHA-256 fingerprint, until `expiry`.
+    ///
+    /// Subresource requests still fail, as does any other certificate for the host.
+    pub fn add_certificate_exception(
         &self,
-        url: Url,
-        destination: PathBuf,
-    ) -> Result<DownloadId, NetError> {
-        let request = Request::get(url);
-        self.download_manager
-            .start(request, destination, &self.client)
-            .await
+        host: &str,
+        cert_fingerprint_sha256: [u8; 32],
+        expiry: SystemTime,
+    ) {
+        info!(host, "Adding certificate exception");
+        let host = host.to_ascii_lowercase();
+        let mut exceptions = self.certificate_exceptions.write().unwrap();
+        exceptions.retain(|e| !(e.host == host && e.sha256 == cert_fingerprint_sha256));
+        exceptions.push(CertificateException {
+            host,
+            sha256: cert_fingerprint_sha256,
+            expiry,
+        });
     }
-}
 
-/// Fetch API for JavaScript compatibility.
-pub struct FetchApi {
-    loader: Arc<ResourceLoader>,
-}
+    /// Remove all certificate exceptions for a host.
+    pub fn clear_certificate_exceptions(&self, host: &str) {
+        self.certificate_exceptions
+            .write()
+            .unwrap()
+            .retain(|e| !e.host.eq_ignore_ascii_case(host));
+    }
 
-impl FetchApi {
-    /// Create a new fetch API.
-    pub fn new(loader: Arc<ResourceLoader>) -> Self {
-        Self { loader }
+    /// Trust additional DER-encoded root certificates (e.g. enterprise roots).
+    ///
+    /// Replaces any previously configured extra roots.
+    pub fn set_extra_root_certificates(&self, roots: Vec<Vec<u8>>) -> Result<(), NetError> {
+        info!(count = roots.len(), "Setting extra root certificates");
+        self.client.set_root_certificates(roots)?;
+        Ok(())
     }
 
-    /// Fetch with options similar to JavaScript fetch().
-    pub async fn fetch(&self, url: &str, options: FetchOptions) -> Result<Response, NetError> {
-        let url = Url::parse(url).map_err(|e| NetError::InvalidUrl(e.to_string()))?;
+    /// Change TLS settings. Applies to new connections; cached sessions are
+    /// dropped.
+    pub fn set_tls_config(&self, config: TlsConfig) -> Result<(), NetError> {
+        info!(?config, "Setting TLS config");
+        self.client.set_tls_config(config)?;
+        Ok(())
+    }
 
-        let mut request = match options.method.as_deref() {
-            Some("POST") => Request::post(url, options.body.unwrap_or_default()),
-            Some("PUT") => {
-                let mut req = Request::get(url);
-                req.method = Method::PUT;
-                req.body = options.body;
-                req
-            }
-            Some("DELETE") => {
-                let mut req = Request::get(url);
-                req.method = Method::DELETE;
-                req
-            }
-            _ => Request::get(url),
-        };
+    /// Current TLS settings.
+    pub fn tls_config(&self) -> TlsConfig {
+        self.client.tls_config()
+    }
 
-        // Add headers
-        for (name, value) in options.headers {
-            if let (Ok(n), Ok(v)) = (
-                HeaderName::try_from(name.as_str()),
-                HeaderValue::try_from(value.as_str()),
-            ) {
-                request.headers.insert(n, v);
-            }
-        }
+    /// Simulate network conditions for all subsequent requests.
+    ///
+    /// Takes effect immediately for paced transfers already in flight;
+    /// going offline aborts them with [`NetError::Offline`].
+    pub fn set_network_conditions(&self, conditions: NetworkConditions) {
+        self.throttle.set(conditions);
+    }
 
-        // Set credentials
-        request.credentials = match options.credentials.as_deref() {
-            Some("omit") => CredentialsMode::Omit,
-            Some("include") => CredentialsMode::Include,
-            _ => CredentialsMode::SameOrigin,
+    /// The current simulated network conditions.
+    pub fn network_conditions(&self) -> NetworkConditions {
+        self.throttle.conditions()
+    }
+
+    /// Watch for changes to the simulated network conditions.
+    pub fn subscribe_network_conditions(&self) -> tokio::sync::watch::Receiver<NetworkConditions> {
+        self.throttle.subscribe()
+    }
+
+    /// Pin the origin of `url` to HTTP/1.1, or return it to the configured
+    /// ALPN protocols with [`ProtocolOverride::Default`].
+    ///
+    /// Takes effect for the next request: an idle connection to the origin
+    /// is not reused. Other origins are unaffected.
+    pub fn set_origin_protocol_override(&self, url: &Url, protocol_override: ProtocolOverride) {
+        let Some(origin) = origin_key(url) else {
+            return;
         };
+        info!(origin, ?protocol_override, "Setting protocol override");
+        self.protocols
+            .write()
+            .unwrap()
+            .set_override(&origin, protocol_override);
+        self.apply_protocol_override(url, protocol_override);
+    }
 
-        self.loader.fetch(request).await
+    /// The protocol policy for the origin of `url`.
+    pub fn origin_protocol_override(&self, url: &Url) -> ProtocolOverride {
+        origin_key(url)
+            .map(|origin| self.protocols.read().unwrap().override_for(&origin))
+            .unwrap_or_default()
     }
-}
 
-/// Options for fetch API.
-#[derive(Debug, Default)]
-pub struct FetchOptions {
-    pub method: Option<String>,
-    pub headers: HashMap<String, String>,
-    pub body: Option<Bytes>,
-    pub credentials: Option<String>,
-    pub mode: Option<String>,
-    pub cache: Option<String>,
-    pub redirect: Option<String>,
-}
+    /// Unexpired alternative services advertised by the origin of `url`.
+    pub fn alt_services(&self, url: &Url) -> Vec<AltService> {
+        origin_key(url)
+            .map(|origin| {
+                self.protocols
+                    .read()
+                    .unwrap()
+                    .alt_services(&origin, SystemTime::now())
+            })
+            .unwrap_or_default()
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Snapshot of the protocol preferences, without expired entries, for
+    /// persisting across sessions.
+    pub fn protocol_preferences(&self) -> ProtocolPreferences {
+        let mut preferences = self.protocols.read().unwrap().clone();
+        preferences.prune(SystemTime::now());
+        preferences
+    }
 
-    #[test]
-    fn test_request_builder() {
-        let url = Url::parse("https://example.com").unwrap();
-        let request = Request::get(url.clone())
-            .header(
-                HeaderName::from_static("accept"),
-                HeaderValue::from_static("application/json"),
-            )
-            .timeout(Duration::from_secs(10));
+    /// Replace the protocol preferences, e.g. with ones saved by an earlier
+    /// session.
+    pub fn restore_protocol_preferences(&self, preferences: ProtocolPreferences) {
+        let previous = std::mem::replace(&mut *self.protocols.write().unwrap(), preferences);
+        for origin in previous.forced_http1_origins() {
+            if let Ok(url) = Url::parse(origin) {
+                self.apply_protocol_override(&url, ProtocolOverride::Default);
+            }
+        }
+        let forced: Vec<Url> = self
+            .protocols
+            .read()
+            .unwrap()
+            .forced_http1_origins()
+            .filter_map(|origin| Url::parse(origin).ok())
+            .collect();
+        for url in forced {
+            self.apply_protocol_override(&url, ProtocolOverride::ForceHttp1);
+        }
+    }
 
-        assert_eq!(request.url, url);
-        assert_eq!(request.method, Method::GET);
-        assert!(request.headers.contains_key("accept"));
-        assert_eq!(request.timeout, Some(Duration::from_secs(10)));
+    /// Forget all alternative services and protocol overrides.
+    pub fn clear_protocol_preferences(&self) {
+        self.restore_protocol_preferences(ProtocolPreferences::default());
     }
 
-    #[test]
-    fn test_request_id_uniqueness() {
-        let id1 = RequestId::new();
-        let id2 = RequestId::new();
-        assert_ne!(id1, id2);
+    /// Connection reuse counters collected so far.
+    pub fn net_stats(&self) -> NetStats {
+        let mut stats = self.stats.lock().unwrap();
+        stats.pushes.wasted += self.prefetched.take_wasted_pushes();
+        NetStats {
+            pool: self.client.pool_stats(),
+            ..stats.clone()
+        }
     }
 
-    #[test]
-    fn test_credentials_mode_default() {
-        assert_eq!(CredentialsMode::default(), CredentialsMode::SameOrigin);
+    /// Reset the connection reuse counters.
+    pub fn reset_net_stats(&self) {
+        self.prefetched.take_wasted_pushes();
+        self.client.reset_pool_stats();
+        *self.stats.lock().unwrap() = NetStats::default();
     }
 
-    #[test]
+    /// Close idle keep-alive connections, e.g. under memory pressure.
+    /// Returns how many were closed.
+    pub fn close_idle_connections(&self) -> usize {
+        self.client.close_idle_connections()
+    }
+
+    /// Bytes of responses held for later requests.
+    pub fn cache_usage(&self) -> usize {
+        self.prefetched.usage()
+    }
+
+    /// Drop held responses until at most `target_bytes` remain. Returns the
+    /// bytes freed.
+    pub fn trim(&self, target_bytes: usize) -> usize {
+        self.prefetched.trim(target_bytes)
+    }
+
+    fn apply_protocol_override(&self, url: &Url, protocol_override: ProtocolOverride) {
+        if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+            self.client.set_http1_only(
+                host,
+                port,
+                protocol_override == ProtocolOverride::ForceHttp1,
+            );
+        }
+    }
+
+    /// Update connection counters and Alt-Svc records from a response.
+    fn record_response(&self, response: &rustkit_http::Response) {
+        let Some(origin) = origin_key(&response.url) else {
+            return;
+        };
+        let reused = response
+            .tls
+            .as_ref()
+            .is_some_and(|tls| tls.connection_reused);
+        let protocol = response
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.alpn_protocol.as_deref())
+            .unwrap_or("http/1.1");
+        self.stats.lock().unwrap().record(&origin, protocol, reused);
+
+        let mut protocols = self.protocols.write().unwrap();
+        protocols.record_success(&origin);
+        if let Some(alt_svc) = response
+            .header("alt-svc")
+            .and_then(|value| parse_alt_svc(value, SystemTime::now()))
+        {
+            trace!(origin, ?alt_svc, "Recorded Alt-Svc");
+            protocols.record_alt_svc(&origin, alt_svc);
+        }
+    }
+
+    /// Pin an `h2` origin to HTTP/1.1 after repeated protocol errors.
+    fn record_protocol_error(&self, url: &Url, error: &rustkit_http::HttpError) {
+        use rustkit_http::HttpError;
+
+        if !matches!(error, HttpError::InvalidResponse(_) | HttpError::IoError(_)) {
+            return;
+        }
+        let Some(origin) = origin_key(url) else {
+            return;
+        };
+        let on_h2 = self
+            .stats
+            .lock()
+            .unwrap()
+            .origins
+            .get(&origin)
+            .is_some_and(|s| s.protocol.as_deref() == Some("h2"));
+        if on_h2 && self.protocols.write().unwrap().record_h2_failure(&origin) {
+            warn!(
+                origin,
+                "Repeated h2 protocol errors; falling back to HTTP/1.1"
+            );
+            self.set_origin_protocol_override(url, ProtocolOverride::ForceHttp1);
+        }
+    }
+
+    /// Unexpired certificate exceptions applicable to a request.
+    fn pinned_certificates(&self, request: &Request) -> Vec<PinnedCertificate> {
+        let Some(host) = request.url.host_str().filter(|_| request.is_navigation) else {
+            return Vec::new();
+        };
+
+        let now = SystemTime::now();
+        self.certificate_exceptions
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.host.eq_ignore_ascii_case(host) && e.expiry > now)
+            .map(|e| PinnedCertificate {
+                host: e.host.clone(),
+                sha256: e.sha256,
+            })
+            .collect()
+    }
+
+    /// Send request lifecycle events to `tx`, or stop sending them.
+    pub fn set_event_sender(&self, tx: Option<mpsc::UnboundedSender<NetEvent>>) {
+        self.in_flight.set_event_sender(tx);
+    }
+
+    /// Send a [`RequestRecord`] for every request that completes to `sink`,
+    /// or stop with `None`. The sink runs on a worker thread of its own.
+    pub fn set_telemetry_sink(&self, sink: Option<Arc<dyn TelemetrySink>>) {
+        self.in_flight.telemetry.set_sink(sink);
+    }
+
+    /// Records dropped because the telemetry sink fell behind.
+    pub fn telemetry_dropped(&self) -> u64 {
+        self.in_flight.telemetry.dropped()
+    }
+
+    /// Cancel every request in flight for `view_id`, including responses
+    /// whose bodies are still being read. Returns how many were cancelled.
+    pub fn cancel_all_for_view(&self, view_id: u64) -> usize {
+        self.in_flight.cancel_view(view_id)
+    }
+
+    /// Number of requests whose response has not been fully read.
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Fetch `request` unless it is unchanged since the response
+    /// `validators` came from, which go out as `If-None-Match` and
+    /// `If-Modified-Since`. Without validators this is a plain fetch.
+    pub async fn fetch_if_modified(
+        &self,
+        mut request: Request,
+        validators: Option<Validators>,
+    ) -> Result<ConditionalResponse, NetError> {
+        if let Some(validators) = &validators {
+            validators.apply(&mut request);
+        }
+        let response = self.fetch(request).await?;
+        if response.status != StatusCode::NOT_MODIFIED {
+            return Ok(ConditionalResponse::Modified(Box::new(response)));
+        }
+        trace!(url = %response.url, "Not modified");
+        Ok(ConditionalResponse::NotModified {
+            validators: validators.unwrap_or_default().refreshed(&response.headers),
+        })
+    }
+
+    /// Poll `url` every `interval` on the current runtime, sending its body
+    /// to `tx` the first time and whenever it changes. See
+    /// [`Self::poll_with`].
+    pub fn poll(
+        self: &Arc<Self>,
+        url: Url,
+        interval: Duration,
+        tx: mpsc::Sender<PollUpdate>,
+    ) -> tokio::task::JoinHandle<()> {
+        self.poll_with(url, PollOptions::new(interval), tx)
+    }
+
+    /// Poll `url` with conditional requests until `tx`'s receiver is
+    /// dropped.
+    ///
+    /// A `429`, or a `503` with `Retry-After`, stretches the wait to what
+    /// the server asks for, doubling it each time when it doesn't say.
+    /// Failed requests are retried at the next interval.
+    pub fn poll_with(
+        self: &Arc<Self>,
+        url: Url,
+        options: PollOptions,
+        tx: mpsc::Sender<PollUpdate>,
+    ) -> tokio::task::JoinHandle<()> {
+        let loader = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut validators = None;
+            let mut last_hash = None;
+            let mut throttled: Option<Duration> = None;
+            loop {
+                let fetched = tokio::select! {
+                    _ = tx.closed() => return,
+                    fetched = loader.fetch_if_modified(Request::get(url.clone()), validators.clone()) => fetched,
+                };
+                let mut delay = options.interval;
+                match fetched {
+                    Ok(ConditionalResponse::NotModified {
+                        validators: refreshed,
+                    }) => {
+                        validators = Some(refreshed);
+                        throttled = None;
+                    }
+                    Ok(ConditionalResponse::Modified(response)) => {
+                        let previous = throttled.unwrap_or(options.interval);
+                        throttled = conditional::backoff(
+                            response.status,
+                            &response.headers,
+                            previous,
+                            SystemTime::now(),
+                        );
+                        if let Some(wait) = throttled {
+                            debug!(%url, status = %response.status, ?wait, "Poll throttled");
+                            delay = wait;
+                        } else if !response.ok() {
+                            debug!(%url, status = %response.status, "Poll got an error status");
+                        } else {
+                            let fresh = response.validators();
+                            let content_type = response.content_type.as_ref().map(Mime::to_string);
+                            match response.bytes().await {
+                                Ok(body) => {
+                                    validators = Some(fresh.clone());
+                                    let hash = conditional::content_hash(&body);
+                                    let unchanged =
+                                        options.compare_content && last_hash == Some(hash);
+                                    last_hash = Some(hash);
+                                    if unchanged {
+                                        trace!(%url, "Validators changed but content did not");
+                                    } else {
+                                        let update = PollUpdate {
+                                            url: url.clone(),
+                                            body,
+                                            content_type,
+                                            validators: fresh,
+                                        };
+                                        if tx.send(update).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Err(e) => debug!(%url, error = %e, "Poll body failed"),
+                            }
+                        }
+                    }
+                    Err(e) => debug!(%url, error = %e, "Poll failed"),
+                }
+                tokio::select! {
+                    _ = tx.closed() => return,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+            }
+        })
+    }
+
+    /// Fetch a URL, returning a handle that aborts it alongside the fetch.
+    pub fn fetch_with_handle(
+        &self,
+        request: Request,
+    ) -> (
+        CancelHandle,
+        impl std::future::Future<Output = Result<Response, NetError>> + '_,
+    ) {
+        (request.cancel_token(), self.fetch(request))
+    }
+
+    /// Fetch a URL.
+    ///
+    /// The request stays registered, and cancellable through its
+    /// [`CancelHandle`], until its response body is read or dropped.
+    /// [`Priority::VeryLow`] requests are held back while a
+    /// [`Priority::High`] one is in flight.
+    pub async fn fetch(&self, request: Request) -> Result<Response, NetError> {
+        let request = outgoing(request);
+        debug!(url = %request.url, method = %request.method, "Fetching resource");
+        if request.url.scheme() == "blob" {
+            return self.fetch_blob(&request);
+        }
+        if request.cancel.is_cancelled() {
+            return Err(NetError::Cancelled);
+        }
+
+        let cancel = request.cancel_token();
+        if request.priority == Priority::VeryLow {
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => return Err(NetError::Cancelled),
+                _ = self.in_flight.critical_idle() => {}
+            }
+        }
+        let tracked = self.in_flight.track(&request);
+        let result = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(NetError::Cancelled),
+            result = self.send(request) => result,
+        };
+        match result {
+            Ok(mut response) => {
+                let mut tracked = tracked;
+                tracked.total = response.content_length;
+                tracked.responded(&response);
+                response.tracked = Some(tracked);
+                Ok(response)
+            }
+            Err(error) => {
+                tracked.fail(&error);
+                Err(error)
+            }
+        }
+    }
+
+    /// Send a request and wait for its response headers, following
+    /// redirects.
+    async fn send(&self, request: Request) -> Result<Response, NetError> {
+        let mut chain = RedirectChain::with_max(self.config.max_redirects);
+        let mut response = self.send_following(request, &mut chain).await?;
+        response.redirect_chain = chain;
+        Ok(response)
+    }
+
+    async fn send_following(
+        &self,
+        request: Request,
+        chain: &mut RedirectChain,
+    ) -> Result<Response, NetError> {
+        self.throttle.check()?;
+
+        // Apply interception
+        let (location, status) = match self.intercept(&request).await {
+            InterceptAction::Allow => {
+                let response = self.send_once(request.clone()).await?;
+                let Some(location) = response.redirect_location(&request.url) else {
+                    return Ok(response);
+                };
+                match self.on_redirect(&request, &location, response.status) {
+                    RedirectAction::Follow => (location, Some(response.status)),
+                    RedirectAction::FollowModified(url) => (url, Some(response.status)),
+                    RedirectAction::Block => {
+                        debug!(url = %request.url, %location, "Redirect not followed");
+                        return Ok(response);
+                    }
+                }
+            }
+            InterceptAction::Block => {
+                warn!(url = %request.url, "Request blocked by interceptor");
+                return Err(NetError::Blocked);
+            }
+            InterceptAction::Redirect(new_url) => (new_url, None),
+            InterceptAction::Modify(modified) => {
+                return Box::pin(self.send_following(outgoing(*modified), chain)).await;
+            }
+        };
+
+        let next = self.redirect_request(&request, chain, location, status)?;
+        Box::pin(self.send_following(next, chain)).await
+    }
+
+    /// Ask the view's interceptor, then the global one, about a server
+    /// redirect.
+    fn on_redirect(&self, request: &Request, location: &Url, status: StatusCode) -> RedirectAction {
+        if let Some(view) = request.view_id.and_then(|id| self.view_interceptor(id)) {
+            if let Some(action) = view.decide_redirect(request, location, status) {
+                return action;
+            }
+        }
+        self.interceptor
+            .decide_redirect(request, location, status)
+            .unwrap_or(RedirectAction::Follow)
+    }
+
+    /// The request that follows `request` to `location`. `status` is the
+    /// server's redirect status, or `None` for an internal redirect.
+    ///
+    /// The redirect counts against the redirect limit, and a target the
+    /// requesting page could not load directly is blocked.
+    fn redirect_request(
+        &self,
+        request: &Request,
+        chain: &mut RedirectChain,
+        location: Url,
+        status: Option<StatusCode>,
+    ) -> Result<Request, NetError> {
+        let mut location = without_userinfo(&location);
+        normalize_path(&mut location);
+        let redirect_type = status
+            .and_then(RedirectType::from_status)
+            .unwrap_or(RedirectType::Internal);
+        let method_changed = match redirect_type {
+            RedirectType::SeeOther => {
+                request.method != Method::GET && request.method != Method::HEAD
+            }
+            RedirectType::MovedPermanently | RedirectType::Found => request.method == Method::POST,
+            _ => false,
+        };
+        debug!(
+            url = %request.url,
+            %location,
+            status = redirect_type.status_code(),
+            "Request redirected"
+        );
+        chain.add(RedirectInfo {
+            from_url: request.url.clone(),
+            to_url: location.clone(),
+            redirect_type,
+            method_changed,
+        })?;
+
+        if !matches!(location.scheme(), "http" | "https") {
+            warn!(%location, "Redirect to a non-HTTP URL blocked");
+            return Err(NetError::Blocked);
+        }
+        if let Some(page) = request.referrer.as_ref().filter(|_| !request.is_navigation) {
+            if check_mixed_content(page, &location, MixedContentType::Other)
+                == MixedContentResult::Blockable
+            {
+                warn!(page = %page, %location, "Mixed content redirect blocked");
+                return Err(NetError::Blocked);
+            }
+        }
+
+        self.in_flight.emit(NetEvent::Redirected {
+            request_id: request.id,
+            from: request.url.clone(),
+            to: location.clone(),
+            status: status.map(|status| status.as_u16()),
+        });
+
+        let mut next = request.clone();
+        if !Origin::from_url(&request.url).same_origin(&Origin::from_url(&location)) {
+            next.headers.remove(http::header::AUTHORIZATION);
+            next.headers.remove(http::header::PROXY_AUTHORIZATION);
+        }
+        if method_changed {
+            next.method = Method::GET;
+            next.body = None;
+            next.headers.remove(http::header::CONTENT_TYPE);
+            next.headers.remove(http::header::CONTENT_LENGTH);
+        }
+        next.url = location;
+        Ok(next)
+    }
+
+    /// Top-level site whose partition of the caches `request` uses, or
+    /// `None` if caches are shared.
+    fn cache_site(&self, request: &Request) -> Option<Site> {
+        if !self.config.partition_caches {
+            return None;
+        }
+        if request.is_navigation {
+            return Site::from_url(&request.url);
+        }
+        request.top_level_site.clone()
+    }
+
+    /// The cache partition of documents whose top-level document is at
+    /// `url`, for caches kept outside the loader. `None` if caches are
+    /// shared.
+    pub fn cache_partition(&self, url: &Url) -> Option<Site> {
+        Site::from_url(url).filter(|_| self.config.partition_caches)
+    }
+
+    /// Send a request, without following redirects.
+    async fn send_once(&self, request: Request) -> Result<Response, NetError> {
+        let site = self.cache_site(&request);
+        if request.method == Method::GET {
+            if let Some(mut arriving) = self.pushes.arriving(site.as_ref(), &request.url) {
+                debug!(url = %request.url, "Waiting for pushed response");
+                let _ = tokio::time::timeout(
+                    self.config.default_timeout,
+                    arriving.wait_for(|done| *done),
+                )
+                .await;
+            }
+            if let Some(prefetched) = self.prefetched.take(site.as_ref(), &request.url) {
+                debug!(url = %request.url, pushed = prefetched.pushed, "Using prefetched response");
+                if prefetched.speculative {
+                    self.stats.lock().unwrap().speculative.cache_hits += 1;
+                }
+                if prefetched.pushed {
+                    self.stats.lock().unwrap().pushes.used += 1;
+                    self.in_flight.emit(NetEvent::ServedFromPush {
+                        request_id: request.id,
+                        url: request.url.clone(),
+                        view_id: request.view_id,
+                    });
+                }
+                return Ok(Self::prefetched_response(&request, prefetched));
+            }
+        }
+
+        // Build headers for rustkit-http request
+        let mut headers = request.headers.clone();
+        for name in headers::sanitize(&mut headers, request.provenance) {
+            warn!(url = %request.url, %name, "Dropping request header");
+        }
+
+        // Cookies from the jar, unless the embedder set its own
+        if !headers.contains_key(http::header::COOKIE) {
+            // Lax cookies follow cross-site links, but not form posts
+            let top_level = request.is_navigation && request.method == Method::GET;
+            let cookie = self
+                .request_cookie_access(&request)
+                .and_then(|is_same_site| {
+                    self.cookies
+                        .cookie_header(&request.url, is_same_site, top_level)
+                });
+            if let Some(value) = cookie.and_then(|c| HeaderValue::try_from(c).ok()) {
+                headers.insert(http::header::COOKIE, value);
+            }
+        }
+
+        // Add Accept-Language
+        if let Ok(val) = HeaderValue::try_from(&self.config.accept_language) {
+            headers.insert(HeaderName::from_static("accept-language"), val);
+        }
+
+        // Add referrer
+        if let Some(ref referrer) = request.referrer {
+            if let Ok(val) = HeaderValue::try_from(referrer.as_str()) {
+                headers.insert(HeaderName::from_static("referer"), val);
+            }
+        }
+
+        if let Some(ref body) = request.body {
+            self.throttle.upload(body.len()).await?;
+        }
+
+        // Certificate exceptions are only honored by whole-body requests
+        let pinned = self.pinned_certificates(&request);
+        if request.streaming && pinned.is_empty() {
+            return self.send_streaming(request, headers).await;
+        }
+
+        // Execute request using rustkit-http. Early hints are reported as
+        // they arrive, and a navigation takes up their preloads meanwhile.
+        // Preloads still running when the response is complete are
+        // dropped; the hints go out with the response for the caller to
+        // take up again.
+        let (hints_tx, mut hints_rx) = mpsc::unbounded_channel();
+        let base = request.url.clone();
+        let on_interim = move |status: StatusCode, headers: &HeaderMap| {
+            if status == StatusCode::EARLY_HINTS {
+                let _ = hints_tx.send(parse_link_headers(headers, &base));
+            }
+        };
+        let send = self.client.request_with_interim(
+            request.method.clone(),
+            request.url.as_str(),
+            headers,
+            request.body.clone(),
+            &pinned,
+            Partition {
+                anonymous: request.credentials == CredentialsMode::Omit,
+                site: site.as_ref().map(Site::as_str),
+            },
+            &on_interim,
+        );
+        let (result, early_hints) = self
+            .with_early_hints(&request, &site, send, &mut hints_rx)
+            .await;
+        let http_response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_protocol_error(&request.url, &e);
+                return Err(e.into());
+            }
+        };
+        self.record_response(&http_response);
+        self.store_cookies(&request, &http_response.headers);
+
+        if http_response.certificate_pinned {
+            warn!(url = %request.url, "Loaded with a certificate exception");
+        }
+
+        self.throttle.first_byte().await?;
+
+        let url = http_response.url.clone();
+
+        // Parse content type
+        let content_type = http_response
+            .content_type()
+            .and_then(|s| s.parse::<Mime>().ok());
+
+        // Get content length
+        let content_length = http_response.content_length();
+
+        trace!(
+            url = %url,
+            status = %http_response.status,
+            content_type = ?content_type,
+            content_length = ?content_length,
+            body_len = http_response.body.len(),
+            "Response received"
+        );
+
+        Ok(Response {
+            request_id: request.id,
+            url,
+            status: http_response.status,
+            headers: http_response.headers,
+            content_type,
+            content_length,
+            certificate_error_overridden: http_response.certificate_pinned,
+            tls: http_response.tls,
+            from_cache: false,
+            redirect_chain: RedirectChain::default(),
+            early_hints,
+            body: if self.throttle.paces_downloads() {
+                ResponseBody::Stream(self.throttle.download(http_response.body))
+            } else {
+                ResponseBody::Full(http_response.body)
+            },
+            tracked: None,
+        })
+    }
+
+    /// Send a streaming request. A task forwards the body as it arrives
+    /// and closes the connection once the response is dropped. Early hints
+    /// are handled as for other requests.
+    async fn send_streaming(
+        &self,
+        request: Request,
+        headers: HeaderMap,
+    ) -> Result<Response, NetError> {
+        let site = self.cache_site(&request);
+        let partition = Partition {
+            anonymous: request.credentials == CredentialsMode::Omit,
+            site: site.as_ref().map(Site::as_str),
+        };
+        let (hints_tx, mut hints_rx) = mpsc::unbounded_channel();
+        let base = request.url.clone();
+        let on_interim = move |status: StatusCode, headers: &HeaderMap| {
+            if status == StatusCode::EARLY_HINTS {
+                let _ = hints_tx.send(parse_link_headers(headers, &base));
+            }
+        };
+        let send = self.client.request_streaming_with_interim(
+            request.method.clone(),
+            request.url.as_str(),
+            headers,
+            request.body.clone(),
+            partition,
+            Some(&on_interim),
+        );
+        let (result, early_hints) = self
+            .with_early_hints(&request, &site, send, &mut hints_rx)
+            .await;
+        let mut http_response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_protocol_error(&request.url, &e);
+                return Err(e.into());
+            }
+        };
+
+        self.throttle.first_byte().await?;
+
+        let read_timeout = request.timeout.unwrap_or(self.config.default_timeout);
+        http_response.set_read_timeout(read_timeout);
+        let content_type = http_response
+            .content_type()
+            .and_then(|s| s.parse::<Mime>().ok());
+        let url = http_response.url.clone();
+        let status = http_response.status;
+        let response_headers = http_response.headers.clone();
+        let content_length = http_response.content_length;
+        self.store_cookies(&request, &response_headers);
+
+        trace!(url = %url, status = %status, "Streaming response received");
+        if let Some(origin) = origin_key(&url) {
+            self.stats
+                .lock()
+                .unwrap()
+                .record(&origin, "http/1.1", false);
+        }
+
+        let (tx, rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            loop {
+                let chunk = tokio::select! {
+                    _ = tx.closed() => return,
+                    chunk = http_response.next_chunk() => chunk,
+                };
+                let chunk = match chunk {
+                    Ok(Some(chunk)) => Ok(chunk),
+                    Ok(None) => return,
+                    Err(HttpError::Timeout) => Err(NetError::Timeout(read_timeout)),
+                    Err(e) => Err(e.into()),
+                };
+                let failed = chunk.is_err();
+                if tx.send(chunk).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response {
+            request_id: request.id,
+            url,
+            status,
+            headers: response_headers,
+            content_type,
+            content_length,
+            certificate_error_overridden: false,
+            tls: None,
+            from_cache: false,
+            redirect_chain: RedirectChain::default(),
+            early_hints,
+            body: ResponseBody::Stream(rx),
+            tracked: None,
+        })
+    }
+
+    /// Wait for `send`, reporting the early hints that arrive on `hints_rx`
+    /// meanwhile. A navigation takes up their preloads as they come;
+    /// preloads still running when `send` completes are dropped.
+    async fn with_early_hints<T>(
+        &self,
+        request: &Request,
+        site: &Option<Site>,
+        send: impl std::future::Future<Output = T>,
+        hints_rx: &mut mpsc::UnboundedReceiver<Vec<ResourceHint>>,
+    ) -> (T, Vec<ResourceHint>) {
+        tokio::pin!(send);
+        let mut early_hints = Vec::new();
+        let mut preloads: FuturesUnordered<BoxFuture<'_, ()>> = FuturesUnordered::new();
+        let result = loop {
+            tokio::select! {
+                result = &mut send => break result,
+                Some(hints) = hints_rx.recv() => {
+                    if request.is_navigation {
+                        for hint in hints.iter().filter(|hint| {
+                            matches!(hint.kind, HintKind::Preload | HintKind::Preconnect)
+                        }) {
+                            preloads.push(self.early_hint(
+                                hint.clone(),
+                                request.view_id,
+                                site.clone(),
+                            ));
+                        }
+                    }
+                    self.early_hints_received(request, &hints);
+                    early_hints.extend(hints);
+                }
+                Some(()) = preloads.next(), if !preloads.is_empty() => {}
+            }
+        };
+        while let Ok(hints) = hints_rx.try_recv() {
+            self.early_hints_received(request, &hints);
+            early_hints.extend(hints);
+        }
+        (result, early_hints)
+    }
+
+    /// Take up an early hint while the navigation that carried it is still
+    /// loading. Boxed here, outside `send_once`, whose future it is part of.
+    fn early_hint(
+        &self,
+        hint: ResourceHint,
+        view_id: Option<u64>,
+        site: Option<Site>,
+    ) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Err(e) = self.apply_hint(&hint, view_id, site.as_ref()).await {
+                debug!(url = %hint.url, error = %e, "Early hint failed");
+            }
+        })
+    }
+
+    /// Take up a resource hint from a document under the top-level `site`,
+    /// unless network conditions or earlier work make it pointless. Every
+    /// hint is reported as [`NetEvent::Hinted`].
+    pub async fn apply_hint(
+        &self,
+        hint: &ResourceHint,
+        view_id: Option<u64>,
+        site: Option<&Site>,
+    ) -> Result<(), NetError> {
+        self.take_up_hint(hint, view_id, site, false).await
+    }
+
+    /// Preload a subresource a document's preload scanner found, like
+    /// [`Self::apply_hint`], counting it in [`NetStats::speculative`].
+    pub async fn apply_speculative_hint(
+        &self,
+        hint: &ResourceHint,
+        view_id: Option<u64>,
+        site: Option<&Site>,
+    ) -> Result<(), NetError> {
+        self.stats.lock().unwrap().speculative.urls_discovered += 1;
+        self.take_up_hint(hint, view_id, site, true).await
+    }
+
+    async fn take_up_hint(
+        &self,
+        hint: &ResourceHint,
+        view_id: Option<u64>,
+        site: Option<&Site>,
+        speculative: bool,
+    ) -> Result<(), NetError> {
+        let site = site.filter(|_| self.config.partition_caches);
+        let partition = Partition {
+            anonymous: hint.anonymous,
+            site: site.map(Site::as_str),
+        };
+        let skipped = self.hint_skip_reason(hint, site);
+        self.in_flight.emit(NetEvent::Hinted {
+            kind: hint.kind,
+            url: hint.url.clone(),
+            view_id,
+            skipped: skipped.map(str::to_string),
+        });
+        if let Some(reason) = skipped {
+            debug!(url = %hint.url, kind = ?hint.kind, reason, "Skipping resource hint");
+            return Ok(());
+        }
+
+        match hint.kind {
+            HintKind::DnsPrefetch => {
+                let (Some(host), Some(port)) =
+                    (hint.url.host_str(), hint.url.port_or_known_default())
+                else {
+                    return Err(NetError::InvalidUrl(hint.url.to_string()));
+                };
+                self.client.resolve(host, port, partition.site).await?;
+            }
+            HintKind::Preconnect => {
+                self.client
+                    .preconnect(&hint.url, partition, hints::PRECONNECT_TTL)
+                    .await?;
+            }
+            HintKind::Prefetch | HintKind::Preload => {
+                let priority = hint
+                    .destination
+                    .map_or(Priority::VeryLow, PreloadDestination::priority);
+                let mut request = Request::get(hint.url.clone())
+                    .priority(priority)
+                    .initiated_by(Initiator::Hint);
+                if let Some(view_id) = view_id {
+                    request = request.for_view(view_id);
+                }
+                if hint.anonymous {
+                    request.credentials = CredentialsMode::Omit;
+                }
+                request.top_level_site = site.cloned();
+                if speculative {
+                    self.stats.lock().unwrap().speculative.fetches_started += 1;
+                }
+                let response = self.fetch(request).await?;
+                if response.ok() && !response.from_cache {
+                    let (status, headers) = (response.status, response.headers.clone());
+                    let body = response.bytes().await?;
+                    if speculative {
+                        self.prefetched
+                            .store_speculative(site, &hint.url, status, headers, body);
+                    } else {
+                        self.prefetched
+                            .store(site, &hint.url, status, headers, body);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Report the hints of a `103 Early Hints` response to `request`.
+    fn early_hints_received(&self, request: &Request, hints: &[ResourceHint]) {
+        debug!(url = %request.url, hints = hints.len(), "Early hints received");
+        self.in_flight.emit(NetEvent::EarlyHintsReceived {
+            request_id: request.id,
+            url: request.url.clone(),
+            view_id: request.view_id,
+            hints: hints.to_vec(),
+        });
+    }
+
+    fn hint_skip_reason(&self, hint: &ResourceHint, site: Option<&Site>) -> Option<&'static str> {
+        let conditions = self.network_conditions();
+        let partition = Partition {
+            anonymous: hint.anonymous,
+            site: site.map(Site::as_str),
+        };
+        match hint.kind {
+            _ if conditions.offline => Some("offline"),
+            HintKind::Preconnect if self.client.has_preconnected(&hint.url, partition) => {
+                Some("already connected")
+            }
+            HintKind::Prefetch if conditions.is_constrained() => Some("constrained network"),
+            HintKind::Prefetch | HintKind::Preload if self.prefetched.is_fresh(site, &hint.url) => {
+                Some("already cached")
+            }
+            _ => None,
+        }
+    }
+
+    /// A response answered from the prefetch cache.
+    fn prefetched_response(request: &Request, prefetched: hints::Prefetched) -> Response {
+        let content_type = prefetched
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        Response {
+            request_id: request.id,
+            url: request.url.clone(),
+            status: prefetched.status,
+            headers: prefetched.headers,
+            content_type,
+            content_length: Some(prefetched.body.len() as u64),
+            certificate_error_overridden: false,
+            tls: None,
+            from_cache: true,
+            redirect_chain: RedirectChain::default(),
+            early_hints: Vec::new(),
+            body: ResponseBody::Full(prefetched.body),
+            tracked: None,
+        }
+    }
+
+    /// Start a download.
+    pub async fn start_download(
+        &self,
+        url: Url,
+        destination: PathBuf,
+    ) -> Result<DownloadId, NetError> {
+        if url.scheme() == "blob" {
+            let data = self
+                .blob_urls
+                .resolve(&url)
+                .ok_or_else(|| NetError::RequestFailed(format!("No blob at {}", url)))?;
+            return self
+                .download_manager
+                .save(url, data.bytes, destination)
+                .await;
+        }
+        let request = outgoing(Request::get(url));
+        self.download_manager
+            .start(request, destination)
+            .await
+    }
+}
+
+/// `request` as the loader sends it: userinfo dropped from its URL and its
+/// path normalized.
+fn outgoing(mut request: Request) -> Request {
+    if has_userinfo(&request.url) {
+        debug!("Dropping userinfo from request URL");
+        request.url = without_userinfo(&request.url);
+    }
+    normalize_path(&mut request.url);
+    request
+}
+
+/// Serialized origin of `url`, or `None` for opaque origins.
+fn origin_key(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// Mark a request issued by `context` as cross-origin where it is.
+fn apply_request_origin(request: &mut Request, context: &SecurityContext) {
+    if context.is_same_origin(&request.url) {
+        return;
+    }
+    if let Ok(value) = HeaderValue::try_from(context.origin.serialize()) {
+        request.headers.insert(http::header::ORIGIN, value);
+    }
+    if request.credentials == CredentialsMode::SameOrigin {
+        request.credentials = CredentialsMode::Omit;
+    }
+}
+
+/// Fetch API for JavaScript compatibility.
+pub struct FetchApi {
+    loader: Arc<ResourceLoader>,
+    /// Context of the document issuing fetches, if known.
+    context: Option<SecurityContext>,
+}
+
+impl FetchApi {
+    /// Create a new fetch API.
+    pub fn new(loader: Arc<ResourceLoader>) -> Self {
+        Self {
+            loader,
+            context: None,
+        }
+    }
+
+    /// Create a fetch API for a document with the given security context.
+    ///
+    /// Requests to other origins carry an `Origin` header and drop
+    /// credentials in `same-origin` mode; an opaque origin (such as a
+    /// sandboxed frame) is cross-origin to everything.
+    pub fn with_security_context(loader: Arc<ResourceLoader>, context: SecurityContext) -> Self {
+        Self {
+            loader,
+            context: Some(context),
+        }
+    }
+
+    /// The security context requests are issued from.
+    pub fn security_context(&self) -> Option<&SecurityContext> {
+        self.context.as_ref()
+    }
+
+    /// Fetch with options similar to JavaScript fetch().
+    pub async fn fetch(&self, url: &str, options: FetchOptions) -> Result<Response, NetError> {
+        let url = parse_url(url)?;
+        if let Some(context) = &self.context {
+            if context.blocks_mixed_content(&url, MixedContentType::Fetch) {
+                warn!(%url, "Mixed content fetch blocked");
+                return Err(NetError::Blocked);
+            }
+        }
+
+        let mut request = match options.method.as_deref() {
+            Some("POST") => Request::post(url, options.body.unwrap_or_default()),
+            Some(method) if method != "GET" => {
+                let mut req = Request::get(url);
+                req.method = Method::from_bytes(method.as_bytes())
+                    .map_err(|_| NetError::RequestFailed(format!("Invalid method: {}", method)))?;
+                req.body = options.body;
+                req
+            }
+            _ => Request::get(url),
+        };
+
+        // Add headers; repeated names are combined
+        request = request.script_originated();
+        for (name, value) in &options.headers {
+            request = request.try_header(name, value)?;
+        }
+
+        // Set credentials
+        request.credentials = match options.credentials.as_deref() {
+            Some("omit") => CredentialsMode::Omit,
+            Some("include") => CredentialsMode::Include,
+            _ => CredentialsMode::SameOrigin,
+        };
+        if let Some(context) = &self.context {
+            request = request.issued_from(context);
+        }
+        if let Some(signal) = options.signal {
+            request = request.cancel_with(signal);
+        }
+        request.view_id = options.view_id;
+
+        self.loader.fetch(request).await
+    }
+}
+
+/// Options for fetch API.
+#[derive(Debug, Default)]
+pub struct FetchOptions {
+    pub method: Option<String>,
+    /// Headers in the order they are added to the request.
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Bytes>,
+    pub credentials: Option<String>,
+    pub mode: Option<String>,
+    pub cache: Option<String>,
+    pub redirect: Option<String>,
+    /// Aborts the fetch, like a JS `AbortSignal`.
+    pub signal: Option<CancelHandle>,
+    /// View the fetch is for, so it is cancelled when the view navigates.
+    pub view_id: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{any, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_request_builder() {
+        let url = Url::parse("https://example.com").unwrap();
+        let request = Request::get(url.clone())
+            .try_header("accept", "application/json")
+            .unwrap()
+            .timeout(Duration::from_secs(10));
+
+        assert_eq!(request.url, url);
+        assert_eq!(request.method, Method::GET);
+        assert!(request.headers.contains_key("accept"));
+        assert_eq!(request.timeout, Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_request_id_uniqueness() {
+        let id1 = RequestId::new();
+        let id2 = RequestId::new();
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn test_credentials_mode_default() {
+        assert_eq!(CredentialsMode::default(), CredentialsMode::SameOrigin);
+    }
+
+    #[test]
+    fn test_request_origin_from_frame_context() {
+        let parent = SecurityContext::from_url(&Url::parse("https://example.com/").unwrap());
+        let srcdoc = Url::parse("about:srcdoc").unwrap();
+        let frame = SecurityContext::for_frame(&parent, &srcdoc, None);
+
+        let mut same = Request::get(Url::parse("https://example.com/data").unwrap());
+        apply_request_origin(&mut same, &frame);
+        assert!(!same.headers.contains_key("origin"));
+        assert_eq!(same.credentials, CredentialsMode::SameOrigin);
+
+        let mut cross = Request::get(Url::parse("https://api.other.com/data").unwrap());
+        apply_request_origin(&mut cross, &frame);
+        assert_eq!(cross.headers["origin"], "https://example.com");
+        assert_eq!(cross.credentials, CredentialsMode::Omit);
+
+        let sandboxed = SecurityContext::for_frame(&parent, &srcdoc, Some(SandboxFlags::default()));
+        let mut opaque = Request::get(Url::parse("https://example.com/data").unwrap());
+        apply_request_origin(&mut opaque, &sandboxed);
+        assert_eq!(opaque.headers["origin"], "null");
+        assert_eq!(opaque.credentials, CredentialsMode::Omit);
+    }
+
+    #[tokio::test]
+    async fn test_certificate_exception_page_blocks_mixed_content() {
+        let (_server, url) = spawn_http_server(b"data".to_vec()).await;
+        let loader = Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap());
+        let page = Url::parse("https://example.com/").unwrap();
+        let context = SecurityContext::from_url(&page).with_certificate_error();
+        let api = FetchApi::with_security_context(loader, context);
+
+        let result = api.fetch(url.as_str(), FetchOptions::default()).await;
+        assert!(matches!(result, Err(NetError::Blocked)));
+    }
+
+    #[test]
     fn test_loader_config_default() {
         let config = LoaderConfig::default();
         assert_eq!(config.user_agent, "RustKit/1.0");
         assert!(config.cookies_enabled);
     }
+
+    #[tokio::test]
+    async fn test_view_interceptor_overrides_global() {
+        let mut global = RequestInterceptor::new();
+        global.block(intercept::UrlPattern::prefix("https://example.com/"));
+        let loader = ResourceLoader::with_interceptor(LoaderConfig::default(), global).unwrap();
+
+        let mut view = RequestInterceptor::new();
+        view.allow(intercept::UrlPattern::exact(
+            "https://example.com/widget.js",
+        ));
+        loader.set_view_interceptor(7, Some(view));
+
+        let url = Url::parse("https://example.com/widget.js").unwrap();
+        let action = loader
+            .intercept(&Request::get(url.clone()).for_view(7))
+            .await;
+        assert!(matches!(action, InterceptAction::Allow));
+
+        // Other views, and requests the view doesn't decide, use the global chain
+        let action = loader
+            .intercept(&Request::get(url.clone()).for_view(8))
+            .await;
+        assert!(matches!(action, InterceptAction::Block));
+        let other = Url::parse("https://example.com/ads.js").unwrap();
+        let action = loader.intercept(&Request::get(other).for_view(7)).await;
+        assert!(matches!(action, InterceptAction::Block));
+
+        loader.set_view_interceptor(7, None);
+        let action = loader.intercept(&Request::get(url).for_view(7)).await;
+        assert!(matches!(action, InterceptAction::Block));
+    }
+
+    #[tokio::test]
+    async fn test_blob_url_fetch_download_and_revoke() {
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.download_manager().set_event_sender(tx).await;
+        let url = loader.blob_urls().register(
+            1,
+            "https://example.com",
+            BlobData {
+                bytes: Bytes::from_static(b"hello, blob"),
+                content_type: "text/plain".into(),
+            },
+        );
+
+        let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(response.ok());
+        assert_eq!(
+            response.content_type.as_ref().unwrap().essence_str(),
+            "text/plain"
+        );
+        assert_eq!(response.headers["content-length"], "11");
+        assert_eq!(response.text().await.unwrap(), "hello, blob");
+
+        let destination = std::env::temp_dir()
+            .join(format!("rustkit-blob-{}", std::process::id()))
+            .join("hello.txt");
+        loader
+            .start_download(url.clone(), destination.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            events.recv().await,
+            Some(DownloadEvent::Started { .. })
+        ));
+        assert!(matches!(
+            events.recv().await,
+            Some(DownloadEvent::Completed { .. })
+        ));
+        assert_eq!(std::fs::read(&destination).unwrap(), b"hello, blob");
+        let _ = std::fs::remove_dir_all(destination.parent().unwrap());
+
+        assert!(loader.blob_urls().revoke(&url));
+        assert!(loader.fetch(Request::get(url.clone())).await.is_err());
+        assert!(loader.start_download(url, destination).await.is_err());
+    }
+
+    const CERT_A: (&[u8], &[u8]) = (
+        include_bytes!("../testdata/localhost_a.pem"),
+        include_bytes!("../testdata/localhost_a.key"),
+    );
+    const CERT_B: (&[u8], &[u8]) = (
+        include_bytes!("../testdata/localhost_b.pem"),
+        include_bytes!("../testdata/localhost_b.key"),
+    );
+
+    /// Serve "ok" over TLS on localhost with a self-signed test certificate.
+    async fn spawn_tls_server(cert: (&[u8], &[u8])) -> u16 {
+        spawn_tls_server_with(cert, None).await.0
+    }
+
+    /// Like [`spawn_tls_server`], optionally capping the TLS version, and
+    /// counting accepted connections. Connections are kept alive.
+    ///
+    /// A raw socket, since a [`MockServer`] only speaks plain HTTP.
+    async fn spawn_tls_server_with(
+        (cert, key): (&[u8], &[u8]),
+        max_version: Option<native_tls::Protocol>,
+    ) -> (u16, Arc<AtomicU64>) {
+        let identity = native_tls::Identity::from_pkcs8(cert, key).unwrap();
+        let acceptor = native_tls::TlsAcceptor::builder(identity)
+            .max_protocol_version(max_version)
+            .build()
+            .unwrap();
+        serve_tls(acceptor).await
+    }
+
+    /// Serve "ok" with `acceptor`, counting accepted connections.
+    async fn serve_tls(acceptor: native_tls::TlsAcceptor) -> (u16, Arc<AtomicU64>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let acceptor = tokio_native_tls::TlsAcceptor::from(acceptor);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicU64::new(0));
+
+        let accepted = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // Validating clients abort the handshake
+                    let Ok(mut tls) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    if tls.get_ref().negotiated_alpn().ok().flatten().as_deref() == Some(b"h2") {
+                        serve_h2(tls, None).await;
+                        return;
+                    }
+                    let mut buf = [0u8; 1024];
+                    while matches!(tls.read(&mut buf).await, Ok(n) if n > 0) {
+                        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        if tls.write_all(response).await.is_err() {
+                            break;
+                        }
+                    }
+                    let _ = tls.shutdown().await;
+                });
+            }
+        });
+
+        (port, connections)
+    }
+
+    fn trusting_loader(tls: TlsConfig) -> ResourceLoader {
+        trusting_loader_with(LoaderConfig {
+            tls,
+            ..Default::default()
+        })
+    }
+
+    fn trusting_loader_with(config: LoaderConfig) -> ResourceLoader {
+        let loader = ResourceLoader::new(config).unwrap();
+        let der = native_tls::Certificate::from_pem(CERT_A.0)
+            .unwrap()
+            .to_der()
+            .unwrap();
+        loader.set_extra_root_certificates(vec![der]).unwrap();
+        loader
+    }
+
+    #[tokio::test]
+    async fn test_tls_min_version() {
+        let (port, _) = spawn_tls_server_with(CERT_A, Some(native_tls::Protocol::Tlsv12)).await;
+        let url = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+
+        let loader = trusting_loader(TlsConfig::default());
+        let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        // A TLS 1.2 server is rejected once 1.3 is required, without
+        // restarting the loader and without blaming the certificate
+        loader
+            .set_tls_config(TlsConfig {
+                min_version: TlsVersion::Tls13,
+                ..Default::default()
+            })
+            .unwrap();
+        let result = loader.fetch(Request::get(url)).await;
+        assert!(
+            matches!(
+                result,
+                Err(NetError::HttpError(rustkit_http::HttpError::TlsError(_)))
+            ),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tls_session_resumption() {
+        let (port, connections) = spawn_tls_server_with(CERT_A, None).await;
+        let url = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+        let loader = trusting_loader(TlsConfig::default());
+
+        let first = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(!first.tls.as_ref().unwrap().connection_reused);
+        let second = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(second.tls.as_ref().unwrap().connection_reused);
+        assert_eq!(second.text().await.unwrap(), "ok");
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Disabled, every request does a full handshake
+        loader
+            .set_tls_config(TlsConfig {
+                session_resumption: false,
+                ..Default::default()
+            })
+            .unwrap();
+        for _ in 0..2 {
+            let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+            assert!(!response.tls.unwrap().connection_reused);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_certificate_exception() {
+        let port = spawn_tls_server(CERT_A).await;
+        let url = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+
+        // Self-signed certificate surfaces a typed error with the certificate
+        let error = loader
+            .fetch(Request::get(url.clone()).navigation())
+            .await
+            .unwrap_err();
+        let NetError::TlsError {
+            host,
+            kind,
+            cert_chain_der,
+        } = error
+        else {
+            panic!("expected TlsError, got {:?}", error);
+        };
+        assert_eq!(host, "localhost");
+        assert_eq!(kind, CertificateErrorKind::UntrustedRoot);
+        assert!(!cert_chain_der.is_empty());
+
+        // Allow exactly this certificate for this host
+        let fingerprint = certificate_fingerprint(&cert_chain_der[0]);
+        let expiry = SystemTime::now() + Duration::from_secs(3600);
+        loader.add_certificate_exception("localhost", fingerprint, expiry);
+
+        let response = loader
+            .fetch(Request::get(url.clone()).navigation())
+            .await
+            .unwrap();
+        assert!(response.certificate_error_overridden);
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        // Subresources never use the exception
+        let result = loader.fetch(Request::get(url.clone())).await;
+        assert!(matches!(result, Err(NetError::TlsError { .. })));
+
+        // A different certificate for the same host is still rejected
+        let other_port = spawn_tls_server(CERT_B).await;
+        let other = Url::parse(&format!("https://localhost:{}/", other_port)).unwrap();
+        let result = loader.fetch(Request::get(other).navigation()).await;
+        assert!(matches!(result, Err(NetError::TlsError { .. })));
+
+        // Expired exceptions don't apply
+        loader.clear_certificate_exceptions("localhost");
+        loader.add_certificate_exception("localhost", fingerprint, SystemTime::now());
+        let result = loader.fetch(Request::get(url).navigation()).await;
+        assert!(matches!(result, Err(NetError::TlsError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_extra_root_certificates() {
+        let port = spawn_tls_server(CERT_A).await;
+        let url = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+
+        let der = native_tls::Certificate::from_pem(CERT_A.0)
+            .unwrap()
+            .to_der()
+            .unwrap();
+        loader.set_extra_root_certificates(vec![der]).unwrap();
+
+        let response = loader.fetch(Request::get(url)).await.unwrap();
+        assert!(!response.certificate_error_overridden);
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    /// Serve `body` over plain HTTP at every path. The server stops when
+    /// the returned [`MockServer`] is dropped.
+    async fn spawn_http_server(body: Vec<u8>) -> (MockServer, Url) {
+        let server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&server)
+            .await;
+        let url = Url::parse(&format!("{}/data", server.uri())).unwrap();
+        (server, url)
+    }
+
+    /// Answer every request with an empty page, recording each request
+    /// head as sent.
+    ///
+    /// A raw socket rather than a [`MockServer`]: tests check the header
+    /// lines exactly as they went over the wire, which a parsed request
+    /// no longer shows.
+    async fn spawn_head_recording_server() -> (Url, Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let heads = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = heads.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    log.lock()
+                        .unwrap()
+                        .push(String::from_utf8_lossy(&head).into_owned());
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        )
+                        .await;
+                });
+            }
+        });
+        (
+            Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap(),
+            heads,
+        )
+    }
+
+    /// Serve `/moved` as a redirect to `/landing?utm_source=feed` and any
+    /// other path as a page whose body is the request target.
+    async fn spawn_redirect_server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(path("/moved"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("Location", "/landing?utm_source=feed")
+                    .set_body_string("moved"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(any())
+            .respond_with(|request: &wiremock::Request| {
+                ResponseTemplate::new(200).set_body_string(request_target(request))
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+
+    /// The path and query a request was sent for.
+    fn request_target(request: &wiremock::Request) -> String {
+        request.url[url::Position::BeforePath..url::Position::AfterQuery].to_string()
+    }
+
+    /// The targets of the requests `server` has received, in order.
+    async fn received_targets(server: &MockServer) -> Vec<String> {
+        let requests = server.received_requests().await.unwrap();
+        requests.iter().map(request_target).collect()
+    }
+
+    /// Serve the current `(etag, body)`, answering `304` when the request's
+    /// `If-None-Match` is the current ETag, and record each status sent.
+    async fn spawn_etag_server(
+        resource: Arc<std::sync::Mutex<(String, String)>>,
+    ) -> (MockServer, Url, Arc<std::sync::Mutex<Vec<u16>>>) {
+        let server = MockServer::start().await;
+        let statuses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = statuses.clone();
+        Mock::given(any())
+            .respond_with(move |request: &wiremock::Request| {
+                let if_none_match = request
+                    .headers
+                    .get("if-none-match")
+                    .and_then(|value| value.to_str().ok());
+                let (etag, body) = resource.lock().unwrap().clone();
+                if if_none_match == Some(etag.as_str()) {
+                    log.lock().unwrap().push(304);
+                    ResponseTemplate::new(304).insert_header("ETag", etag.as_str())
+                } else {
+                    log.lock().unwrap().push(200);
+                    ResponseTemplate::new(200)
+                        .insert_header("ETag", etag.as_str())
+                        .set_body_raw(body, "application/json")
+                }
+            })
+            .mount(&server)
+            .await;
+        let url = Url::parse(&format!("{}/feed.json", server.uri())).unwrap();
+        (server, url, statuses)
+    }
+
+    #[tokio::test]
+    async fn test_poll_sends_only_changed_bodies() {
+        let resource = Arc::new(std::sync::Mutex::new((
+            "\"1\"".to_string(),
+            "{\"v\":1}".to_string(),
+        )));
+        let (_server, url, statuses) = spawn_etag_server(resource.clone()).await;
+        let loader = Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap());
+        let (tx, mut rx) = mpsc::channel(4);
+        let poller = loader.poll(url.clone(), Duration::from_millis(20), tx);
+        let requests_after = |count: usize| {
+            let statuses = statuses.clone();
+            async move {
+                while statuses.lock().unwrap().len() < count {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }
+        };
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(&first.body[..], b"{\"v\":1}");
+        assert_eq!(first.validators.etag.as_deref(), Some("\"1\""));
+        assert_eq!(first.content_type.as_deref(), Some("application/json"));
+
+        // Unchanged: revalidated with a 304 and nothing sent
+        requests_after(3).await;
+        assert_eq!(statuses.lock().unwrap()[..3], [200, 304, 304]);
+        assert!(rx.try_recv().is_err());
+
+        // A new ETag for the same content is not an update
+        resource.lock().unwrap().0 = "W/\"2\"".to_string();
+        let seen = statuses.lock().unwrap().len();
+        requests_after(seen + 2).await;
+        assert!(rx.try_recv().is_err());
+
+        *resource.lock().unwrap() = ("\"3\"".to_string(), "{\"v\":3}".to_string());
+        let changed = rx.recv().await.unwrap();
+        assert_eq!(&changed.body[..], b"{\"v\":3}");
+        assert_eq!(changed.validators.etag.as_deref(), Some("\"3\""));
+        let seen = statuses.lock().unwrap().len();
+        requests_after(seen + 2).await;
+        assert!(rx.try_recv().is_err());
+
+        // The direct API reports the same validators
+        let validators = Some(changed.validators.clone());
+        match loader
+            .fetch_if_modified(Request::get(url), validators)
+            .await
+            .unwrap()
+        {
+            ConditionalResponse::NotModified { validators } => {
+                assert_eq!(validators.etag.as_deref(), Some("\"3\""))
+            }
+            ConditionalResponse::Modified(response) => panic!("got {}", response.status),
+        }
+
+        drop(rx);
+        tokio::time::timeout(Duration::from_secs(5), poller)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tracking_params_stripped_before_sending() {
+        let server = spawn_redirect_server().await;
+        let base = Url::parse(&server.uri()).unwrap();
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader
+            .interceptor()
+            .add_handler(Arc::new(TrackingParamStripper::new()));
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+
+        let url = base
+            .join("/page?id=1&utm_source=news&fbclid=abc#top")
+            .unwrap();
+        let response = loader
+            .fetch(Request::get(url.clone()).navigation())
+            .await
+            .unwrap();
+        assert_eq!(response.url, base.join("/page?id=1#top").unwrap());
+        assert_eq!(response.redirect_chain.count(), 1);
+        assert_eq!(response.redirect_chain.original_url(), Some(&url));
+        assert_eq!(
+            response.redirect_chain.redirects[0].redirect_type,
+            RedirectType::Internal
+        );
+        assert_eq!(response.text().await.unwrap(), "/page?id=1");
+        let mut redirected = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let NetEvent::Redirected { to, status, .. } = event {
+                redirected.push((to.path().to_string(), status));
+            }
+        }
+        assert_eq!(redirected, [("/page".to_string(), None)]);
+
+        // Server redirects are stripped too
+        let response = loader
+            .fetch(Request::get(base.join("/moved").unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.url, base.join("/landing").unwrap());
+        assert_eq!(response.redirect_chain.count(), 1);
+        assert_eq!(
+            response.redirect_chain.redirects[0].redirect_type,
+            RedirectType::Found
+        );
+        drop(response);
+        assert_eq!(
+            received_targets(&server).await,
+            ["/page?id=1", "/moved", "/landing"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocked_redirect_completes_with_redirect_response() {
+        struct KeepRedirects;
+        impl InterceptHandler for KeepRedirects {
+            fn intercept(&self, _request: &Request) -> InterceptAction {
+                InterceptAction::Allow
+            }
+            fn on_redirect(&self, _: &Request, _: &Url, _: StatusCode) -> RedirectAction {
+                RedirectAction::Block
+            }
+        }
+
+        let server = spawn_redirect_server().await;
+        let base = Url::parse(&server.uri()).unwrap();
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.interceptor().add_handler(Arc::new(KeepRedirects));
+        let response = loader
+            .fetch(Request::get(base.join("/moved").unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::FOUND);
+        assert!(!response.redirect_chain.was_redirected());
+        assert_eq!(response.text().await.unwrap(), "moved");
+        assert_eq!(received_targets(&server).await, ["/moved"]);
+    }
+
+    #[tokio::test]
+    async fn test_script_request_cannot_override_host() {
+        let (url, heads) = spawn_head_recording_server().await;
+        let loader = Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap());
+        let options = FetchOptions {
+            headers: [
+                ("Host", "evil.example"),
+                ("Cookie", "session=stolen"),
+                ("Sec-Fetch-Site", "same-origin"),
+                ("X-List", "1"),
+                ("x-list", "2"),
+            ]
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+            ..Default::default()
+        };
+        FetchApi::new(loader.clone())
+            .fetch(url.as_str(), options)
+            .await
+            .unwrap();
+
+        // Internal code may still set them deliberately
+        let internal = Request::get(url.clone())
+            .try_header("Cookie", "session=internal")
+            .unwrap();
+        loader.fetch(internal).await.unwrap();
+
+        let heads = heads.lock().unwrap();
+        let hosts = |head: &str| {
+            head.lines()
+                .filter(|line| line.to_ascii_lowercase().starts_with("host:"))
+                .count()
+        };
+        assert_eq!(hosts(&heads[0]), 1);
+        for dropped in ["evil.example", "stolen", "same-origin"] {
+            assert!(!heads[0].contains(dropped), "{dropped} sent: {}", heads[0]);
+        }
+        assert!(heads[0].contains("x-list: 1, 2\r\n"), "{}", heads[0]);
+        assert!(
+            heads[1].contains("cookie: session=internal\r\n"),
+            "{}",
+            heads[1]
+        );
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_invalid_headers_never_reach_the_wire() {
+        let (url, heads) = spawn_head_recording_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let oversized = "x".repeat(headers::MAX_VALUE_LEN + 1);
+        let adversarial = [
+            "a\r\nX-Injected: 1",
+            "a\r\n\r\nGET /admin HTTP/1.1",
+            "a\nX-Injected: 1",
+            "nul\0byte",
+            " padded",
+            "trailing\t",
+            "caf\u{e9}",
+            oversized.as_str(),
+        ];
+        for value in adversarial {
+            let result = Request::get(url.clone()).try_header("x-test", value);
+            assert!(
+                matches!(result, Err(NetError::InvalidHeader { .. })),
+                "{value:?} accepted"
+            );
+        }
+        assert!(Request::get(url.clone()).try_header("", "a").is_err());
+        assert!(Request::get(url.clone())
+            .try_header("x-a\r\nx-b", "a")
+            .is_err());
+
+        // The infallible builder drops what typed values let through, and
+        // the loader what was put in the map directly
+        let mut request = Request::get(url.clone());
+        for value in adversarial {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                request = request.header(HeaderName::from_static("x-test"), value);
+            }
+        }
+        request = request.header(
+            HeaderName::from_static("x-obs"),
+            HeaderValue::from_bytes(b"caf\xe9").unwrap(),
+        );
+        request
+            .headers
+            .insert("x-direct", HeaderValue::from_static(" sneaky"));
+        loader.fetch(request).await.unwrap();
+
+        let heads = heads.lock().unwrap();
+        let head = &heads[0];
+        assert!(head.is_ascii());
+        assert!(!head.contains('\0'));
+        assert_eq!(head.matches("HTTP/1.1").count(), 1);
+        for name in ["x-test", "x-obs", "x-direct", "x-injected"] {
+            assert!(
+                !head.to_ascii_lowercase().contains(name),
+                "{name} sent: {head}"
+            );
+        }
+        // Every line ends at a CRLF, with none inside
+        assert!(head
+            .split("\r\n")
+            .all(|line| !line.contains('\r') && !line.contains('\n')));
+    }
+
+    #[tokio::test]
+    async fn test_userinfo_never_reaches_the_wire() {
+        let (base, heads) = spawn_head_recording_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+        let mut url = base.join("/private").unwrap();
+        url.set_username("user").unwrap();
+        url.set_password(Some("secret")).unwrap();
+
+        let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(!has_userinfo(&response.url));
+        drop(response);
+        // Asked for, the credentials go as Basic auth
+        loader
+            .fetch(Request::get(url).basic_auth_from_url())
+            .await
+            .unwrap();
+
+        let heads = heads.lock().unwrap();
+        assert!(heads[0].starts_with("GET /private HTTP/1.1\r\n"));
+        assert!(!heads[0].contains("secret"));
+        assert!(!heads[0].to_ascii_lowercase().contains("authorization"));
+        let basic = heads[1]
+            .split("\r\n")
+            .find(|line| line.to_ascii_lowercase().starts_with("authorization:"));
+        assert_eq!(basic, Some("authorization: Basic dXNlcjpzZWNyZXQ="));
+        while let Ok(event) = events.try_recv() {
+            assert!(!format!("{:?}", event).contains("secret"), "{:?}", event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_redirect_loop_fails() {
+        struct PingPong;
+        impl InterceptHandler for PingPong {
+            fn intercept(&self, request: &Request) -> InterceptAction {
+                let next = if request.url.path() == "/a" {
+                    "/b"
+                } else {
+                    "/a"
+                };
+                InterceptAction::Redirect(request.url.join(next).unwrap())
+            }
+        }
+
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.interceptor().add_handler(Arc::new(PingPong));
+        let url = Url::parse("https://example.com/a").unwrap();
+        assert!(matches!(
+            loader.fetch(Request::get(url)).await,
+            Err(NetError::RequestFailed(message)) if message.contains("loop")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_cap_paces_body() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 100 * 1024]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.set_network_conditions(NetworkConditions {
+            download_bps: Some(50 * 1024),
+            ..Default::default()
+        });
+
+        let start = std::time::Instant::now();
+        let mut response = loader.fetch(Request::get(url)).await.unwrap();
+        let mut chunks = 0;
+        let mut received = 0;
+        while let Some(chunk) = response.chunk().await.unwrap() {
+            chunks += 1;
+            received += chunk.len();
+        }
+        assert_eq!(received, 100 * 1024);
+        assert!(chunks > 1, "body arrived in {} chunk(s)", chunks);
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_aggregates_completed_requests() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 1000]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let aggregator = Arc::new(TelemetryAggregator::new());
+        loader.set_telemetry_sink(Some(aggregator.clone()));
+
+        for _ in 0..10 {
+            let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+            response.bytes().await.unwrap();
+        }
+        let refused = Url::parse("http://127.0.0.1:1/").unwrap();
+        assert!(loader.fetch(Request::get(refused)).await.is_err());
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while aggregator.snapshot().requests < 11 && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let snapshot = aggregator.snapshot();
+        assert_eq!(snapshot.requests, 11);
+        assert_eq!(snapshot.status_count(StatusClass::Success), 10);
+        assert_eq!(snapshot.errors.values().sum::<u64>(), 1);
+        assert_eq!(snapshot.bytes_received, 10_000);
+        assert!(snapshot.latency_p50 > Duration::ZERO);
+        assert!(snapshot.latency_p50 <= snapshot.latency_p99);
+        assert!(snapshot.latency_p99 < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_slow_telemetry_sink_does_not_delay_fetches() {
+        struct Slow;
+        impl TelemetrySink for Slow {
+            fn on_request_complete(&self, _: RequestRecord) {
+                std::thread::sleep(Duration::from_millis(200));
+            }
+        }
+        let (_server, url) = spawn_http_server(b"ok".to_vec()).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.set_telemetry_sink(Some(Arc::new(Slow)));
+
+        let start = std::time::Instant::now();
+        for _ in 0..20 {
+            let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+        // Handing every record to the sink inline would take four seconds
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "fetches took {:?}",
+            start.elapsed()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_handed_to_download_manager() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 64 * 1024]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.download_manager().set_event_sender(tx).await;
+        let (net_tx, mut net_events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(net_tx));
+
+        let response = loader.fetch(Request::get(url)).await.unwrap();
+        let destination = std::env::temp_dir()
+            .join(format!("rustkit-handoff-{}", std::process::id()))
+            .join("data.bin");
+        let id = loader
+            .download_manager()
+            .start_response(response, destination.clone())
+            .await
+            .unwrap();
+        loop {
+            match events.recv().await.unwrap() {
+                DownloadEvent::Completed { id: done, path } if done == id => {
+                    assert_eq!(path, destination);
+                    break;
+                }
+                DownloadEvent::Failed { error, .. } => panic!("{}", error),
+                _ => {}
+            }
+        }
+        assert_eq!(std::fs::read(&destination).unwrap().len(), 64 * 1024);
+        assert_eq!(
+            loader.download_manager().get_state(id).await,
+            Some(DownloadState::Completed)
+        );
+        // One request only
+        let mut started = 0;
+        while let Ok(event) = net_events.try_recv() {
+            started += matches!(event, NetEvent::Started { .. }) as usize;
+        }
+        assert_eq!(started, 1);
+        let _ = std::fs::remove_dir_all(destination.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_download_rate_limit_shared_by_downloads() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 128 * 1024]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let manager = loader.download_manager();
+        manager.set_global_rate_limit(Some(256 * 1024));
+        let (tx, mut events) = mpsc::unbounded_channel();
+        manager.set_event_sender(tx).await;
+
+        let directory =
+            std::env::temp_dir().join(format!("rustkit-rate-limit-{}", std::process::id()));
+        let start = std::time::Instant::now();
+        for name in ["a.bin", "b.bin"] {
+            let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+            manager
+                .start_response(response, directory.join(name))
+                .await
+                .unwrap();
+        }
+        let mut completed = 0;
+        let mut fastest: f64 = 0.0;
+        while completed < 2 {
+            match events.recv().await.unwrap() {
+                DownloadEvent::Completed { .. } => completed += 1,
+                DownloadEvent::Progress { progress, .. } if progress.downloaded >= 64 * 1024 => {
+                    fastest = fastest.max(progress.speed_bps);
+                }
+                DownloadEvent::Failed { error, .. } => panic!("{}", error),
+                _ => {}
+            }
+        }
+        // 256 KB at 256 KB/s, with each download held to about half
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        assert!(fastest < 200.0 * 1024.0, "{} B/s", fastest);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn test_offline_aborts_transfer() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 100 * 1024]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.set_network_conditions(NetworkConditions {
+            download_bps: Some(10 * 1024),
+            ..Default::default()
+        });
+
+        let mut response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(response.chunk().await.unwrap().is_some());
+
+        loader.set_network_conditions(NetworkConditions::offline());
+        assert!(matches!(response.chunk().await, Err(NetError::Offline)));
+        assert!(matches!(
+            loader.fetch(Request::get(url)).await,
+            Err(NetError::Offline)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_mid_body_stops_progress() {
+        let (_server, url) = spawn_http_server(vec![b'x'; 100 * 1024]).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.set_network_conditions(NetworkConditions {
+            download_bps: Some(10 * 1024),
+            ..Default::default()
+        });
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+
+        let (cancel, response) = loader.fetch_with_handle(Request::get(url));
+        let mut response = response.await.unwrap();
+        assert!(response.chunk().await.unwrap().is_some());
+        cancel.cancel();
+        assert!(matches!(response.chunk().await, Err(NetError::Cancelled)));
+        assert_eq!(response.chunk().await.unwrap(), None);
+        assert_eq!(loader.in_flight_count(), 0);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let events: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert!(
+            matches!(
+                events.as_slice(),
+                [
+                    NetEvent::Started { .. },
+                    NetEvent::Progress { received: 1024, .. },
+                    NetEvent::Cancelled { .. }
+                ]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_parts_and_cancel_closes_connection() {
+        use futures::StreamExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A raw socket, to see the client close the connection
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=b\r\n\r\n\
+                      --b\r\n\r\none\r\n--b\r\n\r\ntwo\r\n--b\r\n",
+                )
+                .await;
+            let _ = closed_tx.send(matches!(stream.read(&mut buf).await, Ok(0) | Err(_)));
+        });
+        let url = Url::parse(&format!("http://127.0.0.1:{}/poll", port)).unwrap();
+
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let request = Request::get(url).streaming();
+        let cancel = request.cancel_token();
+        let response = loader.fetch(request).await.unwrap();
+        let mut parts = Box::pin(response.parts().unwrap());
+        assert_eq!(parts.next().await.unwrap().unwrap().body, "one");
+        assert_eq!(parts.next().await.unwrap().unwrap().body, "two");
+
+        // The body never ends; cancelling hangs up on the server
+        cancel.cancel();
+        assert!(matches!(parts.next().await, Some(Err(NetError::Cancelled))));
+        let closed = tokio::time::timeout(Duration::from_secs(5), closed_rx).await;
+        assert!(matches!(closed, Ok(Ok(true))));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_for_view() {
+        // Accepts connections and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let url = Url::parse(&format!("http://127.0.0.1:{}/slow", port)).unwrap();
+
+        let loader = Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap());
+        let fetch = |view_id| {
+            let loader = loader.clone();
+            let request = Request::get(url.clone()).for_view(view_id);
+            tokio::spawn(async move { loader.fetch(request).await })
+        };
+        let (view_1, view_2) = (fetch(1), fetch(2));
+        while loader.in_flight_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(loader.cancel_all_for_view(1), 1);
+        assert!(matches!(view_1.await.unwrap(), Err(NetError::Cancelled)));
+        assert_eq!(loader.in_flight_count(), 1);
+        assert!(!view_2.is_finished());
+        assert_eq!(loader.cancel_all_for_view(2), 1);
+        assert!(matches!(view_2.await.unwrap(), Err(NetError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_after_completion_is_noop() {
+        let (_server, url) = spawn_http_server(b"done".to_vec()).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let request = Request::get(url).for_view(3);
+        let cancel = request.cancel_token();
+
+        let response = loader.fetch(request).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+        cancel.cancel();
+        assert_eq!(loader.cancel_all_for_view(3), 0);
+        assert_eq!(loader.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_preconnect_hint_pools_connection() {
+        let (port, connections) = spawn_tls_server_with(CERT_A, None).await;
+        let url = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+        let loader = trusting_loader(TlsConfig::default());
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+
+        let hint = ResourceHint {
+            kind: HintKind::Preconnect,
+            url: url.clone(),
+            anonymous: false,
+            destination: None,
+        };
+        loader.apply_hint(&hint, Some(1), None).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        assert!(loader.client().has_preconnected(&url, Partition::default()));
+        // Anonymous requests get their own connections
+        assert!(!loader.client().has_preconnected(
+            &url,
+            Partition {
+                anonymous: true,
+                site: None
+            }
+        ));
+        let event = events.try_recv().unwrap();
+        assert!(matches!(event, NetEvent::Hinted { skipped: None, .. }));
+        assert_eq!(event.initiator(), Some(Initiator::Hint));
+
+        // A second hint for the origin has nothing left to do
+        loader.apply_hint(&hint, Some(1), None).await.unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(NetEvent::Hinted {
+                skipped: Some(_),
+                ..
+            })
+        ));
+
+        // The first request goes out on the waiting connection
+        let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(response.tls.as_ref().unwrap().connection_reused);
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        assert!(!loader.client().has_preconnected(&url, Partition::default()));
+    }
+
+    #[tokio::test]
+    async fn test_preconnected_request_not_replayed_after_response_starts() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Every connection dies partway through its response head
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let url = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+        let connections = Arc::new(AtomicU64::new(0));
+        let requests = Arc::new(AtomicU64::new(0));
+        let accepted_notify = Arc::new(tokio::sync::Notify::new());
+        let (accepted, received, notify) = (
+            Arc::clone(&connections),
+            Arc::clone(&requests),
+            Arc::clone(&accepted_notify),
+        );
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                notify.notify_one();
+                let received = Arc::clone(&received);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    if matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {
+                        received.fetch_add(1, Ordering::SeqCst);
+                    }
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Le").await;
+                });
+            }
+        });
+
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let hint = ResourceHint {
+            kind: HintKind::Preconnect,
+            url: url.clone(),
+            anonymous: false,
+            destination: None,
+        };
+        loader.apply_hint(&hint, Some(1), None).await.unwrap();
+        // The connect can finish before the server's accept loop runs
+        tokio::time::timeout(Duration::from_secs(5), accepted_notify.notified())
+            .await
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // The server saw the request, so it is not sent again
+        let request = Request::post(url, Bytes::from_static(b"once"));
+        assert!(loader.fetch(request).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    /// Serve `/` as `103 Early Hints` preloading `/style.css`, then, once
+    /// the stylesheet was served or a second passed, the document.
+    ///
+    /// A raw socket, since a [`MockServer`] can't send interim responses.
+    async fn spawn_early_hints_server() -> (Url, Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let styled = Arc::new(tokio::sync::Notify::new());
+        let server_log = log.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (log, styled) = (server_log.clone(), styled.clone());
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..n]);
+                    let target = head.split(' ').nth(1).unwrap_or("/").to_string();
+                    log.lock().unwrap().push(format!("request {}", target));
+                    if target == "/style.css" {
+                        styled.notify_one();
+                        let _ = stream
+                            .write_all(
+                                b"HTTP/1.1 200 OK\r\nContent-Type: text/css\r\n\
+                                  Content-Length: 6\r\nConnection: close\r\n\r\nbody{}",
+                            )
+                            .await;
+                        return;
+                    }
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 103 Early Hints\r\nX-Interim: yes\r\n\
+                              Link: </style.css>; rel=preload; as=style\r\n\r\n",
+                        )
+                        .await;
+                    let _ = tokio::time::timeout(Duration::from_secs(1), styled.notified()).await;
+                    // Let the preload land in the cache
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    log.lock().unwrap().push("document body".to_string());
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\
+                              Content-Length: 4\r\nConnection: close\r\n\r\npage",
+                        )
+                        .await;
+                });
+            }
+        });
+        (
+            Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap(),
+            log,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_early_hints_preload_before_document() {
+        let (url, log) = spawn_early_hints_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+
+        let response = loader
+            .fetch(Request::get(url.clone()).navigation())
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.headers.get("x-interim").is_none());
+        assert_eq!(response.early_hints.len(), 1);
+        assert_eq!(response.text().await.unwrap(), "page");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["request /", "request /style.css", "document body"]
+        );
+
+        let mut hinted = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let NetEvent::EarlyHintsReceived { hints, .. } = event {
+                hinted.extend(hints.into_iter().map(|h| (h.kind, h.destination)));
+            }
+        }
+        assert_eq!(
+            hinted,
+            [(HintKind::Preload, Some(PreloadDestination::Style))]
+        );
+
+        // The page's own request for the stylesheet finds it preloaded
+        let style = url.join("/style.css").unwrap();
+        let request = Request::get(style).top_level_site(Site::from_url(&url).unwrap());
+        let response = loader.fetch(request).await.unwrap();
+        assert!(response.from_cache);
+        assert_eq!(response.text().await.unwrap(), "body{}");
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_early_hints_only_preload_for_navigations() {
+        let (url, log) = spawn_early_hints_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let response = loader.fetch(Request::get(url)).await.unwrap();
+        assert_eq!(response.early_hints.len(), 1);
+        assert_eq!(response.text().await.unwrap(), "page");
+        assert_eq!(*log.lock().unwrap(), ["request /", "document body"]);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_navigation_preloads_early_hints() {
+        let (url, log) = spawn_early_hints_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let response = loader
+            .fetch(Request::get(url).navigation().streaming())
+            .await
+            .unwrap();
+        assert_eq!(response.early_hints.len(), 1);
+        assert_eq!(response.text().await.unwrap(), "page");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["request /", "request /style.css", "document body"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_speculative_hints_are_counted() {
+        let (_server, url) = spawn_http_server(b"img{}".to_vec()).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let hint = ResourceHint {
+            kind: HintKind::Preload,
+            url: url.clone(),
+            anonymous: false,
+            destination: Some(PreloadDestination::Style),
+        };
+
+        loader
+            .apply_speculative_hint(&hint, None, None)
+            .await
+            .unwrap();
+        // Already fetched, so the second is only discovered
+        loader
+            .apply_speculative_hint(&hint, None, None)
+            .await
+            .unwrap();
+        let response = loader.fetch(Request::get(url)).await.unwrap();
+        assert!(response.from_cache);
+        assert_eq!(
+            loader.net_stats().speculative,
+            SpeculativeStats {
+                urls_discovered: 2,
+                fetches_started: 1,
+                cache_hits: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_hint_fills_cache() {
+        let (_server, url) = spawn_http_server(b"next page".to_vec()).await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+        let hint = ResourceHint {
+            kind: HintKind::Prefetch,
+            url: url.clone(),
+            anonymous: false,
+            destination: None,
+        };
+        // Hinted by a document on the site the navigation goes to
+        let site = Site::from_url(&url);
+
+        loader.apply_hint(&hint, None, site.as_ref()).await.unwrap();
+        let mut started = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let NetEvent::Started { initiator, .. } = event {
+                started.push(initiator);
+            }
+        }
+        assert_eq!(started, [Initiator::Hint]);
+
+        // The next navigation is answered without the network
+        let response = loader
+            .fetch(Request::get(url.clone()).navigation())
+            .await
+            .unwrap();
+        assert!(response.from_cache);
+        assert_eq!(response.text().await.unwrap(), "next page");
+        let response = loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert!(!response.from_cache);
+        drop(response);
+
+        // Slow networks skip prefetching
+        loader.set_network_conditions(NetworkConditions::slow_3g());
+        while events.try_recv().is_ok() {}
+        loader.apply_hint(&hint, None, None).await.unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(NetEvent::Hinted { skipped: Some(reason), .. }) if reason == "constrained network"
+        ));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_cache_is_partitioned_by_top_level_site() {
+        let (_server, url) = spawn_http_server(b"shared".to_vec()).await;
+        let site = |url: &str| Site::from_url(&Url::parse(url).unwrap()).unwrap();
+        let (a, b, other) = (
+            site("https://a.example.co.uk/"),
+            site("https://b.example.co.uk/"),
+            site("https://example.com/"),
+        );
+        let hint = ResourceHint {
+            kind: HintKind::Prefetch,
+            url: url.clone(),
+            anonymous: false,
+            destination: None,
+        };
+
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        loader.apply_hint(&hint, None, Some(&a)).await.unwrap();
+        loader.apply_hint(&hint, None, Some(&b)).await.unwrap();
+        assert_eq!(loader.cache_usage(), 6);
+
+        // Another site misses and gets an entry of its own
+        let request = Request::get(url.clone()).top_level_site(other.clone());
+        assert!(!loader.fetch(request).await.unwrap().from_cache);
+        loader.apply_hint(&hint, None, Some(&other)).await.unwrap();
+        assert_eq!(loader.cache_usage(), 12);
+
+        // The same registrable domain shares one
+        let request = Request::get(url.clone()).top_level_site(b);
+        assert!(loader.fetch(request).await.unwrap().from_cache);
+
+        // Opting out shares the cache across sites
+        let loader = ResourceLoader::new(LoaderConfig {
+            partition_caches: false,
+            ..Default::default()
+        })
+        .unwrap();
+        loader.apply_hint(&hint, None, Some(&a)).await.unwrap();
+        let request = Request::get(url).top_level_site(other);
+        assert!(loader.fetch(request).await.unwrap().from_cache);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_waits_for_critical_requests() {
+        // Accepts connections and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let document = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+        let (_next_server, next) = spawn_http_server(b"next".to_vec()).await;
+
+        let loader = Arc::new(ResourceLoader::new(LoaderConfig::default()).unwrap());
+        let request = Request::get(document).navigation();
+        let cancel = request.cancel_token();
+        let navigation = tokio::spawn({
+            let loader = loader.clone();
+            async move { loader.fetch(request).await }
+        });
+        while loader.in_flight_count() < 1 {
+            tokio::task::yield_now().await;
+        }
+
+        let hint = ResourceHint {
+            kind: HintKind::Prefetch,
+            url: next,
+            anonymous: false,
+            destination: None,
+        };
+        let prefetch = tokio::time::timeout(
+            Duration::from_millis(200),
+            loader.apply_hint(&hint, None, None),
+        )
+        .await;
+        assert!(prefetch.is_err());
+        assert_eq!(loader.in_flight_count(), 1);
+
+        cancel.cancel();
+        assert!(navigation.await.unwrap().is_err());
+        loader.apply_hint(&hint, None, None).await.unwrap();
+        assert!(loader.prefetched.is_fresh(None, &hint.url));
+    }
+
+    /// Serve over TLS preferring `h2` via ALPN.
+    async fn spawn_h2_server() -> (u16, Arc<AtomicU64>) {
+        let identity = native_tls::Identity::from_pkcs8(CERT_A.0, CERT_A.1).unwrap();
+        let mut builder = native_tls::TlsAcceptor::builder(identity);
+        builder.accept_alpn(&["h2", "http/1.1"]);
+        serve_tls(builder.build().unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn test_origin_protocol_override() {
+        let (pinned_port, connections) = spawn_h2_server().await;
+        let (other_port, _) = spawn_h2_server().await;
+        let pinned = Url::parse(&format!("https://localhost:{}/", pinned_port)).unwrap();
+        let other = Url::parse(&format!("https://localhost:{}/", other_port)).unwrap();
+        let h2_tls = TlsConfig {
+            alpn_protocols: vec!["h2".to_string(), "http/1.1".to_string()],
+            ..Default::default()
+        };
+        let loader = trusting_loader(h2_tls.clone());
+        let alpn = |response: &Response| response.tls.as_ref().unwrap().alpn_protocol.clone();
+
+        let response = loader.fetch(Request::get(pinned.clone())).await.unwrap();
+        assert_eq!(alpn(&response).as_deref(), Some("h2"));
+
+        // The idle h2 connection is not reused once the origin is pinned
+        loader.set_origin_protocol_override(&pinned, ProtocolOverride::ForceHttp1);
+        let response = loader.fetch(Request::get(pinned.clone())).await.unwrap();
+        assert_eq!(alpn(&response).as_deref(), Some("http/1.1"));
+        assert!(!response.tls.as_ref().unwrap().connection_reused);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        let response = loader.fetch(Request::get(pinned.clone())).await.unwrap();
+        assert!(response.tls.as_ref().unwrap().connection_reused);
+
+        let response = loader.fetch(Request::get(other.clone())).await.unwrap();
+        assert_eq!(alpn(&response).as_deref(), Some("h2"));
+
+        let stats = loader.net_stats();
+        let pinned_stats = &stats.origins[&format!("https://localhost:{}", pinned_port)];
+        assert_eq!(pinned_stats.connections_opened, 2);
+        assert_eq!(pinned_stats.requests, 3);
+        assert_eq!(pinned_stats.protocol.as_deref(), Some("http/1.1"));
+        assert!((pinned_stats.requests_per_connection() - 1.5).abs() < f64::EPSILON);
+        let other_stats = &stats.origins[&format!("https://localhost:{}", other_port)];
+        assert_eq!(other_stats.protocol.as_deref(), Some("h2"));
+        assert_eq!(stats.requests(), 4);
+
+        // Saved preferences pin the origin in a new loader too
+        let saved = loader.protocol_preferences();
+        let restored = trusting_loader(h2_tls);
+        restored.restore_protocol_preferences(saved);
+        let response = restored.fetch(Request::get(pinned.clone())).await.unwrap();
+        assert_eq!(alpn(&response).as_deref(), Some("http/1.1"));
+
+        loader.set_origin_protocol_override(&pinned, ProtocolOverride::Default);
+        let response = loader.fetch(Request::get(pinned)).await.unwrap();
+        assert_eq!(alpn(&response).as_deref(), Some("h2"));
+    }
+
+    /// What the client did with the pushes of [`serve_h2`].
+    #[derive(Default)]
+    struct PushLog {
+        /// Streams the client opened.
+        streams: AtomicU64,
+        /// How the client reset the push, if it did.
+        reset: std::sync::Mutex<Option<h2::Reason>>,
+    }
+
+    /// Answer every request on an HTTP/2 connection with "ok". With `push`,
+    /// the response to `/` comes with a push of `/style.css`.
+    async fn serve_h2(
+        tls: tokio_native_tls::TlsStream<tokio::net::TcpStream>,
+        push: Option<Arc<PushLog>>,
+    ) {
+        let Ok(mut connection) = h2::server::handshake(tls).await else {
+            return;
+        };
+        while let Some(Ok((request, mut respond))) = connection.accept().await {
+            if let Some(log) = &push {
+                log.streams.fetch_add(1, Ordering::SeqCst);
+            }
+            let push = push.clone();
+            tokio::spawn(async move {
+                if let Some(log) = push.filter(|_| request.uri().path() == "/") {
+                    let authority = request.uri().authority().unwrap();
+                    let promise = http::Request::get(format!("https://{}/style.css", authority))
+                        .body(())
+                        .unwrap();
+                    let mut pushed = respond.push_request(promise).unwrap();
+                    // Give the client time to reset the promise
+                    let reset = tokio::time::timeout(
+                        Duration::from_millis(200),
+                        std::future::poll_fn(|cx| pushed.poll_reset(cx)),
+                    )
+                    .await;
+                    if let Ok(Ok(reason)) = reset {
+                        *log.reset.lock().unwrap() = Some(reason);
+                    } else {
+                        let response = http::Response::builder()
+                            .header("content-type", "text/css")
+                            .body(())
+                            .unwrap();
+                        let mut body = pushed.send_response(response, false).unwrap();
+                        body.send_data(Bytes::from_static(b"p{}"), true).unwrap();
+                    }
+                }
+                let Ok(mut body) = respond.send_response(http::Response::new(()), false) else {
+                    return;
+                };
+                let _ = body.send_data(Bytes::from_static(b"ok"), true);
+            });
+        }
+    }
+
+    /// Serve HTTP/2 only, pushing `/style.css` with `/`.
+    async fn spawn_push_server() -> (u16, Arc<PushLog>) {
+        let identity = native_tls::Identity::from_pkcs8(CERT_A.0, CERT_A.1).unwrap();
+        let mut builder = native_tls::TlsAcceptor::builder(identity);
+        builder.accept_alpn(&["h2"]);
+        let acceptor = tokio_native_tls::TlsAcceptor::from(builder.build().unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log = Arc::new(PushLog::default());
+
+        let server_log = Arc::clone(&log);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                let log = Arc::clone(&server_log);
+                tokio::spawn(async move {
+                    if let Ok(tls) = acceptor.accept(stream).await {
+                        serve_h2(tls, Some(log)).await;
+                    }
+                });
+            }
+        });
+        (port, log)
+    }
+
+    fn h2_loader_config(accept_server_push: bool) -> LoaderConfig {
+        LoaderConfig {
+            tls: TlsConfig {
+                alpn_protocols: vec!["h2".to_string()],
+                ..Default::default()
+            },
+            accept_server_push,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pushed_response_answers_next_request() {
+        let (port, log) = spawn_push_server().await;
+        let loader = trusting_loader_with(h2_loader_config(true));
+        let (tx, mut events) = mpsc::unbounded_channel();
+        loader.set_event_sender(Some(tx));
+        let page = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+
+        let response = loader.fetch(Request::get(page.clone())).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let style = page.join("style.css").unwrap();
+        let response = loader.fetch(Request::get(style)).await.unwrap();
+        assert!(response.from_cache);
+        assert_eq!(response.text().await.unwrap(), "p{}");
+
+        // The stylesheet came without a stream of its own
+        assert_eq!(log.streams.load(Ordering::SeqCst), 1);
+        assert_eq!(*log.reset.lock().unwrap(), None);
+        let stats = loader.net_stats().pushes;
+        assert_eq!((stats.accepted, stats.used, stats.rejected), (1, 1, 0));
+        let pushed: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| event.initiator() == Some(Initiator::Push))
+            .collect();
+        assert!(
+            matches!(
+                pushed.as_slice(),
+                [NetEvent::Started { .. }, NetEvent::ServedFromPush { .. }]
+            ),
+            "{:?}",
+            pushed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_declined_push_is_reset() {
+        let (port, log) = spawn_push_server().await;
+        let loader = trusting_loader_with(h2_loader_config(false));
+        let page = Url::parse(&format!("https://localhost:{}/", port)).unwrap();
+
+        let response = loader.fetch(Request::get(page.clone())).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(*log.reset.lock().unwrap(), Some(h2::Reason::CANCEL));
+        let stats = loader.net_stats().pushes;
+        assert_eq!((stats.accepted, stats.rejected), (0, 1));
+
+        let style = page.join("style.css").unwrap();
+        let response = loader.fetch(Request::get(style)).await.unwrap();
+        assert!(!response.from_cache);
+        assert_eq!(log.streams.load(Ordering::SeqCst), 2);
+    }
+
+    /// Answer every request with an empty page carrying `set_cookies` as
+    /// `Set-Cookie` headers.
+    async fn spawn_set_cookie_server(set_cookies: &[&str]) -> MockServer {
+        let server = MockServer::start().await;
+        let response = set_cookies
+            .iter()
+            .fold(ResponseTemplate::new(200), |response, cookie| {
+                response.append_header("Set-Cookie", *cookie)
+            });
+        Mock::given(any())
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn cookie_line(head: &str) -> Option<&str> {
+        head.lines().find_map(|line| line.strip_prefix("cookie: "))
+    }
+
+    #[tokio::test]
+    async fn test_script_cookies_go_out_with_requests() {
+        let (url, heads) = spawn_head_recording_server().await;
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let top = Site::from_url(&url);
+
+        assert!(loader.set_document_cookie(1, &url, top.as_ref(), "theme=dark; path=/"));
+        // Another site's domain is silently ignored
+        assert!(!loader.set_document_cookie(1, &url, top.as_ref(), "evil=1; domain=other.com"));
+        assert_eq!(loader.document_cookie(1, &url, top.as_ref()), "theme=dark");
+
+        loader.fetch(Request::get(url.clone())).await.unwrap();
+        // Omitted credentials and blocked third-party contexts go without
+        let mut anonymous = Request::get(url.clone());
+        anonymous.credentials = CredentialsMode::Omit;
+        loader.fetch(anonymous).await.unwrap();
+        loader.set_third_party_cookies_blocked(2, true);
+        let mut third_party = Request::get(url.clone());
+        third_party.view_id = Some(2);
+        third_party.top_level_site = Site::from_url(&Url::parse("https://example.com/").unwrap());
+        loader.fetch(third_party).await.unwrap();
+
+        let heads = heads.lock().unwrap();
+        assert_eq!(cookie_line(&heads[0]), Some("theme=dark"));
+        assert_eq!(cookie_line(&heads[1]), None);
+        assert_eq!(cookie_line(&heads[2]), None);
+    }
+
+    #[tokio::test]
+    async fn test_http_only_cookies_are_sent_but_hidden_from_script() {
+        let server =
+            spawn_set_cookie_server(&["session=abc; HttpOnly; Path=/", "lang=en; Path=/"]).await;
+        let url = Url::parse(&format!("{}/", server.uri())).unwrap();
+        let loader = ResourceLoader::new(LoaderConfig::default()).unwrap();
+        let top = Site::from_url(&url);
+
+        loader.fetch(Request::get(url.clone())).await.unwrap();
+        assert_eq!(loader.document_cookie(1, &url, top.as_ref()), "lang=en");
+        // Nor can script replace it
+        assert!(!loader.set_document_cookie(1, &url, top.as_ref(), "session=evil; path=/"));
+
+        loader.fetch(Request::get(url.clone())).await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let cookie = |request: &wiremock::Request| {
+            let value = request.headers.get("cookie")?;
+            Some(value.to_str().unwrap().to_string())
+        };
+        assert_eq!(cookie(&requests[0]), None);
+        assert_eq!(
+            cookie(&requests[1]).as_deref(),
+            Some("session=abc; lang=en")
+        );
+    }
 }
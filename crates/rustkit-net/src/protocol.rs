@@ -0,0 +1,304 @@
+//! Per-origin protocol preferences and connection reuse statistics.
+//!
+//! Origins advertise alternative services with `Alt-Svc`; those are recorded
+//! with their max-age. Independently, an origin can be pinned to HTTP/1.1,
+//! either by the embedder or automatically after repeated protocol errors on
+//! an `h2` connection. The preferences serialize with serde so embedders can
+//! persist them across sessions.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// How long an `Alt-Svc` entry without `ma` stays fresh.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Consecutive protocol errors on an `h2` origin before it is pinned to
+/// HTTP/1.1.
+const H2_FAILURE_LIMIT: u32 = 2;
+
+/// An alternative service advertised with `Alt-Svc`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AltService {
+    /// ALPN protocol ID, e.g. `h3` or `h2`.
+    pub protocol: String,
+    /// Alternative host, or `None` for the origin's own host.
+    pub host: Option<String>,
+    /// Alternative port.
+    pub port: u16,
+    /// When the advertisement goes stale.
+    pub expires: SystemTime,
+}
+
+/// A parsed `Alt-Svc` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AltSvc {
+    /// `clear`: forget every alternative for the origin.
+    Clear,
+    /// Alternatives in order of the server's preference.
+    Services(Vec<AltService>),
+}
+
+/// Parse an `Alt-Svc` header value received at `now`.
+///
+/// Malformed entries are skipped; `None` if nothing usable remains.
+pub fn parse_alt_svc(value: &str, now: SystemTime) -> Option<AltSvc> {
+    if value.trim().eq_ignore_ascii_case("clear") {
+        return Some(AltSvc::Clear);
+    }
+
+    let services: Vec<AltService> = value
+        .split(',')
+        .filter_map(|entry| parse_alt_service(entry, now))
+        .collect();
+    (!services.is_empty()).then_some(AltSvc::Services(services))
+}
+
+fn parse_alt_service(entry: &str, now: SystemTime) -> Option<AltService> {
+    let mut params = entry.split(';');
+    let (protocol, authority) = params.next()?.split_once('=')?;
+    let protocol = protocol.trim();
+    let authority = authority.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let (host, port) = authority.rsplit_once(':')?;
+    if protocol.is_empty() {
+        return None;
+    }
+
+    let mut max_age = DEFAULT_MAX_AGE;
+    for param in params {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("ma") {
+                max_age = Duration::from_secs(value.trim().trim_matches('"').parse().ok()?);
+            }
+        }
+    }
+
+    Some(AltService {
+        protocol: protocol.to_string(),
+        host: (!host.is_empty()).then(|| host.to_ascii_lowercase()),
+        port: port.parse().ok()?,
+        expires: now + max_age,
+    })
+}
+
+/// Protocol policy for new connections to an origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ProtocolOverride {
+    /// Negotiate from the configured ALPN protocols.
+    #[default]
+    Default,
+    /// Offer only `http/1.1`.
+    ForceHttp1,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct OriginProtocol {
+    alt_services: Vec<AltService>,
+    protocol_override: ProtocolOverride,
+    /// Protocol errors in a row on an `h2` connection.
+    #[serde(skip)]
+    h2_failures: u32,
+}
+
+/// Alternative services and protocol overrides, keyed by serialized origin.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProtocolPreferences {
+    origins: HashMap<String, OriginProtocol>,
+}
+
+impl ProtocolPreferences {
+    /// Record an `Alt-Svc` header received from `origin`.
+    pub fn record_alt_svc(&mut self, origin: &str, alt_svc: AltSvc) {
+        let services = match alt_svc {
+            AltSvc::Clear => Vec::new(),
+            AltSvc::Services(services) => services,
+        };
+        self.origins
+            .entry(origin.to_string())
+            .or_default()
+            .alt_services = services;
+    }
+
+    /// Unexpired alternative services advertised by `origin`.
+    pub fn alt_services(&self, origin: &str, now: SystemTime) -> Vec<AltService> {
+        self.origins
+            .get(origin)
+            .map(|o| {
+                o.alt_services
+                    .iter()
+                    .filter(|s| s.expires > now)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set the protocol policy for `origin`.
+    pub fn set_override(&mut self, origin: &str, protocol_override: ProtocolOverride) {
+        let entry = self.origins.entry(origin.to_string()).or_default();
+        entry.protocol_override = protocol_override;
+        entry.h2_failures = 0;
+    }
+
+    /// The protocol policy for `origin`.
+    pub fn override_for(&self, origin: &str) -> ProtocolOverride {
+        self.origins
+            .get(origin)
+            .map(|o| o.protocol_override)
+            .unwrap_or_default()
+    }
+
+    /// Origins pinned to HTTP/1.1.
+    pub fn forced_http1_origins(&self) -> impl Iterator<Item = &str> {
+        self.origins
+            .iter()
+            .filter(|(_, o)| o.protocol_override == ProtocolOverride::ForceHttp1)
+            .map(|(origin, _)| origin.as_str())
+    }
+
+    /// Drop expired alternative services and origins with nothing left.
+    pub fn prune(&mut self, now: SystemTime) {
+        self.origins.retain(|_, o| {
+            o.alt_services.retain(|s| s.expires > now);
+            !o.alt_services.is_empty() || o.protocol_override != ProtocolOverride::Default
+        });
+    }
+
+    /// Count a protocol error on an `h2` connection to `origin`. Returns
+    /// whether the origin should now be pinned to HTTP/1.1.
+    pub(crate) fn record_h2_failure(&mut self, origin: &str) -> bool {
+        let entry = self.origins.entry(origin.to_string()).or_default();
+        entry.h2_failures += 1;
+        entry.protocol_override == ProtocolOverride::Default
+            && entry.h2_failures >= H2_FAILURE_LIMIT
+    }
+
+    /// Reset the error count after a successful response from `origin`.
+    pub(crate) fn record_success(&mut self, origin: &str) {
+        if let Some(entry) = self.origins.get_mut(origin) {
+            entry.h2_failures = 0;
+        }
+    }
+}
+
+/// Connection reuse counters for one origin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OriginStats {
+    /// New connections opened.
+    pub connections_opened: u64,
+    /// Requests completed, on new or reused connections.
+    pub requests: u64,
+    /// Protocol negotiated for the most recent request.
+    pub protocol: Option<String>,
+}
+
+impl OriginStats {
+    /// Average number of requests carried by each connection.
+    pub fn requests_per_connection(&self) -> f64 {
+        if self.connections_opened == 0 {
+            return 0.0;
+        }
+        self.requests as f64 / self.connections_opened as f64
+    }
+}
+
+/// Network statistics collected by a [`crate::ResourceLoader`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetStats {
+    /// Per-origin connection counters, keyed by serialized origin.
+    pub origins: HashMap<String, OriginStats>,
+    /// HTTP/2 server push counters.
+    pub pushes: crate::push::PushStats,
+    /// Preload scanner counters.
+    pub speculative: crate::hints::SpeculativeStats,
+    /// Keep-alive connections open and idle by origin.
+    pub pool: rustkit_http::PoolStats,
+}
+
+impl NetStats {
+    /// Count a completed request to `origin`.
+    pub(crate) fn record(&mut self, origin: &str, protocol: &str, reused: bool) {
+        let stats = self.origins.entry(origin.to_string()).or_default();
+        if !reused {
+            stats.connections_opened += 1;
+        }
+        stats.requests += 1;
+        stats.protocol = Some(protocol.to_string());
+    }
+
+    /// Total connections opened across all origins.
+    pub fn connections_opened(&self) -> u64 {
+        self.origins.values().map(|s| s.connections_opened).sum()
+    }
+
+    /// Total requests across all origins.
+    pub fn requests(&self) -> u64 {
+        self.origins.values().map(|s| s.requests).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alt_svc() {
+        let now = SystemTime::UNIX_EPOCH;
+        let parsed = parse_alt_svc(
+            r#"h3=":443"; ma=3600, h2="alt.Example.com:8443"; persist=1, bogus"#,
+            now,
+        );
+        let Some(AltSvc::Services(services)) = parsed else {
+            panic!("{:?}", parsed);
+        };
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].protocol, "h3");
+        assert_eq!(services[0].host, None);
+        assert_eq!(services[0].port, 443);
+        assert_eq!(services[0].expires, now + Duration::from_secs(3600));
+        assert_eq!(services[1].host.as_deref(), Some("alt.example.com"));
+        assert_eq!(services[1].expires, now + DEFAULT_MAX_AGE);
+
+        assert_eq!(parse_alt_svc("clear", now), Some(AltSvc::Clear));
+        assert_eq!(parse_alt_svc("h3=443", now), None);
+    }
+
+    #[test]
+    fn test_preferences_expire_and_round_trip() {
+        let now = SystemTime::UNIX_EPOCH;
+        let origin = "https://example.com";
+        let mut prefs = ProtocolPreferences::default();
+        prefs.record_alt_svc(origin, parse_alt_svc(r#"h3=":443"; ma=60"#, now).unwrap());
+        prefs.set_override("https://broken.example", ProtocolOverride::ForceHttp1);
+        assert_eq!(prefs.alt_services(origin, now).len(), 1);
+        assert!(prefs
+            .alt_services(origin, now + Duration::from_secs(61))
+            .is_empty());
+
+        let json = serde_json::to_string(&prefs).unwrap();
+        let mut restored: ProtocolPreferences = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.override_for("https://broken.example"),
+            ProtocolOverride::ForceHttp1
+        );
+        assert_eq!(
+            restored.alt_services(origin, now),
+            prefs.alt_services(origin, now)
+        );
+
+        restored.prune(now + Duration::from_secs(61));
+        assert_eq!(restored.origins.len(), 1);
+        restored.record_alt_svc(origin, AltSvc::Clear);
+        assert!(restored.alt_services(origin, now).is_empty());
+    }
+
+    #[test]
+    fn test_h2_failures_pin_after_consecutive_errors() {
+        let origin = "https://flaky.example";
+        let mut prefs = ProtocolPreferences::default();
+        assert!(!prefs.record_h2_failure(origin));
+        prefs.record_success(origin);
+        assert!(!prefs.record_h2_failure(origin));
+        assert!(prefs.record_h2_failure(origin));
+    }
+}
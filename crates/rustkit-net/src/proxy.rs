@@ -0,0 +1,857 @@
+//! Proxy configuration: fixed proxies, the Windows per-user settings and
+//! proxy auto-config (PAC) scripts.
+//!
+//! The loader gives its HTTP client a [`ProxyResolver`] that names the
+//! proxies for each request. A PAC script is fetched directly the first
+//! time it is needed, and again a minute after a fetch that failed, and
+//! runs on a thread of its own, in a bare script runtime with only the
+//! PAC helper functions. Finding the proxies for a
+//! request never takes longer than the PAC timeout: a script that is slow,
+//! missing or broken sends the request `DIRECT`, or through the fixed
+//! proxies the system settings also name.
+//!
+//! Results are cached per host and port for a few minutes. A proxy that
+//! fails is tried after the others until its cooldown ends.
+
+use std::collections::HashMap;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use rustkit_http::{Client as HttpClient, ProxyResolver, ProxyServer};
+use rustkit_js::{JsRuntime, JsValue};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+use url::Url;
+
+/// How long a PAC result is reused for the same host and port.
+const PAC_CACHE_TTL: Duration = Duration::from_secs(300);
+/// Most PAC results cached at once.
+const MAX_CACHED: usize = 1024;
+/// How long a failed proxy is tried last.
+const PROXY_RETRY_AFTER: Duration = Duration::from_secs(300);
+/// Loop iterations a PAC call may run before it is abandoned.
+const MAX_PAC_LOOP_ITERATIONS: u64 = 1_000_000;
+/// How long after a failed fetch of the PAC script it is fetched again.
+const PAC_FETCH_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Where WPAD looks for a PAC script when auto-detect is on.
+const WPAD_URL: &str = "http://wpad/wpad.dat";
+
+/// How requests find their proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxyMode {
+    /// Connect directly.
+    #[default]
+    Off,
+    /// Fixed proxies.
+    Manual(ManualProxy),
+    /// The current user's system settings.
+    System,
+    /// The PAC script at this URL.
+    PacUrl(String),
+}
+
+/// Fixed proxies, with hosts that bypass them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManualProxy {
+    /// Proxy for `http` URLs, as `host:port`.
+    pub http: Option<String>,
+    /// Proxy for `https` URLs, as `host:port`.
+    pub https: Option<String>,
+    /// Hosts reached directly: names with `*` wildcards, or `<local>` for
+    /// names without a dot.
+    pub bypass: Vec<String>,
+}
+
+impl ManualProxy {
+    /// Parse proxy settings in the Windows format: `host:port` for every
+    /// scheme or `http=host:port;https=host:port`, and a bypass list
+    /// separated by semicolons.
+    pub fn parse(proxy: &str, bypass: &str) -> Self {
+        let mut manual = ManualProxy::default();
+        for entry in proxy.split([';', ' ']).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((scheme, server)) if scheme.eq_ignore_ascii_case("http") => {
+                    manual.http = Some(server.to_string());
+                }
+                Some((scheme, server)) if scheme.eq_ignore_ascii_case("https") => {
+                    manual.https = Some(server.to_string());
+                }
+                Some(_) => {}
+                None => {
+                    manual.http = Some(entry.to_string());
+                    manual.https = Some(entry.to_string());
+                }
+            }
+        }
+        manual.bypass = bypass
+            .split([';', ' ', ','])
+            .filter(|e| !e.is_empty())
+            .map(str::to_string)
+            .collect();
+        manual
+    }
+
+    /// The proxy for `url`, or `DIRECT`.
+    pub fn proxies_for(&self, url: &Url) -> Vec<ProxyServer> {
+        let host = url.host_str().unwrap_or_default();
+        if self.bypass.iter().any(|pattern| bypasses(pattern, host)) {
+            return vec![ProxyServer::Direct];
+        }
+        let server = match url.scheme() {
+            "https" | "wss" => &self.https,
+            _ => &self.http,
+        };
+        let proxy = server.as_deref().and_then(|s| parse_server(s, 80));
+        vec![proxy.unwrap_or(ProxyServer::Direct)]
+    }
+}
+
+/// Whether a bypass list entry covers `host`.
+fn bypasses(pattern: &str, host: &str) -> bool {
+    if pattern.eq_ignore_ascii_case("<local>") {
+        return !host.contains('.');
+    }
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    wildcard_match(&pattern.to_ascii_lowercase(), &host.to_ascii_lowercase())
+}
+
+/// Match `text` against a pattern where `*` stands for any run of
+/// characters.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    if !text.starts_with(first) || text.len() < first.len() + last.len() {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    if !text.ends_with(last) {
+        return false;
+    }
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Parse `host:port` or `[v6]:port`.
+fn parse_server(server: &str, default_port: u16) -> Option<ProxyServer> {
+    let server = server.trim();
+    let server = server
+        .split_once("://")
+        .map_or(server, |(_, rest)| rest)
+        .trim_end_matches('/');
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (server, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return None;
+    }
+    Some(ProxyServer::Http {
+        host: host.to_string(),
+        port,
+    })
+}
+
+/// Parse what `FindProxyForURL` returned, such as
+/// `PROXY a:8080; PROXY b:8080; DIRECT`. Proxy types other than HTTP are
+/// left out.
+pub fn parse_pac_result(result: &str) -> Vec<ProxyServer> {
+    result
+        .split(';')
+        .filter_map(|entry| {
+            let mut parts = entry.split_whitespace();
+            match parts.next()?.to_ascii_uppercase().as_str() {
+                "DIRECT" => Some(ProxyServer::Direct),
+                "PROXY" | "HTTP" => parse_server(parts.next()?, 80),
+                kind => {
+                    debug!(kind, "Unsupported proxy type");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The current user's proxy settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemProxySettings {
+    /// Find a PAC script with WPAD.
+    pub auto_detect: bool,
+    /// URL of a PAC script.
+    pub pac_url: Option<String>,
+    /// Fixed proxies, used when no script is configured or it fails.
+    pub manual: Option<ManualProxy>,
+}
+
+impl SystemProxySettings {
+    /// Read the settings shared by WinINET and WinHTTP. `None` when they
+    /// cannot be read, and always on other platforms.
+    #[cfg(windows)]
+    pub fn current() -> Option<Self> {
+        use windows::core::PWSTR;
+        use windows::Win32::Foundation::{GlobalFree, HGLOBAL};
+        use windows::Win32::Networking::WinHttp::{
+            WinHttpGetIEProxyConfigForCurrentUser, WINHTTP_CURRENT_USER_IE_PROXY_CONFIG,
+        };
+
+        let mut config = WINHTTP_CURRENT_USER_IE_PROXY_CONFIG::default();
+        // SAFETY: WinHTTP allocates the strings with GlobalAlloc; each is
+        // copied once and freed.
+        unsafe {
+            WinHttpGetIEProxyConfigForCurrentUser(&mut config).ok()?;
+            let take = |s: PWSTR| {
+                if s.is_null() {
+                    return None;
+                }
+                let value = s.to_string().ok().filter(|v| !v.is_empty());
+                let _ = GlobalFree(HGLOBAL(s.0.cast()));
+                value
+            };
+            let pac_url = take(config.lpszAutoConfigUrl);
+            let proxy = take(config.lpszProxy);
+            let bypass = take(config.lpszProxyBypass);
+            Some(Self {
+                auto_detect: config.fAutoDetect.as_bool(),
+                pac_url,
+                manual: proxy
+                    .map(|proxy| ManualProxy::parse(&proxy, bypass.as_deref().unwrap_or(""))),
+            })
+        }
+    }
+
+    /// Read the settings shared by WinINET and WinHTTP. `None` when they
+    /// cannot be read, and always on other platforms.
+    #[cfg(not(windows))]
+    pub fn current() -> Option<Self> {
+        None
+    }
+
+    /// Where the settings send requests: a configured script first, then
+    /// one found by WPAD, then the fixed proxies.
+    fn rules(&self) -> Option<Rules> {
+        let pac_url = self
+            .pac_url
+            .clone()
+            .or_else(|| self.auto_detect.then(|| WPAD_URL.to_string()));
+        match (pac_url, &self.manual) {
+            (Some(url), fallback) => Some(Rules::Pac {
+                url,
+                fallback: fallback.clone(),
+            }),
+            (None, Some(manual)) => Some(Rules::Manual(manual.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The resolver for `mode`, or `None` to connect directly.
+pub(crate) fn resolver(mode: &ProxyMode, pac_timeout: Duration) -> Option<Arc<dyn ProxyResolver>> {
+    let rules = match mode {
+        ProxyMode::Off => return None,
+        ProxyMode::Manual(manual) => Rules::Manual(manual.clone()),
+        ProxyMode::System => SystemProxySettings::current()?.rules()?,
+        ProxyMode::PacUrl(url) => Rules::Pac {
+            url: url.clone(),
+            fallback: None,
+        },
+    };
+    Some(Arc::new(Proxies {
+        rules,
+        pac_timeout,
+        script: Arc::new(tokio::sync::Mutex::new(PacScript::Unfetched)),
+        cache: Mutex::new(HashMap::new()),
+        failed: Mutex::new(HashMap::new()),
+    }))
+}
+
+enum Rules {
+    Manual(ManualProxy),
+    Pac {
+        url: String,
+        /// Used when the script cannot be loaded or run.
+        fallback: Option<ManualProxy>,
+    },
+}
+
+/// PAC results by host and port, with when they were found.
+type PacCache = HashMap<(String, u16), (Vec<ProxyServer>, Instant)>;
+
+/// The PAC script of a resolver, as far as it got.
+enum PacScript {
+    /// Not needed yet.
+    Unfetched,
+    /// Queue of the thread running the script.
+    Running(mpsc::Sender<PacJob>),
+    /// The script could not be fetched or loaded at this time.
+    Failed(Instant),
+}
+
+/// A call of `FindProxyForURL` for the PAC thread.
+struct PacJob {
+    url: String,
+    host: String,
+    reply: oneshot::Sender<Option<String>>,
+}
+
+/// Proxies from fixed rules or a PAC script, with failed ones tried last.
+struct Proxies {
+    rules: Rules,
+    pac_timeout: Duration,
+    /// Held while the script is fetched, so lookups wait for one fetch.
+    script: Arc<tokio::sync::Mutex<PacScript>>,
+    cache: Mutex<PacCache>,
+    failed: Mutex<HashMap<ProxyServer, Instant>>,
+}
+
+impl Proxies {
+    async fn lookup(&self, url: &Url) -> Vec<ProxyServer> {
+        let (pac_url, fallback) = match &self.rules {
+            Rules::Manual(manual) => return manual.proxies_for(url),
+            Rules::Pac { url, fallback } => (url, fallback),
+        };
+        let key = (
+            url.host_str().unwrap_or_default().to_ascii_lowercase(),
+            url.port_or_known_default().unwrap_or(0),
+        );
+        if let Some((proxies, at)) = self.cache.lock().unwrap().get(&key) {
+            if at.elapsed() < PAC_CACHE_TTL {
+                return proxies.clone();
+            }
+        }
+
+        let found = tokio::time::timeout(self.pac_timeout, self.run_script(pac_url, url, &key.0))
+            .await
+            .ok()
+            .flatten()
+            .filter(|proxies| !proxies.is_empty());
+        let Some(proxies) = found else {
+            debug!(url = %url, "No PAC result; not using it");
+            return fallback.as_ref().map_or_else(
+                || vec![ProxyServer::Direct],
+                |manual| manual.proxies_for(url),
+            );
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED {
+            cache.retain(|_, (_, at)| at.elapsed() < PAC_CACHE_TTL);
+            if cache.len() >= MAX_CACHED {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (proxies.clone(), Instant::now()));
+        proxies
+    }
+
+    /// Call `FindProxyForURL` for `url`, fetching the script first if
+    /// needed.
+    async fn run_script(&self, pac_url: &str, url: &Url, host: &str) -> Option<Vec<ProxyServer>> {
+        let mut script = Arc::clone(&self.script).lock_owned().await;
+        let fetch = match *script {
+            PacScript::Unfetched => true,
+            PacScript::Running(_) => false,
+            PacScript::Failed(at) => at.elapsed() >= PAC_FETCH_RETRY_AFTER,
+        };
+        if fetch {
+            // The fetch outlives a lookup that times out waiting for it,
+            // so a slow server still ends up `Running` or `Failed`
+            let pac_url = pac_url.to_string();
+            tokio::spawn(async move {
+                *script = match fetch_script(&pac_url).await {
+                    Some(source) => PacScript::Running(spawn_pac_thread(source)),
+                    None => PacScript::Failed(Instant::now()),
+                };
+            });
+            script = Arc::clone(&self.script).lock_owned().await;
+        }
+        let PacScript::Running(jobs) = &*script else {
+            return None;
+        };
+
+        let (reply, result) = oneshot::channel();
+        let job = PacJob {
+            url: pac_url_argument(url),
+            host: host.to_string(),
+            reply,
+        };
+        // The thread ends when the script does not load
+        if jobs.send(job).is_err() {
+            *script = PacScript::Failed(Instant::now());
+            return None;
+        }
+        drop(script);
+        let result = result.await.ok()??;
+        Some(parse_pac_result(&result))
+    }
+}
+
+impl ProxyResolver for Proxies {
+    fn proxies_for<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Vec<ProxyServer>> {
+        Box::pin(async move {
+            let proxies = self.lookup(url).await;
+            // Proxies in their cooldown go last, keeping their order
+            let failed = {
+                let mut failed = self.failed.lock().unwrap();
+                failed.retain(|_, at| at.elapsed() < PROXY_RETRY_AFTER);
+                failed.clone()
+            };
+            let (live, dead): (Vec<_>, Vec<_>) =
+                proxies.into_iter().partition(|p| !failed.contains_key(p));
+            live.into_iter().chain(dead).collect()
+        })
+    }
+
+    fn report_failure(&self, proxy: &ProxyServer) {
+        debug!(%proxy, "Proxy failed");
+        self.failed
+            .lock()
+            .unwrap()
+            .insert(proxy.clone(), Instant::now());
+    }
+}
+
+/// The URL passed to `FindProxyForURL`: for `https`, only the scheme and
+/// authority, so scripts don't see paths sent encrypted.
+fn pac_url_argument(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.set_fragment(None);
+    if matches!(url.scheme(), "https" | "wss") {
+        url.set_path("/");
+        url.set_query(None);
+    }
+    url.to_string()
+}
+
+/// Fetch a PAC script without going through any proxy.
+async fn fetch_script(pac_url: &str) -> Option<String> {
+    let client = HttpClient::new().ok()?;
+    match client.get(pac_url).await {
+        Ok(response) if response.is_success() => response.text().ok(),
+        Ok(response) => {
+            warn!(pac_url, status = %response.status, "Failed to fetch PAC script");
+            None
+        }
+        Err(e) => {
+            warn!(pac_url, error = %e, "Failed to fetch PAC script");
+            None
+        }
+    }
+}
+
+/// Start the thread that runs `script` and answers jobs until the sender
+/// is dropped.
+fn spawn_pac_thread(script: String) -> mpsc::Sender<PacJob> {
+    let (sender, jobs) = mpsc::channel::<PacJob>();
+    let spawned = std::thread::Builder::new()
+        .name("pac".to_string())
+        .spawn(move || {
+            let mut runtime = match pac_runtime(&script) {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    warn!(error = %e, "Failed to load PAC script");
+                    return;
+                }
+            };
+            for job in jobs {
+                let call = format!("String(FindProxyForURL({:?}, {:?}))", job.url, job.host);
+                let result = match runtime.evaluate_script_bounded(&call, MAX_PAC_LOOP_ITERATIONS) {
+                    Ok(JsValue::String(result)) => Some(result),
+                    Ok(_) => None,
+                    Err(e) => {
+                        debug!(error = %e, "FindProxyForURL failed");
+                        None
+                    }
+                };
+                let _ = job.reply.send(result);
+            }
+        });
+    if let Err(e) = spawned {
+        warn!(error = %e, "Failed to start PAC thread");
+    }
+    sender
+}
+
+/// A runtime with the PAC helpers and `script` loaded, and nothing else
+/// a page would have.
+fn pac_runtime(script: &str) -> Result<JsRuntime, rustkit_js::JsError> {
+    let mut runtime = JsRuntime::new()?;
+    runtime.register_function("dnsResolve", 1, |args| {
+        let host = args.first().map(String::as_str).unwrap_or_default();
+        Ok(resolve_ipv4(host).map_or(JsValue::Null, JsValue::String))
+    })?;
+    runtime.register_function("myIpAddress", 0, |_| Ok(JsValue::String(local_address())))?;
+    runtime.evaluate_script(PAC_UTILS_JS)?;
+    runtime.evaluate_script_bounded(script, MAX_PAC_LOOP_ITERATIONS)?;
+    Ok(runtime)
+}
+
+fn resolve_ipv4(host: &str) -> Option<String> {
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .find(|addr| addr.is_ipv4())
+        .map(|addr| addr.ip().to_string())
+}
+
+/// Address of the interface with the default route. Connecting a UDP
+/// socket sends nothing.
+fn local_address() -> String {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("198.51.100.1:53")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_string())
+}
+
+/// The PAC helper functions besides `dnsResolve` and `myIpAddress`.
+const PAC_UTILS_JS: &str = r#"
+    function isPlainHostName(host) {
+        return host.indexOf('.') < 0;
+    }
+    function dnsDomainIs(host, domain) {
+        return host.length >= domain.length &&
+            host.substring(host.length - domain.length) === domain;
+    }
+    function localHostOrDomainIs(host, hostdom) {
+        return host === hostdom || hostdom.lastIndexOf(host + '.', 0) === 0;
+    }
+    function isResolvable(host) {
+        return dnsResolve(host) !== null;
+    }
+    function dnsDomainLevels(host) {
+        return host.split('.').length - 1;
+    }
+    function convert_addr(ip) {
+        var b = String(ip).split('.');
+        return ((b[0] & 0xff) << 24 | (b[1] & 0xff) << 16 | (b[2] & 0xff) << 8 | (b[3] & 0xff)) >>> 0;
+    }
+    function isInNet(host, pattern, mask) {
+        var ip = /^\d+\.\d+\.\d+\.\d+$/.test(host) ? host : dnsResolve(host);
+        if (ip === null) return false;
+        var m = convert_addr(mask);
+        return ((convert_addr(ip) & m) >>> 0) === ((convert_addr(pattern) & m) >>> 0);
+    }
+    function shExpMatch(str, shexp) {
+        var source = String(shexp)
+            .replace(/[.+^${}()|[\]\\]/g, '\\$&')
+            .replace(/\*/g, '.*')
+            .replace(/\?/g, '.');
+        return new RegExp('^' + source + '$').test(str);
+    }
+
+    var __pacDays = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+    var __pacMonths = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN',
+                       'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+
+    function __pacArgs(args) {
+        var list = Array.prototype.slice.call(args);
+        var gmt = list[list.length - 1] === 'GMT';
+        if (gmt) list.pop();
+        return { list: list, now: new Date(), gmt: gmt };
+    }
+    function __pacInRange(value, first, last) {
+        return first <= last ? value >= first && value <= last : value >= first || value <= last;
+    }
+
+    function weekdayRange() {
+        var a = __pacArgs(arguments);
+        var today = a.gmt ? a.now.getUTCDay() : a.now.getDay();
+        var first = __pacDays.indexOf(a.list[0]);
+        var last = a.list.length > 1 ? __pacDays.indexOf(a.list[1]) : first;
+        return first >= 0 && last >= 0 && __pacInRange(today, first, last);
+    }
+
+    function timeRange() {
+        var a = __pacArgs(arguments);
+        var n = a.list.map(Number);
+        var now = a.gmt
+            ? a.now.getUTCHours() * 3600 + a.now.getUTCMinutes() * 60 + a.now.getUTCSeconds()
+            : a.now.getHours() * 3600 + a.now.getMinutes() * 60 + a.now.getSeconds();
+        if (n.length === 1) return Math.floor(now / 3600) === n[0];
+        var half = n.length / 2;
+        function seconds(list) {
+            return (list[0] || 0) * 3600 + (list[1] || 0) * 60 + (list[2] || 0);
+        }
+        var start = seconds(n.slice(0, half));
+        // An hour or minute end covers all of it
+        var end = seconds(n.slice(half)) + [3600, 60, 1][half - 1] - 1;
+        return __pacInRange(now, start, end);
+    }
+
+    function dateRange() {
+        var a = __pacArgs(arguments);
+        var current = {
+            day: a.gmt ? a.now.getUTCDate() : a.now.getDate(),
+            month: a.gmt ? a.now.getUTCMonth() : a.now.getMonth(),
+            year: a.gmt ? a.now.getUTCFullYear() : a.now.getFullYear()
+        };
+        function field(value) {
+            if (typeof value === 'string') return { kind: 'month', value: __pacMonths.indexOf(value) };
+            return { kind: value > 31 ? 'year' : 'day', value: value };
+        }
+        function key(list, from) {
+            var parts = { year: 0, month: 0, day: 0 };
+            list.forEach(function(value) {
+                var f = field(value);
+                parts[f.kind] = from ? current[f.kind] : f.value;
+            });
+            return parts.year * 10000 + parts.month * 100 + parts.day;
+        }
+        if (a.list.length === 1) {
+            var f = field(a.list[0]);
+            return current[f.kind] === f.value;
+        }
+        var half = a.list.length / 2;
+        if (half !== Math.floor(half)) return false;
+        var first = a.list.slice(0, half);
+        return __pacInRange(key(first, true), key(first, false), key(a.list.slice(half), false));
+    }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LoaderConfig, Request, ResourceLoader};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answer every connection with `body` and close it.
+    async fn serve(body: String) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]);
+                seen.lock()
+                    .unwrap()
+                    .push(head.lines().next().unwrap_or_default().to_string());
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, requests)
+    }
+
+    /// A port nothing listens on.
+    async fn dead_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    async fn pac_loader(script: String) -> (ResourceLoader, Arc<dyn ProxyResolver>) {
+        let (pac_port, _) = serve(script).await;
+        let mode = ProxyMode::PacUrl(format!("http://127.0.0.1:{}/proxy.pac", pac_port));
+        let config = LoaderConfig {
+            proxy: mode.clone(),
+            ..Default::default()
+        };
+        let resolver = resolver(&mode, config.pac_timeout).unwrap();
+        (ResourceLoader::new(config).unwrap(), resolver)
+    }
+
+    #[test]
+    fn test_parse_pac_result() {
+        assert_eq!(
+            parse_pac_result("PROXY a.example:8080; SOCKS s:1080;DIRECT"),
+            vec![
+                ProxyServer::Http {
+                    host: "a.example".to_string(),
+                    port: 8080
+                },
+                ProxyServer::Direct
+            ]
+        );
+        assert_eq!(
+            parse_pac_result("PROXY [::1]:3128"),
+            vec![ProxyServer::Http {
+                host: "::1".to_string(),
+                port: 3128
+            }]
+        );
+        assert!(parse_pac_result("").is_empty());
+    }
+
+    #[test]
+    fn test_manual_proxy_and_bypass() {
+        let manual = ManualProxy::parse("http=web:80;https=secure:443", "*.corp.example;<local>");
+        let proxies = |url: &str| manual.proxies_for(&Url::parse(url).unwrap());
+
+        assert_eq!(
+            proxies("https://example.com/"),
+            vec![ProxyServer::Http {
+                host: "secure".to_string(),
+                port: 443
+            }]
+        );
+        assert_eq!(
+            proxies("http://wiki.corp.example/"),
+            vec![ProxyServer::Direct]
+        );
+        assert_eq!(proxies("http://intranet/"), vec![ProxyServer::Direct]);
+        assert!(
+            matches!(&proxies("http://example.com/")[0], ProxyServer::Http { host, .. } if host == "web")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pac_routes_internal_direct_and_rest_to_proxy() {
+        let (proxy_port, requests) = serve("proxy".to_string()).await;
+        let script = format!(
+            "function FindProxyForURL(url, host) {{ \
+                 if (shExpMatch(host, '*.internal') || isPlainHostName(host)) return 'DIRECT'; \
+                 return 'PROXY 127.0.0.1:{}'; \
+             }}",
+            proxy_port
+        );
+        let (loader, resolver) = pac_loader(script).await;
+
+        let internal = Url::parse("http://app.internal/").unwrap();
+        assert_eq!(
+            resolver.proxies_for(&internal).await,
+            vec![ProxyServer::Direct]
+        );
+
+        let url = Url::parse("http://example.test/page?q=1").unwrap();
+        let response = loader.fetch(Request::get(url)).await.unwrap();
+        assert_eq!(&response.bytes().await.unwrap()[..], b"proxy");
+        assert_eq!(
+            requests.lock().unwrap().as_slice(),
+            ["GET http://example.test/page?q=1 HTTP/1.1"]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dead_proxy_fails_over_and_goes_last() {
+        let (proxy_port, _) = serve("second".to_string()).await;
+        let dead = dead_port().await;
+        let script = format!(
+            "function FindProxyForURL(url, host) {{ \
+                 return 'PROXY 127.0.0.1:{}; PROXY 127.0.0.1:{}'; \
+             }}",
+            dead, proxy_port
+        );
+        let (pac_port, _) = serve(script).await;
+        let mode = ProxyMode::PacUrl(format!("http://127.0.0.1:{}/proxy.pac", pac_port));
+        let resolver = resolver(&mode, Duration::from_secs(3)).unwrap();
+        let client = HttpClient::new().unwrap();
+        client.set_proxy_resolver(Some(Arc::clone(&resolver)));
+
+        let started = Instant::now();
+        let response = client.get("http://example.test/").await.unwrap();
+        assert_eq!(&response.body[..], b"second");
+        assert!(started.elapsed() < rustkit_http::proxy::PROXY_CONNECT_TIMEOUT);
+
+        let url = Url::parse("http://example.test/").unwrap();
+        let order = resolver.proxies_for(&url).await;
+        assert!(matches!(&order[0], ProxyServer::Http { port, .. } if *port == proxy_port));
+        assert!(matches!(&order[1], ProxyServer::Http { port, .. } if *port == dead));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_slow_or_broken_pac_goes_direct() {
+        // Accepts but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let mode = ProxyMode::PacUrl(format!("http://127.0.0.1:{}/proxy.pac", port));
+        let slow = resolver(&mode, Duration::from_millis(200)).unwrap();
+        let url = Url::parse("http://example.test/").unwrap();
+        let started = Instant::now();
+        assert_eq!(slow.proxies_for(&url).await, vec![ProxyServer::Direct]);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let (_, broken) = pac_loader("function FindProxyForURL() { while (true) {} }".into()).await;
+        assert_eq!(broken.proxies_for(&url).await, vec![ProxyServer::Direct]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pac_slower_than_lookup_timeout_still_loads() {
+        let script = "function FindProxyForURL(url, host) { return 'PROXY 127.0.0.1:1'; }";
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let fetches = Arc::new(Mutex::new(0));
+        let counted = Arc::clone(&fetches);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                *counted.lock().unwrap() += 1;
+                let mut buf = vec![0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    script.len(),
+                    script
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        let mode = ProxyMode::PacUrl(format!("http://127.0.0.1:{}/proxy.pac", port));
+        let resolver = resolver(&mode, Duration::from_millis(100)).unwrap();
+        let url = Url::parse("http://example.test/").unwrap();
+
+        // The lookup gives up, but the fetch it started carries on
+        assert_eq!(resolver.proxies_for(&url).await, vec![ProxyServer::Direct]);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(matches!(
+            resolver.proxies_for(&url).await.as_slice(),
+            [ProxyServer::Http { port: 1, .. }]
+        ));
+        assert_eq!(*fetches.lock().unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_pac_fetch_retried_later() {
+        let script = "function FindProxyForURL(url, host) { return 'PROXY 127.0.0.1:1'; }";
+        let (pac_port, requests) = serve(script.to_string()).await;
+        let failed_at = |at: Instant| Proxies {
+            rules: Rules::Pac {
+                url: format!("http://127.0.0.1:{}/proxy.pac", pac_port),
+                fallback: None,
+            },
+            pac_timeout: Duration::from_secs(3),
+            script: Arc::new(tokio::sync::Mutex::new(PacScript::Failed(at))),
+            cache: Mutex::new(HashMap::new()),
+            failed: Mutex::new(HashMap::new()),
+        };
+        let url = Url::parse("http://example.test/").unwrap();
+
+        // Not fetched again until the retry time
+        let recent = failed_at(Instant::now());
+        assert_eq!(recent.lookup(&url).await, vec![ProxyServer::Direct]);
+        assert!(requests.lock().unwrap().is_empty());
+
+        let due = failed_at(Instant::now() - PAC_FETCH_RETRY_AFTER);
+        assert!(matches!(
+            due.lookup(&url).await.as_slice(),
+            [ProxyServer::Http { port: 1, .. }]
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+//! HTTP/2 server push.
+//!
+//! A pushed response is accepted only if it is for a `GET` to the origin
+//! of the request it came with, so the server is authoritative for it.
+//! Accepted pushes are kept like prefetches, in the cache partition of
+//! that request, and answer the next matching `GET` without a new stream;
+//! one still arriving is waited for. A push never replaces a fresh
+//! response already kept. With [`crate::LoaderConfig::accept_server_push`]
+//! off, every promise is reset.
+
+use crate::cancel::{InFlight, Initiator, NetEvent};
+use crate::hints::{cache_key, CacheKey, PrefetchCache};
+use crate::{NetStats, RequestId, Site};
+use http::Method;
+use rustkit_http::{HttpError, PushHandler, PushPromise, Response};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::debug;
+use url::Url;
+
+/// Server push counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PushStats {
+    /// Promises accepted.
+    pub accepted: u64,
+    /// Promises reset: declined, invalid, or over the connection's limit.
+    pub rejected: u64,
+    /// Pushed responses that answered a request.
+    pub used: u64,
+    /// Accepted pushes that failed, were not kept, or expired unused.
+    pub wasted: u64,
+}
+
+/// Receives the pushes of the loader's HTTP/2 connections.
+pub(crate) struct PushReceiver {
+    accept: bool,
+    cache: Arc<PrefetchCache>,
+    stats: Arc<Mutex<NetStats>>,
+    in_flight: Arc<InFlight>,
+    /// Accepted pushes still arriving, with the request reporting them
+    /// and a flag set once they are in.
+    pending: Mutex<HashMap<CacheKey, (RequestId, watch::Sender<bool>)>>,
+}
+
+impl PushReceiver {
+    pub fn new(
+        accept: bool,
+        cache: Arc<PrefetchCache>,
+        stats: Arc<Mutex<NetStats>>,
+        in_flight: Arc<InFlight>,
+    ) -> Self {
+        Self {
+            accept,
+            cache,
+            stats,
+            in_flight,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resolves when the accepted push of `url` for `site` is in, if one
+    /// is on its way.
+    pub fn arriving(&self, site: Option<&Site>, url: &Url) -> Option<watch::Receiver<bool>> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(&cache_key(site, url))
+            .map(|(_, done)| done.subscribe())
+    }
+
+    fn site(promise: &PushPromise) -> Option<Site> {
+        promise.site.clone().map(Site::from_serialized)
+    }
+}
+
+impl PushHandler for PushReceiver {
+    fn accept(&self, promise: &PushPromise) -> bool {
+        let valid = promise.method == Method::GET
+            && promise.url.origin() == promise.initiator.origin()
+            && promise.url.origin().is_tuple();
+        let key = cache_key(Self::site(promise).as_ref(), &promise.url);
+        let mut pending = self.pending.lock().unwrap();
+        if !self.accept || !valid || pending.contains_key(&key) {
+            debug!(url = %promise.url, valid, "Rejecting server push");
+            self.stats.lock().unwrap().pushes.rejected += 1;
+            return false;
+        }
+
+        let request_id = RequestId::new();
+        pending.insert(key, (request_id, watch::channel(false).0));
+        self.stats.lock().unwrap().pushes.accepted += 1;
+        self.in_flight.emit(NetEvent::Started {
+            request_id,
+            url: promise.url.clone(),
+            view_id: None,
+            initiator: Initiator::Push,
+        });
+        true
+    }
+
+    fn refused(&self, promise: &PushPromise) {
+        debug!(url = %promise.url, "Server push over the connection limit");
+        self.stats.lock().unwrap().pushes.rejected += 1;
+    }
+
+    fn received(&self, promise: PushPromise, response: Result<Response, HttpError>) {
+        let site = Self::site(&promise);
+        let Some((request_id, done)) = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&cache_key(site.as_ref(), &promise.url))
+        else {
+            return;
+        };
+        let kept = match response {
+            Ok(response) => {
+                self.in_flight.emit(NetEvent::Finished { request_id });
+                self.cache.store_pushed(
+                    site.as_ref(),
+                    &promise.url,
+                    response.status,
+                    response.headers,
+                    response.body,
+                )
+            }
+            Err(e) => {
+                self.in_flight.emit(NetEvent::Failed {
+                    request_id,
+                    error: e.to_string(),
+                });
+                false
+            }
+        };
+        if !kept {
+            self.stats.lock().unwrap().pushes.wasted += 1;
+        }
+        let _ = done.send(true);
+    }
+}
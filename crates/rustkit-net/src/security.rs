@@ -13,6 +13,7 @@ use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use thiserror::Error;
 use url::Url;
+use crate::site::{self, Site};
 
 // ==================== Origin ====================
 
@@ -32,8 +33,9 @@ pub enum Origin {
 impl Origin {
     /// Create an origin from a URL.
     pub fn from_url(url: &Url) -> Self {
-        // data: and file: URLs have opaque origins
-        if url.scheme() == "data" || url.scheme() == "file" || url.scheme() == "javascript" {
+        // data:, file: and about: URLs have opaque origins; frames that
+        // inherit one go through SecurityContext::for_frame
+        if matches!(url.scheme(), "data" | "file" | "javascript" | "about") {
             return Origin::Opaque(url.to_string());
         }
 
@@ -256,6 +258,17 @@ impl ContentSecurityPolicy {
         self.url_matches_sources(url, sources)
     }
 
+    /// Check if a worker script is allowed (falls back to script-src).
+    pub fn allows_worker(&self, url: &Url) -> bool {
+        let sources = match self.directives.get(&CspDirective::WorkerSrc)
+            .or_else(|| self.get_sources(CspDirective::ScriptSrc))
+        {
+            Some(s) => s,
+            None => return true,
+        };
+        self.url_matches_sources(url, sources)
+    }
+
     /// Check if eval() is allowed.
     pub fn allows_eval(&self) -> bool {
         let sources = match self.get_sources(CspDirective::ScriptSrc) {
@@ -397,24 +410,70 @@ impl CspSource {
                 let url_scheme = format!("{}:", url.scheme());
                 &url_scheme == scheme
             }
-            CspSource::Host(pattern) => {
-                let host = url.host_str().unwrap_or("");
-                if pattern.starts_with("*.") {
-                    // Wildcard match
-                    let domain = &pattern[2..];
-                    host == domain || host.ends_with(&format!(".{}", domain))
-                } else if pattern.contains('/') {
-                    // Path pattern
-                    let full = format!("{}{}", host, url.path());
-                    full.starts_with(pattern)
-                } else {
-                    host == pattern
-                }
-            }
+            CspSource::Host(pattern) => host_source_matches(pattern, url),
         }
     }
 }
 
+/// Match a CSP host-source, `[scheme://]host[:port][path]`, against a URL.
+/// Without a scheme any scheme matches; without a port only the scheme's
+/// default does. A path ending in `/` matches everything under it.
+fn host_source_matches(pattern: &str, url: &Url) -> bool {
+    let (scheme, rest) = match pattern.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, pattern),
+    };
+    if let Some(scheme) = scheme {
+        // An insecure scheme also allows its secure upgrade
+        let scheme = scheme.to_ascii_lowercase();
+        let allowed = url.scheme() == scheme
+            || (scheme == "http" && url.scheme() == "https")
+            || (scheme == "ws" && url.scheme() == "wss");
+        if !allowed {
+            return false;
+        }
+    }
+
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    let (host_pattern, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+
+    let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+    let host_pattern = host_pattern.to_ascii_lowercase();
+    let host_matches = if host_pattern == "*" {
+        true
+    } else if let Some(domain) = host_pattern.strip_prefix("*.") {
+        host.ends_with(&format!(".{}", domain))
+    } else {
+        host == host_pattern
+    };
+    if !host_matches {
+        return false;
+    }
+
+    let port_matches = match port {
+        Some("*") => true,
+        Some(port) => port.parse::<u16>().ok() == url.port_or_known_default(),
+        None => url.port().is_none(),
+    };
+    if !port_matches {
+        return false;
+    }
+
+    if path.is_empty() {
+        true
+    } else if path.ends_with('/') {
+        url.path().starts_with(path)
+    } else {
+        url.path() == path
+    }
+}
+
 /// Hash algorithm for CSP.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HashAlgorithm {
@@ -724,6 +783,24 @@ pub struct CookieAttributes {
 }
 
 impl CookieAttributes {
+    /// Whether the cookie's `Domain` covers `host`. A domain that is a
+    /// public suffix such as `co.uk` only covers that exact host, so one
+    /// site cannot set cookies for every site under the suffix.
+    pub fn domain_matches(&self, host: &str) -> bool {
+        let Some(domain) = self.domain.as_deref() else {
+            return true;
+        };
+        let domain = domain.strip_prefix('.').unwrap_or(domain);
+        if host.eq_ignore_ascii_case(domain) {
+            return true;
+        }
+        if site::is_public_suffix(domain) {
+            return false;
+        }
+        host.to_ascii_lowercase()
+            .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+    }
+
     /// Check if cookie should be sent for a request.
     pub fn should_send(
         &self,
@@ -757,11 +834,8 @@ impl CookieAttributes {
         }
 
         // Check domain
-        if let Some(ref domain) = self.domain {
-            let host = request_url.host_str().unwrap_or("");
-            if !host.eq_ignore_ascii_case(domain) && !host.ends_with(&format!(".{}", domain)) {
-                return false;
-            }
+        if !self.domain_matches(request_url.host_str().unwrap_or("")) {
+            return false;
         }
 
         // Check path
@@ -789,10 +863,14 @@ pub struct SecurityContext {
     pub referrer_policy: ReferrerPolicy,
     /// Whether this is a secure context.
     pub is_secure_context: bool,
+    /// Whether the page was loaded through a certificate exception.
+    pub certificate_error: bool,
     /// Whether sandboxed.
     pub sandboxed: bool,
     /// Sandbox flags (if sandboxed).
     pub sandbox_flags: SandboxFlags,
+    /// Site of the top-level document, which partitions caches.
+    pub top_level_site: Option<Site>,
 }
 
 impl SecurityContext {
@@ -806,9 +884,65 @@ impl SecurityContext {
             csp: None,
             referrer_policy: ReferrerPolicy::default(),
             is_secure_context: is_secure,
+            certificate_error: false,
             sandboxed: false,
             sandbox_flags: SandboxFlags::default(),
+            top_level_site: Site::from_url(url),
+        }
+    }
+
+    /// Mark the document as loaded over an overridden certificate error.
+    ///
+    /// Such documents are never secure contexts, regardless of scheme.
+    pub fn with_certificate_error(mut self) -> Self {
+        self.certificate_error = true;
+        self.is_secure_context = false;
+        self
+    }
+
+    /// Whether a request from the document for `url` is mixed content it
+    /// must not load. A document loaded through a certificate exception is
+    /// no secure context, but it still came over HTTPS and is held to the
+    /// same rule.
+    pub fn blocks_mixed_content(&self, url: &Url, resource_type: MixedContentType) -> bool {
+        let Ok(page) = Url::parse(&self.origin.serialize()) else {
+            return false;
+        };
+        check_mixed_content(&page, url, resource_type) == MixedContentResult::Blockable
+    }
+
+    /// Security context for a frame document at `url` embedded by `parent`.
+    ///
+    /// `about:blank` and `about:srcdoc` documents inherit the parent's
+    /// origin, policy and secure-context state. A `sandbox` without
+    /// `allow-same-origin` forces a unique opaque origin instead.
+    pub fn for_frame(parent: &SecurityContext, url: &Url, sandbox: Option<SandboxFlags>) -> Self {
+        let inherits = url.scheme() == "about" && matches!(url.path(), "blank" | "srcdoc");
+        let mut ctx = if inherits {
+            Self {
+                certificate_error: false,
+                ..parent.clone()
+            }
+        } else {
+            Self::from_url(url)
+        };
+
+        if let Some(flags) = sandbox {
+            ctx.sandboxed = true;
+            ctx.sandbox_flags = flags;
+        }
+        // Sandboxing is inherited by nested frames
+        if parent.sandboxed && !ctx.sandboxed {
+            ctx.sandboxed = true;
+            ctx.sandbox_flags = parent.sandbox_flags;
+        }
+        if ctx.sandboxed && !ctx.sandbox_flags.allow_same_origin {
+            ctx.origin = Origin::Opaque(format!("sandbox:{}", url));
         }
+        ctx.top_level_site = parent.top_level_site.clone();
+        // A frame is only as secure as the document embedding it
+        ctx.is_secure_context &= parent.is_secure_context;
+        ctx
     }
 
     /// Check if same-origin with another URL.
@@ -1050,6 +1184,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_csp_host_source_matching() {
+        let matches = |pattern: &str, url: &str| {
+            CspSource::parse(pattern)
+                .unwrap()
+                .matches_url(&Url::parse(url).unwrap())
+        };
+
+        assert!(matches("https://cdn.example.net", "https://cdn.example.net/worker.js"));
+        assert!(!matches("https://cdn.example.net", "http://cdn.example.net/worker.js"));
+        assert!(matches("http://cdn.example.net", "https://cdn.example.net/a.js"));
+        assert!(matches("cdn.example.net", "http://cdn.example.net/a.js"));
+        assert!(!matches("cdn.example.net", "https://cdn.example.org/a.js"));
+
+        assert!(matches("https://*.example.net", "https://cdn.example.net/a.js"));
+        assert!(!matches("https://*.example.net", "https://example.net/a.js"));
+        assert!(matches("*", "https://anything.test/a.js"));
+
+        assert!(!matches("https://cdn.example.net", "https://cdn.example.net:8443/a.js"));
+        assert!(matches("https://cdn.example.net:8443", "https://cdn.example.net:8443/a.js"));
+        assert!(matches("https://cdn.example.net:443", "https://cdn.example.net/a.js"));
+        assert!(matches("cdn.example.net:*", "https://cdn.example.net:8443/a.js"));
+
+        assert!(matches("https://cdn.example.net/js/", "https://cdn.example.net/js/a.js"));
+        assert!(!matches("https://cdn.example.net/js/", "https://cdn.example.net/css/a.css"));
+        assert!(matches("https://cdn.example.net/js/a.js", "https://cdn.example.net/js/a.js"));
+        assert!(!matches("https://cdn.example.net/js/a.js", "https://cdn.example.net/js/b.js"));
+    }
+
     #[test]
     fn test_cors_simple_request() {
         assert!(CorsChecker::is_simple_request("GET", &[]));
@@ -1129,6 +1292,19 @@ mod tests {
         assert!(!cookie.should_send(&url, false, false));
     }
 
+    #[test]
+    fn test_cookie_domain_skips_public_suffixes() {
+        let cookie = |domain: &str| CookieAttributes {
+            domain: Some(domain.into()),
+            ..Default::default()
+        };
+        assert!(cookie(".example.co.uk").domain_matches("www.example.co.uk"));
+        assert!(!cookie("example.co.uk").domain_matches("badexample.co.uk"));
+        assert!(!cookie("co.uk").domain_matches("example.co.uk"));
+        assert!(!cookie("github.io").domain_matches("alice.github.io"));
+        assert!(cookie("github.io").domain_matches("github.io"));
+    }
+
     #[test]
     fn test_mixed_content() {
         let https_page = Url::parse("https://example.com/").unwrap();
@@ -1171,5 +1347,54 @@ mod tests {
         assert!(ctx.is_same_origin(&url));
         assert!(!ctx.is_same_origin(&Url::parse("https://other.com/").unwrap()));
     }
-}
 
+    #[test]
+    fn test_security_context_certificate_error() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let ctx = SecurityContext::from_url(&url).with_certificate_error();
+
+        assert!(ctx.certificate_error);
+        assert!(!ctx.is_secure_context);
+        let http = Url::parse("http://example.com/app.js").unwrap();
+        assert!(ctx.blocks_mixed_content(&http, MixedContentType::Script));
+        assert!(ctx.blocks_mixed_content(&http, MixedContentType::Fetch));
+        assert!(!ctx.blocks_mixed_content(&http, MixedContentType::Image));
+
+        let other = Url::parse("https://other.com/").unwrap();
+        assert!(!SecurityContext::for_frame(&ctx, &other, None).is_secure_context);
+    }
+
+    #[test]
+    fn test_frame_origin_inheritance() {
+        let parent = SecurityContext::from_url(&Url::parse("https://example.com/page").unwrap());
+        let srcdoc = Url::parse("about:srcdoc").unwrap();
+
+        let inherited = SecurityContext::for_frame(&parent, &srcdoc, None);
+        assert!(inherited.origin.same_origin(&parent.origin));
+        assert!(inherited.is_secure_context);
+        let blank = SecurityContext::for_frame(&parent, &Url::parse("about:blank").unwrap(), None);
+        assert!(blank.is_same_origin(&Url::parse("https://example.com/other").unwrap()));
+
+        let other = Url::parse("https://other.com/").unwrap();
+        assert!(!SecurityContext::for_frame(&parent, &other, None)
+            .origin
+            .same_origin(&parent.origin));
+
+        let sandboxed = SecurityContext::for_frame(
+            &parent,
+            &srcdoc,
+            Some(SandboxFlags::parse("allow-scripts")),
+        );
+        assert!(sandboxed.origin.is_opaque());
+        let allowed = SecurityContext::for_frame(
+            &parent,
+            &srcdoc,
+            Some(SandboxFlags::parse("allow-scripts allow-same-origin")),
+        );
+        assert!(allowed.origin.same_origin(&parent.origin));
+
+        // Nested frames stay sandboxed
+        let nested = SecurityContext::for_frame(&sandboxed, &srcdoc, None);
+        assert!(nested.sandboxed && nested.origin.is_opaque());
+    }
+}
@@ -0,0 +1,237 @@
+//! Sites and the Public Suffix List.
+//!
+//! A site is a scheme plus registrable domain ("eTLD+1"): `https://a.example.co.uk`
+//! and `https://b.example.co.uk` are the same site, while `https://a.github.io`
+//! and `https://b.github.io` are not, because `github.io` is a public suffix.
+//! Caches are partitioned by the site of the top-level document so one site
+//! cannot learn what another has loaded by timing it.
+//!
+//! The list is bundled from <https://publicsuffix.org/list/> (MPL-2.0) and
+//! parsed on first use.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use url::{Host, Origin, Url};
+
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+/// Rules of the Public Suffix List, with internationalized labels in their
+/// ASCII (punycode) form to match hosts as URLs serialize them.
+#[derive(Debug, Default)]
+pub struct PublicSuffixList {
+    /// `co.uk`
+    rules: HashSet<Cow<'static, str>>,
+    /// `*.ck`, stored as `ck`
+    wildcards: HashSet<Cow<'static, str>>,
+    /// `!www.ck`, stored as `www.ck`
+    exceptions: HashSet<Cow<'static, str>>,
+}
+
+impl PublicSuffixList {
+    /// Parse a list in the `public_suffix_list.dat` format.
+    pub fn parse(list: &'static str) -> Self {
+        let mut psl = Self::default();
+        for line in list.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+            let (set, rule) = if let Some(rule) = rule.strip_prefix('!') {
+                (&mut psl.exceptions, rule)
+            } else if let Some(rule) = rule.strip_prefix("*.") {
+                (&mut psl.wildcards, rule)
+            } else {
+                (&mut psl.rules, rule)
+            };
+            if rule.is_ascii() {
+                set.insert(Cow::Borrowed(rule));
+            } else if let Ok(Host::Domain(ascii)) = Host::parse(rule) {
+                set.insert(Cow::Owned(ascii));
+            }
+        }
+        psl
+    }
+
+    /// The bundled list.
+    pub fn bundled() -> &'static Self {
+        static LIST: OnceLock<PublicSuffixList> = OnceLock::new();
+        LIST.get_or_init(|| Self::parse(PUBLIC_SUFFIX_LIST))
+    }
+
+    /// The public suffix of `host`, such as `co.uk` for `www.example.co.uk`.
+    ///
+    /// Hosts no rule matches fall back to their last label. IP addresses
+    /// have no public suffix.
+    pub fn public_suffix<'a>(&self, host: &'a str) -> Option<&'a str> {
+        let host = host.strip_suffix('.').unwrap_or(host);
+        if host.is_empty() || host.starts_with('[') || host.parse::<IpAddr>().is_ok() {
+            return None;
+        }
+        let lower = host.to_ascii_lowercase();
+        // Byte offset of each label, longest suffix first
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(lower.match_indices('.').map(|(i, _)| i + 1))
+            .collect();
+        if lower.split('.').any(str::is_empty) {
+            return None;
+        }
+
+        for (i, &start) in starts.iter().enumerate() {
+            let candidate = &lower[start..];
+            if self.exceptions.contains(candidate) {
+                // The exception's own label is registrable
+                return starts.get(i + 1).map(|&next| &host[next..]);
+            }
+            let wildcard = starts
+                .get(i + 1)
+                .is_some_and(|&next| self.wildcards.contains(&lower[next..]));
+            if wildcard || self.rules.contains(candidate) {
+                return Some(&host[start..]);
+            }
+        }
+        starts.last().map(|&last| &host[last..])
+    }
+
+    /// The registrable domain of `host`: its public suffix plus one more
+    /// label, such as `example.co.uk` for `www.example.co.uk`. `None` for
+    /// IP addresses and hosts that are themselves public suffixes.
+    pub fn registrable_domain<'a>(&self, host: &'a str) -> Option<&'a str> {
+        let host = host.strip_suffix('.').unwrap_or(host);
+        let suffix = self.public_suffix(host)?;
+        let rest = host[..host.len() - suffix.len()].strip_suffix('.')?;
+        let start = rest.rfind('.').map_or(0, |dot| dot + 1);
+        Some(&host[start..])
+    }
+
+    /// Whether `host` is exactly a public suffix, such as `co.uk`.
+    pub fn is_public_suffix(&self, host: &str) -> bool {
+        let host = host.strip_suffix('.').unwrap_or(host);
+        self.public_suffix(host)
+            .is_some_and(|suffix| suffix.len() == host.len())
+    }
+}
+
+/// The public suffix of `host` under the bundled list.
+pub fn public_suffix(host: &str) -> Option<&str> {
+    PublicSuffixList::bundled().public_suffix(host)
+}
+
+/// The registrable domain of `host` under the bundled list.
+pub fn registrable_domain(host: &str) -> Option<&str> {
+    PublicSuffixList::bundled().registrable_domain(host)
+}
+
+/// Whether `host` is a public suffix under the bundled list.
+pub fn is_public_suffix(host: &str) -> bool {
+    PublicSuffixList::bundled().is_public_suffix(host)
+}
+
+/// A schemeful site, serialized like an origin: `https://example.co.uk`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Site(String);
+
+impl Site {
+    /// The site of `url`'s origin. Hosts without a registrable domain,
+    /// such as IP addresses and `localhost`, are their own site; opaque
+    /// origins such as `data:` have none.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        let Origin::Tuple(scheme, host, _) = url.origin() else {
+            return None;
+        };
+        let host = host.to_string();
+        let domain = match registrable_domain(&host) {
+            Some(domain) => domain,
+            None => &host,
+        };
+        Some(Self(format!("{}://{}", scheme, domain)))
+    }
+
+    /// A site from its serialization, as returned by [`Self::as_str`].
+    pub(crate) fn from_serialized(site: String) -> Self {
+        Self(site)
+    }
+
+    /// The serialized site.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable_domains() {
+        assert_eq!(public_suffix("www.example.co.uk"), Some("co.uk"));
+        assert_eq!(
+            registrable_domain("www.example.co.uk"),
+            Some("example.co.uk")
+        );
+        assert_eq!(registrable_domain("example.co.uk"), Some("example.co.uk"));
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert!(is_public_suffix("co.uk"));
+
+        // Private-section rules: every user site is its own domain
+        assert_eq!(
+            registrable_domain("alice.github.io"),
+            Some("alice.github.io")
+        );
+        assert_eq!(
+            registrable_domain("docs.alice.github.io"),
+            Some("alice.github.io")
+        );
+        assert!(is_public_suffix("github.io"));
+
+        // Unlisted suffixes fall back to the last label
+        assert_eq!(registrable_domain("a.b.example"), Some("b.example"));
+        assert_eq!(registrable_domain("localhost"), None);
+        assert_eq!(registrable_domain("127.0.0.1"), None);
+        assert_eq!(registrable_domain("WWW.Example.COM."), Some("Example.COM"));
+    }
+
+    #[test]
+    fn test_wildcard_and_exception_rules() {
+        let psl = PublicSuffixList::parse("*.ck\n!www.ck\n// comment\n公司.cn\n");
+        assert_eq!(psl.public_suffix("a.b.ck"), Some("b.ck"));
+        assert_eq!(psl.registrable_domain("a.b.ck"), Some("a.b.ck"));
+        assert_eq!(psl.registrable_domain("www.ck"), Some("www.ck"));
+        assert_eq!(psl.public_suffix("x.www.ck"), Some("ck"));
+        assert_eq!(
+            psl.registrable_domain("shop.example.xn--55qx5d.cn"),
+            Some("example.xn--55qx5d.cn")
+        );
+    }
+
+    #[test]
+    fn test_sites() {
+        let site = |url: &str| Site::from_url(&Url::parse(url).unwrap());
+        assert_eq!(
+            site("https://a.example.co.uk/x").unwrap().as_str(),
+            "https://example.co.uk"
+        );
+        assert_eq!(
+            site("https://a.example.co.uk"),
+            site("https://b.example.co.uk:8443")
+        );
+        assert_ne!(site("https://a.github.io"), site("https://b.github.io"));
+        assert_ne!(site("http://example.com"), site("https://example.com"));
+        assert_eq!(
+            site("http://127.0.0.1:8080/").unwrap().as_str(),
+            "http://127.0.0.1"
+        );
+        assert_eq!(site("data:text/plain,hi"), None);
+    }
+}
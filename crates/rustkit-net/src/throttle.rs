@@ -0,0 +1,247 @@
+//! Traffic shaping for simulating slow and offline networks.
+//!
+//! Conditions are held in a watch channel so they can change while requests
+//! are in flight: paced transfers pick up new rates at the next chunk, and
+//! going offline aborts them with [`NetError::Offline`].
+
+use crate::NetError;
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Simulated network conditions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkConditions {
+    /// Fail every request with [`NetError::Offline`].
+    pub offline: bool,
+    /// Extra delay before the first byte of each response.
+    pub latency: Duration,
+    /// Download cap in bytes per second.
+    pub download_bps: Option<u64>,
+    /// Upload cap in bytes per second.
+    pub upload_bps: Option<u64>,
+}
+
+impl NetworkConditions {
+    /// No shaping.
+    pub fn online() -> Self {
+        Self::default()
+    }
+
+    /// Every request fails.
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether speculative traffic such as prefetches should be left out:
+    /// offline, 400 ms or more of latency, or downloads capped below
+    /// 1.5 Mbit/s.
+    pub fn is_constrained(&self) -> bool {
+        self.offline
+            || self.latency >= Duration::from_millis(400)
+            || self.download_bps.is_some_and(|bps| bps < 192 * 1024)
+    }
+
+    /// Roughly a slow 3G connection: 400 ms latency, 50 KB/s each way.
+    pub fn slow_3g() -> Self {
+        Self {
+            offline: false,
+            latency: Duration::from_millis(400),
+            download_bps: Some(50 * 1024),
+            upload_bps: Some(50 * 1024),
+        }
+    }
+}
+
+/// Token bucket that starts empty and holds at most one chunk, so transfers
+/// never burst above the configured rate.
+pub(crate) struct TokenBucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new() -> Self {
+        Self {
+            tokens: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Time to wait before `bytes` may pass at `rate` bytes per second.
+    pub(crate) fn reserve(&mut self, bytes: usize, rate: u64) -> Duration {
+        let now = Instant::now();
+        let rate = rate.max(1) as f64;
+        if now > self.last {
+            self.tokens = (self.tokens + (now - self.last).as_secs_f64() * rate).min(bytes as f64);
+            self.last = now;
+        }
+
+        let deficit = bytes as f64 - self.tokens;
+        if deficit <= 0.0 {
+            self.tokens -= bytes as f64;
+        } else {
+            // Waiting refills exactly the deficit
+            self.tokens = 0.0;
+            self.last += Duration::from_secs_f64(deficit / rate);
+        }
+        self.last.saturating_duration_since(now)
+    }
+}
+
+/// Chunk size giving about ten chunks per second at `rate`.
+pub(crate) fn chunk_size(rate: u64) -> usize {
+    (rate / 10).clamp(1024, 16 * 1024) as usize
+}
+
+/// Shared network conditions of a loader.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    conditions: watch::Sender<NetworkConditions>,
+}
+
+impl Throttle {
+    pub(crate) fn new() -> Self {
+        Self {
+            conditions: watch::channel(NetworkConditions::default()).0,
+        }
+    }
+
+    pub(crate) fn conditions(&self) -> NetworkConditions {
+        self.conditions.borrow().clone()
+    }
+
+    /// Replace the conditions. Returns the previous ones.
+    pub(crate) fn set(&self, conditions: NetworkConditions) -> NetworkConditions {
+        debug!(?conditions, "Network conditions changed");
+        self.conditions.send_replace(conditions)
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<NetworkConditions> {
+        self.conditions.subscribe()
+    }
+
+    /// Fail immediately when offline.
+    pub(crate) fn check(&self) -> Result<(), NetError> {
+        if self.conditions.borrow().offline {
+            return Err(NetError::Offline);
+        }
+        Ok(())
+    }
+
+    /// Delay the first byte of a response by the configured latency.
+    pub(crate) async fn first_byte(&self) -> Result<(), NetError> {
+        let latency = self.conditions.borrow().latency;
+        sleep_online(&mut self.subscribe(), latency).await
+    }
+
+    /// Hold a request body back as long as uploading it would take.
+    pub(crate) async fn upload(&self, len: usize) -> Result<(), NetError> {
+        let mut conditions = self.subscribe();
+        let mut bucket = TokenBucket::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            let Some(rate) = conditions.borrow().upload_bps else {
+                break;
+            };
+            let chunk = remaining.min(chunk_size(rate));
+            let wait = bucket.reserve(chunk, rate);
+            sleep_online(&mut conditions, wait).await?;
+            remaining -= chunk;
+        }
+        self.check()
+    }
+
+    /// Whether response bodies should be paced.
+    pub(crate) fn paces_downloads(&self) -> bool {
+        self.conditions.borrow().download_bps.is_some()
+    }
+
+    /// Deliver `body` in chunks at the download rate.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub(crate) fn download(&self, body: Bytes) -> mpsc::Receiver<Result<Bytes, NetError>> {
+        let (tx, rx) = mpsc::channel(4);
+        let mut conditions = self.subscribe();
+        tokio::spawn(async move {
+            let mut bucket = TokenBucket::new();
+            let mut offset = 0;
+            while offset < body.len() {
+                let rate = conditions.borrow().download_bps;
+                let chunk = match rate {
+                    Some(rate) => {
+                        let chunk = (body.len() - offset).min(chunk_size(rate));
+                        let wait = bucket.reserve(chunk, rate);
+                        if let Err(e) = sleep_online(&mut conditions, wait).await {
+                            let _ = tx.send(Err(e)).await;
+                            return;
+                        }
+                        chunk
+                    }
+                    // Shaping was lifted mid-transfer
+                    None => body.len() - offset,
+                };
+                if tx
+                    .send(Ok(body.slice(offset..offset + chunk)))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                offset += chunk;
+            }
+        });
+        rx
+    }
+}
+
+/// Sleep for `duration`, failing as soon as the network goes offline.
+async fn sleep_online(
+    conditions: &mut watch::Receiver<NetworkConditions>,
+    duration: Duration,
+) -> Result<(), NetError> {
+    let sleep = tokio::time::sleep(duration);
+    tokio::pin!(sleep);
+    loop {
+        if conditions.borrow_and_update().offline {
+            return Err(NetError::Offline);
+        }
+        tokio::select! {
+            _ = &mut sleep => return Ok(()),
+            changed = conditions.changed() => {
+                if changed.is_err() {
+                    // The loader is gone; finish the wait unshaped
+                    (&mut sleep).await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_paces_without_burst() {
+        let mut bucket = TokenBucket::new();
+        let first = bucket.reserve(1000, 1000);
+        assert!(first >= Duration::from_millis(999));
+        // The next chunk waits on top of the first
+        let second = bucket.reserve(1000, 1000);
+        assert!(second >= Duration::from_millis(1999));
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        assert_eq!(chunk_size(50 * 1024), 5 * 1024);
+        assert_eq!(chunk_size(100), 1024);
+        assert_eq!(chunk_size(10_000_000), 16 * 1024);
+    }
+}
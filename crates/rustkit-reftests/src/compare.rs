@@ -0,0 +1,139 @@
+//! Pixel comparison and diff images.
+
+use crate::manifest::Tolerance;
+
+/// An RGBA8 image, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    fn pixels(&self) -> impl Iterator<Item = &[u8]> {
+        self.rgba.chunks_exact(4)
+    }
+}
+
+/// How two images differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Comparison {
+    /// Pixels differing by more than the tolerance's channel difference;
+    /// every pixel when the sizes differ.
+    pub differing_pixels: usize,
+    /// Largest difference in any channel of any pixel.
+    pub max_channel_difference: u8,
+}
+
+impl Comparison {
+    /// Whether the images match within `tolerance`.
+    pub fn matches(&self, tolerance: Tolerance) -> bool {
+        self.differing_pixels <= tolerance.max_differing_pixels
+    }
+}
+
+/// Compare two images pixel by pixel.
+pub fn compare(a: &Image, b: &Image, tolerance: Tolerance) -> Comparison {
+    if (a.width, a.height) != (b.width, b.height) {
+        return Comparison {
+            differing_pixels: (a.width * a.height).max(b.width * b.height) as usize,
+            max_channel_difference: u8::MAX,
+        };
+    }
+    let mut comparison = Comparison::default();
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let difference = channel_difference(pa, pb);
+        comparison.max_channel_difference = comparison.max_channel_difference.max(difference);
+        if difference > tolerance.max_channel_difference {
+            comparison.differing_pixels += 1;
+        }
+    }
+    comparison
+}
+
+/// An image of `a` faded towards white, with pixels differing from `b` by
+/// more than the tolerance in red. Sized like `a`; with mismatched sizes
+/// every pixel is red.
+pub fn diff_image(a: &Image, b: &Image, tolerance: Tolerance) -> Image {
+    let same_size = (a.width, a.height) == (b.width, b.height);
+    let mut rgba = Vec::with_capacity(a.rgba.len());
+    for (index, pa) in a.pixels().enumerate() {
+        let differs = !same_size
+            || channel_difference(pa, &b.rgba[index * 4..index * 4 + 4])
+                > tolerance.max_channel_difference;
+        if differs {
+            rgba.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            rgba.extend(pa[..3].iter().map(|&c| 192 + c / 4));
+            rgba.push(255);
+        }
+    }
+    Image {
+        width: a.width,
+        height: a.height,
+        rgba,
+    }
+}
+
+fn channel_difference(a: &[u8], b: &[u8]) -> u8 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> Image {
+        Image {
+            width: pixels.len() as u32,
+            height: 1,
+            rgba: pixels.concat(),
+        }
+    }
+
+    #[test]
+    fn test_compare_with_tolerance() {
+        let white = [255, 255, 255, 255];
+        let a = image(&[white, white, [0, 0, 0, 255], white]);
+        let b = image(&[
+            white,
+            [250, 255, 255, 255],
+            [0, 0, 0, 255],
+            [0, 0, 255, 255],
+        ]);
+
+        let exact = compare(&a, &b, Tolerance::default());
+        assert_eq!(exact.differing_pixels, 2);
+        assert_eq!(exact.max_channel_difference, 255);
+        assert!(!exact.matches(Tolerance::default()));
+
+        let fuzzy = Tolerance {
+            max_channel_difference: 5,
+            max_differing_pixels: 1,
+        };
+        assert!(compare(&a, &b, fuzzy).matches(fuzzy));
+        assert!(compare(&a, &a, Tolerance::default()).matches(Tolerance::default()));
+
+        let wider = Image {
+            width: 2,
+            height: 2,
+            rgba: b.rgba.clone(),
+        };
+        assert_eq!(compare(&a, &wider, fuzzy).differing_pixels, 4);
+    }
+
+    #[test]
+    fn test_diff_image_marks_differences() {
+        let a = image(&[[0, 0, 0, 255], [255, 255, 255, 255]]);
+        let b = image(&[[0, 0, 0, 255], [0, 255, 0, 255]]);
+        let diff = diff_image(&a, &b, Tolerance::default());
+        assert_eq!((diff.width, diff.height), (2, 1));
+        assert_eq!(&diff.rgba[..4], &[192, 192, 192, 255]);
+        assert_eq!(&diff.rgba[4..], &[255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,52 @@
+//! # RustKit Reftests
+//!
+//! Visual regression harness: each test renders a page and a reference page
+//! that should look the same (or different) through the engine in headless
+//! mode, and compares the pixels.
+//!
+//! Tests are listed in a `reftest.list` manifest; see [`manifest`] for the
+//! format. Pages are captured at a fixed [`REFTEST_WIDTH`] x
+//! [`REFTEST_HEIGHT`] at 1x, on the GPU when there is one and otherwise by
+//! the renderer's CPU executor, so the suite runs in CI without a display.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use rustkit_reftests::ReftestRunner;
+//!
+//! let mut runner = ReftestRunner::new()?.with_artifact_dir("target/reftests");
+//! for result in runner.run_manifest("tests/reftests/reftest.list")? {
+//!     println!("{}: {:?}", result.entry.test.display(), result.outcome);
+//! }
+//! ```
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub mod compare;
+pub mod manifest;
+pub mod runner;
+
+pub use compare::{compare, diff_image, Comparison, Image};
+pub use manifest::{load_manifest, parse_manifest, Expectation, ManifestEntry, Relation, Tolerance};
+pub use runner::{Outcome, ReftestResult, ReftestRunner, REFTEST_HEIGHT, REFTEST_WIDTH};
+
+/// Errors from loading manifests and rendering pages.
+#[derive(Error, Debug)]
+pub enum ReftestError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid manifest line {line}: {message}")]
+    Manifest { line: usize, message: String },
+
+    #[error("Engine error: {0}")]
+    Engine(#[from] rustkit_engine::EngineError),
+
+    #[error("Failed to write artifact: {0}")]
+    Artifact(String),
+}
@@ -0,0 +1,187 @@
+//! The `reftest.list` manifest format.
+//!
+//! One test per line, paths relative to the manifest:
+//!
+//! ```text
+//! # comment
+//! == margin-collapse.html margin-collapse-ref.html
+//! != z-index.html z-index-notref.html
+//! fuzzy(2,10) == text.html text-ref.html
+//! fails == known-bug.html known-bug-ref.html
+//! skip == flaky.html flaky-ref.html
+//! ```
+//!
+//! `==` expects matching renderings and `!=` differing ones. Annotations
+//! before the relation mark a test as an expected failure (`fails`), not to
+//! be run (`skip`), or compared with a tolerance (`fuzzy(max channel
+//! difference, max differing pixels)`).
+
+use crate::ReftestError;
+use std::path::{Path, PathBuf};
+
+/// How a test's rendering should relate to its reference's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// `==`: the renderings match.
+    Equal,
+    /// `!=`: the renderings differ.
+    NotEqual,
+}
+
+/// What the test is expected to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Expectation {
+    #[default]
+    Pass,
+    /// A known failure; passing is reported so the annotation gets removed.
+    Fail,
+    /// Not run.
+    Skip,
+}
+
+/// How far two renderings may differ and still match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tolerance {
+    /// Largest difference in any channel for pixels that count as equal.
+    pub max_channel_difference: u8,
+    /// How many pixels may differ by more than that.
+    pub max_differing_pixels: usize,
+}
+
+/// One test in a manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub test: PathBuf,
+    pub reference: PathBuf,
+    pub relation: Relation,
+    pub expectation: Expectation,
+    pub tolerance: Tolerance,
+    /// 1-based line in the manifest.
+    pub line: usize,
+}
+
+/// Parse a manifest, resolving paths against `base`.
+pub fn parse_manifest(source: &str, base: &Path) -> Result<Vec<ManifestEntry>, ReftestError> {
+    let mut entries = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| ReftestError::Manifest {
+            line: line_number,
+            message,
+        };
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut expectation = Expectation::Pass;
+        let mut tolerance = Tolerance::default();
+        let mut relation = None;
+        let mut tokens = line.split_whitespace();
+        for token in tokens.by_ref() {
+            match token {
+                "==" => relation = Some(Relation::Equal),
+                "!=" => relation = Some(Relation::NotEqual),
+                "fails" => expectation = Expectation::Fail,
+                "skip" => expectation = Expectation::Skip,
+                _ => match parse_fuzzy(token) {
+                    Some(fuzzy) => tolerance = fuzzy,
+                    None => return Err(error(format!("unknown annotation `{}`", token))),
+                },
+            }
+            if relation.is_some() {
+                break;
+            }
+        }
+        let relation = relation.ok_or_else(|| error("missing `==` or `!=`".to_string()))?;
+        let (test, reference) = match (tokens.next(), tokens.next(), tokens.next()) {
+            (Some(test), Some(reference), None) => (test, reference),
+            _ => return Err(error("expected a test and a reference".to_string())),
+        };
+
+        entries.push(ManifestEntry {
+            test: base.join(test),
+            reference: base.join(reference),
+            relation,
+            expectation,
+            tolerance,
+            line: line_number,
+        });
+    }
+    Ok(entries)
+}
+
+/// Load and parse the manifest at `path`.
+pub fn load_manifest(path: &Path) -> Result<Vec<ManifestEntry>, ReftestError> {
+    let source = std::fs::read_to_string(path).map_err(|source| ReftestError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_manifest(&source, path.parent().unwrap_or(Path::new(".")))
+}
+
+fn parse_fuzzy(token: &str) -> Option<Tolerance> {
+    let args = token.strip_prefix("fuzzy(")?.strip_suffix(')')?;
+    let (channel, pixels) = args.split_once(',')?;
+    Some(Tolerance {
+        max_channel_difference: channel.trim().parse().ok()?,
+        max_differing_pixels: pixels.trim().parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let source = "\
+# layout
+== a.html a-ref.html
+!= b.html b-notref.html  # differs
+
+fuzzy(3,20) fails == c.html c-ref.html
+skip == d.html d-ref.html
+";
+        let entries = parse_manifest(source, Path::new("suite")).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0].test, Path::new("suite/a.html"));
+        assert_eq!(entries[0].reference, Path::new("suite/a-ref.html"));
+        assert_eq!(entries[0].relation, Relation::Equal);
+        assert_eq!(entries[0].expectation, Expectation::Pass);
+        assert_eq!(entries[0].tolerance, Tolerance::default());
+        assert_eq!(entries[0].line, 2);
+
+        assert_eq!(entries[1].relation, Relation::NotEqual);
+        assert_eq!(
+            entries[2].tolerance,
+            Tolerance {
+                max_channel_difference: 3,
+                max_differing_pixels: 20,
+            }
+        );
+        assert_eq!(entries[2].expectation, Expectation::Fail);
+        assert_eq!(entries[3].expectation, Expectation::Skip);
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        for source in [
+            "a.html a-ref.html",
+            "== a.html",
+            "== a.html b.html c.html",
+            "flaky == a.html a-ref.html",
+            "fuzzy(1) == a.html a-ref.html",
+        ] {
+            assert!(
+                matches!(
+                    parse_manifest(source, Path::new(".")),
+                    Err(ReftestError::Manifest { line: 1, .. })
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Rendering pages and running manifest entries.
+
+use crate::compare::{compare, diff_image, Image};
+use crate::manifest::{load_manifest, Expectation, ManifestEntry, Relation};
+use crate::ReftestError;
+use rustkit_engine::{Engine, EngineConfig};
+use rustkit_viewhost::Bounds;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Width pages are captured at.
+pub const REFTEST_WIDTH: u32 = 800;
+/// Height pages are captured at.
+pub const REFTEST_HEIGHT: u32 = 600;
+
+/// The result of one test against its expectation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    /// Failed unexpectedly, or could not be rendered.
+    Fail(String),
+    /// Failed as annotated.
+    ExpectedFail,
+    /// Annotated `fails` but passed.
+    UnexpectedPass,
+    Skipped,
+}
+
+impl Outcome {
+    /// Whether the outcome is the expected one.
+    pub fn is_expected(&self) -> bool {
+        matches!(self, Self::Pass | Self::ExpectedFail | Self::Skipped)
+    }
+}
+
+/// A test's outcome and what was rendered for it.
+#[derive(Debug, Clone)]
+pub struct ReftestResult {
+    pub entry: ManifestEntry,
+    pub outcome: Outcome,
+    /// Hashes of the test and reference renderings, when both rendered.
+    pub hashes: Option<(u64, u64)>,
+    /// The diff image written for a failure.
+    pub diff_path: Option<PathBuf>,
+}
+
+/// Renders manifest entries through a headless engine.
+pub struct ReftestRunner {
+    engine: Engine,
+    artifact_dir: Option<PathBuf>,
+}
+
+impl ReftestRunner {
+    /// A runner on a new engine, rasterizing on the CPU when there is no GPU.
+    pub fn new() -> Result<Self, ReftestError> {
+        let engine = Engine::new(EngineConfig {
+            javascript_enabled: false,
+            disable_animations: true,
+            software_fallback: true,
+            ..Default::default()
+        })?;
+        Ok(Self {
+            engine,
+            artifact_dir: None,
+        })
+    }
+
+    /// Write test, reference and diff images of failures under `dir`.
+    pub fn with_artifact_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.artifact_dir = Some(dir.into());
+        self
+    }
+
+    /// Render an HTML file in a fresh view.
+    pub fn render(&mut self, path: &Path) -> Result<(Image, u64), ReftestError> {
+        let html = std::fs::read_to_string(path).map_err(|source| ReftestError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let view =
+            self.engine
+                .create_headless_view(Bounds::new(0, 0, REFTEST_WIDTH, REFTEST_HEIGHT))?;
+        let captured = self
+            .engine
+            .load_html(view, &html)
+            .and_then(|()| self.engine.capture_view_pixels(view));
+        self.engine.destroy_view(view)?;
+        let captured = captured?;
+        Ok((
+            Image {
+                width: captured.width,
+                height: captured.height,
+                rgba: captured.rgba,
+            },
+            captured.hash,
+        ))
+    }
+
+    /// Run one test.
+    pub fn run(&mut self, entry: &ManifestEntry) -> ReftestResult {
+        let mut result = ReftestResult {
+            entry: entry.clone(),
+            outcome: Outcome::Skipped,
+            hashes: None,
+            diff_path: None,
+        };
+        if entry.expectation == Expectation::Skip {
+            return result;
+        }
+
+        let rendered = self
+            .render(&entry.test)
+            .and_then(|test| Ok((test, self.render(&entry.reference)?)));
+        let ((test, test_hash), (reference, reference_hash)) = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                result.outcome = Outcome::Fail(e.to_string());
+                return result;
+            }
+        };
+        result.hashes = Some((test_hash, reference_hash));
+
+        let comparison = compare(&test, &reference, entry.tolerance);
+        let matches = comparison.matches(entry.tolerance);
+        let passed = matches == (entry.relation == Relation::Equal);
+        debug!(
+            test = %entry.test.display(),
+            differing = comparison.differing_pixels,
+            passed,
+            "Reftest compared"
+        );
+
+        result.outcome = match (passed, entry.expectation) {
+            (true, Expectation::Fail) => Outcome::UnexpectedPass,
+            (true, _) => Outcome::Pass,
+            (false, Expectation::Fail) => Outcome::ExpectedFail,
+            (false, _) if matches => Outcome::Fail("renderings match".to_string()),
+            (false, _) => Outcome::Fail(format!(
+                "{} pixels differ (max channel difference {})",
+                comparison.differing_pixels, comparison.max_channel_difference
+            )),
+        };
+        if !passed && entry.relation == Relation::Equal {
+            match self.write_artifacts(entry, &test, &reference) {
+                Ok(path) => result.diff_path = path,
+                Err(e) => warn!(error = %e, "Failed to write reftest artifacts"),
+            }
+        }
+        result
+    }
+
+    /// Load a manifest and run its tests in order.
+    pub fn run_manifest(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<ReftestResult>, ReftestError> {
+        let entries = load_manifest(path.as_ref())?;
+        Ok(entries.iter().map(|entry| self.run(entry)).collect())
+    }
+
+    fn write_artifacts(
+        &self,
+        entry: &ManifestEntry,
+        test: &Image,
+        reference: &Image,
+    ) -> Result<Option<PathBuf>, ReftestError> {
+        let Some(dir) = &self.artifact_dir else {
+            return Ok(None);
+        };
+        std::fs::create_dir_all(dir).map_err(|e| ReftestError::Artifact(e.to_string()))?;
+        let stem = entry
+            .test
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| format!("line-{}", entry.line));
+        let diff = diff_image(test, reference, entry.tolerance);
+        let diff_path = dir.join(format!("{}-diff.png", stem));
+        for (image, path) in [
+            (test, dir.join(format!("{}.png", stem))),
+            (reference, dir.join(format!("{}-ref.png", stem))),
+            (&diff, diff_path.clone()),
+        ] {
+            rustkit_renderer::save_png(&path, image.width, image.height, &image.rgba)
+                .map_err(|e| ReftestError::Artifact(e.to_string()))?;
+        }
+        Ok(Some(diff_path))
+    }
+}
@@ -0,0 +1,38 @@
+//! Runs the seeded reftest suite.
+
+use rustkit_reftests::{Outcome, ReftestRunner};
+use std::path::Path;
+
+const MANIFEST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reftests/reftest.list");
+
+#[test]
+fn test_reftest_suite() {
+    let artifacts = Path::new(env!("CARGO_TARGET_TMPDIR")).join("reftests");
+    let mut runner = ReftestRunner::new().unwrap().with_artifact_dir(&artifacts);
+
+    let first = runner.run_manifest(MANIFEST).unwrap();
+    let unexpected: Vec<_> = first
+        .iter()
+        .filter(|result| !result.outcome.is_expected())
+        .map(|result| format!("{}: {:?}", result.entry.test.display(), result.outcome))
+        .collect();
+    assert!(
+        unexpected.is_empty(),
+        "unexpected results:\n{}",
+        unexpected.join("\n")
+    );
+    assert!(first
+        .iter()
+        .any(|result| result.outcome == Outcome::ExpectedFail));
+    assert!(first
+        .iter()
+        .any(|result| result.outcome == Outcome::Skipped));
+
+    // A second run renders every page again and must see the same pixels.
+    let second = runner.run_manifest(MANIFEST).unwrap();
+    assert_eq!(first.len(), second.len());
+    for (a, b) in first.iter().zip(&second) {
+        assert_eq!(a.outcome, b.outcome, "{}", a.entry.test.display());
+        assert_eq!(a.hashes, b.hashes, "{}", a.entry.test.display());
+    }
+}
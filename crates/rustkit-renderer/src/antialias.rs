@@ -0,0 +1,88 @@
+//! Antialiasing of vector geometry.
+//!
+//! Lines, polygons and paths are drawn into a multisampled target that is
+//! resolved into the frame before text and images go on top, so glyph
+//! coverage is never sampled twice. Axis-aligned rectangles split by
+//! `PushClip` land on the same samples from both sides of a shared edge,
+//! so clipped content does not show seams.
+
+/// How vector geometry is antialiased.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntialiasMode {
+    /// Hard, aliased edges.
+    #[default]
+    None,
+    /// Multisampled rendering of frames containing vector commands.
+    Msaa,
+}
+
+/// Antialiasing configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntialiasSettings {
+    pub mode: AntialiasMode,
+    /// Samples per pixel for [`AntialiasMode::Msaa`].
+    pub sample_count: u32,
+}
+
+impl Default for AntialiasSettings {
+    fn default() -> Self {
+        Self {
+            mode: AntialiasMode::Msaa,
+            sample_count: 4,
+        }
+    }
+}
+
+impl AntialiasSettings {
+    /// Settings with antialiasing turned off.
+    pub fn disabled() -> Self {
+        Self {
+            mode: AntialiasMode::None,
+            sample_count: 1,
+        }
+    }
+
+    /// The settings a device supporting `supported_sample_counts` can
+    /// honour: the largest supported count not above the requested one.
+    /// Falls back to [`AntialiasMode::None`] when that leaves one sample.
+    pub fn resolve(self, supported_sample_counts: &[u32]) -> Self {
+        if self.mode == AntialiasMode::None {
+            return Self::disabled();
+        }
+        let count = supported_sample_counts
+            .iter()
+            .copied()
+            .filter(|&count| count <= self.sample_count)
+            .max()
+            .unwrap_or(1);
+        if count <= 1 {
+            return Self::disabled();
+        }
+        Self {
+            mode: AntialiasMode::Msaa,
+            sample_count: count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_sample_count() {
+        let msaa = |sample_count| AntialiasSettings {
+            mode: AntialiasMode::Msaa,
+            sample_count,
+        };
+        assert_eq!(msaa(4).resolve(&[1, 4]), msaa(4));
+        assert_eq!(msaa(8).resolve(&[1, 2, 4]), msaa(4));
+        assert_eq!(msaa(3).resolve(&[1, 2, 4, 8]), msaa(2));
+        assert_eq!(msaa(4).resolve(&[1]), AntialiasSettings::disabled());
+        assert_eq!(msaa(1).resolve(&[1, 4]), AntialiasSettings::disabled());
+        assert_eq!(
+            AntialiasSettings::disabled().resolve(&[1, 4]),
+            AntialiasSettings::disabled()
+        );
+    }
+}
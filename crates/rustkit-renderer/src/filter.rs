@@ -0,0 +1,558 @@
+//! `filter` and `backdrop-filter` passes.
+//!
+//! A filter chain runs function by function over a premultiplied image.
+//! Blurs are separable gaussians, a horizontal pass then a vertical one,
+//! whose taps spread further apart once a radius needs more than
+//! [`MAX_BLUR_TAPS`] of them a side, as if sampling a downsampled image;
+//! standard deviations past [`MAX_BLUR_SIGMA`] are clamped. The color
+//! functions are 4x5 matrices applied to unpremultiplied color and clamped,
+//! as the Filter Effects spec defines them. Edges repeat outwards.
+//!
+//! The math here is shared by the software rasterizer, which runs it on
+//! pixel buffers, and the GPU path, which runs it as fullscreen passes
+//! through `shaders/filter.wgsl`.
+
+use bytemuck::{Pod, Zeroable};
+use rustkit_css::{FilterChain, FilterFunction};
+use wgpu::util::DeviceExt;
+
+use crate::create_filter_pipeline;
+
+/// Largest blur standard deviation drawn, in pixels.
+pub const MAX_BLUR_SIGMA: f32 = 64.0;
+
+/// Most taps a blur samples on each side of a pixel.
+pub const MAX_BLUR_TAPS: u32 = 24;
+
+/// Rows for red, green, blue and alpha, each weighing the input's
+/// channels and adding a constant.
+pub(crate) type ColorMatrix = [[f32; 5]; 4];
+
+/// Sampling of one blur pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Kernel {
+    /// Pixels between taps.
+    pub step: u32,
+    /// Taps on each side.
+    pub radius: u32,
+    /// Standard deviation, in taps.
+    pub sigma: f32,
+}
+
+impl Kernel {
+    /// The kernel of a blur by `sigma` pixels, or `None` where it leaves
+    /// the image as it is.
+    pub(crate) fn new(sigma: f32) -> Option<Self> {
+        let sigma = sigma.min(MAX_BLUR_SIGMA);
+        if sigma < 0.25 {
+            return None;
+        }
+        let reach = (3.0 * sigma).ceil();
+        let step = (reach / MAX_BLUR_TAPS as f32).ceil().max(1.0);
+        Some(Self {
+            step: step as u32,
+            radius: (reach / step).ceil() as u32,
+            sigma: sigma / step,
+        })
+    }
+
+    /// Normalized weights of the taps from `-radius` to `radius`.
+    fn weights(&self) -> Vec<f32> {
+        let radius = self.radius as i32;
+        let weights: Vec<f32> = (-radius..=radius)
+            .map(|i| (-((i * i) as f32) / (2.0 * self.sigma * self.sigma)).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+        weights.into_iter().map(|w| w / total).collect()
+    }
+}
+
+/// The matrix of a color function; `None` for blurs.
+pub(crate) fn color_matrix(function: &FilterFunction) -> Option<ColorMatrix> {
+    let matrix = match *function {
+        FilterFunction::Blur(_) => return None,
+        FilterFunction::Brightness(b) => [
+            [b, 0.0, 0.0, 0.0, 0.0],
+            [0.0, b, 0.0, 0.0, 0.0],
+            [0.0, 0.0, b, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 0.0],
+        ],
+        FilterFunction::Contrast(c) => {
+            let offset = 0.5 - 0.5 * c;
+            [
+                [c, 0.0, 0.0, 0.0, offset],
+                [0.0, c, 0.0, 0.0, offset],
+                [0.0, 0.0, c, 0.0, offset],
+                [0.0, 0.0, 0.0, 1.0, 0.0],
+            ]
+        }
+        FilterFunction::Grayscale(amount) => {
+            let k = 1.0 - amount;
+            [
+                [
+                    0.2126 + 0.7874 * k,
+                    0.7152 - 0.7152 * k,
+                    0.0722 - 0.0722 * k,
+                    0.0,
+                    0.0,
+                ],
+                [
+                    0.2126 - 0.2126 * k,
+                    0.7152 + 0.2848 * k,
+                    0.0722 - 0.0722 * k,
+                    0.0,
+                    0.0,
+                ],
+                [
+                    0.2126 - 0.2126 * k,
+                    0.7152 - 0.7152 * k,
+                    0.0722 + 0.9278 * k,
+                    0.0,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0, 0.0],
+            ]
+        }
+        FilterFunction::Opacity(o) => [
+            [1.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, o, 0.0],
+        ],
+        FilterFunction::Saturate(s) => [
+            [
+                0.213 + 0.787 * s,
+                0.715 - 0.715 * s,
+                0.072 - 0.072 * s,
+                0.0,
+                0.0,
+            ],
+            [
+                0.213 - 0.213 * s,
+                0.715 + 0.285 * s,
+                0.072 - 0.072 * s,
+                0.0,
+                0.0,
+            ],
+            [
+                0.213 - 0.213 * s,
+                0.715 - 0.715 * s,
+                0.072 + 0.928 * s,
+                0.0,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0, 0.0],
+        ],
+    };
+    Some(matrix)
+}
+
+/// Run `chain` over a `width` x `height` image of premultiplied pixels.
+pub(crate) fn apply(chain: &FilterChain, pixels: &mut [[f32; 4]], width: usize, height: usize) {
+    for function in &chain.functions {
+        match (function, color_matrix(function)) {
+            (_, Some(matrix)) => pixels.iter_mut().for_each(|p| *p = transform(&matrix, *p)),
+            (FilterFunction::Blur(sigma), None) => {
+                if let Some(kernel) = Kernel::new(*sigma) {
+                    blur(pixels, width, height, &kernel, (1, 0));
+                    blur(pixels, width, height, &kernel, (0, 1));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Apply `matrix` to a premultiplied pixel.
+fn transform(matrix: &ColorMatrix, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    let input = if a > 0.0 {
+        [r / a, g / a, b / a, a]
+    } else {
+        [0.0; 4]
+    };
+    let [r, g, b, a] = matrix.map(|row| {
+        let sum: f32 = row[..4].iter().zip(input).map(|(w, c)| w * c).sum();
+        (sum + row[4]).clamp(0.0, 1.0)
+    });
+    [r * a, g * a, b * a, a]
+}
+
+/// One pass of a separable blur along `(dx, dy)`.
+fn blur(
+    pixels: &mut [[f32; 4]],
+    width: usize,
+    height: usize,
+    kernel: &Kernel,
+    (dx, dy): (usize, usize),
+) {
+    let weights = kernel.weights();
+    let radius = kernel.radius as isize;
+    let step = kernel.step as isize;
+    let source = pixels.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 4];
+            for (i, weight) in (-radius..=radius).zip(&weights) {
+                let sx = (x as isize + i * step * dx as isize).clamp(0, width as isize - 1);
+                let sy = (y as isize + i * step * dy as isize).clamp(0, height as isize - 1);
+                let tap = source[sy as usize * width + sx as usize];
+                for (channel, value) in sum.iter_mut().zip(tap) {
+                    *channel += value * weight;
+                }
+            }
+            pixels[y * width + x] = sum;
+        }
+    }
+}
+
+/// Per-pass uniforms of `shaders/filter.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct FilterParams {
+    /// Columns of the matrix's first four columns.
+    matrix: [[f32; 4]; 4],
+    offset: [f32; 4],
+    /// Texture coordinates between blur taps.
+    direction: [f32; 2],
+    /// In taps.
+    sigma: f32,
+    radius: f32,
+}
+
+impl FilterParams {
+    fn copy() -> Self {
+        Self::zeroed()
+    }
+
+    fn blur(kernel: &Kernel, direction: [f32; 2]) -> Self {
+        Self {
+            direction: direction.map(|d| d * kernel.step as f32),
+            sigma: kernel.sigma,
+            radius: kernel.radius as f32,
+            ..Self::zeroed()
+        }
+    }
+
+    fn color(matrix: &ColorMatrix) -> Self {
+        Self {
+            matrix: std::array::from_fn(|column| matrix.map(|row| row[column])),
+            offset: matrix.map(|row| row[4]),
+            ..Self::zeroed()
+        }
+    }
+}
+
+/// How a filter pass writes its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Write {
+    /// Replace what is there.
+    Replace,
+    /// Composite the premultiplied source over it.
+    Over,
+}
+
+/// The pipelines, sampler and intermediate textures of the GPU filter
+/// passes.
+pub(crate) struct GpuFilters {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    blur: wgpu::RenderPipeline,
+    color_matrix: wgpu::RenderPipeline,
+    replace: wgpu::RenderPipeline,
+    over: wgpu::RenderPipeline,
+    /// Frame, layer and scratch textures, kept while the target's size
+    /// and format stay the same.
+    textures: Vec<wgpu::Texture>,
+}
+
+impl GpuFilters {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("filter_bind_group_layout"),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Filter Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let pipeline = |entry_point, blend| {
+            create_filter_pipeline(
+                device,
+                format,
+                &bind_group_layout,
+                entry_point,
+                blend,
+                cache,
+            )
+        };
+        Self {
+            blur: pipeline("fs_blur", None),
+            color_matrix: pipeline("fs_color_matrix", None),
+            replace: pipeline("fs_copy", None),
+            over: pipeline(
+                "fs_copy",
+                Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            ),
+            bind_group_layout,
+            sampler,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Views of `count` textures the size and format of `target`, each
+    /// sampleable and drawable.
+    pub(crate) fn targets(
+        &mut self,
+        device: &wgpu::Device,
+        target: &wgpu::Texture,
+        count: usize,
+    ) -> Vec<wgpu::TextureView> {
+        let matches = |texture: &wgpu::Texture| {
+            texture.size() == target.size() && texture.format() == target.format()
+        };
+        if !self.textures.iter().all(matches) {
+            self.textures.clear();
+        }
+        while self.textures.len() < count {
+            self.textures
+                .push(device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Filter Layer"),
+                    size: target.size(),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: target.format(),
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                }));
+        }
+        self.textures[..count]
+            .iter()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect()
+    }
+
+    /// Draw `source` onto `target` within `scissor`, a pixel rectangle
+    /// (the whole target when `None`).
+    pub(crate) fn copy(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        write: Write,
+        scissor: Option<[u32; 4]>,
+    ) {
+        let pipeline = match write {
+            Write::Replace => &self.replace,
+            Write::Over => &self.over,
+        };
+        self.pass(
+            device,
+            encoder,
+            pipeline,
+            FilterParams::copy(),
+            source,
+            target,
+            scissor,
+        );
+    }
+
+    /// Run `chain` over `views[input]` with blurs scaled by `scale`,
+    /// ping-ponging through `views[scratch[0]]` and `views[scratch[1]]`.
+    /// Returns the index of the view holding the result.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn apply(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        chain: &FilterChain,
+        scale: f32,
+        views: &[wgpu::TextureView],
+        input: usize,
+        scratch: [usize; 2],
+        size: (u32, u32),
+    ) -> usize {
+        let mut current = input;
+        let mut run = |pipeline: &wgpu::RenderPipeline, params: FilterParams| {
+            let next = if current == scratch[0] {
+                scratch[1]
+            } else {
+                scratch[0]
+            };
+            self.pass(
+                device,
+                encoder,
+                pipeline,
+                params,
+                &views[current],
+                &views[next],
+                None,
+            );
+            current = next;
+        };
+        for function in &chain.functions {
+            match (function, color_matrix(function)) {
+                (_, Some(matrix)) => run(&self.color_matrix, FilterParams::color(&matrix)),
+                (FilterFunction::Blur(sigma), None) => {
+                    if let Some(kernel) = Kernel::new(sigma * scale) {
+                        let texel = [1.0 / size.0 as f32, 1.0 / size.1 as f32];
+                        run(&self.blur, FilterParams::blur(&kernel, [texel[0], 0.0]));
+                        run(&self.blur, FilterParams::blur(&kernel, [0.0, texel[1]]));
+                    }
+                }
+                _ => {}
+            }
+        }
+        current
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        params: FilterParams,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        scissor: Option<[u32; 4]>,
+    ) {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Filter Params"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("filter_bind_group"),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Filter Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if let Some([x, y, width, height]) = scissor {
+            if width == 0 || height == 0 {
+                return;
+            }
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_blurs_spread_their_taps() {
+        assert_eq!(Kernel::new(0.1), None);
+        let small = Kernel::new(2.0).unwrap();
+        assert_eq!((small.step, small.radius, small.sigma), (1, 6, 2.0));
+
+        let large = Kernel::new(40.0).unwrap();
+        assert!(large.radius <= MAX_BLUR_TAPS);
+        assert_eq!(large.step, 5);
+        assert_eq!(large.sigma, 8.0);
+        assert_eq!(Kernel::new(500.0), Kernel::new(MAX_BLUR_SIGMA));
+
+        let weights = small.weights();
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(weights[0], weights[12]);
+    }
+
+    #[test]
+    fn test_color_matrices() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let gray = transform(&color_matrix(&FilterFunction::Grayscale(1.0)).unwrap(), red);
+        assert_eq!(gray[0], gray[1]);
+        assert_eq!(gray[1], gray[2]);
+
+        let identity = [
+            FilterFunction::Brightness(1.0),
+            FilterFunction::Contrast(1.0),
+            FilterFunction::Grayscale(0.0),
+            FilterFunction::Opacity(1.0),
+            FilterFunction::Saturate(1.0),
+        ];
+        let color = [0.3, 0.2, 0.1, 0.5];
+        for function in identity {
+            let out = transform(&color_matrix(&function).unwrap(), color);
+            for (a, b) in out.iter().zip(color) {
+                assert!((a - b).abs() < 1e-3, "{function:?}: {out:?}");
+            }
+        }
+
+        // Brightness clamps; opacity scales premultiplied color with alpha
+        let bright = color_matrix(&FilterFunction::Brightness(4.0)).unwrap();
+        assert_eq!(
+            transform(&bright, [0.5, 0.1, 0.0, 1.0]),
+            [1.0, 0.4, 0.0, 1.0]
+        );
+        let faded = color_matrix(&FilterFunction::Opacity(0.5)).unwrap();
+        assert_eq!(transform(&faded, red), [0.5, 0.0, 0.0, 0.5]);
+    }
+}
@@ -20,6 +20,101 @@ pub struct GlyphKey {
     pub font_size: u32, // Fixed-point (size * 10)
     pub font_weight: u16,
     pub font_style: u8, // 0 = normal, 1 = italic
+    /// Horizontal pen offset in quarter pixels (0..SUBPIXEL_STEPS).
+    pub subpixel_x: u8,
+}
+
+/// Number of horizontal subpixel positions a glyph can be rasterized at.
+pub const SUBPIXEL_STEPS: u8 = 4;
+
+/// Text rasterization settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextRenderingSettings {
+    /// Place glyphs at quarter-pixel x positions instead of whole pixels.
+    pub subpixel_positioning: bool,
+    /// Coverage gamma for text. 1.0 disables correction; values are clamped
+    /// to 1.0..=3.0.
+    pub gamma: f32,
+}
+
+impl Default for TextRenderingSettings {
+    fn default() -> Self {
+        Self {
+            subpixel_positioning: true,
+            gamma: 1.8,
+        }
+    }
+}
+
+/// Split a pen x position into the whole pixel the glyph is drawn at and
+/// its quarter-pixel offset.
+///
+/// Without subpixel positioning the offset is always 0, so every position
+/// within a pixel shares one cache entry.
+pub fn quantize_subpixel(x: f32, subpixel_positioning: bool) -> (f32, u8) {
+    let steps = SUBPIXEL_STEPS as f32;
+    let quarters = (x * steps).round();
+    let pixel = (quarters / steps).floor();
+    if !subpixel_positioning {
+        return (pixel, 0);
+    }
+    (pixel, (quarters - pixel * steps) as u8)
+}
+
+/// Coverage lookup table boosting glyph coverage like DirectWrite's
+/// contrast enhancement, so thin stems keep their weight after blending.
+fn gamma_lut(gamma: f32) -> [u8; 256] {
+    let inverse = 1.0 / gamma.clamp(1.0, 3.0);
+    let mut lut = [0u8; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        *value = ((i as f32 / 255.0).powf(inverse) * 255.0).round() as u8;
+    }
+    lut
+}
+
+/// Shift a coverage bitmap right by `fraction` of a pixel with a box filter.
+///
+/// Returns the new width, which grows by one column when shifted.
+fn shift_coverage(bitmap: &[u8], width: u32, height: u32, fraction: f32) -> (u32, Vec<u8>) {
+    if fraction <= 0.0 {
+        return (width, bitmap.to_vec());
+    }
+    let (w, h) = (width as usize, height as usize);
+    let mut shifted = vec![0u8; (w + 1) * h];
+    for y in 0..h {
+        let row = &bitmap[y * w..(y + 1) * w];
+        for x in 0..=w {
+            let current = row.get(x).copied().unwrap_or(0) as f32;
+            let previous = if x > 0 { row[x - 1] as f32 } else { 0.0 };
+            shifted[y * (w + 1) + x] =
+                (current * (1.0 - fraction) + previous * fraction).round() as u8;
+        }
+    }
+    (width + 1, shifted)
+}
+
+/// Placeholder coverage for a glyph when no font rasterizer is available:
+/// a bordered box for visible characters, empty for whitespace.
+fn fallback_coverage(key: &GlyphKey) -> (u32, u32, Vec<u8>) {
+    let font_size = key.font_size as f32 / 10.0;
+    let (glyph_width, glyph_height) = estimate_glyph_size(key.codepoint, font_size);
+    let glyph_width = glyph_width.clamp(1, 256);
+    let glyph_height = glyph_height.clamp(1, 256);
+
+    let mut bitmap = vec![0u8; (glyph_width * glyph_height) as usize];
+    if key.codepoint.is_ascii_graphic() || key.codepoint.is_alphabetic() {
+        for y in 0..glyph_height {
+            for x in 0..glyph_width {
+                let idx = (y * glyph_width + x) as usize;
+                let border = x == 0 || x == glyph_width - 1 || y == 0 || y == glyph_height - 1;
+                bitmap[idx] = if border { 255 } else { 200 };
+            }
+        }
+    }
+
+    let fraction = key.subpixel_x as f32 / SUBPIXEL_STEPS as f32;
+    let (width, bitmap) = shift_coverage(&bitmap, glyph_width, glyph_height, fraction);
+    (width, glyph_height, bitmap)
 }
 
 /// Cached glyph entry.
@@ -40,6 +135,10 @@ pub struct GlyphCache {
     bind_group: wgpu::BindGroup,
     atlas_size: u32,
     entries: HashMap<GlyphKey, GlyphEntry>,
+    /// Entries cached only because of a non-zero subpixel offset.
+    subpixel_entries: usize,
+    settings: TextRenderingSettings,
+    gamma_lut: [u8; 256],
     /// CPU mirror of the atlas (R8 coverage). Used for deterministic debug dumps.
     cpu_atlas: Vec<u8>,
     next_x: u32,
@@ -130,6 +229,9 @@ impl GlyphCache {
             bind_group,
             atlas_size,
             entries: HashMap::new(),
+            subpixel_entries: 0,
+            settings: TextRenderingSettings::default(),
+            gamma_lut: gamma_lut(TextRenderingSettings::default().gamma),
             cpu_atlas: empty_data,
             next_x: 1, // Start at 1 to avoid edge artifacts
             next_y: 1,
@@ -142,6 +244,39 @@ impl GlyphCache {
         self.atlas_size
     }
 
+    /// Current text rendering settings.
+    pub fn text_rendering(&self) -> TextRenderingSettings {
+        self.settings
+    }
+
+    /// Change the text rendering settings. Cached glyphs are dropped when
+    /// the settings change, since their coverage depends on them.
+    pub fn set_text_rendering(&mut self, settings: TextRenderingSettings) {
+        if settings != self.settings {
+            self.settings = settings;
+            self.gamma_lut = gamma_lut(settings.gamma);
+            self.clear();
+        }
+    }
+
+    /// Number of cached glyphs that only exist as subpixel variants.
+    pub fn subpixel_entries(&self) -> usize {
+        self.subpixel_entries
+    }
+
+    fn insert_entry(&mut self, key: &GlyphKey, entry: GlyphEntry) {
+        let added = self.entries.insert(key.clone(), entry).is_none();
+        if added && key.subpixel_x != 0 {
+            self.subpixel_entries += 1;
+        }
+    }
+
+    fn apply_gamma(&self, coverage: &mut [u8]) {
+        for value in coverage {
+            *value = self.gamma_lut[*value as usize];
+        }
+    }
+
     /// Get the bind group for the atlas texture.
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
@@ -372,7 +507,7 @@ impl GlyphCache {
                     offset: [0.0, 0.0],
                     advance: advance_width,
                 };
-                self.entries.insert(key.clone(), entry.clone());
+                self.insert_entry(key, entry.clone());
                 return Some(entry);
             }
             
@@ -395,7 +530,7 @@ impl GlyphCache {
                 None,
                 DWRITE_RENDERING_MODE_NATURAL,
                 DWRITE_MEASURING_MODE_NATURAL,
-                0.0, // baseline origin x
+                key.subpixel_x as f32 / SUBPIXEL_STEPS as f32, // baseline origin x
                 0.0, // baseline origin y
             ) {
                 Ok(a) => a,
@@ -464,6 +599,8 @@ impl GlyphCache {
                 }
             }
 
+            self.apply_gamma(&mut alpha_values);
+
             // Debug dump + CPU atlas mirror before upload.
             self.maybe_dump_glyph_bitmap(key, tex_width, tex_height, &alpha_values);
             self.blit_into_cpu_atlas(atlas_x + 1, atlas_y + 1, tex_width, tex_height, &alpha_values);
@@ -524,7 +661,7 @@ impl GlyphCache {
                 advance: advance_width,
             };
             
-            self.entries.insert(key.clone(), entry.clone());
+            self.insert_entry(key, entry.clone());
             Some(entry)
         }
     }
@@ -535,8 +672,6 @@ impl GlyphCache {
         queue: &wgpu::Queue,
         key: &GlyphKey,
     ) -> Option<GlyphEntry> {
-        let font_size = key.font_size as f32 / 10.0;
-        
         #[cfg(windows)]
         {
             // Silence unused import warnings
@@ -544,31 +679,16 @@ impl GlyphCache {
             let _ = |s: u8| match s { 0 => RkFontStyle::Normal, 1 => RkFontStyle::Italic, _ => RkFontStyle::Normal };
         }
 
-        // Estimate glyph dimensions based on character (fallback)
-        let (glyph_width, glyph_height) = estimate_glyph_size(key.codepoint, font_size);
-
-        let glyph_width = glyph_width.max(1).min(256);
-        let glyph_height = glyph_height.max(1).min(256);
+        let font_size = key.font_size as f32 / 10.0;
+        let advance = estimate_glyph_size(key.codepoint, font_size)
+            .0
+            .clamp(1, 256) as f32;
+        let (glyph_width, glyph_height, mut bitmap) = fallback_coverage(key);
+        self.apply_gamma(&mut bitmap);
 
         // Allocate space
         let (atlas_x, atlas_y) = self.allocate_space(glyph_width + 2, glyph_height + 2)?;
 
-        // Create simple glyph bitmap (filled rectangle for fallback)
-        let mut bitmap = vec![0u8; (glyph_width * glyph_height) as usize];
-        
-        // For printable characters, create a visible shape
-        if key.codepoint.is_ascii_graphic() || key.codepoint.is_alphabetic() {
-            for y in 0..glyph_height {
-                for x in 0..glyph_width {
-                    let idx = (y * glyph_width + x) as usize;
-                    // Create a simple pattern
-                    let border = x == 0 || x == glyph_width - 1 || y == 0 || y == glyph_height - 1;
-                    bitmap[idx] = if border { 255 } else { 200 };
-                }
-            }
-        }
-        // Whitespace characters remain transparent
-
         // Upload
         self.maybe_dump_glyph_bitmap(key, glyph_width, glyph_height, &bitmap);
         self.blit_into_cpu_atlas(atlas_x + 1, atlas_y + 1, glyph_width, glyph_height, &bitmap);
@@ -606,10 +726,11 @@ impl GlyphCache {
         let entry = GlyphEntry {
             tex_coords: [u0, v0, u1, v1],
             offset: [0.0, 0.0], // Start at line top
-            advance: glyph_width as f32,
+            // The subpixel shift widens the bitmap but not the advance
+            advance,
         };
 
-        self.entries.insert(key.clone(), entry.clone());
+        self.insert_entry(key, entry.clone());
         Some(entry)
     }
 
@@ -626,6 +747,7 @@ impl GlyphCache {
         if self.next_y + height > self.atlas_size {
             tracing::warn!("Glyph atlas full, clearing cache");
             self.entries.clear();
+            self.subpixel_entries = 0;
             self.next_x = 1;
             self.next_y = 1;
             self.row_height = 0;
@@ -643,11 +765,33 @@ impl GlyphCache {
     /// Clear the cache.
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.subpixel_entries = 0;
         self.cpu_atlas.fill(0);
         self.next_x = 1;
         self.next_y = 1;
         self.row_height = 0;
     }
+
+    /// Bytes of atlas rows holding glyphs.
+    pub fn memory_usage(&self) -> usize {
+        let rows = if self.entries.is_empty() {
+            0
+        } else {
+            (self.next_y + self.row_height).min(self.atlas_size)
+        };
+        rows as usize * self.atlas_size as usize
+    }
+
+    /// Empty the atlas if its glyphs take more than `target_bytes`; glyphs
+    /// are rasterized again as text is drawn. Returns the bytes freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let used = self.memory_usage();
+        if used <= target_bytes {
+            return 0;
+        }
+        self.clear();
+        used
+    }
     
     /// Dump the glyph atlas to a PNG file for debugging.
     ///
@@ -840,6 +984,7 @@ mod tests {
             font_size: 160,
             font_weight: 400,
             font_style: 0,
+            subpixel_x: 0,
         };
 
         let key2 = GlyphKey {
@@ -848,6 +993,7 @@ mod tests {
             font_size: 160,
             font_weight: 400,
             font_style: 0,
+            subpixel_x: 0,
         };
 
         assert_eq!(key1, key2);
@@ -861,6 +1007,7 @@ mod tests {
             font_size: 160,
             font_weight: 400,
             font_style: 0,
+            subpixel_x: 0,
         };
 
         let key2 = GlyphKey {
@@ -869,11 +1016,93 @@ mod tests {
             font_size: 160,
             font_weight: 400,
             font_style: 0,
+            subpixel_x: 0,
         };
 
         assert_ne!(key1, key2);
     }
 
+    /// Rasterize `text` with the fallback rasterizer into a single line of
+    /// coverage starting at `x`, the way `Renderer::draw_text` places glyphs.
+    fn render_line(text: &str, x: f32, settings: TextRenderingSettings) -> Vec<u8> {
+        const WIDTH: usize = 128;
+        let lut = gamma_lut(settings.gamma);
+        let mut canvas = vec![0u8; WIDTH * 16];
+        let mut cursor_x = x;
+        for ch in text.chars() {
+            let (pen_x, subpixel_x) = quantize_subpixel(cursor_x, settings.subpixel_positioning);
+            let key = GlyphKey {
+                codepoint: ch,
+                font_family: "sans-serif".to_string(),
+                font_size: 160,
+                font_weight: 400,
+                font_style: 0,
+                subpixel_x,
+            };
+            let (w, h, bitmap) = fallback_coverage(&key);
+            for row in 0..h as usize {
+                for col in 0..w as usize {
+                    let target = row * WIDTH + pen_x as usize + col;
+                    let value = lut[bitmap[row * w as usize + col] as usize];
+                    canvas[target] = canvas[target].max(value);
+                }
+            }
+            cursor_x += estimate_glyph_size(ch, 16.0).0 as f32;
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_quantize_subpixel() {
+        assert_eq!(quantize_subpixel(10.3, true), (10.0, 1));
+        assert_eq!(quantize_subpixel(10.5, true), (10.0, 2));
+        assert_eq!(quantize_subpixel(10.9, true), (11.0, 0));
+        assert_eq!(quantize_subpixel(10.5, false), (10.0, 0));
+        assert_eq!(quantize_subpixel(-0.25, true), (-1.0, 3));
+    }
+
+    #[test]
+    fn test_subpixel_offsets_change_coverage() {
+        let enabled = TextRenderingSettings::default();
+        let lines: Vec<_> = [0.0, 0.25, 0.5]
+            .iter()
+            .map(|&x| render_line("Hi!", x, enabled))
+            .collect();
+        assert_ne!(lines[0], lines[1]);
+        assert_ne!(lines[1], lines[2]);
+        assert_ne!(lines[0], lines[2]);
+
+        let disabled = TextRenderingSettings {
+            subpixel_positioning: false,
+            ..enabled
+        };
+        let lines: Vec<_> = [0.0, 0.25, 0.5]
+            .iter()
+            .map(|&x| render_line("Hi!", x, disabled))
+            .collect();
+        assert_eq!(lines[0], lines[1]);
+        assert_eq!(lines[1], lines[2]);
+    }
+
+    #[test]
+    fn test_shift_coverage_preserves_ink() {
+        let bitmap = [255u8, 200, 255];
+        let (width, shifted) = shift_coverage(&bitmap, 3, 1, 0.25);
+        assert_eq!(width, 4);
+        assert_eq!(shifted, [191, 214, 241, 64]);
+    }
+
+    #[test]
+    fn test_gamma_lut() {
+        let identity = gamma_lut(1.0);
+        assert!(identity.iter().enumerate().all(|(i, &v)| v as usize == i));
+
+        let corrected = gamma_lut(1.8);
+        assert_eq!((corrected[0], corrected[255]), (0, 255));
+        assert!(corrected[64] > 64);
+        assert!(corrected.windows(2).all(|w| w[0] <= w[1]));
+    }
+
     #[test]
     fn test_estimate_glyph_size() {
         let (w, h) = estimate_glyph_size('A', 16.0);
@@ -42,17 +42,24 @@
 
 use bytemuck::{Pod, Zeroable};
 use hashbrown::HashMap;
-use rustkit_css::Color;
+use rustkit_css::{Color, FilterChain};
 use rustkit_layout::{DisplayCommand, Rect};
+use std::borrow::Cow;
+use std::ops::Range;
 use std::sync::Arc;
 use thiserror::Error;
 use wgpu::util::DeviceExt;
 
+mod antialias;
+mod filter;
 mod glyph;
+mod outline;
 mod pipeline;
 mod shaders;
 pub mod screenshot;
+pub mod software;
 
+pub use antialias::*;
 pub use glyph::*;
 pub use pipeline::*;
 pub use screenshot::*;
@@ -89,6 +96,27 @@ pub struct RenderStats {
     pub texture_index_count: usize,
     pub clip_stack_depth: usize,
     pub stacking_context_depth: usize,
+    /// Total render passes submitted since the renderer was created.
+    pub frames_rendered: u64,
+    /// Glyph cache entries that exist only as subpixel-offset variants.
+    pub subpixel_glyph_entries: usize,
+    /// Antialiasing in effect for vector geometry.
+    pub antialias_mode: AntialiasMode,
+    /// Samples per pixel vector frames are drawn with.
+    pub msaa_sample_count: u32,
+    /// Commands in the display list, before culling to the viewport.
+    pub commands_total: usize,
+    /// Commands the last frame drew.
+    pub commands_drawn: usize,
+}
+
+/// GPU memory held by a renderer's caches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RendererMemory {
+    /// Image textures.
+    pub texture_bytes: usize,
+    /// Atlas rows holding rasterized glyphs.
+    pub glyph_atlas_bytes: usize,
 }
 
 /// Generate a simple ISO8601-ish timestamp without external dependencies.
@@ -296,6 +324,15 @@ impl TextureCache {
         self.textures.get(key).unwrap()
     }
 
+    /// Key of an image's animation frame: the URL itself for the first
+    /// frame, so still images need no key of their own.
+    pub fn frame_key(url: &str, frame: usize) -> Cow<'_, str> {
+        match frame {
+            0 => Cow::Borrowed(url),
+            _ => Cow::Owned(format!("{url}#frame={frame}")),
+        }
+    }
+
     /// Check if a texture exists.
     pub fn contains(&self, key: &str) -> bool {
         self.textures.contains_key(key)
@@ -310,6 +347,35 @@ impl TextureCache {
     pub fn clear(&mut self) {
         self.textures.clear();
     }
+
+    /// Bytes of texture memory held.
+    pub fn memory_usage(&self) -> usize {
+        self.textures
+            .values()
+            .map(|t| t.width as usize * t.height as usize * 4)
+            .sum()
+    }
+
+    /// Drop textures, largest first, until at most `target_bytes` are held.
+    /// Returns the bytes freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let before = self.memory_usage();
+        let mut held = before;
+        let mut by_size: Vec<(usize, String)> = self
+            .textures
+            .iter()
+            .map(|(key, t)| (t.width as usize * t.height as usize * 4, key.clone()))
+            .collect();
+        by_size.sort_by(|a, b| b.cmp(a));
+        for (size, key) in by_size {
+            if held <= target_bytes {
+                break;
+            }
+            self.textures.remove(&key);
+            held -= size;
+        }
+        before - held
+    }
 }
 
 // ==================== Renderer ====================
@@ -322,8 +388,14 @@ pub struct Renderer {
 
     // Pipelines
     color_pipeline: wgpu::RenderPipeline,
+    msaa_color_pipeline: Option<wgpu::RenderPipeline>,
     texture_pipeline: wgpu::RenderPipeline,
 
+    // Antialiasing
+    antialiasing: AntialiasSettings,
+    msaa_texture: Option<wgpu::Texture>,
+    vector_commands: usize,
+
     // Uniform buffer
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
@@ -339,12 +411,27 @@ pub struct Renderer {
     clip_stack: Vec<Rect>,
     stacking_contexts: Vec<StackingContext>,
 
+    // Filter layers: the frame's batches split at layer boundaries
+    filters: filter::GpuFilters,
+    steps: Vec<LayerStep>,
+    span_start: (u32, u32),
+
+    // Render pass counter
+    frames_rendered: u64,
+    /// Display list length and commands drawn in the last frame.
+    commands_total: usize,
+    commands_drawn: usize,
+
     // Caches
     texture_cache: TextureCache,
     glyph_cache: GlyphCache,
 
     // Texture bind group layout (for sharing)
     texture_bind_group_layout: wgpu::BindGroupLayout,
+
+    // Cache pipelines compiled later are added to
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 /// A stacking context for z-ordering.
@@ -354,12 +441,43 @@ pub struct StackingContext {
     pub rect: Rect,
 }
 
+/// A step of a frame with filter layers.
+#[derive(Debug, Clone)]
+enum LayerStep {
+    /// Draw these color and texture indices onto the open layer.
+    Draw {
+        color: Range<u32>,
+        texture: Range<u32>,
+    },
+    /// Filter the backdrop within `rect`, then open a layer.
+    Push {
+        rect: Rect,
+        clip: Option<Rect>,
+        filter: FilterChain,
+        backdrop_filter: FilterChain,
+    },
+    /// Filter the open layer and composite it over the one below.
+    Pop,
+}
+
 impl Renderer {
-    /// Create a new renderer.
+    /// Create a new renderer. Vector geometry is aliased until
+    /// [`Renderer::set_antialiasing`] enables MSAA.
     pub fn new(
         device: Arc<wgpu::Device>,
         queue: Arc<wgpu::Queue>,
         surface_format: wgpu::TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Self::with_pipeline_cache(device, queue, surface_format, None)
+    }
+
+    /// Create a renderer whose pipelines are compiled through `cache`, so
+    /// a cache filled by an earlier run skips shader compilation.
+    pub fn with_pipeline_cache(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        surface_format: wgpu::TextureFormat,
+        pipeline_cache: Option<wgpu::PipelineCache>,
     ) -> Result<Self, RendererError> {
         // Create uniform buffer
         let uniforms = Uniforms {
@@ -426,6 +544,8 @@ impl Renderer {
             &device,
             surface_format,
             &uniform_bind_group_layout,
+            1,
+            pipeline_cache.as_ref(),
         );
 
         let texture_pipeline = create_texture_pipeline(
@@ -433,8 +553,11 @@ impl Renderer {
             surface_format,
             &uniform_bind_group_layout,
             &texture_bind_group_layout,
+            pipeline_cache.as_ref(),
         );
 
+        let filters = filter::GpuFilters::new(&device, surface_format, pipeline_cache.as_ref());
+
         // Create caches
         let texture_cache = TextureCache::new(&device, texture_bind_group_layout.clone());
         let glyph_cache = GlyphCache::new(&device, &queue, texture_bind_group_layout.clone())?;
@@ -444,7 +567,11 @@ impl Renderer {
             queue,
             surface_format,
             color_pipeline,
+            msaa_color_pipeline: None,
             texture_pipeline,
+            antialiasing: AntialiasSettings::disabled(),
+            msaa_texture: None,
+            vector_commands: 0,
             uniform_buffer,
             uniform_bind_group,
             viewport_size: (800, 600),
@@ -454,9 +581,17 @@ impl Renderer {
             texture_indices: Vec::with_capacity(8192),
             clip_stack: Vec::new(),
             stacking_contexts: Vec::new(),
+            filters,
+            steps: Vec::new(),
+            span_start: (0, 0),
+            frames_rendered: 0,
+            commands_total: 0,
+            commands_drawn: 0,
             texture_cache,
             glyph_cache,
             texture_bind_group_layout,
+            uniform_bind_group_layout,
+            pipeline_cache,
         })
     }
 
@@ -473,18 +608,37 @@ impl Renderer {
     }
 
     /// Execute a display list and render to a target.
+    ///
+    /// The target's format must be the renderer's surface format.
     pub fn execute(
         &mut self,
         commands: &[DisplayCommand],
-        target: &wgpu::TextureView,
+        target: &wgpu::Texture,
     ) -> Result<(), RendererError> {
+        self.execute_culled(commands, commands.len(), target)
+    }
+
+    /// Execute the commands left after culling a display list of `total`
+    /// commands to the viewport, and render to a target.
+    pub fn execute_culled(
+        &mut self,
+        commands: &[DisplayCommand],
+        total: usize,
+        target: &wgpu::Texture,
+    ) -> Result<(), RendererError> {
+        self.commands_total = total;
+        self.commands_drawn = commands.len();
+
         // Clear batches
+        self.vector_commands = 0;
         self.color_vertices.clear();
         self.color_indices.clear();
         self.texture_vertices.clear();
         self.texture_indices.clear();
         self.clip_stack.clear();
         self.stacking_contexts.clear();
+        self.steps.clear();
+        self.span_start = (0, 0);
 
         // Process commands
         for cmd in commands {
@@ -492,7 +646,12 @@ impl Renderer {
         }
 
         // Render
-        self.flush_to(target)?;
+        if self.steps.is_empty() {
+            self.flush_to(target)?;
+        } else {
+            self.close_span();
+            self.flush_layers(target)?;
+        }
 
         Ok(())
     }
@@ -515,6 +674,18 @@ impl Renderer {
                 self.draw_border(*rect, *color, *top, *right, *bottom, *left);
             }
 
+            DisplayCommand::Outline {
+                rect,
+                width,
+                color,
+                style,
+                offset,
+            } => {
+                for piece in outline::outline_rects(*rect, *width, *style, *offset) {
+                    self.draw_solid_rect(piece, *color);
+                }
+            }
+
             DisplayCommand::Text {
                 text,
                 x,
@@ -558,8 +729,9 @@ impl Renderer {
                 dest_rect,
                 object_fit: _,
                 opacity: _,
+                frame,
             } => {
-                self.draw_image(url, *dest_rect);
+                self.draw_image(&TextureCache::frame_key(url, *frame), *dest_rect);
             }
 
             DisplayCommand::BackgroundImage {
@@ -568,14 +740,31 @@ impl Renderer {
                 size: _,
                 position: _,
                 repeat: _,
+                frame,
             } => {
-                self.draw_image(url, *rect);
+                self.draw_image(&TextureCache::frame_key(url, *frame), *rect);
             }
 
             DisplayCommand::PushClip(rect) => {
                 self.push_clip(*rect);
             }
 
+            DisplayCommand::PushClipPath { subpaths, .. } => {
+                // Clipping is per rect, so this clips to the path's bounds
+                let (min, max) = subpaths.iter().flatten().fold(
+                    ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+                    |(min, max), &(x, y)| {
+                        ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+                    },
+                );
+                let bounds = if min.0 <= max.0 {
+                    Rect::new(min.0, min.1, max.0 - min.0, max.1 - min.1)
+                } else {
+                    Rect::new(0.0, 0.0, 0.0, 0.0)
+                };
+                self.push_clip(bounds);
+            }
+
             DisplayCommand::PopClip => {
                 self.pop_clip();
             }
@@ -591,6 +780,25 @@ impl Renderer {
                 self.stacking_contexts.pop();
             }
 
+            DisplayCommand::PushLayer {
+                rect,
+                filter,
+                backdrop_filter,
+            } => {
+                self.close_span();
+                self.steps.push(LayerStep::Push {
+                    rect: *rect,
+                    clip: self.current_clip(),
+                    filter: filter.clone(),
+                    backdrop_filter: backdrop_filter.clone(),
+                });
+            }
+
+            DisplayCommand::PopLayer => {
+                self.close_span();
+                self.steps.push(LayerStep::Pop);
+            }
+
             // SVG primitives
             DisplayCommand::FillRect { rect, color } => {
                 self.draw_solid_rect(*rect, *color);
@@ -622,6 +830,7 @@ impl Renderer {
             }
 
             DisplayCommand::Line { x1, y1, x2, y2, color, width } => {
+                self.vector_commands += 1;
                 // Draw as thin rectangle
                 let dx = x2 - x1;
                 let dy = y2 - y1;
@@ -668,6 +877,7 @@ impl Renderer {
             DisplayCommand::FillPolygon { points, color } => {
                 // Simple triangle fan for convex polygons
                 if points.len() >= 3 {
+                    self.vector_commands += 1;
                     let c = [
                         color.r as f32 / 255.0,
                         color.g as f32 / 255.0,
@@ -690,6 +900,36 @@ impl Renderer {
                 }
             }
 
+            DisplayCommand::FillPath {
+                subpaths,
+                fill_rule,
+                color,
+            } => {
+                self.vector_commands += 1;
+                let c = [
+                    color.r as f32 / 255.0,
+                    color.g as f32 / 255.0,
+                    color.b as f32 / 255.0,
+                    color.a,
+                ];
+                for [tl, tr, br, bl] in rustkit_canvas::fill_trapezoids(subpaths, *fill_rule) {
+                    let base = self.color_vertices.len() as u32;
+                    self.color_vertices
+                        .extend([tl, tr, br, bl].map(|(x, y)| ColorVertex {
+                            position: [x, y],
+                            color: c,
+                        }));
+                    self.color_indices.extend_from_slice(&[
+                        base,
+                        base + 1,
+                        base + 2,
+                        base,
+                        base + 2,
+                        base + 3,
+                    ]);
+                }
+            }
+
             DisplayCommand::StrokePolygon { points, color, width } => {
                 // Draw as closed polyline
                 if !points.is_empty() {
@@ -788,6 +1028,7 @@ impl Renderer {
         font_style: u8,
     ) {
         let mut cursor_x = x;
+        let subpixel_positioning = self.glyph_cache.text_rendering().subpixel_positioning;
         let c = [
             color.r as f32 / 255.0,
             color.g as f32 / 255.0,
@@ -799,17 +1040,20 @@ impl Renderer {
         let atlas_size = self.glyph_cache.atlas_size() as f32;
 
         for ch in text.chars() {
+            // The fractional pen position is baked into the glyph bitmap
+            let (pen_x, subpixel_x) = quantize_subpixel(cursor_x, subpixel_positioning);
             let key = GlyphKey {
                 codepoint: ch,
                 font_family: font_family.to_string(),
                 font_size: (font_size * 10.0) as u32,
                 font_weight,
                 font_style,
+                subpixel_x,
             };
 
             // Clone the entry to avoid borrow issues
             if let Some(entry) = self.glyph_cache.get_or_rasterize(&self.device, &self.queue, &key) {
-                let glyph_x = cursor_x + entry.offset[0];
+                let glyph_x = pen_x + entry.offset[0];
                 let glyph_y = y + entry.offset[1];
                 let glyph_w = (entry.tex_coords[2] - entry.tex_coords[0]) * atlas_size;
                 let glyph_h = (entry.tex_coords[3] - entry.tex_coords[1]) * atlas_size;
@@ -913,18 +1157,58 @@ impl Renderer {
         self.clip_stack.last().copied()
     }
 
+    /// End the span of batched geometry drawn onto the open layer.
+    fn close_span(&mut self) {
+        let end = (
+            self.color_indices.len() as u32,
+            self.texture_indices.len() as u32,
+        );
+        let (color, texture) = (self.span_start.0..end.0, self.span_start.1..end.1);
+        if !color.is_empty() || !texture.is_empty() {
+            self.steps.push(LayerStep::Draw { color, texture });
+        }
+        self.span_start = end;
+    }
+
     /// Flush all batched vertices to the target.
-    fn flush_to(&mut self, target: &wgpu::TextureView) -> Result<(), RendererError> {
+    ///
+    /// Frames with vector geometry draw solid colors into a multisampled
+    /// texture resolved into the target, then draw textured quads in a
+    /// second single-sampled pass so text is rasterized exactly as in
+    /// aliased frames.
+    fn flush_to(&mut self, target: &wgpu::Texture) -> Result<(), RendererError> {
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = self.msaa_view(target);
+
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
+        let color_buffers = (!self.color_vertices.is_empty()).then(|| {
+            self.index_buffers(
+                "Color",
+                bytemuck::cast_slice(&self.color_vertices),
+                &self.color_indices,
+            )
+        });
+        let texture_buffers = (!self.texture_vertices.is_empty()).then(|| {
+            self.index_buffers(
+                "Texture",
+                bytemuck::cast_slice(&self.texture_vertices),
+                &self.texture_indices,
+            )
+        });
+
         {
+            let (view, resolve_target, store) = match &msaa_view {
+                Some(msaa_view) => (msaa_view, Some(&target_view), wgpu::StoreOp::Discard),
+                None => (&target_view, None, wgpu::StoreOp::Store),
+            };
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Main Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: target,
-                    resolve_target: None,
+                    view,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
                             r: 1.0,
@@ -932,7 +1216,7 @@ impl Renderer {
                             b: 1.0,
                             a: 1.0,
                         }),
-                        store: wgpu::StoreOp::Store,
+                        store,
                     },
                 })],
                 depth_stencil_attachment: None,
@@ -941,92 +1225,350 @@ impl Renderer {
             });
 
             // Draw solid colors
-            if !self.color_vertices.is_empty() {
-                let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Color Vertex Buffer"),
-                    contents: bytemuck::cast_slice(&self.color_vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
-
-                let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Color Index Buffer"),
-                    contents: bytemuck::cast_slice(&self.color_indices),
-                    usage: wgpu::BufferUsages::INDEX,
-                });
-
-                render_pass.set_pipeline(&self.color_pipeline);
+            if let Some((vertex_buffer, index_buffer)) = &color_buffers {
+                let pipeline = match (&msaa_view, &self.msaa_color_pipeline) {
+                    (Some(_), Some(pipeline)) => pipeline,
+                    _ => &self.color_pipeline,
+                };
+                render_pass.set_pipeline(pipeline);
                 render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
                 render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
                 render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                 render_pass.draw_indexed(0..self.color_indices.len() as u32, 0, 0..1);
             }
 
-            // Draw textured quads (images and glyphs)
-            if !self.texture_vertices.is_empty() {
-                let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Texture Vertex Buffer"),
-                    contents: bytemuck::cast_slice(&self.texture_vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
+            if msaa_view.is_none() {
+                if let Some((vertex_buffer, index_buffer)) = &texture_buffers {
+                    self.draw_textured(
+                        &mut render_pass,
+                        vertex_buffer,
+                        index_buffer,
+                        0..self.texture_indices.len() as u32,
+                    );
+                }
+            }
+        }
 
-                let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Texture Index Buffer"),
-                    contents: bytemuck::cast_slice(&self.texture_indices),
-                    usage: wgpu::BufferUsages::INDEX,
-                });
+        // Text and images go over the resolved frame
+        if let (Some(_), Some((vertex_buffer, index_buffer))) = (&msaa_view, &texture_buffers) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Texture Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.draw_textured(
+                &mut render_pass,
+                vertex_buffer,
+                index_buffer,
+                0..self.texture_indices.len() as u32,
+            );
+        }
 
-                render_pass.set_pipeline(&self.texture_pipeline);
-                render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-                render_pass.set_bind_group(1, self.glyph_cache.bind_group(), &[]);
-                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..self.texture_indices.len() as u32, 0, 0..1);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.frames_rendered += 1;
+
+        Ok(())
+    }
+
+    /// Flush a frame with filter layers.
+    ///
+    /// The frame is drawn into an intermediate texture cleared to white,
+    /// each span of batched geometry onto the layer open at its point in
+    /// the display list. Opening a layer filters the backdrop under it;
+    /// closing one filters it and composites it over the one below. The
+    /// frame is then copied to the target. Such frames are drawn aliased.
+    fn flush_layers(&mut self, target: &wgpu::Texture) -> Result<(), RendererError> {
+        let mut depth = 0usize;
+        let mut deepest = 0;
+        for step in &self.steps {
+            match step {
+                LayerStep::Push { .. } => depth += 1,
+                LayerStep::Pop => depth = depth.saturating_sub(1),
+                LayerStep::Draw { .. } => {}
             }
+            deepest = deepest.max(depth);
         }
+        let views = self.filters.targets(&self.device, target, deepest + 3);
+        let scratch = [deepest + 1, deepest + 2];
+        let size = (target.width(), target.height());
+
+        // The display list is laid out for the viewport; the target may
+        // be smaller
+        let scale = (
+            size.0 as f32 / self.viewport_size.0.max(1) as f32,
+            size.1 as f32 / self.viewport_size.1.max(1) as f32,
+        );
+        let blur_scale = (scale.0 + scale.1) / 2.0;
+        let pixels = |rect: Rect| {
+            let x0 = (rect.x * scale.0).floor().clamp(0.0, size.0 as f32) as u32;
+            let y0 = (rect.y * scale.1).floor().clamp(0.0, size.1 as f32) as u32;
+            let x1 = (rect.right() * scale.0).ceil().clamp(0.0, size.0 as f32) as u32;
+            let y1 = (rect.bottom() * scale.1).ceil().clamp(0.0, size.1 as f32) as u32;
+            [x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0)]
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Layer Render Encoder"),
+            });
+        let color_buffers = (!self.color_vertices.is_empty()).then(|| {
+            self.index_buffers(
+                "Color",
+                bytemuck::cast_slice(&self.color_vertices),
+                &self.color_indices,
+            )
+        });
+        let texture_buffers = (!self.texture_vertices.is_empty()).then(|| {
+            self.index_buffers(
+                "Texture",
+                bytemuck::cast_slice(&self.texture_vertices),
+                &self.texture_indices,
+            )
+        });
+
+        clear(&mut encoder, &views[0], wgpu::Color::WHITE);
+        // The clip and filter of each open layer
+        let mut open: Vec<(Option<Rect>, FilterChain)> = Vec::new();
+        for step in &self.steps {
+            match step {
+                LayerStep::Draw { color, texture } => {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Layer Render Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &views[open.len()],
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    if let (Some((vertex_buffer, index_buffer)), false) =
+                        (&color_buffers, color.is_empty())
+                    {
+                        render_pass.set_pipeline(&self.color_pipeline);
+                        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                        render_pass
+                            .set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        render_pass.draw_indexed(color.clone(), 0, 0..1);
+                    }
+                    if let (Some((vertex_buffer, index_buffer)), false) =
+                        (&texture_buffers, texture.is_empty())
+                    {
+                        self.draw_textured(
+                            &mut render_pass,
+                            vertex_buffer,
+                            index_buffer,
+                            texture.clone(),
+                        );
+                    }
+                }
+                LayerStep::Push {
+                    rect,
+                    clip,
+                    filter,
+                    backdrop_filter,
+                } => {
+                    let below = open.len();
+                    let region = match clip {
+                        Some(clip) => rect.intersect(clip),
+                        None => Some(*rect),
+                    };
+                    if let (Some(region), false) = (region, backdrop_filter.is_empty()) {
+                        let filtered = self.filters.apply(
+                            &self.device,
+                            &mut encoder,
+                            backdrop_filter,
+                            blur_scale,
+                            &views,
+                            below,
+                            scratch,
+                            size,
+                        );
+                        if filtered != below {
+                            self.filters.copy(
+                                &self.device,
+                                &mut encoder,
+                                &views[filtered],
+                                &views[below],
+                                filter::Write::Replace,
+                                Some(pixels(region)),
+                            );
+                        }
+                    }
+                    open.push((*clip, filter.clone()));
+                    clear(&mut encoder, &views[open.len()], wgpu::Color::TRANSPARENT);
+                }
+                LayerStep::Pop => {
+                    let Some((clip, filter)) = open.pop() else {
+                        continue;
+                    };
+                    let layer = open.len() + 1;
+                    let filtered = self.filters.apply(
+                        &self.device,
+                        &mut encoder,
+                        &filter,
+                        blur_scale,
+                        &views,
+                        layer,
+                        scratch,
+                        size,
+                    );
+                    self.filters.copy(
+                        &self.device,
+                        &mut encoder,
+                        &views[filtered],
+                        &views[open.len()],
+                        filter::Write::Over,
+                        clip.map(pixels),
+                    );
+                }
+            }
+        }
+
+        // Layers an unbalanced list left open are dropped
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        self.filters.copy(
+            &self.device,
+            &mut encoder,
+            &views[0],
+            &target_view,
+            filter::Write::Replace,
+            None,
+        );
 
         self.queue.submit(std::iter::once(encoder.finish()));
+        self.frames_rendered += 1;
 
         Ok(())
     }
 
-    /// Execute a display list and capture the result to a PNG file.
+    /// Upload a vertex and index batch.
+    fn index_buffers(
+        &self,
+        label: &str,
+        vertices: &[u8],
+        indices: &[u32],
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_label = format!("{label} Vertex Buffer");
+        let vertex_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&vertex_label),
+                contents: vertices,
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let index_label = format!("{label} Index Buffer");
+        let index_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&index_label),
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+        (vertex_buffer, index_buffer)
+    }
+
+    /// Draw textured quads (images and glyphs) of `indices`.
+    fn draw_textured(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        vertex_buffer: &wgpu::Buffer,
+        index_buffer: &wgpu::Buffer,
+        indices: Range<u32>,
+    ) {
+        render_pass.set_pipeline(&self.texture_pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, self.glyph_cache.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(indices, 0, 0..1);
+    }
+
+    /// The multisampled attachment for this frame, or `None` when it is
+    /// drawn aliased. The texture is kept while the target's size and
+    /// format stay the same.
+    fn msaa_view(&mut self, target: &wgpu::Texture) -> Option<wgpu::TextureView> {
+        let sample_count = self.antialiasing.sample_count;
+        if self.antialiasing.mode != AntialiasMode::Msaa
+            || sample_count <= 1
+            || self.vector_commands == 0
+        {
+            return None;
+        }
+        let reusable = self.msaa_texture.as_ref().is_some_and(|texture| {
+            texture.size() == target.size()
+                && texture.format() == target.format()
+                && texture.sample_count() == sample_count
+        });
+        if !reusable {
+            self.msaa_texture = Some(self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Color Target"),
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: target.format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }));
+        }
+        self.msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Execute a display list offscreen and read back RGBA pixels.
     ///
-    /// This renders to an offscreen texture and reads back the pixels.
-    pub fn execute_and_capture(
+    /// The display list is laid out for the current viewport size; rendering
+    /// into a smaller target scales it down on the GPU.
+    pub fn execute_to_pixels(
         &mut self,
         commands: &[DisplayCommand],
-        output_path: impl AsRef<std::path::Path>,
-    ) -> Result<screenshot::ScreenshotMetadata, RendererError> {
-        let (width, height) = self.viewport_size;
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, RendererError> {
         let capture_format = self.surface_format;
-        
+
         // Create offscreen target
-        let (texture, view) = screenshot::create_offscreen_target(
-            &self.device,
-            width,
-            height,
-            capture_format,
-        );
-        
+        let (texture, _view) =
+            screenshot::create_offscreen_target(&self.device, width, height, capture_format);
+
         // Render to offscreen target
-        self.execute(commands, &view)?;
-        
+        self.execute(commands, &texture)?;
+
         // Create readback buffer
         let readback = screenshot::GpuReadbackBuffer::new(&self.device, width, height);
-        
+
         // Copy texture to readback buffer
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Screenshot Copy Encoder"),
-        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Screenshot Copy Encoder"),
+            });
         readback.copy_from_texture(&mut encoder, &texture);
         self.queue.submit(std::iter::once(encoder.finish()));
-        
+
         // Read back the data
         let mut pixels = readback
             .read_data_sync(&self.device)
             .map_err(|e| RendererError::TextureUpload(e.to_string()))?;
 
-        // If the capture target is BGRA, swizzle to RGBA for PNG encoding.
+        // If the capture target is BGRA, swizzle to RGBA.
         match capture_format {
             wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
                 for px in pixels.chunks_exact_mut(4) {
@@ -1035,7 +1577,23 @@ impl Renderer {
             }
             _ => {}
         }
-        
+
+        Ok(pixels)
+    }
+
+    /// Execute a display list and capture the result to a PNG file.
+    ///
+    /// This renders to an offscreen texture and reads back the pixels.
+    pub fn execute_and_capture(
+        &mut self,
+        commands: &[DisplayCommand],
+        device_pixel_ratio: f32,
+        output_path: impl AsRef<std::path::Path>,
+    ) -> Result<screenshot::ScreenshotMetadata, RendererError> {
+        let (width, height) = self.viewport_size;
+        let capture_format = self.surface_format;
+        let pixels = self.execute_to_pixels(commands, width, height)?;
+
         // Save PNG
         screenshot::save_png(&output_path, width, height, &pixels)
             .map_err(|e| RendererError::TextureUpload(e.to_string()))?;
@@ -1049,6 +1607,7 @@ impl Renderer {
             timestamp: chrono_lite_timestamp(),
             color_vertex_count: self.color_vertices.len(),
             texture_vertex_count: self.texture_vertices.len(),
+            device_pixel_ratio,
         };
         
         let metadata_path = output_path.as_ref().with_extension("json");
@@ -1067,14 +1626,78 @@ impl Renderer {
             texture_index_count: self.texture_indices.len(),
             clip_stack_depth: self.clip_stack.len(),
             stacking_context_depth: self.stacking_contexts.len(),
+            frames_rendered: self.frames_rendered,
+            subpixel_glyph_entries: self.glyph_cache.subpixel_entries(),
+            antialias_mode: self.antialiasing.mode,
+            msaa_sample_count: self.antialiasing.sample_count,
+            commands_total: self.commands_total,
+            commands_drawn: self.commands_drawn,
+        }
+    }
+
+    /// Image texture and glyph atlas bytes held.
+    pub fn memory_usage(&self) -> RendererMemory {
+        RendererMemory {
+            texture_bytes: self.texture_cache.memory_usage(),
+            glyph_atlas_bytes: self.glyph_cache.memory_usage(),
         }
     }
 
+    /// Drop image textures until at most `target_bytes` are held, then
+    /// empty the glyph atlas if the two together are still over. Returns
+    /// the bytes freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let freed = self.texture_cache.trim(target_bytes);
+        let textures = self.texture_cache.memory_usage();
+        freed + self.glyph_cache.trim(target_bytes.saturating_sub(textures))
+    }
+
     /// Get access to the texture cache for external image loading.
     pub fn texture_cache(&mut self) -> &mut TextureCache {
         &mut self.texture_cache
     }
 
+    /// Current text rendering settings.
+    pub fn text_rendering(&self) -> TextRenderingSettings {
+        self.glyph_cache.text_rendering()
+    }
+
+    /// Change how text is rasterized. Affects only glyphs; images are
+    /// drawn unchanged.
+    pub fn set_text_rendering(&mut self, settings: TextRenderingSettings) {
+        self.glyph_cache.set_text_rendering(settings);
+    }
+
+    /// Antialiasing in effect for vector geometry.
+    pub fn antialiasing(&self) -> AntialiasSettings {
+        self.antialiasing
+    }
+
+    /// Change how vector geometry is antialiased. `supported_sample_counts`
+    /// are the MSAA sample counts the device allows for the surface format;
+    /// the request is lowered to fit them. Returns the settings in effect.
+    pub fn set_antialiasing(
+        &mut self,
+        settings: AntialiasSettings,
+        supported_sample_counts: &[u32],
+    ) -> AntialiasSettings {
+        let settings = settings.resolve(supported_sample_counts);
+        if settings != self.antialiasing {
+            self.msaa_texture = None;
+            self.msaa_color_pipeline = (settings.mode == AntialiasMode::Msaa).then(|| {
+                create_color_pipeline(
+                    &self.device,
+                    self.surface_format,
+                    &self.uniform_bind_group_layout,
+                    settings.sample_count,
+                    self.pipeline_cache.as_ref(),
+                )
+            });
+            self.antialiasing = settings;
+        }
+        settings
+    }
+
     /// Get access to the glyph cache.
     pub fn glyph_cache(&mut self) -> &mut GlyphCache {
         &mut self.glyph_cache
@@ -1086,6 +1709,24 @@ impl Renderer {
     }
 }
 
+/// Clear `view` to `color`.
+fn clear(encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, color: wgpu::Color) {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Clear Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+}
+
 // ==================== Rect Extension ====================
 
 trait RectExt {
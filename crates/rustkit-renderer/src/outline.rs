@@ -0,0 +1,136 @@
+//! Outline geometry.
+//!
+//! Outlines are drawn as axis-aligned rectangles around the border box.
+//! Dashed and dotted outlines are split along the ring's centerline with
+//! the canvas dashing helper.
+
+use rustkit_canvas::dash_polyline;
+use rustkit_css::OutlineStyle;
+use rustkit_layout::Rect;
+
+/// Rectangles that paint an outline `width` wide, `offset` outside `rect`.
+pub(crate) fn outline_rects(rect: Rect, width: f32, style: OutlineStyle, offset: f32) -> Vec<Rect> {
+    let outer = offset + width;
+    let ring = Rect::new(
+        rect.x - outer,
+        rect.y - outer,
+        rect.width + 2.0 * outer,
+        rect.height + 2.0 * outer,
+    );
+    if width <= 0.0 || ring.width <= 0.0 || ring.height <= 0.0 {
+        return Vec::new();
+    }
+
+    match style {
+        OutlineStyle::None => Vec::new(),
+        OutlineStyle::Auto | OutlineStyle::Solid => frame(ring, width),
+        OutlineStyle::Double if width >= 3.0 => {
+            let line = width / 3.0;
+            let inset = width - line;
+            let inner = Rect::new(
+                ring.x + inset,
+                ring.y + inset,
+                ring.width - 2.0 * inset,
+                ring.height - 2.0 * inset,
+            );
+            let mut rects = frame(ring, line);
+            rects.extend(frame(inner, line));
+            rects
+        }
+        OutlineStyle::Double => frame(ring, width),
+        OutlineStyle::Dashed => dashed(ring, width, &[3.0 * width, 3.0 * width]),
+        OutlineStyle::Dotted => dashed(ring, width, &[width, width]),
+    }
+}
+
+/// The four sides of a `width` wide frame just inside `rect`.
+fn frame(rect: Rect, width: f32) -> Vec<Rect> {
+    let inner_height = (rect.height - 2.0 * width).max(0.0);
+    vec![
+        Rect::new(rect.x, rect.y, rect.width, width),
+        Rect::new(rect.x, rect.y + rect.height - width, rect.width, width),
+        Rect::new(rect.x, rect.y + width, width, inner_height),
+        Rect::new(
+            rect.x + rect.width - width,
+            rect.y + width,
+            width,
+            inner_height,
+        ),
+    ]
+}
+
+/// Dashes of a `width` wide frame just inside `rect`.
+fn dashed(rect: Rect, width: f32, pattern: &[f32]) -> Vec<Rect> {
+    let half = width / 2.0;
+    let (left, top) = (rect.x + half, rect.y + half);
+    let (right, bottom) = (rect.x + rect.width - half, rect.y + rect.height - half);
+    let centerline = [
+        (left, top),
+        (right, top),
+        (right, bottom),
+        (left, bottom),
+        (left, top),
+    ];
+
+    let mut rects = Vec::new();
+    for dash in dash_polyline(&centerline, pattern, 0.0) {
+        let last = dash.len().saturating_sub(1);
+        for (i, pair) in dash.windows(2).enumerate() {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            // Pieces meeting at a corner overlap to fill it
+            let start = if i > 0 { half } else { 0.0 };
+            let end = if i + 1 < last { half } else { 0.0 };
+            let (min_x, max_x) = (x0.min(x1), x0.max(x1));
+            let (min_y, max_y) = (y0.min(y1), y0.max(y1));
+            let piece = if (y1 - y0).abs() < f32::EPSILON {
+                let (a, b) = if x1 >= x0 { (start, end) } else { (end, start) };
+                Rect::new(min_x - a, y0 - half, max_x - min_x + a + b, width)
+            } else {
+                let (a, b) = if y1 >= y0 { (start, end) } else { (end, start) };
+                Rect::new(x0 - half, min_y - a, width, max_y - min_y + a + b)
+            };
+            if piece.width > 0.0 && piece.height > 0.0 {
+                rects.push(piece);
+            }
+        }
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(rects: &[Rect]) -> f32 {
+        rects.iter().map(|r| r.width * r.height).sum()
+    }
+
+    #[test]
+    fn test_solid_outline_surrounds_the_offset_box() {
+        let rects = outline_rects(
+            Rect::new(10.0, 10.0, 20.0, 10.0),
+            2.0,
+            OutlineStyle::Solid,
+            1.0,
+        );
+        let (x0, y0) = rects
+            .iter()
+            .fold((f32::MAX, f32::MAX), |(x, y), r| (x.min(r.x), y.min(r.y)));
+        assert_eq!((x0, y0), (7.0, 7.0));
+        // 26x16 ring minus the 22x12 hole
+        assert_eq!(area(&rects), 26.0 * 16.0 - 22.0 * 12.0);
+        assert!(
+            outline_rects(Rect::new(0.0, 0.0, 5.0, 5.0), 2.0, OutlineStyle::None, 0.0).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_dashed_outline_covers_about_half_the_ring() {
+        let rect = Rect::new(0.0, 0.0, 58.0, 58.0);
+        let solid = area(&outline_rects(rect, 2.0, OutlineStyle::Solid, 0.0));
+        let dashed = outline_rects(rect, 2.0, OutlineStyle::Dashed, 0.0);
+        assert!(dashed.len() > 4);
+        let ratio = area(&dashed) / solid;
+        assert!((0.4..0.6).contains(&ratio), "{ratio}");
+    }
+}
@@ -2,11 +2,30 @@
 
 use crate::{ColorVertex, TextureVertex};
 
-/// Create the color rendering pipeline.
+/// Straight-alpha sources over premultiplied targets, so filter layers
+/// cleared to transparent keep their coverage in alpha; opaque targets
+/// blend as with plain alpha blending.
+const OVER: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
+/// Create the color rendering pipeline for targets with `sample_count`
+/// samples per pixel, compiling through `cache` when given.
 pub fn create_color_pipeline(
     device: &wgpu::Device,
     surface_format: wgpu::TextureFormat,
     uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    sample_count: u32,
+    cache: Option<&wgpu::PipelineCache>,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("Color Shader"),
@@ -33,7 +52,7 @@ pub fn create_color_pipeline(
             entry_point: Some("fs_main"),
             targets: &[Some(wgpu::ColorTargetState {
                 format: surface_format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                blend: Some(OVER),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -49,21 +68,23 @@ pub fn create_color_pipeline(
         },
         depth_stencil: None,
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
         multiview: None,
-        cache: None,
+        cache,
     })
 }
 
-/// Create the texture rendering pipeline.
+/// Create the texture rendering pipeline, compiling through `cache` when
+/// given.
 pub fn create_texture_pipeline(
     device: &wgpu::Device,
     surface_format: wgpu::TextureFormat,
     uniform_bind_group_layout: &wgpu::BindGroupLayout,
     texture_bind_group_layout: &wgpu::BindGroupLayout,
+    cache: Option<&wgpu::PipelineCache>,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("Texture Shader"),
@@ -90,7 +111,7 @@ pub fn create_texture_pipeline(
             entry_point: Some("fs_main"),
             targets: &[Some(wgpu::ColorTargetState {
                 format: surface_format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                blend: Some(OVER),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -111,7 +132,55 @@ pub fn create_texture_pipeline(
             alpha_to_coverage_enabled: false,
         },
         multiview: None,
-        cache: None,
+        cache,
     })
 }
 
+/// Create a fullscreen filter pass pipeline running the `entry_point`
+/// fragment shader of `shaders/filter.wgsl`, compiling through `cache`
+/// when given.
+pub fn create_filter_pipeline(
+    device: &wgpu::Device,
+    surface_format: wgpu::TextureFormat,
+    filter_bind_group_layout: &wgpu::BindGroupLayout,
+    entry_point: &str,
+    blend: Option<wgpu::BlendState>,
+    cache: Option<&wgpu::PipelineCache>,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Filter Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/filter.wgsl").into()),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Filter Pipeline Layout"),
+        bind_group_layouts: &[filter_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: surface_format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
@@ -35,6 +35,13 @@ pub struct ScreenshotMetadata {
     pub color_vertex_count: usize,
     /// Number of texture vertices rendered (text/images).
     pub texture_vertex_count: usize,
+    /// Physical pixels per CSS pixel; the width and height are physical.
+    #[serde(default = "default_device_pixel_ratio")]
+    pub device_pixel_ratio: f32,
+}
+
+fn default_device_pixel_ratio() -> f32 {
+    1.0
 }
 
 /// GPU readback buffer for capturing rendered frames.
@@ -239,6 +246,7 @@ mod tests {
             timestamp: "2025-01-04T12:00:00Z".to_string(),
             color_vertex_count: 100,
             texture_vertex_count: 50,
+            device_pixel_ratio: 2.0,
         };
         
         let json = serde_json::to_string(&metadata).unwrap();
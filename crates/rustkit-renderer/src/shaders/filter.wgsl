@@ -0,0 +1,69 @@
+// Filter shader: fullscreen passes over premultiplied layers
+
+struct FilterParams {
+    matrix: mat4x4<f32>,
+    offset: vec4<f32>,
+    // Texture coordinates between blur taps
+    direction: vec2<f32>,
+    // In taps
+    sigma: f32,
+    radius: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: FilterParams;
+
+@group(0) @binding(1)
+var t_source: texture_2d<f32>;
+
+@group(0) @binding(2)
+var s_source: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.tex_coords = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+}
+
+// One direction of a separable gaussian
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let radius = i32(params.radius);
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let t = f32(i);
+        let weight = exp(-(t * t) / (2.0 * params.sigma * params.sigma));
+        let coords = in.tex_coords + params.direction * t;
+        sum += textureSampleLevel(t_source, s_source, coords, 0.0) * weight;
+        total += weight;
+    }
+    return sum / total;
+}
+
+// A color matrix on unpremultiplied color, clamped
+@fragment
+fn fs_color_matrix(in: VertexOutput) -> @location(0) vec4<f32> {
+    let source = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+    var color = vec4<f32>(0.0);
+    if (source.a > 0.0) {
+        color = vec4<f32>(source.rgb / source.a, source.a);
+    }
+    let out = clamp(params.matrix * color + params.offset, vec4<f32>(0.0), vec4<f32>(1.0));
+    return vec4<f32>(out.rgb * out.a, out.a);
+}
@@ -0,0 +1,554 @@
+//! CPU execution of display lists, for captures on machines without a GPU.
+//!
+//! Only the core commands are drawn, each pixel taking a shape's color when
+//! its center is inside the shape, so output is exact and the same on every
+//! machine rather than a match for the GPU path. Text is drawn as one box
+//! per non-space character and images are not drawn. Filter layers paint
+//! into a transparent canvas of their own that is filtered and composited
+//! over the one below when the layer ends.
+
+use crate::filter;
+use crate::outline::outline_rects;
+use crate::screenshot::{save_metadata, save_png, ScreenshotError, ScreenshotMetadata};
+use rustkit_css::{Color, FillRule, FilterChain};
+use rustkit_layout::{DisplayCommand, Rect};
+use std::path::Path;
+
+/// Advance of a character box, in ems.
+const CHAR_ADVANCE: f32 = 0.5;
+
+/// Execute `commands` onto a white `width` x `height` canvas and return its
+/// RGBA pixels, row by row.
+pub fn rasterize(commands: &[DisplayCommand], width: u32, height: u32) -> Vec<u8> {
+    let mut canvas = Canvas {
+        width: width as usize,
+        height: height as usize,
+        pixels: vec![[1.0; 4]; width as usize * height as usize],
+        clips: Vec::new(),
+        painted: None,
+        layers: Vec::new(),
+    };
+    for command in commands {
+        canvas.execute(command);
+    }
+    canvas
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.map(|channel| (channel * 255.0).round() as u8))
+        .collect()
+}
+
+/// Rasterize `commands` and save them as a PNG screenshot, with its
+/// metadata next to it, like [`crate::Renderer::execute_and_capture`].
+pub fn capture(
+    commands: &[DisplayCommand],
+    width: u32,
+    height: u32,
+    device_pixel_ratio: f32,
+    output_path: impl AsRef<Path>,
+) -> Result<ScreenshotMetadata, ScreenshotError> {
+    let pixels = rasterize(commands, width, height);
+    save_png(&output_path, width, height, &pixels)?;
+    let metadata = ScreenshotMetadata {
+        width,
+        height,
+        adapter: "software".to_string(),
+        format: "Rgba8Unorm".to_string(),
+        timestamp: crate::chrono_lite_timestamp(),
+        color_vertex_count: 0,
+        texture_vertex_count: 0,
+        device_pixel_ratio,
+    };
+    save_metadata(output_path.as_ref().with_extension("json"), &metadata)?;
+    Ok(metadata)
+}
+
+/// A pixel range `[x0, x1) x [y0, y1)` as `(x0, y0, x1, y1)`.
+type Span = (usize, usize, usize, usize);
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    /// Premultiplied RGBA in 0..=1, opaque outside filter layers.
+    pixels: Vec<[f32; 4]>,
+    /// Pixel bounds of the clips in effect, innermost last.
+    clips: Vec<Span>,
+    /// Pixels painted since the innermost layer began.
+    painted: Option<Span>,
+    /// The layers below the one being painted, innermost last.
+    layers: Vec<Layer>,
+}
+
+/// A layer below the one being painted.
+struct Layer {
+    pixels: Vec<[f32; 4]>,
+    painted: Option<Span>,
+    /// The filter of the layer painted over this one.
+    filter: FilterChain,
+    /// The clip in effect where that layer began.
+    clip: Span,
+}
+
+impl Canvas {
+    fn execute(&mut self, command: &DisplayCommand) {
+        match command {
+            DisplayCommand::SolidColor(color, rect) | DisplayCommand::FillRect { rect, color } => {
+                self.fill_rect(*rect, *color)
+            }
+            DisplayCommand::Border {
+                color,
+                rect,
+                top,
+                right,
+                bottom,
+                left,
+            } => self.fill_frame(*rect, *color, [*top, *right, *bottom, *left]),
+            DisplayCommand::StrokeRect { rect, color, width } => {
+                self.fill_frame(*rect, *color, [*width; 4])
+            }
+            DisplayCommand::Outline {
+                rect,
+                width,
+                color,
+                style,
+                offset,
+            } => {
+                for piece in outline_rects(*rect, *width, *style, *offset) {
+                    self.fill_rect(piece, *color);
+                }
+            }
+            DisplayCommand::Text {
+                text,
+                x,
+                y,
+                color,
+                font_size,
+                ..
+            } => {
+                let advance = font_size * CHAR_ADVANCE;
+                for (i, ch) in text.chars().enumerate() {
+                    if !ch.is_whitespace() {
+                        let left = x + i as f32 * advance + advance * 0.1;
+                        let glyph =
+                            Rect::new(left, y + font_size * 0.2, advance * 0.8, font_size * 0.6);
+                        self.fill_rect(glyph, *color);
+                    }
+                }
+            }
+            DisplayCommand::TextDecoration {
+                x,
+                y,
+                width,
+                thickness,
+                color,
+                ..
+            } => self.fill_rect(Rect::new(*x, *y, *width, *thickness), *color),
+            DisplayCommand::FillCircle {
+                cx,
+                cy,
+                radius,
+                color,
+            } => self.fill_where(
+                Rect::new(cx - radius, cy - radius, radius * 2.0, radius * 2.0),
+                *color,
+                |x, y| (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius,
+            ),
+            DisplayCommand::StrokeCircle {
+                cx,
+                cy,
+                radius,
+                color,
+                width,
+            } => {
+                let (inner, outer) = ((radius - width / 2.0).max(0.0), radius + width / 2.0);
+                self.fill_where(
+                    Rect::new(cx - outer, cy - outer, outer * 2.0, outer * 2.0),
+                    *color,
+                    |x, y| {
+                        let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+                        distance >= inner && distance <= outer
+                    },
+                )
+            }
+            DisplayCommand::FillEllipse { rect, color } => {
+                let (rx, ry) = (rect.width / 2.0, rect.height / 2.0);
+                let (cx, cy) = (rect.x + rx, rect.y + ry);
+                self.fill_where(*rect, *color, |x, y| {
+                    ((x - cx) / rx).powi(2) + ((y - cy) / ry).powi(2) <= 1.0
+                })
+            }
+            DisplayCommand::Line {
+                x1,
+                y1,
+                x2,
+                y2,
+                color,
+                width,
+            } => self.stroke_segment((*x1, *y1), (*x2, *y2), *width, *color),
+            DisplayCommand::Polyline {
+                points,
+                color,
+                width,
+            } => {
+                for pair in points.windows(2) {
+                    self.stroke_segment(pair[0], pair[1], *width, *color);
+                }
+            }
+            DisplayCommand::StrokePolygon {
+                points,
+                color,
+                width,
+            } => {
+                let closing = points.last().zip(points.first()).map(|(&a, &b)| [a, b]);
+                for pair in points.windows(2).chain(closing.as_ref().map(|c| &c[..])) {
+                    self.stroke_segment(pair[0], pair[1], *width, *color);
+                }
+            }
+            DisplayCommand::FillPolygon { points, color } => {
+                self.fill_path(std::slice::from_ref(points), FillRule::NonZero, *color)
+            }
+            DisplayCommand::FillPath {
+                subpaths,
+                fill_rule,
+                color,
+            } => self.fill_path(subpaths, *fill_rule, *color),
+            DisplayCommand::PushClip(rect) => self.push_clip(*rect),
+            DisplayCommand::PushClipPath { subpaths, .. } => {
+                // Clipped to the path's bounds, as on the GPU
+                self.push_clip(bounds(subpaths.iter().flatten().copied()))
+            }
+            DisplayCommand::PopClip => {
+                self.clips.pop();
+            }
+            DisplayCommand::PushLayer {
+                rect,
+                filter,
+                backdrop_filter,
+            } => self.push_layer(*rect, filter, backdrop_filter),
+            DisplayCommand::PopLayer => self.pop_layer(),
+            DisplayCommand::Image { .. }
+            | DisplayCommand::BackgroundImage { .. }
+            | DisplayCommand::PushStackingContext { .. }
+            | DisplayCommand::PopStackingContext => {}
+        }
+    }
+
+    /// The pixel range `[x0, x1) x [y0, y1)` whose centers fall in `rect`,
+    /// limited to the canvas and the current clip.
+    fn pixels_in(&self, rect: Rect) -> Span {
+        let (x0, y0, x1, y1) = self.unclipped(rect);
+        let (cx0, cy0, cx1, cy1) = self.clip();
+        let (x0, y0, x1, y1) = (x0.max(cx0), y0.max(cy0), x1.min(cx1), y1.min(cy1));
+        (x0, y0, x1.max(x0), y1.max(y0))
+    }
+
+    /// The pixels whose centers fall in `rect`, limited to the canvas.
+    fn unclipped(&self, rect: Rect) -> Span {
+        let first = |start: f32| (start - 0.5).ceil().max(0.0) as usize;
+        let (x0, y0) = (first(rect.x), first(rect.y));
+        let (x1, y1) = (
+            first(rect.right()).min(self.width),
+            first(rect.bottom()).min(self.height),
+        );
+        (x0, y0, x1.max(x0), y1.max(y0))
+    }
+
+    /// The current clip, or the whole canvas.
+    fn clip(&self) -> Span {
+        self.clips
+            .last()
+            .copied()
+            .unwrap_or((0, 0, self.width, self.height))
+    }
+
+    /// Run the backdrop within `rect` through `backdrop_filter`, then
+    /// start a transparent layer.
+    fn push_layer(&mut self, rect: Rect, filter: &FilterChain, backdrop_filter: &FilterChain) {
+        if !backdrop_filter.is_empty() {
+            // Blurs read the backdrop around the box, too
+            let reach = backdrop_filter.outset();
+            let area = self.unclipped(Rect::new(
+                rect.x - reach,
+                rect.y - reach,
+                rect.width + 2.0 * reach,
+                rect.height + 2.0 * reach,
+            ));
+            let width = area.2 - area.0;
+            let mut backdrop = self.read(area);
+            filter::apply(backdrop_filter, &mut backdrop, width, area.3 - area.1);
+            let (x0, y0, x1, y1) = self.pixels_in(rect);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let from = (y - area.1) * width + (x - area.0);
+                    self.pixels[y * self.width + x] = backdrop[from];
+                }
+            }
+            self.paint((x0, y0, x1, y1));
+        }
+        let below = std::mem::replace(&mut self.pixels, vec![[0.0; 4]; self.width * self.height]);
+        self.layers.push(Layer {
+            pixels: below,
+            painted: self.painted.take(),
+            filter: filter.clone(),
+            clip: self.clip(),
+        });
+    }
+
+    /// Filter the layer being painted and composite it over the one below.
+    fn pop_layer(&mut self) {
+        let Some(below) = self.layers.pop() else {
+            return;
+        };
+        let layer = std::mem::replace(&mut self.pixels, below.pixels);
+        let painted = std::mem::replace(&mut self.painted, below.painted);
+        let Some((x0, y0, x1, y1)) = painted else {
+            return;
+        };
+        // Blurs spread what was painted
+        let reach = below.filter.outset().ceil() as usize;
+        let area = (
+            x0.saturating_sub(reach),
+            y0.saturating_sub(reach),
+            (x1 + reach).min(self.width),
+            (y1 + reach).min(self.height),
+        );
+        let width = area.2 - area.0;
+        let mut filtered: Vec<[f32; 4]> = (area.1..area.3)
+            .flat_map(|y| &layer[y * self.width + area.0..y * self.width + area.2])
+            .copied()
+            .collect();
+        filter::apply(&below.filter, &mut filtered, width, area.3 - area.1);
+        let (cx0, cy0, cx1, cy1) = below.clip;
+        let (x0, y0) = (area.0.max(cx0), area.1.max(cy0));
+        let (x1, y1) = (area.2.min(cx1).max(x0), area.3.min(cy1).max(y0));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let source = filtered[(y - area.1) * width + (x - area.0)];
+                let pixel = &mut self.pixels[y * self.width + x];
+                for (channel, value) in pixel.iter_mut().zip(source) {
+                    *channel = value + *channel * (1.0 - source[3]);
+                }
+            }
+        }
+        self.paint((x0, y0, x1, y1));
+    }
+
+    /// The pixels of `area`, row by row.
+    fn read(&self, (x0, y0, x1, y1): Span) -> Vec<[f32; 4]> {
+        (y0..y1)
+            .flat_map(|y| &self.pixels[y * self.width + x0..y * self.width + x1])
+            .copied()
+            .collect()
+    }
+
+    /// Grow the painted area of the current layer by `span`.
+    fn paint(&mut self, (x0, y0, x1, y1): Span) {
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        self.painted = Some(match self.painted {
+            Some((px0, py0, px1, py1)) => (px0.min(x0), py0.min(y0), px1.max(x1), py1.max(y1)),
+            None => (x0, y0, x1, y1),
+        });
+    }
+
+    fn push_clip(&mut self, rect: Rect) {
+        let clip = self.pixels_in(rect);
+        self.clips.push(clip);
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        self.fill_where(rect, color, |_, _| true);
+    }
+
+    /// Fill the pixels in `bounds` whose centers pass `inside`.
+    fn fill_where(&mut self, bounds: Rect, color: Color, inside: impl Fn(f32, f32) -> bool) {
+        if color.a <= 0.0 {
+            return;
+        }
+        let source = [
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+            1.0,
+        ];
+        let (x0, y0, x1, y1) = self.pixels_in(bounds);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if !inside(x as f32 + 0.5, y as f32 + 0.5) {
+                    continue;
+                }
+                let pixel = &mut self.pixels[y * self.width + x];
+                for (channel, source) in pixel.iter_mut().zip(source) {
+                    *channel = source * color.a + *channel * (1.0 - color.a);
+                }
+            }
+        }
+        self.paint((x0, y0, x1, y1));
+    }
+
+    /// Fill the frame of `widths` (top, right, bottom, left) inside `rect`.
+    fn fill_frame(&mut self, rect: Rect, color: Color, [top, right, bottom, left]: [f32; 4]) {
+        let inner_height = (rect.height - top - bottom).max(0.0);
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, top), color);
+        self.fill_rect(
+            Rect::new(rect.x, rect.bottom() - bottom, rect.width, bottom),
+            color,
+        );
+        self.fill_rect(Rect::new(rect.x, rect.y + top, left, inner_height), color);
+        self.fill_rect(
+            Rect::new(rect.right() - right, rect.y + top, right, inner_height),
+            color,
+        );
+    }
+
+    fn stroke_segment(&mut self, a: (f32, f32), b: (f32, f32), width: f32, color: Color) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        let quad = vec![
+            (a.0 + nx, a.1 + ny),
+            (b.0 + nx, b.1 + ny),
+            (b.0 - nx, b.1 - ny),
+            (a.0 - nx, a.1 - ny),
+        ];
+        self.fill_path(&[quad], FillRule::NonZero, color);
+    }
+
+    fn fill_path(&mut self, subpaths: &[Vec<(f32, f32)>], rule: FillRule, color: Color) {
+        let area = bounds(subpaths.iter().flatten().copied());
+        self.fill_where(area, color, |x, y| {
+            let winding = subpaths.iter().map(|path| winding(path, x, y)).sum::<i32>();
+            match rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            }
+        });
+    }
+}
+
+/// The winding number of the closed `path` around (`x`, `y`).
+fn winding(path: &[(f32, f32)], x: f32, y: f32) -> i32 {
+    let mut winding = 0;
+    for (i, &(x0, y0)) in path.iter().enumerate() {
+        let (x1, y1) = path[(i + 1) % path.len()];
+        let side = (x1 - x0) * (y - y0) - (x - x0) * (y1 - y0);
+        if y0 <= y && y1 > y && side > 0.0 {
+            winding += 1;
+        } else if y1 <= y && y0 > y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+fn bounds(points: impl Iterator<Item = (f32, f32)>) -> Rect {
+    let (min, max) = points.fold(
+        ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+        |(min, max), (x, y)| ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+    );
+    if min.0 <= max.0 {
+        Rect::new(min.0, min.1, max.0 - min.0, max.1 - min.1)
+    } else {
+        Rect::zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgba: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * width + x) * 4) as usize;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    }
+
+    #[test]
+    fn test_rasterize_core_commands() {
+        let red = Color::from_rgb(255, 0, 0);
+        let blue = Color::from_rgb(0, 0, 255);
+        let commands = [
+            DisplayCommand::SolidColor(red, Rect::new(0.0, 0.0, 4.0, 4.0)),
+            DisplayCommand::PushClip(Rect::new(2.0, 0.0, 8.0, 8.0)),
+            DisplayCommand::Border {
+                color: blue,
+                rect: Rect::new(0.0, 4.0, 8.0, 4.0),
+                top: 1.0,
+                right: 1.0,
+                bottom: 1.0,
+                left: 1.0,
+            },
+            DisplayCommand::PopClip,
+            DisplayCommand::SolidColor(Color::new(0, 0, 0, 0.5), Rect::new(6.0, 0.0, 2.0, 2.0)),
+        ];
+        let rgba = rasterize(&commands, 8, 8);
+        assert_eq!(rgba.len(), 8 * 8 * 4);
+        assert_eq!(pixel(&rgba, 8, 3, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(&rgba, 8, 4, 3), [255, 255, 255, 255]);
+        // The border's left side is clipped away
+        assert_eq!(pixel(&rgba, 8, 0, 5), [255, 255, 255, 255]);
+        assert_eq!(pixel(&rgba, 8, 7, 5), [0, 0, 255, 255]);
+        assert_eq!(pixel(&rgba, 8, 4, 5), [255, 255, 255, 255]);
+        // Translucent colors blend over what is below
+        assert_eq!(pixel(&rgba, 8, 6, 0), [128, 128, 128, 255]);
+    }
+
+    fn layer(rect: Rect, filter: &str, backdrop_filter: &str) -> DisplayCommand {
+        DisplayCommand::PushLayer {
+            rect,
+            filter: FilterChain::parse(filter).unwrap(),
+            backdrop_filter: FilterChain::parse(backdrop_filter).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_grayscale_filter() {
+        let commands = [
+            layer(Rect::new(0.0, 0.0, 8.0, 8.0), "grayscale(100%)", "none"),
+            DisplayCommand::SolidColor(Color::from_rgb(255, 0, 0), Rect::new(2.0, 2.0, 4.0, 4.0)),
+            DisplayCommand::PopLayer,
+        ];
+        let rgba = rasterize(&commands, 8, 8);
+        let [r, g, b, a] = pixel(&rgba, 8, 4, 4);
+        assert_eq!((r, a), (54, 255));
+        assert!(r == g && g == b);
+        assert_eq!(pixel(&rgba, 8, 0, 0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_blur_spreads_past_a_hard_edge() {
+        let rect = Rect::new(10.0, 0.0, 20.0, 20.0);
+        let commands = [
+            layer(rect, "blur(2px)", "none"),
+            DisplayCommand::SolidColor(Color::BLACK, rect),
+            DisplayCommand::PopLayer,
+        ];
+        let rgba = rasterize(&commands, 40, 20);
+        let gray = |x| pixel(&rgba, 40, x, 10)[0];
+        // Outside the edge darkens, inside it lightens
+        assert!(gray(8) > 0 && gray(8) < 255, "{}", gray(8));
+        assert!(gray(11) > 0 && gray(11) < gray(8));
+        assert_eq!(gray(20), 0);
+        assert_eq!(gray(0), 255);
+        assert_eq!(pixel(&rgba, 40, 8, 10)[3], 255);
+    }
+
+    #[test]
+    fn test_backdrop_filter_desaturates_what_is_below() {
+        let red = Color::from_rgb(255, 0, 0);
+        let commands = [
+            DisplayCommand::SolidColor(red, Rect::new(0.0, 0.0, 20.0, 20.0)),
+            layer(Rect::new(5.0, 5.0, 10.0, 10.0), "none", "saturate(50%)"),
+            DisplayCommand::PopLayer,
+        ];
+        let rgba = rasterize(&commands, 20, 20);
+        let [r, g, b, a] = pixel(&rgba, 20, 10, 10);
+        assert!(r < 255 && r > g && g > 0, "{:?}", [r, g, b]);
+        assert_eq!((g, a), (b, 255));
+        // Only the box's backdrop is filtered
+        assert_eq!(pixel(&rgba, 20, 2, 2), [255, 0, 0, 255]);
+    }
+}
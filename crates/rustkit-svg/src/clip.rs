@@ -0,0 +1,407 @@
+//! Clipping paths.
+//!
+//! A `clip-path="url(#id)"` reference is resolved against
+//! [`SvgDocument::defs`](crate::SvgDocument::defs) when the document is
+//! rendered or hit tested, on shapes and on `<g>` groups alike. The shapes
+//! of the `<clipPath>` are flattened to polygons in device space: a single
+//! axis-aligned rectangle becomes a [`DisplayCommand::PushClip`], anything
+//! else a [`DisplayCommand::PushClipPath`]. Clips nest, so an element inside
+//! a clipped group is clipped by both.
+//!
+//! Masks are not supported: a `mask` reference is ignored, so the element
+//! paints unmasked, and the contents of a `<mask>` are never painted.
+
+use crate::{FillRule, SvgElement, SvgStyle, Transform2D};
+use rustkit_layout::{DisplayCommand, Rect};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Segments per full turn when flattening circles and ellipses.
+const CURVE_SEGMENTS: usize = 64;
+
+/// Coordinate system of the contents of a `<clipPath>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipPathUnits {
+    /// The user space of the clipped element.
+    #[default]
+    UserSpaceOnUse,
+    /// Fractions of the clipped element's bounding box.
+    ObjectBoundingBox,
+}
+
+impl ClipPathUnits {
+    /// Parse a `clipPathUnits` attribute.
+    pub fn parse(s: &str) -> Self {
+        match s.trim() {
+            "objectBoundingBox" | "objectboundingbox" => Self::ObjectBoundingBox,
+            _ => Self::UserSpaceOnUse,
+        }
+    }
+}
+
+/// Clipping path element (<clipPath>).
+#[derive(Debug, Clone, Default)]
+pub struct SvgClipPath {
+    pub units: ClipPathUnits,
+    pub transform: Transform2D,
+    /// Shapes, paths and `<use>` references; their union is the clip.
+    pub children: Vec<SvgElement>,
+}
+
+impl SvgClipPath {
+    /// Whether a child of this kind may contribute to the clip.
+    pub(crate) fn accepts(element: &SvgElement) -> bool {
+        !matches!(
+            element,
+            SvgElement::Group(_) | SvgElement::Text(_) | SvgElement::ClipPath(_)
+        )
+    }
+
+    /// The clip area in device space, one region per child. `transform`
+    /// maps the clipped element's user space to device space and `bbox` is
+    /// its bounding box in that user space.
+    pub(crate) fn regions(
+        &self,
+        defs: &HashMap<String, SvgElement>,
+        transform: &Transform2D,
+        bbox: Option<Rect>,
+    ) -> Vec<ClipRegion> {
+        let mut space = *transform;
+        if self.units == ClipPathUnits::ObjectBoundingBox {
+            // An element without an area is clipped away entirely
+            let Some(bbox) = bbox.filter(|b| b.width > 0.0 && b.height > 0.0) else {
+                return Vec::new();
+            };
+            space = space.multiply(&Transform2D {
+                a: bbox.width,
+                b: 0.0,
+                c: 0.0,
+                d: bbox.height,
+                e: bbox.x,
+                f: bbox.y,
+            });
+        }
+        let space = space.multiply(&self.transform);
+
+        self.children
+            .iter()
+            .filter_map(|child| {
+                let (child, placement) = match child {
+                    SvgElement::Use(u) => {
+                        let target = defs.get(u.href.trim_start_matches('#'))?;
+                        let placement = u.transform.translate(u.x, u.y);
+                        (target, placement)
+                    }
+                    _ => (child, Transform2D::identity()),
+                };
+                if !Self::accepts(child) {
+                    return None;
+                }
+                let (local, style) = child.transform_and_style()?;
+                if !style.visibility {
+                    return None;
+                }
+                let to_device = space.multiply(&placement).multiply(&local);
+                let rings = outline(child)?
+                    .into_iter()
+                    .map(|ring| {
+                        ring.into_iter()
+                            .map(|(x, y)| to_device.apply(x, y))
+                            .collect()
+                    })
+                    .collect();
+                Some(ClipRegion {
+                    rings,
+                    rule: style.clip_rule,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The area of one child of a clip path, in device space.
+#[derive(Debug, Clone)]
+pub(crate) struct ClipRegion {
+    pub(crate) rings: Vec<Vec<(f32, f32)>>,
+    pub(crate) rule: FillRule,
+}
+
+/// The command starting a clip to `regions`, or `None` when they cover
+/// nothing and the clipped content is invisible.
+fn push_command(regions: Vec<ClipRegion>) -> Option<DisplayCommand> {
+    if let [region] = regions.as_slice() {
+        if let [ring] = region.rings.as_slice() {
+            if let Some(rect) = axis_aligned_rect(ring) {
+                return Some(DisplayCommand::PushClip(rect));
+            }
+        }
+    }
+    // Regions of different rules can only be merged under nonzero
+    let fill_rule = match regions.as_slice() {
+        [region] => region.rule,
+        _ => FillRule::NonZero,
+    };
+    let subpaths: Vec<_> = regions.into_iter().flat_map(|r| r.rings).collect();
+    if subpaths.is_empty() {
+        return None;
+    }
+    Some(DisplayCommand::PushClipPath {
+        subpaths,
+        fill_rule,
+    })
+}
+
+/// `ring` as a rectangle, if it is one with sides along the axes.
+fn axis_aligned_rect(ring: &[(f32, f32)]) -> Option<Rect> {
+    if ring.len() != 4 {
+        return None;
+    }
+    let bounds = bounds(ring.iter().copied())?;
+    let on_edge = |v: f32, low: f32, high: f32| (v - low).abs() < 1e-3 || (v - high).abs() < 1e-3;
+    ring.iter()
+        .all(|&(x, y)| {
+            on_edge(x, bounds.x, bounds.right()) && on_edge(y, bounds.y, bounds.bottom())
+        })
+        .then_some(bounds)
+}
+
+/// Smallest rectangle holding `points`.
+fn bounds(points: impl Iterator<Item = (f32, f32)>) -> Option<Rect> {
+    let (min_x, min_y, max_x, max_y) = points.fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(x0, y0, x1, y1), (x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+    );
+    (min_x <= max_x).then(|| Rect::new(min_x, min_y, max_x - min_x, max_y - min_y))
+}
+
+/// Closed outlines of a shape in its own coordinates, or `None` for
+/// elements without an area.
+fn outline(element: &SvgElement) -> Option<Vec<Vec<(f32, f32)>>> {
+    let rings = match element {
+        SvgElement::Rect(r) => vec![vec![
+            (r.x, r.y),
+            (r.x + r.width, r.y),
+            (r.x + r.width, r.y + r.height),
+            (r.x, r.y + r.height),
+        ]],
+        SvgElement::Circle(c) => vec![ellipse_points(c.cx, c.cy, c.r, c.r)],
+        SvgElement::Ellipse(e) => vec![ellipse_points(e.cx, e.cy, e.rx, e.ry)],
+        SvgElement::Polygon(p) => vec![p.points.clone()],
+        SvgElement::Polyline(p) => vec![p.points.clone()],
+        SvgElement::Path(p) => p.to_line_segments(),
+        _ => return None,
+    };
+    let rings: Vec<_> = rings.into_iter().filter(|r| r.len() >= 3).collect();
+    (!rings.is_empty()).then_some(rings)
+}
+
+fn ellipse_points(cx: f32, cy: f32, rx: f32, ry: f32) -> Vec<(f32, f32)> {
+    if rx <= 0.0 || ry <= 0.0 {
+        return Vec::new();
+    }
+    (0..CURVE_SEGMENTS)
+        .map(|i| {
+            let angle = i as f32 / CURVE_SEGMENTS as f32 * 2.0 * PI;
+            (cx + rx * angle.cos(), cy + ry * angle.sin())
+        })
+        .collect()
+}
+
+impl SvgElement {
+    /// The element's own transform and style, for elements that paint.
+    pub(crate) fn transform_and_style(&self) -> Option<(Transform2D, &SvgStyle)> {
+        Some(match self {
+            SvgElement::Group(g) => (g.transform, &g.style),
+            SvgElement::Rect(r) => (r.transform, &r.style),
+            SvgElement::Circle(c) => (c.transform, &c.style),
+            SvgElement::Ellipse(e) => (e.transform, &e.style),
+            SvgElement::Line(l) => (l.transform, &l.style),
+            SvgElement::Polyline(p) => (p.transform, &p.style),
+            SvgElement::Polygon(p) => (p.transform, &p.style),
+            SvgElement::Path(p) => (p.transform, &p.style),
+            SvgElement::Text(t) => (t.transform, &t.style),
+            SvgElement::Use(_) | SvgElement::ClipPath(_) => return None,
+        })
+    }
+
+    /// Bounds of the element's geometry in its own coordinates, before its
+    /// `transform`, ignoring strokes.
+    pub fn bounding_box(&self) -> Option<Rect> {
+        match self {
+            SvgElement::Group(g) => {
+                let corners: Vec<_> = g
+                    .children
+                    .iter()
+                    .filter_map(|child| {
+                        let (local, _) = child.transform_and_style()?;
+                        let b = child.bounding_box()?;
+                        Some(
+                            [
+                                (b.x, b.y),
+                                (b.right(), b.y),
+                                (b.right(), b.bottom()),
+                                (b.x, b.bottom()),
+                            ]
+                            .map(|(x, y)| local.apply(x, y)),
+                        )
+                    })
+                    .flatten()
+                    .collect();
+                bounds(corners.into_iter())
+            }
+            SvgElement::Rect(r) => Some(Rect::new(r.x, r.y, r.width, r.height)),
+            SvgElement::Circle(c) => Some(Rect::new(c.cx - c.r, c.cy - c.r, c.r * 2.0, c.r * 2.0)),
+            SvgElement::Ellipse(e) => {
+                Some(Rect::new(e.cx - e.rx, e.cy - e.ry, e.rx * 2.0, e.ry * 2.0))
+            }
+            SvgElement::Line(l) => bounds([(l.x1, l.y1), (l.x2, l.y2)].into_iter()),
+            SvgElement::Polyline(p) => bounds(p.points.iter().copied()),
+            SvgElement::Polygon(p) => bounds(p.points.iter().copied()),
+            SvgElement::Path(p) => bounds(p.to_line_segments().into_iter().flatten()),
+            SvgElement::Text(t) => {
+                // The same advance estimate as hit testing
+                let width = t.content.chars().count() as f32 * t.font_size * 0.5;
+                Some(Rect::new(t.x, t.y - t.font_size, width, t.font_size))
+            }
+            SvgElement::Use(_) | SvgElement::ClipPath(_) => None,
+        }
+    }
+
+    /// The clip path this element references, if `defs` has it.
+    pub(crate) fn clip_path<'a>(
+        &self,
+        defs: &'a HashMap<String, SvgElement>,
+    ) -> Option<&'a SvgClipPath> {
+        let (_, style) = self.transform_and_style()?;
+        match defs.get(style.clip_path.as_deref()?)? {
+            SvgElement::ClipPath(clip) => Some(clip),
+            _ => None,
+        }
+    }
+
+    /// Render with `clip-path` references resolved against `defs`.
+    pub(crate) fn render_in(
+        &self,
+        defs: &HashMap<String, SvgElement>,
+        parent_transform: &Transform2D,
+        parent_style: &SvgStyle,
+        commands: &mut Vec<DisplayCommand>,
+    ) {
+        let clip = self.clip_path(defs).map(|clip| {
+            let local = self
+                .transform_and_style()
+                .map_or(Transform2D::identity(), |(local, _)| local);
+            let space = parent_transform.multiply(&local);
+            push_command(clip.regions(defs, &space, self.bounding_box()))
+        });
+        let clipped = match clip {
+            Some(Some(push)) => {
+                commands.push(push);
+                true
+            }
+            // Clipped to nothing
+            Some(None) => return,
+            None => false,
+        };
+
+        match self {
+            SvgElement::Group(g) => {
+                let transform = parent_transform.multiply(&g.transform);
+                let mut style = g.style.clone();
+                style.inherit_from(parent_style);
+                for child in &g.children {
+                    child.render_in(defs, &transform, &style, commands);
+                }
+            }
+            _ => self.render(parent_transform, parent_style, commands),
+        }
+
+        if clipped {
+            commands.push(DisplayCommand::PopClip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SvgDocument;
+    use rustkit_layout::DisplayCommand;
+
+    const CLIPPED_RECT: &str = r#"<svg viewBox="0 0 100 100">
+        <defs>
+            <clipPath id="disc"><circle cx="50" cy="50" r="40"/></clipPath>
+        </defs>
+        <rect x="0" y="0" width="100" height="100" fill="red" clip-path="url(#disc)"/>
+    </svg>"#;
+
+    #[test]
+    fn test_circle_clip_wraps_rect_in_polygon() {
+        let doc = SvgDocument::parse(CLIPPED_RECT).unwrap();
+        // The clip's circle is not painted itself
+        let commands = doc.render(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(commands.len(), 3, "{:?}", commands);
+        let DisplayCommand::PushClipPath { subpaths, .. } = &commands[0] else {
+            panic!("{:?}", commands[0]);
+        };
+        assert_eq!(subpaths.len(), 1);
+        assert!(subpaths[0].len() >= 32);
+        for &(x, y) in &subpaths[0] {
+            assert!(((x - 50.0).hypot(y - 50.0) - 40.0).abs() < 0.01);
+        }
+        assert!(matches!(commands[1], DisplayCommand::FillRect { .. }));
+        assert!(matches!(commands[2], DisplayCommand::PopClip));
+    }
+
+    #[test]
+    fn test_clip_excludes_hits_outside_the_circle() {
+        let doc = SvgDocument::parse(CLIPPED_RECT).unwrap();
+        assert_eq!(doc.hit_test(50.0, 50.0).unwrap().path, [0]);
+        // Inside the rect, outside the circle
+        assert!(doc.hit_test(5.0, 5.0).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box_units_and_nested_clips() {
+        let doc = SvgDocument::parse(
+            r#"<svg>
+                <clipPath id="half" clipPathUnits="objectBoundingBox">
+                    <rect x="0" y="0" width="0.5" height="1"/>
+                </clipPath>
+                <clipPath id="band"><rect x="0" y="20" width="200" height="10"/></clipPath>
+                <g clip-path="url(#band)">
+                    <rect x="100" y="0" width="40" height="50" fill="blue" clip-path="url(#half)"/>
+                </g>
+            </svg>"#,
+        )
+        .unwrap();
+        let commands = doc.render(0.0, 0.0, 300.0, 150.0);
+        let clips: Vec<_> = commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::PushClip(r) => Some((r.x, r.y, r.width, r.height)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(clips, [(0.0, 20.0, 200.0, 10.0), (100.0, 0.0, 20.0, 50.0)]);
+        assert!(matches!(commands.last(), Some(DisplayCommand::PopClip)));
+
+        assert!(doc.hit_test(110.0, 25.0).is_some());
+        // Outside the group's band, then outside the rect's left half
+        assert!(doc.hit_test(110.0, 5.0).is_none());
+        assert!(doc.hit_test(130.0, 25.0).is_none());
+    }
+
+    #[test]
+    fn test_masked_element_still_paints() {
+        let doc = SvgDocument::parse(
+            r#"<svg>
+                <mask id="fade"><rect x="0" y="0" width="10" height="10" fill="white"/></mask>
+                <circle cx="50" cy="50" r="10" fill="green" mask="url(#fade)"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let commands = doc.render(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(commands.len(), 1);
+        assert!(matches!(commands[0], DisplayCommand::FillCircle { .. }));
+    }
+}
@@ -0,0 +1,268 @@
+//! Hit testing of SVG shapes.
+//!
+//! Points are mapped into each shape's local space through the inverse of
+//! its accumulated transform, so rotated and scaled shapes are tested
+//! against their true outline rather than a bounding box. Strokes are
+//! tested by distance to the outline, in local units like `stroke-width`.
+//! Points outside a shape's or group's `clip-path` miss it.
+
+use crate::{FillRule, Paint, PointerEvents, SvgDocument, SvgElement, SvgStyle, Transform2D};
+use std::collections::HashMap;
+
+/// The shape found by [`SvgDocument::hit_test`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgHitResult {
+    /// Child indices from the root group down to the shape.
+    pub path: Vec<usize>,
+    /// The point in the shape's own coordinate system.
+    pub local_x: f32,
+    pub local_y: f32,
+}
+
+impl SvgDocument {
+    /// The topmost shape under (`x`, `y`), given in the document's user
+    /// space. Map viewport points with the inverse of
+    /// [`Self::viewport_transform`] first.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<SvgHitResult> {
+        self.root.hit_test_in(
+            &self.defs,
+            &Transform2D::identity(),
+            &SvgStyle::default(),
+            x,
+            y,
+        )
+    }
+}
+
+impl SvgElement {
+    /// Hit test this element and its children in reverse paint order.
+    /// `clip-path` references are only resolved by
+    /// [`SvgDocument::hit_test`].
+    pub fn hit_test(
+        &self,
+        parent_transform: &Transform2D,
+        parent_style: &SvgStyle,
+        x: f32,
+        y: f32,
+    ) -> Option<SvgHitResult> {
+        self.hit_test_in(&HashMap::new(), parent_transform, parent_style, x, y)
+    }
+
+    fn hit_test_in(
+        &self,
+        defs: &HashMap<String, SvgElement>,
+        parent_transform: &Transform2D,
+        parent_style: &SvgStyle,
+        x: f32,
+        y: f32,
+    ) -> Option<SvgHitResult> {
+        let (local, style) = self.transform_and_style()?;
+        if let Some(clip) = self.clip_path(defs) {
+            let space = parent_transform.multiply(&local);
+            let inside = clip
+                .regions(defs, &space, self.bounding_box())
+                .iter()
+                .any(|region| {
+                    let rings: Vec<_> = region.rings.iter().map(Vec::as_slice).collect();
+                    fills(&rings, region.rule, x, y)
+                });
+            if !inside {
+                return None;
+            }
+        }
+        if let SvgElement::Group(g) = self {
+            let transform = parent_transform.multiply(&g.transform);
+            let mut style = g.style.clone();
+            style.inherit_from(parent_style);
+            return g.children.iter().enumerate().rev().find_map(|(i, child)| {
+                let mut hit = child.hit_test_in(defs, &transform, &style, x, y)?;
+                hit.path.insert(0, i);
+                Some(hit)
+            });
+        }
+        let mut style = style.clone();
+        style.inherit_from(parent_style);
+        if style.pointer_events == PointerEvents::None {
+            return None;
+        }
+        let (x, y) = parent_transform.multiply(&local).invert()?.apply(x, y);
+        let half_width = style.stroke_width / 2.0;
+
+        let (in_fill, on_stroke) = match self {
+            SvgElement::Rect(r) => {
+                let inside = |grow: f32| {
+                    x >= r.x - grow
+                        && x <= r.x + r.width + grow
+                        && y >= r.y - grow
+                        && y <= r.y + r.height + grow
+                };
+                (inside(0.0), inside(half_width) && !inside(-half_width))
+            }
+            SvgElement::Circle(c) => {
+                let distance = (x - c.cx).hypot(y - c.cy);
+                (distance <= c.r, (distance - c.r).abs() <= half_width)
+            }
+            SvgElement::Ellipse(e) => {
+                if e.rx <= 0.0 || e.ry <= 0.0 {
+                    return None;
+                }
+                let norm = ((x - e.cx) / e.rx).hypot((y - e.cy) / e.ry);
+                // Distance to the outline, exact for circles
+                let distance = (norm - 1.0).abs() * e.rx.min(e.ry);
+                (norm <= 1.0, distance <= half_width)
+            }
+            SvgElement::Line(l) => (
+                false,
+                distance_to_segment((x, y), (l.x1, l.y1), (l.x2, l.y2)) <= half_width,
+            ),
+            SvgElement::Polyline(p) => (false, near_polyline(&p.points, false, x, y, half_width)),
+            SvgElement::Polygon(p) => (
+                p.points.len() >= 3 && fills(&[&p.points], style.fill_rule, x, y),
+                near_polyline(&p.points, true, x, y, half_width),
+            ),
+            SvgElement::Path(p) => {
+                let subpaths = p.to_line_segments();
+                // Only subpaths with an area are filled when painting
+                let rings: Vec<&[(f32, f32)]> = subpaths
+                    .iter()
+                    .filter(|s| s.len() >= 3)
+                    .map(Vec::as_slice)
+                    .collect();
+                (
+                    fills(&rings, style.fill_rule, x, y),
+                    subpaths
+                        .iter()
+                        .any(|s| near_polyline(s, false, x, y, half_width)),
+                )
+            }
+            SvgElement::Text(t) => {
+                // The same advance estimate as text layout
+                let width = t.content.chars().count() as f32 * t.font_size * 0.5;
+                (
+                    x >= t.x && x <= t.x + width && y >= t.y - t.font_size && y <= t.y,
+                    false,
+                )
+            }
+            SvgElement::Group(_) | SvgElement::Use(_) | SvgElement::ClipPath(_) => unreachable!(),
+        };
+
+        accepts(&style, in_fill, on_stroke).then_some(SvgHitResult {
+            path: Vec::new(),
+            local_x: x,
+            local_y: y,
+        })
+    }
+}
+
+/// Whether `pointer-events` lets a point in the fill or on the stroke hit.
+fn accepts(style: &SvgStyle, in_fill: bool, on_stroke: bool) -> bool {
+    let fill_painted = !matches!(style.fill, Paint::None);
+    let stroke_painted = !matches!(style.stroke, Paint::None);
+    let (needs_visible, fill, stroke) = match style.pointer_events {
+        PointerEvents::VisiblePainted => {
+            (true, fill_painted && in_fill, stroke_painted && on_stroke)
+        }
+        PointerEvents::VisibleFill => (true, in_fill, false),
+        PointerEvents::VisibleStroke => (true, false, on_stroke),
+        PointerEvents::Visible => (true, in_fill, on_stroke),
+        PointerEvents::Painted => (false, fill_painted && in_fill, stroke_painted && on_stroke),
+        PointerEvents::Fill => (false, in_fill, false),
+        PointerEvents::Stroke => (false, false, on_stroke),
+        PointerEvents::All => (false, in_fill, on_stroke),
+        PointerEvents::None => return false,
+    };
+    (style.visibility || !needs_visible) && (fill || stroke)
+}
+
+/// Whether (`x`, `y`) is inside the closed `rings` under `rule`.
+fn fills(rings: &[&[(f32, f32)]], rule: FillRule, x: f32, y: f32) -> bool {
+    rule.contains(rings.iter().map(|ring| winding_number(ring, x, y)).sum())
+}
+
+/// Winding number of the closed polygon `points` around (`x`, `y`).
+fn winding_number(points: &[(f32, f32)], x: f32, y: f32) -> i32 {
+    let mut winding = 0;
+    for (i, &(x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        // Positive when the point is left of the edge
+        let side = (x1 - x0) * (y - y0) - (x - x0) * (y1 - y0);
+        if y0 <= y {
+            if y1 > y && side > 0.0 {
+                winding += 1;
+            }
+        } else if y1 <= y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    (p.0 - (a.0 + t * dx)).hypot(p.1 - (a.1 + t * dy))
+}
+
+fn near_polyline(points: &[(f32, f32)], closed: bool, x: f32, y: f32, half_width: f32) -> bool {
+    let closing = points
+        .last()
+        .zip(points.first())
+        .filter(|_| closed)
+        .map(|(a, b)| (*a, *b));
+    points
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .chain(closing)
+        .any(|(a, b)| distance_to_segment((x, y), a, b) <= half_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SvgDocument;
+
+    #[test]
+    fn test_transformed_circle_hit_by_radius() {
+        let doc = SvgDocument::parse(
+            r#"<svg viewBox="0 0 100 100">
+                <rect x="0" y="0" width="100" height="100" fill="white"/>
+                <circle cx="10" cy="10" r="10" transform="translate(40 40) scale(2)" fill="red"/>
+            </svg>"#,
+        )
+        .unwrap();
+        // The circle is centred at (60, 60) with radius 20
+        let hit = doc.hit_test(75.0, 60.0).unwrap();
+        assert_eq!(hit.path, [1]);
+        assert_eq!((hit.local_x, hit.local_y), (17.5, 10.0));
+        // Inside its bounding box but outside the radius
+        assert_eq!(doc.hit_test(44.0, 44.0).unwrap().path, [0]);
+    }
+
+    #[test]
+    fn test_pointer_events_and_strokes() {
+        let doc = SvgDocument::parse(
+            r#"<svg>
+                <polygon points="0,0 50,0 50,50 0,50" fill="blue"/>
+                <rect x="0" y="0" width="50" height="50" fill="red" pointer-events="none"/>
+                <line x1="0" y1="100" x2="100" y2="100" stroke="black" stroke-width="4"/>
+                <path d="M 0 200 L 100 200" fill="none" stroke="black" stroke-width="2"/>
+                <circle cx="300" cy="300" r="10" fill="none" stroke="black"/>
+                <ellipse cx="300" cy="100" rx="20" ry="10" fill="green" visibility="hidden"/>
+            </svg>"#,
+        )
+        .unwrap();
+        // The rect on top is transparent to the pointer
+        assert_eq!(doc.hit_test(25.0, 25.0).unwrap().path, [0]);
+        assert_eq!(doc.hit_test(50.0, 101.5).unwrap().path, [2]);
+        assert!(doc.hit_test(50.0, 103.0).is_none());
+        assert_eq!(doc.hit_test(50.0, 200.5).unwrap().path, [3]);
+        // An unfilled circle is only hit on its stroke
+        assert!(doc.hit_test(300.0, 300.0).is_none());
+        assert_eq!(doc.hit_test(310.0, 300.0).unwrap().path, [4]);
+        assert!(doc.hit_test(300.0, 100.0).is_none());
+    }
+}
@@ -9,6 +9,7 @@
 //! - **Paths**: SVG path commands (M, L, C, S, Q, T, A, Z)
 //! - **Styling**: fill, stroke, opacity, transforms
 //! - **Text**: Basic SVG text rendering
+//! - **Clipping**: `<clipPath>` applied to shapes and groups
 //! - **Rendering**: Convert SVG to display commands
 //!
 //! ## Architecture
@@ -23,11 +24,19 @@
 //! ```
 
 use rustkit_css::Color;
+use rustkit_dom::{Node, NodeId, NodeType};
 use rustkit_layout::{DisplayCommand, Rect};
 use std::collections::HashMap;
 use std::f32::consts::PI;
 use thiserror::Error;
 
+mod clip;
+mod hit;
+
+pub use clip::{ClipPathUnits, SvgClipPath};
+pub use hit::SvgHitResult;
+pub use rustkit_css::FillRule;
+
 // ==================== Errors ====================
 
 /// Errors that can occur in SVG operations.
@@ -59,7 +68,7 @@ pub struct SvgDocument {
     pub width: Option<SvgLength>,
     /// Document height.
     pub height: Option<SvgLength>,
-    /// Defined elements (for use references).
+    /// Elements by `id`, for `<use>` and `clip-path` references.
     pub defs: HashMap<String, SvgElement>,
 }
 
@@ -106,7 +115,7 @@ impl SvgDocument {
         }
 
         // Parse elements (simplified)
-        doc.root = parse_svg_content(xml)?;
+        (doc.root, doc.defs) = parse_svg_content(xml)?;
 
         Ok(doc)
     }
@@ -128,11 +137,50 @@ impl SvgDocument {
         (width, height)
     }
 
-    /// Render to display commands.
-    pub fn render(&self, x: f32, y: f32, width: f32, height: f32) -> Vec<DisplayCommand> {
-        let mut commands = Vec::new();
-        // Apply viewBox transform if present
-        let transform = if let Some(vb) = &self.view_box {
+    /// Build a document from an `<svg>` element of a parsed HTML document.
+    ///
+    /// Also returns the DOM node of each child of [`Self::root`], so a
+    /// [`SvgHitResult`] path can be mapped back to the element it hit.
+    pub fn from_element(svg: &Node) -> (Self, Vec<NodeId>) {
+        let mut doc = Self::new();
+        let attr = |name: &str| svg.get_attribute(name);
+        doc.view_box = attr("viewBox")
+            .or_else(|| attr("viewbox"))
+            .and_then(ViewBox::parse);
+        doc.width = attr("width").and_then(SvgLength::parse);
+        doc.height = attr("height").and_then(SvgLength::parse);
+
+        fn collect(node: &Node, builder: &mut TreeBuilder) {
+            for child in node.children() {
+                if let NodeType::Element {
+                    tag_name,
+                    attributes,
+                    ..
+                } = &child.node_type
+                {
+                    let attrs = attributes
+                        .iter()
+                        .map(|(k, v)| (k.to_lowercase(), v.clone()))
+                        .collect();
+                    builder.open(tag_name, &attrs, Some(child.id));
+                    collect(&child, builder);
+                    builder.close(tag_name);
+                } else {
+                    collect(&child, builder);
+                }
+            }
+        }
+        let mut builder = TreeBuilder::default();
+        collect(svg, &mut builder);
+        doc.root = SvgElement::Group(builder.root);
+        doc.defs = builder.defs;
+        (doc, builder.nodes)
+    }
+
+    /// Map from the document's user space to a `width` by `height`
+    /// viewport at (`x`, `y`), fitting the viewBox if there is one.
+    pub fn viewport_transform(&self, x: f32, y: f32, width: f32, height: f32) -> Transform2D {
+        if let Some(vb) = &self.view_box {
             let scale_x = width / vb.width;
             let scale_y = height / vb.height;
             let scale = scale_x.min(scale_y);
@@ -142,9 +190,15 @@ impl SvgDocument {
                 .scale(scale, scale)
         } else {
             Transform2D::identity().translate(x, y)
-        };
+        }
+    }
 
-        self.root.render(&transform, &SvgStyle::default(), &mut commands);
+    /// Render to display commands, applying `clip-path` references.
+    pub fn render(&self, x: f32, y: f32, width: f32, height: f32) -> Vec<DisplayCommand> {
+        let mut commands = Vec::new();
+        let transform = self.viewport_transform(x, y, width, height);
+        self.root
+            .render_in(&self.defs, &transform, &SvgStyle::default(), &mut commands);
 
         commands
     }
@@ -325,6 +379,22 @@ impl Transform2D {
         )
     }
 
+    /// The inverse transform, or `None` if this one is singular.
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        Some(Transform2D {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
     /// Parse SVG transform attribute.
     pub fn parse(s: &str) -> Self {
         let mut result = Self::identity();
@@ -481,12 +551,38 @@ pub enum LineJoin {
     Bevel,
 }
 
-/// Fill rule.
+/// Which parts of a shape receive pointer events (`pointer-events`).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum FillRule {
+pub enum PointerEvents {
+    /// The painted fill or stroke of a visible shape.
     #[default]
-    NonZero,
-    EvenOdd,
+    VisiblePainted,
+    VisibleFill,
+    VisibleStroke,
+    /// The fill or stroke area of a visible shape, painted or not.
+    Visible,
+    Painted,
+    Fill,
+    Stroke,
+    All,
+    None,
+}
+
+impl PointerEvents {
+    /// Parse a `pointer-events` value; unknown values are the initial one.
+    pub fn parse(s: &str) -> Self {
+        match s.trim() {
+            "visibleFill" => Self::VisibleFill,
+            "visibleStroke" => Self::VisibleStroke,
+            "visible" => Self::Visible,
+            "painted" => Self::Painted,
+            "fill" => Self::Fill,
+            "stroke" => Self::Stroke,
+            "all" => Self::All,
+            "none" => Self::None,
+            _ => Self::VisiblePainted,
+        }
+    }
 }
 
 /// SVG styling properties.
@@ -518,6 +614,12 @@ pub struct SvgStyle {
     pub opacity: f32,
     /// Visibility.
     pub visibility: bool,
+    /// Parts of the shape that are hit by the pointer.
+    pub pointer_events: PointerEvents,
+    /// `id` of the `<clipPath>` clipping this element; not inherited.
+    pub clip_path: Option<String>,
+    /// Fill rule of a shape when it is part of a clip path.
+    pub clip_rule: FillRule,
 }
 
 impl Default for SvgStyle {
@@ -536,6 +638,9 @@ impl Default for SvgStyle {
             stroke_dashoffset: 0.0,
             opacity: 1.0,
             visibility: true,
+            pointer_events: PointerEvents::VisiblePainted,
+            clip_path: None,
+            clip_rule: FillRule::NonZero,
         }
     }
 }
@@ -548,6 +653,15 @@ impl SvgStyle {
         if self.opacity == 1.0 {
             self.opacity = parent.opacity;
         }
+        if self.pointer_events == PointerEvents::default() {
+            self.pointer_events = parent.pointer_events;
+        }
+        if self.fill_rule == FillRule::default() {
+            self.fill_rule = parent.fill_rule;
+        }
+        if self.clip_rule == FillRule::default() {
+            self.clip_rule = parent.clip_rule;
+        }
     }
 
     /// Parse style attributes.
@@ -555,6 +669,9 @@ impl SvgStyle {
         if let Some(fill) = attrs.get("fill") {
             self.fill = Paint::parse(fill);
         }
+        if let Some(rule) = attrs.get("fill-rule").and_then(|r| FillRule::parse(r)) {
+            self.fill_rule = rule;
+        }
         if let Some(fill_opacity) = attrs.get("fill-opacity") {
             self.fill_opacity = fill_opacity.parse().unwrap_or(1.0);
         }
@@ -579,6 +696,18 @@ impl SvgStyle {
                 _ => LineCap::Butt,
             };
         }
+        if let Some(visibility) = attrs.get("visibility") {
+            self.visibility = !matches!(visibility.trim(), "hidden" | "collapse");
+        }
+        if let Some(pointer_events) = attrs.get("pointer-events") {
+            self.pointer_events = PointerEvents::parse(pointer_events);
+        }
+        if let Some(clip_path) = attrs.get("clip-path") {
+            self.clip_path = parse_url_reference(clip_path);
+        }
+        if let Some(rule) = attrs.get("clip-rule").and_then(|r| FillRule::parse(r)) {
+            self.clip_rule = rule;
+        }
         if let Some(linejoin) = attrs.get("stroke-linejoin") {
             self.stroke_linejoin = match linejoin.as_str() {
                 "round" => LineJoin::Round,
@@ -614,6 +743,8 @@ pub enum SvgElement {
     Text(SvgText),
     /// Use reference.
     Use(SvgUse),
+    /// Clipping path, only painted through `clip-path` references.
+    ClipPath(SvgClipPath),
 }
 
 impl SvgElement {
@@ -630,6 +761,7 @@ impl SvgElement {
             SvgElement::Path(p) => p.render(transform, parent_style, commands),
             SvgElement::Text(t) => t.render(transform, parent_style, commands),
             SvgElement::Use(_) => {} // TODO: resolve references
+            SvgElement::ClipPath(_) => {}
         }
     }
 }
@@ -973,8 +1105,9 @@ impl SvgPolygon {
         if let Some(color) = style.fill.as_color() {
             let alpha = (color.a * style.fill_opacity * style.opacity).clamp(0.0, 1.0);
             let fill_color = Color { a: alpha, ..color };
-            commands.push(DisplayCommand::FillPolygon {
-                points: points.clone(),
+            commands.push(DisplayCommand::FillPath {
+                subpaths: vec![points.clone()],
+                fill_rule: style.fill_rule,
                 color: fill_color,
             });
         }
@@ -1345,30 +1478,35 @@ impl SvgPath {
             return;
         }
 
-        let segments = self.to_line_segments();
+        let subpaths: Vec<Vec<(f32, f32)>> = self
+            .to_line_segments()
+            .iter()
+            .map(|segment| {
+                segment
+                    .iter()
+                    .map(|(x, y)| transform.apply(*x, *y))
+                    .collect()
+            })
+            .collect();
 
-        for segment in segments {
-            let points: Vec<(f32, f32)> = segment
-                .iter()
-                .map(|(x, y)| transform.apply(*x, *y))
-                .collect();
+        // All subpaths fill one region, each closed implicitly
+        if let Some(color) = style.fill.as_color() {
+            let filled: Vec<_> = subpaths.iter().filter(|p| p.len() >= 3).cloned().collect();
+            if !filled.is_empty() {
+                let alpha = (color.a * style.fill_opacity * style.opacity).clamp(0.0, 1.0);
+                commands.push(DisplayCommand::FillPath {
+                    subpaths: filled,
+                    fill_rule: style.fill_rule,
+                    color: Color { a: alpha, ..color },
+                });
+            }
+        }
 
+        for points in subpaths {
             if points.len() < 2 {
                 continue;
             }
 
-            // Fill (only for closed paths)
-            if let Some(color) = style.fill.as_color() {
-                if points.len() >= 3 {
-                    let alpha = (color.a * style.fill_opacity * style.opacity).clamp(0.0, 1.0);
-                    let fill_color = Color { a: alpha, ..color };
-                    commands.push(DisplayCommand::FillPolygon {
-                        points: points.clone(),
-                        color: fill_color,
-                    });
-                }
-            }
-
             // Stroke
             if let Some(color) = style.stroke.as_color() {
                 let alpha = (color.a * style.stroke_opacity * style.opacity).clamp(0.0, 1.0);
@@ -1603,9 +1741,9 @@ fn parse_svg_color(s: &str) -> Option<Color> {
     }
 }
 
-/// Parse SVG content into elements.
-fn parse_svg_content(xml: &str) -> Result<SvgElement, SvgError> {
-    let mut group = SvgGroup::new();
+/// Parse SVG content into elements and the elements defined by `id`.
+fn parse_svg_content(xml: &str) -> Result<(SvgElement, HashMap<String, SvgElement>), SvgError> {
+    let mut builder = TreeBuilder::default();
     
     // Simple element parsing
     let mut pos = 0;
@@ -1621,9 +1759,10 @@ fn parse_svg_content(xml: &str) -> Result<SvgElement, SvgError> {
                 }
             }
             
-            // Skip closing tags
+            // Closing tags end containers
             if xml[tag_start..].starts_with("</") {
                 if let Some(end) = xml[tag_start..].find('>') {
+                    builder.close(xml[tag_start + 2..tag_start + end].trim());
                     pos = tag_start + end + 1;
                     continue;
                 }
@@ -1634,8 +1773,11 @@ fn parse_svg_content(xml: &str) -> Result<SvgElement, SvgError> {
                 let tag = &xml[tag_start..tag_start + tag_end + 1];
                 
                 // Parse element
-                if let Some(element) = parse_element(tag) {
-                    group.children.push(element);
+                if let Some((name, attrs)) = parse_tag(tag) {
+                    builder.open(&name, &attrs, None);
+                    if tag.ends_with("/>") {
+                        builder.close(&name);
+                    }
                 }
                 
                 pos = tag_start + tag_end + 1;
@@ -1647,11 +1789,11 @@ fn parse_svg_content(xml: &str) -> Result<SvgElement, SvgError> {
         }
     }
 
-    Ok(SvgElement::Group(group))
+    Ok((SvgElement::Group(builder.root), builder.defs))
 }
 
-/// Parse a single SVG element.
-fn parse_element(tag: &str) -> Option<SvgElement> {
+/// Split a tag into its lowercased name and attributes.
+fn parse_tag(tag: &str) -> Option<(String, HashMap<String, String>)> {
     let tag = tag.trim_start_matches('<').trim_end_matches('>').trim_end_matches('/');
     let parts: Vec<&str> = tag.splitn(2, char::is_whitespace).collect();
     let name = parts.first()?.to_lowercase();
@@ -1664,7 +1806,135 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
         attr_str = rest;
     }
 
-    match name.as_str() {
+    Some((name, attrs))
+}
+
+/// What a container element open during parsing collects its children into.
+enum Container {
+    /// A plain `<g>`, whose children are flattened into the enclosing one.
+    Flat,
+    /// A `<g>` with a `clip-path`, kept as a group so the clip covers it.
+    Group(SvgGroup, Option<NodeId>),
+    /// A `<clipPath>`, added to the defs when it closes.
+    ClipPath(Option<String>, SvgClipPath),
+    /// A `<mask>`, whose contents are dropped.
+    Mask,
+}
+
+/// Builds the element tree from a stream of opened and closed tags.
+#[derive(Default)]
+struct TreeBuilder {
+    root: SvgGroup,
+    defs: HashMap<String, SvgElement>,
+    /// DOM node of each child of `root`.
+    nodes: Vec<NodeId>,
+    open: Vec<(String, Container)>,
+}
+
+impl TreeBuilder {
+    fn open(&mut self, name: &str, attrs: &HashMap<String, String>, node: Option<NodeId>) {
+        let name = name.to_lowercase();
+        let id = attrs.get("id").cloned();
+        let transform = attrs
+            .get("transform")
+            .map(|t| Transform2D::parse(t))
+            .unwrap_or_default();
+        let container = match name.as_str() {
+            "g" if attrs.contains_key("clip-path") => {
+                let mut group = SvgGroup::new();
+                group.transform = transform;
+                group.style.parse_attributes(attrs);
+                group.id = id;
+                Container::Group(group, node)
+            }
+            "g" => Container::Flat,
+            "clippath" => {
+                let clip = SvgClipPath {
+                    units: attrs
+                        .get("clippathunits")
+                        .map(|u| ClipPathUnits::parse(u))
+                        .unwrap_or_default(),
+                    transform,
+                    children: Vec::new(),
+                };
+                Container::ClipPath(id, clip)
+            }
+            "mask" => Container::Mask,
+            _ => {
+                if let Some(element) = element_from_attributes(&name, attrs) {
+                    if let Some(id) = id {
+                        self.defs.insert(id, element.clone());
+                    }
+                    self.add(element, node);
+                }
+                return;
+            }
+        };
+        self.open.push((name, container));
+    }
+
+    fn close(&mut self, name: &str) {
+        if !self
+            .open
+            .last()
+            .is_some_and(|(open, _)| open.eq_ignore_ascii_case(name))
+        {
+            return;
+        }
+        match self.open.pop() {
+            Some((_, Container::Group(group, node))) => {
+                let group = SvgElement::Group(group);
+                if let SvgElement::Group(SvgGroup { id: Some(id), .. }) = &group {
+                    self.defs.insert(id.clone(), group.clone());
+                }
+                self.add(group, node);
+            }
+            Some((_, Container::ClipPath(Some(id), clip))) => {
+                self.defs.insert(id, SvgElement::ClipPath(clip));
+            }
+            _ => {}
+        }
+    }
+
+    /// Add `element` to the innermost container that keeps its children.
+    fn add(&mut self, element: SvgElement, node: Option<NodeId>) {
+        let target = self
+            .open
+            .iter_mut()
+            .rev()
+            .map(|(_, container)| container)
+            .find(|container| !matches!(container, Container::Flat));
+        match target {
+            Some(Container::Group(group, _)) => group.children.push(element),
+            Some(Container::ClipPath(_, clip)) => {
+                if SvgClipPath::accepts(&element) {
+                    clip.children.push(element);
+                }
+            }
+            Some(Container::Mask) => {}
+            Some(Container::Flat) | None => {
+                self.root.children.push(element);
+                self.nodes.extend(node);
+            }
+        }
+    }
+}
+
+/// The `id` in a `url(#id)` reference, or `None` for `none`.
+fn parse_url_reference(s: &str) -> Option<String> {
+    let id = s
+        .trim()
+        .strip_prefix("url(")?
+        .strip_suffix(')')?
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .strip_prefix('#')?;
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// Build a shape element from its tag name and lowercased attributes.
+fn element_from_attributes(name: &str, attrs: &HashMap<String, String>) -> Option<SvgElement> {
+    match name {
         "rect" => {
             let mut rect = SvgRect::default();
             rect.x = attrs.get("x").and_then(|s| s.parse().ok()).unwrap_or(0.0);
@@ -1676,7 +1946,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 rect.transform = Transform2D::parse(t);
             }
-            rect.style.parse_attributes(&attrs);
+            rect.style.parse_attributes(attrs);
             Some(SvgElement::Rect(rect))
         }
         "circle" => {
@@ -1687,7 +1957,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 circle.transform = Transform2D::parse(t);
             }
-            circle.style.parse_attributes(&attrs);
+            circle.style.parse_attributes(attrs);
             Some(SvgElement::Circle(circle))
         }
         "ellipse" => {
@@ -1699,7 +1969,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 ellipse.transform = Transform2D::parse(t);
             }
-            ellipse.style.parse_attributes(&attrs);
+            ellipse.style.parse_attributes(attrs);
             Some(SvgElement::Ellipse(ellipse))
         }
         "line" => {
@@ -1711,7 +1981,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 line.transform = Transform2D::parse(t);
             }
-            line.style.parse_attributes(&attrs);
+            line.style.parse_attributes(attrs);
             Some(SvgElement::Line(line))
         }
         "path" => {
@@ -1722,7 +1992,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 path.transform = Transform2D::parse(t);
             }
-            path.style.parse_attributes(&attrs);
+            path.style.parse_attributes(attrs);
             Some(SvgElement::Path(path))
         }
         "polyline" => {
@@ -1733,9 +2003,27 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 polyline.transform = Transform2D::parse(t);
             }
-            polyline.style.parse_attributes(&attrs);
+            polyline.style.parse_attributes(attrs);
             Some(SvgElement::Polyline(polyline))
         }
+        "use" => {
+            let number = |name: &str| attrs.get(name).and_then(|s| s.parse().ok());
+            Some(SvgElement::Use(SvgUse {
+                href: attrs
+                    .get("href")
+                    .or_else(|| attrs.get("xlink:href"))
+                    .cloned()
+                    .unwrap_or_default(),
+                x: number("x").unwrap_or(0.0),
+                y: number("y").unwrap_or(0.0),
+                width: number("width"),
+                height: number("height"),
+                transform: attrs
+                    .get("transform")
+                    .map(|t| Transform2D::parse(t))
+                    .unwrap_or_default(),
+            }))
+        }
         "polygon" => {
             let mut polygon = SvgPolygon::default();
             if let Some(points_str) = attrs.get("points") {
@@ -1744,7 +2032,7 @@ fn parse_element(tag: &str) -> Option<SvgElement> {
             if let Some(t) = attrs.get("transform") {
                 polygon.transform = Transform2D::parse(t);
             }
-            polygon.style.parse_attributes(&attrs);
+            polygon.style.parse_attributes(attrs);
             Some(SvgElement::Polygon(polygon))
         }
         _ => None,
@@ -1877,6 +2165,94 @@ mod tests {
         assert!(matches!(commands[1], PathCommand::CubicTo(10.0, 20.0, 30.0, 40.0, 50.0, 60.0)));
     }
 
+    /// A circle of four cubic arcs, clockwise on screen unless `reverse`.
+    fn circle_path(cx: f32, cy: f32, r: f32, reverse: bool) -> String {
+        let k = r * 0.5523;
+        let (x0, x1, y0, y1) = (cx - r, cx + r, cy - r, cy + r);
+        if reverse {
+            format!(
+                "M {x1} {cy} C {x1} {a} {b} {y0} {cx} {y0} C {c} {y0} {x0} {a} {x0} {cy} \
+                 C {x0} {d} {c} {y1} {cx} {y1} C {b} {y1} {x1} {d} {x1} {cy} Z",
+                a = cy - k,
+                b = cx + k,
+                c = cx - k,
+                d = cy + k,
+            )
+        } else {
+            format!(
+                "M {x1} {cy} C {x1} {d} {b} {y1} {cx} {y1} C {c} {y1} {x0} {d} {x0} {cy} \
+                 C {x0} {a} {c} {y0} {cx} {y0} C {b} {y0} {x1} {a} {x1} {cy} Z",
+                a = cy - k,
+                b = cx + k,
+                c = cx - k,
+                d = cy + k,
+            )
+        }
+    }
+
+    /// Whether a path of an outer and an inner circle paints (`x`, `y`).
+    fn donut_covers(inner_reversed: bool, fill_rule: FillRule, x: f32, y: f32) -> bool {
+        let path = SvgPath {
+            commands: SvgPath::parse(&format!(
+                "{} {}",
+                circle_path(50.0, 50.0, 40.0, false),
+                circle_path(50.0, 50.0, 20.0, inner_reversed)
+            )),
+            transform: Transform2D::identity(),
+            style: SvgStyle {
+                fill: Paint::Color(Color::BLACK),
+                fill_rule,
+                ..SvgStyle::default()
+            },
+        };
+        let mut commands = Vec::new();
+        path.render(
+            &Transform2D::identity(),
+            &SvgStyle::default(),
+            &mut commands,
+        );
+        let fills: Vec<_> = commands
+            .iter()
+            .filter_map(|c| match c {
+                DisplayCommand::FillPath {
+                    subpaths,
+                    fill_rule,
+                    ..
+                } => Some(rustkit_canvas::fill_trapezoids(subpaths, *fill_rule)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills.len(), 1, "one fill for all subpaths");
+        fills[0].iter().any(|[tl, tr, br, bl]| {
+            let t = (y - tl.1) / (bl.1 - tl.1);
+            (0.0..1.0).contains(&t) && x >= tl.0 + t * (bl.0 - tl.0) && x < tr.0 + t * (br.0 - tr.0)
+        })
+    }
+
+    #[test]
+    fn test_path_fill_rule_across_subpaths() {
+        // Evenodd leaves the inner circle a hole whichever way it runs
+        assert!(!donut_covers(false, FillRule::EvenOdd, 50.0, 50.0));
+        assert!(donut_covers(false, FillRule::EvenOdd, 50.0, 15.0));
+        // Nonzero fills same-direction circles solid...
+        assert!(donut_covers(false, FillRule::NonZero, 50.0, 50.0));
+        // ...but an inner circle running the other way still cuts a hole
+        assert!(!donut_covers(true, FillRule::NonZero, 50.0, 50.0));
+        assert!(donut_covers(true, FillRule::NonZero, 50.0, 15.0));
+    }
+
+    #[test]
+    fn test_fill_rule_attribute_inherits() {
+        let mut parent = SvgStyle::default();
+        parent.parse_attributes(&HashMap::from([(
+            "fill-rule".to_string(),
+            "evenodd".to_string(),
+        )]));
+        let mut child = SvgStyle::default();
+        child.inherit_from(&parent);
+        assert_eq!(child.fill_rule, FillRule::EvenOdd);
+    }
+
     #[test]
     fn test_parse_color() {
         let color = parse_svg_color("#ff0000").unwrap();
@@ -113,6 +113,7 @@ fn format_box(layout: &LayoutBox, output: &mut String, indent: usize) {
     let box_type = match &layout.box_type {
         BoxType::Block => "block",
         BoxType::Inline => "inline",
+        BoxType::InlineBlock => "inline-block",
         BoxType::AnonymousBlock => "anonymous",
         BoxType::Text(_) => "text",
     };
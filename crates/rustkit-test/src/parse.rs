@@ -137,6 +137,16 @@ fn format_node(node: &Rc<Node>, output: &mut String, indent: usize) {
         NodeType::ProcessingInstruction { .. } => {
             output.push_str(&format!("{}<?...?>\n", prefix));
         }
+        NodeType::DocumentFragment => {
+            output.push_str(&format!("{}#document-fragment\n", prefix));
+        }
+    }
+
+    if let Some(content) = node.template_content() {
+        output.push_str(&format!("{}  content\n", prefix));
+        for child in content.children() {
+            format_node(&child, output, indent + 2);
+        }
     }
 
     for child in node.children() {
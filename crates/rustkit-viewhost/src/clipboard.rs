@@ -0,0 +1,290 @@
+//! System clipboard access.
+//!
+//! On Windows [`SystemClipboard`] reads and writes `CF_UNICODETEXT` and the
+//! registered `"HTML Format"` (CF_HTML), whose payload is UTF-8 markup behind
+//! a header of byte offsets. Other platforms keep a clipboard private to the
+//! process.
+
+use std::sync::Mutex;
+
+use crate::ViewHostError;
+
+/// The flavors of data on the clipboard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardContents {
+    /// `text/plain`
+    pub text: Option<String>,
+    /// `text/html`, as a fragment
+    pub html: Option<String>,
+}
+
+/// A clipboard the engine can read and write.
+pub trait Clipboard: Send + Sync {
+    fn read(&self) -> Result<ClipboardContents, ViewHostError>;
+
+    /// Replace the clipboard contents with `contents`.
+    fn write(&self, contents: &ClipboardContents) -> Result<(), ViewHostError>;
+}
+
+/// A clipboard held in memory, for tests and platforms without one.
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    contents: Mutex<ClipboardContents>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What was last written.
+    pub fn contents(&self) -> ClipboardContents {
+        self.contents.lock().unwrap().clone()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn read(&self) -> Result<ClipboardContents, ViewHostError> {
+        Ok(self.contents())
+    }
+
+    fn write(&self, contents: &ClipboardContents) -> Result<(), ViewHostError> {
+        *self.contents.lock().unwrap() = contents.clone();
+        Ok(())
+    }
+}
+
+/// Width of the zero-padded offsets in a CF_HTML header.
+const OFFSET_DIGITS: usize = 10;
+const START_FRAGMENT: &str = "<!--StartFragment-->";
+const END_FRAGMENT: &str = "<!--EndFragment-->";
+
+/// Wrap an HTML fragment in the CF_HTML format: a header giving the byte
+/// offsets of the document and of the fragment within it.
+pub fn encode_cf_html(fragment: &str, source_url: Option<&str>) -> String {
+    let header =
+        |start_html: usize, end_html: usize, start_fragment: usize, end_fragment: usize| {
+            let mut header = format!(
+                "Version:0.9\r\nStartHTML:{:0w$}\r\nEndHTML:{:0w$}\r\n\
+             StartFragment:{:0w$}\r\nEndFragment:{:0w$}\r\n",
+                start_html,
+                end_html,
+                start_fragment,
+                end_fragment,
+                w = OFFSET_DIGITS
+            );
+            if let Some(url) = source_url {
+                header.push_str(&format!("SourceURL:{}\r\n", url));
+            }
+            header
+        };
+    let prefix = format!("<html>\r\n<body>\r\n{}", START_FRAGMENT);
+    let suffix = format!("{}\r\n</body>\r\n</html>", END_FRAGMENT);
+
+    // Offsets are fixed width, so the header's length does not depend on them
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + prefix.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + suffix.len();
+    format!(
+        "{}{}{}{}",
+        header(start_html, end_html, start_fragment, end_fragment),
+        prefix,
+        fragment,
+        suffix
+    )
+}
+
+/// The fragment of CF_HTML data, or the whole document if the header
+/// marks no fragment. `None` if the header is malformed.
+pub fn decode_cf_html(data: &str) -> Option<String> {
+    let offset = |name: &str| -> Option<usize> {
+        data.lines()
+            .take_while(|line| !line.starts_with('<'))
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.trim().parse().ok())
+    };
+    let (start, end) = match (offset("StartFragment"), offset("EndFragment")) {
+        (Some(start), Some(end)) => (start, end),
+        _ => (offset("StartHTML")?, offset("EndHTML")?),
+    };
+    let bytes = data.as_bytes().get(start..end.min(data.len()))?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// The clipboard of the operating system.
+#[derive(Debug, Default)]
+pub struct SystemClipboard {
+    #[cfg(not(windows))]
+    memory: MemoryClipboard,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use super::*;
+    use windows::core::w;
+    use windows::Win32::Foundation::{GlobalFree, HANDLE, HGLOBAL, HWND};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable,
+        OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{
+        GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    };
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+    /// Times to try opening a clipboard another process holds.
+    const OPEN_ATTEMPTS: u32 = 5;
+
+    fn api_error(e: windows::core::Error) -> ViewHostError {
+        ViewHostError::WindowsApi(e.to_string())
+    }
+
+    /// Holds the clipboard open until dropped.
+    struct OpenGuard;
+
+    impl OpenGuard {
+        fn open() -> Result<Self, ViewHostError> {
+            let mut attempt = 0;
+            loop {
+                match unsafe { OpenClipboard(HWND::default()) } {
+                    Ok(()) => return Ok(Self),
+                    Err(e) if attempt + 1 >= OPEN_ATTEMPTS => return Err(api_error(e)),
+                    Err(_) => {
+                        attempt += 1;
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for OpenGuard {
+        fn drop(&mut self) {
+            let _ = unsafe { CloseClipboard() };
+        }
+    }
+
+    fn html_format() -> u32 {
+        unsafe { RegisterClipboardFormatW(w!("HTML Format")) }
+    }
+
+    /// Bytes of the clipboard data in `format`, if present.
+    fn get(format: u32) -> Option<Vec<u8>> {
+        unsafe {
+            IsClipboardFormatAvailable(format).ok()?;
+            let handle = GetClipboardData(format).ok()?;
+            let global = HGLOBAL(handle.0);
+            let data = GlobalLock(global) as *const u8;
+            if data.is_null() {
+                return None;
+            }
+            let bytes = std::slice::from_raw_parts(data, GlobalSize(global)).to_vec();
+            let _ = GlobalUnlock(global);
+            Some(bytes)
+        }
+    }
+
+    /// Hand `bytes` to the clipboard as `format`.
+    fn set(format: u32, bytes: &[u8]) -> Result<(), ViewHostError> {
+        unsafe {
+            let global = GlobalAlloc(GMEM_MOVEABLE, bytes.len()).map_err(api_error)?;
+            let data = GlobalLock(global) as *mut u8;
+            if data.is_null() {
+                let _ = GlobalFree(global);
+                return Err(ViewHostError::WindowsApi("GlobalLock failed".into()));
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+            let _ = GlobalUnlock(global);
+            // The clipboard owns the memory once the call succeeds
+            if let Err(e) = SetClipboardData(format, HANDLE(global.0)) {
+                let _ = GlobalFree(global);
+                return Err(api_error(e));
+            }
+        }
+        Ok(())
+    }
+
+    impl Clipboard for SystemClipboard {
+        fn read(&self) -> Result<ClipboardContents, ViewHostError> {
+            let _open = OpenGuard::open()?;
+            let text = get(CF_UNICODETEXT.0 as u32).map(|bytes| {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .take_while(|&unit| unit != 0)
+                    .collect();
+                String::from_utf16_lossy(&units)
+            });
+            let html = get(html_format()).and_then(|bytes| {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                decode_cf_html(&String::from_utf8_lossy(&bytes[..end]))
+            });
+            Ok(ClipboardContents { text, html })
+        }
+
+        fn write(&self, contents: &ClipboardContents) -> Result<(), ViewHostError> {
+            let _open = OpenGuard::open()?;
+            unsafe { EmptyClipboard() }.map_err(api_error)?;
+            if let Some(text) = &contents.text {
+                let bytes: Vec<u8> = text
+                    .encode_utf16()
+                    .chain(Some(0))
+                    .flat_map(u16::to_le_bytes)
+                    .collect();
+                set(CF_UNICODETEXT.0 as u32, &bytes)?;
+            }
+            if let Some(html) = &contents.html {
+                let mut bytes = encode_cf_html(html, None).into_bytes();
+                bytes.push(0);
+                set(html_format(), &bytes)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl Clipboard for SystemClipboard {
+    fn read(&self) -> Result<ClipboardContents, ViewHostError> {
+        self.memory.read()
+    }
+
+    fn write(&self, contents: &ClipboardContents) -> Result<(), ViewHostError> {
+        self.memory.write(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cf_html_round_trips_a_fragment() {
+        let fragment = "<b>Bold</b> and <i>ünïcode</i>";
+        let data = encode_cf_html(fragment, Some("https://example.com/"));
+        assert!(data.starts_with("Version:0.9\r\nStartHTML:"));
+
+        let offset = |name: &str| -> usize {
+            let start = data.find(name).unwrap() + name.len() + 1;
+            data[start..start + OFFSET_DIGITS].parse().unwrap()
+        };
+        assert_eq!(
+            &data[offset("StartHTML")..offset("StartHTML") + 6],
+            "<html>"
+        );
+        assert_eq!(offset("EndHTML"), data.len());
+        assert_eq!(
+            &data[offset("StartFragment")..offset("EndFragment")],
+            fragment
+        );
+        assert_eq!(decode_cf_html(&data).as_deref(), Some(fragment));
+        assert_eq!(decode_cf_html("not a header"), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! OLE drop target for RustKit views.
+//!
+//! Drags from other applications over a view arrive through `IDropTarget`
+//! and are emitted as [`InputEvent::Drag`]. The data object is read once on
+//! `DragEnter` (files, text and URLs) and again on `Drop`. OLE wants the
+//! effect of a drop at once, so each call answers with the effect the
+//! engine last set with [`crate::ViewHost::set_drop_effect`].
+
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
+
+use rustkit_core::{DragData, DragEvent, DragEventType, DropEffect, InputEvent, Point};
+use tracing::trace;
+use windows::{
+    core::{implement, w, Result},
+    Win32::{
+        Foundation::{HGLOBAL, HWND, POINT, POINTL},
+        Graphics::Gdi::ScreenToClient,
+        System::{
+            Com::{IDataObject, DVASPECT_CONTENT, FORMATETC, TYMED_HGLOBAL},
+            DataExchange::RegisterClipboardFormatW,
+            Memory::{GlobalLock, GlobalSize, GlobalUnlock},
+            Ole::{
+                IDropTarget, IDropTarget_Impl, ReleaseStgMedium, CF_HDROP, CF_UNICODETEXT,
+                DROPEFFECT, DROPEFFECT_COPY, DROPEFFECT_LINK, DROPEFFECT_MOVE, DROPEFFECT_NONE,
+            },
+            SystemServices::MODIFIERKEYS_FLAGS,
+        },
+        UI::Shell::{DragQueryFileW, HDROP},
+    },
+};
+
+use crate::{ViewEvent, ViewHost, ViewId, VIEW_REGISTRY};
+
+/// Accepts drops onto one view.
+#[implement(IDropTarget)]
+pub struct DropTarget {
+    hwnd_raw: isize,
+    view_id: ViewId,
+    /// What the drag in progress carries.
+    data: RefCell<DragData>,
+}
+
+impl DropTarget {
+    /// Create the drop target for a view.
+    pub fn new(hwnd: HWND, view_id: ViewId) -> Self {
+        Self {
+            hwnd_raw: hwnd.0 as isize,
+            view_id,
+            data: RefCell::new(DragData::default()),
+        }
+    }
+
+    fn hwnd(&self) -> HWND {
+        HWND(self.hwnd_raw as *mut _)
+    }
+
+    /// Set the effect OLE is told until the engine decides.
+    fn reset_effect(&self) {
+        if let Some(state) = VIEW_REGISTRY.read().ok().and_then(|r| r.get(self.hwnd_raw)) {
+            state.lock().unwrap().drop_effect = DropEffect::None;
+        }
+    }
+
+    /// Emit a drag event at screen point `pt`, where the source allows the
+    /// effects in `effect`, and answer with the effect of a drop.
+    fn emit(&self, event_type: DragEventType, pt: Option<&POINTL>, effect: *mut DROPEFFECT) {
+        let screen = pt.map_or(POINT::default(), |pt| POINT { x: pt.x, y: pt.y });
+        let mut client = screen;
+        unsafe {
+            let _ = ScreenToClient(self.hwnd(), &mut client);
+        }
+        let allowed = match effect.is_null() {
+            true => Vec::new(),
+            false => effects(unsafe { *effect }),
+        };
+        let mut event = DragEvent::new(
+            event_type,
+            Point::new(client.x as f64, client.y as f64),
+            self.data.borrow().clone(),
+        )
+        .with_allowed_effects(&allowed)
+        .with_modifiers(ViewHost::get_modifiers());
+        event.screen_position = Point::new(screen.x as f64, screen.y as f64);
+
+        trace!(view_id = ?self.view_id, ?event_type, ?allowed, "Drag event");
+        let Ok(registry) = VIEW_REGISTRY.read() else {
+            return;
+        };
+        registry.emit(ViewEvent::Input {
+            view_id: self.view_id,
+            event: InputEvent::Drag(event),
+        });
+        if !effect.is_null() {
+            let chosen = registry
+                .get(self.hwnd_raw)
+                .map_or(DropEffect::None, |state| state.lock().unwrap().drop_effect);
+            let chosen = match allowed.contains(&chosen) {
+                true => chosen,
+                false => DropEffect::None,
+            };
+            unsafe { *effect = flag(chosen) };
+        }
+    }
+}
+
+impl IDropTarget_Impl for DropTarget_Impl {
+    fn DragEnter(
+        &self,
+        pdataobj: Option<&IDataObject>,
+        _grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        *self.data.borrow_mut() = pdataobj.map(drag_data).unwrap_or_default();
+        self.reset_effect();
+        self.emit(DragEventType::Enter, Some(pt), pdweffect);
+        Ok(())
+    }
+
+    fn DragOver(
+        &self,
+        _grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        self.emit(DragEventType::Over, Some(pt), pdweffect);
+        Ok(())
+    }
+
+    fn DragLeave(&self) -> Result<()> {
+        *self.data.borrow_mut() = DragData::default();
+        self.emit(DragEventType::Leave, None, std::ptr::null_mut());
+        self.reset_effect();
+        Ok(())
+    }
+
+    fn Drop(
+        &self,
+        pdataobj: Option<&IDataObject>,
+        _grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        if let Some(data) = pdataobj {
+            *self.data.borrow_mut() = drag_data(data);
+        }
+        self.emit(DragEventType::Drop, Some(pt), pdweffect);
+        *self.data.borrow_mut() = DragData::default();
+        self.reset_effect();
+        Ok(())
+    }
+}
+
+/// The effects set in OLE's flags.
+fn effects(flags: DROPEFFECT) -> Vec<DropEffect> {
+    [
+        (DROPEFFECT_COPY, DropEffect::Copy),
+        (DROPEFFECT_MOVE, DropEffect::Move),
+        (DROPEFFECT_LINK, DropEffect::Link),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.0 & flag.0 != 0)
+    .map(|(_, effect)| effect)
+    .collect()
+}
+
+/// OLE's flag for an effect.
+fn flag(effect: DropEffect) -> DROPEFFECT {
+    match effect {
+        DropEffect::None => DROPEFFECT_NONE,
+        DropEffect::Copy => DROPEFFECT_COPY,
+        DropEffect::Move => DROPEFFECT_MOVE,
+        DropEffect::Link => DROPEFFECT_LINK,
+    }
+}
+
+/// What a data object carries that pages can use.
+fn drag_data(data: &IDataObject) -> DragData {
+    let url_format = unsafe { RegisterClipboardFormatW(w!("UniformResourceLocatorW")) } as u16;
+    DragData {
+        files: files(data),
+        text: text(data, CF_UNICODETEXT.0),
+        urls: text(data, url_format).into_iter().collect(),
+    }
+}
+
+/// Read `format` from `data`, if it has it in global memory.
+fn with_global<T>(
+    data: &IDataObject,
+    format: u16,
+    read: impl FnOnce(HGLOBAL) -> Option<T>,
+) -> Option<T> {
+    let format = FORMATETC {
+        cfFormat: format,
+        ptd: std::ptr::null_mut(),
+        dwAspect: DVASPECT_CONTENT.0 as u32,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL.0 as u32,
+    };
+    unsafe {
+        let mut medium = data.GetData(&format).ok()?;
+        let result = read(medium.u.hGlobal);
+        ReleaseStgMedium(&mut medium);
+        result
+    }
+}
+
+/// Paths of the dropped files (CF_HDROP).
+fn files(data: &IDataObject) -> Vec<PathBuf> {
+    with_global(data, CF_HDROP.0, |global| unsafe {
+        let hdrop = HDROP(global.0);
+        let count = DragQueryFileW(hdrop, u32::MAX, None);
+        let paths = (0..count)
+            .map(|index| {
+                let len = DragQueryFileW(hdrop, index, None) as usize;
+                let mut buffer = vec![0u16; len + 1];
+                DragQueryFileW(hdrop, index, Some(&mut buffer));
+                buffer.truncate(len);
+                PathBuf::from(OsString::from_wide(&buffer))
+            })
+            .collect();
+        Some(paths)
+    })
+    .unwrap_or_default()
+}
+
+/// Null-terminated UTF-16 text in `format`.
+fn text(data: &IDataObject, format: u16) -> Option<String> {
+    with_global(data, format, |global| unsafe {
+        let ptr = GlobalLock(global) as *const u16;
+        if ptr.is_null() {
+            return None;
+        }
+        let units = std::slice::from_raw_parts(ptr, GlobalSize(global) / 2);
+        let end = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+        let text = String::from_utf16_lossy(&units[..end]);
+        let _ = GlobalUnlock(global);
+        Some(text).filter(|text| !text.is_empty())
+    })
+}
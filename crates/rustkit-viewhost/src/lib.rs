@@ -30,11 +30,42 @@ pub mod linux;
 // Screenshot capture
 pub mod screenshot;
 
+// System memory pressure notifications
+pub mod memory;
+
+// System network connectivity notifications
+pub mod network;
+
+// System clipboard
+pub mod clipboard;
+
+// System accessibility settings
+pub mod settings;
+
+// System locale data
+pub mod locale;
+
+// UI Automation provider
+#[cfg(windows)]
+pub mod uia;
+
+// OLE drop target
+#[cfg(windows)]
+pub mod drop_target;
+
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, RwLock};
 use thiserror::Error;
 use tracing::{debug, error, info, trace};
 
+pub use clipboard::{Clipboard, ClipboardContents, MemoryClipboard, SystemClipboard};
+pub use locale::SystemLocales;
+pub use memory::MemoryPressureMonitor;
+pub use network::ConnectivityMonitor;
+pub use settings::{SystemColors, SystemSettings};
+
+use rustkit_core::DropEffect;
+
 #[cfg(windows)]
 use rustkit_core::{
     FocusEvent, FocusEventType, InputEvent, KeyCode, KeyEvent, KeyEventType, KeyboardState,
@@ -50,12 +81,23 @@ use windows::{
             BeginPaint, EndPaint, InvalidateRect, ScreenToClient, UpdateWindow, HBRUSH,
             PAINTSTRUCT,
         },
-        System::LibraryLoader::GetModuleHandleW,
+        System::{
+            LibraryLoader::GetModuleHandleW,
+            Ole::{OleInitialize, RegisterDragDrop, RevokeDragDrop},
+        },
         UI::{
+            Accessibility::{
+                IRawElementProviderSimple, UiaReturnRawElementProvider, UiaRootObjectId,
+            },
             HiDpi::{
                 GetDpiForWindow, SetProcessDpiAwarenessContext,
                 DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
             },
+            Input::Ime::{
+                ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, ImmSetCandidateWindow,
+                CANDIDATEFORM, CFS_EXCLUDE, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, HIMC,
+                IME_COMPOSITION_STRING,
+            },
             Input::KeyboardAndMouse::{
                 GetAsyncKeyState, SetFocus, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT,
                 VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
@@ -122,6 +164,64 @@ impl Bounds {
     }
 }
 
+/// Mouse cursor shown over a view, one per Windows system cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CursorType {
+    /// `IDC_ARROW`.
+    #[default]
+    Arrow,
+    /// `IDC_IBEAM`, over text.
+    IBeam,
+    /// `IDC_HAND`, over links.
+    Hand,
+    /// `IDC_WAIT`.
+    Wait,
+    /// `IDC_APPSTARTING`, an arrow with a busy indicator.
+    Progress,
+    /// `IDC_HELP`.
+    Help,
+    /// `IDC_CROSS`.
+    Crosshair,
+    /// `IDC_SIZEALL`.
+    Move,
+    /// `IDC_NO`, over disabled controls.
+    NotAllowed,
+    /// `IDC_SIZEWE`.
+    SizeWE,
+    /// `IDC_SIZENS`.
+    SizeNS,
+    /// `IDC_SIZENESW`.
+    SizeNESW,
+    /// `IDC_SIZENWSE`.
+    SizeNWSE,
+    /// No cursor at all.
+    Hidden,
+}
+
+#[cfg(windows)]
+impl CursorType {
+    /// The shared system cursor; null for [`CursorType::Hidden`].
+    fn load(self) -> HCURSOR {
+        let id = match self {
+            Self::Arrow => IDC_ARROW,
+            Self::IBeam => IDC_IBEAM,
+            Self::Hand => IDC_HAND,
+            Self::Wait => IDC_WAIT,
+            Self::Progress => IDC_APPSTARTING,
+            Self::Help => IDC_HELP,
+            Self::Crosshair => IDC_CROSS,
+            Self::Move => IDC_SIZEALL,
+            Self::NotAllowed => IDC_NO,
+            Self::SizeWE => IDC_SIZEWE,
+            Self::SizeNS => IDC_SIZENS,
+            Self::SizeNESW => IDC_SIZENESW,
+            Self::SizeNWSE => IDC_SIZENWSE,
+            Self::Hidden => return HCURSOR::default(),
+        };
+        unsafe { LoadCursorW(None, id).unwrap_or_default() }
+    }
+}
+
 /// Errors that can occur in the ViewHost.
 #[derive(Error, Debug)]
 pub enum ViewHostError {
@@ -160,6 +260,13 @@ pub enum ViewEvent {
     /// Input event from the view (Windows only).
     #[cfg(windows)]
     Input { view_id: ViewId, event: InputEvent },
+    /// Assistive technology invoked an accessible element.
+    AccessibilityInvoke {
+        view_id: ViewId,
+        target: rustkit_a11y::AccessibleId,
+    },
+    /// The system's accessibility settings or colors may have changed.
+    SystemSettingsChanged { settings: SystemSettings },
 }
 
 /// Callback for view events.
@@ -175,6 +282,10 @@ struct ViewState {
     dpi: u32,
     visible: bool,
     focused: bool,
+    /// Shown while the mouse is over the view's client area.
+    cursor: CursorType,
+    /// What a drop on the view would do, as the engine last decided.
+    drop_effect: DropEffect,
     #[cfg(windows)]
     keyboard_state: KeyboardState,
     #[cfg(windows)]
@@ -187,6 +298,15 @@ struct ViewState {
     click_count: u32,
     #[cfg(windows)]
     tracking_mouse: bool,
+    /// High surrogate waiting for its WM_CHAR partner.
+    #[cfg(windows)]
+    pending_surrogate: Option<u16>,
+    /// Caret rect of the focused text field, for IME candidate placement.
+    #[cfg(windows)]
+    ime_caret: Option<Bounds>,
+    /// Accessibility tree exposed through UI Automation.
+    #[cfg(windows)]
+    accessibility: Option<Arc<RwLock<rustkit_a11y::AccessibilityTree>>>,
 }
 
 /// Global view registry for window procedure lookups.
@@ -292,6 +412,8 @@ impl ViewHost {
             // Enable per-monitor DPI awareness
             unsafe {
                 let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+                // OLE, for drops onto views
+                let _ = OleInitialize(None);
             }
         }
 
@@ -460,6 +582,15 @@ impl ViewHost {
                 }
             }
 
+            WM_SETTINGCHANGE | WM_SYSCOLORCHANGE => {
+                // Broadcast to top-level windows only
+                if let Ok(registry) = VIEW_REGISTRY.read() {
+                    registry.emit(ViewEvent::SystemSettingsChanged {
+                        settings: SystemSettings::read(),
+                    });
+                }
+            }
+
             WM_CLOSE => {
                 let _ = DestroyWindow(hwnd);
                 return LRESULT(0);
@@ -604,12 +735,17 @@ impl ViewHost {
             dpi,
             visible: true,
             focused: false,
+            cursor: CursorType::Arrow,
+            drop_effect: DropEffect::None,
             keyboard_state: KeyboardState::new(),
             mouse_state: MouseState::new(),
             last_click_time: 0,
             last_click_pos: Point::zero(),
             click_count: 0,
             tracking_mouse: false,
+            pending_surrogate: None,
+            ime_caret: None,
+            accessibility: None,
         }));
 
         // Store in local views map
@@ -624,6 +760,13 @@ impl ViewHost {
             registry.register(hwnd_raw, state);
         }
 
+        // Accept drops from other applications
+        let drop_target: windows::Win32::System::Ole::IDropTarget =
+            drop_target::DropTarget::new(hwnd, view_id).into();
+        if let Err(e) = unsafe { RegisterDragDrop(hwnd, &drop_target) } {
+            tracing::warn!(?view_id, error = %e, "Failed to register drop target");
+        }
+
         info!(?view_id, ?hwnd, dpi, "View created");
         Ok(view_id)
     }
@@ -643,11 +786,28 @@ impl ViewHost {
             dpi: 96,
             visible: true,
             focused: false,
+            cursor: CursorType::Arrow,
+            drop_effect: DropEffect::None,
         }));
         self.views.write().unwrap().insert(view_id, state);
         Ok(view_id)
     }
 
+    /// Attach the accessibility tree exposed to UI Automation for a view.
+    #[cfg(windows)]
+    pub fn set_accessibility_tree(
+        &self,
+        view_id: ViewId,
+        tree: Arc<RwLock<rustkit_a11y::AccessibilityTree>>,
+    ) -> Result<(), ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+        state.lock().unwrap().accessibility = Some(tree);
+        Ok(())
+    }
+
     /// Set the bounds of a view.
     pub fn set_bounds(&self, view_id: ViewId, bounds: Bounds) -> Result<(), ViewHostError> {
         let views = self.views.read().unwrap();
@@ -734,6 +894,90 @@ impl ViewHost {
         Ok(())
     }
 
+    /// Set the caret rect of the focused text field, in view coordinates.
+    ///
+    /// The IME candidate window is placed next to it (and kept from covering
+    /// it) when composition starts.
+    pub fn set_ime_caret_rect(&self, view_id: ViewId, caret: Bounds) -> Result<(), ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+
+        #[cfg(windows)]
+        {
+            let mut state = state.lock().unwrap();
+            state.ime_caret = Some(caret);
+            let hwnd = HWND(state.hwnd_raw as *mut _);
+            drop(state);
+            unsafe { Self::position_candidate_window(hwnd, caret) };
+        }
+        #[cfg(not(windows))]
+        let _ = (state, caret);
+
+        trace!(?view_id, ?caret, "IME caret updated");
+        Ok(())
+    }
+
+    /// Set the cursor shown over a view. It changes at once if the mouse
+    /// is over the view, and otherwise when it next enters.
+    pub fn set_cursor(&self, view_id: ViewId, cursor: CursorType) -> Result<(), ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+        let mut state = state.lock().unwrap();
+        if state.cursor == cursor {
+            return Ok(());
+        }
+        state.cursor = cursor;
+
+        // WM_SETCURSOR only comes with the next mouse move
+        #[cfg(windows)]
+        if state.tracking_mouse {
+            unsafe { SetCursor(cursor.load()) };
+        }
+
+        trace!(?view_id, ?cursor, "Cursor updated");
+        Ok(())
+    }
+
+    /// Get the cursor shown over a view.
+    pub fn get_cursor(&self, view_id: ViewId) -> Result<CursorType, ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+        let cursor = state.lock().unwrap().cursor;
+        Ok(cursor)
+    }
+
+    /// Set what a drop on a view would do. OLE shows it with the cursor
+    /// and reports it to the drag's source.
+    pub fn set_drop_effect(
+        &self,
+        view_id: ViewId,
+        effect: DropEffect,
+    ) -> Result<(), ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+        state.lock().unwrap().drop_effect = effect;
+        trace!(?view_id, ?effect, "Drop effect updated");
+        Ok(())
+    }
+
+    /// Get what a drop on a view would do.
+    pub fn get_drop_effect(&self, view_id: ViewId) -> Result<DropEffect, ViewHostError> {
+        let views = self.views.read().unwrap();
+        let state = views
+            .get(&view_id)
+            .ok_or(ViewHostError::ViewNotFound(view_id))?;
+        let effect = state.lock().unwrap().drop_effect;
+        Ok(effect)
+    }
+
     /// Get the HWND for a view.
     #[cfg(windows)]
     pub fn get_hwnd(&self, view_id: ViewId) -> Result<HWND, ViewHostError> {
@@ -777,6 +1021,7 @@ impl ViewHost {
 
                 let hwnd = HWND(hwnd_raw as *mut _);
                 unsafe {
+                    let _ = RevokeDragDrop(hwnd);
                     let _ = DestroyWindow(hwnd);
                 }
             }
@@ -876,6 +1121,54 @@ impl ViewHost {
         }
     }
 
+    /// Place the IME candidate window below `caret`, excluding the caret rect.
+    #[cfg(windows)]
+    unsafe fn position_candidate_window(hwnd: HWND, caret: Bounds) {
+        let himc = ImmGetContext(hwnd);
+        if himc.0.is_null() {
+            return;
+        }
+        let form = CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: CFS_EXCLUDE,
+            ptCurrentPos: POINT {
+                x: caret.x,
+                y: caret.y + caret.height as i32,
+            },
+            rcArea: RECT {
+                left: caret.x,
+                top: caret.y,
+                right: caret.x + caret.width.max(1) as i32,
+                bottom: caret.y + caret.height as i32,
+            },
+        };
+        let _ = ImmSetCandidateWindow(himc, &form);
+        let _ = ImmReleaseContext(hwnd, himc);
+    }
+
+    /// Read a composition string and, for the cursor, its offset in chars.
+    #[cfg(windows)]
+    unsafe fn composition_string(himc: HIMC, kind: IME_COMPOSITION_STRING) -> (String, usize) {
+        let bytes = ImmGetCompositionStringW(himc, kind, None, 0);
+        if bytes <= 0 {
+            return (String::new(), 0);
+        }
+        let mut units = vec![0u16; bytes as usize / 2];
+        ImmGetCompositionStringW(himc, kind, Some(units.as_mut_ptr() as *mut _), bytes as u32);
+        let text = String::from_utf16_lossy(&units);
+
+        // GCS_CURSORPOS is in UTF-16 units
+        let cursor = ImmGetCompositionStringW(himc, GCS_CURSORPOS, None, 0);
+        let cursor = if cursor >= 0 {
+            String::from_utf16_lossy(&units[..(cursor as usize).min(units.len())])
+                .chars()
+                .count()
+        } else {
+            text.chars().count()
+        };
+        (text, cursor)
+    }
+
     /// Window procedure for view windows.
     #[cfg(windows)]
     unsafe extern "system" fn wnd_proc(
@@ -1044,6 +1337,14 @@ impl ViewHost {
                 }
             }
 
+            WM_SETCURSOR if (lparam.0 & 0xFFFF) as u32 == HTCLIENT => {
+                if let Some(state) = get_state() {
+                    let cursor = state.lock().unwrap().cursor;
+                    SetCursor(cursor.load());
+                    return LRESULT(1);
+                }
+            }
+
             m if m == WM_MOUSELEAVE_MSG => {
                 if let Some(state) = get_state() {
                     let mut state = state.lock().unwrap();
@@ -1108,23 +1409,102 @@ impl ViewHost {
 
             WM_CHAR => {
                 if let Some(state) = get_state() {
-                    let state = state.lock().unwrap();
+                    let mut state = state.lock().unwrap();
                     let view_id = state.id;
-                    drop(state);
 
-                    // wparam contains the UTF-16 code unit
-                    let ch = char::from_u32(wparam.0 as u32).unwrap_or('\0');
-                    if !ch.is_control() || ch == '\r' || ch == '\t' {
-                        let event = KeyEvent::input(ch).with_timestamp(Self::timestamp());
+                    // wparam contains a UTF-16 code unit; characters outside the
+                    // BMP arrive as a surrogate pair over two messages
+                    let unit = wparam.0 as u16;
+                    let ch = match (state.pending_surrogate.take(), unit) {
+                        (_, 0xD800..=0xDBFF) => {
+                            state.pending_surrogate = Some(unit);
+                            None
+                        }
+                        (Some(high), 0xDC00..=0xDFFF) => {
+                            char::decode_utf16([high, unit]).next().and_then(Result::ok)
+                        }
+                        _ => char::from_u32(unit as u32),
+                    };
+                    drop(state);
 
+                    // Backspace arrives as U+0008 and is handled as an edit
+                    if let Some(ch) =
+                        ch.filter(|c| !c.is_control() || matches!(*c, '\r' | '\t' | '\u{8}'))
+                    {
                         emit(ViewEvent::Input {
                             view_id,
-                            event: InputEvent::Key(event),
+                            event: InputEvent::CharInput { ch },
                         });
                     }
                 }
             }
 
+            // A dead key only primes the layout; the composed character (or the
+            // accent itself) follows as a single WM_CHAR.
+            WM_DEADCHAR | WM_SYSDEADCHAR => return LRESULT(0),
+
+            // === IME Events ===
+            WM_IME_STARTCOMPOSITION => {
+                if let Some(state) = get_state() {
+                    let caret = state.lock().unwrap().ime_caret;
+                    if let Some(caret) = caret {
+                        Self::position_candidate_window(hwnd, caret);
+                    }
+                }
+                // The engine draws the composition inline
+                return LRESULT(0);
+            }
+
+            WM_IME_COMPOSITION => {
+                if let Some(state) = get_state() {
+                    let view_id = state.lock().unwrap().id;
+                    let flags = lparam.0 as u32;
+                    let himc = ImmGetContext(hwnd);
+                    if !himc.0.is_null() {
+                        let mut events = Vec::new();
+                        if flags & GCS_RESULTSTR.0 != 0 {
+                            let (text, cursor) = Self::composition_string(himc, GCS_RESULTSTR);
+                            events.push(InputEvent::ImeComposition {
+                                text,
+                                cursor,
+                                is_commit: true,
+                            });
+                        }
+                        if flags & GCS_COMPSTR.0 != 0 {
+                            let (text, cursor) = Self::composition_string(himc, GCS_COMPSTR);
+                            events.push(InputEvent::ImeComposition {
+                                text,
+                                cursor,
+                                is_commit: false,
+                            });
+                        }
+                        let _ = ImmReleaseContext(hwnd, himc);
+
+                        for event in events {
+                            emit(ViewEvent::Input { view_id, event });
+                        }
+                    }
+                }
+                // Not passed on: DefWindowProc would resend the result as WM_IME_CHAR
+                return LRESULT(0);
+            }
+
+            WM_IME_ENDCOMPOSITION => {
+                if let Some(state) = get_state() {
+                    let view_id = state.lock().unwrap().id;
+                    // Drops an uncommitted composition; a no-op after a commit
+                    emit(ViewEvent::Input {
+                        view_id,
+                        event: InputEvent::ImeComposition {
+                            text: String::new(),
+                            cursor: 0,
+                            is_commit: false,
+                        },
+                    });
+                }
+                return LRESULT(0);
+            }
+
             // === Focus Events ===
             WM_SETFOCUS => {
                 if let Some(state) = get_state() {
@@ -1212,6 +1592,31 @@ impl ViewHost {
                 }
             }
 
+            // Reaches views parented to a window that forwards it
+            WM_SETTINGCHANGE | WM_SYSCOLORCHANGE => {
+                emit(ViewEvent::SystemSettingsChanged {
+                    settings: SystemSettings::read(),
+                });
+            }
+
+            // === Accessibility ===
+            WM_GETOBJECT => {
+                if lparam.0 as i32 == UiaRootObjectId {
+                    if let Some(state) = get_state() {
+                        let state = state.lock().unwrap();
+                        let view_id = state.id;
+                        let tree = state.accessibility.clone();
+                        drop(state);
+
+                        if let Some(tree) = tree {
+                            let provider: IRawElementProviderSimple =
+                                uia::UiaProvider::root(hwnd, view_id, tree).into();
+                            return UiaReturnRawElementProvider(hwnd, wparam, lparam, &provider);
+                        }
+                    }
+                }
+            }
+
             WM_PAINT => {
                 let mut ps = PAINTSTRUCT::default();
                 let _hdc = BeginPaint(hwnd, &mut ps);
@@ -0,0 +1,210 @@
+//! Locale data from the system, for `Intl`.
+//!
+//! On Windows [`SystemLocales`] reads separators, date and time pictures,
+//! month names and the local currency with `GetLocaleInfoEx`, the user's
+//! languages with `GetUserPreferredUILanguages`, and the time zone's key
+//! name (a Windows zone name such as `W. Europe Standard Time`, not an IANA
+//! one). Other platforms take the languages from `LANGUAGE`, `LC_ALL`,
+//! `LC_MESSAGES` or `LANG`, the zone from `TZ`, and data from
+//! [`BuiltinLocales`].
+
+use rustkit_core::locale::{LocaleData, LocaleProvider};
+#[cfg(not(windows))]
+use rustkit_core::locale::BuiltinLocales;
+
+/// The system's locales.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemLocales;
+
+impl SystemLocales {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(windows)]
+mod windows_locales {
+    use super::*;
+    use rustkit_core::locale::CurrencyPlacement;
+    use windows::core::{HSTRING, PWSTR};
+    use windows::Win32::Globalization::{
+        GetLocaleInfoEx, GetUserPreferredUILanguages, IsValidLocaleName, LOCALE_ICURRENCY,
+        LOCALE_IPOSITIVEPERCENT, LOCALE_S1159, LOCALE_S2359, LOCALE_SABBREVMONTHNAME1,
+        LOCALE_SCURRENCY, LOCALE_SDECIMAL, LOCALE_SINTLSYMBOL, LOCALE_SLONGDATE,
+        LOCALE_SMONTHNAME1, LOCALE_SNAME, LOCALE_SSHORTDATE, LOCALE_STHOUSAND, LOCALE_STIMEFORMAT,
+        MUI_LANGUAGE_NAME,
+    };
+    use windows::Win32::System::Time::{GetDynamicTimeZoneInformation, DYNAMIC_TIME_ZONE_INFORMATION};
+
+    fn info(name: &HSTRING, kind: u32) -> Option<String> {
+        unsafe {
+            let length = GetLocaleInfoEx(name, kind, None);
+            if length <= 0 {
+                return None;
+            }
+            let mut buffer = vec![0u16; length as usize];
+            let length = GetLocaleInfoEx(name, kind, Some(&mut buffer));
+            if length <= 0 {
+                return None;
+            }
+            // The length counts the terminating NUL
+            Some(String::from_utf16_lossy(&buffer[..length as usize - 1]))
+        }
+    }
+
+    impl LocaleProvider for SystemLocales {
+        fn locale(&self, tag: &str) -> Option<LocaleData> {
+            let name = HSTRING::from(tag);
+            if !unsafe { IsValidLocaleName(&name) }.as_bool() {
+                return None;
+            }
+            let get = |kind| info(&name, kind).unwrap_or_default();
+            let months = |first: u32| (0..12).map(|month| get(first + month)).collect();
+            let currency_placement = match get(LOCALE_ICURRENCY).as_str() {
+                "1" => CurrencyPlacement::After,
+                "2" => CurrencyPlacement::BeforeSpace,
+                "3" => CurrencyPlacement::AfterSpace,
+                _ => CurrencyPlacement::Before,
+            };
+            Some(LocaleData {
+                tag: info(&name, LOCALE_SNAME).unwrap_or_else(|| tag.to_string()),
+                decimal_separator: get(LOCALE_SDECIMAL),
+                group_separator: get(LOCALE_STHOUSAND),
+                short_date: get(LOCALE_SSHORTDATE),
+                long_date: get(LOCALE_SLONGDATE),
+                time_format: get(LOCALE_STIMEFORMAT),
+                month_names: months(LOCALE_SMONTHNAME1),
+                abbreviated_month_names: months(LOCALE_SABBREVMONTHNAME1),
+                am: get(LOCALE_S1159),
+                pm: get(LOCALE_S2359),
+                currency_code: get(LOCALE_SINTLSYMBOL),
+                currency_symbol: get(LOCALE_SCURRENCY),
+                currency_placement,
+                // 0 is "# %"
+                percent_space: get(LOCALE_IPOSITIVEPERCENT) == "0",
+            })
+        }
+
+        fn preferred_languages(&self) -> Vec<String> {
+            let mut count = 0u32;
+            let mut length = 0u32;
+            unsafe {
+                if GetUserPreferredUILanguages(
+                    MUI_LANGUAGE_NAME,
+                    &mut count,
+                    PWSTR::null(),
+                    &mut length,
+                )
+                .is_err()
+                {
+                    return vec!["en-US".to_string()];
+                }
+                let mut buffer = vec![0u16; length as usize];
+                if GetUserPreferredUILanguages(
+                    MUI_LANGUAGE_NAME,
+                    &mut count,
+                    PWSTR(buffer.as_mut_ptr()),
+                    &mut length,
+                )
+                .is_err()
+                {
+                    return vec!["en-US".to_string()];
+                }
+                // A double-NUL-terminated list
+                buffer
+                    .split(|&unit| unit == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(String::from_utf16_lossy)
+                    .collect()
+            }
+        }
+
+        fn time_zone(&self) -> String {
+            let mut zone = DYNAMIC_TIME_ZONE_INFORMATION::default();
+            unsafe { GetDynamicTimeZoneInformation(&mut zone) };
+            let name = &zone.TimeZoneKeyName;
+            let end = name
+                .iter()
+                .position(|&unit| unit == 0)
+                .unwrap_or(name.len());
+            match String::from_utf16_lossy(&name[..end]) {
+                name if name.is_empty() => "UTC".to_string(),
+                name => name,
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl LocaleProvider for SystemLocales {
+    fn locale(&self, tag: &str) -> Option<LocaleData> {
+        BuiltinLocales.locale(tag)
+    }
+
+    fn preferred_languages(&self) -> Vec<String> {
+        let languages = preferred_languages_from_env(|name| std::env::var(name).ok());
+        if languages.is_empty() {
+            BuiltinLocales.preferred_languages()
+        } else {
+            languages
+        }
+    }
+
+    fn time_zone(&self) -> String {
+        std::env::var("TZ")
+            .ok()
+            .map(|zone| zone.trim_start_matches(':').to_string())
+            .filter(|zone| !zone.is_empty())
+            .unwrap_or_else(|| "UTC".to_string())
+    }
+}
+
+/// Languages from POSIX locale variables, e.g. `de_DE.UTF-8` as `de-DE`.
+#[cfg(not(windows))]
+fn preferred_languages_from_env(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let to_tag = |locale: &str| {
+        let locale = locale.split(['.', '@']).next().unwrap_or("");
+        (!locale.is_empty() && locale != "C" && locale != "POSIX").then(|| locale.replace('_', "-"))
+    };
+    if let Some(list) = var("LANGUAGE").filter(|list| !list.is_empty()) {
+        let languages: Vec<String> = list.split(':').filter_map(to_tag).collect();
+        if !languages.is_empty() {
+            return languages;
+        }
+    }
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .and_then(|locale| to_tag(&locale))
+        .into_iter()
+        .collect()
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferred_languages_from_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            preferred_languages_from_env(env(&[("LANG", "de_DE.UTF-8")])),
+            ["de-DE"]
+        );
+        assert_eq!(
+            preferred_languages_from_env(env(&[("LANGUAGE", "fr_FR:en"), ("LANG", "de_DE")])),
+            ["fr-FR", "en"]
+        );
+        assert_eq!(
+            preferred_languages_from_env(env(&[("LC_ALL", "ja_JP.UTF-8"), ("LANG", "C")])),
+            ["ja-JP"]
+        );
+        assert!(preferred_languages_from_env(env(&[("LANG", "C.UTF-8")])).is_empty());
+    }
+}
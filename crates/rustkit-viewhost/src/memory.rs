@@ -0,0 +1,71 @@
+//! System memory pressure.
+//!
+//! On Windows this registers for the low-memory resource notification the
+//! system signals when physical memory runs short. Other platforms never
+//! report pressure.
+
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{CloseHandle, BOOL, HANDLE},
+    System::Memory::{
+        CreateMemoryResourceNotification, LowMemoryResourceNotification,
+        QueryMemoryResourceNotification,
+    },
+};
+
+/// Watches for the system running low on memory.
+pub struct MemoryPressureMonitor {
+    #[cfg(windows)]
+    handle: Option<HANDLE>,
+}
+
+impl MemoryPressureMonitor {
+    /// Register for memory pressure notifications.
+    pub fn new() -> Self {
+        #[cfg(windows)]
+        {
+            let handle = unsafe { CreateMemoryResourceNotification(LowMemoryResourceNotification) };
+            if let Err(e) = &handle {
+                tracing::warn!(error = %e, "Memory resource notification unavailable");
+            }
+            Self {
+                handle: handle.ok(),
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            Self {}
+        }
+    }
+
+    /// Whether the system currently reports low memory.
+    pub fn is_low(&self) -> bool {
+        #[cfg(windows)]
+        {
+            let Some(handle) = self.handle else {
+                return false;
+            };
+            let mut low = BOOL(0);
+            unsafe { QueryMemoryResourceNotification(handle, &mut low) }.is_ok() && low.as_bool()
+        }
+        #[cfg(not(windows))]
+        {
+            false
+        }
+    }
+}
+
+impl Default for MemoryPressureMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(windows)]
+impl Drop for MemoryPressureMonitor {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = unsafe { CloseHandle(handle) };
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! System network connectivity.
+//!
+//! On Windows this registers for the connectivity hints the system sends
+//! whenever the network changes, e.g. as Wi-Fi comes back after sleep. A
+//! network with no route beyond the local link counts as offline. Other
+//! platforms, and Windows before a hint arrives, report nothing.
+
+#[cfg(windows)]
+use std::ffi::c_void;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicU8, Ordering};
+#[cfg(windows)]
+use std::sync::Arc;
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{BOOLEAN, HANDLE},
+    NetworkManagement::IpHelper::{CancelMibChangeNotify2, NotifyNetworkConnectivityHintChange},
+    Networking::WinSock::{
+        NetworkConnectivityLevelHintLocalAccess, NetworkConnectivityLevelHintNone,
+        NetworkConnectivityLevelHintUnknown, NL_NETWORK_CONNECTIVITY_HINT,
+    },
+};
+
+#[cfg(windows)]
+const UNKNOWN: u8 = 0;
+#[cfg(windows)]
+const OFFLINE: u8 = 1;
+#[cfg(windows)]
+const ONLINE: u8 = 2;
+
+/// Called by the system on its own thread with each new hint.
+#[cfg(windows)]
+unsafe extern "system" fn hint_changed(context: *const c_void, hint: NL_NETWORK_CONNECTIVITY_HINT) {
+    let state = &*(context as *const AtomicU8);
+    let level = hint.ConnectivityLevel;
+    let value = if level == NetworkConnectivityLevelHintUnknown {
+        UNKNOWN
+    } else if level == NetworkConnectivityLevelHintNone
+        || level == NetworkConnectivityLevelHintLocalAccess
+    {
+        OFFLINE
+    } else {
+        ONLINE
+    };
+    state.store(value, Ordering::Relaxed);
+}
+
+/// Watches whether the system has a network connection.
+pub struct ConnectivityMonitor {
+    /// Last hint, shared with the callback.
+    #[cfg(windows)]
+    state: Arc<AtomicU8>,
+    #[cfg(windows)]
+    handle: Option<HANDLE>,
+}
+
+impl ConnectivityMonitor {
+    /// Register for connectivity notifications.
+    pub fn new() -> Self {
+        #[cfg(windows)]
+        {
+            let state = Arc::new(AtomicU8::new(UNKNOWN));
+            let mut handle = HANDLE::default();
+            let result = unsafe {
+                NotifyNetworkConnectivityHintChange(
+                    Some(hint_changed),
+                    Some(Arc::as_ptr(&state) as *const c_void),
+                    BOOLEAN::from(true),
+                    &mut handle,
+                )
+            };
+            if let Err(e) = result.ok() {
+                tracing::warn!(error = %e, "Network connectivity notification unavailable");
+            }
+            Self {
+                handle: result.is_ok().then_some(handle),
+                state,
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            Self {}
+        }
+    }
+
+    /// Whether the system reports a connection beyond the local network,
+    /// or `None` if it hasn't said.
+    pub fn is_online(&self) -> Option<bool> {
+        #[cfg(windows)]
+        {
+            match self.state.load(Ordering::Relaxed) {
+                ONLINE => Some(true),
+                OFFLINE => Some(false),
+                _ => None,
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            None
+        }
+    }
+}
+
+impl Default for ConnectivityMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ConnectivityMonitor {
+    fn drop(&mut self) {
+        // Waits for a callback in progress, so the state outlives it
+        if let Some(handle) = self.handle.take() {
+            let _ = unsafe { CancelMibChangeNotify2(handle) };
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! System accessibility settings that content can observe.
+//!
+//! On Windows these are the "show animations" switch, high contrast, and
+//! the theme's system colors, re-read whenever the system broadcasts
+//! `WM_SETTINGCHANGE` or `WM_SYSCOLORCHANGE`. Other platforms report the
+//! defaults: animations on, no high contrast, and a light palette.
+
+#[cfg(windows)]
+use windows::Win32::{
+    Graphics::Gdi::{
+        GetSysColor, COLOR_BTNFACE, COLOR_BTNTEXT, COLOR_GRAYTEXT, COLOR_HIGHLIGHT,
+        COLOR_HIGHLIGHTTEXT, COLOR_HOTLIGHT, COLOR_WINDOW, COLOR_WINDOWTEXT, SYS_COLOR_INDEX,
+    },
+    UI::{
+        Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW},
+        WindowsAndMessaging::{
+            SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST,
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+        },
+    },
+};
+
+/// An opaque color as red, green and blue.
+pub type Rgb = [u8; 3];
+
+/// The system colors of the current theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemColors {
+    /// Window background.
+    pub window: Rgb,
+    /// Text on the window background.
+    pub window_text: Rgb,
+    /// Hyperlinks.
+    pub hot_light: Rgb,
+    /// Button faces.
+    pub button_face: Rgb,
+    /// Text on buttons.
+    pub button_text: Rgb,
+    /// Selected items.
+    pub highlight: Rgb,
+    /// Text of selected items.
+    pub highlight_text: Rgb,
+    /// Disabled text.
+    pub gray_text: Rgb,
+}
+
+impl Default for SystemColors {
+    fn default() -> Self {
+        Self {
+            window: [255, 255, 255],
+            window_text: [0, 0, 0],
+            hot_light: [0, 102, 204],
+            button_face: [240, 240, 240],
+            button_text: [0, 0, 0],
+            highlight: [0, 120, 215],
+            highlight_text: [255, 255, 255],
+            gray_text: [109, 109, 109],
+        }
+    }
+}
+
+/// The accessibility settings of the system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemSettings {
+    /// The user turned animations off.
+    pub reduced_motion: bool,
+    /// A high contrast theme is on.
+    pub high_contrast: bool,
+    pub colors: SystemColors,
+}
+
+impl SystemSettings {
+    /// Read the current settings.
+    pub fn read() -> Self {
+        #[cfg(windows)]
+        {
+            let mut animations = windows::Win32::Foundation::BOOL(1);
+            let animations_read = unsafe {
+                SystemParametersInfoW(
+                    SPI_GETCLIENTAREAANIMATION,
+                    0,
+                    Some(&mut animations as *mut _ as *mut _),
+                    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+                )
+            };
+            if let Err(e) = &animations_read {
+                tracing::warn!(error = %e, "Client area animation setting unavailable");
+            }
+
+            let mut contrast = HIGHCONTRASTW {
+                cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+                ..Default::default()
+            };
+            let contrast_read = unsafe {
+                SystemParametersInfoW(
+                    SPI_GETHIGHCONTRAST,
+                    contrast.cbSize,
+                    Some(&mut contrast as *mut _ as *mut _),
+                    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+                )
+            };
+            if let Err(e) = &contrast_read {
+                tracing::warn!(error = %e, "High contrast setting unavailable");
+            }
+
+            let color = |index: SYS_COLOR_INDEX| -> Rgb {
+                // COLORREF is 0x00BBGGRR
+                let value = unsafe { GetSysColor(index) };
+                [value as u8, (value >> 8) as u8, (value >> 16) as u8]
+            };
+            Self {
+                reduced_motion: animations_read.is_ok() && !animations.as_bool(),
+                high_contrast: contrast_read.is_ok()
+                    && contrast.dwFlags.0 & HCF_HIGHCONTRASTON.0 != 0,
+                colors: SystemColors {
+                    window: color(COLOR_WINDOW),
+                    window_text: color(COLOR_WINDOWTEXT),
+                    hot_light: color(COLOR_HOTLIGHT),
+                    button_face: color(COLOR_BTNFACE),
+                    button_text: color(COLOR_BTNTEXT),
+                    highlight: color(COLOR_HIGHLIGHT),
+                    highlight_text: color(COLOR_HIGHLIGHTTEXT),
+                    gray_text: color(COLOR_GRAYTEXT),
+                },
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            Self::default()
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! UI Automation provider for RustKit views.
+//!
+//! Exposes the engine's accessibility tree to Windows assistive technology
+//! (Narrator, NVDA, JAWS) through the UIA provider interfaces:
+//!
+//! - `IRawElementProviderSimple`: names, control types, and states
+//! - `IRawElementProviderFragment`: tree navigation and bounds
+//! - `IRawElementProviderFragmentRoot`: hit testing and focus
+//! - `IInvokeProvider`: activating buttons and links
+//!
+//! Invoking an element emits [`ViewEvent::AccessibilityInvoke`], which the
+//! engine routes into its click dispatch.
+
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use rustkit_a11y::{AccessibilityTree, AccessibleId, Role, State};
+use tracing::trace;
+use windows::{
+    core::{implement, Error, IUnknown, Interface, Result, VARIANT},
+    Win32::{
+        Foundation::{E_FAIL, HWND, POINT},
+        Graphics::Gdi::{ClientToScreen, ScreenToClient},
+        System::{
+            Com::SAFEARRAY,
+            Ole::{SafeArrayCreateVector, SafeArrayPutElement},
+            Variant::VT_I4,
+        },
+        UI::Accessibility::*,
+    },
+};
+
+use crate::{ViewEvent, ViewId, VIEW_REGISTRY};
+
+/// UIA heading level for `<h1>`; levels 2-6 follow consecutively.
+const HEADING_LEVEL_1: i32 = 80051;
+
+/// A UIA provider for one node of a view's accessibility tree.
+#[implement(
+    IRawElementProviderSimple,
+    IRawElementProviderFragment,
+    IRawElementProviderFragmentRoot,
+    IInvokeProvider
+)]
+pub struct UiaProvider {
+    hwnd_raw: isize,
+    view_id: ViewId,
+    tree: Arc<RwLock<AccessibilityTree>>,
+    /// Node this provider represents (None = tree root).
+    node: Option<AccessibleId>,
+}
+
+impl UiaProvider {
+    /// Create the root provider for a view.
+    pub fn root(hwnd: HWND, view_id: ViewId, tree: Arc<RwLock<AccessibilityTree>>) -> Self {
+        Self {
+            hwnd_raw: hwnd.0 as isize,
+            view_id,
+            tree,
+            node: None,
+        }
+    }
+
+    fn hwnd(&self) -> HWND {
+        HWND(self.hwnd_raw as *mut _)
+    }
+
+    /// Read the tree; a lock poisoned by a panicking writer is `E_FAIL`.
+    fn read_tree(&self) -> Result<RwLockReadGuard<'_, AccessibilityTree>> {
+        self.tree.read().map_err(|_| Error::from(E_FAIL))
+    }
+
+    /// Resolve the node this provider represents.
+    fn node_id(&self) -> Option<AccessibleId> {
+        self.node_id_in(&*self.tree.read().ok()?)
+    }
+
+    /// Resolve the node this provider represents in `tree`, which the
+    /// caller has locked.
+    fn node_id_in(&self, tree: &AccessibilityTree) -> Option<AccessibleId> {
+        self.node.or_else(|| tree.root().map(|n| n.id))
+    }
+
+    /// Whether this provider is for the root of `tree`.
+    fn is_root_in(&self, tree: &AccessibilityTree) -> bool {
+        self.node.is_none() || self.node == tree.root().map(|n| n.id)
+    }
+
+    fn provider_for(&self, id: AccessibleId) -> IRawElementProviderFragment {
+        Self {
+            hwnd_raw: self.hwnd_raw,
+            view_id: self.view_id,
+            tree: self.tree.clone(),
+            node: Some(id),
+        }
+        .into()
+    }
+
+    fn root_provider(&self) -> IRawElementProviderFragmentRoot {
+        Self {
+            hwnd_raw: self.hwnd_raw,
+            view_id: self.view_id,
+            tree: self.tree.clone(),
+            node: None,
+        }
+        .into()
+    }
+}
+
+/// Map an ARIA role to a UIA control type.
+fn control_type(role: Role) -> UIA_CONTROLTYPE_ID {
+    match role {
+        Role::Button | Role::Switch => UIA_ButtonControlTypeId,
+        Role::Link => UIA_HyperlinkControlTypeId,
+        Role::Heading | Role::StaticText => UIA_TextControlTypeId,
+        Role::Img => UIA_ImageControlTypeId,
+        Role::Checkbox => UIA_CheckBoxControlTypeId,
+        Role::Radio => UIA_RadioButtonControlTypeId,
+        Role::TextBox | Role::SearchBox => UIA_EditControlTypeId,
+        Role::List => UIA_ListControlTypeId,
+        Role::ListItem | Role::Option => UIA_ListItemControlTypeId,
+        Role::Document => UIA_DocumentControlTypeId,
+        Role::Table | Role::Grid => UIA_TableControlTypeId,
+        Role::Slider => UIA_SliderControlTypeId,
+        Role::SpinButton => UIA_SpinnerControlTypeId,
+        Role::ProgressBar => UIA_ProgressBarControlTypeId,
+        Role::Separator => UIA_SeparatorControlTypeId,
+        Role::Menu | Role::MenuBar => UIA_MenuControlTypeId,
+        Role::MenuItem | Role::MenuItemCheckbox | Role::MenuItemRadio => UIA_MenuItemControlTypeId,
+        Role::Tab => UIA_TabItemControlTypeId,
+        Role::TabList => UIA_TabControlTypeId,
+        Role::Dialog | Role::AlertDialog => UIA_WindowControlTypeId,
+        Role::Tree => UIA_TreeControlTypeId,
+        Role::TreeItem => UIA_TreeItemControlTypeId,
+        Role::Tooltip => UIA_ToolTipControlTypeId,
+        _ => UIA_GroupControlTypeId,
+    }
+}
+
+impl IRawElementProviderSimple_Impl for UiaProvider_Impl {
+    fn ProviderOptions(&self) -> Result<ProviderOptions> {
+        Ok(ProviderOptions_ServerSideProvider | ProviderOptions_UseComThreading)
+    }
+
+    fn GetPatternProvider(&self, pattern_id: UIA_PATTERN_ID) -> Result<IUnknown> {
+        let invokable = {
+            let tree = self.read_tree()?;
+            self.node_id_in(&tree)
+                .and_then(|id| tree.get(id))
+                .is_some_and(|n| {
+                    matches!(
+                        n.role,
+                        Role::Button | Role::Link | Role::MenuItem | Role::Tab
+                    ) && !n.has_state(State::Disabled)
+                })
+        };
+
+        if pattern_id == UIA_InvokePatternId && invokable {
+            let invoke: IInvokeProvider = self.to_interface();
+            return invoke.cast();
+        }
+
+        // S_OK with a null provider means "pattern not supported"
+        Err(Error::empty())
+    }
+
+    fn GetPropertyValue(&self, property_id: UIA_PROPERTY_ID) -> Result<VARIANT> {
+        let tree = self.read_tree()?;
+        let Some(node) = self.node_id_in(&tree).and_then(|id| tree.get(id)) else {
+            return Ok(VARIANT::default());
+        };
+
+        let value = match property_id {
+            UIA_NamePropertyId => node.name.as_deref().map(VARIANT::from),
+            UIA_ControlTypePropertyId => Some(VARIANT::from(control_type(node.role).0)),
+            UIA_HelpTextPropertyId => node.description.as_deref().map(VARIANT::from),
+            UIA_HeadingLevelPropertyId => node
+                .level
+                .filter(|_| node.role == Role::Heading)
+                .map(|level| VARIANT::from(HEADING_LEVEL_1 + level.clamp(1, 9) as i32 - 1)),
+            UIA_IsEnabledPropertyId => Some(VARIANT::from(!node.has_state(State::Disabled))),
+            UIA_IsKeyboardFocusablePropertyId => Some(VARIANT::from(node.is_focusable())),
+            UIA_HasKeyboardFocusPropertyId => Some(VARIANT::from(
+                tree.get_focus().is_some_and(|f| f.id == node.id),
+            )),
+            UIA_IsPasswordPropertyId => Some(VARIANT::from(false)),
+            UIA_IsControlElementPropertyId | UIA_IsContentElementPropertyId => {
+                Some(VARIANT::from(node.role != Role::Presentation))
+            }
+            UIA_ValueValuePropertyId => node.value.as_deref().map(VARIANT::from),
+            UIA_ToggleToggleStatePropertyId if node.role == Role::Checkbox => {
+                Some(VARIANT::from(if node.has_state(State::Checked) {
+                    ToggleState_On.0
+                } else {
+                    ToggleState_Off.0
+                }))
+            }
+            _ => None,
+        };
+
+        Ok(value.unwrap_or_default())
+    }
+
+    fn HostRawElementProvider(&self) -> Result<IRawElementProviderSimple> {
+        if self.is_root_in(&*self.read_tree()?) {
+            unsafe { UiaHostProviderFromHwnd(self.hwnd()) }
+        } else {
+            Err(Error::empty())
+        }
+    }
+}
+
+impl IRawElementProviderFragment_Impl for UiaProvider_Impl {
+    fn Navigate(&self, direction: NavigateDirection) -> Result<IRawElementProviderFragment> {
+        let tree = self.read_tree()?;
+        let Some(node) = self.node_id_in(&tree).and_then(|id| tree.get(id)) else {
+            return Err(Error::empty());
+        };
+
+        let is_root = self.is_root_in(&tree);
+        let target = match direction {
+            // The root's parent is the hosting HWND, which UIA resolves itself
+            NavigateDirection_Parent if !is_root => node.parent,
+            NavigateDirection_NextSibling if !is_root => tree.next_sibling(node.id),
+            NavigateDirection_PreviousSibling if !is_root => tree.prev_sibling(node.id),
+            NavigateDirection_FirstChild => node.children.first().copied(),
+            NavigateDirection_LastChild => node.children.last().copied(),
+            _ => None,
+        };
+
+        match target {
+            Some(id) => Ok(self.provider_for(id)),
+            None => Err(Error::empty()),
+        }
+    }
+
+    fn GetRuntimeId(&self) -> Result<*mut SAFEARRAY> {
+        let id = self.node_id().map(|id| id.raw() as i32).unwrap_or(0);
+        let parts = [UiaAppendRuntimeId as i32, id];
+
+        unsafe {
+            let array = SafeArrayCreateVector(VT_I4, 0, parts.len() as u32);
+            for (index, part) in parts.iter().enumerate() {
+                let index = index as i32;
+                SafeArrayPutElement(array, &index, part as *const i32 as *const _)?;
+            }
+            Ok(array)
+        }
+    }
+
+    fn BoundingRectangle(&self) -> Result<UiaRect> {
+        let tree = self.read_tree()?;
+        let Some((x, y, width, height)) = self
+            .node_id_in(&tree)
+            .and_then(|id| tree.get(id))
+            .and_then(|n| n.bounds)
+        else {
+            return Ok(UiaRect::default());
+        };
+
+        // Layout bounds are client-relative; UIA wants screen coordinates
+        let mut origin = POINT {
+            x: x as i32,
+            y: y as i32,
+        };
+        unsafe {
+            let _ = ClientToScreen(self.hwnd(), &mut origin);
+        }
+
+        Ok(UiaRect {
+            left: origin.x as f64,
+            top: origin.y as f64,
+            width: width as f64,
+            height: height as f64,
+        })
+    }
+
+    fn GetEmbeddedFragmentRoots(&self) -> Result<*mut SAFEARRAY> {
+        Ok(std::ptr::null_mut())
+    }
+
+    fn SetFocus(&self) -> Result<()> {
+        let mut tree = self.tree.write().map_err(|_| Error::from(E_FAIL))?;
+        if let Some(id) = self.node_id_in(&tree) {
+            tree.set_focus(Some(id));
+        }
+        Ok(())
+    }
+
+    fn FragmentRoot(&self) -> Result<IRawElementProviderFragmentRoot> {
+        Ok(self.root_provider())
+    }
+}
+
+impl IRawElementProviderFragmentRoot_Impl for UiaProvider_Impl {
+    fn ElementProviderFromPoint(&self, x: f64, y: f64) -> Result<IRawElementProviderFragment> {
+        let mut point = POINT {
+            x: x as i32,
+            y: y as i32,
+        };
+        unsafe {
+            let _ = ScreenToClient(self.hwnd(), &mut point);
+        }
+
+        let hit = self.read_tree()?.hit_test(point.x as f32, point.y as f32);
+
+        match hit {
+            Some(id) => Ok(self.provider_for(id)),
+            None => Err(Error::empty()),
+        }
+    }
+
+    fn GetFocus(&self) -> Result<IRawElementProviderFragment> {
+        let focus = self.read_tree()?.get_focus().map(|n| n.id);
+        match focus {
+            Some(id) => Ok(self.provider_for(id)),
+            None => Err(Error::empty()),
+        }
+    }
+}
+
+impl IInvokeProvider_Impl for UiaProvider_Impl {
+    fn Invoke(&self) -> Result<()> {
+        let Some(target) = self.node_id() else {
+            return Ok(());
+        };
+
+        trace!(view_id = ?self.view_id, ?target, "UIA invoke");
+        if let Ok(registry) = VIEW_REGISTRY.read() {
+            registry.emit(ViewEvent::AccessibilityInvoke {
+                view_id: self.view_id,
+                target,
+            });
+        }
+        Ok(())
+    }
+}